@@ -4,7 +4,7 @@ use r_efi::efi;
 
 use crate::{boxed::BootServicesBox, BootServices};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AllocType {
     AnyPage,
     MaxAddress(usize),
@@ -12,6 +12,7 @@ pub enum AllocType {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct MemoryType(u32);
 
@@ -40,6 +41,12 @@ impl Into<u32> for MemoryType {
     }
 }
 
+impl From<u32> for MemoryType {
+    fn from(value: u32) -> Self {
+        MemoryType(value)
+    }
+}
+
 #[derive(Debug)]
 pub struct MemoryMap<'a, B: BootServices> {
     pub descriptors: BootServicesBox<'a, [MemoryDescriptor], B>,
@@ -48,6 +55,7 @@ pub struct MemoryMap<'a, B: BootServices> {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryDescriptor {
     pub memory_type: MemoryType,
     pub physical_start: usize,
@@ -57,6 +65,7 @@ pub struct MemoryDescriptor {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryAttribute(u64);
 
 impl MemoryAttribute {
@@ -76,6 +85,10 @@ impl MemoryAttribute {
     pub const RUNTIME: MemoryAttribute = MemoryAttribute(efi::MEMORY_RUNTIME);
     pub const ISA_VALID: MemoryAttribute = MemoryAttribute(efi::MEMORY_ISA_VALID);
     pub const ISA_MASK: MemoryAttribute = MemoryAttribute(efi::MEMORY_ISA_MASK);
+
+    pub const fn contains(&self, attributes: MemoryAttribute) -> bool {
+        self.0 & attributes.0 == attributes.0
+    }
 }
 
 impl BitOr for MemoryAttribute {
@@ -107,3 +120,120 @@ impl Into<u64> for MemoryAttribute {
         self.0
     }
 }
+
+impl From<u64> for MemoryAttribute {
+    fn from(value: u64) -> Self {
+        MemoryAttribute(value)
+    }
+}
+
+#[cfg(feature = "uefi")]
+mod uefi_conversions {
+    use uefi::mem::memory_map;
+
+    use super::{MemoryAttribute, MemoryDescriptor, MemoryType};
+
+    impl From<memory_map::MemoryType> for MemoryType {
+        fn from(memory_type: memory_map::MemoryType) -> Self {
+            MemoryType(memory_type.0)
+        }
+    }
+
+    impl From<MemoryType> for memory_map::MemoryType {
+        fn from(memory_type: MemoryType) -> Self {
+            memory_map::MemoryType(memory_type.0)
+        }
+    }
+
+    impl From<memory_map::MemoryAttribute> for MemoryAttribute {
+        fn from(attribute: memory_map::MemoryAttribute) -> Self {
+            MemoryAttribute(attribute.bits())
+        }
+    }
+
+    /// Bits unknown to the `uefi` crate are kept.
+    impl From<MemoryAttribute> for memory_map::MemoryAttribute {
+        fn from(attribute: MemoryAttribute) -> Self {
+            memory_map::MemoryAttribute::from_bits_retain(attribute.0)
+        }
+    }
+
+    impl From<&memory_map::MemoryDescriptor> for MemoryDescriptor {
+        fn from(descriptor: &memory_map::MemoryDescriptor) -> Self {
+            MemoryDescriptor {
+                memory_type: descriptor.ty.into(),
+                physical_start: descriptor.phys_start as usize,
+                virtual_start: descriptor.virt_start as usize,
+                nb_pages: descriptor.page_count as usize,
+                attribute: descriptor.att.into(),
+            }
+        }
+    }
+
+    impl From<&MemoryDescriptor> for memory_map::MemoryDescriptor {
+        fn from(descriptor: &MemoryDescriptor) -> Self {
+            memory_map::MemoryDescriptor {
+                ty: descriptor.memory_type.into(),
+                phys_start: descriptor.physical_start as u64,
+                virt_start: descriptor.virtual_start as u64,
+                page_count: descriptor.nb_pages as u64,
+                att: descriptor.attribute.into(),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn test_memory_descriptor_uefi_conversion() {
+            let descriptor = MemoryDescriptor {
+                memory_type: MemoryType::RUNTIME_SERVICES_DATA,
+                physical_start: 0x2000,
+                virtual_start: 0x8000_2000,
+                nb_pages: 2,
+                attribute: MemoryAttribute::WB | MemoryAttribute::RUNTIME,
+            };
+            let uefi_descriptor = memory_map::MemoryDescriptor::from(&descriptor);
+            assert_eq!(memory_map::MemoryType::RUNTIME_SERVICES_DATA, uefi_descriptor.ty);
+            assert_eq!(
+                (0x2000, 0x8000_2000, 2),
+                (uefi_descriptor.phys_start, uefi_descriptor.virt_start, uefi_descriptor.page_count)
+            );
+            assert_eq!(
+                memory_map::MemoryAttribute::WRITE_BACK | memory_map::MemoryAttribute::RUNTIME,
+                uefi_descriptor.att
+            );
+
+            let round_trip = MemoryDescriptor::from(&uefi_descriptor);
+            assert_eq!(MemoryType::RUNTIME_SERVICES_DATA, round_trip.memory_type);
+            assert_eq!(
+                (0x2000, 0x8000_2000, 2),
+                (round_trip.physical_start, round_trip.virtual_start, round_trip.nb_pages)
+            );
+            assert_eq!(MemoryAttribute::WB | MemoryAttribute::RUNTIME, round_trip.attribute);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_memory_descriptor_serde() {
+        let descriptor = MemoryDescriptor {
+            memory_type: MemoryType::BOOT_SERVICES_DATA,
+            physical_start: 0x1000,
+            virtual_start: 0,
+            nb_pages: 4,
+            attribute: MemoryAttribute::WB | MemoryAttribute::XP,
+        };
+        let json = serde_json::to_string(&descriptor).unwrap();
+        let round_trip: MemoryDescriptor = serde_json::from_str(&json).unwrap();
+        assert_eq!(MemoryType::BOOT_SERVICES_DATA, round_trip.memory_type);
+        assert_eq!((0x1000, 0, 4), (round_trip.physical_start, round_trip.virtual_start, round_trip.nb_pages));
+        assert_eq!(MemoryAttribute::WB | MemoryAttribute::XP, round_trip.attribute);
+    }
+}
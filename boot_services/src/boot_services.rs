@@ -7,8 +7,15 @@ extern crate alloc;
 
 pub mod allocation;
 pub mod boxed;
+pub mod configuration_table;
+pub mod deferred;
 pub mod event;
+pub mod memory_attributes;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod protocol_handler;
+#[cfg(any(test, feature = "mock"))]
+pub mod recording;
 pub mod static_ptr;
 pub mod tpl;
 
@@ -28,13 +35,34 @@ use core::{
 use static_ptr::{StaticPtr, StaticPtrMut};
 
 use r_efi::efi;
+use status::EfiError;
 
 use allocation::{AllocType, MemoryMap, MemoryType};
 use boxed::BootServicesBox;
 use event::{EventNotifyCallback, EventTimerType, EventType};
-use protocol_handler::{HandleSearchType, Protocol, Registration};
+use protocol_handler::{ConnectError, GuidBuffer, HandleBuffer, HandleSearchType, Protocol, Registration};
 use tpl::{Tpl, TplGuard};
 
+#[doc(hidden)]
+pub mod __private {
+    //! Re-exports used by the code generated from `boot_services_macros`.
+    pub use r_efi;
+
+    /// Gives the output type of a function pointer, which names `!` on stable Rust.
+    pub trait FnOutput {
+        type Output;
+    }
+
+    impl<T> FnOutput for fn() -> T {
+        type Output = T;
+    }
+}
+
+/// The never type `!`, returned by the functions that do not return.
+///
+/// It is named with an alias since the mock of [`BootServices`] cannot be generated for a method returning `!` itself.
+pub type Never = <fn() -> ! as __private::FnOutput>::Output;
+
 /// This is the boot services used in the UEFI.
 /// it wraps an atomic ptr to [`efi::BootServices`]
 #[derive(Debug)]
@@ -98,7 +126,7 @@ pub trait BootServices: Sized {
         notify_tpl: Tpl,
         notify_function: Option<EventNotifyCallback<T>>,
         notify_context: T,
-    ) -> Result<efi::Event, efi::Status>
+    ) -> Result<efi::Event, EfiError>
     where
         T: StaticPtr + 'static,
         <T as StaticPtr>::Pointee: Sized + 'static,
@@ -132,7 +160,7 @@ pub trait BootServices: Sized {
         notify_tpl: Tpl,
         notify_function: Option<EventNotifyCallback<*mut T>>,
         notify_context: *mut T,
-    ) -> Result<efi::Event, efi::Status>;
+    ) -> Result<efi::Event, EfiError>;
 
     /// Create an event in a group.
     ///
@@ -144,7 +172,7 @@ pub trait BootServices: Sized {
         notify_function: Option<EventNotifyCallback<T>>,
         notify_context: T,
         event_group: &'static efi::Guid,
-    ) -> Result<efi::Event, efi::Status>
+    ) -> Result<efi::Event, EfiError>
     where
         T: StaticPtr + 'static,
         <T as StaticPtr>::Pointee: Sized + 'static,
@@ -173,34 +201,34 @@ pub trait BootServices: Sized {
         notify_function: EventNotifyCallback<*mut T>,
         notify_context: *mut T,
         event_group: &'static efi::Guid,
-    ) -> Result<efi::Event, efi::Status>;
+    ) -> Result<efi::Event, EfiError>;
 
     /// Close an event.
     ///
     /// [UEFI Spec Documentation: 7.1.3. EFI_BOOT_SERVICES.CloseEvent()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-closeevent)
     ///
     /// [^note]: It is safe to call *close_event* in the notify function.
-    fn close_event(&self, event: efi::Event) -> Result<(), efi::Status>;
+    fn close_event(&self, event: efi::Event) -> Result<(), EfiError>;
 
     /// Signals an event.
     ///
     /// [UEFI Spec Documentation: 7.1.4. EFI_BOOT_SERVICES.SignalEvent()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-signalevent)
-    fn signal_event(&self, event: efi::Event) -> Result<(), efi::Status>;
+    fn signal_event(&self, event: efi::Event) -> Result<(), EfiError>;
 
     /// Stops execution until an event is signaled.
     ///
     /// [UEFI Spec Documentation: 7.1.5. EFI_BOOT_SERVICES.WaitForEvent()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-waitforevent)
-    fn wait_for_event(&self, events: &mut [efi::Event]) -> Result<usize, efi::Status>;
+    fn wait_for_event(&self, events: &mut [efi::Event]) -> Result<usize, EfiError>;
 
     /// Checks whether an event is in the signaled state.
     ///
     /// [UEFI Spec Documentation: 7.1.6. EFI_BOOT_SERVICES.CheckEvent()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-checkevent)
-    fn check_event(&self, event: efi::Event) -> Result<(), efi::Status>;
+    fn check_event(&self, event: efi::Event) -> Result<(), EfiError>;
 
     /// Sets the type of timer and the trigger time for a timer event.
     ///
     /// [UEFI Spec Documentation: 7.1.7. EFI_BOOT_SERVICES.SetTimer()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-settimer)
-    fn set_timer(&self, event: efi::Event, timer_type: EventTimerType, trigger_time: u64) -> Result<(), efi::Status>;
+    fn set_timer(&self, event: efi::Event, timer_type: EventTimerType, trigger_time: u64) -> Result<(), EfiError>;
 
     /// Raises a task's priority level and returns a [`TplGuard`] that will restore the tpl when dropped.
     ///
@@ -227,25 +255,25 @@ pub trait BootServices: Sized {
         alloc_type: AllocType,
         memory_type: MemoryType,
         nb_pages: usize,
-    ) -> Result<usize, efi::Status>;
+    ) -> Result<usize, EfiError>;
 
     /// Frees memory pages.
     ///
     /// [UEFI Spec Documentation: 7.2.2. EFI_BOOT_SERVICES.FreePages()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-freepages)
-    fn free_pages(&self, address: usize, nb_pages: usize) -> Result<(), efi::Status>;
+    fn free_pages(&self, address: usize, nb_pages: usize) -> Result<(), EfiError>;
 
     /// Returns the current memory map.
     ///
     /// [UEFI Spec Documentation: 7.2.3. EFI_BOOT_SERVICES.GetMemoryMap()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-getmemorymap)
-    fn get_memory_map<'a>(&'a self) -> Result<MemoryMap<'a, Self>, (efi::Status, usize)>;
+    fn get_memory_map<'a>(&'a self) -> Result<MemoryMap<'a, Self>, (EfiError, usize)>;
 
     /// Allocates pool memory.
     ///
     /// [UEFI Spec Documentation: 7.2.4. EFI_BOOT_SERVICES.AllocatePool()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-allocatepool)
-    fn allocate_pool(&self, pool_type: MemoryType, size: usize) -> Result<*mut u8, efi::Status>;
+    fn allocate_pool(&self, pool_type: MemoryType, size: usize) -> Result<*mut u8, EfiError>;
 
     /// Allocates pool memory casted as given type.
-    fn allocate_pool_for_type<T: 'static>(&self, pool_type: MemoryType) -> Result<*mut T, efi::Status> {
+    fn allocate_pool_for_type<T: 'static>(&self, pool_type: MemoryType) -> Result<*mut T, EfiError> {
         let ptr = self.allocate_pool(pool_type, mem::size_of::<T>())?;
         Ok(ptr as *mut T)
     }
@@ -253,7 +281,7 @@ pub trait BootServices: Sized {
     /// Returns pool memory to the system.
     ///
     /// [UEFI Spec Documentation: 7.2.5. EFI_BOOT_SERVICES.FreePool()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-freepool)
-    fn free_pool(&self, buffer: *mut u8) -> Result<(), efi::Status>;
+    fn free_pool(&self, buffer: *mut u8) -> Result<(), EfiError>;
 
     /// Installs a protocol interface on a device handle.
     /// If the handle does not exist, it is created and added to the list of handles in the system.
@@ -264,7 +292,7 @@ pub trait BootServices: Sized {
         handle: Option<efi::Handle>,
         protocol: &P,
         interface: &'static mut I,
-    ) -> Result<efi::Handle, efi::Status> {
+    ) -> Result<efi::Handle, EfiError> {
         let interface_ptr = match (interface as &dyn Any).downcast_ref::<()>() {
             Some(()) => ptr::null_mut(),
             None => interface as *mut _ as *mut c_void,
@@ -284,7 +312,7 @@ pub trait BootServices: Sized {
         handle: Option<efi::Handle>,
         protocol: &'static efi::Guid,
         interface: *mut c_void,
-    ) -> Result<efi::Handle, efi::Status>;
+    ) -> Result<efi::Handle, EfiError>;
 
     /// Removes a protocol interface from a device handle.
     ///
@@ -294,7 +322,7 @@ pub trait BootServices: Sized {
         handle: efi::Handle,
         protocol: &P,
         interface: &'static mut I,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         let interface_ptr = match (interface as &dyn Any).downcast_ref::<()>() {
             Some(()) => ptr::null_mut(),
             None => interface as *mut _ as *mut c_void,
@@ -312,7 +340,7 @@ pub trait BootServices: Sized {
         handle: efi::Handle,
         protocol: &'static efi::Guid,
         interface: *mut c_void,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
 
     /// Reinstalls a protocol interface on a device handle.
     ///
@@ -323,7 +351,7 @@ pub trait BootServices: Sized {
         protocol: &P,
         old_protocol_interface: &'static mut I,
         new_protocol_interface: &'static mut I,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         let old_protocol_interface_ptr;
         let new_protocol_interface_ptr;
         if TypeId::of::<I>() == TypeId::of::<()>() {
@@ -355,7 +383,7 @@ pub trait BootServices: Sized {
         protocol: &'static efi::Guid,
         old_protocol_interface: *mut c_void,
         new_protocol_interface: *mut c_void,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
 
     /// Creates an event that is to be signaled whenever an interface is installed for a specified protocol.
     ///
@@ -364,7 +392,7 @@ pub trait BootServices: Sized {
         &self,
         protocol: &'static efi::Guid,
         event: efi::Event,
-    ) -> Result<Registration, efi::Status>;
+    ) -> Result<Registration, EfiError>;
 
     /// Returns an array of handles that support a specified protocol.
     ///
@@ -372,7 +400,7 @@ pub trait BootServices: Sized {
     fn locate_handle<'a>(
         &'a self,
         search_type: HandleSearchType,
-    ) -> Result<BootServicesBox<'a, [efi::Handle], Self>, efi::Status>;
+    ) -> Result<BootServicesBox<'a, [efi::Handle], Self>, EfiError>;
 
     /// Queries a handle to determine if it supports a specified protocol.
     ///
@@ -381,7 +409,7 @@ pub trait BootServices: Sized {
         &self,
         handle: efi::Handle,
         protocol: &P,
-    ) -> Result<&'static mut I, efi::Status> {
+    ) -> Result<&'static mut I, EfiError> {
         //SAFETY: The generic Protocol ensure that the interfaces is the right type for the specified protocol.
         unsafe {
             self.handle_protocol_unchecked(handle, protocol.protocol_guid()).map(|i| (i as *mut I).as_mut().unwrap())
@@ -396,7 +424,40 @@ pub trait BootServices: Sized {
         &self,
         handle: efi::Handle,
         protocol: &efi::Guid,
-    ) -> Result<*mut c_void, efi::Status>;
+    ) -> Result<*mut c_void, EfiError>;
+
+    /// Returns `true` if the handle supports the specified protocol.
+    ///
+    /// See [`BootServices::handle_protocol`] for more details.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    fn supports_protocol<P: Protocol + 'static>(&self, handle: efi::Handle, protocol: &P) -> bool {
+        //SAFETY: The interface is never dereferenced.
+        unsafe { self.handle_protocol_unchecked(handle, protocol.protocol_guid()).is_ok() }
+    }
+
+    /// Gets the protocol interface installed on a handle on behalf of the agent.
+    ///
+    /// This is the [`BootServices::open_protocol`] equivalent of [`BootServices::handle_protocol`] which should be
+    /// preferred by drivers. The protocol is opened with the `GET_PROTOCOL` attribute which does not need to be closed.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    fn get_protocol<P: Protocol<Interface = I> + 'static, I: 'static>(
+        &self,
+        handle: efi::Handle,
+        protocol: &P,
+        agent_handle: efi::Handle,
+    ) -> Result<&'static mut I, EfiError> {
+        //SAFETY: The generic Protocol ensure that the interfaces is the right type for the specified protocol.
+        unsafe {
+            self.open_protocol_unchecked(
+                handle,
+                protocol.protocol_guid(),
+                agent_handle,
+                ptr::null_mut(),
+                efi::OPEN_PROTOCOL_GET_PROTOCOL,
+            )
+            .and_then(|i| (i as *mut I).as_mut().ok_or(efi::Status::UNSUPPORTED.into()))
+        }
+    }
 
     /// Locates the handle to a device on the device path that supports the specified protocol.
     ///
@@ -407,7 +468,7 @@ pub trait BootServices: Sized {
         &self,
         protocol: &efi::Guid,
         device_path: *mut *mut efi::protocols::device_path::Protocol,
-    ) -> Result<efi::Handle, efi::Status>;
+    ) -> Result<efi::Handle, EfiError>;
 
     /// Queries a handle to determine if it supports a specified protocol.
     /// If the protocol is supported by the handle, it opens the protocol on behalf of the calling agent.
@@ -420,7 +481,7 @@ pub trait BootServices: Sized {
         agent_handle: efi::Handle,
         controller_handle: efi::Handle,
         attribute: u32,
-    ) -> Result<Option<&'static mut I>, efi::Status> {
+    ) -> Result<Option<&'static mut I>, EfiError> {
         //SAFETY: The generic Protocol ensure that the interfaces is the right type for the specified protocol.
         unsafe {
             self.open_protocol_unchecked(handle, protocol, agent_handle, controller_handle, attribute)
@@ -440,7 +501,7 @@ pub trait BootServices: Sized {
         agent_handle: efi::Handle,
         controller_handle: efi::Handle,
         attribute: u32,
-    ) -> Result<*mut c_void, efi::Status>;
+    ) -> Result<*mut c_void, EfiError>;
 
     /// Closes a protocol on a handle that was previously opened.
     ///
@@ -451,7 +512,7 @@ pub trait BootServices: Sized {
         protocol: &efi::Guid,
         agent_handle: efi::Handle,
         controller_handle: efi::Handle,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
 
     /// Retrieves the list of agents that currently have a protocol interface opened.
     ///
@@ -460,7 +521,7 @@ pub trait BootServices: Sized {
         &'a self,
         handle: efi::Handle,
         protocol: &efi::Guid,
-    ) -> Result<BootServicesBox<'a, [efi::OpenProtocolInformationEntry], Self>, efi::Status>;
+    ) -> Result<BootServicesBox<'a, [efi::OpenProtocolInformationEntry], Self>, EfiError>;
 
     /// Connects one or more drivers to a controller.
     ///
@@ -475,7 +536,35 @@ pub trait BootServices: Sized {
         driver_image_handle: Vec<efi::Handle>,
         remaining_device_path: *mut efi::protocols::device_path::Protocol,
         recursive: bool,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
+
+    /// Connects all the drivers that support the controller, without any remaining device path.
+    ///
+    /// Returns [`ConnectError::NoDriverConnected`] if no driver was connected to the controller.
+    ///
+    /// See [`BootServices::connect_controller`] for more details.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    fn connect_drivers(&self, controller_handle: efi::Handle, recursive: bool) -> Result<(), ConnectError> {
+        //SAFETY: An empty driver list and a null remaining device path are always valid.
+        unsafe { self.connect_controller(controller_handle, Vec::new(), ptr::null_mut(), recursive) }
+            .map_err(ConnectError::from)
+    }
+
+    /// Recursively connects all the drivers to every handle in the handle database.
+    ///
+    /// Controllers without any driver to connect are ignored, the first real failure is returned once every handle
+    /// has been processed.
+    fn connect_all(&self) -> Result<(), EfiError> {
+        let handles = self.locate_handle_buffer(HandleSearchType::AllHandle)?;
+        let mut result = Ok(());
+        for &handle in handles.iter() {
+            match self.connect_drivers(handle, true) {
+                Err(ConnectError::Failed(status)) if result.is_ok() => result = Err(status.into()),
+                _ => (),
+            }
+        }
+        result
+    }
 
     /// Disconnects one or more drivers from a controller.
     ///
@@ -485,7 +574,15 @@ pub trait BootServices: Sized {
         controller_handle: efi::Handle,
         driver_image_handle: Option<efi::Handle>,
         child_handle: Option<efi::Handle>,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
+
+    /// Disconnects all the drivers that are managing a controller and destroys its child handles.
+    ///
+    /// See [`BootServices::disconnect_controller`] for more details.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    fn disconnect_all_drivers_from(&self, controller_handle: efi::Handle) -> Result<(), EfiError> {
+        self.disconnect_controller(controller_handle, None, None)
+    }
 
     /// Retrieves the list of protocol interface GUIDs that are installed on a handle in a buffer allocated from pool.
     ///
@@ -493,7 +590,15 @@ pub trait BootServices: Sized {
     fn protocols_per_handle<'a>(
         &'a self,
         handle: efi::Handle,
-    ) -> Result<BootServicesBox<'a, [efi::Guid], Self>, efi::Status>;
+    ) -> Result<BootServicesBox<'a, [&'static efi::Guid], Self>, EfiError>;
+
+    /// Returns a [`GuidBuffer`] of the protocols installed on a handle.
+    ///
+    /// See [`BootServices::protocols_per_handle`] for more details.
+    #[allow(clippy::needless_lifetimes)] // The mock of the trait needs the lifetime to be named.
+    fn protocols_on_handle<'a>(&'a self, handle: efi::Handle) -> Result<GuidBuffer<'a, Self>, EfiError> {
+        self.protocols_per_handle(handle).map(GuidBuffer::from)
+    }
 
     /// Returns an array of handles that support the requested protocol in a buffer allocated from pool.
     ///
@@ -501,7 +606,15 @@ pub trait BootServices: Sized {
     fn locate_handle_buffer<'a>(
         &'a self,
         search_type: HandleSearchType,
-    ) -> Result<BootServicesBox<'a, [efi::Handle], Self>, efi::Status>;
+    ) -> Result<BootServicesBox<'a, [efi::Handle], Self>, EfiError>;
+
+    /// Returns a [`HandleBuffer`] of the handles that match the search type.
+    ///
+    /// See [`BootServices::locate_handle_buffer`] for more details.
+    #[allow(clippy::needless_lifetimes)] // The mock of the trait needs the lifetime to be named.
+    fn locate_handles<'a>(&'a self, search_type: HandleSearchType) -> Result<HandleBuffer<'a, Self>, EfiError> {
+        self.locate_handle_buffer(search_type).map(HandleBuffer::from)
+    }
 
     /// Returns the first protocol instance that matches the given protocol.
     ///
@@ -510,7 +623,7 @@ pub trait BootServices: Sized {
         &self,
         protocol: &P,
         registration: Option<Registration>,
-    ) -> Result<&'static mut I, efi::Status> {
+    ) -> Result<&'static mut I, EfiError> {
         //SAFETY: The generic Protocol ensure that the interfaces is the right type for the specified protocol.
         unsafe {
             self.locate_protocol_unchecked(
@@ -529,7 +642,7 @@ pub trait BootServices: Sized {
         &self,
         protocol: &'static efi::Guid,
         registration: *mut c_void,
-    ) -> Result<*mut c_void, efi::Status>;
+    ) -> Result<*mut c_void, EfiError>;
 
     /// Adds, updates, or removes a configuration table entry from the EFI System Table.
     ///
@@ -538,10 +651,18 @@ pub trait BootServices: Sized {
         &self,
         guid: &efi::Guid,
         table: T,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         unsafe { self.install_configuration_table_unchecked(guid, table.into_raw_mut() as *mut c_void) }
     }
 
+    /// Removes a configuration table entry from the EFI System Table.
+    ///
+    /// [UEFI Spec Documentation: 7.5.6. EFI_BOOT_SERVICES.InstallConfigurationTable()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-installconfigurationtable)
+    fn uninstall_configuration_table(&self, guid: &efi::Guid) -> Result<(), EfiError> {
+        //SAFETY: A null table removes the entry, no pointer is dereferenced.
+        unsafe { self.install_configuration_table_unchecked(guid, ptr::null_mut()) }
+    }
+
     /// Prefer normal [`BootServices::install_configuration_table`] when possible.
     ///
     /// # Safety
@@ -550,7 +671,77 @@ pub trait BootServices: Sized {
         &self,
         guid: &efi::Guid,
         table: *mut c_void,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
+
+    /// Waits for at least the given number of microseconds.
+    ///
+    /// [UEFI Spec Documentation: 7.5.1. EFI_BOOT_SERVICES.Stall()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-stall)
+    fn stall(&self, microseconds: usize) -> Result<(), EfiError>;
+
+    /// Returns a monotonically increasing count for the platform.
+    ///
+    /// [UEFI Spec Documentation: 7.5.2. EFI_BOOT_SERVICES.GetNextMonotonicCount()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-getnextmonotoniccount)
+    fn get_next_monotonic_count(&self) -> Result<u64, EfiError>;
+
+    /// Terminates a loaded image and returns control to boot services.
+    ///
+    /// When `exit_data` is set, it is given to the caller of StartImage() as a null-terminated UTF-16 string allocated
+    /// from pool, as required by the specification.
+    ///
+    /// # Panics
+    ///
+    /// Panics once the exit data is freed if the firmware returns, which happens when the image handle is not the
+    /// currently executing image.
+    ///
+    /// [UEFI Spec Documentation: 7.4.5. EFI_BOOT_SERVICES.Exit()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-exit)
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    #[allow(unreachable_code)] // The mock of the method returns after calling an expectation that never returns.
+                               // The mock of the trait needs the lifetime of the exit data to be named, and tied to `self` to not be elided.
+    fn exit<'a>(&'a self, image_handle: efi::Handle, exit_status: efi::Status, exit_data: Option<&'a str>) -> Never {
+        let (exit_data_size, exit_data) = match exit_data {
+            Some(exit_data) => {
+                let size = (exit_data.encode_utf16().count() + 1) * mem::size_of::<u16>();
+                match self.allocate_pool(MemoryType::BOOT_SERVICES_DATA, size) {
+                    Ok(buffer) => {
+                        let buffer = buffer as *mut u16;
+                        for (i, c) in exit_data.encode_utf16().chain([0]).enumerate() {
+                            //SAFETY: The buffer was allocated for every character and the null terminator.
+                            unsafe { buffer.add(i).write_unaligned(c) };
+                        }
+                        (size, buffer)
+                    }
+                    // The exit data is optional, exiting is more important than reporting it.
+                    Err(_) => (0, ptr::null_mut()),
+                }
+            }
+            None => (0, ptr::null_mut()),
+        };
+        //SAFETY: The exit data is null or a null-terminated string allocated from pool.
+        let status = unsafe { self.exit_unchecked(image_handle, exit_status, exit_data_size, exit_data) };
+        if !exit_data.is_null() {
+            let _ = self.free_pool(exit_data as *mut u8);
+        }
+        panic!("Exit returned with status {}.", status::StatusDisplay(status))
+    }
+
+    /// Terminates a loaded image and returns control to boot services.
+    /// Returns only if the image could not be exited.
+    ///
+    /// Prefer normal [`BootServices::exit`] when possible.
+    ///
+    /// # Safety
+    ///
+    /// *exit_data* must be null or a null-terminated UTF-16 string, optionally followed by binary data, allocated
+    /// with [`BootServices::allocate_pool`] and of *exit_data_size* bytes.
+    ///
+    /// [UEFI Spec Documentation: 7.4.5. EFI_BOOT_SERVICES.Exit()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-exit)
+    unsafe fn exit_unchecked(
+        &self,
+        image_handle: efi::Handle,
+        exit_status: efi::Status,
+        exit_data_size: usize,
+        exit_data: *mut u16,
+    ) -> efi::Status;
 }
 
 impl BootServices for StandardBootServices<'_> {
@@ -560,7 +751,7 @@ impl BootServices for StandardBootServices<'_> {
         notify_tpl: Tpl,
         notify_function: Option<EventNotifyCallback<*mut T>>,
         notify_context: *mut T,
-    ) -> Result<efi::Event, efi::Status> {
+    ) -> Result<efi::Event, EfiError> {
         let create_event = self.efi_boot_services().create_event;
         if create_event as usize == 0 {
             panic!("function not initialize.")
@@ -575,7 +766,7 @@ impl BootServices for StandardBootServices<'_> {
             event.as_mut_ptr(),
         );
         if status.is_error() {
-            Err(status)
+            Err(status.into())
         } else {
             Ok(event.assume_init())
         }
@@ -588,7 +779,7 @@ impl BootServices for StandardBootServices<'_> {
         notify_function: EventNotifyCallback<*mut T>,
         notify_context: *mut T,
         event_group: &'static efi::Guid,
-    ) -> Result<efi::Event, efi::Status> {
+    ) -> Result<efi::Event, EfiError> {
         let create_event_ex = self.efi_boot_services().create_event_ex;
         if create_event_ex as usize == 0 {
             panic!("function not initialize.")
@@ -604,66 +795,67 @@ impl BootServices for StandardBootServices<'_> {
             event.as_mut_ptr(),
         );
         if status.is_error() {
-            Err(status)
+            Err(status.into())
         } else {
             Ok(event.assume_init())
         }
     }
 
-    fn close_event(&self, event: efi::Event) -> Result<(), efi::Status> {
+    fn close_event(&self, event: efi::Event) -> Result<(), EfiError> {
         let close_event = self.efi_boot_services().close_event;
         if close_event as usize == 0 {
             panic!("function not initialize.")
         }
-        match close_event(event) {
-            s if s.is_error() => Err(s),
+        match unsafe { r_efi::efi_call(close_event, (event,)) } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
 
-    fn signal_event(&self, event: efi::Event) -> Result<(), efi::Status> {
+    fn signal_event(&self, event: efi::Event) -> Result<(), EfiError> {
         let signal_event = self.efi_boot_services().signal_event;
         if signal_event as usize == 0 {
             panic!("function not initialize.")
         }
-        match signal_event(event) {
-            s if s.is_error() => Err(s),
+        match unsafe { r_efi::efi_call(signal_event, (event,)) } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
 
-    fn wait_for_event(&self, events: &mut [efi::Event]) -> Result<usize, efi::Status> {
+    fn wait_for_event(&self, events: &mut [efi::Event]) -> Result<usize, EfiError> {
         let wait_for_event = self.efi_boot_services().wait_for_event;
         if wait_for_event as usize == 0 {
             panic!("function not initialize.")
         }
         let mut index = MaybeUninit::zeroed();
-        let status = wait_for_event(events.len(), events.as_mut_ptr(), index.as_mut_ptr());
+        let status =
+            unsafe { r_efi::efi_call(wait_for_event, (events.len(), events.as_mut_ptr(), index.as_mut_ptr())) };
         if status.is_error() {
-            Err(status)
+            Err(status.into())
         } else {
             Ok(unsafe { index.assume_init() })
         }
     }
 
-    fn check_event(&self, event: efi::Event) -> Result<(), efi::Status> {
+    fn check_event(&self, event: efi::Event) -> Result<(), EfiError> {
         let check_event = self.efi_boot_services().check_event;
         if check_event as usize == 0 {
             panic!("function not initialize.")
         }
-        match check_event(event) {
-            s if s.is_error() => Err(s),
+        match unsafe { r_efi::efi_call(check_event, (event,)) } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
 
-    fn set_timer(&self, event: efi::Event, timer_type: EventTimerType, trigger_time: u64) -> Result<(), efi::Status> {
+    fn set_timer(&self, event: efi::Event, timer_type: EventTimerType, trigger_time: u64) -> Result<(), EfiError> {
         let set_timer = self.efi_boot_services().set_timer;
         if set_timer as usize == 0 {
             panic!("function not initialize.")
         }
-        match set_timer(event, timer_type.into(), trigger_time) {
-            s if s.is_error() => Err(s),
+        match unsafe { r_efi::efi_call(set_timer, (event, timer_type.into(), trigger_time)) } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
@@ -673,7 +865,7 @@ impl BootServices for StandardBootServices<'_> {
         if raise_tpl as usize == 0 {
             panic!("function not initialize.")
         }
-        raise_tpl(new_tpl.into()).into()
+        unsafe { r_efi::efi_call(raise_tpl, (new_tpl.into(),)) }.into()
     }
 
     fn restore_tpl(&self, old_tpl: Tpl) {
@@ -681,7 +873,7 @@ impl BootServices for StandardBootServices<'_> {
         if restore_tpl as usize == 0 {
             panic!("function not initialize.")
         }
-        restore_tpl(old_tpl.into())
+        unsafe { r_efi::efi_call(restore_tpl, (old_tpl.into(),)) }
     }
 
     fn allocate_pages(
@@ -689,7 +881,7 @@ impl BootServices for StandardBootServices<'_> {
         alloc_type: AllocType,
         memory_type: MemoryType,
         nb_pages: usize,
-    ) -> Result<usize, efi::Status> {
+    ) -> Result<usize, EfiError> {
         let allocate_pages = self.efi_boot_services().allocate_pages;
         if allocate_pages as usize == 0 {
             panic!("function not initialize.")
@@ -700,29 +892,29 @@ impl BootServices for StandardBootServices<'_> {
             AllocType::MaxAddress(address) => address,
             _ => 0,
         };
-        match allocate_pages(
-            alloc_type.into(),
-            memory_type.into(),
-            nb_pages,
-            ptr::addr_of_mut!(memory_address) as *mut u64,
-        ) {
-            s if s.is_error() => Err(s),
+        match unsafe {
+            r_efi::efi_call(
+                allocate_pages,
+                (alloc_type.into(), memory_type.into(), nb_pages, ptr::addr_of_mut!(memory_address) as *mut u64),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(memory_address),
         }
     }
 
-    fn free_pages(&self, address: usize, nb_pages: usize) -> Result<(), efi::Status> {
+    fn free_pages(&self, address: usize, nb_pages: usize) -> Result<(), EfiError> {
         let free_pages = self.efi_boot_services().free_pages;
         if free_pages as usize == 0 {
             panic!("function not initialize.")
         }
-        match free_pages(address as u64, nb_pages) {
-            s if s.is_error() => Err(s),
+        match unsafe { r_efi::efi_call(free_pages, (address as u64, nb_pages)) } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
 
-    fn get_memory_map<'a>(&'a self) -> Result<MemoryMap<'a, Self>, (efi::Status, usize)> {
+    fn get_memory_map<'a>(&'a self) -> Result<MemoryMap<'a, Self>, (EfiError, usize)> {
         let get_memory_map = self.efi_boot_services().get_memory_map;
         if get_memory_map as usize == 0 {
             panic!("function not initialize.")
@@ -733,28 +925,38 @@ impl BootServices for StandardBootServices<'_> {
         let mut descriptor_size = 0;
         let mut descriptor_version = 0;
 
-        match get_memory_map(
-            ptr::addr_of_mut!(memory_map_size),
-            ptr::null_mut(),
-            ptr::addr_of_mut!(map_key),
-            ptr::addr_of_mut!(descriptor_size),
-            ptr::addr_of_mut!(descriptor_version),
-        ) {
+        match unsafe {
+            r_efi::efi_call(
+                get_memory_map,
+                (
+                    ptr::addr_of_mut!(memory_map_size),
+                    ptr::null_mut(),
+                    ptr::addr_of_mut!(map_key),
+                    ptr::addr_of_mut!(descriptor_size),
+                    ptr::addr_of_mut!(descriptor_version),
+                ),
+            )
+        } {
             s if s == efi::Status::BUFFER_TOO_SMALL => memory_map_size += 0x400, // add more space in case allocation makes the memory map bigger.
             _ => (),
         };
 
         let buffer = self.allocate_pool(MemoryType::BOOT_SERVICES_DATA, memory_map_size).map_err(|s| (s, 0))?;
 
-        match get_memory_map(
-            ptr::addr_of_mut!(memory_map_size),
-            buffer as *mut _,
-            ptr::addr_of_mut!(map_key),
-            ptr::addr_of_mut!(descriptor_size),
-            ptr::addr_of_mut!(descriptor_version),
-        ) {
-            s if s == efi::Status::BUFFER_TOO_SMALL => return Err((s, memory_map_size)),
-            s if s.is_error() => return Err((s, 0)),
+        match unsafe {
+            r_efi::efi_call(
+                get_memory_map,
+                (
+                    ptr::addr_of_mut!(memory_map_size),
+                    buffer as *mut _,
+                    ptr::addr_of_mut!(map_key),
+                    ptr::addr_of_mut!(descriptor_size),
+                    ptr::addr_of_mut!(descriptor_version),
+                ),
+            )
+        } {
+            s if s == efi::Status::BUFFER_TOO_SMALL => return Err((s.into(), memory_map_size)),
+            s if s.is_error() => return Err((s.into(), 0)),
             _ => (),
         }
         Ok(MemoryMap {
@@ -764,25 +966,25 @@ impl BootServices for StandardBootServices<'_> {
         })
     }
 
-    fn allocate_pool(&self, memory_type: MemoryType, size: usize) -> Result<*mut u8, efi::Status> {
+    fn allocate_pool(&self, memory_type: MemoryType, size: usize) -> Result<*mut u8, EfiError> {
         let allocate_pool = self.efi_boot_services().allocate_pool;
         if allocate_pool as usize == 0 {
             panic!("function not initialize.")
         }
         let mut buffer = ptr::null_mut();
-        match allocate_pool(memory_type.into(), size, ptr::addr_of_mut!(buffer)) {
-            s if s.is_error() => return Err(s),
+        match unsafe { r_efi::efi_call(allocate_pool, (memory_type.into(), size, ptr::addr_of_mut!(buffer))) } {
+            s if s.is_error() => return Err(s.into()),
             _ => Ok(buffer as *mut u8),
         }
     }
 
-    fn free_pool(&self, buffer: *mut u8) -> Result<(), efi::Status> {
+    fn free_pool(&self, buffer: *mut u8) -> Result<(), EfiError> {
         let free_pool = self.efi_boot_services().free_pool;
         if free_pool as usize == 0 {
             panic!("function not initialize.")
         }
-        match free_pool(buffer as *mut c_void) {
-            s if s.is_error() => return Err(s),
+        match unsafe { r_efi::efi_call(free_pool, (buffer as *mut c_void,)) } {
+            s if s.is_error() => return Err(s.into()),
             _ => Ok(()),
         }
     }
@@ -792,7 +994,7 @@ impl BootServices for StandardBootServices<'_> {
         handle: Option<efi::Handle>,
         protocol: &'static efi::Guid,
         interface: *mut c_void,
-    ) -> Result<efi::Handle, efi::Status> {
+    ) -> Result<efi::Handle, EfiError> {
         let install_protocol_interface = self.efi_boot_services().install_protocol_interface;
         if install_protocol_interface as usize == 0 {
             panic!("function not initialize.")
@@ -805,7 +1007,7 @@ impl BootServices for StandardBootServices<'_> {
             efi::NATIVE_INTERFACE,
             interface,
         ) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
             _ => Ok(handle),
         }
     }
@@ -815,13 +1017,13 @@ impl BootServices for StandardBootServices<'_> {
         handle: efi::Handle,
         protocol: &'static efi::Guid,
         interface: *mut c_void,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         let uninstall_protocol_interface = self.efi_boot_services().uninstall_protocol_interface;
         if uninstall_protocol_interface as usize == 0 {
             panic!("function not initialize.")
         }
         match uninstall_protocol_interface(handle, protocol as *const _ as *mut _, interface) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
@@ -832,7 +1034,7 @@ impl BootServices for StandardBootServices<'_> {
         protocol: &'static efi::Guid,
         old_protocol_interface: *mut c_void,
         new_protocol_interface: *mut c_void,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         let reinstall_protocol_interface = self.efi_boot_services().reinstall_protocol_interface;
         if reinstall_protocol_interface as usize == 0 {
             panic!("function not initialize.")
@@ -843,27 +1045,29 @@ impl BootServices for StandardBootServices<'_> {
             old_protocol_interface,
             new_protocol_interface,
         ) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
 
-    fn register_protocol_notify(&self, protocol: &efi::Guid, event: efi::Event) -> Result<Registration, efi::Status> {
+    fn register_protocol_notify(&self, protocol: &efi::Guid, event: efi::Event) -> Result<Registration, EfiError> {
         let register_protocol_notify = self.efi_boot_services().register_protocol_notify;
         if register_protocol_notify as usize == 0 {
             panic!("function not initialize.")
         }
         let mut registration = MaybeUninit::uninit();
-        match register_protocol_notify(protocol as *const _ as *mut _, event, registration.as_mut_ptr() as *mut _) {
-            s if s.is_error() => Err(s),
+        match unsafe {
+            r_efi::efi_call(
+                register_protocol_notify,
+                (protocol as *const _ as *mut _, event, registration.as_mut_ptr() as *mut _),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(unsafe { registration.assume_init() }),
         }
     }
 
-    fn locate_handle(
-        &self,
-        search_type: HandleSearchType,
-    ) -> Result<BootServicesBox<[efi::Handle], Self>, efi::Status> {
+    fn locate_handle(&self, search_type: HandleSearchType) -> Result<BootServicesBox<[efi::Handle], Self>, EfiError> {
         let locate_handle = self.efi_boot_services().locate_handle;
         if locate_handle as usize == 0 {
             panic!("function not initialize.")
@@ -879,18 +1083,22 @@ impl BootServices for StandardBootServices<'_> {
 
         // Use to get the buffer_size
         let mut buffer_size = 0;
-        locate_handle(search_type.into(), protocol, search_key, ptr::addr_of_mut!(buffer_size), ptr::null_mut());
+        unsafe {
+            r_efi::efi_call(
+                locate_handle,
+                (search_type.into(), protocol, search_key, ptr::addr_of_mut!(buffer_size), ptr::null_mut()),
+            )
+        };
 
         let buffer = self.allocate_pool(MemoryType::BOOT_SERVICES_DATA, buffer_size)?;
 
-        match locate_handle(
-            search_type.into(),
-            protocol,
-            search_key,
-            ptr::addr_of_mut!(buffer_size),
-            buffer as *mut efi::Handle,
-        ) {
-            s if s.is_error() => Err(s),
+        match unsafe {
+            r_efi::efi_call(
+                locate_handle,
+                (search_type.into(), protocol, search_key, ptr::addr_of_mut!(buffer_size), buffer as *mut efi::Handle),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(unsafe {
                 BootServicesBox::from_raw_parts(buffer as *mut _, buffer_size / mem::size_of::<efi::Handle>(), &self)
             }),
@@ -901,14 +1109,14 @@ impl BootServices for StandardBootServices<'_> {
         &self,
         handle: efi::Handle,
         protocol: &efi::Guid,
-    ) -> Result<*mut c_void, efi::Status> {
+    ) -> Result<*mut c_void, EfiError> {
         let handle_protocol = self.efi_boot_services().handle_protocol;
         if handle_protocol as usize == 0 {
             panic!("function not initialize.")
         }
         let mut interface = ptr::null_mut();
         match handle_protocol(handle, protocol as *const _ as *mut _, ptr::addr_of_mut!(interface)) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
             _ => Ok(interface),
         }
     }
@@ -917,14 +1125,14 @@ impl BootServices for StandardBootServices<'_> {
         &self,
         protocol: &efi::Guid,
         device_path: *mut *mut efi::protocols::device_path::Protocol,
-    ) -> Result<efi::Handle, efi::Status> {
+    ) -> Result<efi::Handle, EfiError> {
         let locate_device_path = self.efi_boot_services().locate_device_path;
         if locate_device_path as usize == 0 {
             panic!("function not initialize.")
         }
         let mut device = ptr::null_mut();
         match locate_device_path(protocol as *const _ as *mut _, device_path, ptr::addr_of_mut!(device)) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
             _ => Ok(device),
         }
     }
@@ -936,7 +1144,7 @@ impl BootServices for StandardBootServices<'_> {
         agent_handle: efi::Handle,
         controller_handle: efi::Handle,
         attribute: u32,
-    ) -> Result<*mut c_void, efi::Status> {
+    ) -> Result<*mut c_void, EfiError> {
         let open_protocol = self.efi_boot_services().open_protocol;
         if open_protocol as usize == 0 {
             panic!("function not initialize.")
@@ -950,7 +1158,7 @@ impl BootServices for StandardBootServices<'_> {
             controller_handle,
             attribute,
         ) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
             _ => Ok(interface),
         }
     }
@@ -961,13 +1169,15 @@ impl BootServices for StandardBootServices<'_> {
         protocol: &efi::Guid,
         agent_handle: efi::Handle,
         controller_handle: efi::Handle,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         let close_protocol = self.efi_boot_services().close_protocol;
         if close_protocol as usize == 0 {
             panic!("function not initialize.")
         }
-        match close_protocol(handle, protocol as *const _ as *mut _, agent_handle, controller_handle) {
-            s if s.is_error() => Err(s),
+        match unsafe {
+            r_efi::efi_call(close_protocol, (handle, protocol as *const _ as *mut _, agent_handle, controller_handle))
+        } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
@@ -976,7 +1186,7 @@ impl BootServices for StandardBootServices<'_> {
         &self,
         handle: efi::Handle,
         protocol: &efi::Guid,
-    ) -> Result<BootServicesBox<[efi::OpenProtocolInformationEntry], Self>, efi::Status>
+    ) -> Result<BootServicesBox<[efi::OpenProtocolInformationEntry], Self>, EfiError>
     where
         Self: Sized,
     {
@@ -987,13 +1197,18 @@ impl BootServices for StandardBootServices<'_> {
 
         let mut entry_buffer = ptr::null_mut();
         let mut entry_count = 0;
-        match open_protocol_information(
-            handle,
-            protocol as *const _ as *mut _,
-            ptr::addr_of_mut!(entry_buffer),
-            ptr::addr_of_mut!(entry_count),
-        ) {
-            s if s.is_error() => Err(s),
+        match unsafe {
+            r_efi::efi_call(
+                open_protocol_information,
+                (
+                    handle,
+                    protocol as *const _ as *mut _,
+                    ptr::addr_of_mut!(entry_buffer),
+                    ptr::addr_of_mut!(entry_count),
+                ),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(unsafe { BootServicesBox::from_raw_parts(entry_buffer, entry_count, self) }),
         }
     }
@@ -1004,7 +1219,7 @@ impl BootServices for StandardBootServices<'_> {
         mut driver_image_handle: Vec<efi::Handle>,
         remaining_device_path: *mut efi::protocols::device_path::Protocol,
         recursive: bool,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         let connect_controller = self.efi_boot_services().connect_controller;
         if connect_controller as usize == 0 {
             panic!("function not initialize.")
@@ -1017,7 +1232,7 @@ impl BootServices for StandardBootServices<'_> {
             driver_image_handle.as_mut_ptr()
         };
         match connect_controller(controller_handle, driver_image_handle, remaining_device_path, recursive.into()) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
@@ -1027,22 +1242,30 @@ impl BootServices for StandardBootServices<'_> {
         controller_handle: efi::Handle,
         driver_image_handle: Option<efi::Handle>,
         child_handle: Option<efi::Handle>,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         let disconnect_controller = self.efi_boot_services().disconnect_controller;
         if disconnect_controller as usize == 0 {
             panic!("function not initialize.")
         }
-        match disconnect_controller(
-            controller_handle,
-            driver_image_handle.unwrap_or(ptr::null_mut()),
-            child_handle.unwrap_or(ptr::null_mut()),
-        ) {
-            s if s.is_error() => Err(s),
+        match unsafe {
+            r_efi::efi_call(
+                disconnect_controller,
+                (
+                    controller_handle,
+                    driver_image_handle.unwrap_or(ptr::null_mut()),
+                    child_handle.unwrap_or(ptr::null_mut()),
+                ),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
 
-    fn protocols_per_handle(&self, handle: efi::Handle) -> Result<BootServicesBox<[efi::Guid], Self>, efi::Status> {
+    fn protocols_per_handle(
+        &self,
+        handle: efi::Handle,
+    ) -> Result<BootServicesBox<[&'static efi::Guid], Self>, EfiError> {
         let protocols_per_handle = self.efi_boot_services().protocols_per_handle;
         if protocols_per_handle as usize == 0 {
             panic!("function not initialize.")
@@ -1050,9 +1273,13 @@ impl BootServices for StandardBootServices<'_> {
 
         let mut protocol_buffer = ptr::null_mut();
         let mut protocol_buffer_count = 0;
-        match protocols_per_handle(handle, ptr::addr_of_mut!(protocol_buffer), ptr::addr_of_mut!(protocol_buffer_count))
-        {
-            s if s.is_error() => Err(s),
+        match unsafe {
+            r_efi::efi_call(
+                protocols_per_handle,
+                (handle, ptr::addr_of_mut!(protocol_buffer), ptr::addr_of_mut!(protocol_buffer_count)),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(unsafe {
                 BootServicesBox::<[_], _>::from_raw_parts(protocol_buffer as *mut _, protocol_buffer_count, self)
             }),
@@ -1062,7 +1289,7 @@ impl BootServices for StandardBootServices<'_> {
     fn locate_handle_buffer(
         &self,
         search_type: HandleSearchType,
-    ) -> Result<BootServicesBox<[efi::Handle], Self>, efi::Status>
+    ) -> Result<BootServicesBox<[efi::Handle], Self>, EfiError>
     where
         Self: Sized,
     {
@@ -1081,14 +1308,13 @@ impl BootServices for StandardBootServices<'_> {
             HandleSearchType::ByRegisterNotify(r) => r.as_ptr(),
             _ => ptr::null_mut(),
         };
-        match locate_handle_buffer(
-            search_type.into(),
-            protocol,
-            search_key,
-            ptr::addr_of_mut!(buffer_count),
-            ptr::addr_of_mut!(buffer),
-        ) {
-            s if s.is_error() => Err(s),
+        match unsafe {
+            r_efi::efi_call(
+                locate_handle_buffer,
+                (search_type.into(), protocol, search_key, ptr::addr_of_mut!(buffer_count), ptr::addr_of_mut!(buffer)),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
             _ => {
                 Ok(unsafe { BootServicesBox::<[_], _>::from_raw_parts(buffer as *mut efi::Handle, buffer_count, self) })
             }
@@ -1099,14 +1325,14 @@ impl BootServices for StandardBootServices<'_> {
         &self,
         protocol: &'static efi::Guid,
         registration: *mut c_void,
-    ) -> Result<*mut c_void, efi::Status> {
+    ) -> Result<*mut c_void, EfiError> {
         let locate_protocol = self.efi_boot_services().locate_protocol;
         if locate_protocol as usize == 0 {
             panic!("function not initialize.")
         }
         let mut interface = ptr::null_mut();
         match locate_protocol(protocol as *const _ as *mut _, registration, ptr::addr_of_mut!(interface)) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
             _ => Ok(interface),
         }
     }
@@ -1115,16 +1341,53 @@ impl BootServices for StandardBootServices<'_> {
         &self,
         guid: &efi::Guid,
         table: *mut c_void,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         let install_configuration_table = self.efi_boot_services().install_configuration_table;
         if install_configuration_table as usize == 0 {
             panic!("function not initialize.")
         }
         match install_configuration_table(guid as *const _ as *mut _, table) {
-            s if s.is_error() => Err(s),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn stall(&self, microseconds: usize) -> Result<(), EfiError> {
+        let stall = self.efi_boot_services().stall;
+        if stall as usize == 0 {
+            panic!("function not initialize.")
+        }
+        match unsafe { r_efi::efi_call(stall, (microseconds,)) } {
+            s if s.is_error() => Err(s.into()),
             _ => Ok(()),
         }
     }
+
+    fn get_next_monotonic_count(&self) -> Result<u64, EfiError> {
+        let get_next_monotonic_count = self.efi_boot_services().get_next_monotonic_count;
+        if get_next_monotonic_count as usize == 0 {
+            panic!("function not initialize.")
+        }
+        let mut count = 0;
+        match unsafe { r_efi::efi_call(get_next_monotonic_count, (ptr::addr_of_mut!(count),)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(count),
+        }
+    }
+
+    unsafe fn exit_unchecked(
+        &self,
+        image_handle: efi::Handle,
+        exit_status: efi::Status,
+        exit_data_size: usize,
+        exit_data: *mut u16,
+    ) -> efi::Status {
+        let exit = self.efi_boot_services().exit;
+        if exit as usize == 0 {
+            panic!("function not initialize.")
+        }
+        exit(image_handle, exit_status, exit_data_size, exit_data as *mut efi::Char16)
+    }
 }
 
 #[cfg(test)]
@@ -1132,7 +1395,10 @@ mod test {
     use efi;
 
     use super::*;
-    use core::{mem::MaybeUninit, sync::atomic::AtomicUsize};
+    use core::{
+        mem::MaybeUninit,
+        sync::atomic::{AtomicU64, AtomicUsize},
+    };
 
     macro_rules! boot_services {
     ($($efi_services:ident = $efi_service_fn:ident),*) => {{
@@ -1195,7 +1461,7 @@ mod test {
             assert_ne!(ptr::null_mut(), event);
 
             if let Some(notify_function) = notify_function {
-                notify_function(ptr::null_mut(), notify_context);
+                unsafe { r_efi::efi_call(notify_function, (ptr::null_mut(), notify_context)) };
             }
             efi::Status::SUCCESS
         }
@@ -1268,7 +1534,7 @@ mod test {
             assert_ne!(ptr::null_mut(), event);
 
             if let Some(notify_function) = notify_function {
-                notify_function(ptr::null_mut(), notify_context as *mut _);
+                unsafe { r_efi::efi_call(notify_function, (ptr::null_mut(), notify_context as *mut _)) };
             }
             efi::Status::SUCCESS
         }
@@ -1585,6 +1851,178 @@ mod test {
         assert_eq!(status, Ok(0x55AA as *mut u8));
     }
 
+    struct TestProtocol;
+    unsafe impl Protocol for TestProtocol {
+        type Interface = u32;
+        fn protocol_guid(&self) -> &'static efi::Guid {
+            static GUID: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+            &GUID
+        }
+    }
+    impl core::ops::Deref for TestProtocol {
+        type Target = efi::Guid;
+        fn deref(&self) -> &Self::Target {
+            self.protocol_guid()
+        }
+    }
+
+    #[test]
+    fn test_supports_protocol() {
+        let boot_services = boot_services!(handle_protocol = efi_handle_protocol);
+
+        extern "efiapi" fn efi_handle_protocol(
+            handle: efi::Handle,
+            protocol: *mut efi::Guid,
+            interface: *mut *mut c_void,
+        ) -> efi::Status {
+            assert_eq!(unsafe { &*protocol }, protocol_handler::DriverBinding.protocol_guid());
+            match handle as usize {
+                1 => {
+                    unsafe { ptr::write(interface, 0x55AA as *mut c_void) };
+                    efi::Status::SUCCESS
+                }
+                _ => efi::Status::UNSUPPORTED,
+            }
+        }
+
+        assert!(boot_services.supports_protocol(1_usize as efi::Handle, &protocol_handler::DriverBinding));
+        assert!(!boot_services.supports_protocol(2_usize as efi::Handle, &protocol_handler::DriverBinding));
+    }
+
+    #[test]
+    fn test_get_protocol() {
+        let boot_services = boot_services!(open_protocol = efi_open_protocol);
+
+        static mut INTERFACE: u32 = 42;
+
+        extern "efiapi" fn efi_open_protocol(
+            handle: efi::Handle,
+            protocol: *mut efi::Guid,
+            interface: *mut *mut c_void,
+            agent_handle: efi::Handle,
+            controller_handle: efi::Handle,
+            attributes: u32,
+        ) -> efi::Status {
+            assert_eq!(unsafe { &*protocol }, TestProtocol.protocol_guid());
+            assert_eq!(2, agent_handle as usize);
+            assert_eq!(ptr::null_mut(), controller_handle);
+            assert_eq!(efi::OPEN_PROTOCOL_GET_PROTOCOL, attributes);
+            match handle as usize {
+                1 => {
+                    unsafe { ptr::write(interface, ptr::addr_of_mut!(INTERFACE) as *mut c_void) };
+                    efi::Status::SUCCESS
+                }
+                _ => efi::Status::UNSUPPORTED,
+            }
+        }
+
+        let interface =
+            boot_services.get_protocol(1_usize as efi::Handle, &TestProtocol, 2_usize as efi::Handle).unwrap();
+        assert_eq!(42, *interface);
+        assert_eq!(
+            Err(efi::Status::UNSUPPORTED.into()),
+            boot_services.get_protocol(3_usize as efi::Handle, &TestProtocol, 2_usize as efi::Handle)
+        );
+    }
+
+    #[test]
+    fn test_locate_handles() {
+        let boot_services = boot_services!(locate_handle_buffer = efi_locate_handle_buffer, free_pool = efi_free_pool);
+
+        static mut HANDLES: [efi::Handle; 3] = [1 as efi::Handle, 2 as efi::Handle, 3 as efi::Handle];
+        static FREE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        extern "efiapi" fn efi_locate_handle_buffer(
+            search_type: efi::LocateSearchType,
+            protocol: *mut efi::Guid,
+            search_key: *mut c_void,
+            no_handles: *mut usize,
+            buffer: *mut *mut efi::Handle,
+        ) -> efi::Status {
+            assert_eq!(efi::BY_PROTOCOL, search_type);
+            assert_eq!(unsafe { &*protocol }, protocol_handler::DriverBinding.protocol_guid());
+            assert_eq!(ptr::null_mut(), search_key);
+            unsafe {
+                ptr::write(no_handles, 3);
+                ptr::write(buffer, ptr::addr_of_mut!(HANDLES) as *mut efi::Handle);
+            }
+            efi::Status::SUCCESS
+        }
+
+        extern "efiapi" fn efi_free_pool(buffer: *mut c_void) -> efi::Status {
+            assert_eq!(unsafe { ptr::addr_of_mut!(HANDLES) } as *mut c_void, buffer);
+            FREE_COUNT.fetch_add(1, Ordering::Relaxed);
+            efi::Status::SUCCESS
+        }
+
+        let handles = HandleBuffer::supporting(boot_services, &protocol_handler::DriverBinding).unwrap();
+        assert_eq!(3, handles.len());
+        assert_eq!(vec![1_usize, 2, 3], handles.iter().map(|h| h as usize).collect::<Vec<_>>());
+        for (i, handle) in (&handles).into_iter().enumerate() {
+            assert_eq!(i + 1, handle as usize);
+        }
+        assert_eq!(0, FREE_COUNT.load(Ordering::Relaxed));
+        drop(handles);
+        assert_eq!(1, FREE_COUNT.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_connect_all() {
+        let boot_services = boot_services!(
+            locate_handle_buffer = efi_locate_handle_buffer,
+            connect_controller = efi_connect_controller,
+            free_pool = efi_free_pool
+        );
+
+        static mut HANDLES: [efi::Handle; 3] = [1 as efi::Handle, 2 as efi::Handle, 3 as efi::Handle];
+        static CONNECT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        extern "efiapi" fn efi_locate_handle_buffer(
+            search_type: efi::LocateSearchType,
+            _protocol: *mut efi::Guid,
+            _search_key: *mut c_void,
+            no_handles: *mut usize,
+            buffer: *mut *mut efi::Handle,
+        ) -> efi::Status {
+            assert_eq!(efi::ALL_HANDLES, search_type);
+            unsafe {
+                ptr::write(no_handles, 3);
+                ptr::write(buffer, ptr::addr_of_mut!(HANDLES) as *mut efi::Handle);
+            }
+            efi::Status::SUCCESS
+        }
+
+        extern "efiapi" fn efi_connect_controller(
+            controller_handle: efi::Handle,
+            driver_image_handle: *mut efi::Handle,
+            remaining_device_path: *mut efi::protocols::device_path::Protocol,
+            recursive: efi::Boolean,
+        ) -> efi::Status {
+            assert_eq!(ptr::null_mut(), driver_image_handle);
+            assert_eq!(ptr::null_mut(), remaining_device_path);
+            assert_eq!(efi::Boolean::TRUE, recursive);
+            CONNECT_COUNT.fetch_add(1, Ordering::Relaxed);
+            match controller_handle as usize {
+                1 => efi::Status::NOT_FOUND,
+                2 => efi::Status::DEVICE_ERROR,
+                _ => efi::Status::SUCCESS,
+            }
+        }
+
+        extern "efiapi" fn efi_free_pool(_buffer: *mut c_void) -> efi::Status {
+            efi::Status::SUCCESS
+        }
+
+        assert_eq!(Err(ConnectError::NoDriverConnected), boot_services.connect_drivers(1 as efi::Handle, true));
+        assert_eq!(
+            Err(ConnectError::Failed(efi::Status::DEVICE_ERROR)),
+            boot_services.connect_drivers(2 as efi::Handle, true)
+        );
+        CONNECT_COUNT.store(0, Ordering::Relaxed);
+        assert_eq!(Err(efi::Status::DEVICE_ERROR.into()), boot_services.connect_all());
+        assert_eq!(3, CONNECT_COUNT.load(Ordering::Relaxed));
+    }
+
     #[test]
     fn test_free_pool() {
         let boot_services = boot_services!(free_pool = efi_free_pool);
@@ -1604,6 +2042,123 @@ mod test {
 
         // negative test
         let status = boot_services.free_pool(ptr::null_mut());
-        assert_eq!(status, Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(status, Err(efi::Status::INVALID_PARAMETER.into()));
+    }
+
+    #[test]
+    fn test_install_configuration_table() {
+        let boot_services = boot_services!(install_configuration_table = efi_install_configuration_table);
+
+        const GUID: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6, 7, 8, 9, 10, 11]);
+
+        extern "efiapi" fn efi_install_configuration_table(guid: *mut efi::Guid, table: *mut c_void) -> efi::Status {
+            assert_eq!(GUID, unsafe { *guid });
+            if table.is_null() {
+                efi::Status::NOT_FOUND
+            } else {
+                assert_eq!(42, unsafe { *(table as *mut u32) });
+                efi::Status::SUCCESS
+            }
+        }
+
+        assert_eq!(Ok(()), boot_services.install_configuration_table(&GUID, Box::leak(Box::new(42_u32))));
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), boot_services.uninstall_configuration_table(&GUID));
+    }
+
+    #[test]
+    fn test_stall() {
+        let boot_services = boot_services!(stall = efi_stall);
+
+        extern "efiapi" fn efi_stall(microseconds: usize) -> efi::Status {
+            match microseconds {
+                0 => efi::Status::INVALID_PARAMETER,
+                _ => efi::Status::SUCCESS,
+            }
+        }
+
+        assert_eq!(Ok(()), boot_services.stall(1000));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), boot_services.stall(0));
+    }
+
+    #[test]
+    fn test_get_next_monotonic_count() {
+        let boot_services = boot_services!(get_next_monotonic_count = efi_get_next_monotonic_count);
+
+        extern "efiapi" fn efi_get_next_monotonic_count(count: *mut u64) -> efi::Status {
+            static COUNT: AtomicU64 = AtomicU64::new(0);
+            unsafe { *count = COUNT.fetch_add(1, Ordering::Relaxed) };
+            efi::Status::SUCCESS
+        }
+
+        assert_eq!(Ok(0), boot_services.get_next_monotonic_count());
+        assert_eq!(Ok(1), boot_services.get_next_monotonic_count());
+    }
+
+    static mut EXIT_DATA: [u16; 8] = [0; 8];
+    static EXIT_DATA_FREED: AtomicUsize = AtomicUsize::new(0);
+
+    extern "efiapi" fn efi_allocate_exit_data(
+        pool_type: efi::MemoryType,
+        size: usize,
+        buffer: *mut *mut c_void,
+    ) -> efi::Status {
+        assert_eq!(efi::BOOT_SERVICES_DATA, pool_type);
+        assert_eq!(10, size);
+        unsafe { *buffer = ptr::addr_of_mut!(EXIT_DATA) as *mut c_void };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn efi_free_exit_data(buffer: *mut c_void) -> efi::Status {
+        assert_eq!(unsafe { ptr::addr_of_mut!(EXIT_DATA) } as *mut c_void, buffer);
+        EXIT_DATA_FREED.fetch_add(1, Ordering::SeqCst);
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    #[should_panic = "Exit returned with status"]
+    fn test_exit() {
+        let boot_services =
+            boot_services!(allocate_pool = efi_allocate_exit_data, exit = efi_exit, free_pool = efi_free_exit_data);
+
+        extern "efiapi" fn efi_exit(
+            image_handle: efi::Handle,
+            exit_status: efi::Status,
+            exit_data_size: usize,
+            exit_data: *mut efi::Char16,
+        ) -> efi::Status {
+            assert_eq!(1_usize as efi::Handle, image_handle);
+            assert_eq!(efi::Status::ABORTED, exit_status);
+            assert_eq!(10, exit_data_size);
+            let exit_data = unsafe { core::slice::from_raw_parts(exit_data, 5) };
+            assert_eq!([b'O' as u16, b'o' as u16, b'p' as u16, b's' as u16, 0], exit_data);
+            assert_eq!(0, EXIT_DATA_FREED.load(Ordering::SeqCst));
+            efi::Status::INVALID_PARAMETER
+        }
+
+        let panic = std::panic::catch_unwind(|| {
+            boot_services.exit(1_usize as efi::Handle, efi::Status::ABORTED, Some("Oops"));
+        });
+        assert_eq!(1, EXIT_DATA_FREED.load(Ordering::SeqCst), "The exit data must be freed before panicking.");
+        std::panic::resume_unwind(panic.unwrap_err());
+    }
+
+    #[test]
+    #[should_panic = "Exit returned with status"]
+    fn test_exit_without_exit_data() {
+        let boot_services = boot_services!(exit = efi_exit);
+
+        extern "efiapi" fn efi_exit(
+            _image_handle: efi::Handle,
+            exit_status: efi::Status,
+            exit_data_size: usize,
+            exit_data: *mut efi::Char16,
+        ) -> efi::Status {
+            assert_eq!(efi::Status::SUCCESS, exit_status);
+            assert_eq!(0, exit_data_size);
+            assert!(exit_data.is_null());
+            efi::Status::UNSUPPORTED
+        }
+
+        boot_services.exit(1_usize as efi::Handle, efi::Status::SUCCESS, None);
     }
 }
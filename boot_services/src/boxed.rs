@@ -20,7 +20,9 @@ impl<'a, T, B: BootServices> BootServicesBox<'a, T, B> {
         unsafe { ptr::write(ptr, value) };
         Self { boot_services, ptr }
     }
+}
 
+impl<'a, T: ?Sized, B: BootServices> BootServicesBox<'a, T, B> {
     pub unsafe fn from_raw(ptr: *mut T, boot_services: &'a B) -> Self {
         Self { boot_services, ptr }
     }
@@ -0,0 +1,458 @@
+//! This module defined every helper related to the configuration tables of the system table.
+//!
+//! The well-known tables are found by their marker type, which validates them before giving a typed reference:
+//!
+//! ```ignore
+//! let rsdp = configuration_table::find::<Acpi20>(entry_point::system_table())?;
+//! ```
+
+use core::{ffi::c_void, fmt, mem, ptr, slice};
+
+use r_efi::efi;
+use status::EfiError;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+/// Read access to the configuration tables of the EFI System Table.
+///
+/// The tables are read from the system table on every call since installing or removing a table with
+/// [`BootServices::install_configuration_table`](crate::BootServices::install_configuration_table) can reallocate them.
+#[derive(Clone, Copy)]
+pub struct ConfigurationTables<'a>(&'a efi::SystemTable);
+
+impl<'a> ConfigurationTables<'a> {
+    /// Create a view over the configuration tables of the given system table.
+    pub const fn new(system_table: &'a efi::SystemTable) -> Self {
+        Self(system_table)
+    }
+
+    /// Returns an iterator over copies of the configuration table entries.
+    ///
+    /// Every entry is read from the system table when the iterator reaches it, so the iterator stays valid when the
+    /// tables are reallocated while iterating, unlike a slice of them would.
+    pub fn iter(&self) -> Iter<'a> {
+        Iter { system_table: self.0, index: 0 }
+    }
+
+    /// Returns a copy of the entry at `index`, `None` past the last entry.
+    fn entry(system_table: &efi::SystemTable, index: usize) -> Option<efi::ConfigurationTable> {
+        //SAFETY: The fields are read from a valid reference, volatile as the firmware updates them behind it.
+        let (tables, count) = unsafe {
+            (
+                ptr::read_volatile(ptr::addr_of!(system_table.configuration_table)),
+                ptr::read_volatile(ptr::addr_of!(system_table.number_of_table_entries)),
+            )
+        };
+        if tables.is_null() || index >= count {
+            return None;
+        }
+        //SAFETY: The system table holds number_of_table_entries entries at configuration_table.
+        Some(unsafe { ptr::read(tables.add(index)) })
+    }
+
+    /// Returns the table associated with the given guid, if installed.
+    pub fn find_table(&self, guid: &efi::Guid) -> Option<*mut c_void> {
+        self.iter().find(|entry| entry.vendor_guid == *guid).map(|entry| entry.vendor_table)
+    }
+
+    /// Returns the well-known table `T`, once validated.
+    ///
+    /// # Errors
+    ///
+    /// * `NOT_FOUND` if the table is not installed.
+    /// * `INVALID_PARAMETER` if the table is not aligned for its type.
+    /// * The error of [`KnownTable::validate`] if the table is invalid.
+    pub fn find<T: KnownTable>(&self) -> Result<&'a T::Table, EfiError> {
+        let table = self.find_table(&T::GUID).filter(|table| !table.is_null()).ok_or(efi::Status::NOT_FOUND)?;
+        if table.align_offset(mem::align_of::<T::Table>()) != 0 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        //SAFETY: The implementation of KnownTable guarantees that a table installed with its GUID starts with it.
+        let table = unsafe { &*(table as *const T::Table) };
+        T::validate(table).map(|()| table)
+    }
+}
+
+/// Returns the well-known table `T` of the system table, once validated, see [`ConfigurationTables::find`].
+pub fn find<T: KnownTable>(system_table: &efi::SystemTable) -> Result<&T::Table, EfiError> {
+    ConfigurationTables::new(system_table).find::<T>()
+}
+
+/// Marker type of a well-known configuration table.
+///
+/// # Safety
+///
+/// The tables installed with `GUID` must start with `Table`, and `validate` must only read the table within the
+/// sizes it declares.
+pub unsafe trait KnownTable {
+    const GUID: efi::Guid;
+    type Table: 'static;
+
+    /// Checks the content of the table found in the configuration tables.
+    fn validate(table: &Self::Table) -> Result<(), EfiError>;
+}
+
+/// Returns true if the bytes sum to zero, the checksum of ACPI and SMBIOS structures.
+fn is_checksum_valid(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+/// The `size` bytes from the start of a table.
+///
+/// # Safety
+///
+/// The table must be at least `size` bytes long.
+unsafe fn table_bytes<T>(table: &T, size: usize) -> &[u8] {
+    slice::from_raw_parts(table as *const T as *const u8, size)
+}
+
+/// ACPI 2.0 and later Root System Description Pointer, `EFI_ACPI_2_0_ROOT_SYSTEM_DESCRIPTION_POINTER`.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct Rsdp {
+    pub signature: [u8; 8],
+    /// Checksum of the first 20 bytes, the ACPI 1.0 structure.
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub revision: u8,
+    pub rsdt_address: u32,
+    /// Size of the whole structure, in bytes.
+    pub length: u32,
+    pub xsdt_address: u64,
+    /// Checksum of the whole structure.
+    pub extended_checksum: u8,
+    pub reserved: [u8; 3],
+}
+
+impl Rsdp {
+    pub const SIGNATURE: [u8; 8] = *b"RSD PTR ";
+    /// Size of the ACPI 1.0 structure, covered by `checksum`.
+    pub const ACPI_1_0_LENGTH: usize = 20;
+}
+
+/// ACPI 2.0 and later table, its Root System Description Pointer.
+pub struct Acpi20;
+
+unsafe impl KnownTable for Acpi20 {
+    const GUID: efi::Guid = efi::ACPI_20_TABLE_GUID;
+    type Table = Rsdp;
+
+    fn validate(rsdp: &Rsdp) -> Result<(), EfiError> {
+        let length = rsdp.length as usize;
+        if rsdp.signature != Rsdp::SIGNATURE || rsdp.revision < 2 || length < mem::size_of::<Rsdp>() {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        //SAFETY: The structure is as long as it declares.
+        let valid = is_checksum_valid(&rsdp.as_bytes()[..Rsdp::ACPI_1_0_LENGTH])
+            && is_checksum_valid(unsafe { table_bytes(rsdp, length) });
+        if valid {
+            Ok(())
+        } else {
+            Err(efi::Status::CRC_ERROR.into())
+        }
+    }
+}
+
+/// SMBIOS 2.x entry point structure.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct SmbiosEntryPoint {
+    pub anchor: [u8; 4],
+    /// Checksum of the whole structure.
+    pub checksum: u8,
+    /// Size of the structure, in bytes.
+    pub length: u8,
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub max_structure_size: u16,
+    pub revision: u8,
+    pub formatted_area: [u8; 5],
+    pub intermediate_anchor: [u8; 5],
+    /// Checksum of the intermediate structure, from `intermediate_anchor`.
+    pub intermediate_checksum: u8,
+    pub table_length: u16,
+    pub table_address: u32,
+    pub number_of_structures: u16,
+    pub bcd_revision: u8,
+}
+
+impl SmbiosEntryPoint {
+    pub const ANCHOR: [u8; 4] = *b"_SM_";
+    pub const INTERMEDIATE_ANCHOR: [u8; 5] = *b"_DMI_";
+    /// Offset of the intermediate structure.
+    const INTERMEDIATE_OFFSET: usize = 0x10;
+}
+
+/// SMBIOS 2.x table, its entry point structure.
+pub struct Smbios;
+
+unsafe impl KnownTable for Smbios {
+    const GUID: efi::Guid = efi::SMBIOS_TABLE_GUID;
+    type Table = SmbiosEntryPoint;
+
+    fn validate(entry_point: &SmbiosEntryPoint) -> Result<(), EfiError> {
+        if entry_point.anchor != SmbiosEntryPoint::ANCHOR
+            || entry_point.intermediate_anchor != SmbiosEntryPoint::INTERMEDIATE_ANCHOR
+        {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        // SMBIOS 2.1 declares 0x1E bytes for its 0x1F bytes structure.
+        let length = (entry_point.length as usize).max(mem::size_of::<SmbiosEntryPoint>());
+        //SAFETY: The structure is at least as long as its type and its declared length.
+        let bytes = unsafe { table_bytes(entry_point, length) };
+        let intermediate = &bytes[SmbiosEntryPoint::INTERMEDIATE_OFFSET..mem::size_of::<SmbiosEntryPoint>()];
+        if is_checksum_valid(&bytes[..entry_point.length as usize]) && is_checksum_valid(intermediate) {
+            Ok(())
+        } else {
+            Err(efi::Status::CRC_ERROR.into())
+        }
+    }
+}
+
+/// SMBIOS 3.x entry point structure.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct Smbios3EntryPoint {
+    pub anchor: [u8; 5],
+    /// Checksum of the whole structure.
+    pub checksum: u8,
+    /// Size of the structure, in bytes.
+    pub length: u8,
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub docrev: u8,
+    pub revision: u8,
+    pub reserved: u8,
+    pub table_max_size: u32,
+    pub table_address: u64,
+}
+
+impl Smbios3EntryPoint {
+    pub const ANCHOR: [u8; 5] = *b"_SM3_";
+}
+
+/// SMBIOS 3.x table, its entry point structure.
+pub struct Smbios3;
+
+unsafe impl KnownTable for Smbios3 {
+    const GUID: efi::Guid = efi::SMBIOS3_TABLE_GUID;
+    type Table = Smbios3EntryPoint;
+
+    fn validate(entry_point: &Smbios3EntryPoint) -> Result<(), EfiError> {
+        let length = entry_point.length as usize;
+        if entry_point.anchor != Smbios3EntryPoint::ANCHOR || length < mem::size_of::<Smbios3EntryPoint>() {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        //SAFETY: The structure is as long as it declares.
+        if is_checksum_valid(unsafe { table_bytes(entry_point, length) }) {
+            Ok(())
+        } else {
+            Err(efi::Status::CRC_ERROR.into())
+        }
+    }
+}
+
+/// Header of a flattened device tree, its fields are big-endian.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+pub struct FdtHeader {
+    pub magic: u32,
+    /// Size of the whole device tree, in bytes.
+    pub total_size: u32,
+    pub off_dt_struct: u32,
+    pub off_dt_strings: u32,
+    pub off_mem_rsvmap: u32,
+    pub version: u32,
+    pub last_comp_version: u32,
+    pub boot_cpuid_phys: u32,
+    pub size_dt_strings: u32,
+    pub size_dt_struct: u32,
+}
+
+impl FdtHeader {
+    pub const MAGIC: u32 = 0xd00dfeed;
+
+    /// Size of the whole device tree, in bytes.
+    pub fn size(&self) -> usize {
+        u32::from_be(self.total_size) as usize
+    }
+}
+
+/// Flattened device tree, its header.
+pub struct DeviceTree;
+
+unsafe impl KnownTable for DeviceTree {
+    const GUID: efi::Guid = efi::DTB_TABLE_GUID;
+    type Table = FdtHeader;
+
+    fn validate(header: &FdtHeader) -> Result<(), EfiError> {
+        if u32::from_be(header.magic) != FdtHeader::MAGIC || header.size() < mem::size_of::<FdtHeader>() {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        Ok(())
+    }
+}
+
+/// Memory Attributes table, its header followed by the descriptors of the runtime memory.
+pub struct MemoryAttributes;
+
+unsafe impl KnownTable for MemoryAttributes {
+    const GUID: efi::Guid = efi::MEMORY_ATTRIBUTES_TABLE_GUID;
+    type Table = efi::MemoryAttributesTable;
+
+    fn validate(table: &efi::MemoryAttributesTable) -> Result<(), EfiError> {
+        if table.version < efi::MEMORY_ATTRIBUTES_TABLE_VERSION
+            || (table.descriptor_size as usize) < mem::size_of::<efi::MemoryDescriptor>()
+        {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        Ok(())
+    }
+}
+
+/// Runtime Properties table, the runtime services supported after ExitBootServices().
+pub struct RtProperties;
+
+unsafe impl KnownTable for RtProperties {
+    const GUID: efi::Guid = efi::RT_PROPERTIES_TABLE_GUID;
+    type Table = efi::RtPropertiesTable;
+
+    fn validate(table: &efi::RtPropertiesTable) -> Result<(), EfiError> {
+        if table.version < efi::RT_PROPERTIES_TABLE_VERSION
+            || (table.length as usize) < mem::size_of::<efi::RtPropertiesTable>()
+        {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over copies of the configuration table entries, see [`ConfigurationTables::iter`].
+#[derive(Clone)]
+pub struct Iter<'a> {
+    system_table: &'a efi::SystemTable,
+    index: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = efi::ConfigurationTable;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = ConfigurationTables::entry(self.system_table, self.index)?;
+        self.index += 1;
+        Some(entry)
+    }
+}
+
+impl<'a> IntoIterator for ConfigurationTables<'a> {
+    type Item = efi::ConfigurationTable;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for ConfigurationTables<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::mem::MaybeUninit;
+
+    const GUID_1: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+    const GUID_2: efi::Guid = efi::Guid::from_fields(2, 0, 0, 0, 0, &[0; 6]);
+
+    fn system_table(tables: &mut [efi::ConfigurationTable]) -> efi::SystemTable {
+        //SAFETY: Only the configuration table fields are read.
+        let mut system_table = unsafe { MaybeUninit::<efi::SystemTable>::zeroed().assume_init() };
+        system_table.number_of_table_entries = tables.len();
+        system_table.configuration_table = tables.as_mut_ptr();
+        system_table
+    }
+
+    #[test]
+    fn test_find_table() {
+        let mut tables = [
+            efi::ConfigurationTable { vendor_guid: GUID_1, vendor_table: 0x1000 as *mut c_void },
+            efi::ConfigurationTable { vendor_guid: GUID_2, vendor_table: 0x2000 as *mut c_void },
+        ];
+        let system_table = system_table(&mut tables);
+        let configuration_tables = ConfigurationTables::new(&system_table);
+
+        assert_eq!(Some(0x1000 as *mut c_void), configuration_tables.find_table(&GUID_1));
+        assert_eq!(Some(0x2000 as *mut c_void), configuration_tables.find_table(&GUID_2));
+        assert_eq!(None, configuration_tables.find_table(&efi::Guid::from_fields(3, 0, 0, 0, 0, &[0; 6])));
+        assert_eq!(vec![GUID_1, GUID_2], configuration_tables.into_iter().map(|t| t.vendor_guid).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_no_tables() {
+        let mut system_table = system_table(&mut []);
+        system_table.configuration_table = ptr::null_mut();
+        let configuration_tables = ConfigurationTables::new(&system_table);
+        assert_eq!(0, configuration_tables.iter().count());
+        assert_eq!(None, configuration_tables.find_table(&GUID_1));
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), configuration_tables.find::<Acpi20>().map(|_| ()));
+    }
+
+    fn rsdp() -> Rsdp {
+        let mut rsdp = Rsdp {
+            signature: Rsdp::SIGNATURE,
+            checksum: 0,
+            oem_id: *b"OEMID ",
+            revision: 2,
+            rsdt_address: 0,
+            length: mem::size_of::<Rsdp>() as u32,
+            xsdt_address: 0x1000,
+            extended_checksum: 0,
+            reserved: [0; 3],
+        };
+        let sum = |bytes: &[u8]| bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        rsdp.checksum = 0u8.wrapping_sub(sum(&rsdp.as_bytes()[..Rsdp::ACPI_1_0_LENGTH]));
+        rsdp.extended_checksum = 0u8.wrapping_sub(sum(rsdp.as_bytes()));
+        rsdp
+    }
+
+    #[test]
+    fn test_find_acpi_20() {
+        let rsdp = Box::leak(Box::new(rsdp()));
+        let mut tables =
+            [efi::ConfigurationTable { vendor_guid: Acpi20::GUID, vendor_table: rsdp as *mut Rsdp as *mut c_void }];
+        let system_table = system_table(&mut tables);
+        let xsdt_address = find::<Acpi20>(&system_table).unwrap().xsdt_address;
+        assert_eq!(0x1000, xsdt_address);
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), find::<Smbios3>(&system_table).map(|_| ()));
+
+        rsdp.xsdt_address = 0x2000;
+        assert_eq!(Err(efi::Status::CRC_ERROR.into()), find::<Acpi20>(&system_table).map(|_| ()));
+        rsdp.revision = 0;
+        assert_eq!(Err(efi::Status::INCOMPATIBLE_VERSION.into()), find::<Acpi20>(&system_table).map(|_| ()));
+    }
+
+    #[test]
+    fn test_find_rt_properties() {
+        let properties = Box::leak(Box::new(efi::RtPropertiesTable {
+            version: efi::RT_PROPERTIES_TABLE_VERSION,
+            length: mem::size_of::<efi::RtPropertiesTable>() as u16,
+            runtime_services_supported: efi::RT_SUPPORTED_GET_TIME,
+        }));
+        let mut tables = [efi::ConfigurationTable {
+            vendor_guid: RtProperties::GUID,
+            vendor_table: properties as *mut efi::RtPropertiesTable as *mut c_void,
+        }];
+        let aligned = system_table(&mut tables);
+        assert_eq!(efi::RT_SUPPORTED_GET_TIME, find::<RtProperties>(&aligned).unwrap().runtime_services_supported);
+
+        properties.version = 0;
+        assert_eq!(Err(efi::Status::INCOMPATIBLE_VERSION.into()), find::<RtProperties>(&aligned).map(|_| ()));
+        // The tables must be aligned for their type.
+        tables[0].vendor_table = (properties as *mut efi::RtPropertiesTable as usize + 1) as *mut c_void;
+        let unaligned = system_table(&mut tables);
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), find::<RtProperties>(&unaligned).map(|_| ()));
+    }
+}
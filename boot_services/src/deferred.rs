@@ -0,0 +1,245 @@
+//! Deferred work, run when the boot reaches a phase signaled by an event group.
+//!
+//! [`DeferredWork`] queues closures for the `EndOfDxe`, `ReadyToBoot`, `AfterReadyToBoot` and `ExitBootServices`
+//! event groups, with a single event per phase instead of one per module. The closures of a phase run once, at the
+//! first signal of its group, by priority and then in the order they were queued:
+//!
+//! ```ignore
+//! let work: &'static _ = Box::leak(Box::new(DeferredWork::new(&BOOT_SERVICES)));
+//! work.defer(Phase::EndOfDxe, || lock_flash_regions())?;
+//! work.defer_with_priority(Phase::ReadyToBoot, 10, || publish_boot_tables())?;
+//! work.defer(Phase::ReadyToBoot, || log::info!("ready to boot"))?;
+//! ```
+//!
+//! The boot manager signals the boot phases with [`signal_ready_to_boot`].
+//!
+//! [UEFI Spec Documentation: 7.1.2. EFI_BOOT_SERVICES.CreateEventEx()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-createeventex)
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    fmt, mem,
+};
+
+use r_efi::efi;
+use status::EfiError;
+
+use crate::{event::EventType, tpl::Tpl, BootServices};
+
+/// The group signaled by the DXE core once the platform drivers from the manufacturer are dispatched.
+pub const END_OF_DXE_EVENT_GROUP_GUID: efi::Guid =
+    efi::Guid::from_fields(0x02ce967a, 0xdd7e, 0x4ffc, 0x9e, 0xe7, &[0x81, 0x0c, 0xf0, 0x47, 0x08, 0x80]);
+
+/// Priority of the work queued without one.
+pub const DEFAULT_PRIORITY: u32 = 100;
+
+/// A phase of the boot, signaled by an event group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// The third party code may run from now on.
+    EndOfDxe,
+    /// The boot manager is about to load and start a boot option.
+    ReadyToBoot,
+    /// Right after `ReadyToBoot`, once its notifications ran.
+    AfterReadyToBoot,
+    /// The loader exits the boot services, the work must not use the boot services nor allocate memory.
+    ExitBootServices,
+}
+
+impl Phase {
+    const ALL: [Phase; 4] = [Phase::EndOfDxe, Phase::ReadyToBoot, Phase::AfterReadyToBoot, Phase::ExitBootServices];
+
+    /// The event group signaled at the phase.
+    pub fn event_group(self) -> &'static efi::Guid {
+        match self {
+            Phase::EndOfDxe => &END_OF_DXE_EVENT_GROUP_GUID,
+            Phase::ReadyToBoot => &efi::EVENT_GROUP_READY_TO_BOOT,
+            Phase::AfterReadyToBoot => &efi::EVENT_GROUP_AFTER_READY_TO_BOOT,
+            Phase::ExitBootServices => &efi::EVENT_GROUP_EXIT_BOOT_SERVICES,
+        }
+    }
+}
+
+struct Work {
+    priority: u32,
+    run: Box<dyn FnOnce()>,
+}
+
+/// The work of a phase, the context of the notification of its event.
+struct PhaseQueue<B: BootServices + 'static> {
+    boot_services: &'static B,
+    phase: Phase,
+    event: Cell<Option<efi::Event>>,
+    started: Cell<bool>,
+    work: RefCell<Vec<Work>>,
+}
+
+/// Queues of closures run at the phases of the boot.
+///
+/// The queue is `'static`, as the events of the phases refer to it until they are signaled.
+pub struct DeferredWork<B: BootServices + 'static> {
+    queues: [PhaseQueue<B>; 4],
+}
+
+impl<B: BootServices + 'static> DeferredWork<B> {
+    pub fn new(boot_services: &'static B) -> Self {
+        Self {
+            queues: Phase::ALL.map(|phase| PhaseQueue {
+                boot_services,
+                phase,
+                event: Cell::new(None),
+                started: Cell::new(false),
+                work: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Queues `work` for `phase`, with the default priority.
+    pub fn defer(&'static self, phase: Phase, work: impl FnOnce() + 'static) -> Result<(), EfiError> {
+        self.defer_with_priority(phase, DEFAULT_PRIORITY, work)
+    }
+
+    /// Queues `work` for `phase`, the work of lower priority runs first.
+    ///
+    /// Returns `ALREADY_STARTED` if the phase was already signaled, as the work would never run. Must be called at
+    /// `TPL_CALLBACK` or below.
+    pub fn defer_with_priority(
+        &'static self,
+        phase: Phase,
+        priority: u32,
+        work: impl FnOnce() + 'static,
+    ) -> Result<(), EfiError> {
+        let queue = &self.queues[phase as usize];
+        // The notification of the phase runs at the same TPL, it does not interrupt the queuing.
+        let _tpl = queue.boot_services.raise_tpl_guarded(Tpl::CALLBACK);
+        if queue.started.get() {
+            return Err(efi::Status::ALREADY_STARTED.into());
+        }
+        if queue.event.get().is_none() {
+            //SAFETY: The queue is `'static`, and only used from notifications at the TPL of its own notification.
+            let event = unsafe {
+                queue.boot_services.create_event_ex_unchecked(
+                    EventType::NOTIFY_SIGNAL,
+                    Tpl::CALLBACK,
+                    run_phase::<B>,
+                    queue as *const PhaseQueue<B> as *mut PhaseQueue<B>,
+                    phase.event_group(),
+                )
+            }?;
+            queue.event.set(Some(event));
+        }
+        queue.work.borrow_mut().push(Work { priority, run: Box::new(work) });
+        Ok(())
+    }
+
+    /// The number of closures queued for `phase` and not run yet.
+    pub fn pending(&self, phase: Phase) -> usize {
+        self.queues[phase as usize].work.borrow().len()
+    }
+
+    /// Whether `phase` was signaled.
+    pub fn is_started(&self, phase: Phase) -> bool {
+        self.queues[phase as usize].started.get()
+    }
+}
+
+impl<B: BootServices> fmt::Debug for DeferredWork<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_map();
+        for queue in &self.queues {
+            debug.entry(&queue.phase, &(queue.started.get(), queue.work.borrow().len()));
+        }
+        debug.finish()
+    }
+}
+
+extern "efiapi" fn run_phase<B: BootServices>(event: efi::Event, queue: *mut PhaseQueue<B>) {
+    //SAFETY: The context is the `'static` queue the event was created for.
+    let queue = unsafe { &*queue };
+    // The group may be signaled again, as `ReadyToBoot` is for every boot option, the work runs once.
+    let _ = queue.boot_services.close_event(event);
+    queue.event.set(None);
+    queue.started.set(true);
+    let mut work = mem::take(&mut *queue.work.borrow_mut());
+    // The sort is stable, the work of the same priority keeps its order.
+    work.sort_by_key(|work| work.priority);
+    for work in work {
+        (work.run)();
+    }
+}
+
+extern "efiapi" fn empty_notify(_event: efi::Event, _context: &'static ()) {}
+
+/// Signals an event group, as `EfiEventGroupSignal` does, with an event created and closed for the signal.
+pub fn signal_event_group<B: BootServices>(boot_services: &B, event_group: &'static efi::Guid) -> Result<(), EfiError> {
+    let event =
+        boot_services.create_event_ex(EventType::NOTIFY_SIGNAL, Tpl::CALLBACK, Some(empty_notify), &(), event_group)?;
+    let result = boot_services.signal_event(event);
+    let _ = boot_services.close_event(event);
+    result
+}
+
+/// Signals `ReadyToBoot` and then `AfterReadyToBoot`, as the boot manager does before it starts a boot option.
+pub fn signal_ready_to_boot<B: BootServices>(boot_services: &B) -> Result<(), EfiError> {
+    signal_event_group(boot_services, Phase::ReadyToBoot.event_group())?;
+    signal_event_group(boot_services, Phase::AfterReadyToBoot.event_group())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::InMemoryBootServices;
+    use alloc::rc::Rc;
+
+    fn deferred_work() -> (&'static InMemoryBootServices, &'static DeferredWork<InMemoryBootServices>) {
+        let boot_services = Box::leak(Box::new(InMemoryBootServices::new()));
+        (boot_services, Box::leak(Box::new(DeferredWork::new(boot_services))))
+    }
+
+    #[test]
+    fn test_ordering() {
+        let (boot_services, work) = deferred_work();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let record = |entry: &'static str| {
+            let log = log.clone();
+            move || log.borrow_mut().push(entry)
+        };
+        work.defer(Phase::AfterReadyToBoot, record("after ready to boot")).unwrap();
+        work.defer(Phase::ReadyToBoot, record("first")).unwrap();
+        work.defer_with_priority(Phase::ReadyToBoot, 1, record("urgent")).unwrap();
+        work.defer(Phase::ReadyToBoot, record("second")).unwrap();
+        work.defer(Phase::ExitBootServices, record("exit boot services")).unwrap();
+        assert_eq!(3, work.pending(Phase::ReadyToBoot));
+
+        signal_ready_to_boot(boot_services).unwrap();
+        assert_eq!(vec!["urgent", "first", "second", "after ready to boot"], *log.borrow());
+        assert!(work.is_started(Phase::ReadyToBoot) && !work.is_started(Phase::ExitBootServices));
+        assert_eq!(0, work.pending(Phase::ReadyToBoot));
+
+        // The work runs once, and cannot be queued for a phase that started.
+        signal_ready_to_boot(boot_services).unwrap();
+        assert_eq!(4, log.borrow().len());
+        assert_eq!(efi::Status::ALREADY_STARTED, work.defer(Phase::ReadyToBoot, || ()).unwrap_err());
+
+        signal_event_group(boot_services, &efi::EVENT_GROUP_EXIT_BOOT_SERVICES).unwrap();
+        assert_eq!(Some(&"exit boot services"), log.borrow().last());
+    }
+
+    #[test]
+    fn test_defer_from_work() {
+        let (boot_services, work) = deferred_work();
+        let ran = Rc::new(Cell::new(false));
+        let inner = ran.clone();
+        work.defer(Phase::EndOfDxe, move || {
+            work.defer(Phase::ReadyToBoot, move || inner.set(true)).unwrap();
+            assert_eq!(efi::Status::ALREADY_STARTED, work.defer(Phase::EndOfDxe, || ()).unwrap_err());
+        })
+        .unwrap();
+        assert_eq!(0, work.pending(Phase::ReadyToBoot));
+
+        signal_event_group(boot_services, &END_OF_DXE_EVENT_GROUP_GUID).unwrap();
+        assert_eq!(1, work.pending(Phase::ReadyToBoot));
+        signal_ready_to_boot(boot_services).unwrap();
+        assert!(ran.get());
+    }
+}
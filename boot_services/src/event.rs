@@ -8,7 +8,7 @@ use r_efi::efi;
 pub type EventNotifyCallback<T> = extern "efiapi" fn(efi::Event, T);
 
 /// The type of time that is specified in TriggerTime. See the timer delay types in “Related Definitions.”
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum EventTimerType {
     /// The event’s timer setting is to be cancelled and no timer trigger is to be set.
@@ -38,6 +38,9 @@ impl Into<u32> for EventTimerType {
 pub struct EventType(u32);
 
 impl EventType {
+    /// The event has no notification function, it is only signaled and checked with [`BootServices::check_event`](super::BootServices::check_event).
+    pub const NONE: EventType = EventType(0);
+
     /// The event is a timer event and may be passed to [`BootServices::set_timer`](super::BootServices::set_timer).
     /// Note that timers only function during boot services time.
     pub const TIMER: EventType = EventType(efi::EVT_TIMER);
@@ -88,3 +91,9 @@ impl Into<u32> for EventType {
         self.0
     }
 }
+
+impl From<u32> for EventType {
+    fn from(value: u32) -> Self {
+        EventType(value)
+    }
+}
@@ -0,0 +1,373 @@
+//! Memory Attributes table, the protections of the runtime memory.
+//!
+//! The table splits the runtime memory of the memory map into its code and data regions, with the protections the OS
+//! applies when it maps them. A loader or an audit tool can check the table against the memory map:
+//!
+//! ```ignore
+//! let table = MemoryAttributesTable::from_system_table(entry_point::system_table())?;
+//! let memory_map = boot_services.get_memory_map().map_err(|(status, _)| status)?;
+//! for violation in table.violations(&memory_map.descriptors) {
+//!     log::warn!("Memory Attributes table: {violation:?}");
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 4.6.4. EFI_MEMORY_ATTRIBUTES_TABLE](https://uefi.org/specs/UEFI/2.10/04_EFI_System_Table.html#efi-memory-attributes-table)
+
+use alloc::vec::Vec;
+use core::mem;
+
+use r_efi::efi;
+use status::EfiError;
+
+use crate::{
+    allocation::{MemoryAttribute, MemoryDescriptor, MemoryType},
+    configuration_table::{self, KnownTable, MemoryAttributes},
+};
+
+/// Size of the pages of the descriptors.
+const PAGE_SIZE: u64 = 0x1000;
+
+/// The runtime code is compiled with forward control flow guards, `EFI_MEMORY_ATTRIBUTES_FLAGS_RT_FORWARD_CONTROL_FLOW_GUARD`.
+pub const FLAGS_RT_FORWARD_CONTROL_FLOW_GUARD: u32 = 0x1;
+
+/// A runtime region of the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeRegion {
+    pub memory_type: MemoryType,
+    pub physical_start: u64,
+    pub virtual_start: u64,
+    pub number_of_pages: u64,
+    pub attribute: MemoryAttribute,
+}
+
+impl RuntimeRegion {
+    /// Returns true for a runtime code region, false for a runtime data region.
+    pub fn is_code(&self) -> bool {
+        self.memory_type == MemoryType::RUNTIME_SERVICES_CODE
+    }
+
+    /// Returns true if the region is mapped read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.attribute.contains(MemoryAttribute::RO)
+    }
+
+    /// Returns true if the region is mapped non-executable.
+    pub fn is_execute_protected(&self) -> bool {
+        self.attribute.contains(MemoryAttribute::XP)
+    }
+
+    /// Size of the region, in bytes.
+    pub fn size(&self) -> u64 {
+        self.number_of_pages.saturating_mul(PAGE_SIZE)
+    }
+
+    /// Physical address of the end of the region, exclusive.
+    pub fn end(&self) -> u64 {
+        self.physical_start.saturating_add(self.size())
+    }
+}
+
+/// A mismatch between the table and the UEFI memory map, or within the table, found by
+/// [`MemoryAttributesTable::violations`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The region is neither runtime code nor runtime data.
+    InvalidType(RuntimeRegion),
+    /// The region is both writable and executable.
+    WritableExecutable(RuntimeRegion),
+    /// The region starts before the end of the previous one, the regions must be sorted and disjoint.
+    Overlap(RuntimeRegion),
+    /// The region is not within a runtime descriptor of the memory map.
+    NotInMemoryMap(RuntimeRegion),
+    /// A range of a runtime descriptor of the memory map is not described by the table.
+    Uncovered { memory_type: MemoryType, physical_start: u64, end: u64 },
+}
+
+/// Memory Attributes table, its header and its descriptors.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryAttributesTable<'a> {
+    version: u32,
+    flags: u32,
+    descriptor_size: usize,
+    descriptors: &'a [u8],
+}
+
+impl MemoryAttributesTable<'static> {
+    /// The table installed in the configuration tables.
+    pub fn from_system_table(system_table: &efi::SystemTable) -> Result<Self, EfiError> {
+        let table = configuration_table::find::<MemoryAttributes>(system_table)? as *const efi::MemoryAttributesTable;
+        //SAFETY: The installed table is followed by its descriptors and valid for the life of the firmware.
+        unsafe { Self::from_table(&*table) }
+    }
+}
+
+impl<'a> MemoryAttributesTable<'a> {
+    /// The table of `table`, the header of a table followed by its descriptors.
+    ///
+    /// # Safety
+    ///
+    /// `table` must be followed by its `number_of_entries` descriptors of `descriptor_size` bytes.
+    pub unsafe fn from_table(table: &'a efi::MemoryAttributesTable) -> Result<Self, EfiError> {
+        MemoryAttributes::validate(table)?;
+        let size = Self::descriptors_size(table)?;
+        let descriptors = (table as *const efi::MemoryAttributesTable).add(1) as *const u8;
+        Ok(Self::new(table, core::slice::from_raw_parts(descriptors, size)))
+    }
+
+    /// The table of `bytes`, the header of a table followed by its descriptors.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, EfiError> {
+        let header_size = mem::size_of::<efi::MemoryAttributesTable>();
+        if bytes.len() < header_size {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        //SAFETY: The bytes are large enough for the header, which is valid for any content.
+        let table = unsafe { (bytes.as_ptr() as *const efi::MemoryAttributesTable).read_unaligned() };
+        MemoryAttributes::validate(&table)?;
+        let size = Self::descriptors_size(&table)?;
+        let descriptors = bytes.get(header_size..header_size + size).ok_or(efi::Status::INVALID_PARAMETER)?;
+        Ok(Self::new(&table, descriptors))
+    }
+
+    fn new(table: &efi::MemoryAttributesTable, descriptors: &'a [u8]) -> Self {
+        Self {
+            version: table.version,
+            flags: table.reserved,
+            descriptor_size: table.descriptor_size as usize,
+            descriptors,
+        }
+    }
+
+    fn descriptors_size(table: &efi::MemoryAttributesTable) -> Result<usize, EfiError> {
+        (table.number_of_entries as usize)
+            .checked_mul(table.descriptor_size as usize)
+            .ok_or(efi::Status::INVALID_PARAMETER.into())
+    }
+
+    /// Version of the table.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Flags of the table, such as [`FLAGS_RT_FORWARD_CONTROL_FLOW_GUARD`], reserved before version 2.
+    pub fn flags(&self) -> u32 {
+        if self.version >= 2 {
+            self.flags
+        } else {
+            0
+        }
+    }
+
+    /// Iterates over the runtime regions of the table, in the order of the table.
+    pub fn regions(&self) -> impl Iterator<Item = RuntimeRegion> + 'a {
+        self.descriptors.chunks_exact(self.descriptor_size).map(|descriptor| {
+            //SAFETY: The descriptor size is at least the size of a descriptor, which is valid for any content.
+            let descriptor = unsafe { (descriptor.as_ptr() as *const efi::MemoryDescriptor).read_unaligned() };
+            RuntimeRegion {
+                memory_type: descriptor.r#type.into(),
+                physical_start: descriptor.physical_start,
+                virtual_start: descriptor.virtual_start,
+                number_of_pages: descriptor.number_of_pages,
+                attribute: descriptor.attribute.into(),
+            }
+        })
+    }
+
+    /// Checks the table against the runtime descriptors of the UEFI memory map.
+    ///
+    /// The regions must be runtime code or data, sorted, disjoint and never both writable and executable. Each region
+    /// must be within a runtime descriptor of the memory map, and the regions must cover these descriptors entirely.
+    /// An empty list means the table is consistent.
+    pub fn violations(&self, memory_map: &[MemoryDescriptor]) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let is_runtime = |memory_type: MemoryType| {
+            memory_type == MemoryType::RUNTIME_SERVICES_CODE || memory_type == MemoryType::RUNTIME_SERVICES_DATA
+        };
+        let runtime_descriptors = || {
+            memory_map.iter().filter(|descriptor| is_runtime(descriptor.memory_type)).map(|descriptor| {
+                let start = descriptor.physical_start as u64;
+                (
+                    descriptor.memory_type,
+                    start,
+                    start.saturating_add((descriptor.nb_pages as u64).saturating_mul(PAGE_SIZE)),
+                )
+            })
+        };
+
+        let mut previous_end = 0;
+        for region in self.regions() {
+            if !is_runtime(region.memory_type) {
+                violations.push(Violation::InvalidType(region));
+            }
+            if !region.is_read_only() && !region.is_execute_protected() {
+                violations.push(Violation::WritableExecutable(region));
+            }
+            if region.physical_start < previous_end {
+                violations.push(Violation::Overlap(region));
+            }
+            previous_end = previous_end.max(region.end());
+            if !runtime_descriptors().any(|(_, start, end)| start <= region.physical_start && region.end() <= end) {
+                violations.push(Violation::NotInMemoryMap(region));
+            }
+        }
+
+        let mut regions = self.regions().collect::<Vec<_>>();
+        regions.sort_by_key(|region| region.physical_start);
+        for (memory_type, start, end) in runtime_descriptors() {
+            let mut covered = start;
+            for region in regions.iter().filter(|region| region.end() > start && region.physical_start < end) {
+                if region.physical_start > covered {
+                    violations.push(Violation::Uncovered {
+                        memory_type,
+                        physical_start: covered,
+                        end: region.physical_start,
+                    });
+                }
+                covered = covered.max(region.end());
+            }
+            if covered < end {
+                violations.push(Violation::Uncovered { memory_type, physical_start: covered, end });
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use core::{ffi::c_void, mem::MaybeUninit};
+
+    /// Descriptors with the size of a descriptor of a newer version.
+    const DESCRIPTOR_SIZE: usize = mem::size_of::<efi::MemoryDescriptor>() + 8;
+
+    fn table(version: u32, regions: &[(u32, u64, u64, u64)]) -> Vec<u8> {
+        let header = efi::MemoryAttributesTable {
+            version,
+            number_of_entries: regions.len() as u32,
+            descriptor_size: DESCRIPTOR_SIZE as u32,
+            reserved: FLAGS_RT_FORWARD_CONTROL_FLOW_GUARD,
+            entry: [],
+        };
+        let mut bytes = unsafe {
+            core::slice::from_raw_parts(
+                &header as *const efi::MemoryAttributesTable as *const u8,
+                mem::size_of::<efi::MemoryAttributesTable>(),
+            )
+        }
+        .to_vec();
+        for &(r#type, physical_start, number_of_pages, attribute) in regions {
+            let descriptor =
+                efi::MemoryDescriptor { r#type, physical_start, virtual_start: 0, number_of_pages, attribute };
+            bytes.extend_from_slice(unsafe {
+                core::slice::from_raw_parts(
+                    &descriptor as *const efi::MemoryDescriptor as *const u8,
+                    mem::size_of::<efi::MemoryDescriptor>(),
+                )
+            });
+            bytes.extend_from_slice(&[0; DESCRIPTOR_SIZE - mem::size_of::<efi::MemoryDescriptor>()]);
+        }
+        bytes
+    }
+
+    fn descriptor(memory_type: MemoryType, physical_start: usize, nb_pages: usize) -> MemoryDescriptor {
+        MemoryDescriptor {
+            memory_type,
+            physical_start,
+            virtual_start: 0,
+            nb_pages,
+            attribute: MemoryAttribute::RUNTIME | MemoryAttribute::WB,
+        }
+    }
+
+    #[test]
+    fn test_regions() {
+        let bytes = table(
+            2,
+            &[
+                (efi::RUNTIME_SERVICES_CODE, 0x10000, 2, efi::MEMORY_RUNTIME | efi::MEMORY_RO),
+                (efi::RUNTIME_SERVICES_DATA, 0x12000, 1, efi::MEMORY_RUNTIME | efi::MEMORY_XP),
+            ],
+        );
+        let table = MemoryAttributesTable::from_bytes(&bytes).unwrap();
+        assert_eq!(FLAGS_RT_FORWARD_CONTROL_FLOW_GUARD, table.flags());
+        let regions = table.regions().collect::<Vec<_>>();
+        assert_eq!(2, regions.len());
+        assert!(regions[0].is_code() && regions[0].is_read_only() && !regions[0].is_execute_protected());
+        assert_eq!((0x12000, 0x13000), (regions[1].physical_start, regions[1].end()));
+        assert!(!regions[1].is_code() && regions[1].is_execute_protected());
+
+        let memory_map = [
+            descriptor(MemoryType::BOOT_SERVICES_DATA, 0x0, 0x10),
+            descriptor(MemoryType::RUNTIME_SERVICES_CODE, 0x10000, 3),
+        ];
+        assert_eq!(Vec::<Violation>::new(), table.violations(&memory_map));
+
+        assert_eq!(0, MemoryAttributesTable::from_bytes(&self::table(1, &[])).unwrap().flags());
+        assert_eq!(
+            efi::Status::INCOMPATIBLE_VERSION,
+            MemoryAttributesTable::from_bytes(&self::table(0, &[])).unwrap_err()
+        );
+        assert_eq!(
+            efi::Status::INVALID_PARAMETER,
+            MemoryAttributesTable::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_violations() {
+        let rwx = (efi::RUNTIME_SERVICES_DATA, 0x11000, 2, efi::MEMORY_RUNTIME);
+        let bytes = table(
+            1,
+            &[
+                (efi::RUNTIME_SERVICES_CODE, 0x12000, 1, efi::MEMORY_RO),
+                rwx,
+                (efi::BOOT_SERVICES_CODE, 0x20000, 1, efi::MEMORY_XP),
+            ],
+        );
+        let table = MemoryAttributesTable::from_bytes(&bytes).unwrap();
+        let regions = table.regions().collect::<Vec<_>>();
+        let memory_map = [descriptor(MemoryType::RUNTIME_SERVICES_CODE, 0x10000, 4)];
+        assert_eq!(
+            vec![
+                Violation::WritableExecutable(regions[1]),
+                Violation::Overlap(regions[1]),
+                Violation::InvalidType(regions[2]),
+                Violation::NotInMemoryMap(regions[2]),
+                Violation::Uncovered {
+                    memory_type: MemoryType::RUNTIME_SERVICES_CODE,
+                    physical_start: 0x10000,
+                    end: 0x11000
+                },
+                Violation::Uncovered {
+                    memory_type: MemoryType::RUNTIME_SERVICES_CODE,
+                    physical_start: 0x13000,
+                    end: 0x14000
+                },
+            ],
+            table.violations(&memory_map)
+        );
+    }
+
+    #[test]
+    fn test_from_system_table() {
+        let bytes = Box::leak(
+            table(1, &[(efi::RUNTIME_SERVICES_CODE, 0x10000, 1, efi::MEMORY_RO)])
+                .chunks(8)
+                .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+        );
+        let mut tables = [efi::ConfigurationTable {
+            vendor_guid: MemoryAttributes::GUID,
+            vendor_table: bytes.as_mut_ptr() as *mut c_void,
+        }];
+        //SAFETY: Only the configuration table fields are read.
+        let mut system_table = unsafe { MaybeUninit::<efi::SystemTable>::zeroed().assume_init() };
+        system_table.number_of_table_entries = tables.len();
+        system_table.configuration_table = tables.as_mut_ptr();
+
+        let table = MemoryAttributesTable::from_system_table(&system_table).unwrap();
+        assert_eq!(vec![0x10000], table.regions().map(|region| region.physical_start).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,1381 @@
+//! In-memory boot services, for unit tests of drivers and other code using the handle database.
+//!
+//! The handles, protocols, events and allocations live in a database following the semantics of the boot services,
+//! so driver model code runs on the host the same way it does on real firmware:
+//!
+//! ```ignore
+//! let boot_services = InMemoryBootServices::new();
+//! let controller = unsafe { boot_services.install_protocol_interface_unchecked(None, &BLOCK_IO_GUID, block_io) }?;
+//! unsafe { boot_services.install_protocol_interface_unchecked(None, &driver_binding::PROTOCOL_GUID, binding) }?;
+//! boot_services.connect_drivers(controller, true)?;
+//! ```
+//!
+//! Notification functions run when their event is signaled, or once the TPL is restored below their own. Timers run
+//! on a virtual clock which only moves forward with [`InMemoryBootServices::advance_time`], or when waiting for
+//! events, so timer driven code is tested deterministically:
+//!
+//! ```ignore
+//! boot_services.set_timer(event, EventTimerType::Periodic, 10_000)?;
+//! boot_services.advance_time(Duration::from_millis(3));
+//! assert_eq!(3, TICKS.load(Ordering::SeqCst));
+//! ```
+//!
+//! The handles and their protocols are captured with [`InMemoryBootServices::snapshot`], and a database is created
+//! from a [`HandleDatabaseSnapshot`], for instance loaded from a fixture with the `serde` feature.
+//!
+//! The memory map, device path lookups and image services are not emulated and return `UNSUPPORTED`.
+//!
+//! [UEFI Spec Documentation: 7. Services - Boot Services](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html)
+
+use alloc::{
+    alloc::{alloc_zeroed, dealloc, Layout},
+    boxed::Box,
+    collections::VecDeque,
+    vec::Vec,
+};
+use core::{
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    ffi::c_void,
+    mem,
+    ptr::{self, NonNull},
+    time::Duration,
+};
+
+use guid::Guid;
+use r_efi::efi::{self, protocols::driver_binding};
+use status::EfiError;
+
+use crate::{
+    allocation::{AllocType, MemoryMap, MemoryType},
+    boxed::BootServicesBox,
+    event::{EventNotifyCallback, EventTimerType, EventType},
+    protocol_handler::{HandleSearchType, Registration},
+    tpl::Tpl,
+    BootServices,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+/// Alignment of the pool allocations, as guaranteed by the firmware.
+const POOL_ALIGNMENT: usize = 8;
+/// Timer trigger times are in units of 100ns.
+const TIMER_UNIT_NS: u128 = 100;
+
+#[derive(Debug)]
+struct ProtocolEntry {
+    guid: &'static efi::Guid,
+    interface: *mut c_void,
+    open_list: Vec<efi::OpenProtocolInformationEntry>,
+}
+
+#[derive(Debug)]
+struct HandleEntry {
+    id: usize,
+    protocols: Vec<ProtocolEntry>,
+}
+
+#[derive(Debug)]
+struct Event {
+    id: usize,
+    event_type: u32,
+    notify_tpl: Tpl,
+    notify_function: Option<EventNotifyCallback<*mut c_void>>,
+    notify_context: *mut c_void,
+    event_group: Option<efi::Guid>,
+    signaled: bool,
+    /// The order the notification function was queued in, if it is.
+    queued: Option<usize>,
+    timer: Option<Timer>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Timer {
+    /// The virtual time the timer fires at, in 100ns units.
+    trigger_time: u64,
+    period: Option<u64>,
+}
+
+/// A protocol notify registration, with the handles the protocol was installed on since the last lookup.
+#[derive(Debug)]
+struct Notify {
+    id: usize,
+    protocol: efi::Guid,
+    event: usize,
+    handles: VecDeque<usize>,
+}
+
+#[derive(Debug)]
+struct Database {
+    next_id: usize,
+    handles: Vec<HandleEntry>,
+    events: Vec<Event>,
+    notifies: Vec<Notify>,
+    pool: Vec<(usize, Layout)>,
+    pages: Vec<(usize, Layout)>,
+    configuration_tables: Vec<efi::ConfigurationTable>,
+    monotonic_count: u64,
+    /// The virtual time, in 100ns units.
+    time: u64,
+}
+
+impl Database {
+    fn new_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn handle(&self, handle: efi::Handle) -> Result<&HandleEntry, EfiError> {
+        self.handles.iter().find(|entry| entry.id == handle as usize).ok_or(efi::Status::INVALID_PARAMETER.into())
+    }
+
+    fn handle_mut(&mut self, handle: efi::Handle) -> Result<&mut HandleEntry, EfiError> {
+        self.handles.iter_mut().find(|entry| entry.id == handle as usize).ok_or(efi::Status::INVALID_PARAMETER.into())
+    }
+
+    fn protocol_mut(&mut self, handle: efi::Handle, protocol: &efi::Guid) -> Result<&mut ProtocolEntry, EfiError> {
+        self.handle_mut(handle)?
+            .protocols
+            .iter_mut()
+            .find(|entry| entry.guid == protocol)
+            .ok_or(efi::Status::UNSUPPORTED.into())
+    }
+
+    fn event_mut(&mut self, event: efi::Event) -> Result<&mut Event, EfiError> {
+        self.events.iter_mut().find(|entry| entry.id == event as usize).ok_or(efi::Status::INVALID_PARAMETER.into())
+    }
+
+    /// The open entries of every protocol on a handle.
+    fn open_entries(&self, handle: efi::Handle) -> impl Iterator<Item = &efi::OpenProtocolInformationEntry> {
+        self.handle(handle).into_iter().flat_map(|entry| entry.protocols.iter()).flat_map(|p| p.open_list.iter())
+    }
+
+    /// Queues the handle for the registrations of the protocol, returning the events to signal.
+    fn queue_notifies(&mut self, protocol: &efi::Guid, handle: usize) -> Vec<usize> {
+        self.notifies
+            .iter_mut()
+            .filter(|notify| notify.protocol == *protocol)
+            .map(|notify| {
+                notify.handles.push_back(handle);
+                notify.event
+            })
+            .collect()
+    }
+}
+
+/// A capture of the handles of a handle database, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HandleDatabaseSnapshot {
+    pub handles: Vec<HandleSnapshot>,
+}
+
+/// A handle of a [`HandleDatabaseSnapshot`], with the protocols in installation order.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HandleSnapshot {
+    pub protocols: Vec<Guid>,
+}
+
+/// Boot services backed by an in-memory handle database.
+///
+/// Handles, events and registrations are opaque identifiers, allocations come from the heap of the host.
+#[derive(Debug)]
+pub struct InMemoryBootServices {
+    database: RefCell<Database>,
+    tpl: Cell<Tpl>,
+}
+
+impl InMemoryBootServices {
+    pub fn new() -> Self {
+        Self {
+            database: RefCell::new(Database {
+                next_id: 0,
+                handles: Vec::new(),
+                events: Vec::new(),
+                notifies: Vec::new(),
+                pool: Vec::new(),
+                pages: Vec::new(),
+                configuration_tables: Vec::new(),
+                monotonic_count: 0,
+                time: 0,
+            }),
+            tpl: Cell::new(Tpl::APPLICATION),
+        }
+    }
+
+    /// Creates a database with the handles of a snapshot.
+    ///
+    /// The protocols are installed with null interfaces, tests replace the ones they use with
+    /// [`BootServices::reinstall_protocol_interface_unchecked`]. Returns `INVALID_PARAMETER` for a handle without
+    /// protocols or with a protocol twice.
+    pub fn from_snapshot(snapshot: &HandleDatabaseSnapshot) -> Result<Self, EfiError> {
+        let boot_services = Self::new();
+        // Installed protocols are identified by static GUIDs, each one is leaked once.
+        let mut guids: Vec<&'static efi::Guid> = Vec::new();
+        for handle in snapshot.handles.iter() {
+            let mut installed = None;
+            for protocol in handle.protocols.iter() {
+                let guid = match guids.iter().find(|&&guid| protocol == guid) {
+                    Some(&guid) => guid,
+                    None => {
+                        let guid: &'static efi::Guid = Box::leak(Box::new((*protocol).into()));
+                        guids.push(guid);
+                        guid
+                    }
+                };
+                //SAFETY: A null interface is never dereferenced by the database.
+                installed = Some(unsafe {
+                    boot_services.install_protocol_interface_unchecked(installed, guid, ptr::null_mut())
+                }?);
+            }
+            installed.ok_or(efi::Status::INVALID_PARAMETER)?;
+        }
+        Ok(boot_services)
+    }
+
+    /// Captures the handles of the database and the protocols installed on them.
+    pub fn snapshot(&self) -> HandleDatabaseSnapshot {
+        HandleDatabaseSnapshot {
+            handles: self
+                .database
+                .borrow()
+                .handles
+                .iter()
+                .map(|entry| HandleSnapshot {
+                    protocols: entry.protocols.iter().map(|protocol| Guid::from(*protocol.guid)).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The handles of the database, oldest first.
+    pub fn handles(&self) -> Vec<efi::Handle> {
+        self.database.borrow().handles.iter().map(|entry| entry.id as efi::Handle).collect()
+    }
+
+    /// The current task priority level.
+    pub fn tpl(&self) -> Tpl {
+        self.tpl.get()
+    }
+
+    /// The number of pool and page allocations not freed yet, to check for leaks.
+    pub fn allocations(&self) -> usize {
+        let database = self.database.borrow();
+        database.pool.len() + database.pages.len()
+    }
+
+    /// The virtual time elapsed since the creation of the boot services.
+    pub fn time(&self) -> Duration {
+        Duration::from_nanos(self.database.borrow().time.saturating_mul(TIMER_UNIT_NS as u64))
+    }
+
+    /// Moves the virtual time forward, signaling the timer events that expire on the way in the order they expire.
+    ///
+    /// A periodic timer fires once per period elapsed, or once per call with a period of 0.
+    pub fn advance_time(&self, duration: Duration) {
+        let ticks = (duration.as_nanos() / TIMER_UNIT_NS).min(u64::MAX as u128) as u64;
+        let target = self.database.borrow().time.saturating_add(ticks);
+        loop {
+            let event = {
+                let mut database = self.database.borrow_mut();
+                let Some((trigger_time, event)) = database
+                    .events
+                    .iter()
+                    .filter_map(|event| event.timer.map(|timer| (timer.trigger_time, event.id)))
+                    .filter(|&(trigger_time, _)| trigger_time <= target)
+                    .min()
+                else {
+                    database.time = target;
+                    return;
+                };
+                database.time = database.time.max(trigger_time);
+                if let Ok(entry) = database.event_mut(event as efi::Event) {
+                    // A periodic timer of 0 would fire forever, it waits for the next call instead.
+                    entry.timer = entry.timer.and_then(|timer| timer.period).map(|period| Timer {
+                        trigger_time: match period {
+                            0 => target.saturating_add(1),
+                            period => trigger_time.saturating_add(period),
+                        },
+                        period: Some(period),
+                    });
+                }
+                event
+            };
+            let _ = self.signal_event(event as efi::Event);
+        }
+    }
+
+    /// The virtual time of the next timer to fire, if any.
+    fn next_trigger_time(&self) -> Option<u64> {
+        self.database.borrow().events.iter().filter_map(|event| event.timer).map(|timer| timer.trigger_time).min()
+    }
+
+    /// The configuration tables installed so far.
+    pub fn configuration_tables(&self) -> Vec<efi::ConfigurationTable> {
+        self.database.borrow().configuration_tables.clone()
+    }
+
+    /// Copies `items` in a pool allocation.
+    fn pool_slice<T: Copy>(&self, items: &[T]) -> Result<BootServicesBox<'_, [T], Self>, EfiError> {
+        let buffer = self.allocate_pool(MemoryType::BOOT_SERVICES_DATA, mem::size_of_val(items))? as *mut T;
+        //SAFETY: The allocation is large enough and aligned for the items, and freed by the box.
+        unsafe {
+            ptr::copy_nonoverlapping(items.as_ptr(), buffer, items.len());
+            Ok(BootServicesBox::from_raw_parts(buffer, items.len(), self))
+        }
+    }
+
+    /// Runs the queued notification functions of a TPL above the current one, highest TPL first.
+    fn dispatch(&self) {
+        loop {
+            let (event, notify_tpl, notify_function, notify_context) = {
+                let mut database = self.database.borrow_mut();
+                let tpl = self.tpl.get();
+                let Some(event) = database
+                    .events
+                    .iter_mut()
+                    .filter(|event| event.queued.is_some() && event.notify_tpl > tpl)
+                    .min_by_key(|event| (Reverse(event.notify_tpl), event.queued))
+                else {
+                    return;
+                };
+                event.queued = None;
+                if event.event_type & efi::EVT_NOTIFY_SIGNAL != 0 {
+                    event.signaled = false;
+                }
+                (event.id, event.notify_tpl, event.notify_function, event.notify_context)
+            };
+            let tpl = self.tpl.replace(notify_tpl);
+            if let Some(notify_function) = notify_function {
+                notify_function(event as efi::Event, notify_context);
+            }
+            self.tpl.set(tpl);
+        }
+    }
+
+    /// The children created by the drivers managing a controller, or by `agent` only.
+    fn children(&self, controller: efi::Handle, agent: Option<efi::Handle>) -> Vec<efi::Handle> {
+        let database = self.database.borrow();
+        let mut children = Vec::new();
+        for entry in database.open_entries(controller) {
+            if entry.attributes & efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER != 0
+                && agent.map_or(true, |agent| agent == entry.agent_handle)
+                && !children.contains(&entry.controller_handle)
+            {
+                children.push(entry.controller_handle);
+            }
+        }
+        children
+    }
+}
+
+impl Default for InMemoryBootServices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for InMemoryBootServices {
+    fn drop(&mut self) {
+        let database = self.database.get_mut();
+        for (address, layout) in database.pool.drain(..).chain(database.pages.drain(..)) {
+            //SAFETY: The memory was allocated with this layout and not freed yet.
+            unsafe { dealloc(address as *mut u8, layout) };
+        }
+    }
+}
+
+impl BootServices for InMemoryBootServices {
+    unsafe fn create_event_unchecked<T: Sized + 'static>(
+        &self,
+        event_type: EventType,
+        notify_tpl: Tpl,
+        notify_function: Option<EventNotifyCallback<*mut T>>,
+        notify_context: *mut T,
+    ) -> Result<efi::Event, EfiError> {
+        let event_type: u32 = event_type.into();
+        let notify = efi::EVT_NOTIFY_SIGNAL | efi::EVT_NOTIFY_WAIT;
+        if event_type & notify == notify
+            || (event_type & notify != 0
+                && (notify_function.is_none()
+                    || notify_tpl <= Tpl::APPLICATION
+                    || notify_tpl > Tpl(efi::TPL_HIGH_LEVEL)))
+        {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let mut database = self.database.borrow_mut();
+        let id = database.new_id();
+        database.events.push(Event {
+            id,
+            event_type,
+            notify_tpl,
+            //SAFETY: The context is only given back to the function as the pointer it was given as.
+            notify_function: mem::transmute::<
+                Option<EventNotifyCallback<*mut T>>,
+                Option<EventNotifyCallback<*mut c_void>>,
+            >(notify_function),
+            notify_context: notify_context as *mut c_void,
+            event_group: None,
+            signaled: false,
+            queued: None,
+            timer: None,
+        });
+        Ok(id as efi::Event)
+    }
+
+    unsafe fn create_event_ex_unchecked<T: Sized + 'static>(
+        &self,
+        event_type: EventType,
+        notify_tpl: Tpl,
+        notify_function: EventNotifyCallback<*mut T>,
+        notify_context: *mut T,
+        event_group: &'static efi::Guid,
+    ) -> Result<efi::Event, EfiError> {
+        let event = self.create_event_unchecked(event_type, notify_tpl, Some(notify_function), notify_context)?;
+        self.database.borrow_mut().event_mut(event)?.event_group = Some(*event_group);
+        Ok(event)
+    }
+
+    fn close_event(&self, event: efi::Event) -> Result<(), EfiError> {
+        let mut database = self.database.borrow_mut();
+        let index = database
+            .events
+            .iter()
+            .position(|entry| entry.id == event as usize)
+            .ok_or(efi::Status::INVALID_PARAMETER)?;
+        database.events.remove(index);
+        database.notifies.retain(|notify| notify.event != event as usize);
+        Ok(())
+    }
+
+    fn signal_event(&self, event: efi::Event) -> Result<(), EfiError> {
+        {
+            let mut database = self.database.borrow_mut();
+            let event_group = database.event_mut(event)?.event_group;
+            let mut queued = database.new_id();
+            for entry in database.events.iter_mut() {
+                let in_group = event_group.is_some() && entry.event_group == event_group;
+                if (entry.id == event as usize || in_group) && !entry.signaled {
+                    entry.signaled = true;
+                    if entry.event_type & efi::EVT_NOTIFY_SIGNAL != 0 {
+                        entry.queued = Some(queued);
+                        queued += 1;
+                    }
+                }
+            }
+            database.next_id = queued;
+        }
+        self.dispatch();
+        Ok(())
+    }
+
+    /// Advances the virtual time to the next timer while none of the events is signaled, and returns `NOT_READY`
+    /// instead of blocking forever when no timer is left.
+    fn wait_for_event(&self, events: &mut [efi::Event]) -> Result<usize, EfiError> {
+        if self.tpl.get() != Tpl::APPLICATION {
+            return Err(efi::Status::UNSUPPORTED.into());
+        }
+        loop {
+            for (index, &event) in events.iter().enumerate() {
+                match self.check_event(event) {
+                    Ok(()) => return Ok(index),
+                    Err(status) if status == efi::Status::NOT_READY => (),
+                    Err(status) => return Err(status),
+                }
+            }
+            let trigger_time = self.next_trigger_time().ok_or(efi::Status::NOT_READY)?;
+            let time = self.database.borrow().time;
+            self.advance_time(Duration::from_nanos(
+                trigger_time.saturating_sub(time).saturating_mul(TIMER_UNIT_NS as u64),
+            ));
+        }
+    }
+
+    fn check_event(&self, event: efi::Event) -> Result<(), EfiError> {
+        {
+            let mut database = self.database.borrow_mut();
+            let queued = database.new_id();
+            let entry = database.event_mut(event)?;
+            if entry.event_type & efi::EVT_NOTIFY_SIGNAL != 0 {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            if !entry.signaled && entry.event_type & efi::EVT_NOTIFY_WAIT != 0 && entry.queued.is_none() {
+                entry.queued = Some(queued);
+            }
+        }
+        self.dispatch();
+        let mut database = self.database.borrow_mut();
+        let entry = database.event_mut(event)?;
+        if entry.signaled {
+            entry.signaled = false;
+            Ok(())
+        } else {
+            Err(efi::Status::NOT_READY.into())
+        }
+    }
+
+    fn set_timer(&self, event: efi::Event, timer_type: EventTimerType, trigger_time: u64) -> Result<(), EfiError> {
+        let mut database = self.database.borrow_mut();
+        let time = database.time;
+        let entry = database.event_mut(event)?;
+        if entry.event_type & efi::EVT_TIMER == 0 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        entry.timer = match timer_type {
+            EventTimerType::Cancel => None,
+            EventTimerType::Relative => Some(Timer { trigger_time: time.saturating_add(trigger_time), period: None }),
+            EventTimerType::Periodic => {
+                Some(Timer { trigger_time: time.saturating_add(trigger_time), period: Some(trigger_time) })
+            }
+        };
+        Ok(())
+    }
+
+    fn raise_tpl(&self, tpl: Tpl) -> Tpl {
+        let previous = self.tpl.replace(tpl);
+        debug_assert!(tpl >= previous, "raise_tpl called with a lower TPL.");
+        previous
+    }
+
+    fn restore_tpl(&self, tpl: Tpl) {
+        debug_assert!(tpl <= self.tpl.get(), "restore_tpl called with a higher TPL.");
+        self.tpl.set(tpl);
+        self.dispatch();
+    }
+
+    fn allocate_pages(
+        &self,
+        alloc_type: AllocType,
+        _memory_type: MemoryType,
+        nb_pages: usize,
+    ) -> Result<usize, EfiError> {
+        if let AllocType::Address(_) = alloc_type {
+            return Err(efi::Status::NOT_FOUND.into());
+        }
+        let size = nb_pages.checked_mul(PAGE_SIZE).ok_or(efi::Status::OUT_OF_RESOURCES)?;
+        let layout = Layout::from_size_align(size.max(1), PAGE_SIZE).map_err(|_| efi::Status::OUT_OF_RESOURCES)?;
+        //SAFETY: The layout is not zero-sized.
+        let address = unsafe { alloc_zeroed(layout) } as usize;
+        if address == 0 {
+            return Err(efi::Status::OUT_OF_RESOURCES.into());
+        }
+        if let AllocType::MaxAddress(max_address) = alloc_type {
+            if address + layout.size() - 1 > max_address {
+                //SAFETY: The memory was just allocated with this layout.
+                unsafe { dealloc(address as *mut u8, layout) };
+                return Err(efi::Status::NOT_FOUND.into());
+            }
+        }
+        self.database.borrow_mut().pages.push((address, layout));
+        Ok(address)
+    }
+
+    fn free_pages(&self, address: usize, nb_pages: usize) -> Result<(), EfiError> {
+        let mut database = self.database.borrow_mut();
+        let index = database
+            .pages
+            .iter()
+            .position(|&(start, layout)| start == address && layout.size() == (nb_pages * PAGE_SIZE).max(1))
+            .ok_or(efi::Status::NOT_FOUND)?;
+        let (address, layout) = database.pages.remove(index);
+        //SAFETY: The memory was allocated with this layout and not freed yet.
+        unsafe { dealloc(address as *mut u8, layout) };
+        Ok(())
+    }
+
+    fn get_memory_map(&self) -> Result<MemoryMap<'_, Self>, (EfiError, usize)> {
+        Err((efi::Status::UNSUPPORTED.into(), 0))
+    }
+
+    fn allocate_pool(&self, _pool_type: MemoryType, size: usize) -> Result<*mut u8, EfiError> {
+        let layout = Layout::from_size_align(size.max(1), POOL_ALIGNMENT).map_err(|_| efi::Status::OUT_OF_RESOURCES)?;
+        //SAFETY: The layout is not zero-sized.
+        let buffer = unsafe { alloc_zeroed(layout) };
+        if buffer.is_null() {
+            return Err(efi::Status::OUT_OF_RESOURCES.into());
+        }
+        self.database.borrow_mut().pool.push((buffer as usize, layout));
+        Ok(buffer)
+    }
+
+    fn free_pool(&self, buffer: *mut u8) -> Result<(), EfiError> {
+        let mut database = self.database.borrow_mut();
+        let index = database
+            .pool
+            .iter()
+            .position(|&(address, _)| address == buffer as usize)
+            .ok_or(efi::Status::INVALID_PARAMETER)?;
+        let (address, layout) = database.pool.remove(index);
+        //SAFETY: The memory was allocated with this layout and not freed yet.
+        unsafe { dealloc(address as *mut u8, layout) };
+        Ok(())
+    }
+
+    unsafe fn install_protocol_interface_unchecked(
+        &self,
+        handle: Option<efi::Handle>,
+        protocol: &'static efi::Guid,
+        interface: *mut c_void,
+    ) -> Result<efi::Handle, EfiError> {
+        let (handle, events) = {
+            let mut database = self.database.borrow_mut();
+            let handle = match handle {
+                Some(handle) => {
+                    let entry = database.handle_mut(handle)?;
+                    if entry.protocols.iter().any(|entry| entry.guid == protocol) {
+                        return Err(efi::Status::INVALID_PARAMETER.into());
+                    }
+                    handle as usize
+                }
+                None => {
+                    let id = database.new_id();
+                    database.handles.push(HandleEntry { id, protocols: Vec::new() });
+                    id
+                }
+            };
+            database.handle_mut(handle as efi::Handle)?.protocols.push(ProtocolEntry {
+                guid: protocol,
+                interface,
+                open_list: Vec::new(),
+            });
+            (handle, database.queue_notifies(protocol, handle))
+        };
+        for event in events {
+            let _ = self.signal_event(event as efi::Event);
+        }
+        Ok(handle as efi::Handle)
+    }
+
+    unsafe fn uninstall_protocol_interface_unchecked(
+        &self,
+        handle: efi::Handle,
+        protocol: &'static efi::Guid,
+        interface: *mut c_void,
+    ) -> Result<(), EfiError> {
+        let mut drivers = Vec::new();
+        {
+            let mut database = self.database.borrow_mut();
+            let entry = database.protocol_mut(handle, protocol).map_err(|_| efi::Status::NOT_FOUND)?;
+            if entry.interface != interface {
+                return Err(efi::Status::NOT_FOUND.into());
+            }
+            for open in entry.open_list.iter() {
+                if open.attributes & efi::OPEN_PROTOCOL_BY_DRIVER != 0 && !drivers.contains(&open.agent_handle) {
+                    drivers.push(open.agent_handle);
+                }
+            }
+        }
+        // The drivers using the protocol are stopped first, like the firmware does.
+        for driver in drivers {
+            let _ = self.disconnect_controller(handle, Some(driver), None);
+        }
+
+        let mut database = self.database.borrow_mut();
+        let entry = database.handle_mut(handle)?;
+        let index = entry.protocols.iter().position(|entry| entry.guid == protocol).ok_or(efi::Status::NOT_FOUND)?;
+        let exclusive = efi::OPEN_PROTOCOL_BY_DRIVER | efi::OPEN_PROTOCOL_EXCLUSIVE;
+        if entry.protocols[index].open_list.iter().any(|open| open.attributes & exclusive != 0) {
+            return Err(efi::Status::ACCESS_DENIED.into());
+        }
+        entry.protocols.remove(index);
+        if entry.protocols.is_empty() {
+            database.handles.retain(|entry| entry.id != handle as usize);
+        }
+        Ok(())
+    }
+
+    unsafe fn reinstall_protocol_interface_unchecked(
+        &self,
+        handle: efi::Handle,
+        protocol: &'static efi::Guid,
+        old_protocol_interface: *mut c_void,
+        new_protocol_interface: *mut c_void,
+    ) -> Result<(), EfiError> {
+        let events = {
+            let mut database = self.database.borrow_mut();
+            let entry = database.protocol_mut(handle, protocol).map_err(|_| efi::Status::NOT_FOUND)?;
+            if entry.interface != old_protocol_interface {
+                return Err(efi::Status::NOT_FOUND.into());
+            }
+            entry.interface = new_protocol_interface;
+            database.queue_notifies(protocol, handle as usize)
+        };
+        for event in events {
+            let _ = self.signal_event(event as efi::Event);
+        }
+        Ok(())
+    }
+
+    fn register_protocol_notify(
+        &self,
+        protocol: &'static efi::Guid,
+        event: efi::Event,
+    ) -> Result<Registration, EfiError> {
+        let mut database = self.database.borrow_mut();
+        database.event_mut(event)?;
+        let id = database.new_id();
+        database.notifies.push(Notify { id, protocol: *protocol, event: event as usize, handles: VecDeque::new() });
+        NonNull::new(id as *mut c_void).ok_or(efi::Status::OUT_OF_RESOURCES.into())
+    }
+
+    fn locate_handle(
+        &self,
+        search_type: HandleSearchType,
+    ) -> Result<BootServicesBox<'_, [efi::Handle], Self>, EfiError> {
+        self.locate_handle_buffer(search_type)
+    }
+
+    unsafe fn handle_protocol_unchecked(
+        &self,
+        handle: efi::Handle,
+        protocol: &efi::Guid,
+    ) -> Result<*mut c_void, EfiError> {
+        self.database.borrow_mut().protocol_mut(handle, protocol).map(|entry| entry.interface)
+    }
+
+    unsafe fn locate_device_path(
+        &self,
+        _protocol: &efi::Guid,
+        _device_path: *mut *mut efi::protocols::device_path::Protocol,
+    ) -> Result<efi::Handle, EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn open_protocol_unchecked(
+        &self,
+        handle: efi::Handle,
+        protocol: &efi::Guid,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+        attribute: u32,
+    ) -> Result<*mut c_void, EfiError> {
+        let needs_agent = attribute & !(efi::OPEN_PROTOCOL_TEST_PROTOCOL | efi::OPEN_PROTOCOL_GET_PROTOCOL) != 0;
+        let needs_controller = attribute & (efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER | efi::OPEN_PROTOCOL_BY_DRIVER) != 0;
+        let valid = matches!(
+            attribute,
+            efi::OPEN_PROTOCOL_BY_HANDLE_PROTOCOL
+                | efi::OPEN_PROTOCOL_GET_PROTOCOL
+                | efi::OPEN_PROTOCOL_TEST_PROTOCOL
+                | efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER
+                | efi::OPEN_PROTOCOL_BY_DRIVER
+                | efi::OPEN_PROTOCOL_EXCLUSIVE
+        ) || attribute == efi::OPEN_PROTOCOL_BY_DRIVER | efi::OPEN_PROTOCOL_EXCLUSIVE;
+        if !valid
+            || (needs_agent && agent_handle.is_null())
+            || (needs_controller && controller_handle.is_null())
+            || (attribute == efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER && controller_handle == handle)
+        {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+
+        let mut database = self.database.borrow_mut();
+        let entry = database.protocol_mut(handle, protocol)?;
+        if attribute == efi::OPEN_PROTOCOL_TEST_PROTOCOL {
+            return Ok(ptr::null_mut());
+        }
+        if attribute & (efi::OPEN_PROTOCOL_BY_DRIVER | efi::OPEN_PROTOCOL_EXCLUSIVE) != 0 {
+            for open in entry.open_list.iter() {
+                let by_driver = open.attributes & efi::OPEN_PROTOCOL_BY_DRIVER != 0;
+                let exclusive = open.attributes & efi::OPEN_PROTOCOL_EXCLUSIVE != 0;
+                if by_driver && open.agent_handle == agent_handle && open.attributes == attribute {
+                    return Err(efi::Status::ALREADY_STARTED.into());
+                }
+                // The firmware would disconnect the other drivers for an exclusive open, they are kept instead.
+                if exclusive || by_driver {
+                    return Err(efi::Status::ACCESS_DENIED.into());
+                }
+            }
+        }
+        match entry.open_list.iter_mut().find(|open| {
+            open.agent_handle == agent_handle
+                && open.controller_handle == controller_handle
+                && open.attributes == attribute
+        }) {
+            Some(open) => open.open_count += 1,
+            None => entry.open_list.push(efi::OpenProtocolInformationEntry {
+                agent_handle,
+                controller_handle,
+                attributes: attribute,
+                open_count: 1,
+            }),
+        }
+        Ok(entry.interface)
+    }
+
+    fn close_protocol(
+        &self,
+        handle: efi::Handle,
+        protocol: &efi::Guid,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+    ) -> Result<(), EfiError> {
+        if agent_handle.is_null() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let mut database = self.database.borrow_mut();
+        let entry = database.protocol_mut(handle, protocol).map_err(|_| efi::Status::NOT_FOUND)?;
+        let count = entry.open_list.len();
+        entry.open_list.retain(|open| open.agent_handle != agent_handle || open.controller_handle != controller_handle);
+        if entry.open_list.len() == count {
+            return Err(efi::Status::NOT_FOUND.into());
+        }
+        Ok(())
+    }
+
+    fn open_protocol_information(
+        &self,
+        handle: efi::Handle,
+        protocol: &efi::Guid,
+    ) -> Result<BootServicesBox<'_, [efi::OpenProtocolInformationEntry], Self>, EfiError> {
+        let open_list = self
+            .database
+            .borrow_mut()
+            .protocol_mut(handle, protocol)
+            .map_err(|_| efi::Status::NOT_FOUND)?
+            .open_list
+            .clone();
+        self.pool_slice(&open_list)
+    }
+
+    /// Starts the drivers of the Driver Binding protocols installed in the database that support the controller,
+    /// highest version first.
+    unsafe fn connect_controller(
+        &self,
+        controller_handle: efi::Handle,
+        driver_image_handle: Vec<efi::Handle>,
+        remaining_device_path: *mut efi::protocols::device_path::Protocol,
+        recursive: bool,
+    ) -> Result<(), EfiError> {
+        let mut bindings = {
+            let database = self.database.borrow();
+            database.handle(controller_handle)?;
+            let drivers = driver_image_handle.into_iter().filter(|handle| !handle.is_null()).collect::<Vec<_>>();
+            database
+                .handles
+                .iter()
+                .filter(|entry| drivers.is_empty() || drivers.contains(&(entry.id as efi::Handle)))
+                .flat_map(|entry| entry.protocols.iter())
+                .filter(|entry| *entry.guid == driver_binding::PROTOCOL_GUID)
+                .map(|entry| entry.interface as *mut driver_binding::Protocol)
+                .collect::<Vec<_>>()
+        };
+        bindings.sort_by_key(|&binding| Reverse((*binding).version));
+
+        let mut started = false;
+        for binding in bindings {
+            if ((*binding).supported)(binding, controller_handle, remaining_device_path) == efi::Status::SUCCESS
+                && ((*binding).start)(binding, controller_handle, remaining_device_path) == efi::Status::SUCCESS
+            {
+                started = true;
+            }
+        }
+        if recursive {
+            for child in self.children(controller_handle, None) {
+                let _ = self.connect_controller(child, Vec::new(), ptr::null_mut(), true);
+            }
+        }
+        if started {
+            Ok(())
+        } else {
+            Err(efi::Status::NOT_FOUND.into())
+        }
+    }
+
+    /// Stops the drivers managing the controller through their Driver Binding protocol, their children first.
+    fn disconnect_controller(
+        &self,
+        controller_handle: efi::Handle,
+        driver_image_handle: Option<efi::Handle>,
+        child_handle: Option<efi::Handle>,
+    ) -> Result<(), EfiError> {
+        let mut drivers = Vec::new();
+        {
+            let database = self.database.borrow();
+            database.handle(controller_handle)?;
+            for open in database.open_entries(controller_handle) {
+                if open.attributes & efi::OPEN_PROTOCOL_BY_DRIVER != 0
+                    && driver_image_handle.map_or(true, |driver| driver == open.agent_handle)
+                    && !drivers.contains(&open.agent_handle)
+                {
+                    drivers.push(open.agent_handle);
+                }
+            }
+        }
+
+        for driver in drivers {
+            //SAFETY: Driver Binding protocols are installed with their interface.
+            let Ok(binding) = (unsafe { self.handle_protocol_unchecked(driver, &driver_binding::PROTOCOL_GUID) })
+            else {
+                continue;
+            };
+            let binding = binding as *mut driver_binding::Protocol;
+            let mut children = self.children(controller_handle, Some(driver));
+            children.retain(|&child| child_handle.map_or(true, |child_handle| child == child_handle));
+            //SAFETY: The interface is a Driver Binding protocol, given the children it created.
+            unsafe {
+                if !children.is_empty() {
+                    let status = ((*binding).stop)(binding, controller_handle, children.len(), children.as_mut_ptr());
+                    if status.is_error() {
+                        return Err(status.into());
+                    }
+                }
+                if child_handle.is_none() || self.children(controller_handle, Some(driver)).is_empty() {
+                    let status = ((*binding).stop)(binding, controller_handle, 0, ptr::null_mut());
+                    if status.is_error() {
+                        return Err(status.into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn protocols_per_handle(
+        &self,
+        handle: efi::Handle,
+    ) -> Result<BootServicesBox<'_, [&'static efi::Guid], Self>, EfiError> {
+        let protocols =
+            self.database.borrow().handle(handle)?.protocols.iter().map(|entry| entry.guid).collect::<Vec<_>>();
+        self.pool_slice(&protocols)
+    }
+
+    fn locate_handle_buffer(
+        &self,
+        search_type: HandleSearchType,
+    ) -> Result<BootServicesBox<'_, [efi::Handle], Self>, EfiError> {
+        let handles = {
+            let mut database = self.database.borrow_mut();
+            match search_type {
+                HandleSearchType::AllHandle => database.handles.iter().map(|entry| entry.id).collect(),
+                HandleSearchType::ByProtocol(protocol) => database
+                    .handles
+                    .iter()
+                    .filter(|entry| entry.protocols.iter().any(|entry| entry.guid == protocol))
+                    .map(|entry| entry.id)
+                    .collect(),
+                HandleSearchType::ByRegisterNotify(registration) => {
+                    let notify = database
+                        .notifies
+                        .iter_mut()
+                        .find(|notify| notify.id == registration.as_ptr() as usize)
+                        .ok_or(efi::Status::INVALID_PARAMETER)?;
+                    notify.handles.pop_front().into_iter().collect::<Vec<_>>()
+                }
+            }
+        };
+        if handles.is_empty() {
+            return Err(efi::Status::NOT_FOUND.into());
+        }
+        self.pool_slice(&handles.into_iter().map(|id| id as efi::Handle).collect::<Vec<_>>())
+    }
+
+    unsafe fn locate_protocol_unchecked(
+        &self,
+        protocol: &'static efi::Guid,
+        registration: *mut c_void,
+    ) -> Result<*mut c_void, EfiError> {
+        let mut database = self.database.borrow_mut();
+        if registration.is_null() {
+            return database
+                .handles
+                .iter()
+                .flat_map(|entry| entry.protocols.iter())
+                .find(|entry| entry.guid == protocol)
+                .map(|entry| entry.interface)
+                .ok_or(efi::Status::NOT_FOUND.into());
+        }
+        loop {
+            let notify = database
+                .notifies
+                .iter_mut()
+                .find(|notify| notify.id == registration as usize)
+                .ok_or(efi::Status::INVALID_PARAMETER)?;
+            let handle = notify.handles.pop_front().ok_or(efi::Status::NOT_FOUND)?;
+            // The protocol may have been uninstalled since.
+            if let Ok(entry) = database.protocol_mut(handle as efi::Handle, protocol) {
+                return Ok(entry.interface);
+            }
+        }
+    }
+
+    unsafe fn install_configuration_table_unchecked(
+        &self,
+        guid: &efi::Guid,
+        table: *mut c_void,
+    ) -> Result<(), EfiError> {
+        let mut database = self.database.borrow_mut();
+        let index = database.configuration_tables.iter().position(|entry| entry.vendor_guid == *guid);
+        match (index, table.is_null()) {
+            (Some(index), true) => _ = database.configuration_tables.remove(index),
+            (Some(index), false) => database.configuration_tables[index].vendor_table = table,
+            (None, true) => return Err(efi::Status::NOT_FOUND.into()),
+            (None, false) => {
+                database.configuration_tables.push(efi::ConfigurationTable { vendor_guid: *guid, vendor_table: table })
+            }
+        }
+        Ok(())
+    }
+
+    /// Advances the virtual time instead of waiting.
+    fn stall(&self, microseconds: usize) -> Result<(), EfiError> {
+        self.advance_time(Duration::from_micros(microseconds as u64));
+        Ok(())
+    }
+
+    fn get_next_monotonic_count(&self) -> Result<u64, EfiError> {
+        let mut database = self.database.borrow_mut();
+        database.monotonic_count += 1;
+        Ok(database.monotonic_count)
+    }
+
+    unsafe fn exit_unchecked(
+        &self,
+        _image_handle: efi::Handle,
+        _exit_status: efi::Status,
+        _exit_data_size: usize,
+        _exit_data: *mut u16,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static PROTOCOL_GUID: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+    static CHILD_PROTOCOL_GUID: efi::Guid = efi::Guid::from_fields(2, 0, 0, 0, 0, &[0; 6]);
+
+    fn interface(value: usize) -> *mut c_void {
+        Box::leak(Box::new(value)) as *mut usize as *mut c_void
+    }
+
+    #[test]
+    fn test_protocol_notify() {
+        static NOTIFY_COUNT: AtomicUsize = AtomicUsize::new(0);
+        extern "efiapi" fn notify(_event: efi::Event, count: *mut AtomicUsize) {
+            //SAFETY: The context is the static counter.
+            unsafe { &*count }.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let boot_services = InMemoryBootServices::new();
+        let event = unsafe {
+            boot_services.create_event_unchecked(
+                EventType::NOTIFY_SIGNAL,
+                Tpl::CALLBACK,
+                Some(notify),
+                &NOTIFY_COUNT as *const AtomicUsize as *mut AtomicUsize,
+            )
+        }
+        .unwrap();
+        let registration = boot_services.register_protocol_notify(&PROTOCOL_GUID, event).unwrap();
+
+        // The notification is deferred until the TPL is restored below the TPL of the event.
+        let tpl = boot_services.raise_tpl(Tpl::NOTIFY);
+        let interface = interface(1);
+        let handle =
+            unsafe { boot_services.install_protocol_interface_unchecked(None, &PROTOCOL_GUID, interface) }.unwrap();
+        assert_eq!(0, NOTIFY_COUNT.load(Ordering::SeqCst));
+        boot_services.restore_tpl(tpl);
+        assert_eq!(1, NOTIFY_COUNT.load(Ordering::SeqCst));
+
+        assert_eq!(Ok(interface), unsafe {
+            boot_services.locate_protocol_unchecked(&PROTOCOL_GUID, registration.as_ptr())
+        });
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), unsafe {
+            boot_services.locate_protocol_unchecked(&PROTOCOL_GUID, registration.as_ptr())
+        });
+        assert_eq!(Ok(interface), unsafe { boot_services.locate_protocol_unchecked(&PROTOCOL_GUID, ptr::null_mut()) });
+        assert_eq!(
+            &[handle],
+            &*boot_services.locate_handle_buffer(HandleSearchType::ByProtocol(&PROTOCOL_GUID)).unwrap()
+        );
+        assert_eq!(0, boot_services.allocations());
+
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), unsafe {
+            boot_services.uninstall_protocol_interface_unchecked(handle, &PROTOCOL_GUID, ptr::null_mut())
+        });
+        unsafe { boot_services.uninstall_protocol_interface_unchecked(handle, &PROTOCOL_GUID, interface) }.unwrap();
+        assert!(boot_services.handles().is_empty());
+        assert_eq!(0, boot_services.allocations());
+    }
+
+    #[test]
+    fn test_open_protocol() {
+        let boot_services = InMemoryBootServices::new();
+        let interface = interface(1);
+        let handle =
+            unsafe { boot_services.install_protocol_interface_unchecked(None, &PROTOCOL_GUID, interface) }.unwrap();
+        let (agent, other_agent, controller) = (0x100 as efi::Handle, 0x200 as efi::Handle, 0x300 as efi::Handle);
+        let open = |agent, attribute| unsafe {
+            boot_services.open_protocol_unchecked(handle, &PROTOCOL_GUID, agent, controller, attribute)
+        };
+
+        assert_eq!(Ok(interface), open(agent, efi::OPEN_PROTOCOL_BY_DRIVER));
+        assert_eq!(Err(efi::Status::ALREADY_STARTED.into()), open(agent, efi::OPEN_PROTOCOL_BY_DRIVER));
+        assert_eq!(Err(efi::Status::ACCESS_DENIED.into()), open(other_agent, efi::OPEN_PROTOCOL_BY_DRIVER));
+        assert_eq!(Ok(interface), open(other_agent, efi::OPEN_PROTOCOL_GET_PROTOCOL));
+        assert_eq!(Ok(interface), open(other_agent, efi::OPEN_PROTOCOL_GET_PROTOCOL));
+        assert_eq!(Ok(ptr::null_mut()), open(other_agent, efi::OPEN_PROTOCOL_TEST_PROTOCOL));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), open(ptr::null_mut(), efi::OPEN_PROTOCOL_BY_DRIVER));
+
+        let information = boot_services.open_protocol_information(handle, &PROTOCOL_GUID).unwrap();
+        assert_eq!(2, information.len());
+        assert_eq!((agent, efi::OPEN_PROTOCOL_BY_DRIVER, 1), {
+            let entry = information[0];
+            (entry.agent_handle, entry.attributes, entry.open_count)
+        });
+        assert_eq!(2, information[1].open_count);
+        drop(information);
+
+        assert_eq!(Err(efi::Status::ACCESS_DENIED.into()), unsafe {
+            boot_services.uninstall_protocol_interface_unchecked(handle, &PROTOCOL_GUID, interface)
+        });
+        boot_services.close_protocol(handle, &PROTOCOL_GUID, agent, controller).unwrap();
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            boot_services.close_protocol(handle, &PROTOCOL_GUID, agent, controller)
+        );
+        assert_eq!(Ok(interface), open(other_agent, efi::OPEN_PROTOCOL_BY_DRIVER));
+        assert_eq!(0, boot_services.allocations());
+    }
+
+    /// A driver creating a child for every controller with the test protocol.
+    #[repr(C)]
+    struct Driver {
+        binding: driver_binding::Protocol,
+        boot_services: *const InMemoryBootServices,
+    }
+
+    extern "efiapi" fn supported(
+        this: *mut driver_binding::Protocol,
+        controller: efi::Handle,
+        _: *mut efi::protocols::device_path::Protocol,
+    ) -> efi::Status {
+        //SAFETY: The binding is the first field of the driver.
+        let driver = unsafe { &*(this as *mut Driver) };
+        let boot_services = unsafe { &*driver.boot_services };
+        match unsafe {
+            boot_services.open_protocol_unchecked(
+                controller,
+                &PROTOCOL_GUID,
+                driver.binding.driver_binding_handle,
+                controller,
+                efi::OPEN_PROTOCOL_TEST_PROTOCOL,
+            )
+        } {
+            Ok(_) => efi::Status::SUCCESS,
+            Err(status) => status.into(),
+        }
+    }
+
+    extern "efiapi" fn start(
+        this: *mut driver_binding::Protocol,
+        controller: efi::Handle,
+        _: *mut efi::protocols::device_path::Protocol,
+    ) -> efi::Status {
+        //SAFETY: The binding is the first field of the driver.
+        let driver = unsafe { &*(this as *mut Driver) };
+        let boot_services = unsafe { &*driver.boot_services };
+        let agent = driver.binding.driver_binding_handle;
+        let result = unsafe {
+            boot_services
+                .open_protocol_unchecked(controller, &PROTOCOL_GUID, agent, controller, efi::OPEN_PROTOCOL_BY_DRIVER)
+                .and_then(|_| {
+                    boot_services.install_protocol_interface_unchecked(None, &CHILD_PROTOCOL_GUID, interface(2))
+                })
+                .and_then(|child| {
+                    boot_services.open_protocol_unchecked(
+                        controller,
+                        &PROTOCOL_GUID,
+                        agent,
+                        child,
+                        efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER,
+                    )
+                })
+        };
+        result.map_or_else(|status| status.into(), |_| efi::Status::SUCCESS)
+    }
+
+    extern "efiapi" fn stop(
+        this: *mut driver_binding::Protocol,
+        controller: efi::Handle,
+        number_of_children: usize,
+        children: *mut efi::Handle,
+    ) -> efi::Status {
+        //SAFETY: The binding is the first field of the driver.
+        let driver = unsafe { &*(this as *mut Driver) };
+        let boot_services = unsafe { &*driver.boot_services };
+        let agent = driver.binding.driver_binding_handle;
+        if number_of_children == 0 {
+            return boot_services
+                .close_protocol(controller, &PROTOCOL_GUID, agent, controller)
+                .map_or_else(|status| status.into(), |_| efi::Status::SUCCESS);
+        }
+        //SAFETY: The firmware gives the children in a buffer of number_of_children handles.
+        for &child in unsafe { core::slice::from_raw_parts(children, number_of_children) } {
+            let result = boot_services.close_protocol(controller, &PROTOCOL_GUID, agent, child).and_then(|_| unsafe {
+                let interface = boot_services.handle_protocol_unchecked(child, &CHILD_PROTOCOL_GUID)?;
+                boot_services.uninstall_protocol_interface_unchecked(child, &CHILD_PROTOCOL_GUID, interface)
+            });
+            if let Err(status) = result {
+                return status.into();
+            }
+        }
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_connect_controller() {
+        let boot_services = InMemoryBootServices::new();
+        let controller =
+            unsafe { boot_services.install_protocol_interface_unchecked(None, &PROTOCOL_GUID, interface(1)) }.unwrap();
+        let other_controller =
+            unsafe { boot_services.install_protocol_interface_unchecked(None, &CHILD_PROTOCOL_GUID, interface(3)) }
+                .unwrap();
+        let driver = Box::leak(Box::new(Driver {
+            binding: driver_binding::Protocol {
+                supported,
+                start,
+                stop,
+                version: 1,
+                image_handle: ptr::null_mut(),
+                driver_binding_handle: ptr::null_mut(),
+            },
+            boot_services: &boot_services,
+        }));
+        let driver_handle = unsafe {
+            boot_services.install_protocol_interface_unchecked(
+                None,
+                &driver_binding::PROTOCOL_GUID,
+                &mut driver.binding as *mut _ as *mut c_void,
+            )
+        }
+        .unwrap();
+        driver.binding.driver_binding_handle = driver_handle;
+
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), unsafe {
+            boot_services.connect_controller(other_controller, Vec::new(), ptr::null_mut(), false)
+        });
+        boot_services.connect_all().unwrap();
+        let children = boot_services.children(controller, Some(driver_handle));
+        assert_eq!(1, children.len());
+        assert!(unsafe { boot_services.handle_protocol_unchecked(children[0], &CHILD_PROTOCOL_GUID) }.is_ok());
+
+        boot_services.disconnect_all_drivers_from(controller).unwrap();
+        assert_eq!(3, boot_services.handles().len());
+        assert!(boot_services.open_protocol_information(controller, &PROTOCOL_GUID).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_timers() {
+        static TICK_COUNT: AtomicUsize = AtomicUsize::new(0);
+        extern "efiapi" fn tick(_event: efi::Event, count: *mut AtomicUsize) {
+            //SAFETY: The context is the static counter.
+            unsafe { &*count }.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let boot_services = InMemoryBootServices::new();
+        let periodic = unsafe {
+            boot_services.create_event_unchecked(
+                EventType::TIMER | EventType::NOTIFY_SIGNAL,
+                Tpl::CALLBACK,
+                Some(tick),
+                &TICK_COUNT as *const AtomicUsize as *mut AtomicUsize,
+            )
+        }
+        .unwrap();
+        boot_services.set_timer(periodic, EventTimerType::Periodic, 10_000).unwrap();
+        boot_services.advance_time(Duration::from_micros(3500));
+        assert_eq!(3, TICK_COUNT.load(Ordering::SeqCst));
+        assert_eq!(Duration::from_micros(3500), boot_services.time());
+
+        // Expirations at a higher TPL are delivered once the TPL is restored.
+        let tpl = boot_services.raise_tpl(Tpl::NOTIFY);
+        boot_services.advance_time(Duration::from_millis(1));
+        assert_eq!(3, TICK_COUNT.load(Ordering::SeqCst));
+        boot_services.restore_tpl(tpl);
+        assert_eq!(4, TICK_COUNT.load(Ordering::SeqCst));
+
+        boot_services.set_timer(periodic, EventTimerType::Cancel, 0).unwrap();
+        boot_services.advance_time(Duration::from_secs(1));
+        assert_eq!(4, TICK_COUNT.load(Ordering::SeqCst));
+
+        // Waiting moves the virtual time to the expiration of the timer.
+        let timeout = unsafe {
+            boot_services.create_event_unchecked::<()>(EventType::TIMER, Tpl::APPLICATION, None, ptr::null_mut())
+        }
+        .unwrap();
+        let start = boot_services.time();
+        boot_services.set_timer(timeout, EventTimerType::Relative, 50_000).unwrap();
+        assert_eq!(Ok(0), boot_services.wait_for_event(&mut [timeout]));
+        assert_eq!(start + Duration::from_millis(5), boot_services.time());
+        assert_eq!(Err(efi::Status::NOT_READY.into()), boot_services.wait_for_event(&mut [timeout]));
+
+        // Signaling the event manually completes the wait without moving the time.
+        boot_services.set_timer(timeout, EventTimerType::Relative, 50_000).unwrap();
+        boot_services.signal_event(timeout).unwrap();
+        assert_eq!(Ok(0), boot_services.wait_for_event(&mut [timeout]));
+        assert_eq!(start + Duration::from_millis(5), boot_services.time());
+
+        let event = unsafe {
+            boot_services.create_event_unchecked::<()>(EventType::NONE, Tpl::APPLICATION, None, ptr::null_mut())
+        }
+        .unwrap();
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            boot_services.set_timer(event, EventTimerType::Relative, 0)
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let boot_services = InMemoryBootServices::new();
+        let handle =
+            unsafe { boot_services.install_protocol_interface_unchecked(None, &PROTOCOL_GUID, interface(1)) }.unwrap();
+        unsafe { boot_services.install_protocol_interface_unchecked(Some(handle), &CHILD_PROTOCOL_GUID, interface(2)) }
+            .unwrap();
+        unsafe { boot_services.install_protocol_interface_unchecked(None, &CHILD_PROTOCOL_GUID, interface(3)) }
+            .unwrap();
+
+        let snapshot = boot_services.snapshot();
+        assert_eq!(
+            vec![
+                HandleSnapshot { protocols: vec![PROTOCOL_GUID.into(), CHILD_PROTOCOL_GUID.into()] },
+                HandleSnapshot { protocols: vec![CHILD_PROTOCOL_GUID.into()] }
+            ],
+            snapshot.handles
+        );
+        let restored = InMemoryBootServices::from_snapshot(&snapshot).unwrap();
+        assert_eq!(snapshot, restored.snapshot());
+        let handles = restored.locate_handle_buffer(HandleSearchType::ByProtocol(&CHILD_PROTOCOL_GUID)).unwrap();
+        assert_eq!(2, handles.len());
+
+        let invalid = HandleDatabaseSnapshot { handles: vec![HandleSnapshot::default()] };
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            InMemoryBootServices::from_snapshot(&invalid).map(|_| ())
+        );
+        let invalid = HandleDatabaseSnapshot {
+            handles: vec![HandleSnapshot { protocols: vec![PROTOCOL_GUID.into(), PROTOCOL_GUID.into()] }],
+        };
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            InMemoryBootServices::from_snapshot(&invalid).map(|_| ())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_snapshot_serde() {
+        let json = r#"{"handles":[{"protocols":["00000001-0000-0000-0000-000000000000"]}]}"#;
+        let snapshot: HandleDatabaseSnapshot = serde_json::from_str(json).unwrap();
+        let boot_services = InMemoryBootServices::from_snapshot(&snapshot).unwrap();
+        assert_eq!(Ok(ptr::null_mut()), unsafe {
+            boot_services.locate_protocol_unchecked(&PROTOCOL_GUID, ptr::null_mut())
+        });
+        assert_eq!(json, serde_json::to_string(&boot_services.snapshot()).unwrap());
+    }
+}
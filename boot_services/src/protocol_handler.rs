@@ -1,6 +1,20 @@
-use core::{ffi::c_void, ops::Deref, ptr::NonNull};
+use alloc::boxed::Box;
+use core::{
+    ffi::c_void,
+    fmt, iter,
+    marker::PhantomData,
+    mem, ops,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
 
 use r_efi::efi;
+use status::EfiError;
+
+use crate::{boxed::BootServicesBox, event::EventType, tpl::Tpl, BootServices};
+
+pub use boot_services_macros::Protocol;
 
 pub unsafe trait Protocol: Deref<Target = efi::Guid> {
     type Interface;
@@ -9,7 +23,7 @@ pub unsafe trait Protocol: Deref<Target = efi::Guid> {
 
 pub type Registration = NonNull<c_void>;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HandleSearchType {
     AllHandle,
     ByRegisterNotify(Registration),
@@ -26,6 +40,726 @@ impl Into<efi::LocateSearchType> for HandleSearchType {
     }
 }
 
+/// Buffer of handles returned by [`BootServices::locate_handles`].
+///
+/// The buffer is owned and freed when dropped.
+pub struct HandleBuffer<'a, B: BootServices>(BootServicesBox<'a, [efi::Handle], B>);
+
+impl<'a, B: BootServices> HandleBuffer<'a, B> {
+    /// Returns the handles that support the specified protocol.
+    ///
+    /// See [`BootServices::locate_handles`] for more details.
+    pub fn supporting<P: Protocol>(boot_services: &'a B, protocol: &P) -> Result<Self, EfiError> {
+        boot_services.locate_handles(HandleSearchType::ByProtocol(protocol.protocol_guid()))
+    }
+
+    /// Returns an iterator over the handles.
+    pub fn iter(&self) -> iter::Copied<slice::Iter<'_, efi::Handle>> {
+        self.0.iter().copied()
+    }
+}
+
+impl<'a, B: BootServices> From<BootServicesBox<'a, [efi::Handle], B>> for HandleBuffer<'a, B> {
+    fn from(handles: BootServicesBox<'a, [efi::Handle], B>) -> Self {
+        Self(handles)
+    }
+}
+
+impl<B: BootServices> Deref for HandleBuffer<'_, B> {
+    type Target = [efi::Handle];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'b, B: BootServices> IntoIterator for &'b HandleBuffer<'_, B> {
+    type Item = efi::Handle;
+    type IntoIter = iter::Copied<slice::Iter<'b, efi::Handle>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<B: BootServices> fmt::Debug for HandleBuffer<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Buffer of protocol GUIDs returned by [`BootServices::protocols_on_handle`].
+///
+/// The buffer is owned and freed when dropped.
+/// The [`fmt::Display`] implementation prints the name of the well-known protocols, see [`protocol_name`].
+pub struct GuidBuffer<'a, B: BootServices>(BootServicesBox<'a, [&'static efi::Guid], B>);
+
+impl<B: BootServices> GuidBuffer<'_, B> {
+    /// Returns an iterator over the protocol GUIDs.
+    pub fn iter(&self) -> iter::Copied<slice::Iter<'_, &'static efi::Guid>> {
+        self.0.iter().copied()
+    }
+
+    /// Returns true if the protocol is in the buffer.
+    pub fn contains<P: Protocol>(&self, protocol: &P) -> bool {
+        self.iter().any(|guid| guid == protocol.protocol_guid())
+    }
+}
+
+impl<'a, B: BootServices> From<BootServicesBox<'a, [&'static efi::Guid], B>> for GuidBuffer<'a, B> {
+    fn from(guids: BootServicesBox<'a, [&'static efi::Guid], B>) -> Self {
+        Self(guids)
+    }
+}
+
+impl<B: BootServices> Deref for GuidBuffer<'_, B> {
+    type Target = [&'static efi::Guid];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'b, B: BootServices> IntoIterator for &'b GuidBuffer<'_, B> {
+    type Item = &'static efi::Guid;
+    type IntoIter = iter::Copied<slice::Iter<'b, &'static efi::Guid>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<B: BootServices> fmt::Debug for GuidBuffer<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter().map(ProtocolGuidDisplay)).finish()
+    }
+}
+
+impl<B: BootServices> fmt::Display for GuidBuffer<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, guid) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", ProtocolGuidDisplay(guid))?;
+        }
+        Ok(())
+    }
+}
+
+/// Display a protocol GUID as its name when it is well-known or in the registry format otherwise.
+struct ProtocolGuidDisplay<'a>(&'a efi::Guid);
+
+impl fmt::Display for ProtocolGuidDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = protocol_name(self.0) {
+            return f.write_str(name);
+        }
+        let (d1, d2, d3, d4, d5, node) = self.0.as_fields();
+        write!(f, "{d1:08X}-{d2:04X}-{d3:04X}-{d4:02X}{d5:02X}-")?;
+        node.iter().try_for_each(|b| write!(f, "{b:02X}"))
+    }
+}
+
+impl fmt::Debug for ProtocolGuidDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Error returned by [`BootServices::connect_drivers`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConnectError {
+    /// No driver was connected to the controller.
+    NoDriverConnected,
+    /// The connection failed with the given status.
+    Failed(efi::Status),
+}
+
+impl fmt::Debug for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::NoDriverConnected => f.write_str("NoDriverConnected"),
+            ConnectError::Failed(status) => f.debug_tuple("Failed").field(&status::StatusDisplay(*status)).finish(),
+        }
+    }
+}
+
+impl From<efi::Status> for ConnectError {
+    fn from(status: efi::Status) -> Self {
+        match status {
+            efi::Status::NOT_FOUND => ConnectError::NoDriverConnected,
+            status => ConnectError::Failed(status),
+        }
+    }
+}
+
+impl From<EfiError> for ConnectError {
+    fn from(error: EfiError) -> Self {
+        error.status().into()
+    }
+}
+
+impl From<ConnectError> for efi::Status {
+    fn from(error: ConnectError) -> Self {
+        match error {
+            ConnectError::NoDriverConnected => efi::Status::NOT_FOUND,
+            ConnectError::Failed(status) => status,
+        }
+    }
+}
+
+impl From<ConnectError> for EfiError {
+    fn from(error: ConnectError) -> Self {
+        EfiError::new(error.into()).with_operation("ConnectController")
+    }
+}
+
+/// Attributes used to open a protocol interface with [`OpenedProtocol::open`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct OpenProtocolAttribute(u32);
+
+impl OpenProtocolAttribute {
+    /// Used in the implementation of [`BootServices::handle_protocol`].
+    pub const BY_HANDLE_PROTOCOL: OpenProtocolAttribute = OpenProtocolAttribute(efi::OPEN_PROTOCOL_BY_HANDLE_PROTOCOL);
+
+    /// Used by a driver to get a protocol interface from a handle.
+    /// Care must be taken when using this open mode because the driver that opens a protocol interface in this manner
+    /// will not be informed if the protocol interface is uninstalled or reinstalled.
+    pub const GET_PROTOCOL: OpenProtocolAttribute = OpenProtocolAttribute(efi::OPEN_PROTOCOL_GET_PROTOCOL);
+
+    /// Used by a driver to test for the existence of a protocol interface on a handle.
+    pub const TEST_PROTOCOL: OpenProtocolAttribute = OpenProtocolAttribute(efi::OPEN_PROTOCOL_TEST_PROTOCOL);
+
+    /// Used by bus drivers to show that a protocol interface is being used by one of the child controllers of a bus.
+    pub const BY_CHILD_CONTROLLER: OpenProtocolAttribute =
+        OpenProtocolAttribute(efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER);
+
+    /// Used by a driver to gain access to a protocol interface.
+    /// When this mode is used, the driver’s Stop() function will be called by DisconnectController() if the protocol
+    /// interface is reinstalled or uninstalled.
+    pub const BY_DRIVER: OpenProtocolAttribute = OpenProtocolAttribute(efi::OPEN_PROTOCOL_BY_DRIVER);
+
+    /// Used by a driver to gain exclusive access to a protocol interface.
+    /// If any other drivers have the protocol interface opened with an attribute of [`Self::BY_DRIVER`],
+    /// then an attempt will be made to remove them with DisconnectController().
+    pub const EXCLUSIVE: OpenProtocolAttribute = OpenProtocolAttribute(efi::OPEN_PROTOCOL_EXCLUSIVE);
+
+    /// Returns true if all the attributes of `other` are set.
+    pub const fn contains(self, other: OpenProtocolAttribute) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for OpenProtocolAttribute {
+    type Output = OpenProtocolAttribute;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        OpenProtocolAttribute(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for OpenProtocolAttribute {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl From<OpenProtocolAttribute> for u32 {
+    fn from(value: OpenProtocolAttribute) -> Self {
+        value.0
+    }
+}
+
+impl From<u32> for OpenProtocolAttribute {
+    fn from(value: u32) -> Self {
+        OpenProtocolAttribute(value)
+    }
+}
+
+/// Agent that has a protocol interface opened, see [`OpenProtocolInformation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenProtocolInformationEntry {
+    /// The handle of the agent that opened the protocol.
+    pub agent_handle: efi::Handle,
+    /// The handle of the controller that required the protocol.
+    pub controller_handle: efi::Handle,
+    /// The attributes used to open the protocol.
+    pub attributes: OpenProtocolAttribute,
+    /// The number of times the protocol was opened by the agent with these attributes.
+    pub open_count: u32,
+}
+
+impl From<&efi::OpenProtocolInformationEntry> for OpenProtocolInformationEntry {
+    fn from(entry: &efi::OpenProtocolInformationEntry) -> Self {
+        Self {
+            agent_handle: entry.agent_handle,
+            controller_handle: entry.controller_handle,
+            attributes: entry.attributes.into(),
+            open_count: entry.open_count,
+        }
+    }
+}
+
+/// Buffer of the agents that have a protocol interface opened.
+///
+/// The buffer is owned and freed when dropped.
+pub struct OpenProtocolInformation<'a, B: BootServices>(BootServicesBox<'a, [efi::OpenProtocolInformationEntry], B>);
+
+impl<'a, B: BootServices> OpenProtocolInformation<'a, B> {
+    /// Retrieves the list of agents that currently have the protocol opened on the handle.
+    ///
+    /// See [`BootServices::open_protocol_information`] for more details.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn query<P: Protocol>(boot_services: &'a B, handle: efi::Handle, protocol: &P) -> Result<Self, EfiError> {
+        boot_services.open_protocol_information(handle, protocol.protocol_guid()).map(Self)
+    }
+
+    /// Returns an iterator over the entries.
+    pub fn iter(&self) -> impl Iterator<Item = OpenProtocolInformationEntry> + '_ {
+        self.0.iter().map(OpenProtocolInformationEntry::from)
+    }
+
+    /// Returns an iterator over the entries of the agents that have the protocol opened with
+    /// [`OpenProtocolAttribute::BY_DRIVER`], these drivers will have to be stopped to gain exclusive access.
+    pub fn by_driver(&self) -> impl Iterator<Item = OpenProtocolInformationEntry> + '_ {
+        self.iter().filter(|entry| entry.attributes.contains(OpenProtocolAttribute::BY_DRIVER))
+    }
+
+    /// Returns true if an agent has the protocol opened with [`OpenProtocolAttribute::EXCLUSIVE`].
+    pub fn is_opened_exclusively(&self) -> bool {
+        self.iter().any(|entry| entry.attributes.contains(OpenProtocolAttribute::EXCLUSIVE))
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if no agent has the protocol opened.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<B: BootServices> fmt::Debug for OpenProtocolInformation<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// RAII implementation of an opened protocol interface.
+/// When this structure is dropped, the protocol will be closed with [`BootServices::close_protocol`].
+///
+/// See [`OpenedProtocol::open`] for more details.
+#[must_use = "if unused the protocol will immediately be closed"]
+pub struct OpenedProtocol<'a, P: Protocol + 'static, B: BootServices> {
+    boot_services: &'a B,
+    protocol: &'static efi::Guid,
+    interface: NonNull<P::Interface>,
+    handle: efi::Handle,
+    agent_handle: efi::Handle,
+    controller_handle: efi::Handle,
+}
+
+impl<'a, P: Protocol + 'static, B: BootServices> OpenedProtocol<'a, P, B> {
+    /// Opens a protocol on a handle and returns an [`OpenedProtocol`] that will close the protocol when dropped.
+    ///
+    /// [`OpenProtocolAttribute::TEST_PROTOCOL`] is not supported since no interface is returned and nothing needs to
+    /// be closed, use [`BootServices::open_protocol`] instead.
+    ///
+    /// See [`BootServices::open_protocol`] and [`BootServices::close_protocol`] for more details.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn open(
+        boot_services: &'a B,
+        handle: efi::Handle,
+        protocol: &P,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+        attribute: OpenProtocolAttribute,
+    ) -> Result<Self, EfiError> {
+        if attribute == OpenProtocolAttribute::TEST_PROTOCOL {
+            debug_assert!(false, "TEST_PROTOCOL attribute is not supported by OpenedProtocol.");
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        //SAFETY: The generic Protocol ensure that the interfaces is the right type for the specified protocol.
+        let interface = unsafe {
+            boot_services.open_protocol_unchecked(
+                handle,
+                protocol,
+                agent_handle,
+                controller_handle,
+                attribute.into(),
+            )?
+        } as *mut P::Interface;
+        let interface = match NonNull::new(interface) {
+            Some(interface) => interface,
+            // A protocol with a zero sized interface is installed with a null interface.
+            None if mem::size_of::<P::Interface>() == 0 => NonNull::dangling(),
+            None => {
+                let _ = boot_services.close_protocol(handle, protocol, agent_handle, controller_handle);
+                return Err(efi::Status::UNSUPPORTED.into());
+            }
+        };
+        Ok(Self {
+            boot_services,
+            protocol: protocol.protocol_guid(),
+            interface,
+            handle,
+            agent_handle,
+            controller_handle,
+        })
+    }
+
+    /// The handle on which the protocol is opened.
+    pub fn handle(&self) -> efi::Handle {
+        self.handle
+    }
+
+    /// The handle of the agent that opened the protocol.
+    pub fn agent_handle(&self) -> efi::Handle {
+        self.agent_handle
+    }
+
+    /// The handle of the controller that required the protocol.
+    pub fn controller_handle(&self) -> efi::Handle {
+        self.controller_handle
+    }
+
+    /// Close the protocol, returning the status of [`BootServices::close_protocol`].
+    pub fn close(self) -> Result<(), EfiError> {
+        let this = mem::ManuallyDrop::new(self);
+        this.boot_services.close_protocol(this.handle, this.protocol, this.agent_handle, this.controller_handle)
+    }
+}
+
+impl<P: Protocol + 'static, B: BootServices> Drop for OpenedProtocol<'_, P, B> {
+    fn drop(&mut self) {
+        let _ =
+            self.boot_services.close_protocol(self.handle, self.protocol, self.agent_handle, self.controller_handle);
+    }
+}
+
+impl<P: Protocol + 'static, B: BootServices> Deref for OpenedProtocol<'_, P, B> {
+    type Target = P::Interface;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The interface pointer is valid until the protocol is closed, which happens when the guard is dropped.
+        unsafe { self.interface.as_ref() }
+    }
+}
+
+impl<P: Protocol + 'static, B: BootServices> DerefMut for OpenedProtocol<'_, P, B> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The interface pointer is valid until the protocol is closed, which happens when the guard is dropped.
+        unsafe { self.interface.as_mut() }
+    }
+}
+
+impl<P: Protocol + 'static, B: BootServices> fmt::Debug for OpenedProtocol<'_, P, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenedProtocol")
+            .field("protocol", self.protocol)
+            .field("interface", &self.interface)
+            .field("handle", &self.handle)
+            .field("agent_handle", &self.agent_handle)
+            .field("controller_handle", &self.controller_handle)
+            .finish()
+    }
+}
+
+/// Token of a protocol interface installed with [`InstalledProtocol::install`].
+///
+/// The installation owns the interface and keeps it at a stable address for as long as it is installed.
+/// Use [`InstalledProtocol::uninstall`] to remove the protocol and get back the interface.
+///
+/// <div class="warning">
+///
+/// If the token is dropped, the protocol stays installed and the interface is leaked.
+///
+/// </div>
+#[must_use = "if unused the interface will be leaked and the protocol will never be uninstalled"]
+pub struct InstalledProtocol<'a, P: Protocol + 'static, B: BootServices> {
+    boot_services: &'a B,
+    protocol: &'static efi::Guid,
+    interface: NonNull<P::Interface>,
+    handle: efi::Handle,
+}
+
+impl<'a, P: Protocol + 'static, B: BootServices> InstalledProtocol<'a, P, B> {
+    /// Installs a protocol interface on a device handle.
+    /// If the handle is `None`, a new handle is created.
+    ///
+    /// See [`BootServices::install_protocol_interface`] for more details.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn install(
+        boot_services: &'a B,
+        handle: Option<efi::Handle>,
+        protocol: &P,
+        interface: Box<P::Interface>,
+    ) -> Result<Self, EfiError> {
+        let interface = NonNull::from(Box::leak(interface));
+        //SAFETY: The generic Protocol ensure that the interface is the right type for the specified protocol.
+        let result = unsafe {
+            boot_services.install_protocol_interface_unchecked(
+                handle,
+                protocol.protocol_guid(),
+                Self::interface_ptr(interface),
+            )
+        };
+        match result {
+            Ok(handle) => Ok(Self { boot_services, protocol: protocol.protocol_guid(), interface, handle }),
+            Err(status) => {
+                //SAFETY: The interface was leaked from a box above and was not installed.
+                drop(unsafe { Box::from_raw(interface.as_ptr()) });
+                Err(status)
+            }
+        }
+    }
+
+    /// The handle on which the protocol is installed.
+    pub fn handle(&self) -> efi::Handle {
+        self.handle
+    }
+
+    /// Uninstalls the protocol interface and gives back the interface.
+    ///
+    /// On error, the protocol stays installed and the token is returned along with the error.
+    ///
+    /// See [`BootServices::uninstall_protocol_interface`] for more details.
+    pub fn uninstall(self) -> Result<Box<P::Interface>, (Self, EfiError)> {
+        //SAFETY: The interface is the one that was installed for this protocol.
+        let result = unsafe {
+            self.boot_services.uninstall_protocol_interface_unchecked(
+                self.handle,
+                self.protocol,
+                Self::interface_ptr(self.interface),
+            )
+        };
+        match result {
+            //SAFETY: The interface was leaked from a box at installation and is no longer referenced by the firmware.
+            Ok(()) => Ok(unsafe { Box::from_raw(self.interface.as_ptr()) }),
+            Err(status) => Err((self, status)),
+        }
+    }
+
+    /// Replaces the installed interface with a new one and gives back the previous interface.
+    ///
+    /// See [`BootServices::reinstall_protocol_interface`] for more details.
+    pub fn reinstall(&mut self, interface: Box<P::Interface>) -> Result<Box<P::Interface>, EfiError> {
+        let new_interface = NonNull::from(Box::leak(interface));
+        //SAFETY: Both interfaces are of the right type for the protocol.
+        let result = unsafe {
+            self.boot_services.reinstall_protocol_interface_unchecked(
+                self.handle,
+                self.protocol,
+                Self::interface_ptr(self.interface),
+                Self::interface_ptr(new_interface),
+            )
+        };
+        match result {
+            Ok(()) => {
+                let old_interface = mem::replace(&mut self.interface, new_interface);
+                //SAFETY: The old interface was leaked from a box and is no longer referenced by the firmware.
+                Ok(unsafe { Box::from_raw(old_interface.as_ptr()) })
+            }
+            Err(status) => {
+                //SAFETY: The new interface was leaked from a box above and was not installed.
+                drop(unsafe { Box::from_raw(new_interface.as_ptr()) });
+                Err(status)
+            }
+        }
+    }
+
+    /// Leaks the installation, the protocol will stay installed for the rest of the boot.
+    pub fn leak(self) -> &'static P::Interface {
+        let this = mem::ManuallyDrop::new(self);
+        //SAFETY: The interface is leaked and will never be freed.
+        unsafe { this.interface.as_ref() }
+    }
+
+    fn interface_ptr(interface: NonNull<P::Interface>) -> *mut c_void {
+        // A protocol with a zero sized interface is installed with a null interface.
+        if mem::size_of::<P::Interface>() == 0 {
+            ptr::null_mut()
+        } else {
+            interface.as_ptr() as *mut c_void
+        }
+    }
+}
+
+impl<P: Protocol + 'static, B: BootServices> Deref for InstalledProtocol<'_, P, B> {
+    type Target = P::Interface;
+
+    fn deref(&self) -> &Self::Target {
+        //SAFETY: The interface is owned by the installation.
+        unsafe { self.interface.as_ref() }
+    }
+}
+
+impl<P: Protocol + 'static, B: BootServices> fmt::Debug for InstalledProtocol<'_, P, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstalledProtocol")
+            .field("protocol", self.protocol)
+            .field("interface", &self.interface)
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+/// Protocol notification registered with [`ProtocolNotify::register`].
+///
+/// When this structure is dropped, the notification event is closed and the callback is freed.
+#[must_use = "if unused the notification will immediately be canceled"]
+pub struct ProtocolNotify<B: BootServices + 'static> {
+    boot_services: &'static B,
+    event: efi::Event,
+    context: *mut c_void,
+    free_context: unsafe fn(*mut c_void),
+}
+
+struct ProtocolNotifyContext<P: Protocol + 'static, B: BootServices + 'static, F> {
+    boot_services: &'static B,
+    protocol: &'static efi::Guid,
+    registration: Option<Registration>,
+    callback: F,
+    _protocol: PhantomData<P>,
+}
+
+impl<B: BootServices + 'static> ProtocolNotify<B> {
+    /// Registers a callback that is called for every handle on which the protocol gets installed.
+    ///
+    /// The notification event is signaled once after the registration so the callback is also called for the handles
+    /// on which the protocol is already installed.
+    ///
+    /// See [`BootServices::register_protocol_notify`] and [`HandleSearchType::ByRegisterNotify`] for more details.
+    pub fn register<P, F>(
+        boot_services: &'static B,
+        protocol: &P,
+        notify_tpl: Tpl,
+        callback: F,
+    ) -> Result<Self, EfiError>
+    where
+        P: Protocol + 'static,
+        F: FnMut(efi::Handle, &'static mut P::Interface) + 'static,
+    {
+        let context = Box::into_raw(Box::new(ProtocolNotifyContext::<P, B, F> {
+            boot_services,
+            protocol: protocol.protocol_guid(),
+            registration: None,
+            callback,
+            _protocol: PhantomData,
+        }));
+
+        //SAFETY: The context is valid until the event is closed in drop.
+        let event = match unsafe {
+            boot_services.create_event_unchecked(
+                EventType::NOTIFY_SIGNAL,
+                notify_tpl,
+                Some(Self::notify::<P, F>),
+                context,
+            )
+        } {
+            Ok(event) => event,
+            Err(status) => {
+                //SAFETY: The context was not given to any event.
+                drop(unsafe { Box::from_raw(context) });
+                return Err(status);
+            }
+        };
+
+        let protocol_notify =
+            Self { boot_services, event, context: context as *mut c_void, free_context: Self::free_context::<P, F> };
+
+        let registration = boot_services.register_protocol_notify(protocol.protocol_guid(), event)?;
+        //SAFETY: The event has not been signaled yet, so nothing else is referencing the context.
+        unsafe { (*context).registration = Some(registration) };
+
+        boot_services.signal_event(event)?;
+        Ok(protocol_notify)
+    }
+
+    /// The notification event.
+    pub fn event(&self) -> efi::Event {
+        self.event
+    }
+
+    /// Leaks the notification, the callback will be called for the rest of the boot.
+    pub fn leak(self) {
+        mem::forget(self);
+    }
+
+    extern "efiapi" fn notify<P, F>(_event: efi::Event, context: *mut ProtocolNotifyContext<P, B, F>)
+    where
+        P: Protocol + 'static,
+        F: FnMut(efi::Handle, &'static mut P::Interface) + 'static,
+    {
+        //SAFETY: The context is valid for the lifetime of the event.
+        let context = unsafe { &mut *context };
+        let Some(registration) = context.registration else {
+            return;
+        };
+        // Each call returns the handles that are new since the last call until none are left.
+        while let Ok(handles) = context.boot_services.locate_handle(HandleSearchType::ByRegisterNotify(registration)) {
+            for &handle in handles.iter() {
+                //SAFETY: The generic Protocol ensure that the interface is the right type for the specified protocol.
+                let Ok(interface) =
+                    (unsafe { context.boot_services.handle_protocol_unchecked(handle, context.protocol) })
+                else {
+                    continue;
+                };
+                let interface = match NonNull::new(interface as *mut P::Interface) {
+                    Some(interface) => interface,
+                    // A protocol with a zero sized interface is installed with a null interface.
+                    None if mem::size_of::<P::Interface>() == 0 => NonNull::dangling(),
+                    None => continue,
+                };
+                //SAFETY: The interface is valid for as long as the protocol is installed.
+                (context.callback)(handle, unsafe { &mut *interface.as_ptr() });
+            }
+        }
+    }
+
+    unsafe fn free_context<P, F>(context: *mut c_void)
+    where
+        P: Protocol + 'static,
+        F: FnMut(efi::Handle, &'static mut P::Interface) + 'static,
+    {
+        drop(Box::from_raw(context as *mut ProtocolNotifyContext<P, B, F>));
+    }
+}
+
+impl<B: BootServices + 'static> Drop for ProtocolNotify<B> {
+    fn drop(&mut self) {
+        // The event is closed first so the callback can not be called once the context is freed.
+        if self.boot_services.close_event(self.event).is_ok() {
+            //SAFETY: The context is no longer referenced by the event.
+            unsafe { (self.free_context)(self.context) };
+        }
+    }
+}
+
+impl<B: BootServices + 'static> fmt::Debug for ProtocolNotify<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolNotify").field("event", &self.event).finish()
+    }
+}
+
+/// Converts a handle of the `uefi` crate into a handle of the boot services.
+#[cfg(feature = "uefi")]
+pub fn handle_from_uefi(handle: uefi::Handle) -> efi::Handle {
+    handle.as_ptr()
+}
+
+/// Converts a handle of the boot services into a handle of the `uefi` crate, `None` if it is null.
+#[cfg(feature = "uefi")]
+#[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+pub fn handle_to_uefi(handle: efi::Handle) -> Option<uefi::Handle> {
+    //SAFETY: The handle is opaque, the uefi crate only gives it back to the firmware.
+    unsafe { uefi::Handle::from_ptr(handle) }
+}
+
 macro_rules! impl_protocol {
     ($protocol_struct:ident, $protocol_type:ty, $guid:expr) => {
         pub struct $protocol_struct;
@@ -62,6 +796,7 @@ impl_r_efi_protocol!(DebugPort, debugport);
 impl_r_efi_protocol!(Decompress, decompress);
 impl_r_efi_protocol!(DevicePath, device_path);
 impl_r_efi_protocol!(DevicePathFromText, device_path_from_text);
+impl_r_efi_protocol!(DevicePathToText, device_path_to_text);
 impl_r_efi_protocol!(DevicePathUtilities, device_path_utilities);
 impl_r_efi_protocol!(DiskIo, disk_io);
 impl_r_efi_protocol!(DiskIo2, disk_io2);
@@ -86,6 +821,7 @@ impl_protocol!(
     efi::protocols::loaded_image_device_path::PROTOCOL_GUID
 );
 impl_r_efi_protocol!(ManagedNetwork, managed_network);
+impl_r_efi_protocol!(MemoryAttribute, memory_attribute);
 impl_r_efi_protocol!(MpService, mp_services);
 impl_r_efi_protocol!(PciIo, pci_io);
 impl_r_efi_protocol!(PlatformDriverOverride, platform_driver_override);
@@ -104,3 +840,336 @@ impl_r_efi_protocol!(Tcp6, tcp6);
 impl_r_efi_protocol!(Timerstamp, timestamp);
 impl_r_efi_protocol!(Udp4, udp4);
 impl_r_efi_protocol!(Udp6, udp6);
+
+/// Returns the EDK2 name of a well-known protocol from its GUID, like `gEfiDevicePathProtocolGuid`.
+pub fn protocol_name(guid: &efi::Guid) -> Option<&'static str> {
+    guid::well_known::lookup_name(&guid::Guid::new(*guid))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::EventNotifyCallback, MockBootServices};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    struct TestProtocol;
+    unsafe impl Protocol for TestProtocol {
+        type Interface = u32;
+        fn protocol_guid(&self) -> &'static efi::Guid {
+            static GUID: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+            &GUID
+        }
+    }
+    impl Deref for TestProtocol {
+        type Target = efi::Guid;
+        fn deref(&self) -> &Self::Target {
+            self.protocol_guid()
+        }
+    }
+
+    #[test]
+    fn test_opened_protocol_is_closed_on_drop() {
+        static mut INTERFACE: u32 = 42;
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_open_protocol_unchecked()
+            .withf(|handle, protocol, agent_handle, controller_handle, attribute| {
+                *handle == 1_usize as efi::Handle
+                    && protocol == TestProtocol.protocol_guid()
+                    && *agent_handle == 2_usize as efi::Handle
+                    && *controller_handle == 3_usize as efi::Handle
+                    && *attribute == efi::OPEN_PROTOCOL_BY_DRIVER | efi::OPEN_PROTOCOL_EXCLUSIVE
+            })
+            .once()
+            .returning(|_, _, _, _, _| Ok(unsafe { ptr::addr_of_mut!(INTERFACE) } as *mut c_void));
+        boot_services
+            .expect_close_protocol()
+            .withf(|handle, protocol, agent_handle, controller_handle| {
+                *handle == 1_usize as efi::Handle
+                    && protocol == TestProtocol.protocol_guid()
+                    && *agent_handle == 2_usize as efi::Handle
+                    && *controller_handle == 3_usize as efi::Handle
+            })
+            .once()
+            .returning(|_, _, _, _| Ok(()));
+
+        let mut opened = OpenedProtocol::open(
+            &boot_services,
+            1_usize as efi::Handle,
+            &TestProtocol,
+            2_usize as efi::Handle,
+            3_usize as efi::Handle,
+            OpenProtocolAttribute::BY_DRIVER | OpenProtocolAttribute::EXCLUSIVE,
+        )
+        .unwrap();
+        assert_eq!(42, *opened);
+        *opened = 43;
+        drop(opened);
+        assert_eq!(43, unsafe { INTERFACE });
+    }
+
+    #[test]
+    fn test_opened_protocol_close() {
+        static mut INTERFACE: u32 = 0;
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_open_protocol_unchecked()
+            .returning(|_, _, _, _, _| Ok(unsafe { ptr::addr_of_mut!(INTERFACE) } as *mut c_void));
+        boot_services.expect_close_protocol().once().returning(|_, _, _, _| Err(efi::Status::NOT_FOUND.into()));
+
+        let opened = OpenedProtocol::open(
+            &boot_services,
+            1_usize as efi::Handle,
+            &TestProtocol,
+            2_usize as efi::Handle,
+            ptr::null_mut(),
+            OpenProtocolAttribute::GET_PROTOCOL,
+        )
+        .unwrap();
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), opened.close());
+    }
+
+    #[test]
+    fn test_opened_protocol_open_error() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_open_protocol_unchecked().returning(|_, _, _, _, _| Err(efi::Status::UNSUPPORTED.into()));
+        boot_services.expect_close_protocol().never();
+
+        let opened = OpenedProtocol::open(
+            &boot_services,
+            1_usize as efi::Handle,
+            &TestProtocol,
+            2_usize as efi::Handle,
+            ptr::null_mut(),
+            OpenProtocolAttribute::BY_DRIVER,
+        );
+        assert!(matches!(opened, Err(error) if error == efi::Status::UNSUPPORTED));
+    }
+
+    #[test]
+    fn test_installed_protocol_uninstall() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_install_protocol_interface_unchecked()
+            .withf(|handle, protocol, interface| {
+                handle.is_none()
+                    && *protocol == *TestProtocol.protocol_guid()
+                    && unsafe { *(*interface as *mut u32) } == 42
+            })
+            .once()
+            .returning(|_, _, _| Ok(1_usize as efi::Handle));
+        boot_services
+            .expect_uninstall_protocol_interface_unchecked()
+            .withf(|handle, protocol, interface| {
+                *handle == 1_usize as efi::Handle
+                    && *protocol == *TestProtocol.protocol_guid()
+                    && unsafe { *(*interface as *mut u32) } == 42
+            })
+            .times(2)
+            .returning({
+                let mut attempt = 0;
+                move |_, _, _| {
+                    attempt += 1;
+                    match attempt {
+                        1 => Err(efi::Status::ACCESS_DENIED.into()),
+                        _ => Ok(()),
+                    }
+                }
+            });
+
+        let installed = InstalledProtocol::install(&boot_services, None, &TestProtocol, Box::new(42)).unwrap();
+        assert_eq!(1_usize as efi::Handle, installed.handle());
+        assert_eq!(42, *installed);
+
+        let Err((installed, status)) = installed.uninstall() else { panic!("First uninstall should fail.") };
+        assert_eq!(efi::Status::ACCESS_DENIED, status);
+        assert_eq!(Box::new(42), installed.uninstall().unwrap());
+    }
+
+    #[test]
+    fn test_installed_protocol_reinstall() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_install_protocol_interface_unchecked().returning(|_, _, _| Ok(1_usize as efi::Handle));
+        boot_services
+            .expect_reinstall_protocol_interface_unchecked()
+            .withf(|handle, _, old_interface, new_interface| {
+                *handle == 1_usize as efi::Handle
+                    && unsafe { *(*old_interface as *mut u32) } == 1
+                    && unsafe { *(*new_interface as *mut u32) } == 2
+            })
+            .once()
+            .returning(|_, _, _, _| Ok(()));
+
+        let mut installed =
+            InstalledProtocol::install(&boot_services, Some(1_usize as efi::Handle), &TestProtocol, Box::new(1))
+                .unwrap();
+        assert_eq!(Box::new(1), installed.reinstall(Box::new(2)).unwrap());
+        assert_eq!(2, *installed.leak());
+    }
+
+    #[test]
+    fn test_installed_protocol_install_error() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_install_protocol_interface_unchecked()
+            .returning(|_, _, _| Err(efi::Status::INVALID_PARAMETER.into()));
+
+        let installed = InstalledProtocol::install(&boot_services, None, &TestProtocol, Box::new(1));
+        assert!(matches!(installed, Err(error) if error == efi::Status::INVALID_PARAMETER));
+    }
+
+    #[test]
+    fn test_guid_buffer_display() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_free_pool().once().returning(|_| Ok(()));
+
+        let guids = Box::leak(Box::new([DevicePath.protocol_guid(), TestProtocol.protocol_guid()]));
+        let guids = GuidBuffer::from(unsafe {
+            BootServicesBox::from_raw_parts(guids.as_mut_ptr(), guids.len(), &boot_services)
+        });
+
+        assert!(guids.contains(&DevicePath));
+        assert!(guids.contains(&TestProtocol));
+        assert!(!guids.contains(&LoadedImage));
+        assert_eq!(Some("gEfiLoadedImageDevicePathProtocolGuid"), protocol_name(&LoadedImageDevicePath));
+        assert_eq!("gEfiDevicePathProtocolGuid, 00000001-0002-0003-0405-060606060606", format!("{guids}"));
+        assert_eq!("[gEfiDevicePathProtocolGuid, 00000001-0002-0003-0405-060606060606]", format!("{guids:?}"));
+    }
+
+    #[test]
+    fn test_open_protocol_information() {
+        let mut free_boot_services = MockBootServices::new();
+        free_boot_services.expect_free_pool().once().returning(|_| Ok(()));
+        let free_boot_services: &'static MockBootServices = Box::leak(Box::new(free_boot_services));
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_open_protocol_information()
+            .withf(|handle, protocol| *handle == 1_usize as efi::Handle && protocol == TestProtocol.protocol_guid())
+            .once()
+            .returning(|_, _| {
+                let entries = Box::leak(Box::new([
+                    efi::OpenProtocolInformationEntry {
+                        agent_handle: 2_usize as efi::Handle,
+                        controller_handle: 1_usize as efi::Handle,
+                        attributes: efi::OPEN_PROTOCOL_BY_DRIVER,
+                        open_count: 1,
+                    },
+                    efi::OpenProtocolInformationEntry {
+                        agent_handle: 3_usize as efi::Handle,
+                        controller_handle: ptr::null_mut(),
+                        attributes: efi::OPEN_PROTOCOL_GET_PROTOCOL,
+                        open_count: 2,
+                    },
+                ]));
+                Ok(unsafe { BootServicesBox::from_raw_parts(entries.as_mut_ptr(), entries.len(), free_boot_services) })
+            });
+
+        let information =
+            OpenProtocolInformation::query(&boot_services, 1_usize as efi::Handle, &TestProtocol).unwrap();
+        assert_eq!(2, information.len());
+        assert!(!information.is_opened_exclusively());
+        assert_eq!(
+            vec![OpenProtocolInformationEntry {
+                agent_handle: 2_usize as efi::Handle,
+                controller_handle: 1_usize as efi::Handle,
+                attributes: OpenProtocolAttribute::BY_DRIVER,
+                open_count: 1,
+            }],
+            information.by_driver().collect::<Vec<_>>()
+        );
+        assert_eq!(3, information.iter().map(|entry| entry.open_count).sum::<u32>());
+    }
+
+    #[test]
+    fn test_protocol_notify() {
+        type Callback = fn(efi::Handle, &'static mut u32);
+        type Context = ProtocolNotifyContext<TestProtocol, MockBootServices, Callback>;
+
+        static NOTIFY: AtomicUsize = AtomicUsize::new(0);
+        static CONTEXT: AtomicUsize = AtomicUsize::new(0);
+        static NOTIFIED: AtomicUsize = AtomicUsize::new(0);
+        static mut INTERFACE: u32 = 42;
+
+        let mut free_boot_services = MockBootServices::new();
+        free_boot_services.expect_free_pool().returning(|_| Ok(()));
+        let free_boot_services: &'static MockBootServices = Box::leak(Box::new(free_boot_services));
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_create_event_unchecked::<Context>()
+            .withf(|event_type, notify_tpl, notify, _| {
+                *event_type == EventType::NOTIFY_SIGNAL && *notify_tpl == Tpl::CALLBACK && notify.is_some()
+            })
+            .once()
+            .returning(|_, _, notify, context| {
+                NOTIFY.store(notify.unwrap() as usize, Ordering::SeqCst);
+                CONTEXT.store(context as usize, Ordering::SeqCst);
+                Ok(1_usize as efi::Event)
+            });
+        boot_services
+            .expect_register_protocol_notify()
+            .withf(|protocol, event| *protocol == *TestProtocol.protocol_guid() && *event == 1_usize as efi::Event)
+            .once()
+            .returning(|_, _| Ok(NonNull::dangling()));
+        boot_services.expect_signal_event().once().returning(|event| {
+            let notify: EventNotifyCallback<*mut Context> = unsafe { mem::transmute(NOTIFY.load(Ordering::SeqCst)) };
+            notify(event, CONTEXT.load(Ordering::SeqCst) as *mut Context);
+            Ok(())
+        });
+        boot_services.expect_locate_handle().times(2).returning({
+            let mut call = 0;
+            move |search_type| {
+                assert!(matches!(search_type, HandleSearchType::ByRegisterNotify(_)));
+                call += 1;
+                if call > 1 {
+                    return Err(efi::Status::NOT_FOUND.into());
+                }
+                let handles = Box::leak(Box::new([2_usize as efi::Handle, 3_usize as efi::Handle]));
+                Ok(unsafe { BootServicesBox::from_raw_parts(handles.as_mut_ptr(), handles.len(), free_boot_services) })
+            }
+        });
+        boot_services.expect_handle_protocol_unchecked().times(2).returning(|handle, _| match handle as usize {
+            2 => Ok(unsafe { ptr::addr_of_mut!(INTERFACE) } as *mut c_void),
+            _ => Err(efi::Status::UNSUPPORTED.into()),
+        });
+        boot_services.expect_close_event().withf(|event| *event == 1_usize as efi::Event).once().returning(|_| Ok(()));
+        let boot_services: &'static MockBootServices = Box::leak(Box::new(boot_services));
+
+        let callback: Callback = |handle, interface| {
+            assert_eq!(2_usize as efi::Handle, handle);
+            assert_eq!(42, *interface);
+            NOTIFIED.fetch_add(1, Ordering::SeqCst);
+        };
+        let protocol_notify = ProtocolNotify::register(boot_services, &TestProtocol, Tpl::CALLBACK, callback).unwrap();
+        assert_eq!(1, NOTIFIED.load(Ordering::SeqCst));
+        assert_eq!(1_usize as efi::Event, protocol_notify.event());
+        drop(protocol_notify);
+    }
+
+    #[test]
+    #[should_panic = "TEST_PROTOCOL attribute is not supported by OpenedProtocol."]
+    fn test_opened_protocol_with_test_protocol_attribute() {
+        let boot_services = MockBootServices::new();
+        let _ = OpenedProtocol::open(
+            &boot_services,
+            1_usize as efi::Handle,
+            &TestProtocol,
+            2_usize as efi::Handle,
+            ptr::null_mut(),
+            OpenProtocolAttribute::TEST_PROTOCOL,
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "uefi")]
+    fn test_handle_uefi_conversion() {
+        let handle = 0x1000_usize as efi::Handle;
+        let uefi_handle = handle_to_uefi(handle).unwrap();
+        assert_eq!(handle, uefi_handle.as_ptr());
+        assert_eq!(handle, handle_from_uefi(uefi_handle));
+        assert_eq!(None, handle_to_uefi(ptr::null_mut()));
+    }
+}
@@ -0,0 +1,693 @@
+//! Call-recording boot services, to verify the sequence of boot services a component performs and exercise its
+//! error paths.
+//!
+//! [`RecordingBootServices`] forwards every call to the boot services it wraps and records it with its arguments:
+//!
+//! ```ignore
+//! let boot_services = RecordingBootServices::new(StandardBootServices::new(efi_boot_services));
+//! component.start(&boot_services)?;
+//! boot_services.assert_called_in_order(&[
+//!     BootServicesCall::AllocatePool { pool_type: MemoryType::BOOT_SERVICES_DATA, size: 0x20 },
+//!     BootServicesCall::InstallConfigurationTable { guid: TABLE_GUID, table: table_ptr },
+//! ]);
+//! ```
+//!
+//! Faults make a given call fail deterministically, like the second allocation of the component:
+//!
+//! ```ignore
+//! let is_allocate_pool = |call: &BootServicesCall| matches!(call, BootServicesCall::AllocatePool { .. });
+//! boot_services.inject_fault(is_allocate_pool, 2, efi::Status::OUT_OF_RESOURCES);
+//! assert_eq!(Err(efi::Status::OUT_OF_RESOURCES.into()), component.start(&boot_services));
+//! ```
+//!
+//! Only the services implemented by each [`BootServices`] are recorded, the helpers they are called through, like
+//! [`BootServices::locate_protocol`] for [`BootServices::locate_protocol_unchecked`], are not.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{cell::RefCell, ffi::c_void, fmt};
+
+use r_efi::efi;
+use status::EfiError;
+
+use crate::{
+    allocation::{AllocType, MemoryMap, MemoryType},
+    boxed::BootServicesBox,
+    event::{EventNotifyCallback, EventTimerType, EventType},
+    protocol_handler::{HandleSearchType, Registration},
+    tpl::Tpl,
+    BootServices,
+};
+
+/// A call to the boot services, with its arguments.
+///
+/// Pointers are recorded as addresses, the memory they point to is not copied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootServicesCall {
+    CreateEvent {
+        event_type: EventType,
+        notify_tpl: Tpl,
+        notify_context: *mut c_void,
+    },
+    CreateEventEx {
+        event_type: EventType,
+        notify_tpl: Tpl,
+        notify_context: *mut c_void,
+        event_group: efi::Guid,
+    },
+    CloseEvent {
+        event: efi::Event,
+    },
+    SignalEvent {
+        event: efi::Event,
+    },
+    WaitForEvent {
+        events: Vec<efi::Event>,
+    },
+    CheckEvent {
+        event: efi::Event,
+    },
+    SetTimer {
+        event: efi::Event,
+        timer_type: EventTimerType,
+        trigger_time: u64,
+    },
+    RaiseTpl {
+        tpl: Tpl,
+    },
+    RestoreTpl {
+        tpl: Tpl,
+    },
+    AllocatePages {
+        alloc_type: AllocType,
+        memory_type: MemoryType,
+        nb_pages: usize,
+    },
+    FreePages {
+        address: usize,
+        nb_pages: usize,
+    },
+    GetMemoryMap,
+    AllocatePool {
+        pool_type: MemoryType,
+        size: usize,
+    },
+    FreePool {
+        buffer: *mut u8,
+    },
+    InstallProtocolInterface {
+        handle: Option<efi::Handle>,
+        protocol: efi::Guid,
+        interface: *mut c_void,
+    },
+    UninstallProtocolInterface {
+        handle: efi::Handle,
+        protocol: efi::Guid,
+        interface: *mut c_void,
+    },
+    ReinstallProtocolInterface {
+        handle: efi::Handle,
+        protocol: efi::Guid,
+        old_protocol_interface: *mut c_void,
+        new_protocol_interface: *mut c_void,
+    },
+    RegisterProtocolNotify {
+        protocol: efi::Guid,
+        event: efi::Event,
+    },
+    LocateHandle {
+        search_type: HandleSearchType,
+    },
+    HandleProtocol {
+        handle: efi::Handle,
+        protocol: efi::Guid,
+    },
+    LocateDevicePath {
+        protocol: efi::Guid,
+        device_path: *mut *mut efi::protocols::device_path::Protocol,
+    },
+    OpenProtocol {
+        handle: efi::Handle,
+        protocol: efi::Guid,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+        attribute: u32,
+    },
+    CloseProtocol {
+        handle: efi::Handle,
+        protocol: efi::Guid,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+    },
+    OpenProtocolInformation {
+        handle: efi::Handle,
+        protocol: efi::Guid,
+    },
+    ConnectController {
+        controller_handle: efi::Handle,
+        driver_image_handle: Vec<efi::Handle>,
+        remaining_device_path: *mut efi::protocols::device_path::Protocol,
+        recursive: bool,
+    },
+    DisconnectController {
+        controller_handle: efi::Handle,
+        driver_image_handle: Option<efi::Handle>,
+        child_handle: Option<efi::Handle>,
+    },
+    ProtocolsPerHandle {
+        handle: efi::Handle,
+    },
+    LocateHandleBuffer {
+        search_type: HandleSearchType,
+    },
+    LocateProtocol {
+        protocol: efi::Guid,
+        registration: *mut c_void,
+    },
+    InstallConfigurationTable {
+        guid: efi::Guid,
+        table: *mut c_void,
+    },
+    Stall {
+        microseconds: usize,
+    },
+    GetNextMonotonicCount,
+    Exit {
+        image_handle: efi::Handle,
+        exit_status: efi::Status,
+        exit_data_size: usize,
+    },
+}
+
+/// A fault injected in the calls matching a predicate.
+struct Fault {
+    predicate: Box<dyn Fn(&BootServicesCall) -> bool>,
+    /// The number of matching calls until the fault, the last one fails.
+    remaining: usize,
+    status: efi::Status,
+}
+
+impl fmt::Debug for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fault")
+            .field("remaining", &self.remaining)
+            .field("status", &self.status)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Boot services recording every call before forwarding it to the boot services they wrap, unless a fault is
+/// injected in it.
+#[derive(Debug)]
+pub struct RecordingBootServices<B: BootServices> {
+    boot_services: B,
+    calls: RefCell<Vec<BootServicesCall>>,
+    faults: RefCell<Vec<Fault>>,
+}
+
+impl<B: BootServices> RecordingBootServices<B> {
+    pub fn new(boot_services: B) -> Self {
+        Self { boot_services, calls: RefCell::new(Vec::new()), faults: RefCell::new(Vec::new()) }
+    }
+
+    /// The wrapped boot services.
+    pub fn inner(&self) -> &B {
+        &self.boot_services
+    }
+
+    pub fn into_inner(self) -> B {
+        self.boot_services
+    }
+
+    /// The calls recorded so far, oldest first.
+    pub fn calls(&self) -> Vec<BootServicesCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// Forgets the calls recorded so far.
+    pub fn clear(&self) {
+        self.calls.borrow_mut().clear()
+    }
+
+    /// The number of recorded calls matching `predicate`.
+    pub fn count(&self, predicate: impl Fn(&BootServicesCall) -> bool) -> usize {
+        self.calls.borrow().iter().filter(|call| predicate(call)).count()
+    }
+
+    /// Panics unless `call` was recorded exactly once.
+    #[track_caller]
+    pub fn assert_called_once_with(&self, call: &BootServicesCall) {
+        let count = self.count(|recorded| recorded == call);
+        assert!(count == 1, "expected {call:?} to be called once, got {count} times in {:#?}", self.calls.borrow());
+    }
+
+    /// Panics unless `calls` were recorded in this order, other calls may come between them.
+    #[track_caller]
+    pub fn assert_called_in_order(&self, calls: &[BootServicesCall]) {
+        let recorded = self.calls.borrow();
+        let mut remaining = recorded.iter();
+        for call in calls {
+            assert!(remaining.any(|recorded| recorded == call), "expected {call:?} in order in {recorded:#?}");
+        }
+    }
+
+    /// Fails the `nth` call matching `predicate` from now on with `status`, without forwarding it.
+    ///
+    /// The status is returned as the error of the call, warnings included. Calls that cannot fail ignore faults.
+    pub fn inject_fault(
+        &self,
+        predicate: impl Fn(&BootServicesCall) -> bool + 'static,
+        nth: usize,
+        status: efi::Status,
+    ) {
+        assert!(nth > 0, "calls are counted from 1");
+        self.faults.borrow_mut().push(Fault { predicate: Box::new(predicate), remaining: nth, status });
+    }
+
+    fn record(&self, call: BootServicesCall) {
+        self.calls.borrow_mut().push(call)
+    }
+
+    /// Records the call, then returns the status of the first fault it triggers.
+    fn intercept(&self, call: BootServicesCall) -> Result<(), efi::Status> {
+        let mut status = None;
+        self.faults.borrow_mut().retain_mut(|fault| {
+            if !(fault.predicate)(&call) {
+                return true;
+            }
+            fault.remaining -= 1;
+            if fault.remaining == 0 {
+                status = status.or(Some(fault.status));
+            }
+            fault.remaining > 0
+        });
+        self.record(call);
+        status.map_or(Ok(()), Err)
+    }
+
+    /// Hands a buffer allocated by the wrapped boot services to the recording ones, which free it when dropped.
+    fn rebox<'a, T>(&'a self, buffer: BootServicesBox<'a, [T], B>) -> BootServicesBox<'a, [T], Self> {
+        let buffer = buffer.leak();
+        //SAFETY: The buffer was allocated by the wrapped boot services, which free it for the recording ones.
+        unsafe { BootServicesBox::from_raw_parts(buffer.as_mut_ptr(), buffer.len(), self) }
+    }
+}
+
+impl<B: BootServices> BootServices for RecordingBootServices<B> {
+    unsafe fn create_event_unchecked<T: Sized + 'static>(
+        &self,
+        event_type: EventType,
+        notify_tpl: Tpl,
+        notify_function: Option<EventNotifyCallback<*mut T>>,
+        notify_context: *mut T,
+    ) -> Result<efi::Event, EfiError> {
+        self.intercept(BootServicesCall::CreateEvent {
+            event_type,
+            notify_tpl,
+            notify_context: notify_context as *mut c_void,
+        })?;
+        self.boot_services.create_event_unchecked(event_type, notify_tpl, notify_function, notify_context)
+    }
+
+    unsafe fn create_event_ex_unchecked<T: Sized + 'static>(
+        &self,
+        event_type: EventType,
+        notify_tpl: Tpl,
+        notify_function: EventNotifyCallback<*mut T>,
+        notify_context: *mut T,
+        event_group: &'static efi::Guid,
+    ) -> Result<efi::Event, EfiError> {
+        self.intercept(BootServicesCall::CreateEventEx {
+            event_type,
+            notify_tpl,
+            notify_context: notify_context as *mut c_void,
+            event_group: *event_group,
+        })?;
+        self.boot_services.create_event_ex_unchecked(
+            event_type,
+            notify_tpl,
+            notify_function,
+            notify_context,
+            event_group,
+        )
+    }
+
+    fn close_event(&self, event: efi::Event) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::CloseEvent { event })?;
+        self.boot_services.close_event(event)
+    }
+
+    fn signal_event(&self, event: efi::Event) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::SignalEvent { event })?;
+        self.boot_services.signal_event(event)
+    }
+
+    fn wait_for_event(&self, events: &mut [efi::Event]) -> Result<usize, EfiError> {
+        self.intercept(BootServicesCall::WaitForEvent { events: events.to_vec() })?;
+        self.boot_services.wait_for_event(events)
+    }
+
+    fn check_event(&self, event: efi::Event) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::CheckEvent { event })?;
+        self.boot_services.check_event(event)
+    }
+
+    fn set_timer(&self, event: efi::Event, timer_type: EventTimerType, trigger_time: u64) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::SetTimer { event, timer_type, trigger_time })?;
+        self.boot_services.set_timer(event, timer_type, trigger_time)
+    }
+
+    fn raise_tpl(&self, tpl: Tpl) -> Tpl {
+        self.record(BootServicesCall::RaiseTpl { tpl });
+        self.boot_services.raise_tpl(tpl)
+    }
+
+    fn restore_tpl(&self, tpl: Tpl) {
+        self.record(BootServicesCall::RestoreTpl { tpl });
+        self.boot_services.restore_tpl(tpl)
+    }
+
+    fn allocate_pages(
+        &self,
+        alloc_type: AllocType,
+        memory_type: MemoryType,
+        nb_pages: usize,
+    ) -> Result<usize, EfiError> {
+        self.intercept(BootServicesCall::AllocatePages { alloc_type, memory_type, nb_pages })?;
+        self.boot_services.allocate_pages(alloc_type, memory_type, nb_pages)
+    }
+
+    fn free_pages(&self, address: usize, nb_pages: usize) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::FreePages { address, nb_pages })?;
+        self.boot_services.free_pages(address, nb_pages)
+    }
+
+    fn get_memory_map(&self) -> Result<MemoryMap<'_, Self>, (EfiError, usize)> {
+        self.intercept(BootServicesCall::GetMemoryMap).map_err(|status| (status.into(), 0))?;
+        let memory_map = self.boot_services.get_memory_map()?;
+        Ok(MemoryMap {
+            descriptors: self.rebox(memory_map.descriptors),
+            map_key: memory_map.map_key,
+            descriptor_version: memory_map.descriptor_version,
+        })
+    }
+
+    fn allocate_pool(&self, pool_type: MemoryType, size: usize) -> Result<*mut u8, EfiError> {
+        self.intercept(BootServicesCall::AllocatePool { pool_type, size })?;
+        self.boot_services.allocate_pool(pool_type, size)
+    }
+
+    fn free_pool(&self, buffer: *mut u8) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::FreePool { buffer })?;
+        self.boot_services.free_pool(buffer)
+    }
+
+    unsafe fn install_protocol_interface_unchecked(
+        &self,
+        handle: Option<efi::Handle>,
+        protocol: &'static efi::Guid,
+        interface: *mut c_void,
+    ) -> Result<efi::Handle, EfiError> {
+        self.intercept(BootServicesCall::InstallProtocolInterface { handle, protocol: *protocol, interface })?;
+        self.boot_services.install_protocol_interface_unchecked(handle, protocol, interface)
+    }
+
+    unsafe fn uninstall_protocol_interface_unchecked(
+        &self,
+        handle: efi::Handle,
+        protocol: &'static efi::Guid,
+        interface: *mut c_void,
+    ) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::UninstallProtocolInterface { handle, protocol: *protocol, interface })?;
+        self.boot_services.uninstall_protocol_interface_unchecked(handle, protocol, interface)
+    }
+
+    unsafe fn reinstall_protocol_interface_unchecked(
+        &self,
+        handle: efi::Handle,
+        protocol: &'static efi::Guid,
+        old_protocol_interface: *mut c_void,
+        new_protocol_interface: *mut c_void,
+    ) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::ReinstallProtocolInterface {
+            handle,
+            protocol: *protocol,
+            old_protocol_interface,
+            new_protocol_interface,
+        })?;
+        self.boot_services.reinstall_protocol_interface_unchecked(
+            handle,
+            protocol,
+            old_protocol_interface,
+            new_protocol_interface,
+        )
+    }
+
+    fn register_protocol_notify(
+        &self,
+        protocol: &'static efi::Guid,
+        event: efi::Event,
+    ) -> Result<Registration, EfiError> {
+        self.intercept(BootServicesCall::RegisterProtocolNotify { protocol: *protocol, event })?;
+        self.boot_services.register_protocol_notify(protocol, event)
+    }
+
+    fn locate_handle(
+        &self,
+        search_type: HandleSearchType,
+    ) -> Result<BootServicesBox<'_, [efi::Handle], Self>, EfiError> {
+        self.intercept(BootServicesCall::LocateHandle { search_type })?;
+        self.boot_services.locate_handle(search_type).map(|handles| self.rebox(handles))
+    }
+
+    unsafe fn handle_protocol_unchecked(
+        &self,
+        handle: efi::Handle,
+        protocol: &efi::Guid,
+    ) -> Result<*mut c_void, EfiError> {
+        self.intercept(BootServicesCall::HandleProtocol { handle, protocol: *protocol })?;
+        self.boot_services.handle_protocol_unchecked(handle, protocol)
+    }
+
+    unsafe fn locate_device_path(
+        &self,
+        protocol: &efi::Guid,
+        device_path: *mut *mut efi::protocols::device_path::Protocol,
+    ) -> Result<efi::Handle, EfiError> {
+        self.intercept(BootServicesCall::LocateDevicePath { protocol: *protocol, device_path })?;
+        self.boot_services.locate_device_path(protocol, device_path)
+    }
+
+    unsafe fn open_protocol_unchecked(
+        &self,
+        handle: efi::Handle,
+        protocol: &efi::Guid,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+        attribute: u32,
+    ) -> Result<*mut c_void, EfiError> {
+        self.intercept(BootServicesCall::OpenProtocol {
+            handle,
+            protocol: *protocol,
+            agent_handle,
+            controller_handle,
+            attribute,
+        })?;
+        self.boot_services.open_protocol_unchecked(handle, protocol, agent_handle, controller_handle, attribute)
+    }
+
+    fn close_protocol(
+        &self,
+        handle: efi::Handle,
+        protocol: &efi::Guid,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+    ) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::CloseProtocol {
+            handle,
+            protocol: *protocol,
+            agent_handle,
+            controller_handle,
+        })?;
+        self.boot_services.close_protocol(handle, protocol, agent_handle, controller_handle)
+    }
+
+    fn open_protocol_information(
+        &self,
+        handle: efi::Handle,
+        protocol: &efi::Guid,
+    ) -> Result<BootServicesBox<'_, [efi::OpenProtocolInformationEntry], Self>, EfiError> {
+        self.intercept(BootServicesCall::OpenProtocolInformation { handle, protocol: *protocol })?;
+        self.boot_services.open_protocol_information(handle, protocol).map(|entries| self.rebox(entries))
+    }
+
+    unsafe fn connect_controller(
+        &self,
+        controller_handle: efi::Handle,
+        driver_image_handle: Vec<efi::Handle>,
+        remaining_device_path: *mut efi::protocols::device_path::Protocol,
+        recursive: bool,
+    ) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::ConnectController {
+            controller_handle,
+            driver_image_handle: driver_image_handle.clone(),
+            remaining_device_path,
+            recursive,
+        })?;
+        self.boot_services.connect_controller(controller_handle, driver_image_handle, remaining_device_path, recursive)
+    }
+
+    fn disconnect_controller(
+        &self,
+        controller_handle: efi::Handle,
+        driver_image_handle: Option<efi::Handle>,
+        child_handle: Option<efi::Handle>,
+    ) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::DisconnectController {
+            controller_handle,
+            driver_image_handle,
+            child_handle,
+        })?;
+        self.boot_services.disconnect_controller(controller_handle, driver_image_handle, child_handle)
+    }
+
+    fn protocols_per_handle(
+        &self,
+        handle: efi::Handle,
+    ) -> Result<BootServicesBox<'_, [&'static efi::Guid], Self>, EfiError> {
+        self.intercept(BootServicesCall::ProtocolsPerHandle { handle })?;
+        self.boot_services.protocols_per_handle(handle).map(|protocols| self.rebox(protocols))
+    }
+
+    fn locate_handle_buffer(
+        &self,
+        search_type: HandleSearchType,
+    ) -> Result<BootServicesBox<'_, [efi::Handle], Self>, EfiError> {
+        self.intercept(BootServicesCall::LocateHandleBuffer { search_type })?;
+        self.boot_services.locate_handle_buffer(search_type).map(|handles| self.rebox(handles))
+    }
+
+    unsafe fn locate_protocol_unchecked(
+        &self,
+        protocol: &'static efi::Guid,
+        registration: *mut c_void,
+    ) -> Result<*mut c_void, EfiError> {
+        self.intercept(BootServicesCall::LocateProtocol { protocol: *protocol, registration })?;
+        self.boot_services.locate_protocol_unchecked(protocol, registration)
+    }
+
+    unsafe fn install_configuration_table_unchecked(
+        &self,
+        guid: &efi::Guid,
+        table: *mut c_void,
+    ) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::InstallConfigurationTable { guid: *guid, table })?;
+        self.boot_services.install_configuration_table_unchecked(guid, table)
+    }
+
+    fn stall(&self, microseconds: usize) -> Result<(), EfiError> {
+        self.intercept(BootServicesCall::Stall { microseconds })?;
+        self.boot_services.stall(microseconds)
+    }
+
+    fn get_next_monotonic_count(&self) -> Result<u64, EfiError> {
+        self.intercept(BootServicesCall::GetNextMonotonicCount)?;
+        self.boot_services.get_next_monotonic_count()
+    }
+
+    unsafe fn exit_unchecked(
+        &self,
+        image_handle: efi::Handle,
+        exit_status: efi::Status,
+        exit_data_size: usize,
+        exit_data: *mut u16,
+    ) -> efi::Status {
+        if let Err(status) = self.intercept(BootServicesCall::Exit { image_handle, exit_status, exit_data_size }) {
+            return status;
+        }
+        self.boot_services.exit_unchecked(image_handle, exit_status, exit_data_size, exit_data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockBootServices;
+    use core::ptr;
+
+    const TABLE_GUID: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+
+    #[test]
+    fn test_recording() {
+        let mut mock = MockBootServices::new();
+        mock.expect_allocate_pool().returning(|_, _| Ok(0x1000 as *mut u8));
+        mock.expect_install_configuration_table_unchecked().returning(|_, _| Ok(()));
+        mock.expect_free_pool().returning(|_| Ok(()));
+        mock.expect_raise_tpl().returning(|_| Tpl::APPLICATION);
+        mock.expect_restore_tpl().return_const(());
+
+        let boot_services = RecordingBootServices::new(mock);
+        {
+            let _tpl = boot_services.raise_tpl_guarded(Tpl::NOTIFY);
+            let table = boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, 0x20).unwrap();
+            //SAFETY: The mock does not dereference the table.
+            unsafe { boot_services.install_configuration_table_unchecked(&TABLE_GUID, table as *mut c_void) }.unwrap();
+        }
+        boot_services.free_pool(0x1000 as *mut u8).unwrap();
+
+        boot_services.assert_called_once_with(&BootServicesCall::AllocatePool {
+            pool_type: MemoryType::BOOT_SERVICES_DATA,
+            size: 0x20,
+        });
+        boot_services.assert_called_in_order(&[
+            BootServicesCall::RaiseTpl { tpl: Tpl::NOTIFY },
+            BootServicesCall::InstallConfigurationTable { guid: TABLE_GUID, table: 0x1000 as *mut c_void },
+            BootServicesCall::RestoreTpl { tpl: Tpl::APPLICATION },
+            BootServicesCall::FreePool { buffer: 0x1000 as *mut u8 },
+        ]);
+        assert_eq!(5, boot_services.calls().len());
+        assert_eq!(1, boot_services.count(|call| matches!(call, BootServicesCall::FreePool { .. })));
+
+        boot_services.clear();
+        assert!(boot_services.calls().is_empty());
+    }
+
+    #[test]
+    fn test_inject_fault() {
+        let mut mock = MockBootServices::new();
+        mock.expect_allocate_pool().times(2).returning(|_, _| Ok(0x1000 as *mut u8));
+        mock.expect_locate_protocol_unchecked().never();
+
+        let boot_services = RecordingBootServices::new(mock);
+        let is_allocate_pool = |call: &BootServicesCall| matches!(call, BootServicesCall::AllocatePool { .. });
+        boot_services.inject_fault(is_allocate_pool, 2, efi::Status::OUT_OF_RESOURCES);
+        boot_services.inject_fault(|_| true, 4, efi::Status::WARN_STALE_DATA);
+
+        assert!(boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, 0x20).is_ok());
+        assert_eq!(
+            Err(efi::Status::OUT_OF_RESOURCES.into()),
+            boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, 0x20)
+        );
+        assert!(boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, 0x20).is_ok());
+        //SAFETY: The call is never forwarded.
+        assert_eq!(Err(efi::Status::WARN_STALE_DATA.into()), unsafe {
+            boot_services.locate_protocol_unchecked(&TABLE_GUID, ptr::null_mut())
+        });
+        assert_eq!(4, boot_services.calls().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "in order")]
+    fn test_assert_called_in_order() {
+        let mut mock = MockBootServices::new();
+        mock.expect_signal_event().returning(|_| Ok(()));
+        mock.expect_close_event().returning(|_| Ok(()));
+
+        let boot_services = RecordingBootServices::new(mock);
+        boot_services.close_event(1 as efi::Event).unwrap();
+        boot_services.signal_event(1 as efi::Event).unwrap();
+        boot_services.assert_called_in_order(&[
+            BootServicesCall::SignalEvent { event: 1 as efi::Event },
+            BootServicesCall::CloseEvent { event: 1 as efi::Event },
+        ]);
+    }
+}
@@ -0,0 +1,194 @@
+//! Procedural macros companion to the `boot_services` crate.
+//!
+//! These macros are re-exported by `boot_services` and should be used through it.
+//!
+//! ```ignore
+//! use boot_services::protocol_handler::Protocol;
+//!
+//! #[derive(Protocol)]
+//! #[protocol_guid = "8F644FA9-E850-4DB1-9CE2-0B44698E8DA4"]
+//! #[protocol_interface = "MyProtocolInterface"]
+//! pub struct MyProtocol;
+//! ```
+//!
+//! The generated code refers to `::boot_services`. Crates using the re-export of another crate give its path instead:
+//!
+//! ```ignore
+//! #[derive(Protocol)]
+//! #[protocol(crate = "mu_rust_helpers::boot_services")]
+//! #[protocol_guid = "8F644FA9-E850-4DB1-9CE2-0B44698E8DA4"]
+//! pub struct MyProtocol;
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Expr, ExprLit, Lit, LitStr, Meta, Path, Type};
+
+/// Derive `boot_services::protocol_handler::Protocol` for a protocol marker struct.
+///
+/// # Attributes
+/// * `#[protocol_guid = "xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx"]` (required): The protocol GUID.
+/// * `#[protocol_interface = "Type"]` (optional): The interface type of the protocol.
+///   If omitted, the interface is `()` and the protocol is installed with a null interface.
+/// * `#[protocol(crate = "path")]` (optional): The path of the `boot_services` crate, `::boot_services` if omitted.
+#[proc_macro_derive(Protocol, attributes(protocol, protocol_guid, protocol_interface))]
+pub fn derive_protocol(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_protocol(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_protocol(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !matches!(input.data, Data::Struct(_)) {
+        return Err(syn::Error::new(input.ident.span(), "Protocol can only be derived for structs."));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(input.generics.span(), "Protocol can not be derived for generic structs."));
+    }
+
+    let mut guid = None;
+    let mut interface = None;
+    let mut krate = None;
+    for attr in &input.attrs {
+        if attr.path().is_ident("protocol_guid") {
+            let Meta::NameValue(name_value) = &attr.meta else {
+                return Err(syn::Error::new(attr.span(), "Expected `#[protocol_guid = \"...\"]`."));
+            };
+            let Expr::Lit(ExprLit { lit: Lit::Str(guid_str), .. }) = &name_value.value else {
+                return Err(syn::Error::new(name_value.value.span(), "Expected a GUID string literal."));
+            };
+            let fields = parse_guid(&guid_str.value()).map_err(|msg| syn::Error::new(guid_str.span(), msg))?;
+            guid = Some(fields);
+        } else if attr.path().is_ident("protocol_interface") {
+            let Meta::NameValue(name_value) = &attr.meta else {
+                return Err(syn::Error::new(attr.span(), "Expected `#[protocol_interface = \"Type\"]`."));
+            };
+            let Expr::Lit(ExprLit { lit: Lit::Str(type_str), .. }) = &name_value.value else {
+                return Err(syn::Error::new(name_value.value.span(), "Expected a type string literal."));
+            };
+            let ty: Type = type_str.parse()?;
+            interface = Some(ty);
+        } else if attr.path().is_ident("protocol") {
+            attr.parse_nested_meta(|meta| {
+                if !meta.path.is_ident("crate") {
+                    return Err(meta.error("Expected `#[protocol(crate = \"path\")]`."));
+                }
+                let path: Path = meta.value()?.parse::<LitStr>()?.parse()?;
+                krate = Some(path);
+                Ok(())
+            })?;
+        }
+    }
+
+    let Some((d1, d2, d3, d4, d5, d6)) = guid else {
+        return Err(syn::Error::new(Span::call_site(), "Missing `#[protocol_guid = \"...\"]` attribute."));
+    };
+    let interface = interface.map_or_else(|| quote!(()), |ty| quote!(#ty));
+    let krate = krate.map_or_else(|| quote!(::boot_services), |path| quote!(#path));
+    let ident = &input.ident;
+
+    Ok(quote! {
+        unsafe impl #krate::protocol_handler::Protocol for #ident {
+            type Interface = #interface;
+            fn protocol_guid(&self) -> &'static #krate::__private::r_efi::efi::Guid {
+                static PROTOCOL_GUID: #krate::__private::r_efi::efi::Guid =
+                    #krate::__private::r_efi::efi::Guid::from_fields(#d1, #d2, #d3, #d4, #d5, &[#(#d6),*]);
+                &PROTOCOL_GUID
+            }
+        }
+
+        impl ::core::ops::Deref for #ident {
+            type Target = #krate::__private::r_efi::efi::Guid;
+            fn deref(&self) -> &Self::Target {
+                #krate::protocol_handler::Protocol::protocol_guid(self)
+            }
+        }
+    })
+}
+
+/// Fields of a GUID as expected by `efi::Guid::from_fields`.
+type GuidFields = (u32, u16, u16, u8, u8, [u8; 6]);
+
+/// Parse a GUID in the registry format (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`) into its fields.
+fn parse_guid(guid: &str) -> Result<GuidFields, String> {
+    let groups = guid.split('-').collect::<Vec<_>>();
+    if groups.len() != 5 || groups.iter().map(|g| g.len()).ne([8, 4, 4, 4, 12]) {
+        return Err(format!("Invalid GUID \"{guid}\", expected the format xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx."));
+    }
+    if let Some(c) = guid.chars().find(|c| *c != '-' && !c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid GUID \"{guid}\", unexpected character '{c}'."));
+    }
+
+    let hex = |s: &str| u64::from_str_radix(s, 16).unwrap();
+    let node = hex(groups[4]).to_be_bytes();
+    Ok((
+        hex(groups[0]) as u32,
+        hex(groups[1]) as u16,
+        hex(groups[2]) as u16,
+        hex(&groups[3][..2]) as u8,
+        hex(&groups[3][2..]) as u8,
+        [node[2], node[3], node[4], node[5], node[6], node[7]],
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_guid() {
+        assert_eq!(
+            Ok((0x434f695c, 0xef26, 0x4a12, 0x9e, 0xba, [0xdd, 0xef, 0x00, 0x97, 0x49, 0x7c])),
+            parse_guid("434F695C-EF26-4A12-9EBA-DDEF0097497C")
+        );
+        assert_eq!(Ok((0, 0, 0, 0, 0, [0; 6])), parse_guid("00000000-0000-0000-0000-000000000000"));
+    }
+
+    #[test]
+    fn test_parse_invalid_guid() {
+        assert!(parse_guid("434F695C-EF26-4A12-9EBA").is_err());
+        assert!(parse_guid("434F695CEF264A129EBADDEF0097497C").is_err());
+        assert!(parse_guid("434F695C-EF26-4A12-9EBADD-EF0097497C").is_err());
+        assert!(parse_guid("434F695C-EF26-4A12-9EBA-DDEF0097497G").is_err());
+        assert!(parse_guid("+34F695C-EF26-4A12-9EBA-DDEF0097497C").is_err());
+    }
+
+    #[test]
+    fn test_derive_protocol_without_guid() {
+        let input: DeriveInput = syn::parse_quote! {
+            pub struct MyProtocol;
+        };
+        assert!(expand_protocol(input).is_err());
+    }
+
+    #[test]
+    fn test_derive_protocol_with_crate() {
+        let input: DeriveInput = syn::parse_quote! {
+            #[protocol(crate = "mu_rust_helpers::boot_services")]
+            #[protocol_guid = "434F695C-EF26-4A12-9EBA-DDEF0097497C"]
+            pub struct MyProtocol;
+        };
+        let tokens = expand_protocol(input).unwrap().to_string();
+        assert!(tokens.contains("mu_rust_helpers :: boot_services :: protocol_handler :: Protocol"));
+        assert!(!tokens.contains("impl :: boot_services"));
+
+        let input: DeriveInput = syn::parse_quote! {
+            #[protocol(krate = "mu_rust_helpers::boot_services")]
+            #[protocol_guid = "434F695C-EF26-4A12-9EBA-DDEF0097497C"]
+            pub struct MyProtocol;
+        };
+        assert!(expand_protocol(input).is_err());
+    }
+
+    #[test]
+    fn test_derive_protocol_on_enum() {
+        let input: DeriveInput = syn::parse_quote! {
+            #[protocol_guid = "434F695C-EF26-4A12-9EBA-DDEF0097497C"]
+            pub enum MyProtocol {}
+        };
+        assert!(expand_protocol(input).is_err());
+    }
+}
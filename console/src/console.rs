@@ -0,0 +1,60 @@
+//! Console input and output.
+//!
+//! [`con_in`] gives the console input of the system table to read keys from, [`con_in_ex`] adds the state of the
+//! modifiers and key notifications.
+//!
+//! [`SimplePointer`] and [`AbsolutePointer`] read the state of the mouse and touch devices.
+//!
+//! [`con_out`] gives the console output of the system table, which [`print!`] and [`println!`] write to:
+//!
+//! ```ignore
+//! console::println!("Booting {}...", name);
+//! ```
+//!
+//! [`ui`] draws tables, progress bars and menus on the console.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod input;
+pub mod input_ex;
+pub mod output;
+pub mod pointer;
+pub mod ui;
+
+pub use input::{con_in, Key, ScanCode, TextInput};
+pub use input_ex::{con_in_ex, KeyData, KeyNotification, ShiftState, TextInputEx, ToggleState};
+pub use output::{con_out, Attribute, Color, ColorGuard, ConsoleState, TextMode, TextOutput};
+pub use pointer::{AbsolutePointer, NormalizedPosition, Pointer, SimplePointer};
+pub use ui::{Alignment, Menu, ProgressBar, Table};
+
+/// Prints to the console output, if boot services are available.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::__private::print(format_args!($($arg)*))
+    };
+}
+
+/// Prints to the console output with a newline, if boot services are available.
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::__private::print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    //! Helpers used by the code generated from the macros of this crate.
+    use core::fmt::{self, Write};
+
+    pub fn print(args: fmt::Arguments<'_>) {
+        if let Some(mut con_out) = crate::con_out() {
+            let _ = con_out.write_fmt(args);
+        }
+    }
+}
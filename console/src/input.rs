@@ -0,0 +1,273 @@
+//! Simple Text Input protocol.
+//!
+//! [UEFI Spec Documentation: 12.3. Simple Text Input Protocol](https://uefi.org/specs/UEFI/2.10/12_Protocols_Console_Support.html#simple-text-input-protocol)
+
+use core::fmt;
+
+use boot_services::BootServices;
+use r_efi::efi;
+use status::EfiError;
+
+type SimpleTextInputProtocol = efi::protocols::simple_text_input::Protocol;
+
+/// Scan code of a key that has no unicode character.
+///
+/// [UEFI Spec Documentation: 12.3.3. EFI Scan Codes for EFI_SIMPLE_TEXT_INPUT_PROTOCOL](https://uefi.org/specs/UEFI/2.10/12_Protocols_Console_Support.html#efi-scan-codes-for-efi-simple-text-input-protocol)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct ScanCode(pub u16);
+
+impl ScanCode {
+    pub const NULL: Self = Self(0x00);
+    pub const UP: Self = Self(0x01);
+    pub const DOWN: Self = Self(0x02);
+    pub const RIGHT: Self = Self(0x03);
+    pub const LEFT: Self = Self(0x04);
+    pub const HOME: Self = Self(0x05);
+    pub const END: Self = Self(0x06);
+    pub const INSERT: Self = Self(0x07);
+    pub const DELETE: Self = Self(0x08);
+    pub const PAGE_UP: Self = Self(0x09);
+    pub const PAGE_DOWN: Self = Self(0x0A);
+    pub const F1: Self = Self(0x0B);
+    pub const F2: Self = Self(0x0C);
+    pub const F3: Self = Self(0x0D);
+    pub const F4: Self = Self(0x0E);
+    pub const F5: Self = Self(0x0F);
+    pub const F6: Self = Self(0x10);
+    pub const F7: Self = Self(0x11);
+    pub const F8: Self = Self(0x12);
+    pub const F9: Self = Self(0x13);
+    pub const F10: Self = Self(0x14);
+    pub const F11: Self = Self(0x15);
+    pub const F12: Self = Self(0x16);
+    pub const ESC: Self = Self(0x17);
+}
+
+/// A key read from a console input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// A key producing a character, such as letters, `'\r'` for enter or `'\u{8}'` for backspace.
+    Char(char),
+    /// A key without character, such as the arrows or the function keys.
+    Special(ScanCode),
+}
+
+impl From<efi::protocols::simple_text_input::InputKey> for Key {
+    fn from(key: efi::protocols::simple_text_input::InputKey) -> Self {
+        match key.unicode_char {
+            0 => Key::Special(ScanCode(key.scan_code)),
+            c => Key::Char(char::from_u32(c as u32).unwrap_or(char::REPLACEMENT_CHARACTER)),
+        }
+    }
+}
+
+/// Typed access to an instance of the Simple Text Input protocol.
+pub struct TextInput(*mut SimpleTextInputProtocol);
+
+/// The console input of the system table, `None` if boot services are not available or if there is no console.
+pub fn con_in() -> Option<TextInput> {
+    if !entry_point::boot_services_available() {
+        return None;
+    }
+    //SAFETY: The console input of the system table is valid while boot services are available.
+    unsafe { TextInput::from_ptr(entry_point::system_table().con_in) }
+}
+
+impl TextInput {
+    /// Wraps an instance of the protocol, returns `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to an instance of the protocol that stays valid for the life of the wrapper.
+    pub unsafe fn from_ptr(protocol: *mut SimpleTextInputProtocol) -> Option<Self> {
+        (!protocol.is_null()).then_some(Self(protocol))
+    }
+
+    fn protocol(&self) -> &SimpleTextInputProtocol {
+        //SAFETY: The pointer is valid for the life of the wrapper.
+        unsafe { &*self.0 }
+    }
+
+    /// Resets the input device.
+    pub fn reset(&mut self, extended_verification: bool) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().reset, (self.0, extended_verification.into())) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Event signaled when a key is available.
+    pub fn wait_for_key_event(&self) -> efi::Event {
+        self.protocol().wait_for_key
+    }
+
+    /// Reads the next key, returns `None` if no key is pending.
+    pub fn read_key(&mut self) -> Result<Option<Key>, EfiError> {
+        let mut key = efi::protocols::simple_text_input::InputKey { scan_code: 0, unicode_char: 0 };
+        match unsafe { r_efi::efi_call(self.protocol().read_key_stroke, (self.0, &mut key)) } {
+            efi::Status::NOT_READY => Ok(None),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(Some(key.into())),
+        }
+    }
+
+    /// Waits for the next key and reads it.
+    pub fn read_key_blocking<B: BootServices>(&mut self, boot_services: &B) -> Result<Key, EfiError> {
+        loop {
+            if let Some(key) = self.read_key()? {
+                return Ok(key);
+            }
+            boot_services.wait_for_event(&mut [self.wait_for_key_event()])?;
+        }
+    }
+
+    /// Future resolving to the next key.
+    ///
+    /// The future does not register for the key event, it asks to be polled again until a key is available.
+    #[cfg(feature = "async")]
+    pub fn next_key(&mut self) -> NextKey<'_> {
+        NextKey(self)
+    }
+}
+
+impl fmt::Debug for TextInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TextInput").field(&self.0).finish()
+    }
+}
+
+/// Future returned by [`TextInput::next_key`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct NextKey<'a>(&'a mut TextInput);
+
+#[cfg(feature = "async")]
+impl core::future::Future for NextKey<'_> {
+    type Output = Result<Key, EfiError>;
+
+    fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> core::task::Poll<Self::Output> {
+        use core::task::Poll;
+        match self.0.read_key() {
+            Ok(Some(key)) => Poll::Ready(Ok(key)),
+            Ok(None) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Err(status) => Poll::Ready(Err(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, ptr};
+    use std::collections::VecDeque;
+
+    use efi::protocols::simple_text_input::InputKey;
+
+    /// Console input returning the keys of its queue, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    pub(crate) struct TestInput {
+        pub protocol: SimpleTextInputProtocol,
+        pub keys: RefCell<VecDeque<InputKey>>,
+    }
+
+    impl TestInput {
+        fn from_protocol<'a>(this: *mut SimpleTextInputProtocol) -> &'a TestInput {
+            unsafe { &*(this as *const TestInput) }
+        }
+
+        pub fn new() -> Box<Self> {
+            Box::new(TestInput {
+                protocol: SimpleTextInputProtocol { reset, read_key_stroke, wait_for_key: 0x1234 as efi::Event },
+                keys: RefCell::new(VecDeque::new()),
+            })
+        }
+
+        pub fn push(&self, scan_code: ScanCode, unicode_char: u16) {
+            self.keys.borrow_mut().push_back(InputKey { scan_code: scan_code.0, unicode_char });
+        }
+
+        pub fn text_input(&mut self) -> TextInput {
+            unsafe { TextInput::from_ptr(&mut self.protocol) }.unwrap()
+        }
+    }
+
+    extern "efiapi" fn reset(this: *mut SimpleTextInputProtocol, _: efi::Boolean) -> efi::Status {
+        TestInput::from_protocol(this).keys.borrow_mut().clear();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_key_stroke(this: *mut SimpleTextInputProtocol, key: *mut InputKey) -> efi::Status {
+        match TestInput::from_protocol(this).keys.borrow_mut().pop_front() {
+            Some(k) => {
+                unsafe { ptr::write(key, k) };
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::NOT_READY,
+        }
+    }
+
+    #[test]
+    fn test_read_key() {
+        let mut input = TestInput::new();
+        input.push(ScanCode::NULL, b'a' as u16);
+        input.push(ScanCode::UP, 0);
+        input.push(ScanCode::NULL, 0xD800);
+
+        let mut text_input = input.text_input();
+        assert_eq!(Some(Key::Char('a')), text_input.read_key().unwrap());
+        assert_eq!(Some(Key::Special(ScanCode::UP)), text_input.read_key().unwrap());
+        assert_eq!(Some(Key::Char(char::REPLACEMENT_CHARACTER)), text_input.read_key().unwrap());
+        assert_eq!(None, text_input.read_key().unwrap());
+
+        input.push(ScanCode::NULL, b'b' as u16);
+        text_input.reset(false).unwrap();
+        assert_eq!(None, text_input.read_key().unwrap());
+    }
+
+    #[test]
+    fn test_read_key_blocking() {
+        let mut input = TestInput::new();
+        let input_ptr = &*input as *const TestInput as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_wait_for_event().once().returning(move |events| {
+            assert_eq!(&[0x1234 as efi::Event], events);
+            // The key arrives while waiting.
+            unsafe { &*(input_ptr as *const TestInput) }.push(ScanCode::ESC, 0);
+            Ok(0)
+        });
+        assert_eq!(Key::Special(ScanCode::ESC), input.text_input().read_key_blocking(&boot_services).unwrap());
+    }
+
+    #[test]
+    fn test_con_in_not_initialized() {
+        assert!(con_in().is_none());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_next_key() {
+        use core::{
+            future::Future,
+            pin::pin,
+            task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+        };
+
+        const VTABLE: RawWakerVTable =
+            RawWakerVTable::new(|_| RawWaker::new(ptr::null(), &VTABLE), |_| {}, |_| {}, |_| {});
+
+        let mut input = TestInput::new();
+        let input_ptr = &*input as *const TestInput;
+        let mut text_input = input.text_input();
+        let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+        let mut cx = Context::from_waker(&waker);
+        let mut next_key = pin!(text_input.next_key());
+        assert_eq!(Poll::Pending, next_key.as_mut().poll(&mut cx));
+        unsafe { &*input_ptr }.push(ScanCode::NULL, b'x' as u16);
+        assert_eq!(Poll::Ready(Ok(Key::Char('x'))), next_key.as_mut().poll(&mut cx));
+    }
+}
@@ -0,0 +1,527 @@
+//! Simple Text Input Ex protocol.
+//!
+//! [UEFI Spec Documentation: 12.2. Simple Text Input Ex Protocol](https://uefi.org/specs/UEFI/2.10/12_Protocols_Console_Support.html#simple-text-input-ex-protocol)
+
+use alloc::boxed::Box;
+use core::{
+    ffi::c_void,
+    fmt,
+    marker::PhantomData,
+    ops, ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::input::{Key, ScanCode};
+
+use efi::protocols::simple_text_input_ex as ex;
+
+type SimpleTextInputExProtocol = ex::Protocol;
+
+/// State of the shift, control, alt, logo, menu and system request keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct ShiftState(u32);
+
+impl ShiftState {
+    pub const RIGHT_SHIFT_PRESSED: ShiftState = ShiftState(ex::RIGHT_SHIFT_PRESSED);
+    pub const LEFT_SHIFT_PRESSED: ShiftState = ShiftState(ex::LEFT_SHIFT_PRESSED);
+    pub const RIGHT_CONTROL_PRESSED: ShiftState = ShiftState(ex::RIGHT_CONTROL_PRESSED);
+    pub const LEFT_CONTROL_PRESSED: ShiftState = ShiftState(ex::LEFT_CONTROL_PRESSED);
+    pub const RIGHT_ALT_PRESSED: ShiftState = ShiftState(ex::RIGHT_ALT_PRESSED);
+    pub const LEFT_ALT_PRESSED: ShiftState = ShiftState(ex::LEFT_ALT_PRESSED);
+    pub const RIGHT_LOGO_PRESSED: ShiftState = ShiftState(ex::RIGHT_LOGO_PRESSED);
+    pub const LEFT_LOGO_PRESSED: ShiftState = ShiftState(ex::LEFT_LOGO_PRESSED);
+    pub const MENU_KEY_PRESSED: ShiftState = ShiftState(ex::MENU_KEY_PRESSED);
+    pub const SYS_REQ_PRESSED: ShiftState = ShiftState(ex::SYS_REQ_PRESSED);
+
+    /// No modifier pressed.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns true if every modifier of `other` is pressed.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if either shift key is pressed.
+    pub const fn shift(self) -> bool {
+        self.0 & (ex::RIGHT_SHIFT_PRESSED | ex::LEFT_SHIFT_PRESSED) != 0
+    }
+
+    /// Returns true if either control key is pressed.
+    pub const fn control(self) -> bool {
+        self.0 & (ex::RIGHT_CONTROL_PRESSED | ex::LEFT_CONTROL_PRESSED) != 0
+    }
+
+    /// Returns true if either alt key is pressed.
+    pub const fn alt(self) -> bool {
+        self.0 & (ex::RIGHT_ALT_PRESSED | ex::LEFT_ALT_PRESSED) != 0
+    }
+
+    /// Returns true if either logo key is pressed.
+    pub const fn logo(self) -> bool {
+        self.0 & (ex::RIGHT_LOGO_PRESSED | ex::LEFT_LOGO_PRESSED) != 0
+    }
+}
+
+impl ops::BitOr for ShiftState {
+    type Output = ShiftState;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        ShiftState(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for ShiftState {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// State of the toggle keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct ToggleState(u8);
+
+impl ToggleState {
+    pub const SCROLL_LOCK_ACTIVE: ToggleState = ToggleState(ex::SCROLL_LOCK_ACTIVE);
+    pub const NUM_LOCK_ACTIVE: ToggleState = ToggleState(ex::NUM_LOCK_ACTIVE);
+    pub const CAPS_LOCK_ACTIVE: ToggleState = ToggleState(ex::CAPS_LOCK_ACTIVE);
+    /// Partial keystrokes, such as a modifier pressed alone, are reported.
+    pub const KEY_STATE_EXPOSED: ToggleState = ToggleState(ex::KEY_STATE_EXPOSED);
+
+    /// No toggle active.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns true if every toggle of `other` is active.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for ToggleState {
+    type Output = ToggleState;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        ToggleState(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for ToggleState {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A key with the state of the modifiers and toggles when it was pressed.
+///
+/// The states are `None` when the device does not report them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyData {
+    pub key: Key,
+    pub shift_state: Option<ShiftState>,
+    pub toggle_state: Option<ToggleState>,
+}
+
+impl KeyData {
+    /// A key without modifier or toggle state.
+    pub const fn new(key: Key) -> Self {
+        Self { key, shift_state: None, toggle_state: None }
+    }
+
+    /// Sets the modifiers of the key.
+    pub const fn with_shift_state(mut self, shift_state: ShiftState) -> Self {
+        self.shift_state = Some(shift_state);
+        self
+    }
+
+    /// Sets the toggles of the key.
+    pub const fn with_toggle_state(mut self, toggle_state: ToggleState) -> Self {
+        self.toggle_state = Some(toggle_state);
+        self
+    }
+
+    fn to_efi(self) -> Result<ex::KeyData, EfiError> {
+        let (scan_code, unicode_char) = match self.key {
+            Key::Char(c) => (0, u16::try_from(c as u32).map_err(|_| efi::Status::INVALID_PARAMETER)?),
+            Key::Special(ScanCode(scan_code)) => (scan_code, 0),
+        };
+        Ok(ex::KeyData {
+            key: efi::protocols::simple_text_input::InputKey { scan_code, unicode_char },
+            key_state: ex::KeyState {
+                key_shift_state: self.shift_state.map_or(0, |s| s.0 | ex::SHIFT_STATE_VALID),
+                key_toggle_state: self.toggle_state.map_or(0, |t| t.0 | ex::TOGGLE_STATE_VALID),
+            },
+        })
+    }
+}
+
+impl From<ex::KeyData> for KeyData {
+    fn from(key_data: ex::KeyData) -> Self {
+        let ex::KeyState { key_shift_state, key_toggle_state } = key_data.key_state;
+        Self {
+            key: key_data.key.into(),
+            shift_state: (key_shift_state & ex::SHIFT_STATE_VALID != 0)
+                .then_some(ShiftState(key_shift_state & !ex::SHIFT_STATE_VALID)),
+            toggle_state: (key_toggle_state & ex::TOGGLE_STATE_VALID != 0)
+                .then_some(ToggleState(key_toggle_state & !ex::TOGGLE_STATE_VALID)),
+        }
+    }
+}
+
+/// Typed access to an instance of the Simple Text Input Ex protocol.
+pub struct TextInputEx(*mut SimpleTextInputExProtocol);
+
+/// The Simple Text Input Ex protocol of the console input handle of the system table, `None` if boot services are not
+/// available or if the console does not support it.
+pub fn con_in_ex() -> Option<TextInputEx> {
+    if !entry_point::boot_services_available() {
+        return None;
+    }
+    let handle = entry_point::system_table().console_in_handle;
+    let protocol = entry_point::boot_services().handle_protocol(handle, &protocol_handler::SimpleTextInputEx).ok()?;
+    //SAFETY: Protocols installed on the console handle are valid while boot services are available.
+    unsafe { TextInputEx::from_ptr(protocol) }
+}
+
+impl TextInputEx {
+    /// Wraps an instance of the protocol, returns `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to an instance of the protocol that stays valid for the life of the wrapper.
+    pub unsafe fn from_ptr(protocol: *mut SimpleTextInputExProtocol) -> Option<Self> {
+        (!protocol.is_null()).then_some(Self(protocol))
+    }
+
+    fn protocol(&self) -> &SimpleTextInputExProtocol {
+        //SAFETY: The pointer is valid for the life of the wrapper.
+        unsafe { &*self.0 }
+    }
+
+    /// Resets the input device.
+    pub fn reset(&mut self, extended_verification: bool) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().reset, (self.0, extended_verification.into())) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Event signaled when a key is available.
+    pub fn wait_for_key_event(&self) -> efi::Event {
+        self.protocol().wait_for_key_ex
+    }
+
+    /// Reads the next key with its modifiers, returns `None` if no key is pending.
+    pub fn read_key(&mut self) -> Result<Option<KeyData>, EfiError> {
+        let mut key_data = ex::KeyData::default();
+        match unsafe { r_efi::efi_call(self.protocol().read_key_stroke_ex, (self.0, &mut key_data)) } {
+            efi::Status::NOT_READY => Ok(None),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(Some(key_data.into())),
+        }
+    }
+
+    /// Waits for the next key and reads it.
+    pub fn read_key_blocking<B: BootServices>(&mut self, boot_services: &B) -> Result<KeyData, EfiError> {
+        loop {
+            if let Some(key_data) = self.read_key()? {
+                return Ok(key_data);
+            }
+            boot_services.wait_for_event(&mut [self.wait_for_key_event()])?;
+        }
+    }
+
+    /// Sets the toggle state of the input device, such as the lock lights of a keyboard.
+    pub fn set_toggle_state(&mut self, toggle_state: ToggleState) -> Result<(), EfiError> {
+        let mut state = toggle_state.0 | ex::TOGGLE_STATE_VALID;
+        match unsafe { r_efi::efi_call(self.protocol().set_state, (self.0, &mut state)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Calls `notify` each time `key` is pressed, until the returned [`KeyNotification`] is dropped.
+    ///
+    /// The modifiers of `key` are only matched if its shift state is set. `notify` is called at the TPL of the
+    /// firmware notification, it can not drop a [`KeyNotification`].
+    ///
+    /// # Errors
+    ///
+    /// * `OUT_OF_RESOURCES` if [`MAX_KEY_NOTIFICATIONS`] are already registered.
+    /// * `INVALID_PARAMETER` if the key is a character outside of the basic multilingual plane.
+    pub fn register_key_notify<F>(&self, key: KeyData, notify: F) -> Result<KeyNotification<'_>, EfiError>
+    where
+        F: FnMut(&KeyData) + 'static,
+    {
+        let mut key_data = key.to_efi()?;
+        let notify: *mut KeyNotifyFn = Box::into_raw(Box::new(Box::new(notify)));
+        let Some(slot) = KEY_NOTIFY_SLOTS.iter().position(|slot| {
+            slot.compare_exchange(ptr::null_mut(), notify, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+        }) else {
+            //SAFETY: The pointer comes from Box::into_raw above and was not stored.
+            drop(unsafe { Box::from_raw(notify) });
+            return Err(efi::Status::OUT_OF_RESOURCES.into());
+        };
+
+        let mut handle = ptr::null_mut();
+        match unsafe {
+            r_efi::efi_call(
+                self.protocol().register_key_notify,
+                (self.0, &mut key_data, KEY_NOTIFY_FUNCTIONS[slot], &mut handle),
+            )
+        } {
+            s if s.is_error() => {
+                free_slot(slot);
+                Err(s.into())
+            }
+            _ => Ok(KeyNotification { protocol: self.0, handle, slot, _text_input: PhantomData }),
+        }
+    }
+}
+
+impl fmt::Debug for TextInputEx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TextInputEx").field(&self.0).finish()
+    }
+}
+
+/// Registration made by [`TextInputEx::register_key_notify`], unregistered on drop.
+#[must_use = "the notification is unregistered when dropped"]
+pub struct KeyNotification<'a> {
+    protocol: *mut SimpleTextInputExProtocol,
+    handle: *mut c_void,
+    slot: usize,
+    _text_input: PhantomData<&'a TextInputEx>,
+}
+
+impl Drop for KeyNotification<'_> {
+    fn drop(&mut self) {
+        //SAFETY: The protocol outlives the notification, as its wrapper is borrowed for the notification lifetime.
+        let unregister_key_notify = unsafe { &*self.protocol }.unregister_key_notify;
+        // The closure is only freed once the firmware will not call it anymore.
+        if !unsafe { r_efi::efi_call(unregister_key_notify, (self.protocol, self.handle)) }.is_error() {
+            free_slot(self.slot);
+        }
+    }
+}
+
+impl fmt::Debug for KeyNotification<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyNotification").field("handle", &self.handle).field("slot", &self.slot).finish()
+    }
+}
+
+type KeyNotifyFn = Box<dyn FnMut(&KeyData)>;
+
+/// Maximum number of key notifications registered at the same time.
+///
+/// The firmware does not give a context to notification functions, each registration uses one of a fixed set of
+/// functions to find its closure.
+pub const MAX_KEY_NOTIFICATIONS: usize = 16;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: AtomicPtr<KeyNotifyFn> = AtomicPtr::new(ptr::null_mut());
+static KEY_NOTIFY_SLOTS: [AtomicPtr<KeyNotifyFn>; MAX_KEY_NOTIFICATIONS] = [EMPTY_SLOT; MAX_KEY_NOTIFICATIONS];
+
+fn free_slot(slot: usize) {
+    let notify = KEY_NOTIFY_SLOTS[slot].swap(ptr::null_mut(), Ordering::SeqCst);
+    if !notify.is_null() {
+        //SAFETY: Slots only hold pointers from Box::into_raw.
+        drop(unsafe { Box::from_raw(notify) });
+    }
+}
+
+fn key_notify(slot: usize, key_data: *mut ex::KeyData) -> efi::Status {
+    let notify = KEY_NOTIFY_SLOTS[slot].load(Ordering::SeqCst);
+    //SAFETY: The firmware gives a valid key data. The closure of a slot is only freed after it is unregistered and a
+    // notification function does not run concurrently with itself.
+    if let (Some(key_data), Some(notify)) = unsafe { (key_data.as_ref(), notify.as_mut()) } {
+        notify(&KeyData::from(*key_data));
+    }
+    efi::Status::SUCCESS
+}
+
+macro_rules! key_notify_functions {
+    ($($slot:literal),*) => {
+        [$({
+            extern "efiapi" fn notify(key_data: *mut ex::KeyData) -> efi::Status {
+                key_notify($slot, key_data)
+            }
+            notify as ex::KeyNotifyFunction
+        }),*]
+    };
+}
+
+static KEY_NOTIFY_FUNCTIONS: [ex::KeyNotifyFunction; MAX_KEY_NOTIFICATIONS] =
+    key_notify_functions!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::{Cell, RefCell};
+    use std::{collections::VecDeque, rc::Rc, vec::Vec};
+
+    /// Console input returning the keys of its queue, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestInputEx {
+        protocol: SimpleTextInputExProtocol,
+        keys: RefCell<VecDeque<ex::KeyData>>,
+        toggle_state: Cell<u8>,
+        notifications: RefCell<Vec<(ex::KeyData, ex::KeyNotifyFunction)>>,
+    }
+
+    impl TestInputEx {
+        fn from_protocol<'a>(this: *mut SimpleTextInputExProtocol) -> &'a TestInputEx {
+            unsafe { &*(this as *const TestInputEx) }
+        }
+
+        fn new() -> Box<Self> {
+            Box::new(TestInputEx {
+                protocol: SimpleTextInputExProtocol {
+                    reset,
+                    read_key_stroke_ex,
+                    wait_for_key_ex: 0x1234 as efi::Event,
+                    set_state,
+                    register_key_notify,
+                    unregister_key_notify,
+                },
+                keys: RefCell::new(VecDeque::new()),
+                toggle_state: Cell::new(0),
+                notifications: RefCell::new(Vec::new()),
+            })
+        }
+
+        fn text_input_ex(&mut self) -> TextInputEx {
+            unsafe { TextInputEx::from_ptr(&mut self.protocol) }.unwrap()
+        }
+
+        /// Calls the notifications registered for the key, the way the firmware does.
+        fn press(&self, key: KeyData) {
+            let mut key_data = key.to_efi().unwrap();
+            let notifications = self.notifications.borrow().clone();
+            for (registered, notify) in notifications {
+                if registered.key.scan_code == key_data.key.scan_code
+                    && registered.key.unicode_char == key_data.key.unicode_char
+                {
+                    unsafe { r_efi::efi_call(notify, (&mut key_data,)) };
+                }
+            }
+        }
+    }
+
+    extern "efiapi" fn reset(this: *mut SimpleTextInputExProtocol, _: efi::Boolean) -> efi::Status {
+        TestInputEx::from_protocol(this).keys.borrow_mut().clear();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_key_stroke_ex(this: *mut SimpleTextInputExProtocol, key: *mut ex::KeyData) -> efi::Status {
+        match TestInputEx::from_protocol(this).keys.borrow_mut().pop_front() {
+            Some(k) => {
+                unsafe { ptr::write(key, k) };
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::NOT_READY,
+        }
+    }
+
+    extern "efiapi" fn set_state(this: *mut SimpleTextInputExProtocol, state: *mut u8) -> efi::Status {
+        TestInputEx::from_protocol(this).toggle_state.set(unsafe { *state });
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn register_key_notify(
+        this: *mut SimpleTextInputExProtocol,
+        key_data: *mut ex::KeyData,
+        notify: ex::KeyNotifyFunction,
+        handle: *mut *mut c_void,
+    ) -> efi::Status {
+        let mut notifications = TestInputEx::from_protocol(this).notifications.borrow_mut();
+        notifications.push((unsafe { *key_data }, notify));
+        unsafe { *handle = notifications.len() as *mut c_void };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn unregister_key_notify(this: *mut SimpleTextInputExProtocol, handle: *mut c_void) -> efi::Status {
+        let mut notifications = TestInputEx::from_protocol(this).notifications.borrow_mut();
+        // Unregistered entries are replaced by an entry matching no key to keep the handles stable.
+        match notifications.get_mut(handle as usize - 1) {
+            Some(entry) => {
+                entry.0.key.unicode_char = 0xFFFF;
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::INVALID_PARAMETER,
+        }
+    }
+
+    #[test]
+    fn test_read_key() {
+        let mut input = TestInputEx::new();
+        let key = KeyData::new(Key::Char('a'))
+            .with_shift_state(ShiftState::LEFT_CONTROL_PRESSED | ShiftState::RIGHT_ALT_PRESSED)
+            .with_toggle_state(ToggleState::NUM_LOCK_ACTIVE);
+        input.keys.borrow_mut().push_back(key.to_efi().unwrap());
+        input.keys.borrow_mut().push_back(KeyData::new(Key::Special(ScanCode::F1)).to_efi().unwrap());
+
+        let mut text_input = input.text_input_ex();
+        let read = text_input.read_key().unwrap().unwrap();
+        assert_eq!(key, read);
+        let shift_state = read.shift_state.unwrap();
+        assert!(shift_state.control() && shift_state.alt() && !shift_state.shift() && !shift_state.logo());
+        assert!(read.toggle_state.unwrap().contains(ToggleState::NUM_LOCK_ACTIVE));
+        assert_eq!(Some(KeyData::new(Key::Special(ScanCode::F1))), text_input.read_key().unwrap());
+        assert_eq!(None, text_input.read_key().unwrap());
+
+        text_input.set_toggle_state(ToggleState::CAPS_LOCK_ACTIVE).unwrap();
+        assert_eq!(ex::CAPS_LOCK_ACTIVE | ex::TOGGLE_STATE_VALID, input.toggle_state.get());
+    }
+
+    #[test]
+    fn test_register_key_notify() {
+        let mut input = TestInputEx::new();
+        let input_ptr = &*input as *const TestInputEx;
+        let text_input = input.text_input_ex();
+        let pressed = Rc::new(RefCell::new(Vec::new()));
+
+        let f1 = KeyData::new(Key::Special(ScanCode::F1));
+        let control_c = KeyData::new(Key::Char('c')).with_shift_state(ShiftState::LEFT_CONTROL_PRESSED);
+        let pressed_f1 = pressed.clone();
+        let notification_f1 = text_input.register_key_notify(f1, move |k| pressed_f1.borrow_mut().push(*k)).unwrap();
+        let pressed_c = pressed.clone();
+        let notification_c =
+            text_input.register_key_notify(control_c, move |k| pressed_c.borrow_mut().push(*k)).unwrap();
+
+        let input = unsafe { &*input_ptr };
+        input.press(f1);
+        input.press(control_c);
+        assert_eq!(vec![f1, control_c], *pressed.borrow());
+
+        drop(notification_f1);
+        input.press(f1);
+        assert_eq!(2, pressed.borrow().len());
+        drop(notification_c);
+        assert_eq!(1, Rc::strong_count(&pressed));
+    }
+
+    #[test]
+    fn test_register_key_notify_invalid_key() {
+        let mut input = TestInputEx::new();
+        let text_input = input.text_input_ex();
+        assert_eq!(
+            efi::Status::INVALID_PARAMETER,
+            text_input.register_key_notify(KeyData::new(Key::Char('😀')), |_| {}).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_con_in_ex_not_initialized() {
+        assert!(con_in_ex().is_none());
+    }
+}
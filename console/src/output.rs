@@ -0,0 +1,610 @@
+//! Simple Text Output protocol.
+//!
+//! [UEFI Spec Documentation: 12.4. Simple Text Output Protocol](https://uefi.org/specs/UEFI/2.10/12_Protocols_Console_Support.html#simple-text-output-protocol)
+
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, Ucs2Writer};
+
+type SimpleTextOutputProtocol = efi::protocols::simple_text_output::Protocol;
+
+/// Color of the text or of the background.
+///
+/// Only the first 8 colors, up to [`Color::LightGray`], can be used as background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Color {
+    Black = 0x00,
+    Blue = 0x01,
+    Green = 0x02,
+    Cyan = 0x03,
+    Red = 0x04,
+    Magenta = 0x05,
+    Brown = 0x06,
+    LightGray = 0x07,
+    DarkGray = 0x08,
+    LightBlue = 0x09,
+    LightGreen = 0x0A,
+    LightCyan = 0x0B,
+    LightRed = 0x0C,
+    LightMagenta = 0x0D,
+    Yellow = 0x0E,
+    White = 0x0F,
+}
+
+impl Color {
+    const ALL: [Color; 16] = [
+        Color::Black,
+        Color::Blue,
+        Color::Green,
+        Color::Cyan,
+        Color::Red,
+        Color::Magenta,
+        Color::Brown,
+        Color::LightGray,
+        Color::DarkGray,
+        Color::LightBlue,
+        Color::LightGreen,
+        Color::LightCyan,
+        Color::LightRed,
+        Color::LightMagenta,
+        Color::Yellow,
+        Color::White,
+    ];
+
+    /// Returns true if the color can be used as background.
+    pub const fn is_background(self) -> bool {
+        (self as u8) < 0x08
+    }
+}
+
+/// Foreground and background colors of the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Attribute {
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Attribute {
+    /// Creates an attribute, returns `None` if the background color is not one of the first 8 colors.
+    pub const fn new(foreground: Color, background: Color) -> Option<Self> {
+        if background.is_background() {
+            Some(Self { foreground, background })
+        } else {
+            None
+        }
+    }
+
+    /// Decodes the attribute value of the protocol, ignoring unknown bits.
+    pub const fn from_raw(attribute: usize) -> Self {
+        Self { foreground: Color::ALL[attribute & 0x0F], background: Color::ALL[(attribute >> 4) & 0x07] }
+    }
+
+    /// The attribute value of the protocol.
+    pub const fn to_raw(self) -> usize {
+        (self.foreground as usize) | ((self.background as usize) << 4)
+    }
+}
+
+impl Default for Attribute {
+    /// Light gray on black, the attribute of a console after reset.
+    fn default() -> Self {
+        Self { foreground: Color::LightGray, background: Color::Black }
+    }
+}
+
+/// A text mode supported by an output device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextMode {
+    /// Number of the mode, to give to [`TextOutput::set_mode`].
+    pub number: usize,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+/// Mode, attribute and cursor of an output device, saved by [`TextOutput::save_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsoleState {
+    pub mode: usize,
+    pub attribute: usize,
+    pub cursor_column: usize,
+    pub cursor_row: usize,
+    pub cursor_visible: bool,
+}
+
+/// Typed access to an instance of the Simple Text Output protocol.
+///
+/// Formatted text can be written with [`write!`], newlines are translated to the `\r\n` expected by consoles.
+pub struct TextOutput(*mut SimpleTextOutputProtocol);
+
+/// The console output of the system table, `None` if boot services are not available or if there is no console.
+pub fn con_out() -> Option<TextOutput> {
+    if !entry_point::boot_services_available() {
+        return None;
+    }
+    //SAFETY: The console output of the system table is valid while boot services are available.
+    unsafe { TextOutput::from_ptr(entry_point::system_table().con_out) }
+}
+
+impl TextOutput {
+    /// Wraps an instance of the protocol, returns `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to an instance of the protocol that stays valid for the life of the wrapper.
+    pub unsafe fn from_ptr(protocol: *mut SimpleTextOutputProtocol) -> Option<Self> {
+        (!protocol.is_null()).then_some(Self(protocol))
+    }
+
+    fn protocol(&self) -> &SimpleTextOutputProtocol {
+        //SAFETY: The pointer is valid for the life of the wrapper.
+        unsafe { &*self.0 }
+    }
+
+    /// Resets the output device.
+    pub fn reset(&mut self, extended_verification: bool) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().reset, (self.0, extended_verification.into())) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn mode(&self) -> &efi::protocols::simple_text_output::Mode {
+        //SAFETY: The mode of a valid protocol instance is valid.
+        unsafe { &*self.protocol().mode }
+    }
+
+    /// Number of rows and columns of a mode, `None` if the device does not support it.
+    pub fn query_mode(&self, number: usize) -> Option<TextMode> {
+        let (mut columns, mut rows) = (0, 0);
+        match unsafe { r_efi::efi_call(self.protocol().query_mode, (self.0, number, &mut columns, &mut rows)) } {
+            s if s.is_error() => None,
+            _ => Some(TextMode { number, columns, rows }),
+        }
+    }
+
+    /// Iterates over the modes supported by the device.
+    pub fn modes(&self) -> impl Iterator<Item = TextMode> + '_ {
+        (0..self.mode().max_mode.max(0) as usize).filter_map(|number| self.query_mode(number))
+    }
+
+    /// The current mode, `None` if the device is not in a valid mode.
+    pub fn current_mode(&self) -> Option<TextMode> {
+        usize::try_from(self.mode().mode).ok().and_then(|number| self.query_mode(number))
+    }
+
+    /// Sets the mode of the device, which clears the screen.
+    pub fn set_mode(&mut self, number: usize) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().set_mode, (self.0, number)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets the mode with the most characters, the first one if several have the same size.
+    pub fn set_best_mode(&mut self) -> Result<TextMode, EfiError> {
+        let mut best: Option<TextMode> = None;
+        for mode in self.modes() {
+            if best.map_or(true, |best| mode.columns * mode.rows > best.columns * best.rows) {
+                best = Some(mode);
+            }
+        }
+        let best = best.ok_or(efi::Status::UNSUPPORTED)?;
+        if self.current_mode() != Some(best) {
+            self.set_mode(best.number)?;
+        }
+        Ok(best)
+    }
+
+    /// Current column and row of the cursor.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let mode = self.mode();
+        (mode.cursor_column.max(0) as usize, mode.cursor_row.max(0) as usize)
+    }
+
+    /// Moves the cursor, the top left corner being column 0 and row 0.
+    pub fn set_cursor_position(&mut self, column: usize, row: usize) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().set_cursor_position, (self.0, column, row)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns true if the cursor is visible.
+    pub fn cursor_visible(&self) -> bool {
+        self.mode().cursor_visible.into()
+    }
+
+    /// Shows or hides the cursor.
+    pub fn enable_cursor(&mut self, visible: bool) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().enable_cursor, (self.0, visible.into())) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Saves the mode, attribute and cursor of the device, to restore them after full-screen output.
+    pub fn save_state(&self) -> ConsoleState {
+        let mode = self.mode();
+        let (cursor_column, cursor_row) = self.cursor_position();
+        ConsoleState {
+            mode: mode.mode.max(0) as usize,
+            attribute: mode.attribute.max(0) as usize,
+            cursor_column,
+            cursor_row,
+            cursor_visible: self.cursor_visible(),
+        }
+    }
+
+    /// Restores a state saved by [`TextOutput::save_state`], the mode is only set if it changed since it clears the
+    /// screen.
+    pub fn restore_state(&mut self, state: &ConsoleState) -> Result<(), EfiError> {
+        if self.mode().mode.max(0) as usize != state.mode {
+            self.set_mode(state.mode)?;
+        }
+        self.set_raw_attribute(state.attribute)?;
+        self.set_cursor_position(state.cursor_column, state.cursor_row)?;
+        self.enable_cursor(state.cursor_visible)
+    }
+
+    fn set_raw_attribute(&mut self, attribute: usize) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().set_attribute, (self.0, attribute)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The colors used for the text written.
+    pub fn attribute(&self) -> Attribute {
+        Attribute::from_raw(self.mode().attribute.max(0) as usize)
+    }
+
+    /// Sets the colors used for the text written.
+    pub fn set_attribute(&mut self, attribute: Attribute) -> Result<(), EfiError> {
+        self.set_raw_attribute(attribute.to_raw())
+    }
+
+    /// Sets the colors used for the text written.
+    ///
+    /// # Errors
+    ///
+    /// * `INVALID_PARAMETER` if the background color is not one of the first 8 colors.
+    pub fn set_color(&mut self, foreground: Color, background: Color) -> Result<(), EfiError> {
+        self.set_attribute(Attribute::new(foreground, background).ok_or(efi::Status::INVALID_PARAMETER)?)
+    }
+
+    /// Sets the colors until the returned guard is dropped, the output is written through the guard.
+    ///
+    /// ```ignore
+    /// let mut output = con_out.with_color(Color::Red, Color::Black)?;
+    /// writeln!(output, "Error: {status:?}")?;
+    /// ```
+    pub fn with_color(&mut self, foreground: Color, background: Color) -> Result<ColorGuard<'_>, EfiError> {
+        let previous = self.mode().attribute.max(0) as usize;
+        self.set_color(foreground, background)?;
+        Ok(ColorGuard { output: self, previous })
+    }
+
+    /// Clears the screen with the background color and moves the cursor to the top left corner.
+    pub fn clear_screen(&mut self) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().clear_screen, (self.0,)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes a string at the cursor position, without newline translation.
+    pub fn output_string(&mut self, s: &Str16) -> Result<(), EfiError> {
+        // The string is only read by the protocol.
+        match unsafe { r_efi::efi_call(self.protocol().output_string, (self.0, s.as_ptr() as *mut u16)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Write for TextOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut output = |s: &Str16| self.output_string(s).map_err(|_| fmt::Error);
+        let mut writer = Ucs2Writer::from_callback(&mut output);
+        let mut lines = s.split('\n');
+        if let Some(line) = lines.next() {
+            writer.write_str(line)?;
+        }
+        for line in lines {
+            writer.write_str("\r\n")?;
+            writer.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Colors set by [`TextOutput::with_color`], the previous attribute is restored on drop.
+#[must_use = "the previous colors are restored when dropped"]
+pub struct ColorGuard<'a> {
+    output: &'a mut TextOutput,
+    previous: usize,
+}
+
+impl Deref for ColorGuard<'_> {
+    type Target = TextOutput;
+
+    fn deref(&self) -> &Self::Target {
+        self.output
+    }
+}
+
+impl DerefMut for ColorGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.output
+    }
+}
+
+impl fmt::Write for ColorGuard<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.output.write_str(s)
+    }
+}
+
+impl Drop for ColorGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.output.set_raw_attribute(self.previous);
+    }
+}
+
+impl fmt::Debug for ColorGuard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ColorGuard").field("output", &self.output).field("previous", &self.previous).finish()
+    }
+}
+
+impl fmt::Debug for TextOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TextOutput").field(&self.0).finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use core::{cell::RefCell, fmt::Write, ptr};
+
+    /// Console output recording the text written to it, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    pub(crate) struct TestConsole {
+        pub protocol: SimpleTextOutputProtocol,
+        pub mode: efi::protocols::simple_text_output::Mode,
+        pub output: RefCell<String>,
+    }
+
+    impl TestConsole {
+        fn from_protocol<'a>(this: *mut SimpleTextOutputProtocol) -> &'a TestConsole {
+            unsafe { &*(this as *const TestConsole) }
+        }
+
+        pub fn new() -> Box<Self> {
+            let mut console = Box::new(TestConsole {
+                protocol: SimpleTextOutputProtocol {
+                    reset,
+                    output_string,
+                    test_string: output_string,
+                    query_mode,
+                    set_mode,
+                    set_attribute,
+                    clear_screen,
+                    set_cursor_position,
+                    enable_cursor,
+                    mode: ptr::null_mut(),
+                },
+                mode: efi::protocols::simple_text_output::Mode {
+                    max_mode: MODES.len() as i32,
+                    mode: 0,
+                    attribute: 0x07,
+                    cursor_column: 0,
+                    cursor_row: 0,
+                    cursor_visible: efi::Boolean::TRUE,
+                },
+                output: RefCell::new(String::new()),
+            });
+            console.protocol.mode = &mut console.mode;
+            console
+        }
+
+        pub fn text_output(&mut self) -> TextOutput {
+            unsafe { TextOutput::from_ptr(&mut self.protocol) }.unwrap()
+        }
+    }
+
+    extern "efiapi" fn reset(this: *mut SimpleTextOutputProtocol, _: efi::Boolean) -> efi::Status {
+        TestConsole::from_protocol(this).output.borrow_mut().clear();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn output_string(this: *mut SimpleTextOutputProtocol, s: *mut u16) -> efi::Status {
+        let s = unsafe { Str16::from_ptr(s) }.to_string_lossy();
+        TestConsole::from_protocol(this).output.borrow_mut().push_str(&s);
+        efi::Status::SUCCESS
+    }
+
+    /// Columns and rows of the test modes, mode 2 is not supported.
+    const MODES: [(usize, usize); 4] = [(80, 25), (80, 50), (0, 0), (128, 40)];
+
+    fn mode<'a>(this: *mut SimpleTextOutputProtocol) -> &'a mut efi::protocols::simple_text_output::Mode {
+        unsafe { &mut *(*this).mode }
+    }
+
+    extern "efiapi" fn query_mode(
+        _: *mut SimpleTextOutputProtocol,
+        number: usize,
+        columns: *mut usize,
+        rows: *mut usize,
+    ) -> efi::Status {
+        match MODES.get(number) {
+            Some(&(c, r)) if c != 0 => {
+                unsafe { (*columns, *rows) = (c, r) };
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    extern "efiapi" fn set_mode(this: *mut SimpleTextOutputProtocol, number: usize) -> efi::Status {
+        match MODES.get(number) {
+            Some(&(c, _)) if c != 0 => {
+                let mode = mode(this);
+                (mode.mode, mode.cursor_column, mode.cursor_row) = (number as i32, 0, 0);
+                TestConsole::from_protocol(this).output.borrow_mut().clear();
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    extern "efiapi" fn set_attribute(this: *mut SimpleTextOutputProtocol, attribute: usize) -> efi::Status {
+        mode(this).attribute = attribute as i32;
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn clear_screen(this: *mut SimpleTextOutputProtocol) -> efi::Status {
+        let mode = mode(this);
+        (mode.cursor_column, mode.cursor_row) = (0, 0);
+        TestConsole::from_protocol(this).output.borrow_mut().clear();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_cursor_position(
+        this: *mut SimpleTextOutputProtocol,
+        column: usize,
+        row: usize,
+    ) -> efi::Status {
+        let mode = mode(this);
+        let (columns, rows) = MODES[mode.mode as usize];
+        if column >= columns || row >= rows {
+            return efi::Status::UNSUPPORTED;
+        }
+        (mode.cursor_column, mode.cursor_row) = (column as i32, row as i32);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn enable_cursor(this: *mut SimpleTextOutputProtocol, visible: efi::Boolean) -> efi::Status {
+        mode(this).cursor_visible = visible;
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_write() {
+        let mut console = TestConsole::new();
+        let mut text_output = console.text_output();
+        let name = "World";
+        write!(text_output, "Hello {name}!\nBye\n").unwrap();
+        assert_eq!("Hello World!\r\nBye\r\n", *console.output.borrow());
+
+        text_output.reset(false).unwrap();
+        assert!(console.output.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_write_non_ucs2() {
+        let mut console = TestConsole::new();
+        write!(console.text_output(), "a😀\0b").unwrap();
+        assert_eq!("a\u{FFFD}\u{FFFD}b", *console.output.borrow());
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut console = TestConsole::new();
+        let mut text_output = console.text_output();
+        let modes: Vec<_> = text_output.modes().map(|m| (m.number, m.columns, m.rows)).collect();
+        assert_eq!(vec![(0, 80, 25), (1, 80, 50), (3, 128, 40)], modes);
+        assert_eq!(Some(TextMode { number: 0, columns: 80, rows: 25 }), text_output.current_mode());
+
+        assert_eq!(TextMode { number: 3, columns: 128, rows: 40 }, text_output.set_best_mode().unwrap());
+        assert_eq!(3, console.mode.mode);
+        assert_eq!(efi::Status::UNSUPPORTED, text_output.set_mode(2).unwrap_err());
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut console = TestConsole::new();
+        let mut text_output = console.text_output();
+        text_output.set_cursor_position(10, 5).unwrap();
+        assert_eq!((10, 5), text_output.cursor_position());
+        assert_eq!(efi::Status::UNSUPPORTED, text_output.set_cursor_position(80, 5).unwrap_err());
+
+        assert!(text_output.cursor_visible());
+        text_output.enable_cursor(false).unwrap();
+        assert!(!text_output.cursor_visible());
+    }
+
+    #[test]
+    fn test_save_restore_state() {
+        let mut console = TestConsole::new();
+        let mut text_output = console.text_output();
+        text_output.set_cursor_position(3, 4).unwrap();
+        let state = text_output.save_state();
+
+        text_output.set_best_mode().unwrap();
+        text_output.enable_cursor(false).unwrap();
+        text_output.set_cursor_position(90, 30).unwrap();
+        text_output.set_color(Color::White, Color::Blue).unwrap();
+
+        text_output.restore_state(&state).unwrap();
+        assert_eq!(state, text_output.save_state());
+        assert_eq!(
+            ConsoleState { mode: 0, attribute: 0x07, cursor_column: 3, cursor_row: 4, cursor_visible: true },
+            state
+        );
+    }
+
+    #[test]
+    fn test_attribute() {
+        assert_eq!(0x1F, Attribute::new(Color::White, Color::Blue).unwrap().to_raw());
+        assert_eq!(None, Attribute::new(Color::White, Color::LightBlue));
+        assert_eq!(Attribute { foreground: Color::Yellow, background: Color::Red }, Attribute::from_raw(0x4E));
+        assert_eq!(Attribute::from_raw(0x07), Attribute::default());
+
+        let mut console = TestConsole::new();
+        let mut text_output = console.text_output();
+        assert_eq!(Attribute::default(), text_output.attribute());
+        text_output.set_color(Color::Green, Color::Black).unwrap();
+        assert_eq!(0x02, console.mode.attribute);
+        assert_eq!(efi::Status::INVALID_PARAMETER, text_output.set_color(Color::Black, Color::White).unwrap_err());
+    }
+
+    #[test]
+    fn test_with_color() {
+        let mut console = TestConsole::new();
+        let mut text_output = console.text_output();
+        {
+            let mut red = text_output.with_color(Color::LightRed, Color::Black).unwrap();
+            write!(red, "Error").unwrap();
+            assert_eq!(Attribute::new(Color::LightRed, Color::Black).unwrap(), red.attribute());
+        }
+        assert_eq!(Attribute::default(), text_output.attribute());
+        assert!(text_output.with_color(Color::Red, Color::Yellow).is_err());
+        assert_eq!("Error", *console.output.borrow());
+    }
+
+    #[test]
+    fn test_clear_screen() {
+        let mut console = TestConsole::new();
+        let mut text_output = console.text_output();
+        write!(text_output, "Hello").unwrap();
+        text_output.set_cursor_position(5, 0).unwrap();
+        text_output.clear_screen().unwrap();
+        assert_eq!((0, 0), text_output.cursor_position());
+        assert!(console.output.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_con_out_not_initialized() {
+        assert!(con_out().is_none());
+        // Printing without a console does nothing.
+        crate::println!("Hello {}", 1);
+    }
+}
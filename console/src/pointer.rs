@@ -0,0 +1,603 @@
+//! Simple Pointer and Absolute Pointer protocols.
+//!
+//! [`SimplePointer`] reports the movement of a mouse since its last state was read, [`AbsolutePointer`] the position
+//! of a touch screen or digitizer. Both implement [`Pointer`] to read their next state:
+//!
+//! ```ignore
+//! let mut touch = AbsolutePointer::get(&boot_services, handle)?;
+//! let mode = touch.mode();
+//! let state = touch.read_state_blocking(&boot_services)?;
+//! let (x, y) = state.normalized(&mode).to_screen(width, height);
+//! ```
+//!
+//! [UEFI Spec Documentation: 12.5. Simple Pointer Protocol](https://uefi.org/specs/UEFI/2.10/12_Protocols_Console_Support.html#simple-pointer-protocol)
+//!
+//! [UEFI Spec Documentation: 12.7. Absolute Pointer Protocol](https://uefi.org/specs/UEFI/2.10/12_Protocols_Console_Support.html#absolute-pointer-protocol)
+
+use core::{fmt, ops::Deref};
+
+use boot_services::{
+    protocol_handler::{self, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+use efi::protocols::absolute_pointer as absolute;
+
+type AbsolutePointerProtocol = absolute::Protocol;
+
+/// FFI definitions of the Simple Pointer protocol, which r-efi does not define.
+pub mod simple {
+    use r_efi::efi;
+
+    pub const PROTOCOL_GUID: efi::Guid =
+        efi::Guid::from_fields(0x31878c87, 0x0b75, 0x11d5, 0x9a, 0x4f, &[0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d]);
+
+    pub type ProtocolReset = extern "efiapi" fn(*mut Protocol, efi::Boolean) -> efi::Status;
+
+    pub type ProtocolGetState = extern "efiapi" fn(*mut Protocol, *mut State) -> efi::Status;
+
+    /// FFI definition of `EFI_SIMPLE_POINTER_MODE`.
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Mode {
+        /// Counts per millimeter on each axis, 0 if the axis is not supported.
+        pub resolution_x: u64,
+        pub resolution_y: u64,
+        pub resolution_z: u64,
+        pub left_button: efi::Boolean,
+        pub right_button: efi::Boolean,
+    }
+
+    /// FFI definition of `EFI_SIMPLE_POINTER_STATE`.
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct State {
+        pub relative_movement_x: i32,
+        pub relative_movement_y: i32,
+        pub relative_movement_z: i32,
+        pub left_button: efi::Boolean,
+        pub right_button: efi::Boolean,
+    }
+
+    /// FFI definition of `EFI_SIMPLE_POINTER_PROTOCOL`.
+    #[repr(C)]
+    pub struct Protocol {
+        pub reset: ProtocolReset,
+        pub get_state: ProtocolGetState,
+        pub wait_for_input: efi::Event,
+        pub mode: *mut Mode,
+    }
+}
+
+/// Simple Pointer protocol.
+pub struct SimplePointerProtocol;
+
+unsafe impl ProtocolTrait for SimplePointerProtocol {
+    type Interface = simple::Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &simple::PROTOCOL_GUID
+    }
+}
+
+impl Deref for SimplePointerProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// A pointer device whose state is read when the device signals an input.
+pub trait Pointer {
+    type State;
+
+    /// Event signaled when an input is available.
+    fn wait_for_input_event(&self) -> efi::Event;
+
+    /// Reads the state of the device, returns `None` if it did not change since the last read.
+    fn read_state(&mut self) -> Result<Option<Self::State>, EfiError>;
+
+    /// Waits for the next input and reads the state of the device.
+    fn read_state_blocking<B: BootServices>(&mut self, boot_services: &B) -> Result<Self::State, EfiError> {
+        loop {
+            if let Some(state) = self.read_state()? {
+                return Ok(state);
+            }
+            boot_services.wait_for_event(&mut [self.wait_for_input_event()])?;
+        }
+    }
+
+    /// Future resolving to the next state of the device.
+    ///
+    /// The future does not register for the input event, it asks to be polled again until the state changes.
+    #[cfg(feature = "async")]
+    fn next_state(&mut self) -> NextState<'_, Self>
+    where
+        Self: Sized,
+    {
+        NextState(self)
+    }
+}
+
+/// Future returned by [`Pointer::next_state`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct NextState<'a, P: Pointer>(&'a mut P);
+
+#[cfg(feature = "async")]
+impl<P: Pointer> core::future::Future for NextState<'_, P> {
+    type Output = Result<P::State, EfiError>;
+
+    fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> core::task::Poll<Self::Output> {
+        use core::task::Poll;
+        match self.0.read_state() {
+            Ok(Some(state)) => Poll::Ready(Ok(state)),
+            Ok(None) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Err(status) => Poll::Ready(Err(status)),
+        }
+    }
+}
+
+/// Resolution and buttons of a Simple Pointer device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SimplePointerMode {
+    /// Counts per millimeter on each axis, 0 if the axis is not supported.
+    pub resolution_x: u64,
+    pub resolution_y: u64,
+    pub resolution_z: u64,
+    pub left_button: bool,
+    pub right_button: bool,
+}
+
+impl From<simple::Mode> for SimplePointerMode {
+    fn from(mode: simple::Mode) -> Self {
+        Self {
+            resolution_x: mode.resolution_x,
+            resolution_y: mode.resolution_y,
+            resolution_z: mode.resolution_z,
+            left_button: mode.left_button.into(),
+            right_button: mode.right_button.into(),
+        }
+    }
+}
+
+/// Movement of a Simple Pointer device since its last read, in counts, and state of its buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RelativeState {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub left_button: bool,
+    pub right_button: bool,
+}
+
+impl RelativeState {
+    /// The movement on the x and y axes in millimeters, 0 on an axis the device does not support.
+    pub fn millimeters(&self, mode: &SimplePointerMode) -> (f32, f32) {
+        let scale = |counts: i32, resolution: u64| match resolution {
+            0 => 0.0,
+            resolution => counts as f32 / resolution as f32,
+        };
+        (scale(self.x, mode.resolution_x), scale(self.y, mode.resolution_y))
+    }
+}
+
+impl From<simple::State> for RelativeState {
+    fn from(state: simple::State) -> Self {
+        Self {
+            x: state.relative_movement_x,
+            y: state.relative_movement_y,
+            z: state.relative_movement_z,
+            left_button: state.left_button.into(),
+            right_button: state.right_button.into(),
+        }
+    }
+}
+
+/// Typed access to an instance of the Simple Pointer protocol.
+pub struct SimplePointer(*mut simple::Protocol);
+
+impl SimplePointer {
+    /// Gets the instance of the protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &SimplePointerProtocol).map(|protocol| Self(protocol))
+    }
+
+    /// Wraps an instance of the protocol, returns `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to an instance of the protocol that stays valid for the life of the wrapper.
+    pub unsafe fn from_ptr(protocol: *mut simple::Protocol) -> Option<Self> {
+        (!protocol.is_null()).then_some(Self(protocol))
+    }
+
+    fn protocol(&self) -> &simple::Protocol {
+        //SAFETY: The pointer is valid for the life of the wrapper.
+        unsafe { &*self.0 }
+    }
+
+    /// Resets the pointer device.
+    pub fn reset(&mut self, extended_verification: bool) -> Result<(), EfiError> {
+        match (self.protocol().reset)(self.0, extended_verification.into()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn mode(&self) -> SimplePointerMode {
+        //SAFETY: The mode of a valid protocol instance is valid.
+        SimplePointerMode::from(unsafe { *self.protocol().mode })
+    }
+}
+
+impl Pointer for SimplePointer {
+    type State = RelativeState;
+
+    fn wait_for_input_event(&self) -> efi::Event {
+        self.protocol().wait_for_input
+    }
+
+    fn read_state(&mut self) -> Result<Option<RelativeState>, EfiError> {
+        let mut state = simple::State::default();
+        match (self.protocol().get_state)(self.0, &mut state) {
+            efi::Status::NOT_READY => Ok(None),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(Some(state.into())),
+        }
+    }
+}
+
+impl fmt::Debug for SimplePointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SimplePointer").field(&self.0).finish()
+    }
+}
+
+/// Range of the axes and capabilities of an Absolute Pointer device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AbsolutePointerMode {
+    pub min_x: u64,
+    pub min_y: u64,
+    pub min_z: u64,
+    pub max_x: u64,
+    pub max_y: u64,
+    /// Maximum of the z axis, 0 if the axis is not supported.
+    pub max_z: u64,
+    /// The device has a second button, such as the side button of a pen.
+    pub supports_alt_active: bool,
+    /// The z axis reports the pressure of a touch.
+    pub supports_pressure_as_z: bool,
+}
+
+impl From<absolute::Mode> for AbsolutePointerMode {
+    fn from(mode: absolute::Mode) -> Self {
+        Self {
+            min_x: mode.absolute_min_x,
+            min_y: mode.absolute_min_y,
+            min_z: mode.absolute_min_z,
+            max_x: mode.absolute_max_x,
+            max_y: mode.absolute_max_y,
+            max_z: mode.absolute_max_z,
+            supports_alt_active: mode.attributes & absolute::SUPPORTS_ALT_ACTIVE != 0,
+            supports_pressure_as_z: mode.attributes & absolute::SUPPORTS_PRESSURE_AS_Z != 0,
+        }
+    }
+}
+
+/// Position of an Absolute Pointer device, in the range of its axes, and state of its buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AbsoluteState {
+    pub x: u64,
+    pub y: u64,
+    pub z: u64,
+    pub touch_active: bool,
+    pub alt_active: bool,
+}
+
+impl AbsoluteState {
+    /// The position relative to the range of the axes of the device.
+    pub fn normalized(&self, mode: &AbsolutePointerMode) -> NormalizedPosition {
+        let scale = |value: u64, min: u64, max: u64| match max.saturating_sub(min) {
+            0 => 0.0,
+            range => (value.clamp(min, max) - min) as f32 / range as f32,
+        };
+        NormalizedPosition {
+            x: scale(self.x, mode.min_x, mode.max_x),
+            y: scale(self.y, mode.min_y, mode.max_y),
+            pressure: mode.supports_pressure_as_z.then(|| scale(self.z, mode.min_z, mode.max_z)),
+        }
+    }
+}
+
+impl From<absolute::State> for AbsoluteState {
+    fn from(state: absolute::State) -> Self {
+        Self {
+            x: state.current_x,
+            y: state.current_y,
+            z: state.current_z,
+            touch_active: state.active_buttons & absolute::TOUCH_ACTIVE != 0,
+            alt_active: state.active_buttons & absolute::ALT_ACTIVE != 0,
+        }
+    }
+}
+
+/// Position of an Absolute Pointer device, each axis from 0.0 at its minimum to 1.0 at its maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NormalizedPosition {
+    pub x: f32,
+    pub y: f32,
+    /// The pressure of the touch, `None` if the device does not report it.
+    pub pressure: Option<f32>,
+}
+
+impl NormalizedPosition {
+    /// The nearest pixel of a screen of `width` by `height` pixels.
+    pub fn to_screen(&self, width: usize, height: usize) -> (usize, usize) {
+        let scale = |position: f32, size: usize| (position * size.saturating_sub(1) as f32 + 0.5) as usize;
+        (scale(self.x, width), scale(self.y, height))
+    }
+}
+
+/// Typed access to an instance of the Absolute Pointer protocol.
+pub struct AbsolutePointer(*mut AbsolutePointerProtocol);
+
+impl AbsolutePointer {
+    /// Gets the instance of the protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::AbsolutePointer).map(|protocol| Self(protocol))
+    }
+
+    /// Wraps an instance of the protocol, returns `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to an instance of the protocol that stays valid for the life of the wrapper.
+    pub unsafe fn from_ptr(protocol: *mut AbsolutePointerProtocol) -> Option<Self> {
+        (!protocol.is_null()).then_some(Self(protocol))
+    }
+
+    fn protocol(&self) -> &AbsolutePointerProtocol {
+        //SAFETY: The pointer is valid for the life of the wrapper.
+        unsafe { &*self.0 }
+    }
+
+    /// Resets the pointer device.
+    pub fn reset(&mut self, extended_verification: bool) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().reset, (self.0, extended_verification)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn mode(&self) -> AbsolutePointerMode {
+        //SAFETY: The mode of a valid protocol instance is valid.
+        AbsolutePointerMode::from(unsafe { *self.protocol().mode })
+    }
+}
+
+impl Pointer for AbsolutePointer {
+    type State = AbsoluteState;
+
+    fn wait_for_input_event(&self) -> efi::Event {
+        self.protocol().wait_for_input
+    }
+
+    fn read_state(&mut self) -> Result<Option<AbsoluteState>, EfiError> {
+        let mut state = absolute::State::default();
+        match unsafe { r_efi::efi_call(self.protocol().get_state, (self.0, &mut state)) } {
+            efi::Status::NOT_READY => Ok(None),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(Some(state.into())),
+        }
+    }
+}
+
+impl fmt::Debug for AbsolutePointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AbsolutePointer").field(&self.0).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, ptr};
+    use std::collections::VecDeque;
+
+    /// Mouse returning the movements of its queue, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestMouse {
+        protocol: simple::Protocol,
+        mode: simple::Mode,
+        states: RefCell<VecDeque<simple::State>>,
+    }
+
+    impl TestMouse {
+        fn from_protocol<'a>(this: *mut simple::Protocol) -> &'a TestMouse {
+            unsafe { &*(this as *const TestMouse) }
+        }
+
+        fn new() -> Box<Self> {
+            let mut mouse = Box::new(TestMouse {
+                protocol: simple::Protocol {
+                    reset: mouse_reset,
+                    get_state: mouse_get_state,
+                    wait_for_input: 0x1234 as efi::Event,
+                    mode: ptr::null_mut(),
+                },
+                mode: simple::Mode {
+                    resolution_x: 8,
+                    resolution_y: 8,
+                    resolution_z: 0,
+                    left_button: efi::Boolean::TRUE,
+                    right_button: efi::Boolean::TRUE,
+                },
+                states: RefCell::new(VecDeque::new()),
+            });
+            mouse.protocol.mode = &mut mouse.mode;
+            mouse
+        }
+
+        fn push(&self, x: i32, y: i32, left_button: bool) {
+            self.states.borrow_mut().push_back(simple::State {
+                relative_movement_x: x,
+                relative_movement_y: y,
+                left_button: left_button.into(),
+                ..Default::default()
+            });
+        }
+    }
+
+    extern "efiapi" fn mouse_reset(this: *mut simple::Protocol, _: efi::Boolean) -> efi::Status {
+        TestMouse::from_protocol(this).states.borrow_mut().clear();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn mouse_get_state(this: *mut simple::Protocol, state: *mut simple::State) -> efi::Status {
+        match TestMouse::from_protocol(this).states.borrow_mut().pop_front() {
+            Some(s) => {
+                unsafe { ptr::write(state, s) };
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::NOT_READY,
+        }
+    }
+
+    /// Touch screen with axes from 100 to 1100, reporting the pressure of a touch.
+    #[repr(C)]
+    struct TestTouch {
+        protocol: AbsolutePointerProtocol,
+        mode: absolute::Mode,
+        state: RefCell<Option<absolute::State>>,
+    }
+
+    impl TestTouch {
+        fn new() -> Box<Self> {
+            let mut touch = Box::new(TestTouch {
+                protocol: AbsolutePointerProtocol {
+                    reset: touch_reset,
+                    get_state: touch_get_state,
+                    wait_for_input: 0x5678 as efi::Event,
+                    mode: ptr::null_mut(),
+                },
+                mode: absolute::Mode {
+                    absolute_min_x: 100,
+                    absolute_min_y: 100,
+                    absolute_min_z: 0,
+                    absolute_max_x: 1100,
+                    absolute_max_y: 1100,
+                    absolute_max_z: 255,
+                    attributes: absolute::SUPPORTS_PRESSURE_AS_Z,
+                },
+                state: RefCell::new(None),
+            });
+            touch.protocol.mode = &mut touch.mode;
+            touch
+        }
+    }
+
+    extern "efiapi" fn touch_reset(_: *mut AbsolutePointerProtocol, _: bool) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn touch_get_state(this: *mut AbsolutePointerProtocol, state: *mut absolute::State) -> efi::Status {
+        match unsafe { &*(this as *const TestTouch) }.state.borrow_mut().take() {
+            Some(s) => {
+                unsafe { ptr::write(state, s) };
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::NOT_READY,
+        }
+    }
+
+    #[test]
+    fn test_simple_pointer() {
+        let mut mouse = TestMouse::new();
+        mouse.push(16, -4, true);
+        mouse.push(1, 1, false);
+
+        let mut pointer = unsafe { SimplePointer::from_ptr(&mut mouse.protocol) }.unwrap();
+        let mode = pointer.mode();
+        assert_eq!(8, mode.resolution_x);
+        assert!(mode.left_button && mode.right_button);
+
+        let state = pointer.read_state().unwrap().unwrap();
+        assert_eq!(RelativeState { x: 16, y: -4, z: 0, left_button: true, right_button: false }, state);
+        assert_eq!((2.0, -0.5), state.millimeters(&mode));
+
+        pointer.reset(false).unwrap();
+        assert_eq!(None, pointer.read_state().unwrap());
+    }
+
+    #[test]
+    fn test_read_state_blocking() {
+        let mut mouse = TestMouse::new();
+        let mouse_ptr = &*mouse as *const TestMouse as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_wait_for_event().once().returning(move |events| {
+            assert_eq!(&[0x1234 as efi::Event], events);
+            // The mouse moves while waiting.
+            unsafe { &*(mouse_ptr as *const TestMouse) }.push(0, 3, false);
+            Ok(0)
+        });
+        let mut pointer = unsafe { SimplePointer::from_ptr(&mut mouse.protocol) }.unwrap();
+        assert_eq!(3, pointer.read_state_blocking(&boot_services).unwrap().y);
+    }
+
+    #[test]
+    fn test_absolute_pointer() {
+        let mut touch = TestTouch::new();
+        let mut pointer = unsafe { AbsolutePointer::from_ptr(&mut touch.protocol) }.unwrap();
+        let mode = pointer.mode();
+        assert_eq!(1100, mode.max_x);
+        assert!(mode.supports_pressure_as_z && !mode.supports_alt_active);
+        assert_eq!(None, pointer.read_state().unwrap());
+
+        touch.state.replace(Some(absolute::State {
+            current_x: 600,
+            current_y: 1200,
+            current_z: 51,
+            active_buttons: absolute::TOUCH_ACTIVE,
+        }));
+        let state = pointer.read_state().unwrap().unwrap();
+        assert!(state.touch_active && !state.alt_active);
+        let position = state.normalized(&mode);
+        assert_eq!(NormalizedPosition { x: 0.5, y: 1.0, pressure: Some(0.2) }, position);
+        assert_eq!((400, 599), position.to_screen(800, 600));
+
+        let mode = AbsolutePointerMode { max_x: 10, max_y: 10, ..Default::default() };
+        assert_eq!(None, AbsoluteState { x: 5, ..Default::default() }.normalized(&mode).pressure);
+        assert_eq!(0.0, AbsoluteState::default().normalized(&AbsolutePointerMode::default()).x);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_next_state() {
+        use core::{
+            future::Future,
+            pin::pin,
+            task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+        };
+
+        const VTABLE: RawWakerVTable =
+            RawWakerVTable::new(|_| RawWaker::new(ptr::null(), &VTABLE), |_| {}, |_| {}, |_| {});
+
+        let mut mouse = TestMouse::new();
+        let mouse_ptr = &*mouse as *const TestMouse;
+        let mut pointer = unsafe { SimplePointer::from_ptr(&mut mouse.protocol) }.unwrap();
+        let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+        let mut cx = Context::from_waker(&waker);
+        let mut next_state = pin!(pointer.next_state());
+        assert_eq!(Poll::Pending, next_state.as_mut().poll(&mut cx));
+        unsafe { &*mouse_ptr }.push(-2, 0, false);
+        assert!(matches!(next_state.as_mut().poll(&mut cx), Poll::Ready(Ok(RelativeState { x: -2, .. }))));
+    }
+}
@@ -0,0 +1,398 @@
+//! Text user interface building blocks over the console wrappers: tables, progress bars and menus.
+//!
+//! [`Table`] and [`ProgressBar`] write to any [`fmt::Write`], like a [`TextOutput`] or a log buffer, [`Menu`] reads the
+//! keys of a [`TextInput`]:
+//!
+//! ```ignore
+//! let mut table = Table::new(["Device", "Size"]).align(1, Alignment::Right);
+//! for disk in &disks {
+//!     table.row([disk.name(), format!("{} MiB", disk.size() >> 20)]);
+//! }
+//! table.render(&mut con_out)?;
+//!
+//! let clock = Clock::locate(&boot_services)?;
+//! let (start, mut progress) = (clock.now(), ProgressBar::new("Flashing", image.len() as u64));
+//! for (index, block) in image.chunks(BLOCK_SIZE).enumerate() {
+//!     write_block(index, block)?;
+//!     progress.update(&mut con_out, ((index + 1) * BLOCK_SIZE) as u64, start.elapsed())?;
+//! }
+//! progress.finish(&mut con_out)?;
+//!
+//! match Menu::new(&["Boot", "Setup", "Reset"]).run(&mut con_out, &mut con_in, &boot_services)? {
+//!     Some(choice) => ...,
+//!     None => ..., // Escape.
+//! }
+//! ```
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Write},
+    time::Duration,
+};
+
+use boot_services::BootServices;
+use r_efi::efi;
+use status::EfiError;
+
+use crate::{Color, Key, ScanCode, TextInput, TextOutput};
+
+/// Alignment of the cells of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Right,
+}
+
+/// Columns of text aligned under their header.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    header: Vec<String>,
+    alignments: Vec<Alignment>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Spaces between the columns of a [`Table`].
+const COLUMN_GAP: usize = 2;
+
+impl Table {
+    /// A table with the columns of `header`, left aligned.
+    pub fn new<S: ToString>(header: impl IntoIterator<Item = S>) -> Self {
+        let header = header.into_iter().map(|cell| cell.to_string()).collect::<Vec<_>>();
+        Self { alignments: alloc::vec![Alignment::Left; header.len()], header, rows: Vec::new() }
+    }
+
+    /// Aligns the cells of the column at `index`, which is ignored if it is not a column of the header.
+    pub fn align(mut self, index: usize, alignment: Alignment) -> Self {
+        if let Some(column) = self.alignments.get_mut(index) {
+            *column = alignment;
+        }
+        self
+    }
+
+    /// Adds a row, the cells beyond the columns of the header are ignored and the missing ones are empty.
+    pub fn row<S: ToString>(&mut self, cells: impl IntoIterator<Item = S>) -> &mut Self {
+        let mut row = cells.into_iter().take(self.header.len()).map(|cell| cell.to_string()).collect::<Vec<_>>();
+        row.resize(self.header.len(), String::new());
+        self.rows.push(row);
+        self
+    }
+
+    /// The number of rows, without the header.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns true if the table has no row.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Writes the header, a line under it and the rows, one line each.
+    pub fn render<W: Write + ?Sized>(&self, output: &mut W) -> fmt::Result {
+        let widths = (0..self.header.len())
+            .map(|column| {
+                let cells = core::iter::once(&self.header[column]).chain(self.rows.iter().map(|row| &row[column]));
+                cells.map(|cell| cell.chars().count()).max().unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        self.render_line(output, &widths, &self.header)?;
+        let rule = widths.iter().sum::<usize>() + COLUMN_GAP * widths.len().saturating_sub(1);
+        writeln!(output, "{:-<rule$}", "")?;
+        for row in &self.rows {
+            self.render_line(output, &widths, row)?;
+        }
+        Ok(())
+    }
+
+    fn render_line<W: Write + ?Sized>(&self, output: &mut W, widths: &[usize], cells: &[String]) -> fmt::Result {
+        let mut line = String::new();
+        for (column, (cell, &width)) in cells.iter().zip(widths).enumerate() {
+            if column > 0 {
+                line.extend([' '; COLUMN_GAP]);
+            }
+            match self.alignments[column] {
+                Alignment::Left => write!(line, "{cell:<width$}")?,
+                Alignment::Right => write!(line, "{cell:>width$}")?,
+            }
+        }
+        writeln!(output, "{}", line.trim_end())
+    }
+}
+
+/// A progress bar on one line, redrawn in place with a carriage return.
+///
+/// ```text
+/// Flashing [##############----------------]  45% ETA 0:12
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProgressBar {
+    label: String,
+    total: u64,
+    width: usize,
+    /// The last line drawn, to only redraw when it changes.
+    line: String,
+}
+
+/// Columns of the bar of a [`ProgressBar`] by default.
+const BAR_WIDTH: usize = 30;
+
+impl ProgressBar {
+    /// A progress bar of `label` towards `total`, in any unit.
+    pub fn new(label: &str, total: u64) -> Self {
+        Self { label: label.to_string(), total, width: BAR_WIDTH, line: String::new() }
+    }
+
+    /// Sets the columns of the bar, without the label, the percentage and the ETA.
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Draws the bar at `done` out of the total, `elapsed` since the start estimating the time left.
+    ///
+    /// Nothing is written when the line is the same as the one drawn last, so the bar can be updated often on slow
+    /// consoles like the serial ones.
+    pub fn update<W: Write + ?Sized>(&mut self, output: &mut W, done: u64, elapsed: Duration) -> fmt::Result {
+        let done = done.min(self.total);
+        let mut line = String::new();
+        let (filled, percent) = match self.total {
+            0 => (self.width, 100),
+            total => (
+                (done as u128 * self.width as u128 / total as u128) as usize,
+                (done as u128 * 100 / total as u128) as u8,
+            ),
+        };
+        write!(line, "{} [{:#<filled$}{:-<empty$}] {percent:>3}%", self.label, "", "", empty = self.width - filled)?;
+        if done > 0 && done < self.total {
+            let left = elapsed.as_millis() * (self.total - done) as u128 / done as u128 / 1000;
+            let (minutes, seconds) = (left / 60, left % 60);
+            write!(line, " ETA {minutes}:{seconds:02}")?;
+        }
+        if line == self.line {
+            return Ok(());
+        }
+        // Blanks the end of a longer line drawn before.
+        let width = self.line.chars().count();
+        write!(output, "\r{line:<width$}")?;
+        self.line = line;
+        Ok(())
+    }
+
+    /// Draws the bar complete and ends its line.
+    pub fn finish<W: Write + ?Sized>(mut self, output: &mut W) -> fmt::Result {
+        self.update(output, self.total, Duration::ZERO)?;
+        writeln!(output)
+    }
+}
+
+/// A list of choices selected with the arrow keys and enter, or directly with the digit of the first nine.
+#[derive(Debug, Clone)]
+pub struct Menu<'a> {
+    items: &'a [&'a str],
+    selected: usize,
+}
+
+impl<'a> Menu<'a> {
+    /// A menu of `items`, the first one selected.
+    pub fn new(items: &'a [&'a str]) -> Self {
+        Self { items, selected: 0 }
+    }
+
+    /// Selects the item at `index` initially, the last one if it is beyond.
+    pub fn selected(mut self, index: usize) -> Self {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+        self
+    }
+
+    /// Draws the menu from the cursor and waits for a choice, returns `None` on escape.
+    ///
+    /// The cursor is hidden while the menu is shown, and moved to the line after it when it returns.
+    ///
+    /// # Errors
+    ///
+    /// * `INVALID_PARAMETER` if the menu has no item.
+    pub fn run<B: BootServices>(
+        mut self,
+        output: &mut TextOutput,
+        input: &mut TextInput,
+        boot_services: &B,
+    ) -> Result<Option<usize>, EfiError> {
+        if self.items.is_empty() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let cursor_visible = output.cursor_visible();
+        // Consoles without cursor fail to hide it, the menu works regardless.
+        let _ = output.enable_cursor(false);
+        let result = self.select(output, input, boot_services);
+        let _ = output.enable_cursor(cursor_visible);
+        result
+    }
+
+    fn select<B: BootServices>(
+        &mut self,
+        output: &mut TextOutput,
+        input: &mut TextInput,
+        boot_services: &B,
+    ) -> Result<Option<usize>, EfiError> {
+        let width = self.items.iter().map(|item| item.chars().count()).max().unwrap_or_default();
+        for index in 0..self.items.len() {
+            if index > 0 {
+                output.write_str("\n").map_err(|_| efi::Status::DEVICE_ERROR)?;
+            }
+            self.draw_item(output, index, width)?;
+        }
+        // The console scrolls when the menu is drawn at its bottom.
+        let top = output.cursor_position().1.saturating_sub(self.items.len() - 1);
+        let last = self.items.len() - 1;
+        let choice = loop {
+            let selected = match input.read_key_blocking(boot_services)? {
+                Key::Char('\r') => break Some(self.selected),
+                Key::Special(ScanCode::ESC) => break None,
+                Key::Char(digit @ '1'..='9') => {
+                    let index = digit as usize - '1' as usize;
+                    if index <= last {
+                        self.move_to(output, top, index, width)?;
+                        break Some(index);
+                    }
+                    continue;
+                }
+                Key::Special(ScanCode::UP) => self.selected.checked_sub(1).unwrap_or(last),
+                Key::Special(ScanCode::DOWN) => (self.selected + 1) % self.items.len(),
+                Key::Special(ScanCode::HOME) => 0,
+                Key::Special(ScanCode::END) => last,
+                _ => continue,
+            };
+            self.move_to(output, top, selected, width)?;
+        };
+        output.set_cursor_position(0, top + last)?;
+        output.write_str("\n").map_err(|_| efi::Status::DEVICE_ERROR)?;
+        Ok(choice)
+    }
+
+    /// Selects the item at `index`, redrawing only the items whose selection changed.
+    fn move_to(&mut self, output: &mut TextOutput, top: usize, index: usize, width: usize) -> Result<(), EfiError> {
+        let previous = core::mem::replace(&mut self.selected, index);
+        for index in [previous, index] {
+            output.set_cursor_position(0, top + index)?;
+            self.draw_item(output, index, width)?;
+        }
+        Ok(())
+    }
+
+    fn draw_item(&self, output: &mut TextOutput, index: usize, width: usize) -> Result<(), EfiError> {
+        let item = self.items[index];
+        match index == self.selected {
+            true => {
+                output.write_str("> ").map_err(|_| efi::Status::DEVICE_ERROR)?;
+                let mut highlight = output.with_color(Color::Black, Color::LightGray)?;
+                write!(highlight, "{item:<width$}").map_err(|_| efi::Status::DEVICE_ERROR.into())
+            }
+            false => write!(output, "  {item:<width$}").map_err(|_| efi::Status::DEVICE_ERROR.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{input::test::TestInput, output::test::TestConsole};
+    use boot_services::MockBootServices;
+
+    #[test]
+    fn test_table() {
+        let mut table = Table::new(["Device", "Size", "Status"]).align(1, Alignment::Right).align(5, Alignment::Right);
+        assert!(table.is_empty());
+        table.row(["NVMe0", "512 GiB", "Ready"]).row(["USB", "8 GiB"]).row(["SATA", "1 TiB", "Failed", "extra"]);
+        assert_eq!(3, table.len());
+
+        let mut output = String::new();
+        table.render(&mut output).unwrap();
+        assert_eq!(
+            "Device     Size  Status\n\
+             -----------------------\n\
+             NVMe0   512 GiB  Ready\n\
+             USB       8 GiB\n\
+             SATA      1 TiB  Failed\n",
+            output
+        );
+
+        // The newlines are translated by the console.
+        let mut console = TestConsole::new();
+        Table::new(["é"]).render(&mut console.text_output()).unwrap();
+        assert_eq!("é\r\n-\r\n", *console.output.borrow());
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        let mut output = String::new();
+        let mut progress = ProgressBar::new("Flashing", 200).width(10);
+        progress.update(&mut output, 0, Duration::ZERO).unwrap();
+        assert_eq!("\rFlashing [----------]   0%", output);
+
+        output.clear();
+        progress.update(&mut output, 50, Duration::from_secs(20)).unwrap();
+        assert_eq!("\rFlashing [##--------]  25% ETA 1:00", output);
+
+        // Same line, nothing is redrawn.
+        output.clear();
+        progress.update(&mut output, 50, Duration::from_millis(20_200)).unwrap();
+        assert!(output.is_empty());
+
+        // The end of the longer line drawn before is blanked.
+        output.clear();
+        progress.finish(&mut output).unwrap();
+        assert_eq!("\rFlashing [##########] 100%         \n", output);
+
+        output.clear();
+        ProgressBar::new("Empty", 0).width(4).finish(&mut output).unwrap();
+        assert_eq!("\rEmpty [####] 100%\n", output);
+    }
+
+    fn run_menu(menu: Menu<'_>, keys: &[(ScanCode, u16)]) -> (Option<usize>, String, bool) {
+        let mut console = TestConsole::new();
+        let mut input = TestInput::new();
+        for &(scan_code, unicode_char) in keys {
+            input.push(scan_code, unicode_char);
+        }
+        let mut text_output = console.text_output();
+        let choice = menu.run(&mut text_output, &mut input.text_input(), &MockBootServices::new()).unwrap();
+        let output = console.output.borrow().clone();
+        (choice, output, text_output.cursor_visible())
+    }
+
+    #[test]
+    fn test_menu() {
+        let items = ["Boot", "Setup", "Reset"];
+        let (choice, output, cursor_visible) = run_menu(
+            Menu::new(&items),
+            &[(ScanCode::DOWN, 0), (ScanCode::F1, 0), (ScanCode::NULL, b'x' as u16), (ScanCode::NULL, b'\r' as u16)],
+        );
+        assert_eq!(Some(1), choice);
+        assert!(output.starts_with("> Boot \r\n  Setup\r\n  Reset"));
+        // Only the items whose selection changed are redrawn.
+        assert!(output.ends_with("  Reset  Boot > Setup\r\n"));
+        assert!(cursor_visible);
+
+        let (choice, ..) = run_menu(Menu::new(&items), &[(ScanCode::UP, 0), (ScanCode::NULL, b'\r' as u16)]);
+        assert_eq!(Some(2), choice);
+        let (choice, ..) =
+            run_menu(Menu::new(&items).selected(9), &[(ScanCode::HOME, 0), (ScanCode::NULL, b'\r' as u16)]);
+        assert_eq!(Some(0), choice);
+        let (choice, ..) = run_menu(Menu::new(&items), &[(ScanCode::NULL, b'4' as u16), (ScanCode::NULL, b'3' as u16)]);
+        assert_eq!(Some(2), choice);
+        let (choice, ..) = run_menu(Menu::new(&items).selected(1), &[(ScanCode::END, 0), (ScanCode::ESC, 0)]);
+        assert_eq!(None, choice);
+    }
+
+    #[test]
+    fn test_empty_menu() {
+        let mut console = TestConsole::new();
+        let mut input = TestInput::new();
+        let result = Menu::new(&[]).run(&mut console.text_output(), &mut input.text_input(), &MockBootServices::new());
+        assert_eq!(efi::Status::INVALID_PARAMETER, result.unwrap_err());
+    }
+}
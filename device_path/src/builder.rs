@@ -0,0 +1,156 @@
+//! Building device paths from nodes.
+
+use alloc::vec::Vec;
+use core::ptr;
+
+use boot_services::{allocation::MemoryType, boxed::BootServicesBox, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::{node_types::DevicePathNode, DevicePath, DevicePathBuf, Node};
+
+const END_INSTANCE: [u8; 4] =
+    [efi::protocols::device_path::TYPE_END, efi::protocols::device_path::End::SUBTYPE_INSTANCE, 0x04, 0x00];
+pub(crate) const END_ENTIRE: [u8; 4] =
+    [efi::protocols::device_path::TYPE_END, efi::protocols::device_path::End::SUBTYPE_ENTIRE, 0x04, 0x00];
+
+/// Builds a device path by appending nodes, the end of entire device path node is added when building it.
+///
+/// ```ignore
+/// let device_path = DevicePathBuilder::new()
+///     .push(&Acpi::pci_root(0))
+///     .push(&Pci::new(0x1F, 2))
+///     .push(&Sata::new(0, 0xFFFF, 0))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DevicePathBuilder(Vec<u8>);
+
+impl DevicePathBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Creates a builder starting with the nodes of a device path, like a parent device path.
+    pub fn from_device_path(device_path: &DevicePath) -> Self {
+        let mut builder = Self::new();
+        builder.append(device_path);
+        builder
+    }
+
+    /// Appends a typed node.
+    pub fn push<T: DevicePathNode>(&mut self, node: &T) -> &mut Self {
+        self.0.extend_from_slice(&node.to_bytes());
+        self
+    }
+
+    /// Appends a node of another device path.
+    pub fn push_node(&mut self, node: Node<'_>) -> &mut Self {
+        self.0.extend_from_slice(node.as_bytes());
+        self
+    }
+
+    /// Appends the nodes of a device path, without its end of entire device path node.
+    pub fn append(&mut self, device_path: &DevicePath) -> &mut Self {
+        self.0.extend_from_slice(&device_path.as_bytes()[..device_path.size() - END_ENTIRE.len()]);
+        self
+    }
+
+    /// Ends the current instance, the next nodes are part of a new instance of a multi-instance device path.
+    pub fn end_instance(&mut self) -> &mut Self {
+        self.0.extend_from_slice(&END_INSTANCE);
+        self
+    }
+
+    /// Size in bytes of the device path that will be built, including the end of entire device path node.
+    pub fn size(&self) -> usize {
+        self.0.len() + END_ENTIRE.len()
+    }
+
+    /// Builds the device path in memory owned by Rust.
+    pub fn build(&self) -> DevicePathBuf {
+        let mut bytes = Vec::with_capacity(self.size());
+        bytes.extend_from_slice(&self.0);
+        bytes.extend_from_slice(&END_ENTIRE);
+        //SAFETY: The bytes are complete nodes followed by the end of entire device path node.
+        unsafe { DevicePathBuf::from_vec_unchecked(bytes) }
+    }
+
+    /// Builds the device path in pool memory, for device paths given to the firmware that frees them.
+    pub fn build_in_pool<'a, B: BootServices>(
+        &self,
+        boot_services: &'a B,
+        memory_type: MemoryType,
+    ) -> Result<BootServicesBox<'a, DevicePath, B>, EfiError> {
+        let size = self.size();
+        let buffer = boot_services.allocate_pool(memory_type, size)?;
+        //SAFETY: The buffer was allocated with the size of the device path, which is valid once copied.
+        unsafe {
+            ptr::copy_nonoverlapping(self.0.as_ptr(), buffer, self.0.len());
+            ptr::copy_nonoverlapping(END_ENTIRE.as_ptr(), buffer.add(self.0.len()), END_ENTIRE.len());
+            Ok(BootServicesBox::from_raw(ptr::slice_from_raw_parts_mut(buffer, size) as *mut DevicePath, boot_services))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::node_types::{Acpi, FilePath, Pci};
+    use boot_services::MockBootServices;
+    use ucs2::String16;
+
+    const PCI_ROOT_PCI_AND_END: [u8; 22] = [
+        0x02, 0x01, 0x0C, 0x00, 0xD0, 0x41, 0x03, 0x0A, 0x00, 0x00, 0x00, 0x00, // PciRoot(0x0)
+        0x01, 0x01, 0x06, 0x00, 0x00, 0x1F, // Pci(0x1F,0x0)
+        0x7F, 0xFF, 0x04, 0x00, // End
+    ];
+
+    #[test]
+    fn test_build() {
+        let device_path = DevicePathBuilder::new().push(&Acpi::pci_root(0)).push(&Pci::new(0x1F, 0)).build();
+        assert_eq!(&PCI_ROOT_PCI_AND_END, device_path.as_bytes());
+        assert!(DevicePathBuilder::new().build().is_end());
+    }
+
+    #[test]
+    fn test_build_from_device_path() {
+        let parent = DevicePath::from_bytes(&PCI_ROOT_PCI_AND_END).unwrap();
+        let file_path = FilePath::new(String16::try_from("a").unwrap());
+        let device_path = DevicePathBuilder::from_device_path(parent).push(&file_path).build();
+
+        let nodes = device_path.nodes().collect::<Vec<_>>();
+        assert_eq!(3, nodes.len());
+        assert_eq!(Some(file_path), nodes[2].parse());
+        assert_eq!(&PCI_ROOT_PCI_AND_END[..18], &device_path.as_bytes()[..18]);
+
+        let copy = DevicePathBuilder::new().push_node(nodes[0]).push_node(nodes[1]).build();
+        assert_eq!(parent, &*copy);
+    }
+
+    #[test]
+    fn test_build_multi_instance() {
+        let device_path = DevicePathBuilder::new().push(&Pci::new(0, 0)).end_instance().push(&Pci::new(1, 0)).build();
+        assert_eq!(20, device_path.size());
+        assert!(device_path.nodes().nth(1).unwrap().is_end_instance());
+    }
+
+    #[test]
+    fn test_build_in_pool() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_allocate_pool()
+            .withf(|memory_type, size| *memory_type == MemoryType::BOOT_SERVICES_DATA && *size == 22)
+            .once()
+            .returning(|_, size| Ok(Vec::leak(vec![0; size]).as_mut_ptr()));
+        boot_services.expect_free_pool().once().returning(|_| Ok(()));
+
+        let device_path = DevicePathBuilder::new()
+            .push(&Acpi::pci_root(0))
+            .push(&Pci::new(0x1F, 0))
+            .build_in_pool(&boot_services, MemoryType::BOOT_SERVICES_DATA)
+            .unwrap();
+        assert_eq!(&PCI_ROOT_PCI_AND_END, device_path.as_bytes());
+    }
+}
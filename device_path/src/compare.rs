@@ -0,0 +1,157 @@
+//! Comparison and matching of device paths, including multi-instance device paths.
+
+use alloc::vec::Vec;
+
+use crate::{
+    node_types::{DevicePathNode, FilePath},
+    DevicePath, DevicePathBuf, DevicePathBuilder, Node, Nodes,
+};
+
+impl DevicePath {
+    /// Returns true if the nodes of `prefix` are the first nodes of the device path.
+    ///
+    /// Used to find the device path of a handle in a boot option, which continues with the media and file nodes.
+    pub fn starts_with(&self, prefix: &DevicePath) -> bool {
+        let mut nodes = self.nodes();
+        prefix.nodes().all(|node| nodes.next() == Some(node))
+    }
+
+    /// Returns true if the last node is a file path node.
+    pub fn ends_with_file_path(&self) -> bool {
+        self.nodes()
+            .last()
+            .is_some_and(|node| (node.node_type(), node.sub_type()) == (FilePath::TYPE, FilePath::SUB_TYPE))
+    }
+
+    /// Compares the nodes of two device paths, ignoring a trailing end of device path instance node.
+    pub fn structurally_eq(&self, other: &DevicePath) -> bool {
+        fn without_trailing_end(device_path: &DevicePath) -> Vec<Node<'_>> {
+            let mut nodes = device_path.nodes().collect::<Vec<_>>();
+            if nodes.last().is_some_and(Node::is_end_instance) {
+                nodes.pop();
+            }
+            nodes
+        }
+        without_trailing_end(self) == without_trailing_end(other)
+    }
+
+    /// Returns true if the device path has more than one instance.
+    pub fn is_multi_instance(&self) -> bool {
+        self.instances().nth(1).is_some()
+    }
+
+    /// Iterator over the instances of a multi-instance device path, each as its own device path.
+    ///
+    /// A single-instance device path has one instance and a device path with only an end node has none.
+    pub fn instances(&self) -> Instances<'_> {
+        Instances(self.nodes())
+    }
+
+    /// Returns true if one of the instances is structurally equal to `instance`.
+    pub fn contains_instance(&self, instance: &DevicePath) -> bool {
+        self.instances().any(|i| i.structurally_eq(instance))
+    }
+
+    /// Returns the device path without the instances that are structurally equal to `instance`, or `None` if there
+    /// was no such instance.
+    ///
+    /// Used to remove a device from a multi-instance variable like `ConOut`.
+    pub fn without_instance(&self, instance: &DevicePath) -> Option<DevicePathBuf> {
+        let mut builder = DevicePathBuilder::new();
+        let mut found = false;
+        let mut first = true;
+        for i in self.instances() {
+            if i.structurally_eq(instance) {
+                found = true;
+                continue;
+            }
+            if !first {
+                builder.end_instance();
+            }
+            builder.append(&i);
+            first = false;
+        }
+        found.then(|| builder.build())
+    }
+}
+
+/// Iterator over the instances of a device path, see [`DevicePath::instances`].
+#[derive(Clone)]
+pub struct Instances<'a>(Nodes<'a>);
+
+impl Iterator for Instances<'_> {
+    type Item = DevicePathBuf;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut builder = DevicePathBuilder::new();
+        let mut empty = true;
+        for node in self.0.by_ref() {
+            if node.is_end_instance() {
+                return Some(builder.build());
+            }
+            builder.push_node(node);
+            empty = false;
+        }
+        (!empty).then(|| builder.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::node_types::{Acpi, HardDrive, Pci};
+    use r_efi::efi;
+    use ucs2::String16;
+
+    fn file_path(path: &str) -> FilePath {
+        FilePath::new(String16::try_from(path).unwrap())
+    }
+
+    #[test]
+    fn test_starts_with() {
+        let controller = DevicePathBuilder::new().push(&Acpi::pci_root(0)).push(&Pci::new(0x1F, 2)).build();
+        let boot_option = DevicePathBuilder::from_device_path(&controller)
+            .push(&HardDrive::gpt(1, 0x800, 0x1000, efi::Guid::from_bytes(&[1; 16])))
+            .push(&file_path("\\EFI\\BOOT\\BOOTX64.EFI"))
+            .build();
+        assert!(boot_option.starts_with(&controller));
+        assert!(boot_option.starts_with(&boot_option));
+        assert!(boot_option.starts_with(&DevicePathBuilder::new().build()));
+        assert!(!controller.starts_with(&boot_option));
+        let other = DevicePathBuilder::new().push(&Acpi::pci_root(1)).build();
+        assert!(!boot_option.starts_with(&other));
+
+        assert!(boot_option.ends_with_file_path());
+        assert!(!controller.ends_with_file_path());
+    }
+
+    #[test]
+    fn test_structurally_eq() {
+        let a = DevicePathBuilder::new().push(&Pci::new(0, 0)).build();
+        let b = DevicePathBuilder::new().push(&Pci::new(0, 0)).end_instance().build();
+        let c = DevicePathBuilder::new().push(&Pci::new(0, 1)).build();
+        assert!(a.structurally_eq(&b));
+        assert!(b.structurally_eq(&a));
+        assert_ne!(a, b);
+        assert!(!a.structurally_eq(&c));
+    }
+
+    #[test]
+    fn test_instances() {
+        let a = DevicePathBuilder::new().push(&Pci::new(0, 0)).build();
+        let b = DevicePathBuilder::new().push(&Pci::new(1, 0)).push(&Pci::new(0, 0)).build();
+        let multi_instance = DevicePathBuilder::from_device_path(&a).end_instance().append(&b).build();
+
+        assert_eq!(vec![a.clone(), b.clone()], multi_instance.instances().collect::<Vec<_>>());
+        assert!(multi_instance.is_multi_instance());
+        assert!(!a.is_multi_instance());
+        assert_eq!(vec![a.clone()], a.instances().collect::<Vec<_>>());
+        assert_eq!(0, DevicePathBuilder::new().build().instances().count());
+
+        assert!(multi_instance.contains_instance(&b));
+        assert_eq!(Some(b.clone()), multi_instance.without_instance(&a));
+        assert_eq!(Some(a.clone()), multi_instance.without_instance(&b));
+        assert_eq!(None, b.without_instance(&a));
+        assert!(a.without_instance(&a).unwrap().is_end());
+    }
+}
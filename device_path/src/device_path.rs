@@ -0,0 +1,466 @@
+//! Device path helpers.
+//!
+//! A [`DevicePath`] is a borrowed, validated view over the bytes of a device path. It can be created from raw pointers
+//! given by the firmware and used with the boot services that take or return device paths.
+//!
+//! The [`Node`]s of a device path are iterated with [`DevicePath::nodes`], without copying them, and parsed into the
+//! types of [`node_types`]. Device paths are displayed in their text representation, see [`text`].
+//!
+//! Without the default `alloc` feature, only the validation, node iteration and parsing over borrowed bytes remain,
+//! for contexts without a heap.
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{borrow::ToOwned, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::{borrow::Borrow, ops::Deref};
+use core::{fmt, mem, slice};
+
+#[cfg(feature = "alloc")]
+use boot_services::{
+    protocol_handler::{self, Protocol},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+#[cfg(feature = "alloc")]
+use ucs2::Str16;
+
+#[cfg(feature = "alloc")]
+mod builder;
+#[cfg(feature = "alloc")]
+mod compare;
+#[cfg(feature = "alloc")]
+mod file_path;
+#[cfg(feature = "alloc")]
+pub mod load_option;
+pub mod node;
+pub mod node_types;
+#[cfg(feature = "alloc")]
+pub mod text;
+#[cfg(feature = "alloc")]
+pub mod utilities;
+
+#[cfg(feature = "alloc")]
+pub use builder::DevicePathBuilder;
+#[cfg(feature = "alloc")]
+pub use compare::Instances;
+#[cfg(feature = "alloc")]
+pub use file_path::normalize_file_path;
+pub use node::{Node, Nodes};
+
+type DevicePathProtocol = efi::protocols::device_path::Protocol;
+
+const NODE_HEADER_SIZE: usize = mem::size_of::<DevicePathProtocol>();
+
+/// A complete device path, from its first node up to and including the end of entire device path node.
+///
+/// This is a dynamically sized type over the bytes of the device path, it is always used behind a reference.
+#[repr(transparent)]
+pub struct DevicePath([u8]);
+
+impl DevicePath {
+    /// Creates a device path from bytes.
+    ///
+    /// Returns [`efi::Status::INVALID_PARAMETER`] if a node is malformed or if the bytes do not end with the end of
+    /// entire device path node.
+    pub fn from_bytes(bytes: &[u8]) -> Result<&DevicePath, EfiError> {
+        match Self::validate(bytes) {
+            //SAFETY: The bytes were validated as a device path.
+            Some(size) if size == bytes.len() => Ok(unsafe { Self::from_bytes_unchecked(bytes) }),
+            _ => Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+    }
+
+    /// Creates a device path from bytes without validating them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be a well-formed device path ending with the end of entire device path node.
+    pub unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &DevicePath {
+        //SAFETY: DevicePath is a transparent wrapper over [u8].
+        mem::transmute(bytes)
+    }
+
+    /// Creates a device path from a pointer to its first node.
+    ///
+    /// Returns [`efi::Status::INVALID_PARAMETER`] if the pointer is null or a node is malformed.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to a device path that ends with an end of entire device path node and that stays valid
+    /// and unchanged for the lifetime `'a`.
+    pub unsafe fn from_ptr<'a>(device_path: *const DevicePathProtocol) -> Result<&'a DevicePath, EfiError> {
+        let size = device_path_size(device_path)?;
+        Ok(Self::from_bytes_unchecked(slice::from_raw_parts(device_path.cast::<u8>(), size)))
+    }
+
+    /// Iterator over the nodes of the device path, without the end of entire device path node.
+    pub fn nodes(&self) -> Nodes<'_> {
+        Nodes::new(self)
+    }
+
+    /// Pointer to the first node of the device path.
+    pub fn as_ptr(&self) -> *const DevicePathProtocol {
+        self.0.as_ptr().cast()
+    }
+
+    /// The bytes of the device path.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Size in bytes of the device path, including the end of entire device path node.
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the device path only contains the end of entire device path node.
+    pub fn is_end(&self) -> bool {
+        self.0.len() == NODE_HEADER_SIZE
+    }
+
+    fn is_end_of_entire_node(node: &DevicePathProtocol) -> bool {
+        node.r#type == efi::protocols::device_path::TYPE_END
+            && node.sub_type == efi::protocols::device_path::End::SUBTYPE_ENTIRE
+    }
+
+    /// Returns the size of the device path at the start of the bytes or `None` if it is malformed.
+    fn validate(bytes: &[u8]) -> Option<usize> {
+        let mut size = 0;
+        loop {
+            let header = bytes.get(size..size + NODE_HEADER_SIZE)?;
+            let node = DevicePathProtocol { r#type: header[0], sub_type: header[1], length: [header[2], header[3]] };
+            let node_size = u16::from_le_bytes(node.length) as usize;
+            if node_size < NODE_HEADER_SIZE || size + node_size > bytes.len() {
+                return None;
+            }
+            size += node_size;
+            if Self::is_end_of_entire_node(&node) {
+                return Some(size);
+            }
+        }
+    }
+}
+
+impl PartialEq for DevicePath {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for DevicePath {}
+
+impl AsRef<[u8]> for DevicePath {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for DevicePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DevicePath").field(&&self.0).finish()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ToOwned for DevicePath {
+    type Owned = DevicePathBuf;
+
+    fn to_owned(&self) -> Self::Owned {
+        DevicePathBuf(self.0.to_vec())
+    }
+}
+
+/// An owned device path, see [`DevicePath`].
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq)]
+pub struct DevicePathBuf(Vec<u8>);
+
+#[cfg(feature = "alloc")]
+impl DevicePathBuf {
+    /// Creates a device path from bytes, see [`DevicePath::from_bytes`].
+    pub fn from_vec(bytes: Vec<u8>) -> Result<Self, EfiError> {
+        DevicePath::from_bytes(&bytes)?;
+        Ok(Self(bytes))
+    }
+
+    /// Creates a device path from bytes without validating them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be a well-formed device path ending with the end of entire device path node.
+    pub unsafe fn from_vec_unchecked(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes of the device path.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Deref for DevicePathBuf {
+    type Target = DevicePath;
+
+    fn deref(&self) -> &Self::Target {
+        //SAFETY: The bytes are always a valid device path.
+        unsafe { DevicePath::from_bytes_unchecked(&self.0) }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Borrow<DevicePath> for DevicePathBuf {
+    fn borrow(&self) -> &DevicePath {
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl AsRef<DevicePath> for DevicePathBuf {
+    fn as_ref(&self) -> &DevicePath {
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<&DevicePath> for DevicePathBuf {
+    fn from(device_path: &DevicePath) -> Self {
+        device_path.to_owned()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for DevicePathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Fails with [`efi::Status::INVALID_PARAMETER`] if a node of the device path is malformed.
+#[cfg(feature = "uefi")]
+impl<'a> TryFrom<&'a uefi::proto::device_path::DevicePath> for &'a DevicePath {
+    type Error = efi::Status;
+
+    fn try_from(device_path: &'a uefi::proto::device_path::DevicePath) -> Result<Self, Self::Error> {
+        //SAFETY: The uefi device path ends with an end of entire device path node and is borrowed for 'a.
+        unsafe { DevicePath::from_ptr(device_path.as_ffi_ptr().cast()) }
+    }
+}
+
+#[cfg(feature = "uefi")]
+impl<'a> From<&'a DevicePath> for &'a uefi::proto::device_path::DevicePath {
+    fn from(device_path: &'a DevicePath) -> Self {
+        //SAFETY: The device path was validated and is borrowed for 'a.
+        unsafe { uefi::proto::device_path::DevicePath::from_ffi_ptr(device_path.as_ptr().cast()) }
+    }
+}
+
+/// Returns the total size in bytes of the device path at the pointer, including the end of entire device path node.
+///
+/// Returns [`efi::Status::INVALID_PARAMETER`] if the pointer is null or a node is shorter than a node header.
+///
+/// # Safety
+///
+/// The pointer must point to a device path that ends with an end of entire device path node.
+pub unsafe fn device_path_size(device_path: *const DevicePathProtocol) -> Result<usize, EfiError> {
+    if device_path.is_null() {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    let mut size = 0;
+    loop {
+        let node = device_path.cast::<u8>().add(size).cast::<DevicePathProtocol>().read_unaligned();
+        let node_size = u16::from_le_bytes(node.length) as usize;
+        if node_size < NODE_HEADER_SIZE {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        size += node_size;
+        if DevicePath::is_end_of_entire_node(&node) {
+            return Ok(size);
+        }
+    }
+}
+
+/// Locates the handle to a device on the device path that supports the specified protocol.
+///
+/// Returns the handle and the remaining part of the device path that was not matched by the handle.
+///
+/// [UEFI Spec Documentation: 7.3.8. EFI_BOOT_SERVICES.LocateDevicePath()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-locatedevicepath)
+#[cfg(feature = "alloc")]
+pub fn locate_device_path<'a, P: Protocol, B: BootServices>(
+    boot_services: &B,
+    protocol: &P,
+    device_path: &'a DevicePath,
+) -> Result<(efi::Handle, &'a DevicePath), EfiError> {
+    let mut remaining_device_path = device_path.as_ptr() as *mut DevicePathProtocol;
+    //SAFETY: The device path is a valid device path and the firmware only moves the pointer forward within it.
+    let handle = unsafe { boot_services.locate_device_path(protocol.protocol_guid(), &mut remaining_device_path)? };
+    let offset = (remaining_device_path as usize).wrapping_sub(device_path.as_ptr() as usize);
+    let remaining_device_path = device_path
+        .as_bytes()
+        .get(offset..)
+        .ok_or(efi::Status::INVALID_PARAMETER.into())
+        .and_then(DevicePath::from_bytes)?;
+    Ok((handle, remaining_device_path))
+}
+
+/// Returns a copy of the device path of a handle.
+///
+/// Returns [`efi::Status::UNSUPPORTED`] if the handle has no device path.
+#[cfg(feature = "alloc")]
+#[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+pub fn device_path_for_handle<B: BootServices>(
+    boot_services: &B,
+    handle: efi::Handle,
+) -> Result<DevicePathBuf, EfiError> {
+    let device_path = boot_services.handle_protocol(handle, &protocol_handler::DevicePath)?;
+    //SAFETY: The device path is owned by the protocol and is copied before the protocol could be uninstalled.
+    unsafe { DevicePath::from_ptr(device_path) }.map(DevicePathBuf::from)
+}
+
+/// Returns the device path of a file on the device of a handle, like a file system handle.
+///
+/// This is the device path of the handle followed by a file path node, as used by boot options and `LoadImage()`.
+#[cfg(feature = "alloc")]
+#[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+pub fn file_device_path<B: BootServices>(
+    boot_services: &B,
+    handle: efi::Handle,
+    path: &Str16,
+) -> Result<DevicePathBuf, EfiError> {
+    let device_path = device_path_for_handle(boot_services, handle)?;
+    Ok(DevicePathBuilder::from_device_path(&device_path).push(&node_types::FilePath::from(path)).build())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::{protocol_handler::DevicePath as DevicePathProtocolGuid, MockBootServices};
+    use ucs2::u16str;
+
+    const PCI_ROOT_AND_END: [u8; 16] = [
+        0x02, 0x01, 0x0C, 0x00, 0xD0, 0x41, 0x03, 0x0A, 0x00, 0x00, 0x00, 0x00, // PciRoot(0x0)
+        0x7F, 0xFF, 0x04, 0x00, // End
+    ];
+    const PCI_ROOT_PCI_AND_END: [u8; 22] = [
+        0x02, 0x01, 0x0C, 0x00, 0xD0, 0x41, 0x03, 0x0A, 0x00, 0x00, 0x00, 0x00, // PciRoot(0x0)
+        0x01, 0x01, 0x06, 0x00, 0x00, 0x1F, // Pci(0x1F,0x0)
+        0x7F, 0xFF, 0x04, 0x00, // End
+    ];
+
+    #[test]
+    fn test_from_bytes() {
+        let device_path = DevicePath::from_bytes(&PCI_ROOT_AND_END).unwrap();
+        assert_eq!(16, device_path.size());
+        assert!(!device_path.is_end());
+        assert!(DevicePath::from_bytes(&PCI_ROOT_AND_END[12..]).unwrap().is_end());
+    }
+
+    #[test]
+    #[cfg(feature = "uefi")]
+    fn test_uefi_conversions() {
+        let device_path = DevicePath::from_bytes(&PCI_ROOT_PCI_AND_END).unwrap();
+        let uefi_device_path = <&uefi::proto::device_path::DevicePath>::from(device_path);
+        assert_eq!(2, uefi_device_path.node_iter().count());
+        assert_eq!(device_path, <&DevicePath>::try_from(uefi_device_path).unwrap());
+    }
+
+    #[test]
+    fn test_from_bytes_invalid() {
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), DevicePath::from_bytes(&[]));
+        // Missing end node.
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), DevicePath::from_bytes(&PCI_ROOT_AND_END[..12]));
+        // Trailing bytes after the end node.
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), DevicePath::from_bytes(&[0x7F, 0xFF, 0x04, 0x00, 0x00]));
+        // Node length smaller than a node header.
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), DevicePath::from_bytes(&[0x01, 0x01, 0x02, 0x00]));
+        // Node length past the end of the bytes.
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), DevicePath::from_bytes(&[0x7F, 0xFF, 0x08, 0x00]));
+    }
+
+    #[test]
+    fn test_from_ptr() {
+        let device_path = unsafe { DevicePath::from_ptr(PCI_ROOT_PCI_AND_END.as_ptr().cast()) }.unwrap();
+        assert_eq!(&PCI_ROOT_PCI_AND_END, device_path.as_bytes());
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), unsafe { DevicePath::from_ptr(core::ptr::null()) });
+    }
+
+    #[test]
+    fn test_device_path_size() {
+        assert_eq!(Ok(22), unsafe { device_path_size(PCI_ROOT_PCI_AND_END.as_ptr().cast()) });
+        assert_eq!(Ok(4), unsafe { device_path_size(PCI_ROOT_PCI_AND_END[18..].as_ptr().cast()) });
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), unsafe {
+            device_path_size([0x01, 0x01, 0x02, 0x00].as_ptr().cast())
+        });
+    }
+
+    #[test]
+    fn test_locate_device_path() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_device_path()
+            .withf(|protocol, _| protocol == DevicePathProtocolGuid.protocol_guid())
+            .once()
+            .returning(|_, device_path| {
+                unsafe { *device_path = (*device_path as *mut u8).add(12) as *mut _ };
+                Ok(1_usize as efi::Handle)
+            });
+
+        let device_path = DevicePath::from_bytes(&PCI_ROOT_PCI_AND_END).unwrap();
+        let (handle, remaining_device_path) =
+            locate_device_path(&boot_services, &DevicePathProtocolGuid, device_path).unwrap();
+        assert_eq!(1_usize as efi::Handle, handle);
+        assert_eq!(&PCI_ROOT_PCI_AND_END[12..], remaining_device_path.as_bytes());
+    }
+
+    #[test]
+    fn test_locate_device_path_error() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_locate_device_path().returning(|_, _| Err(efi::Status::NOT_FOUND.into()));
+
+        let device_path = DevicePath::from_bytes(&PCI_ROOT_AND_END).unwrap();
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            locate_device_path(&boot_services, &DevicePathProtocolGuid, device_path)
+        );
+    }
+
+    fn device_path_boot_services() -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_handle_protocol::<DevicePathProtocolGuid, DevicePathProtocol>().returning(|handle, _| {
+            match handle as usize {
+                1 => Ok(unsafe { &mut *(PCI_ROOT_PCI_AND_END.as_ptr() as *mut DevicePathProtocol) }),
+                _ => Err(efi::Status::UNSUPPORTED.into()),
+            }
+        });
+        boot_services
+    }
+
+    #[test]
+    fn test_device_path_for_handle() {
+        let boot_services = device_path_boot_services();
+        let device_path = device_path_for_handle(&boot_services, 1_usize as efi::Handle).unwrap();
+        assert_eq!(&PCI_ROOT_PCI_AND_END, device_path.as_bytes());
+        assert_eq!(
+            Err(efi::Status::UNSUPPORTED.into()),
+            device_path_for_handle(&boot_services, 2_usize as efi::Handle)
+        );
+    }
+
+    #[test]
+    fn test_file_device_path() {
+        let boot_services = device_path_boot_services();
+        let path = Str16::from_slice_with_nul(u16str!("\\EFI\\BOOT\\BOOTX64.EFI")).unwrap();
+        let device_path = file_device_path(&boot_services, 1_usize as efi::Handle, path).unwrap();
+        assert!(device_path.starts_with(DevicePath::from_bytes(&PCI_ROOT_PCI_AND_END).unwrap()));
+        assert_eq!(device_path.file_path().unwrap(), *path);
+        assert_eq!(
+            Err(efi::Status::UNSUPPORTED.into()),
+            file_device_path(&boot_services, 2_usize as efi::Handle, path)
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! File paths of device paths.
+
+use alloc::vec::Vec;
+
+use ucs2::{Str16, String16};
+
+use crate::{node_types::FilePath, DevicePath};
+
+const SEPARATOR: u16 = b'\\' as u16;
+
+impl DevicePath {
+    /// The normalized file path of the trailing file path nodes, see [`normalize_file_path`].
+    ///
+    /// A file path can be split over several nodes, which are joined with separators. Returns `None` if the device path
+    /// does not end with a file path.
+    pub fn file_path(&self) -> Option<String16> {
+        let nodes = self.nodes().map(|node| node.parse::<FilePath>()).collect::<Vec<_>>();
+        let start = nodes.iter().rposition(Option::is_none).map_or(0, |i| i + 1);
+        if start == nodes.len() {
+            return None;
+        }
+        let mut path = Vec::new();
+        for file_path in nodes[start..].iter().flatten() {
+            path.push(SEPARATOR);
+            path.extend_from_slice(file_path.path_name.as_slice());
+        }
+        path.push(0);
+        String16::from_vec_with_nul(path).ok().map(|path| normalize_file_path(&path))
+    }
+}
+
+/// Normalizes a file path to the form expected by file systems: absolute, with `\` separators, without repeated or
+/// trailing separators and with the `.` and `..` components resolved.
+///
+/// ```ignore
+/// assert_eq!(normalize_file_path(&String16::try_from("EFI//Boot/./..\\BOOT\\")?), "\\EFI\\BOOT");
+/// ```
+pub fn normalize_file_path(path: &Str16) -> String16 {
+    let mut components: Vec<&[u16]> = Vec::new();
+    for component in path.as_slice().split(|c| *c == SEPARATOR || *c == b'/' as u16) {
+        match component {
+            [] => (),
+            [c] if *c == b'.' as u16 => (),
+            [c1, c2] if *c1 == b'.' as u16 && *c2 == b'.' as u16 => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    let mut normalized = Vec::with_capacity(path.len() + 2);
+    for component in components {
+        normalized.push(SEPARATOR);
+        normalized.extend_from_slice(component);
+    }
+    if normalized.is_empty() {
+        normalized.push(SEPARATOR);
+    }
+    normalized.push(0);
+    // The components come from a string without null characters.
+    String16::from_vec_with_nul(normalized).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{node_types::Pci, DevicePathBuilder};
+
+    fn file_path(path: &str) -> FilePath {
+        FilePath::new(String16::try_from(path).unwrap())
+    }
+
+    fn normalize(path: &str) -> String16 {
+        normalize_file_path(&String16::try_from(path).unwrap())
+    }
+
+    #[test]
+    fn test_file_path() {
+        let device_path = DevicePathBuilder::new()
+            .push(&Pci::new(0, 0))
+            .push(&file_path("\\EFI"))
+            .push(&file_path("BOOT\\"))
+            .push(&file_path("BOOTX64.EFI"))
+            .build();
+        assert_eq!(device_path.file_path().unwrap(), "\\EFI\\BOOT\\BOOTX64.EFI");
+
+        let device_path = DevicePathBuilder::new().push(&file_path("EFI/Boot/app.efi")).build();
+        assert_eq!(device_path.file_path().unwrap(), "\\EFI\\Boot\\app.efi");
+
+        // Only the trailing file path nodes are part of the file path.
+        let device_path =
+            DevicePathBuilder::new().push(&file_path("\\ignored")).push(&Pci::new(0, 0)).push(&file_path("a")).build();
+        assert_eq!(device_path.file_path().unwrap(), "\\a");
+
+        assert_eq!(None, DevicePathBuilder::new().push(&Pci::new(0, 0)).build().file_path());
+        assert_eq!(None, DevicePathBuilder::new().build().file_path());
+    }
+
+    #[test]
+    fn test_normalize_file_path() {
+        assert_eq!(normalize("\\EFI\\BOOT\\BOOTX64.EFI"), "\\EFI\\BOOT\\BOOTX64.EFI");
+        assert_eq!(normalize("EFI//Boot/./..\\BOOT\\"), "\\EFI\\BOOT");
+        assert_eq!(normalize("\\..\\a\\.\\b\\..\\c"), "\\a\\c");
+        assert_eq!(normalize(""), "\\");
+        assert_eq!(normalize("\\\\"), "\\");
+        assert_eq!(normalize("...\\a"), "\\...\\a");
+    }
+}
@@ -0,0 +1,162 @@
+//! Parsing of `EFI_LOAD_OPTION`, the data of the `Boot####`, `Driver####`, `SysPrep####` and `PlatformRecovery####`
+//! variables.
+//!
+//! ```ignore
+//! let (data, _) = runtime_services.get_variable_str16::<Vec<u8>>(u16str!("Boot0001"), &efi::GLOBAL_VARIABLE, None)?;
+//! let option = LoadOption::parse(&data)?;
+//! log::info!("{}: {}", option.description, option.device_path());
+//! ```
+//!
+//! [UEFI Spec Documentation: 3.1.3. Load Options](https://uefi.org/specs/UEFI/2.10/03_Boot_Manager.html#load-options)
+
+use alloc::vec::Vec;
+
+use r_efi::efi;
+use status::EfiError;
+use ucs2::String16;
+use zerocopy::{
+    little_endian::{U16, U32},
+    FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned,
+};
+
+use crate::DevicePath;
+
+/// The boot manager attempts to boot the option.
+pub const LOAD_OPTION_ACTIVE: u32 = 0x0000_0001;
+/// The controllers are reconnected after loading a driver option.
+pub const LOAD_OPTION_FORCE_RECONNECT: u32 = 0x0000_0002;
+/// The option is not shown in the boot menu.
+pub const LOAD_OPTION_HIDDEN: u32 = 0x0000_0008;
+pub const LOAD_OPTION_CATEGORY: u32 = 0x0000_1F00;
+pub const LOAD_OPTION_CATEGORY_BOOT: u32 = 0x0000_0000;
+pub const LOAD_OPTION_CATEGORY_APP: u32 = 0x0000_0100;
+
+/// FFI definition of the start of `EFI_LOAD_OPTION`, followed by the description, the file path list and the optional
+/// data.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct LoadOptionHeader {
+    pub attributes: U32,
+    /// Size of the file path list, in bytes.
+    pub file_path_list_length: U16,
+}
+
+/// Size of the attributes and of the length of the file path list.
+const HEADER_SIZE: usize = core::mem::size_of::<LoadOptionHeader>();
+
+/// A parsed `EFI_LOAD_OPTION`, borrowing the device paths and the optional data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadOption<'a> {
+    pub attributes: u32,
+    pub description: String16,
+    file_path_list: &'a [u8],
+    /// The data given to the image as its load options.
+    pub optional_data: &'a [u8],
+}
+
+impl<'a> LoadOption<'a> {
+    /// Parses a load option.
+    ///
+    /// Returns [`efi::Status::INVALID_PARAMETER`] if the description is not a null-terminated UCS-2 string or if the
+    /// file path list is not a non-empty sequence of device paths.
+    pub fn parse(data: &'a [u8]) -> Result<Self, EfiError> {
+        let (header, _) = LoadOptionHeader::ref_from_prefix(data).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        let attributes = header.attributes.get();
+        let file_path_list_length = header.file_path_list_length.get() as usize;
+
+        let mut description = Vec::new();
+        let mut chars = data[HEADER_SIZE..].chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]]));
+        loop {
+            let c = chars.next().ok_or(efi::Status::INVALID_PARAMETER)?;
+            description.push(c);
+            if c == 0 {
+                break;
+            }
+        }
+        let rest = &data[HEADER_SIZE + description.len() * 2..];
+        let description = String16::from_vec_with_nul(description).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+
+        if file_path_list_length == 0 || file_path_list_length > rest.len() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let (file_path_list, optional_data) = rest.split_at(file_path_list_length);
+        let mut device_paths = file_path_list;
+        while !device_paths.is_empty() {
+            let size = DevicePath::validate(device_paths).ok_or(efi::Status::INVALID_PARAMETER)?;
+            device_paths = &device_paths[size..];
+        }
+        Ok(Self { attributes, description, file_path_list, optional_data })
+    }
+
+    /// Whether the boot manager attempts to boot the option.
+    pub fn is_active(&self) -> bool {
+        self.attributes & LOAD_OPTION_ACTIVE != 0
+    }
+
+    /// The category of the option, [`LOAD_OPTION_CATEGORY_BOOT`] or [`LOAD_OPTION_CATEGORY_APP`].
+    pub fn category(&self) -> u32 {
+        self.attributes & LOAD_OPTION_CATEGORY
+    }
+
+    /// The device path of the image, the first one of the file path list.
+    pub fn device_path(&self) -> &'a DevicePath {
+        self.file_paths().next().expect("The file path list is never empty.")
+    }
+
+    /// The device paths of the file path list, the first one being the image to load.
+    pub fn file_paths(&self) -> impl Iterator<Item = &'a DevicePath> {
+        let mut device_paths = self.file_path_list;
+        core::iter::from_fn(move || {
+            let size = DevicePath::validate(device_paths)?;
+            let (device_path, rest) = device_paths.split_at(size);
+            device_paths = rest;
+            //SAFETY: The file path list was validated when parsed.
+            Some(unsafe { DevicePath::from_bytes_unchecked(device_path) })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    const END: [u8; 4] = [0x7F, 0xFF, 4, 0];
+    const PCI: [u8; 10] = [1, 1, 6, 0, 0, 2, 0x7F, 0xFF, 4, 0];
+
+    fn load_option(attributes: u32, description: &str, file_path_list: &[u8], optional_data: &[u8]) -> Vec<u8> {
+        let mut data = attributes.to_le_bytes().to_vec();
+        data.extend_from_slice(&(file_path_list.len() as u16).to_le_bytes());
+        data.extend(description.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+        data.extend_from_slice(file_path_list);
+        data.extend_from_slice(optional_data);
+        data
+    }
+
+    #[test]
+    fn test_parse() {
+        let file_path_list = [&PCI[..], &END[..]].concat();
+        let data = load_option(LOAD_OPTION_ACTIVE | LOAD_OPTION_CATEGORY_APP, "Shell", &file_path_list, b"-nostartup");
+        let option = LoadOption::parse(&data).unwrap();
+        assert!(option.is_active());
+        assert_eq!(LOAD_OPTION_CATEGORY_APP, option.category());
+        assert_eq!(option.description, "Shell");
+        assert_eq!(&PCI[..], option.device_path().as_bytes());
+        assert_eq!(vec![&PCI[..], &END[..]], option.file_paths().map(DevicePath::as_bytes).collect::<Vec<_>>());
+        assert_eq!(b"-nostartup", option.optional_data);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), LoadOption::parse(&[1, 0, 0, 0, 4]));
+        let data = load_option(LOAD_OPTION_ACTIVE, "Boot", &END, &[]);
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), LoadOption::parse(&data[..data.len() - 1]));
+        // The description is not terminated.
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), LoadOption::parse(&data[..8]));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), LoadOption::parse(&load_option(0, "Boot", &[], &[])));
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            LoadOption::parse(&load_option(0, "Boot", &PCI[..6], &[]))
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Nodes of a device path.
+
+use core::{fmt, iter::FusedIterator};
+
+use r_efi::efi;
+
+use crate::{node_types::DevicePathNode, DevicePath, NODE_HEADER_SIZE};
+
+/// A node of a device path, borrowed from the device path bytes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Node<'a>(&'a [u8]);
+
+impl<'a> Node<'a> {
+    /// The type of the node, like [`efi::protocols::device_path::TYPE_HARDWARE`].
+    pub fn node_type(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// The sub-type of the node, its meaning depends on the type.
+    pub fn sub_type(&self) -> u8 {
+        self.0[1]
+    }
+
+    /// The data of the node, after the node header.
+    pub fn data(&self) -> &'a [u8] {
+        &self.0[NODE_HEADER_SIZE..]
+    }
+
+    /// The bytes of the node, including the node header.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Size in bytes of the node, including the node header.
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Parses the node as a typed node, returns `None` if it has another type or if its data is malformed.
+    pub fn parse<T: DevicePathNode>(&self) -> Option<T> {
+        if (self.node_type(), self.sub_type()) != (T::TYPE, T::SUB_TYPE) {
+            return None;
+        }
+        T::from_data(self.data())
+    }
+
+    /// Returns true if the node is an end of device path instance node, separating the instances of a multi-instance
+    /// device path.
+    pub fn is_end_instance(&self) -> bool {
+        self.node_type() == efi::protocols::device_path::TYPE_END
+            && self.sub_type() == efi::protocols::device_path::End::SUBTYPE_INSTANCE
+    }
+}
+
+impl fmt::Debug for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("type", &self.node_type())
+            .field("sub_type", &self.sub_type())
+            .field("data", &self.data())
+            .finish()
+    }
+}
+
+/// Iterator over the nodes of a device path, without its end of entire device path node.
+#[derive(Clone)]
+pub struct Nodes<'a>(&'a [u8]);
+
+impl<'a> Nodes<'a> {
+    pub(crate) fn new(device_path: &'a DevicePath) -> Self {
+        // The end of entire device path node is always the last header of a valid device path.
+        Self(&device_path.as_bytes()[..device_path.size() - NODE_HEADER_SIZE])
+    }
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        // The device path was validated, every node length is within the bytes.
+        let size = u16::from_le_bytes([self.0[2], self.0[3]]) as usize;
+        let (node, rest) = self.0.split_at(size);
+        self.0 = rest;
+        Some(Node(node))
+    }
+}
+
+impl FusedIterator for Nodes<'_> {}
+
+impl<'a> IntoIterator for &'a DevicePath {
+    type Item = Node<'a>;
+    type IntoIter = Nodes<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MULTI_INSTANCE: [u8; 26] = [
+        0x02, 0x01, 0x0C, 0x00, 0xD0, 0x41, 0x03, 0x0A, 0x00, 0x00, 0x00, 0x00, // PciRoot(0x0)
+        0x01, 0x01, 0x06, 0x00, 0x00, 0x1F, // Pci(0x1F,0x0)
+        0x7F, 0x01, 0x04, 0x00, // End instance
+        0x7F, 0xFF, 0x04, 0x00, // End
+    ];
+
+    #[test]
+    fn test_nodes() {
+        let device_path = DevicePath::from_bytes(&MULTI_INSTANCE).unwrap();
+        let nodes = device_path.nodes().collect::<Vec<_>>();
+        assert_eq!(3, nodes.len());
+
+        assert_eq!(efi::protocols::device_path::TYPE_ACPI, nodes[0].node_type());
+        assert_eq!(0x01, nodes[0].sub_type());
+        assert_eq!(&[0xD0, 0x41, 0x03, 0x0A, 0x00, 0x00, 0x00, 0x00], nodes[0].data());
+        assert_eq!(12, nodes[0].size());
+
+        assert_eq!(efi::protocols::device_path::TYPE_HARDWARE, nodes[1].node_type());
+        assert_eq!(&[0x00, 0x1F], nodes[1].data());
+        assert!(!nodes[1].is_end_instance());
+
+        assert!(nodes[2].is_end_instance());
+        assert!(nodes[2].data().is_empty());
+    }
+
+    #[test]
+    fn test_nodes_end() {
+        let device_path = DevicePath::from_bytes(&MULTI_INSTANCE[22..]).unwrap();
+        assert_eq!(None, device_path.into_iter().next());
+    }
+}
@@ -0,0 +1,733 @@
+//! Typed device path nodes.
+//!
+//! Each type implements [`DevicePathNode`] to be parsed from a [`Node`](crate::Node) with
+//! [`Node::parse`](crate::Node::parse) and to be converted back to bytes.
+//!
+//! [UEFI Spec Documentation: 10.3. Device Path Nodes](https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#device-path-nodes)
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
+use r_efi::efi;
+#[cfg(feature = "alloc")]
+use ucs2::{Str16, String16};
+
+#[cfg(feature = "alloc")]
+use crate::NODE_HEADER_SIZE;
+
+const TYPE_HARDWARE: u8 = efi::protocols::device_path::TYPE_HARDWARE;
+const TYPE_ACPI: u8 = efi::protocols::device_path::TYPE_ACPI;
+const TYPE_MESSAGING: u8 = efi::protocols::device_path::TYPE_MESSAGING;
+const TYPE_MEDIA: u8 = efi::protocols::device_path::TYPE_MEDIA;
+
+/// A device path node with a known type and sub-type.
+pub trait DevicePathNode: Sized {
+    /// The type of the node.
+    const TYPE: u8;
+    /// The sub-type of the node.
+    const SUB_TYPE: u8;
+
+    /// Parses the data of the node, after the node header. Returns `None` if the data is malformed.
+    fn from_data(data: &[u8]) -> Option<Self>;
+
+    /// Appends the data of the node, without the node header, to the buffer.
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>);
+
+    /// The bytes of the node, including the node header.
+    ///
+    /// Panics if the node is larger than the maximum node size of 64 KiB.
+    #[cfg(feature = "alloc")]
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from([Self::TYPE, Self::SUB_TYPE, 0, 0]);
+        self.write_data(&mut bytes);
+        let length = u16::try_from(bytes.len()).expect("Device path node is too large.");
+        bytes[2..NODE_HEADER_SIZE].copy_from_slice(&length.to_le_bytes());
+        bytes
+    }
+}
+
+/// Little-endian reader over the data of a node.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.0.get(..len)?;
+        self.0 = &self.0[len..];
+        Some(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.bytes(N)?.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.array::<1>().map(|[b]| b)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn guid(&mut self) -> Option<efi::Guid> {
+        self.array().map(|bytes| efi::Guid::from_bytes(&bytes))
+    }
+
+    /// A null-terminated ASCII string.
+    #[cfg(feature = "alloc")]
+    fn c_str(&mut self) -> Option<String> {
+        let len = self.0.iter().position(|b| *b == 0)?;
+        let s = core::str::from_utf8(self.bytes(len)?).ok()?;
+        self.bytes(1)?;
+        Some(String::from(s))
+    }
+
+    /// The remaining bytes.
+    #[cfg(feature = "alloc")]
+    fn rest(&mut self) -> &'a [u8] {
+        self.bytes(self.0.len()).unwrap_or_default()
+    }
+
+    /// Returns the value if all the data was read.
+    fn end<T>(&self, value: T) -> Option<T> {
+        self.0.is_empty().then_some(value)
+    }
+}
+
+/// PCI device, relative to its parent PCI root bridge or bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pci {
+    pub function: u8,
+    pub device: u8,
+}
+
+impl Pci {
+    pub fn new(device: u8, function: u8) -> Self {
+        Self { function, device }
+    }
+}
+
+impl DevicePathNode for Pci {
+    const TYPE: u8 = TYPE_HARDWARE;
+    const SUB_TYPE: u8 = efi::protocols::device_path::Hardware::SUBTYPE_PCI;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node = Self { function: reader.u8()?, device: reader.u8()? };
+        reader.end(node)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&[self.function, self.device]);
+    }
+}
+
+/// ACPI device, identified by its compressed EISA `_HID` and its `_UID`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Acpi {
+    pub hid: u32,
+    pub uid: u32,
+}
+
+impl Acpi {
+    pub fn new(hid: u32, uid: u32) -> Self {
+        Self { hid, uid }
+    }
+
+    /// PCI root bridge (`PNP0A03`) with the given `_UID`.
+    pub fn pci_root(uid: u32) -> Self {
+        Self::new(eisa_id(*b"PNP", 0x0A03), uid)
+    }
+}
+
+/// Compressed EISA id, like `PNP0A03` for `eisa_id(*b"PNP", 0x0A03)`.
+pub const fn eisa_id(vendor: [u8; 3], product: u16) -> u32 {
+    let vendor = ((vendor[0] - b'@') as u32) << 10 | ((vendor[1] - b'@') as u32) << 5 | (vendor[2] - b'@') as u32;
+    (product as u32) << 16 | vendor
+}
+
+impl DevicePathNode for Acpi {
+    const TYPE: u8 = TYPE_ACPI;
+    const SUB_TYPE: u8 = 0x01;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node = Self { hid: reader.u32()?, uid: reader.u32()? };
+        reader.end(node)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.hid.to_le_bytes());
+        buffer.extend_from_slice(&self.uid.to_le_bytes());
+    }
+}
+
+/// ACPI device with its `_CID` and optional string identifiers, used instead of the numeric ones when not empty.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcpiEx {
+    pub hid: u32,
+    pub uid: u32,
+    pub cid: u32,
+    pub hid_str: String,
+    pub uid_str: String,
+    pub cid_str: String,
+}
+
+#[cfg(feature = "alloc")]
+impl AcpiEx {
+    pub fn new(hid: u32, uid: u32, cid: u32) -> Self {
+        Self { hid, uid, cid, hid_str: String::new(), uid_str: String::new(), cid_str: String::new() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl DevicePathNode for AcpiEx {
+    const TYPE: u8 = TYPE_ACPI;
+    const SUB_TYPE: u8 = 0x02;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node = Self {
+            hid: reader.u32()?,
+            uid: reader.u32()?,
+            cid: reader.u32()?,
+            hid_str: reader.c_str()?,
+            uid_str: reader.c_str()?,
+            cid_str: reader.c_str()?,
+        };
+        reader.end(node)
+    }
+
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.hid.to_le_bytes());
+        buffer.extend_from_slice(&self.uid.to_le_bytes());
+        buffer.extend_from_slice(&self.cid.to_le_bytes());
+        for s in [&self.hid_str, &self.uid_str, &self.cid_str] {
+            buffer.extend_from_slice(s.as_bytes());
+            buffer.push(0);
+        }
+    }
+}
+
+/// USB device, relative to its parent USB controller or hub.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usb {
+    pub parent_port_number: u8,
+    pub interface_number: u8,
+}
+
+impl Usb {
+    pub fn new(parent_port_number: u8, interface_number: u8) -> Self {
+        Self { parent_port_number, interface_number }
+    }
+}
+
+impl DevicePathNode for Usb {
+    const TYPE: u8 = TYPE_MESSAGING;
+    const SUB_TYPE: u8 = 0x05;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node = Self { parent_port_number: reader.u8()?, interface_number: reader.u8()? };
+        reader.end(node)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&[self.parent_port_number, self.interface_number]);
+    }
+}
+
+/// SATA device, relative to its parent AHCI controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sata {
+    pub hba_port_number: u16,
+    /// `0xFFFF` if the device is directly connected to the HBA port.
+    pub port_multiplier_port_number: u16,
+    pub lun: u16,
+}
+
+impl Sata {
+    pub fn new(hba_port_number: u16, port_multiplier_port_number: u16, lun: u16) -> Self {
+        Self { hba_port_number, port_multiplier_port_number, lun }
+    }
+}
+
+impl DevicePathNode for Sata {
+    const TYPE: u8 = TYPE_MESSAGING;
+    const SUB_TYPE: u8 = 0x12;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node =
+            Self { hba_port_number: reader.u16()?, port_multiplier_port_number: reader.u16()?, lun: reader.u16()? };
+        reader.end(node)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.hba_port_number.to_le_bytes());
+        buffer.extend_from_slice(&self.port_multiplier_port_number.to_le_bytes());
+        buffer.extend_from_slice(&self.lun.to_le_bytes());
+    }
+}
+
+/// NVMe namespace, relative to its parent NVMe controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nvme {
+    pub namespace_id: u32,
+    /// IEEE Extended Unique Identifier (EUI-64) of the namespace, 0 if it has none.
+    pub namespace_eui: u64,
+}
+
+impl Nvme {
+    pub fn new(namespace_id: u32, namespace_eui: u64) -> Self {
+        Self { namespace_id, namespace_eui }
+    }
+}
+
+impl DevicePathNode for Nvme {
+    const TYPE: u8 = TYPE_MESSAGING;
+    const SUB_TYPE: u8 = 0x17;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node = Self { namespace_id: reader.u32()?, namespace_eui: reader.u64()? };
+        reader.end(node)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.namespace_id.to_le_bytes());
+        buffer.extend_from_slice(&self.namespace_eui.to_le_bytes());
+    }
+}
+
+/// MAC address of a network interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MacAddress {
+    /// The MAC address, padded with zeros.
+    pub mac_address: [u8; 32],
+    /// Network interface type, 1 for Ethernet.
+    pub if_type: u8,
+}
+
+impl MacAddress {
+    /// MAC address of an Ethernet interface.
+    pub fn ethernet(mac_address: [u8; 6]) -> Self {
+        let mut padded = [0; 32];
+        padded[..6].copy_from_slice(&mac_address);
+        Self { mac_address: padded, if_type: 1 }
+    }
+}
+
+impl DevicePathNode for MacAddress {
+    const TYPE: u8 = TYPE_MESSAGING;
+    const SUB_TYPE: u8 = 0x0B;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node = Self { mac_address: reader.array()?, if_type: reader.u8()? };
+        reader.end(node)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.mac_address);
+        buffer.push(self.if_type);
+    }
+}
+
+/// IPv4 connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4 {
+    pub local_ip_address: [u8; 4],
+    pub remote_ip_address: [u8; 4],
+    pub local_port: u16,
+    pub remote_port: u16,
+    /// IP protocol number, like 6 for TCP and 17 for UDP.
+    pub protocol: u16,
+    /// True for a static address, false if it was assigned by DHCP.
+    pub static_ip_address: bool,
+    pub gateway_ip_address: [u8; 4],
+    pub subnet_mask: [u8; 4],
+}
+
+impl Ipv4 {
+    /// Connection to a remote address with a DHCP assigned local address.
+    pub fn new(remote_ip_address: [u8; 4], remote_port: u16, protocol: u16) -> Self {
+        Self {
+            local_ip_address: [0; 4],
+            remote_ip_address,
+            local_port: 0,
+            remote_port,
+            protocol,
+            static_ip_address: false,
+            gateway_ip_address: [0; 4],
+            subnet_mask: [0; 4],
+        }
+    }
+}
+
+impl DevicePathNode for Ipv4 {
+    const TYPE: u8 = TYPE_MESSAGING;
+    const SUB_TYPE: u8 = 0x0C;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node = Self {
+            local_ip_address: reader.array()?,
+            remote_ip_address: reader.array()?,
+            local_port: reader.u16()?,
+            remote_port: reader.u16()?,
+            protocol: reader.u16()?,
+            static_ip_address: reader.u8()? != 0,
+            gateway_ip_address: reader.array()?,
+            subnet_mask: reader.array()?,
+        };
+        reader.end(node)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.local_ip_address);
+        buffer.extend_from_slice(&self.remote_ip_address);
+        buffer.extend_from_slice(&self.local_port.to_le_bytes());
+        buffer.extend_from_slice(&self.remote_port.to_le_bytes());
+        buffer.extend_from_slice(&self.protocol.to_le_bytes());
+        buffer.push(self.static_ip_address as u8);
+        buffer.extend_from_slice(&self.gateway_ip_address);
+        buffer.extend_from_slice(&self.subnet_mask);
+    }
+}
+
+/// IPv6 connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv6 {
+    pub local_ip_address: [u8; 16],
+    pub remote_ip_address: [u8; 16],
+    pub local_port: u16,
+    pub remote_port: u16,
+    /// IP protocol number, like 6 for TCP and 17 for UDP.
+    pub protocol: u16,
+    /// 0 for a manual address, 1 for stateless auto-configuration and 2 for stateful auto-configuration.
+    pub ip_address_origin: u8,
+    pub prefix_length: u8,
+    pub gateway_ip_address: [u8; 16],
+}
+
+impl Ipv6 {
+    /// Connection to a remote address with a manually configured local address.
+    pub fn new(remote_ip_address: [u8; 16], remote_port: u16, protocol: u16) -> Self {
+        Self {
+            local_ip_address: [0; 16],
+            remote_ip_address,
+            local_port: 0,
+            remote_port,
+            protocol,
+            ip_address_origin: 0,
+            prefix_length: 0,
+            gateway_ip_address: [0; 16],
+        }
+    }
+}
+
+impl DevicePathNode for Ipv6 {
+    const TYPE: u8 = TYPE_MESSAGING;
+    const SUB_TYPE: u8 = 0x0D;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let node = Self {
+            local_ip_address: reader.array()?,
+            remote_ip_address: reader.array()?,
+            local_port: reader.u16()?,
+            remote_port: reader.u16()?,
+            protocol: reader.u16()?,
+            ip_address_origin: reader.u8()?,
+            prefix_length: reader.u8()?,
+            gateway_ip_address: reader.array()?,
+        };
+        reader.end(node)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.local_ip_address);
+        buffer.extend_from_slice(&self.remote_ip_address);
+        buffer.extend_from_slice(&self.local_port.to_le_bytes());
+        buffer.extend_from_slice(&self.remote_port.to_le_bytes());
+        buffer.extend_from_slice(&self.protocol.to_le_bytes());
+        buffer.extend_from_slice(&[self.ip_address_origin, self.prefix_length]);
+        buffer.extend_from_slice(&self.gateway_ip_address);
+    }
+}
+
+/// Partition table format of a [`HardDrive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionFormat {
+    Mbr = 1,
+    Gpt = 2,
+}
+
+/// Signature identifying the partition of a [`HardDrive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionSignature {
+    None,
+    /// The 32-bit MBR disk signature.
+    Mbr(u32),
+    /// The GPT unique partition GUID.
+    Guid(efi::Guid),
+}
+
+/// Partition of a hard drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HardDrive {
+    /// Partition number, starting at 1.
+    pub partition_number: u32,
+    /// Starting LBA of the partition.
+    pub partition_start: u64,
+    /// Size of the partition in logical blocks.
+    pub partition_size: u64,
+    pub partition_format: PartitionFormat,
+    pub signature: PartitionSignature,
+}
+
+impl HardDrive {
+    /// Partition of a GPT disk.
+    pub fn gpt(partition_number: u32, partition_start: u64, partition_size: u64, partition_guid: efi::Guid) -> Self {
+        Self {
+            partition_number,
+            partition_start,
+            partition_size,
+            partition_format: PartitionFormat::Gpt,
+            signature: PartitionSignature::Guid(partition_guid),
+        }
+    }
+
+    /// Partition of an MBR disk.
+    pub fn mbr(partition_number: u32, partition_start: u64, partition_size: u64, disk_signature: u32) -> Self {
+        Self {
+            partition_number,
+            partition_start,
+            partition_size,
+            partition_format: PartitionFormat::Mbr,
+            signature: PartitionSignature::Mbr(disk_signature),
+        }
+    }
+}
+
+impl DevicePathNode for HardDrive {
+    const TYPE: u8 = TYPE_MEDIA;
+    const SUB_TYPE: u8 = efi::protocols::device_path::Media::SUBTYPE_HARDDRIVE;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader(data);
+        let partition_number = reader.u32()?;
+        let partition_start = reader.u64()?;
+        let partition_size = reader.u64()?;
+        let signature: [u8; 16] = reader.array()?;
+        let partition_format = match reader.u8()? {
+            1 => PartitionFormat::Mbr,
+            2 => PartitionFormat::Gpt,
+            _ => return None,
+        };
+        let signature = match reader.u8()? {
+            0 => PartitionSignature::None,
+            1 => PartitionSignature::Mbr(u32::from_le_bytes(signature[..4].try_into().ok()?)),
+            2 => PartitionSignature::Guid(efi::Guid::from_bytes(&signature)),
+            _ => return None,
+        };
+        reader.end(Self { partition_number, partition_start, partition_size, partition_format, signature })
+    }
+
+    #[cfg(feature = "alloc")]
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        let (signature, signature_type) = match self.signature {
+            PartitionSignature::None => ([0; 16], 0),
+            PartitionSignature::Mbr(signature) => {
+                let mut bytes = [0; 16];
+                bytes[..4].copy_from_slice(&signature.to_le_bytes());
+                (bytes, 1)
+            }
+            PartitionSignature::Guid(guid) => (*guid.as_bytes(), 2),
+        };
+        buffer.extend_from_slice(&self.partition_number.to_le_bytes());
+        buffer.extend_from_slice(&self.partition_start.to_le_bytes());
+        buffer.extend_from_slice(&self.partition_size.to_le_bytes());
+        buffer.extend_from_slice(&signature);
+        buffer.extend_from_slice(&[self.partition_format as u8, signature_type]);
+    }
+}
+
+/// File path, relative to the previous node or absolute if it starts with `\`.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePath {
+    pub path_name: String16,
+}
+
+#[cfg(feature = "alloc")]
+impl FilePath {
+    pub fn new(path_name: String16) -> Self {
+        Self { path_name }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl DevicePathNode for FilePath {
+    const TYPE: u8 = TYPE_MEDIA;
+    const SUB_TYPE: u8 = efi::protocols::device_path::Media::SUBTYPE_FILE_PATH;
+
+    fn from_data(data: &[u8]) -> Option<Self> {
+        if data.len() % 2 != 0 {
+            return None;
+        }
+        let path_name = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect::<Vec<_>>();
+        String16::from_vec_with_nul(path_name).ok().map(Self::new)
+    }
+
+    fn write_data(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(self.path_name.as_slice_with_nul().iter().flat_map(|c| c.to_le_bytes()));
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<&Str16> for FilePath {
+    fn from(path_name: &Str16) -> Self {
+        Self::new(path_name.into())
+    }
+}
+
+#[cfg(feature = "alloc")]
+macro_rules! vendor_node {
+    ($(#[$attr:meta])* $name:ident, $type:expr, $sub_type:expr) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct $name {
+            /// GUID of the vendor, defining the format of the data.
+            pub guid: efi::Guid,
+            pub data: Vec<u8>,
+        }
+
+        impl $name {
+            pub fn new(guid: efi::Guid, data: &[u8]) -> Self {
+                Self { guid, data: data.to_vec() }
+            }
+        }
+
+        impl DevicePathNode for $name {
+            const TYPE: u8 = $type;
+            const SUB_TYPE: u8 = $sub_type;
+
+            fn from_data(data: &[u8]) -> Option<Self> {
+                let mut reader = Reader(data);
+                Some(Self { guid: reader.guid()?, data: reader.rest().to_vec() })
+            }
+
+            fn write_data(&self, buffer: &mut Vec<u8>) {
+                buffer.extend_from_slice(self.guid.as_bytes());
+                buffer.extend_from_slice(&self.data);
+            }
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+vendor_node!(
+    /// Vendor-defined hardware device.
+    VendorHardware,
+    TYPE_HARDWARE,
+    efi::protocols::device_path::Hardware::SUBTYPE_VENDOR
+);
+#[cfg(feature = "alloc")]
+vendor_node!(
+    /// Vendor-defined messaging device.
+    VendorMessaging,
+    TYPE_MESSAGING,
+    0x0A
+);
+#[cfg(feature = "alloc")]
+vendor_node!(
+    /// Vendor-defined media.
+    VendorMedia,
+    TYPE_MEDIA,
+    efi::protocols::device_path::Media::SUBTYPE_VENDOR
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DevicePath;
+
+    /// Converts the node to bytes and parses them back.
+    fn round_trip<T: DevicePathNode + core::fmt::Debug + PartialEq>(node: T, size: usize) {
+        let mut bytes = node.to_bytes();
+        assert_eq!(size, bytes.len());
+        bytes.extend_from_slice(&[0x7F, 0xFF, 0x04, 0x00]);
+        let device_path = DevicePath::from_bytes(&bytes).unwrap();
+        assert_eq!(Some(node), device_path.nodes().next().unwrap().parse::<T>());
+    }
+
+    #[test]
+    fn test_parse() {
+        let device_path = DevicePath::from_bytes(&[
+            0x02, 0x01, 0x0C, 0x00, 0xD0, 0x41, 0x03, 0x0A, 0x00, 0x00, 0x00, 0x00, // PciRoot(0x0)
+            0x01, 0x01, 0x06, 0x00, 0x00, 0x1F, // Pci(0x1F,0x0)
+            0x7F, 0xFF, 0x04, 0x00, // End
+        ])
+        .unwrap();
+        let nodes = device_path.nodes().collect::<Vec<_>>();
+        assert_eq!(Some(Acpi::pci_root(0)), nodes[0].parse());
+        assert_eq!(None, nodes[0].parse::<Pci>());
+        assert_eq!(Some(Pci::new(0x1F, 0)), nodes[1].parse());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(Pci::new(2, 1), 6);
+        round_trip(Acpi::new(eisa_id(*b"PNP", 0x0A08), 1), 12);
+        round_trip(AcpiEx { hid_str: String::from("MSFT0001"), ..AcpiEx::new(0, 1, 2) }, 27);
+        round_trip(Usb::new(1, 0), 6);
+        round_trip(Sata::new(0, 0xFFFF, 0), 10);
+        round_trip(Nvme::new(1, 0x1122334455667788), 16);
+        round_trip(MacAddress::ethernet([0, 1, 2, 3, 4, 5]), 37);
+        round_trip(Ipv4::new([192, 168, 0, 1], 69, 17), 27);
+        round_trip(Ipv6::new([0xFE; 16], 69, 17), 60);
+        round_trip(HardDrive::gpt(1, 2048, 4096, efi::Guid::from_bytes(&[7; 16])), 42);
+        round_trip(HardDrive::mbr(2, 63, 1024, 0x12345678), 42);
+        round_trip(FilePath::new(String16::try_from("\\EFI\\BOOT\\BOOTX64.EFI").unwrap()), 48);
+        round_trip(VendorHardware::new(efi::Guid::from_bytes(&[1; 16]), &[1, 2, 3]), 23);
+        round_trip(VendorMessaging::new(efi::Guid::from_bytes(&[2; 16]), &[]), 20);
+        round_trip(VendorMedia::new(efi::Guid::from_bytes(&[3; 16]), &[4]), 21);
+    }
+
+    #[test]
+    fn test_malformed_data() {
+        assert_eq!(None, Pci::from_data(&[0]));
+        assert_eq!(None, Pci::from_data(&[0, 1, 2]));
+        assert_eq!(None, AcpiEx::from_data(&[0; 12]));
+        assert_eq!(None, FilePath::from_data(&[0x41, 0x00, 0x42]));
+        assert_eq!(None, FilePath::from_data(&[0x41, 0x00, 0x00, 0x00, 0x42, 0x00, 0x00, 0x00]));
+        let mut hard_drive = HardDrive::gpt(1, 0, 0, efi::Guid::from_bytes(&[0; 16])).to_bytes();
+        hard_drive[40] = 3;
+        assert_eq!(None, HardDrive::from_data(&hard_drive[NODE_HEADER_SIZE..]));
+    }
+
+    #[test]
+    fn test_eisa_id() {
+        assert_eq!(0x0A0341D0, eisa_id(*b"PNP", 0x0A03));
+    }
+}
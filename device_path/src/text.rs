@@ -0,0 +1,433 @@
+//! Conversions between device paths and text.
+//!
+//! [`DevicePathToText`] and [`DevicePathFromText`] wrap the firmware protocols. [`DevicePath`] and [`Node`] implement
+//! [`fmt::Display`] with the Device Path to Text protocol when boot services are available through
+//! [`entry_point`], and otherwise with a formatter for the most common node types using the same text format.
+//!
+//! [UEFI Spec Documentation: 10.6. Device Path Text Representation](https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#text-device-node-reference)
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+
+use crate::{
+    builder::END_ENTIRE,
+    node_types::{
+        Acpi, DevicePathNode, FilePath, HardDrive, Ipv4, Ipv6, MacAddress, Nvme, PartitionFormat, PartitionSignature,
+        Pci, Sata, Usb, VendorHardware, VendorMedia, VendorMessaging,
+    },
+    DevicePath, DevicePathBuf, Node, NODE_HEADER_SIZE,
+};
+
+/// Reads the null-terminated string returned by a protocol and frees it.
+fn take_pool_string<B: BootServices>(boot_services: &B, text: *mut u16) -> Result<String, EfiError> {
+    if text.is_null() {
+        return Err(efi::Status::OUT_OF_RESOURCES.into());
+    }
+    //SAFETY: The protocol returns a null-terminated string allocated from pool.
+    let string = unsafe { Str16::from_ptr(text) }.to_string_lossy();
+    let _ = boot_services.free_pool(text as *mut u8);
+    Ok(string)
+}
+
+/// Device Path to Text protocol.
+///
+/// [UEFI Spec Documentation: 10.6.3. Device Path to Text Protocol](https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#device-path-to-text-protocol)
+pub struct DevicePathToText<'a, B: BootServices> {
+    protocol: &'static mut efi::protocols::device_path_to_text::Protocol,
+    boot_services: &'a B,
+}
+
+impl<'a, B: BootServices> DevicePathToText<'a, B> {
+    /// Locates the first instance of the protocol.
+    pub fn locate(boot_services: &'a B) -> Result<Self, EfiError> {
+        let protocol = boot_services.locate_protocol(&protocol_handler::DevicePathToText, None)?;
+        Ok(Self { protocol, boot_services })
+    }
+
+    /// Converts a device node to text.
+    ///
+    /// `display_only` selects the shorter text representation and `allow_shortcuts` the shortcut forms, like
+    /// `PciRoot(0x0)` instead of `Acpi(PNP0A03,0x0)`.
+    pub fn convert_device_node_to_text(
+        &self,
+        node: Node<'_>,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> Result<String, EfiError> {
+        // The node is only read by the protocol.
+        let text = unsafe {
+            r_efi::efi_call(
+                self.protocol.convert_device_node_to_text,
+                (node.as_bytes().as_ptr() as *mut _, display_only.into(), allow_shortcuts.into()),
+            )
+        };
+        take_pool_string(self.boot_services, text)
+    }
+
+    /// Converts a device path to text, see [`Self::convert_device_node_to_text`] for the options.
+    pub fn convert_device_path_to_text(
+        &self,
+        device_path: &DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> Result<String, EfiError> {
+        // The device path is only read by the protocol.
+        let text = unsafe {
+            r_efi::efi_call(
+                self.protocol.convert_device_path_to_text,
+                (device_path.as_ptr() as *mut _, display_only.into(), allow_shortcuts.into()),
+            )
+        };
+        take_pool_string(self.boot_services, text)
+    }
+}
+
+/// Device Path from Text protocol.
+///
+/// [UEFI Spec Documentation: 10.6.4. Device Path from Text Protocol](https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#device-path-from-text-protocol)
+pub struct DevicePathFromText<'a, B: BootServices> {
+    protocol: &'static mut efi::protocols::device_path_from_text::Protocol,
+    boot_services: &'a B,
+}
+
+impl<'a, B: BootServices> DevicePathFromText<'a, B> {
+    /// Locates the first instance of the protocol.
+    pub fn locate(boot_services: &'a B) -> Result<Self, EfiError> {
+        let protocol = boot_services.locate_protocol(&protocol_handler::DevicePathFromText, None)?;
+        Ok(Self { protocol, boot_services })
+    }
+
+    /// Converts the text of a device node to a device path containing only that node.
+    ///
+    /// Returns [`efi::Status::INVALID_PARAMETER`] if the text is not a valid device node.
+    pub fn convert_text_to_device_node(&self, text: &str) -> Result<DevicePathBuf, EfiError> {
+        let text = String16::try_from(text).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        // The text is only read by the protocol.
+        let node = unsafe { r_efi::efi_call(self.protocol.convert_text_to_device_node, (text.as_ptr(),)) };
+        if node.is_null() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        //SAFETY: The protocol returns a single node allocated from pool.
+        let bytes = unsafe {
+            let size = u16::from_le_bytes((*node).length) as usize;
+            core::slice::from_raw_parts(node as *const u8, size.max(NODE_HEADER_SIZE)).to_vec()
+        };
+        let _ = self.boot_services.free_pool(node as *mut u8);
+        let mut device_path = bytes;
+        device_path.extend_from_slice(&END_ENTIRE);
+        DevicePathBuf::from_vec(device_path)
+    }
+
+    /// Converts the text of a device path to a device path.
+    ///
+    /// Returns [`efi::Status::INVALID_PARAMETER`] if the text is not a valid device path.
+    pub fn convert_text_to_device_path(&self, text: &str) -> Result<DevicePathBuf, EfiError> {
+        let text = String16::try_from(text).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        // The text is only read by the protocol.
+        let device_path = unsafe { r_efi::efi_call(self.protocol.convert_text_to_device_path, (text.as_ptr(),)) };
+        //SAFETY: The protocol returns a device path allocated from pool, or null.
+        let result = unsafe { DevicePath::from_ptr(device_path) }.map(DevicePathBuf::from);
+        if !device_path.is_null() {
+            let _ = self.boot_services.free_pool(device_path as *mut u8);
+        }
+        result
+    }
+}
+
+/// Formats bytes as hexadecimal digits.
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02X}"))
+    }
+}
+
+/// Formats a vendor node, with its data if it has any.
+fn fmt_vendor(f: &mut fmt::Formatter<'_>, name: &str, guid: efi::Guid, data: &[u8]) -> fmt::Result {
+    write!(f, "{name}({}", guid::Guid::from(guid))?;
+    if !data.is_empty() {
+        write!(f, ",{}", Hex(data))?;
+    }
+    f.write_str(")")
+}
+
+/// Formats a node with the text format of the Device Path to Text protocol, for the most common node types.
+fn fmt_node(node: &Node<'_>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match (node.node_type(), node.sub_type()) {
+        (Acpi::TYPE, Acpi::SUB_TYPE) => {
+            if let Some(acpi) = node.parse::<Acpi>() {
+                return match acpi.hid {
+                    hid if hid == crate::node_types::eisa_id(*b"PNP", 0x0A03) => write!(f, "PciRoot(0x{:X})", acpi.uid),
+                    hid if hid == crate::node_types::eisa_id(*b"PNP", 0x0A08) => {
+                        write!(f, "PcieRoot(0x{:X})", acpi.uid)
+                    }
+                    hid => {
+                        let vendor =
+                            [(hid >> 10) & 0x1F, (hid >> 5) & 0x1F, hid & 0x1F].map(|c| (c as u8 + b'@') as char);
+                        write!(f, "Acpi({}{}{}{:04X},0x{:X})", vendor[0], vendor[1], vendor[2], hid >> 16, acpi.uid)
+                    }
+                };
+            }
+        }
+        (Pci::TYPE, Pci::SUB_TYPE) => {
+            if let Some(pci) = node.parse::<Pci>() {
+                return write!(f, "Pci(0x{:X},0x{:X})", pci.device, pci.function);
+            }
+        }
+        (Usb::TYPE, Usb::SUB_TYPE) => {
+            if let Some(usb) = node.parse::<Usb>() {
+                return write!(f, "USB(0x{:X},0x{:X})", usb.parent_port_number, usb.interface_number);
+            }
+        }
+        (Sata::TYPE, Sata::SUB_TYPE) => {
+            if let Some(sata) = node.parse::<Sata>() {
+                return write!(
+                    f,
+                    "Sata(0x{:X},0x{:X},0x{:X})",
+                    sata.hba_port_number, sata.port_multiplier_port_number, sata.lun
+                );
+            }
+        }
+        (Nvme::TYPE, Nvme::SUB_TYPE) => {
+            if let Some(nvme) = node.parse::<Nvme>() {
+                let eui = nvme.namespace_eui.to_be_bytes().map(|b| alloc::format!("{b:02X}"));
+                return write!(f, "NVMe(0x{:X},{})", nvme.namespace_id, eui.join("-"));
+            }
+        }
+        (MacAddress::TYPE, MacAddress::SUB_TYPE) => {
+            if let Some(mac) = node.parse::<MacAddress>() {
+                let len = if mac.if_type <= 1 { 6 } else { mac.mac_address.len() };
+                return write!(f, "MAC({},0x{:X})", Hex(&mac.mac_address[..len]), mac.if_type);
+            }
+        }
+        (Ipv4::TYPE, Ipv4::SUB_TYPE) => {
+            if let Some(ipv4) = node.parse::<Ipv4>() {
+                let [a, b, c, d] = ipv4.remote_ip_address;
+                return write!(f, "IPv4({a}.{b}.{c}.{d})");
+            }
+        }
+        (Ipv6::TYPE, Ipv6::SUB_TYPE) => {
+            if let Some(ipv6) = node.parse::<Ipv6>() {
+                let groups = ipv6
+                    .remote_ip_address
+                    .chunks_exact(2)
+                    .map(|g| alloc::format!("{:x}", u16::from_be_bytes([g[0], g[1]])))
+                    .collect::<Vec<_>>();
+                return write!(f, "IPv6({})", groups.join(":"));
+            }
+        }
+        (HardDrive::TYPE, HardDrive::SUB_TYPE) => {
+            if let Some(hd) = node.parse::<HardDrive>() {
+                write!(f, "HD({},", hd.partition_number)?;
+                match (hd.partition_format, hd.signature) {
+                    (PartitionFormat::Mbr, PartitionSignature::Mbr(signature)) => write!(f, "MBR,0x{signature:08X}")?,
+                    (_, PartitionSignature::Guid(guid)) => write!(f, "GPT,{}", guid::Guid::from(guid))?,
+                    (format, _) => write!(f, "{},0", format as u8)?,
+                }
+                return write!(f, ",0x{:X},0x{:X})", hd.partition_start, hd.partition_size);
+            }
+        }
+        (FilePath::TYPE, FilePath::SUB_TYPE) => {
+            if let Some(file_path) = node.parse::<FilePath>() {
+                return write!(f, "{}", file_path.path_name);
+            }
+        }
+        (VendorHardware::TYPE, VendorHardware::SUB_TYPE) => {
+            if let Some(vendor) = node.parse::<VendorHardware>() {
+                return fmt_vendor(f, "VenHw", vendor.guid, &vendor.data);
+            }
+        }
+        (VendorMessaging::TYPE, VendorMessaging::SUB_TYPE) => {
+            if let Some(vendor) = node.parse::<VendorMessaging>() {
+                return fmt_vendor(f, "VenMsg", vendor.guid, &vendor.data);
+            }
+        }
+        (VendorMedia::TYPE, VendorMedia::SUB_TYPE) => {
+            if let Some(vendor) = node.parse::<VendorMedia>() {
+                return fmt_vendor(f, "VenMedia", vendor.guid, &vendor.data);
+            }
+        }
+        _ => (),
+    }
+    write!(f, "Path({},{},{})", node.node_type(), node.sub_type(), Hex(node.data()))
+}
+
+/// Formats a device path, nodes are separated by `/` and instances by `,`.
+fn fmt_device_path(device_path: &DevicePath, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut separator = "";
+    for node in device_path.nodes() {
+        if node.is_end_instance() {
+            f.write_str(",")?;
+            separator = "";
+            continue;
+        }
+        f.write_str(separator)?;
+        fmt_node(&node, f)?;
+        separator = "/";
+    }
+    Ok(())
+}
+
+/// Returns the Device Path to Text protocol if boot services are available.
+fn device_path_to_text() -> Option<DevicePathToText<'static, impl BootServices>> {
+    if !entry_point::boot_services_available() {
+        return None;
+    }
+    DevicePathToText::locate(entry_point::boot_services()).ok()
+}
+
+impl fmt::Display for DevicePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match device_path_to_text().and_then(|to_text| to_text.convert_device_path_to_text(self, false, true).ok()) {
+            Some(text) => f.write_str(&text),
+            None => fmt_device_path(self, f),
+        }
+    }
+}
+
+impl fmt::Display for DevicePathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match device_path_to_text().and_then(|to_text| to_text.convert_device_node_to_text(*self, false, true).ok()) {
+            Some(text) => f.write_str(&text),
+            None => fmt_node(self, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{node_types::AcpiEx, DevicePathBuilder};
+    use alloc::{boxed::Box, string::ToString};
+    use boot_services::MockBootServices;
+
+    #[test]
+    fn test_display() {
+        let gpt = efi::Guid::from_fields(0x12345678, 0x9ABC, 0xDEF0, 0x12, 0x34, &[0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0]);
+        let device_path = DevicePathBuilder::new()
+            .push(&Acpi::pci_root(0))
+            .push(&Pci::new(0x1F, 2))
+            .push(&Sata::new(0, 0xFFFF, 0))
+            .push(&HardDrive::gpt(1, 0x800, 0x100000, gpt))
+            .push(&FilePath::new(String16::try_from("\\EFI\\BOOT\\BOOTX64.EFI").unwrap()))
+            .build();
+        assert_eq!(
+            "PciRoot(0x0)/Pci(0x1F,0x2)/Sata(0x0,0xFFFF,0x0)/HD(1,GPT,12345678-9ABC-DEF0-1234-56789ABCDEF0,0x800,0x100000)/\\EFI\\BOOT\\BOOTX64.EFI",
+            device_path.to_string()
+        );
+        assert_eq!("", DevicePathBuilder::new().build().to_string());
+    }
+
+    #[test]
+    fn test_display_nodes() {
+        fn text<T: DevicePathNode>(node: T) -> String {
+            DevicePathBuilder::new().push(&node).build().nodes().next().unwrap().to_string()
+        }
+        assert_eq!("PcieRoot(0x1)", text(Acpi::new(crate::node_types::eisa_id(*b"PNP", 0x0A08), 1)));
+        assert_eq!("Acpi(PNP0501,0x0)", text(Acpi::new(crate::node_types::eisa_id(*b"PNP", 0x0501), 0)));
+        assert_eq!("USB(0x1,0x0)", text(Usb::new(1, 0)));
+        assert_eq!("NVMe(0x1,11-22-33-44-55-66-77-88)", text(Nvme::new(1, 0x1122334455667788)));
+        assert_eq!("MAC(001122334455,0x1)", text(MacAddress::ethernet([0x00, 0x11, 0x22, 0x33, 0x44, 0x55])));
+        assert_eq!("IPv4(192.168.0.1)", text(Ipv4::new([192, 168, 0, 1], 69, 17)));
+        let mut ipv6 = [0; 16];
+        ipv6[..2].copy_from_slice(&[0xFE, 0x80]);
+        ipv6[15] = 1;
+        assert_eq!("IPv6(fe80:0:0:0:0:0:0:1)", text(Ipv6::new(ipv6, 69, 17)));
+        assert_eq!("HD(2,MBR,0x12345678,0x3F,0x400)", text(HardDrive::mbr(2, 0x3F, 0x400, 0x12345678)));
+        assert_eq!(
+            "VenHw(01010101-0101-0101-0101-010101010101,0102)",
+            text(VendorHardware::new(efi::Guid::from_bytes(&[1; 16]), &[1, 2]))
+        );
+        assert_eq!(
+            "VenMedia(03030303-0303-0303-0303-030303030303)",
+            text(VendorMedia::new(efi::Guid::from_bytes(&[3; 16]), &[]))
+        );
+        assert_eq!("Path(2,2,000000000100000000000000000000)", text(AcpiEx::new(0, 1, 0)));
+    }
+
+    #[test]
+    fn test_display_multi_instance() {
+        let device_path = DevicePathBuilder::new().push(&Pci::new(0, 0)).end_instance().push(&Pci::new(1, 0)).build();
+        assert_eq!("Pci(0x0,0x0),Pci(0x1,0x0)", device_path.to_string());
+    }
+
+    extern "efiapi" fn convert_device_path_to_text(
+        device_path: *mut efi::protocols::device_path::Protocol,
+        display_only: efi::Boolean,
+        allow_shortcuts: efi::Boolean,
+    ) -> *mut u16 {
+        assert!(!bool::from(display_only) && bool::from(allow_shortcuts));
+        let size = unsafe { crate::device_path_size(device_path) }.unwrap();
+        let text = String16::try_from(alloc::format!("Path{size}").as_str()).unwrap();
+        text.into_vec_with_nul().leak().as_mut_ptr()
+    }
+
+    extern "efiapi" fn convert_text_to_device_path(text: *const u16) -> *mut efi::protocols::device_path::Protocol {
+        match unsafe { Str16::from_ptr(text) }.to_string_lossy().as_str() {
+            "Pci(0x1F,0x0)" => {
+                DevicePathBuilder::new().push(&Pci::new(0x1F, 0)).build().into_vec().leak().as_mut_ptr().cast()
+            }
+            _ => core::ptr::null_mut(),
+        }
+    }
+
+    extern "efiapi" fn convert_text_to_device_node(text: *const u16) -> *mut efi::protocols::device_path::Protocol {
+        match unsafe { Str16::from_ptr(text) }.to_string_lossy().as_str() {
+            "Pci(0x1F,0x0)" => Pci::new(0x1F, 0).to_bytes().leak().as_mut_ptr().cast(),
+            _ => core::ptr::null_mut(),
+        }
+    }
+
+    #[test]
+    fn test_device_path_to_text() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::DevicePathToText, efi::protocols::device_path_to_text::Protocol>()
+            .once()
+            .returning(|_, _| {
+                Ok(Box::leak(Box::new(efi::protocols::device_path_to_text::Protocol {
+                    convert_device_node_to_text: convert_device_path_to_text,
+                    convert_device_path_to_text,
+                })))
+            });
+        boot_services.expect_free_pool().times(1).returning(|_| Ok(()));
+
+        let to_text = DevicePathToText::locate(&boot_services).unwrap();
+        let device_path = DevicePathBuilder::new().push(&Pci::new(0x1F, 0)).build();
+        assert_eq!(Ok(String::from("Path10")), to_text.convert_device_path_to_text(&device_path, false, true));
+    }
+
+    #[test]
+    fn test_device_path_from_text() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::DevicePathFromText, efi::protocols::device_path_from_text::Protocol>()
+            .once()
+            .returning(|_, _| {
+                Ok(Box::leak(Box::new(efi::protocols::device_path_from_text::Protocol {
+                    convert_text_to_device_node,
+                    convert_text_to_device_path,
+                })))
+            });
+        boot_services.expect_free_pool().times(2).returning(|_| Ok(()));
+
+        let from_text = DevicePathFromText::locate(&boot_services).unwrap();
+        let expected = DevicePathBuilder::new().push(&Pci::new(0x1F, 0)).build();
+        assert_eq!(Ok(expected.clone()), from_text.convert_text_to_device_path("Pci(0x1F,0x0)"));
+        assert_eq!(Ok(expected), from_text.convert_text_to_device_node("Pci(0x1F,0x0)"));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), from_text.convert_text_to_device_path("Invalid"));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), from_text.convert_text_to_device_node("Invalid"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Device Path Utilities protocol.
+//!
+//! [UEFI Spec Documentation: 10.5. Device Path Utilities Protocol](https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#device-path-utilities-protocol)
+
+use core::ptr;
+
+use boot_services::{allocation::MemoryType, boxed::BootServicesBox, protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::{device_path_size, DevicePath, DevicePathBuilder, Node};
+
+type DevicePathProtocol = efi::protocols::device_path::Protocol;
+
+/// Device path owned by pool memory.
+pub type PoolDevicePath<'a, B> = BootServicesBox<'a, DevicePath, B>;
+
+/// Device Path Utilities protocol, with the same operations implemented in Rust when the protocol is not installed.
+///
+/// Device paths returned by the operations are allocated from pool like the ones returned by the protocol.
+pub struct DevicePathUtilities<'a, B: BootServices> {
+    protocol: Option<&'static mut efi::protocols::device_path_utilities::Protocol>,
+    boot_services: &'a B,
+}
+
+impl<'a, B: BootServices> DevicePathUtilities<'a, B> {
+    /// Locates the protocol, the Rust implementation is used if it is not installed.
+    pub fn new(boot_services: &'a B) -> Self {
+        Self {
+            protocol: boot_services.locate_protocol(&protocol_handler::DevicePathUtilities, None).ok(),
+            boot_services,
+        }
+    }
+
+    /// Returns true if the firmware protocol is used instead of the Rust implementation.
+    pub fn is_protocol_installed(&self) -> bool {
+        self.protocol.is_some()
+    }
+
+    /// Takes ownership of a device path returned by the protocol.
+    fn take_pool_device_path(&self, device_path: *mut DevicePathProtocol) -> Result<PoolDevicePath<'a, B>, EfiError> {
+        if device_path.is_null() {
+            return Err(efi::Status::OUT_OF_RESOURCES.into());
+        }
+        //SAFETY: The protocol returns a valid device path allocated from pool.
+        unsafe {
+            let size = device_path_size(device_path)?;
+            Ok(BootServicesBox::from_raw(
+                ptr::slice_from_raw_parts_mut(device_path as *mut u8, size) as *mut DevicePath,
+                self.boot_services,
+            ))
+        }
+    }
+
+    /// Size in bytes of the device path, including the end of entire device path node.
+    pub fn get_device_path_size(&self, device_path: &DevicePath) -> usize {
+        match &self.protocol {
+            Some(protocol) => unsafe { r_efi::efi_call(protocol.get_device_path_size, (device_path.as_ptr(),)) },
+            None => device_path.size(),
+        }
+    }
+
+    /// Copies the device path to pool memory.
+    pub fn duplicate_device_path(&self, device_path: &DevicePath) -> Result<PoolDevicePath<'a, B>, EfiError> {
+        match &self.protocol {
+            Some(protocol) => self.take_pool_device_path(unsafe {
+                r_efi::efi_call(protocol.duplicate_device_path, (device_path.as_ptr(),))
+            }),
+            None => DevicePathBuilder::from_device_path(device_path)
+                .build_in_pool(self.boot_services, MemoryType::BOOT_SERVICES_DATA),
+        }
+    }
+
+    /// Creates a device path with the nodes of `first` followed by the nodes of `second`.
+    pub fn append_device_path(
+        &self,
+        first: &DevicePath,
+        second: &DevicePath,
+    ) -> Result<PoolDevicePath<'a, B>, EfiError> {
+        match &self.protocol {
+            Some(protocol) => self.take_pool_device_path(unsafe {
+                r_efi::efi_call(protocol.append_device_path, (first.as_ptr(), second.as_ptr()))
+            }),
+            None => DevicePathBuilder::from_device_path(first)
+                .append(second)
+                .build_in_pool(self.boot_services, MemoryType::BOOT_SERVICES_DATA),
+        }
+    }
+
+    /// Creates a device path with the nodes of `device_path` followed by `node`.
+    pub fn append_device_node(
+        &self,
+        device_path: &DevicePath,
+        node: Node<'_>,
+    ) -> Result<PoolDevicePath<'a, B>, EfiError> {
+        match &self.protocol {
+            Some(protocol) => self.take_pool_device_path(unsafe {
+                r_efi::efi_call(
+                    protocol.append_device_node,
+                    (device_path.as_ptr(), node.as_bytes().as_ptr() as *const DevicePathProtocol),
+                )
+            }),
+            None => DevicePathBuilder::from_device_path(device_path)
+                .push_node(node)
+                .build_in_pool(self.boot_services, MemoryType::BOOT_SERVICES_DATA),
+        }
+    }
+
+    /// Creates a multi-instance device path with the instances of `device_path` followed by `instance`.
+    pub fn append_device_path_instance(
+        &self,
+        device_path: &DevicePath,
+        instance: &DevicePath,
+    ) -> Result<PoolDevicePath<'a, B>, EfiError> {
+        match &self.protocol {
+            Some(protocol) => self.take_pool_device_path(unsafe {
+                r_efi::efi_call(protocol.append_device_path_instance, (device_path.as_ptr(), instance.as_ptr()))
+            }),
+            None if device_path.is_end() => self.duplicate_device_path(instance),
+            None => DevicePathBuilder::from_device_path(device_path)
+                .end_instance()
+                .append(instance)
+                .build_in_pool(self.boot_services, MemoryType::BOOT_SERVICES_DATA),
+        }
+    }
+
+    /// Returns true if the device path has more than one instance.
+    pub fn is_device_path_multi_instance(&self, device_path: &DevicePath) -> bool {
+        match &self.protocol {
+            Some(protocol) => {
+                unsafe { r_efi::efi_call(protocol.is_device_path_multi_instance, (device_path.as_ptr(),)) }.into()
+            }
+            None => device_path.is_multi_instance(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{node_types::Pci, DevicePathBuf};
+    use alloc::{boxed::Box, vec, vec::Vec};
+    use boot_services::MockBootServices;
+
+    fn pool_boot_services(allocations: usize) -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::DevicePathUtilities, efi::protocols::device_path_utilities::Protocol>()
+            .returning(|_, _| Err(efi::Status::NOT_FOUND.into()));
+        boot_services
+            .expect_allocate_pool()
+            .times(allocations)
+            .returning(|_, size| Ok(Vec::leak(vec![0; size]).as_mut_ptr()));
+        boot_services.expect_free_pool().times(allocations).returning(|_| Ok(()));
+        boot_services
+    }
+
+    fn pci(device: u8) -> DevicePathBuf {
+        DevicePathBuilder::new().push(&Pci::new(device, 0)).build()
+    }
+
+    #[test]
+    fn test_rust_implementation() {
+        let boot_services = pool_boot_services(5);
+        let utilities = DevicePathUtilities::new(&boot_services);
+        assert!(!utilities.is_protocol_installed());
+
+        let (a, b) = (pci(1), pci(2));
+        assert_eq!(10, utilities.get_device_path_size(&a));
+        assert_eq!(a.as_bytes(), utilities.duplicate_device_path(&a).unwrap().as_bytes());
+
+        let appended = utilities.append_device_path(&a, &b).unwrap();
+        let expected = DevicePathBuilder::from_device_path(&a).append(&b).build();
+        assert_eq!(expected.as_bytes(), appended.as_bytes());
+
+        let node = b.nodes().next().unwrap();
+        assert_eq!(expected.as_bytes(), utilities.append_device_node(&a, node).unwrap().as_bytes());
+
+        let multi_instance = utilities.append_device_path_instance(&a, &b).unwrap();
+        assert!(utilities.is_device_path_multi_instance(&multi_instance));
+        assert!(!utilities.is_device_path_multi_instance(&a));
+
+        let end = DevicePathBuilder::new().build();
+        assert_eq!(b.as_bytes(), utilities.append_device_path_instance(&end, &b).unwrap().as_bytes());
+    }
+
+    extern "efiapi" fn get_device_path_size(device_path: *const DevicePathProtocol) -> usize {
+        unsafe { device_path_size(device_path) }.unwrap() + 1000
+    }
+
+    extern "efiapi" fn duplicate_device_path(device_path: *const DevicePathProtocol) -> *mut DevicePathProtocol {
+        let device_path = unsafe { DevicePath::from_ptr(device_path) }.unwrap();
+        device_path.as_bytes().to_vec().leak().as_mut_ptr().cast()
+    }
+
+    extern "efiapi" fn fail(_: *const DevicePathProtocol, _: *const DevicePathProtocol) -> *mut DevicePathProtocol {
+        ptr::null_mut()
+    }
+
+    extern "efiapi" fn get_next_device_path_instance(
+        _: *mut *mut DevicePathProtocol,
+        _: *mut usize,
+    ) -> *mut DevicePathProtocol {
+        ptr::null_mut()
+    }
+
+    extern "efiapi" fn is_device_path_multi_instance(_: *const DevicePathProtocol) -> efi::Boolean {
+        efi::Boolean::TRUE
+    }
+
+    extern "efiapi" fn create_device_node(_: u8, _: u8, _: u16) -> *mut DevicePathProtocol {
+        ptr::null_mut()
+    }
+
+    #[test]
+    fn test_protocol() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::DevicePathUtilities, efi::protocols::device_path_utilities::Protocol>()
+            .returning(|_, _| {
+                Ok(Box::leak(Box::new(efi::protocols::device_path_utilities::Protocol {
+                    get_device_path_size,
+                    duplicate_device_path,
+                    append_device_path: fail,
+                    append_device_node: fail,
+                    append_device_path_instance: fail,
+                    get_next_device_path_instance,
+                    is_device_path_multi_instance,
+                    create_device_node,
+                })))
+            });
+        boot_services.expect_free_pool().once().returning(|_| Ok(()));
+
+        let utilities = DevicePathUtilities::new(&boot_services);
+        assert!(utilities.is_protocol_installed());
+
+        let a = pci(1);
+        assert_eq!(1010, utilities.get_device_path_size(&a));
+        assert_eq!(a.as_bytes(), utilities.duplicate_device_path(&a).unwrap().as_bytes());
+        assert_eq!(Err(efi::Status::OUT_OF_RESOURCES.into()), utilities.append_device_path(&a, &a).map(|_| ()));
+        assert!(utilities.is_device_path_multi_instance(&a));
+    }
+}
@@ -0,0 +1,295 @@
+//! Entry point support.
+//!
+//! The image entry point calls [`init`] once with the arguments it was given, after which [`boot_services`],
+//! [`runtime_services`], [`system_table`] and [`image_handle`] can be used from anywhere in the image instead of
+//! keeping its own statics.
+//!
+//! The [`entry`] attribute generates that entry point:
+//!
+//! ```ignore
+//! #[entry_point::entry]
+//! fn main() -> Result<(), EfiError> {
+//!     let boot_services = entry_point::boot_services();
+//!     // ...
+//!     Ok(())
+//! }
+//! ```
+//!
+//! With the `global_allocator` feature, the allocations of the image are served from the pool of the boot services
+//! stored by [`init`], they must not be made before it or after ExitBootServices().
+//!
+//! Or it can be written by hand:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub extern "efiapi" fn efi_main(image_handle: efi::Handle, system_table: *mut efi::SystemTable) -> efi::Status {
+//!     if let Err(status) = unsafe { entry_point::init(image_handle, system_table) } {
+//!         return status;
+//!     }
+//!     let boot_services = entry_point::boot_services();
+//!     // ...
+//! }
+//! ```
+#![cfg_attr(not(test), no_std)]
+
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+#[cfg(all(feature = "global_allocator", not(test)))]
+use boot_services::global_allocator::BootServicesGlobalAllocator;
+use boot_services::{event::EventType, tpl::Tpl, BootServices, StandardBootServices};
+use r_efi::efi;
+use runtime_services::{RuntimeServices, StandardRuntimeServices};
+use status::EfiError;
+
+pub use entry_point_macros::entry;
+
+#[doc(hidden)]
+pub mod __private {
+    //! Re-exports and helpers used by the code generated from `entry_point_macros`.
+    pub use r_efi;
+    use r_efi::efi;
+
+    /// Converts the result of the entry function into the status returned to the firmware.
+    pub fn into_status<E: Into<efi::Status>>(result: Result<(), E>) -> efi::Status {
+        result.map_or_else(Into::into, |()| efi::Status::SUCCESS)
+    }
+}
+
+static IMAGE_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static SYSTEM_TABLE: AtomicPtr<efi::SystemTable> = AtomicPtr::new(ptr::null_mut());
+static BOOT_SERVICES: StandardBootServices = StandardBootServices::new_uninit();
+static RUNTIME_SERVICES: StandardRuntimeServices = StandardRuntimeServices::new_uninit();
+
+#[cfg(all(feature = "global_allocator", not(test)))]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: BootServicesGlobalAllocator<StandardBootServices> =
+    BootServicesGlobalAllocator(&BOOT_SERVICES);
+
+/// Set when ExitBootServices() is called.
+static BOOT_SERVICES_EXITED: AtomicBool = AtomicBool::new(false);
+
+/// Stores the image handle and the system table given to the entry point.
+///
+/// # Errors
+///
+/// * `INVALID_PARAMETER` if the image handle is null or if the system table is null, has an invalid signature or
+///   is missing its boot or runtime services.
+/// * `ALREADY_STARTED` if it was already initialized with another system table.
+///
+/// Initializing again with the same system table, like a host test harness starting the image again, only replaces
+/// the image handle.
+///
+/// # Safety
+///
+/// *system_table* must be the system table given to the entry point, it must stay valid for the life of the image.
+pub unsafe fn init(image_handle: efi::Handle, system_table: *mut efi::SystemTable) -> Result<(), EfiError> {
+    let efi_system_table = system_table.as_ref().ok_or(efi::Status::INVALID_PARAMETER)?;
+    if image_handle.is_null() || efi_system_table.hdr.signature != efi::SYSTEM_TABLE_SIGNATURE {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    let efi_boot_services = efi_system_table.boot_services.as_ref().ok_or(efi::Status::INVALID_PARAMETER)?;
+    let efi_runtime_services = efi_system_table.runtime_services.as_ref().ok_or(efi::Status::INVALID_PARAMETER)?;
+
+    match SYSTEM_TABLE.compare_exchange(ptr::null_mut(), system_table, Ordering::SeqCst, Ordering::SeqCst) {
+        Ok(_) => (),
+        Err(current) if current == system_table => {
+            IMAGE_HANDLE.store(image_handle, Ordering::SeqCst);
+            return Ok(());
+        }
+        Err(_) => return Err(efi::Status::ALREADY_STARTED.into()),
+    }
+    IMAGE_HANDLE.store(image_handle, Ordering::SeqCst);
+    BOOT_SERVICES.initialize(efi_boot_services);
+    RUNTIME_SERVICES.initialize(efi_runtime_services);
+
+    // Without the event, boot services are considered available for the life of the image.
+    let _ = BOOT_SERVICES.create_event(
+        EventType::SIGNAL_EXIT_BOOT_SERVICES,
+        Tpl::NOTIFY,
+        Some(exit_boot_services_notify),
+        None::<&'static ()>,
+    );
+    Ok(())
+}
+
+/// Returns true if [`init`] was successfully called.
+pub fn is_initialized() -> bool {
+    !SYSTEM_TABLE.load(Ordering::SeqCst).is_null()
+}
+
+/// The image handle given to the entry point.
+///
+/// # Panics
+///
+/// Panics if [`init`] was not called.
+pub fn image_handle() -> efi::Handle {
+    assert!(is_initialized(), "Entry point is not initialized.");
+    IMAGE_HANDLE.load(Ordering::SeqCst)
+}
+
+/// The system table given to the entry point.
+///
+/// # Panics
+///
+/// Panics if [`init`] was not called.
+pub fn system_table() -> &'static efi::SystemTable {
+    //SAFETY: The pointer was validated in init and is required to stay valid for the life of the image.
+    unsafe { SYSTEM_TABLE.load(Ordering::SeqCst).as_ref() }.expect("Entry point is not initialized.")
+}
+
+/// The boot services of the system table.
+///
+/// # Panics
+///
+/// Panics if [`init`] was not called.
+///
+/// # Debug asserts
+///
+/// This function will assert on debug if called after ExitBootServices().
+pub fn boot_services() -> &'static StandardBootServices<'static> {
+    assert!(is_initialized(), "Entry point is not initialized.");
+    debug_assert!(!BOOT_SERVICES_EXITED.load(Ordering::SeqCst), "Boot services used after ExitBootServices().");
+    &BOOT_SERVICES
+}
+
+/// Returns true if [`init`] was called and ExitBootServices() was not, [`boot_services`] can then be used.
+pub fn boot_services_available() -> bool {
+    is_initialized() && !BOOT_SERVICES_EXITED.load(Ordering::SeqCst)
+}
+
+/// The runtime services of the system table.
+///
+/// # Panics
+///
+/// Panics if [`init`] was not called.
+pub fn runtime_services() -> &'static StandardRuntimeServices<'static> {
+    assert!(is_initialized(), "Entry point is not initialized.");
+    &RUNTIME_SERVICES
+}
+
+/// Returns the next value of the platform's monotonic counter, usable as a unique and ordered identifier.
+///
+/// Before ExitBootServices() this is the count returned by GetNextMonotonicCount(). After it, only the high 32 bits
+/// can be incremented with GetNextHighMonotonicCount() and the low 32 bits are zero, which keeps the values ordered.
+///
+/// # Panics
+///
+/// Panics if [`init`] was not called.
+pub fn next_monotonic_count() -> Result<u64, EfiError> {
+    if BOOT_SERVICES_EXITED.load(Ordering::SeqCst) {
+        runtime_services().get_next_high_monotonic_count().map(|high_count| (high_count as u64) << 32)
+    } else {
+        boot_services().get_next_monotonic_count()
+    }
+}
+
+extern "efiapi" fn exit_boot_services_notify(_event: efi::Event, _context: Option<&'static ()>) {
+    BOOT_SERVICES_EXITED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::mem::MaybeUninit;
+    use std::panic;
+
+    static NOTIFY_FUNCTION: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+    extern "efiapi" fn efi_create_event(
+        event_type: u32,
+        notify_tpl: efi::Tpl,
+        notify_function: Option<efi::EventNotify>,
+        _notify_context: *mut c_void,
+        event: *mut efi::Event,
+    ) -> efi::Status {
+        assert_eq!(efi::EVT_SIGNAL_EXIT_BOOT_SERVICES, event_type);
+        assert_eq!(efi::TPL_NOTIFY, notify_tpl);
+        NOTIFY_FUNCTION.store(notify_function.unwrap() as *mut c_void, Ordering::SeqCst);
+        unsafe { event.write(1_usize as efi::Event) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn efi_get_next_monotonic_count(count: *mut u64) -> efi::Status {
+        unsafe { *count = 0x1_0000_0005 };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn efi_get_next_high_mono_count(high_count: *mut u32) -> efi::Status {
+        unsafe { *high_count = 2 };
+        efi::Status::SUCCESS
+    }
+
+    fn efi_system_table() -> *mut efi::SystemTable {
+        unsafe {
+            let mut bs = MaybeUninit::<efi::BootServices>::zeroed();
+            bs.assume_init_mut().create_event = efi_create_event;
+            bs.assume_init_mut().get_next_monotonic_count = efi_get_next_monotonic_count;
+            let mut rs = MaybeUninit::<efi::RuntimeServices>::zeroed();
+            rs.assume_init_mut().get_next_high_mono_count = efi_get_next_high_mono_count;
+            let mut st = MaybeUninit::<efi::SystemTable>::zeroed();
+            st.assume_init_mut().hdr.signature = efi::SYSTEM_TABLE_SIGNATURE;
+            st.assume_init_mut().boot_services = Box::leak(Box::new(bs.assume_init()));
+            st.assume_init_mut().runtime_services = Box::leak(Box::new(rs.assume_init()));
+            Box::leak(Box::new(st.assume_init()))
+        }
+    }
+
+    #[test]
+    fn test_init_invalid_parameters() {
+        let image_handle = 1_usize as efi::Handle;
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), unsafe { init(image_handle, ptr::null_mut()) });
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), unsafe { init(ptr::null_mut(), efi_system_table()) });
+
+        let st = efi_system_table();
+        unsafe { (*st).hdr.signature = 0 };
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), unsafe { init(image_handle, st) });
+
+        let st = efi_system_table();
+        unsafe { (*st).boot_services = ptr::null_mut() };
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), unsafe { init(image_handle, st) });
+
+        let st = efi_system_table();
+        unsafe { (*st).runtime_services = ptr::null_mut() };
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), unsafe { init(image_handle, st) });
+    }
+
+    #[test]
+    fn test_init() {
+        // Every state is global, the whole life of an image is tested at once.
+        assert!(panic::catch_unwind(image_handle).is_err());
+        assert!(panic::catch_unwind(system_table).is_err());
+        assert!(panic::catch_unwind(boot_services).is_err());
+        assert!(!boot_services_available());
+
+        let st = efi_system_table();
+        assert_eq!(Ok(()), unsafe { init(1_usize as efi::Handle, st) });
+        assert_eq!(Err(efi::Status::ALREADY_STARTED.into()), unsafe {
+            init(1_usize as efi::Handle, efi_system_table())
+        });
+        assert_eq!(Ok(()), unsafe { init(2_usize as efi::Handle, st) });
+        assert_eq!(2_usize as efi::Handle, image_handle());
+        assert_eq!(Ok(()), unsafe { init(1_usize as efi::Handle, st) });
+
+        assert!(is_initialized());
+        assert!(boot_services_available());
+        assert_eq!(1_usize as efi::Handle, image_handle());
+        assert_eq!(st as *const efi::SystemTable, system_table() as *const _);
+        let _ = boot_services();
+        let _ = runtime_services();
+        assert_eq!(Ok(0x1_0000_0005), next_monotonic_count());
+
+        let notify_function = NOTIFY_FUNCTION.load(Ordering::SeqCst);
+        assert!(!notify_function.is_null());
+        let notify_function: efi::EventNotify = unsafe { core::mem::transmute(notify_function) };
+        unsafe { r_efi::efi_call(notify_function, (1_usize as efi::Event, ptr::null_mut())) };
+
+        assert!(panic::catch_unwind(boot_services).is_err());
+        assert!(!boot_services_available());
+        let _ = runtime_services();
+        assert_eq!(Ok(0x2_0000_0000), next_monotonic_count());
+    }
+}
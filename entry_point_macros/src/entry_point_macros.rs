@@ -0,0 +1,183 @@
+//! Procedural macros companion to the `entry_point` crate.
+//!
+//! These macros are re-exported by `entry_point` and should be used through it.
+//!
+//! ```ignore
+//! use entry_point::entry;
+//!
+//! #[entry]
+//! fn main() -> Result<(), efi::Status> {
+//!     let boot_services = entry_point::boot_services();
+//!     Ok(())
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse::Parser, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Expr, ItemFn, Meta, MetaNameValue,
+    Token,
+};
+
+/// Generate the `efi_main` entry point of the image around the annotated function.
+///
+/// The generated entry point initializes `entry_point` with the image handle and the system table, then calls the
+/// annotated function and converts its result into the status returned to the firmware. The tables are then
+/// available through `entry_point::boot_services()`, `entry_point::runtime_services()` and
+/// `entry_point::system_table()`, and the allocations are served by the boot services with the `global_allocator`
+/// feature of `entry_point`.
+///
+/// The annotated function takes no arguments and returns `Result<(), E>` where `E: Into<efi::Status>`.
+///
+/// The attribute takes comma-separated arguments, the services they set up are ready before the annotated function
+/// is called and their errors are ignored:
+///
+/// * `logger` sets the default logger of the `logger` crate with `logger::init_logging()`.
+/// * `init = path` calls the function at `path`, for other image-wide services.
+///
+/// ```ignore
+/// #[entry(logger, init = platform::init)]
+/// fn main() -> Result<(), efi::Status> {
+///     log::info!("Hello");
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn entry(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = TokenStream2::from(args);
+    let input = parse_macro_input!(input as ItemFn);
+    match expand_entry(args, input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_entry(args: TokenStream2, input: ItemFn) -> syn::Result<TokenStream2> {
+    let (mut logger, mut init) = (None, None);
+    let error = |span| syn::Error::new(span, "The entry attribute only takes `logger` and `init = path` arguments.");
+    let args = Punctuated::<Meta, Token![,]>::parse_terminated.parse2(args.clone()).map_err(|_| error(args.span()))?;
+    for arg in args {
+        match arg {
+            Meta::Path(path) if path.is_ident("logger") && logger.is_none() => {
+                logger = Some(quote!(let _ = ::logger::init_logging();));
+            }
+            Meta::NameValue(MetaNameValue { path, value: Expr::Path(value), .. })
+                if path.is_ident("init") && init.is_none() =>
+            {
+                init = Some(quote!(let _ = #value();));
+            }
+            arg => return Err(error(arg.span())),
+        }
+    }
+    let sig = &input.sig;
+    if !sig.inputs.is_empty() {
+        return Err(syn::Error::new(
+            sig.inputs.span(),
+            "The entry function can not take arguments, use `entry_point::image_handle()` and `entry_point::system_table()`.",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(sig.generics.span(), "The entry function can not be generic."));
+    }
+    if let Some(asyncness) = sig.asyncness {
+        return Err(syn::Error::new(asyncness.span(), "The entry function can not be async."));
+    }
+    if sig.ident == "efi_main" {
+        return Err(syn::Error::new(sig.ident.span(), "The entry function can not be named `efi_main`."));
+    }
+    if let Some(unsafety) = sig.unsafety {
+        return Err(syn::Error::new(unsafety.span(), "The entry function can not be unsafe."));
+    }
+    let ident = &sig.ident;
+    let efi = quote!(::entry_point::__private::r_efi::efi);
+
+    Ok(quote! {
+        #input
+
+        #[no_mangle]
+        #[allow(clippy::not_unsafe_ptr_arg_deref)] // The firmware calls the entry point with valid tables.
+        pub extern "efiapi" fn efi_main(image_handle: #efi::Handle, system_table: *mut #efi::SystemTable) -> #efi::Status {
+            //SAFETY: These are the arguments given by the firmware to the entry point of the image.
+            if let Err(status) = unsafe { ::entry_point::init(image_handle, system_table) } {
+                return status.into();
+            }
+            #logger
+            #init
+            ::entry_point::__private::into_status(#ident())
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_entry() {
+        let input: ItemFn = syn::parse_quote! {
+            fn main() -> Result<(), efi::Status> {
+                Ok(())
+            }
+        };
+        let tokens = expand_entry(TokenStream2::new(), input).unwrap().to_string();
+        assert!(tokens.contains("fn efi_main"));
+        assert!(tokens.contains("into_status (main ())"));
+    }
+
+    #[test]
+    fn test_entry_with_arguments() {
+        let input: ItemFn = syn::parse_quote! {
+            fn main(image_handle: efi::Handle) -> Result<(), efi::Status> {
+                Ok(())
+            }
+        };
+        assert!(expand_entry(TokenStream2::new(), input).is_err());
+    }
+
+    #[test]
+    fn test_entry_with_attribute_arguments() {
+        let input: ItemFn = syn::parse_quote! {
+            fn main() -> Result<(), efi::Status> {
+                Ok(())
+            }
+        };
+        assert!(expand_entry(quote!(driver), input).is_err());
+    }
+
+    #[test]
+    fn test_entry_with_init() {
+        let input: ItemFn = syn::parse_quote! {
+            fn main() -> Result<(), efi::Status> {
+                Ok(())
+            }
+        };
+        let tokens = expand_entry(quote!(init = logger::init_logging), input.clone()).unwrap().to_string();
+        assert!(tokens.contains("let _ = logger :: init_logging () ;"));
+        assert!(expand_entry(quote!(setup = logger::init_logging), input.clone()).is_err());
+        assert!(expand_entry(quote!(init = 1), input).is_err());
+    }
+
+    #[test]
+    fn test_entry_with_logger() {
+        let input: ItemFn = syn::parse_quote! {
+            fn main() -> Result<(), efi::Status> {
+                Ok(())
+            }
+        };
+        let tokens = expand_entry(quote!(logger, init = platform::init), input.clone()).unwrap().to_string();
+        assert!(tokens.contains("let _ = :: logger :: init_logging () ; let _ = platform :: init () ;"));
+        assert!(expand_entry(quote!(logger, logger), input.clone()).is_err());
+        assert!(expand_entry(quote!(logger = 1), input).is_err());
+    }
+
+    #[test]
+    fn test_entry_named_efi_main() {
+        let input: ItemFn = syn::parse_quote! {
+            fn efi_main() -> Result<(), efi::Status> {
+                Ok(())
+            }
+        };
+        assert!(expand_entry(TokenStream2::new(), input).is_err());
+    }
+}
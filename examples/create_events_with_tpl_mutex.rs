@@ -88,7 +88,7 @@ extern "efiapi" fn efi_create_event(
     _event: *mut efi::Event,
 ) -> efi::Status {
     if let Some(notify_function) = notify_function {
-        notify_function(ptr::null_mut(), notify_context);
+        unsafe { r_efi::efi_call(notify_function, (ptr::null_mut(), notify_context)) };
     }
     efi::Status::SUCCESS
 }
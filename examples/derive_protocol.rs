@@ -0,0 +1,42 @@
+use boot_services::{
+    protocol_handler::{DriverBinding, Protocol},
+    BootServices, MockBootServices,
+};
+use r_efi::efi;
+
+/// Interface of a protocol defined outside of r-efi.
+#[repr(C)]
+pub struct MyProtocolInterface {
+    pub revision: u32,
+    pub get_value: extern "efiapi" fn(this: *mut MyProtocolInterface) -> u32,
+}
+
+#[derive(Protocol)]
+#[protocol_guid = "8F644FA9-E850-4DB1-9CE2-0B44698E8DA4"]
+#[protocol_interface = "MyProtocolInterface"]
+pub struct MyProtocol;
+
+/// A protocol without interface, installed with a null interface pointer.
+#[derive(Protocol)]
+#[protocol_guid = "2B0585EB-D8B8-49A9-8B8C-E21B01AEF2B7"]
+pub struct MyTagProtocol;
+
+extern "efiapi" fn get_value(this: *mut MyProtocolInterface) -> u32 {
+    unsafe { (*this).revision * 2 }
+}
+
+fn main() {
+    let mut boot_services = MockBootServices::new();
+
+    boot_services
+        .expect_locate_protocol::<MyProtocol, MyProtocolInterface>()
+        .withf(|protocol, registration| **protocol == *MyProtocol && registration.is_none())
+        .returning(|_, _| Ok(Box::leak(Box::new(MyProtocolInterface { revision: 21, get_value }))));
+
+    let interface = boot_services.locate_protocol(&MyProtocol, None).unwrap();
+    println!("value: {}", (interface.get_value)(interface));
+
+    let guid: &efi::Guid = &MyTagProtocol;
+    println!("tag protocol guid: {:?}", guid.as_fields());
+    assert_ne!(MyTagProtocol.protocol_guid(), DriverBinding.protocol_guid());
+}
@@ -0,0 +1,48 @@
+use core::{ffi::c_void, mem::MaybeUninit};
+
+use mu_rust_helpers::{
+    boot_services::BootServices,
+    entry_point::{self, entry},
+};
+use r_efi::efi;
+
+#[entry]
+fn driver_entry() -> mu_rust_helpers::Result<()> {
+    let count = entry_point::boot_services().get_next_monotonic_count()?;
+    println!("image {:?} started, monotonic count: {count}", entry_point::image_handle());
+    Err(efi::Status::UNSUPPORTED.into())
+}
+
+extern "efiapi" fn efi_create_event(
+    _event_type: u32,
+    _notify_tpl: efi::Tpl,
+    _notify_function: Option<efi::EventNotify>,
+    _notify_context: *mut c_void,
+    event: *mut efi::Event,
+) -> efi::Status {
+    unsafe { event.write(1_usize as efi::Event) };
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn efi_get_next_monotonic_count(count: *mut u64) -> efi::Status {
+    unsafe { *count = 42 };
+    efi::Status::SUCCESS
+}
+
+fn main() {
+    // Tables normally given by the firmware to the entry point.
+    let system_table = unsafe {
+        let mut bs = MaybeUninit::<efi::BootServices>::zeroed();
+        bs.assume_init_mut().create_event = efi_create_event;
+        bs.assume_init_mut().get_next_monotonic_count = efi_get_next_monotonic_count;
+        let rs = MaybeUninit::<efi::RuntimeServices>::zeroed();
+        let mut st = MaybeUninit::<efi::SystemTable>::zeroed();
+        st.assume_init_mut().hdr.signature = efi::SYSTEM_TABLE_SIGNATURE;
+        st.assume_init_mut().boot_services = Box::leak(Box::new(bs.assume_init()));
+        st.assume_init_mut().runtime_services = Box::leak(Box::new(rs.assume_init()));
+        Box::leak(Box::new(st.assume_init()))
+    };
+
+    let status = efi_main(1_usize as efi::Handle, system_table);
+    assert_eq!(efi::Status::UNSUPPORTED, status);
+}
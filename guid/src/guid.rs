@@ -1,15 +1,21 @@
 #![cfg_attr(target_os = "uefi", no_std)]
 
+use core::{cmp::Ordering, fmt, str::FromStr};
+
 use r_efi::efi;
-use uuid::uuid;
 
-/// Macro for creating an `efi::Guid` from string representation.
-/// This is a wrapper for `uuid!` from the uuid crate.
+pub mod well_known;
+
+/// Macro for creating an `efi::Guid` from string representation at compile time.
+///
+/// The string must be in the registry format `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`, an invalid string fails the
+/// build.
 #[macro_export]
 macro_rules! guid {
-    ($guid_str:expr) => {
-        efi::Guid::from_bytes(&uuid!($guid_str).to_bytes_le())
-    };
+    ($guid_str:expr) => {{
+        const GUID: $crate::__private::r_efi::efi::Guid = $crate::__private::parse_guid($guid_str);
+        GUID
+    }};
 }
 
 /// Macro for printing an `efi::Guid` as a string.
@@ -28,6 +34,215 @@ macro_rules! guid_to_uuid {
     };
 }
 
+#[doc(hidden)]
+pub mod __private {
+    //! Re-exports and helpers used by the code generated from the macros of this crate.
+    pub use r_efi;
+    use r_efi::efi;
+
+    use crate::ParseGuidError;
+
+    /// Parse a GUID in the registry format (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the GUID is not in the registry format, which fails the build when evaluated in a const context.
+    pub const fn parse_guid(guid: &str) -> efi::Guid {
+        match try_parse_guid(guid) {
+            Ok(guid) => guid,
+            Err(ParseGuidError::InvalidFormat) => {
+                panic!("Invalid GUID, expected the format xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx.")
+            }
+            Err(ParseGuidError::InvalidCharacter) => panic!("Invalid GUID, unexpected character."),
+        }
+    }
+
+    /// Fallible version of [`parse_guid`].
+    pub const fn try_parse_guid(guid: &str) -> Result<efi::Guid, ParseGuidError> {
+        let guid = guid.as_bytes();
+        if guid.len() != 36 || guid[8] != b'-' || guid[13] != b'-' || guid[18] != b'-' || guid[23] != b'-' {
+            return Err(ParseGuidError::InvalidFormat);
+        }
+        let mut node = [0; 6];
+        let mut i = 0;
+        while i < node.len() {
+            node[i] = match hex(guid, 24 + i * 2, 2) {
+                Ok(value) => value as u8,
+                Err(err) => return Err(err),
+            };
+            i += 1;
+        }
+        match (hex(guid, 0, 8), hex(guid, 9, 4), hex(guid, 14, 4), hex(guid, 19, 2), hex(guid, 21, 2)) {
+            (Ok(d1), Ok(d2), Ok(d3), Ok(d4), Ok(d5)) => {
+                Ok(efi::Guid::from_fields(d1 as u32, d2 as u16, d3 as u16, d4 as u8, d5 as u8, &node))
+            }
+            _ => Err(ParseGuidError::InvalidCharacter),
+        }
+    }
+
+    /// Value of the `len` hexadecimal digits at `start`.
+    const fn hex(guid: &[u8], start: usize, len: usize) -> Result<u64, ParseGuidError> {
+        let mut value = 0;
+        let mut i = start;
+        while i < start + len {
+            let digit = match guid[i] {
+                c @ b'0'..=b'9' => c - b'0',
+                c @ b'a'..=b'f' => c - b'a' + 10,
+                c @ b'A'..=b'F' => c - b'A' + 10,
+                _ => return Err(ParseGuidError::InvalidCharacter),
+            };
+            value = value << 4 | digit as u64;
+            i += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// A GUID with canonical formatting, parsing and ordering.
+///
+/// It has the same layout as [`efi::Guid`] and converts to and from it. It is displayed and parsed in the registry
+/// format (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`) and ordered as its string representation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Guid(efi::Guid);
+
+impl Guid {
+    /// Create a new Guid from an [`efi::Guid`].
+    pub const fn new(guid: efi::Guid) -> Self {
+        Self(guid)
+    }
+
+    /// The wrapped [`efi::Guid`].
+    pub const fn as_efi_guid(&self) -> &efi::Guid {
+        &self.0
+    }
+}
+
+impl From<efi::Guid> for Guid {
+    fn from(guid: efi::Guid) -> Self {
+        Self(guid)
+    }
+}
+
+impl From<Guid> for efi::Guid {
+    fn from(guid: Guid) -> Self {
+        guid.0
+    }
+}
+
+impl AsRef<efi::Guid> for Guid {
+    fn as_ref(&self) -> &efi::Guid {
+        &self.0
+    }
+}
+
+impl PartialEq<efi::Guid> for Guid {
+    fn eq(&self, other: &efi::Guid) -> bool {
+        self.0 == *other
+    }
+}
+
+impl Ord for Guid {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.as_fields().cmp(&other.0.as_fields())
+    }
+}
+
+impl PartialOrd for Guid {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (d1, d2, d3, d4, d5, node) = self.0.as_fields();
+        write!(f, "{d1:08X}-{d2:04X}-{d3:04X}-{d4:02X}{d5:02X}-")?;
+        node.iter().try_for_each(|b| write!(f, "{b:02X}"))
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Guid({self})")
+    }
+}
+
+impl FromStr for Guid {
+    type Err = ParseGuidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        __private::try_parse_guid(s).map(Self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Guid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Guid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct GuidVisitor;
+
+        impl serde::de::Visitor<'_> for GuidVisitor {
+            type Value = Guid;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a GUID in the format xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(GuidVisitor)
+    }
+}
+
+#[cfg(feature = "uefi")]
+impl From<uefi::Guid> for Guid {
+    fn from(guid: uefi::Guid) -> Self {
+        Self(efi::Guid::from_bytes(&guid.to_bytes()))
+    }
+}
+
+#[cfg(feature = "uefi")]
+impl From<Guid> for uefi::Guid {
+    fn from(guid: Guid) -> Self {
+        uefi::Guid::from_bytes(*guid.0.as_bytes())
+    }
+}
+
+/// Error returned when parsing a [`Guid`] from a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseGuidError {
+    /// The string is not in the format `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`.
+    InvalidFormat,
+    /// The string contains a character that is not an hexadecimal digit.
+    InvalidCharacter,
+}
+
+impl fmt::Display for ParseGuidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseGuidError::InvalidFormat => f.write_str("expected the format xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx"),
+            ParseGuidError::InvalidCharacter => f.write_str("unexpected character"),
+        }
+    }
+}
+
+#[cfg(feature = "status")]
+impl From<ParseGuidError> for status::EfiError {
+    fn from(_: ParseGuidError) -> Self {
+        status::EfiError::new(efi::Status::INVALID_PARAMETER)
+    }
+}
+
 const ZERO_GUID_STR: &str = "00000000-0000-0000-0000-000000000000";
 
 pub const ZERO: efi::Guid = guid!(ZERO_GUID_STR);
@@ -45,7 +260,9 @@ mod tests {
     use r_efi::efi;
     use uuid::uuid;
 
-    use crate::{CALLER_ID, ZERO, ZERO_GUID_STR};
+    use std::collections::BTreeSet;
+
+    use crate::{Guid, ParseGuidError, __private::parse_guid, CALLER_ID, ZERO, ZERO_GUID_STR};
 
     const MS_WHEA_RSC_DATA_TYPE_GUID_FROM_MACRO: efi::Guid = guid!("91DEEA05-8C0A-4DCD-B91E-F21CA0C68405");
     const ADVANCED_LOGGER_PROTOCOL_GUID_FROM_MACRO: efi::Guid = guid!("434F695C-EF26-4A12-9EBA-DDEF0097497C");
@@ -64,6 +281,87 @@ mod tests {
         assert_ne!(ADVANCED_LOGGER_PROTOCOL_GUID_FROM_MACRO, MS_WHEA_RSC_DATA_TYPE_GUID_FROM_MACRO);
     }
 
+    #[test]
+    fn test_guid_macro_matches_uuid() {
+        let guid = guid!("91deea05-8c0a-4dcd-b91e-f21ca0c68405");
+        assert_eq!(MS_WHEA_RSC_DATA_TYPE_GUID_FROM_MACRO, guid);
+        assert_eq!(uuid!("91DEEA05-8C0A-4DCD-B91E-F21CA0C68405").to_bytes_le(), *guid.as_bytes());
+    }
+
+    #[test]
+    fn test_guid_macro_without_imports() {
+        mod no_imports {
+            pub const GUID: r_efi::efi::Guid = guid!("434F695C-EF26-4A12-9EBA-DDEF0097497C");
+        }
+        assert_eq!(ADVANCED_LOGGER_PROTOCOL_GUID_FROM_FIELDS, no_imports::GUID);
+    }
+
+    #[test]
+    #[should_panic = "Invalid GUID"]
+    fn test_parse_guid_invalid_length() {
+        parse_guid("434F695C-EF26-4A12-9EBA-DDEF0097497");
+    }
+
+    #[test]
+    #[should_panic = "Invalid GUID"]
+    fn test_parse_guid_invalid_format() {
+        parse_guid("{434F695C-EF26-4A12-9EBA-DDEF0097497C}");
+    }
+
+    #[test]
+    #[should_panic = "Invalid GUID"]
+    fn test_parse_guid_invalid_character() {
+        parse_guid("434F695C-EF26-4A12-9EBA-DDEF0097497G");
+    }
+
+    #[test]
+    fn test_guid_display_and_from_str() {
+        let guid = Guid::from(ADVANCED_LOGGER_PROTOCOL_GUID_FROM_FIELDS);
+        assert_eq!("434F695C-EF26-4A12-9EBA-DDEF0097497C", guid.to_string());
+        assert_eq!("Guid(434F695C-EF26-4A12-9EBA-DDEF0097497C)", format!("{guid:?}"));
+        assert_eq!(Ok(guid), "434f695c-ef26-4a12-9eba-ddef0097497c".parse());
+        assert_eq!(guid, ADVANCED_LOGGER_PROTOCOL_GUID_FROM_FIELDS);
+        assert_eq!(ADVANCED_LOGGER_PROTOCOL_GUID_FROM_FIELDS, efi::Guid::from(guid));
+    }
+
+    #[test]
+    fn test_guid_from_str_invalid() {
+        assert_eq!(Err(ParseGuidError::InvalidFormat), "434F695C-EF26-4A12-9EBA".parse::<Guid>());
+        assert_eq!(Err(ParseGuidError::InvalidFormat), "434F695C+EF26-4A12-9EBA-DDEF0097497C".parse::<Guid>());
+        assert_eq!(Err(ParseGuidError::InvalidCharacter), "434F695C-EF26-4A12-9EBA-DDEF0097497G".parse::<Guid>());
+    }
+
+    #[test]
+    fn test_guid_ordering() {
+        // Ordered as strings, while the bytes of efi::Guid are little endian.
+        let low = Guid::new(efi::Guid::from_fields(0x0000_00FF, 0, 0, 0, 0, &[0; 6]));
+        let high = Guid::new(efi::Guid::from_fields(0x0000_0100, 0, 0, 0, 0, &[0; 6]));
+        assert!(low < high);
+        assert!(low.as_efi_guid() > high.as_efi_guid());
+
+        let set = BTreeSet::from([high, Guid::new(ZERO), low]);
+        assert_eq!(vec![Guid::new(ZERO), low, high], set.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg(feature = "uefi")]
+    fn test_guid_uefi_conversion() {
+        let guid = Guid::new(ADVANCED_LOGGER_PROTOCOL_GUID_FROM_FIELDS);
+        let uefi_guid = uefi::Guid::from(guid);
+        assert_eq!(uefi::guid!("434F695C-EF26-4A12-9EBA-DDEF0097497C"), uefi_guid);
+        assert_eq!(guid, Guid::from(uefi_guid));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_guid_serde() {
+        let guid = Guid::new(ADVANCED_LOGGER_PROTOCOL_GUID_FROM_FIELDS);
+        let json = serde_json::to_string(&guid).unwrap();
+        assert_eq!(r#""434F695C-EF26-4A12-9EBA-DDEF0097497C""#, json);
+        assert_eq!(guid, serde_json::from_str::<Guid>(&json).unwrap());
+        assert!(serde_json::from_str::<Guid>(r#""434F695C""#).is_err());
+    }
+
     #[test]
     fn test_guid_string_macro() {
         assert_eq!(
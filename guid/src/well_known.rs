@@ -0,0 +1,163 @@
+//! Names of well-known UEFI, PI and Project Mu GUIDs.
+//!
+//! The names are the EDK2 global variable names (`gEfi...Guid`) so they can be searched in the firmware sources.
+
+use r_efi::efi;
+
+use crate::Guid;
+
+macro_rules! well_known_guids {
+    ($($name:literal = $guid:expr,)*) => {
+        /// Table of well-known GUIDs and their names.
+        pub static WELL_KNOWN_GUIDS: &[(efi::Guid, &str)] = &[$(($guid, $name)),*];
+    };
+}
+
+well_known_guids! {
+    // UEFI protocols.
+    "gEfiLoadedImageProtocolGuid" = efi::protocols::loaded_image::PROTOCOL_GUID,
+    "gEfiLoadedImageDevicePathProtocolGuid" = efi::protocols::loaded_image_device_path::PROTOCOL_GUID,
+    "gEfiDevicePathProtocolGuid" = efi::protocols::device_path::PROTOCOL_GUID,
+    "gEfiDevicePathToTextProtocolGuid" = efi::protocols::device_path_to_text::PROTOCOL_GUID,
+    "gEfiDevicePathFromTextProtocolGuid" = efi::protocols::device_path_from_text::PROTOCOL_GUID,
+    "gEfiDevicePathUtilitiesProtocolGuid" = efi::protocols::device_path_utilities::PROTOCOL_GUID,
+    "gEfiDriverBindingProtocolGuid" = efi::protocols::driver_binding::PROTOCOL_GUID,
+    "gEfiComponentNameProtocolGuid" = crate::guid!("107A772C-D5E1-11D4-9A46-0090273FC14D"),
+    "gEfiComponentName2ProtocolGuid" = crate::guid!("6A7A5CFF-E8D9-4F70-BADA-75AB3025CE14"),
+    "gEfiDriverDiagnostics2ProtocolGuid" = efi::protocols::driver_diagnostics2::PROTOCOL_GUID,
+    "gEfiDriverFamilyOverrideProtocolGuid" = efi::protocols::driver_family_override::PROTOCOL_GUID,
+    "gEfiPlatformDriverOverrideProtocolGuid" = efi::protocols::platform_driver_override::PROTOCOL_GUID,
+    "gEfiBusSpecificDriverOverrideProtocolGuid" = efi::protocols::bus_specific_driver_override::PROTOCOL_GUID,
+    "gEfiSimpleTextInProtocolGuid" = efi::protocols::simple_text_input::PROTOCOL_GUID,
+    "gEfiSimpleTextInputExProtocolGuid" = efi::protocols::simple_text_input_ex::PROTOCOL_GUID,
+    "gEfiSimpleTextOutProtocolGuid" = efi::protocols::simple_text_output::PROTOCOL_GUID,
+    "gEfiAbsolutePointerProtocolGuid" = efi::protocols::absolute_pointer::PROTOCOL_GUID,
+    "gEfiGraphicsOutputProtocolGuid" = efi::protocols::graphics_output::PROTOCOL_GUID,
+    "gEfiBlockIoProtocolGuid" = efi::protocols::block_io::PROTOCOL_GUID,
+    "gEfiDiskIoProtocolGuid" = efi::protocols::disk_io::PROTOCOL_GUID,
+    "gEfiDiskIo2ProtocolGuid" = efi::protocols::disk_io2::PROTOCOL_GUID,
+    "gEfiSimpleFileSystemProtocolGuid" = efi::protocols::simple_file_system::PROTOCOL_GUID,
+    "gEfiLoadFileProtocolGuid" = efi::protocols::load_file::PROTOCOL_GUID,
+    "gEfiLoadFile2ProtocolGuid" = efi::protocols::load_file2::PROTOCOL_GUID,
+    "gEfiPciIoProtocolGuid" = efi::protocols::pci_io::PROTOCOL_GUID,
+    "gEfiDecompressProtocolGuid" = efi::protocols::decompress::PROTOCOL_GUID,
+    "gEfiDebugSupportProtocolGuid" = efi::protocols::debug_support::PROTOCOL_GUID,
+    "gEfiDebugPortProtocolGuid" = efi::protocols::debugport::PROTOCOL_GUID,
+    "gEfiMpServiceProtocolGuid" = efi::protocols::mp_services::PROTOCOL_GUID,
+    "gEfiMemoryAttributeProtocolGuid" = efi::protocols::memory_attribute::PROTOCOL_GUID,
+    "gEfiRngProtocolGuid" = efi::protocols::rng::PROTOCOL_GUID,
+    "gEfiTimestampProtocolGuid" = efi::protocols::timestamp::PROTOCOL_GUID,
+    "gEfiSimpleNetworkProtocolGuid" = efi::protocols::simple_network::PROTOCOL_GUID,
+    "gEfiManagedNetworkProtocolGuid" = efi::protocols::managed_network::PROTOCOL_GUID,
+    "gEfiManagedNetworkServiceBindingProtocolGuid" = efi::protocols::managed_network::SERVICE_BINDING_PROTOCOL_GUID,
+    "gEfiIp4ProtocolGuid" = efi::protocols::ip4::PROTOCOL_GUID,
+    "gEfiIp4ServiceBindingProtocolGuid" = efi::protocols::ip4::SERVICE_BINDING_PROTOCOL_GUID,
+    "gEfiIp6ProtocolGuid" = efi::protocols::ip6::PROTOCOL_GUID,
+    "gEfiIp6ServiceBindingProtocolGuid" = efi::protocols::ip6::SERVICE_BINDING_PROTOCOL_GUID,
+    "gEfiTcp4ProtocolGuid" = efi::protocols::tcp4::PROTOCOL_GUID,
+    "gEfiTcp4ServiceBindingProtocolGuid" = efi::protocols::tcp4::SERVICE_BINDING_PROTOCOL_GUID,
+    "gEfiTcp6ProtocolGuid" = efi::protocols::tcp6::PROTOCOL_GUID,
+    "gEfiTcp6ServiceBindingProtocolGuid" = efi::protocols::tcp6::SERVICE_BINDING_PROTOCOL_GUID,
+    "gEfiUdp4ProtocolGuid" = efi::protocols::udp4::PROTOCOL_GUID,
+    "gEfiUdp4ServiceBindingProtocolGuid" = efi::protocols::udp4::SERVICE_BINDING_PROTOCOL_GUID,
+    "gEfiUdp6ProtocolGuid" = efi::protocols::udp6::PROTOCOL_GUID,
+    "gEfiUdp6ServiceBindingProtocolGuid" = efi::protocols::udp6::SERVICE_BINDING_PROTOCOL_GUID,
+    "gEfiHiiDatabaseProtocolGuid" = efi::protocols::hii_database::PROTOCOL_GUID,
+    "gEfiHiiStringProtocolGuid" = efi::protocols::hii_string::PROTOCOL_GUID,
+    "gEfiHiiFontProtocolGuid" = efi::protocols::hii_font::PROTOCOL_GUID,
+    "gEfiHiiFontExProtocolGuid" = efi::protocols::hii_font_ex::PROTOCOL_GUID,
+    "gEfiHiiPackageListProtocolGuid" = efi::protocols::hii_package_list::PROTOCOL_GUID,
+    "gEfiShellProtocolGuid" = efi::protocols::shell::PROTOCOL_GUID,
+    "gEfiShellParametersProtocolGuid" = efi::protocols::shell_parameters::PROTOCOL_GUID,
+    "gEfiShellDynamicCommandProtocolGuid" = efi::protocols::shell_dynamic_command::PROTOCOL_GUID,
+    "gEfiTcg2ProtocolGuid" = crate::guid!("607F766C-7455-42BE-930B-E4D76DB2720F"),
+    "gEfiAcpiTableProtocolGuid" = crate::guid!("FFE06BDD-6107-46A6-7BB2-5A9C7EC5275C"),
+    "gEfiSmbiosProtocolGuid" = crate::guid!("03583FF6-CB36-4940-947E-B9B39F4AFAF7"),
+
+    // PI protocols.
+    "gEfiFirmwareVolume2ProtocolGuid" = crate::guid!("220E73B6-6BDB-4413-8405-B974B108619A"),
+    "gEfiFirmwareVolumeBlockProtocolGuid" = crate::guid!("8F644FA9-E850-4DB1-9CE2-0B44698E8DA4"),
+    "gEfiCpuArchProtocolGuid" = crate::guid!("26BACCB1-6F42-11D4-BCE7-0080C73C8881"),
+    "gEfiTimerArchProtocolGuid" = crate::guid!("26BACCB3-6F42-11D4-BCE7-0080C73C8881"),
+    "gEfiVariableArchProtocolGuid" = crate::guid!("1E5668E2-8481-11D4-BCF1-0080C73C8881"),
+    "gEfiVariableWriteArchProtocolGuid" = crate::guid!("6441F818-6362-4E44-B570-7DBA31DD2453"),
+    "gEfiStatusCodeRuntimeProtocolGuid" = crate::guid!("D2B2B828-0826-48A7-B3DF-983C006024F0"),
+    "gEfiDxeSmmReadyToLockProtocolGuid" = crate::guid!("60FF8964-E906-41D0-AFED-F241E974E08E"),
+
+    // Variable namespaces.
+    "gEfiGlobalVariableGuid" = crate::guid!("8BE4DF61-93CA-11D2-AA0D-00E098032B8C"),
+    "gEfiImageSecurityDatabaseGuid" = crate::guid!("D719B2CB-3D3A-4596-A3BC-DAD00E67656F"),
+    "gEfiHardwareErrorVariableGuid" = efi::HARDWARE_ERROR_VARIABLE_GUID,
+    "gShellVariableGuid" = crate::guid!("158DEF5A-F656-419C-B027-7A3192C079D2"),
+
+    // Event groups.
+    "gEfiEventExitBootServicesGuid" = efi::EVENT_GROUP_EXIT_BOOT_SERVICES,
+    "gEfiEventBeforeExitBootServicesGuid" = efi::EVENT_GROUP_BEFORE_EXIT_BOOT_SERVICES,
+    "gEfiEventVirtualAddressChangeGuid" = efi::EVENT_GROUP_VIRTUAL_ADDRESS_CHANGE,
+    "gEfiEventMemoryMapChangeGuid" = efi::EVENT_GROUP_MEMORY_MAP_CHANGE,
+    "gEfiEventReadyToBootGuid" = efi::EVENT_GROUP_READY_TO_BOOT,
+    "gEfiEventAfterReadyToBootGuid" = efi::EVENT_GROUP_AFTER_READY_TO_BOOT,
+    "gEfiEventResetSystemGuid" = efi::EVENT_GROUP_RESET_SYSTEM,
+    "gEfiEndOfDxeEventGroupGuid" = crate::guid!("02CE967A-DD7E-4FFC-9EE7-810CF0470880"),
+    "gEfiEventDxeDispatchGuid" = crate::guid!("7081E22F-CAC6-4053-9468-675782CF88E5"),
+
+    // Configuration tables.
+    "gEfiAcpi10TableGuid" = efi::ACPI_10_TABLE_GUID,
+    "gEfiAcpi20TableGuid" = efi::ACPI_20_TABLE_GUID,
+    "gEfiSmbiosTableGuid" = efi::SMBIOS_TABLE_GUID,
+    "gEfiSmbios3TableGuid" = efi::SMBIOS3_TABLE_GUID,
+    "gEfiMpsTableGuid" = efi::MPS_TABLE_GUID,
+    "gEfiSalSystemTableGuid" = efi::SAL_SYSTEM_TABLE_GUID,
+    "gFdtTableGuid" = efi::DTB_TABLE_GUID,
+    "gEfiPropertiesTableGuid" = efi::PROPERTIES_TABLE_GUID,
+    "gEfiMemoryAttributesTableGuid" = efi::MEMORY_ATTRIBUTES_TABLE_GUID,
+    "gEfiRtPropertiesTableGuid" = efi::RT_PROPERTIES_TABLE_GUID,
+    "gEfiConformanceProfilesTableGuid" = efi::CONFORMANCE_PROFILES_TABLE_GUID,
+    "gEfiCapsuleReportGuid" = efi::CAPSULE_REPORT_GUID,
+    "gEfiHobListGuid" = crate::guid!("7739F24C-93D7-11D4-9A3A-0090273FC14D"),
+    "gEfiDxeServicesTableGuid" = crate::guid!("05AD34BA-6F02-4214-952E-4DA0398E2BB9"),
+    "gEfiDebugImageInfoTableGuid" = crate::guid!("49152E77-1ADA-4764-B7A2-7AFEFED95E8B"),
+
+    // HOB types.
+    "gEfiHobMemoryAllocStackGuid" = crate::guid!("4ED4BF27-4092-42E9-807D-527B1D00C9BD"),
+    "gEfiHobMemoryAllocBspStoreGuid" = crate::guid!("564B33CD-C92A-4593-90BF-2473E43C6322"),
+    "gEfiHobMemoryAllocModuleGuid" = crate::guid!("F8E21975-0899-4F58-A4BE-5525A9C6D77A"),
+    "gEfiMemoryTypeInformationGuid" = crate::guid!("4C19049F-4137-4DD3-9C10-8B97A83FFDFA"),
+
+    // Project Mu.
+    "gAdvancedLoggerProtocolGuid" = crate::guid!("434F695C-EF26-4A12-9EBA-DDEF0097497C"),
+    "gMsWheaRSCDataTypeGuid" = crate::guid!("91DEEA05-8C0A-4DCD-B91E-F21CA0C68405"),
+}
+
+/// Returns the name of a well-known GUID.
+pub fn lookup_name(guid: &Guid) -> Option<&'static str> {
+    WELL_KNOWN_GUIDS.iter().find(|(well_known, _)| guid == well_known).map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn test_lookup_name() {
+        assert_eq!(
+            Some("gEfiLoadedImageProtocolGuid"),
+            lookup_name(&Guid::new(efi::protocols::loaded_image::PROTOCOL_GUID))
+        );
+        assert_eq!(
+            Some("gEfiGlobalVariableGuid"),
+            lookup_name(&"8BE4DF61-93CA-11D2-AA0D-00E098032B8C".parse().unwrap())
+        );
+        assert_eq!(None, lookup_name(&Guid::new(crate::ZERO)));
+    }
+
+    #[test]
+    fn test_well_known_guids_are_unique() {
+        let guids = WELL_KNOWN_GUIDS.iter().map(|(guid, _)| Guid::new(*guid)).collect::<BTreeSet<_>>();
+        assert_eq!(WELL_KNOWN_GUIDS.len(), guids.len());
+        let names = WELL_KNOWN_GUIDS.iter().map(|(_, name)| *name).collect::<BTreeSet<_>>();
+        assert_eq!(WELL_KNOWN_GUIDS.len(), names.len());
+    }
+}
@@ -0,0 +1,155 @@
+//! Advanced Logger protocol of Project Mu.
+//!
+//! [`AdvancedLoggerSink`] writes the records to the in-memory log of the firmware, with the debug level matching the
+//! level of the record. The firmware tags each entry with the phase it is written in, DXE or runtime, through the
+//! version 2 of the protocol, which is the only one the sink writes to.
+
+use core::{fmt, ops::Deref};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use log::Level;
+use r_efi::efi;
+use status::EfiError;
+
+use crate::sink::{Sink, TryLock};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x434f695c, 0xef26, 0x4a12, 0x9e, 0xba, &[0xdd, 0xef, 0x00, 0x97, 0x49, 0x7c]);
+
+/// `ADVANCED_LOGGER_PROTOCOL_SIGNATURE`, `'LOGP'`.
+pub const SIGNATURE: u32 = u32::from_le_bytes(*b"LOGP");
+/// `ADVANCED_LOGGER_PROTOCOL_VERSION`, the version whose `write` receives the protocol.
+pub const VERSION: u32 = 2;
+
+pub const DEBUG_ERROR: usize = 0x80000000;
+pub const DEBUG_WARN: usize = 0x00000002;
+pub const DEBUG_INFO: usize = 0x00000040;
+pub const DEBUG_VERBOSE: usize = 0x00400000;
+
+pub type ProtocolWrite = extern "efiapi" fn(*mut Protocol, usize, *const u8, usize) -> efi::Status;
+
+/// FFI definition of `ADVANCED_LOGGER_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub signature: u32,
+    pub version: u32,
+    pub write: ProtocolWrite,
+}
+
+/// Advanced Logger protocol.
+pub struct AdvancedLogger;
+
+unsafe impl ProtocolTrait for AdvancedLogger {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for AdvancedLogger {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Debug level of the Advanced Logger for a log level.
+pub const fn debug_level(level: Level) -> usize {
+    match level {
+        Level::Error => DEBUG_ERROR,
+        Level::Warn => DEBUG_WARN,
+        Level::Info => DEBUG_INFO,
+        Level::Debug | Level::Trace => DEBUG_VERBOSE,
+    }
+}
+
+/// Writes the records to the Advanced Logger.
+pub struct AdvancedLoggerSink(TryLock<*mut Protocol>);
+
+//SAFETY: UEFI runs on a single processor and the protocol is only accessed through the lock.
+unsafe impl Send for AdvancedLoggerSink {}
+//SAFETY: See Send above.
+unsafe impl Sync for AdvancedLoggerSink {}
+
+impl AdvancedLoggerSink {
+    /// Writes to the installed Advanced Logger.
+    ///
+    /// # Errors
+    ///
+    /// Returns `INCOMPATIBLE_VERSION` if the protocol does not have the signature or the version of the sink.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        let protocol = boot_services.locate_protocol(&AdvancedLogger, None)?;
+        if protocol.signature != SIGNATURE || protocol.version < VERSION {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        Ok(Self(TryLock::new(protocol)))
+    }
+}
+
+impl Sink for AdvancedLoggerSink {
+    fn write(&self, level: Level, message: fmt::Arguments<'_>) {
+        struct Writer(*mut Protocol, usize);
+
+        impl fmt::Write for Writer {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                //SAFETY: The protocol was located from boot services, the buffer is only read.
+                unsafe { ((*self.0).write)(self.0, self.1, s.as_ptr(), s.len()) };
+                Ok(())
+            }
+        }
+
+        self.0.try_with(|protocol| {
+            let _ = fmt::Write::write_fmt(&mut Writer(*protocol, debug_level(level)), message);
+        });
+    }
+}
+
+impl fmt::Debug for AdvancedLoggerSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AdvancedLoggerSink").finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use std::{boxed::Box, slice, string::String, sync::Mutex, vec::Vec};
+
+    static OUTPUT: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+
+    extern "efiapi" fn write(_: *mut Protocol, level: usize, buffer: *const u8, size: usize) -> efi::Status {
+        let bytes = unsafe { slice::from_raw_parts(buffer, size) };
+        OUTPUT.lock().unwrap().push((level, String::from_utf8(bytes.to_vec()).unwrap()));
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_advanced_logger_sink() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<AdvancedLogger, Protocol>()
+            .returning(|_, _| Ok(Box::leak(Box::new(Protocol { signature: SIGNATURE, version: VERSION, write }))));
+        let sink = AdvancedLoggerSink::locate(&boot_services).unwrap();
+        sink.write(Level::Warn, format_args!("WARN: low memory\n"));
+        sink.write(Level::Trace, format_args!("TRACE: {}\n", "details"));
+
+        let output: String = OUTPUT.lock().unwrap().iter().map(|(_, s)| s.as_str()).collect();
+        assert_eq!("WARN: low memory\nTRACE: details\n", output);
+        assert!(OUTPUT.lock().unwrap().iter().any(|(level, _)| *level == DEBUG_WARN));
+        assert!(OUTPUT.lock().unwrap().iter().any(|(level, _)| *level == DEBUG_VERBOSE));
+        assert_eq!(DEBUG_ERROR, debug_level(Level::Error));
+        assert_eq!(DEBUG_INFO, debug_level(Level::Info));
+    }
+
+    #[test]
+    fn test_advanced_logger_incompatible() {
+        for (signature, version) in [(0, VERSION), (SIGNATURE, 1)] {
+            let mut boot_services = MockBootServices::new();
+            boot_services
+                .expect_locate_protocol::<AdvancedLogger, Protocol>()
+                .returning(move |_, _| Ok(Box::leak(Box::new(Protocol { signature, version, write }))));
+            assert_eq!(efi::Status::INCOMPATIBLE_VERSION, AdvancedLoggerSink::locate(&boot_services).unwrap_err());
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! [`log`] backend for UEFI images.
+//!
+//! A [`Logger`] writes each record to its [`Sink`]s whose level filter allows it, such as the console output, a
+//! serial port, the debug port or the Advanced Logger.
+//!
+//! ```ignore
+//! Logger::new()
+//!     .with_sink(ConOutSink, LevelFilter::Warn)
+//!     .with_sink(SerialSink::locate(entry_point::boot_services())?, LevelFilter::Trace)
+//!     .init()?;
+//! log::info!("Driver loaded");
+//! ```
+//!
+//! [`init_logging`] sets up a default logger, the entry point calls it with `#[entry(logger)]`.
+//!
+//! The `panic_handler` feature provides a `#[panic_handler]` reporting panics through the logger, see [`panic`].
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod advanced_logger;
+pub mod panic;
+pub mod sink;
+
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+pub use advanced_logger::AdvancedLoggerSink;
+pub use panic::{handle_panic, set_panic_behavior, PanicBehavior};
+pub use sink::{ConOutSink, DebugPortSink, SerialSink, Sink};
+
+/// A [`log`] backend writing to several sinks, each with its own level filter.
+#[derive(Default)]
+pub struct Logger {
+    sinks: Vec<(Box<dyn Sink>, LevelFilter)>,
+}
+
+impl Logger {
+    /// A logger without sinks.
+    pub const fn new() -> Self {
+        Self { sinks: Vec::new() }
+    }
+
+    /// Adds a sink receiving the records up to `level`.
+    pub fn with_sink<S: Sink + 'static>(mut self, sink: S, level: LevelFilter) -> Self {
+        self.sinks.push((Box::new(sink), level));
+        self
+    }
+
+    /// The most verbose level of the sinks.
+    pub fn max_level(&self) -> LevelFilter {
+        self.sinks.iter().map(|(_, level)| *level).max().unwrap_or(LevelFilter::Off)
+    }
+
+    /// Sets the logger as the [`log`] backend of the image.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a backend was already set.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let max_level = self.max_level();
+        log::set_logger(Box::leak(Box::new(self)))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        for (sink, level) in &self.sinks {
+            if record.level() <= *level {
+                sink.write(record.level(), format_args!("{}: {}\n", record.level(), record.args()));
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl fmt::Debug for Logger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.sinks.iter().map(|(_, level)| level)).finish()
+    }
+}
+
+/// Sets a default logger as the [`log`] backend of the image.
+///
+/// The logger writes warnings and errors to the console output. When boot services are available, it also writes every
+/// record to the Advanced Logger if it is installed, or else to the first serial port, or else to the console output.
+pub fn init_logging() -> Result<(), SetLoggerError> {
+    let mut con_out_level = LevelFilter::Warn;
+    let mut logger = Logger::new();
+    if entry_point::boot_services_available() {
+        let boot_services = entry_point::boot_services();
+        if let Ok(sink) = AdvancedLoggerSink::locate(boot_services) {
+            logger = logger.with_sink(sink, LevelFilter::Trace);
+        } else if let Ok(sink) = SerialSink::locate(boot_services) {
+            logger = logger.with_sink(sink, LevelFilter::Trace);
+        } else {
+            con_out_level = LevelFilter::Trace;
+        }
+    }
+    logger.with_sink(ConOutSink, con_out_level).init()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use log::Level;
+    use std::{
+        string::String,
+        sync::{Arc, Mutex},
+    };
+
+    struct TestSink(Arc<Mutex<String>>);
+
+    impl Sink for TestSink {
+        fn write(&self, _level: Level, message: fmt::Arguments<'_>) {
+            self.0.lock().unwrap().push_str(&message.to_string());
+        }
+    }
+
+    fn record(logger: &Logger, level: Level, message: &str) {
+        logger.log(&Record::builder().level(level).args(format_args!("{message}")).build());
+    }
+
+    #[test]
+    fn test_logger() {
+        let (errors, all) = (Arc::new(Mutex::new(String::new())), Arc::new(Mutex::new(String::new())));
+        let logger = Logger::new()
+            .with_sink(TestSink(errors.clone()), LevelFilter::Error)
+            .with_sink(TestSink(all.clone()), LevelFilter::Debug);
+        assert_eq!(LevelFilter::Debug, logger.max_level());
+        assert!(logger.enabled(&Metadata::builder().level(Level::Debug).build()));
+        assert!(!logger.enabled(&Metadata::builder().level(Level::Trace).build()));
+
+        record(&logger, Level::Error, "failed");
+        record(&logger, Level::Info, "started");
+        record(&logger, Level::Trace, "ignored");
+        assert_eq!("ERROR: failed\n", *errors.lock().unwrap());
+        assert_eq!("ERROR: failed\nINFO: started\n", *all.lock().unwrap());
+    }
+
+    #[test]
+    fn test_no_sink() {
+        assert_eq!(LevelFilter::Off, Logger::new().max_level());
+    }
+
+    #[test]
+    fn test_init_logging() {
+        init_logging().unwrap();
+        assert_eq!(LevelFilter::Warn, log::max_level());
+        // Without boot services, the console output is not available and the record is dropped.
+        log::error!("not written");
+        assert!(init_logging().is_err());
+    }
+}
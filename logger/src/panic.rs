@@ -0,0 +1,107 @@
+//! Panic handler.
+//!
+//! With the `panic_handler` feature, the crate provides the `#[panic_handler]` of images built for UEFI targets: the
+//! panic message, with its file and line, is logged as an error, then the handler stalls and resets the system as set
+//! by [`set_panic_behavior`]. Without the feature, an image can call [`handle_panic`] from its own handler.
+//!
+//! The message is written to the console output instead when no logger is set, so that panics during early
+//! initialization are not lost.
+
+use core::{
+    fmt::{self, Write},
+    hint,
+    panic::PanicInfo,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use log::LevelFilter;
+use r_efi::efi;
+
+/// What the panic handler does after reporting a panic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PanicBehavior {
+    /// Time to wait before halting or resetting, in microseconds, to leave the message on screen.
+    pub stall_microseconds: usize,
+    /// Issues a warm reset instead of halting.
+    pub warm_reset: bool,
+}
+
+static STALL_MICROSECONDS: AtomicUsize = AtomicUsize::new(0);
+static WARM_RESET: AtomicBool = AtomicBool::new(false);
+
+/// Sets what the panic handler does after reporting a panic, by default it halts immediately.
+pub fn set_panic_behavior(behavior: PanicBehavior) {
+    STALL_MICROSECONDS.store(behavior.stall_microseconds, Ordering::SeqCst);
+    WARM_RESET.store(behavior.warm_reset, Ordering::SeqCst);
+}
+
+/// What the panic handler does after reporting a panic.
+pub fn panic_behavior() -> PanicBehavior {
+    PanicBehavior {
+        stall_microseconds: STALL_MICROSECONDS.load(Ordering::SeqCst),
+        warm_reset: WARM_RESET.load(Ordering::SeqCst),
+    }
+}
+
+/// Reports the panic, then stalls and halts or resets as set by [`set_panic_behavior`].
+pub fn handle_panic(info: &PanicInfo<'_>) -> ! {
+    report(info);
+
+    let behavior = panic_behavior();
+    if behavior.stall_microseconds > 0 && entry_point::boot_services_available() {
+        //SAFETY: The boot services of the system table are valid while they are available.
+        unsafe { ((*entry_point::system_table().boot_services).stall)(behavior.stall_microseconds) };
+    }
+    if behavior.warm_reset && entry_point::is_initialized() {
+        //SAFETY: The runtime services of the system table are valid for the life of the image.
+        unsafe {
+            ((*entry_point::system_table().runtime_services).reset_system)(
+                efi::RESET_WARM,
+                efi::Status::ABORTED,
+                0,
+                ptr::null_mut(),
+            )
+        };
+    }
+    loop {
+        hint::spin_loop();
+    }
+}
+
+/// Logs the message as an error, or writes it to the console output if no logger is set.
+fn report(message: &dyn fmt::Display) {
+    if log::max_level() == LevelFilter::Off {
+        if let Some(mut con_out) = console::con_out() {
+            let _ = writeln!(con_out, "{message}");
+        }
+    } else {
+        log::error!("{message}");
+    }
+}
+
+#[cfg(all(feature = "panic_handler", target_os = "uefi"))]
+#[panic_handler]
+fn panic_handler(info: &PanicInfo<'_>) -> ! {
+    handle_panic(info)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_panic_behavior() {
+        assert_eq!(PanicBehavior::default(), panic_behavior());
+        let behavior = PanicBehavior { stall_microseconds: 5_000_000, warm_reset: true };
+        set_panic_behavior(behavior);
+        assert_eq!(behavior, panic_behavior());
+        set_panic_behavior(PanicBehavior::default());
+    }
+
+    #[test]
+    fn test_report_without_logger() {
+        // Neither a logger nor a console is available, the message is dropped.
+        report(&"panicked at src/main.rs:1:1:\nfailed");
+    }
+}
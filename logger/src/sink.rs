@@ -0,0 +1,229 @@
+//! Destinations of the log records.
+
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use boot_services::BootServices;
+use log::Level;
+use protocols::{debug_port::DebugPort, serial_io::SerialIo};
+use status::EfiError;
+
+/// Destination of the records of a [`Logger`](crate::Logger).
+pub trait Sink: Send + Sync {
+    /// Writes a formatted record, ending with a newline.
+    fn write(&self, level: Level, message: fmt::Arguments<'_>);
+}
+
+/// Gives exclusive access to a value, without waiting.
+///
+/// A record logged from a notification while another record is written to the same sink at a lower TPL can not wait
+/// for it to complete, it is dropped instead.
+pub(crate) struct TryLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+//SAFETY: The value is only accessed by the holder of the lock.
+unsafe impl<T: Send> Sync for TryLock<T> {}
+
+impl<T> TryLock<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Self { locked: AtomicBool::new(false), value: UnsafeCell::new(value) }
+    }
+
+    /// Calls `f` with the value, does nothing if it is already in use.
+    pub(crate) fn try_with(&self, f: impl FnOnce(&mut T)) {
+        if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+            //SAFETY: The lock is held.
+            f(unsafe { &mut *self.value.get() });
+            self.locked.store(false, Ordering::Release);
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+/// Writes the records to the console output of the system table, when boot services are available.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConOutSink;
+
+impl Sink for ConOutSink {
+    fn write(&self, _level: Level, message: fmt::Arguments<'_>) {
+        if let Some(mut con_out) = console::con_out() {
+            let _ = con_out.write_fmt(message);
+        }
+    }
+}
+
+/// Writes the records to a serial port.
+pub struct SerialSink(TryLock<SerialIo>);
+
+//SAFETY: UEFI runs on a single processor and the serial port is only accessed through the lock.
+unsafe impl Send for SerialSink {}
+//SAFETY: See Send above.
+unsafe impl Sync for SerialSink {}
+
+impl SerialSink {
+    /// Writes to the given serial port.
+    pub fn new(serial_io: SerialIo) -> Self {
+        Self(TryLock::new(serial_io))
+    }
+
+    /// Writes to the first serial port.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        SerialIo::locate(boot_services).map(Self::new)
+    }
+}
+
+impl Sink for SerialSink {
+    fn write(&self, _level: Level, message: fmt::Arguments<'_>) {
+        self.0.try_with(|serial_io| {
+            let _ = serial_io.write_fmt(message);
+        });
+    }
+}
+
+impl fmt::Debug for SerialSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SerialSink").finish()
+    }
+}
+
+/// Writes the records to the debug port, for the platforms where it replaces the serial port.
+///
+/// The input of the debug host can be polled between the records with [`DebugPortSink::poll_read`].
+pub struct DebugPortSink(TryLock<DebugPort>);
+
+//SAFETY: UEFI runs on a single processor and the debug port is only accessed through the lock.
+unsafe impl Send for DebugPortSink {}
+//SAFETY: See Send above.
+unsafe impl Sync for DebugPortSink {}
+
+impl DebugPortSink {
+    /// Default timeout of a write, in microseconds.
+    pub const DEFAULT_TIMEOUT: u32 = DebugPort::DEFAULT_TIMEOUT;
+
+    /// Writes to the given debug port.
+    pub fn new(debug_port: DebugPort) -> Self {
+        Self(TryLock::new(debug_port))
+    }
+
+    /// Writes to the debug port.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        DebugPort::locate(boot_services).map(Self::new)
+    }
+
+    /// Sets the timeout of a write, in microseconds.
+    pub fn with_timeout(self, timeout: u32) -> Self {
+        Self::new(self.0.into_inner().with_timeout(timeout))
+    }
+
+    /// Reads the bytes waiting on the debug port without waiting, returns 0 if there are none or a record is being
+    /// written.
+    pub fn poll_read(&self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        let mut result = Ok(0);
+        self.0.try_with(|debug_port| result = debug_port.poll_read(buffer));
+        result
+    }
+}
+
+impl Sink for DebugPortSink {
+    fn write(&self, _level: Level, message: fmt::Arguments<'_>) {
+        self.0.try_with(|debug_port| {
+            let _ = debug_port.write_fmt(message);
+        });
+    }
+}
+
+impl fmt::Debug for DebugPortSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_tuple("DebugPortSink");
+        self.0.try_with(|debug_port| {
+            debug.field(debug_port);
+        });
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use core::{ffi::c_void, slice};
+    use protocols::debug_port;
+    use r_efi::efi;
+    use std::{boxed::Box, string::String, sync::Mutex, vec::Vec};
+
+    static DEBUG_PORT_OUTPUT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+    extern "efiapi" fn debug_port_write(
+        _: *mut debug_port::Protocol,
+        timeout: u32,
+        size: *mut usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        assert_eq!(1000, timeout);
+        let bytes = unsafe { slice::from_raw_parts(buffer as *const u8, *size) };
+        DEBUG_PORT_OUTPUT.lock().unwrap().extend_from_slice(bytes);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn debug_port_reset(_: *mut debug_port::Protocol) -> efi::Status {
+        unreachable!()
+    }
+
+    extern "efiapi" fn debug_port_read(
+        _: *mut debug_port::Protocol,
+        _: u32,
+        size: *mut usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        unsafe {
+            *(buffer as *mut u8) = b'q';
+            *size = 1;
+        }
+        efi::Status::TIMEOUT
+    }
+
+    extern "efiapi" fn debug_port_poll(_: *mut debug_port::Protocol) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_try_lock() {
+        let lock = TryLock::new(0);
+        lock.try_with(|value| {
+            *value += 1;
+            // Already in use, the nested access is skipped.
+            lock.try_with(|_| unreachable!());
+        });
+        lock.try_with(|value| assert_eq!(1, *value));
+    }
+
+    #[test]
+    fn test_debug_port_sink() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_locate_protocol::<debug_port::DebugPortProtocol, debug_port::Protocol>().returning(
+            |_, _| {
+                Ok(Box::leak(Box::new(debug_port::Protocol {
+                    reset: debug_port_reset,
+                    write: debug_port_write,
+                    read: debug_port_read,
+                    poll: debug_port_poll,
+                })))
+            },
+        );
+        let sink = DebugPortSink::locate(&boot_services).unwrap().with_timeout(1000);
+        sink.write(Level::Info, format_args!("INFO: {}\n", 42));
+        assert_eq!("INFO: 42\r\n", String::from_utf8(DEBUG_PORT_OUTPUT.lock().unwrap().clone()).unwrap());
+
+        let mut input = [0; 4];
+        assert_eq!(1, sink.poll_read(&mut input).unwrap());
+        assert_eq!(b'q', input[0]);
+    }
+}
@@ -0,0 +1,330 @@
+//! ACPI Table protocol.
+//!
+//! [`AcpiTable`] adds tables to the ACPI tables of the system, their checksum is computed by the protocol.
+//! [`TableRegistry`] keeps the keys of the tables it installs, to remove them later by key or signature, and
+//! [`builder::TableBuilder`] constructs tables from templates.
+//!
+//! [UEFI Spec Documentation: 20.2. EFI ACPI Table Protocol](https://uefi.org/specs/UEFI/2.10/20_Protocols_ACPI.html#efi-acpi-table-protocol)
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, fmt, ops::Deref};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub mod builder;
+pub mod tables;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xffe06bdd, 0x6107, 0x46a6, 0x7b, 0xb2, &[0x5a, 0x9c, 0x7e, 0xc5, 0x27, 0x5c]);
+
+pub type ProtocolInstallAcpiTable = extern "efiapi" fn(*mut Protocol, *mut c_void, usize, *mut usize) -> efi::Status;
+
+pub type ProtocolUninstallAcpiTable = extern "efiapi" fn(*mut Protocol, usize) -> efi::Status;
+
+/// FFI definition of `EFI_ACPI_TABLE_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub install_acpi_table: ProtocolInstallAcpiTable,
+    pub uninstall_acpi_table: ProtocolUninstallAcpiTable,
+}
+
+/// ACPI Table protocol.
+pub struct AcpiTableProtocol;
+
+unsafe impl ProtocolTrait for AcpiTableProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for AcpiTableProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// FFI definition of `EFI_ACPI_DESCRIPTION_HEADER`, the header of every ACPI table.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct DescriptionHeader {
+    pub signature: [u8; 4],
+    /// Size of the whole table, in bytes.
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: [u8; 4],
+    pub creator_revision: u32,
+}
+
+impl DescriptionHeader {
+    /// The header of a table of `length` bytes, with blank OEM and creator information.
+    pub const fn new(signature: [u8; 4], length: u32, revision: u8) -> Self {
+        Self {
+            signature,
+            length,
+            revision,
+            checksum: 0,
+            oem_id: *b"      ",
+            oem_table_id: *b"        ",
+            oem_revision: 0,
+            creator_id: *b"    ",
+            creator_revision: 0,
+        }
+    }
+}
+
+/// Sets the checksum of `table`, so that its bytes sum to zero.
+pub fn fix_checksum(table: &mut [u8]) -> Result<(), EfiError> {
+    if table.len() < core::mem::size_of::<DescriptionHeader>() {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    // The checksum is the byte after the signature, the length and the revision.
+    table[9] = 0;
+    table[9] = 0u8.wrapping_sub(table.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)));
+    Ok(())
+}
+
+/// Identifier of an installed table, to uninstall it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TableKey(pub usize);
+
+/// Typed access to the ACPI Table protocol.
+///
+/// ```ignore
+/// let mut acpi_table = AcpiTable::locate(&boot_services)?;
+/// let key = acpi_table.install_table(&ssdt)?;
+/// ```
+pub struct AcpiTable(&'static mut Protocol);
+
+impl AcpiTable {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&AcpiTableProtocol, None).map(Self)
+    }
+
+    /// Installs a copy of `table`, which starts with a [`DescriptionHeader`] of its length.
+    pub fn install_table(&mut self, table: &[u8]) -> Result<TableKey, EfiError> {
+        if table.len() < core::mem::size_of::<DescriptionHeader>() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let mut key = 0;
+        // The table is only read by the protocol, which installs a copy.
+        match (self.0.install_acpi_table)(self.0, table.as_ptr() as *mut c_void, table.len(), &mut key) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(TableKey(key)),
+        }
+    }
+
+    /// Removes a table installed by [`AcpiTable::install_table`].
+    pub fn uninstall_table(&mut self, key: TableKey) -> Result<(), EfiError> {
+        match (self.0.uninstall_acpi_table)(self.0, key.0) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A table installed through a [`TableRegistry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstalledTable {
+    pub signature: [u8; 4],
+    pub oem_table_id: [u8; 8],
+    pub key: TableKey,
+}
+
+/// Tables installed by a driver, to uninstall them when they are no longer valid, e.g. when a device is removed.
+///
+/// ```ignore
+/// let mut registry = TableRegistry::new(AcpiTable::locate(&boot_services)?);
+/// registry.install(&ssdt)?;
+/// // ...
+/// registry.uninstall_all()?;
+/// ```
+#[derive(Debug)]
+pub struct TableRegistry {
+    acpi_table: AcpiTable,
+    tables: Vec<InstalledTable>,
+}
+
+impl TableRegistry {
+    pub fn new(acpi_table: AcpiTable) -> Self {
+        Self { acpi_table, tables: Vec::new() }
+    }
+
+    /// Installs a copy of `table` and records its key.
+    pub fn install(&mut self, table: &[u8]) -> Result<TableKey, EfiError> {
+        let key = self.acpi_table.install_table(table)?;
+        // The table is at least as long as its header, checked by the installation.
+        let (header, _) = DescriptionHeader::read_from_prefix(table).unwrap();
+        self.tables.push(InstalledTable { signature: header.signature, oem_table_id: header.oem_table_id, key });
+        Ok(key)
+    }
+
+    /// Uninstalls a table installed by [`TableRegistry::install`], returns `NOT_FOUND` for other tables.
+    pub fn uninstall(&mut self, key: TableKey) -> Result<(), EfiError> {
+        let index = self.tables.iter().position(|table| table.key == key).ok_or(efi::Status::NOT_FOUND)?;
+        self.acpi_table.uninstall_table(key)?;
+        self.tables.remove(index);
+        Ok(())
+    }
+
+    /// Uninstalls the tables of `signature`, and of `oem_table_id` if given, returns how many were uninstalled.
+    pub fn uninstall_matching(
+        &mut self,
+        signature: &[u8; 4],
+        oem_table_id: Option<&[u8; 8]>,
+    ) -> Result<usize, EfiError> {
+        let keys = self
+            .tables
+            .iter()
+            .filter(|table| table.signature == *signature && oem_table_id.iter().all(|id| table.oem_table_id == **id))
+            .map(|table| table.key)
+            .collect::<Vec<_>>();
+        for key in &keys {
+            self.uninstall(*key)?;
+        }
+        Ok(keys.len())
+    }
+
+    /// Uninstalls all the tables, the ones that fail to uninstall stay in the registry.
+    pub fn uninstall_all(&mut self) -> Result<(), EfiError> {
+        let mut result = Ok(());
+        for key in self.tables.iter().map(|table| table.key).collect::<Vec<_>>() {
+            if let Err(status) = self.uninstall(key) {
+                result = result.and(Err(status));
+            }
+        }
+        result
+    }
+
+    /// The tables currently installed, in the order of their installation.
+    pub fn tables(&self) -> &[InstalledTable] {
+        &self.tables
+    }
+}
+
+impl From<&'static mut Protocol> for AcpiTable {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for AcpiTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcpiTable").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec::Vec};
+    use boot_services::MockBootServices;
+    use core::slice;
+
+    /// Firmware keeping the tables it installs, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    pub(crate) struct TestAcpi {
+        protocol: Protocol,
+        pub tables: Vec<Option<Vec<u8>>>,
+    }
+
+    fn test_acpi<'a>(this: *mut Protocol) -> &'a mut TestAcpi {
+        unsafe { &mut *(this as *mut TestAcpi) }
+    }
+
+    extern "efiapi" fn install_acpi_table(
+        this: *mut Protocol,
+        table: *mut c_void,
+        size: usize,
+        key: *mut usize,
+    ) -> efi::Status {
+        let acpi = test_acpi(this);
+        let table = unsafe { slice::from_raw_parts(table as *const u8, size) };
+        if u32::from_le_bytes(table[4..8].try_into().unwrap()) as usize != size {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        acpi.tables.push(Some(table.to_vec()));
+        unsafe { *key = acpi.tables.len() };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn uninstall_acpi_table(this: *mut Protocol, key: usize) -> efi::Status {
+        match test_acpi(this).tables.get_mut(key.wrapping_sub(1)).and_then(Option::take) {
+            Some(_) => efi::Status::SUCCESS,
+            None => efi::Status::NOT_FOUND,
+        }
+    }
+
+    /// Boot services locating an ACPI Table protocol without tables.
+    pub(crate) fn acpi_boot_services() -> (MockBootServices, &'static TestAcpi) {
+        let acpi = Box::leak(Box::new(TestAcpi {
+            protocol: Protocol { install_acpi_table, uninstall_acpi_table },
+            tables: Vec::new(),
+        }));
+        let acpi_ptr = acpi as *mut TestAcpi as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<AcpiTableProtocol, Protocol>()
+            .returning(move |_, _| Ok(unsafe { &mut (*(acpi_ptr as *mut TestAcpi)).protocol }));
+        (boot_services, unsafe { &*(acpi_ptr as *const TestAcpi) })
+    }
+
+    #[test]
+    fn test_install_table() {
+        let (boot_services, acpi) = acpi_boot_services();
+        let mut acpi_table = AcpiTable::locate(&boot_services).unwrap();
+        let header = DescriptionHeader::new(*b"SSDT", 36, 2);
+        let table = unsafe { slice::from_raw_parts(&header as *const _ as *const u8, 36) };
+        let key = acpi_table.install_table(table).unwrap();
+        assert_eq!(Some(table), acpi.tables[0].as_deref());
+
+        assert_eq!(efi::Status::INVALID_PARAMETER, acpi_table.install_table(&table[..35]).unwrap_err());
+        acpi_table.uninstall_table(key).unwrap();
+        assert_eq!(None, acpi.tables[0]);
+        assert_eq!(efi::Status::NOT_FOUND, acpi_table.uninstall_table(key).unwrap_err());
+    }
+
+    #[test]
+    fn test_fix_checksum() {
+        let header = DescriptionHeader::new(*b"SSDT", 36, 2);
+        let mut table = header.as_bytes().to_vec();
+        table[9] = 0x55;
+        fix_checksum(&mut table).unwrap();
+        assert_eq!(0, table.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)));
+        assert_eq!(efi::Status::INVALID_PARAMETER, fix_checksum(&mut table[..35]).unwrap_err());
+    }
+
+    #[test]
+    fn test_table_registry() {
+        let (boot_services, acpi) = acpi_boot_services();
+        let mut registry = TableRegistry::new(AcpiTable::locate(&boot_services).unwrap());
+        let ssdt =
+            |oem_table_id: &[u8; 8]| builder::TableBuilder::new(*b"SSDT", 2).with_oem_table_id(oem_table_id).build();
+        let first = registry.install(&ssdt(b"SENSORS ")).unwrap();
+        registry.install(&ssdt(b"EC      ")).unwrap();
+        registry.install(&ssdt(b"SENSORS ")).unwrap();
+        let hpet = registry.install(&builder::TableBuilder::new(*b"HPET", 1).append(&[0; 20]).build()).unwrap();
+        assert_eq!(4, registry.tables().len());
+        assert_eq!((*b"HPET", hpet), (registry.tables()[3].signature, registry.tables()[3].key));
+
+        registry.uninstall(first).unwrap();
+        assert_eq!(efi::Status::NOT_FOUND, registry.uninstall(first).unwrap_err());
+        assert_eq!(1, registry.uninstall_matching(b"SSDT", Some(b"SENSORS ")).unwrap());
+        assert_eq!(0, registry.uninstall_matching(b"SSDT", Some(b"SENSORS ")).unwrap());
+        assert_eq!(1, registry.uninstall_matching(b"SSDT", None).unwrap());
+        assert_eq!(1, registry.tables().len());
+
+        registry.uninstall_all().unwrap();
+        assert!(registry.tables().is_empty());
+        assert!(acpi.tables.iter().all(Option::is_none));
+    }
+}
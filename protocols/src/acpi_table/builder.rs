@@ -0,0 +1,257 @@
+//! Construction of ACPI tables.
+//!
+//! [`TableBuilder`] starts from a header or from a template, usually an SSDT compiled with the firmware, patches it
+//! and sets its length and checksum:
+//!
+//! ```ignore
+//! let ssdt = TableBuilder::from_template(include_bytes!("Ssdt.aml"))?
+//!     .with_oem_table_id(b"SENSORS ")
+//!     .patch_aml_name(b"TBAR", bar_address)?
+//!     .build();
+//! registry.install(&ssdt)?;
+//! ```
+//!
+//! [ACPI Spec Documentation: 20.2.5.1. Namespace Modifier Objects Encoding](https://uefi.org/specs/ACPI/6.5/20_AML_Specification.html#namespace-modifier-objects-encoding)
+
+use alloc::vec::Vec;
+use core::mem;
+
+use r_efi::efi;
+use status::EfiError;
+use zerocopy::{FromBytes, IntoBytes};
+
+use super::{fix_checksum, DescriptionHeader};
+
+const HEADER_SIZE: usize = mem::size_of::<DescriptionHeader>();
+
+const AML_NAME_OP: u8 = 0x08;
+const AML_BYTE_PREFIX: u8 = 0x0A;
+const AML_WORD_PREFIX: u8 = 0x0B;
+const AML_DWORD_PREFIX: u8 = 0x0C;
+const AML_QWORD_PREFIX: u8 = 0x0E;
+
+/// Builder of an ACPI table, whose length and checksum are set by [`TableBuilder::build`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableBuilder {
+    bytes: Vec<u8>,
+}
+
+impl TableBuilder {
+    /// A table with only a header, with blank OEM and creator information.
+    pub fn new(signature: [u8; 4], revision: u8) -> Self {
+        Self { bytes: DescriptionHeader::new(signature, HEADER_SIZE as u32, revision).as_bytes().to_vec() }
+    }
+
+    /// A table copied from `template`, as long as its header declares.
+    pub fn from_template(template: &[u8]) -> Result<Self, EfiError> {
+        let length = template.get(4..8).map(|length| u32::from_le_bytes(length.try_into().unwrap()) as usize);
+        match length {
+            Some(length) if length >= HEADER_SIZE && length <= template.len() => {
+                Ok(Self { bytes: template[..length].to_vec() })
+            }
+            _ => Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+    }
+
+    fn header_mut(&mut self) -> &mut DescriptionHeader {
+        // The table is at least as long as its header.
+        DescriptionHeader::mut_from_prefix(self.bytes.as_mut_slice()).map(|(header, _)| header).unwrap()
+    }
+
+    pub fn with_revision(mut self, revision: u8) -> Self {
+        self.header_mut().revision = revision;
+        self
+    }
+
+    pub fn with_oem_id(mut self, oem_id: &[u8; 6]) -> Self {
+        self.header_mut().oem_id = *oem_id;
+        self
+    }
+
+    pub fn with_oem_table_id(mut self, oem_table_id: &[u8; 8]) -> Self {
+        self.header_mut().oem_table_id = *oem_table_id;
+        self
+    }
+
+    pub fn with_oem_revision(mut self, oem_revision: u32) -> Self {
+        self.header_mut().oem_revision = oem_revision;
+        self
+    }
+
+    pub fn with_creator(mut self, creator_id: &[u8; 4], creator_revision: u32) -> Self {
+        let header = self.header_mut();
+        header.creator_id = *creator_id;
+        header.creator_revision = creator_revision;
+        self
+    }
+
+    /// Appends `data` to the content of the table.
+    pub fn append(mut self, data: &[u8]) -> Self {
+        self.bytes.extend_from_slice(data);
+        self
+    }
+
+    /// Size of the table, including its header.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Offset of the first occurrence of `pattern` in the content of the table.
+    pub fn find(&self, pattern: &[u8]) -> Option<usize> {
+        if pattern.is_empty() {
+            return None;
+        }
+        self.bytes[HEADER_SIZE..].windows(pattern.len()).position(|window| window == pattern).map(|i| i + HEADER_SIZE)
+    }
+
+    /// Overwrites the bytes at `offset` of the table with `bytes`.
+    pub fn patch(mut self, offset: usize, bytes: &[u8]) -> Result<Self, EfiError> {
+        match offset.checked_add(bytes.len()).and_then(|end| self.bytes.get_mut(offset..end)) {
+            Some(target) => target.copy_from_slice(bytes),
+            None => return Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+        Ok(self)
+    }
+
+    pub fn patch_u8(self, offset: usize, value: u8) -> Result<Self, EfiError> {
+        self.patch(offset, &[value])
+    }
+
+    pub fn patch_u16(self, offset: usize, value: u16) -> Result<Self, EfiError> {
+        self.patch(offset, &value.to_le_bytes())
+    }
+
+    pub fn patch_u32(self, offset: usize, value: u32) -> Result<Self, EfiError> {
+        self.patch(offset, &value.to_le_bytes())
+    }
+
+    pub fn patch_u64(self, offset: usize, value: u64) -> Result<Self, EfiError> {
+        self.patch(offset, &value.to_le_bytes())
+    }
+
+    /// Overwrites the first occurrence of `marker`, a placeholder in the template, with `bytes` of the same length.
+    ///
+    /// Returns `NOT_FOUND` if there is no `marker` in the table.
+    pub fn patch_marker(self, marker: &[u8], bytes: &[u8]) -> Result<Self, EfiError> {
+        if marker.len() != bytes.len() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let offset = self.find(marker).ok_or(efi::Status::NOT_FOUND)?;
+        self.patch(offset, bytes)
+    }
+
+    /// Sets the value of the AML integer object `name`, declared by `Name (NAME, 0x...)` in the template.
+    ///
+    /// The value keeps the width of the integer in the template, `INVALID_PARAMETER` is returned if it does not fit or
+    /// if the template declares it as a constant without width, like `Zero` or `One`.
+    pub fn patch_aml_name(self, name: &[u8; 4], value: u64) -> Result<Self, EfiError> {
+        let mut pattern = [AML_NAME_OP; 5];
+        pattern[1..].copy_from_slice(name);
+        let offset = self.find(&pattern).ok_or(efi::Status::NOT_FOUND)? + pattern.len();
+        let width = match self.bytes.get(offset) {
+            Some(&AML_BYTE_PREFIX) => 1,
+            Some(&AML_WORD_PREFIX) => 2,
+            Some(&AML_DWORD_PREFIX) => 4,
+            Some(&AML_QWORD_PREFIX) => 8,
+            _ => return Err(efi::Status::INVALID_PARAMETER.into()),
+        };
+        if width < 8 && value >> (width * 8) != 0 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        self.patch(offset + 1, &value.to_le_bytes()[..width])
+    }
+
+    /// The table with the length and checksum of its header set.
+    pub fn build(mut self) -> Vec<u8> {
+        self.header_mut().length = self.bytes.len() as u32;
+        // The table is at least as long as its header.
+        fix_checksum(&mut self.bytes).unwrap();
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::acpi_table::tables::Table;
+
+    /// `DefinitionBlock` of an SSDT with `Name (TBAR, 0xFED00000)`, `Name (TCNT, 0x02)` and `Name (TZRO, Zero)`.
+    fn ssdt() -> Vec<u8> {
+        let aml = [
+            0x08, b'T', b'B', b'A', b'R', 0x0C, 0x00, 0x00, 0xD0, 0xFE, 0x08, b'T', b'C', b'N', b'T', 0x0A, 0x02, 0x08,
+            b'T', b'Z', b'R', b'O', 0x00, b'$', b'M', b'A', b'C', b'$',
+        ];
+        let mut template = TableBuilder::new(*b"SSDT", 2).append(&aml).build();
+        // A template longer than its header declares, as in a section padded by the build.
+        template.extend_from_slice(&[0; 4]);
+        template
+    }
+
+    #[test]
+    fn test_build() {
+        let table = TableBuilder::new(*b"OEM1", 1)
+            .with_oem_id(b"OEMID ")
+            .with_oem_table_id(b"TABLEID ")
+            .with_oem_revision(3)
+            .with_creator(b"RUST", 4)
+            .with_revision(2)
+            .append(&[1, 2, 3])
+            .build();
+        let table = Table::from_bytes(&table).unwrap();
+        assert!(table.is_checksum_valid());
+        let header = table.header();
+        assert_eq!(
+            (39, 2, 3, 4),
+            ({ header.length }, header.revision, { header.oem_revision }, { header.creator_revision })
+        );
+        assert_eq!((*b"OEMID ", *b"TABLEID ", *b"RUST"), (header.oem_id, header.oem_table_id, header.creator_id));
+        assert_eq!(&[1, 2, 3], table.data());
+    }
+
+    #[test]
+    fn test_patch_template() {
+        let template = ssdt();
+        let builder = TableBuilder::from_template(&template).unwrap();
+        assert_eq!(template.len() - 4, builder.len());
+        let table = builder
+            .patch_aml_name(b"TBAR", 0xFEB0_0000)
+            .unwrap()
+            .patch_aml_name(b"TCNT", 4)
+            .unwrap()
+            .patch_marker(b"$MAC$", b"\x01\x02\x03\x04\x05")
+            .unwrap()
+            .build();
+        let table = Table::from_bytes(&table).unwrap();
+        assert!(table.is_checksum_valid());
+        assert_eq!([0x0C, 0x00, 0x00, 0xB0, 0xFE], table.data()[5..10]);
+        assert_eq!([0x0A, 0x04], table.data()[15..17]);
+        assert_eq!([1, 2, 3, 4, 5], table.data()[23..28]);
+
+        let builder = TableBuilder::from_template(&template).unwrap();
+        let offset = builder.find(b"TCNT").unwrap();
+        let table = builder.patch_u8(offset + 5, 7).unwrap().patch_u32(offset - 4, 0x1234).unwrap().build();
+        assert_eq!([0x34, 0x12, 0, 0, b'T', b'C', b'N', b'T', 0x0A, 7], table[offset - 4..offset + 6]);
+    }
+
+    #[test]
+    fn test_invalid_patch() {
+        let builder = TableBuilder::from_template(&ssdt()).unwrap();
+        let len = builder.len();
+        assert_eq!(efi::Status::INVALID_PARAMETER, builder.clone().patch_u16(len - 1, 0).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, builder.clone().patch(usize::MAX, &[0]).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, builder.clone().patch_aml_name(b"TCNT", 0x100).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, builder.clone().patch_aml_name(b"TZRO", 1).unwrap_err());
+        assert_eq!(efi::Status::NOT_FOUND, builder.clone().patch_aml_name(b"NONE", 1).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, builder.clone().patch_marker(b"$MAC$", &[0]).unwrap_err());
+        assert_eq!(efi::Status::NOT_FOUND, builder.patch_marker(b"$IP$", b"1234").unwrap_err());
+
+        assert_eq!(efi::Status::INVALID_PARAMETER, TableBuilder::from_template(&[0; 35]).unwrap_err());
+        let mut template = ssdt();
+        template[4] = 0xFF;
+        assert_eq!(efi::Status::INVALID_PARAMETER, TableBuilder::from_template(&template).unwrap_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Walk of the ACPI tables published by the firmware.
+//!
+//! [`AcpiTables`] validates the RSDP and the XSDT it points to, then finds the tables by signature, for a tool to read
+//! the tables without an ACPI crate:
+//!
+//! ```ignore
+//! let tables = AcpiTables::from_system_table(entry_point::system_table())?;
+//! let mcfg = tables.find_table(b"MCFG")?;
+//! for allocation in mcfg.mcfg_allocations().unwrap() {
+//!     // ...
+//! }
+//! ```
+//!
+//! [ACPI Spec Documentation: 5.2. ACPI System Description Tables](https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#acpi-system-description-tables)
+
+use core::{mem, slice};
+
+use boot_services::configuration_table::{self, Acpi20, Rsdp};
+use r_efi::efi;
+use status::EfiError;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use super::DescriptionHeader;
+
+const HEADER_SIZE: usize = mem::size_of::<DescriptionHeader>();
+
+/// Offset of the 32-bit address of the DSDT in the FADT.
+const FADT_DSDT_OFFSET: usize = 40;
+/// Offset of the 64-bit address of the DSDT in the FADT, since ACPI 2.0.
+const FADT_X_DSDT_OFFSET: usize = 140;
+
+/// An ACPI table, its header followed by its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Table<'a>(&'a [u8]);
+
+impl<'a> Table<'a> {
+    /// The table at the start of `bytes`, as long as its header declares.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, EfiError> {
+        let length = bytes.get(4..8).map(|length| u32::from_le_bytes(length.try_into().unwrap()) as usize);
+        match length {
+            Some(length) if length >= HEADER_SIZE && length <= bytes.len() => Ok(Self(&bytes[..length])),
+            _ => Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+    }
+
+    /// The table at `address`.
+    ///
+    /// # Safety
+    ///
+    /// `address` must be the address of a table as long as its header declares, which stays valid for `'a`.
+    unsafe fn from_address(address: u64) -> Result<Self, EfiError> {
+        if address == 0 {
+            return Err(efi::Status::NOT_FOUND.into());
+        }
+        let header = (address as *const DescriptionHeader).read_unaligned();
+        let length = header.length as usize;
+        if length < HEADER_SIZE {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        Ok(Self(slice::from_raw_parts(address as *const u8, length)))
+    }
+
+    pub fn header(&self) -> DescriptionHeader {
+        // The table is at least as long as its header.
+        DescriptionHeader::read_from_prefix(self.0).map(|(header, _)| header).unwrap()
+    }
+
+    pub fn signature(&self) -> [u8; 4] {
+        self.header().signature
+    }
+
+    /// The whole table, including its header.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// The content of the table, after its header.
+    pub fn data(&self) -> &'a [u8] {
+        &self.0[HEADER_SIZE..]
+    }
+
+    /// Returns true if the bytes of the table sum to zero.
+    pub fn is_checksum_valid(&self) -> bool {
+        self.0.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+    }
+
+    /// The configuration space allocations of an MCFG table, `None` for other tables.
+    pub fn mcfg_allocations(&self) -> Option<impl Iterator<Item = McfgAllocation> + 'a> {
+        if self.signature() != *b"MCFG" {
+            return None;
+        }
+        // The allocations follow 8 reserved bytes.
+        let allocations = self.data().get(8..).unwrap_or_default();
+        let count = allocations.len() / mem::size_of::<McfgAllocation>();
+        let (allocations, _) = <[McfgAllocation]>::ref_from_prefix_with_elems(allocations, count).ok()?;
+        Some(allocations.iter().copied())
+    }
+
+    /// The content of a MADT, `None` for other tables or if the table is too short.
+    pub fn madt(&self) -> Option<Madt<'a>> {
+        let data = self.data();
+        if self.signature() != *b"APIC" || data.len() < 8 {
+            return None;
+        }
+        Some(Madt {
+            local_interrupt_controller_address: u32::from_le_bytes(data[0..4].try_into().unwrap()),
+            flags: u32::from_le_bytes(data[4..8].try_into().unwrap()),
+            structures: &data[8..],
+        })
+    }
+}
+
+/// Allocation of the configuration space of PCI segment group buses, in the MCFG table.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct McfgAllocation {
+    pub base_address: u64,
+    pub segment_group: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+    pub reserved: u32,
+}
+
+/// Content of the Multiple APIC Description Table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Madt<'a> {
+    pub local_interrupt_controller_address: u32,
+    pub flags: u32,
+    structures: &'a [u8],
+}
+
+impl<'a> Madt<'a> {
+    /// The interrupt controller structures of the table, as `(type, content after the type and length)`.
+    ///
+    /// The iteration stops at the first structure that does not fit in the table.
+    pub fn interrupt_controllers(&self) -> impl Iterator<Item = (u8, &'a [u8])> + 'a {
+        let mut structures = self.structures;
+        core::iter::from_fn(move || {
+            let length = *structures.get(1)? as usize;
+            if length < 2 || length > structures.len() {
+                return None;
+            }
+            let (structure, rest) = structures.split_at(length);
+            structures = rest;
+            Some((structure[0], &structure[2..]))
+        })
+    }
+}
+
+/// The tables referenced by the XSDT of the firmware.
+#[derive(Debug, Clone, Copy)]
+pub struct AcpiTables<'a> {
+    xsdt: Table<'a>,
+}
+
+impl AcpiTables<'static> {
+    /// The tables of the RSDP installed in the configuration tables of the system table.
+    pub fn from_system_table(system_table: &efi::SystemTable) -> Result<Self, EfiError> {
+        let rsdp = configuration_table::find::<Acpi20>(system_table)?;
+        //SAFETY: The RSDP installed by the firmware points to the tables of the firmware.
+        unsafe { Self::from_rsdp(rsdp) }
+    }
+}
+
+impl<'a> AcpiTables<'a> {
+    /// The tables of the XSDT that `rsdp` points to, once the XSDT is validated.
+    ///
+    /// # Safety
+    ///
+    /// The XSDT and the tables it references must be as long as their headers declare and stay valid for `'a`.
+    pub unsafe fn from_rsdp(rsdp: &Rsdp) -> Result<Self, EfiError> {
+        let xsdt = Table::from_address(rsdp.xsdt_address)?;
+        if xsdt.signature() != *b"XSDT" {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        if !xsdt.is_checksum_valid() {
+            return Err(efi::Status::CRC_ERROR.into());
+        }
+        Ok(Self { xsdt })
+    }
+
+    pub fn xsdt(&self) -> Table<'a> {
+        self.xsdt
+    }
+
+    /// Iterates over the tables referenced by the XSDT, without checking their checksum.
+    ///
+    /// The null and malformed entries are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = Table<'a>> + 'a {
+        self.xsdt.data().chunks_exact(mem::size_of::<u64>()).filter_map(|address| {
+            let address = u64::from_le_bytes(address.try_into().unwrap());
+            //SAFETY: The tables referenced by the XSDT are valid for 'a, as required to create Self.
+            unsafe { Table::from_address(address) }.ok()
+        })
+    }
+
+    /// The first table of `signature`.
+    ///
+    /// Returns `NOT_FOUND` if there is none, `CRC_ERROR` if its checksum is invalid.
+    pub fn find_table(&self, signature: &[u8; 4]) -> Result<Table<'a>, EfiError> {
+        let table = self.iter().find(|table| table.signature() == *signature).ok_or(efi::Status::NOT_FOUND)?;
+        if table.is_checksum_valid() {
+            Ok(table)
+        } else {
+            Err(efi::Status::CRC_ERROR.into())
+        }
+    }
+
+    /// The DSDT, referenced by the FADT rather than the XSDT.
+    pub fn dsdt(&self) -> Result<Table<'a>, EfiError> {
+        let fadt = self.find_table(b"FACP")?.as_bytes();
+        let address = |offset: usize, size: usize| {
+            let mut bytes = [0u8; 8];
+            bytes[..size].copy_from_slice(fadt.get(offset..offset + size)?);
+            Some(u64::from_le_bytes(bytes)).filter(|address| *address != 0)
+        };
+        let address =
+            address(FADT_X_DSDT_OFFSET, 8).or_else(|| address(FADT_DSDT_OFFSET, 4)).ok_or(efi::Status::NOT_FOUND)?;
+        //SAFETY: The tables referenced by the FADT are valid for 'a, as required to create Self.
+        let dsdt = unsafe { Table::from_address(address) }?;
+        match dsdt {
+            _ if dsdt.signature() != *b"DSDT" => Err(efi::Status::INVALID_PARAMETER.into()),
+            _ if !dsdt.is_checksum_valid() => Err(efi::Status::CRC_ERROR.into()),
+            _ => Ok(dsdt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec, vec::Vec};
+
+    /// A table of `signature` with `data`, its checksum set.
+    fn table(signature: &[u8; 4], data: &[u8]) -> &'static [u8] {
+        let header = DescriptionHeader::new(*signature, (HEADER_SIZE + data.len()) as u32, 1);
+        let mut table =
+            unsafe { slice::from_raw_parts(&header as *const DescriptionHeader as *const u8, HEADER_SIZE) }.to_vec();
+        table.extend_from_slice(data);
+        table[9] = 0u8.wrapping_sub(table.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)));
+        Box::leak(table.into_boxed_slice())
+    }
+
+    fn address(table: &[u8]) -> u64 {
+        table.as_ptr() as u64
+    }
+
+    /// An RSDP whose XSDT references a FADT, a MADT, an MCFG and a null entry, and whose FADT references a DSDT.
+    fn rsdp() -> Rsdp {
+        let dsdt = table(b"DSDT", &[0x10; 4]);
+        let mut fadt = vec![0u8; 244 - HEADER_SIZE];
+        fadt[FADT_X_DSDT_OFFSET - HEADER_SIZE..FADT_X_DSDT_OFFSET - HEADER_SIZE + 8]
+            .copy_from_slice(&address(dsdt).to_le_bytes());
+        let fadt = table(b"FACP", &fadt);
+        let madt = table(
+            b"APIC",
+            &[
+                0x00, 0x00, 0xE0, 0xFE, 0x01, 0, 0, 0, 0x00, 8, 0, 0, 1, 0, 0, 0, 0x01, 12, 1, 0, 0, 0, 0xC0, 0xFE, 0,
+                0, 0, 0,
+            ],
+        );
+        let mut mcfg = vec![0u8; 8];
+        mcfg.extend_from_slice(&[0x00, 0x00, 0x00, 0xE0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0, 0, 0, 0]);
+        let mcfg = table(b"MCFG", &mcfg);
+        let entries: Vec<u8> =
+            [address(fadt), address(madt), 0, address(mcfg)].iter().flat_map(|address| address.to_le_bytes()).collect();
+        let xsdt = table(b"XSDT", &entries);
+        Rsdp {
+            signature: Rsdp::SIGNATURE,
+            checksum: 0,
+            oem_id: [0; 6],
+            revision: 2,
+            rsdt_address: 0,
+            length: mem::size_of::<Rsdp>() as u32,
+            xsdt_address: address(xsdt),
+            extended_checksum: 0,
+            reserved: [0; 3],
+        }
+    }
+
+    #[test]
+    fn test_find_table() {
+        let tables = unsafe { AcpiTables::from_rsdp(&rsdp()) }.unwrap();
+        let signatures = tables.iter().map(|table| table.signature()).collect::<Vec<_>>();
+        assert_eq!(vec![*b"FACP", *b"APIC", *b"MCFG"], signatures);
+        assert_eq!(244, tables.find_table(b"FACP").unwrap().as_bytes().len());
+        assert_eq!(efi::Status::NOT_FOUND, tables.find_table(b"HPET").unwrap_err());
+        assert_eq!(&[0x10; 4], tables.dsdt().unwrap().data());
+
+        let mcfg = tables.find_table(b"MCFG").unwrap();
+        let allocations = mcfg.mcfg_allocations().unwrap().collect::<Vec<_>>();
+        assert_eq!(
+            vec![McfgAllocation {
+                base_address: 0xE0000000,
+                segment_group: 0,
+                start_bus: 0,
+                end_bus: 0xFF,
+                reserved: 0
+            }],
+            allocations
+        );
+        assert!(tables.xsdt().mcfg_allocations().is_none());
+
+        let madt = tables.find_table(b"APIC").unwrap().madt().unwrap();
+        assert_eq!((0xFEE00000, 1), (madt.local_interrupt_controller_address, madt.flags));
+        let controllers = madt.interrupt_controllers().map(|(t, data)| (t, data.len())).collect::<Vec<_>>();
+        assert_eq!(vec![(0x00, 6), (0x01, 10)], controllers);
+    }
+
+    #[test]
+    fn test_invalid_tables() {
+        let mut rsdp = rsdp();
+        let xsdt = unsafe { slice::from_raw_parts_mut(rsdp.xsdt_address as *mut u8, HEADER_SIZE) };
+        xsdt[9] = xsdt[9].wrapping_add(1);
+        assert_eq!(efi::Status::CRC_ERROR, unsafe { AcpiTables::from_rsdp(&rsdp) }.unwrap_err());
+        xsdt[9] = xsdt[9].wrapping_sub(1);
+
+        let tables = unsafe { AcpiTables::from_rsdp(&rsdp) }.unwrap();
+        let madt = tables.find_table(b"APIC").unwrap().as_bytes();
+        unsafe { *(madt.as_ptr().add(HEADER_SIZE) as *mut u8) = 0xFF };
+        assert_eq!(efi::Status::CRC_ERROR, tables.find_table(b"APIC").unwrap_err());
+
+        rsdp.xsdt_address = 0;
+        assert_eq!(efi::Status::NOT_FOUND, unsafe { AcpiTables::from_rsdp(&rsdp) }.unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, Table::from_bytes(&[0; 35]).unwrap_err());
+    }
+}
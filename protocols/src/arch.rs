@@ -0,0 +1,230 @@
+//! Monotonic cycle counters, behind the [`CycleCounter`] trait the timing helpers read.
+//!
+//! The [`ProcessorCounter`] reads the time stamp counter on IA32 and x64, and the virtual count of the generic timer
+//! on ARM and AArch64, the counter `GetPerformanceCounter()` of EDK II reads too. The frequency of the generic timer
+//! is architectural, the one of the time stamp counter is reported by CPUID on recent processors and measured with
+//! [`calibrate`] otherwise:
+//!
+//! ```ignore
+//! let counter = ProcessorCounter::new().ok_or(efi::Status::UNSUPPORTED)?;
+//! let frequency = calibrate(&counter, &boot_services)?;
+//! ```
+//!
+//! The time stamp counter only has a constant rate on the processors with an invariant TSC, which all the processors
+//! running UEFI firmware have in practice.
+
+use boot_services::BootServices;
+use r_efi::efi;
+use status::EfiError;
+
+/// Time a counter is measured over to calibrate it, in microseconds.
+pub const CALIBRATION_STALL: usize = 1000;
+
+/// A monotonic counter of ticks at a constant frequency.
+pub trait CycleCounter {
+    /// The current value of the counter.
+    fn ticks(&self) -> u64;
+
+    /// The frequency of the counter in hertz, `None` if it must be measured.
+    fn frequency(&self) -> Option<u64>;
+
+    /// The value after which the counter rolls over to 0.
+    fn end_value(&self) -> u64 {
+        u64::MAX
+    }
+
+    /// The ticks from `earlier` to `later`, the counter having rolled over at most once.
+    fn ticks_between(&self, earlier: u64, later: u64) -> u64 {
+        match later.checked_sub(earlier) {
+            Some(ticks) => ticks,
+            None => (self.end_value() - earlier).saturating_add(later).saturating_add(1),
+        }
+    }
+}
+
+/// The counter of the processor.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessorCounter(());
+
+impl ProcessorCounter {
+    /// The counter of the processor, `None` on the architectures without one.
+    pub fn new() -> Option<Self> {
+        imp::SUPPORTED.then_some(Self(()))
+    }
+}
+
+impl CycleCounter for ProcessorCounter {
+    fn ticks(&self) -> u64 {
+        imp::ticks()
+    }
+
+    fn frequency(&self) -> Option<u64> {
+        imp::frequency()
+    }
+}
+
+/// The current value of the counter of the processor, `None` on the architectures without one.
+pub fn processor_ticks() -> Option<u64> {
+    ProcessorCounter::new().map(|counter| counter.ticks())
+}
+
+/// The frequency of `counter` in hertz, measured over a [`CALIBRATION_STALL`] when the counter does not report it.
+///
+/// Returns `DEVICE_ERROR` if the frequency is 0, like for a counter that does not advance.
+pub fn calibrate<C: CycleCounter + ?Sized, B: BootServices>(counter: &C, boot_services: &B) -> Result<u64, EfiError> {
+    let frequency = match counter.frequency() {
+        Some(frequency) => frequency,
+        None => {
+            let start = counter.ticks();
+            boot_services.stall(CALIBRATION_STALL)?;
+            let end = counter.ticks();
+            counter.ticks_between(start, end).saturating_mul(1_000_000 / CALIBRATION_STALL as u64)
+        }
+    };
+    match frequency {
+        0 => Err(efi::Status::DEVICE_ERROR.into()),
+        frequency => Ok(frequency),
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod imp {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::{__cpuid, _rdtsc};
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::{__cpuid, _rdtsc};
+
+    /// Leaf of CPUID reporting the ratio of the time stamp counter to the core crystal clock.
+    const TSC_LEAF: u32 = 0x15;
+
+    pub const SUPPORTED: bool = true;
+
+    pub fn ticks() -> u64 {
+        //SAFETY: The time stamp counter is readable at any privilege level UEFI runs at.
+        unsafe { _rdtsc() }
+    }
+
+    /// The frequency of the time stamp counter is not architectural, only some processors report it.
+    pub fn frequency() -> Option<u64> {
+        //SAFETY: CPUID is available on every processor UEFI runs on.
+        if unsafe { __cpuid(0) }.eax < TSC_LEAF {
+            return None;
+        }
+        //SAFETY: The leaf is supported.
+        let leaf = unsafe { __cpuid(TSC_LEAF) };
+        match (leaf.eax, leaf.ebx, leaf.ecx) {
+            (0, _, _) | (_, 0, _) | (_, _, 0) => None,
+            (denominator, numerator, crystal) => Some(crystal as u64 * numerator as u64 / denominator as u64),
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod imp {
+    pub const SUPPORTED: bool = true;
+
+    pub fn ticks() -> u64 {
+        let counter: u64;
+        //SAFETY: The virtual count of the generic timer is readable from EL1 and above.
+        unsafe { core::arch::asm!("isb", "mrs {}, cntvct_el0", out(reg) counter, options(nomem, nostack)) };
+        counter
+    }
+
+    pub fn frequency() -> Option<u64> {
+        let frequency: u64;
+        //SAFETY: The frequency of the generic timer is readable from EL1 and above.
+        unsafe { core::arch::asm!("mrs {}, cntfrq_el0", out(reg) frequency, options(nomem, nostack)) };
+        Some(frequency)
+    }
+}
+
+#[cfg(target_arch = "arm")]
+mod imp {
+    pub const SUPPORTED: bool = true;
+
+    pub fn ticks() -> u64 {
+        let (low, high): (u32, u32);
+        //SAFETY: The virtual count of the generic timer, CNTVCT, is readable from PL1 and above.
+        unsafe {
+            core::arch::asm!("isb", "mrrc p15, 1, {}, {}, c14", out(reg) low, out(reg) high, options(nomem, nostack))
+        };
+        (high as u64) << 32 | low as u64
+    }
+
+    pub fn frequency() -> Option<u64> {
+        let frequency: u32;
+        //SAFETY: The frequency of the generic timer, CNTFRQ, is readable from PL1 and above.
+        unsafe { core::arch::asm!("mrc p15, 0, {}, c14, c0, 0", out(reg) frequency, options(nomem, nostack)) };
+        Some(frequency as u64)
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+mod imp {
+    pub const SUPPORTED: bool = false;
+
+    pub fn ticks() -> u64 {
+        0
+    }
+
+    pub fn frequency() -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use core::cell::Cell;
+
+    /// A counter advancing by `step` ticks at every read, rolling over after 0xFFFF.
+    struct TestCounter {
+        ticks: Cell<u64>,
+        step: u64,
+    }
+
+    impl CycleCounter for TestCounter {
+        fn ticks(&self) -> u64 {
+            let ticks = self.ticks.get();
+            self.ticks.set((ticks + self.step) % 0x10000);
+            ticks
+        }
+
+        fn frequency(&self) -> Option<u64> {
+            None
+        }
+
+        fn end_value(&self) -> u64 {
+            0xFFFF
+        }
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_stall().withf(|&microseconds| microseconds == CALIBRATION_STALL).returning(|_| Ok(()));
+        // The counter rolls over during the calibration.
+        let counter = TestCounter { ticks: Cell::new(0xFF00), step: 2500 };
+        assert_eq!(2_500_000, calibrate(&counter, &boot_services).unwrap());
+        assert_eq!(0x100 + 0x10, counter.ticks_between(0xFF00, 0x10));
+
+        let stopped = TestCounter { ticks: Cell::new(0), step: 0 };
+        assert_eq!(efi::Status::DEVICE_ERROR, calibrate(&stopped, &boot_services).unwrap_err());
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+    #[test]
+    fn test_processor_counter() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_stall().returning(|microseconds| {
+            std::thread::sleep(std::time::Duration::from_micros(microseconds as u64));
+            Ok(())
+        });
+        let counter = ProcessorCounter::new().unwrap();
+        assert!(calibrate(&counter, &boot_services).unwrap() > 0);
+        let start = processor_ticks().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert!(counter.ticks_between(start, counter.ticks()) > 0);
+    }
+}
@@ -0,0 +1,295 @@
+//! Component Name 2 protocol.
+//!
+//! [`ComponentName`] is used by a driver to produce the protocol from a table of names per language.
+//!
+//! [UEFI Spec Documentation: 11.5. EFI Component Name Protocol](https://uefi.org/specs/UEFI/2.10/11_Protocols_UEFI_Driver_Model.html#efi-component-name2-protocol)
+
+use alloc::{string::String, vec::Vec};
+use core::{cell::RefCell, ffi::CStr, fmt, ops::Deref, ptr};
+
+use boot_services::{
+    protocol_handler::{InstalledProtocol, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x6a7a5cff, 0xe8d9, 0x4f70, 0xba, 0xda, &[0x75, 0xab, 0x30, 0x25, 0xce, 0x14]);
+
+pub type ProtocolGetDriverName = extern "efiapi" fn(*mut Protocol, *mut u8, *mut *mut u16) -> efi::Status;
+
+pub type ProtocolGetControllerName =
+    extern "efiapi" fn(*mut Protocol, efi::Handle, efi::Handle, *mut u8, *mut *mut u16) -> efi::Status;
+
+/// FFI definition of `EFI_COMPONENT_NAME2_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub get_driver_name: ProtocolGetDriverName,
+    pub get_controller_name: ProtocolGetControllerName,
+    pub supported_languages: *mut u8,
+}
+
+/// Component Name 2 protocol, used to consume the protocol.
+pub struct ComponentName2;
+
+unsafe impl ProtocolTrait for ComponentName2 {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for ComponentName2 {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Component Name 2 protocol with a [`ComponentName`] interface, used to produce the protocol.
+pub struct ComponentName2Producer;
+
+unsafe impl ProtocolTrait for ComponentName2Producer {
+    type Interface = ComponentName;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for ComponentName2Producer {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Names of a component in different languages.
+struct Names(Vec<(String, Vec<u16>)>);
+
+impl Names {
+    fn new(names: &[(&str, &str)]) -> Self {
+        Self(
+            names
+                .iter()
+                .map(|(language, name)| (String::from(*language), name.encode_utf16().chain([0]).collect()))
+                .collect(),
+        )
+    }
+
+    /// Returns the name for the language, languages are matched without case sensitivity per RFC 4646.
+    fn lookup(&self, language: &[u8]) -> Option<*mut u16> {
+        self.0
+            .iter()
+            .find(|(l, _)| l.as_bytes().eq_ignore_ascii_case(language))
+            .map(|(_, name)| name.as_ptr() as *mut u16)
+    }
+}
+
+struct ControllerName {
+    controller_handle: efi::Handle,
+    child_handle: Option<efi::Handle>,
+    names: Names,
+}
+
+/// Implementation of the Component Name 2 protocol produced by a driver.
+///
+/// The supported languages are the languages of the driver names, controller names should use the same languages.
+///
+/// ```ignore
+/// let component_name = ComponentName::new(&[("en", "My Driver"), ("fr", "Mon Pilote")]);
+/// let installed = component_name.install(&boot_services, driver_binding_handle)?;
+/// // In DriverBinding.Start():
+/// installed.add_controller_name(controller_handle, None, &[("en", "My Controller")]);
+/// ```
+#[repr(C)]
+pub struct ComponentName {
+    protocol: Protocol,
+    supported_languages: Vec<u8>,
+    driver_names: Names,
+    controller_names: RefCell<Vec<ControllerName>>,
+}
+
+impl ComponentName {
+    /// Creates a component name implementation from the driver names, as `(language, name)` pairs.
+    pub fn new(driver_names: &[(&str, &str)]) -> Self {
+        let mut supported_languages = driver_names.iter().map(|(language, _)| *language).collect::<Vec<_>>().join(";");
+        supported_languages.push('\0');
+        let mut supported_languages = supported_languages.into_bytes();
+        Self {
+            protocol: Protocol {
+                get_driver_name: Self::get_driver_name,
+                get_controller_name: Self::get_controller_name,
+                supported_languages: supported_languages.as_mut_ptr(),
+            },
+            supported_languages,
+            driver_names: Names::new(driver_names),
+            controller_names: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Installs the protocol on the driver binding handle of the driver.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn install<B: BootServices>(
+        self,
+        boot_services: &B,
+        driver_binding_handle: efi::Handle,
+    ) -> Result<InstalledProtocol<'_, ComponentName2Producer, B>, EfiError> {
+        InstalledProtocol::install(
+            boot_services,
+            Some(driver_binding_handle),
+            &ComponentName2Producer,
+            alloc::boxed::Box::new(self),
+        )
+    }
+
+    /// The languages supported by the component, separated by `;`.
+    pub fn supported_languages(&self) -> &str {
+        // The supported languages are built from a string, minus the null terminator.
+        core::str::from_utf8(&self.supported_languages[..self.supported_languages.len() - 1]).unwrap_or_default()
+    }
+
+    /// Sets the names of a controller managed by the driver, or of a child controller when `child_handle` is set.
+    /// Previous names of the same controller are replaced.
+    pub fn add_controller_name(
+        &self,
+        controller_handle: efi::Handle,
+        child_handle: Option<efi::Handle>,
+        names: &[(&str, &str)],
+    ) {
+        let mut controller_names = self.controller_names.borrow_mut();
+        controller_names.retain(|c| c.controller_handle != controller_handle || c.child_handle != child_handle);
+        controller_names.push(ControllerName { controller_handle, child_handle, names: Names::new(names) });
+    }
+
+    /// Removes the names of a controller and of all its children, typically when the driver stops managing it.
+    pub fn remove_controller_names(&self, controller_handle: efi::Handle) {
+        self.controller_names.borrow_mut().retain(|c| c.controller_handle != controller_handle);
+    }
+
+    extern "efiapi" fn get_driver_name(
+        this: *mut Protocol,
+        language: *mut u8,
+        driver_name: *mut *mut u16,
+    ) -> efi::Status {
+        if this.is_null() || language.is_null() || driver_name.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        //SAFETY: The protocol is the first field of the ComponentName that was installed.
+        let this = unsafe { &*(this as *const ComponentName) };
+        //SAFETY: The language is a null terminated ASCII string per spec.
+        let language = unsafe { CStr::from_ptr(language as *const _) };
+        match this.driver_names.lookup(language.to_bytes()) {
+            Some(name) => {
+                //SAFETY: The pointer was checked for null.
+                unsafe { ptr::write(driver_name, name) };
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    extern "efiapi" fn get_controller_name(
+        this: *mut Protocol,
+        controller_handle: efi::Handle,
+        child_handle: efi::Handle,
+        language: *mut u8,
+        controller_name: *mut *mut u16,
+    ) -> efi::Status {
+        if this.is_null() || controller_handle.is_null() || language.is_null() || controller_name.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        //SAFETY: The protocol is the first field of the ComponentName that was installed.
+        let this = unsafe { &*(this as *const ComponentName) };
+        //SAFETY: The language is a null terminated ASCII string per spec.
+        let language = unsafe { CStr::from_ptr(language as *const _) };
+        let child_handle = if child_handle.is_null() { None } else { Some(child_handle) };
+        let Ok(controller_names) = this.controller_names.try_borrow() else {
+            return efi::Status::UNSUPPORTED;
+        };
+        let name = controller_names
+            .iter()
+            .find(|c| c.controller_handle == controller_handle && c.child_handle == child_handle)
+            .and_then(|c| c.names.lookup(language.to_bytes()));
+        match name {
+            Some(name) => {
+                //SAFETY: The pointer was checked for null.
+                unsafe { ptr::write(controller_name, name) };
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::UNSUPPORTED,
+        }
+    }
+}
+
+impl fmt::Debug for ComponentName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComponentName").field("supported_languages", &self.supported_languages()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+
+    fn utf16_str(s: *mut u16) -> String {
+        let len = (0..).take_while(|i| unsafe { *s.add(*i) } != 0).count();
+        String::from_utf16(unsafe { core::slice::from_raw_parts(s, len) }).unwrap()
+    }
+
+    #[test]
+    fn test_get_driver_name() {
+        let mut component_name = ComponentName::new(&[("en", "My Driver"), ("fr-FR", "Mon Pilote")]);
+        assert_eq!("en;fr-FR", component_name.supported_languages());
+        assert_eq!(
+            "en;fr-FR",
+            unsafe { CStr::from_ptr(component_name.protocol.supported_languages as *const _) }.to_str().unwrap()
+        );
+
+        let this = ptr::addr_of_mut!(component_name.protocol);
+        let mut name = ptr::null_mut();
+        let status = (component_name.protocol.get_driver_name)(this, b"fr-fr\0".as_ptr() as *mut _, &mut name);
+        assert_eq!(efi::Status::SUCCESS, status);
+        assert_eq!("Mon Pilote", utf16_str(name));
+
+        let status = (component_name.protocol.get_driver_name)(this, b"de\0".as_ptr() as *mut _, &mut name);
+        assert_eq!(efi::Status::UNSUPPORTED, status);
+        let status = (component_name.protocol.get_driver_name)(this, ptr::null_mut(), &mut name);
+        assert_eq!(efi::Status::INVALID_PARAMETER, status);
+    }
+
+    #[test]
+    fn test_get_controller_name() {
+        let mut component_name = ComponentName::new(&[("en", "My Driver")]);
+        component_name.add_controller_name(1_usize as efi::Handle, None, &[("en", "My Controller")]);
+        component_name.add_controller_name(1_usize as efi::Handle, Some(2_usize as efi::Handle), &[("en", "My Child")]);
+
+        let this = ptr::addr_of_mut!(component_name.protocol);
+        let get_controller_name = component_name.protocol.get_controller_name;
+        let mut name = ptr::null_mut();
+        let en = b"en\0".as_ptr() as *mut u8;
+        assert_eq!(efi::Status::SUCCESS, get_controller_name(this, 1_usize as _, ptr::null_mut(), en, &mut name));
+        assert_eq!("My Controller", utf16_str(name));
+        assert_eq!(efi::Status::SUCCESS, get_controller_name(this, 1_usize as _, 2_usize as _, en, &mut name));
+        assert_eq!("My Child", utf16_str(name));
+        assert_eq!(efi::Status::UNSUPPORTED, get_controller_name(this, 3_usize as _, ptr::null_mut(), en, &mut name));
+
+        component_name.remove_controller_names(1_usize as efi::Handle);
+        assert_eq!(efi::Status::UNSUPPORTED, get_controller_name(this, 1_usize as _, ptr::null_mut(), en, &mut name));
+    }
+
+    #[test]
+    fn test_install() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_install_protocol_interface_unchecked()
+            .withf(|handle, protocol, _| *handle == Some(1_usize as efi::Handle) && *protocol == PROTOCOL_GUID)
+            .once()
+            .returning(|handle, _, _| Ok(handle.unwrap()));
+
+        let installed = ComponentName::new(&[("en", "My Driver")]).install(&boot_services, 1_usize as _).unwrap();
+        assert_eq!("en", installed.supported_languages());
+        installed.leak();
+    }
+}
@@ -0,0 +1,403 @@
+//! CPU Architectural protocol of the PI specification.
+//!
+//! [`CpuArch`] gives the drivers access to the processor the DXE core runs on: its interrupts, cache, timers and the
+//! cacheability and protection attributes of memory. An [`InterruptsDisabled`] guard makes a short critical section,
+//! the interrupts are enabled back when it is dropped if they were enabled before:
+//!
+//! ```ignore
+//! let mut cpu = CpuArch::locate(&boot_services)?;
+//! {
+//!     let _interrupts = cpu.disable_interrupts_guarded()?;
+//!     program_controller();
+//! }
+//! ```
+//!
+//! The protocol only sets memory attributes, the attributes of a range are read from the GCD memory space map or
+//! with [`MemoryProtection::get_attributes`](crate::memory_attribute::MemoryProtection::get_attributes).
+//!
+//! [PI Spec Documentation: Volume 2 - 12.3 CPU Architectural Protocol](https://uefi.org/specs/PI/1.8/V2_DXE_Architectural_Protocols.html#cpu-architectural-protocol)
+
+use core::{fmt, ops::Deref};
+
+use boot_services::{allocation::MemoryAttribute, protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub use efi::protocols::debug_support::{ExceptionType, SystemContext};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x26baccb1, 0x6f42, 0x11d4, 0xbc, 0xe7, &[0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81]);
+
+/// FFI definition of `EFI_CPU_FLUSH_TYPE`.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushType {
+    WriteBackInvalidate = 0,
+    WriteBack = 1,
+    Invalidate = 2,
+}
+
+/// FFI definition of `EFI_CPU_INIT_TYPE`.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitType {
+    Init = 0,
+}
+
+/// Handler of an interrupt or exception, `EFI_CPU_INTERRUPT_HANDLER`.
+pub type InterruptHandler = extern "efiapi" fn(ExceptionType, SystemContext);
+
+pub type ProtocolFlushDataCache =
+    extern "efiapi" fn(*mut Protocol, efi::PhysicalAddress, u64, FlushType) -> efi::Status;
+
+pub type ProtocolEnableInterrupt = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+pub type ProtocolDisableInterrupt = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+pub type ProtocolGetInterruptState = extern "efiapi" fn(*mut Protocol, *mut efi::Boolean) -> efi::Status;
+
+pub type ProtocolInit = extern "efiapi" fn(*mut Protocol, InitType) -> efi::Status;
+
+pub type ProtocolRegisterInterruptHandler =
+    extern "efiapi" fn(*mut Protocol, ExceptionType, Option<InterruptHandler>) -> efi::Status;
+
+pub type ProtocolGetTimerValue = extern "efiapi" fn(*mut Protocol, u32, *mut u64, *mut u64) -> efi::Status;
+
+pub type ProtocolSetMemoryAttributes = extern "efiapi" fn(*mut Protocol, efi::PhysicalAddress, u64, u64) -> efi::Status;
+
+/// FFI definition of `EFI_CPU_ARCH_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub flush_data_cache: ProtocolFlushDataCache,
+    pub enable_interrupt: ProtocolEnableInterrupt,
+    pub disable_interrupt: ProtocolDisableInterrupt,
+    pub get_interrupt_state: ProtocolGetInterruptState,
+    pub init: ProtocolInit,
+    pub register_interrupt_handler: ProtocolRegisterInterruptHandler,
+    pub get_timer_value: ProtocolGetTimerValue,
+    pub set_memory_attributes: ProtocolSetMemoryAttributes,
+    pub number_of_timers: u32,
+    pub dma_buffer_alignment: u32,
+}
+
+/// CPU Architectural protocol.
+pub struct CpuArchProtocol;
+
+unsafe impl ProtocolTrait for CpuArchProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for CpuArchProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Typed access to the CPU Architectural protocol.
+pub struct CpuArch(&'static mut Protocol);
+
+impl CpuArch {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&CpuArchProtocol, None).map(Self)
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.0 as *mut Protocol
+    }
+
+    /// Number of timers of [`CpuArch::get_timer_value`].
+    pub fn number_of_timers(&self) -> u32 {
+        self.0.number_of_timers
+    }
+
+    /// Alignment of the buffers used for DMA, in bytes.
+    pub fn dma_buffer_alignment(&self) -> u32 {
+        self.0.dma_buffer_alignment
+    }
+
+    /// Flushes the data cache of `length` bytes from `start`.
+    pub fn flush_data_cache(
+        &mut self,
+        start: efi::PhysicalAddress,
+        length: u64,
+        flush_type: FlushType,
+    ) -> Result<(), EfiError> {
+        match (self.0.flush_data_cache)(self.this(), start, length, flush_type) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Enables the interrupts of the processor.
+    pub fn enable_interrupts(&mut self) -> Result<(), EfiError> {
+        match (self.0.enable_interrupt)(self.this()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Disables the interrupts of the processor.
+    ///
+    /// Prefer [`CpuArch::disable_interrupts_guarded`] when possible.
+    pub fn disable_interrupts(&mut self) -> Result<(), EfiError> {
+        match (self.0.disable_interrupt)(self.this()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Disables the interrupts of the processor until the returned guard is dropped.
+    pub fn disable_interrupts_guarded(&mut self) -> Result<InterruptsDisabled<'_>, EfiError> {
+        let enabled = self.interrupts_enabled()?;
+        self.disable_interrupts()?;
+        Ok(InterruptsDisabled { cpu: self, enabled })
+    }
+
+    /// Whether the interrupts of the processor are enabled.
+    pub fn interrupts_enabled(&mut self) -> Result<bool, EfiError> {
+        let mut state = efi::Boolean::FALSE;
+        match (self.0.get_interrupt_state)(self.this(), &mut state) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(state.into()),
+        }
+    }
+
+    /// Resets the processor, returns only if the reset failed.
+    pub fn init(&mut self, init_type: InitType) -> efi::Status {
+        (self.0.init)(self.this(), init_type)
+    }
+
+    /// Registers the handler of an interrupt or exception, or unregisters it if `handler` is `None`.
+    ///
+    /// Returns `ALREADY_STARTED` if a handler is already registered, and `INVALID_PARAMETER` if none is registered
+    /// when unregistering.
+    pub fn register_interrupt_handler(
+        &mut self,
+        interrupt_type: ExceptionType,
+        handler: Option<InterruptHandler>,
+    ) -> Result<(), EfiError> {
+        match (self.0.register_interrupt_handler)(self.this(), interrupt_type, handler) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The value of a timer of the processor, and its period in femtoseconds.
+    pub fn get_timer_value(&mut self, timer_index: u32) -> Result<(u64, u64), EfiError> {
+        let (mut value, mut period) = (0, 0);
+        match (self.0.get_timer_value)(self.this(), timer_index, &mut value, &mut period) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok((value, period)),
+        }
+    }
+
+    /// Sets the attributes of `length` bytes from `base`, replacing the cacheability or access attributes when
+    /// `attributes` has some.
+    pub fn set_memory_attributes(
+        &mut self,
+        base: efi::PhysicalAddress,
+        length: u64,
+        attributes: MemoryAttribute,
+    ) -> Result<(), EfiError> {
+        match (self.0.set_memory_attributes)(self.this(), base, length, attributes.into()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for CpuArch {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for CpuArch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CpuArch")
+            .field("number_of_timers", &self.number_of_timers())
+            .field("dma_buffer_alignment", &self.dma_buffer_alignment())
+            .finish()
+    }
+}
+
+/// Interrupts disabled by [`CpuArch::disable_interrupts_guarded`], enabled back when dropped if they were enabled.
+pub struct InterruptsDisabled<'a> {
+    cpu: &'a mut CpuArch,
+    /// Whether the interrupts were enabled before.
+    enabled: bool,
+}
+
+impl Drop for InterruptsDisabled<'_> {
+    fn drop(&mut self) {
+        if self.enabled {
+            // Nothing can report the failure from a drop.
+            let _ = self.cpu.enable_interrupts();
+        }
+    }
+}
+
+impl fmt::Debug for InterruptsDisabled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InterruptsDisabled").field("enabled", &self.enabled).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec::Vec};
+    use boot_services::MockBootServices;
+    use core::cell::RefCell;
+
+    /// Processor recording its calls, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestCpu {
+        protocol: Protocol,
+        interrupts: bool,
+        handlers: Vec<ExceptionType>,
+        attributes: RefCell<Vec<(u64, u64, u64)>>,
+    }
+
+    fn test_cpu<'a>(this: *mut Protocol) -> &'a mut TestCpu {
+        unsafe { &mut *(this as *mut TestCpu) }
+    }
+
+    extern "efiapi" fn flush_data_cache(
+        _: *mut Protocol,
+        _: efi::PhysicalAddress,
+        _: u64,
+        _: FlushType,
+    ) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn enable_interrupt(this: *mut Protocol) -> efi::Status {
+        test_cpu(this).interrupts = true;
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn disable_interrupt(this: *mut Protocol) -> efi::Status {
+        test_cpu(this).interrupts = false;
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_interrupt_state(this: *mut Protocol, state: *mut efi::Boolean) -> efi::Status {
+        unsafe { *state = test_cpu(this).interrupts.into() };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn init(_: *mut Protocol, _: InitType) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn register_interrupt_handler(
+        this: *mut Protocol,
+        interrupt_type: ExceptionType,
+        handler: Option<InterruptHandler>,
+    ) -> efi::Status {
+        let handlers = &mut test_cpu(this).handlers;
+        let registered = handlers.iter().position(|t| *t == interrupt_type);
+        match (handler, registered) {
+            (Some(_), Some(_)) => efi::Status::ALREADY_STARTED,
+            (Some(_), None) => {
+                handlers.push(interrupt_type);
+                efi::Status::SUCCESS
+            }
+            (None, Some(index)) => {
+                handlers.remove(index);
+                efi::Status::SUCCESS
+            }
+            (None, None) => efi::Status::INVALID_PARAMETER,
+        }
+    }
+
+    extern "efiapi" fn get_timer_value(_: *mut Protocol, index: u32, value: *mut u64, period: *mut u64) -> efi::Status {
+        if index != 0 {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        unsafe {
+            *value = 42;
+            *period = 1_000_000;
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_memory_attributes(
+        this: *mut Protocol,
+        base: efi::PhysicalAddress,
+        length: u64,
+        attributes: u64,
+    ) -> efi::Status {
+        test_cpu(this).attributes.borrow_mut().push((base, length, attributes));
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn handler(_: ExceptionType, _: SystemContext) {}
+
+    fn cpu_arch() -> (CpuArch, &'static mut TestCpu) {
+        let cpu = Box::leak(Box::new(TestCpu {
+            protocol: Protocol {
+                flush_data_cache,
+                enable_interrupt,
+                disable_interrupt,
+                get_interrupt_state,
+                init,
+                register_interrupt_handler,
+                get_timer_value,
+                set_memory_attributes,
+                number_of_timers: 1,
+                dma_buffer_alignment: 64,
+            },
+            interrupts: true,
+            handlers: Vec::new(),
+            attributes: RefCell::new(Vec::new()),
+        }));
+        let cpu_ptr = cpu as *mut TestCpu as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<CpuArchProtocol, Protocol>()
+            .once()
+            .returning(move |_, _| Ok(unsafe { &mut (*(cpu_ptr as *mut TestCpu)).protocol }));
+        (CpuArch::locate(&boot_services).unwrap(), unsafe { &mut *(cpu_ptr as *mut TestCpu) })
+    }
+
+    #[test]
+    fn test_interrupts_disabled() {
+        let (mut cpu_arch, cpu) = cpu_arch();
+        {
+            let _interrupts = cpu_arch.disable_interrupts_guarded().unwrap();
+            assert!(!cpu.interrupts);
+            {
+                // Already disabled, they stay disabled.
+                let mut nested = CpuArch::from(unsafe { &mut *(&mut cpu.protocol as *mut Protocol) });
+                let _interrupts = nested.disable_interrupts_guarded().unwrap();
+            }
+            assert!(!cpu.interrupts);
+        }
+        assert!(cpu_arch.interrupts_enabled().unwrap());
+    }
+
+    #[test]
+    fn test_cpu_arch() {
+        let (mut cpu_arch, cpu) = cpu_arch();
+        assert_eq!((1, 64), (cpu_arch.number_of_timers(), cpu_arch.dma_buffer_alignment()));
+        assert_eq!((42, 1_000_000), cpu_arch.get_timer_value(0).unwrap());
+        assert_eq!(efi::Status::INVALID_PARAMETER, cpu_arch.get_timer_value(1).unwrap_err());
+        cpu_arch.flush_data_cache(0x1000, 0x1000, FlushType::WriteBack).unwrap();
+        assert_eq!(efi::Status::UNSUPPORTED, cpu_arch.init(InitType::Init));
+
+        cpu_arch.register_interrupt_handler(32, Some(handler)).unwrap();
+        assert_eq!(efi::Status::ALREADY_STARTED, cpu_arch.register_interrupt_handler(32, Some(handler)).unwrap_err());
+        cpu_arch.register_interrupt_handler(32, None).unwrap();
+        assert_eq!(efi::Status::INVALID_PARAMETER, cpu_arch.register_interrupt_handler(32, None).unwrap_err());
+
+        cpu_arch.set_memory_attributes(0x1000, 0x2000, MemoryAttribute::UC | MemoryAttribute::XP).unwrap();
+        assert_eq!(&[(0x1000, 0x2000, efi::MEMORY_UC | efi::MEMORY_XP)], cpu.attributes.borrow().as_slice());
+    }
+}
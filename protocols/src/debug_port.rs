@@ -0,0 +1,291 @@
+//! Debug Port protocol.
+//!
+//! [`DebugPort`] transfers bytes over the link to the debug host, which on some platforms is the only serial port,
+//! the reads poll the port so a debug console can check for input without waiting. It implements [`fmt::Write`] to be
+//! used as a log output.
+//!
+//! The definition of r-efi returns a pointer instead of a status from the functions of the protocol, this one follows
+//! the specification.
+//!
+//! [UEFI Spec Documentation: 18.3. EFI Debug Port Protocol](https://uefi.org/specs/UEFI/2.10/18_Protocols_Debugger_Support.html#efi-debugport-protocol)
+
+use core::{ffi::c_void, fmt, ops::Deref};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid = efi::protocols::debugport::PROTOCOL_GUID;
+
+pub type ProtocolReset = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+pub type ProtocolWrite = extern "efiapi" fn(*mut Protocol, u32, *mut usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolRead = extern "efiapi" fn(*mut Protocol, u32, *mut usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolPoll = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+/// FFI definition of `EFI_DEBUGPORT_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub reset: ProtocolReset,
+    pub write: ProtocolWrite,
+    pub read: ProtocolRead,
+    pub poll: ProtocolPoll,
+}
+
+/// Debug Port protocol.
+pub struct DebugPortProtocol;
+
+unsafe impl ProtocolTrait for DebugPortProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for DebugPortProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Typed access to the Debug Port protocol.
+///
+/// ```ignore
+/// let mut debug_port = DebugPort::locate(&boot_services)?.with_timeout(1000);
+/// writeln!(debug_port, "Hello")?;
+/// let mut input = [0; 16];
+/// let read = debug_port.poll_read(&mut input)?;
+/// ```
+pub struct DebugPort {
+    protocol: &'static mut Protocol,
+    timeout: u32,
+}
+
+impl DebugPort {
+    /// Default timeout of a read or a write, in microseconds.
+    pub const DEFAULT_TIMEOUT: u32 = 100_000;
+
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&DebugPortProtocol, None).map(Self::from)
+    }
+
+    /// Sets the timeout of a read or a write, in microseconds.
+    pub fn with_timeout(mut self, timeout: u32) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The timeout of a read or a write, in microseconds.
+    pub fn timeout(&self) -> u32 {
+        self.timeout
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.protocol as *mut Protocol
+    }
+
+    /// Resets the debug port.
+    pub fn reset(&mut self) -> Result<(), EfiError> {
+        match (self.protocol.reset)(self.this()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes bytes until they are all written or the timeout expires, returns the number of bytes written.
+    pub fn write(&mut self, buffer: &[u8]) -> Result<usize, EfiError> {
+        let mut size = buffer.len();
+        // The buffer is only read by the protocol.
+        match (self.protocol.write)(self.this(), self.timeout, &mut size, buffer.as_ptr() as *mut c_void) {
+            efi::Status::TIMEOUT => Ok(size),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Writes every byte, waiting as long as the port accepts some before the timeout.
+    ///
+    /// Returns `TIMEOUT` if no byte was written before the timeout expired.
+    pub fn write_all(&mut self, mut buffer: &[u8]) -> Result<(), EfiError> {
+        while !buffer.is_empty() {
+            match self.write(buffer)? {
+                0 => return Err(efi::Status::TIMEOUT.into()),
+                written => buffer = &buffer[written..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads bytes until the buffer is full or the timeout expires, returns the number of bytes read.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        let mut size = buffer.len();
+        match (self.protocol.read)(self.this(), self.timeout, &mut size, buffer.as_mut_ptr() as *mut c_void) {
+            efi::Status::TIMEOUT => Ok(size),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Whether bytes are waiting to be read.
+    pub fn poll(&mut self) -> Result<bool, EfiError> {
+        match (self.protocol.poll)(self.this()) {
+            efi::Status::NOT_READY => Ok(false),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(true),
+        }
+    }
+
+    /// Reads the bytes waiting to be read, returns 0 without waiting for the timeout if there are none.
+    pub fn poll_read(&mut self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        match self.poll()? {
+            true => self.read(buffer),
+            false => Ok(0),
+        }
+    }
+}
+
+impl fmt::Write for DebugPort {
+    /// Writes the string, newlines are translated to `\r\n`.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut lines = s.split('\n');
+        if let Some(line) = lines.next() {
+            self.write_all(line.as_bytes()).map_err(|_| fmt::Error)?;
+        }
+        for line in lines {
+            self.write_all(b"\r\n").map_err(|_| fmt::Error)?;
+            self.write_all(line.as_bytes()).map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&'static mut Protocol> for DebugPort {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self { protocol, timeout: Self::DEFAULT_TIMEOUT }
+    }
+}
+
+impl fmt::Debug for DebugPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugPort").field("timeout", &self.timeout).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, fmt::Write, slice};
+
+    /// Debug port transferring at most 4 bytes per call, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestDebugPort {
+        protocol: Protocol,
+        written: RefCell<Vec<u8>>,
+        input: RefCell<VecDeque<u8>>,
+        /// Whether the port stopped accepting bytes.
+        stalled: bool,
+    }
+
+    fn test_debug_port<'a>(this: *mut Protocol) -> &'a mut TestDebugPort {
+        unsafe { &mut *(this as *mut TestDebugPort) }
+    }
+
+    extern "efiapi" fn reset(this: *mut Protocol) -> efi::Status {
+        test_debug_port(this).input.borrow_mut().clear();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn write(this: *mut Protocol, timeout: u32, size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        assert_eq!(1000, timeout);
+        let port = test_debug_port(this);
+        let requested = unsafe { *size };
+        let written = if port.stalled { 0 } else { requested.min(4) };
+        let buffer = unsafe { slice::from_raw_parts(buffer as *const u8, written) };
+        port.written.borrow_mut().extend_from_slice(buffer);
+        unsafe { *size = written };
+        if written < requested {
+            efi::Status::TIMEOUT
+        } else {
+            efi::Status::SUCCESS
+        }
+    }
+
+    extern "efiapi" fn read(this: *mut Protocol, timeout: u32, size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        assert_eq!(1000, timeout);
+        let requested = unsafe { *size };
+        let mut input = test_debug_port(this).input.borrow_mut();
+        let read = requested.min(4).min(input.len());
+        for (i, byte) in input.drain(..read).enumerate() {
+            unsafe { (buffer as *mut u8).add(i).write(byte) };
+        }
+        unsafe { *size = read };
+        if read < requested {
+            efi::Status::TIMEOUT
+        } else {
+            efi::Status::SUCCESS
+        }
+    }
+
+    extern "efiapi" fn poll(this: *mut Protocol) -> efi::Status {
+        match test_debug_port(this).input.borrow().is_empty() {
+            true => efi::Status::NOT_READY,
+            false => efi::Status::SUCCESS,
+        }
+    }
+
+    fn debug_port() -> (DebugPort, &'static mut TestDebugPort) {
+        let port = Box::leak(Box::new(TestDebugPort {
+            protocol: Protocol { reset, write, read, poll },
+            written: RefCell::new(Vec::new()),
+            input: RefCell::new(VecDeque::new()),
+            stalled: false,
+        }));
+        let port_ptr = port as *mut TestDebugPort as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<DebugPortProtocol, Protocol>()
+            .once()
+            .returning(move |_, _| Ok(unsafe { &mut (*(port_ptr as *mut TestDebugPort)).protocol }));
+        let debug_port = DebugPort::locate(&boot_services).unwrap().with_timeout(1000);
+        (debug_port, unsafe { &mut *(port_ptr as *mut TestDebugPort) })
+    }
+
+    #[test]
+    fn test_write() {
+        let (mut debug_port, port) = debug_port();
+        assert_eq!(4, debug_port.write(b"Hello").unwrap());
+        debug_port.write_all(b" World").unwrap();
+        assert_eq!(b"Hell World", port.written.borrow().as_slice());
+
+        port.written.borrow_mut().clear();
+        write!(debug_port, "a\nb{}\n", 1).unwrap();
+        assert_eq!(b"a\r\nb1\r\n", port.written.borrow().as_slice());
+
+        port.stalled = true;
+        assert_eq!(efi::Status::TIMEOUT, debug_port.write_all(b"Lost").unwrap_err());
+    }
+
+    #[test]
+    fn test_read() {
+        let (mut debug_port, port) = debug_port();
+        let mut buffer = [0; 6];
+        assert!(!debug_port.poll().unwrap());
+        assert_eq!(0, debug_port.poll_read(&mut buffer).unwrap());
+
+        port.input.borrow_mut().extend(b"0123456789");
+        assert!(debug_port.poll().unwrap());
+        assert_eq!(4, debug_port.poll_read(&mut buffer).unwrap());
+        assert_eq!(b"0123", &buffer[..4]);
+        assert_eq!(4, debug_port.read(&mut buffer).unwrap());
+        assert_eq!(b"4567", &buffer[..4]);
+
+        debug_port.reset().unwrap();
+        assert!(!debug_port.poll().unwrap());
+    }
+}
@@ -0,0 +1,667 @@
+//! Decompress protocol, and the UEFI compression format in Rust.
+//!
+//! [`Decompress`] unpacks data of the UEFI compression format with the protocol of the firmware. [`decompress`] does
+//! the same in Rust, also for the Tiano variant of the format, for firmware without the protocol or when unpacking
+//! sections the firmware does not know about:
+//!
+//! ```ignore
+//! let section = volume.read_section(&boot_services, &FILE_GUID, SectionType::COMPRESSION, 0)?;
+//! let data = decompress::decompress_section(&section)?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 19.1. Compression Algorithm Specification](https://uefi.org/specs/UEFI/2.10/19_Protocols_Compression_Algorithm_Specification.html)
+
+use alloc::{vec, vec::Vec};
+use core::{ffi::c_void, fmt};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use efi::protocols::decompress;
+
+type DecompressProtocol = decompress::Protocol;
+
+/// Size of the header of compressed data, its compressed and original sizes.
+const HEADER_SIZE: usize = 8;
+
+const BITBUFSIZ: u32 = 32;
+const THRESHOLD: usize = 3;
+/// Number of symbols of the char and length set: the bytes and the lengths of the matches.
+const NC: usize = 0xFF + 256 + 2 - THRESHOLD;
+const CBIT: u32 = 9;
+const TBIT: u32 = 5;
+/// Number of symbols of the position set, for the largest window.
+const MAXNP: usize = (1 << 5) - 1;
+/// Number of symbols of the extra set, which encodes the lengths of the codes of the char and length set.
+const NT: usize = 16 + 3;
+const NPT: usize = MAXNP;
+/// Number of nodes of the trees of the codes longer than the lookup tables.
+const NODES: usize = 2 * NC - 1;
+
+/// Variant of the UEFI compression format, which differ by the size of their window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Version {
+    /// The format of the specification and of the Decompress protocol, with an 8 KiB window.
+    Efi,
+    /// The format of the Tiano compression GUIDed sections, with a 512 KiB window.
+    Tiano,
+}
+
+impl Version {
+    /// Number of bits of the number of symbols of the position set.
+    fn p_bit(self) -> u32 {
+        match self {
+            Version::Efi => 4,
+            Version::Tiano => 5,
+        }
+    }
+}
+
+/// The compressed and original sizes of `source`, from its header.
+///
+/// Returns `INVALID_PARAMETER` if `source` is shorter than its header declares.
+pub fn get_info(source: &[u8]) -> Result<(usize, usize), EfiError> {
+    let header = source.get(..HEADER_SIZE).ok_or(efi::Status::INVALID_PARAMETER)?;
+    let compressed_size = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let original_size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    if source.len() - HEADER_SIZE < compressed_size {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    Ok((compressed_size, original_size))
+}
+
+/// Decompresses `source`, data of the UEFI compression format of `version` with its header.
+///
+/// Returns `INVALID_PARAMETER` if the data is malformed or truncated, and `OUT_OF_RESOURCES` if the original size
+/// cannot be allocated.
+pub fn decompress(source: &[u8], version: Version) -> Result<Vec<u8>, EfiError> {
+    let (compressed_size, original_size) = get_info(source)?;
+    let mut destination = Vec::new();
+    destination.try_reserve_exact(original_size).map_err(|_| efi::Status::OUT_OF_RESOURCES)?;
+    destination.resize(original_size, 0);
+    if original_size > 0 {
+        Decoder::new(&source[HEADER_SIZE..HEADER_SIZE + compressed_size], version).decode(&mut destination)?;
+    }
+    Ok(destination)
+}
+
+/// Compression type of a compression section without compression.
+pub const NOT_COMPRESSED: u8 = 0x00;
+/// Compression type of a compression section of the UEFI compression format.
+pub const STANDARD_COMPRESSION: u8 = 0x01;
+
+/// Decompresses the content of an `EFI_SECTION_COMPRESSION`, after its common header.
+pub fn decompress_section(section: &[u8]) -> Result<Vec<u8>, EfiError> {
+    let uncompressed_length = section.get(0..4).ok_or(efi::Status::INVALID_PARAMETER)?;
+    let uncompressed_length = u32::from_le_bytes(uncompressed_length.try_into().unwrap()) as usize;
+    let data = match section.get(4) {
+        Some(&NOT_COMPRESSED) => section[5..].get(..uncompressed_length).map(<[u8]>::to_vec),
+        Some(&STANDARD_COMPRESSION) => Some(decompress(&section[5..], Version::Efi)?),
+        Some(_) => return Err(efi::Status::UNSUPPORTED.into()),
+        None => None,
+    };
+    data.filter(|data| data.len() == uncompressed_length).ok_or(efi::Status::INVALID_PARAMETER.into())
+}
+
+/// Decompresses `source` with the protocol if it is installed, or else in Rust.
+pub fn decompress_with<B: BootServices>(boot_services: &B, source: &[u8]) -> Result<Vec<u8>, EfiError> {
+    match Decompress::locate(boot_services) {
+        Ok(mut protocol) => protocol.decompress(source),
+        Err(_) => decompress(source, Version::Efi),
+    }
+}
+
+/// A location where `make_table` stores a symbol or a node.
+#[derive(Clone, Copy)]
+enum Slot {
+    Table(usize),
+    Left(usize),
+    Right(usize),
+}
+
+impl Slot {
+    fn entry<'t>(self, table: &'t mut [u16], left: &'t mut [u16], right: &'t mut [u16]) -> &'t mut u16 {
+        match self {
+            Slot::Table(index) => &mut table[index],
+            Slot::Left(index) => &mut left[index],
+            Slot::Right(index) => &mut right[index],
+        }
+    }
+}
+
+/// Decoder of the blocks of compressed data, as `EFI_DECOMPRESS_PROTOCOL.Decompress` of the specification.
+struct Decoder<'a> {
+    source: &'a [u8],
+    position: usize,
+    bit_buf: u32,
+    sub_bit_buf: u32,
+    bit_count: u32,
+    /// Number of symbols left in the block.
+    block_size: u16,
+    p_bit: u32,
+    left: Vec<u16>,
+    right: Vec<u16>,
+    c_len: Vec<u8>,
+    pt_len: [u8; NPT],
+    c_table: Vec<u16>,
+    pt_table: Vec<u16>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(source: &'a [u8], version: Version) -> Self {
+        let mut decoder = Self {
+            source,
+            position: 0,
+            bit_buf: 0,
+            sub_bit_buf: 0,
+            bit_count: 0,
+            block_size: 0,
+            p_bit: version.p_bit(),
+            left: vec![0; NODES],
+            right: vec![0; NODES],
+            c_len: vec![0; NC],
+            pt_len: [0; NPT],
+            c_table: vec![0; 1 << 12],
+            pt_table: vec![0; 1 << 8],
+        };
+        decoder.fill_buf(BITBUFSIZ);
+        decoder
+    }
+
+    /// Shifts `bits` bits out of the bit buffer and refills it, with zeros past the end of the source.
+    fn fill_buf(&mut self, mut bits: u32) {
+        self.bit_buf = ((self.bit_buf as u64) << bits) as u32;
+        while bits > self.bit_count {
+            bits -= self.bit_count;
+            self.bit_buf |= ((self.sub_bit_buf as u64) << bits) as u32;
+            self.sub_bit_buf = self.source.get(self.position).copied().unwrap_or_default() as u32;
+            self.position += 1;
+            self.bit_count = 8;
+        }
+        self.bit_count -= bits;
+        self.bit_buf |= self.sub_bit_buf >> self.bit_count;
+    }
+
+    /// Whether more bits were consumed than the source has, the data being truncated.
+    fn overrun(&self) -> bool {
+        // The bit buffer and the current byte hold bits not consumed yet.
+        let consumed = (self.position * 8) as u64 - (self.bit_count + BITBUFSIZ) as u64;
+        consumed > (self.source.len() * 8) as u64
+    }
+
+    fn get_bits(&mut self, bits: u32) -> u32 {
+        let value = ((self.bit_buf as u64) >> (BITBUFSIZ - bits)) as u32;
+        self.fill_buf(bits);
+        value
+    }
+
+    /// Walks the tree from `node` along the bits of the bit buffer from `mask`, down to a symbol below `limit`.
+    fn walk(&self, mut node: u16, limit: usize, mut mask: u32) -> Result<u16, EfiError> {
+        while node as usize >= limit {
+            if mask == 0 || node as usize >= NODES {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            node = match self.bit_buf & mask {
+                0 => self.left[node as usize],
+                _ => self.right[node as usize],
+            };
+            mask >>= 1;
+        }
+        Ok(node)
+    }
+
+    /// Reads the lengths of the codes of the extra or position set, of `count` symbols.
+    ///
+    /// After the symbol `special`, 2 bits give a number of symbols without code.
+    fn read_pt_len(&mut self, count: usize, bits: u32, special: Option<usize>) -> Result<(), EfiError> {
+        let number = self.get_bits(bits) as usize;
+        if number > NPT {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        if number == 0 {
+            // A single symbol, of a code of 0 bits.
+            let symbol = self.get_bits(bits) as u16;
+            self.pt_table.fill(symbol);
+            self.pt_len[..count].fill(0);
+            return Ok(());
+        }
+        let mut index = 0;
+        while index < number {
+            // The lengths up to 6 are on 3 bits, longer ones are 7 followed by as many ones as their excess.
+            let mut length = self.bit_buf >> (BITBUFSIZ - 3);
+            if length == 7 {
+                let mut mask = 1 << (BITBUFSIZ - 1 - 3);
+                while mask & self.bit_buf != 0 {
+                    mask >>= 1;
+                    length += 1;
+                }
+            }
+            self.fill_buf(if length < 7 { 3 } else { length - 3 });
+            self.pt_len[index] = length as u8;
+            index += 1;
+            if Some(index) == special {
+                for _ in 0..self.get_bits(2) {
+                    if index < NPT {
+                        self.pt_len[index] = 0;
+                        index += 1;
+                    }
+                }
+            }
+        }
+        if index < count {
+            self.pt_len[index..count].fill(0);
+        }
+        make_table(&self.pt_len[..count], 8, &mut self.pt_table, &mut self.left, &mut self.right)
+    }
+
+    /// Reads the lengths of the codes of the char and length set, encoded with the extra set.
+    fn read_c_len(&mut self) -> Result<(), EfiError> {
+        let number = self.get_bits(CBIT) as usize;
+        if number == 0 {
+            let symbol = self.get_bits(CBIT) as u16;
+            self.c_len.fill(0);
+            self.c_table.fill(symbol);
+            return Ok(());
+        }
+        let mut index = 0;
+        while index < number.min(NC) {
+            let symbol = self.pt_table[(self.bit_buf >> (BITBUFSIZ - 8)) as usize];
+            let symbol = self.walk(symbol, NT, 1 << (BITBUFSIZ - 1 - 8))?;
+            self.fill_buf(self.pt_len[symbol as usize] as u32);
+            // The symbols 0 to 2 are runs of symbols without code, the others lengths from 1.
+            let zeros = match symbol {
+                0 => 1,
+                1 => self.get_bits(4) as usize + 3,
+                2 => self.get_bits(CBIT) as usize + 20,
+                length => {
+                    self.c_len[index] = length as u8 - 2;
+                    index += 1;
+                    continue;
+                }
+            };
+            let end = (index + zeros).min(NC);
+            self.c_len[index..end].fill(0);
+            index = end;
+        }
+        self.c_len[index..].fill(0);
+        make_table(&self.c_len, 12, &mut self.c_table, &mut self.left, &mut self.right)
+    }
+
+    /// Decodes a symbol of the char and length set, reading the tables of a new block first if needed.
+    fn decode_c(&mut self) -> Result<u16, EfiError> {
+        if self.block_size == 0 {
+            self.block_size = self.get_bits(16) as u16;
+            self.read_pt_len(NT, TBIT, Some(3))?;
+            self.read_c_len()?;
+            self.read_pt_len(MAXNP, self.p_bit, None)?;
+        }
+        self.block_size = self.block_size.wrapping_sub(1);
+        let symbol = self.c_table[(self.bit_buf >> (BITBUFSIZ - 12)) as usize];
+        let symbol = self.walk(symbol, NC, 1 << (BITBUFSIZ - 1 - 12))?;
+        self.fill_buf(self.c_len[symbol as usize] as u32);
+        Ok(symbol)
+    }
+
+    /// Decodes the distance of a match, minus 1.
+    fn decode_p(&mut self) -> Result<usize, EfiError> {
+        let symbol = self.pt_table[(self.bit_buf >> (BITBUFSIZ - 8)) as usize];
+        let symbol = self.walk(symbol, MAXNP, 1 << (BITBUFSIZ - 1 - 8))? as u32;
+        self.fill_buf(self.pt_len[symbol as usize] as u32);
+        // The symbol is the number of significant bits of the distance, whose most significant bit is implicit.
+        Ok(match symbol {
+            0 | 1 => symbol as usize,
+            _ => ((1 << (symbol - 1)) + self.get_bits(symbol - 1)) as usize,
+        })
+    }
+
+    fn decode(&mut self, destination: &mut [u8]) -> Result<(), EfiError> {
+        let mut output = 0;
+        while output < destination.len() {
+            let symbol = self.decode_c()? as usize;
+            let distance = match symbol {
+                0..=0xFF => None,
+                _ => Some(self.decode_p()?),
+            };
+            if self.overrun() {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            match distance {
+                None => {
+                    destination[output] = symbol as u8;
+                    output += 1;
+                }
+                Some(distance) => {
+                    let length = symbol - (0x100 - THRESHOLD);
+                    let start = output.checked_sub(distance + 1).ok_or(efi::Status::INVALID_PARAMETER)?;
+                    // The match may overlap the bytes it produces.
+                    for from in start..start + length.min(destination.len() - output) {
+                        destination[output] = destination[from];
+                        output += 1;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the lookup table of `table_bits` bits of the canonical code of the lengths `bit_len`, and the trees of the
+/// longer codes.
+fn make_table(
+    bit_len: &[u8],
+    table_bits: usize,
+    table: &mut [u16],
+    left: &mut [u16],
+    right: &mut [u16],
+) -> Result<(), EfiError> {
+    let mut count = [0u32; 17];
+    for &length in bit_len {
+        *count.get_mut(length as usize).ok_or(efi::Status::INVALID_PARAMETER)? += 1;
+    }
+    // The first code of each length, on 16 bits, the code being complete.
+    let mut start = [0u32; 18];
+    for length in 1..=16 {
+        start[length + 1] = start[length] + (count[length] << (16 - length));
+    }
+    if start[17] != 1 << 16 {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    let ju_bits = 16 - table_bits;
+    let mut weight = [0u32; 17];
+    for length in 1..=16 {
+        if length <= table_bits {
+            start[length] >>= ju_bits;
+            weight[length] = 1 << (table_bits - length);
+        } else {
+            weight[length] = 1 << (16 - length);
+        }
+    }
+    let table_length = 1 << table_bits;
+    let end = (start[table_bits + 1] >> ju_bits) as usize;
+    if end != 0 && end < table_length {
+        table[end..table_length].fill(0);
+    }
+
+    let mut avail = bit_len.len();
+    let mask = 1 << (15 - table_bits);
+    for (symbol, &length) in bit_len.iter().enumerate() {
+        let length = length as usize;
+        if length == 0 {
+            continue;
+        }
+        let next_code = start[length] + weight[length];
+        if length <= table_bits {
+            if next_code as usize > table_length {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            table[start[length] as usize..next_code as usize].fill(symbol as u16);
+        } else {
+            // The code continues in a tree from the entry of its first bits.
+            let mut code = start[length];
+            let mut slot = Slot::Table((code >> ju_bits) as usize);
+            for _ in 0..length - table_bits {
+                let mut node = *slot.entry(table, left, right) as usize;
+                if node == 0 && avail < NODES {
+                    left[avail] = 0;
+                    right[avail] = 0;
+                    *slot.entry(table, left, right) = avail as u16;
+                    node = avail;
+                    avail += 1;
+                }
+                if node < NODES {
+                    slot = match code & mask {
+                        0 => Slot::Left(node),
+                        _ => Slot::Right(node),
+                    };
+                }
+                code <<= 1;
+            }
+            *slot.entry(table, left, right) = symbol as u16;
+        }
+        start[length] = next_code;
+    }
+    Ok(())
+}
+
+/// Typed access to the Decompress protocol.
+pub struct Decompress(&'static mut DecompressProtocol);
+
+impl Decompress {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::Decompress, None).map(Self)
+    }
+
+    fn this(&mut self) -> *mut DecompressProtocol {
+        self.0 as *mut DecompressProtocol
+    }
+
+    /// The sizes of the decompressed data of `source` and of the scratch buffer to decompress it.
+    pub fn get_info(&mut self, source: &[u8]) -> Result<(u32, u32), EfiError> {
+        let source_size = u32::try_from(source.len()).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+        let (mut destination_size, mut scratch_size) = (0, 0);
+        // The source is only read by the protocol.
+        match unsafe {
+            r_efi::efi_call(
+                self.0.get_info,
+                (self.this(), source.as_ptr() as *mut c_void, source_size, &mut destination_size, &mut scratch_size),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok((destination_size, scratch_size)),
+        }
+    }
+
+    /// Decompresses `source`, data of the UEFI compression format with its header.
+    pub fn decompress(&mut self, source: &[u8]) -> Result<Vec<u8>, EfiError> {
+        let (destination_size, scratch_size) = self.get_info(source)?;
+        let mut destination = vec![0u8; destination_size as usize];
+        let mut scratch = vec![0u8; scratch_size as usize];
+        match unsafe {
+            r_efi::efi_call(
+                self.0.decompress,
+                (
+                    self.this(),
+                    source.as_ptr() as *mut c_void,
+                    source.len() as u32,
+                    destination.as_mut_ptr() as *mut c_void,
+                    destination_size,
+                    scratch.as_mut_ptr() as *mut c_void,
+                    scratch_size,
+                ),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(destination),
+        }
+    }
+}
+
+impl From<&'static mut DecompressProtocol> for Decompress {
+    fn from(protocol: &'static mut DecompressProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for Decompress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decompress").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+    use core::slice;
+
+    /// Writer of the bits of a compressed stream, from the most significant.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        bits: usize,
+    }
+
+    impl BitWriter {
+        fn put(mut self, value: u32, count: u32) -> Self {
+            for bit in (0..count).rev() {
+                if self.bits == self.bytes.len() * 8 {
+                    self.bytes.push(0);
+                }
+                *self.bytes.last_mut().unwrap() |= (((value >> bit) & 1) as u8) << (7 - self.bits % 8);
+                self.bits += 1;
+            }
+            self
+        }
+
+        /// A block of `size` symbols whose sets have a single symbol each, coded on 0 bits.
+        fn single_symbol_block(self, size: u32, c: u32, p: u32, p_bit: u32) -> Self {
+            self.put(size, 16).put(0, TBIT).put(0, TBIT).put(0, CBIT).put(c, CBIT).put(0, p_bit).put(p, p_bit)
+        }
+
+        fn stream(self, original_size: u32) -> Vec<u8> {
+            let mut stream = (self.bytes.len() as u32).to_le_bytes().to_vec();
+            stream.extend_from_slice(&original_size.to_le_bytes());
+            stream.extend_from_slice(&self.bytes);
+            stream
+        }
+    }
+
+    /// "xyxyxy" as "x", "y" and a match of 4 bytes at a distance of 2.
+    fn matches(version: Version) -> Vec<u8> {
+        let p_bit = version.p_bit();
+        BitWriter::default()
+            .single_symbol_block(1, b'x' as u32, 0, p_bit)
+            .single_symbol_block(1, b'y' as u32, 0, p_bit)
+            .single_symbol_block(1, 0x100 - THRESHOLD as u32 + 4, 1, p_bit)
+            .stream(6)
+    }
+
+    /// "abba" with the codes 0 for "a" and 1 for "b", whose lengths are coded with the extra set.
+    fn huffman() -> Vec<u8> {
+        BitWriter::default()
+            .put(4, 16)
+            // The extra symbols 2 and 3 have codes of 1 bit, the special 2 bits after the third length skip none.
+            .put(4, TBIT)
+            .put(0, 3)
+            .put(0, 3)
+            .put(1, 3)
+            .put(0, 2)
+            .put(1, 3)
+            // 97 symbols without code, then "a" and "b" of 1 bit.
+            .put(99, CBIT)
+            .put(0, 1)
+            .put(97 - 20, CBIT)
+            .put(1, 1)
+            .put(1, 1)
+            .put(0, 4)
+            .put(0, 4)
+            .put(0b0110, 4)
+            .stream(4)
+    }
+
+    #[test]
+    fn test_decompress() {
+        assert_eq!(b"xyxyxy", decompress(&matches(Version::Efi), Version::Efi).unwrap().as_slice());
+        assert_eq!(b"xyxyxy", decompress(&matches(Version::Tiano), Version::Tiano).unwrap().as_slice());
+        assert_eq!(b"abba", decompress(&huffman(), Version::Efi).unwrap().as_slice());
+        assert_eq!((huffman().len() - HEADER_SIZE, 4), get_info(&huffman()).unwrap());
+        assert!(decompress(&[0, 0, 0, 0, 0, 0, 0, 0], Version::Efi).unwrap().is_empty());
+
+        let mut section = vec![4, 0, 0, 0, STANDARD_COMPRESSION];
+        section.extend_from_slice(&huffman());
+        assert_eq!(b"abba", decompress_section(&section).unwrap().as_slice());
+        assert_eq!(b"raw", decompress_section(&[3, 0, 0, 0, NOT_COMPRESSED, b'r', b'a', b'w']).unwrap().as_slice());
+        assert_eq!(efi::Status::UNSUPPORTED, decompress_section(&[3, 0, 0, 0, 2]).unwrap_err());
+        section[0] = 5;
+        assert_eq!(efi::Status::INVALID_PARAMETER, decompress_section(&section).unwrap_err());
+    }
+
+    #[test]
+    fn test_malformed_data() {
+        let stream = huffman();
+        assert_eq!(efi::Status::INVALID_PARAMETER, get_info(&stream[..7]).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, decompress(&stream[..stream.len() - 1], Version::Efi).unwrap_err());
+
+        // A match before the start of the data.
+        let stream = BitWriter::default().single_symbol_block(1, 0x100, 1, 4).stream(3);
+        assert_eq!(efi::Status::INVALID_PARAMETER, decompress(&stream, Version::Efi).unwrap_err());
+
+        // An incomplete code of the extra set.
+        let stream = BitWriter::default().put(1, 16).put(1, TBIT).put(1, 3).stream(1);
+        assert_eq!(efi::Status::INVALID_PARAMETER, decompress(&stream, Version::Efi).unwrap_err());
+
+        // Too many lengths of the position set.
+        let stream = BitWriter::default().put(1, 16).put(0, TBIT).put(0, TBIT).put(0, CBIT).put(0, CBIT).put(31, 5);
+        assert_eq!(efi::Status::INVALID_PARAMETER, decompress(&stream.stream(1), Version::Tiano).unwrap_err());
+
+        // More data than the stream has.
+        let mut stream = huffman();
+        stream[4] = 5;
+        assert_eq!(efi::Status::INVALID_PARAMETER, decompress(&stream, Version::Efi).unwrap_err());
+    }
+
+    /// Protocol decompressing in Rust, with a scratch buffer of 16 bytes.
+    extern "efiapi" fn protocol_get_info(
+        _this: *mut DecompressProtocol,
+        source: *mut c_void,
+        source_size: u32,
+        destination_size: *mut u32,
+        scratch_size: *mut u32,
+    ) -> efi::Status {
+        let source = unsafe { slice::from_raw_parts(source as *const u8, source_size as usize) };
+        match get_info(source) {
+            Ok((_, original_size)) => {
+                unsafe {
+                    *destination_size = original_size as u32;
+                    *scratch_size = 16;
+                }
+                efi::Status::SUCCESS
+            }
+            Err(status) => status.into(),
+        }
+    }
+
+    extern "efiapi" fn protocol_decompress(
+        _this: *mut DecompressProtocol,
+        source: *mut c_void,
+        source_size: u32,
+        destination: *mut c_void,
+        destination_size: u32,
+        _scratch: *mut c_void,
+        scratch_size: u32,
+    ) -> efi::Status {
+        let source = unsafe { slice::from_raw_parts(source as *const u8, source_size as usize) };
+        let destination = unsafe { slice::from_raw_parts_mut(destination as *mut u8, destination_size as usize) };
+        match decompress(source, Version::Efi) {
+            _ if scratch_size < 16 => efi::Status::INVALID_PARAMETER,
+            Ok(data) if data.len() == destination.len() => {
+                destination.copy_from_slice(&data);
+                efi::Status::SUCCESS
+            }
+            Ok(_) => efi::Status::BUFFER_TOO_SMALL,
+            Err(status) => status.into(),
+        }
+    }
+
+    #[test]
+    fn test_decompress_protocol() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_locate_protocol::<protocol_handler::Decompress, DecompressProtocol>().returning(|_, _| {
+            Ok(Box::leak(Box::new(DecompressProtocol { get_info: protocol_get_info, decompress: protocol_decompress })))
+        });
+        let mut protocol = Decompress::locate(&boot_services).unwrap();
+        assert_eq!((4, 16), protocol.get_info(&huffman()).unwrap());
+        assert_eq!(b"abba", protocol.decompress(&huffman()).unwrap().as_slice());
+        assert_eq!(efi::Status::INVALID_PARAMETER, protocol.decompress(&huffman()[..4]).unwrap_err());
+        assert_eq!(b"xyxyxy", decompress_with(&boot_services, &matches(Version::Efi)).unwrap().as_slice());
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::Decompress, DecompressProtocol>()
+            .returning(|_, _| Err(efi::Status::NOT_FOUND.into()));
+        assert_eq!(b"abba", decompress_with(&boot_services, &huffman()).unwrap().as_slice());
+    }
+}
@@ -0,0 +1,220 @@
+//! Deferred Image Load protocol.
+//!
+//! The images whose execution the security handlers deferred until the end of DXE are listed by the protocol, so
+//! that the boot manager loads them once the platform is locked, usually at `ReadyToBoot`:
+//!
+//! ```ignore
+//! for deferred_image_load in deferred_image_load::instances(&boot_services)? {
+//!     for image in deferred_image_load.images().iter().filter(|image| !image.boot_option) {
+//!         load_and_start(image.device_path, image.image)?;
+//!     }
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 32. Secure Boot and Driver Signing](https://uefi.org/specs/UEFI/2.10/32_Secure_Boot_and_Driver_Signing.html)
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, fmt, ops::Deref, ptr, slice};
+
+use boot_services::{
+    protocol_handler::{HandleBuffer, Protocol as ProtocolTrait},
+    BootServices,
+};
+use device_path::DevicePath;
+use r_efi::efi;
+use status::EfiError;
+
+use efi::protocols::device_path::Protocol as DevicePathProtocol;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x15853d7c, 0x3ddf, 0x43e0, 0xa1, 0xcb, &[0xeb, 0xf8, 0x5b, 0x8f, 0x87, 0x2c]);
+
+pub type ProtocolGetImageInfo = extern "efiapi" fn(
+    *mut Protocol,
+    usize,
+    *mut *mut DevicePathProtocol,
+    *mut *mut c_void,
+    *mut usize,
+    *mut efi::Boolean,
+) -> efi::Status;
+
+/// FFI definition of `EFI_DEFERRED_IMAGE_LOAD_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub get_image_info: ProtocolGetImageInfo,
+}
+
+/// Deferred Image Load protocol.
+pub struct DeferredImageLoadProtocol;
+
+unsafe impl ProtocolTrait for DeferredImageLoadProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for DeferredImageLoadProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// An image whose execution was deferred, owned by the protocol.
+#[derive(Clone, Copy)]
+pub struct DeferredImage<'a> {
+    /// The device path of the image, `None` if it is malformed.
+    pub device_path: Option<&'a DevicePath>,
+    /// The image, empty if the protocol only has its device path.
+    pub image: &'a [u8],
+    /// Whether the image is a boot option rather than a driver or an option ROM.
+    pub boot_option: bool,
+}
+
+impl fmt::Debug for DeferredImage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeferredImage")
+            .field("device_path", &self.device_path.is_some())
+            .field("image_size", &self.image.len())
+            .field("boot_option", &self.boot_option)
+            .finish()
+    }
+}
+
+/// Typed access to an instance of the Deferred Image Load protocol.
+pub struct DeferredImageLoad(&'static mut Protocol);
+
+impl DeferredImageLoad {
+    /// Gets the instance of the protocol installed on the handle of a security handler.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &DeferredImageLoadProtocol).map(Self)
+    }
+
+    fn protocol_ptr(&self) -> *mut Protocol {
+        &*self.0 as *const Protocol as *mut Protocol
+    }
+
+    /// The deferred image at `index`, from 0.
+    ///
+    /// Returns `NOT_FOUND` past the last image.
+    pub fn image_info(&self, index: usize) -> Result<DeferredImage<'_>, EfiError> {
+        let mut device_path = ptr::null_mut();
+        let mut image = ptr::null_mut();
+        let mut image_size = 0;
+        let mut boot_option = efi::Boolean::FALSE;
+        match (self.0.get_image_info)(
+            self.protocol_ptr(),
+            index,
+            &mut device_path,
+            &mut image,
+            &mut image_size,
+            &mut boot_option,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(DeferredImage {
+                //SAFETY: The device path is owned by the protocol.
+                device_path: unsafe { DevicePath::from_ptr(device_path) }.ok(),
+                image: match image.is_null() {
+                    true => &[],
+                    //SAFETY: The image is a buffer of `image_size` bytes owned by the protocol.
+                    false => unsafe { slice::from_raw_parts(image as *const u8, image_size) },
+                },
+                boot_option: boot_option.into(),
+            }),
+        }
+    }
+
+    /// The deferred images, until the first one that cannot be read.
+    pub fn images(&self) -> Vec<DeferredImage<'_>> {
+        (0..).map_while(|index| self.image_info(index).ok()).collect()
+    }
+}
+
+impl From<&'static mut Protocol> for DeferredImageLoad {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for DeferredImageLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeferredImageLoad").finish_non_exhaustive()
+    }
+}
+
+/// The instances of the protocol, from the handles that support it.
+pub fn instances<B: BootServices>(boot_services: &B) -> Result<Vec<DeferredImageLoad>, EfiError> {
+    let handles = HandleBuffer::supporting(boot_services, &DeferredImageLoadProtocol)?;
+    Ok((&handles).into_iter().filter_map(|handle| DeferredImageLoad::get(boot_services, handle).ok()).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use boot_services::mock::InMemoryBootServices;
+    use device_path::{node_types::VendorHardware, DevicePathBuf, DevicePathBuilder};
+
+    /// A security handler with deferred images, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestSecurityHandler {
+        protocol: Protocol,
+        images: Vec<(DevicePathBuf, Vec<u8>, bool)>,
+    }
+
+    extern "efiapi" fn get_image_info(
+        this: *mut Protocol,
+        index: usize,
+        device_path: *mut *mut DevicePathProtocol,
+        image: *mut *mut c_void,
+        image_size: *mut usize,
+        boot_option: *mut efi::Boolean,
+    ) -> efi::Status {
+        let handler = unsafe { &mut *(this as *mut TestSecurityHandler) };
+        let Some((path, data, is_boot_option)) = handler.images.get_mut(index) else {
+            return efi::Status::NOT_FOUND;
+        };
+        unsafe {
+            *device_path = path.as_ptr() as *mut DevicePathProtocol;
+            *image = if data.is_empty() { ptr::null_mut() } else { data.as_mut_ptr() as *mut c_void };
+            *image_size = data.len();
+            *boot_option = (*is_boot_option).into();
+        }
+        efi::Status::SUCCESS
+    }
+
+    fn device_path(id: u8) -> DevicePathBuf {
+        DevicePathBuilder::new()
+            .push(&VendorHardware::new(efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]), &[id]))
+            .build()
+    }
+
+    #[test]
+    fn test_images() {
+        let boot_services = InMemoryBootServices::new();
+        let handler = Box::leak(Box::new(TestSecurityHandler {
+            protocol: Protocol { get_image_info },
+            images: vec![(device_path(1), vec![b'M', b'Z'], false), (device_path(2), Vec::new(), true)],
+        }));
+        unsafe {
+            boot_services.install_protocol_interface_unchecked(None, &PROTOCOL_GUID, handler as *mut _ as *mut c_void)
+        }
+        .unwrap();
+
+        let instances = instances(&boot_services).unwrap();
+        assert_eq!(1, instances.len());
+        let images = instances[0].images();
+        assert_eq!(2, images.len());
+        assert_eq!(
+            (Some(&*device_path(1)), &b"MZ"[..], false),
+            (images[0].device_path, images[0].image, images[0].boot_option)
+        );
+        assert_eq!(
+            (Some(&*device_path(2)), &[][..], true),
+            (images[1].device_path, images[1].image, images[1].boot_option)
+        );
+        assert_eq!(efi::Status::NOT_FOUND, instances[0].image_info(2).unwrap_err());
+    }
+}
@@ -0,0 +1,658 @@
+//! Driver Health protocol.
+//!
+//! [`DriverHealth`] queries the health of the controllers of a driver, and [`platform_health`] collects it from all
+//! the drivers, as the boot manager does before booting. [`recover`] then runs the repairs and reconnections the
+//! drivers ask for:
+//!
+//! ```ignore
+//! let health = driver_health::recover(&boot_services, None)?;
+//! if health.status() == HealthStatus::REBOOT_REQUIRED {
+//!     runtime_services.reset_system(ResetType::Cold, efi::Status::SUCCESS, None);
+//! }
+//! ```
+//!
+//! [`HealthReporter`] is used by a driver to produce the protocol, from the health it reports for its controllers.
+//!
+//! [UEFI Spec Documentation: 11.10. EFI Driver Health Protocol](https://uefi.org/specs/UEFI/2.10/11_Protocols_UEFI_Driver_Model.html#efi-driver-health-protocol)
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{cell::RefCell, fmt, mem, ops::Deref, ptr, slice};
+
+use boot_services::{
+    allocation::MemoryType,
+    protocol_handler::{ConnectError, HandleBuffer, HandleSearchType, InstalledProtocol, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::hii::{HiiHandle, StringId};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x2a534210, 0x9280, 0x41d8, 0xae, 0x79, &[0xca, 0xda, 0x01, 0xa2, 0xb1, 0x27]);
+
+/// Notification of the progress of a repair, `value` out of `limit`.
+pub type ProtocolRepairNotify = extern "efiapi" fn(usize, usize) -> efi::Status;
+
+pub type ProtocolGetHealthStatus = extern "efiapi" fn(
+    *mut Protocol,
+    efi::Handle,
+    efi::Handle,
+    *mut HealthStatus,
+    *mut *mut Message,
+    *mut HiiHandle,
+) -> efi::Status;
+
+pub type ProtocolRepair =
+    extern "efiapi" fn(*mut Protocol, efi::Handle, efi::Handle, Option<ProtocolRepairNotify>) -> efi::Status;
+
+/// FFI definition of `EFI_DRIVER_HEALTH_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub get_health_status: ProtocolGetHealthStatus,
+    pub repair: ProtocolRepair,
+}
+
+/// Driver Health protocol, used to consume the protocol.
+pub struct DriverHealthProtocol;
+
+unsafe impl ProtocolTrait for DriverHealthProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for DriverHealthProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Driver Health protocol with a [`HealthReporter`] interface, used to produce the protocol.
+pub struct DriverHealthProducer;
+
+unsafe impl ProtocolTrait for DriverHealthProducer {
+    type Interface = HealthReporter;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for DriverHealthProducer {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Health of a controller, `EFI_DRIVER_HEALTH_STATUS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct HealthStatus(pub u32);
+
+impl HealthStatus {
+    pub const HEALTHY: HealthStatus = HealthStatus(0);
+    /// The controller can be repaired with [`DriverHealth::repair`].
+    pub const REPAIR_REQUIRED: HealthStatus = HealthStatus(1);
+    /// The controller must be configured by the user, with the form of its report.
+    pub const CONFIGURATION_REQUIRED: HealthStatus = HealthStatus(2);
+    pub const FAILED: HealthStatus = HealthStatus(3);
+    /// The controller must be disconnected and connected again.
+    pub const RECONNECT_REQUIRED: HealthStatus = HealthStatus(4);
+    pub const REBOOT_REQUIRED: HealthStatus = HealthStatus(5);
+
+    /// Rank of the status from the healthy one, unknown statuses being failures.
+    fn severity(self) -> u8 {
+        match self {
+            HealthStatus::HEALTHY => 0,
+            HealthStatus::CONFIGURATION_REQUIRED => 1,
+            HealthStatus::REPAIR_REQUIRED => 2,
+            HealthStatus::RECONNECT_REQUIRED => 3,
+            HealthStatus::REBOOT_REQUIRED => 4,
+            _ => 5,
+        }
+    }
+
+    /// The most severe of the statuses, `HEALTHY` if there are none.
+    pub fn worst(statuses: impl IntoIterator<Item = HealthStatus>) -> HealthStatus {
+        statuses.into_iter().max_by_key(|status| status.severity()).unwrap_or(HealthStatus::HEALTHY)
+    }
+}
+
+/// A message about the health of a controller, `EFI_DRIVER_HEALTH_HII_MESSAGE`.
+///
+/// The text is the string `string_id` of the package list `hii_handle`, `message_code` is specific to the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Message {
+    pub hii_handle: HiiHandle,
+    pub string_id: StringId,
+    pub message_code: u64,
+}
+
+/// The health of a controller, with the messages and the configuration form the driver gives for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub messages: Vec<Message>,
+    /// Package list of the form to configure the controller, for `CONFIGURATION_REQUIRED`.
+    pub form: Option<HiiHandle>,
+}
+
+impl HealthReport {
+    pub fn new(status: HealthStatus) -> Self {
+        Self { status, messages: Vec::new(), form: None }
+    }
+
+    pub fn with_message(mut self, hii_handle: HiiHandle, string_id: StringId, message_code: u64) -> Self {
+        self.messages.push(Message { hii_handle, string_id, message_code });
+        self
+    }
+
+    pub fn with_form(mut self, form: HiiHandle) -> Self {
+        self.form = Some(form);
+        self
+    }
+}
+
+fn handle_or_null(handle: Option<efi::Handle>) -> efi::Handle {
+    handle.unwrap_or(ptr::null_mut())
+}
+
+/// Typed access to an instance of the Driver Health protocol.
+pub struct DriverHealth(&'static mut Protocol);
+
+impl DriverHealth {
+    /// Gets the instance of the protocol installed on the driver binding handle of a driver.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, driver_handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(driver_handle, &DriverHealthProtocol).map(Self)
+    }
+
+    fn protocol_ptr(&self) -> *mut Protocol {
+        &*self.0 as *const Protocol as *mut Protocol
+    }
+
+    /// The health of all the controllers of the driver.
+    pub fn status(&self) -> Result<HealthStatus, EfiError> {
+        let mut status = HealthStatus::HEALTHY;
+        match (self.0.get_health_status)(
+            self.protocol_ptr(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut status,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(status),
+        }
+    }
+
+    /// The health of a controller managed by the driver, or of its child when `child_handle` is set.
+    ///
+    /// Returns `UNSUPPORTED` if the driver does not manage the controller. The message list is allocated by the
+    /// driver, the boot services are used to free it once copied.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn controller_health<B: BootServices>(
+        &self,
+        boot_services: &B,
+        controller_handle: efi::Handle,
+        child_handle: Option<efi::Handle>,
+    ) -> Result<HealthReport, EfiError> {
+        let mut status = HealthStatus::HEALTHY;
+        let mut messages = ptr::null_mut();
+        let mut form = ptr::null_mut();
+        match (self.0.get_health_status)(
+            self.protocol_ptr(),
+            controller_handle,
+            handle_or_null(child_handle),
+            &mut status,
+            &mut messages,
+            &mut form,
+        ) {
+            s if s.is_error() => return Err(s.into()),
+            _ => (),
+        }
+        let mut report = HealthReport::new(status);
+        report.form = (!form.is_null()).then_some(form);
+        if !messages.is_null() {
+            //SAFETY: The list allocated by the driver ends with a message without HII handle.
+            let count = (0..).take_while(|&i| !unsafe { &*messages.add(i) }.hii_handle.is_null()).count();
+            report.messages = unsafe { slice::from_raw_parts(messages, count) }.to_vec();
+            let _ = boot_services.free_pool(messages as *mut u8);
+        }
+        Ok(report)
+    }
+
+    /// Repairs a controller whose health is `REPAIR_REQUIRED`, `notify` is called with the progress of the repair.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn repair(
+        &self,
+        controller_handle: efi::Handle,
+        child_handle: Option<efi::Handle>,
+        notify: Option<ProtocolRepairNotify>,
+    ) -> Result<(), EfiError> {
+        match (self.0.repair)(self.protocol_ptr(), controller_handle, handle_or_null(child_handle), notify) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for DriverHealth {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for DriverHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DriverHealth").finish_non_exhaustive()
+    }
+}
+
+/// The health of a controller of a driver, as collected by [`platform_health`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthEntry {
+    /// Driver binding handle of the driver.
+    pub driver_handle: efi::Handle,
+    /// The controller, `None` for the health of all the controllers of the driver.
+    pub controller_handle: Option<efi::Handle>,
+    pub report: HealthReport,
+}
+
+/// The health of the platform, the unhealthy controllers of all the drivers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlatformHealth {
+    entries: Vec<HealthEntry>,
+}
+
+impl PlatformHealth {
+    /// The most severe status of the drivers.
+    pub fn status(&self) -> HealthStatus {
+        HealthStatus::worst(self.entries.iter().map(|entry| entry.report.status))
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.status() == HealthStatus::HEALTHY
+    }
+
+    pub fn entries(&self) -> &[HealthEntry] {
+        &self.entries
+    }
+
+    /// The entries of a status, such as the controllers to configure.
+    pub fn entries_with(&self, status: HealthStatus) -> impl Iterator<Item = &HealthEntry> {
+        self.entries.iter().filter(move |entry| entry.report.status == status)
+    }
+}
+
+/// Collects the health of the drivers that produce the protocol.
+///
+/// The controllers of an unhealthy driver are searched among all the handles, a driver whose unhealthy controllers
+/// are not found has an entry for all its controllers.
+pub fn platform_health<B: BootServices>(boot_services: &B) -> Result<PlatformHealth, EfiError> {
+    let mut health = PlatformHealth::default();
+    let drivers = match HandleBuffer::supporting(boot_services, &DriverHealthProtocol) {
+        Ok(drivers) => drivers,
+        Err(status) if status == efi::Status::NOT_FOUND => return Ok(health),
+        Err(status) => return Err(status),
+    };
+    let mut controllers = None;
+    for driver_handle in &drivers {
+        let Ok(driver_health) = DriverHealth::get(boot_services, driver_handle) else {
+            continue;
+        };
+        let status = driver_health.status()?;
+        if status == HealthStatus::HEALTHY {
+            continue;
+        }
+        if controllers.is_none() {
+            controllers = Some(boot_services.locate_handle_buffer(HandleSearchType::AllHandle)?);
+        }
+        let count = health.entries.len();
+        for &controller_handle in controllers.as_deref().unwrap_or_default() {
+            match driver_health.controller_health(boot_services, controller_handle, None) {
+                Ok(report) if report.status != HealthStatus::HEALTHY => health.entries.push(HealthEntry {
+                    driver_handle,
+                    controller_handle: Some(controller_handle),
+                    report,
+                }),
+                _ => (),
+            }
+        }
+        if health.entries.len() == count {
+            health.entries.push(HealthEntry {
+                driver_handle,
+                controller_handle: None,
+                report: HealthReport::new(status),
+            });
+        }
+    }
+    Ok(health)
+}
+
+/// Repairs the controllers that require it, then reconnects the controllers that require it, as the boot manager
+/// does, and collects the health again.
+///
+/// The controllers that require a configuration or a reboot, and the failed ones, are left to the caller.
+pub fn recover<B: BootServices>(
+    boot_services: &B,
+    notify: Option<ProtocolRepairNotify>,
+) -> Result<PlatformHealth, EfiError> {
+    let health = platform_health(boot_services)?;
+    for entry in health.entries_with(HealthStatus::REPAIR_REQUIRED) {
+        if let (Ok(driver_health), Some(controller_handle)) =
+            (DriverHealth::get(boot_services, entry.driver_handle), entry.controller_handle)
+        {
+            // A failed repair shows in the health collected again.
+            let _ = driver_health.repair(controller_handle, None, notify);
+        }
+    }
+    let health = platform_health(boot_services)?;
+    let mut reconnected = false;
+    for controller_handle in health.entries_with(HealthStatus::RECONNECT_REQUIRED).filter_map(|e| e.controller_handle) {
+        let _ = boot_services.disconnect_controller(controller_handle, None, None);
+        match boot_services.connect_drivers(controller_handle, true) {
+            Ok(()) | Err(ConnectError::NoDriverConnected) => (),
+            Err(ConnectError::Failed(status)) => return Err(status.into()),
+        }
+        reconnected = true;
+    }
+    match reconnected {
+        true => platform_health(boot_services),
+        false => Ok(health),
+    }
+}
+
+type AllocatePool = Box<dyn Fn(usize) -> Result<*mut u8, EfiError>>;
+
+type RepairHandler = Box<dyn Fn(efi::Handle, Option<efi::Handle>, &mut dyn FnMut(usize, usize)) -> HealthReport>;
+
+struct ControllerHealth {
+    controller_handle: efi::Handle,
+    child_handle: Option<efi::Handle>,
+    report: HealthReport,
+}
+
+/// Implementation of the Driver Health protocol produced by a driver.
+///
+/// The driver reports the health of the controllers it manages, controllers without a report are not managed. The
+/// repair handler gives the health of a controller after its repair, with a function to notify its progress.
+///
+/// ```ignore
+/// let reporter = HealthReporter::new(boot_services).with_repair(|controller, _, notify| {
+///     notify(0, 1);
+///     rebuild_tables(controller);
+///     HealthReport::new(HealthStatus::HEALTHY)
+/// });
+/// let installed = reporter.install(boot_services, driver_binding_handle)?;
+/// // In DriverBinding.Start():
+/// installed.report(controller_handle, None, HealthReport::new(HealthStatus::REPAIR_REQUIRED));
+/// ```
+#[repr(C)]
+pub struct HealthReporter {
+    protocol: Protocol,
+    controllers: RefCell<Vec<ControllerHealth>>,
+    repair: Option<RepairHandler>,
+    allocate_pool: AllocatePool,
+}
+
+impl HealthReporter {
+    /// Creates a Driver Health implementation without reports, the message lists are allocated from the pool.
+    pub fn new<B: BootServices>(boot_services: &'static B) -> Self {
+        Self {
+            protocol: Protocol { get_health_status: Self::get_health_status, repair: Self::repair },
+            controllers: RefCell::new(Vec::new()),
+            repair: None,
+            allocate_pool: Box::new(|size| boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, size)),
+        }
+    }
+
+    /// Sets the handler repairing the controllers, without it the repairs are unsupported.
+    pub fn with_repair(
+        mut self,
+        repair: impl Fn(efi::Handle, Option<efi::Handle>, &mut dyn FnMut(usize, usize)) -> HealthReport + 'static,
+    ) -> Self {
+        self.repair = Some(Box::new(repair));
+        self
+    }
+
+    /// Installs the protocol on the driver binding handle of the driver.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn install<B: BootServices>(
+        self,
+        boot_services: &B,
+        driver_binding_handle: efi::Handle,
+    ) -> Result<InstalledProtocol<'_, DriverHealthProducer, B>, EfiError> {
+        InstalledProtocol::install(boot_services, Some(driver_binding_handle), &DriverHealthProducer, Box::new(self))
+    }
+
+    /// Sets the health of a controller managed by the driver, or of a child controller when `child_handle` is set.
+    pub fn report(&self, controller_handle: efi::Handle, child_handle: Option<efi::Handle>, report: HealthReport) {
+        let mut controllers = self.controllers.borrow_mut();
+        match controllers
+            .iter_mut()
+            .find(|c| c.controller_handle == controller_handle && c.child_handle == child_handle)
+        {
+            Some(controller) => controller.report = report,
+            None => controllers.push(ControllerHealth { controller_handle, child_handle, report }),
+        }
+    }
+
+    /// The health reported for a controller.
+    pub fn health(&self, controller_handle: efi::Handle, child_handle: Option<efi::Handle>) -> Option<HealthStatus> {
+        self.controllers
+            .borrow()
+            .iter()
+            .find(|c| c.controller_handle == controller_handle && c.child_handle == child_handle)
+            .map(|c| c.report.status)
+    }
+
+    /// Removes the reports of a controller and of all its children, typically when the driver stops managing it.
+    pub fn remove(&self, controller_handle: efi::Handle) {
+        self.controllers.borrow_mut().retain(|c| c.controller_handle != controller_handle);
+    }
+
+    /// Copies the messages to a list allocated from the pool, ended by a message without HII handle.
+    fn pool_messages(&self, messages: &[Message]) -> Result<*mut Message, EfiError> {
+        if messages.is_empty() {
+            return Ok(ptr::null_mut());
+        }
+        let pool = (self.allocate_pool)((messages.len() + 1) * mem::size_of::<Message>())? as *mut Message;
+        //SAFETY: The allocation holds the messages and the terminator, and is aligned for them.
+        let list = unsafe { slice::from_raw_parts_mut(pool, messages.len() + 1) };
+        list[..messages.len()].copy_from_slice(messages);
+        list[messages.len()] = Message { hii_handle: ptr::null_mut(), string_id: 0, message_code: 0 };
+        Ok(pool)
+    }
+
+    extern "efiapi" fn get_health_status(
+        this: *mut Protocol,
+        controller_handle: efi::Handle,
+        child_handle: efi::Handle,
+        health_status: *mut HealthStatus,
+        message_list: *mut *mut Message,
+        form_hii_handle: *mut HiiHandle,
+    ) -> efi::Status {
+        if this.is_null() || health_status.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        //SAFETY: The protocol is the first field of the HealthReporter that was installed.
+        let this = unsafe { &*(this as *const HealthReporter) };
+        let Ok(controllers) = this.controllers.try_borrow() else {
+            return efi::Status::DEVICE_ERROR;
+        };
+        if controller_handle.is_null() {
+            let status = HealthStatus::worst(controllers.iter().map(|c| c.report.status));
+            //SAFETY: The pointer was checked for null.
+            unsafe { health_status.write(status) };
+            return efi::Status::SUCCESS;
+        }
+        let child_handle = if child_handle.is_null() { None } else { Some(child_handle) };
+        let Some(controller) =
+            controllers.iter().find(|c| c.controller_handle == controller_handle && c.child_handle == child_handle)
+        else {
+            return efi::Status::UNSUPPORTED;
+        };
+        if !message_list.is_null() {
+            match this.pool_messages(&controller.report.messages) {
+                //SAFETY: The pointer was checked for null.
+                Ok(messages) => unsafe { message_list.write(messages) },
+                Err(status) => return status.into(),
+            }
+        }
+        if !form_hii_handle.is_null() {
+            //SAFETY: The pointer was checked for null.
+            unsafe { form_hii_handle.write(controller.report.form.unwrap_or(ptr::null_mut())) };
+        }
+        //SAFETY: The pointer was checked for null.
+        unsafe { health_status.write(controller.report.status) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn repair(
+        this: *mut Protocol,
+        controller_handle: efi::Handle,
+        child_handle: efi::Handle,
+        notify: Option<ProtocolRepairNotify>,
+    ) -> efi::Status {
+        if this.is_null() || controller_handle.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        //SAFETY: The protocol is the first field of the HealthReporter that was installed.
+        let this = unsafe { &*(this as *const HealthReporter) };
+        let child_handle = if child_handle.is_null() { None } else { Some(child_handle) };
+        let Some(repair) = &this.repair else {
+            return efi::Status::UNSUPPORTED;
+        };
+        if this.health(controller_handle, child_handle).is_none() {
+            return efi::Status::UNSUPPORTED;
+        }
+        let mut notify = |value, limit| {
+            if let Some(notify) = notify {
+                notify(value, limit);
+            }
+        };
+        // The handler runs without borrowing the reports, which it may change.
+        let report = repair(controller_handle, child_handle, &mut notify);
+        this.report(controller_handle, child_handle, report);
+        efi::Status::SUCCESS
+    }
+}
+
+impl fmt::Debug for HealthReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HealthReporter")
+            .field("controllers", &self.controllers.borrow().len())
+            .field("repair", &self.repair.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::mock::InMemoryBootServices;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    const CONTROLLER_GUID: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+
+    /// Boot services with two controllers, which are handles with a protocol.
+    fn boot_services() -> (&'static InMemoryBootServices, efi::Handle, efi::Handle) {
+        let boot_services = Box::leak(Box::new(InMemoryBootServices::new()));
+        let controller = || unsafe {
+            boot_services.install_protocol_interface_unchecked(None, &CONTROLLER_GUID, ptr::null_mut()).unwrap()
+        };
+        let (first, second) = (controller(), controller());
+        (boot_services, first, second)
+    }
+
+    fn driver_handle(boot_services: &InMemoryBootServices) -> efi::Handle {
+        unsafe { boot_services.install_protocol_interface_unchecked(None, &CONTROLLER_GUID, ptr::null_mut()) }.unwrap()
+    }
+
+    static PROGRESS: AtomicUsize = AtomicUsize::new(0);
+
+    extern "efiapi" fn notify(value: usize, limit: usize) -> efi::Status {
+        PROGRESS.store(value * 100 / limit, Ordering::SeqCst);
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_controller_health() {
+        let (boot_services, first, second) = boot_services();
+        let driver = driver_handle(boot_services);
+        let installed = HealthReporter::new(boot_services).install(boot_services, driver).unwrap();
+        let (hii_handle, form) = (0x10_usize as HiiHandle, 0x20_usize as HiiHandle);
+        installed.report(first, None, HealthReport::new(HealthStatus::HEALTHY));
+        installed.report(
+            second,
+            Some(first),
+            HealthReport::new(HealthStatus::CONFIGURATION_REQUIRED).with_message(hii_handle, 3, 0xDEAD).with_form(form),
+        );
+
+        let driver_health = DriverHealth::get(boot_services, driver).unwrap();
+        assert_eq!(HealthStatus::CONFIGURATION_REQUIRED, driver_health.status().unwrap());
+        assert_eq!(
+            HealthReport::new(HealthStatus::HEALTHY),
+            driver_health.controller_health(boot_services, first, None).unwrap()
+        );
+        let allocations = boot_services.allocations();
+        let report = driver_health.controller_health(boot_services, second, Some(first)).unwrap();
+        assert_eq!(&[Message { hii_handle, string_id: 3, message_code: 0xDEAD }], report.messages.as_slice());
+        assert_eq!(Some(form), report.form);
+        // The message list is freed.
+        assert_eq!(allocations, boot_services.allocations());
+        assert_eq!(efi::Status::UNSUPPORTED, driver_health.controller_health(boot_services, second, None).unwrap_err());
+
+        installed.remove(second);
+        assert_eq!(HealthStatus::HEALTHY, driver_health.status().unwrap());
+        assert_eq!(efi::Status::UNSUPPORTED, driver_health.repair(first, None, None).unwrap_err());
+        installed.leak();
+    }
+
+    #[test]
+    fn test_recover() {
+        let (boot_services, first, second) = boot_services();
+        assert!(platform_health(boot_services).unwrap().is_healthy());
+
+        let driver = driver_handle(boot_services);
+        let reporter = HealthReporter::new(boot_services).with_repair(|_, _, notify| {
+            notify(1, 2);
+            HealthReport::new(HealthStatus::HEALTHY)
+        });
+        let installed = reporter.install(boot_services, driver).unwrap();
+        installed.report(first, None, HealthReport::new(HealthStatus::REPAIR_REQUIRED));
+        installed.report(second, None, HealthReport::new(HealthStatus::REBOOT_REQUIRED));
+
+        let health = platform_health(boot_services).unwrap();
+        assert_eq!(HealthStatus::REBOOT_REQUIRED, health.status());
+        assert_eq!(2, health.entries().len());
+        let entry = health.entries_with(HealthStatus::REPAIR_REQUIRED).next().unwrap();
+        assert_eq!((driver, Some(first)), (entry.driver_handle, entry.controller_handle));
+
+        let health = recover(boot_services, Some(notify)).unwrap();
+        assert_eq!(50, PROGRESS.load(Ordering::SeqCst));
+        assert_eq!(Some(HealthStatus::HEALTHY), installed.health(first, None));
+        assert_eq!(1, health.entries().len());
+        assert_eq!(HealthStatus::REBOOT_REQUIRED, health.status());
+
+        // An unhealthy child, whose controller is not found from the handles.
+        installed.remove(second);
+        installed.report(first, Some(second), HealthReport::new(HealthStatus::FAILED));
+        let health = platform_health(boot_services).unwrap();
+        assert_eq!(
+            &[HealthEntry {
+                driver_handle: driver,
+                controller_handle: None,
+                report: HealthReport::new(HealthStatus::FAILED)
+            }],
+            health.entries()
+        );
+        installed.leak();
+    }
+}
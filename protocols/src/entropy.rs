@@ -0,0 +1,313 @@
+//! Entropy pool, for nonces and identifiers on platforms with or without the Random Number Generator protocol.
+//!
+//! [`EntropyPool`] mixes the sources available: the Random Number Generator protocol, the jitter of the counter of the
+//! processor, the Timestamp protocol and the monotonic count of the boot services. Every output reports its
+//! [`Quality`], from the weakest source it depends on:
+//!
+//! ```ignore
+//! let (guid, quality) = entropy::generate_uuid_v4(&boot_services);
+//! if quality < Quality::Jitter {
+//!     log::warn!("{guid:?} may collide with the one of another boot");
+//! }
+//! ```
+//!
+//! The pool is not a cryptographic generator: only bytes of [`Quality::Strong`], which come from the protocol, are
+//! fit for keys.
+
+use core::{fmt, ptr};
+
+use boot_services::BootServices;
+use r_efi::efi;
+
+use crate::{arch, rng::Rng, timestamp::Timestamp};
+
+/// Number of samples of the counter of the processor collected for their jitter.
+const JITTER_SAMPLES: usize = 64;
+
+/// Quality of random bytes, ordered from the weakest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Quality {
+    /// Mixed from counters and addresses only, unique within a boot but guessable.
+    Predictable,
+    /// Mixed with the jitter of the counter of the processor, hard to guess but not verified.
+    Jitter,
+    /// From the Random Number Generator protocol, fit for keys.
+    Strong,
+}
+
+/// Pool of entropy, mixed with a SipHash permutation.
+pub struct EntropyPool {
+    state: [u64; 4],
+    rng: Option<Rng>,
+    quality: Quality,
+}
+
+impl EntropyPool {
+    /// A pool seeded from the sources available through `boot_services`.
+    pub fn new<B: BootServices>(boot_services: &B) -> Self {
+        let mut pool = Self::from_rng(Rng::locate(boot_services).ok());
+        if let Ok(count) = boot_services.get_next_monotonic_count() {
+            pool.absorb(count);
+        }
+        if let Ok(timestamp) = Timestamp::locate(boot_services) {
+            pool.absorb(timestamp.get_timestamp());
+        }
+        pool
+    }
+
+    /// A pool seeded without the boot services, drawing from `rng` if given.
+    pub fn from_rng(rng: Option<Rng>) -> Self {
+        let mut pool = Self {
+            // The initialization vector of SipHash.
+            state: [0x736f6d6570736575, 0x646f72616e646f6d, 0x6c7967656e657261, 0x7465646279746573],
+            rng,
+            quality: Quality::Predictable,
+        };
+        // The addresses vary with where the firmware loads the image and allocates the pool.
+        pool.absorb(ptr::addr_of!(pool) as usize as u64);
+        pool.absorb(Self::round as fn(&mut Self) as usize as u64);
+        if pool.collect_jitter() {
+            pool.quality = Quality::Jitter;
+        }
+        if pool.rng.is_some() {
+            let mut seed = [0; 32];
+            if pool.draw_rng(&mut seed) {
+                pool.add_entropy(&seed);
+            }
+        }
+        pool
+    }
+
+    /// The quality of the bytes of the pool, without the protocol.
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// Whether the Random Number Generator protocol is used.
+    pub fn has_rng(&self) -> bool {
+        self.rng.is_some()
+    }
+
+    /// Mixes `data`, such as a serial number or a MAC address, in the pool.
+    pub fn add_entropy(&mut self, data: &[u8]) {
+        for chunk in data.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.absorb(u64::from_le_bytes(word));
+        }
+        self.absorb(data.len() as u64);
+    }
+
+    /// Fills `buffer` with random bytes, returns their quality.
+    ///
+    /// The bytes are `Strong` if the protocol gives them, they are mixed with the pool nonetheless.
+    pub fn fill_random(&mut self, buffer: &mut [u8]) -> Quality {
+        let quality = match self.draw_rng(buffer) {
+            true => Quality::Strong,
+            false => {
+                buffer.fill(0);
+                self.quality
+            }
+        };
+        // Fresh jitter, so that the bytes of pools with the same seed differ.
+        if let Some(counter) = arch::processor_ticks() {
+            self.absorb(counter);
+        }
+        for chunk in buffer.chunks_mut(8) {
+            let word = self.squeeze().to_le_bytes();
+            chunk.iter_mut().zip(word).for_each(|(byte, random)| *byte ^= random);
+        }
+        quality
+    }
+
+    /// A random UUID, of version 4 and variant 1 as specified by RFC 9562, and its quality.
+    pub fn generate_uuid_v4(&mut self) -> (efi::Guid, Quality) {
+        let mut bytes = [0; 16];
+        let quality = self.fill_random(&mut bytes);
+        let data1 = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        let data2 = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+        let data3 = u16::from_le_bytes(bytes[6..8].try_into().unwrap()) & 0x0FFF | 0x4000;
+        let clock_seq_high = bytes[8] & 0x3F | 0x80;
+        let guid =
+            efi::Guid::from_fields(data1, data2, data3, clock_seq_high, bytes[9], &bytes[10..16].try_into().unwrap());
+        (guid, quality)
+    }
+
+    /// Fills `buffer` from the protocol, returns false if it is not used or fails.
+    fn draw_rng(&mut self, buffer: &mut [u8]) -> bool {
+        self.rng.as_mut().is_some_and(|rng| rng.get_random(buffer).is_ok())
+    }
+
+    /// Collects the jitter of the counter of the processor, returns whether it varied enough to be unpredictable.
+    fn collect_jitter(&mut self) -> bool {
+        let Some(mut previous) = arch::processor_ticks() else {
+            return false;
+        };
+        let mut last_delta = 0;
+        let mut changes = 0;
+        for sample in 0..JITTER_SAMPLES {
+            // The mixing between the samples takes a time that varies with the caches and the interrupts.
+            self.absorb(sample as u64);
+            let counter = arch::processor_ticks().unwrap_or_default();
+            let delta = counter.wrapping_sub(previous);
+            if delta != last_delta {
+                changes += 1;
+            }
+            self.absorb(delta);
+            (previous, last_delta) = (counter, delta);
+        }
+        changes >= JITTER_SAMPLES / 4
+    }
+
+    fn round(&mut self) {
+        let v = &mut self.state;
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    fn absorb(&mut self, word: u64) {
+        self.state[3] ^= word;
+        self.round();
+        self.round();
+        self.state[0] ^= word;
+    }
+
+    fn squeeze(&mut self) -> u64 {
+        self.state[2] ^= 0xFF;
+        for _ in 0..4 {
+            self.round();
+        }
+        let word = self.state.iter().fold(0, |word, v| word ^ v);
+        // Feeds the output back, so that the next one differs.
+        self.absorb(word);
+        word
+    }
+}
+
+impl fmt::Debug for EntropyPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntropyPool").field("rng", &self.rng.is_some()).field("quality", &self.quality).finish()
+    }
+}
+
+/// Fills `buffer` with random bytes from a pool seeded through `boot_services`, returns their quality.
+pub fn fill_random<B: BootServices>(boot_services: &B, buffer: &mut [u8]) -> Quality {
+    EntropyPool::new(boot_services).fill_random(buffer)
+}
+
+/// A random UUID from a pool seeded through `boot_services`, and its quality.
+pub fn generate_uuid_v4<B: BootServices>(boot_services: &B) -> (efi::Guid, Quality) {
+    EntropyPool::new(boot_services).generate_uuid_v4()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::{protocol_handler, MockBootServices};
+    use core::slice;
+    use efi::protocols::{rng, timestamp as timestamp_protocol};
+
+    extern "efiapi" fn get_info(
+        _this: *mut rng::Protocol,
+        _size: *mut usize,
+        _algorithms: *mut efi::Guid,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    /// Generator of constant bytes, failing for more than 32 bytes.
+    extern "efiapi" fn get_rng(
+        _this: *mut rng::Protocol,
+        _algorithm: *mut efi::Guid,
+        size: usize,
+        value: *mut u8,
+    ) -> efi::Status {
+        if size > 32 {
+            return efi::Status::DEVICE_ERROR;
+        }
+        unsafe { slice::from_raw_parts_mut(value, size) }.fill(0x5A);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_timestamp() -> u64 {
+        0x1234
+    }
+
+    extern "efiapi" fn get_properties(_properties: *mut timestamp_protocol::Properties) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    fn boot_services(with_rng: bool) -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_locate_protocol::<protocol_handler::Rng, rng::Protocol>().returning(move |_, _| {
+            match with_rng {
+                true => Ok(Box::leak(Box::new(rng::Protocol { get_info, get_rng }))),
+                false => Err(efi::Status::NOT_FOUND.into()),
+            }
+        });
+        boot_services
+            .expect_locate_protocol::<protocol_handler::Timerstamp, timestamp_protocol::Protocol>()
+            .returning(|_, _| Ok(Box::leak(Box::new(timestamp_protocol::Protocol { get_timestamp, get_properties }))));
+        boot_services.expect_get_next_monotonic_count().returning(|| Ok(1));
+        boot_services
+    }
+
+    #[test]
+    fn test_fill_random() {
+        let mut pool = EntropyPool::new(&boot_services(true));
+        assert!(pool.has_rng());
+        let (mut first, mut second) = ([0; 32], [0; 32]);
+        assert_eq!(Quality::Strong, pool.fill_random(&mut first));
+        assert_eq!(Quality::Strong, pool.fill_random(&mut second));
+        // The bytes of the protocol are mixed with the pool.
+        assert_ne!([0x5A; 32], first);
+        assert_ne!(first, second);
+
+        // The protocol fails, the bytes only come from the pool.
+        let mut buffer = [0; 33];
+        assert_eq!(pool.quality(), pool.fill_random(&mut buffer));
+        assert!(pool.quality() < Quality::Strong);
+        assert_ne!([0; 33], buffer);
+        assert_eq!(Quality::Strong, fill_random(&boot_services(true), &mut []));
+    }
+
+    #[test]
+    fn test_without_rng() {
+        let mut pool = EntropyPool::new(&boot_services(false));
+        assert!(!pool.has_rng());
+        pool.add_entropy(b"serial number");
+        let (mut first, mut second) = ([0; 16], [0; 16]);
+        assert!(pool.fill_random(&mut first) < Quality::Strong);
+        pool.fill_random(&mut second);
+        assert_ne!(first, second);
+
+        // Pools of different seeds differ.
+        let mut other = EntropyPool::from_rng(None);
+        other.fill_random(&mut second);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_generate_uuid_v4() {
+        let (guid, quality) = generate_uuid_v4(&boot_services(true));
+        assert_eq!(Quality::Strong, quality);
+        let (_, _, data3, clock_seq_high, _, _) = guid.as_fields();
+        assert_eq!((0x4, 0x2), (data3 >> 12, clock_seq_high >> 6));
+
+        let mut pool = EntropyPool::from_rng(None);
+        let (first, _) = pool.generate_uuid_v4();
+        let (second, quality) = pool.generate_uuid_v4();
+        assert_ne!(first, second);
+        assert!(quality < Quality::Strong);
+        assert_eq!(0x4, second.as_fields().2 >> 12);
+    }
+}
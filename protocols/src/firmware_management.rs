@@ -0,0 +1,753 @@
+//! Firmware Management protocol.
+//!
+//! [`FirmwareManagement`] describes, reads, checks and updates the firmware images of a device, for an update agent
+//! to drive every instance of the protocol:
+//!
+//! ```ignore
+//! for mut fmp in firmware_management::instances(&boot_services)? {
+//!     let info = fmp.image_info(&boot_services)?;
+//!     for descriptor in info.descriptors.iter().filter(|d| d.image_type_id == CAPSULE_GUID) {
+//!         if fmp.check_image(descriptor.image_index, &image)?.contains(ImageUpdatable::VALID) {
+//!             fmp.set_image(&boot_services, descriptor.image_index, &image, None, Some(&mut |percent| log(percent)))?;
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 23.1. Firmware Management Protocol](https://uefi.org/specs/UEFI/2.10/23_Firmware_Update_and_Reporting.html#firmware-management-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{
+    ffi::c_void,
+    fmt,
+    mem::{self, offset_of, MaybeUninit},
+    ops::Deref,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use boot_services::{
+    protocol_handler::{HandleBuffer, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+
+pub mod esrt;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x86c77a67, 0x0b97, 0x4633, 0xa1, 0x87, &[0x49, 0x10, 0x4d, 0x06, 0x85, 0xc7]);
+
+/// Latest version of [`FirmwareImageDescriptor`].
+pub const IMAGE_DESCRIPTOR_VERSION: u32 = 4;
+
+pub type ProtocolGetImageInfo = extern "efiapi" fn(
+    *mut Protocol,
+    *mut usize,
+    *mut FirmwareImageDescriptor,
+    *mut u32,
+    *mut u8,
+    *mut usize,
+    *mut u32,
+    *mut *mut u16,
+) -> efi::Status;
+
+pub type ProtocolGetImage = extern "efiapi" fn(*mut Protocol, u8, *mut c_void, *mut usize) -> efi::Status;
+
+/// `EFI_FIRMWARE_MANAGEMENT_UPDATE_IMAGE_PROGRESS`, called with the completion of the update, from 1 to 100.
+pub type UpdateImageProgress = extern "efiapi" fn(usize) -> efi::Status;
+
+pub type ProtocolSetImage = extern "efiapi" fn(
+    *mut Protocol,
+    u8,
+    *const c_void,
+    usize,
+    *const c_void,
+    Option<UpdateImageProgress>,
+    *mut *mut u16,
+) -> efi::Status;
+
+pub type ProtocolCheckImage =
+    extern "efiapi" fn(*mut Protocol, u8, *const c_void, usize, *mut ImageUpdatable) -> efi::Status;
+
+pub type ProtocolGetPackageInfo =
+    extern "efiapi" fn(*mut Protocol, *mut u32, *mut *mut u16, *mut u32, *mut u64, *mut u64) -> efi::Status;
+
+pub type ProtocolSetPackageInfo =
+    extern "efiapi" fn(*mut Protocol, *const c_void, usize, *const c_void, u32, *const u16) -> efi::Status;
+
+/// FFI definition of `EFI_FIRMWARE_MANAGEMENT_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub get_image_info: ProtocolGetImageInfo,
+    pub get_image: ProtocolGetImage,
+    pub set_image: ProtocolSetImage,
+    pub check_image: ProtocolCheckImage,
+    pub get_package_info: ProtocolGetPackageInfo,
+    pub set_package_info: ProtocolSetPackageInfo,
+}
+
+/// FFI definition of `EFI_FIRMWARE_IMAGE_DESCRIPTOR`, in its latest version.
+///
+/// Each version only appends fields, the descriptors of older versions are prefixes of this one.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FirmwareImageDescriptor {
+    pub image_index: u8,
+    pub image_type_id: efi::Guid,
+    pub image_id: u64,
+    pub image_id_name: *mut u16,
+    pub version: u32,
+    pub version_name: *mut u16,
+    pub size: usize,
+    pub attributes_supported: ImageAttributes,
+    pub attributes_setting: ImageAttributes,
+    pub compatibilities: u64,
+    /// Since version 2.
+    pub lowest_supported_image_version: u32,
+    /// Since version 3.
+    pub last_attempt_version: u32,
+    /// Since version 3.
+    pub last_attempt_status: LastAttemptStatus,
+    /// Since version 3.
+    pub hardware_instance: u64,
+    /// Since version 4, `EFI_FIRMWARE_IMAGE_DEP`.
+    pub dependencies: *mut u8,
+}
+
+impl FirmwareImageDescriptor {
+    /// Size of the descriptors of `version`, without the padding of the producer.
+    fn size_of_version(version: u32) -> Option<usize> {
+        match version {
+            0 => None,
+            1 => Some(offset_of!(Self, lowest_supported_image_version)),
+            2 => Some(offset_of!(Self, last_attempt_version)),
+            3 => Some(offset_of!(Self, dependencies)),
+            _ => Some(mem::size_of::<Self>()),
+        }
+    }
+}
+
+/// Firmware Management protocol.
+pub struct FirmwareManagementProtocol;
+
+unsafe impl ProtocolTrait for FirmwareManagementProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for FirmwareManagementProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Attributes of an image, `IMAGE_ATTRIBUTE_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct ImageAttributes(pub u64);
+
+impl ImageAttributes {
+    pub const IMAGE_UPDATABLE: ImageAttributes = ImageAttributes(0x0000000000000001);
+    pub const RESET_REQUIRED: ImageAttributes = ImageAttributes(0x0000000000000002);
+    pub const AUTHENTICATION_REQUIRED: ImageAttributes = ImageAttributes(0x0000000000000004);
+    pub const IN_USE: ImageAttributes = ImageAttributes(0x0000000000000008);
+    pub const UEFI_IMAGE: ImageAttributes = ImageAttributes(0x0000000000000010);
+    pub const DEPENDENCY: ImageAttributes = ImageAttributes(0x0000000000000020);
+
+    /// Returns true if all the attributes of `other` are set.
+    pub const fn contains(&self, other: ImageAttributes) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Status of the last update attempt of an image, `LAST_ATTEMPT_STATUS_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct LastAttemptStatus(pub u32);
+
+impl LastAttemptStatus {
+    pub const SUCCESS: LastAttemptStatus = LastAttemptStatus(0x00000000);
+    pub const ERROR_UNSUCCESSFUL: LastAttemptStatus = LastAttemptStatus(0x00000001);
+    pub const ERROR_INSUFFICIENT_RESOURCES: LastAttemptStatus = LastAttemptStatus(0x00000002);
+    pub const ERROR_INCORRECT_VERSION: LastAttemptStatus = LastAttemptStatus(0x00000003);
+    pub const ERROR_INVALID_FORMAT: LastAttemptStatus = LastAttemptStatus(0x00000004);
+    pub const ERROR_AUTH_ERROR: LastAttemptStatus = LastAttemptStatus(0x00000005);
+    pub const ERROR_PWR_EVT_AC: LastAttemptStatus = LastAttemptStatus(0x00000006);
+    pub const ERROR_PWR_EVT_BATT: LastAttemptStatus = LastAttemptStatus(0x00000007);
+    pub const ERROR_UNSATISFIED_DEPENDENCIES: LastAttemptStatus = LastAttemptStatus(0x00000008);
+}
+
+/// Result of [`FirmwareManagement::check_image`], `IMAGE_UPDATABLE_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct ImageUpdatable(pub u32);
+
+impl ImageUpdatable {
+    pub const VALID: ImageUpdatable = ImageUpdatable(0x00000001);
+    pub const INVALID: ImageUpdatable = ImageUpdatable(0x00000002);
+    pub const INVALID_TYPE: ImageUpdatable = ImageUpdatable(0x00000004);
+    pub const INVALID_OLD: ImageUpdatable = ImageUpdatable(0x00000008);
+    pub const VALID_WITH_VENDOR_CODE: ImageUpdatable = ImageUpdatable(0x00000010);
+
+    /// Returns true if all the flags of `other` are set.
+    pub const fn contains(&self, other: ImageUpdatable) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Description of an image, whatever the version of the descriptor returned by the protocol.
+///
+/// The fields appended by later versions are `None` when the protocol returned an older version. The dependencies of
+/// version 4 are not read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageDescriptor {
+    /// Index of the image given to the other functions of the protocol, from 1.
+    pub image_index: u8,
+    pub image_type_id: efi::Guid,
+    pub image_id: u64,
+    pub image_id_name: Option<String16>,
+    pub version: u32,
+    pub version_name: Option<String16>,
+    pub size: usize,
+    pub attributes_supported: ImageAttributes,
+    pub attributes_setting: ImageAttributes,
+    pub compatibilities: u64,
+    pub lowest_supported_image_version: Option<u32>,
+    pub last_attempt_version: Option<u32>,
+    pub last_attempt_status: Option<LastAttemptStatus>,
+    pub hardware_instance: Option<u64>,
+}
+
+impl ImageDescriptor {
+    /// Reads a descriptor of `version` from the `size` bytes at `descriptor`.
+    ///
+    /// # Safety
+    ///
+    /// `descriptor` must point to `size` readable bytes, its strings must be null or null-terminated.
+    unsafe fn read(descriptor: *const u8, size: usize, version: u32) -> Self {
+        // The fields missing from older versions are read as zeros.
+        let mut raw = MaybeUninit::<FirmwareImageDescriptor>::zeroed();
+        ptr::copy_nonoverlapping(
+            descriptor,
+            raw.as_mut_ptr() as *mut u8,
+            size.min(mem::size_of::<FirmwareImageDescriptor>()),
+        );
+        let raw = raw.assume_init();
+        Self {
+            image_index: raw.image_index,
+            image_type_id: raw.image_type_id,
+            image_id: raw.image_id,
+            image_id_name: string16(raw.image_id_name),
+            version: raw.version,
+            version_name: string16(raw.version_name),
+            size: raw.size,
+            attributes_supported: raw.attributes_supported,
+            attributes_setting: raw.attributes_setting,
+            compatibilities: raw.compatibilities,
+            lowest_supported_image_version: (version >= 2).then_some(raw.lowest_supported_image_version),
+            last_attempt_version: (version >= 3).then_some(raw.last_attempt_version),
+            last_attempt_status: (version >= 3).then_some(raw.last_attempt_status),
+            hardware_instance: (version >= 3).then_some(raw.hardware_instance),
+        }
+    }
+}
+
+/// The images of a device, returned by [`FirmwareManagement::image_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// Version of the descriptors returned by the protocol.
+    pub descriptor_version: u32,
+    pub descriptors: Vec<ImageDescriptor>,
+    /// Version of the package of the images, `0xFFFFFFFF` if the device does not support packages.
+    pub package_version: u32,
+    pub package_version_name: Option<String16>,
+}
+
+/// Failure of [`FirmwareManagement::set_image`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct UpdateError {
+    pub status: efi::Status,
+    /// Reason given by the device for `ABORTED`.
+    pub abort_reason: Option<String16>,
+}
+
+impl fmt::Debug for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpdateError")
+            .field("status", &status::StatusDisplay(self.status))
+            .field("abort_reason", &self.abort_reason)
+            .finish()
+    }
+}
+
+impl From<efi::Status> for UpdateError {
+    fn from(status: efi::Status) -> Self {
+        Self { status, abort_reason: None }
+    }
+}
+
+impl From<UpdateError> for status::EfiError {
+    fn from(error: UpdateError) -> Self {
+        status::EfiError::new(error.status).with_operation("SetImage")
+    }
+}
+
+/// Copies a string owned by the protocol.
+///
+/// # Safety
+///
+/// `string` must be null or point to a null-terminated string.
+unsafe fn string16(string: *const u16) -> Option<String16> {
+    (!string.is_null()).then(|| String16::from(Str16::from_ptr(string)))
+}
+
+/// Copies then frees a string allocated from pool by the protocol.
+///
+/// # Safety
+///
+/// `string` must be null or point to a null-terminated string allocated from pool.
+unsafe fn take_string16<B: BootServices>(boot_services: &B, string: *mut u16) -> Option<String16> {
+    let copy = string16(string);
+    if !string.is_null() {
+        let _ = boot_services.free_pool(string as *mut u8);
+    }
+    copy
+}
+
+/// Progress callback of the running [`FirmwareManagement::set_image`], a `*mut &mut dyn FnMut(usize)`.
+///
+/// `EFI_FIRMWARE_MANAGEMENT_UPDATE_IMAGE_PROGRESS` has no context, so the callback is found from here.
+static PROGRESS: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+extern "efiapi" fn report_progress(completion: usize) -> efi::Status {
+    let progress = PROGRESS.load(Ordering::Acquire) as *mut &mut dyn FnMut(usize);
+    if !progress.is_null() {
+        //SAFETY: The callback is set for the duration of `set_image`, the only time the protocol reports progress.
+        unsafe { (*progress)(completion) };
+    }
+    efi::Status::SUCCESS
+}
+
+/// Typed access to an instance of the Firmware Management protocol.
+pub struct FirmwareManagement(&'static mut Protocol);
+
+impl FirmwareManagement {
+    /// Gets the instance of the protocol installed on the handle of a device.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &FirmwareManagementProtocol).map(Self)
+    }
+
+    fn this(&self) -> *mut Protocol {
+        &*self.0 as *const Protocol as *mut Protocol
+    }
+
+    /// Describes the images of the device.
+    ///
+    /// The descriptors are read according to the version returned by the protocol. The protocol allocates the name of
+    /// the package from pool, the boot services are used to free it once copied.
+    pub fn image_info<B: BootServices>(&self, boot_services: &B) -> Result<ImageInfo, EfiError> {
+        let mut size = 0;
+        let mut descriptor_version = 0;
+        let mut count = 0;
+        let mut descriptor_size = 0;
+        let mut package_version = 0;
+        let mut package_version_name = ptr::null_mut();
+        let mut buffer: Vec<u64> = Vec::new();
+        loop {
+            // The buffer is of u64 for the alignment of the descriptors.
+            match (self.0.get_image_info)(
+                self.this(),
+                &mut size,
+                buffer.as_mut_ptr() as *mut FirmwareImageDescriptor,
+                &mut descriptor_version,
+                &mut count,
+                &mut descriptor_size,
+                &mut package_version,
+                &mut package_version_name,
+            ) {
+                efi::Status::BUFFER_TOO_SMALL if size > buffer.len() * mem::size_of::<u64>() => {
+                    buffer = vec![0; size.div_ceil(mem::size_of::<u64>())];
+                }
+                s if s.is_error() => return Err(s.into()),
+                _ => break,
+            }
+        }
+        //SAFETY: The protocol returned the name of the package, if any, owned by the caller.
+        let package_version_name = unsafe { take_string16(boot_services, package_version_name) };
+
+        let minimum_size =
+            FirmwareImageDescriptor::size_of_version(descriptor_version).ok_or(efi::Status::INCOMPATIBLE_VERSION)?;
+        if count > 0 && (descriptor_size < minimum_size || descriptor_size * count as usize > size) {
+            return Err(efi::Status::BAD_BUFFER_SIZE.into());
+        }
+        let descriptors = (0..count as usize)
+            .map(|i| {
+                //SAFETY: The descriptors are within the `size` bytes written by the protocol.
+                unsafe {
+                    ImageDescriptor::read(
+                        (buffer.as_ptr() as *const u8).add(i * descriptor_size),
+                        descriptor_size,
+                        descriptor_version,
+                    )
+                }
+            })
+            .collect();
+        Ok(ImageInfo { descriptor_version, descriptors, package_version, package_version_name })
+    }
+
+    /// Reads the image `image_index`.
+    pub fn get_image(&self, image_index: u8) -> Result<Vec<u8>, EfiError> {
+        let mut image = Vec::new();
+        let mut size = 0;
+        loop {
+            let buffer = if image.is_empty() { ptr::null_mut() } else { image.as_mut_ptr() as *mut c_void };
+            match (self.0.get_image)(self.this(), image_index, buffer, &mut size) {
+                efi::Status::BUFFER_TOO_SMALL if size > image.len() => image = vec![0u8; size],
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    image.truncate(size);
+                    return Ok(image);
+                }
+            }
+        }
+    }
+
+    /// Checks that `image` can update the image `image_index`.
+    pub fn check_image(&self, image_index: u8, image: &[u8]) -> Result<ImageUpdatable, EfiError> {
+        let mut updatable = ImageUpdatable::default();
+        match (self.0.check_image)(
+            self.this(),
+            image_index,
+            image.as_ptr() as *const c_void,
+            image.len(),
+            &mut updatable,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(updatable),
+        }
+    }
+
+    /// Updates the image `image_index` with `image`.
+    ///
+    /// `progress` is called with the completion of the update, from 1 to 100. A single update reports progress at a
+    /// time: the update fails with `NOT_READY` while another one reports progress. The protocol allocates the reason
+    /// of an abort from pool, the boot services are used to free it once copied.
+    pub fn set_image<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        image_index: u8,
+        image: &[u8],
+        vendor_code: Option<&[u8]>,
+        progress: Option<&mut dyn FnMut(usize)>,
+    ) -> Result<(), UpdateError> {
+        let vendor_code = vendor_code.map_or(ptr::null(), |vendor_code| vendor_code.as_ptr() as *const c_void);
+        let mut progress = progress;
+        let report: Option<UpdateImageProgress> = match progress.as_mut() {
+            Some(progress) => {
+                let progress = progress as *mut &mut dyn FnMut(usize) as *mut c_void;
+                PROGRESS
+                    .compare_exchange(ptr::null_mut(), progress, Ordering::AcqRel, Ordering::Acquire)
+                    .map_err(|_| efi::Status::NOT_READY)?;
+                Some(report_progress)
+            }
+            None => None,
+        };
+        let mut abort_reason = ptr::null_mut();
+        let status = (self.0.set_image)(
+            self.this(),
+            image_index,
+            image.as_ptr() as *const c_void,
+            image.len(),
+            vendor_code,
+            report,
+            &mut abort_reason,
+        );
+        if report.is_some() {
+            PROGRESS.store(ptr::null_mut(), Ordering::Release);
+        }
+        //SAFETY: The protocol returned the reason of an abort, if any, owned by the caller.
+        let abort_reason = unsafe { take_string16(boot_services, abort_reason) };
+        match status {
+            s if s.is_error() => Err(UpdateError { status: s, abort_reason }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for FirmwareManagement {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for FirmwareManagement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FirmwareManagement").finish_non_exhaustive()
+    }
+}
+
+/// The instances of the protocol, from the handles that support it.
+pub fn instances<B: BootServices>(boot_services: &B) -> Result<Vec<FirmwareManagement>, EfiError> {
+    let handles = HandleBuffer::supporting(boot_services, &FirmwareManagementProtocol)?;
+    Ok((&handles).into_iter().filter_map(|handle| FirmwareManagement::get(boot_services, handle).ok()).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::{boxed::BootServicesBox, MockBootServices};
+    use core::slice;
+    use ucs2::u16str;
+
+    const IMAGE_TYPE: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+
+    /// Device with two images, the protocol is the first field to be found from its pointer.
+    ///
+    /// The descriptors are given in `descriptor_version`, from the full ones of the images.
+    #[repr(C)]
+    struct TestDevice {
+        protocol: Protocol,
+        descriptor_version: u32,
+        images: Vec<(FirmwareImageDescriptor, Vec<u8>)>,
+    }
+
+    fn test_device<'a>(this: *mut Protocol) -> &'a mut TestDevice {
+        unsafe { &mut *(this as *mut TestDevice) }
+    }
+
+    fn leak_string(string: &[u16]) -> *mut u16 {
+        Box::leak(string.to_vec().into_boxed_slice()).as_mut_ptr()
+    }
+
+    extern "efiapi" fn get_image_info(
+        this: *mut Protocol,
+        size: *mut usize,
+        info: *mut FirmwareImageDescriptor,
+        descriptor_version: *mut u32,
+        count: *mut u8,
+        descriptor_size: *mut usize,
+        package_version: *mut u32,
+        package_version_name: *mut *mut u16,
+    ) -> efi::Status {
+        let device = test_device(this);
+        let stride = FirmwareImageDescriptor::size_of_version(device.descriptor_version).unwrap().next_multiple_of(8);
+        let required = stride * device.images.len();
+        let available = unsafe { ptr::replace(size, required) };
+        if available < required {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        for (i, (descriptor, _)) in device.images.iter().enumerate() {
+            let bytes = unsafe { slice::from_raw_parts(descriptor as *const _ as *const u8, stride) };
+            unsafe { slice::from_raw_parts_mut((info as *mut u8).add(i * stride), stride) }.copy_from_slice(bytes);
+        }
+        unsafe {
+            *descriptor_version = device.descriptor_version;
+            *count = device.images.len() as u8;
+            *descriptor_size = stride;
+            *package_version = 0xFFFFFFFF;
+            // The name is freed by the mocked free_pool.
+            *package_version_name = leak_string(u16str!("Package"));
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_image(this: *mut Protocol, index: u8, image: *mut c_void, size: *mut usize) -> efi::Status {
+        let Some((_, data)) = test_device(this).images.get((index as usize).wrapping_sub(1)) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let available = unsafe { ptr::replace(size, data.len()) };
+        if available < data.len() {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(image as *mut u8, data.len()) }.copy_from_slice(data);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_image(
+        this: *mut Protocol,
+        index: u8,
+        image: *const c_void,
+        size: usize,
+        _: *const c_void,
+        progress: Option<UpdateImageProgress>,
+        abort_reason: *mut *mut u16,
+    ) -> efi::Status {
+        let device = test_device(this);
+        let Some((descriptor, data)) = device.images.get_mut((index as usize).wrapping_sub(1)) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        if size == 0 {
+            unsafe { *abort_reason = leak_string(u16str!("Empty image")) };
+            return efi::Status::ABORTED;
+        }
+        if let Some(progress) = progress {
+            progress(50);
+            progress(100);
+        }
+        *data = unsafe { slice::from_raw_parts(image as *const u8, size) }.to_vec();
+        descriptor.last_attempt_status = LastAttemptStatus::SUCCESS;
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn check_image(
+        _: *mut Protocol,
+        _: u8,
+        image: *const c_void,
+        size: usize,
+        updatable: *mut ImageUpdatable,
+    ) -> efi::Status {
+        let image = unsafe { slice::from_raw_parts(image as *const u8, size) };
+        unsafe {
+            *updatable = if image.starts_with(b"FMP") { ImageUpdatable::VALID } else { ImageUpdatable::INVALID };
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_package_info(
+        _: *mut Protocol,
+        _: *mut u32,
+        _: *mut *mut u16,
+        _: *mut u32,
+        _: *mut u64,
+        _: *mut u64,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn set_package_info(
+        _: *mut Protocol,
+        _: *const c_void,
+        _: usize,
+        _: *const c_void,
+        _: u32,
+        _: *const u16,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    fn descriptor(image_index: u8, version: u32) -> FirmwareImageDescriptor {
+        FirmwareImageDescriptor {
+            image_index,
+            image_type_id: IMAGE_TYPE,
+            image_id: image_index as u64,
+            image_id_name: leak_string(u16str!("Image")),
+            version,
+            version_name: ptr::null_mut(),
+            size: 4,
+            attributes_supported: ImageAttributes(0x7),
+            attributes_setting: ImageAttributes::IMAGE_UPDATABLE,
+            compatibilities: 0,
+            lowest_supported_image_version: 1,
+            last_attempt_version: version,
+            last_attempt_status: LastAttemptStatus::ERROR_INVALID_FORMAT,
+            hardware_instance: 0,
+            dependencies: ptr::null_mut(),
+        }
+    }
+
+    fn firmware_management(descriptor_version: u32) -> FirmwareManagement {
+        let device = Box::leak(Box::new(TestDevice {
+            protocol: Protocol {
+                get_image_info,
+                get_image,
+                set_image,
+                check_image,
+                get_package_info,
+                set_package_info,
+            },
+            descriptor_version,
+            images: vec![(descriptor(1, 2), b"FMP1".to_vec()), (descriptor(2, 5), b"FMP2".to_vec())],
+        }));
+        FirmwareManagement::from(&mut device.protocol)
+    }
+
+    fn free_boot_services() -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_free_pool().returning(|_| Ok(()));
+        boot_services
+    }
+
+    #[test]
+    fn test_image_info() {
+        let boot_services = free_boot_services();
+        let info = firmware_management(3).image_info(&boot_services).unwrap();
+        assert_eq!((3, 0xFFFFFFFF), (info.descriptor_version, info.package_version));
+        assert_eq!(Some(String16::try_from("Package").unwrap()), info.package_version_name);
+        assert_eq!(2, info.descriptors.len());
+        let descriptor = &info.descriptors[1];
+        assert_eq!((2, IMAGE_TYPE, 5), (descriptor.image_index, descriptor.image_type_id, descriptor.version));
+        assert_eq!(Some(String16::try_from("Image").unwrap()), descriptor.image_id_name);
+        assert_eq!(None, descriptor.version_name);
+        assert!(descriptor.attributes_setting.contains(ImageAttributes::IMAGE_UPDATABLE));
+        assert_eq!(Some(1), descriptor.lowest_supported_image_version);
+        assert_eq!(Some(LastAttemptStatus::ERROR_INVALID_FORMAT), descriptor.last_attempt_status);
+        assert_eq!(Some(0), descriptor.hardware_instance);
+
+        // The fields appended after version 1 are not read.
+        let info = firmware_management(1).image_info(&boot_services).unwrap();
+        let descriptor = &info.descriptors[1];
+        assert_eq!((2, 5, 4), (descriptor.image_index, descriptor.version, descriptor.size));
+        assert_eq!(
+            (None, None, None),
+            (descriptor.lowest_supported_image_version, descriptor.last_attempt_version, descriptor.hardware_instance)
+        );
+    }
+
+    #[test]
+    fn test_get_and_check_image() {
+        let fmp = firmware_management(IMAGE_DESCRIPTOR_VERSION);
+        assert_eq!(b"FMP2".to_vec(), fmp.get_image(2).unwrap());
+        assert_eq!(efi::Status::INVALID_PARAMETER, fmp.get_image(3).unwrap_err());
+        assert_eq!(ImageUpdatable::VALID, fmp.check_image(1, b"FMP3").unwrap());
+        assert!(fmp.check_image(1, b"BAD").unwrap().contains(ImageUpdatable::INVALID));
+    }
+
+    #[test]
+    fn test_set_image() {
+        let boot_services = free_boot_services();
+        let mut fmp = firmware_management(IMAGE_DESCRIPTOR_VERSION);
+        let mut progress = Vec::new();
+        fmp.set_image(&boot_services, 1, b"FMP3", None, Some(&mut |completion| progress.push(completion))).unwrap();
+        assert_eq!(vec![50, 100], progress);
+        assert_eq!(b"FMP3".to_vec(), fmp.get_image(1).unwrap());
+
+        fmp.set_image(&boot_services, 2, b"FMP4", None, None).unwrap();
+        assert_eq!(
+            UpdateError {
+                status: efi::Status::ABORTED,
+                abort_reason: Some(String16::try_from("Empty image").unwrap())
+            },
+            fmp.set_image(&boot_services, 2, &[], None, None).unwrap_err()
+        );
+        assert_eq!(b"FMP4".to_vec(), fmp.get_image(2).unwrap());
+    }
+
+    #[test]
+    fn test_instances() {
+        let free_boot_services: &'static MockBootServices = Box::leak(Box::new(free_boot_services()));
+        let protocol = Box::leak(Box::new(firmware_management(IMAGE_DESCRIPTOR_VERSION))).this() as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_handle_protocol::<FirmwareManagementProtocol, Protocol>().returning(move |handle, _| {
+            match handle as usize {
+                1 => Ok(unsafe { &mut *(protocol as *mut Protocol) }),
+                _ => Err(efi::Status::UNSUPPORTED.into()),
+            }
+        });
+        boot_services.expect_locate_handles().returning(move |_| {
+            let handles = Box::leak(Box::new([1_usize as efi::Handle, 2_usize as efi::Handle]));
+            Ok(HandleBuffer::from(unsafe {
+                BootServicesBox::from_raw_parts(handles.as_mut_ptr(), handles.len(), free_boot_services)
+            }))
+        });
+        let instances = instances(&boot_services).unwrap();
+        assert_eq!(1, instances.len());
+        assert_eq!(b"FMP1".to_vec(), instances[0].get_image(1).unwrap());
+    }
+}
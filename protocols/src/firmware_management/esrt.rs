@@ -0,0 +1,297 @@
+//! EFI System Resource Table, the firmware resources that capsules can update, as reported to the OS.
+//!
+//! A platform builds the table from the descriptors of its firmware management instances and publishes it:
+//!
+//! ```ignore
+//! let mut esrt = SystemResourceTable::new();
+//! for fmp in firmware_management::instances(&boot_services)? {
+//!     for descriptor in fmp.image_info(&boot_services)?.descriptors {
+//!         esrt.add_descriptor(&descriptor, FirmwareType::DEVICE_FIRMWARE);
+//!     }
+//! }
+//! esrt.publish(&boot_services)?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 23.4. EFI System Resource Table](https://uefi.org/specs/UEFI/2.10/23_Firmware_Update_and_Reporting.html#efi-system-resource-table)
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, mem, slice};
+
+use boot_services::{allocation::MemoryType, configuration_table::ConfigurationTables, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use super::{ImageDescriptor, LastAttemptStatus};
+
+/// GUID of the table in the configuration tables, `EFI_SYSTEM_RESOURCE_TABLE_GUID`.
+pub const SYSTEM_RESOURCE_TABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0xb122a263, 0x3661, 0x4f68, 0x99, 0x29, &[0x78, 0xf8, 0xb0, 0xd6, 0x21, 0x80]);
+
+/// Version of the table, `EFI_SYSTEM_RESOURCE_TABLE_FIRMWARE_RESOURCE_VERSION`.
+pub const FIRMWARE_RESOURCE_VERSION: u64 = 1;
+
+/// FFI definition of the header of `EFI_SYSTEM_RESOURCE_TABLE`, followed by the entries.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+pub struct TableHeader {
+    pub fw_resource_count: u32,
+    pub fw_resource_count_max: u32,
+    pub fw_resource_version: u64,
+}
+
+/// Type of a firmware resource, `ESRT_FW_TYPE_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct FirmwareType(pub u32);
+
+impl FirmwareType {
+    pub const UNKNOWN: FirmwareType = FirmwareType(0x00000000);
+    pub const SYSTEM_FIRMWARE: FirmwareType = FirmwareType(0x00000001);
+    pub const DEVICE_FIRMWARE: FirmwareType = FirmwareType(0x00000002);
+    pub const UEFI_DRIVER: FirmwareType = FirmwareType(0x00000003);
+}
+
+/// FFI definition of `EFI_SYSTEM_RESOURCE_ENTRY`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemResourceEntry {
+    /// Firmware class of the resource, the image type of its firmware management descriptor.
+    pub fw_class: efi::Guid,
+    pub fw_type: FirmwareType,
+    pub fw_version: u32,
+    pub lowest_supported_fw_version: u32,
+    pub capsule_flags: u32,
+    pub last_attempt_version: u32,
+    pub last_attempt_status: LastAttemptStatus,
+}
+
+impl SystemResourceEntry {
+    /// The entry of the image described by `descriptor`.
+    ///
+    /// The fields missing from older descriptor versions are zeros, the capsule flags are left to the platform.
+    pub fn from_descriptor(descriptor: &ImageDescriptor, fw_type: FirmwareType) -> Self {
+        Self {
+            fw_class: descriptor.image_type_id,
+            fw_type,
+            fw_version: descriptor.version,
+            lowest_supported_fw_version: descriptor.lowest_supported_image_version.unwrap_or(0),
+            capsule_flags: 0,
+            last_attempt_version: descriptor.last_attempt_version.unwrap_or(0),
+            last_attempt_status: descriptor.last_attempt_status.unwrap_or(LastAttemptStatus::SUCCESS),
+        }
+    }
+}
+
+/// The entries of an ESRT, parsed from a table or to publish.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemResourceTable {
+    entries: Vec<SystemResourceEntry>,
+}
+
+impl SystemResourceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a table, its entries past the end of `data` are an error.
+    pub fn parse(data: &[u8]) -> Result<Self, EfiError> {
+        let header_size = mem::size_of::<TableHeader>();
+        let (header, _) = TableHeader::read_from_prefix(data).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        if header.fw_resource_version != FIRMWARE_RESOURCE_VERSION {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        let count = header.fw_resource_count as usize;
+        let entry_size = mem::size_of::<SystemResourceEntry>();
+        if header.fw_resource_count > header.fw_resource_count_max || data.len() < header_size + count * entry_size {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let entries = (0..count)
+            .map(|i| {
+                //SAFETY: The entries are within the data.
+                unsafe {
+                    (data.as_ptr().add(header_size + i * entry_size) as *const SystemResourceEntry).read_unaligned()
+                }
+            })
+            .collect();
+        Ok(Self { entries })
+    }
+
+    /// Parses the table installed in the configuration tables, `NOT_FOUND` if there is none.
+    pub fn find(configuration_tables: ConfigurationTables) -> Result<Self, EfiError> {
+        let table = configuration_tables.find_table(&SYSTEM_RESOURCE_TABLE_GUID).ok_or(efi::Status::NOT_FOUND)?;
+        if table.is_null() {
+            return Err(efi::Status::NOT_FOUND.into());
+        }
+        //SAFETY: The installed table starts with its header, followed by `fw_resource_count` entries.
+        let data = unsafe {
+            let header = (table as *const TableHeader).read_unaligned();
+            let size = mem::size_of::<TableHeader>()
+                + header.fw_resource_count as usize * mem::size_of::<SystemResourceEntry>();
+            slice::from_raw_parts(table as *const u8, size)
+        };
+        Self::parse(data)
+    }
+
+    pub fn entries(&self) -> &[SystemResourceEntry] {
+        &self.entries
+    }
+
+    /// The entry of the firmware class `fw_class`.
+    pub fn entry(&self, fw_class: &efi::Guid) -> Option<&SystemResourceEntry> {
+        self.entries.iter().find(|entry| entry.fw_class == *fw_class)
+    }
+
+    /// Adds an entry, or replaces the entry of the same firmware class if it has a higher version.
+    ///
+    /// A class has a single entry, the lowest version of its instances, as a capsule of the class updates them all.
+    pub fn add(&mut self, entry: SystemResourceEntry) {
+        match self.entries.iter_mut().find(|existing| existing.fw_class == entry.fw_class) {
+            Some(existing) if entry.fw_version < existing.fw_version => *existing = entry,
+            Some(_) => (),
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Adds the entry of the image described by `descriptor`, see [`SystemResourceEntry::from_descriptor`].
+    pub fn add_descriptor(&mut self, descriptor: &ImageDescriptor, fw_type: FirmwareType) {
+        self.add(SystemResourceEntry::from_descriptor(descriptor, fw_type));
+    }
+
+    /// The table, its header followed by its entries.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = TableHeader {
+            fw_resource_count: self.entries.len() as u32,
+            fw_resource_count_max: self.entries.len() as u32,
+            fw_resource_version: FIRMWARE_RESOURCE_VERSION,
+        };
+        let mut bytes = Vec::with_capacity(mem::size_of::<TableHeader>() + mem::size_of_val(&self.entries[..]));
+        bytes.extend_from_slice(header.as_bytes());
+        //SAFETY: The entries have no padding, all their bytes are initialized.
+        bytes.extend_from_slice(unsafe {
+            slice::from_raw_parts(self.entries.as_ptr() as *const u8, mem::size_of_val(&self.entries[..]))
+        });
+        bytes
+    }
+
+    /// Installs the table in the configuration tables, replacing any previous one.
+    ///
+    /// The table is copied to boot services data memory, as required for the OS to read it.
+    pub fn publish<B: BootServices>(&self, boot_services: &B) -> Result<(), EfiError> {
+        let bytes = self.to_bytes();
+        let table = boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, bytes.len())?;
+        //SAFETY: The allocation is as large as the table.
+        unsafe { slice::from_raw_parts_mut(table, bytes.len()) }.copy_from_slice(&bytes);
+        //SAFETY: The table stays allocated for as long as it is installed.
+        unsafe {
+            boot_services.install_configuration_table_unchecked(&SYSTEM_RESOURCE_TABLE_GUID, table as *mut c_void)
+        }
+        .inspect_err(|_| {
+            let _ = boot_services.free_pool(table);
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use boot_services::MockBootServices;
+    use core::mem::MaybeUninit;
+
+    const SYSTEM: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+    const DEVICE: efi::Guid = efi::Guid::from_fields(2, 0, 0, 0, 0, &[0; 6]);
+
+    fn descriptor(image_type_id: efi::Guid, version: u32, last_attempt: Option<LastAttemptStatus>) -> ImageDescriptor {
+        ImageDescriptor {
+            image_index: 1,
+            image_type_id,
+            image_id: 0,
+            image_id_name: None,
+            version,
+            version_name: None,
+            size: 0,
+            attributes_supported: Default::default(),
+            attributes_setting: Default::default(),
+            compatibilities: 0,
+            lowest_supported_image_version: last_attempt.map(|_| 1),
+            last_attempt_version: last_attempt.map(|_| version),
+            last_attempt_status: last_attempt,
+            hardware_instance: last_attempt.map(|_| 0),
+        }
+    }
+
+    fn table() -> SystemResourceTable {
+        let mut esrt = SystemResourceTable::new();
+        esrt.add_descriptor(&descriptor(SYSTEM, 3, Some(LastAttemptStatus::SUCCESS)), FirmwareType::SYSTEM_FIRMWARE);
+        esrt.add_descriptor(&descriptor(DEVICE, 5, None), FirmwareType::DEVICE_FIRMWARE);
+        esrt.add_descriptor(
+            &descriptor(DEVICE, 4, Some(LastAttemptStatus::ERROR_AUTH_ERROR)),
+            FirmwareType::DEVICE_FIRMWARE,
+        );
+        esrt.add_descriptor(&descriptor(DEVICE, 6, None), FirmwareType::DEVICE_FIRMWARE);
+        esrt
+    }
+
+    #[test]
+    fn test_add_descriptor() {
+        let esrt = table();
+        assert_eq!(2, esrt.entries().len());
+        let system = esrt.entry(&SYSTEM).unwrap();
+        assert_eq!(
+            (FirmwareType::SYSTEM_FIRMWARE, 3, 1, 3),
+            (system.fw_type, system.fw_version, system.lowest_supported_fw_version, system.last_attempt_version)
+        );
+        // The lowest version of the class is kept.
+        let device = esrt.entry(&DEVICE).unwrap();
+        assert_eq!((4, LastAttemptStatus::ERROR_AUTH_ERROR), (device.fw_version, device.last_attempt_status));
+    }
+
+    #[test]
+    fn test_parse() {
+        let esrt = table();
+        let bytes = esrt.to_bytes();
+        assert_eq!(16 + 2 * 40, bytes.len());
+        assert_eq!(esrt, SystemResourceTable::parse(&bytes).unwrap());
+        assert_eq!(efi::Status::INVALID_PARAMETER, SystemResourceTable::parse(&bytes[..95]).unwrap_err());
+        let mut bytes = bytes;
+        bytes[8] = 2;
+        assert_eq!(efi::Status::INCOMPATIBLE_VERSION, SystemResourceTable::parse(&bytes).unwrap_err());
+        assert_eq!(
+            SystemResourceTable::new(),
+            SystemResourceTable::parse(&SystemResourceTable::new().to_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_publish_and_find() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_allocate_pool().once().returning(|memory_type, size| {
+            assert_eq!(MemoryType::BOOT_SERVICES_DATA, memory_type);
+            Ok(Box::leak(vec![0u8; size].into_boxed_slice()).as_mut_ptr())
+        });
+        let installed = Box::leak(Box::new(efi::ConfigurationTable {
+            vendor_guid: SYSTEM_RESOURCE_TABLE_GUID,
+            vendor_table: core::ptr::null_mut(),
+        }));
+        let installed_ptr = installed as *mut efi::ConfigurationTable as usize;
+        boot_services.expect_install_configuration_table_unchecked().once().returning(move |guid, table| {
+            assert_eq!(SYSTEM_RESOURCE_TABLE_GUID, *guid);
+            unsafe { (*(installed_ptr as *mut efi::ConfigurationTable)).vendor_table = table };
+            Ok(())
+        });
+        let esrt = table();
+        esrt.publish(&boot_services).unwrap();
+
+        //SAFETY: Only the configuration table fields are read.
+        let mut system_table = unsafe { MaybeUninit::<efi::SystemTable>::zeroed().assume_init() };
+        system_table.number_of_table_entries = 1;
+        system_table.configuration_table = installed;
+        assert_eq!(esrt, SystemResourceTable::find(ConfigurationTables::new(&system_table)).unwrap());
+        system_table.number_of_table_entries = 0;
+        assert_eq!(
+            efi::Status::NOT_FOUND,
+            SystemResourceTable::find(ConfigurationTables::new(&system_table)).unwrap_err()
+        );
+    }
+}
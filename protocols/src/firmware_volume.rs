@@ -0,0 +1,594 @@
+//! Firmware Volume 2 protocol.
+//!
+//! [`FirmwareVolume`] reads the files of a firmware volume and their sections, such as a raw section embedded in the
+//! firmware image next to the driver that needs it:
+//!
+//! ```ignore
+//! let data = firmware_volume::find_section(&boot_services, &FILE_GUID, SectionType::RAW)?;
+//! ```
+//!
+//! [PI Spec Documentation: Volume 3, 3.4.1. EFI_FIRMWARE_VOLUME2_PROTOCOL](https://uefi.org/specs/PI/1.8/V3_Code_Definitions.html#efi-firmware-volume2-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{ffi::c_void, fmt, ops::Deref, ptr, slice};
+
+use boot_services::{
+    protocol_handler::{HandleBuffer, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x220e73b6, 0x6bdb, 0x4413, 0x84, 0x05, &[0xb9, 0x74, 0xb1, 0x08, 0x61, 0x9a]);
+
+pub type ProtocolGetVolumeAttributes = extern "efiapi" fn(*mut Protocol, *mut u64) -> efi::Status;
+
+pub type ProtocolSetVolumeAttributes = extern "efiapi" fn(*mut Protocol, *mut u64) -> efi::Status;
+
+pub type ProtocolReadFile = extern "efiapi" fn(
+    *mut Protocol,
+    *const efi::Guid,
+    *mut *mut c_void,
+    *mut usize,
+    *mut FileType,
+    *mut u32,
+    *mut u32,
+) -> efi::Status;
+
+pub type ProtocolReadSection = extern "efiapi" fn(
+    *mut Protocol,
+    *const efi::Guid,
+    SectionType,
+    usize,
+    *mut *mut c_void,
+    *mut usize,
+    *mut u32,
+) -> efi::Status;
+
+pub type ProtocolWriteFile = extern "efiapi" fn(*mut Protocol, u32, u32, *mut c_void) -> efi::Status;
+
+pub type ProtocolGetNextFile =
+    extern "efiapi" fn(*mut Protocol, *mut c_void, *mut FileType, *mut efi::Guid, *mut u32, *mut usize) -> efi::Status;
+
+pub type ProtocolGetInfo = extern "efiapi" fn(*mut Protocol, *const efi::Guid, *mut usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolSetInfo = extern "efiapi" fn(*mut Protocol, *const efi::Guid, usize, *const c_void) -> efi::Status;
+
+/// FFI definition of `EFI_FIRMWARE_VOLUME2_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub get_volume_attributes: ProtocolGetVolumeAttributes,
+    pub set_volume_attributes: ProtocolSetVolumeAttributes,
+    pub read_file: ProtocolReadFile,
+    pub read_section: ProtocolReadSection,
+    pub write_file: ProtocolWriteFile,
+    pub get_next_file: ProtocolGetNextFile,
+    pub key_size: u32,
+    pub parent_handle: efi::Handle,
+    pub get_info: ProtocolGetInfo,
+    pub set_info: ProtocolSetInfo,
+}
+
+/// Firmware Volume 2 protocol.
+pub struct FirmwareVolume2;
+
+unsafe impl ProtocolTrait for FirmwareVolume2 {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for FirmwareVolume2 {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Type of a file of a firmware volume, `EFI_FV_FILETYPE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct FileType(pub u8);
+
+impl FileType {
+    /// Any type, to list the files of a volume.
+    pub const ALL: FileType = FileType(0x00);
+    pub const RAW: FileType = FileType(0x01);
+    pub const FREEFORM: FileType = FileType(0x02);
+    pub const SECURITY_CORE: FileType = FileType(0x03);
+    pub const PEI_CORE: FileType = FileType(0x04);
+    pub const DXE_CORE: FileType = FileType(0x05);
+    pub const PEIM: FileType = FileType(0x06);
+    pub const DRIVER: FileType = FileType(0x07);
+    pub const COMBINED_PEIM_DRIVER: FileType = FileType(0x08);
+    pub const APPLICATION: FileType = FileType(0x09);
+    pub const MM: FileType = FileType(0x0A);
+    pub const FIRMWARE_VOLUME_IMAGE: FileType = FileType(0x0B);
+    pub const COMBINED_MM_DXE: FileType = FileType(0x0C);
+    pub const MM_CORE: FileType = FileType(0x0D);
+    pub const MM_STANDALONE: FileType = FileType(0x0E);
+    pub const MM_CORE_STANDALONE: FileType = FileType(0x0F);
+}
+
+/// Type of a section of a file, `EFI_SECTION_TYPE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct SectionType(pub u8);
+
+impl SectionType {
+    /// Any type, to read a file section by section.
+    pub const ALL: SectionType = SectionType(0x00);
+    pub const COMPRESSION: SectionType = SectionType(0x01);
+    pub const GUID_DEFINED: SectionType = SectionType(0x02);
+    pub const DISPOSABLE: SectionType = SectionType(0x03);
+    pub const PE32: SectionType = SectionType(0x10);
+    pub const PIC: SectionType = SectionType(0x11);
+    pub const TE: SectionType = SectionType(0x12);
+    pub const DXE_DEPEX: SectionType = SectionType(0x13);
+    pub const VERSION: SectionType = SectionType(0x14);
+    pub const USER_INTERFACE: SectionType = SectionType(0x15);
+    pub const COMPATIBILITY16: SectionType = SectionType(0x16);
+    pub const FIRMWARE_VOLUME_IMAGE: SectionType = SectionType(0x17);
+    pub const FREEFORM_SUBTYPE_GUID: SectionType = SectionType(0x18);
+    pub const RAW: SectionType = SectionType(0x19);
+    pub const PEI_DEPEX: SectionType = SectionType(0x1B);
+    pub const MM_DEPEX: SectionType = SectionType(0x1C);
+}
+
+/// A file of a firmware volume, as listed by [`FirmwareVolume::files`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileEntry {
+    pub name: efi::Guid,
+    pub file_type: FileType,
+    /// `EFI_FV_FILE_ATTRIBUTES` of the file.
+    pub attributes: u32,
+    /// Size of the file, without its header.
+    pub size: usize,
+}
+
+/// A file read with [`FirmwareVolume::read_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    pub file_type: FileType,
+    /// `EFI_FV_FILE_ATTRIBUTES` of the file.
+    pub attributes: u32,
+    /// The content of the file, without its header.
+    pub data: Vec<u8>,
+}
+
+/// Typed access to an instance of the Firmware Volume 2 protocol.
+pub struct FirmwareVolume(&'static mut Protocol);
+
+impl FirmwareVolume {
+    /// Gets the instance of the protocol installed on the handle of a firmware volume.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &FirmwareVolume2).map(Self)
+    }
+
+    /// The protocol pointer given to its functions, which do not change the protocol.
+    fn protocol_ptr(&self) -> *mut Protocol {
+        &*self.0 as *const Protocol as *mut Protocol
+    }
+
+    /// The `EFI_FV_ATTRIBUTES` of the volume.
+    pub fn attributes(&self) -> Result<u64, EfiError> {
+        let mut attributes = 0;
+        match (self.0.get_volume_attributes)(self.protocol_ptr(), &mut attributes) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(attributes),
+        }
+    }
+
+    /// Reads a whole file, without its header.
+    pub fn read_file(&self, name: &efi::Guid) -> Result<File, EfiError> {
+        let mut size = 0;
+        let mut file_type = FileType::ALL;
+        let mut attributes = 0;
+        let mut authentication_status = 0;
+        // Without buffer, only the size, type and attributes of the file are returned.
+        match (self.0.read_file)(
+            self.protocol_ptr(),
+            name,
+            ptr::null_mut(),
+            &mut size,
+            &mut file_type,
+            &mut attributes,
+            &mut authentication_status,
+        ) {
+            s if s.is_error() => return Err(s.into()),
+            _ => (),
+        }
+        let mut data = vec![0u8; size];
+        let mut buffer = data.as_mut_ptr() as *mut c_void;
+        match (self.0.read_file)(
+            self.protocol_ptr(),
+            name,
+            &mut buffer,
+            &mut size,
+            &mut file_type,
+            &mut attributes,
+            &mut authentication_status,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            efi::Status::WARN_BUFFER_TOO_SMALL => Err(efi::Status::BUFFER_TOO_SMALL.into()),
+            _ => {
+                data.truncate(size);
+                Ok(File { file_type, attributes, data })
+            }
+        }
+    }
+
+    /// Reads the `instance`-th section of the given type of a file, from 0.
+    ///
+    /// Encapsulation sections, such as compressed ones, are searched through. The protocol allocates the section from
+    /// pool, the boot services are used to free it once copied.
+    pub fn read_section<B: BootServices>(
+        &self,
+        boot_services: &B,
+        name: &efi::Guid,
+        section_type: SectionType,
+        instance: usize,
+    ) -> Result<Vec<u8>, EfiError> {
+        let mut buffer = ptr::null_mut();
+        let mut size = 0;
+        let mut authentication_status = 0;
+        match (self.0.read_section)(
+            self.protocol_ptr(),
+            name,
+            section_type,
+            instance,
+            &mut buffer,
+            &mut size,
+            &mut authentication_status,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ if buffer.is_null() => Err(efi::Status::DEVICE_ERROR.into()),
+            _ => {
+                //SAFETY: The protocol allocated a buffer of `size` bytes, owned by the caller.
+                let section = unsafe { slice::from_raw_parts(buffer as *const u8, size) }.to_vec();
+                let _ = boot_services.free_pool(buffer as *mut u8);
+                Ok(section)
+            }
+        }
+    }
+
+    /// Iterates over the files of the volume.
+    pub fn files(&self) -> Files<'_> {
+        self.files_of_type(FileType::ALL)
+    }
+
+    /// Iterates over the files of the given type of the volume.
+    pub fn files_of_type(&self, file_type: FileType) -> Files<'_> {
+        Files { volume: self, file_type, key: vec![0; self.0.key_size as usize], done: false }
+    }
+}
+
+impl From<&'static mut Protocol> for FirmwareVolume {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for FirmwareVolume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FirmwareVolume").field("parent_handle", &self.0.parent_handle).finish()
+    }
+}
+
+/// Iterator over the files of a volume, returned by [`FirmwareVolume::files`].
+///
+/// The iteration stops after the first error.
+pub struct Files<'a> {
+    volume: &'a FirmwareVolume,
+    file_type: FileType,
+    /// Opaque position of the iteration, of the size requested by the protocol.
+    key: Vec<u8>,
+    done: bool,
+}
+
+impl Iterator for Files<'_> {
+    type Item = Result<FileEntry, EfiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut entry =
+            FileEntry { name: efi::Guid::from_bytes(&[0; 16]), file_type: self.file_type, attributes: 0, size: 0 };
+        match (self.volume.0.get_next_file)(
+            self.volume.protocol_ptr(),
+            self.key.as_mut_ptr() as *mut c_void,
+            &mut entry.file_type,
+            &mut entry.name,
+            &mut entry.attributes,
+            &mut entry.size,
+        ) {
+            efi::Status::NOT_FOUND => {
+                self.done = true;
+                None
+            }
+            s if s.is_error() => {
+                self.done = true;
+                Some(Err(s.into()))
+            }
+            _ => Some(Ok(entry)),
+        }
+    }
+}
+
+impl fmt::Debug for Files<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Files").field("file_type", &self.file_type).field("done", &self.done).finish()
+    }
+}
+
+/// Reads the first section of the given type of a file, from the first firmware volume that has the file.
+pub fn find_section<B: BootServices>(
+    boot_services: &B,
+    name: &efi::Guid,
+    section_type: SectionType,
+) -> Result<Vec<u8>, EfiError> {
+    let handles = HandleBuffer::supporting(boot_services, &FirmwareVolume2)?;
+    let mut error = EfiError::new(efi::Status::NOT_FOUND);
+    for handle in &handles {
+        let Ok(volume) = FirmwareVolume::get(boot_services, handle) else {
+            continue;
+        };
+        match volume.read_section(boot_services, name, section_type, 0) {
+            Ok(section) => return Ok(section),
+            Err(status) if status == efi::Status::NOT_FOUND => (),
+            Err(status) => error = status,
+        }
+    }
+    Err(error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::{boxed::BootServicesBox, MockBootServices};
+
+    const DRIVER: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+    const CONFIG: efi::Guid = efi::Guid::from_fields(2, 0, 0, 0, 0, &[0; 6]);
+    const MISSING: efi::Guid = efi::Guid::from_fields(3, 0, 0, 0, 0, &[0; 6]);
+
+    struct TestFile {
+        name: efi::Guid,
+        file_type: FileType,
+        sections: Vec<(SectionType, Vec<u8>)>,
+    }
+
+    /// Volume with a driver and a freeform file, the protocol is the first field to be found from its pointer.
+    ///
+    /// The content of a file is the concatenation of its sections, without section headers.
+    #[repr(C)]
+    struct TestVolume {
+        protocol: Protocol,
+        files: Vec<TestFile>,
+    }
+
+    fn test_volume<'a>(this: *mut Protocol) -> &'a TestVolume {
+        unsafe { &*(this as *const TestVolume) }
+    }
+
+    impl TestVolume {
+        fn file(&self, name: *const efi::Guid) -> Option<&TestFile> {
+            self.files.iter().find(|file| file.name == unsafe { *name })
+        }
+    }
+
+    extern "efiapi" fn get_volume_attributes(_: *mut Protocol, attributes: *mut u64) -> efi::Status {
+        unsafe { *attributes = 0x4000 };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_volume_attributes(_: *mut Protocol, _: *mut u64) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn read_file(
+        this: *mut Protocol,
+        name: *const efi::Guid,
+        buffer: *mut *mut c_void,
+        size: *mut usize,
+        file_type: *mut FileType,
+        attributes: *mut u32,
+        _: *mut u32,
+    ) -> efi::Status {
+        let Some(file) = test_volume(this).file(name) else {
+            return efi::Status::NOT_FOUND;
+        };
+        let data: Vec<u8> = file.sections.iter().flat_map(|(_, data)| data.iter().copied()).collect();
+        unsafe {
+            *file_type = file.file_type;
+            *attributes = 0x100;
+        }
+        if buffer.is_null() {
+            unsafe { *size = data.len() };
+            return efi::Status::SUCCESS;
+        }
+        let available = unsafe { ptr::replace(size, data.len()) };
+        if available < data.len() {
+            return efi::Status::WARN_BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(*buffer as *mut u8, data.len()) }.copy_from_slice(&data);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_section(
+        this: *mut Protocol,
+        name: *const efi::Guid,
+        section_type: SectionType,
+        instance: usize,
+        buffer: *mut *mut c_void,
+        size: *mut usize,
+        _: *mut u32,
+    ) -> efi::Status {
+        let Some(file) = test_volume(this).file(name) else {
+            return efi::Status::NOT_FOUND;
+        };
+        let Some((_, data)) = file.sections.iter().filter(|(t, _)| *t == section_type).nth(instance) else {
+            return efi::Status::NOT_FOUND;
+        };
+        // The buffer is freed by the mocked free_pool.
+        let section = Box::leak(data.clone().into_boxed_slice());
+        unsafe {
+            *buffer = section.as_mut_ptr() as *mut c_void;
+            *size = section.len();
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn write_file(_: *mut Protocol, _: u32, _: u32, _: *mut c_void) -> efi::Status {
+        efi::Status::WRITE_PROTECTED
+    }
+
+    extern "efiapi" fn get_next_file(
+        this: *mut Protocol,
+        key: *mut c_void,
+        file_type: *mut FileType,
+        name: *mut efi::Guid,
+        attributes: *mut u32,
+        size: *mut usize,
+    ) -> efi::Status {
+        // The key is the index of the next file.
+        let index = unsafe { &mut *(key as *mut usize) };
+        let requested = unsafe { *file_type };
+        let volume = test_volume(this);
+        let Some((i, file)) = volume
+            .files
+            .iter()
+            .enumerate()
+            .skip(*index)
+            .find(|(_, file)| requested == FileType::ALL || file.file_type == requested)
+        else {
+            return efi::Status::NOT_FOUND;
+        };
+        *index = i + 1;
+        unsafe {
+            *file_type = file.file_type;
+            *name = file.name;
+            *attributes = 0x100;
+            *size = file.sections.iter().map(|(_, data)| data.len()).sum();
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_info(_: *mut Protocol, _: *const efi::Guid, _: *mut usize, _: *mut c_void) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn set_info(_: *mut Protocol, _: *const efi::Guid, _: usize, _: *const c_void) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    fn firmware_volume() -> &'static mut Protocol {
+        let volume = Box::leak(Box::new(TestVolume {
+            protocol: Protocol {
+                get_volume_attributes,
+                set_volume_attributes,
+                read_file,
+                read_section,
+                write_file,
+                get_next_file,
+                key_size: core::mem::size_of::<usize>() as u32,
+                parent_handle: ptr::null_mut(),
+                get_info,
+                set_info,
+            },
+            files: vec![
+                TestFile {
+                    name: DRIVER,
+                    file_type: FileType::DRIVER,
+                    sections: vec![
+                        (SectionType::PE32, vec![0x4D, 0x5A]),
+                        (SectionType::RAW, vec![1, 2]),
+                        (SectionType::RAW, vec![3]),
+                    ],
+                },
+                TestFile {
+                    name: CONFIG,
+                    file_type: FileType::FREEFORM,
+                    sections: vec![(SectionType::RAW, b"config".to_vec())],
+                },
+            ],
+        }));
+        &mut volume.protocol
+    }
+
+    fn boot_services() -> MockBootServices {
+        let protocol = firmware_volume() as *mut Protocol as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol::<FirmwareVolume2, Protocol>()
+            .returning(move |_, _| Ok(unsafe { &mut *(protocol as *mut Protocol) }));
+        boot_services.expect_free_pool().returning(|_| Ok(()));
+        boot_services
+    }
+
+    #[test]
+    fn test_read_file() {
+        let boot_services = boot_services();
+        let volume = FirmwareVolume::get(&boot_services, 1_usize as efi::Handle).unwrap();
+        assert_eq!(0x4000, volume.attributes().unwrap());
+        assert_eq!(
+            File { file_type: FileType::DRIVER, attributes: 0x100, data: vec![0x4D, 0x5A, 1, 2, 3] },
+            volume.read_file(&DRIVER).unwrap()
+        );
+        assert_eq!(efi::Status::NOT_FOUND, volume.read_file(&MISSING).unwrap_err());
+    }
+
+    #[test]
+    fn test_read_section() {
+        let boot_services = boot_services();
+        let volume = FirmwareVolume::get(&boot_services, 1_usize as efi::Handle).unwrap();
+        assert_eq!(vec![1, 2], volume.read_section(&boot_services, &DRIVER, SectionType::RAW, 0).unwrap());
+        assert_eq!(vec![3], volume.read_section(&boot_services, &DRIVER, SectionType::RAW, 1).unwrap());
+        assert_eq!(
+            efi::Status::NOT_FOUND,
+            volume.read_section(&boot_services, &DRIVER, SectionType::RAW, 2).unwrap_err()
+        );
+        assert_eq!(
+            efi::Status::NOT_FOUND,
+            volume.read_section(&boot_services, &CONFIG, SectionType::PE32, 0).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let boot_services = boot_services();
+        let volume = FirmwareVolume::get(&boot_services, 1_usize as efi::Handle).unwrap();
+        let files = volume.files().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            vec![
+                FileEntry { name: DRIVER, file_type: FileType::DRIVER, attributes: 0x100, size: 5 },
+                FileEntry { name: CONFIG, file_type: FileType::FREEFORM, attributes: 0x100, size: 6 },
+            ],
+            files
+        );
+        let freeform = volume.files_of_type(FileType::FREEFORM).map(|f| f.unwrap().name).collect::<Vec<_>>();
+        assert_eq!(vec![CONFIG], freeform);
+        assert_eq!(0, volume.files_of_type(FileType::APPLICATION).count());
+    }
+
+    #[test]
+    fn test_find_section() {
+        let mut free_boot_services = MockBootServices::new();
+        free_boot_services.expect_free_pool().returning(|_| Ok(()));
+        let free_boot_services: &'static MockBootServices = Box::leak(Box::new(free_boot_services));
+
+        let mut boot_services = boot_services();
+        boot_services.expect_locate_handles().returning(move |_| {
+            let handles = Box::leak(Box::new([1_usize as efi::Handle, 2_usize as efi::Handle]));
+            Ok(HandleBuffer::from(unsafe {
+                BootServicesBox::from_raw_parts(handles.as_mut_ptr(), handles.len(), free_boot_services)
+            }))
+        });
+        assert_eq!(b"config".to_vec(), find_section(&boot_services, &CONFIG, SectionType::RAW).unwrap());
+        assert_eq!(efi::Status::NOT_FOUND, find_section(&boot_services, &MISSING, SectionType::RAW).unwrap_err());
+    }
+}
@@ -0,0 +1,582 @@
+//! Firmware Volume Block 2 protocol.
+//!
+//! [`FirmwareVolumeBlock`] accesses the blocks of the flash device under a firmware volume, within the bounds of its
+//! [`BlockMap`]. Regions spanning blocks are read with [`FirmwareVolumeBlock::read`] and updated with
+//! [`FirmwareVolumeBlock::update`], which erases a block only when the new data cannot be programmed over the old:
+//!
+//! ```ignore
+//! let fvb = firmware_volume_block::find_by_address(&boot_services, NV_STORAGE_BASE)?;
+//! let mut header = [0; 16];
+//! fvb.read(0, &mut header)?;
+//! fvb.update(CONFIG_OFFSET, &config)?;
+//! ```
+//!
+//! The updates are not fault tolerant: a block erased for an update is lost if the write that follows is interrupted.
+//!
+//! [PI Spec Documentation: Volume 3, 3.4.2. EFI_FIRMWARE_VOLUME_BLOCK2_PROTOCOL](https://uefi.org/specs/PI/1.8/V3_Code_Definitions.html#efi-firmware-volume-block2-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, ops::Deref};
+
+use boot_services::{
+    protocol_handler::{HandleBuffer, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x8f644fa9, 0xe850, 0x4db1, 0x9c, 0xe2, &[0x0b, 0x44, 0x69, 0x8e, 0x8d, 0xa4]);
+
+/// Ends the list of block ranges given to `EraseBlocks`.
+pub const LBA_LIST_TERMINATOR: u64 = u64::MAX;
+
+/// Bits of the `EFI_FVB_ATTRIBUTES_2` of a volume.
+pub mod attributes {
+    pub const READ_DISABLED_CAP: u32 = 0x0000_0001;
+    pub const READ_ENABLED_CAP: u32 = 0x0000_0002;
+    pub const READ_STATUS: u32 = 0x0000_0004;
+    pub const WRITE_DISABLED_CAP: u32 = 0x0000_0008;
+    pub const WRITE_ENABLED_CAP: u32 = 0x0000_0010;
+    pub const WRITE_STATUS: u32 = 0x0000_0020;
+    pub const LOCK_CAP: u32 = 0x0000_0040;
+    pub const LOCK_STATUS: u32 = 0x0000_0080;
+    pub const STICKY_WRITE: u32 = 0x0000_0200;
+    pub const MEMORY_MAPPED: u32 = 0x0000_0400;
+    /// The erased bits are 1s, they are 0s otherwise.
+    pub const ERASE_POLARITY: u32 = 0x0000_0800;
+    pub const READ_LOCK_CAP: u32 = 0x0000_1000;
+    pub const READ_LOCK_STATUS: u32 = 0x0000_2000;
+    pub const WRITE_LOCK_CAP: u32 = 0x0000_4000;
+    pub const WRITE_LOCK_STATUS: u32 = 0x0000_8000;
+    pub const ALIGNMENT: u32 = 0x001F_0000;
+}
+
+pub type ProtocolGetAttributes = extern "efiapi" fn(*mut Protocol, *mut u32) -> efi::Status;
+
+pub type ProtocolSetAttributes = extern "efiapi" fn(*mut Protocol, *mut u32) -> efi::Status;
+
+pub type ProtocolGetPhysicalAddress = extern "efiapi" fn(*mut Protocol, *mut efi::PhysicalAddress) -> efi::Status;
+
+pub type ProtocolGetBlockSize = extern "efiapi" fn(*mut Protocol, u64, *mut usize, *mut usize) -> efi::Status;
+
+pub type ProtocolRead = extern "efiapi" fn(*mut Protocol, u64, usize, *mut usize, *mut u8) -> efi::Status;
+
+pub type ProtocolWrite = extern "efiapi" fn(*mut Protocol, u64, usize, *mut usize, *mut u8) -> efi::Status;
+
+/// `EraseBlocks` is variadic, it is called with a single range of blocks followed by [`LBA_LIST_TERMINATOR`].
+pub type ProtocolEraseBlocks = extern "efiapi" fn(*mut Protocol, u64, usize, u64) -> efi::Status;
+
+/// FFI definition of `EFI_FIRMWARE_VOLUME_BLOCK2_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub get_attributes: ProtocolGetAttributes,
+    pub set_attributes: ProtocolSetAttributes,
+    pub get_physical_address: ProtocolGetPhysicalAddress,
+    pub get_block_size: ProtocolGetBlockSize,
+    pub read: ProtocolRead,
+    pub write: ProtocolWrite,
+    pub erase_blocks: ProtocolEraseBlocks,
+    pub parent_handle: efi::Handle,
+}
+
+/// Firmware Volume Block 2 protocol.
+pub struct FirmwareVolumeBlock2;
+
+unsafe impl ProtocolTrait for FirmwareVolumeBlock2 {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for FirmwareVolumeBlock2 {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// The blocks of a volume, as runs of blocks of the same size.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlockMap {
+    /// The number of blocks and the size of the blocks of each run.
+    runs: Vec<(usize, usize)>,
+}
+
+impl BlockMap {
+    /// A map of runs of `(number of blocks, block size)`.
+    pub fn new(runs: Vec<(usize, usize)>) -> Self {
+        Self { runs }
+    }
+
+    pub fn runs(&self) -> &[(usize, usize)] {
+        &self.runs
+    }
+
+    pub fn block_count(&self) -> u64 {
+        self.runs.iter().map(|(count, _)| *count as u64).sum()
+    }
+
+    /// The size of the volume, in bytes.
+    pub fn size(&self) -> usize {
+        self.runs.iter().map(|(count, size)| count * size).sum()
+    }
+
+    /// The offset in the volume and the size of the block `lba`.
+    pub fn block(&self, lba: u64) -> Option<(usize, usize)> {
+        let mut first = 0;
+        let mut offset = 0;
+        for &(count, size) in &self.runs {
+            if lba < first + count as u64 {
+                return Some((offset + (lba - first) as usize * size, size));
+            }
+            first += count as u64;
+            offset += count * size;
+        }
+        None
+    }
+
+    /// The block at `offset` in the volume, and the offset in the block.
+    pub fn locate(&self, offset: usize) -> Option<(u64, usize)> {
+        let mut lba = 0;
+        let mut start = 0;
+        for &(count, size) in &self.runs {
+            if offset < start + count * size {
+                let index = (offset - start) / size;
+                return Some((lba + index as u64, offset - start - index * size));
+            }
+            lba += count as u64;
+            start += count * size;
+        }
+        None
+    }
+}
+
+/// Typed access to an instance of the Firmware Volume Block 2 protocol, with the block map of its volume.
+pub struct FirmwareVolumeBlock {
+    protocol: &'static mut Protocol,
+    block_map: BlockMap,
+}
+
+impl FirmwareVolumeBlock {
+    /// Gets the instance of the protocol installed on the handle of a firmware volume, and reads its block map.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &FirmwareVolumeBlock2).and_then(Self::new)
+    }
+
+    /// Wraps an instance of the protocol, and reads its block map.
+    pub fn new(protocol: &'static mut Protocol) -> Result<Self, EfiError> {
+        let mut fvb = Self { protocol, block_map: BlockMap::default() };
+        // Each run starts at the block following the previous one, past the last block the protocol fails.
+        let mut lba = 0;
+        while let Ok((size, count)) = fvb.block_size(lba) {
+            if size == 0 || count == 0 {
+                return Err(efi::Status::VOLUME_CORRUPTED.into());
+            }
+            fvb.block_map.runs.push((count, size));
+            lba += count as u64;
+        }
+        match fvb.block_map.runs.is_empty() {
+            true => Err(efi::Status::VOLUME_CORRUPTED.into()),
+            false => Ok(fvb),
+        }
+    }
+
+    fn protocol_ptr(&self) -> *mut Protocol {
+        &*self.protocol as *const Protocol as *mut Protocol
+    }
+
+    pub fn block_map(&self) -> &BlockMap {
+        &self.block_map
+    }
+
+    /// The handle of the device the volume is on.
+    pub fn parent_handle(&self) -> efi::Handle {
+        self.protocol.parent_handle
+    }
+
+    /// The `EFI_FVB_ATTRIBUTES_2` of the volume, see [`attributes`].
+    pub fn attributes(&self) -> Result<u32, EfiError> {
+        let mut attributes = 0;
+        match (self.protocol.get_attributes)(self.protocol_ptr(), &mut attributes) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(attributes),
+        }
+    }
+
+    /// Sets the attributes of the volume that can be changed, returns the resulting attributes.
+    pub fn set_attributes(&mut self, attributes: u32) -> Result<u32, EfiError> {
+        let mut attributes = attributes;
+        match (self.protocol.set_attributes)(self.protocol_ptr(), &mut attributes) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(attributes),
+        }
+    }
+
+    /// The address of the volume, if it is memory mapped.
+    pub fn physical_address(&self) -> Result<efi::PhysicalAddress, EfiError> {
+        let mut address = 0;
+        match (self.protocol.get_physical_address)(self.protocol_ptr(), &mut address) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(address),
+        }
+    }
+
+    /// The size of the block `lba`, and the number of consecutive blocks of that size from it.
+    pub fn block_size(&self, lba: u64) -> Result<(usize, usize), EfiError> {
+        let (mut size, mut count) = (0, 0);
+        match (self.protocol.get_block_size)(self.protocol_ptr(), lba, &mut size, &mut count) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok((size, count)),
+        }
+    }
+
+    /// The value of an erased byte.
+    pub fn erased_byte(&self) -> Result<u8, EfiError> {
+        Ok(match self.attributes()? & attributes::ERASE_POLARITY {
+            0 => 0x00,
+            _ => 0xFF,
+        })
+    }
+
+    /// Checks that `length` bytes from `offset` are within the block `lba`.
+    fn check_block_range(&self, lba: u64, offset: usize, length: usize) -> Result<(), EfiError> {
+        let (_, size) = self.block_map.block(lba).ok_or(efi::Status::INVALID_PARAMETER)?;
+        match offset.checked_add(length) {
+            Some(end) if end <= size => Ok(()),
+            _ => Err(efi::Status::BAD_BUFFER_SIZE.into()),
+        }
+    }
+
+    /// Reads `buffer` from `offset` in the block `lba`.
+    ///
+    /// Returns `BAD_BUFFER_SIZE` if the read would cross the end of the block.
+    pub fn read_block(&self, lba: u64, offset: usize, buffer: &mut [u8]) -> Result<(), EfiError> {
+        self.check_block_range(lba, offset, buffer.len())?;
+        let mut size = buffer.len();
+        match (self.protocol.read)(self.protocol_ptr(), lba, offset, &mut size, buffer.as_mut_ptr()) {
+            s if s.is_error() => Err(s.into()),
+            _ if size != buffer.len() => Err(efi::Status::DEVICE_ERROR.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes `data` at `offset` in the block `lba`, the flash only programs the bits that are erased.
+    ///
+    /// Returns `BAD_BUFFER_SIZE` if the write would cross the end of the block.
+    pub fn write_block(&mut self, lba: u64, offset: usize, data: &[u8]) -> Result<(), EfiError> {
+        self.check_block_range(lba, offset, data.len())?;
+        let mut size = data.len();
+        // The data is only read by the protocol.
+        match (self.protocol.write)(self.protocol_ptr(), lba, offset, &mut size, data.as_ptr() as *mut u8) {
+            s if s.is_error() => Err(s.into()),
+            _ if size != data.len() => Err(efi::Status::DEVICE_ERROR.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Erases `count` blocks from `lba`.
+    pub fn erase_blocks(&mut self, lba: u64, count: usize) -> Result<(), EfiError> {
+        match lba.checked_add(count as u64) {
+            Some(end) if end <= self.block_map.block_count() => (),
+            _ => return Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+        if count == 0 {
+            return Ok(());
+        }
+        match (self.protocol.erase_blocks)(self.protocol_ptr(), lba, count, LBA_LIST_TERMINATOR) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Splits the region of `length` bytes from `offset` in the volume by block, as `(lba, offset in block, offset
+    /// in region, length)`.
+    fn chunks(&self, offset: usize, length: usize) -> Result<Vec<(u64, usize, usize, usize)>, EfiError> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.block_map.size() => (),
+            _ => return Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+        let mut chunks = Vec::new();
+        let mut done = 0;
+        while done < length {
+            // The region is within the volume.
+            let (lba, block_offset) = self.block_map.locate(offset + done).unwrap();
+            let (_, size) = self.block_map.block(lba).unwrap();
+            let chunk = (size - block_offset).min(length - done);
+            chunks.push((lba, block_offset, done, chunk));
+            done += chunk;
+        }
+        Ok(chunks)
+    }
+
+    /// Reads `buffer` from `offset` in the volume, across blocks.
+    pub fn read(&self, offset: usize, buffer: &mut [u8]) -> Result<(), EfiError> {
+        for (lba, block_offset, start, length) in self.chunks(offset, buffer.len())? {
+            self.read_block(lba, block_offset, &mut buffer[start..start + length])?;
+        }
+        Ok(())
+    }
+
+    /// Reads `length` bytes from `offset` in the volume.
+    pub fn read_region(&self, offset: usize, length: usize) -> Result<Vec<u8>, EfiError> {
+        let mut buffer = vec![0; length];
+        self.read(offset, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Writes `data` at `offset` in the volume, across blocks, and returns the number of blocks erased.
+    ///
+    /// The blocks whose data does not change are left alone, the data is programmed over the old data when only
+    /// erased bits are changed, otherwise the block is read, erased and written back with the data.
+    pub fn update(&mut self, offset: usize, data: &[u8]) -> Result<usize, EfiError> {
+        let chunks = self.chunks(offset, data.len())?;
+        if self.attributes()? & attributes::WRITE_STATUS == 0 {
+            return Err(efi::Status::WRITE_PROTECTED.into());
+        }
+        let erased = self.erased_byte()?;
+        let mut erases = 0;
+        for (lba, block_offset, start, length) in chunks {
+            let (_, size) = self.block_map.block(lba).unwrap();
+            let mut block = vec![0; size];
+            self.read_block(lba, 0, &mut block)?;
+            let new = &data[start..start + length];
+            let old = &mut block[block_offset..block_offset + length];
+            if old == new {
+                continue;
+            }
+            // The programming only turns erased bits into the other value.
+            let programmable = old.iter().zip(new).all(|(old, new)| (old ^ new) & (old ^ erased) == 0);
+            if programmable {
+                self.write_block(lba, block_offset, new)?;
+                continue;
+            }
+            old.copy_from_slice(new);
+            self.erase_blocks(lba, 1)?;
+            erases += 1;
+            self.write_block(lba, 0, &block)?;
+        }
+        Ok(erases)
+    }
+}
+
+impl fmt::Debug for FirmwareVolumeBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FirmwareVolumeBlock").field("block_map", &self.block_map).finish_non_exhaustive()
+    }
+}
+
+/// The instances of the protocol whose block map can be read, from the handles that support it.
+pub fn instances<B: BootServices>(boot_services: &B) -> Result<Vec<FirmwareVolumeBlock>, EfiError> {
+    let handles = HandleBuffer::supporting(boot_services, &FirmwareVolumeBlock2)?;
+    Ok((&handles).into_iter().filter_map(|handle| FirmwareVolumeBlock::get(boot_services, handle).ok()).collect())
+}
+
+/// The instance of the protocol of the memory mapped volume at `address`.
+pub fn find_by_address<B: BootServices>(
+    boot_services: &B,
+    address: efi::PhysicalAddress,
+) -> Result<FirmwareVolumeBlock, EfiError> {
+    instances(boot_services)?
+        .into_iter()
+        .find(|fvb| fvb.physical_address() == Ok(address))
+        .ok_or(efi::Status::NOT_FOUND.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::mock::InMemoryBootServices;
+    use core::{ffi::c_void, ptr, slice};
+
+    const BASE: efi::PhysicalAddress = 0xFF00_0000;
+
+    /// Flash of four blocks of 16 bytes and two of 32 bytes, the protocol is the first field to be found from its
+    /// pointer. The writes only clear bits, as on a NOR flash.
+    #[repr(C)]
+    struct TestFlash {
+        protocol: Protocol,
+        attributes: u32,
+        runs: Vec<(usize, usize)>,
+        data: Vec<u8>,
+        erases: Vec<(u64, usize)>,
+    }
+
+    fn test_flash<'a>(this: *mut Protocol) -> &'a mut TestFlash {
+        unsafe { &mut *(this as *mut TestFlash) }
+    }
+
+    extern "efiapi" fn get_attributes(this: *mut Protocol, attributes: *mut u32) -> efi::Status {
+        unsafe { *attributes = test_flash(this).attributes };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_attributes(this: *mut Protocol, attributes: *mut u32) -> efi::Status {
+        // Only the write status can change.
+        let flash = test_flash(this);
+        let requested = unsafe { *attributes };
+        flash.attributes = flash.attributes & !attributes::WRITE_STATUS | requested & attributes::WRITE_STATUS;
+        unsafe { *attributes = flash.attributes };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_physical_address(_this: *mut Protocol, address: *mut efi::PhysicalAddress) -> efi::Status {
+        unsafe { *address = BASE };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_block_size(
+        this: *mut Protocol,
+        lba: u64,
+        size: *mut usize,
+        count: *mut usize,
+    ) -> efi::Status {
+        let mut first = 0;
+        for &(run_count, run_size) in &test_flash(this).runs {
+            if lba < first + run_count as u64 {
+                unsafe {
+                    *size = run_size;
+                    *count = run_count - (lba - first) as usize;
+                }
+                return efi::Status::SUCCESS;
+            }
+            first += run_count as u64;
+        }
+        efi::Status::INVALID_PARAMETER
+    }
+
+    fn block_range(flash: &TestFlash, lba: u64, offset: usize, size: usize) -> Option<core::ops::Range<usize>> {
+        let (start, block_size) = BlockMap::new(flash.runs.clone()).block(lba)?;
+        (offset + size <= block_size).then_some(start + offset..start + offset + size)
+    }
+
+    extern "efiapi" fn read(
+        this: *mut Protocol,
+        lba: u64,
+        offset: usize,
+        size: *mut usize,
+        buffer: *mut u8,
+    ) -> efi::Status {
+        let flash = test_flash(this);
+        let Some(range) = block_range(flash, lba, offset, unsafe { *size }) else {
+            return efi::Status::BAD_BUFFER_SIZE;
+        };
+        unsafe { slice::from_raw_parts_mut(buffer, range.len()) }.copy_from_slice(&flash.data[range]);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn write(
+        this: *mut Protocol,
+        lba: u64,
+        offset: usize,
+        size: *mut usize,
+        buffer: *mut u8,
+    ) -> efi::Status {
+        let flash = test_flash(this);
+        if flash.attributes & attributes::WRITE_STATUS == 0 {
+            return efi::Status::ACCESS_DENIED;
+        }
+        let Some(range) = block_range(flash, lba, offset, unsafe { *size }) else {
+            return efi::Status::BAD_BUFFER_SIZE;
+        };
+        let data = unsafe { slice::from_raw_parts(buffer, range.len()) };
+        flash.data[range].iter_mut().zip(data).for_each(|(cell, byte)| *cell &= byte);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn erase_blocks(this: *mut Protocol, lba: u64, count: usize, terminator: u64) -> efi::Status {
+        assert_eq!(LBA_LIST_TERMINATOR, terminator);
+        let flash = test_flash(this);
+        for lba in lba..lba + count as u64 {
+            let (start, size) = BlockMap::new(flash.runs.clone()).block(lba).unwrap();
+            flash.data[start..start + size].fill(0xFF);
+        }
+        flash.erases.push((lba, count));
+        efi::Status::SUCCESS
+    }
+
+    fn flash() -> (InMemoryBootServices, &'static mut TestFlash) {
+        let boot_services = InMemoryBootServices::new();
+        let flash = Box::leak(Box::new(TestFlash {
+            protocol: Protocol {
+                get_attributes,
+                set_attributes,
+                get_physical_address,
+                get_block_size,
+                read,
+                write,
+                erase_blocks,
+                parent_handle: ptr::null_mut(),
+            },
+            attributes: attributes::ERASE_POLARITY | attributes::WRITE_STATUS | attributes::MEMORY_MAPPED,
+            runs: vec![(4, 16), (2, 32)],
+            data: (0..128).map(|i| i as u8).collect(),
+            erases: Vec::new(),
+        }));
+        let flash_ptr = flash as *mut TestFlash;
+        unsafe { boot_services.install_protocol_interface_unchecked(None, &PROTOCOL_GUID, flash_ptr as *mut c_void) }
+            .unwrap();
+        (boot_services, unsafe { &mut *flash_ptr })
+    }
+
+    #[test]
+    fn test_block_map() {
+        let (boot_services, _) = flash();
+        let fvb = find_by_address(&boot_services, BASE).unwrap();
+        let map = fvb.block_map();
+        assert_eq!(&[(4, 16), (2, 32)], map.runs());
+        assert_eq!((6, 128), (map.block_count(), map.size()));
+        assert_eq!((Some((48, 16)), Some((96, 32)), None), (map.block(3), map.block(5), map.block(6)));
+        assert_eq!((Some((4, 4)), Some((3, 15)), None), (map.locate(68), map.locate(63), map.locate(128)));
+        assert_eq!(efi::Status::NOT_FOUND, find_by_address(&boot_services, 0).unwrap_err());
+    }
+
+    #[test]
+    fn test_read_write_blocks() {
+        let (boot_services, flash) = flash();
+        let mut fvb = instances(&boot_services).unwrap().pop().unwrap();
+        assert_eq!(0xFF, fvb.erased_byte().unwrap());
+
+        let mut buffer = [0; 4];
+        fvb.read_block(4, 28, &mut buffer).unwrap();
+        assert_eq!([92, 93, 94, 95], buffer);
+        assert_eq!(efi::Status::BAD_BUFFER_SIZE, fvb.read_block(0, 14, &mut buffer).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, fvb.read_block(6, 0, &mut buffer).unwrap_err());
+
+        fvb.erase_blocks(1, 2).unwrap();
+        assert_eq!(&[(1, 2)], flash.erases.as_slice());
+        fvb.write_block(2, 0, &[1, 2]).unwrap();
+        assert_eq!([1, 2, 0xFF], flash.data[32..35]);
+        assert_eq!(efi::Status::INVALID_PARAMETER, fvb.erase_blocks(5, 2).unwrap_err());
+
+        // A region across blocks.
+        assert_eq!(vec![13, 14, 15, 0xFF, 0xFF], fvb.read_region(13, 5).unwrap());
+        assert_eq!(efi::Status::INVALID_PARAMETER, fvb.read_region(120, 9).unwrap_err());
+    }
+
+    #[test]
+    fn test_update() {
+        let (boot_services, flash) = flash();
+        let mut fvb = instances(&boot_services).unwrap().pop().unwrap();
+
+        // The same data, then only bits cleared: nothing is erased.
+        assert_eq!(0, fvb.update(0, &[0, 1, 2]).unwrap());
+        assert_eq!(0, fvb.update(3, &[0x02]).unwrap());
+        assert_eq!(0x02, flash.data[3]);
+
+        // Bits set across two blocks of different sizes, the rest of the blocks is kept.
+        let data = [0xAA; 8];
+        assert_eq!(2, fvb.update(60, &data).unwrap());
+        assert_eq!(&[(3, 1), (4, 1)], flash.erases.as_slice());
+        assert_eq!(data, flash.data[60..68]);
+        assert_eq!([58, 59], flash.data[58..60]);
+        assert_eq!((68..96).collect::<Vec<u8>>(), flash.data[68..96]);
+
+        fvb.set_attributes(attributes::ERASE_POLARITY).unwrap();
+        assert_eq!(efi::Status::WRITE_PROTECTED, fvb.update(0, &[0xFF]).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, fvb.update(127, &[0, 0]).unwrap_err());
+    }
+}
@@ -0,0 +1,651 @@
+//! Graphics Output protocol.
+//!
+//! [`GraphicsOutput`] lists and selects the video modes of a display, and copies rectangles of pixels between the
+//! screen and [`BltBuffer`]s. Every rectangle is checked against the screen and the buffer before it reaches the
+//! firmware:
+//!
+//! ```ignore
+//! let mut gop = GraphicsOutput::locate(&boot_services)?;
+//! let mode = gop.modes(&boot_services).max_by_key(|mode| mode.width * mode.height).ok_or(efi::Status::NOT_FOUND)?;
+//! gop.set_mode(mode.mode)?;
+//! gop.fill(Pixel::rgb(0, 0, 0x80), Rect::new(0, 0, mode.width as usize, mode.height as usize))?;
+//! ```
+//!
+//! BMP images are decoded with [`bmp::decode`], and [`boot_logo`] displays the boot logo of the platform. Menus and
+//! progress bars are drawn on a [`BltSurface`] to be shown at once.
+//!
+//! With the `embedded_graphics` feature, the screen and the buffers are also `embedded-graphics` draw targets.
+//!
+//! [UEFI Spec Documentation: 12.9. Graphics Output Protocol](https://uefi.org/specs/UEFI/2.10/12_Protocols_Console_Support.html#efi-graphics-output-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, mem, ptr};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use efi::protocols::graphics_output as gop;
+
+pub mod bmp;
+pub mod boot_logo;
+#[cfg(feature = "embedded_graphics")]
+mod draw_target;
+mod font;
+mod surface;
+
+pub use surface::BltSurface;
+
+type GraphicsOutputProtocol = gop::Protocol;
+
+/// Masks of the bits of each color in a pixel of the frame buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PixelBitmask {
+    pub red: u32,
+    pub green: u32,
+    pub blue: u32,
+    pub reserved: u32,
+}
+
+/// Layout of the pixels of the frame buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// A byte of red, green, blue and reserved, in this order.
+    Rgb,
+    /// A byte of blue, green, red and reserved, in this order.
+    Bgr,
+    /// 32 bits whose colors are given by masks.
+    Bitmask(PixelBitmask),
+    /// There is no frame buffer, the screen is only accessible with Blt operations.
+    BltOnly,
+}
+
+impl From<&gop::ModeInformation> for PixelFormat {
+    fn from(info: &gop::ModeInformation) -> Self {
+        match info.pixel_format {
+            gop::PIXEL_RED_GREEN_BLUE_RESERVED_8_BIT_PER_COLOR => Self::Rgb,
+            gop::PIXEL_BLUE_GREEN_RED_RESERVED_8_BIT_PER_COLOR => Self::Bgr,
+            gop::PIXEL_BIT_MASK => Self::Bitmask(PixelBitmask {
+                red: info.pixel_information.red_mask,
+                green: info.pixel_information.green_mask,
+                blue: info.pixel_information.blue_mask,
+                reserved: info.pixel_information.reserved_mask,
+            }),
+            // The frame buffer of an unknown format cannot be used.
+            _ => Self::BltOnly,
+        }
+    }
+}
+
+/// Description of a video mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModeInfo {
+    /// Number of the mode, to select it with [`GraphicsOutput::set_mode`].
+    pub mode: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    /// Number of pixels of a line of the frame buffer, which may be more than the width.
+    pub stride: u32,
+}
+
+impl ModeInfo {
+    fn new(mode: u32, info: &gop::ModeInformation) -> Self {
+        Self {
+            mode,
+            width: info.horizontal_resolution,
+            height: info.vertical_resolution,
+            pixel_format: PixelFormat::from(info),
+            stride: info.pixels_per_scan_line,
+        }
+    }
+}
+
+/// A pixel of a Blt operation, with the layout of `EFI_GRAPHICS_OUTPUT_BLT_PIXEL`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Pixel {
+    pub blue: u8,
+    pub green: u8,
+    pub red: u8,
+    pub reserved: u8,
+}
+
+impl Pixel {
+    pub const BLACK: Pixel = Pixel::rgb(0, 0, 0);
+    pub const WHITE: Pixel = Pixel::rgb(0xFF, 0xFF, 0xFF);
+
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Self { blue, green, red, reserved: 0 }
+    }
+}
+
+/// A position on the screen or in a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Point {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Point {
+    pub const fn new(x: usize, y: usize) -> Self {
+        Self { x, y }
+    }
+}
+
+/// A rectangle on the screen or in a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// The top left corner of the rectangle.
+    pub const fn origin(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The rectangle of the same size whose top left corner is `origin`.
+    pub const fn moved_to(&self, origin: Point) -> Self {
+        Self::new(origin.x, origin.y, self.width, self.height)
+    }
+
+    /// Whether the rectangle is inside an area of `width` by `height`.
+    pub fn fits_in(&self, width: usize, height: usize) -> bool {
+        matches!(self.x.checked_add(self.width), Some(right) if right <= width)
+            && matches!(self.y.checked_add(self.height), Some(bottom) if bottom <= height)
+    }
+}
+
+/// Pixels in memory, the source or destination of Blt operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BltBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl BltBuffer {
+    /// A buffer of `width` by `height` black pixels.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, pixels: vec![Pixel::BLACK; width * height] }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The rectangle covering the whole buffer.
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// The pixels of the buffer, line by line.
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Pixel] {
+        &mut self.pixels
+    }
+
+    /// The pixel at `point`, if it is in the buffer.
+    pub fn pixel(&self, point: Point) -> Option<Pixel> {
+        (point.x < self.width && point.y < self.height).then(|| self.pixels[point.y * self.width + point.x])
+    }
+
+    /// Sets the pixel at `point`, points outside of the buffer are ignored.
+    pub fn set_pixel(&mut self, point: Point, pixel: Pixel) {
+        if point.x < self.width && point.y < self.height {
+            self.pixels[point.y * self.width + point.x] = pixel;
+        }
+    }
+
+    /// Sets all the pixels of `rect` that are in the buffer.
+    pub fn fill(&mut self, rect: Rect, pixel: Pixel) {
+        let right = rect.x.saturating_add(rect.width).min(self.width);
+        let bottom = rect.y.saturating_add(rect.height).min(self.height);
+        for y in rect.y.min(bottom)..bottom {
+            self.pixels[y * self.width + rect.x.min(right)..y * self.width + right].fill(pixel);
+        }
+    }
+
+    /// The distance between two lines, in bytes.
+    fn delta(&self) -> usize {
+        self.width * mem::size_of::<Pixel>()
+    }
+}
+
+/// Typed access to an instance of the Graphics Output protocol.
+pub struct GraphicsOutput(&'static mut GraphicsOutputProtocol);
+
+impl GraphicsOutput {
+    /// Locates the first instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::GraphicOutput, None).map(Self)
+    }
+
+    /// Gets the instance of the protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::GraphicOutput).map(Self)
+    }
+
+    fn mode(&self) -> &gop::Mode {
+        //SAFETY: The protocol keeps its mode valid while it is installed.
+        unsafe { &*self.0.mode }
+    }
+
+    /// The number of video modes, they are numbered from 0.
+    pub fn mode_count(&self) -> u32 {
+        self.mode().max_mode
+    }
+
+    /// The description of `mode`.
+    pub fn query_mode<B: BootServices>(&self, boot_services: &B, mode: u32) -> Result<ModeInfo, EfiError> {
+        let this = self.0 as *const GraphicsOutputProtocol as *mut GraphicsOutputProtocol;
+        let mut size = 0;
+        let mut info = ptr::null_mut();
+        match unsafe { r_efi::efi_call(self.0.query_mode, (this, mode, &mut size, &mut info)) } {
+            s if s.is_error() => return Err(s.into()),
+            _ if info.is_null() || size < mem::size_of::<gop::ModeInformation>() => {
+                return Err(efi::Status::DEVICE_ERROR.into());
+            }
+            _ => (),
+        }
+        //SAFETY: The information is allocated by the protocol for the caller and checked to be large enough.
+        let mode_info = ModeInfo::new(mode, unsafe { &*info });
+        let _ = boot_services.free_pool(info as *mut u8);
+        Ok(mode_info)
+    }
+
+    /// The description of every video mode supported by the display.
+    pub fn modes<'a, B: BootServices>(&'a self, boot_services: &'a B) -> impl Iterator<Item = ModeInfo> + 'a {
+        (0..self.mode_count()).filter_map(|mode| self.query_mode(boot_services, mode).ok())
+    }
+
+    /// The description of the current video mode.
+    pub fn current_mode(&self) -> ModeInfo {
+        let mode = self.mode();
+        //SAFETY: The information of the current mode is owned by the protocol.
+        ModeInfo::new(mode.mode, unsafe { &*mode.info })
+    }
+
+    /// Selects a video mode, which clears the screen.
+    pub fn set_mode(&mut self, mode: u32) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.0.set_mode, (self.0, mode)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The physical address of the frame buffer, if the current mode has one.
+    pub fn frame_buffer_base(&self) -> Option<efi::PhysicalAddress> {
+        match self.current_mode().pixel_format {
+            PixelFormat::BltOnly => None,
+            _ => Some(self.mode().frame_buffer_base),
+        }
+    }
+
+    /// The size of the frame buffer, in bytes.
+    pub fn frame_buffer_size(&self) -> usize {
+        self.mode().frame_buffer_size
+    }
+
+    /// The number of pixels of a line of the frame buffer.
+    pub fn stride(&self) -> u32 {
+        self.current_mode().stride
+    }
+
+    /// Checks that `rect` is on the screen.
+    fn check_screen(&self, rect: &Rect) -> Result<(), EfiError> {
+        let mode = self.current_mode();
+        match rect.fits_in(mode.width as usize, mode.height as usize) {
+            true => Ok(()),
+            false => Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+    }
+
+    /// Checks that `rect` is in `buffer`.
+    fn check_buffer(buffer: &BltBuffer, rect: &Rect) -> Result<(), EfiError> {
+        match rect.fits_in(buffer.width, buffer.height) {
+            true => Ok(()),
+            false => Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn blt(
+        &mut self,
+        buffer: *mut Pixel,
+        operation: gop::BltOperation,
+        source: Point,
+        destination: Point,
+        width: usize,
+        height: usize,
+        delta: usize,
+    ) -> Result<(), EfiError> {
+        match unsafe {
+            r_efi::efi_call(
+                self.0.blt,
+                (
+                    self.0,
+                    buffer as *mut gop::BltPixel,
+                    operation,
+                    source.x,
+                    source.y,
+                    destination.x,
+                    destination.y,
+                    width,
+                    height,
+                    delta,
+                ),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fills `rect` of the screen with `pixel`.
+    pub fn fill(&mut self, pixel: Pixel, rect: Rect) -> Result<(), EfiError> {
+        self.check_screen(&rect)?;
+        if rect.is_empty() {
+            return Ok(());
+        }
+        let mut pixel = pixel;
+        self.blt(&mut pixel, gop::BLT_VIDEO_FILL, Point::default(), rect.origin(), rect.width, rect.height, 0)
+    }
+
+    /// Copies `source` of `buffer` to the screen, at `destination`.
+    pub fn buffer_to_video(&mut self, buffer: &BltBuffer, source: Rect, destination: Point) -> Result<(), EfiError> {
+        Self::check_buffer(buffer, &source)?;
+        self.check_screen(&source.moved_to(destination))?;
+        if source.is_empty() {
+            return Ok(());
+        }
+        // The firmware only reads the buffer of this operation.
+        let pixels = buffer.pixels.as_ptr() as *mut Pixel;
+        self.blt(
+            pixels,
+            gop::BLT_BUFFER_TO_VIDEO,
+            source.origin(),
+            destination,
+            source.width,
+            source.height,
+            buffer.delta(),
+        )
+    }
+
+    /// Copies `source` of the screen to `buffer`, at `destination`.
+    pub fn video_to_buffer(
+        &mut self,
+        source: Rect,
+        buffer: &mut BltBuffer,
+        destination: Point,
+    ) -> Result<(), EfiError> {
+        self.check_screen(&source)?;
+        Self::check_buffer(buffer, &source.moved_to(destination))?;
+        if source.is_empty() {
+            return Ok(());
+        }
+        let delta = buffer.delta();
+        self.blt(
+            buffer.pixels.as_mut_ptr(),
+            gop::BLT_VIDEO_TO_BLT_BUFFER,
+            source.origin(),
+            destination,
+            source.width,
+            source.height,
+            delta,
+        )
+    }
+
+    /// Copies `source` of the screen to `destination`, the two may overlap.
+    pub fn video_to_video(&mut self, source: Rect, destination: Point) -> Result<(), EfiError> {
+        self.check_screen(&source)?;
+        self.check_screen(&source.moved_to(destination))?;
+        if source.is_empty() {
+            return Ok(());
+        }
+        self.blt(ptr::null_mut(), gop::BLT_VIDEO_TO_VIDEO, source.origin(), destination, source.width, source.height, 0)
+    }
+}
+
+impl From<&'static mut GraphicsOutputProtocol> for GraphicsOutput {
+    fn from(protocol: &'static mut GraphicsOutputProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for GraphicsOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GraphicsOutput").field("mode", &self.current_mode()).finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+
+    /// Display of two modes drawing into a frame buffer of BGR pixels, the protocol is the first field to be found
+    /// from its pointer.
+    #[repr(C)]
+    pub(crate) struct TestDisplay {
+        protocol: GraphicsOutputProtocol,
+        mode: gop::Mode,
+        infos: Vec<gop::ModeInformation>,
+        pub frame: Vec<Pixel>,
+        pub blt_calls: usize,
+    }
+
+    fn test_display<'a>(this: *mut GraphicsOutputProtocol) -> &'a mut TestDisplay {
+        unsafe { &mut *(this as *mut TestDisplay) }
+    }
+
+    fn mode_information(width: u32, height: u32, pixel_format: gop::GraphicsPixelFormat) -> gop::ModeInformation {
+        gop::ModeInformation {
+            version: 0,
+            horizontal_resolution: width,
+            vertical_resolution: height,
+            pixel_format,
+            pixel_information: gop::PixelBitmask { red_mask: 0, green_mask: 0, blue_mask: 0, reserved_mask: 0 },
+            pixels_per_scan_line: width,
+        }
+    }
+
+    extern "efiapi" fn query_mode(
+        this: *mut GraphicsOutputProtocol,
+        mode: u32,
+        size: *mut usize,
+        info: *mut *mut gop::ModeInformation,
+    ) -> efi::Status {
+        match test_display(this).infos.get(mode as usize) {
+            Some(mode_info) => {
+                unsafe {
+                    *size = mem::size_of::<gop::ModeInformation>();
+                    *info = Box::leak(Box::new(*mode_info));
+                }
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::INVALID_PARAMETER,
+        }
+    }
+
+    extern "efiapi" fn set_mode(this: *mut GraphicsOutputProtocol, mode: u32) -> efi::Status {
+        let display = test_display(this);
+        if mode as usize >= display.infos.len() {
+            return efi::Status::UNSUPPORTED;
+        }
+        let info = &mut display.infos[mode as usize];
+        display.frame = vec![Pixel::BLACK; (info.horizontal_resolution * info.vertical_resolution) as usize];
+        display.mode.mode = mode;
+        display.mode.info = info;
+        display.mode.frame_buffer_base = display.frame.as_ptr() as efi::PhysicalAddress;
+        display.mode.frame_buffer_size = display.frame.len() * mem::size_of::<Pixel>();
+        efi::Status::SUCCESS
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    extern "efiapi" fn blt(
+        this: *mut GraphicsOutputProtocol,
+        buffer: *mut gop::BltPixel,
+        operation: gop::BltOperation,
+        source_x: usize,
+        source_y: usize,
+        destination_x: usize,
+        destination_y: usize,
+        width: usize,
+        height: usize,
+        delta: usize,
+    ) -> efi::Status {
+        let display = test_display(this);
+        display.blt_calls += 1;
+        let screen_width = unsafe { (*display.mode.info).horizontal_resolution } as usize;
+        let buffer = buffer as *mut Pixel;
+        let stride = delta / mem::size_of::<Pixel>();
+        // Copy from a snapshot of the frame, the rectangles of a video to video copy may overlap.
+        let frame = display.frame.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let screen = (destination_y + y) * screen_width + destination_x + x;
+                let source = (source_y + y) * screen_width + source_x + x;
+                match operation {
+                    gop::BLT_VIDEO_FILL => display.frame[screen] = unsafe { *buffer },
+                    gop::BLT_BUFFER_TO_VIDEO => {
+                        display.frame[screen] = unsafe { *buffer.add((source_y + y) * stride + source_x + x) }
+                    }
+                    gop::BLT_VIDEO_TO_BLT_BUFFER => unsafe {
+                        *buffer.add((destination_y + y) * stride + destination_x + x) = frame[source]
+                    },
+                    gop::BLT_VIDEO_TO_VIDEO => display.frame[screen] = frame[source],
+                    _ => return efi::Status::UNSUPPORTED,
+                }
+            }
+        }
+        efi::Status::SUCCESS
+    }
+
+    /// Display of modes 8x6 BGR and 4x3 without frame buffer, in the first mode.
+    pub(crate) fn graphics_output() -> (GraphicsOutput, &'static TestDisplay, MockBootServices) {
+        let display = Box::leak(Box::new(TestDisplay {
+            protocol: GraphicsOutputProtocol { query_mode, set_mode, blt, mode: ptr::null_mut() },
+            mode: gop::Mode {
+                max_mode: 2,
+                mode: 0,
+                info: ptr::null_mut(),
+                size_of_info: mem::size_of::<gop::ModeInformation>(),
+                frame_buffer_base: 0,
+                frame_buffer_size: 0,
+            },
+            infos: vec![
+                mode_information(8, 6, gop::PIXEL_BLUE_GREEN_RED_RESERVED_8_BIT_PER_COLOR),
+                mode_information(4, 3, gop::PIXEL_BLT_ONLY),
+            ],
+            frame: Vec::new(),
+            blt_calls: 0,
+        }));
+        display.protocol.mode = &mut display.mode;
+        assert_eq!(efi::Status::SUCCESS, set_mode(&mut display.protocol, 0));
+        let display_ptr = display as *mut TestDisplay as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::GraphicOutput, GraphicsOutputProtocol>()
+            .once()
+            .returning(move |_, _| Ok(unsafe { &mut (*(display_ptr as *mut TestDisplay)).protocol }));
+        (
+            GraphicsOutput::locate(&boot_services).unwrap(),
+            unsafe { &*(display_ptr as *const TestDisplay) },
+            boot_services,
+        )
+    }
+
+    #[test]
+    fn test_modes() {
+        let (mut gop, display, mut boot_services) = graphics_output();
+        boot_services.expect_free_pool().times(2).returning(|info| {
+            drop(unsafe { Box::from_raw(info as *mut gop::ModeInformation) });
+            Ok(())
+        });
+        let modes = gop.modes(&boot_services).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ModeInfo { mode: 0, width: 8, height: 6, pixel_format: PixelFormat::Bgr, stride: 8 },
+                ModeInfo { mode: 1, width: 4, height: 3, pixel_format: PixelFormat::BltOnly, stride: 4 },
+            ],
+            modes
+        );
+        assert_eq!(modes[0], gop.current_mode());
+        assert_eq!(Some(display.frame.as_ptr() as efi::PhysicalAddress), gop.frame_buffer_base());
+        assert_eq!(8 * 6 * 4, gop.frame_buffer_size());
+
+        gop.set_mode(1).unwrap();
+        assert_eq!(modes[1], gop.current_mode());
+        assert_eq!(4, gop.stride());
+        assert_eq!(None, gop.frame_buffer_base());
+        assert_eq!(efi::Status::UNSUPPORTED, gop.set_mode(2).unwrap_err());
+    }
+
+    #[test]
+    fn test_blt() {
+        let (mut gop, display, _boot_services) = graphics_output();
+        let red = Pixel::rgb(0xFF, 0, 0);
+        gop.fill(red, Rect::new(6, 4, 2, 2)).unwrap();
+        assert_eq!(red, display.frame[5 * 8 + 7]);
+        assert_eq!(Pixel::BLACK, display.frame[3 * 8 + 5]);
+
+        let mut buffer = BltBuffer::new(3, 3);
+        gop.video_to_buffer(Rect::new(6, 4, 2, 2), &mut buffer, Point::new(1, 1)).unwrap();
+        assert_eq!(Some(red), buffer.pixel(Point::new(2, 2)));
+        assert_eq!(Some(Pixel::BLACK), buffer.pixel(Point::new(0, 0)));
+
+        buffer.fill(Rect::new(0, 0, 1, 3), Pixel::WHITE);
+        gop.buffer_to_video(&buffer, buffer.bounds(), Point::new(0, 0)).unwrap();
+        assert_eq!(Pixel::WHITE, display.frame[2 * 8]);
+        assert_eq!(red, display.frame[2 * 8 + 2]);
+
+        gop.video_to_video(Rect::new(0, 0, 3, 3), Point::new(1, 0)).unwrap();
+        assert_eq!(Pixel::WHITE, display.frame[1]);
+        assert_eq!(Pixel::WHITE, display.frame[2 * 8 + 1]);
+        assert_eq!(red, display.frame[2 * 8 + 3]);
+        assert_eq!(4, display.blt_calls);
+    }
+
+    #[test]
+    fn test_blt_bounds() {
+        let (mut gop, display, _boot_services) = graphics_output();
+        let mut buffer = BltBuffer::new(2, 2);
+        let invalid = efi::Status::INVALID_PARAMETER;
+        assert_eq!(invalid, gop.fill(Pixel::WHITE, Rect::new(7, 0, 2, 1)).unwrap_err());
+        assert_eq!(invalid, gop.fill(Pixel::WHITE, Rect::new(0, usize::MAX, 1, 1)).unwrap_err());
+        assert_eq!(invalid, gop.buffer_to_video(&buffer, Rect::new(1, 1, 2, 1), Point::new(0, 0)).unwrap_err());
+        assert_eq!(invalid, gop.buffer_to_video(&buffer, buffer.bounds(), Point::new(0, 5)).unwrap_err());
+        assert_eq!(invalid, gop.video_to_buffer(Rect::new(0, 0, 2, 2), &mut buffer, Point::new(1, 0)).unwrap_err());
+        assert_eq!(invalid, gop.video_to_video(Rect::new(0, 0, 2, 2), Point::new(7, 0)).unwrap_err());
+        gop.fill(Pixel::WHITE, Rect::new(8, 6, 0, 0)).unwrap();
+        assert_eq!(0, display.blt_calls);
+        assert!(display.frame.iter().all(|pixel| *pixel == Pixel::BLACK));
+
+        buffer.fill(Rect::new(1, 1, 5, 5), Pixel::WHITE);
+        buffer.set_pixel(Point::new(2, 0), Pixel::WHITE);
+        assert_eq!(&[Pixel::BLACK, Pixel::BLACK, Pixel::BLACK, Pixel::WHITE], buffer.pixels());
+        assert_eq!(None, buffer.pixel(Point::new(2, 0)));
+    }
+}
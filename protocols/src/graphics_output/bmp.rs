@@ -0,0 +1,169 @@
+//! Decoding of BMP images into [`BltBuffer`]s.
+//!
+//! The supported images are the ones firmware logos use, as `TranslateBmpToGopBlt` of EDK II: uncompressed, stored
+//! bottom-up, with 1, 4 or 8 bits of palette index or 24 or 32 bits of color per pixel.
+
+use r_efi::efi;
+use status::EfiError;
+
+use super::{BltBuffer, Pixel, Point};
+
+/// Size of `BMP_IMAGE_HEADER`, the file header followed by the information header.
+const HEADER_SIZE: usize = 54;
+
+/// Size of the file header, before the information header.
+const FILE_HEADER_SIZE: usize = 14;
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+/// Decodes a BMP image.
+///
+/// Malformed images are rejected with `INVALID_PARAMETER` and the images of an unsupported format with `UNSUPPORTED`.
+pub fn decode(bmp: &[u8]) -> Result<BltBuffer, EfiError> {
+    if bmp.len() < HEADER_SIZE || &bmp[..2] != b"BM" {
+        return Err(efi::Status::UNSUPPORTED.into());
+    }
+    let image_offset = u32_at(bmp, 10) as usize;
+    let info_size = u32_at(bmp, 14) as usize;
+    let width = u32_at(bmp, 18);
+    let height = u32_at(bmp, 22);
+    let bits_per_pixel = u16_at(bmp, 28);
+    if u32_at(bmp, 30) != 0 || (height as i32) < 0 {
+        // Compressed and top-down images.
+        return Err(efi::Status::UNSUPPORTED.into());
+    }
+    if !matches!(bits_per_pixel, 1 | 4 | 8 | 24 | 32) {
+        return Err(efi::Status::UNSUPPORTED.into());
+    }
+    let palette_start = FILE_HEADER_SIZE.checked_add(info_size).ok_or(efi::Status::INVALID_PARAMETER)?;
+    if info_size < HEADER_SIZE - FILE_HEADER_SIZE || image_offset < palette_start || image_offset > bmp.len() {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    let palette = &bmp[palette_start..image_offset];
+    if width == 0 || height == 0 {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+
+    // Lines are padded to 4 bytes.
+    let (width, height) = (width as usize, height as usize);
+    let line_size = width
+        .checked_mul(bits_per_pixel as usize)
+        .and_then(|bits| bits.checked_add(31))
+        .map(|bits| bits / 32 * 4)
+        .ok_or(efi::Status::INVALID_PARAMETER)?;
+    match line_size.checked_mul(height) {
+        Some(size) if size <= bmp.len() - image_offset => (),
+        _ => return Err(efi::Status::INVALID_PARAMETER.into()),
+    }
+
+    let mut buffer = BltBuffer::new(width, height);
+    for (line, data) in bmp[image_offset..].chunks_exact(line_size).take(height).enumerate() {
+        // The first line of the image is the bottom one.
+        let y = height - 1 - line;
+        for x in 0..width {
+            let pixel = match bits_per_pixel {
+                24 | 32 => {
+                    let offset = x * bits_per_pixel as usize / 8;
+                    Pixel::rgb(data[offset + 2], data[offset + 1], data[offset])
+                }
+                _ => {
+                    let bit = x * bits_per_pixel as usize;
+                    let shift = 8 - bits_per_pixel as usize - bit % 8;
+                    let index = (data[bit / 8] >> shift) as usize & ((1 << bits_per_pixel) - 1);
+                    match palette.get(index * 4..index * 4 + 3) {
+                        Some(color) => Pixel::rgb(color[2], color[1], color[0]),
+                        None => return Err(efi::Status::INVALID_PARAMETER.into()),
+                    }
+                }
+            };
+            buffer.set_pixel(Point::new(x, y), pixel);
+        }
+    }
+    Ok(buffer)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// A BMP image of `width` pixels by `lines.len()`, whose lines are given from the bottom one.
+    pub(crate) fn bmp(width: u32, bits_per_pixel: u16, palette: &[[u8; 4]], lines: &[&[u8]]) -> Vec<u8> {
+        let image_offset = HEADER_SIZE + palette.len() * 4;
+        let line_size = ((width * bits_per_pixel as u32 + 31) / 32 * 4) as usize;
+        let mut bmp = Vec::new();
+        bmp.extend_from_slice(b"BM");
+        bmp.extend_from_slice(&((image_offset + line_size * lines.len()) as u32).to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&(image_offset as u32).to_le_bytes());
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&width.to_le_bytes());
+        bmp.extend_from_slice(&(lines.len() as u32).to_le_bytes());
+        bmp.extend_from_slice(&1u16.to_le_bytes());
+        bmp.extend_from_slice(&bits_per_pixel.to_le_bytes());
+        bmp.extend_from_slice(&[0; 24]);
+        palette.iter().for_each(|color| bmp.extend_from_slice(color));
+        for line in lines {
+            bmp.extend_from_slice(line);
+            bmp.resize(bmp.len() + line_size - line.len(), 0);
+        }
+        bmp
+    }
+
+    const RED: Pixel = Pixel::rgb(0xFF, 0, 0);
+    const BLUE: Pixel = Pixel::rgb(0, 0, 0xFF);
+
+    #[test]
+    fn test_decode_true_color() {
+        let image = decode(&bmp(2, 24, &[], &[&[0, 0, 0xFF, 0xFF, 0, 0], &[0xFF, 0xFF, 0xFF, 0, 0, 0]])).unwrap();
+        assert_eq!((2, 2), (image.width(), image.height()));
+        assert_eq!(&[Pixel::WHITE, Pixel::BLACK, RED, BLUE], image.pixels());
+
+        let image = decode(&bmp(1, 32, &[], &[&[0xFF, 0, 0, 0x80]])).unwrap();
+        assert_eq!(&[BLUE], image.pixels());
+    }
+
+    #[test]
+    fn test_decode_palette() {
+        let palette = [[0, 0, 0, 0], [0, 0, 0xFF, 0], [0xFF, 0, 0, 0]];
+        let image = decode(&bmp(3, 4, &palette, &[&[0x12, 0x00]])).unwrap();
+        assert_eq!(&[RED, BLUE, Pixel::BLACK], image.pixels());
+
+        let image = decode(&bmp(10, 1, &palette[..2], &[&[0b1000_0000, 0b0100_0000]])).unwrap();
+        assert_eq!(RED, image.pixels()[0]);
+        assert_eq!(RED, image.pixels()[9]);
+        assert!(image.pixels()[1..9].iter().all(|pixel| *pixel == Pixel::BLACK));
+
+        let image = decode(&bmp(2, 8, &palette, &[&[2, 1]])).unwrap();
+        assert_eq!(&[BLUE, RED], image.pixels());
+        assert_eq!(efi::Status::INVALID_PARAMETER, decode(&bmp(1, 8, &palette, &[&[3]])).unwrap_err());
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let image = bmp(2, 24, &[], &[&[0; 6], &[0; 6]]);
+        assert_eq!(efi::Status::INVALID_PARAMETER, decode(&image[..image.len() - 1]).unwrap_err());
+        assert_eq!(efi::Status::UNSUPPORTED, decode(&image[..HEADER_SIZE - 1]).unwrap_err());
+        assert_eq!(efi::Status::UNSUPPORTED, decode(&bmp(2, 16, &[], &[&[0; 4]])).unwrap_err());
+
+        let mut compressed = image.clone();
+        compressed[30] = 1;
+        assert_eq!(efi::Status::UNSUPPORTED, decode(&compressed).unwrap_err());
+        let mut top_down = image.clone();
+        top_down[22..26].copy_from_slice(&(-2i32).to_le_bytes());
+        assert_eq!(efi::Status::UNSUPPORTED, decode(&top_down).unwrap_err());
+        let mut empty = image.clone();
+        empty[18..22].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(efi::Status::INVALID_PARAMETER, decode(&empty).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, decode(&bmp(2, 24, &[], &[])).unwrap_err());
+        let mut huge = image;
+        huge[18..22].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(efi::Status::INVALID_PARAMETER, decode(&huge).unwrap_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Display of the boot logo and publication of its Boot Graphics Resource Table.
+//!
+//! [`enable_boot_logo`] does what `BootLogoEnableLogo` of EDK II does for a BMP logo: it centers the logo on the
+//! screen and publishes the BGRT, for the OS to keep the logo on the screen while it boots:
+//!
+//! ```ignore
+//! let mut gop = GraphicsOutput::locate(&boot_services)?;
+//! enable_boot_logo(&boot_services, &mut gop, LOGO_BMP)?;
+//! ```
+//!
+//! [ACPI Spec Documentation: 5.2.23. Boot Graphics Resource Table (BGRT)](https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#boot-graphics-resource-table-bgrt)
+
+use core::{mem, slice};
+
+use boot_services::{allocation::MemoryType, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use super::{bmp, BltBuffer, GraphicsOutput, Point};
+use crate::acpi_table::{AcpiTable, DescriptionHeader, TableKey};
+
+/// Definition of the Boot Graphics Resource Table.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct Bgrt {
+    pub header: DescriptionHeader,
+    pub version: u16,
+    pub status: u8,
+    pub image_type: u8,
+    /// Physical address of the BMP image, in boot services data memory.
+    pub image_address: u64,
+    /// Position of the image on the screen.
+    pub image_offset_x: u32,
+    pub image_offset_y: u32,
+}
+
+impl Bgrt {
+    pub const SIGNATURE: [u8; 4] = *b"BGRT";
+    pub const REVISION: u8 = 1;
+    pub const VERSION: u16 = 1;
+    /// The image is displayed, without rotation.
+    pub const STATUS_DISPLAYED: u8 = 0x01;
+    pub const IMAGE_TYPE_BMP: u8 = 0;
+
+    /// The table of a displayed BMP image at `image_address` whose top left corner is at `offset` on the screen.
+    pub const fn new(image_address: u64, offset: Point) -> Self {
+        Self {
+            header: DescriptionHeader::new(Self::SIGNATURE, mem::size_of::<Self>() as u32, Self::REVISION),
+            version: Self::VERSION,
+            status: Self::STATUS_DISPLAYED,
+            image_type: Self::IMAGE_TYPE_BMP,
+            image_address,
+            image_offset_x: offset.x as u32,
+            image_offset_y: offset.y as u32,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        IntoBytes::as_bytes(self)
+    }
+}
+
+/// Displays `logo` at the center of the screen, returns the position of its top left corner.
+pub fn show_centered(gop: &mut GraphicsOutput, logo: &BltBuffer) -> Result<Point, EfiError> {
+    let mode = gop.current_mode();
+    let (width, height) = (mode.width as usize, mode.height as usize);
+    if logo.width() > width || logo.height() > height {
+        return Err(efi::Status::BAD_BUFFER_SIZE.into());
+    }
+    let position = Point::new((width - logo.width()) / 2, (height - logo.height()) / 2);
+    gop.buffer_to_video(logo, logo.bounds(), position)?;
+    Ok(position)
+}
+
+/// Publishes the BGRT of the BMP image `bmp`, displayed at `offset`.
+///
+/// The image is copied to boot services data memory, which the OS reclaims once it has read the image.
+pub fn publish_bgrt<B: BootServices>(boot_services: &B, bmp: &[u8], offset: Point) -> Result<TableKey, EfiError> {
+    let mut acpi_table = AcpiTable::locate(boot_services)?;
+    let image = boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, bmp.len())?;
+    //SAFETY: The allocation is as large as the image.
+    unsafe { slice::from_raw_parts_mut(image, bmp.len()) }.copy_from_slice(bmp);
+    let bgrt = Bgrt::new(image as u64, offset);
+    acpi_table.install_table(bgrt.as_bytes()).inspect_err(|_| {
+        let _ = boot_services.free_pool(image);
+    })
+}
+
+/// Displays the BMP image `bmp` at the center of the screen and publishes its BGRT.
+pub fn enable_boot_logo<B: BootServices>(
+    boot_services: &B,
+    gop: &mut GraphicsOutput,
+    bmp: &[u8],
+) -> Result<TableKey, EfiError> {
+    let logo = bmp::decode(bmp)?;
+    let position = show_centered(gop, &logo)?;
+    publish_bgrt(boot_services, bmp, position)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        acpi_table::test::acpi_boot_services,
+        graphics_output::{bmp::test::bmp, test::graphics_output, Pixel},
+    };
+    use alloc::{boxed::Box, vec};
+
+    #[test]
+    fn test_enable_boot_logo() {
+        let (mut gop, display, _) = graphics_output();
+        let (mut boot_services, acpi) = acpi_boot_services();
+        boot_services.expect_allocate_pool().once().returning(|memory_type, size| {
+            assert_eq!(MemoryType::BOOT_SERVICES_DATA, memory_type);
+            Ok(Box::leak(vec![0u8; size].into_boxed_slice()).as_mut_ptr())
+        });
+        let logo = bmp(2, 24, &[], &[&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]]);
+        enable_boot_logo(&boot_services, &mut gop, &logo).unwrap();
+
+        // The 2x2 logo is centered on the 8x6 screen.
+        let white = (0..8 * 6).filter(|i| display.frame[*i] == Pixel::WHITE).collect::<vec::Vec<_>>();
+        assert_eq!(vec![2 * 8 + 3, 2 * 8 + 4, 3 * 8 + 3, 3 * 8 + 4], white);
+
+        let table = acpi.tables[0].as_deref().unwrap();
+        assert_eq!(mem::size_of::<Bgrt>(), table.len());
+        let bgrt = Bgrt::read_from_bytes(table).unwrap();
+        assert_eq!(Bgrt::SIGNATURE, bgrt.header.signature);
+        assert_eq!((3, 2), (bgrt.image_offset_x, bgrt.image_offset_y));
+        let image = unsafe { slice::from_raw_parts(bgrt.image_address as *const u8, logo.len()) };
+        assert_eq!(&logo[..], image);
+    }
+
+    #[test]
+    fn test_show_centered_too_large() {
+        let (mut gop, display, _) = graphics_output();
+        assert_eq!(efi::Status::BAD_BUFFER_SIZE, show_centered(&mut gop, &BltBuffer::new(9, 1)).unwrap_err());
+        assert_eq!(Point::new(0, 0), show_centered(&mut gop, &BltBuffer::new(8, 6)).unwrap());
+        assert_eq!(1, display.blt_calls);
+    }
+}
@@ -0,0 +1,234 @@
+//! [`DrawTarget`] implementations, to draw on the screen and in [`BltBuffer`]s with `embedded-graphics`.
+//!
+//! Drawings are clipped to the screen or the buffer, and areas are transferred with a single Blt operation:
+//!
+//! ```ignore
+//! let mut gop = GraphicsOutput::locate(&boot_services)?;
+//! gop.clear(Rgb888::BLACK)?;
+//! Text::new("Booting...", Point::new(10, 20), MonoTextStyle::new(&FONT_10X20, Rgb888::WHITE)).draw(&mut gop)?;
+//! ```
+
+use core::convert::Infallible;
+
+use embedded_graphics_core::{
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Point as EgPoint, Size},
+    pixelcolor::{Rgb888, RgbColor},
+    primitives::{PointsIter, Rectangle},
+    Pixel as EgPixel,
+};
+use r_efi::efi;
+use status::EfiError;
+
+use super::{BltBuffer, BltSurface, GraphicsOutput, Pixel, Point, Rect};
+
+impl From<Rgb888> for Pixel {
+    fn from(color: Rgb888) -> Self {
+        Pixel::rgb(color.r(), color.g(), color.b())
+    }
+}
+
+impl From<Pixel> for Rgb888 {
+    fn from(pixel: Pixel) -> Self {
+        Rgb888::new(pixel.red, pixel.green, pixel.blue)
+    }
+}
+
+/// The part of `area` inside an area of `size` at the origin, if any.
+fn clip(area: &Rectangle, size: Size) -> Option<Rect> {
+    let clipped = area.intersection(&Rectangle::new(EgPoint::zero(), size));
+    // The intersection with an area at the origin has no negative coordinate.
+    (!clipped.is_zero_sized()).then(|| {
+        Rect::new(
+            clipped.top_left.x as usize,
+            clipped.top_left.y as usize,
+            clipped.size.width as usize,
+            clipped.size.height as usize,
+        )
+    })
+}
+
+/// The top left corner of `rect`, as an `embedded-graphics` point.
+fn rect_origin(rect: &Rect) -> EgPoint {
+    EgPoint::new(rect.x as i32, rect.y as i32)
+}
+
+/// The point of an `embedded-graphics` pixel, if it is inside an area of `size` at the origin.
+fn point_in(point: EgPoint, size: Size) -> Option<Point> {
+    match (usize::try_from(point.x), usize::try_from(point.y)) {
+        (Ok(x), Ok(y)) if x < size.width as usize && y < size.height as usize => Some(Point::new(x, y)),
+        _ => None,
+    }
+}
+
+impl OriginDimensions for BltBuffer {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl DrawTarget for BltBuffer {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = EgPixel<Self::Color>>,
+    {
+        let size = self.size();
+        for EgPixel(point, color) in pixels {
+            if let Some(point) = point_in(point, size) {
+                self.set_pixel(point, color.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        if let Some(rect) = clip(area, self.size()) {
+            self.fill(rect, color.into());
+        }
+        Ok(())
+    }
+}
+
+impl OriginDimensions for BltSurface {
+    fn size(&self) -> Size {
+        self.buffer().size()
+    }
+}
+
+impl DrawTarget for BltSurface {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = EgPixel<Self::Color>>,
+    {
+        let size = self.size();
+        for EgPixel(point, color) in pixels {
+            if let Some(point) = point_in(point, size) {
+                self.set_pixel(point, color.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        if let Some(rect) = clip(area, self.size()) {
+            self.fill_rect(rect, color.into());
+        }
+        Ok(())
+    }
+}
+
+impl OriginDimensions for GraphicsOutput {
+    fn size(&self) -> Size {
+        let mode = self.current_mode();
+        Size::new(mode.width, mode.height)
+    }
+}
+
+impl DrawTarget for GraphicsOutput {
+    type Color = Rgb888;
+    type Error = EfiError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = EgPixel<Self::Color>>,
+    {
+        let size = self.size();
+        for EgPixel(point, color) in pixels {
+            if let Some(point) = point_in(point, size) {
+                GraphicsOutput::fill(self, color.into(), Rect::new(point.x, point.y, 1, 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let Some(rect) = clip(area, self.size()) else {
+            return Ok(());
+        };
+        // Gather the visible part of the area to transfer it at once.
+        let mut buffer = BltBuffer::new(rect.width, rect.height);
+        for (point, color) in area.points().zip(colors) {
+            if let Some(point) = point_in(point - rect_origin(&rect), buffer.size()) {
+                buffer.set_pixel(point, color.into());
+            }
+        }
+        self.buffer_to_video(&buffer, buffer.bounds(), rect.origin())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        match clip(area, self.size()) {
+            Some(rect) => GraphicsOutput::fill(self, color.into(), rect),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graphics_output::test::graphics_output;
+
+    #[test]
+    fn test_buffer_draw_target() {
+        let mut buffer = BltBuffer::new(3, 2);
+        let red = Rgb888::new(0xFF, 0, 0);
+        buffer
+            .draw_iter([
+                EgPixel(EgPoint::new(1, 1), red),
+                EgPixel(EgPoint::new(-1, 0), red),
+                EgPixel(EgPoint::new(3, 0), red),
+            ])
+            .unwrap();
+        buffer.fill_solid(&Rectangle::new(EgPoint::new(-2, 0), Size::new(3, 1)), Rgb888::WHITE).unwrap();
+        assert_eq!(
+            &[Pixel::WHITE, Pixel::BLACK, Pixel::BLACK, Pixel::BLACK, Pixel::rgb(0xFF, 0, 0), Pixel::BLACK],
+            buffer.pixels()
+        );
+        assert_eq!(Rgb888::WHITE, Rgb888::from(buffer.pixels()[0]));
+    }
+
+    #[test]
+    fn test_surface_draw_target() {
+        let mut surface = BltSurface::new(4, 4);
+        surface.flush(&mut graphics_output().0, Point::new(0, 0)).unwrap();
+        surface.fill_solid(&Rectangle::new(EgPoint::new(2, -1), Size::new(5, 2)), Rgb888::WHITE).unwrap();
+        surface
+            .draw_iter([EgPixel(EgPoint::new(0, 3), Rgb888::WHITE), EgPixel(EgPoint::new(4, 0), Rgb888::WHITE)])
+            .unwrap();
+        assert_eq!(Some(Rect::new(0, 0, 4, 4)), surface.dirty_area());
+        assert_eq!(Some(Pixel::WHITE), surface.pixel(Point::new(3, 0)));
+        assert_eq!(Some(Pixel::BLACK), surface.pixel(Point::new(2, 1)));
+    }
+
+    #[test]
+    fn test_graphics_output_draw_target() {
+        let (mut gop, display, _boot_services) = graphics_output();
+        let green = Rgb888::new(0, 0xFF, 0);
+        gop.clear(Rgb888::WHITE).unwrap();
+        assert!(display.frame.iter().all(|pixel| *pixel == Pixel::WHITE));
+
+        // Only the visible 2x2 corner of the area is transferred, in one Blt.
+        let area = Rectangle::new(EgPoint::new(6, 4), Size::new(3, 3));
+        gop.fill_contiguous(&area, (0..9).map(|i| if i % 3 == 0 { green } else { Rgb888::BLACK })).unwrap();
+        assert_eq!(2, display.blt_calls);
+        assert_eq!(Pixel::rgb(0, 0xFF, 0), display.frame[4 * 8 + 6]);
+        assert_eq!(Pixel::BLACK, display.frame[4 * 8 + 7]);
+        assert_eq!(Pixel::rgb(0, 0xFF, 0), display.frame[5 * 8 + 6]);
+
+        gop.draw_iter([EgPixel(EgPoint::new(0, 0), green), EgPixel(EgPoint::new(8, 0), green)]).unwrap();
+        assert_eq!(3, display.blt_calls);
+        assert_eq!(Pixel::rgb(0, 0xFF, 0), display.frame[0]);
+
+        gop.fill_solid(&Rectangle::new(EgPoint::new(-5, -5), Size::new(4, 4)), green).unwrap();
+        assert_eq!(3, display.blt_calls);
+    }
+}
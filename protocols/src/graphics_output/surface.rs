@@ -0,0 +1,306 @@
+//! Offscreen surface, drawn in memory and shown on the screen with a single Blt operation.
+
+use status::EfiError;
+
+use super::{
+    font::{self, GLYPH_HEIGHT, GLYPH_WIDTH},
+    BltBuffer, GraphicsOutput, Pixel, Point, Rect,
+};
+
+/// Pixels drawn in memory before being shown on the screen.
+///
+/// The surface tracks the area changed since it was last flushed, [`BltSurface::flush`] transfers this area only,
+/// at once, which avoids the flicker of drawing on the screen with many small Blt operations:
+///
+/// ```ignore
+/// let mut surface = BltSurface::new(320, 24);
+/// surface.draw_rect(surface.bounds(), Pixel::WHITE);
+/// surface.fill_rect(Rect::new(2, 2, 316 * percent / 100, 20), Pixel::rgb(0, 0x80, 0));
+/// surface.draw_text(Point::new(150, 8), &format!("{percent}%"), Pixel::WHITE, None);
+/// surface.flush(&mut gop, Point::new(480, 700))?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BltSurface {
+    buffer: BltBuffer,
+    /// The area changed since the last flush.
+    dirty: Option<Rect>,
+}
+
+impl BltSurface {
+    /// Width of a character of [`BltSurface::draw_text`], with the space after it.
+    pub const CHAR_WIDTH: usize = GLYPH_WIDTH + 1;
+    /// Height of a line of [`BltSurface::draw_text`], with the space below it.
+    pub const CHAR_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+    /// A surface of `width` by `height` black pixels, which are all flushed by the first flush.
+    pub fn new(width: usize, height: usize) -> Self {
+        let buffer = BltBuffer::new(width, height);
+        let dirty = Some(buffer.bounds()).filter(|bounds| !bounds.is_empty());
+        Self { buffer, dirty }
+    }
+
+    pub fn width(&self) -> usize {
+        self.buffer.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.buffer.height()
+    }
+
+    /// The rectangle covering the whole surface.
+    pub fn bounds(&self) -> Rect {
+        self.buffer.bounds()
+    }
+
+    /// The pixels of the surface.
+    pub fn buffer(&self) -> &BltBuffer {
+        &self.buffer
+    }
+
+    /// The area changed since the last flush.
+    pub fn dirty_area(&self) -> Option<Rect> {
+        self.dirty
+    }
+
+    /// Marks the whole surface as changed, to flush it entirely.
+    pub fn invalidate(&mut self) {
+        self.mark_dirty(self.bounds());
+    }
+
+    /// The part of `rect` inside the surface, if any.
+    fn clip(&self, rect: Rect) -> Option<Rect> {
+        let width = rect.width.min(self.width().saturating_sub(rect.x));
+        let height = rect.height.min(self.height().saturating_sub(rect.y));
+        Some(Rect::new(rect.x, rect.y, width, height)).filter(|rect| !rect.is_empty())
+    }
+
+    /// Extends the changed area to `rect`, which is inside the surface.
+    fn mark_dirty(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => {
+                let (x, y) = (dirty.x.min(rect.x), dirty.y.min(rect.y));
+                let right = (dirty.x + dirty.width).max(rect.x + rect.width);
+                let bottom = (dirty.y + dirty.height).max(rect.y + rect.height);
+                Rect::new(x, y, right - x, bottom - y)
+            }
+            None => rect,
+        });
+    }
+
+    /// The pixel at `point`, if it is on the surface.
+    pub fn pixel(&self, point: Point) -> Option<Pixel> {
+        self.buffer.pixel(point)
+    }
+
+    /// Sets the pixel at `point`, points outside of the surface are ignored.
+    pub fn set_pixel(&mut self, point: Point, pixel: Pixel) {
+        if let Some(rect) = self.clip(Rect::new(point.x, point.y, 1, 1)) {
+            self.buffer.set_pixel(point, pixel);
+            self.mark_dirty(rect);
+        }
+    }
+
+    /// Sets all the pixels of the surface.
+    pub fn clear(&mut self, pixel: Pixel) {
+        self.fill_rect(self.bounds(), pixel);
+    }
+
+    /// Sets the pixels of `rect`, clipped to the surface.
+    pub fn fill_rect(&mut self, rect: Rect, pixel: Pixel) {
+        if let Some(rect) = self.clip(rect) {
+            self.buffer.fill(rect, pixel);
+            self.mark_dirty(rect);
+        }
+    }
+
+    /// Draws the outline of `rect`, one pixel wide.
+    pub fn draw_rect(&mut self, rect: Rect, pixel: Pixel) {
+        if rect.is_empty() {
+            return;
+        }
+        let (right, bottom) = (rect.x.saturating_add(rect.width - 1), rect.y.saturating_add(rect.height - 1));
+        self.fill_rect(Rect::new(rect.x, rect.y, rect.width, 1), pixel);
+        self.fill_rect(Rect::new(rect.x, bottom, rect.width, 1), pixel);
+        self.fill_rect(Rect::new(rect.x, rect.y, 1, rect.height), pixel);
+        self.fill_rect(Rect::new(right, rect.y, 1, rect.height), pixel);
+    }
+
+    /// Copies `rect` of `source` to `destination`, clipped to the source and to the surface.
+    pub fn copy_from(&mut self, source: &BltBuffer, rect: Rect, destination: Point) {
+        let Some((rect, destination)) = self.clip_copy(source, rect, destination) else {
+            return;
+        };
+        let (width, source_width) = (self.width(), source.width());
+        for line in 0..rect.height {
+            let from = (rect.y + line) * source_width + rect.x;
+            let to = (destination.y + line) * width + destination.x;
+            self.buffer.pixels_mut()[to..to + rect.width].copy_from_slice(&source.pixels()[from..from + rect.width]);
+        }
+        self.mark_dirty(rect.moved_to(destination));
+    }
+
+    /// Copies `rect` of the surface to `destination`, the two may overlap.
+    pub fn copy_within(&mut self, rect: Rect, destination: Point) {
+        let Some((rect, destination)) = self.clip_copy(&self.buffer, rect, destination) else {
+            return;
+        };
+        let width = self.width();
+        // Copy the lines in the order that does not overwrite a line before it is copied.
+        let copy_line = |pixels: &mut [Pixel], line: usize| {
+            let from = (rect.y + line) * width + rect.x;
+            pixels.copy_within(from..from + rect.width, (destination.y + line) * width + destination.x);
+        };
+        match destination.y > rect.y {
+            true => (0..rect.height).rev().for_each(|line| copy_line(self.buffer.pixels_mut(), line)),
+            false => (0..rect.height).for_each(|line| copy_line(self.buffer.pixels_mut(), line)),
+        }
+        self.mark_dirty(rect.moved_to(destination));
+    }
+
+    /// The part of `rect` of `source` that can be copied to `destination` on the surface, with its destination.
+    fn clip_copy(&self, source: &BltBuffer, rect: Rect, destination: Point) -> Option<(Rect, Point)> {
+        let width =
+            rect.width.min(source.width().saturating_sub(rect.x)).min(self.width().saturating_sub(destination.x));
+        let height =
+            rect.height.min(source.height().saturating_sub(rect.y)).min(self.height().saturating_sub(destination.y));
+        Some((Rect::new(rect.x, rect.y, width, height), destination)).filter(|(rect, _)| !rect.is_empty())
+    }
+
+    /// The size of `text` drawn with [`BltSurface::draw_text`], in pixels.
+    pub fn text_size(text: &str) -> (usize, usize) {
+        let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+        (columns * Self::CHAR_WIDTH, text.lines().count() * Self::CHAR_HEIGHT)
+    }
+
+    /// Draws `text` with the built-in font, its top left corner at `position`.
+    ///
+    /// A line feed starts a new line below `position`, characters outside of printable ASCII are drawn as `'?'`.
+    /// The cells of the characters are filled with `background` if there is one. Returns the position following the
+    /// last character.
+    pub fn draw_text(&mut self, position: Point, text: &str, foreground: Pixel, background: Option<Pixel>) -> Point {
+        let mut cursor = position;
+        for c in text.chars() {
+            if c == '\n' {
+                cursor = Point::new(position.x, cursor.y.saturating_add(Self::CHAR_HEIGHT));
+                continue;
+            }
+            if let Some(background) = background {
+                self.fill_rect(Rect::new(cursor.x, cursor.y, Self::CHAR_WIDTH, Self::CHAR_HEIGHT), background);
+            }
+            for (y, line) in font::glyph(c).iter().enumerate() {
+                for x in (0..GLYPH_WIDTH).filter(|x| line & (1 << (GLYPH_WIDTH - 1 - x)) != 0) {
+                    self.set_pixel(Point::new(cursor.x.saturating_add(x), cursor.y.saturating_add(y)), foreground);
+                }
+            }
+            cursor.x = cursor.x.saturating_add(Self::CHAR_WIDTH);
+        }
+        cursor
+    }
+
+    /// Shows the area changed since the last flush on the screen, the surface having its top left corner at
+    /// `position`.
+    pub fn flush(&mut self, gop: &mut GraphicsOutput, position: Point) -> Result<(), EfiError> {
+        let Some(dirty) = self.dirty else {
+            return Ok(());
+        };
+        let destination = Point::new(position.x.saturating_add(dirty.x), position.y.saturating_add(dirty.y));
+        gop.buffer_to_video(&self.buffer, dirty, destination)?;
+        self.dirty = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graphics_output::test::graphics_output;
+    use alloc::{string::String, vec::Vec};
+    use r_efi::efi;
+
+    /// The pixels of `surface` as lines of `#` for `pixel` and `.` for the others.
+    fn render(surface: &BltSurface, pixel: Pixel) -> Vec<String> {
+        surface
+            .buffer()
+            .pixels()
+            .chunks(surface.width())
+            .map(|line| line.iter().map(|p| if *p == pixel { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut surface = BltSurface::new(14, 9);
+        surface.dirty = None;
+        let end = surface.draw_text(Point::new(1, 1), "Hi\u{e9}", Pixel::WHITE, None);
+        assert_eq!(Point::new(1 + 3 * BltSurface::CHAR_WIDTH, 1), end);
+        assert_eq!(
+            vec![
+                "..............",
+                ".#...#...#....",
+                ".#...#.......#",
+                ".#...#..##....",
+                ".#####...#....",
+                ".#...#...#....",
+                ".#...#...#....",
+                ".#...#..###...",
+                "..............",
+            ],
+            render(&surface, Pixel::WHITE)
+        );
+        // The '?' of the last character is clipped.
+        assert_eq!(Some(Rect::new(1, 1, 13, 7)), surface.dirty_area());
+
+        let end = surface.draw_text(Point::new(0, 0), "a\nb", Pixel::WHITE, Some(Pixel::BLACK));
+        assert_eq!(Point::new(BltSurface::CHAR_WIDTH, BltSurface::CHAR_HEIGHT), end);
+        assert_eq!((3 * BltSurface::CHAR_WIDTH, 2 * BltSurface::CHAR_HEIGHT), BltSurface::text_size("a\nbcd"));
+    }
+
+    #[test]
+    fn test_fill_and_copy() {
+        let mut surface = BltSurface::new(6, 4);
+        let red = Pixel::rgb(0xFF, 0, 0);
+        surface.fill_rect(Rect::new(4, 2, 10, 10), red);
+        surface.draw_rect(Rect::new(0, 0, 3, 3), Pixel::WHITE);
+        assert_eq!(vec!["###...", "#.#...", "###...", "......"], render(&surface, Pixel::WHITE));
+        assert_eq!(vec!["......", "......", "....##", "....##"], render(&surface, red));
+
+        let mut source = BltBuffer::new(2, 2);
+        source.fill(Rect::new(0, 0, 1, 2), red);
+        surface.copy_from(&source, source.bounds(), Point::new(5, 0));
+        assert_eq!(vec![".....#", ".....#", "....##", "....##"], render(&surface, red));
+
+        surface.copy_within(Rect::new(0, 0, 3, 3), Point::new(1, 1));
+        assert_eq!(vec!["###...", "####..", "##.#..", ".###.."], render(&surface, Pixel::WHITE));
+        assert_eq!(vec![".....#", ".....#", "....##", "....##"], render(&surface, red));
+        surface.copy_within(Rect::new(4, 1, 2, 3), Point::new(3, 0));
+        assert_eq!(vec!["....##", "...###", "...###", "....##"], render(&surface, red));
+    }
+
+    #[test]
+    fn test_flush() {
+        let (mut gop, display, _) = graphics_output();
+        let mut surface = BltSurface::new(4, 3);
+        surface.clear(Pixel::WHITE);
+        surface.flush(&mut gop, Point::new(2, 2)).unwrap();
+        assert_eq!(1, display.blt_calls);
+        assert_eq!(Pixel::WHITE, display.frame[2 * 8 + 2]);
+        assert_eq!(Pixel::WHITE, display.frame[4 * 8 + 5]);
+        assert_eq!(None, surface.dirty_area());
+
+        // Only the changed area is transferred, in a single Blt.
+        surface.set_pixel(Point::new(0, 0), Pixel::BLACK);
+        surface.set_pixel(Point::new(1, 1), Pixel::BLACK);
+        assert_eq!(Some(Rect::new(0, 0, 2, 2)), surface.dirty_area());
+        surface.flush(&mut gop, Point::new(2, 2)).unwrap();
+        surface.flush(&mut gop, Point::new(2, 2)).unwrap();
+        assert_eq!(2, display.blt_calls);
+        assert_eq!(Pixel::BLACK, display.frame[3 * 8 + 3]);
+
+        surface.invalidate();
+        assert_eq!(efi::Status::INVALID_PARAMETER, surface.flush(&mut gop, Point::new(6, 0)).unwrap_err());
+        assert_eq!(Some(surface.bounds()), surface.dirty_area());
+    }
+}
@@ -0,0 +1,52 @@
+//! Human Interface Infrastructure protocols.
+//!
+//! A driver publishes its strings as a [`PackageList`] in the [`HiiDatabase`], then reads them back through
+//! [`HiiString`] by the ID given when they were added to their [`StringPackage`]:
+//!
+//! ```ignore
+//! let mut strings = StringPackage::new("en-US", &String16::try_from("English")?);
+//! let title = strings.add(&String16::try_from("Boot Options")?);
+//! let mut package_list = PackageList::new(PACKAGE_LIST_GUID);
+//! package_list.add_package(&strings.to_bytes());
+//! let hii_handle = HiiDatabase::locate(&boot_services)?.new_package_list(&package_list, Some(image_handle))?;
+//! let title = HiiString::locate(&boot_services)?.get_string(hii_handle, title, "en-US")?;
+//! ```
+//!
+//! Forms are published with a [`ConfigAccess`] serving their configuration, see [`config_access`], and shown
+//! with the [`FormBrowser`].
+//!
+//! [UEFI Spec Documentation: 34. HII Protocols](https://uefi.org/specs/UEFI/2.10/34_HII_Protocols.html)
+
+use r_efi::efi;
+
+pub mod config_access;
+pub mod config_string;
+pub mod database;
+pub mod form_browser;
+pub mod string;
+
+pub use config_access::{publish_forms, ConfigAccess, ConfigAccessHandler};
+pub use config_string::{ConfigHeader, ConfigString};
+pub use database::{HiiDatabase, PackageList};
+pub use form_browser::FormBrowser;
+pub use string::{HiiString, StringPackage};
+
+/// Handle of a package list registered in the HII database.
+pub type HiiHandle = efi::hii::Handle;
+
+/// ID of a string in the string packages of a package list, the IDs start at 1.
+pub type StringId = efi::hii::StringId;
+
+/// Size of the header of a package, its 24 bits length followed by its type.
+pub const PACKAGE_HEADER_SIZE: usize = 4;
+
+/// The header of a package of `length` bytes, header included.
+///
+/// # Panics
+///
+/// If the length does not fit in the 24 bits of the header.
+pub fn package_header(package_type: u8, length: usize) -> [u8; PACKAGE_HEADER_SIZE] {
+    assert!(length < 1 << 24, "package of {length} bytes is too large");
+    let length = (length as u32).to_le_bytes();
+    [length[0], length[1], length[2], package_type]
+}
@@ -0,0 +1,594 @@
+//! HII Config Access protocol, produced by a driver to give the configuration of its forms to the forms browser.
+//!
+//! [`ConfigAccess`] produces the protocol from a [`ConfigAccessHandler`] serving the buffer storage of the forms,
+//! [`publish_forms`] installs it with the package list of the forms:
+//!
+//! ```ignore
+//! struct Settings { header: ConfigHeader, data: RefCell<[u8; 4]> }
+//!
+//! impl ConfigAccessHandler for Settings {
+//!     fn header(&self) -> &ConfigHeader { &self.header }
+//!     fn extract_config(&self, request: &ConfigString) -> Result<ConfigString, EfiError> {
+//!         request.extract(&*self.data.borrow())
+//!     }
+//!     fn route_config(&self, configuration: &ConfigString) -> Result<(), EfiError> {
+//!         configuration.route(&mut *self.data.borrow_mut())
+//!     }
+//! }
+//!
+//! let config_access = ConfigAccess::new(boot_services, Settings { header, data: RefCell::new([0; 4]) });
+//! let forms = publish_forms(boot_services, &device_path, &package_list, config_access)?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 35.5. EFI HII Configuration Access Protocol](https://uefi.org/specs/UEFI/2.10/35_HII_Configuration_Processing_and_Browser_Protocol.html)
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{ffi::c_void, fmt, ops::Deref, ptr, slice};
+
+use boot_services::{
+    allocation::MemoryType,
+    protocol_handler::{self, InstalledProtocol, Protocol as ProtocolTrait},
+    BootServices,
+};
+use device_path::DevicePath;
+use r_efi::efi;
+use status::EfiError;
+use ucs2::Str16;
+
+use super::{
+    config_string::{self, ConfigHeader, ConfigString},
+    HiiDatabase, HiiHandle, PackageList, StringId,
+};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x330d4706, 0xf2a0, 0x4e4f, 0xa3, 0x69, &[0xb6, 0x6f, 0xa8, 0xd5, 0x43, 0x85]);
+
+pub type ProtocolExtractConfig =
+    extern "efiapi" fn(*const Protocol, *const u16, *mut *mut u16, *mut *mut u16) -> efi::Status;
+
+pub type ProtocolRouteConfig = extern "efiapi" fn(*const Protocol, *const u16, *mut *mut u16) -> efi::Status;
+
+pub type ProtocolCallback = extern "efiapi" fn(
+    *const Protocol,
+    BrowserAction,
+    efi::hii::QuestionId,
+    u8,
+    *mut efi::hii::IfrTypeValue,
+    *mut ActionRequest,
+) -> efi::Status;
+
+/// FFI definition of `EFI_HII_CONFIG_ACCESS_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub extract_config: ProtocolExtractConfig,
+    pub route_config: ProtocolRouteConfig,
+    pub callback: ProtocolCallback,
+}
+
+/// HII Config Access protocol with a [`ConfigAccess`] interface, used to produce the protocol.
+pub struct HiiConfigAccessProducer;
+
+unsafe impl ProtocolTrait for HiiConfigAccessProducer {
+    type Interface = ConfigAccess;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for HiiConfigAccessProducer {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// The action of the forms browser a callback is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct BrowserAction(pub usize);
+
+impl BrowserAction {
+    /// The value of the question is about to change.
+    pub const CHANGING: BrowserAction = BrowserAction(0);
+    /// The value of the question changed.
+    pub const CHANGED: BrowserAction = BrowserAction(1);
+    /// The browser is about to show the question, the callback can give its value.
+    pub const RETRIEVE: BrowserAction = BrowserAction(2);
+    pub const FORM_OPEN: BrowserAction = BrowserAction(3);
+    pub const FORM_CLOSE: BrowserAction = BrowserAction(4);
+    /// The values of the form were submitted.
+    pub const SUBMITTED: BrowserAction = BrowserAction(5);
+    /// The callback can give the standard default value of the question.
+    pub const DEFAULT_STANDARD: BrowserAction = BrowserAction(0x1000);
+    /// The callback can give the manufacturing default value of the question.
+    pub const DEFAULT_MANUFACTURING: BrowserAction = BrowserAction(0x1001);
+}
+
+/// The action the forms browser takes after a callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct ActionRequest(pub usize);
+
+impl ActionRequest {
+    pub const NONE: ActionRequest = ActionRequest(0);
+    pub const RESET: ActionRequest = ActionRequest(1);
+    pub const SUBMIT: ActionRequest = ActionRequest(2);
+    pub const EXIT: ActionRequest = ActionRequest(3);
+    pub const FORM_SUBMIT_EXIT: ActionRequest = ActionRequest(4);
+    pub const FORM_DISCARD_EXIT: ActionRequest = ActionRequest(5);
+    pub const FORM_APPLY: ActionRequest = ActionRequest(6);
+    pub const FORM_DISCARD: ActionRequest = ActionRequest(7);
+    pub const RECONNECT: ActionRequest = ActionRequest(8);
+    pub const QUESTION_APPLY: ActionRequest = ActionRequest(9);
+}
+
+/// The value of a question given to a callback, that the callback can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuestionValue {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    Boolean(bool),
+    String(StringId),
+    /// The string of the configuration of an action question.
+    Action(StringId),
+    /// A value of another `EFI_IFR_TYPE_*` type, which is not given to the callback.
+    Other(u8),
+}
+
+impl QuestionValue {
+    /// Reads a value of a type.
+    ///
+    /// # Safety
+    ///
+    /// The value must be valid for its type.
+    unsafe fn read(value_type: u8, value: &efi::hii::IfrTypeValue) -> Self {
+        match value_type {
+            efi::hii::IFR_TYPE_NUM_SIZE_8 => Self::U8(value.r#u8),
+            efi::hii::IFR_TYPE_NUM_SIZE_16 => Self::U16(value.r#u16),
+            efi::hii::IFR_TYPE_NUM_SIZE_32 => Self::U32(value.r#u32),
+            efi::hii::IFR_TYPE_NUM_SIZE_64 => Self::U64(value.r#u64),
+            efi::hii::IFR_TYPE_BOOLEAN => Self::Boolean(value.b.into()),
+            efi::hii::IFR_TYPE_STRING => Self::String(value.string),
+            efi::hii::IFR_TYPE_ACTION => Self::Action(value.string),
+            other => Self::Other(other),
+        }
+    }
+
+    /// Writes the value back, values of other types are left untouched.
+    fn write(self, value: &mut efi::hii::IfrTypeValue) {
+        match self {
+            Self::U8(v) => value.r#u8 = v,
+            Self::U16(v) => value.r#u16 = v,
+            Self::U32(v) => value.r#u32 = v,
+            Self::U64(v) => value.r#u64 = v,
+            Self::Boolean(v) => value.b = v.into(),
+            Self::String(v) | Self::Action(v) => value.string = v,
+            Self::Other(_) => (),
+        }
+    }
+}
+
+/// The configuration of the buffer storage of forms, served by a [`ConfigAccess`].
+///
+/// Requests and configurations for other storages are answered with `NOT_FOUND` without calling the handler.
+pub trait ConfigAccessHandler {
+    /// The header of the storage, its device path is the device path of the driver handle of the forms.
+    fn header(&self) -> &ConfigHeader;
+
+    /// Answers a request for blocks of the storage, or for the whole storage if the request has no blocks.
+    ///
+    /// [`ConfigString::extract`] answers it from the bytes of the storage.
+    fn extract_config(&self, request: &ConfigString) -> Result<ConfigString, EfiError>;
+
+    /// Applies the values of the blocks of a configuration to the storage.
+    ///
+    /// [`ConfigString::route`] writes them in the bytes of the storage.
+    fn route_config(&self, configuration: &ConfigString) -> Result<(), EfiError>;
+
+    /// Handles an action of the forms browser on a question with the `INTERACTIVE` flag, returns the action the
+    /// browser takes next.
+    fn callback(
+        &self,
+        _action: BrowserAction,
+        _question_id: efi::hii::QuestionId,
+        _value: &mut QuestionValue,
+    ) -> Result<ActionRequest, EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+}
+
+type AllocatePool = Box<dyn Fn(usize) -> Result<*mut u8, EfiError>>;
+
+/// Implementation of the HII Config Access protocol produced by a driver.
+///
+/// The results of the requests are allocated from the pool, as the caller frees them.
+#[repr(C)]
+pub struct ConfigAccess {
+    protocol: Protocol,
+    handler: Box<dyn ConfigAccessHandler>,
+    allocate_pool: AllocatePool,
+}
+
+impl ConfigAccess {
+    /// Creates a HII Config Access implementation serving the storage of `handler`.
+    pub fn new<B: BootServices>(boot_services: &'static B, handler: impl ConfigAccessHandler + 'static) -> Self {
+        Self {
+            protocol: Protocol {
+                extract_config: Self::extract_config,
+                route_config: Self::route_config,
+                callback: Self::callback,
+            },
+            handler: Box::new(handler),
+            allocate_pool: Box::new(|size| boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, size)),
+        }
+    }
+
+    /// Installs the protocol on the driver handle of the forms.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn install<B: BootServices>(
+        self,
+        boot_services: &B,
+        driver_handle: efi::Handle,
+    ) -> Result<InstalledProtocol<'_, HiiConfigAccessProducer, B>, EfiError> {
+        InstalledProtocol::install(boot_services, Some(driver_handle), &HiiConfigAccessProducer, Box::new(self))
+    }
+
+    /// Parses the configuration strings and runs `f` on each, stopping at the first error.
+    ///
+    /// `progress` is set to the end of the string, or to the `&` before the configuration string or element that
+    /// failed.
+    fn for_each_config(
+        &self,
+        configs: *const u16,
+        progress: *mut *mut u16,
+        mut f: impl FnMut(&ConfigString) -> Result<(), EfiError>,
+    ) -> efi::Status {
+        //SAFETY: The caller gives a null-terminated string.
+        let configs_str = unsafe { Str16::from_ptr(configs) };
+        let set_progress = |position: usize| {
+            //SAFETY: The caller gives a pointer to the progress, which is set to a character of the string.
+            unsafe { progress.write(configs.add(position) as *mut u16) };
+        };
+        set_progress(configs_str.as_slice().len());
+        let configs = match config_string::parse_with_positions(configs_str) {
+            Ok(configs) => configs,
+            Err(error) => {
+                set_progress(error.position);
+                return efi::Status::INVALID_PARAMETER;
+            }
+        };
+        for (position, config) in configs {
+            let result = match config.header == *self.handler.header() {
+                true => f(&config),
+                false => Err(efi::Status::NOT_FOUND.into()),
+            };
+            if let Err(status) = result {
+                set_progress(position);
+                return status.into();
+            }
+        }
+        efi::Status::SUCCESS
+    }
+
+    /// Copies a string to a null-terminated string allocated from the pool.
+    fn pool_string(&self, s: &str) -> Result<*mut u16, EfiError> {
+        let s = s.encode_utf16().chain([0]).collect::<Vec<_>>();
+        let pool = (self.allocate_pool)(s.len() * 2)? as *mut u16;
+        //SAFETY: The allocation holds the string and is aligned for its characters.
+        unsafe { slice::from_raw_parts_mut(pool, s.len()) }.copy_from_slice(&s);
+        Ok(pool)
+    }
+
+    extern "efiapi" fn extract_config(
+        this: *const Protocol,
+        request: *const u16,
+        progress: *mut *mut u16,
+        results: *mut *mut u16,
+    ) -> efi::Status {
+        if this.is_null() || progress.is_null() || results.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        //SAFETY: The protocol is the first field of the ConfigAccess that was installed.
+        let this = unsafe { &*(this as *const ConfigAccess) };
+        let mut configs = Vec::new();
+        let status = match request.is_null() {
+            // No request is a request for the whole storage.
+            true => {
+                //SAFETY: The pointer was checked for null.
+                unsafe { progress.write(ptr::null_mut()) };
+                this.handler.extract_config(&ConfigString::new(this.handler.header().clone())).map(|c| configs.push(c))
+            }
+            false => {
+                let status = this.for_each_config(request, progress, |request| {
+                    configs.push(this.handler.extract_config(request)?);
+                    Ok(())
+                });
+                match status {
+                    efi::Status::SUCCESS => Ok(()),
+                    status => Err(status.into()),
+                }
+            }
+        };
+        if let Err(status) = status {
+            return status.into();
+        }
+        let configs = configs.iter().map(|config| config.to_string()).collect::<Vec<String>>().join("&");
+        match this.pool_string(&configs) {
+            Ok(configs) => {
+                //SAFETY: The pointer was checked for null.
+                unsafe { results.write(configs) };
+                efi::Status::SUCCESS
+            }
+            Err(status) => status.into(),
+        }
+    }
+
+    extern "efiapi" fn route_config(
+        this: *const Protocol,
+        configuration: *const u16,
+        progress: *mut *mut u16,
+    ) -> efi::Status {
+        if this.is_null() || configuration.is_null() || progress.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        //SAFETY: The protocol is the first field of the ConfigAccess that was installed.
+        let this = unsafe { &*(this as *const ConfigAccess) };
+        this.for_each_config(configuration, progress, |configuration| this.handler.route_config(configuration))
+    }
+
+    extern "efiapi" fn callback(
+        this: *const Protocol,
+        action: BrowserAction,
+        question_id: efi::hii::QuestionId,
+        value_type: u8,
+        value: *mut efi::hii::IfrTypeValue,
+        action_request: *mut ActionRequest,
+    ) -> efi::Status {
+        if this.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        //SAFETY: The protocol is the first field of the ConfigAccess that was installed.
+        let this = unsafe { &*(this as *const ConfigAccess) };
+        //SAFETY: The browser gives a value of the type, or null.
+        let mut question_value = match unsafe { value.as_ref() } {
+            Some(value) => unsafe { QuestionValue::read(value_type, value) },
+            None => QuestionValue::Other(value_type),
+        };
+        match this.handler.callback(action, question_id, &mut question_value) {
+            Ok(request) => {
+                //SAFETY: The pointers are valid when not null.
+                if let Some(value) = unsafe { value.as_mut() } {
+                    question_value.write(value);
+                }
+                if let Some(action_request) = unsafe { action_request.as_mut() } {
+                    *action_request = request;
+                }
+                efi::Status::SUCCESS
+            }
+            Err(status) => status.into(),
+        }
+    }
+}
+
+impl fmt::Debug for ConfigAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigAccess").field("header", self.handler.header()).finish_non_exhaustive()
+    }
+}
+
+/// Forms published by [`publish_forms`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublishedForms {
+    /// The handle with the device path and the config access of the forms.
+    pub driver_handle: efi::Handle,
+    /// The handle of the package list in the HII database.
+    pub hii_handle: HiiHandle,
+}
+
+/// Publishes the forms of a package list to the forms browser.
+///
+/// The device path and the config access are installed on a new driver handle, with which the package list is
+/// registered. The device path is the one of the header of the config access, the forms stay published for the rest
+/// of the boot.
+pub fn publish_forms<B: BootServices>(
+    boot_services: &'static B,
+    device_path: &DevicePath,
+    package_list: &PackageList,
+    config_access: ConfigAccess,
+) -> Result<PublishedForms, EfiError> {
+    let device_path = Box::leak(device_path.as_bytes().to_vec().into_boxed_slice());
+    //SAFETY: The interface is a valid device path that is never freed.
+    let driver_handle = unsafe {
+        boot_services.install_protocol_interface_unchecked(
+            None,
+            protocol_handler::DevicePath.protocol_guid(),
+            device_path.as_mut_ptr() as *mut c_void,
+        )?
+    };
+    config_access.install(boot_services, driver_handle)?.leak();
+    let hii_handle = HiiDatabase::locate(boot_services)?.new_package_list(package_list, Some(driver_handle))?;
+    Ok(PublishedForms { driver_handle, hii_handle })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hii::database::test::hii_database_protocol;
+    use boot_services::mock::InMemoryBootServices;
+    use core::cell::RefCell;
+    use device_path::{node_types::VendorHardware, DevicePathBuf, DevicePathBuilder};
+    use ucs2::String16;
+
+    const STORAGE_GUID: efi::Guid =
+        efi::Guid::from_fields(0x5e7a9c21, 0x4b0d, 0x4c3a, 0x8f, 0x16, &[0x2d, 0x90, 0x3e, 0x5b, 0x7c, 0x41]);
+
+    /// Settings of a driver, the callback doubles the retrieved `U8` values and rejects question 9.
+    struct Settings {
+        header: ConfigHeader,
+        data: RefCell<[u8; 4]>,
+    }
+
+    impl ConfigAccessHandler for Settings {
+        fn header(&self) -> &ConfigHeader {
+            &self.header
+        }
+
+        fn extract_config(&self, request: &ConfigString) -> Result<ConfigString, EfiError> {
+            request.extract(&*self.data.borrow())
+        }
+
+        fn route_config(&self, configuration: &ConfigString) -> Result<(), EfiError> {
+            configuration.route(&mut *self.data.borrow_mut())
+        }
+
+        fn callback(
+            &self,
+            action: BrowserAction,
+            question_id: efi::hii::QuestionId,
+            value: &mut QuestionValue,
+        ) -> Result<ActionRequest, EfiError> {
+            match (action, value) {
+                _ if question_id == 9 => Err(efi::Status::UNSUPPORTED.into()),
+                (BrowserAction::RETRIEVE, QuestionValue::U8(v)) => {
+                    *v *= 2;
+                    Ok(ActionRequest::NONE)
+                }
+                (BrowserAction::CHANGED, _) => Ok(ActionRequest::SUBMIT),
+                _ => Ok(ActionRequest::NONE),
+            }
+        }
+    }
+
+    fn device_path() -> DevicePathBuf {
+        DevicePathBuilder::new().push(&VendorHardware::new(STORAGE_GUID, &[])).build()
+    }
+
+    fn header() -> ConfigHeader {
+        ConfigHeader::new(STORAGE_GUID, &String16::try_from("Settings").unwrap(), &device_path())
+    }
+
+    fn config_access() -> (&'static InMemoryBootServices, ConfigAccess) {
+        let boot_services = Box::leak(Box::new(InMemoryBootServices::new()));
+        let settings = Settings { header: header(), data: RefCell::new([1, 2, 3, 4]) };
+        (boot_services, ConfigAccess::new(boot_services, settings))
+    }
+
+    fn s(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain([0]).collect()
+    }
+
+    /// The results of a request, freed from the pool.
+    fn take_results(boot_services: &InMemoryBootServices, results: *mut u16) -> String {
+        let string = unsafe { Str16::from_ptr(results) }.to_string_lossy();
+        boot_services.free_pool(results as *mut u8).unwrap();
+        string
+    }
+
+    #[test]
+    fn test_extract_config() {
+        let (boot_services, config_access) = config_access();
+        let this = &config_access.protocol as *const Protocol;
+        let mut progress = ptr::null_mut();
+        let mut results = ptr::null_mut();
+
+        let status = (config_access.protocol.extract_config)(this, ptr::null(), &mut progress, &mut results);
+        assert_eq!(efi::Status::SUCCESS, status);
+        assert_eq!(format!("{}&OFFSET=0000&WIDTH=0004&VALUE=04030201", header()), take_results(boot_services, results));
+
+        let request = s(&format!("{}&OFFSET=1&WIDTH=2", header()));
+        let status = (config_access.protocol.extract_config)(this, request.as_ptr(), &mut progress, &mut results);
+        assert_eq!(efi::Status::SUCCESS, status);
+        assert_eq!(unsafe { request.as_ptr().add(request.len() - 1) }, progress);
+        assert_eq!(format!("{}&OFFSET=0001&WIDTH=0002&VALUE=0302", header()), take_results(boot_services, results));
+
+        let mut other = header();
+        other.name = String16::try_from("Other").unwrap();
+        let request = s(&format!("{}&{}", header(), other));
+        let status = (config_access.protocol.extract_config)(this, request.as_ptr(), &mut progress, &mut results);
+        assert_eq!(efi::Status::NOT_FOUND, status);
+        assert_eq!(unsafe { request.as_ptr().add(header().to_string().len()) }, progress);
+
+        let request = s("GUID=00");
+        let status = (config_access.protocol.extract_config)(this, request.as_ptr(), &mut progress, &mut results);
+        assert_eq!(efi::Status::INVALID_PARAMETER, status);
+        assert_eq!(request.as_ptr(), progress);
+    }
+
+    #[test]
+    fn test_route_config() {
+        let (boot_services, config_access) = config_access();
+        let this = &config_access.protocol as *const Protocol;
+        let mut progress = ptr::null_mut();
+
+        let configuration = s(&format!("{}&OFFSET=2&WIDTH=2&VALUE=0a0b", header()));
+        let status = (config_access.protocol.route_config)(this, configuration.as_ptr(), &mut progress);
+        assert_eq!(efi::Status::SUCCESS, status);
+
+        let mut results = ptr::null_mut();
+        (config_access.protocol.extract_config)(this, ptr::null(), &mut progress, &mut results);
+        assert_eq!(format!("{}&OFFSET=0000&WIDTH=0004&VALUE=0a0b0201", header()), take_results(boot_services, results));
+
+        let configuration = s(&format!("{}&OFFSET=3&WIDTH=2&VALUE=0000", header()));
+        let status = (config_access.protocol.route_config)(this, configuration.as_ptr(), &mut progress);
+        assert_eq!(efi::Status::INVALID_PARAMETER, status);
+        assert_eq!(configuration.as_ptr(), progress);
+        let status = (config_access.protocol.route_config)(this, ptr::null(), &mut progress);
+        assert_eq!(efi::Status::INVALID_PARAMETER, status);
+    }
+
+    #[test]
+    fn test_callback() {
+        let (_, config_access) = config_access();
+        let this = &config_access.protocol as *const Protocol;
+        let callback = config_access.protocol.callback;
+        let mut value: efi::hii::IfrTypeValue = unsafe { core::mem::zeroed() };
+        value.r#u8 = 21;
+        let mut request = ActionRequest::EXIT;
+
+        let status =
+            callback(this, BrowserAction::RETRIEVE, 1, efi::hii::IFR_TYPE_NUM_SIZE_8, &mut value, &mut request);
+        assert_eq!(efi::Status::SUCCESS, status);
+        assert_eq!(42, unsafe { value.r#u8 });
+        assert_eq!(ActionRequest::NONE, request);
+
+        value.b = efi::Boolean::TRUE;
+        let status = callback(this, BrowserAction::CHANGED, 2, efi::hii::IFR_TYPE_BOOLEAN, &mut value, &mut request);
+        assert_eq!(efi::Status::SUCCESS, status);
+        assert_eq!(ActionRequest::SUBMIT, request);
+
+        let status = callback(this, BrowserAction::CHANGED, 9, efi::hii::IFR_TYPE_BOOLEAN, &mut value, &mut request);
+        assert_eq!(efi::Status::UNSUPPORTED, status);
+    }
+
+    #[test]
+    fn test_publish_forms() {
+        let (boot_services, config_access) = config_access();
+        let database = hii_database_protocol();
+        unsafe {
+            boot_services
+                .install_protocol_interface_unchecked(
+                    None,
+                    &efi::protocols::hii_database::PROTOCOL_GUID,
+                    database as *mut _ as *mut c_void,
+                )
+                .unwrap()
+        };
+
+        let package_list = PackageList::new(STORAGE_GUID);
+        let forms = publish_forms(boot_services, &device_path(), &package_list, config_access).unwrap();
+        let database = HiiDatabase::locate(boot_services).unwrap();
+        assert_eq!(package_list.to_bytes(), database.export_package_list(forms.hii_handle).unwrap());
+        assert_eq!(forms.driver_handle, database.driver_handle(forms.hii_handle).unwrap());
+
+        let installed = boot_services.handle_protocol(forms.driver_handle, &HiiConfigAccessProducer).unwrap();
+        assert_eq!(header(), *installed.handler.header());
+        let path = boot_services.handle_protocol(forms.driver_handle, &protocol_handler::DevicePath).unwrap();
+        assert_eq!(&*device_path(), unsafe { DevicePath::from_ptr(path) }.unwrap());
+    }
+}
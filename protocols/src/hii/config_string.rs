@@ -0,0 +1,346 @@
+//! Configuration strings, exchanged between the forms browser, the HII configuration routing and the drivers.
+//!
+//! A configuration string addresses a buffer storage with a [`ConfigHeader`], `GUID=...&NAME=...&PATH=...`, followed
+//! by the `&OFFSET=...&WIDTH=...` blocks of a request, each with its `&VALUE=...` in a configuration. The numbers
+//! and the bytes are in hexadecimal, a value has the bytes of the storage in reverse order:
+//!
+//! ```ignore
+//! let requests = ConfigString::parse(request)?;
+//! let results = requests[0].extract(settings.as_bytes())?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 35.2.1. Configuration String Syntax](https://uefi.org/specs/UEFI/2.10/35_HII_Configuration_Processing_and_Browser_Protocol.html)
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use device_path::{DevicePath, DevicePathBuf};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+
+/// The header of a configuration string, addressing the buffer storage of a form set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigHeader {
+    /// The GUID of the storage.
+    pub guid: efi::Guid,
+    /// The name of the storage.
+    pub name: String16,
+    /// The device path of the driver handle of the form set.
+    pub device_path: DevicePathBuf,
+}
+
+impl ConfigHeader {
+    pub fn new(guid: efi::Guid, name: &Str16, device_path: &DevicePath) -> Self {
+        Self { guid, name: name.into(), device_path: device_path.into() }
+    }
+}
+
+impl fmt::Display for ConfigHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("GUID=")?;
+        write_hex(f, self.guid.as_bytes())?;
+        f.write_str("&NAME=")?;
+        self.name.as_slice().iter().try_for_each(|c| write!(f, "{c:04x}"))?;
+        f.write_str("&PATH=")?;
+        write_hex(f, self.device_path.as_bytes())
+    }
+}
+
+/// A block of the storage, with its value in a configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigBlock {
+    pub offset: usize,
+    pub width: usize,
+    /// The bytes of the block, in the order of the storage.
+    pub value: Option<Vec<u8>>,
+}
+
+/// A request for blocks of a storage, or a configuration giving their values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigString {
+    pub header: ConfigHeader,
+    pub blocks: Vec<ConfigBlock>,
+}
+
+/// Error parsing a configuration string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    /// Index of the `&` before the element that is not valid, or 0 for the first element.
+    pub position: usize,
+}
+
+impl From<ParseError> for efi::Status {
+    fn from(_: ParseError) -> Self {
+        efi::Status::INVALID_PARAMETER
+    }
+}
+
+impl From<ParseError> for EfiError {
+    fn from(error: ParseError) -> Self {
+        efi::Status::from(error).into()
+    }
+}
+
+impl ConfigString {
+    /// Creates a request for the whole storage, without blocks.
+    pub fn new(header: ConfigHeader) -> Self {
+        Self { header, blocks: Vec::new() }
+    }
+
+    /// Parses the configuration strings that follow each other, each starting with its `GUID=`.
+    pub fn parse(s: &Str16) -> Result<Vec<ConfigString>, ParseError> {
+        Ok(parse_with_positions(s)?.into_iter().map(|(_, config)| config).collect())
+    }
+
+    /// The configuration answering the request from the bytes of the storage, with every byte if the request has no
+    /// blocks.
+    ///
+    /// `INVALID_PARAMETER` if a block is outside of the storage.
+    pub fn extract(&self, storage: &[u8]) -> Result<ConfigString, EfiError> {
+        let blocks = match self.blocks.is_empty() {
+            true => &[ConfigBlock { offset: 0, width: storage.len(), value: None }][..],
+            false => &self.blocks[..],
+        };
+        let blocks = blocks
+            .iter()
+            .map(|block| {
+                let value = storage.get(block.offset..block.offset.checked_add(block.width)?)?;
+                Some(ConfigBlock { value: Some(value.to_vec()), ..*block })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(efi::Status::INVALID_PARAMETER)?;
+        Ok(ConfigString { header: self.header.clone(), blocks })
+    }
+
+    /// Writes the values of the configuration in the bytes of the storage, the storage is unchanged on error.
+    ///
+    /// `INVALID_PARAMETER` if a block has no value or is outside of the storage.
+    pub fn route(&self, storage: &mut [u8]) -> Result<(), EfiError> {
+        let fits = |block: &ConfigBlock| {
+            block.value.as_ref().is_some_and(|value| value.len() == block.width)
+                && block.offset.checked_add(block.width).is_some_and(|end| end <= storage.len())
+        };
+        if !self.blocks.iter().all(fits) {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        for block in &self.blocks {
+            if let Some(value) = &block.value {
+                storage[block.offset..block.offset + block.width].copy_from_slice(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// The configuration string as given to the firmware.
+    pub fn to_string16(&self) -> String16 {
+        // The string only has ASCII characters.
+        String16::try_from(self.to_string().as_str()).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ConfigString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.header)?;
+        for block in &self.blocks {
+            write!(f, "&OFFSET={:04x}&WIDTH={:04x}", block.offset, block.width)?;
+            if let Some(value) = &block.value {
+                f.write_str("&VALUE=")?;
+                value.iter().rev().try_for_each(|byte| write!(f, "{byte:02x}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+}
+
+/// Parses the configuration strings, each with the position of the `&` before it or 0 for the first.
+pub(crate) fn parse_with_positions(s: &Str16) -> Result<Vec<(usize, ConfigString)>, ParseError> {
+    let chars = s.as_slice();
+    let mut elements = Vec::new();
+    let mut start = 0;
+    for (index, &c) in chars.iter().enumerate() {
+        if c == u16::from(b'&') {
+            elements.push((start, index));
+            start = index;
+        } else if !(0x20..0x7F).contains(&c) {
+            return Err(ParseError { position: start });
+        }
+    }
+    if !chars.is_empty() {
+        elements.push((start, chars.len()));
+    }
+    // Each element with the position of its `&`, and its characters without the `&`.
+    let mut elements = elements
+        .into_iter()
+        .map(|(position, end)| {
+            let element = &chars[position..end];
+            let element = element.strip_prefix(&[u16::from(b'&')]).unwrap_or(element);
+            (position, element.iter().map(|&c| c as u8 as char).collect::<String>())
+        })
+        .peekable();
+
+    let mut configs = Vec::new();
+    while let Some((position, guid)) = elements.next() {
+        let mut field = |name: &str| {
+            let (field_position, element) = elements.next().ok_or(ParseError { position: s.as_slice().len() })?;
+            element.strip_prefix(name).map(String::from).ok_or(ParseError { position: field_position })
+        };
+        let error = ParseError { position };
+        let guid = guid.strip_prefix("GUID=").and_then(hex_bytes).ok_or(error)?;
+        let guid = efi::Guid::from_bytes(&guid.try_into().map_err(|_| error)?);
+        let name = field("NAME=")?;
+        let name = hex_chars(&name).ok_or(error)?;
+        let path = hex_bytes(&field("PATH=")?).ok_or(error)?;
+        let header = ConfigHeader { guid, name, device_path: DevicePathBuf::from_vec(path).map_err(|_| error)? };
+
+        let mut blocks = Vec::new();
+        while let Some((block_position, offset)) = elements.next_if(|(_, element)| element.starts_with("OFFSET=")) {
+            let error = ParseError { position: block_position };
+            let offset = hex_number(&offset["OFFSET=".len()..]).ok_or(error)?;
+            let width = match elements.next() {
+                Some((_, width)) => width.strip_prefix("WIDTH=").and_then(hex_number).ok_or(error)?,
+                None => return Err(error),
+            };
+            let value = match elements.next_if(|(_, element)| element.starts_with("VALUE=")) {
+                Some((_, value)) => {
+                    let mut value = hex_bytes(&value["VALUE=".len()..]).filter(|v| v.len() == width).ok_or(error)?;
+                    value.reverse();
+                    Some(value)
+                }
+                None => None,
+            };
+            blocks.push(ConfigBlock { offset, width, value });
+        }
+        configs.push((position, ConfigString { header, blocks }));
+    }
+    Ok(configs)
+}
+
+fn hex_number(s: &str) -> Option<usize> {
+    (!s.is_empty()).then(|| usize::from_str_radix(s, 16).ok()).flatten()
+}
+
+fn hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok()).collect()
+}
+
+/// The characters of a name, 4 hexadecimal digits each.
+fn hex_chars(s: &str) -> Option<String16> {
+    if s.len() % 4 != 0 {
+        return None;
+    }
+    let mut chars =
+        (0..s.len()).step_by(4).map(|i| u16::from_str_radix(&s[i..i + 4], 16).ok()).collect::<Option<Vec<_>>>()?;
+    chars.push(0);
+    String16::from_vec_with_nul(chars).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+    use device_path::{node_types::VendorHardware, DevicePathBuilder};
+
+    const STORAGE_GUID: efi::Guid =
+        efi::Guid::from_fields(0x12345678, 0x9abc, 0xdef0, 0x01, 0x23, &[0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+
+    fn header() -> ConfigHeader {
+        let device_path = DevicePathBuilder::new().push(&VendorHardware::new(STORAGE_GUID, &[])).build();
+        ConfigHeader::new(STORAGE_GUID, &String16::try_from("Setup").unwrap(), &device_path)
+    }
+
+    fn header_string() -> String {
+        let mut path = "0104140078563412bc9af0de0123456789abcdef".to_string();
+        path.push_str("7fff0400");
+        format!("GUID=78563412bc9af0de0123456789abcdef&NAME=00530065007400750070&PATH={path}")
+    }
+
+    fn s(s: &str) -> String16 {
+        String16::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut config = ConfigString::new(header());
+        assert_eq!(header_string(), config.to_string());
+        config.blocks.push(ConfigBlock { offset: 2, width: 2, value: None });
+        config.blocks.push(ConfigBlock { offset: 0x10, width: 4, value: Some(vec![0x78, 0x56, 0x34, 0x12]) });
+        assert_eq!(
+            format!("{}&OFFSET=0002&WIDTH=0002&OFFSET=0010&WIDTH=0004&VALUE=12345678", header_string()),
+            config.to_string()
+        );
+        assert_eq!(s(&config.to_string()), config.to_string16());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(ConfigString::parse(&s("")).unwrap().is_empty());
+
+        let request = format!("{0}&OFFSET=0&WIDTH=1&OFFSET=04&WIDTH=0002&VALUE=BEEF&{0}", header_string());
+        let configs = parse_with_positions(&s(&request)).unwrap();
+        assert_eq!(2, configs.len());
+        assert_eq!(0, configs[0].0);
+        assert_eq!(request.len() - header_string().len() - 1, configs[1].0);
+        assert_eq!(header(), configs[0].1.header);
+        assert_eq!(
+            vec![
+                ConfigBlock { offset: 0, width: 1, value: None },
+                ConfigBlock { offset: 4, width: 2, value: Some(vec![0xEF, 0xBE]) }
+            ],
+            configs[0].1.blocks
+        );
+        assert_eq!(ConfigString::new(header()), configs[1].1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let header = header_string();
+        let offset_position = header.len();
+        for (request, position) in [
+            ("NAME=00".to_string(), 0),
+            (header[..header.find("&PATH").unwrap()].to_string(), header.find("&PATH").unwrap()),
+            (header.replace("GUID=78", "GUID=7"), 0),
+            (format!("{header}&OFFSET=0"), offset_position),
+            (format!("{header}&OFFSET=0&WIDTH=2&VALUE=01"), offset_position),
+            (format!("{header}&OFFSET=0&WIDTH=x"), offset_position),
+            (format!("{header}&OFFSET=0&WIDTH=1&ALTCFG=0000"), offset_position + "&OFFSET=0&WIDTH=1".len()),
+        ] {
+            assert_eq!(Err(ParseError { position }), ConfigString::parse(&s(&request)), "{request}");
+        }
+        assert_eq!(efi::Status::INVALID_PARAMETER, efi::Status::from(ParseError { position: 0 }));
+    }
+
+    #[test]
+    fn test_extract_and_route() {
+        let mut storage = [1, 2, 3, 4, 5];
+        let request = ConfigString::new(header());
+        let all = request.extract(&storage).unwrap();
+        assert_eq!(vec![ConfigBlock { offset: 0, width: 5, value: Some(storage.to_vec()) }], all.blocks);
+
+        let mut request = ConfigString::new(header());
+        request.blocks.push(ConfigBlock { offset: 3, width: 2, value: None });
+        let config = request.extract(&storage).unwrap();
+        assert_eq!(format!("{}&OFFSET=0003&WIDTH=0002&VALUE=0504", header_string()), config.to_string());
+        request.blocks.push(ConfigBlock { offset: 4, width: 2, value: None });
+        assert_eq!(efi::Status::INVALID_PARAMETER, request.extract(&storage).unwrap_err());
+
+        let mut config = ConfigString::parse(&s(&format!("{}&OFFSET=1&WIDTH=2&VALUE=aabb", header_string()))).unwrap();
+        config[0].route(&mut storage).unwrap();
+        assert_eq!([1, 0xbb, 0xaa, 4, 5], storage);
+        config[0].blocks.push(ConfigBlock { offset: 4, width: 1, value: None });
+        assert_eq!(efi::Status::INVALID_PARAMETER, config[0].route(&mut storage).unwrap_err());
+        config[0].blocks[1].value = Some(vec![0; 2]);
+        assert_eq!(efi::Status::INVALID_PARAMETER, config[0].route(&mut storage).unwrap_err());
+        assert_eq!([1, 0xbb, 0xaa, 4, 5], storage);
+    }
+}
@@ -0,0 +1,417 @@
+//! HII Database protocol.
+//!
+//! [UEFI Spec Documentation: 34.8. Database Protocol](https://uefi.org/specs/UEFI/2.10/34_HII_Protocols.html#efi-hii-database-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, mem, ptr};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::{package_header, HiiHandle, PACKAGE_HEADER_SIZE};
+
+type Protocol = efi::protocols::hii_database::Protocol;
+
+/// A package list to register in the HII database, the packages follow a header with the GUID of the list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageList {
+    guid: efi::Guid,
+    packages: Vec<u8>,
+}
+
+impl PackageList {
+    /// Creates an empty package list.
+    pub fn new(guid: efi::Guid) -> Self {
+        Self { guid, packages: Vec::new() }
+    }
+
+    /// The GUID of the package list.
+    pub fn guid(&self) -> &efi::Guid {
+        &self.guid
+    }
+
+    /// Adds a package, with its header, like a [`StringPackage`](super::StringPackage) or the forms package built
+    /// from a VFR file.
+    pub fn add_package(&mut self, package: &[u8]) {
+        self.packages.extend_from_slice(package);
+    }
+
+    /// The package list as given to the HII database, ending with the end package.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let length = mem::size_of::<efi::hii::PackageListHeader>() + self.packages.len() + PACKAGE_HEADER_SIZE;
+        let mut bytes = Vec::with_capacity(length);
+        bytes.extend_from_slice(self.guid.as_bytes());
+        bytes.extend_from_slice(&(length as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.packages);
+        bytes.extend_from_slice(&package_header(efi::hii::PACKAGE_END, PACKAGE_HEADER_SIZE));
+        bytes
+    }
+}
+
+/// Typed access to the HII Database protocol.
+pub struct HiiDatabase(&'static mut Protocol);
+
+impl HiiDatabase {
+    /// Locates the HII database.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::HiiDatabase, None).map(Self)
+    }
+
+    /// Registers a package list, returns its handle in the database.
+    ///
+    /// The driver handle receives the device path package of the list, if it has one.
+    pub fn new_package_list(
+        &self,
+        package_list: &PackageList,
+        driver_handle: Option<efi::Handle>,
+    ) -> Result<HiiHandle, EfiError> {
+        let bytes = package_list.to_bytes();
+        let mut handle = ptr::null_mut();
+        match unsafe {
+            r_efi::efi_call(
+                self.0.new_package_list,
+                (
+                    self.0,
+                    bytes.as_ptr() as *const efi::hii::PackageListHeader,
+                    driver_handle.unwrap_or(ptr::null_mut()),
+                    &mut handle,
+                ),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(handle),
+        }
+    }
+
+    /// Removes a package list from the database.
+    pub fn remove_package_list(&self, handle: HiiHandle) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.0.remove_package_list, (self.0, handle)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces the packages of a registered package list by the packages of the same types in `package_list`.
+    pub fn update_package_list(&self, handle: HiiHandle, package_list: &PackageList) -> Result<(), EfiError> {
+        let bytes = package_list.to_bytes();
+        match unsafe {
+            r_efi::efi_call(
+                self.0.update_package_list,
+                (self.0, handle, bytes.as_ptr() as *const efi::hii::PackageListHeader),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The handles of the package lists that have a package of the given type, `PACKAGE_TYPE_ALL` for any type.
+    ///
+    /// The GUID is the package list GUID, or the GUID of the package for `PACKAGE_TYPE_GUID`.
+    pub fn list_package_lists(&self, package_type: u8, guid: Option<&efi::Guid>) -> Result<Vec<HiiHandle>, EfiError> {
+        let guid = guid.map_or(ptr::null(), |guid| guid as *const efi::Guid);
+        let mut handles: Vec<HiiHandle> = Vec::new();
+        loop {
+            let mut size = handles.len() * mem::size_of::<HiiHandle>();
+            match unsafe {
+                r_efi::efi_call(
+                    self.0.list_package_lists,
+                    (self.0, package_type, guid, &mut size, handles.as_mut_ptr()),
+                )
+            } {
+                efi::Status::BUFFER_TOO_SMALL if size > handles.len() * mem::size_of::<HiiHandle>() => {
+                    handles = vec![ptr::null_mut(); size.div_ceil(mem::size_of::<HiiHandle>())]
+                }
+                efi::Status::NOT_FOUND => return Ok(Vec::new()),
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    handles.truncate(size / mem::size_of::<HiiHandle>());
+                    return Ok(handles);
+                }
+            }
+        }
+    }
+
+    /// The package list registered with the handle, header included.
+    pub fn export_package_list(&self, handle: HiiHandle) -> Result<Vec<u8>, EfiError> {
+        let mut buffer = Vec::new();
+        loop {
+            let mut size = buffer.len();
+            match unsafe {
+                r_efi::efi_call(
+                    self.0.export_package_lists,
+                    (self.0, handle, &mut size, buffer.as_mut_ptr() as *mut efi::hii::PackageListHeader),
+                )
+            } {
+                efi::Status::BUFFER_TOO_SMALL if size > buffer.len() => buffer.resize(size, 0),
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    buffer.truncate(size);
+                    return Ok(buffer);
+                }
+            }
+        }
+    }
+
+    /// The driver handle the package list was registered with.
+    pub fn driver_handle(&self, handle: HiiHandle) -> Result<efi::Handle, EfiError> {
+        let mut driver_handle = ptr::null_mut();
+        match unsafe { r_efi::efi_call(self.0.get_package_list_handle, (self.0, handle, &mut driver_handle)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(driver_handle),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for HiiDatabase {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for HiiDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("HiiDatabase").field(&(self.0 as *const Protocol)).finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    //! In-memory HII database implementing the protocol.
+    use super::*;
+    use alloc::boxed::Box;
+    use core::{cell::RefCell, slice};
+    use efi::protocols::hii_database::{KeyboardLayout, Notify, NotifyType};
+
+    /// A registered package list with its driver handle, `None` once removed.
+    type TestPackageList = Option<(Vec<u8>, efi::Handle)>;
+
+    /// Database of the package lists, a handle is the index of its list plus one.
+    #[repr(C)]
+    struct TestDatabase {
+        protocol: Protocol,
+        lists: RefCell<Vec<TestPackageList>>,
+    }
+
+    fn test_database<'a>(this: *const Protocol) -> &'a TestDatabase {
+        unsafe { &*(this as *const TestDatabase) }
+    }
+
+    /// The package list a pointer gives, from the length in its header.
+    fn package_list(list: *const efi::hii::PackageListHeader) -> Vec<u8> {
+        let length = unsafe { ptr::addr_of!((*list).package_length).read_unaligned() } as usize;
+        unsafe { slice::from_raw_parts(list as *const u8, length) }.to_vec()
+    }
+
+    fn list_index(handle: HiiHandle) -> usize {
+        (handle as usize).wrapping_sub(1)
+    }
+
+    extern "efiapi" fn new_package_list(
+        this: *const Protocol,
+        list: *const efi::hii::PackageListHeader,
+        driver_handle: efi::Handle,
+        handle: *mut HiiHandle,
+    ) -> efi::Status {
+        let mut lists = test_database(this).lists.borrow_mut();
+        lists.push(Some((package_list(list), driver_handle)));
+        unsafe { handle.write(lists.len() as HiiHandle) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn remove_package_list(this: *const Protocol, handle: HiiHandle) -> efi::Status {
+        match test_database(this).lists.borrow_mut().get_mut(list_index(handle)) {
+            Some(list @ Some(_)) => {
+                *list = None;
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::NOT_FOUND,
+        }
+    }
+
+    extern "efiapi" fn update_package_list(
+        this: *const Protocol,
+        handle: HiiHandle,
+        list: *const efi::hii::PackageListHeader,
+    ) -> efi::Status {
+        match test_database(this).lists.borrow_mut().get_mut(list_index(handle)) {
+            Some(Some((bytes, _))) => {
+                *bytes = package_list(list);
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::NOT_FOUND,
+        }
+    }
+
+    extern "efiapi" fn list_package_lists(
+        this: *const Protocol,
+        _package_type: u8,
+        guid: *const efi::Guid,
+        size: *mut usize,
+        handles: *mut HiiHandle,
+    ) -> efi::Status {
+        let found = test_database(this)
+            .lists
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|(_, list)| match (list, unsafe { guid.as_ref() }) {
+                (Some((bytes, _)), Some(guid)) => bytes[..16] == *guid.as_bytes(),
+                (list, None) => list.is_some(),
+                (None, _) => false,
+            })
+            .map(|(index, _)| (index + 1) as HiiHandle)
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            return efi::Status::NOT_FOUND;
+        }
+        let needed = found.len() * mem::size_of::<HiiHandle>();
+        if unsafe { *size } < needed {
+            unsafe { size.write(needed) };
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(handles, found.len()) }.copy_from_slice(&found);
+        unsafe { size.write(needed) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn export_package_lists(
+        this: *const Protocol,
+        handle: HiiHandle,
+        size: *mut usize,
+        buffer: *mut efi::hii::PackageListHeader,
+    ) -> efi::Status {
+        let lists = test_database(this).lists.borrow();
+        let Some(Some((bytes, _))) = lists.get(list_index(handle)) else {
+            return efi::Status::NOT_FOUND;
+        };
+        if unsafe { *size } < bytes.len() {
+            unsafe { size.write(bytes.len()) };
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(buffer as *mut u8, bytes.len()) }.copy_from_slice(bytes);
+        unsafe { size.write(bytes.len()) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_package_list_handle(
+        this: *const Protocol,
+        handle: HiiHandle,
+        driver_handle: *mut efi::Handle,
+    ) -> efi::Status {
+        match test_database(this).lists.borrow().get(list_index(handle)) {
+            Some(Some((_, driver))) => {
+                unsafe { driver_handle.write(*driver) };
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::INVALID_PARAMETER,
+        }
+    }
+
+    extern "efiapi" fn register_package_notify(
+        _: *const Protocol,
+        _: u8,
+        _: *const efi::Guid,
+        _: Notify,
+        _: NotifyType,
+        _: *mut efi::Handle,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn unregister_package_notify(_: *const Protocol, _: efi::Handle) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn find_keyboard_layouts(_: *const Protocol, _: *mut u16, _: *mut efi::Guid) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn get_keyboard_layout(
+        _: *const Protocol,
+        _: *const efi::Guid,
+        _: *mut u16,
+        _: *mut KeyboardLayout,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn set_keyboard_layout(_: *const Protocol, _: *mut efi::Guid) -> efi::Status {
+        unimplemented!()
+    }
+
+    /// An empty database, leaked for the test.
+    fn test_database_instance() -> &'static mut TestDatabase {
+        Box::leak(Box::new(TestDatabase {
+            protocol: Protocol {
+                new_package_list,
+                remove_package_list,
+                update_package_list,
+                list_package_lists,
+                export_package_lists,
+                register_package_notify,
+                unregister_package_notify,
+                find_keyboard_layouts,
+                get_keyboard_layout,
+                set_keyboard_layout,
+                get_package_list_handle,
+            },
+            lists: RefCell::new(Vec::new()),
+        }))
+    }
+
+    pub(crate) fn hii_database_protocol() -> &'static mut Protocol {
+        &mut test_database_instance().protocol
+    }
+
+    pub(crate) fn hii_database() -> HiiDatabase {
+        HiiDatabase::from(hii_database_protocol())
+    }
+
+    const LIST_GUID: efi::Guid =
+        efi::Guid::from_fields(0x0d2a5a64, 0x3f13, 0x4a1e, 0x9a, 0x44, &[0x1c, 0x8b, 0x5f, 0x20, 0x71, 0x3e]);
+
+    #[test]
+    fn test_package_list_bytes() {
+        let mut package_list = PackageList::new(LIST_GUID);
+        assert_eq!(&LIST_GUID, package_list.guid());
+        package_list.add_package(&[6, 0, 0, efi::hii::PACKAGE_TYPE_GUID, 0xAA, 0xBB]);
+
+        let bytes = package_list.to_bytes();
+        assert_eq!(LIST_GUID.as_bytes(), &bytes[..16]);
+        assert_eq!(30, u32::from_le_bytes(bytes[16..20].try_into().unwrap()));
+        assert_eq!([6, 0, 0, efi::hii::PACKAGE_TYPE_GUID, 0xAA, 0xBB], bytes[20..26]);
+        assert_eq!([4, 0, 0, efi::hii::PACKAGE_END], bytes[26..]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_package_header_too_large() {
+        package_header(efi::hii::PACKAGE_FORMS, 1 << 24);
+    }
+
+    #[test]
+    fn test_register_package_lists() {
+        let database = hii_database();
+        assert!(database.list_package_lists(efi::hii::PACKAGE_TYPE_ALL, None).unwrap().is_empty());
+
+        let mut package_list = PackageList::new(LIST_GUID);
+        let driver = 9_usize as efi::Handle;
+        let handle = database.new_package_list(&package_list, Some(driver)).unwrap();
+        let other = database.new_package_list(&PackageList::new(efi::Guid::from_bytes(&[1; 16])), None).unwrap();
+        assert_eq!(package_list.to_bytes(), database.export_package_list(handle).unwrap());
+        assert_eq!(driver, database.driver_handle(handle).unwrap());
+        assert_eq!(vec![handle, other], database.list_package_lists(efi::hii::PACKAGE_TYPE_ALL, None).unwrap());
+        assert_eq!(vec![handle], database.list_package_lists(efi::hii::PACKAGE_TYPE_ALL, Some(&LIST_GUID)).unwrap());
+
+        package_list.add_package(&package_header(efi::hii::PACKAGE_TYPE_GUID, PACKAGE_HEADER_SIZE));
+        database.update_package_list(handle, &package_list).unwrap();
+        assert_eq!(package_list.to_bytes(), database.export_package_list(handle).unwrap());
+
+        database.remove_package_list(handle).unwrap();
+        assert_eq!(efi::Status::NOT_FOUND, database.remove_package_list(handle).unwrap_err());
+        assert_eq!(efi::Status::NOT_FOUND, database.export_package_list(handle).unwrap_err());
+        assert!(database.list_package_lists(efi::hii::PACKAGE_TYPE_ALL, Some(&LIST_GUID)).unwrap().is_empty());
+    }
+}
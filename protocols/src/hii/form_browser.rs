@@ -0,0 +1,325 @@
+//! Form Browser2 protocol, displaying the forms of HII package lists.
+//!
+//! [`FormBrowser::send_form`] shows forms until the user leaves them, while a form is shown the callbacks of its
+//! [`ConfigAccess`](super::ConfigAccess) read and change the values the browser holds for a storage, before they are
+//! submitted:
+//!
+//! ```ignore
+//! let browser = FormBrowser::locate(boot_services)?;
+//! match browser.send_form(&[forms.hii_handle], Some(&FORMSET_GUID), None, None)? {
+//!     ActionRequest::RESET => runtime_services.reset_system(efi::RESET_COLD, efi::Status::SUCCESS, None),
+//!     _ => (),
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 35.6. Form Browser Protocol](https://uefi.org/specs/UEFI/2.10/35_HII_Configuration_Processing_and_Browser_Protocol.html#form-browser-protocol)
+
+use alloc::{string::ToString, vec, vec::Vec};
+use core::{fmt, ops::Deref, ptr};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::Str16;
+
+use super::{
+    config_access::ActionRequest,
+    config_string::{ConfigHeader, ConfigString},
+    HiiHandle,
+};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xb9d4c360, 0xbcfb, 0x4f9b, 0x92, 0x98, &[0x53, 0xc1, 0x36, 0x98, 0x22, 0x58]);
+
+pub type ProtocolSendForm = extern "efiapi" fn(
+    *const Protocol,
+    *const HiiHandle,
+    usize,
+    *const efi::Guid,
+    efi::hii::FormId,
+    *const ScreenDescriptor,
+    *mut ActionRequest,
+) -> efi::Status;
+
+pub type ProtocolBrowserCallback = extern "efiapi" fn(
+    *const Protocol,
+    *mut usize,
+    *mut u16,
+    efi::Boolean,
+    *const efi::Guid,
+    *const u16,
+) -> efi::Status;
+
+/// FFI definition of `EFI_FORM_BROWSER2_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub send_form: ProtocolSendForm,
+    pub browser_callback: ProtocolBrowserCallback,
+}
+
+/// Form Browser2 protocol.
+pub struct FormBrowser2Protocol;
+
+unsafe impl ProtocolTrait for FormBrowser2Protocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for FormBrowser2Protocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// FFI definition of `EFI_SCREEN_DESCRIPTOR`, the part of the screen the forms are shown in.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScreenDescriptor {
+    pub left_column: usize,
+    pub right_column: usize,
+    pub top_row: usize,
+    pub bottom_row: usize,
+}
+
+/// Typed access to the Form Browser2 protocol.
+pub struct FormBrowser(&'static mut Protocol);
+
+impl FormBrowser {
+    /// Locates the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&FormBrowser2Protocol, None).map(Self)
+    }
+
+    /// Shows the forms of the package lists until the user leaves them, returns the action the caller is to take,
+    /// such as [`ActionRequest::RESET`].
+    ///
+    /// The browser starts with the form set of the GUID, or the first one of the package lists, at the form of the
+    /// ID, or the first one of the form set. The forms take the whole screen without `screen`.
+    ///
+    /// # Errors
+    ///
+    /// * `NOT_FOUND` if no form set matches.
+    pub fn send_form(
+        &self,
+        hii_handles: &[HiiHandle],
+        formset_guid: Option<&efi::Guid>,
+        form_id: Option<efi::hii::FormId>,
+        screen: Option<&ScreenDescriptor>,
+    ) -> Result<ActionRequest, EfiError> {
+        if hii_handles.is_empty() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let mut action_request = ActionRequest::NONE;
+        match (self.0.send_form)(
+            self.0,
+            hii_handles.as_ptr(),
+            hii_handles.len(),
+            formset_guid.map_or(ptr::null(), |guid| guid as *const _),
+            form_id.unwrap_or(0),
+            screen.map_or(ptr::null(), |screen| screen as *const _),
+            &mut action_request,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(action_request),
+        }
+    }
+
+    /// The values the browser holds for the storage of the header, which the user may have changed since they were
+    /// extracted from the driver.
+    ///
+    /// Only available while a form using the storage is shown, that is from the callbacks of its config access.
+    ///
+    /// # Errors
+    ///
+    /// * `NOT_FOUND` if no form using the storage is shown.
+    pub fn browser_data(&self, header: &ConfigHeader) -> Result<ConfigString, EfiError> {
+        let mut results: Vec<u16> = Vec::new();
+        loop {
+            let mut size = results.len() * 2;
+            match (self.0.browser_callback)(
+                self.0,
+                &mut size,
+                results.as_mut_ptr(),
+                efi::Boolean::TRUE,
+                &header.guid,
+                header.name.as_ptr(),
+            ) {
+                efi::Status::BUFFER_TOO_SMALL if size > results.len() * 2 => results = vec![0; size.div_ceil(2)],
+                s if s.is_error() => return Err(s.into()),
+                _ => break,
+            }
+        }
+        // The results are the blocks of a configuration string, without the header.
+        let results = Str16::from_slice_until_nul(&results).map_err(|_| efi::Status::DEVICE_ERROR)?;
+        let mut configuration = ConfigString::new(header.clone()).to_string16();
+        configuration.push_str16(results);
+        let configuration = ConfigString::parse(&configuration).map_err(|_| efi::Status::DEVICE_ERROR)?;
+        match <[ConfigString; 1]>::try_from(configuration) {
+            Ok([configuration]) => Ok(configuration),
+            Err(_) => Err(efi::Status::DEVICE_ERROR.into()),
+        }
+    }
+
+    /// Changes the values the browser holds for the storage of the configuration to the values of its blocks, they
+    /// are shown to the user and routed to the driver when the form is submitted.
+    ///
+    /// Only available while a form using the storage is shown, see [`FormBrowser::browser_data`].
+    pub fn set_browser_data(&self, configuration: &ConfigString) -> Result<(), EfiError> {
+        if configuration.blocks.iter().any(|block| block.value.is_none()) {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let header = &configuration.header;
+        let blocks = configuration.to_string().split_off(header.to_string().len());
+        let mut blocks = blocks.encode_utf16().chain([0]).collect::<Vec<_>>();
+        let mut size = blocks.len() * 2;
+        match (self.0.browser_callback)(
+            self.0,
+            &mut size,
+            blocks.as_mut_ptr(),
+            efi::Boolean::FALSE,
+            &header.guid,
+            header.name.as_ptr(),
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for FormBrowser {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for FormBrowser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FormBrowser").field(&(self.0 as *const Protocol)).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, string::String};
+    use core::{cell::RefCell, slice};
+    use device_path::{node_types::VendorHardware, DevicePathBuilder};
+    use ucs2::String16;
+
+    const FORMSET_GUID: efi::Guid =
+        efi::Guid::from_fields(0x3c1f7e02, 0x6a5d, 0x4b98, 0xa4, 0x0e, &[0x71, 0x2c, 0x9b, 0x58, 0xd3, 0x16]);
+
+    /// The handles, form set GUID, form ID and screen of the last forms shown.
+    type ShownForm = (Vec<usize>, Option<efi::Guid>, efi::hii::FormId, Option<ScreenDescriptor>);
+
+    /// Browser showing one form using the `Settings` storage, with the values it holds for it.
+    #[repr(C)]
+    struct TestBrowser {
+        protocol: Protocol,
+        shown: RefCell<Option<ShownForm>>,
+        data: RefCell<String>,
+    }
+
+    fn test_browser<'a>(this: *const Protocol) -> &'a TestBrowser {
+        unsafe { &*(this as *const TestBrowser) }
+    }
+
+    extern "efiapi" fn send_form(
+        this: *const Protocol,
+        handles: *const HiiHandle,
+        handle_count: usize,
+        formset_guid: *const efi::Guid,
+        form_id: efi::hii::FormId,
+        screen: *const ScreenDescriptor,
+        action_request: *mut ActionRequest,
+    ) -> efi::Status {
+        let handles = unsafe { slice::from_raw_parts(handles, handle_count) }.iter().map(|h| *h as usize).collect();
+        let formset_guid = unsafe { formset_guid.as_ref() }.copied();
+        if formset_guid.is_some_and(|guid| guid != FORMSET_GUID) {
+            return efi::Status::NOT_FOUND;
+        }
+        let screen = unsafe { screen.as_ref() }.copied();
+        *test_browser(this).shown.borrow_mut() = Some((handles, formset_guid, form_id, screen));
+        unsafe { action_request.write(ActionRequest::RESET) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn browser_callback(
+        this: *const Protocol,
+        size: *mut usize,
+        results: *mut u16,
+        retrieve: efi::Boolean,
+        _guid: *const efi::Guid,
+        name: *const u16,
+    ) -> efi::Status {
+        if unsafe { Str16::from_ptr(name) } != "Settings" {
+            return efi::Status::NOT_FOUND;
+        }
+        let mut data = test_browser(this).data.borrow_mut();
+        if !bool::from(retrieve) {
+            *data = unsafe { Str16::from_ptr(results) }.to_string_lossy();
+            return efi::Status::SUCCESS;
+        }
+        let value = data.encode_utf16().chain([0]).collect::<Vec<_>>();
+        if unsafe { *size } < value.len() * 2 {
+            unsafe { size.write(value.len() * 2) };
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(results, value.len()) }.copy_from_slice(&value);
+        efi::Status::SUCCESS
+    }
+
+    fn form_browser() -> (FormBrowser, &'static TestBrowser) {
+        let browser = Box::leak(Box::new(TestBrowser {
+            protocol: Protocol { send_form, browser_callback },
+            shown: RefCell::new(None),
+            data: RefCell::new(String::from("&OFFSET=0000&WIDTH=0002&VALUE=0201")),
+        }));
+        let test_browser = test_browser(&browser.protocol);
+        (FormBrowser::from(&mut browser.protocol), test_browser)
+    }
+
+    fn header(name: &str) -> ConfigHeader {
+        let device_path = DevicePathBuilder::new().push(&VendorHardware::new(FORMSET_GUID, &[])).build();
+        ConfigHeader::new(FORMSET_GUID, &String16::try_from(name).unwrap(), &device_path)
+    }
+
+    #[test]
+    fn test_send_form() {
+        let (browser, test_browser) = form_browser();
+        let handles = [1 as HiiHandle, 2 as HiiHandle];
+        let screen = ScreenDescriptor { left_column: 0, right_column: 80, top_row: 1, bottom_row: 24 };
+
+        assert_eq!(Ok(ActionRequest::RESET), browser.send_form(&handles, Some(&FORMSET_GUID), Some(3), Some(&screen)));
+        assert_eq!(Some((vec![1, 2], Some(FORMSET_GUID), 3, Some(screen))), test_browser.shown.take());
+        assert_eq!(Ok(ActionRequest::RESET), browser.send_form(&handles[..1], None, None, None));
+        assert_eq!(Some((vec![1], None, 0, None)), test_browser.shown.take());
+
+        let other_guid = efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6]);
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), browser.send_form(&handles, Some(&other_guid), None, None));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), browser.send_form(&[], None, None, None));
+    }
+
+    #[test]
+    fn test_browser_data() {
+        let (browser, test_browser) = form_browser();
+
+        let mut data = browser.browser_data(&header("Settings")).unwrap();
+        assert_eq!(header("Settings"), data.header);
+        let mut storage = [0; 4];
+        data.route(&mut storage).unwrap();
+        assert_eq!([1, 2, 0, 0], storage);
+
+        data.blocks[0].value = Some(vec![7, 8]);
+        browser.set_browser_data(&data).unwrap();
+        assert_eq!("&OFFSET=0000&WIDTH=0002&VALUE=0807", *test_browser.data.borrow());
+        assert_eq!(Some(vec![7, 8]), browser.browser_data(&header("Settings")).unwrap().blocks[0].value);
+
+        data.blocks[0].value = None;
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), browser.set_browser_data(&data));
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), browser.browser_data(&header("Other")));
+    }
+}
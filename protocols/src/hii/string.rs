@@ -0,0 +1,421 @@
+//! HII String protocol and string packages.
+//!
+//! [UEFI Spec Documentation: 34.3. String Protocol](https://uefi.org/specs/UEFI/2.10/34_HII_Protocols.html#efi-hii-string-protocol)
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{fmt, ptr};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+
+use super::{package_header, HiiHandle, StringId, PACKAGE_HEADER_SIZE};
+
+type Protocol = efi::protocols::hii_string::Protocol;
+
+/// String block ending the strings of a package.
+pub const SIBT_END: u8 = 0x00;
+/// String block holding a null-terminated UCS-2 string.
+pub const SIBT_STRING_UCS2: u8 = 0x14;
+
+/// Size of the string package header without its language.
+const STRING_PACKAGE_HEADER_SIZE: usize = PACKAGE_HEADER_SIZE + 4 + 4 + 32 + 2;
+
+/// The strings of one language, built into a package to add to a [`PackageList`](super::PackageList).
+///
+/// The packages of the other languages of a package list must add their strings in the same order, for the IDs to
+/// match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringPackage {
+    language: String,
+    strings: Vec<String16>,
+}
+
+impl StringPackage {
+    /// Creates the package of an RFC 4646 language code like `en-US`, the first string is the name of the language
+    /// shown to the user.
+    pub fn new(language: &str, language_name: &Str16) -> Self {
+        Self { language: language.to_string(), strings: vec![language_name.into()] }
+    }
+
+    /// The language of the strings.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Adds a string, returns its ID.
+    pub fn add(&mut self, string: &Str16) -> StringId {
+        self.strings.push(string.into());
+        self.strings.len() as StringId
+    }
+
+    /// The string with the ID.
+    pub fn get(&self, id: StringId) -> Option<&Str16> {
+        self.strings.get((id as usize).checked_sub(1)?).map(String16::as_str16)
+    }
+
+    /// The string package, with its header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header_size = STRING_PACKAGE_HEADER_SIZE + self.language.len() + 1;
+        let mut bytes = vec![0; header_size];
+        bytes[4..8].copy_from_slice(&(header_size as u32).to_le_bytes());
+        // The strings follow the header, the language window stays empty.
+        bytes[8..12].copy_from_slice(&(header_size as u32).to_le_bytes());
+        bytes[44..46].copy_from_slice(&1_u16.to_le_bytes());
+        bytes[46..header_size - 1].copy_from_slice(self.language.as_bytes());
+        for string in &self.strings {
+            bytes.push(SIBT_STRING_UCS2);
+            bytes.extend(string.as_slice_with_nul().iter().flat_map(|c| c.to_le_bytes()));
+        }
+        bytes.push(SIBT_END);
+        let header = package_header(efi::hii::PACKAGE_STRINGS, bytes.len());
+        bytes[..PACKAGE_HEADER_SIZE].copy_from_slice(&header);
+        bytes
+    }
+}
+
+/// Typed access to the HII String protocol.
+pub struct HiiString(&'static mut Protocol);
+
+impl HiiString {
+    /// Locates the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::HiiString, None).map(Self)
+    }
+
+    /// The string with the ID in the package list, in an RFC 4646 language.
+    ///
+    /// # Errors
+    ///
+    /// * `NOT_FOUND` if the package list has no string with the ID.
+    /// * `INVALID_LANGUAGE` if the package list has no strings in the language.
+    pub fn get_string(&self, handle: HiiHandle, id: StringId, language: &str) -> Result<String16, EfiError> {
+        let language = c_language(language);
+        let mut string: Vec<u16> = Vec::new();
+        loop {
+            let mut size = string.len() * 2;
+            match unsafe {
+                r_efi::efi_call(
+                    self.0.get_string,
+                    (self.0, language.as_ptr(), handle, id, string.as_mut_ptr(), &mut size, ptr::null_mut()),
+                )
+            } {
+                efi::Status::BUFFER_TOO_SMALL if size > string.len() * 2 => string = vec![0; size.div_ceil(2)],
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    let string = Str16::from_slice_until_nul(&string).map_err(|_| efi::Status::DEVICE_ERROR)?;
+                    return Ok(string.into());
+                }
+            }
+        }
+    }
+
+    /// Adds a string to the package list, returns its ID.
+    ///
+    /// The ID is reserved in the other languages of the package list, where it gives an empty string until set.
+    pub fn new_string(&self, handle: HiiHandle, language: &str, string: &Str16) -> Result<StringId, EfiError> {
+        let language = c_language(language);
+        let mut id = 0;
+        // The string is only read by the protocol.
+        let string = string.as_ptr() as *mut u16;
+        match unsafe {
+            r_efi::efi_call(
+                self.0.new_string,
+                (self.0, handle, &mut id, language.as_ptr(), ptr::null(), string, ptr::null()),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(id),
+        }
+    }
+
+    /// Replaces the string with the ID in a language of the package list.
+    pub fn set_string(&self, handle: HiiHandle, id: StringId, language: &str, string: &Str16) -> Result<(), EfiError> {
+        let language = c_language(language);
+        // The string is only read by the protocol.
+        match unsafe {
+            r_efi::efi_call(
+                self.0.set_string,
+                (self.0, handle, id, language.as_ptr(), string.as_ptr() as *mut u16, ptr::null()),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The RFC 4646 languages of the strings of the package list.
+    pub fn languages(&self, handle: HiiHandle) -> Result<Vec<String>, EfiError> {
+        language_list(|buffer, size| unsafe { r_efi::efi_call(self.0.get_languages, (self.0, handle, buffer, size)) })
+    }
+
+    /// The languages of the package list that can be used for a primary language, like `en-GB` for `en`.
+    pub fn secondary_languages(&self, handle: HiiHandle, primary_language: &str) -> Result<Vec<String>, EfiError> {
+        let primary_language = c_language(primary_language);
+        language_list(|buffer, size| unsafe {
+            r_efi::efi_call(self.0.get_secondary_languages, (self.0, handle, primary_language.as_ptr(), buffer, size))
+        })
+    }
+}
+
+impl From<&'static mut Protocol> for HiiString {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for HiiString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("HiiString").field(&(self.0 as *const Protocol)).finish()
+    }
+}
+
+/// The language as the null-terminated ASCII string given to the protocol.
+fn c_language(language: &str) -> Vec<u8> {
+    language.bytes().chain([0]).collect()
+}
+
+/// Gets a list of languages separated by `;` from the protocol, growing the buffer to the size it needs.
+fn language_list(mut get: impl FnMut(*mut u8, *mut usize) -> efi::Status) -> Result<Vec<String>, EfiError> {
+    let mut buffer = Vec::new();
+    loop {
+        let mut size = buffer.len();
+        match get(buffer.as_mut_ptr(), &mut size) {
+            efi::Status::BUFFER_TOO_SMALL if size > buffer.len() => buffer.resize(size, 0),
+            s if s.is_error() => return Err(s.into()),
+            _ => break,
+        }
+    }
+    let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    let languages = core::str::from_utf8(&buffer[..end]).map_err(|_| efi::Status::DEVICE_ERROR)?;
+    Ok(languages.split(';').filter(|language| !language.is_empty()).map(String::from).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hii::{database::test::hii_database, HiiDatabase, PackageList};
+    use alloc::{boxed::Box, collections::BTreeMap};
+    use core::{cell::RefCell, ffi::CStr, slice};
+    use efi::protocols::hii_string::Info;
+
+    /// String protocol over the string packages of [`HiiDatabase`], with the strings added or changed on top.
+    #[repr(C)]
+    struct TestStrings {
+        protocol: Protocol,
+        database: HiiDatabase,
+        changed: RefCell<BTreeMap<(usize, String, StringId), Vec<u16>>>,
+    }
+
+    fn test_strings<'a>(this: *const Protocol) -> &'a TestStrings {
+        unsafe { &*(this as *const TestStrings) }
+    }
+
+    fn language(language: *const u8) -> String {
+        unsafe { CStr::from_ptr(language as *const _) }.to_str().unwrap().to_string()
+    }
+
+    /// The strings of the package list by language and ID, read from its string packages.
+    fn strings(this: *const Protocol, handle: HiiHandle) -> Option<BTreeMap<(String, StringId), Vec<u16>>> {
+        let test_strings = test_strings(this);
+        let list = test_strings.database.export_package_list(handle).ok()?;
+        let mut strings = BTreeMap::new();
+        let mut packages = &list[20..];
+        while packages[3] != efi::hii::PACKAGE_END {
+            let length = u32::from_le_bytes([packages[0], packages[1], packages[2], 0]) as usize;
+            let (package, rest) = packages.split_at(length);
+            packages = rest;
+            if package[3] != efi::hii::PACKAGE_STRINGS {
+                continue;
+            }
+            let header_size = u32::from_le_bytes(package[4..8].try_into().unwrap()) as usize;
+            let language = CStr::from_bytes_until_nul(&package[46..]).unwrap().to_str().unwrap().to_string();
+            let mut blocks = &package[header_size..];
+            let mut id = 1;
+            while blocks[0] == SIBT_STRING_UCS2 {
+                let string = blocks[1..].chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect::<Vec<_>>();
+                let length = string.iter().position(|&c| c == 0).unwrap() + 1;
+                strings.insert((language.clone(), id), string[..length].to_vec());
+                blocks = &blocks[1 + 2 * length..];
+                id += 1;
+            }
+            assert_eq!(SIBT_END, blocks[0]);
+        }
+        for ((list, language, id), string) in test_strings.changed.borrow().iter() {
+            if *list == handle as usize {
+                strings.insert((language.clone(), *id), string.clone());
+            }
+        }
+        Some(strings)
+    }
+
+    extern "efiapi" fn get_string(
+        this: *const Protocol,
+        language_ptr: *const u8,
+        handle: HiiHandle,
+        id: StringId,
+        string: *mut u16,
+        size: *mut usize,
+        _info: *mut *mut Info,
+    ) -> efi::Status {
+        let Some(strings) = strings(this, handle) else {
+            return efi::Status::NOT_FOUND;
+        };
+        let language = language(language_ptr);
+        if !strings.keys().any(|(l, _)| *l == language) {
+            return efi::Status::INVALID_LANGUAGE;
+        }
+        let Some(value) = strings.get(&(language, id)) else {
+            return efi::Status::NOT_FOUND;
+        };
+        if unsafe { *size } < value.len() * 2 {
+            unsafe { size.write(value.len() * 2) };
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(string, value.len()) }.copy_from_slice(value);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn new_string(
+        this: *const Protocol,
+        handle: HiiHandle,
+        id: *mut StringId,
+        language_ptr: *const u8,
+        _language_name: *const u16,
+        string: *mut u16,
+        _info: *const Info,
+    ) -> efi::Status {
+        let Some(strings) = strings(this, handle) else {
+            return efi::Status::NOT_FOUND;
+        };
+        let new_id = strings.keys().map(|(_, id)| *id).max().unwrap_or(0) + 1;
+        let string = unsafe { Str16::from_ptr(string) }.as_slice_with_nul().to_vec();
+        test_strings(this).changed.borrow_mut().insert((handle as usize, language(language_ptr), new_id), string);
+        unsafe { id.write(new_id) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_string(
+        this: *const Protocol,
+        handle: HiiHandle,
+        id: StringId,
+        language_ptr: *const u8,
+        string: *mut u16,
+        _info: *const Info,
+    ) -> efi::Status {
+        let language = language(language_ptr);
+        match strings(this, handle) {
+            Some(strings) if strings.contains_key(&(language.clone(), id)) => {
+                let string = unsafe { Str16::from_ptr(string) }.as_slice_with_nul().to_vec();
+                test_strings(this).changed.borrow_mut().insert((handle as usize, language, id), string);
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::NOT_FOUND,
+        }
+    }
+
+    extern "efiapi" fn get_languages(
+        this: *const Protocol,
+        handle: HiiHandle,
+        languages: *mut u8,
+        size: *mut usize,
+    ) -> efi::Status {
+        let Some(strings) = strings(this, handle) else {
+            return efi::Status::NOT_FOUND;
+        };
+        let mut list = strings.keys().map(|(language, _)| language.as_str()).collect::<Vec<_>>();
+        list.dedup();
+        let list = c_language(&list.join(";"));
+        if unsafe { *size } < list.len() {
+            unsafe { size.write(list.len()) };
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(languages, list.len()) }.copy_from_slice(&list);
+        unsafe { size.write(list.len()) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_secondary_languages(
+        _: *const Protocol,
+        _: HiiHandle,
+        _: *const u8,
+        _: *mut u8,
+        _: *mut usize,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    fn hii_string() -> (HiiString, &'static TestStrings) {
+        let strings = Box::leak(Box::new(TestStrings {
+            protocol: Protocol { new_string, get_string, set_string, get_languages, get_secondary_languages },
+            database: hii_database(),
+            changed: RefCell::new(BTreeMap::new()),
+        }));
+        let strings_ptr = strings as *mut TestStrings;
+        (HiiString::from(&mut strings.protocol), unsafe { &*strings_ptr })
+    }
+
+    const LIST_GUID: efi::Guid =
+        efi::Guid::from_fields(0x61d4c4c9, 0x1a4b, 0x4f71, 0x8c, 0x2e, &[0x5e, 0x39, 0x0f, 0x6a, 0x42, 0x17]);
+
+    fn s(s: &str) -> String16 {
+        String16::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_string_package_bytes() {
+        let mut package = StringPackage::new("en", &s("English"));
+        assert_eq!("en", package.language());
+        assert_eq!(2, package.add(&s("Hi")));
+        assert_eq!(Some(s("Hi").as_str16()), package.get(2));
+        assert!(package.get(0).is_none());
+        assert!(package.get(3).is_none());
+
+        let bytes = package.to_bytes();
+        let header_size = STRING_PACKAGE_HEADER_SIZE + 3;
+        assert_eq!(bytes.len(), u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as usize);
+        assert_eq!(efi::hii::PACKAGE_STRINGS, bytes[3]);
+        assert_eq!(header_size as u32, u32::from_le_bytes(bytes[4..8].try_into().unwrap()));
+        assert_eq!(header_size as u32, u32::from_le_bytes(bytes[8..12].try_into().unwrap()));
+        assert_eq!([0; 32], bytes[12..44]);
+        assert_eq!([1, 0], bytes[44..46]);
+        assert_eq!(b"en\0", &bytes[46..header_size]);
+        assert_eq!(SIBT_STRING_UCS2, bytes[header_size]);
+        assert_eq!(
+            [SIBT_STRING_UCS2, b'H', 0, b'i', 0, 0, 0, SIBT_END],
+            bytes[header_size + 1 + 2 * "English\0".len()..]
+        );
+    }
+
+    #[test]
+    fn test_get_and_set_strings() {
+        let (hii_string, test_strings) = hii_string();
+        let mut english = StringPackage::new("en-US", &s("English"));
+        let mut french = StringPackage::new("fr-FR", &s("Français"));
+        let title = english.add(&s("Boot Options"));
+        assert_eq!(title, french.add(&s("Options de démarrage")));
+        let mut package_list = PackageList::new(LIST_GUID);
+        package_list.add_package(&english.to_bytes());
+        package_list.add_package(&french.to_bytes());
+        let handle = test_strings.database.new_package_list(&package_list, None).unwrap();
+
+        assert_eq!(vec!["en-US", "fr-FR"], hii_string.languages(handle).unwrap());
+        assert_eq!(hii_string.get_string(handle, 1, "fr-FR").unwrap(), "Français");
+        assert_eq!(hii_string.get_string(handle, title, "en-US").unwrap(), "Boot Options");
+        assert_eq!(hii_string.get_string(handle, title, "fr-FR").unwrap(), "Options de démarrage");
+        assert_eq!(efi::Status::NOT_FOUND, hii_string.get_string(handle, 3, "en-US").unwrap_err());
+        assert_eq!(efi::Status::INVALID_LANGUAGE, hii_string.get_string(handle, title, "de-DE").unwrap_err());
+
+        hii_string.set_string(handle, title, "en-US", &s("Boot Menu")).unwrap();
+        assert_eq!(hii_string.get_string(handle, title, "en-US").unwrap(), "Boot Menu");
+        let help = hii_string.new_string(handle, "en-US", &s("Select the boot order")).unwrap();
+        assert_eq!(3, help);
+        assert_eq!(hii_string.get_string(handle, help, "en-US").unwrap(), "Select the boot order");
+        assert_eq!(efi::Status::NOT_FOUND, hii_string.set_string(handle, 9, "en-US", &s("")).unwrap_err());
+    }
+}
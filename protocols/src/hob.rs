@@ -0,0 +1,472 @@
+//! Hand-off blocks, the data handed off from PEI to DXE.
+//!
+//! [`HobList`] iterates over the HOBs of the list given to the DXE core, or installed in the configuration tables,
+//! for a DXE driver to consume the data produced in PEI:
+//!
+//! ```ignore
+//! let hobs = HobList::from_system_table(entry_point::system_table())?;
+//! let config = hobs.guid_hobs(&PLATFORM_CONFIG_GUID).next().ok_or(efi::Status::NOT_FOUND)?;
+//! ```
+//!
+//! [PI Spec Documentation: Volume 3, 5. HOB Code Definitions](https://uefi.org/specs/PI/1.8/V3_HOB_Code_Definitions.html)
+
+use core::{ffi::c_void, mem, ptr, slice};
+
+use boot_services::{allocation::MemoryType, configuration_table::ConfigurationTables};
+use r_efi::efi;
+use status::EfiError;
+
+/// GUID of the HOB list in the configuration tables, `gEfiHobListGuid`.
+pub const HOB_LIST_GUID: efi::Guid =
+    efi::Guid::from_fields(0x7739f24c, 0x93d7, 0x11d4, 0x9a, 0x3a, &[0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d]);
+
+/// Type of a HOB, `EFI_HOB_TYPE_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct HobType(pub u16);
+
+impl HobType {
+    pub const HANDOFF: HobType = HobType(0x0001);
+    pub const MEMORY_ALLOCATION: HobType = HobType(0x0002);
+    pub const RESOURCE_DESCRIPTOR: HobType = HobType(0x0003);
+    pub const GUID_EXTENSION: HobType = HobType(0x0004);
+    pub const FV: HobType = HobType(0x0005);
+    pub const CPU: HobType = HobType(0x0006);
+    pub const MEMORY_POOL: HobType = HobType(0x0007);
+    pub const FV2: HobType = HobType(0x0009);
+    pub const UEFI_CAPSULE: HobType = HobType(0x000B);
+    pub const FV3: HobType = HobType(0x000C);
+    pub const UNUSED: HobType = HobType(0xFFFE);
+    pub const END_OF_HOB_LIST: HobType = HobType(0xFFFF);
+}
+
+/// FFI definition of `EFI_HOB_GENERIC_HEADER`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenericHeader {
+    pub hob_type: HobType,
+    /// Size of the whole HOB, a multiple of 8 bytes.
+    pub hob_length: u16,
+    pub reserved: u32,
+}
+
+/// FFI definition of `EFI_HOB_HANDOFF_INFO_TABLE`, the first HOB of the list.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandoffInfoTable {
+    pub header: GenericHeader,
+    pub version: u32,
+    /// `EFI_BOOT_MODE` of the boot.
+    pub boot_mode: u32,
+    pub memory_top: efi::PhysicalAddress,
+    pub memory_bottom: efi::PhysicalAddress,
+    pub free_memory_top: efi::PhysicalAddress,
+    pub free_memory_bottom: efi::PhysicalAddress,
+    pub end_of_hob_list: efi::PhysicalAddress,
+}
+
+/// FFI definition of `EFI_HOB_MEMORY_ALLOCATION`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAllocation {
+    pub header: GenericHeader,
+    /// GUID of the allocation, such as the stack, or zero.
+    pub name: efi::Guid,
+    pub memory_base_address: efi::PhysicalAddress,
+    pub memory_length: u64,
+    pub memory_type: MemoryType,
+    pub reserved: [u8; 4],
+}
+
+/// Type of a resource, `EFI_RESOURCE_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct ResourceType(pub u32);
+
+impl ResourceType {
+    pub const SYSTEM_MEMORY: ResourceType = ResourceType(0x00000000);
+    pub const MEMORY_MAPPED_IO: ResourceType = ResourceType(0x00000001);
+    pub const IO: ResourceType = ResourceType(0x00000002);
+    pub const FIRMWARE_DEVICE: ResourceType = ResourceType(0x00000003);
+    pub const MEMORY_MAPPED_IO_PORT: ResourceType = ResourceType(0x00000004);
+    pub const MEMORY_RESERVED: ResourceType = ResourceType(0x00000005);
+    pub const IO_RESERVED: ResourceType = ResourceType(0x00000006);
+}
+
+/// Attributes of a resource, `EFI_RESOURCE_ATTRIBUTE_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct ResourceAttribute(pub u32);
+
+impl ResourceAttribute {
+    pub const PRESENT: ResourceAttribute = ResourceAttribute(0x00000001);
+    pub const INITIALIZED: ResourceAttribute = ResourceAttribute(0x00000002);
+    pub const TESTED: ResourceAttribute = ResourceAttribute(0x00000004);
+    pub const READ_PROTECTED: ResourceAttribute = ResourceAttribute(0x00000080);
+    pub const WRITE_PROTECTED: ResourceAttribute = ResourceAttribute(0x00000100);
+    pub const EXECUTION_PROTECTED: ResourceAttribute = ResourceAttribute(0x00000200);
+    pub const UNCACHEABLE: ResourceAttribute = ResourceAttribute(0x00000400);
+    pub const WRITE_COMBINEABLE: ResourceAttribute = ResourceAttribute(0x00000800);
+    pub const WRITE_THROUGH_CACHEABLE: ResourceAttribute = ResourceAttribute(0x00001000);
+    pub const WRITE_BACK_CACHEABLE: ResourceAttribute = ResourceAttribute(0x00002000);
+    pub const READ_ONLY_PROTECTED: ResourceAttribute = ResourceAttribute(0x00040000);
+    pub const PERSISTENT: ResourceAttribute = ResourceAttribute(0x00800000);
+
+    /// Returns true if all the attributes of `other` are set.
+    pub const fn contains(&self, other: ResourceAttribute) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// FFI definition of `EFI_HOB_RESOURCE_DESCRIPTOR`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceDescriptor {
+    pub header: GenericHeader,
+    pub owner: efi::Guid,
+    pub resource_type: ResourceType,
+    pub resource_attribute: ResourceAttribute,
+    pub physical_start: efi::PhysicalAddress,
+    pub resource_length: u64,
+}
+
+/// FFI definition of `EFI_HOB_GUID_TYPE`, followed by the data of the GUID extension HOB.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuidExtensionHeader {
+    pub header: GenericHeader,
+    pub name: efi::Guid,
+}
+
+/// FFI definition of `EFI_HOB_FIRMWARE_VOLUME`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareVolume {
+    pub header: GenericHeader,
+    pub base_address: efi::PhysicalAddress,
+    pub length: u64,
+}
+
+/// FFI definition of `EFI_HOB_CPU`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cpu {
+    pub header: GenericHeader,
+    /// Number of address bits of the memory space.
+    pub size_of_memory_space: u8,
+    /// Number of address bits of the I/O space.
+    pub size_of_io_space: u8,
+    pub reserved: [u8; 6],
+}
+
+/// A HOB of the list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hob<'a> {
+    Handoff(HandoffInfoTable),
+    MemoryAllocation(MemoryAllocation),
+    ResourceDescriptor(ResourceDescriptor),
+    /// A GUID extension HOB, its name and its data.
+    GuidExtension(efi::Guid, &'a [u8]),
+    FirmwareVolume(FirmwareVolume),
+    Cpu(Cpu),
+    /// A HOB of another type, its whole content including its header.
+    Other(HobType, &'a [u8]),
+}
+
+impl<'a> Hob<'a> {
+    /// Parses the HOB of `bytes`, its whole content.
+    fn parse(hob_type: HobType, bytes: &'a [u8]) -> Result<Self, EfiError> {
+        /// Copies the HOB, if it is large enough.
+        fn read<T>(bytes: &[u8]) -> Result<T, EfiError> {
+            if bytes.len() < mem::size_of::<T>() {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            //SAFETY: The bytes are large enough, the HOB structures are valid for any content.
+            Ok(unsafe { (bytes.as_ptr() as *const T).read_unaligned() })
+        }
+        Ok(match hob_type {
+            HobType::HANDOFF => Hob::Handoff(read(bytes)?),
+            HobType::MEMORY_ALLOCATION => Hob::MemoryAllocation(read(bytes)?),
+            HobType::RESOURCE_DESCRIPTOR => Hob::ResourceDescriptor(read(bytes)?),
+            HobType::GUID_EXTENSION => {
+                let header = read::<GuidExtensionHeader>(bytes)?;
+                Hob::GuidExtension(header.name, &bytes[mem::size_of::<GuidExtensionHeader>()..])
+            }
+            HobType::FV => Hob::FirmwareVolume(read(bytes)?),
+            HobType::CPU => Hob::Cpu(read(bytes)?),
+            _ => Hob::Other(hob_type, bytes),
+        })
+    }
+}
+
+/// A HOB list, up to and including its end of list HOB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HobList<'a>(&'a [u8]);
+
+impl HobList<'static> {
+    /// The HOB list installed in the configuration tables.
+    pub fn from_system_table(system_table: &efi::SystemTable) -> Result<Self, EfiError> {
+        let hob_list =
+            ConfigurationTables::new(system_table).find_table(&HOB_LIST_GUID).ok_or(efi::Status::NOT_FOUND)?;
+        //SAFETY: The HOB list installed by the DXE core is valid for the life of the firmware.
+        unsafe { Self::from_ptr(hob_list) }
+    }
+}
+
+impl<'a> HobList<'a> {
+    /// The HOB list of `bytes`, which must contain its end of list HOB.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, EfiError> {
+        let size = Self::list_size(bytes.len(), |offset| {
+            bytes.get(offset..offset + 4).map(|header| header.try_into().unwrap())
+        })?;
+        Ok(Self(&bytes[..size]))
+    }
+
+    /// The HOB list at `hob_list`, such as the one given to the DXE core.
+    ///
+    /// # Safety
+    ///
+    /// `hob_list` must point to a HOB list that ends with an end of list HOB and stays valid for `'a`.
+    pub unsafe fn from_ptr(hob_list: *const c_void) -> Result<Self, EfiError> {
+        if hob_list.is_null() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let hob_list = hob_list as *const u8;
+        let size =
+            Self::list_size(usize::MAX, |offset| Some(ptr::read_unaligned(hob_list.add(offset) as *const [u8; 4])))?;
+        Ok(Self(slice::from_raw_parts(hob_list, size)))
+    }
+
+    /// Size of the list up to and including its end of list HOB, from the type and length of the HOB at an offset.
+    ///
+    /// Returns `INVALID_PARAMETER` if a HOB ends beyond `len` bytes.
+    fn list_size(len: usize, header_at: impl Fn(usize) -> Option<[u8; 4]>) -> Result<usize, EfiError> {
+        let mut offset = 0;
+        loop {
+            let header = header_at(offset).ok_or(efi::Status::INVALID_PARAMETER)?;
+            let hob_type = HobType(u16::from_le_bytes([header[0], header[1]]));
+            let hob_length = u16::from_le_bytes([header[2], header[3]]) as usize;
+            if hob_length < mem::size_of::<GenericHeader>() {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            offset = offset.checked_add(hob_length).filter(|&end| end <= len).ok_or(efi::Status::INVALID_PARAMETER)?;
+            if hob_type == HobType::END_OF_HOB_LIST {
+                return Ok(offset);
+            }
+        }
+    }
+
+    /// The whole list, including its end of list HOB.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Iterates over the HOBs of the list, without the unused HOBs and the end of list HOB.
+    ///
+    /// The iteration stops after the first HOB too small for its type, which is an `INVALID_PARAMETER` error.
+    pub fn iter(&self) -> Hobs<'a> {
+        Hobs { data: self.0, done: false }
+    }
+
+    /// The Phase Handoff Information Table, the first HOB of the list.
+    pub fn handoff_info_table(&self) -> Option<HandoffInfoTable> {
+        match self.iter().next() {
+            Some(Ok(Hob::Handoff(handoff))) => Some(handoff),
+            _ => None,
+        }
+    }
+
+    /// The resource descriptors of the list.
+    pub fn resource_descriptors(&self) -> impl Iterator<Item = ResourceDescriptor> + 'a {
+        self.iter().map_while(Result::ok).filter_map(|hob| match hob {
+            Hob::ResourceDescriptor(resource) => Some(resource),
+            _ => None,
+        })
+    }
+
+    /// The memory allocations of the list.
+    pub fn memory_allocations(&self) -> impl Iterator<Item = MemoryAllocation> + 'a {
+        self.iter().map_while(Result::ok).filter_map(|hob| match hob {
+            Hob::MemoryAllocation(allocation) => Some(allocation),
+            _ => None,
+        })
+    }
+
+    /// The data of the GUID extension HOBs of `name`.
+    pub fn guid_hobs(&self, name: &efi::Guid) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let name = *name;
+        self.iter().map_while(Result::ok).filter_map(move |hob| match hob {
+            Hob::GuidExtension(guid, data) if guid == name => Some(data),
+            _ => None,
+        })
+    }
+
+    /// The data of the first GUID extension HOB of `name`, as a `T`, if the data is large enough.
+    ///
+    /// # Safety
+    ///
+    /// Any content of the size of `T` must be a valid `T`, as for plain data structures.
+    pub unsafe fn find_guid_hob<T: Copy>(&self, name: &efi::Guid) -> Option<T> {
+        let data = self.guid_hobs(name).next()?;
+        (data.len() >= mem::size_of::<T>()).then(|| (data.as_ptr() as *const T).read_unaligned())
+    }
+}
+
+impl<'a> IntoIterator for HobList<'a> {
+    type Item = Result<Hob<'a>, EfiError>;
+    type IntoIter = Hobs<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the HOBs of a list, returned by [`HobList::iter`].
+#[derive(Debug, Clone)]
+pub struct Hobs<'a> {
+    data: &'a [u8],
+    done: bool,
+}
+
+impl<'a> Iterator for Hobs<'a> {
+    type Item = Result<Hob<'a>, EfiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            // The list was validated up to its end of list HOB when created.
+            let hob_type = HobType(u16::from_le_bytes([self.data[0], self.data[1]]));
+            let hob_length = u16::from_le_bytes([self.data[2], self.data[3]]) as usize;
+            let (hob, rest) = self.data.split_at(hob_length);
+            self.data = rest;
+            match hob_type {
+                HobType::END_OF_HOB_LIST => self.done = true,
+                HobType::UNUSED => (),
+                _ => {
+                    let hob = Hob::parse(hob_type, hob);
+                    self.done = hob.is_err();
+                    return Some(hob);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    const CONFIG_GUID: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+
+    /// The bytes of a HOB structure, with its header set for `hob_type`.
+    fn hob<T>(hob_type: HobType, mut hob: T, extra: &[u8]) -> Vec<u8> {
+        let length = (mem::size_of::<T>() + extra.len()).next_multiple_of(8);
+        //SAFETY: The HOB structures start with their header.
+        unsafe {
+            *(&mut hob as *mut T as *mut GenericHeader) =
+                GenericHeader { hob_type, hob_length: length as u16, reserved: 0 };
+        }
+        let mut bytes = unsafe { slice::from_raw_parts(&hob as *const T as *const u8, mem::size_of::<T>()) }.to_vec();
+        bytes.extend_from_slice(extra);
+        bytes.resize(length, 0);
+        bytes
+    }
+
+    const HEADER: GenericHeader = GenericHeader { hob_type: HobType::UNUSED, hob_length: 0, reserved: 0 };
+
+    fn hob_list() -> Vec<u8> {
+        let handoff = HandoffInfoTable {
+            header: HEADER,
+            version: 0x9,
+            boot_mode: 0,
+            memory_top: 0x8000_0000,
+            memory_bottom: 0x7000_0000,
+            free_memory_top: 0x7800_0000,
+            free_memory_bottom: 0x7100_0000,
+            end_of_hob_list: 0x7100_0000,
+        };
+        let resource = ResourceDescriptor {
+            header: HEADER,
+            owner: efi::Guid::from_bytes(&[0; 16]),
+            resource_type: ResourceType::SYSTEM_MEMORY,
+            resource_attribute: ResourceAttribute(0x7),
+            physical_start: 0,
+            resource_length: 0x8000_0000,
+        };
+        let allocation = MemoryAllocation {
+            header: HEADER,
+            name: efi::Guid::from_bytes(&[0; 16]),
+            memory_base_address: 0x7F00_0000,
+            memory_length: 0x1000,
+            memory_type: MemoryType::BOOT_SERVICES_DATA,
+            reserved: [0; 4],
+        };
+        [
+            hob(HobType::HANDOFF, handoff, &[]),
+            hob(HobType::RESOURCE_DESCRIPTOR, resource, &[]),
+            hob(HobType::UNUSED, HEADER, &[]),
+            hob(HobType::MEMORY_ALLOCATION, allocation, &[]),
+            hob(
+                HobType::GUID_EXTENSION,
+                GuidExtensionHeader { header: HEADER, name: CONFIG_GUID },
+                &0x1234_5678_u32.to_le_bytes(),
+            ),
+            hob(
+                HobType::CPU,
+                Cpu { header: HEADER, size_of_memory_space: 48, size_of_io_space: 16, reserved: [0; 6] },
+                &[],
+            ),
+            hob(HobType::MEMORY_POOL, HEADER, &[1, 2]),
+            hob(HobType::END_OF_HOB_LIST, HEADER, &[]),
+            hob(HobType::CPU, HEADER, &[]),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_hob_list() {
+        let bytes = hob_list();
+        let hobs = HobList::from_bytes(&bytes).unwrap();
+        assert_eq!(bytes.len() - 8, hobs.as_bytes().len());
+        assert_eq!(6, hobs.iter().count());
+        assert_eq!(0x7100_0000, hobs.handoff_info_table().unwrap().end_of_hob_list);
+
+        let resources = hobs.resource_descriptors().collect::<Vec<_>>();
+        assert_eq!(1, resources.len());
+        assert!(resources[0].resource_attribute.contains(ResourceAttribute::TESTED));
+        let allocations = hobs.memory_allocations().map(|a| (a.memory_base_address, a.memory_type)).collect::<Vec<_>>();
+        assert_eq!(vec![(0x7F00_0000, MemoryType::BOOT_SERVICES_DATA)], allocations);
+        assert!(matches!(hobs.iter().nth(4), Some(Ok(Hob::Cpu(Cpu { size_of_memory_space: 48, .. })))));
+        assert!(matches!(hobs.iter().nth(5), Some(Ok(Hob::Other(HobType::MEMORY_POOL, data))) if data.len() == 16));
+
+        assert_eq!(1, hobs.guid_hobs(&CONFIG_GUID).count());
+        assert_eq!(Some(0x1234_5678_u32), unsafe { hobs.find_guid_hob::<u32>(&CONFIG_GUID) });
+        assert_eq!(None, unsafe { hobs.find_guid_hob::<[u32; 3]>(&CONFIG_GUID) });
+        assert_eq!(None, unsafe { hobs.find_guid_hob::<u32>(&HOB_LIST_GUID) });
+
+        let from_ptr = unsafe { HobList::from_ptr(bytes.as_ptr() as *const c_void) }.unwrap();
+        assert_eq!(hobs, from_ptr);
+    }
+
+    #[test]
+    fn test_invalid_hob_list() {
+        let bytes = hob_list();
+        // Without its end of list HOB.
+        assert_eq!(efi::Status::INVALID_PARAMETER, HobList::from_bytes(&bytes[..bytes.len() - 16]).unwrap_err());
+        // A HOB shorter than its header.
+        let mut short = bytes.clone();
+        short[2] = 4;
+        assert_eq!(efi::Status::INVALID_PARAMETER, HobList::from_bytes(&short).unwrap_err());
+        // A HOB longer than the bytes.
+        assert_eq!(efi::Status::INVALID_PARAMETER, HobList::from_bytes(&[0xFF, 0xFF, 8, 0]).unwrap_err());
+        let mut long = bytes.clone();
+        long[2] = 0xF8;
+        assert_eq!(efi::Status::INVALID_PARAMETER, HobList::from_bytes(&long).unwrap_err());
+        // A HOB too small for its type ends the iteration.
+        let truncated =
+            [hob(HobType::RESOURCE_DESCRIPTOR, HEADER, &[]), hob(HobType::END_OF_HOB_LIST, HEADER, &[])].concat();
+        let hobs = HobList::from_bytes(&truncated).unwrap().into_iter().collect::<Vec<_>>();
+        assert_eq!(vec![Err(efi::Status::INVALID_PARAMETER.into())], hobs);
+    }
+}
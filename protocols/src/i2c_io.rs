@@ -0,0 +1,522 @@
+//! I2C IO protocol.
+//!
+//! [`I2cIo`] executes an [`I2cTransaction`], a sequence of writes and reads separated by repeated starts, on an I2C
+//! device:
+//!
+//! ```ignore
+//! let mut eeprom = I2cIo::get(&boot_services, handle)?.with_timeout(Duration::from_millis(100));
+//! let mut serial_number = [0; 16];
+//! eeprom.write_read(&boot_services, 0, &[0x80], &mut serial_number)?;
+//! ```
+//!
+//! Without a timeout the transactions are synchronous. With a timeout, a transaction that does not complete in time
+//! fails with `TIMEOUT` and the buffers given to the controller are leaked, since it may still use them.
+//!
+//! [PI Spec Documentation: Volume 5, 13.4. I2C IO Protocol](https://uefi.org/specs/PI/1.8/V5_I2C_Protocol_Stack.html#efi-i2c-io-protocol)
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{fmt, mem, ops::Deref, ptr, time::Duration};
+
+use boot_services::{
+    event::{EventTimerType, EventType},
+    protocol_handler::Protocol as ProtocolTrait,
+    tpl::Tpl,
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xb60a3e6b, 0x18c4, 0x46e5, 0xa2, 0x9a, &[0xc9, 0xa1, 0x06, 0x65, 0xa2, 0x8e]);
+
+pub const FLAG_READ: u32 = 0x0000_0001;
+pub const FLAG_SMBUS_OPERATION: u32 = 0x0001_0000;
+pub const FLAG_SMBUS_BLOCK: u32 = 0x0002_0000;
+pub const FLAG_SMBUS_PROCESS_CALL: u32 = 0x0004_0000;
+pub const FLAG_SMBUS_PEC: u32 = 0x0008_0000;
+
+pub type ProtocolQueueRequest =
+    extern "efiapi" fn(*mut Protocol, usize, efi::Event, *mut RequestPacket, *mut efi::Status) -> efi::Status;
+
+/// FFI definition of `EFI_I2C_OPERATION`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Operation {
+    pub flags: u32,
+    pub length_in_bytes: u32,
+    pub buffer: *mut u8,
+}
+
+/// FFI definition of `EFI_I2C_REQUEST_PACKET`, whose operations follow each other from `operation`.
+#[repr(C)]
+pub struct RequestPacket {
+    pub operation_count: usize,
+    pub operation: [Operation; 1],
+}
+
+/// FFI definition of `EFI_I2C_CONTROLLER_CAPABILITIES`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ControllerCapabilities {
+    pub structure_size_in_bytes: u32,
+    pub maximum_receive_bytes: u32,
+    pub maximum_transmit_bytes: u32,
+    pub maximum_total_bytes: u32,
+}
+
+/// FFI definition of `EFI_I2C_IO_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub queue_request: ProtocolQueueRequest,
+    pub device_guid: *const efi::Guid,
+    pub device_index: u32,
+    pub hardware_revision: u32,
+    pub i2c_controller_capabilities: *const ControllerCapabilities,
+}
+
+/// I2C IO protocol.
+pub struct I2cIoProtocol;
+
+unsafe impl ProtocolTrait for I2cIoProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for I2cIoProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// An operation of an [`I2cTransaction`].
+#[derive(Debug)]
+pub enum I2cOperation<'a> {
+    Write(&'a [u8]),
+    Read(&'a mut [u8]),
+}
+
+/// Builder of the operations of an I2C transaction.
+#[derive(Debug, Default)]
+pub struct I2cTransaction<'a> {
+    operations: Vec<I2cOperation<'a>>,
+}
+
+impl<'a> I2cTransaction<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes a register address followed by reading its value.
+    pub fn write_read(data: &'a [u8], buffer: &'a mut [u8]) -> Self {
+        Self::new().write(data).read(buffer)
+    }
+
+    pub fn write(mut self, data: &'a [u8]) -> Self {
+        self.operations.push(I2cOperation::Write(data));
+        self
+    }
+
+    pub fn read(mut self, buffer: &'a mut [u8]) -> Self {
+        self.operations.push(I2cOperation::Read(buffer));
+        self
+    }
+
+    pub fn operations(&self) -> &[I2cOperation<'a>] {
+        &self.operations
+    }
+}
+
+/// A request packet and the buffers of its operations, which stay valid while the controller executes it.
+struct Request {
+    /// `EFI_I2C_REQUEST_PACKET`, in words to be aligned for the operation count.
+    packet: Vec<usize>,
+    buffers: Vec<Vec<u8>>,
+    status: Box<efi::Status>,
+}
+
+impl Request {
+    fn new(transaction: &I2cTransaction) -> Result<Self, EfiError> {
+        let mut buffers: Vec<Vec<u8>> = transaction
+            .operations
+            .iter()
+            .map(|operation| match operation {
+                I2cOperation::Write(data) => data.to_vec(),
+                I2cOperation::Read(buffer) => vec![0; buffer.len()],
+            })
+            .collect();
+        let mut operations = Vec::new();
+        for (operation, buffer) in transaction.operations.iter().zip(&mut buffers) {
+            operations.push(Operation {
+                flags: if matches!(operation, I2cOperation::Read(_)) { FLAG_READ } else { 0 },
+                length_in_bytes: u32::try_from(buffer.len()).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?,
+                buffer: buffer.as_mut_ptr(),
+            });
+        }
+        let operation_words = mem::size_of::<Operation>().div_ceil(mem::size_of::<usize>());
+        let mut packet = vec![0; 1 + operations.len() * operation_words];
+        packet[0] = operations.len();
+        //SAFETY: The packet has room for the operations after the operation count, which is aligned as them.
+        unsafe {
+            ptr::copy_nonoverlapping(
+                operations.as_ptr(),
+                packet.as_mut_ptr().add(1) as *mut Operation,
+                operations.len(),
+            )
+        };
+        Ok(Self { packet, buffers, status: Box::new(efi::Status::NOT_READY) })
+    }
+
+    fn packet(&mut self) -> *mut RequestPacket {
+        self.packet.as_mut_ptr() as *mut RequestPacket
+    }
+
+    /// Copies the data read by the controller to the buffers of the transaction.
+    fn complete(self, transaction: &mut I2cTransaction) {
+        for (operation, buffer) in transaction.operations.iter_mut().zip(&self.buffers) {
+            if let I2cOperation::Read(data) = operation {
+                data.copy_from_slice(buffer);
+            }
+        }
+    }
+}
+
+/// Completion event of a request and timer of its timeout.
+struct TimeoutEvents<'a, B: BootServices> {
+    boot_services: &'a B,
+    completion: efi::Event,
+    timer: efi::Event,
+    /// Whether the controller still owns the completion event, which must not be closed.
+    pending: bool,
+}
+
+impl<'a, B: BootServices> TimeoutEvents<'a, B> {
+    fn new(boot_services: &'a B, timeout: Duration) -> Result<Self, EfiError> {
+        let completion = boot_services.create_event(EventType::NONE, Tpl::CALLBACK, None, None::<&'static ()>)?;
+        let timer = match boot_services.create_event(EventType::TIMER, Tpl::CALLBACK, None, None::<&'static ()>) {
+            Ok(timer) => timer,
+            Err(status) => {
+                let _ = boot_services.close_event(completion);
+                return Err(status);
+            }
+        };
+        let events = Self { boot_services, completion, timer, pending: false };
+        // The timer is set in units of 100 nanoseconds.
+        let trigger_time = u64::try_from(timeout.as_nanos() / 100).unwrap_or(u64::MAX);
+        boot_services.set_timer(timer, EventTimerType::Relative, trigger_time)?;
+        Ok(events)
+    }
+
+    /// Waits for the completion of the request, returns false if the timeout elapsed first.
+    fn wait(&mut self) -> Result<bool, EfiError> {
+        let completed = self.boot_services.wait_for_event(&mut [self.completion, self.timer])? == 0;
+        self.pending = !completed;
+        Ok(completed)
+    }
+}
+
+impl<B: BootServices> Drop for TimeoutEvents<'_, B> {
+    fn drop(&mut self) {
+        if !self.pending {
+            let _ = self.boot_services.close_event(self.completion);
+        }
+        let _ = self.boot_services.close_event(self.timer);
+    }
+}
+
+/// Typed access to an instance of the I2C IO protocol.
+pub struct I2cIo {
+    protocol: &'static mut Protocol,
+    timeout: Option<Duration>,
+}
+
+impl I2cIo {
+    /// Gets the instance of the protocol installed on the handle of an I2C device.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &I2cIoProtocol).map(Self::from)
+    }
+
+    /// Sets the time after which a transaction fails with `TIMEOUT`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.protocol as *mut Protocol
+    }
+
+    /// GUID identifying the type of the device, given by the board.
+    pub fn device_guid(&self) -> efi::Guid {
+        //SAFETY: The GUID of a valid protocol instance is valid.
+        unsafe { *self.protocol.device_guid }
+    }
+
+    /// Index of the device among the devices of the same type.
+    pub fn device_index(&self) -> u32 {
+        self.protocol.device_index
+    }
+
+    pub fn hardware_revision(&self) -> u32 {
+        self.protocol.hardware_revision
+    }
+
+    pub fn capabilities(&self) -> ControllerCapabilities {
+        //SAFETY: The capabilities of a valid protocol instance are valid.
+        unsafe { *self.protocol.i2c_controller_capabilities }
+    }
+
+    /// Executes a transaction with the device at an index of its slave addresses, 0 for most devices.
+    pub fn execute<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        slave_address_index: usize,
+        transaction: &mut I2cTransaction,
+    ) -> Result<(), EfiError> {
+        let mut request = Request::new(transaction)?;
+        let Some(timeout) = self.timeout else {
+            return match (self.protocol.queue_request)(
+                self.this(),
+                slave_address_index,
+                ptr::null_mut(),
+                request.packet(),
+                ptr::null_mut(),
+            ) {
+                s if s.is_error() => Err(s.into()),
+                _ => {
+                    request.complete(transaction);
+                    Ok(())
+                }
+            };
+        };
+        let mut events = TimeoutEvents::new(boot_services, timeout)?;
+        let status = (self.protocol.queue_request)(
+            self.this(),
+            slave_address_index,
+            events.completion,
+            request.packet(),
+            &mut *request.status,
+        );
+        if status.is_error() {
+            return Err(status.into());
+        }
+        if !events.wait()? {
+            // The controller may still write to the request.
+            mem::forget(request);
+            return Err(efi::Status::TIMEOUT.into());
+        }
+        match *request.status {
+            s if s.is_error() => Err(s.into()),
+            _ => {
+                request.complete(transaction);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn write<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        slave_address_index: usize,
+        data: &[u8],
+    ) -> Result<(), EfiError> {
+        self.execute(boot_services, slave_address_index, &mut I2cTransaction::new().write(data))
+    }
+
+    pub fn read<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        slave_address_index: usize,
+        buffer: &mut [u8],
+    ) -> Result<(), EfiError> {
+        self.execute(boot_services, slave_address_index, &mut I2cTransaction::new().read(buffer))
+    }
+
+    /// Writes `data`, usually a register address, then reads `buffer` after a repeated start.
+    pub fn write_read<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        slave_address_index: usize,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), EfiError> {
+        self.execute(boot_services, slave_address_index, &mut I2cTransaction::write_read(data, buffer))
+    }
+}
+
+impl From<&'static mut Protocol> for I2cIo {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self { protocol, timeout: None }
+    }
+}
+
+impl fmt::Debug for I2cIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("I2cIo")
+            .field("device_index", &self.protocol.device_index)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, slice};
+
+    const DEVICE_GUID: efi::Guid =
+        efi::Guid::from_fields(0x12345678, 0x9abc, 0xdef0, 0x12, 0x34, &[0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0]);
+    const CAPABILITIES: ControllerCapabilities = ControllerCapabilities {
+        structure_size_in_bytes: 16,
+        maximum_receive_bytes: 32,
+        maximum_transmit_bytes: 32,
+        maximum_total_bytes: 64,
+    };
+
+    /// EEPROM of 256 bytes, whose address is set by the first byte written. It executes the requests at once, or
+    /// keeps them pending while `pending` is set.
+    #[repr(C)]
+    struct TestEeprom {
+        protocol: Protocol,
+        memory: RefCell<[u8; 256]>,
+        address: RefCell<usize>,
+        pending: bool,
+    }
+
+    fn test_eeprom<'a>(this: *mut Protocol) -> &'a TestEeprom {
+        unsafe { &*(this as *const TestEeprom) }
+    }
+
+    extern "efiapi" fn queue_request(
+        this: *mut Protocol,
+        slave_address_index: usize,
+        event: efi::Event,
+        packet: *mut RequestPacket,
+        status: *mut efi::Status,
+    ) -> efi::Status {
+        let eeprom = test_eeprom(this);
+        if slave_address_index != 0 {
+            return efi::Status::NO_RESPONSE;
+        }
+        if eeprom.pending {
+            return efi::Status::SUCCESS;
+        }
+        let packet = unsafe { &*packet };
+        let operations = unsafe { slice::from_raw_parts(packet.operation.as_ptr(), packet.operation_count) };
+        for operation in operations {
+            let buffer = unsafe { slice::from_raw_parts_mut(operation.buffer, operation.length_in_bytes as usize) };
+            let mut memory = eeprom.memory.borrow_mut();
+            let mut address = eeprom.address.borrow_mut();
+            if operation.flags & FLAG_READ != 0 {
+                for byte in buffer {
+                    *byte = memory[*address];
+                    *address = (*address + 1) % memory.len();
+                }
+            } else if let Some((first, data)) = buffer.split_first() {
+                *address = *first as usize;
+                for byte in data {
+                    memory[*address] = *byte;
+                    *address = (*address + 1) % memory.len();
+                }
+            }
+        }
+        if !event.is_null() {
+            unsafe { status.write(efi::Status::SUCCESS) };
+        }
+        efi::Status::SUCCESS
+    }
+
+    fn i2c_io(pending: bool) -> (I2cIo, &'static TestEeprom) {
+        let eeprom = Box::leak(Box::new(TestEeprom {
+            protocol: Protocol {
+                queue_request,
+                device_guid: &DEVICE_GUID,
+                device_index: 1,
+                hardware_revision: 2,
+                i2c_controller_capabilities: &CAPABILITIES,
+            },
+            memory: RefCell::new([0; 256]),
+            address: RefCell::new(0),
+            pending,
+        }));
+        let test_eeprom = test_eeprom(&mut eeprom.protocol);
+        (I2cIo::from(&mut eeprom.protocol), test_eeprom)
+    }
+
+    fn boot_services(completed: bool) -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_create_event::<Option<&'static ()>>().returning(|event_type, _, _, _| {
+            Ok(match event_type == EventType::TIMER {
+                true => 0x200 as efi::Event,
+                false => 0x100 as efi::Event,
+            })
+        });
+        boot_services
+            .expect_set_timer()
+            .withf(|event, timer_type, trigger_time| {
+                *event == 0x200 as efi::Event
+                    && matches!(timer_type, EventTimerType::Relative)
+                    && *trigger_time == 1_000_000
+            })
+            .returning(|_, _, _| Ok(()));
+        boot_services.expect_wait_for_event().once().returning(move |events| {
+            assert_eq!([0x100 as efi::Event, 0x200 as efi::Event].as_slice(), events);
+            Ok(if completed { 0 } else { 1 })
+        });
+        boot_services
+    }
+
+    #[test]
+    fn test_execute() {
+        let (mut i2c, eeprom) = i2c_io(false);
+        assert_eq!(DEVICE_GUID, i2c.device_guid());
+        assert_eq!((1, 2), (i2c.device_index(), i2c.hardware_revision()));
+        assert_eq!(64, i2c.capabilities().maximum_total_bytes);
+
+        let boot_services = MockBootServices::new();
+        i2c.write(&boot_services, 0, &[0x10, 1, 2, 3]).unwrap();
+        assert_eq!([1, 2, 3], eeprom.memory.borrow()[0x10..0x13]);
+
+        let mut buffer = [0; 2];
+        i2c.write_read(&boot_services, 0, &[0x11], &mut buffer).unwrap();
+        assert_eq!([2, 3], buffer);
+        i2c.read(&boot_services, 0, &mut buffer).unwrap();
+        assert_eq!([0, 0], buffer);
+
+        let mut transaction = I2cTransaction::new().write(&[0x12]).read(&mut buffer);
+        assert_eq!(2, transaction.operations().len());
+        assert_eq!(efi::Status::NO_RESPONSE, i2c.execute(&boot_services, 1, &mut transaction).unwrap_err());
+    }
+
+    #[test]
+    fn test_execute_with_timeout() {
+        let (i2c, eeprom) = i2c_io(false);
+        let mut i2c = i2c.with_timeout(Duration::from_millis(100));
+        eeprom.memory.borrow_mut()[0x20] = 0xaa;
+
+        let mut boot_services = boot_services(true);
+        boot_services.expect_close_event().times(2).returning(|_| Ok(()));
+        let mut buffer = [0; 1];
+        i2c.write_read(&boot_services, 0, &[0x20], &mut buffer).unwrap();
+        assert_eq!([0xaa], buffer);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (i2c, _) = i2c_io(true);
+        let mut i2c = i2c.with_timeout(Duration::from_millis(100));
+        let mut boot_services = boot_services(false);
+        // The completion event stays open for the controller.
+        boot_services.expect_close_event().withf(|event| *event == 0x200 as efi::Event).once().returning(|_| Ok(()));
+        let mut buffer = [0; 1];
+        assert_eq!(efi::Status::TIMEOUT, i2c.write_read(&boot_services, 0, &[0x20], &mut buffer).unwrap_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! IP4 Config2 and IP6 Config protocols.
+//!
+//! [`Ip4Config2`] and [`Ip6Config`] read and set the IP configuration of a network interface: its policy, manual
+//! addresses, gateways and DNS servers. The settings that the protocols apply asynchronously are waited for, as is
+//! the address of an automatic configuration:
+//!
+//! ```ignore
+//! let config = Ip4Config2::get(&boot_services, nic_handle)?;
+//! config.set_policy(&boot_services, Policy::DHCP)?;
+//! let address = config.wait_for_address(&boot_services, Duration::from_secs(10))?;
+//! let dns_servers = config.dns_servers()?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 28.5. EFI IPv4 Configuration II Protocol](https://uefi.org/specs/UEFI/2.10/28_Network_Protocols_TCP_IP_and_Configuration.html#efi-ipv4-configuration-ii-protocol)
+//!
+//! [UEFI Spec Documentation: 28.7. EFI IPv6 Configuration Protocol](https://uefi.org/specs/UEFI/2.10/28_Network_Protocols_TCP_IP_and_Configuration.html#efi-ipv6-configuration-protocol)
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, mem, ptr, time::Duration};
+
+use boot_services::{
+    event::{EventTimerType, EventType},
+    tpl::Tpl,
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+pub mod ip4_config2;
+pub mod ip6_config;
+
+pub use ip4_config2::Ip4Config2;
+pub use ip6_config::Ip6Config;
+
+/// Reads data of variable size with the `GetData` function of a protocol, as an array of `T`.
+///
+/// `T` gives the alignment of the buffer, the data can be a header followed by other data.
+fn get_data<T: Copy>(mut get_data: impl FnMut(*mut usize, *mut c_void) -> efi::Status) -> Result<Vec<T>, EfiError> {
+    let mut data = Vec::<T>::new();
+    loop {
+        let mut size = data.capacity() * mem::size_of::<T>();
+        let buffer = match data.capacity() {
+            0 => ptr::null_mut(),
+            _ => data.as_mut_ptr() as *mut c_void,
+        };
+        match get_data(&mut size, buffer) {
+            efi::Status::BUFFER_TOO_SMALL => data.reserve_exact(size.div_ceil(mem::size_of::<T>())),
+            s if s.is_error() => return Err(s.into()),
+            _ => {
+                //SAFETY: The protocol wrote `size` bytes of data, which fit in the capacity of the buffer.
+                unsafe { data.set_len(size / mem::size_of::<T>()) };
+                return Ok(data);
+            }
+        }
+    }
+}
+
+/// Sets data with the `SetData` function of a protocol, from an array of `T`. An empty array clears the data.
+fn set_data<T, B: BootServices>(
+    boot_services: &B,
+    notify: &impl Fn(efi::Event, bool) -> efi::Status,
+    set_data: impl FnOnce(usize, *mut c_void) -> efi::Status,
+    data: &[T],
+) -> Result<(), EfiError> {
+    let buffer = match data.is_empty() {
+        true => ptr::null_mut(),
+        false => data.as_ptr() as *mut c_void,
+    };
+    let notify = DataNotify::register(boot_services, notify, None)?;
+    match set_data(mem::size_of_val(data), buffer) {
+        // The protocol signals the event once it applied the data.
+        efi::Status::NOT_READY => notify.wait(),
+        s if s.is_error() => Err(s.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Event registered with `RegisterDataNotify` for a data type, signaled when the data changes.
+struct DataNotify<'a, B: BootServices, N: Fn(efi::Event, bool) -> efi::Status> {
+    boot_services: &'a B,
+    notify: &'a N,
+    event: efi::Event,
+    registered: bool,
+    timer: Option<efi::Event>,
+}
+
+impl<'a, B: BootServices, N: Fn(efi::Event, bool) -> efi::Status> DataNotify<'a, B, N> {
+    /// Registers an event with `notify(event, true)`, which is unregistered with `notify(event, false)`.
+    ///
+    /// With a timeout, [`DataNotify::wait`] fails with `TIMEOUT` once it has elapsed.
+    fn register(boot_services: &'a B, notify: &'a N, timeout: Option<Duration>) -> Result<Self, EfiError> {
+        let event = boot_services.create_event(EventType::NONE, Tpl::CALLBACK, None, None::<&'static ()>)?;
+        let mut this = Self { boot_services, notify, event, registered: false, timer: None };
+        if let Some(timeout) = timeout {
+            let timer = boot_services.create_event(EventType::TIMER, Tpl::CALLBACK, None, None::<&'static ()>)?;
+            this.timer = Some(timer);
+            // The timer is set in units of 100 nanoseconds.
+            let trigger_time = u64::try_from(timeout.as_nanos() / 100).unwrap_or(u64::MAX);
+            boot_services.set_timer(timer, EventTimerType::Relative, trigger_time)?;
+        }
+        match notify(event, true) {
+            s if s.is_error() => Err(s.into()),
+            _ => {
+                this.registered = true;
+                Ok(this)
+            }
+        }
+    }
+
+    /// Waits for the data to change.
+    fn wait(&self) -> Result<(), EfiError> {
+        let mut events = [self.event, self.timer.unwrap_or(self.event)];
+        match self.boot_services.wait_for_event(&mut events[..1 + self.timer.is_some() as usize])? {
+            0 => Ok(()),
+            _ => Err(efi::Status::TIMEOUT.into()),
+        }
+    }
+}
+
+impl<B: BootServices, N: Fn(efi::Event, bool) -> efi::Status> Drop for DataNotify<'_, B, N> {
+    fn drop(&mut self) {
+        if self.registered {
+            let _ = (self.notify)(self.event, false);
+        }
+        let _ = self.boot_services.close_event(self.event);
+        if let Some(timer) = self.timer {
+            let _ = self.boot_services.close_event(timer);
+        }
+    }
+}
@@ -0,0 +1,570 @@
+//! IP4 Config2 protocol.
+//!
+//! [UEFI Spec Documentation: 28.5. EFI IPv4 Configuration II Protocol](https://uefi.org/specs/UEFI/2.10/28_Network_Protocols_TCP_IP_and_Configuration.html#efi-ipv4-configuration-ii-protocol)
+
+use alloc::{string::String, vec::Vec};
+use core::{char, ffi::c_void, fmt, mem, net::Ipv4Addr, ops::Deref, slice, time::Duration};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::DataNotify;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x5b446ed1, 0xe30b, 0x4faa, 0x87, 0x1a, &[0x36, 0x54, 0xec, 0xa3, 0x60, 0x80]);
+
+pub const INTERFACE_INFO_NAME_SIZE: usize = 32;
+
+pub type ProtocolSetData = extern "efiapi" fn(*mut Protocol, DataType, usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolGetData = extern "efiapi" fn(*mut Protocol, DataType, *mut usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolRegisterDataNotify = extern "efiapi" fn(*mut Protocol, DataType, efi::Event) -> efi::Status;
+
+pub type ProtocolUnregisterDataNotify = extern "efiapi" fn(*mut Protocol, DataType, efi::Event) -> efi::Status;
+
+/// FFI definition of `EFI_IP4_CONFIG2_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub set_data: ProtocolSetData,
+    pub get_data: ProtocolGetData,
+    pub register_data_notify: ProtocolRegisterDataNotify,
+    pub unregister_data_notify: ProtocolUnregisterDataNotify,
+}
+
+/// Type of configuration data, `EFI_IP4_CONFIG2_DATA_TYPE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct DataType(pub u32);
+
+impl DataType {
+    pub const INTERFACE_INFO: DataType = DataType(0);
+    pub const POLICY: DataType = DataType(1);
+    pub const MANUAL_ADDRESS: DataType = DataType(2);
+    pub const GATEWAY: DataType = DataType(3);
+    pub const DNS_SERVER: DataType = DataType(4);
+}
+
+/// FFI definition of `EFI_IP4_CONFIG2_INTERFACE_INFO`, followed by its route table in the data.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct InterfaceInfo {
+    pub name: [efi::Char16; INTERFACE_INFO_NAME_SIZE],
+    pub if_type: u8,
+    pub hw_address_size: u32,
+    pub hw_address: efi::MacAddress,
+    pub station_address: efi::Ipv4Address,
+    pub subnet_mask: efi::Ipv4Address,
+    pub route_table_size: u32,
+    pub route_table: *mut efi::protocols::ip4::RouteTable,
+}
+
+/// Policy of the configuration, `EFI_IP4_CONFIG2_POLICY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Policy(pub u32);
+
+impl Policy {
+    /// The configuration is set manually.
+    pub const STATIC: Policy = Policy(0);
+    /// The configuration is obtained with DHCP.
+    pub const DHCP: Policy = Policy(1);
+}
+
+/// FFI definition of `EFI_IP4_CONFIG2_MANUAL_ADDRESS`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManualAddress {
+    pub address: efi::Ipv4Address,
+    pub subnet_mask: efi::Ipv4Address,
+}
+
+/// IP4 Config2 protocol.
+pub struct Ip4Config2Protocol;
+
+unsafe impl ProtocolTrait for Ip4Config2Protocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for Ip4Config2Protocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// A route of an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub subnet: Ipv4Addr,
+    pub subnet_mask: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+}
+
+/// Information about an interface and its current configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    /// The type of the hardware, from the ARP hardware types.
+    pub if_type: u8,
+    pub hardware_address: Vec<u8>,
+    /// The address of the interface, unspecified until it is configured.
+    pub station_address: Ipv4Addr,
+    pub subnet_mask: Ipv4Addr,
+    pub routes: Vec<Route>,
+}
+
+/// Wrapper around the IP4 Config2 protocol.
+pub struct Ip4Config2(&'static mut Protocol);
+
+impl Ip4Config2 {
+    /// Gets the instance of the IP4 Config2 protocol installed on a network interface.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &Ip4Config2Protocol).map(Self)
+    }
+
+    fn protocol_ptr(&self) -> *mut Protocol {
+        self.0 as *const Protocol as *mut Protocol
+    }
+
+    fn get_data<T: Copy>(&self, data_type: DataType) -> Result<Vec<T>, EfiError> {
+        super::get_data(|size, data| (self.0.get_data)(self.protocol_ptr(), data_type, size, data))
+    }
+
+    /// Gets data made of a single value.
+    fn get_value<T: Copy>(&self, data_type: DataType) -> Result<T, EfiError> {
+        self.get_data::<T>(data_type)?.first().copied().ok_or(efi::Status::BAD_BUFFER_SIZE.into())
+    }
+
+    fn set_data<T, B: BootServices>(&self, boot_services: &B, data_type: DataType, data: &[T]) -> Result<(), EfiError> {
+        let notify = |event, register| self.notify(data_type, event, register);
+        super::set_data(
+            boot_services,
+            &notify,
+            |size, data| (self.0.set_data)(self.protocol_ptr(), data_type, size, data),
+            data,
+        )
+    }
+
+    fn notify(&self, data_type: DataType, event: efi::Event, register: bool) -> efi::Status {
+        match register {
+            true => (self.0.register_data_notify)(self.protocol_ptr(), data_type, event),
+            false => (self.0.unregister_data_notify)(self.protocol_ptr(), data_type, event),
+        }
+    }
+
+    /// Information about the interface and its current configuration.
+    pub fn interface(&self) -> Result<Interface, EfiError> {
+        // The buffer is aligned for the pointer of the information.
+        let data = self.get_data::<u64>(DataType::INTERFACE_INFO)?;
+        if mem::size_of_val(data.as_slice()) < mem::size_of::<InterfaceInfo>() {
+            return Err(efi::Status::BAD_BUFFER_SIZE.into());
+        }
+        //SAFETY: The buffer holds an aligned interface information.
+        let info = unsafe { &*(data.as_ptr() as *const InterfaceInfo) };
+        let routes = match info.route_table.is_null() {
+            true => &[][..],
+            //SAFETY: The route table follows the information in the buffer.
+            false => unsafe { slice::from_raw_parts(info.route_table, info.route_table_size as usize) },
+        };
+        let name_length = info.name.iter().position(|c| *c == 0).unwrap_or(INTERFACE_INFO_NAME_SIZE);
+        Ok(Interface {
+            name: char::decode_utf16(info.name[..name_length].iter().copied())
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+            if_type: info.if_type,
+            hardware_address: info.hw_address.addr[..(info.hw_address_size as usize).min(32)].to_vec(),
+            station_address: Ipv4Addr::from(info.station_address.addr),
+            subnet_mask: Ipv4Addr::from(info.subnet_mask.addr),
+            routes: routes
+                .iter()
+                .map(|route| Route {
+                    subnet: Ipv4Addr::from(route.subnet_address.addr),
+                    subnet_mask: Ipv4Addr::from(route.subnet_mask.addr),
+                    gateway: Ipv4Addr::from(route.gateway_address.addr),
+                })
+                .collect(),
+        })
+    }
+
+    pub fn policy(&self) -> Result<Policy, EfiError> {
+        self.get_value(DataType::POLICY)
+    }
+
+    /// Sets the policy, changing it clears the manual address, gateways and DNS servers.
+    pub fn set_policy<B: BootServices>(&self, boot_services: &B, policy: Policy) -> Result<(), EfiError> {
+        self.set_data(boot_services, DataType::POLICY, &[policy])
+    }
+
+    /// The manual address and its subnet mask, `None` if it is not set.
+    pub fn manual_address(&self) -> Result<Option<(Ipv4Addr, Ipv4Addr)>, EfiError> {
+        match self.get_value::<ManualAddress>(DataType::MANUAL_ADDRESS) {
+            Ok(manual) => Ok(Some((Ipv4Addr::from(manual.address.addr), Ipv4Addr::from(manual.subnet_mask.addr)))),
+            Err(status) if status == efi::Status::NOT_FOUND => Ok(None),
+            Err(status) => Err(status),
+        }
+    }
+
+    /// Sets the manual address of the static policy, waiting for the protocol to apply it.
+    pub fn set_manual_address<B: BootServices>(
+        &self,
+        boot_services: &B,
+        address: Ipv4Addr,
+        subnet_mask: Ipv4Addr,
+    ) -> Result<(), EfiError> {
+        let manual = ManualAddress {
+            address: efi::Ipv4Address { addr: address.octets() },
+            subnet_mask: efi::Ipv4Address { addr: subnet_mask.octets() },
+        };
+        self.set_data(boot_services, DataType::MANUAL_ADDRESS, &[manual])
+    }
+
+    /// Clears the manual address.
+    pub fn clear_manual_address<B: BootServices>(&self, boot_services: &B) -> Result<(), EfiError> {
+        self.set_data::<ManualAddress, B>(boot_services, DataType::MANUAL_ADDRESS, &[])
+    }
+
+    fn get_addresses(&self, data_type: DataType) -> Result<Vec<Ipv4Addr>, EfiError> {
+        match self.get_data::<efi::Ipv4Address>(data_type) {
+            Ok(addresses) => Ok(addresses.iter().map(|address| Ipv4Addr::from(address.addr)).collect()),
+            Err(status) if status == efi::Status::NOT_FOUND => Ok(Vec::new()),
+            Err(status) => Err(status),
+        }
+    }
+
+    fn set_addresses<B: BootServices>(
+        &self,
+        boot_services: &B,
+        data_type: DataType,
+        addresses: &[Ipv4Addr],
+    ) -> Result<(), EfiError> {
+        let addresses = addresses.iter().map(|address| efi::Ipv4Address { addr: address.octets() }).collect::<Vec<_>>();
+        self.set_data(boot_services, data_type, &addresses)
+    }
+
+    /// The default gateways of the static policy.
+    pub fn gateways(&self) -> Result<Vec<Ipv4Addr>, EfiError> {
+        self.get_addresses(DataType::GATEWAY)
+    }
+
+    /// Sets the default gateways of the static policy, an empty list clears them.
+    pub fn set_gateways<B: BootServices>(&self, boot_services: &B, gateways: &[Ipv4Addr]) -> Result<(), EfiError> {
+        self.set_addresses(boot_services, DataType::GATEWAY, gateways)
+    }
+
+    /// The DNS servers, set manually or obtained with DHCP.
+    pub fn dns_servers(&self) -> Result<Vec<Ipv4Addr>, EfiError> {
+        self.get_addresses(DataType::DNS_SERVER)
+    }
+
+    /// Sets the DNS servers of the static policy, an empty list clears them.
+    pub fn set_dns_servers<B: BootServices>(&self, boot_services: &B, servers: &[Ipv4Addr]) -> Result<(), EfiError> {
+        self.set_addresses(boot_services, DataType::DNS_SERVER, servers)
+    }
+
+    /// Waits for the interface to have an address, such as one obtained with DHCP, and returns it.
+    ///
+    /// Fails with `TIMEOUT` when the interface has no address after `timeout`.
+    pub fn wait_for_address<B: BootServices>(
+        &self,
+        boot_services: &B,
+        timeout: Duration,
+    ) -> Result<Ipv4Addr, EfiError> {
+        let notify = |event, register| self.notify(DataType::INTERFACE_INFO, event, register);
+        let notify = DataNotify::register(boot_services, &notify, Some(timeout))?;
+        loop {
+            let address = self.interface()?.station_address;
+            if !address.is_unspecified() {
+                return Ok(address);
+            }
+            notify.wait()?;
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for Ip4Config2 {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for Ip4Config2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ip4Config2").field("protocol", &(self.0 as *const Protocol)).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use boot_services::{
+        event::{EventTimerType, EventType},
+        MockBootServices,
+    };
+    use core::{
+        cell::{Cell, RefCell},
+        ptr,
+    };
+    use efi::protocols::ip4::RouteTable;
+
+    /// Configuration of an interface, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestConfig {
+        protocol: Protocol,
+        policy: Cell<Policy>,
+        station_address: Cell<[u8; 4]>,
+        manual_address: Cell<Option<ManualAddress>>,
+        gateways: RefCell<Vec<efi::Ipv4Address>>,
+        dns_servers: RefCell<Vec<efi::Ipv4Address>>,
+        notified: RefCell<Vec<(DataType, efi::Event)>>,
+    }
+
+    fn test_config<'a>(this: *mut Protocol) -> &'a TestConfig {
+        unsafe { &*(this as *const TestConfig) }
+    }
+
+    /// Copies the data to the buffer of the caller, if it is large enough.
+    fn copy_data<T>(data: &[T], size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        let data_size = mem::size_of_val(data);
+        let available = unsafe { ptr::replace(size, data_size) };
+        if available < data_size {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        if data_size == 0 {
+            return efi::Status::SUCCESS;
+        }
+        unsafe { ptr::copy_nonoverlapping(data.as_ptr() as *const u8, buffer as *mut u8, data_size) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_data(
+        this: *mut Protocol,
+        data_type: DataType,
+        size: usize,
+        data: *mut c_void,
+    ) -> efi::Status {
+        let config = test_config(this);
+        let addresses = || match data.is_null() {
+            true => Vec::new(),
+            false => unsafe { slice::from_raw_parts(data as *const efi::Ipv4Address, size / 4) }.to_vec(),
+        };
+        match data_type {
+            DataType::POLICY => config.policy.set(unsafe { *(data as *const Policy) }),
+            DataType::MANUAL_ADDRESS if size == 0 => config.manual_address.set(None),
+            DataType::MANUAL_ADDRESS => {
+                let manual = unsafe { *(data as *const ManualAddress) };
+                config.manual_address.set(Some(manual));
+                config.station_address.set(manual.address.addr);
+                // The address is applied once it is checked for conflicts.
+                return efi::Status::NOT_READY;
+            }
+            DataType::GATEWAY => *config.gateways.borrow_mut() = addresses(),
+            DataType::DNS_SERVER => *config.dns_servers.borrow_mut() = addresses(),
+            _ => return efi::Status::WRITE_PROTECTED,
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_data(
+        this: *mut Protocol,
+        data_type: DataType,
+        size: *mut usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let config = test_config(this);
+        match data_type {
+            DataType::INTERFACE_INFO => {
+                let mut name = [0; INTERFACE_INFO_NAME_SIZE];
+                name[..4].copy_from_slice(&[b'e' as u16, b't' as u16, b'h' as u16, b'0' as u16]);
+                let mut hw_address = efi::MacAddress { addr: [0; 32] };
+                hw_address.addr[..6].copy_from_slice(&[0, 1, 2, 3, 4, 5]);
+                let route = RouteTable {
+                    subnet_address: efi::Ipv4Address { addr: [10, 0, 0, 0] },
+                    subnet_mask: efi::Ipv4Address { addr: [255, 0, 0, 0] },
+                    gateway_address: efi::Ipv4Address { addr: [0; 4] },
+                };
+                let info = InterfaceInfo {
+                    name,
+                    if_type: 1,
+                    hw_address_size: 6,
+                    hw_address,
+                    station_address: efi::Ipv4Address { addr: config.station_address.get() },
+                    subnet_mask: efi::Ipv4Address { addr: [255, 0, 0, 0] },
+                    route_table_size: 1,
+                    route_table: unsafe { (buffer as *mut InterfaceInfo).add(1) as *mut RouteTable },
+                };
+                let data_size = mem::size_of::<InterfaceInfo>() + mem::size_of::<RouteTable>();
+                if unsafe { ptr::replace(size, data_size) } < data_size {
+                    return efi::Status::BUFFER_TOO_SMALL;
+                }
+                unsafe {
+                    ptr::write(buffer as *mut InterfaceInfo, info);
+                    ptr::write_unaligned(info.route_table, route);
+                }
+                efi::Status::SUCCESS
+            }
+            DataType::POLICY => copy_data(&[config.policy.get()], size, buffer),
+            DataType::MANUAL_ADDRESS => match config.manual_address.get() {
+                Some(manual) => copy_data(&[manual], size, buffer),
+                None => efi::Status::NOT_FOUND,
+            },
+            DataType::GATEWAY => copy_data(&config.gateways.borrow(), size, buffer),
+            DataType::DNS_SERVER => match config.dns_servers.borrow().as_slice() {
+                [] => efi::Status::NOT_FOUND,
+                servers => copy_data(servers, size, buffer),
+            },
+            _ => efi::Status::INVALID_PARAMETER,
+        }
+    }
+
+    extern "efiapi" fn register_data_notify(
+        this: *mut Protocol,
+        data_type: DataType,
+        event: efi::Event,
+    ) -> efi::Status {
+        test_config(this).notified.borrow_mut().push((data_type, event));
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn unregister_data_notify(
+        this: *mut Protocol,
+        data_type: DataType,
+        event: efi::Event,
+    ) -> efi::Status {
+        let mut notified = test_config(this).notified.borrow_mut();
+        match notified.iter().position(|notify| *notify == (data_type, event)) {
+            Some(index) => {
+                notified.remove(index);
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::NOT_FOUND,
+        }
+    }
+
+    fn ip4_config2() -> (Ip4Config2, &'static TestConfig) {
+        let config = Box::leak(Box::new(TestConfig {
+            protocol: Protocol { set_data, get_data, register_data_notify, unregister_data_notify },
+            policy: Cell::new(Policy::STATIC),
+            station_address: Cell::new([0; 4]),
+            manual_address: Cell::new(None),
+            gateways: RefCell::new(Vec::new()),
+            dns_servers: RefCell::new(Vec::new()),
+            notified: RefCell::new(Vec::new()),
+        }));
+        let test_config = unsafe { &*(config as *const TestConfig) };
+        (Ip4Config2::from(&mut config.protocol), test_config)
+    }
+
+    fn boot_services() -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_create_event::<Option<&'static ()>>().returning(|event_type, _, _, _| {
+            Ok(match event_type == EventType::TIMER {
+                true => 0x200 as efi::Event,
+                false => 0x100 as efi::Event,
+            })
+        });
+        boot_services.expect_close_event().returning(|_| Ok(()));
+        boot_services
+    }
+
+    #[test]
+    fn test_static_configuration() {
+        let (config, test_config) = ip4_config2();
+        let mut boot_services = boot_services();
+        boot_services.expect_wait_for_event().once().returning(|events| {
+            assert_eq!([0x100 as efi::Event].as_slice(), events);
+            Ok(0)
+        });
+
+        config.set_policy(&boot_services, Policy::DHCP).unwrap();
+        assert_eq!(Policy::DHCP, config.policy().unwrap());
+        config.set_policy(&boot_services, Policy::STATIC).unwrap();
+
+        assert_eq!(None, config.manual_address().unwrap());
+        let address = Ipv4Addr::new(10, 0, 0, 5);
+        let subnet_mask = Ipv4Addr::new(255, 0, 0, 0);
+        config.set_manual_address(&boot_services, address, subnet_mask).unwrap();
+        assert_eq!(Some((address, subnet_mask)), config.manual_address().unwrap());
+        assert!(test_config.notified.borrow().is_empty());
+
+        let gateways = [Ipv4Addr::new(10, 0, 0, 1)];
+        config.set_gateways(&boot_services, &gateways).unwrap();
+        assert_eq!(gateways.as_slice(), config.gateways().unwrap());
+        assert_eq!(Vec::<Ipv4Addr>::new(), config.dns_servers().unwrap());
+        let dns_servers = [Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 3)];
+        config.set_dns_servers(&boot_services, &dns_servers).unwrap();
+        assert_eq!(dns_servers.as_slice(), config.dns_servers().unwrap());
+        config.set_dns_servers(&boot_services, &[]).unwrap();
+        assert_eq!(Vec::<Ipv4Addr>::new(), config.dns_servers().unwrap());
+
+        config.clear_manual_address(&boot_services).unwrap();
+        assert_eq!(None, config.manual_address().unwrap());
+    }
+
+    #[test]
+    fn test_interface() {
+        let (config, test_config) = ip4_config2();
+        test_config.station_address.set([10, 0, 0, 5]);
+        assert_eq!(
+            Interface {
+                name: "eth0".into(),
+                if_type: 1,
+                hardware_address: vec![0, 1, 2, 3, 4, 5],
+                station_address: Ipv4Addr::new(10, 0, 0, 5),
+                subnet_mask: Ipv4Addr::new(255, 0, 0, 0),
+                routes: vec![Route {
+                    subnet: Ipv4Addr::new(10, 0, 0, 0),
+                    subnet_mask: Ipv4Addr::new(255, 0, 0, 0),
+                    gateway: Ipv4Addr::UNSPECIFIED,
+                }],
+            },
+            config.interface().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_wait_for_address() {
+        let (config, test_config) = ip4_config2();
+        let test_config_ptr = test_config as *const TestConfig as usize;
+        let mut boot_services = boot_services();
+        boot_services
+            .expect_set_timer()
+            .withf(|event, timer_type, trigger_time| {
+                *event == 0x200 as efi::Event
+                    && matches!(timer_type, EventTimerType::Relative)
+                    && *trigger_time == 10_000_000
+            })
+            .once()
+            .returning(|_, _, _| Ok(()));
+        boot_services.expect_wait_for_event().once().returning(move |events| {
+            assert_eq!([0x100 as efi::Event, 0x200 as efi::Event].as_slice(), events);
+            let test_config = unsafe { &*(test_config_ptr as *const TestConfig) };
+            assert_eq!(vec![(DataType::INTERFACE_INFO, events[0])], *test_config.notified.borrow());
+            test_config.station_address.set([10, 0, 0, 7]);
+            Ok(0)
+        });
+        assert_eq!(
+            Ipv4Addr::new(10, 0, 0, 7),
+            config.wait_for_address(&boot_services, Duration::from_secs(1)).unwrap()
+        );
+        assert!(test_config.notified.borrow().is_empty());
+
+        test_config.station_address.set([0; 4]);
+        let boot_services = boot_services_with_timeout();
+        assert_eq!(efi::Status::TIMEOUT, config.wait_for_address(&boot_services, Duration::from_secs(1)).unwrap_err());
+        assert!(test_config.notified.borrow().is_empty());
+    }
+
+    fn boot_services_with_timeout() -> MockBootServices {
+        let mut boot_services = boot_services();
+        boot_services.expect_set_timer().once().returning(|_, _, _| Ok(()));
+        boot_services.expect_wait_for_event().once().returning(|_| Ok(1));
+        boot_services
+    }
+}
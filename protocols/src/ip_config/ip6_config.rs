@@ -0,0 +1,504 @@
+//! IP6 Config protocol.
+//!
+//! [UEFI Spec Documentation: 28.7. EFI IPv6 Configuration Protocol](https://uefi.org/specs/UEFI/2.10/28_Network_Protocols_TCP_IP_and_Configuration.html#efi-ipv6-configuration-protocol)
+
+use alloc::{string::String, vec::Vec};
+use core::{char, ffi::c_void, fmt, mem, net::Ipv6Addr, ops::Deref, slice, time::Duration};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::DataNotify;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x937fe521, 0x95ae, 0x4d1a, 0x89, 0x29, &[0x48, 0xbc, 0xd9, 0x0a, 0xd3, 0x1a]);
+
+pub const INTERFACE_INFO_NAME_SIZE: usize = 32;
+
+pub type ProtocolSetData = extern "efiapi" fn(*mut Protocol, DataType, usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolGetData = extern "efiapi" fn(*mut Protocol, DataType, *mut usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolRegisterDataNotify = extern "efiapi" fn(*mut Protocol, DataType, efi::Event) -> efi::Status;
+
+pub type ProtocolUnregisterDataNotify = extern "efiapi" fn(*mut Protocol, DataType, efi::Event) -> efi::Status;
+
+/// FFI definition of `EFI_IP6_CONFIG_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub set_data: ProtocolSetData,
+    pub get_data: ProtocolGetData,
+    pub register_data_notify: ProtocolRegisterDataNotify,
+    pub unregister_data_notify: ProtocolUnregisterDataNotify,
+}
+
+/// Type of configuration data, `EFI_IP6_CONFIG_DATA_TYPE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct DataType(pub u32);
+
+impl DataType {
+    pub const INTERFACE_INFO: DataType = DataType(0);
+    pub const ALT_INTERFACE_ID: DataType = DataType(1);
+    pub const POLICY: DataType = DataType(2);
+    pub const DUP_ADDR_DETECT_TRANSMITS: DataType = DataType(3);
+    pub const MANUAL_ADDRESS: DataType = DataType(4);
+    pub const GATEWAY: DataType = DataType(5);
+    pub const DNS_SERVER: DataType = DataType(6);
+}
+
+/// FFI definition of `EFI_IP6_CONFIG_INTERFACE_INFO`, followed by its addresses and route table in the data.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct InterfaceInfo {
+    pub name: [efi::Char16; INTERFACE_INFO_NAME_SIZE],
+    pub if_type: u8,
+    pub hw_address_size: u32,
+    pub hw_address: efi::MacAddress,
+    pub address_info_count: u32,
+    pub address_info: *mut efi::protocols::ip6::AddressInfo,
+    pub route_count: u32,
+    pub route_table: *mut efi::protocols::ip6::RouteTable,
+}
+
+/// Policy of the configuration, `EFI_IP6_CONFIG_POLICY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Policy(pub u32);
+
+impl Policy {
+    /// The configuration is set manually.
+    pub const MANUAL: Policy = Policy(0);
+    /// The configuration is obtained with stateless autoconfiguration or DHCPv6.
+    pub const AUTOMATIC: Policy = Policy(1);
+}
+
+/// FFI definition of `EFI_IP6_CONFIG_DUP_ADDR_DETECT_TRANSMITS`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DupAddrDetectTransmits {
+    pub dup_addr_detect_transmits: u32,
+}
+
+/// FFI definition of `EFI_IP6_CONFIG_MANUAL_ADDRESS`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManualAddress {
+    pub address: efi::Ipv6Address,
+    pub is_anycast: efi::Boolean,
+    pub prefix_length: u8,
+}
+
+/// IP6 Config protocol.
+pub struct Ip6ConfigProtocol;
+
+unsafe impl ProtocolTrait for Ip6ConfigProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for Ip6ConfigProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// An address of an interface with the length of its prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    pub address: Ipv6Addr,
+    pub prefix_length: u8,
+}
+
+impl Address {
+    /// Whether the address is a link-local unicast address, in `fe80::/10`.
+    pub fn is_link_local(&self) -> bool {
+        self.address.segments()[0] & 0xffc0 == 0xfe80
+    }
+}
+
+/// A manual address of the manual policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticAddress {
+    pub address: Ipv6Addr,
+    pub prefix_length: u8,
+    pub anycast: bool,
+}
+
+/// A route of an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub gateway: Ipv6Addr,
+    pub destination: Ipv6Addr,
+    pub prefix_length: u8,
+}
+
+/// Information about an interface and its current configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    /// The type of the hardware, from the ARP hardware types.
+    pub if_type: u8,
+    pub hardware_address: Vec<u8>,
+    pub addresses: Vec<Address>,
+    pub routes: Vec<Route>,
+}
+
+/// Wrapper around the IP6 Config protocol.
+pub struct Ip6Config(&'static mut Protocol);
+
+impl Ip6Config {
+    /// Gets the instance of the IP6 Config protocol installed on a network interface.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &Ip6ConfigProtocol).map(Self)
+    }
+
+    fn protocol_ptr(&self) -> *mut Protocol {
+        self.0 as *const Protocol as *mut Protocol
+    }
+
+    fn get_data<T: Copy>(&self, data_type: DataType) -> Result<Vec<T>, EfiError> {
+        super::get_data(|size, data| (self.0.get_data)(self.protocol_ptr(), data_type, size, data))
+    }
+
+    /// Gets data made of a single value.
+    fn get_value<T: Copy>(&self, data_type: DataType) -> Result<T, EfiError> {
+        self.get_data::<T>(data_type)?.first().copied().ok_or(efi::Status::BAD_BUFFER_SIZE.into())
+    }
+
+    fn set_data<T, B: BootServices>(&self, boot_services: &B, data_type: DataType, data: &[T]) -> Result<(), EfiError> {
+        let notify = |event, register| self.notify(data_type, event, register);
+        super::set_data(
+            boot_services,
+            &notify,
+            |size, data| (self.0.set_data)(self.protocol_ptr(), data_type, size, data),
+            data,
+        )
+    }
+
+    fn notify(&self, data_type: DataType, event: efi::Event, register: bool) -> efi::Status {
+        match register {
+            true => (self.0.register_data_notify)(self.protocol_ptr(), data_type, event),
+            false => (self.0.unregister_data_notify)(self.protocol_ptr(), data_type, event),
+        }
+    }
+
+    /// Information about the interface and its current configuration.
+    pub fn interface(&self) -> Result<Interface, EfiError> {
+        // The buffer is aligned for the pointers of the information.
+        let data = self.get_data::<u64>(DataType::INTERFACE_INFO)?;
+        if mem::size_of_val(data.as_slice()) < mem::size_of::<InterfaceInfo>() {
+            return Err(efi::Status::BAD_BUFFER_SIZE.into());
+        }
+        //SAFETY: The buffer holds an aligned interface information.
+        let info = unsafe { &*(data.as_ptr() as *const InterfaceInfo) };
+        let addresses = match info.address_info.is_null() {
+            true => &[][..],
+            //SAFETY: The addresses follow the information in the buffer.
+            false => unsafe { slice::from_raw_parts(info.address_info, info.address_info_count as usize) },
+        };
+        let routes = match info.route_table.is_null() {
+            true => &[][..],
+            //SAFETY: The route table follows the information in the buffer.
+            false => unsafe { slice::from_raw_parts(info.route_table, info.route_count as usize) },
+        };
+        let name_length = info.name.iter().position(|c| *c == 0).unwrap_or(INTERFACE_INFO_NAME_SIZE);
+        Ok(Interface {
+            name: char::decode_utf16(info.name[..name_length].iter().copied())
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+            if_type: info.if_type,
+            hardware_address: info.hw_address.addr[..(info.hw_address_size as usize).min(32)].to_vec(),
+            addresses: addresses
+                .iter()
+                .map(|address| Address {
+                    address: Ipv6Addr::from(address.address.addr),
+                    prefix_length: address.prefix_length,
+                })
+                .collect(),
+            routes: routes
+                .iter()
+                .map(|route| Route {
+                    gateway: Ipv6Addr::from(route.gateway.addr),
+                    destination: Ipv6Addr::from(route.destination.addr),
+                    prefix_length: route.prefix_length,
+                })
+                .collect(),
+        })
+    }
+
+    pub fn policy(&self) -> Result<Policy, EfiError> {
+        self.get_value(DataType::POLICY)
+    }
+
+    /// Sets the policy, changing it clears the manual addresses, gateways and DNS servers.
+    pub fn set_policy<B: BootServices>(&self, boot_services: &B, policy: Policy) -> Result<(), EfiError> {
+        self.set_data(boot_services, DataType::POLICY, &[policy])
+    }
+
+    /// The number of neighbor solicitations sent for the duplicate address detection of a new address.
+    pub fn dad_transmits(&self) -> Result<u32, EfiError> {
+        Ok(self.get_value::<DupAddrDetectTransmits>(DataType::DUP_ADDR_DETECT_TRANSMITS)?.dup_addr_detect_transmits)
+    }
+
+    /// Sets the number of neighbor solicitations sent for duplicate address detection, 0 disables it.
+    pub fn set_dad_transmits<B: BootServices>(&self, boot_services: &B, transmits: u32) -> Result<(), EfiError> {
+        let data = DupAddrDetectTransmits { dup_addr_detect_transmits: transmits };
+        self.set_data(boot_services, DataType::DUP_ADDR_DETECT_TRANSMITS, &[data])
+    }
+
+    /// The manual addresses of the manual policy.
+    pub fn manual_addresses(&self) -> Result<Vec<StaticAddress>, EfiError> {
+        match self.get_data::<ManualAddress>(DataType::MANUAL_ADDRESS) {
+            Ok(addresses) => Ok(addresses
+                .iter()
+                .map(|manual| StaticAddress {
+                    address: Ipv6Addr::from(manual.address.addr),
+                    prefix_length: manual.prefix_length,
+                    anycast: manual.is_anycast.into(),
+                })
+                .collect()),
+            Err(status) if status == efi::Status::NOT_FOUND => Ok(Vec::new()),
+            Err(status) => Err(status),
+        }
+    }
+
+    /// Sets the manual addresses of the manual policy, waiting for their duplicate address detection. An empty list
+    /// clears them.
+    ///
+    /// The addresses that fail duplicate address detection are not configured, [`Ip6Config::manual_addresses`]
+    /// gives the addresses in use.
+    pub fn set_manual_addresses<B: BootServices>(
+        &self,
+        boot_services: &B,
+        addresses: &[StaticAddress],
+    ) -> Result<(), EfiError> {
+        let addresses = addresses
+            .iter()
+            .map(|address| ManualAddress {
+                address: efi::Ipv6Address { addr: address.address.octets() },
+                is_anycast: address.anycast.into(),
+                prefix_length: address.prefix_length,
+            })
+            .collect::<Vec<_>>();
+        self.set_data(boot_services, DataType::MANUAL_ADDRESS, &addresses)
+    }
+
+    fn get_addresses(&self, data_type: DataType) -> Result<Vec<Ipv6Addr>, EfiError> {
+        match self.get_data::<efi::Ipv6Address>(data_type) {
+            Ok(addresses) => Ok(addresses.iter().map(|address| Ipv6Addr::from(address.addr)).collect()),
+            Err(status) if status == efi::Status::NOT_FOUND => Ok(Vec::new()),
+            Err(status) => Err(status),
+        }
+    }
+
+    fn set_addresses<B: BootServices>(
+        &self,
+        boot_services: &B,
+        data_type: DataType,
+        addresses: &[Ipv6Addr],
+    ) -> Result<(), EfiError> {
+        let addresses = addresses.iter().map(|address| efi::Ipv6Address { addr: address.octets() }).collect::<Vec<_>>();
+        self.set_data(boot_services, data_type, &addresses)
+    }
+
+    /// The gateways of the manual policy.
+    pub fn gateways(&self) -> Result<Vec<Ipv6Addr>, EfiError> {
+        self.get_addresses(DataType::GATEWAY)
+    }
+
+    /// Sets the gateways of the manual policy, an empty list clears them.
+    pub fn set_gateways<B: BootServices>(&self, boot_services: &B, gateways: &[Ipv6Addr]) -> Result<(), EfiError> {
+        self.set_addresses(boot_services, DataType::GATEWAY, gateways)
+    }
+
+    /// The DNS servers, set manually or obtained with DHCPv6.
+    pub fn dns_servers(&self) -> Result<Vec<Ipv6Addr>, EfiError> {
+        self.get_addresses(DataType::DNS_SERVER)
+    }
+
+    /// Sets the DNS servers of the manual policy, an empty list clears them.
+    pub fn set_dns_servers<B: BootServices>(&self, boot_services: &B, servers: &[Ipv6Addr]) -> Result<(), EfiError> {
+        self.set_addresses(boot_services, DataType::DNS_SERVER, servers)
+    }
+
+    /// Waits for the interface to have an address that is not link-local, such as one obtained with stateless
+    /// autoconfiguration or DHCPv6, and returns it.
+    ///
+    /// Fails with `TIMEOUT` when the interface has no such address after `timeout`.
+    pub fn wait_for_address<B: BootServices>(
+        &self,
+        boot_services: &B,
+        timeout: Duration,
+    ) -> Result<Ipv6Addr, EfiError> {
+        let notify = |event, register| self.notify(DataType::INTERFACE_INFO, event, register);
+        let notify = DataNotify::register(boot_services, &notify, Some(timeout))?;
+        loop {
+            let interface = self.interface()?;
+            if let Some(address) = interface.addresses.iter().find(|address| !address.is_link_local()) {
+                return Ok(address.address);
+            }
+            notify.wait()?;
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for Ip6Config {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for Ip6Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ip6Config").field("protocol", &(self.0 as *const Protocol)).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, ptr};
+    use efi::protocols::ip6::{AddressInfo, RouteTable};
+
+    const LINK_LOCAL: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const GLOBAL: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 5);
+
+    /// Configuration of an interface, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestConfig {
+        protocol: Protocol,
+        manual_addresses: RefCell<Vec<ManualAddress>>,
+        notified: RefCell<Vec<efi::Event>>,
+    }
+
+    fn test_config<'a>(this: *mut Protocol) -> &'a TestConfig {
+        unsafe { &*(this as *const TestConfig) }
+    }
+
+    extern "efiapi" fn set_data(
+        this: *mut Protocol,
+        data_type: DataType,
+        size: usize,
+        data: *mut c_void,
+    ) -> efi::Status {
+        assert_eq!(DataType::MANUAL_ADDRESS, data_type);
+        let addresses = unsafe { slice::from_raw_parts(data as *const ManualAddress, size / 18) };
+        *test_config(this).manual_addresses.borrow_mut() = addresses.to_vec();
+        // The addresses are applied once duplicate address detection ends.
+        efi::Status::NOT_READY
+    }
+
+    extern "efiapi" fn get_data(
+        this: *mut Protocol,
+        data_type: DataType,
+        size: *mut usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let config = test_config(this);
+        match data_type {
+            DataType::INTERFACE_INFO => {
+                let mut addresses =
+                    vec![AddressInfo { address: efi::Ipv6Address { addr: LINK_LOCAL.octets() }, prefix_length: 64 }];
+                addresses.extend(
+                    config
+                        .manual_addresses
+                        .borrow()
+                        .iter()
+                        .map(|manual| AddressInfo { address: manual.address, prefix_length: manual.prefix_length }),
+                );
+                let route = RouteTable {
+                    gateway: efi::Ipv6Address { addr: [0; 16] },
+                    destination: efi::Ipv6Address { addr: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0).octets() },
+                    prefix_length: 64,
+                };
+                let info_size = mem::size_of::<InterfaceInfo>();
+                let addresses_size = mem::size_of_val(addresses.as_slice());
+                let data_size = info_size + addresses_size + mem::size_of::<RouteTable>();
+                if unsafe { ptr::replace(size, data_size) } < data_size {
+                    return efi::Status::BUFFER_TOO_SMALL;
+                }
+                let buffer = buffer as *mut u8;
+                let info = InterfaceInfo {
+                    name: [0; INTERFACE_INFO_NAME_SIZE],
+                    if_type: 1,
+                    hw_address_size: 6,
+                    hw_address: efi::MacAddress { addr: [0; 32] },
+                    address_info_count: addresses.len() as u32,
+                    address_info: unsafe { buffer.add(info_size) } as *mut AddressInfo,
+                    route_count: 1,
+                    route_table: unsafe { buffer.add(info_size + addresses_size) } as *mut RouteTable,
+                };
+                unsafe {
+                    ptr::write(buffer as *mut InterfaceInfo, info);
+                    ptr::copy_nonoverlapping(addresses.as_ptr(), info.address_info, addresses.len());
+                    ptr::write(info.route_table, route);
+                }
+                efi::Status::SUCCESS
+            }
+            DataType::MANUAL_ADDRESS => {
+                let addresses = config.manual_addresses.borrow();
+                if addresses.is_empty() {
+                    return efi::Status::NOT_FOUND;
+                }
+                let data_size = mem::size_of_val(addresses.as_slice());
+                if unsafe { ptr::replace(size, data_size) } < data_size {
+                    return efi::Status::BUFFER_TOO_SMALL;
+                }
+                unsafe { ptr::copy_nonoverlapping(addresses.as_ptr(), buffer as *mut ManualAddress, addresses.len()) };
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::NOT_FOUND,
+        }
+    }
+
+    extern "efiapi" fn register_data_notify(this: *mut Protocol, _: DataType, event: efi::Event) -> efi::Status {
+        test_config(this).notified.borrow_mut().push(event);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn unregister_data_notify(this: *mut Protocol, _: DataType, event: efi::Event) -> efi::Status {
+        test_config(this).notified.borrow_mut().retain(|notified| *notified != event);
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_manual_addresses() {
+        let config = Box::leak(Box::new(TestConfig {
+            protocol: Protocol { set_data, get_data, register_data_notify, unregister_data_notify },
+            manual_addresses: RefCell::new(Vec::new()),
+            notified: RefCell::new(Vec::new()),
+        }));
+        let test_config = unsafe { &*(config as *const TestConfig) };
+        let config = Ip6Config::from(&mut config.protocol);
+
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_create_event::<Option<&'static ()>>().returning(|_, _, _, _| Ok(0x100 as efi::Event));
+        boot_services.expect_set_timer().returning(|_, _, _| Ok(()));
+        boot_services.expect_close_event().returning(|_| Ok(()));
+        boot_services.expect_wait_for_event().once().returning(|events| {
+            assert_eq!(1, events.len());
+            Ok(0)
+        });
+
+        assert_eq!(Vec::<StaticAddress>::new(), config.manual_addresses().unwrap());
+        assert_eq!(Vec::<Ipv6Addr>::new(), config.dns_servers().unwrap());
+        let interface = config.interface().unwrap();
+        assert_eq!(vec![Address { address: LINK_LOCAL, prefix_length: 64 }], interface.addresses);
+        assert!(interface.addresses[0].is_link_local());
+        assert_eq!(64, interface.routes[0].prefix_length);
+
+        let manual = StaticAddress { address: GLOBAL, prefix_length: 64, anycast: false };
+        config.set_manual_addresses(&boot_services, &[manual]).unwrap();
+        assert_eq!(vec![manual], config.manual_addresses().unwrap());
+        assert!(test_config.notified.borrow().is_empty());
+        assert_eq!(GLOBAL, config.wait_for_address(&boot_services, Duration::from_secs(1)).unwrap());
+    }
+}
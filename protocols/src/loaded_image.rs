@@ -0,0 +1,320 @@
+//! Loaded Image protocol.
+//!
+//! [UEFI Spec Documentation: 9.1. EFI Loaded Image Protocol](https://uefi.org/specs/UEFI/2.10/09_Protocols_EFI_Loaded_Image.html#efi-loaded-image-protocol)
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
+    char,
+    ffi::c_void,
+    fmt, ptr, slice,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use boot_services::{allocation::MemoryType, protocol_handler, BootServices};
+use device_path::DevicePath;
+use r_efi::efi;
+use status::EfiError;
+
+pub mod debug_image_info;
+
+type LoadedImageProtocol = efi::protocols::loaded_image::Protocol;
+
+type UnloadHandler = Box<dyn FnMut(efi::Handle) -> Result<(), EfiError>>;
+
+/// The unload handler of the image, there is only one since the unload function is per image binary.
+static UNLOAD_HANDLER: AtomicPtr<UnloadHandler> = AtomicPtr::new(ptr::null_mut());
+
+/// Typed access to the Loaded Image protocol of an image.
+pub struct LoadedImage(&'static mut LoadedImageProtocol);
+
+impl LoadedImage {
+    /// Gets the Loaded Image protocol of an image, usually the image handle given to the entry point.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, image_handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(image_handle, &protocol_handler::LoadedImage).map(Self)
+    }
+
+    /// The revision of the protocol.
+    pub fn revision(&self) -> u32 {
+        self.0.revision
+    }
+
+    /// The handle of the image that loaded this image, null if the image was loaded by the firmware.
+    pub fn parent_handle(&self) -> efi::Handle {
+        self.0.parent_handle
+    }
+
+    /// The system table given to the image.
+    pub fn system_table(&self) -> *mut efi::SystemTable {
+        self.0.system_table
+    }
+
+    /// The handle of the device from which the image was loaded.
+    pub fn device_handle(&self) -> efi::Handle {
+        self.0.device_handle
+    }
+
+    /// The file path of the image, relative to the device handle.
+    /// Returns `None` if the image was loaded from a buffer or if the device path is malformed.
+    pub fn file_path(&self) -> Option<&DevicePath> {
+        //SAFETY: The file path is owned by the protocol and valid for as long as the image is loaded.
+        unsafe { DevicePath::from_ptr(self.0.file_path) }.ok()
+    }
+
+    /// The load options given to the image.
+    pub fn load_options(&self) -> &[u8] {
+        if self.0.load_options.is_null() {
+            return &[];
+        }
+        //SAFETY: The load options are a buffer of `load_options_size` bytes owned by the protocol.
+        unsafe { slice::from_raw_parts(self.0.load_options as *const u8, self.0.load_options_size as usize) }
+    }
+
+    /// The command line arguments of the image, parsed from the load options.
+    ///
+    /// The load options are read as a null terminated UTF-16 string and split following the UEFI Shell
+    /// conventions: arguments are separated by spaces or tabs, double quotes group text containing spaces and `^`
+    /// escapes the next character. As with the Shell, the first argument is usually the image name.
+    pub fn args(&self) -> Args {
+        let options = self.load_options();
+        let chars = char::decode_utf16(
+            options.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).take_while(|c| *c != 0),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+        Args { chars, position: 0 }
+    }
+
+    /// Sets the load options given to the image, used before starting an image.
+    ///
+    /// # Safety
+    ///
+    /// The load options must stay valid until the image is unloaded.
+    pub unsafe fn set_load_options(&mut self, load_options: &[u8]) {
+        self.0.load_options = load_options.as_ptr() as *mut c_void;
+        self.0.load_options_size = load_options.len() as u32;
+    }
+
+    /// The address where the image is loaded.
+    pub fn image_base(&self) -> *const c_void {
+        self.0.image_base
+    }
+
+    /// The size in bytes of the loaded image.
+    pub fn image_size(&self) -> u64 {
+        self.0.image_size
+    }
+
+    /// The memory type of the code sections of the image.
+    pub fn image_code_type(&self) -> MemoryType {
+        self.0.image_code_type.into()
+    }
+
+    /// The memory type of the data sections of the image.
+    pub fn image_data_type(&self) -> MemoryType {
+        self.0.image_data_type.into()
+    }
+
+    /// Sets the function called when the image is unloaded with UnloadImage().
+    ///
+    /// The image is unloaded only if the handler returns `Ok`, otherwise the handler stays registered.
+    /// There is one unload handler per image binary, setting a new handler replaces the previous one.
+    pub fn set_unload_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(efi::Handle) -> Result<(), EfiError> + 'static,
+    {
+        let handler = Box::into_raw(Box::new(Box::new(handler) as UnloadHandler));
+        let previous = UNLOAD_HANDLER.swap(handler, Ordering::SeqCst);
+        if !previous.is_null() {
+            //SAFETY: The previous handler was leaked from a box and is no longer referenced.
+            drop(unsafe { Box::from_raw(previous) });
+        }
+        self.0.unload = Some(Self::unload);
+    }
+
+    /// The raw protocol.
+    pub fn protocol(&mut self) -> &mut LoadedImageProtocol {
+        self.0
+    }
+
+    extern "efiapi" fn unload(image_handle: efi::Handle) -> efi::Status {
+        let handler = UNLOAD_HANDLER.swap(ptr::null_mut(), Ordering::SeqCst);
+        if handler.is_null() {
+            return efi::Status::SUCCESS;
+        }
+        //SAFETY: The handler was leaked from a box in set_unload_handler.
+        let mut handler = unsafe { Box::from_raw(handler) };
+        match handler(image_handle) {
+            Ok(()) => efi::Status::SUCCESS,
+            Err(status) => {
+                // Keep the handler so the unload can be attempted again, unless another one was set meanwhile.
+                let handler = Box::into_raw(handler);
+                if UNLOAD_HANDLER
+                    .compare_exchange(ptr::null_mut(), handler, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_err()
+                {
+                    //SAFETY: The handler was not stored.
+                    drop(unsafe { Box::from_raw(handler) });
+                }
+                status.into()
+            }
+        }
+    }
+}
+
+/// Iterator over the command line arguments of an image, see [`LoadedImage::args`].
+#[derive(Debug, Clone)]
+pub struct Args {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chars = self.chars[self.position..].iter().copied();
+        let mut arg = None;
+        let mut in_quotes = false;
+        let mut consumed = 0;
+        while let Some(c) = chars.next() {
+            consumed += 1;
+            match c {
+                ' ' | '\t' if !in_quotes => {
+                    if arg.is_some() {
+                        break;
+                    }
+                }
+                '"' => {
+                    in_quotes = !in_quotes;
+                    arg.get_or_insert_with(String::new);
+                }
+                '^' => {
+                    let arg = arg.get_or_insert_with(String::new);
+                    if let Some(c) = chars.next() {
+                        consumed += 1;
+                        arg.push(c);
+                    }
+                }
+                c => arg.get_or_insert_with(String::new).push(c),
+            }
+        }
+        self.position += consumed;
+        arg
+    }
+}
+
+impl From<&'static mut LoadedImageProtocol> for LoadedImage {
+    fn from(protocol: &'static mut LoadedImageProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for LoadedImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadedImage")
+            .field("parent_handle", &self.parent_handle())
+            .field("device_handle", &self.device_handle())
+            .field("file_path", &self.file_path())
+            .field("image_base", &self.image_base())
+            .field("image_size", &self.image_size())
+            .field("image_code_type", &self.image_code_type())
+            .field("image_data_type", &self.image_data_type())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use core::sync::atomic::AtomicUsize;
+
+    fn loaded_image_protocol() -> &'static mut LoadedImageProtocol {
+        static FILE_PATH: [u8; 4] = [0x7F, 0xFF, 0x04, 0x00];
+        static LOAD_OPTIONS: [u8; 4] = [1, 2, 3, 4];
+        Box::leak(Box::new(LoadedImageProtocol {
+            revision: efi::protocols::loaded_image::REVISION,
+            parent_handle: 1_usize as efi::Handle,
+            system_table: ptr::null_mut(),
+            device_handle: 2_usize as efi::Handle,
+            file_path: FILE_PATH.as_ptr() as *mut _,
+            reserved: ptr::null_mut(),
+            load_options_size: LOAD_OPTIONS.len() as u32,
+            load_options: LOAD_OPTIONS.as_ptr() as *mut c_void,
+            image_base: 0x1000 as *mut c_void,
+            image_size: 0x2000,
+            image_code_type: efi::BOOT_SERVICES_CODE,
+            image_data_type: efi::BOOT_SERVICES_DATA,
+            unload: None,
+        }))
+    }
+
+    #[test]
+    fn test_loaded_image() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol::<protocol_handler::LoadedImage, LoadedImageProtocol>()
+            .withf(|handle, _| *handle == 3_usize as efi::Handle)
+            .once()
+            .returning(|_, _| Ok(loaded_image_protocol()));
+
+        let loaded_image = LoadedImage::get(&boot_services, 3_usize as efi::Handle).unwrap();
+        assert_eq!(1_usize as efi::Handle, loaded_image.parent_handle());
+        assert_eq!(2_usize as efi::Handle, loaded_image.device_handle());
+        assert!(loaded_image.file_path().unwrap().is_end());
+        assert_eq!(&[1, 2, 3, 4], loaded_image.load_options());
+        assert_eq!(0x1000, loaded_image.image_base() as usize);
+        assert_eq!(0x2000, loaded_image.image_size());
+        assert_eq!(MemoryType::BOOT_SERVICES_CODE, loaded_image.image_code_type());
+        assert_eq!(MemoryType::BOOT_SERVICES_DATA, loaded_image.image_data_type());
+    }
+
+    fn args(command_line: &str) -> Vec<String> {
+        let load_options = command_line.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+        let protocol = loaded_image_protocol();
+        protocol.load_options_size = load_options.len() as u32;
+        protocol.load_options = load_options.leak().as_mut_ptr() as *mut c_void;
+        LoadedImage::from(protocol).args().collect()
+    }
+
+    #[test]
+    fn test_args() {
+        assert_eq!(vec!["app.efi", "-v", "file.txt"], args("app.efi -v  file.txt"));
+        assert_eq!(vec!["app.efi", "My File.txt", "a b"], args("\tapp.efi \"My File.txt\" a\" \"b "));
+        assert_eq!(vec!["app.efi", "", "\"quoted\"", "^"], args("app.efi \"\" ^\"quoted^\" ^^"));
+        assert_eq!(vec!["-h"], args("-h\0ignored"));
+        assert!(args("").is_empty());
+        assert!(args("   ").is_empty());
+    }
+
+    #[test]
+    fn test_args_without_load_options() {
+        let protocol = loaded_image_protocol();
+        protocol.load_options_size = 0;
+        protocol.load_options = ptr::null_mut();
+        assert_eq!(0, LoadedImage::from(protocol).args().count());
+    }
+
+    #[test]
+    fn test_unload_handler() {
+        static UNLOAD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let mut loaded_image = LoadedImage::from(loaded_image_protocol());
+        loaded_image.set_unload_handler(|image_handle| {
+            assert_eq!(3_usize as efi::Handle, image_handle);
+            match UNLOAD_COUNT.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(efi::Status::ACCESS_DENIED.into()),
+                _ => Ok(()),
+            }
+        });
+
+        let unload = loaded_image.protocol().unload.unwrap();
+        assert_eq!(efi::Status::ACCESS_DENIED, unsafe { r_efi::efi_call(unload, (3_usize as efi::Handle,)) });
+        assert_eq!(efi::Status::SUCCESS, unsafe { r_efi::efi_call(unload, (3_usize as efi::Handle,)) });
+        assert_eq!(2, UNLOAD_COUNT.load(Ordering::SeqCst));
+        // The handler is consumed once the image is unloaded.
+        assert_eq!(efi::Status::SUCCESS, unsafe { r_efi::efi_call(unload, (3_usize as efi::Handle,)) });
+        assert_eq!(2, UNLOAD_COUNT.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,354 @@
+//! Debug Image Info table, the loaded images that source-level debuggers symbolize.
+//!
+//! The DXE core usually owns the table. A platform without one publishes it and registers its images, so that
+//! debuggers find them:
+//!
+//! ```ignore
+//! let mut table = DebugImageInfoTable::publish(&boot_services, ConfigurationTables::new(system_table))?;
+//! table.register(&boot_services, image_handle, &LoadedImage::get(&boot_services, image_handle)?)?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 18.4.3. Debug Image Info Table](https://uefi.org/specs/UEFI/2.10/18_Protocols_Debugger_Support.html#efi-debug-support-table)
+
+use alloc::vec::Vec;
+use core::{fmt, mem, ptr, slice};
+
+use boot_services::{allocation::MemoryType, configuration_table::ConfigurationTables, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::{LoadedImage, LoadedImageProtocol};
+
+/// GUID of the table in the configuration tables, `EFI_DEBUG_IMAGE_INFO_TABLE_GUID`.
+pub const DEBUG_IMAGE_INFO_TABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x49152e77, 0x1ada, 0x4764, 0xb7, 0xa2, &[0x7a, 0xfe, 0xfe, 0xd9, 0x5e, 0x8b]);
+
+/// The table is being updated, `EFI_DEBUG_IMAGE_INFO_UPDATE_IN_PROGRESS`.
+pub const UPDATE_IN_PROGRESS: u32 = 0x01;
+/// The table changed since the debugger last cleared the bit, `EFI_DEBUG_IMAGE_INFO_TABLE_MODIFIED`.
+pub const TABLE_MODIFIED: u32 = 0x02;
+
+/// Type of the entries describing a loaded image, `EFI_DEBUG_IMAGE_INFO_TYPE_NORMAL`.
+pub const IMAGE_INFO_TYPE_NORMAL: u32 = 0x01;
+
+/// Number of entries of a newly published table, doubled whenever it is full.
+const INITIAL_CAPACITY: usize = 32;
+
+/// FFI definition of `EFI_DEBUG_IMAGE_INFO_TABLE_HEADER`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct TableHeader {
+    pub update_status: u32,
+    /// Number of entries in use, null entries included.
+    pub table_size: u32,
+    pub efi_debug_image_info_table: *mut *mut ImageInfoNormal,
+}
+
+/// FFI definition of `EFI_DEBUG_IMAGE_INFO_NORMAL`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfoNormal {
+    pub image_info_type: u32,
+    pub loaded_image_protocol_instance: *mut LoadedImageProtocol,
+    pub image_handle: efi::Handle,
+}
+
+/// Returns the images of the table installed in the configuration tables, `NOT_FOUND` if there is none.
+///
+/// The table is only read, whoever owns it keeps updating it.
+pub fn images(configuration_tables: ConfigurationTables) -> Result<Vec<ImageInfoNormal>, EfiError> {
+    let header = configuration_tables
+        .find_table(&DEBUG_IMAGE_INFO_TABLE_GUID)
+        .filter(|table| !table.is_null())
+        .ok_or(efi::Status::NOT_FOUND)? as *const TableHeader;
+    //SAFETY: The installed table starts with its header.
+    let header = unsafe { &*header };
+    //SAFETY: Updates are visible to debuggers, which halt the firmware at any time.
+    if unsafe { ptr::read_volatile(&header.update_status) } & UPDATE_IN_PROGRESS != 0 {
+        return Err(efi::Status::NOT_READY.into());
+    }
+    if header.efi_debug_image_info_table.is_null() {
+        return Ok(Vec::new());
+    }
+    //SAFETY: The header points to `table_size` entries, each null or pointing to an image info.
+    let entries = unsafe { slice::from_raw_parts(header.efi_debug_image_info_table, header.table_size as usize) };
+    Ok(entries
+        .iter()
+        .filter(|entry| !entry.is_null())
+        //SAFETY: Non-null entries point to an image info.
+        .map(|entry| unsafe { entry.read_unaligned() })
+        .filter(|entry| entry.image_info_type == IMAGE_INFO_TYPE_NORMAL)
+        .collect())
+}
+
+/// A Debug Image Info table published by this image.
+pub struct DebugImageInfoTable {
+    header: *mut TableHeader,
+    capacity: usize,
+}
+
+impl DebugImageInfoTable {
+    /// Allocates an empty table and installs it in the configuration tables.
+    ///
+    /// `ALREADY_STARTED` if a table is already installed, it belongs to the DXE core and is left untouched.
+    pub fn publish<B: BootServices>(
+        boot_services: &B,
+        configuration_tables: ConfigurationTables,
+    ) -> Result<Self, EfiError> {
+        if configuration_tables.find_table(&DEBUG_IMAGE_INFO_TABLE_GUID).is_some() {
+            return Err(efi::Status::ALREADY_STARTED.into());
+        }
+        let entries = Self::allocate_entries(boot_services, INITIAL_CAPACITY)?;
+        let header = boot_services
+            .allocate_pool(MemoryType::BOOT_SERVICES_DATA, mem::size_of::<TableHeader>())
+            .map(|pool| pool as *mut TableHeader)
+            .inspect_err(|_| {
+                let _ = boot_services.free_pool(entries as *mut u8);
+            })?;
+        //SAFETY: The allocation is as large as the header.
+        unsafe { header.write(TableHeader { update_status: 0, table_size: 0, efi_debug_image_info_table: entries }) };
+        //SAFETY: The table stays allocated for as long as it is installed, it is never freed.
+        unsafe { boot_services.install_configuration_table_unchecked(&DEBUG_IMAGE_INFO_TABLE_GUID, header as *mut _) }
+            .inspect_err(|_| {
+                let _ = boot_services.free_pool(entries as *mut u8);
+                let _ = boot_services.free_pool(header as *mut u8);
+            })?;
+        Ok(Self { header, capacity: INITIAL_CAPACITY })
+    }
+
+    /// Adds a loaded image to the table, reusing the first null entry or growing the table if it is full.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn register<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        image_handle: efi::Handle,
+        loaded_image: &LoadedImage,
+    ) -> Result<(), EfiError> {
+        let info = boot_services
+            .allocate_pool(MemoryType::BOOT_SERVICES_DATA, mem::size_of::<ImageInfoNormal>())
+            .map(|pool| pool as *mut ImageInfoNormal)?;
+        //SAFETY: The allocation is as large as the image info.
+        unsafe {
+            info.write(ImageInfoNormal {
+                image_info_type: IMAGE_INFO_TYPE_NORMAL,
+                loaded_image_protocol_instance: loaded_image.0 as *const LoadedImageProtocol as *mut _,
+                image_handle,
+            })
+        };
+
+        self.begin_update();
+        let result = self.insert(boot_services, info);
+        self.end_update(result.is_ok());
+        result.inspect_err(|_| {
+            let _ = boot_services.free_pool(info as *mut u8);
+        })
+    }
+
+    /// Removes the entry of an image from the table, `NOT_FOUND` if it was not registered.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn unregister<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        image_handle: efi::Handle,
+    ) -> Result<(), EfiError> {
+        let entries = self.entries();
+        //SAFETY: Non-null entries point to an image info allocated by register.
+        let index = entries
+            .iter()
+            .position(|entry| !entry.is_null() && unsafe { (**entry).image_handle } == image_handle)
+            .ok_or(efi::Status::NOT_FOUND)?;
+        let info = entries[index];
+
+        self.begin_update();
+        self.entries_mut()[index] = ptr::null_mut();
+        let header = self.header_mut();
+        if index + 1 == header.table_size as usize {
+            header.table_size -= 1;
+        }
+        self.end_update(true);
+        boot_services.free_pool(info as *mut u8)
+    }
+
+    /// The number of registered images.
+    pub fn len(&self) -> usize {
+        self.entries().iter().filter(|entry| !entry.is_null()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert<B: BootServices>(&mut self, boot_services: &B, info: *mut ImageInfoNormal) -> Result<(), EfiError> {
+        if let Some(entry) = self.entries_mut().iter_mut().find(|entry| entry.is_null()) {
+            *entry = info;
+            return Ok(());
+        }
+        let table_size = self.header_mut().table_size as usize;
+        if table_size == self.capacity {
+            let capacity = self.capacity * 2;
+            let entries = Self::allocate_entries(boot_services, capacity)?;
+            let old_entries = self.header_mut().efi_debug_image_info_table;
+            //SAFETY: Both arrays hold at least `table_size` entries and are distinct allocations.
+            unsafe { ptr::copy_nonoverlapping(old_entries, entries, table_size) };
+            self.header_mut().efi_debug_image_info_table = entries;
+            self.capacity = capacity;
+            boot_services.free_pool(old_entries as *mut u8)?;
+        }
+        let header = self.header_mut();
+        //SAFETY: The array holds `capacity` entries, more than `table_size`.
+        unsafe { header.efi_debug_image_info_table.add(table_size).write(info) };
+        header.table_size += 1;
+        Ok(())
+    }
+
+    fn allocate_entries<B: BootServices>(
+        boot_services: &B,
+        capacity: usize,
+    ) -> Result<*mut *mut ImageInfoNormal, EfiError> {
+        let size = capacity * mem::size_of::<*mut ImageInfoNormal>();
+        let entries = boot_services.allocate_pool(MemoryType::BOOT_SERVICES_DATA, size)?;
+        //SAFETY: The allocation is `size` bytes long.
+        unsafe { ptr::write_bytes(entries, 0, size) };
+        Ok(entries as *mut *mut ImageInfoNormal)
+    }
+
+    fn begin_update(&mut self) {
+        let header = self.header_mut();
+        //SAFETY: The status is written volatile since debuggers read it while the firmware is halted.
+        unsafe { ptr::write_volatile(&mut header.update_status, header.update_status | UPDATE_IN_PROGRESS) };
+    }
+
+    fn end_update(&mut self, modified: bool) {
+        let header = self.header_mut();
+        let mut update_status = header.update_status & !UPDATE_IN_PROGRESS;
+        if modified {
+            update_status |= TABLE_MODIFIED;
+        }
+        //SAFETY: The status is written volatile since debuggers read it while the firmware is halted.
+        unsafe { ptr::write_volatile(&mut header.update_status, update_status) };
+    }
+
+    fn header_mut(&mut self) -> &mut TableHeader {
+        //SAFETY: The header was allocated by publish and is never freed.
+        unsafe { &mut *self.header }
+    }
+
+    fn entries(&self) -> &[*mut ImageInfoNormal] {
+        //SAFETY: The header was allocated by publish and points to `table_size` entries.
+        unsafe {
+            let header = &*self.header;
+            slice::from_raw_parts(header.efi_debug_image_info_table, header.table_size as usize)
+        }
+    }
+
+    fn entries_mut(&mut self) -> &mut [*mut ImageInfoNormal] {
+        let header = self.header_mut();
+        //SAFETY: The header points to `table_size` entries.
+        unsafe { slice::from_raw_parts_mut(header.efi_debug_image_info_table, header.table_size as usize) }
+    }
+}
+
+impl fmt::Debug for DebugImageInfoTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugImageInfoTable").field("len", &self.len()).field("capacity", &self.capacity).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use boot_services::MockBootServices;
+    use core::{ffi::c_void, mem::MaybeUninit};
+
+    fn boot_services() -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_allocate_pool()
+            // Pool allocations are 8 byte aligned.
+            .returning(|_, size| {
+                Ok(Box::leak(vec![u64::MAX; size.div_ceil(8)].into_boxed_slice()).as_mut_ptr() as *mut u8)
+            });
+        boot_services.expect_free_pool().returning(|_| Ok(()));
+        boot_services
+    }
+
+    fn loaded_image() -> LoadedImage {
+        //SAFETY: Only the address of the protocol is used.
+        LoadedImage::from(Box::leak(Box::new(unsafe { MaybeUninit::<LoadedImageProtocol>::zeroed().assume_init() })))
+    }
+
+    fn system_table(tables: &mut [efi::ConfigurationTable]) -> efi::SystemTable {
+        //SAFETY: Only the configuration table fields are read.
+        let mut system_table = unsafe { MaybeUninit::<efi::SystemTable>::zeroed().assume_init() };
+        system_table.number_of_table_entries = tables.len();
+        system_table.configuration_table = tables.as_mut_ptr();
+        system_table
+    }
+
+    #[test]
+    fn test_register() {
+        static mut INSTALLED: *mut c_void = ptr::null_mut();
+        let mut boot_services = boot_services();
+        boot_services
+            .expect_install_configuration_table_unchecked()
+            .withf(|guid, _| *guid == DEBUG_IMAGE_INFO_TABLE_GUID)
+            .once()
+            //SAFETY: The test is the only user of INSTALLED.
+            .returning(|_, table| unsafe {
+                INSTALLED = table;
+                Ok(())
+            });
+
+        let mut table =
+            DebugImageInfoTable::publish(&boot_services, ConfigurationTables::new(&system_table(&mut []))).unwrap();
+        let loaded =
+            (1..=INITIAL_CAPACITY + 1).map(|handle| (handle as efi::Handle, loaded_image())).collect::<Vec<_>>();
+        for (handle, image) in &loaded {
+            table.register(&boot_services, *handle, image).unwrap();
+        }
+        assert_eq!(INITIAL_CAPACITY + 1, table.len());
+        assert_eq!(INITIAL_CAPACITY * 2, table.capacity);
+
+        table.unregister(&boot_services, 2 as efi::Handle).unwrap();
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), table.unregister(&boot_services, 2 as efi::Handle));
+        table.register(&boot_services, 0x100 as efi::Handle, &loaded[0].1).unwrap();
+
+        //SAFETY: The table was installed by publish.
+        let mut tables =
+            [efi::ConfigurationTable { vendor_guid: DEBUG_IMAGE_INFO_TABLE_GUID, vendor_table: unsafe { INSTALLED } }];
+        let system_table = system_table(&mut tables);
+        let registered = images(ConfigurationTables::new(&system_table)).unwrap();
+        assert_eq!(INITIAL_CAPACITY + 1, registered.len());
+        assert_eq!(1 as efi::Handle, registered[0].image_handle);
+        assert_eq!(0x100 as efi::Handle, registered[1].image_handle);
+        assert_eq!(
+            loaded[0].1 .0 as *const _ as *mut LoadedImageProtocol,
+            registered[1].loaded_image_protocol_instance
+        );
+        //SAFETY: The table was installed by publish.
+        let header = unsafe { &*(tables[0].vendor_table as *const TableHeader) };
+        assert_eq!(TABLE_MODIFIED, header.update_status);
+
+        assert_eq!(
+            efi::Status::ALREADY_STARTED,
+            DebugImageInfoTable::publish(&boot_services, ConfigurationTables::new(&system_table)).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_images_update_in_progress() {
+        let mut header = TableHeader {
+            update_status: UPDATE_IN_PROGRESS,
+            table_size: 0,
+            efi_debug_image_info_table: ptr::null_mut(),
+        };
+        let mut tables = [efi::ConfigurationTable {
+            vendor_guid: DEBUG_IMAGE_INFO_TABLE_GUID,
+            vendor_table: ptr::addr_of_mut!(header) as *mut c_void,
+        }];
+        let system_table = system_table(&mut tables);
+        assert_eq!(Err(efi::Status::NOT_READY.into()), images(ConfigurationTables::new(&system_table)));
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), images(ConfigurationTables::new(&self::system_table(&mut []))));
+    }
+}
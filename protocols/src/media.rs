@@ -0,0 +1,905 @@
+//! Simple File System and File protocols.
+//!
+//! [`SimpleFileSystem::open_volume`] gives the root directory of a volume, from which [`File`]s are opened by path:
+//!
+//! ```ignore
+//! let mut volume = SimpleFileSystem::get(&boot_services, device_handle)?.open_volume()?;
+//! let name = String16::try_from(r"\EFI\Boot\config.txt")?;
+//! let mut file = volume.open(&name, OpenMode::Read, FileAttribute::empty())?;
+//! let content = file.read_to_end()?;
+//! ```
+//!
+//! The [`fs`] helpers read or write a whole file in one call.
+//!
+//! [UEFI Spec Documentation: 13.4. Simple File System Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#simple-file-system-protocol)
+//!
+//! [UEFI Spec Documentation: 13.5. File Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#file-protocol)
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, fmt, ops, ptr};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+
+pub mod block_io;
+pub mod directory;
+pub mod disk_io;
+pub mod file_info;
+pub mod fs;
+pub mod load_file;
+pub mod partition;
+pub mod storage_security;
+
+pub use block_io::{BlockIo, MediaInfo};
+pub use directory::{Directory, Entries};
+pub use disk_io::{DiskIo, DiskIo2};
+pub use file_info::{FileInfo, FileSystemInfo};
+pub use load_file::{FileLoader, LoadFile};
+pub use partition::{Gpt, PartitionInfo};
+pub use storage_security::{Level0Discovery, StorageSecurity};
+
+type SimpleFileSystemProtocol = efi::protocols::simple_file_system::Protocol;
+type FileProtocol = efi::protocols::file::Protocol;
+
+/// Typed access to an instance of the Simple File System protocol.
+pub struct SimpleFileSystem(&'static mut SimpleFileSystemProtocol);
+
+impl SimpleFileSystem {
+    /// Locates the first instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::SimpleFileSystem, None).map(Self)
+    }
+
+    /// Gets the instance of the protocol installed on a handle, such as the device handle of the loaded image.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::SimpleFileSystem).map(Self)
+    }
+
+    /// Opens the root directory of the volume.
+    pub fn open_volume(&mut self) -> Result<File, EfiError> {
+        let mut root = ptr::null_mut();
+        match unsafe { r_efi::efi_call(self.0.open_volume, (self.0, &mut root)) } {
+            s if s.is_error() => Err(s.into()),
+            //SAFETY: The protocol gave a valid file that is now owned by the wrapper.
+            _ => unsafe { File::from_raw(root) }.ok_or(efi::Status::DEVICE_ERROR.into()),
+        }
+    }
+}
+
+impl From<&'static mut SimpleFileSystemProtocol> for SimpleFileSystem {
+    fn from(protocol: &'static mut SimpleFileSystemProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for SimpleFileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimpleFileSystem").field("revision", &self.0.revision).finish()
+    }
+}
+
+/// How a file is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpenMode {
+    Read,
+    ReadWrite,
+    /// Opens the file for reading and writing, creating it if it does not exist.
+    Create,
+}
+
+impl From<OpenMode> for u64 {
+    fn from(mode: OpenMode) -> Self {
+        use efi::protocols::file::{MODE_CREATE, MODE_READ, MODE_WRITE};
+        match mode {
+            OpenMode::Read => MODE_READ,
+            OpenMode::ReadWrite => MODE_READ | MODE_WRITE,
+            OpenMode::Create => MODE_READ | MODE_WRITE | MODE_CREATE,
+        }
+    }
+}
+
+/// Attributes of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct FileAttribute(u64);
+
+impl FileAttribute {
+    pub const READ_ONLY: FileAttribute = FileAttribute(efi::protocols::file::READ_ONLY);
+    pub const HIDDEN: FileAttribute = FileAttribute(efi::protocols::file::HIDDEN);
+    pub const SYSTEM: FileAttribute = FileAttribute(efi::protocols::file::SYSTEM);
+    pub const DIRECTORY: FileAttribute = FileAttribute(efi::protocols::file::DIRECTORY);
+    pub const ARCHIVE: FileAttribute = FileAttribute(efi::protocols::file::ARCHIVE);
+
+    /// No attribute.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Creates attributes from their raw value, ignoring the bits that are not valid attributes.
+    pub const fn from_bits_truncate(bits: u64) -> Self {
+        Self(bits & efi::protocols::file::VALID_ATTR)
+    }
+
+    /// The raw value of the attributes.
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if every attribute of `other` is set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for FileAttribute {
+    type Output = FileAttribute;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        FileAttribute(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for FileAttribute {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Position to move to with [`File::seek`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    Start(u64),
+    /// Offset from the end of the file, moving past the end of a file extends it on the next write.
+    End(i64),
+    Current(i64),
+}
+
+/// An open file or directory, closed on drop.
+pub struct File(pub(crate) *mut FileProtocol);
+
+impl File {
+    /// Takes ownership of an open file.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be an open file that is not closed elsewhere, or null.
+    pub unsafe fn from_raw(protocol: *mut FileProtocol) -> Option<Self> {
+        (!protocol.is_null()).then_some(Self(protocol))
+    }
+
+    /// Gives up ownership of the file, which is then not closed on drop.
+    pub fn into_raw(self) -> *mut FileProtocol {
+        let protocol = self.0;
+        core::mem::forget(self);
+        protocol
+    }
+
+    pub(crate) fn protocol(&self) -> &FileProtocol {
+        //SAFETY: The file stays open for the life of the wrapper.
+        unsafe { &*self.0 }
+    }
+
+    /// Opens a file relative to this directory, or from the root of the volume if `name` starts with `\`.
+    ///
+    /// `attributes` are only used when the file is created.
+    pub fn open(&self, name: &Str16, mode: OpenMode, attributes: FileAttribute) -> Result<File, EfiError> {
+        let mut file = ptr::null_mut();
+        // The name is only read by the protocol.
+        match unsafe {
+            r_efi::efi_call(
+                self.protocol().open,
+                (self.0, &mut file, name.as_ptr() as *mut u16, mode.into(), attributes.bits()),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            //SAFETY: The protocol gave a valid file that is now owned by the wrapper.
+            _ => unsafe { File::from_raw(file) }.ok_or(efi::Status::DEVICE_ERROR.into()),
+        }
+    }
+
+    /// Reads from the current position, returns the number of bytes read, 0 at the end of the file.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        let mut size = buffer.len();
+        match unsafe { r_efi::efi_call(self.protocol().read, (self.0, &mut size, buffer.as_mut_ptr() as *mut c_void)) }
+        {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Reads from the current position to the end of the file.
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>, EfiError> {
+        let mut content = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            match self.read(&mut chunk)? {
+                0 => return Ok(content),
+                read => content.extend_from_slice(&chunk[..read]),
+            }
+        }
+    }
+
+    /// Writes at the current position, returns the number of bytes written.
+    pub fn write(&mut self, buffer: &[u8]) -> Result<usize, EfiError> {
+        let mut size = buffer.len();
+        // The buffer is only read by the protocol.
+        match unsafe { r_efi::efi_call(self.protocol().write, (self.0, &mut size, buffer.as_ptr() as *mut c_void)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Writes the whole buffer at the current position.
+    pub fn write_all(&mut self, mut buffer: &[u8]) -> Result<(), EfiError> {
+        while !buffer.is_empty() {
+            match self.write(buffer)? {
+                0 => return Err(efi::Status::VOLUME_FULL.into()),
+                written => buffer = &buffer[written..],
+            }
+        }
+        Ok(())
+    }
+
+    /// The current position in the file.
+    pub fn position(&self) -> Result<u64, EfiError> {
+        let mut position = 0;
+        match unsafe { r_efi::efi_call(self.protocol().get_position, (self.0, &mut position)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(position),
+        }
+    }
+
+    /// Sets the current position in the file, `u64::MAX` moves to the end of the file.
+    pub fn set_position(&mut self, position: u64) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().set_position, (self.0, position)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Moves the current position, returns the new position from the start of the file.
+    ///
+    /// # Errors
+    ///
+    /// * `INVALID_PARAMETER` if the position would be before the start of the file.
+    pub fn seek(&mut self, seek: SeekFrom) -> Result<u64, EfiError> {
+        let (base, offset) = match seek {
+            SeekFrom::Start(position) => (position, 0),
+            SeekFrom::End(offset) => {
+                self.set_position(u64::MAX)?;
+                (self.position()?, offset)
+            }
+            SeekFrom::Current(offset) => (self.position()?, offset),
+        };
+        let position = base.checked_add_signed(offset).ok_or(efi::Status::INVALID_PARAMETER)?;
+        self.set_position(position)?;
+        Ok(position)
+    }
+
+    /// Reads the information of the given type, growing the buffer to the size needed by the protocol.
+    fn get_info(&self, information_type: &efi::Guid) -> Result<Vec<u8>, EfiError> {
+        let mut buffer = Vec::new();
+        loop {
+            let mut size = buffer.len();
+            // The GUID is only read by the protocol.
+            let guid = information_type as *const efi::Guid as *mut efi::Guid;
+            match unsafe {
+                r_efi::efi_call(self.protocol().get_info, (self.0, guid, &mut size, buffer.as_mut_ptr() as *mut c_void))
+            } {
+                efi::Status::BUFFER_TOO_SMALL if size > buffer.len() => buffer.resize(size, 0),
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    buffer.truncate(size);
+                    return Ok(buffer);
+                }
+            }
+        }
+    }
+
+    fn set_info(&mut self, information_type: &efi::Guid, mut buffer: Vec<u8>) -> Result<(), EfiError> {
+        // The GUID is only read by the protocol.
+        let guid = information_type as *const efi::Guid as *mut efi::Guid;
+        match unsafe {
+            r_efi::efi_call(self.protocol().set_info, (self.0, guid, buffer.len(), buffer.as_mut_ptr() as *mut c_void))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Information about the file, such as its size, attributes and times.
+    pub fn info(&self) -> Result<FileInfo, EfiError> {
+        FileInfo::from_bytes(&self.get_info(&efi::protocols::file::INFO_ID)?)
+            .ok_or(efi::Status::VOLUME_CORRUPTED.into())
+    }
+
+    /// Updates the information about the file, the file is renamed if the name changed.
+    ///
+    /// The physical size of the information is ignored, times set to zero are not changed.
+    pub fn set_file_info(&mut self, info: &FileInfo) -> Result<(), EfiError> {
+        self.set_info(&efi::protocols::file::INFO_ID, info.to_bytes())
+    }
+
+    /// Renames the file, a name starting with `\` also moves it to another directory of the volume.
+    pub fn rename(&mut self, new_name: &Str16) -> Result<(), EfiError> {
+        let info = FileInfo { file_name: String16::from(new_name), ..self.info()? };
+        self.set_file_info(&info)
+    }
+
+    /// Truncates or extends the file, added bytes are zeroes.
+    pub fn set_len(&mut self, file_size: u64) -> Result<(), EfiError> {
+        let info = FileInfo { file_size, ..self.info()? };
+        self.set_file_info(&info)
+    }
+
+    /// Sets the attributes of the file, [`FileAttribute::DIRECTORY`] can not be changed.
+    pub fn set_attributes(&mut self, attribute: FileAttribute) -> Result<(), EfiError> {
+        let info = FileInfo { attribute, ..self.info()? };
+        self.set_file_info(&info)
+    }
+
+    /// Information about the volume of the file, such as its size and free space.
+    pub fn file_system_info(&self) -> Result<FileSystemInfo, EfiError> {
+        let bytes = self.get_info(&efi::protocols::file::SYSTEM_INFO_ID)?;
+        FileSystemInfo::from_bytes(&bytes).ok_or(efi::Status::VOLUME_CORRUPTED.into())
+    }
+
+    /// The space available on the volume of the file, in bytes.
+    pub fn free_space(&self) -> Result<u64, EfiError> {
+        self.file_system_info().map(|info| info.free_space)
+    }
+
+    /// The label of the volume of the file.
+    pub fn volume_label(&self) -> Result<String16, EfiError> {
+        let bytes = self.get_info(&efi::protocols::file::SYSTEM_VOLUME_LABEL_ID)?;
+        file_info::volume_label_from_bytes(&bytes).ok_or(efi::Status::VOLUME_CORRUPTED.into())
+    }
+
+    /// Sets the label of the volume of the file.
+    pub fn set_volume_label(&mut self, label: &Str16) -> Result<(), EfiError> {
+        let bytes = label.as_slice_with_nul().iter().flat_map(|c| c.to_le_bytes()).collect();
+        self.set_info(&efi::protocols::file::SYSTEM_VOLUME_LABEL_ID, bytes)
+    }
+
+    /// Writes the pending data of the file to the device.
+    pub fn flush(&mut self) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.protocol().flush, (self.0,)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Closes the file, the same as dropping it but with the status of the close.
+    pub fn close(self) -> Result<(), EfiError> {
+        let protocol = self.into_raw();
+        //SAFETY: The file was open and is closed only once since the wrapper is consumed.
+        match unsafe { ((*protocol).close)(protocol) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Deletes the file, which is closed even if it could not be deleted.
+    ///
+    /// # Errors
+    ///
+    /// * `WARN_DELETE_FAILURE` if the file was closed but not deleted.
+    pub fn delete(self) -> Result<(), EfiError> {
+        let protocol = self.into_raw();
+        //SAFETY: The file was open and is closed by delete, the wrapper is consumed.
+        match unsafe { ((*protocol).delete)(protocol) } {
+            efi::Status::SUCCESS => Ok(()),
+            s => Err(s.into()),
+        }
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = unsafe { r_efi::efi_call(self.protocol().close, (self.0,)) };
+    }
+}
+
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("File").field(&self.0).finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    //! In-memory file system implementing the protocols.
+    use super::*;
+    use alloc::{boxed::Box, rc::Rc, string::String, vec};
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, slice};
+    use std::collections::BTreeMap;
+    use ucs2::String16;
+
+    #[derive(Debug, Clone)]
+    pub(crate) enum Node {
+        File(Vec<u8>, u64),
+        Directory,
+    }
+
+    /// Entries of the volume by path, with its label.
+    pub(crate) struct TestVolume {
+        entries: BTreeMap<String, Node>,
+        pub label: String,
+    }
+
+    impl core::ops::Deref for TestVolume {
+        type Target = BTreeMap<String, Node>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.entries
+        }
+    }
+
+    impl core::ops::DerefMut for TestVolume {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.entries
+        }
+    }
+
+    pub(crate) type Volume = Rc<RefCell<TestVolume>>;
+
+    /// An open file, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    pub(crate) struct TestFile {
+        protocol: FileProtocol,
+        pub volume: Volume,
+        pub path: String,
+        pub position: u64,
+        pub writable: bool,
+    }
+
+    pub(crate) fn test_file<'a>(this: *mut FileProtocol) -> &'a mut TestFile {
+        unsafe { &mut *(this as *mut TestFile) }
+    }
+
+    /// The open files, to check that they are all closed.
+    pub(crate) static OPEN_FILES: std::sync::atomic::AtomicIsize = std::sync::atomic::AtomicIsize::new(0);
+
+    pub(crate) fn open_test_file(volume: &Volume, path: String, writable: bool) -> *mut FileProtocol {
+        OPEN_FILES.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let file = Box::leak(Box::new(TestFile {
+            protocol: FileProtocol {
+                revision: efi::protocols::file::REVISION,
+                open,
+                close,
+                delete,
+                read,
+                write,
+                get_position,
+                set_position,
+                get_info,
+                set_info,
+                flush,
+                open_ex,
+                read_ex,
+                write_ex,
+                flush_ex,
+            },
+            volume: volume.clone(),
+            path,
+            position: 0,
+            writable,
+        }));
+        &mut file.protocol
+    }
+
+    /// Joins a name to the path of a directory, `\` being the root.
+    pub(crate) fn join(directory: &str, name: &str) -> String {
+        let mut path = if name.starts_with('\\') { String::new() } else { String::from(directory) };
+        for component in name.split('\\').filter(|c| !c.is_empty()) {
+            match component {
+                "." => (),
+                ".." => path.truncate(path.rfind('\\').unwrap_or(0)),
+                _ => {
+                    path.push('\\');
+                    path.push_str(component);
+                }
+            }
+        }
+        path
+    }
+
+    extern "efiapi" fn open(
+        this: *mut FileProtocol,
+        new: *mut *mut FileProtocol,
+        name: *mut u16,
+        mode: u64,
+        attributes: u64,
+    ) -> efi::Status {
+        let file = test_file(this);
+        let path = join(&file.path, &unsafe { Str16::from_ptr(name) }.to_string_lossy());
+        let mut volume = file.volume.borrow_mut();
+        if !volume.contains_key(&path) && !path.is_empty() {
+            if mode & efi::protocols::file::MODE_CREATE == 0 {
+                return efi::Status::NOT_FOUND;
+            }
+            let node = if attributes & efi::protocols::file::DIRECTORY != 0 {
+                Node::Directory
+            } else {
+                Node::File(Vec::new(), attributes)
+            };
+            volume.insert(path.clone(), node);
+        }
+        drop(volume);
+        let writable = mode & efi::protocols::file::MODE_WRITE != 0;
+        unsafe { *new = open_test_file(&file.volume, path, writable) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn close(this: *mut FileProtocol) -> efi::Status {
+        drop(unsafe { Box::from_raw(this as *mut TestFile) });
+        OPEN_FILES.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn delete(this: *mut FileProtocol) -> efi::Status {
+        let file = test_file(this);
+        let deleted = file.volume.borrow_mut().remove(&file.path).is_some();
+        close(this);
+        if deleted {
+            efi::Status::SUCCESS
+        } else {
+            efi::Status::WARN_DELETE_FAILURE
+        }
+    }
+
+    extern "efiapi" fn read(this: *mut FileProtocol, size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        let file = test_file(this);
+        let volume = file.volume.borrow();
+        match volume.get(&file.path) {
+            Some(Node::File(data, _)) => {
+                let start = (file.position as usize).min(data.len());
+                let read = unsafe { *size }.min(data.len() - start);
+                unsafe { slice::from_raw_parts_mut(buffer as *mut u8, read) }
+                    .copy_from_slice(&data[start..start + read]);
+                unsafe { *size = read };
+                file.position += read as u64;
+                efi::Status::SUCCESS
+            }
+            Some(Node::Directory) | None => {
+                let mut entries = vec![];
+                if !file.path.is_empty() {
+                    entries.push(file_info::test::info_bytes(".", 0, efi::protocols::file::DIRECTORY));
+                    entries.push(file_info::test::info_bytes("..", 0, efi::protocols::file::DIRECTORY));
+                }
+                let prefix = format!("{}\\", file.path);
+                for (path, node) in volume.iter() {
+                    match path.strip_prefix(&prefix) {
+                        Some(name) if !name.contains('\\') => entries.push(match node {
+                            Node::File(data, attributes) => {
+                                file_info::test::info_bytes(name, data.len() as u64, *attributes)
+                            }
+                            Node::Directory => file_info::test::info_bytes(name, 0, efi::protocols::file::DIRECTORY),
+                        }),
+                        _ => (),
+                    }
+                }
+                match entries.get(file.position as usize) {
+                    Some(entry) if entry.len() > unsafe { *size } => {
+                        unsafe { *size = entry.len() };
+                        efi::Status::BUFFER_TOO_SMALL
+                    }
+                    Some(entry) => {
+                        unsafe { slice::from_raw_parts_mut(buffer as *mut u8, entry.len()) }.copy_from_slice(entry);
+                        unsafe { *size = entry.len() };
+                        file.position += 1;
+                        efi::Status::SUCCESS
+                    }
+                    None => {
+                        unsafe { *size = 0 };
+                        efi::Status::SUCCESS
+                    }
+                }
+            }
+        }
+    }
+
+    extern "efiapi" fn write(this: *mut FileProtocol, size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        let file = test_file(this);
+        if !file.writable {
+            return efi::Status::ACCESS_DENIED;
+        }
+        let mut volume = file.volume.borrow_mut();
+        match volume.get_mut(&file.path) {
+            Some(Node::File(data, _)) => {
+                let bytes = unsafe { slice::from_raw_parts(buffer as *const u8, *size) };
+                let start = file.position as usize;
+                if data.len() < start + bytes.len() {
+                    data.resize(start + bytes.len(), 0);
+                }
+                data[start..start + bytes.len()].copy_from_slice(bytes);
+                file.position += bytes.len() as u64;
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    extern "efiapi" fn get_position(this: *mut FileProtocol, position: *mut u64) -> efi::Status {
+        unsafe { *position = test_file(this).position };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_position(this: *mut FileProtocol, position: u64) -> efi::Status {
+        let file = test_file(this);
+        let len = match file.volume.borrow().get(&file.path) {
+            Some(Node::File(data, _)) => data.len() as u64,
+            // Directories can only be restarted.
+            _ if position == 0 => 0,
+            _ => return efi::Status::UNSUPPORTED,
+        };
+        file.position = if position == u64::MAX { len } else { position };
+        efi::Status::SUCCESS
+    }
+
+    fn file_info(file: &TestFile) -> Option<Vec<u8>> {
+        let name = file.path.rsplit('\\').next().unwrap_or_default();
+        match file.volume.borrow().get(&file.path) {
+            Some(Node::File(data, attributes)) => {
+                Some(file_info::test::info_bytes(name, data.len() as u64, *attributes))
+            }
+            Some(Node::Directory) => Some(file_info::test::info_bytes(name, 0, efi::protocols::file::DIRECTORY)),
+            None if file.path.is_empty() => Some(file_info::test::info_bytes("", 0, efi::protocols::file::DIRECTORY)),
+            None => None,
+        }
+    }
+
+    extern "efiapi" fn get_info(
+        this: *mut FileProtocol,
+        information_type: *mut efi::Guid,
+        size: *mut usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let file = test_file(this);
+        let information_type = unsafe { *information_type };
+        let info = if information_type == efi::protocols::file::INFO_ID {
+            file_info(file)
+        } else if information_type == efi::protocols::file::SYSTEM_INFO_ID {
+            Some(file_info::test::system_info_bytes(&file.volume.borrow().label, 0x100_0000))
+        } else if information_type == efi::protocols::file::SYSTEM_VOLUME_LABEL_ID {
+            let label = String16::try_from(file.volume.borrow().label.as_str()).unwrap();
+            Some(label.as_slice_with_nul().iter().flat_map(|c| c.to_le_bytes()).collect())
+        } else {
+            return efi::Status::UNSUPPORTED;
+        };
+        let Some(info) = info else {
+            return efi::Status::DEVICE_ERROR;
+        };
+        let available = unsafe { *size };
+        unsafe { *size = info.len() };
+        if available < info.len() {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(buffer as *mut u8, info.len()) }.copy_from_slice(&info);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_info(
+        this: *mut FileProtocol,
+        information_type: *mut efi::Guid,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let file = test_file(this);
+        let bytes = unsafe { slice::from_raw_parts(buffer as *const u8, size) };
+        let information_type = unsafe { *information_type };
+        if information_type == efi::protocols::file::SYSTEM_VOLUME_LABEL_ID {
+            file.volume.borrow_mut().label = file_info::volume_label_from_bytes(bytes).unwrap().to_string_lossy();
+            return efi::Status::SUCCESS;
+        }
+        if information_type != efi::protocols::file::INFO_ID {
+            return efi::Status::UNSUPPORTED;
+        }
+        if !file.writable {
+            return efi::Status::ACCESS_DENIED;
+        }
+        let info = FileInfo::from_bytes(bytes).unwrap();
+        let mut volume = file.volume.borrow_mut();
+        let Some(mut node) = volume.remove(&file.path) else {
+            return efi::Status::ACCESS_DENIED;
+        };
+        if let Node::File(data, attributes) = &mut node {
+            data.resize(info.file_size as usize, 0);
+            *attributes = info.attribute.bits();
+        }
+        let directory = &file.path[..file.path.rfind('\\').unwrap_or(0)];
+        file.path = join(directory, &info.file_name.to_string_lossy());
+        volume.insert(file.path.clone(), node);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn flush(this: *mut FileProtocol) -> efi::Status {
+        match test_file(this).writable {
+            true => efi::Status::SUCCESS,
+            false => efi::Status::ACCESS_DENIED,
+        }
+    }
+
+    extern "efiapi" fn open_ex(
+        _: *mut FileProtocol,
+        _: *mut *mut FileProtocol,
+        _: *mut u16,
+        _: u64,
+        _: u64,
+        _: *mut efi::protocols::file::IoToken,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn read_ex(_: *mut FileProtocol, _: *mut efi::protocols::file::IoToken) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn write_ex(_: *mut FileProtocol, _: *mut efi::protocols::file::IoToken) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn flush_ex(_: *mut FileProtocol, _: *mut efi::protocols::file::IoToken) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn open_volume(this: *mut SimpleFileSystemProtocol, root: *mut *mut FileProtocol) -> efi::Status {
+        // The volume is stored right after the protocol.
+        let volume = unsafe { &*(this.add(1) as *const Volume) };
+        unsafe { *root = open_test_file(volume, String::new(), true) };
+        efi::Status::SUCCESS
+    }
+
+    /// Boot services giving the Simple File System protocol of [`test_volume`] on handle 1.
+    pub(crate) fn test_boot_services() -> (Volume, MockBootServices) {
+        let entries = BTreeMap::from([
+            (String::from(r"\EFI"), Node::Directory),
+            (String::from(r"\EFI\Boot"), Node::Directory),
+            (String::from(r"\EFI\Boot\bootx64.efi"), Node::File(vec![0x4D, 0x5A], efi::protocols::file::ARCHIVE)),
+            (String::from(r"\readme.txt"), Node::File(b"Hello".to_vec(), efi::protocols::file::READ_ONLY)),
+        ]);
+        let volume: Volume = Rc::new(RefCell::new(TestVolume { entries, label: String::from("ESP") }));
+
+        #[repr(C)]
+        struct TestFileSystem(SimpleFileSystemProtocol, Volume);
+        let file_system = Box::leak(Box::new(TestFileSystem(
+            SimpleFileSystemProtocol { revision: efi::protocols::simple_file_system::REVISION, open_volume },
+            volume.clone(),
+        )));
+        let file_system_ptr = &mut file_system.0 as *mut SimpleFileSystemProtocol as usize;
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol::<protocol_handler::SimpleFileSystem, SimpleFileSystemProtocol>()
+            .withf(|handle, _| *handle == 1_usize as efi::Handle)
+            .once()
+            .returning(move |_, _| Ok(unsafe { &mut *(file_system_ptr as *mut SimpleFileSystemProtocol) }));
+        (volume, boot_services)
+    }
+
+    /// A volume with `\EFI\Boot\bootx64.efi` and `\readme.txt`.
+    pub(crate) fn test_volume() -> (Volume, File) {
+        let (volume, boot_services) = test_boot_services();
+        let mut file_system = SimpleFileSystem::get(&boot_services, 1_usize as efi::Handle).unwrap();
+        (volume, file_system.open_volume().unwrap())
+    }
+
+    pub(crate) fn name(s: &str) -> String16 {
+        String16::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_read() {
+        let (_volume, root) = test_volume();
+        let mut file = root.open(&name(r"\readme.txt"), OpenMode::Read, FileAttribute::empty()).unwrap();
+        let mut buffer = [0; 3];
+        assert_eq!(3, file.read(&mut buffer).unwrap());
+        assert_eq!(b"Hel", &buffer);
+        assert_eq!(b"lo", file.read_to_end().unwrap().as_slice());
+        assert_eq!(0, file.read(&mut buffer).unwrap());
+
+        let efi = root.open(&name("EFI"), OpenMode::Read, FileAttribute::empty()).unwrap();
+        let mut boot = efi.open(&name(r"Boot\bootx64.efi"), OpenMode::Read, FileAttribute::empty()).unwrap();
+        assert_eq!(vec![0x4D, 0x5A], boot.read_to_end().unwrap());
+
+        assert_eq!(
+            efi::Status::NOT_FOUND,
+            root.open(&name("missing"), OpenMode::Read, FileAttribute::empty()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_write_and_seek() {
+        let (volume, root) = test_volume();
+        let mut file = root.open(&name("log.txt"), OpenMode::Create, FileAttribute::ARCHIVE).unwrap();
+        file.write_all(b"Hello World").unwrap();
+        file.flush().unwrap();
+        assert_eq!(11, file.position().unwrap());
+
+        assert_eq!(6, file.seek(SeekFrom::Start(6)).unwrap());
+        file.write_all(b"UEFI!").unwrap();
+        assert_eq!(8, file.seek(SeekFrom::End(-3)).unwrap());
+        assert_eq!(5, file.seek(SeekFrom::Current(-3)).unwrap());
+        assert_eq!(efi::Status::INVALID_PARAMETER, file.seek(SeekFrom::Current(-6)).unwrap_err());
+        file.close().unwrap();
+
+        match volume.borrow().get(r"\log.txt") {
+            Some(Node::File(data, attributes)) => {
+                assert_eq!(b"Hello UEFI!", data.as_slice());
+                assert_eq!(efi::protocols::file::ARCHIVE, *attributes);
+            }
+            node => panic!("unexpected {node:?}"),
+        }
+
+        let mut read_only = root.open(&name("log.txt"), OpenMode::Read, FileAttribute::empty()).unwrap();
+        assert_eq!(efi::Status::ACCESS_DENIED, read_only.write(b"x").unwrap_err());
+    }
+
+    #[test]
+    fn test_delete_and_drop() {
+        let open_files = OPEN_FILES.load(std::sync::atomic::Ordering::SeqCst);
+        let (volume, root) = test_volume();
+        let file = root.open(&name("readme.txt"), OpenMode::ReadWrite, FileAttribute::empty()).unwrap();
+        file.delete().unwrap();
+        assert!(!volume.borrow().contains_key(r"\readme.txt"));
+
+        let raw = root.open(&name("EFI"), OpenMode::Read, FileAttribute::empty()).unwrap().into_raw();
+        drop(unsafe { File::from_raw(raw) });
+        drop(root);
+        // Other tests can run concurrently, only check that this test did not leak files.
+        assert!(OPEN_FILES.load(std::sync::atomic::Ordering::SeqCst) <= open_files);
+    }
+
+    #[test]
+    fn test_info() {
+        let (volume, root) = test_volume();
+        let info = root.info().unwrap();
+        assert!(info.is_directory());
+
+        let mut file = root.open(&name(r"EFI\Boot\bootx64.efi"), OpenMode::ReadWrite, FileAttribute::empty()).unwrap();
+        let info = file.info().unwrap();
+        assert_eq!(info.file_name, "bootx64.efi");
+        assert_eq!(2, info.file_size);
+
+        file.set_len(10).unwrap();
+        assert_eq!(10, file.info().unwrap().file_size);
+        file.set_attributes(FileAttribute::HIDDEN | FileAttribute::SYSTEM).unwrap();
+        assert_eq!(FileAttribute::HIDDEN | FileAttribute::SYSTEM, file.info().unwrap().attribute);
+
+        file.rename(&name("grubx64.efi")).unwrap();
+        assert_eq!(file.info().unwrap().file_name, "grubx64.efi");
+        assert!(volume.borrow().contains_key(r"\EFI\Boot\grubx64.efi"));
+        assert!(!volume.borrow().contains_key(r"\EFI\Boot\bootx64.efi"));
+
+        let mut read_only = root.open(&name("readme.txt"), OpenMode::Read, FileAttribute::empty()).unwrap();
+        assert_eq!(efi::Status::ACCESS_DENIED, read_only.set_len(0).unwrap_err());
+    }
+
+    #[test]
+    fn test_file_system_info() {
+        let (_volume, mut root) = test_volume();
+        let info = root.file_system_info().unwrap();
+        assert_eq!(info.volume_label, "ESP");
+        assert_eq!(512, info.block_size);
+        assert_eq!(0x100_0000, root.free_space().unwrap());
+
+        assert_eq!(root.volume_label().unwrap(), "ESP");
+        root.set_volume_label(&name("BOOT")).unwrap();
+        assert_eq!(root.volume_label().unwrap(), "BOOT");
+        assert_eq!(root.file_system_info().unwrap().volume_label, "BOOT");
+    }
+
+    #[test]
+    fn test_attributes() {
+        let attributes = FileAttribute::READ_ONLY | FileAttribute::HIDDEN;
+        assert!(attributes.contains(FileAttribute::HIDDEN));
+        assert!(!attributes.contains(FileAttribute::DIRECTORY));
+        assert_eq!(0x03, attributes.bits());
+        assert_eq!(FileAttribute::DIRECTORY, FileAttribute::from_bits_truncate(0x10 | 0x100));
+        assert_eq!(0x8000000000000003, u64::from(OpenMode::Create));
+    }
+}
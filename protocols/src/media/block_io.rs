@@ -0,0 +1,393 @@
+//! Block I/O protocol.
+//!
+//! [`BlockIo`] reads and writes whole blocks of a device. Buffers that do not meet the alignment required by the
+//! device are copied through an aligned intermediate buffer.
+//!
+//! [UEFI Spec Documentation: 13.9. Block I/O Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#block-i-o-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{ffi::c_void, fmt};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+type BlockIoProtocol = efi::protocols::block_io::Protocol;
+
+/// Description of the media of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MediaInfo {
+    /// Identifier of the current media, it changes when the media changes.
+    pub media_id: u32,
+    pub removable: bool,
+    pub present: bool,
+    /// The device is a partition of another device.
+    pub logical_partition: bool,
+    pub read_only: bool,
+    pub write_caching: bool,
+    /// Size of a block, in bytes.
+    pub block_size: u32,
+    /// Alignment required for the buffers, 0 or 1 if there is none.
+    pub io_align: u32,
+    pub last_block: efi::Lba,
+}
+
+impl MediaInfo {
+    /// The number of blocks of the media.
+    pub const fn block_count(&self) -> u64 {
+        self.last_block + 1
+    }
+
+    /// The size of the media, in bytes.
+    pub const fn size(&self) -> u64 {
+        self.block_count() * self.block_size as u64
+    }
+}
+
+impl From<&efi::protocols::block_io::Media> for MediaInfo {
+    fn from(media: &efi::protocols::block_io::Media) -> Self {
+        Self {
+            media_id: media.media_id,
+            removable: media.removable_media,
+            present: media.media_present,
+            logical_partition: media.logical_partition,
+            read_only: media.read_only,
+            write_caching: media.write_caching,
+            block_size: media.block_size,
+            io_align: media.io_align,
+            last_block: media.last_block,
+        }
+    }
+}
+
+/// A buffer aligned for the device.
+pub(crate) struct AlignedBuffer {
+    storage: Vec<u8>,
+    offset: usize,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    pub(crate) fn new(len: usize, align: usize) -> Self {
+        let storage = vec![0; len + align - 1];
+        let offset = storage.as_ptr().align_offset(align);
+        Self { storage, offset, len }
+    }
+
+    /// An aligned copy of `data`.
+    pub(crate) fn from_slice(data: &[u8], align: usize) -> Self {
+        let mut buffer = Self::new(data.len(), align);
+        buffer.as_mut_slice().copy_from_slice(data);
+        buffer
+    }
+
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        &self.storage[self.offset..self.offset + self.len]
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.storage[self.offset..self.offset + self.len]
+    }
+}
+
+/// Typed access to an instance of the Block I/O protocol.
+///
+/// ```ignore
+/// let mut block_io = BlockIo::get(&boot_services, device_handle)?;
+/// let mut mbr = vec![0; block_io.media().block_size as usize];
+/// block_io.read_blocks(0, &mut mbr)?;
+/// ```
+pub struct BlockIo(&'static mut BlockIoProtocol);
+
+impl BlockIo {
+    /// Locates the first instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::BlockIo, None).map(Self)
+    }
+
+    /// Gets the instance of the protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::BlockIo).map(Self)
+    }
+
+    /// The current media of the device.
+    pub fn media(&self) -> MediaInfo {
+        //SAFETY: The protocol keeps its media valid while it is installed.
+        MediaInfo::from(unsafe { &*self.0.media })
+    }
+
+    /// Resets the device, with an exhaustive check of the hardware if `extended_verification` is set.
+    pub fn reset(&mut self, extended_verification: bool) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.0.reset, (self.0, extended_verification.into())) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// An intermediate buffer if `buffer` does not meet the alignment of the device.
+    fn bounce_buffer(media: &MediaInfo, buffer: &[u8]) -> Option<AlignedBuffer> {
+        let align = media.io_align.max(1) as usize;
+        (buffer.as_ptr() as usize % align != 0).then(|| AlignedBuffer::new(buffer.len(), align))
+    }
+
+    /// Reads the blocks starting at `lba` into `buffer`, whose size must be a multiple of the block size.
+    pub fn read_blocks(&mut self, lba: efi::Lba, buffer: &mut [u8]) -> Result<(), EfiError> {
+        let media = self.media();
+        let mut bounce = Self::bounce_buffer(&media, buffer);
+        let target = match bounce.as_mut() {
+            Some(bounce) => bounce.as_mut_slice(),
+            None => &mut *buffer,
+        };
+        match unsafe {
+            r_efi::efi_call(
+                self.0.read_blocks,
+                (self.0, media.media_id, lba, target.len(), target.as_mut_ptr() as *mut c_void),
+            )
+        } {
+            s if s.is_error() => return Err(s.into()),
+            _ => (),
+        }
+        if let Some(mut bounce) = bounce {
+            buffer.copy_from_slice(bounce.as_mut_slice());
+        }
+        Ok(())
+    }
+
+    /// Writes `buffer` to the blocks starting at `lba`, its size must be a multiple of the block size.
+    pub fn write_blocks(&mut self, lba: efi::Lba, buffer: &[u8]) -> Result<(), EfiError> {
+        let media = self.media();
+        let mut bounce = Self::bounce_buffer(&media, buffer);
+        let source = match bounce.as_mut() {
+            Some(bounce) => {
+                bounce.as_mut_slice().copy_from_slice(buffer);
+                bounce.as_mut_slice()
+            }
+            // The buffer is only read by the protocol.
+            None => buffer,
+        };
+        match unsafe {
+            r_efi::efi_call(
+                self.0.write_blocks,
+                (self.0, media.media_id, lba, source.len(), source.as_ptr() as *mut c_void),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the cached blocks to the device.
+    pub fn flush(&mut self) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.0.flush_blocks, (self.0,)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut BlockIoProtocol> for BlockIo {
+    fn from(protocol: &'static mut BlockIoProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for BlockIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockIo").field("media", &self.media()).finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, ptr, slice};
+
+    const BLOCK_SIZE: usize = 16;
+    const IO_ALIGN: u32 = 8;
+
+    /// Disk requiring buffers aligned on 8 bytes, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    pub(crate) struct TestDisk {
+        protocol: BlockIoProtocol,
+        media: efi::protocols::block_io::Media,
+        pub data: RefCell<Vec<u8>>,
+        flushed: bool,
+    }
+
+    fn test_disk<'a>(this: *mut BlockIoProtocol) -> &'a mut TestDisk {
+        unsafe { &mut *(this as *mut TestDisk) }
+    }
+
+    /// The range of the disk accessed by a transfer, or the error of the transfer.
+    fn check_transfer(
+        disk: &TestDisk,
+        media_id: u32,
+        lba: efi::Lba,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> Result<core::ops::Range<usize>, EfiError> {
+        if media_id != disk.media.media_id {
+            return Err(efi::Status::MEDIA_CHANGED.into());
+        }
+        let block_size = disk.media.block_size as usize;
+        if size % block_size != 0 {
+            return Err(efi::Status::BAD_BUFFER_SIZE.into());
+        }
+        if buffer as usize % IO_ALIGN as usize != 0 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let start = lba as usize * block_size;
+        if start + size > disk.data.borrow().len() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        Ok(start..start + size)
+    }
+
+    extern "efiapi" fn reset(this: *mut BlockIoProtocol, _: efi::Boolean) -> efi::Status {
+        test_disk(this).data.borrow_mut().fill(0);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_blocks(
+        this: *mut BlockIoProtocol,
+        media_id: u32,
+        lba: efi::Lba,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let disk = test_disk(this);
+        match check_transfer(disk, media_id, lba, size, buffer) {
+            Ok(range) => {
+                unsafe { slice::from_raw_parts_mut(buffer as *mut u8, size) }
+                    .copy_from_slice(&disk.data.borrow()[range]);
+                efi::Status::SUCCESS
+            }
+            Err(status) => status.into(),
+        }
+    }
+
+    extern "efiapi" fn write_blocks(
+        this: *mut BlockIoProtocol,
+        media_id: u32,
+        lba: efi::Lba,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let disk = test_disk(this);
+        if disk.media.read_only {
+            return efi::Status::WRITE_PROTECTED;
+        }
+        match check_transfer(disk, media_id, lba, size, buffer) {
+            Ok(range) => {
+                disk.data.borrow_mut()[range]
+                    .copy_from_slice(unsafe { slice::from_raw_parts(buffer as *const u8, size) });
+                disk.flushed = false;
+                efi::Status::SUCCESS
+            }
+            Err(status) => status.into(),
+        }
+    }
+
+    extern "efiapi" fn flush_blocks(this: *mut BlockIoProtocol) -> efi::Status {
+        test_disk(this).flushed = true;
+        efi::Status::SUCCESS
+    }
+
+    /// Disk of 4 blocks of 16 bytes.
+    fn block_io() -> (BlockIo, &'static TestDisk) {
+        test_block_io((0..4 * BLOCK_SIZE as u8).collect(), BLOCK_SIZE as u32)
+    }
+
+    /// Disk holding `data`, whose size is a multiple of `block_size`.
+    pub(crate) fn test_block_io(data: Vec<u8>, block_size: u32) -> (BlockIo, &'static TestDisk) {
+        let last_block = (data.len() / block_size as usize) as u64 - 1;
+        let disk = Box::leak(Box::new(TestDisk {
+            protocol: BlockIoProtocol {
+                revision: efi::protocols::block_io::REVISION,
+                media: ptr::null(),
+                reset,
+                read_blocks,
+                write_blocks,
+                flush_blocks,
+            },
+            media: efi::protocols::block_io::Media {
+                media_id: 7,
+                removable_media: true,
+                media_present: true,
+                logical_partition: false,
+                read_only: false,
+                write_caching: true,
+                block_size,
+                io_align: IO_ALIGN,
+                last_block,
+                lowest_aligned_lba: 0,
+                logical_blocks_per_physical_block: 1,
+                optimal_transfer_length_granularity: 0,
+            },
+            data: RefCell::new(data),
+            flushed: true,
+        }));
+        disk.protocol.media = &disk.media;
+        let disk_ptr = disk as *mut TestDisk as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol::<protocol_handler::BlockIo, BlockIoProtocol>()
+            .withf(|handle, _| *handle == 1_usize as efi::Handle)
+            .once()
+            .returning(move |_, _| Ok(unsafe { &mut (*(disk_ptr as *mut TestDisk)).protocol }));
+        (BlockIo::get(&boot_services, 1_usize as efi::Handle).unwrap(), unsafe { &*(disk_ptr as *const TestDisk) })
+    }
+
+    /// A buffer of `len` bytes starting `misalignment` bytes after an aligned address.
+    fn buffer(storage: &mut [u64], misalignment: usize, len: usize) -> &mut [u8] {
+        let bytes = unsafe { slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut u8, storage.len() * 8) };
+        &mut bytes[misalignment..misalignment + len]
+    }
+
+    #[test]
+    fn test_media() {
+        let (block_io, _) = block_io();
+        let media = block_io.media();
+        assert_eq!(7, media.media_id);
+        assert!(media.removable && media.present && !media.read_only);
+        assert_eq!(4, media.block_count());
+        assert_eq!(64, media.size());
+    }
+
+    #[test]
+    fn test_read_blocks() {
+        let (mut block_io, _) = block_io();
+        let mut storage = [0u64; 8];
+        let aligned = buffer(&mut storage, 0, 2 * BLOCK_SIZE);
+        block_io.read_blocks(1, aligned).unwrap();
+        assert_eq!((16..48).collect::<Vec<u8>>(), aligned);
+
+        let misaligned = buffer(&mut storage, 3, BLOCK_SIZE);
+        block_io.read_blocks(3, misaligned).unwrap();
+        assert_eq!((48..64).collect::<Vec<u8>>(), misaligned);
+
+        assert_eq!(efi::Status::BAD_BUFFER_SIZE, block_io.read_blocks(0, &mut [0; 10]).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, block_io.read_blocks(4, &mut [0; BLOCK_SIZE]).unwrap_err());
+    }
+
+    #[test]
+    fn test_write_blocks() {
+        let (mut block_io, disk) = block_io();
+        let mut storage = [0u64; 8];
+        let misaligned = buffer(&mut storage, 5, 2 * BLOCK_SIZE);
+        misaligned.fill(0xAA);
+        block_io.write_blocks(2, misaligned).unwrap();
+        assert!(!disk.flushed);
+        assert!(disk.data.borrow()[32..].iter().all(|b| *b == 0xAA));
+        assert_eq!((0..32).collect::<Vec<u8>>(), disk.data.borrow()[..32]);
+
+        block_io.flush().unwrap();
+        assert!(disk.flushed);
+
+        block_io.reset(false).unwrap();
+        assert!(disk.data.borrow().iter().all(|b| *b == 0));
+    }
+}
@@ -0,0 +1,185 @@
+//! Directory listing.
+
+use alloc::{vec, vec::Vec};
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use r_efi::efi;
+use status::EfiError;
+
+use super::{File, FileInfo};
+
+/// An open directory, whose entries are listed with [`Directory::entries`].
+///
+/// ```ignore
+/// let mut root = Directory::from(file_system.open_volume()?);
+/// for entry in root.entries() {
+///     let entry = entry?;
+///     log::info!("{} {}", entry.file_name, entry.file_size);
+/// }
+/// ```
+pub struct Directory(File);
+
+impl Directory {
+    /// Iterates over the entries of the directory, from the first one.
+    ///
+    /// The entries include `.` and `..`, except in the root directory.
+    pub fn entries(&mut self) -> Entries<'_> {
+        let restarted = self.0.set_position(0);
+        Entries { directory: self, buffer: vec![0; Entries::INITIAL_BUFFER_SIZE], error: restarted.err(), done: false }
+    }
+
+    /// Gives back the underlying file.
+    pub fn into_file(self) -> File {
+        self.0
+    }
+}
+
+impl From<File> for Directory {
+    /// Uses an open file as a directory, listing the entries of a file that is not a directory fails.
+    fn from(file: File) -> Self {
+        Self(file)
+    }
+}
+
+impl Deref for Directory {
+    type Target = File;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Directory {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl fmt::Debug for Directory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Directory").field(&self.0).finish()
+    }
+}
+
+/// Iterator over the entries of a directory, returned by [`Directory::entries`].
+///
+/// The iteration ends after the first error.
+pub struct Entries<'a> {
+    directory: &'a mut Directory,
+    buffer: Vec<u8>,
+    error: Option<EfiError>,
+    done: bool,
+}
+
+impl Entries<'_> {
+    /// Large enough for most entries, the buffer grows when an entry has a longer name.
+    const INITIAL_BUFFER_SIZE: usize = 256;
+
+    fn read_entry(&mut self) -> Result<Option<FileInfo>, EfiError> {
+        loop {
+            let mut size = self.buffer.len();
+            let file = &self.directory.0;
+            match unsafe {
+                r_efi::efi_call(file.protocol().read, (file.0, &mut size, self.buffer.as_mut_ptr() as *mut _))
+            } {
+                // The size was set to the size needed by the entry.
+                efi::Status::BUFFER_TOO_SMALL if size > self.buffer.len() => self.buffer.resize(size, 0),
+                s if s.is_error() => return Err(s.into()),
+                _ if size == 0 => return Ok(None),
+                _ => {
+                    return FileInfo::from_bytes(&self.buffer[..size])
+                        .map(Some)
+                        .ok_or(efi::Status::VOLUME_CORRUPTED.into())
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Entries<'_> {
+    type Item = Result<FileInfo, EfiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(error) = self.error.take() {
+            self.done = true;
+            return Some(Err(error));
+        }
+        match self.read_entry() {
+            Ok(Some(info)) => Some(Ok(info)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(status) => {
+                self.done = true;
+                Some(Err(status))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Entries<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entries").field("directory", &self.directory).field("done", &self.done).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::media::{
+        test::{name, test_volume},
+        FileAttribute, OpenMode,
+    };
+    use alloc::string::String;
+
+    fn names(directory: &mut Directory) -> Vec<String> {
+        directory.entries().map(|entry| entry.unwrap().file_name.to_string_lossy()).collect()
+    }
+
+    #[test]
+    fn test_entries() {
+        let (_volume, root) = test_volume();
+        let mut root = Directory::from(root);
+        assert_eq!(vec!["EFI", "readme.txt"], names(&mut root));
+        // Listing again restarts from the first entry.
+        assert_eq!(2, root.entries().count());
+
+        let readme = root.entries().map(Result::unwrap).find(|entry| entry.file_name == "readme.txt").unwrap();
+        assert_eq!(5, readme.file_size);
+        assert!(readme.attribute.contains(FileAttribute::READ_ONLY));
+
+        let mut boot = Directory::from(root.open(&name(r"EFI\Boot"), OpenMode::Read, FileAttribute::empty()).unwrap());
+        let entries: Vec<_> = boot.entries().map(Result::unwrap).collect();
+        assert_eq!(
+            vec![".", "..", "bootx64.efi"],
+            entries.iter().map(|e| e.file_name.to_string_lossy()).collect::<Vec<_>>()
+        );
+        assert!(entries[0].is_directory());
+    }
+
+    #[test]
+    fn test_entries_long_name() {
+        let (_volume, root) = test_volume();
+        let long_name = "a".repeat(300);
+        root.open(&name(&long_name), OpenMode::Create, FileAttribute::empty()).unwrap();
+        let mut root = Directory::from(root);
+        assert!(names(&mut root).contains(&long_name));
+    }
+
+    #[test]
+    fn test_entries_of_file() {
+        let (_volume, root) = test_volume();
+        let file = root.open(&name("readme.txt"), OpenMode::Read, FileAttribute::empty()).unwrap();
+        let mut not_directory = Directory::from(file);
+        let mut entries = not_directory.entries();
+        assert!(entries.next().unwrap().is_err());
+        assert!(entries.next().is_none());
+    }
+}
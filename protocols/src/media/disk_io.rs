@@ -0,0 +1,578 @@
+//! Disk I/O and Disk I/O 2 protocols.
+//!
+//! [`DiskIo`] reads and writes a device at any byte offset. [`DiskIo2`] does the same and, with the `async` feature,
+//! gives futures completing when the device signals the end of a transfer, so several transfers can be in flight:
+//!
+//! ```ignore
+//! let disk_io = DiskIo2::get(&boot_services, device_handle)?;
+//! let media_id = BlockIo::get(&boot_services, device_handle)?.media().media_id;
+//! let (first, second) = (&mut buffer[..0x1000], &mut buffer[0x1000..]);
+//! let first = disk_io.read_at(&boot_services, media_id, 0, first)?;
+//! let second = disk_io.read_at(&boot_services, media_id, 0x1000, second)?;
+//! first.await?;
+//! second.await?;
+//! ```
+//!
+//! The media identifier of the transfers is the one of the Block I/O protocol of the device, see
+//! [`MediaInfo::media_id`](super::MediaInfo::media_id).
+//!
+//! [UEFI Spec Documentation: 13.7. Disk I/O Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#disk-i-o-protocol)
+//!
+//! [UEFI Spec Documentation: 13.8. Disk I/O 2 Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#disk-i-o-2-protocol)
+
+use core::{ffi::c_void, fmt, ptr};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+type DiskIoProtocol = efi::protocols::disk_io::Protocol;
+type DiskIo2Protocol = efi::protocols::disk_io2::Protocol;
+
+/// FFI definition of `EFI_DISK_IO2_TOKEN`, whose fields are private in r-efi.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Token {
+    /// Event signaled when the transfer completes.
+    pub event: efi::Event,
+    /// Status of the transfer once completed.
+    pub transaction_status: efi::Status,
+}
+
+/// Typed access to an instance of the Disk I/O protocol.
+pub struct DiskIo(&'static mut DiskIoProtocol);
+
+impl DiskIo {
+    /// Locates the first instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::DiskIo, None).map(Self)
+    }
+
+    /// Gets the instance of the protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::DiskIo).map(Self)
+    }
+
+    /// Reads `buffer.len()` bytes of the media at `offset`.
+    pub fn read(&mut self, media_id: u32, offset: u64, buffer: &mut [u8]) -> Result<(), EfiError> {
+        match unsafe {
+            r_efi::efi_call(
+                self.0.read_disk,
+                (self.0, media_id, offset, buffer.len(), buffer.as_mut_ptr() as *mut c_void),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes `buffer` to the media at `offset`.
+    pub fn write(&mut self, media_id: u32, offset: u64, buffer: &[u8]) -> Result<(), EfiError> {
+        // The buffer is only read by the protocol.
+        match unsafe {
+            r_efi::efi_call(self.0.write_disk, (self.0, media_id, offset, buffer.len(), buffer.as_ptr() as *mut c_void))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut DiskIoProtocol> for DiskIo {
+    fn from(protocol: &'static mut DiskIoProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for DiskIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskIo").field("revision", &self.0.revision).finish()
+    }
+}
+
+/// Typed access to an instance of the Disk I/O 2 protocol.
+pub struct DiskIo2(&'static mut DiskIo2Protocol);
+
+impl DiskIo2 {
+    /// Locates the first instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::DiskIo2, None).map(Self)
+    }
+
+    /// Gets the instance of the protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::DiskIo2).map(Self)
+    }
+
+    /// The protocol pointer given to its functions, which may be called while transfers are in flight.
+    fn protocol_ptr(&self) -> *mut DiskIo2Protocol {
+        &*self.0 as *const DiskIo2Protocol as *mut DiskIo2Protocol
+    }
+
+    /// Reads `buffer.len()` bytes of the media at `offset`, waiting for the end of the transfer.
+    pub fn read(&mut self, media_id: u32, offset: u64, buffer: &mut [u8]) -> Result<(), EfiError> {
+        let buffer_ptr = buffer.as_mut_ptr() as *mut c_void;
+        match unsafe {
+            r_efi::efi_call(self.0.read_disk_ex, (self.0, media_id, offset, ptr::null_mut(), buffer.len(), buffer_ptr))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes `buffer` to the media at `offset`, waiting for the end of the transfer.
+    pub fn write(&mut self, media_id: u32, offset: u64, buffer: &[u8]) -> Result<(), EfiError> {
+        // The buffer is only read by the protocol.
+        let buffer_ptr = buffer.as_ptr() as *mut c_void;
+        match unsafe {
+            r_efi::efi_call(self.0.write_disk_ex, (self.0, media_id, offset, ptr::null_mut(), buffer.len(), buffer_ptr))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the cached data to the media, waiting for the end of the transfer.
+    pub fn flush(&mut self) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.0.flush_disk_ex, (self.0, ptr::null_mut())) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Aborts the transfers in flight, which complete with [`efi::Status::ABORTED`].
+    pub fn cancel(&self) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.0.cancel, (self.protocol_ptr(),)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Starts reading `buffer.len()` bytes of the media at `offset`.
+    ///
+    /// The returned future resolves to the status of the transfer, dropping it before the end of the transfer cancels
+    /// every transfer in flight on the device.
+    #[cfg(feature = "async")]
+    pub fn read_at<'a, B: BootServices>(
+        &'a self,
+        boot_services: &'a B,
+        media_id: u32,
+        offset: u64,
+        buffer: &'a mut [u8],
+    ) -> Result<Transfer<'a, B>, EfiError> {
+        let (size, buffer_ptr) = (buffer.len(), buffer.as_mut_ptr() as *mut c_void);
+        Transfer::start(self, boot_services, |token| {
+            (self.0.read_disk_ex)(self.protocol_ptr(), media_id, offset, token, size, buffer_ptr)
+        })
+    }
+
+    /// Starts writing `buffer` to the media at `offset`.
+    ///
+    /// The returned future resolves to the status of the transfer, dropping it before the end of the transfer cancels
+    /// every transfer in flight on the device.
+    #[cfg(feature = "async")]
+    pub fn write_at<'a, B: BootServices>(
+        &'a self,
+        boot_services: &'a B,
+        media_id: u32,
+        offset: u64,
+        buffer: &'a [u8],
+    ) -> Result<Transfer<'a, B>, EfiError> {
+        // The buffer is only read by the protocol.
+        let (size, buffer_ptr) = (buffer.len(), buffer.as_ptr() as *mut c_void);
+        Transfer::start(self, boot_services, |token| {
+            (self.0.write_disk_ex)(self.protocol_ptr(), media_id, offset, token, size, buffer_ptr)
+        })
+    }
+}
+
+impl From<&'static mut DiskIo2Protocol> for DiskIo2 {
+    fn from(protocol: &'static mut DiskIo2Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for DiskIo2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskIo2").field("revision", &self.0.revision).finish()
+    }
+}
+
+/// Future of a transfer started with [`DiskIo2::read_at`] or [`DiskIo2::write_at`].
+#[cfg(feature = "async")]
+pub struct Transfer<'a, B: BootServices> {
+    disk_io: &'a DiskIo2,
+    boot_services: &'a B,
+    /// Boxed so that it does not move while the protocol updates it.
+    token: alloc::boxed::Box<Token>,
+    completed: bool,
+}
+
+#[cfg(feature = "async")]
+impl<'a, B: BootServices> Transfer<'a, B> {
+    fn start(
+        disk_io: &'a DiskIo2,
+        boot_services: &'a B,
+        transfer: impl FnOnce(*mut efi::protocols::disk_io2::Token) -> efi::Status,
+    ) -> Result<Self, EfiError> {
+        use boot_services::{event::EventType, tpl::Tpl};
+
+        let event = boot_services.create_event(EventType::NONE, Tpl::CALLBACK, None, None::<&'static ()>)?;
+        let mut token = alloc::boxed::Box::new(Token { event, transaction_status: efi::Status::SUCCESS });
+        match transfer(&mut *token as *mut Token as *mut efi::protocols::disk_io2::Token) {
+            s if s.is_error() => {
+                let _ = boot_services.close_event(event);
+                Err(s.into())
+            }
+            _ => Ok(Self { disk_io, boot_services, token, completed: false }),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<B: BootServices> core::future::Future for Transfer<'_, B> {
+    type Output = Result<(), EfiError>;
+
+    fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> core::task::Poll<Self::Output> {
+        use core::task::Poll;
+        if self.completed {
+            return Poll::Ready(Err(efi::Status::ALREADY_STARTED.into()));
+        }
+        match self.boot_services.check_event(self.token.event) {
+            Ok(()) => {
+                self.completed = true;
+                Poll::Ready(match self.token.transaction_status {
+                    s if s.is_error() => Err(s.into()),
+                    _ => Ok(()),
+                })
+            }
+            Err(status) if status == efi::Status::NOT_READY => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Err(status) => Poll::Ready(Err(status)),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<B: BootServices> Drop for Transfer<'_, B> {
+    fn drop(&mut self) {
+        if !self.completed && self.boot_services.check_event(self.token.event).is_err() {
+            // The protocol no longer uses the token once the transfer is aborted.
+            let _ = self.disk_io.cancel();
+        }
+        let _ = self.boot_services.close_event(self.token.event);
+    }
+}
+
+#[cfg(feature = "async")]
+impl<B: BootServices> fmt::Debug for Transfer<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transfer").field("token", &self.token).field("completed", &self.completed).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec::Vec};
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, slice};
+
+    const MEDIA_ID: u32 = 3;
+
+    /// Disk of 64 bytes, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestDisk {
+        protocol: DiskIoProtocol,
+        data: RefCell<Vec<u8>>,
+    }
+
+    /// The range of the disk accessed by a transfer, or the error of the transfer.
+    fn check_transfer(
+        data: &[u8],
+        media_id: u32,
+        offset: u64,
+        size: usize,
+    ) -> Result<core::ops::Range<usize>, EfiError> {
+        if media_id != MEDIA_ID {
+            return Err(efi::Status::MEDIA_CHANGED.into());
+        }
+        match (offset as usize).checked_add(size) {
+            Some(end) if end <= data.len() => Ok(offset as usize..end),
+            _ => Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+    }
+
+    fn read_data(data: &[u8], media_id: u32, offset: u64, size: usize, buffer: *mut c_void) -> efi::Status {
+        match check_transfer(data, media_id, offset, size) {
+            Ok(range) => {
+                unsafe { slice::from_raw_parts_mut(buffer as *mut u8, size) }.copy_from_slice(&data[range]);
+                efi::Status::SUCCESS
+            }
+            Err(status) => status.into(),
+        }
+    }
+
+    fn write_data(data: &mut [u8], media_id: u32, offset: u64, size: usize, buffer: *mut c_void) -> efi::Status {
+        match check_transfer(data, media_id, offset, size) {
+            Ok(range) => {
+                data[range].copy_from_slice(unsafe { slice::from_raw_parts(buffer as *const u8, size) });
+                efi::Status::SUCCESS
+            }
+            Err(status) => status.into(),
+        }
+    }
+
+    extern "efiapi" fn read_disk(
+        this: *mut DiskIoProtocol,
+        media_id: u32,
+        offset: u64,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let disk = unsafe { &*(this as *const TestDisk) };
+        read_data(&disk.data.borrow(), media_id, offset, size, buffer)
+    }
+
+    extern "efiapi" fn write_disk(
+        this: *mut DiskIoProtocol,
+        media_id: u32,
+        offset: u64,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let disk = unsafe { &*(this as *const TestDisk) };
+        write_data(&mut disk.data.borrow_mut(), media_id, offset, size, buffer)
+    }
+
+    #[test]
+    fn test_disk_io() {
+        let disk = Box::leak(Box::new(TestDisk {
+            protocol: DiskIoProtocol { revision: efi::protocols::disk_io::REVISION, read_disk, write_disk },
+            data: RefCell::new((0..64).collect()),
+        }));
+        let disk_ptr = disk as *mut TestDisk as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::DiskIo, DiskIoProtocol>()
+            .once()
+            .returning(move |_, _| Ok(unsafe { &mut (*(disk_ptr as *mut TestDisk)).protocol }));
+        let mut disk_io = DiskIo::locate(&boot_services).unwrap();
+
+        let mut buffer = [0; 5];
+        disk_io.read(MEDIA_ID, 13, &mut buffer).unwrap();
+        assert_eq!([13, 14, 15, 16, 17], buffer);
+        disk_io.write(MEDIA_ID, 62, &[0xAA, 0xBB]).unwrap();
+        assert_eq!([61, 0xAA, 0xBB], disk.data.borrow()[61..]);
+
+        assert_eq!(efi::Status::INVALID_PARAMETER, disk_io.read(MEDIA_ID, 60, &mut buffer).unwrap_err());
+        assert_eq!(efi::Status::MEDIA_CHANGED, disk_io.write(MEDIA_ID + 1, 0, &buffer).unwrap_err());
+    }
+
+    /// Disk of 64 bytes completing the transfers with a token only when asked, the protocol is the first field to be
+    /// found from its pointer.
+    #[repr(C)]
+    struct TestDisk2 {
+        protocol: DiskIo2Protocol,
+        data: RefCell<Vec<u8>>,
+        pending: RefCell<Vec<PendingTransfer>>,
+    }
+
+    /// A transfer in flight.
+    struct PendingTransfer {
+        token: *mut Token,
+        write: bool,
+        offset: u64,
+        size: usize,
+        buffer: *mut c_void,
+    }
+
+    /// Events signaled by [`TestDisk2::complete`], the event being the address of the token.
+    static SIGNALED: std::sync::Mutex<Vec<usize>> = std::sync::Mutex::new(Vec::new());
+
+    impl TestDisk2 {
+        fn from_protocol<'a>(this: *mut DiskIo2Protocol) -> &'a TestDisk2 {
+            unsafe { &*(this as *const TestDisk2) }
+        }
+
+        /// Completes the oldest transfer in flight with the given status.
+        fn complete(&self, status: efi::Status) {
+            let PendingTransfer { token, write, offset, size, buffer } = self.pending.borrow_mut().remove(0);
+            let status = match (status, write) {
+                (efi::Status::SUCCESS, false) => read_data(&self.data.borrow(), MEDIA_ID, offset, size, buffer),
+                (efi::Status::SUCCESS, true) => write_data(&mut self.data.borrow_mut(), MEDIA_ID, offset, size, buffer),
+                (status, _) => status,
+            };
+            let token = unsafe { &mut *token };
+            token.transaction_status = status;
+            SIGNALED.lock().unwrap().push(token.event as usize);
+        }
+
+        fn transfer(
+            this: *mut DiskIo2Protocol,
+            write: bool,
+            media_id: u32,
+            offset: u64,
+            token: *mut efi::protocols::disk_io2::Token,
+            size: usize,
+            buffer: *mut c_void,
+        ) -> efi::Status {
+            let disk = Self::from_protocol(this);
+            if token.is_null() {
+                return match write {
+                    false => read_data(&disk.data.borrow(), media_id, offset, size, buffer),
+                    true => write_data(&mut disk.data.borrow_mut(), media_id, offset, size, buffer),
+                };
+            }
+            if let Err(status) = check_transfer(&disk.data.borrow(), media_id, offset, size) {
+                return status.into();
+            }
+            disk.pending.borrow_mut().push(PendingTransfer { token: token as *mut Token, write, offset, size, buffer });
+            efi::Status::SUCCESS
+        }
+    }
+
+    extern "efiapi" fn cancel(this: *mut DiskIo2Protocol) -> efi::Status {
+        let disk = TestDisk2::from_protocol(this);
+        while !disk.pending.borrow().is_empty() {
+            disk.complete(efi::Status::ABORTED);
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_disk_ex(
+        this: *mut DiskIo2Protocol,
+        media_id: u32,
+        offset: u64,
+        token: *mut efi::protocols::disk_io2::Token,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        TestDisk2::transfer(this, false, media_id, offset, token, size, buffer)
+    }
+
+    extern "efiapi" fn write_disk_ex(
+        this: *mut DiskIo2Protocol,
+        media_id: u32,
+        offset: u64,
+        token: *mut efi::protocols::disk_io2::Token,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        TestDisk2::transfer(this, true, media_id, offset, token, size, buffer)
+    }
+
+    extern "efiapi" fn flush_disk_ex(_: *mut DiskIo2Protocol, _: *mut efi::protocols::disk_io2::Token) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    fn disk_io2() -> (DiskIo2, &'static TestDisk2, MockBootServices) {
+        let disk = Box::leak(Box::new(TestDisk2 {
+            protocol: DiskIo2Protocol {
+                revision: efi::protocols::disk_io2::REVISION,
+                cancel,
+                read_disk_ex,
+                write_disk_ex,
+                flush_disk_ex,
+            },
+            data: RefCell::new((0..64).collect()),
+            pending: RefCell::new(Vec::new()),
+        }));
+        let disk_ptr = disk as *mut TestDisk2 as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol::<protocol_handler::DiskIo2, DiskIo2Protocol>()
+            .once()
+            .returning(move |_, _| Ok(unsafe { &mut (*(disk_ptr as *mut TestDisk2)).protocol }));
+        let disk_io = DiskIo2::get(&boot_services, 1_usize as efi::Handle).unwrap();
+        (disk_io, unsafe { &*(disk_ptr as *const TestDisk2) }, boot_services)
+    }
+
+    #[test]
+    fn test_disk_io2() {
+        let (mut disk_io, disk, _) = disk_io2();
+        let mut buffer = [0; 4];
+        disk_io.read(MEDIA_ID, 60, &mut buffer).unwrap();
+        assert_eq!([60, 61, 62, 63], buffer);
+        disk_io.write(MEDIA_ID, 0, &[0xAA; 2]).unwrap();
+        assert_eq!([0xAA, 0xAA, 2], disk.data.borrow()[..3]);
+        disk_io.flush().unwrap();
+        assert_eq!(efi::Status::INVALID_PARAMETER, disk_io.read(MEDIA_ID, 61, &mut buffer).unwrap_err());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_transfers() {
+        use core::{
+            future::Future,
+            pin::pin,
+            task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+        };
+
+        const VTABLE: RawWakerVTable =
+            RawWakerVTable::new(|_| RawWaker::new(ptr::null(), &VTABLE), |_| {}, |_| {}, |_| {});
+        let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+        let mut cx = Context::from_waker(&waker);
+
+        let (disk_io, disk, mut boot_services) = disk_io2();
+        let next_event = std::sync::atomic::AtomicUsize::new(0x100);
+        boot_services.expect_create_event::<Option<&'static ()>>().returning(move |_, _, _, _| {
+            Ok(next_event.fetch_add(1, std::sync::atomic::Ordering::SeqCst) as efi::Event)
+        });
+        boot_services.expect_check_event().returning(|event| {
+            match SIGNALED.lock().unwrap().contains(&(event as usize)) {
+                true => Ok(()),
+                false => Err(efi::Status::NOT_READY.into()),
+            }
+        });
+        boot_services.expect_close_event().times(4).returning(|_| Ok(()));
+
+        let mut first = [0; 4];
+        let mut second = [0; 4];
+        {
+            let mut read = pin!(disk_io.read_at(&boot_services, MEDIA_ID, 8, &mut first).unwrap());
+            let mut write = pin!(disk_io.write_at(&boot_services, MEDIA_ID, 0, &[0xAA; 4]).unwrap());
+            let mut failed = pin!(disk_io.read_at(&boot_services, MEDIA_ID, 0, &mut second).unwrap());
+            assert_eq!(Poll::Pending, read.as_mut().poll(&mut cx));
+            assert_eq!(Poll::Pending, write.as_mut().poll(&mut cx));
+
+            disk.complete(efi::Status::SUCCESS);
+            assert_eq!(Poll::Ready(Ok(())), read.as_mut().poll(&mut cx));
+            assert_eq!(Poll::Pending, write.as_mut().poll(&mut cx));
+            disk.complete(efi::Status::SUCCESS);
+            assert_eq!(Poll::Ready(Ok(())), write.as_mut().poll(&mut cx));
+            disk.complete(efi::Status::DEVICE_ERROR);
+            assert_eq!(Poll::Ready(Err(efi::Status::DEVICE_ERROR.into())), failed.as_mut().poll(&mut cx));
+        }
+        assert_eq!([8, 9, 10, 11], first);
+        assert_eq!([0xAA; 4], disk.data.borrow()[..4]);
+
+        assert_eq!(
+            efi::Status::INVALID_PARAMETER,
+            disk_io.write_at(&boot_services, MEDIA_ID, 62, &[0; 4]).map(|_| ()).unwrap_err()
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_drop_cancels() {
+        let (disk_io, disk, mut boot_services) = disk_io2();
+        boot_services.expect_create_event::<Option<&'static ()>>().returning(|_, _, _, _| Ok(0x200 as efi::Event));
+        boot_services.expect_check_event().returning(|event| {
+            match SIGNALED.lock().unwrap().contains(&(event as usize)) {
+                true => Ok(()),
+                false => Err(efi::Status::NOT_READY.into()),
+            }
+        });
+        boot_services.expect_close_event().once().returning(|_| Ok(()));
+
+        let mut buffer = [0; 4];
+        drop(disk_io.read_at(&boot_services, MEDIA_ID, 0, &mut buffer).unwrap());
+        assert!(disk.pending.borrow().is_empty());
+        assert_eq!([0; 4], buffer);
+    }
+}
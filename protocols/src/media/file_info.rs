@@ -0,0 +1,220 @@
+//! Typed `EFI_FILE_INFO`, `EFI_FILE_SYSTEM_INFO` and `EFI_FILE_SYSTEM_VOLUME_LABEL`.
+
+use alloc::{vec, vec::Vec};
+use core::{mem, ptr};
+
+use r_efi::efi;
+use ucs2::{Str16, String16};
+
+use super::FileAttribute;
+
+type InfoHeader = efi::protocols::file::Info;
+type SystemInfoHeader = efi::protocols::file::SystemInfo;
+
+/// Reads the null-terminated string at the end of an information structure.
+fn read_name(bytes: &[u8]) -> Option<String16> {
+    let name: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    Str16::from_slice_until_nul(&name).ok().map(String16::from)
+}
+
+/// Information about a file or a directory.
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    /// Size of the content of the file, in bytes.
+    pub file_size: u64,
+    /// Space used by the file on the volume, in bytes.
+    pub physical_size: u64,
+    pub create_time: efi::Time,
+    pub last_access_time: efi::Time,
+    pub modification_time: efi::Time,
+    pub attribute: FileAttribute,
+    /// Name of the file, without its directory.
+    pub file_name: String16,
+}
+
+impl FileInfo {
+    /// Returns true if the entry is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.attribute.contains(FileAttribute::DIRECTORY)
+    }
+
+    /// Parses an `EFI_FILE_INFO` written by the protocol, `None` if it is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < mem::size_of::<InfoHeader>() {
+            return None;
+        }
+        //SAFETY: The buffer is large enough for the header, which is read without alignment requirement.
+        let header = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const InfoHeader) };
+        let size = usize::try_from(header.size).ok()?;
+        let file_name = read_name(bytes.get(mem::offset_of!(InfoHeader, file_name)..size)?)?;
+        Some(Self {
+            file_size: header.file_size,
+            physical_size: header.physical_size,
+            create_time: header.create_time,
+            last_access_time: header.last_access_time,
+            modification_time: header.modification_time,
+            attribute: FileAttribute::from_bits_truncate(header.attribute),
+            file_name,
+        })
+    }
+
+    /// Encodes the information as an `EFI_FILE_INFO`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.file_name.as_slice_with_nul();
+        let name_offset = mem::offset_of!(InfoHeader, file_name);
+        let header = InfoHeader {
+            size: (name_offset + name.len() * 2) as u64,
+            file_size: self.file_size,
+            physical_size: self.physical_size,
+            create_time: self.create_time,
+            last_access_time: self.last_access_time,
+            modification_time: self.modification_time,
+            attribute: self.attribute.bits(),
+            file_name: [],
+        };
+        let mut bytes = vec![0; name_offset];
+        //SAFETY: The buffer is large enough for the header, which is written without alignment requirement.
+        unsafe { ptr::write_unaligned(bytes.as_mut_ptr() as *mut InfoHeader, header) };
+        bytes.truncate(name_offset);
+        bytes.extend(name.iter().flat_map(|c| c.to_le_bytes()));
+        bytes
+    }
+}
+
+/// Information about the volume of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSystemInfo {
+    pub read_only: bool,
+    /// Size of the volume, in bytes.
+    pub volume_size: u64,
+    /// Space available on the volume, in bytes.
+    pub free_space: u64,
+    /// Size of the blocks of the volume, in bytes.
+    pub block_size: u32,
+    pub volume_label: String16,
+}
+
+impl FileSystemInfo {
+    /// Parses an `EFI_FILE_SYSTEM_INFO` written by the protocol, `None` if it is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < mem::size_of::<SystemInfoHeader>() {
+            return None;
+        }
+        //SAFETY: The buffer is large enough for the header, which is read without alignment requirement.
+        let header = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const SystemInfoHeader) };
+        let size = usize::try_from(header.size).ok()?;
+        let volume_label = read_name(bytes.get(mem::offset_of!(SystemInfoHeader, volume_label)..size)?)?;
+        Some(Self {
+            read_only: header.read_only.into(),
+            volume_size: header.volume_size,
+            free_space: header.free_space,
+            block_size: header.block_size,
+            volume_label,
+        })
+    }
+}
+
+/// Parses an `EFI_FILE_SYSTEM_VOLUME_LABEL` written by the protocol, `None` if it is malformed.
+pub fn volume_label_from_bytes(bytes: &[u8]) -> Option<String16> {
+    read_name(bytes)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Encodes an `EFI_FILE_INFO` the way the protocol writes it.
+    pub(crate) fn info_bytes(name: &str, file_size: u64, attribute: u64) -> Vec<u8> {
+        let name = String16::try_from(name).unwrap();
+        let size = mem::size_of::<InfoHeader>() + name.as_slice_with_nul().len() * 2;
+        let header = InfoHeader {
+            size: size as u64,
+            file_size,
+            physical_size: file_size.next_multiple_of(512),
+            create_time: efi::Time { year: 2024, month: 1, day: 2, ..Default::default() },
+            last_access_time: efi::Time::default(),
+            modification_time: efi::Time { year: 2024, month: 3, day: 4, ..Default::default() },
+            attribute,
+            file_name: [],
+        };
+        let mut bytes = vec![0; mem::size_of::<InfoHeader>()];
+        unsafe { ptr::write_unaligned(bytes.as_mut_ptr() as *mut InfoHeader, header) };
+        bytes.extend(name.as_slice_with_nul().iter().flat_map(|c| c.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let info = FileInfo::from_bytes(&info_bytes("bootx64.efi", 1000, efi::protocols::file::ARCHIVE)).unwrap();
+        assert_eq!(info.file_name, "bootx64.efi");
+        assert_eq!(1000, info.file_size);
+        assert_eq!(1024, info.physical_size);
+        assert_eq!(2024, info.create_time.year);
+        assert_eq!(3, info.modification_time.month);
+        assert_eq!(FileAttribute::ARCHIVE, info.attribute);
+        assert!(!info.is_directory());
+
+        let directory = FileInfo::from_bytes(&info_bytes("EFI", 0, efi::protocols::file::DIRECTORY)).unwrap();
+        assert!(directory.is_directory());
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let bytes = info_bytes("config.txt", 42, efi::protocols::file::READ_ONLY);
+        let info = FileInfo::from_bytes(&bytes).unwrap();
+        assert_eq!(bytes, info.to_bytes());
+
+        let renamed = FileInfo { file_name: String16::try_from("a").unwrap(), ..info };
+        let bytes = renamed.to_bytes();
+        assert_eq!(84, bytes.len());
+        assert_eq!(renamed.file_name, FileInfo::from_bytes(&bytes).unwrap().file_name);
+    }
+
+    /// Encodes an `EFI_FILE_SYSTEM_INFO` the way the protocol writes it.
+    pub(crate) fn system_info_bytes(label: &str, free_space: u64) -> Vec<u8> {
+        let label = String16::try_from(label).unwrap();
+        let label_offset = mem::offset_of!(SystemInfoHeader, volume_label);
+        let header = SystemInfoHeader {
+            size: (label_offset + label.as_slice_with_nul().len() * 2) as u64,
+            read_only: efi::Boolean::FALSE,
+            volume_size: 0x1000_0000,
+            free_space,
+            block_size: 512,
+            volume_label: [],
+        };
+        let mut bytes = vec![0; mem::size_of::<SystemInfoHeader>()];
+        unsafe { ptr::write_unaligned(bytes.as_mut_ptr() as *mut SystemInfoHeader, header) };
+        bytes.truncate(label_offset);
+        bytes.extend(label.as_slice_with_nul().iter().flat_map(|c| c.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn test_file_system_info() {
+        let info = FileSystemInfo::from_bytes(&system_info_bytes("ESP", 0x800_0000)).unwrap();
+        assert_eq!(
+            FileSystemInfo {
+                read_only: false,
+                volume_size: 0x1000_0000,
+                free_space: 0x800_0000,
+                block_size: 512,
+                volume_label: String16::try_from("ESP").unwrap()
+            },
+            info
+        );
+        assert!(FileSystemInfo::from_bytes(&system_info_bytes("ESP", 0)[..30]).is_none());
+        assert_eq!(volume_label_from_bytes(&[b'A', 0, 0, 0]).unwrap(), "A");
+        assert!(volume_label_from_bytes(&[b'A', 0]).is_none());
+    }
+
+    #[test]
+    fn test_from_bytes_malformed() {
+        let bytes = info_bytes("a", 0, 0);
+        assert!(FileInfo::from_bytes(&bytes[..40]).is_none());
+        assert!(FileInfo::from_bytes(&bytes[..bytes.len() - 2]).is_none());
+        // Without the null terminator.
+        let mut bytes = bytes[..bytes.len() - 2].to_vec();
+        bytes[0] -= 2;
+        assert!(FileInfo::from_bytes(&bytes).is_none());
+    }
+}
@@ -0,0 +1,149 @@
+//! One-shot helpers to read and write whole files.
+//!
+//! ```ignore
+//! let config = fs::read((&boot_services, device_handle), r"\EFI\Boot\config.txt")?;
+//! fs::write(&mut file_system, r"\EFI\Logs\boot.log", &log)?;
+//! ```
+//!
+//! Paths are relative to the root directory of the volume, both `\` and `/` separate their components.
+
+use alloc::vec::Vec;
+
+use boot_services::BootServices;
+use r_efi::efi;
+use status::EfiError;
+use ucs2::String16;
+
+use super::{File, FileAttribute, OpenMode, SimpleFileSystem};
+
+/// A volume the helpers can open the root directory of.
+pub trait Volume {
+    /// Calls `f` with the root directory of the volume.
+    fn with_root<R>(self, f: impl FnOnce(&File) -> Result<R, EfiError>) -> Result<R, EfiError>;
+}
+
+/// An open directory, paths are relative to it.
+impl Volume for &File {
+    fn with_root<R>(self, f: impl FnOnce(&File) -> Result<R, EfiError>) -> Result<R, EfiError> {
+        f(self)
+    }
+}
+
+impl Volume for &mut SimpleFileSystem {
+    fn with_root<R>(self, f: impl FnOnce(&File) -> Result<R, EfiError>) -> Result<R, EfiError> {
+        f(&self.open_volume()?)
+    }
+}
+
+/// The Simple File System protocol installed on a handle, such as the device handle of the loaded image.
+impl<B: BootServices> Volume for (&B, efi::Handle) {
+    fn with_root<R>(self, f: impl FnOnce(&File) -> Result<R, EfiError>) -> Result<R, EfiError> {
+        SimpleFileSystem::get(self.0, self.1)?.with_root(f)
+    }
+}
+
+impl Volume for SimpleFileSystem {
+    fn with_root<R>(mut self, f: impl FnOnce(&File) -> Result<R, EfiError>) -> Result<R, EfiError> {
+        (&mut self).with_root(f)
+    }
+}
+
+/// The components of a path, converted to UCS-2.
+fn components(path: &str) -> Result<Vec<String16>, EfiError> {
+    path.split(['\\', '/'])
+        .filter(|c| !c.is_empty())
+        .map(|c| String16::try_from(c).map_err(|_| efi::Status::INVALID_PARAMETER.into()))
+        .collect()
+}
+
+/// Reads the whole content of a file.
+pub fn read(volume: impl Volume, path: &str) -> Result<Vec<u8>, EfiError> {
+    let components = components(path)?;
+    volume.with_root(|root| {
+        let Some((name, parents)) = components.split_last() else {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        };
+        let mut directory = None;
+        for parent in parents {
+            let current = directory.as_ref().unwrap_or(root);
+            directory = Some(current.open(parent, OpenMode::Read, FileAttribute::empty())?);
+        }
+        let mut file = directory.as_ref().unwrap_or(root).open(name, OpenMode::Read, FileAttribute::empty())?;
+        if file.info()?.is_directory() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        file.read_to_end()
+    })
+}
+
+/// Writes `data` as the whole content of a file.
+///
+/// The file is created if it does not exist and its previous content is replaced otherwise. Missing parent
+/// directories are created.
+pub fn write(volume: impl Volume, path: &str, data: &[u8]) -> Result<(), EfiError> {
+    let components = components(path)?;
+    volume.with_root(|root| {
+        let Some((name, parents)) = components.split_last() else {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        };
+        let mut directory = None;
+        for parent in parents {
+            let current = directory.as_ref().unwrap_or(root);
+            directory = Some(current.open(parent, OpenMode::Create, FileAttribute::DIRECTORY)?);
+        }
+        let mut file = directory.as_ref().unwrap_or(root).open(name, OpenMode::Create, FileAttribute::empty())?;
+        if file.info()?.is_directory() {
+            return Err(efi::Status::ACCESS_DENIED.into());
+        }
+        file.set_len(0)?;
+        file.write_all(data)?;
+        file.flush()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::media::test::{test_boot_services, test_volume, Node};
+    use alloc::vec;
+
+    #[test]
+    fn test_read() {
+        let (_volume, root) = test_volume();
+        assert_eq!(b"Hello", read(&root, r"\readme.txt").unwrap().as_slice());
+        assert_eq!(vec![0x4D, 0x5A], read(&root, "EFI/Boot/bootx64.efi").unwrap());
+        assert_eq!(efi::Status::NOT_FOUND, read(&root, r"\EFI\Boot\grubx64.efi").unwrap_err());
+        assert_eq!(efi::Status::NOT_FOUND, read(&root, r"\Missing\grubx64.efi").unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, read(&root, r"\EFI").unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, read(&root, r"\").unwrap_err());
+    }
+
+    #[test]
+    fn test_read_from_handle() {
+        let (_volume, boot_services) = test_boot_services();
+        let content = read((&boot_services, 1_usize as efi::Handle), r"\readme.txt").unwrap();
+        assert_eq!(b"Hello", content.as_slice());
+    }
+
+    #[test]
+    fn test_write() {
+        let (volume, root) = test_volume();
+        write(&root, r"\EFI\Boot\bootx64.efi", b"new").unwrap();
+        assert!(matches!(&volume.borrow()[r"\EFI\Boot\bootx64.efi"], Node::File(data, _) if data == b"new"));
+
+        write(&root, "Logs/2024/boot.log", b"log").unwrap();
+        assert!(matches!(volume.borrow()[r"\Logs"], Node::Directory));
+        assert!(matches!(volume.borrow()[r"\Logs\2024"], Node::Directory));
+        assert_eq!(b"log", read(&root, r"\Logs\2024\boot.log").unwrap().as_slice());
+
+        assert_eq!(efi::Status::ACCESS_DENIED, write(&root, r"\EFI", b"").unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, write(&root, "", b"").unwrap_err());
+    }
+
+    #[test]
+    fn test_write_to_handle() {
+        let (volume, boot_services) = test_boot_services();
+        write((&boot_services, 1_usize as efi::Handle), r"\config.txt", b"timeout=5").unwrap();
+        assert!(matches!(&volume.borrow()[r"\config.txt"], Node::File(data, _) if data == b"timeout=5"));
+    }
+}
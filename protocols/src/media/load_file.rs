@@ -0,0 +1,355 @@
+//! Load File and Load File 2 protocols.
+//!
+//! [`load_file`] gets a file from the device of a device path, such as a file served over the network or an initrd
+//! provided by a boot loader, sizing the buffer as requested by the protocol.
+//!
+//! [`FileLoader`] is used by a driver to produce Load File 2 from a [`FileSource`], [`install_initrd`] installs it
+//! on the device path the Linux EFI stub loads its initrd from.
+//!
+//! [UEFI Spec Documentation: 13.1. Load File Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#load-file-protocol)
+//!
+//! [UEFI Spec Documentation: 13.2. Load File 2 Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#load-file-2-protocol)
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{ffi::c_void, fmt, ops::Deref, ptr, slice};
+
+use boot_services::{
+    protocol_handler::{self, InstalledProtocol, Protocol as ProtocolTrait},
+    BootServices,
+};
+use device_path::{node_types::VendorMedia, DevicePath, DevicePathBuilder};
+use r_efi::efi;
+use status::EfiError;
+
+type LoadFileProtocol = efi::protocols::load_file::Protocol;
+
+/// Vendor media GUID of the device path the Linux EFI stub loads its initrd from with Load File 2.
+pub const LINUX_EFI_INITRD_MEDIA_GUID: efi::Guid =
+    efi::Guid::from_fields(0x5568e427, 0x68fc, 0x4f3d, 0xac, 0x74, &[0xca, 0x55, 0x52, 0x31, 0xcc, 0x68]);
+
+/// Typed access to an instance of the Load File or Load File 2 protocol.
+pub struct LoadFile(&'static mut LoadFileProtocol);
+
+impl LoadFile {
+    /// Gets the instance of the Load File protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::LoadFile).map(Self)
+    }
+
+    /// Gets the instance of the Load File 2 protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get2<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::LoadFile2).map(Self)
+    }
+
+    /// Loads the file at `file_path`, the part of its device path after the device of the protocol.
+    ///
+    /// `boot_policy` requests a boot selection rather than an exact file, it is not supported by Load File 2.
+    pub fn load(&mut self, file_path: &DevicePath, boot_policy: bool) -> Result<Vec<u8>, EfiError> {
+        // The device path is only read by the protocol.
+        let file_path = file_path.as_ptr() as *mut efi::protocols::device_path::Protocol;
+        let mut buffer = Vec::new();
+        loop {
+            let mut size = buffer.len();
+            let buffer_ptr = if buffer.is_empty() { ptr::null_mut() } else { buffer.as_mut_ptr() as *mut c_void };
+            match unsafe {
+                r_efi::efi_call(self.0.load_file, (self.0, file_path, boot_policy.into(), &mut size, buffer_ptr))
+            } {
+                efi::Status::BUFFER_TOO_SMALL if size > buffer.len() => buffer.resize(size, 0),
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    buffer.truncate(size);
+                    return Ok(buffer);
+                }
+            }
+        }
+    }
+}
+
+impl From<&'static mut LoadFileProtocol> for LoadFile {
+    fn from(protocol: &'static mut LoadFileProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for LoadFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LoadFile").field(&(self.0 as *const LoadFileProtocol)).finish()
+    }
+}
+
+/// Loads the file at a device path with the Load File 2 protocol of its device, or the Load File protocol if the
+/// device has no Load File 2.
+pub fn load_file<B: BootServices>(boot_services: &B, device_path: &DevicePath) -> Result<Vec<u8>, EfiError> {
+    match device_path::locate_device_path(boot_services, &protocol_handler::LoadFile2, device_path) {
+        Ok((handle, file_path)) => LoadFile::get2(boot_services, handle)?.load(file_path, false),
+        Err(status) if status == efi::Status::NOT_FOUND => {
+            let (handle, file_path) =
+                device_path::locate_device_path(boot_services, &protocol_handler::LoadFile, device_path)?;
+            LoadFile::get(boot_services, handle)?.load(file_path, false)
+        }
+        Err(status) => Err(status),
+    }
+}
+
+/// Files served by a [`FileLoader`].
+pub trait FileSource {
+    /// The content of the file at `file_path`, the part of the device path after the device of the protocol.
+    ///
+    /// The path is only the end node when the whole device is the file, as for an initrd.
+    fn file(&self, file_path: &DevicePath) -> Result<&[u8], EfiError>;
+}
+
+/// A single file served for any path.
+impl FileSource for Vec<u8> {
+    fn file(&self, _file_path: &DevicePath) -> Result<&[u8], EfiError> {
+        Ok(self)
+    }
+}
+
+/// Load File 2 protocol with a [`FileLoader`] interface, used to produce the protocol.
+pub struct LoadFile2Producer;
+
+unsafe impl ProtocolTrait for LoadFile2Producer {
+    type Interface = FileLoader;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &efi::protocols::load_file2::PROTOCOL_GUID
+    }
+}
+
+impl Deref for LoadFile2Producer {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Implementation of the Load File 2 protocol produced by a driver.
+///
+/// The protocol reports the size of the file when the buffer of the caller is too small, as its callers expect.
+///
+/// ```ignore
+/// let installed = FileLoader::new(initrd).install(&boot_services, Some(handle_with_device_path))?;
+/// ```
+#[repr(C)]
+pub struct FileLoader {
+    protocol: LoadFileProtocol,
+    source: Box<dyn FileSource>,
+}
+
+impl FileLoader {
+    /// Creates a Load File 2 implementation serving the files of `source`.
+    pub fn new(source: impl FileSource + 'static) -> Self {
+        Self { protocol: LoadFileProtocol { load_file: Self::load_file }, source: Box::new(source) }
+    }
+
+    /// Installs the protocol on a handle, which should have the device path of the files, or on a new handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn install<B: BootServices>(
+        self,
+        boot_services: &B,
+        handle: Option<efi::Handle>,
+    ) -> Result<InstalledProtocol<'_, LoadFile2Producer, B>, EfiError> {
+        InstalledProtocol::install(boot_services, handle, &LoadFile2Producer, Box::new(self))
+    }
+
+    extern "efiapi" fn load_file(
+        this: *mut LoadFileProtocol,
+        file_path: *mut efi::protocols::device_path::Protocol,
+        boot_policy: efi::Boolean,
+        buffer_size: *mut usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        if this.is_null() || file_path.is_null() || buffer_size.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        if boot_policy.into() {
+            return efi::Status::UNSUPPORTED;
+        }
+        //SAFETY: The protocol is the first field of the FileLoader that was installed.
+        let this = unsafe { &*(this as *const FileLoader) };
+        //SAFETY: The caller gives a valid device path for the duration of the call.
+        let file = match unsafe { DevicePath::from_ptr(file_path) }.and_then(|path| this.source.file(path)) {
+            Ok(file) => file,
+            Err(status) => return status.into(),
+        };
+        //SAFETY: The pointer was checked for null.
+        let available = unsafe { ptr::replace(buffer_size, file.len()) };
+        if buffer.is_null() || available < file.len() {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        //SAFETY: The caller gives a buffer of `available` bytes, which holds the file.
+        unsafe { slice::from_raw_parts_mut(buffer as *mut u8, file.len()) }.copy_from_slice(file);
+        efi::Status::SUCCESS
+    }
+}
+
+impl fmt::Debug for FileLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileLoader").finish_non_exhaustive()
+    }
+}
+
+/// Installs `initrd` on a new handle with the device path of [`LINUX_EFI_INITRD_MEDIA_GUID`], where the Linux EFI
+/// stub loads it from.
+///
+/// The initrd and its device path stay installed for the rest of the boot.
+pub fn install_initrd<B: BootServices>(boot_services: &B, initrd: Vec<u8>) -> Result<efi::Handle, EfiError> {
+    let device_path = DevicePathBuilder::new().push(&VendorMedia::new(LINUX_EFI_INITRD_MEDIA_GUID, &[])).build();
+    let device_path = Box::leak(device_path.into_vec().into_boxed_slice());
+    //SAFETY: The interface is a valid device path that is never freed.
+    let handle = unsafe {
+        boot_services.install_protocol_interface_unchecked(
+            None,
+            protocol_handler::DevicePath.protocol_guid(),
+            device_path.as_mut_ptr() as *mut c_void,
+        )?
+    };
+    FileLoader::new(initrd).install(boot_services, Some(handle))?.leak();
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+    use boot_services::MockBootServices;
+    use device_path::DevicePathBuf;
+
+    /// Network device serving `boot.efi` with Load File, as `file_path` device path.
+    fn network_file_path() -> DevicePathBuf {
+        DevicePathBuilder::new()
+            .push(&device_path::node_types::FilePath::from(&*ucs2::String16::try_from("boot.efi").unwrap()))
+            .build()
+    }
+
+    extern "efiapi" fn network_load_file(
+        _: *mut LoadFileProtocol,
+        file_path: *mut efi::protocols::device_path::Protocol,
+        boot_policy: efi::Boolean,
+        buffer_size: *mut usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let file_path = unsafe { DevicePath::from_ptr(file_path) }.unwrap();
+        if file_path != &*network_file_path() {
+            return efi::Status::NOT_FOUND;
+        }
+        let file: &[u8] = if boot_policy.into() { b"boot selection" } else { b"MZ file" };
+        let available = unsafe { ptr::replace(buffer_size, file.len()) };
+        if available < file.len() {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(buffer as *mut u8, file.len()) }.copy_from_slice(file);
+        efi::Status::SUCCESS
+    }
+
+    static mut NETWORK: LoadFileProtocol = LoadFileProtocol { load_file: network_load_file };
+
+    #[test]
+    fn test_load() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol::<protocol_handler::LoadFile, LoadFileProtocol>()
+            .returning(|_, _| Ok(unsafe { &mut *ptr::addr_of_mut!(NETWORK) }));
+        let mut load_file = LoadFile::get(&boot_services, 1_usize as efi::Handle).unwrap();
+        assert_eq!(b"MZ file", load_file.load(&network_file_path(), false).unwrap().as_slice());
+        assert_eq!(b"boot selection", load_file.load(&network_file_path(), true).unwrap().as_slice());
+        let end = DevicePathBuilder::new().build();
+        assert_eq!(efi::Status::NOT_FOUND, load_file.load(&end, false).unwrap_err());
+    }
+
+    #[test]
+    fn test_load_file_falls_back_to_load_file() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_locate_device_path().returning(|protocol, _| match *protocol {
+            efi::protocols::load_file::PROTOCOL_GUID => Ok(1_usize as efi::Handle),
+            _ => Err(efi::Status::NOT_FOUND.into()),
+        });
+        boot_services
+            .expect_handle_protocol::<protocol_handler::LoadFile, LoadFileProtocol>()
+            .withf(|handle, _| *handle == 1_usize as efi::Handle)
+            .returning(|_, _| Ok(unsafe { &mut *ptr::addr_of_mut!(NETWORK) }));
+        assert_eq!(b"MZ file", load_file(&boot_services, &network_file_path()).unwrap().as_slice());
+    }
+
+    struct TestSource;
+
+    impl FileSource for TestSource {
+        fn file(&self, file_path: &DevicePath) -> Result<&[u8], EfiError> {
+            match file_path.is_end() {
+                true => Ok(b"whole device"),
+                false => Err(efi::Status::NOT_FOUND.into()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_file_loader() {
+        let loader = Box::leak(Box::new(FileLoader::new(TestSource)));
+        let load = loader.protocol.load_file;
+        let this = &mut loader.protocol as *mut LoadFileProtocol;
+        let end = DevicePathBuilder::new().build();
+        let end_ptr = end.as_ptr() as *mut efi::protocols::device_path::Protocol;
+
+        let mut size = 0;
+        assert_eq!(efi::Status::BUFFER_TOO_SMALL, unsafe {
+            r_efi::efi_call(load, (this, end_ptr, efi::Boolean::FALSE, &mut size, ptr::null_mut()))
+        });
+        assert_eq!(12, size);
+        let mut buffer = vec![0u8; 4];
+        size = buffer.len();
+        assert_eq!(efi::Status::BUFFER_TOO_SMALL, unsafe {
+            r_efi::efi_call(load, (this, end_ptr, efi::Boolean::FALSE, &mut size, buffer.as_mut_ptr() as *mut c_void))
+        });
+        buffer.resize(size, 0);
+        assert_eq!(efi::Status::SUCCESS, unsafe {
+            r_efi::efi_call(load, (this, end_ptr, efi::Boolean::FALSE, &mut size, buffer.as_mut_ptr() as *mut c_void))
+        });
+        assert_eq!(b"whole device", buffer.as_slice());
+
+        assert_eq!(efi::Status::UNSUPPORTED, unsafe {
+            r_efi::efi_call(load, (this, end_ptr, efi::Boolean::TRUE, &mut size, ptr::null_mut()))
+        });
+        assert_eq!(efi::Status::INVALID_PARAMETER, unsafe {
+            r_efi::efi_call(load, (this, end_ptr, efi::Boolean::FALSE, ptr::null_mut(), ptr::null_mut()))
+        });
+        let file = network_file_path();
+        let file_ptr = file.as_ptr() as *mut efi::protocols::device_path::Protocol;
+        assert_eq!(efi::Status::NOT_FOUND, unsafe {
+            r_efi::efi_call(load, (this, file_ptr, efi::Boolean::FALSE, &mut size, ptr::null_mut()))
+        });
+    }
+
+    #[test]
+    fn test_install_initrd() {
+        static INTERFACE: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_install_protocol_interface_unchecked()
+            .withf(|handle, protocol, _| handle.is_none() && *protocol == efi::protocols::device_path::PROTOCOL_GUID)
+            .once()
+            .returning(|_, _, interface| {
+                let device_path = unsafe { DevicePath::from_ptr(interface as *const _) }.unwrap();
+                let node = device_path.nodes().next().unwrap();
+                assert_eq!(Some(VendorMedia::new(LINUX_EFI_INITRD_MEDIA_GUID, &[])), node.parse::<VendorMedia>());
+                Ok(5_usize as efi::Handle)
+            });
+        boot_services
+            .expect_install_protocol_interface_unchecked()
+            .withf(|handle, protocol, _| {
+                *handle == Some(5_usize as efi::Handle) && *protocol == efi::protocols::load_file2::PROTOCOL_GUID
+            })
+            .once()
+            .returning(|handle, _, interface| {
+                INTERFACE.store(interface as usize, std::sync::atomic::Ordering::SeqCst);
+                Ok(handle.unwrap())
+            });
+
+        assert_eq!(5_usize as efi::Handle, install_initrd(&boot_services, vec![1, 2, 3]).unwrap());
+        let mut load_file = LoadFile::from(unsafe {
+            &mut *(INTERFACE.load(std::sync::atomic::Ordering::SeqCst) as *mut LoadFileProtocol)
+        });
+        assert_eq!(vec![1, 2, 3], load_file.load(&DevicePathBuilder::new().build(), false).unwrap());
+    }
+}
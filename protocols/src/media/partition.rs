@@ -0,0 +1,611 @@
+//! Partition discovery.
+//!
+//! [`PartitionInfo`] describes the partition a handle was created for by the partition driver, which is enough to
+//! find a partition by type with [`find_partition_by_type_guid`] or [`find_esp`]:
+//!
+//! ```ignore
+//! let esp = partition::find_esp(&boot_services)?;
+//! let config = fs::read((&boot_services, esp), r"\EFI\Vendor\config.txt")?;
+//! ```
+//!
+//! [`Gpt`] reads the GUID Partition Table of a whole disk directly from its Block I/O protocol, validating its CRCs
+//! and falling back to the backup table when the primary one is corrupted.
+//!
+//! [UEFI Spec Documentation: 5.3. GUID Partition Table (GPT) Disk Layout](https://uefi.org/specs/UEFI/2.10/05_GUID_Partition_Table_Format.html#guid-partition-table-gpt-disk-layout)
+//!
+//! [UEFI Spec Documentation: 13.18. Partition Information Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#partition-information-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, mem, ops::Deref, ptr};
+
+use boot_services::{
+    protocol_handler::{HandleBuffer, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+
+use super::BlockIo;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x8cf2f62c, 0xbc9b, 0x4821, 0x80, 0x8d, &[0xec, 0x9e, 0xc4, 0x21, 0xa1, 0xa0]);
+
+pub const REVISION: u32 = 0x00001000;
+
+pub const PARTITION_TYPE_OTHER: u32 = 0x00;
+pub const PARTITION_TYPE_MBR: u32 = 0x01;
+pub const PARTITION_TYPE_GPT: u32 = 0x02;
+
+/// Partition type GUID of the EFI System Partition.
+pub const EFI_SYSTEM_PARTITION_GUID: efi::Guid =
+    efi::Guid::from_fields(0xc12a7328, 0xf81f, 0x11d2, 0xba, 0x4b, &[0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b]);
+
+/// Signature of a GPT header, `"EFI PART"`.
+pub const GPT_HEADER_SIGNATURE: u64 = 0x5452415020494645;
+
+/// FFI definition of `MBR_PARTITION_RECORD`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MbrPartitionRecord {
+    pub boot_indicator: u8,
+    pub start_head: u8,
+    pub start_sector: u8,
+    pub start_track: u8,
+    pub os_indicator: u8,
+    pub end_head: u8,
+    pub end_sector: u8,
+    pub end_track: u8,
+    pub starting_lba: [u8; 4],
+    pub size_in_lba: [u8; 4],
+}
+
+impl MbrPartitionRecord {
+    /// The first block of the partition.
+    pub const fn starting_lba(&self) -> u32 {
+        u32::from_le_bytes(self.starting_lba)
+    }
+
+    /// The number of blocks of the partition.
+    pub const fn size_in_lba(&self) -> u32 {
+        u32::from_le_bytes(self.size_in_lba)
+    }
+}
+
+/// FFI definition of `EFI_PARTITION_ENTRY`, an entry of the GPT.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartitionEntry {
+    /// Type of the partition, zero if the entry is unused.
+    pub partition_type_guid: efi::Guid,
+    pub unique_partition_guid: efi::Guid,
+    pub starting_lba: efi::Lba,
+    /// Last block of the partition, inclusive.
+    pub ending_lba: efi::Lba,
+    pub attributes: u64,
+    pub partition_name: [u16; 36],
+}
+
+impl PartitionEntry {
+    /// Returns true if the entry describes a partition.
+    pub fn is_used(&self) -> bool {
+        self.partition_type_guid != efi::Guid::from_bytes(&[0; 16])
+    }
+
+    /// The number of blocks of the partition, `None` for a corrupted entry ending before it starts.
+    pub const fn block_count(&self) -> Option<u64> {
+        match self.ending_lba.checked_sub(self.starting_lba) {
+            Some(last) => last.checked_add(1),
+            None => None,
+        }
+    }
+
+    /// The name of the partition, `None` if it is not valid UCS-2.
+    pub fn name(&self) -> Option<String16> {
+        let name = self.partition_name;
+        let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        let mut string = String16::new();
+        for c in &name[..len] {
+            string.push(char::from_u32(*c as u32)?).ok()?;
+        }
+        Some(string)
+    }
+}
+
+/// FFI definition of the union of `EFI_PARTITION_INFO_PROTOCOL`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union Info {
+    pub mbr: MbrPartitionRecord,
+    pub gpt: PartitionEntry,
+}
+
+/// FFI definition of `EFI_PARTITION_INFO_PROTOCOL`.
+///
+/// The structure is packed in the specification, its fields are naturally aligned anyway.
+#[repr(C)]
+pub struct Protocol {
+    pub revision: u32,
+    pub r#type: u32,
+    /// 1 if the partition is an EFI System Partition.
+    pub system: u8,
+    pub reserved: [u8; 7],
+    pub info: Info,
+}
+
+/// Partition Information protocol.
+pub struct PartitionInfoProtocol;
+
+unsafe impl ProtocolTrait for PartitionInfoProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for PartitionInfoProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Description of a partition, from the partition table of its disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    Mbr(MbrPartitionRecord),
+    Gpt(PartitionEntry),
+    /// A partition of another type, such as an El Torito image of a CD-ROM.
+    Other,
+}
+
+/// Typed access to an instance of the Partition Information protocol.
+pub struct PartitionInfo(&'static Protocol);
+
+impl PartitionInfo {
+    /// Gets the instance of the protocol installed on the handle of a partition.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &PartitionInfoProtocol).map(|protocol| Self(protocol))
+    }
+
+    /// Returns true if the partition is an EFI System Partition.
+    pub fn is_system(&self) -> bool {
+        self.0.system == 1
+    }
+
+    /// The description of the partition.
+    pub fn partition(&self) -> Partition {
+        //SAFETY: The type selects the valid field of the union.
+        match self.0.r#type {
+            PARTITION_TYPE_MBR => Partition::Mbr(unsafe { self.0.info.mbr }),
+            PARTITION_TYPE_GPT => Partition::Gpt(unsafe { self.0.info.gpt }),
+            _ => Partition::Other,
+        }
+    }
+
+    /// The GPT entry of the partition, `None` for a partition of another type.
+    pub fn gpt_entry(&self) -> Option<PartitionEntry> {
+        match self.partition() {
+            Partition::Gpt(entry) => Some(entry),
+            _ => None,
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for PartitionInfo {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for PartitionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartitionInfo")
+            .field("system", &self.is_system())
+            .field("partition", &self.partition())
+            .finish()
+    }
+}
+
+/// Finds the first partition handle whose partition satisfies `predicate`.
+fn find_partition<B: BootServices>(
+    boot_services: &B,
+    predicate: impl Fn(&PartitionInfo) -> bool,
+) -> Result<efi::Handle, EfiError> {
+    let handles = HandleBuffer::supporting(boot_services, &PartitionInfoProtocol)?;
+    handles
+        .iter()
+        .find(|handle| PartitionInfo::get(boot_services, *handle).is_ok_and(|info| predicate(&info)))
+        .ok_or(efi::Status::NOT_FOUND.into())
+}
+
+/// Finds the handle of the first GPT partition of the given type.
+pub fn find_partition_by_type_guid<B: BootServices>(
+    boot_services: &B,
+    type_guid: &efi::Guid,
+) -> Result<efi::Handle, EfiError> {
+    find_partition(boot_services, |info| info.gpt_entry().is_some_and(|entry| entry.partition_type_guid == *type_guid))
+}
+
+/// Finds the handle of the first EFI System Partition.
+pub fn find_esp<B: BootServices>(boot_services: &B) -> Result<efi::Handle, EfiError> {
+    find_partition(boot_services, |info| {
+        info.is_system() || info.gpt_entry().is_some_and(|entry| entry.partition_type_guid == EFI_SYSTEM_PARTITION_GUID)
+    })
+}
+
+/// CRC-32 used by the GPT, the same as the `CalculateCrc32()` boot service.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 })
+    })
+}
+
+/// FFI definition of `EFI_PARTITION_TABLE_HEADER`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GptHeader {
+    pub signature: u64,
+    pub revision: u32,
+    pub header_size: u32,
+    pub header_crc32: u32,
+    pub reserved: u32,
+    pub my_lba: efi::Lba,
+    pub alternate_lba: efi::Lba,
+    pub first_usable_lba: efi::Lba,
+    pub last_usable_lba: efi::Lba,
+    pub disk_guid: efi::Guid,
+    pub partition_entry_lba: efi::Lba,
+    pub number_of_partition_entries: u32,
+    pub size_of_partition_entry: u32,
+    pub partition_entry_array_crc32: u32,
+}
+
+/// Size of the header defined by the specification, without the padding of the structure.
+const GPT_HEADER_SIZE: usize = mem::offset_of!(GptHeader, partition_entry_array_crc32) + mem::size_of::<u32>();
+
+impl GptHeader {
+    /// Parses and validates a header read from a block.
+    ///
+    /// Fails with [`efi::Status::NOT_FOUND`] without signature, with [`efi::Status::CRC_ERROR`] if the header is
+    /// corrupted and with [`efi::Status::VOLUME_CORRUPTED`] if it is not valid.
+    pub fn from_bytes(block: &[u8]) -> Result<Self, EfiError> {
+        if block.len() < mem::size_of::<GptHeader>() {
+            return Err(efi::Status::BAD_BUFFER_SIZE.into());
+        }
+        //SAFETY: The buffer is large enough for the header, which is read without alignment requirement.
+        let header = unsafe { ptr::read_unaligned(block.as_ptr() as *const GptHeader) };
+        if header.signature != GPT_HEADER_SIGNATURE {
+            return Err(efi::Status::NOT_FOUND.into());
+        }
+        let size = header.header_size as usize;
+        if !(GPT_HEADER_SIZE..=block.len()).contains(&size) {
+            return Err(efi::Status::VOLUME_CORRUPTED.into());
+        }
+        let mut bytes = block[..size].to_vec();
+        bytes[mem::offset_of!(GptHeader, header_crc32)..][..4].fill(0);
+        if crc32(&bytes) != header.header_crc32 {
+            return Err(efi::Status::CRC_ERROR.into());
+        }
+        let entry_size = header.size_of_partition_entry as usize;
+        if entry_size < mem::size_of::<PartitionEntry>() || entry_size % 8 != 0 {
+            return Err(efi::Status::VOLUME_CORRUPTED.into());
+        }
+        Ok(header)
+    }
+
+    /// Size of the partition entry array, in bytes, `None` if it overflows.
+    pub fn entries_size(&self) -> Option<usize> {
+        (self.number_of_partition_entries as usize).checked_mul(self.size_of_partition_entry as usize)
+    }
+
+    /// Parses the partition entry array of the header from `array`, the blocks it is stored in.
+    ///
+    /// Fails with [`efi::Status::CRC_ERROR`] if the array is corrupted and with [`efi::Status::VOLUME_CORRUPTED`] if
+    /// its entries are too small or `array` is too short for it.
+    pub fn parse_entries(&self, array: &[u8]) -> Result<Vec<PartitionEntry>, EfiError> {
+        let entry_size = self.size_of_partition_entry as usize;
+        if entry_size < mem::size_of::<PartitionEntry>() {
+            return Err(efi::Status::VOLUME_CORRUPTED.into());
+        }
+        let array = self.entries_size().and_then(|size| array.get(..size)).ok_or(efi::Status::VOLUME_CORRUPTED)?;
+        if crc32(array) != self.partition_entry_array_crc32 {
+            return Err(efi::Status::CRC_ERROR.into());
+        }
+        Ok(array
+            .chunks_exact(entry_size)
+            //SAFETY: The entry size was checked to hold an entry, which is read without alignment requirement.
+            .map(|entry| unsafe { ptr::read_unaligned(entry.as_ptr() as *const PartitionEntry) })
+            .collect())
+    }
+}
+
+/// The GUID Partition Table of a disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gpt {
+    pub header: GptHeader,
+    /// Every entry of the table, including the unused ones.
+    pub entries: Vec<PartitionEntry>,
+}
+
+impl Gpt {
+    /// Reads the table of the disk, from its backup if the primary table is not valid.
+    pub fn read(block_io: &mut BlockIo) -> Result<Self, EfiError> {
+        Self::read_at(block_io, 1).or_else(|status| match status.status() {
+            efi::Status::NOT_FOUND | efi::Status::CRC_ERROR | efi::Status::VOLUME_CORRUPTED => {
+                let last_block = block_io.media().last_block;
+                Self::read_at(block_io, last_block).map_err(|_| status)
+            }
+            status => Err(status.into()),
+        })
+    }
+
+    /// Reads the table whose header is at `lba`.
+    ///
+    /// The partition entry array must be on the disk, which bounds the memory allocated for it.
+    pub fn read_at(block_io: &mut BlockIo, lba: efi::Lba) -> Result<Self, EfiError> {
+        let media = block_io.media();
+        let block_size = media.block_size as usize;
+        if block_size == 0 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let mut block = vec![0; block_size];
+        block_io.read_blocks(lba, &mut block)?;
+        let header = GptHeader::from_bytes(&block)?;
+        if header.my_lba != lba {
+            return Err(efi::Status::VOLUME_CORRUPTED.into());
+        }
+
+        let blocks = header.entries_size().ok_or(efi::Status::VOLUME_CORRUPTED)?.div_ceil(block_size);
+        let on_disk = header
+            .partition_entry_lba
+            .checked_add(blocks as u64)
+            .is_some_and(|end| end <= media.last_block.saturating_add(1));
+        let array_size = blocks.checked_mul(block_size).filter(|_| on_disk).ok_or(efi::Status::VOLUME_CORRUPTED)?;
+        let mut array = Vec::new();
+        array.try_reserve_exact(array_size).map_err(|_| efi::Status::OUT_OF_RESOURCES)?;
+        array.resize(array_size, 0);
+        block_io.read_blocks(header.partition_entry_lba, &mut array)?;
+        let entries = header.parse_entries(&array)?;
+        Ok(Self { header, entries })
+    }
+
+    /// The entries describing a partition.
+    pub fn partitions(&self) -> impl Iterator<Item = &PartitionEntry> {
+        self.entries.iter().filter(|entry| entry.is_used())
+    }
+
+    /// The first partition of the given type.
+    pub fn find_by_type_guid(&self, type_guid: &efi::Guid) -> Option<&PartitionEntry> {
+        self.partitions().find(|entry| entry.partition_type_guid == *type_guid)
+    }
+
+    /// The first partition with the given name.
+    pub fn find_by_name(&self, name: &Str16) -> Option<&PartitionEntry> {
+        self.partitions().find(|entry| entry.name().is_some_and(|n| *n == *name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::media::block_io::test::test_block_io;
+    use alloc::boxed::Box;
+    use boot_services::{boxed::BootServicesBox, MockBootServices};
+
+    const BLOCK_SIZE: usize = 512;
+    const BLOCK_COUNT: usize = 64;
+    const ENTRY_COUNT: usize = 8;
+
+    const BASIC_DATA_GUID: efi::Guid =
+        efi::Guid::from_fields(0xebd0a0a2, 0xb9e5, 0x4433, 0x87, 0xc0, &[0x68, 0xb6, 0xb7, 0x26, 0x99, 0xc7]);
+
+    fn entry(type_guid: efi::Guid, starting_lba: u64, ending_lba: u64, name: &str) -> PartitionEntry {
+        let mut partition_name = [0; 36];
+        for (c, n) in name.encode_utf16().zip(partition_name.iter_mut()) {
+            *n = c;
+        }
+        PartitionEntry {
+            partition_type_guid: type_guid,
+            unique_partition_guid: efi::Guid::from_fields(starting_lba as u32, 0, 0, 0, 0, &[0; 6]),
+            starting_lba,
+            ending_lba,
+            attributes: 0,
+            partition_name,
+        }
+    }
+
+    fn test_entries() -> [PartitionEntry; 2] {
+        [entry(EFI_SYSTEM_PARTITION_GUID, 34, 40, "EFI system partition"), entry(BASIC_DATA_GUID, 41, 60, "Data")]
+    }
+
+    /// Writes a table at `lba` with its entries at `entries_lba`.
+    fn write_table(disk: &mut [u8], lba: u64, alternate_lba: u64, entries_lba: u64, entries: &[PartitionEntry]) {
+        let mut array = vec![0; ENTRY_COUNT * mem::size_of::<PartitionEntry>()];
+        for (entry, bytes) in entries.iter().zip(array.chunks_exact_mut(mem::size_of::<PartitionEntry>())) {
+            unsafe { ptr::write_unaligned(bytes.as_mut_ptr() as *mut PartitionEntry, *entry) };
+        }
+        let mut header = GptHeader {
+            signature: GPT_HEADER_SIGNATURE,
+            revision: 0x00010000,
+            header_size: GPT_HEADER_SIZE as u32,
+            header_crc32: 0,
+            reserved: 0,
+            my_lba: lba,
+            alternate_lba,
+            first_usable_lba: 34,
+            last_usable_lba: BLOCK_COUNT as u64 - 34,
+            disk_guid: efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]),
+            partition_entry_lba: entries_lba,
+            number_of_partition_entries: ENTRY_COUNT as u32,
+            size_of_partition_entry: mem::size_of::<PartitionEntry>() as u32,
+            partition_entry_array_crc32: crc32(&array),
+        };
+        let mut bytes = [0; mem::size_of::<GptHeader>()];
+        unsafe { ptr::write_unaligned(bytes.as_mut_ptr() as *mut GptHeader, header) };
+        header.header_crc32 = crc32(&bytes[..GPT_HEADER_SIZE]);
+        unsafe { ptr::write_unaligned(bytes.as_mut_ptr() as *mut GptHeader, header) };
+
+        let header_offset = lba as usize * BLOCK_SIZE;
+        disk[header_offset..header_offset + GPT_HEADER_SIZE].copy_from_slice(&bytes[..GPT_HEADER_SIZE]);
+        let entries_offset = entries_lba as usize * BLOCK_SIZE;
+        disk[entries_offset..entries_offset + array.len()].copy_from_slice(&array);
+    }
+
+    /// A disk with the primary table at block 1 and the backup table at the last block.
+    fn gpt_disk() -> Vec<u8> {
+        let mut disk = vec![0; BLOCK_COUNT * BLOCK_SIZE];
+        let last_block = BLOCK_COUNT as u64 - 1;
+        write_table(&mut disk, 1, last_block, 2, &test_entries());
+        write_table(&mut disk, last_block, 1, last_block - 2, &test_entries());
+        disk
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0xCBF43926, crc32(b"123456789"));
+        assert_eq!(0, crc32(&[]));
+    }
+
+    #[test]
+    fn test_read_gpt() {
+        let (mut block_io, _) = test_block_io(gpt_disk(), BLOCK_SIZE as u32);
+        let gpt = Gpt::read(&mut block_io).unwrap();
+        assert_eq!(1, gpt.header.my_lba);
+        assert_eq!(ENTRY_COUNT, gpt.entries.len());
+        assert_eq!(test_entries().iter().collect::<Vec<_>>(), gpt.partitions().collect::<Vec<_>>());
+
+        let esp = gpt.find_by_type_guid(&EFI_SYSTEM_PARTITION_GUID).unwrap();
+        assert_eq!(Some(7), esp.block_count());
+        assert_eq!(None, entry(BASIC_DATA_GUID, 41, 40, "").block_count());
+        assert_eq!(None, entry(BASIC_DATA_GUID, 0, u64::MAX, "").block_count());
+        assert_eq!(esp.name().unwrap(), "EFI system partition");
+        assert_eq!(41, gpt.find_by_name(&String16::try_from("Data").unwrap()).unwrap().starting_lba);
+        assert!(gpt.find_by_type_guid(&efi::Guid::from_bytes(&[1; 16])).is_none());
+    }
+
+    #[test]
+    fn test_read_gpt_backup() {
+        let (mut block_io, disk) = test_block_io(gpt_disk(), BLOCK_SIZE as u32);
+        // Corrupt the name of the first partition of the primary table.
+        disk.data.borrow_mut()[2 * BLOCK_SIZE + 56] ^= 0xFF;
+        assert_eq!(efi::Status::CRC_ERROR, Gpt::read_at(&mut block_io, 1).unwrap_err());
+        let gpt = Gpt::read(&mut block_io).unwrap();
+        assert_eq!(BLOCK_COUNT as u64 - 1, gpt.header.my_lba);
+        assert_eq!(2, gpt.partitions().count());
+
+        // Corrupt the backup header too.
+        disk.data.borrow_mut()[(BLOCK_COUNT - 1) * BLOCK_SIZE + 32] ^= 0xFF;
+        assert_eq!(efi::Status::CRC_ERROR, Gpt::read(&mut block_io).unwrap_err());
+    }
+
+    #[test]
+    fn test_read_gpt_oversized_array() {
+        let mut disk = gpt_disk();
+        // A valid header whose entry array is larger than the disk.
+        let header = &mut disk[BLOCK_SIZE..BLOCK_SIZE + GPT_HEADER_SIZE];
+        header[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        header[16..20].fill(0);
+        let crc = crc32(header);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        let (mut block_io, _) = test_block_io(disk, BLOCK_SIZE as u32);
+        assert_eq!(efi::Status::VOLUME_CORRUPTED, Gpt::read_at(&mut block_io, 1).unwrap_err());
+    }
+
+    #[test]
+    fn test_read_gpt_without_table() {
+        let (mut block_io, _) = test_block_io(vec![0; BLOCK_COUNT * BLOCK_SIZE], BLOCK_SIZE as u32);
+        assert_eq!(efi::Status::NOT_FOUND, Gpt::read(&mut block_io).unwrap_err());
+    }
+
+    #[test]
+    fn test_header_from_bytes() {
+        let disk = gpt_disk();
+        let block = &disk[BLOCK_SIZE..2 * BLOCK_SIZE];
+        let header = GptHeader::from_bytes(block).unwrap();
+        assert_eq!(2, header.partition_entry_lba);
+        assert_eq!(Some(ENTRY_COUNT * 128), header.entries_size());
+        let huge = GptHeader { number_of_partition_entries: u32::MAX, size_of_partition_entry: u32::MAX, ..header };
+        assert_eq!(efi::Status::VOLUME_CORRUPTED, huge.parse_entries(&disk[2 * BLOCK_SIZE..]).unwrap_err());
+
+        let mut oversized = block.to_vec();
+        oversized[12] = 0xFF;
+        oversized[13] = 0x0F;
+        assert_eq!(efi::Status::VOLUME_CORRUPTED, GptHeader::from_bytes(&oversized).unwrap_err());
+        assert_eq!(efi::Status::BAD_BUFFER_SIZE, GptHeader::from_bytes(&block[..64]).unwrap_err());
+    }
+
+    fn partition_info(system: u8, partition: Partition) -> &'static mut Protocol {
+        let (r#type, info) = match partition {
+            Partition::Mbr(mbr) => (PARTITION_TYPE_MBR, Info { mbr }),
+            Partition::Gpt(gpt) => (PARTITION_TYPE_GPT, Info { gpt }),
+            Partition::Other => (PARTITION_TYPE_OTHER, Info { gpt: entry(efi::Guid::from_bytes(&[0; 16]), 0, 0, "") }),
+        };
+        Box::leak(Box::new(Protocol { revision: REVISION, r#type, system, reserved: [0; 7], info }))
+    }
+
+    /// Boot services with an MBR partition on handle 1, a GPT data partition on handle 2 and a GPT ESP on handle 3.
+    fn boot_services() -> MockBootServices {
+        let mut free_boot_services = MockBootServices::new();
+        free_boot_services.expect_free_pool().returning(|_| Ok(()));
+        let free_boot_services: &'static MockBootServices = Box::leak(Box::new(free_boot_services));
+
+        let mbr = MbrPartitionRecord {
+            boot_indicator: 0x80,
+            start_head: 0,
+            start_sector: 0,
+            start_track: 0,
+            os_indicator: 0xEF,
+            end_head: 0,
+            end_sector: 0,
+            end_track: 0,
+            starting_lba: 2048u32.to_le_bytes(),
+            size_in_lba: 4096u32.to_le_bytes(),
+        };
+        let [esp, data] = test_entries();
+        let protocols = [
+            partition_info(0, Partition::Mbr(mbr)),
+            partition_info(0, Partition::Gpt(data)),
+            partition_info(1, Partition::Gpt(esp)),
+        ]
+        .map(|protocol| protocol as *mut Protocol as usize);
+
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_locate_handles().returning(move |_| {
+            let handles = Box::leak(Box::new([1_usize as efi::Handle, 2_usize as efi::Handle, 3_usize as efi::Handle]));
+            Ok(HandleBuffer::from(unsafe {
+                BootServicesBox::from_raw_parts(handles.as_mut_ptr(), handles.len(), free_boot_services)
+            }))
+        });
+        boot_services
+            .expect_handle_protocol::<PartitionInfoProtocol, Protocol>()
+            .returning(move |handle, _| Ok(unsafe { &mut *(protocols[handle as usize - 1] as *mut Protocol) }));
+        boot_services
+    }
+
+    #[test]
+    fn test_partition_info() {
+        let boot_services = boot_services();
+        let mbr = PartitionInfo::get(&boot_services, 1_usize as efi::Handle).unwrap();
+        assert!(!mbr.is_system());
+        assert!(mbr.gpt_entry().is_none());
+        let Partition::Mbr(record) = mbr.partition() else { panic!("not an MBR partition") };
+        assert_eq!((2048, 4096), (record.starting_lba(), record.size_in_lba()));
+
+        let esp = PartitionInfo::get(&boot_services, 3_usize as efi::Handle).unwrap();
+        assert!(esp.is_system());
+        assert_eq!(Some(test_entries()[0]), esp.gpt_entry());
+    }
+
+    #[test]
+    fn test_find_partition() {
+        let boot_services = boot_services();
+        assert_eq!(3_usize as efi::Handle, find_esp(&boot_services).unwrap());
+        assert_eq!(2_usize as efi::Handle, find_partition_by_type_guid(&boot_services, &BASIC_DATA_GUID).unwrap());
+        assert_eq!(
+            efi::Status::NOT_FOUND,
+            find_partition_by_type_guid(&boot_services, &efi::Guid::from_bytes(&[1; 16])).unwrap_err()
+        );
+    }
+}
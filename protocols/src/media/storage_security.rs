@@ -0,0 +1,447 @@
+//! Storage Security Command protocol.
+//!
+//! [`StorageSecurity`] sends and receives the payloads of a security protocol, such as the TCG protocols of self
+//! encrypting drives or IEEE 1667, to a storage device. [`StorageSecurity::level0_discovery`] gives the features of a
+//! TCG drive:
+//!
+//! ```ignore
+//! let media_id = BlockIo::get(&boot_services, device_handle)?.media().media_id;
+//! let mut security = StorageSecurity::get(&boot_services, device_handle)?;
+//! if security.supported_protocols(media_id)?.contains(&SecurityProtocol::TCG_1) {
+//!     let discovery = security.level0_discovery(media_id)?;
+//!     let locked = discovery.locking().is_some_and(|locking| locking.locked());
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 13.11. Storage Security Command Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#storage-security-command-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{ffi::c_void, fmt, ops::Deref};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xc88b0b6d, 0x0dfc, 0x49a7, 0x9c, 0xb4, &[0x49, 0x07, 0x4b, 0x4c, 0x3a, 0x78]);
+
+pub type ProtocolReceiveData =
+    extern "efiapi" fn(*mut Protocol, u32, u64, u8, u16, usize, *mut c_void, *mut usize) -> efi::Status;
+
+pub type ProtocolSendData = extern "efiapi" fn(*mut Protocol, u32, u64, u8, u16, usize, *mut c_void) -> efi::Status;
+
+/// FFI definition of `EFI_STORAGE_SECURITY_COMMAND_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub receive_data: ProtocolReceiveData,
+    pub send_data: ProtocolSendData,
+}
+
+/// Storage Security Command protocol.
+pub struct StorageSecurityCommandProtocol;
+
+unsafe impl ProtocolTrait for StorageSecurityCommandProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for StorageSecurityCommandProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Security protocol IDs of the SECURITY PROTOCOL IN and OUT commands.
+pub struct SecurityProtocol;
+
+impl SecurityProtocol {
+    /// Security protocol information, such as the list of the supported protocols.
+    pub const INFORMATION: u8 = 0x00;
+    /// TCG protocol of the Opal, Enterprise and Pyrite SSCs.
+    pub const TCG_1: u8 = 0x01;
+    pub const TCG_2: u8 = 0x02;
+    pub const IEEE_1667: u8 = 0xee;
+    pub const ATA_DEVICE_SERVER_PASSWORD: u8 = 0xef;
+}
+
+/// Typed access to an instance of the Storage Security Command protocol.
+pub struct StorageSecurity {
+    protocol: &'static mut Protocol,
+    timeout: u64,
+}
+
+impl StorageSecurity {
+    /// Default timeout of a command, in units of 100 ns.
+    pub const DEFAULT_TIMEOUT: u64 = 30 * 10_000_000;
+
+    /// Size of the buffer receiving the level 0 discovery data.
+    const LEVEL0_DISCOVERY_SIZE: usize = 2048;
+
+    /// Gets the instance of the protocol installed on the handle of a storage device.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &StorageSecurityCommandProtocol).map(Self::from)
+    }
+
+    /// Sets the timeout of a command, in units of 100 ns, 0 to wait as long as needed.
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.protocol as *mut Protocol
+    }
+
+    /// Receives the payload of a security protocol into `buffer` and gives its size, truncated to the size of the
+    /// buffer.
+    ///
+    /// `sp_specific` is the protocol specific field of the command, such as the ComID of a TCG protocol. It is given to
+    /// the device most significant byte first, as the command expects.
+    pub fn receive(
+        &mut self,
+        media_id: u32,
+        protocol_id: u8,
+        sp_specific: u16,
+        buffer: &mut [u8],
+    ) -> Result<usize, EfiError> {
+        let mut transferred = 0;
+        match (self.protocol.receive_data)(
+            self.this(),
+            media_id,
+            self.timeout,
+            protocol_id,
+            sp_specific.to_be(),
+            buffer.len(),
+            buffer.as_mut_ptr() as *mut c_void,
+            &mut transferred,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(transferred.min(buffer.len())),
+        }
+    }
+
+    /// Sends the payload of a security protocol, see [`StorageSecurity::receive`] for `sp_specific`.
+    pub fn send(&mut self, media_id: u32, protocol_id: u8, sp_specific: u16, payload: &[u8]) -> Result<(), EfiError> {
+        // The payload is only read by the protocol.
+        match (self.protocol.send_data)(
+            self.this(),
+            media_id,
+            self.timeout,
+            protocol_id,
+            sp_specific.to_be(),
+            payload.len(),
+            payload.as_ptr() as *mut c_void,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The IDs of the security protocols supported by the device.
+    pub fn supported_protocols(&mut self, media_id: u32) -> Result<Vec<u8>, EfiError> {
+        let mut buffer = vec![0; 512];
+        let len = self.receive(media_id, SecurityProtocol::INFORMATION, 0x0000, &mut buffer)?;
+        let list = buffer[..len].get(6..8).ok_or(efi::Status::INVALID_PARAMETER)?;
+        let count = u16::from_be_bytes([list[0], list[1]]) as usize;
+        buffer[..len].get(8..8 + count).map(<[u8]>::to_vec).ok_or(efi::Status::INVALID_PARAMETER.into())
+    }
+
+    /// The TCG level 0 discovery data of the device, received with the TCG protocol 1 at ComID 0x0001.
+    pub fn level0_discovery(&mut self, media_id: u32) -> Result<Level0Discovery, EfiError> {
+        let mut buffer = vec![0; Self::LEVEL0_DISCOVERY_SIZE];
+        let len = self.receive(media_id, SecurityProtocol::TCG_1, 0x0001, &mut buffer)?;
+        Level0Discovery::parse(&buffer[..len])
+    }
+}
+
+impl From<&'static mut Protocol> for StorageSecurity {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self { protocol, timeout: Self::DEFAULT_TIMEOUT }
+    }
+}
+
+impl fmt::Debug for StorageSecurity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StorageSecurity").field("timeout", &self.timeout).finish()
+    }
+}
+
+/// A feature descriptor of the level 0 discovery data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feature {
+    pub code: u16,
+    pub version: u8,
+    pub data: Vec<u8>,
+}
+
+impl Feature {
+    pub const TPER: u16 = 0x0001;
+    pub const LOCKING: u16 = 0x0002;
+    pub const GEOMETRY: u16 = 0x0003;
+    pub const ENTERPRISE_SSC: u16 = 0x0100;
+    pub const OPAL_SSC_V1: u16 = 0x0200;
+    pub const SINGLE_USER_MODE: u16 = 0x0201;
+    pub const DATA_STORE: u16 = 0x0202;
+    pub const OPAL_SSC_V2: u16 = 0x0203;
+    pub const OPALITE_SSC: u16 = 0x0301;
+    pub const PYRITE_SSC_V1: u16 = 0x0302;
+    pub const PYRITE_SSC_V2: u16 = 0x0303;
+    pub const RUBY_SSC: u16 = 0x0304;
+    pub const BLOCK_SID_AUTHENTICATION: u16 = 0x0402;
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        self.data.get(offset..offset + 2).map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+/// Flags of the Locking feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockingFeature(pub u8);
+
+impl LockingFeature {
+    pub fn supported(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    /// Whether a locking range of the device is locked.
+    pub fn locked(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    pub fn media_encryption(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    pub fn mbr_enabled(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn mbr_done(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+}
+
+/// The communication properties of an SSC feature, such as Opal or Pyrite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SscFeature {
+    /// Feature code of the SSC.
+    pub code: u16,
+    /// First ComID used for the sessions with the device.
+    pub base_com_id: u16,
+    pub com_id_count: u16,
+}
+
+/// The TCG level 0 discovery data of a device, listing its features.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level0Discovery {
+    pub revision: u32,
+    pub features: Vec<Feature>,
+}
+
+impl Level0Discovery {
+    /// Size of the header preceding the feature descriptors.
+    const HEADER_SIZE: usize = 48;
+
+    /// SSC features, in order of preference.
+    const SSC_FEATURES: [u16; 6] = [
+        Feature::OPAL_SSC_V2,
+        Feature::OPAL_SSC_V1,
+        Feature::RUBY_SSC,
+        Feature::OPALITE_SSC,
+        Feature::PYRITE_SSC_V2,
+        Feature::PYRITE_SSC_V1,
+    ];
+
+    /// Parses the level 0 discovery data, `INVALID_PARAMETER` if its header or a feature descriptor is truncated.
+    pub fn parse(bytes: &[u8]) -> Result<Self, EfiError> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let length = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        let revision = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
+        // The length of the data does not include the length field.
+        let end = length.checked_add(4).filter(|end| *end <= bytes.len()).ok_or(efi::Status::INVALID_PARAMETER)?;
+        let mut descriptors = bytes.get(Self::HEADER_SIZE..end).unwrap_or_default();
+        let mut features = Vec::new();
+        while !descriptors.is_empty() {
+            if descriptors.len() < 4 || descriptors.len() < 4 + descriptors[3] as usize {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            let (descriptor, rest) = descriptors.split_at(4 + descriptors[3] as usize);
+            features.push(Feature {
+                code: u16::from_be_bytes([descriptor[0], descriptor[1]]),
+                version: descriptor[2] >> 4,
+                data: descriptor[4..].to_vec(),
+            });
+            descriptors = rest;
+        }
+        Ok(Self { revision, features })
+    }
+
+    pub fn feature(&self, code: u16) -> Option<&Feature> {
+        self.features.iter().find(|feature| feature.code == code)
+    }
+
+    pub fn locking(&self) -> Option<LockingFeature> {
+        self.feature(Feature::LOCKING).map(|feature| LockingFeature(feature.data.first().copied().unwrap_or(0)))
+    }
+
+    /// The SSC implemented by the device, the Opal SSC 2 first when several are reported.
+    pub fn ssc(&self) -> Option<SscFeature> {
+        Self::SSC_FEATURES.iter().find_map(|code| self.feature(*code)).map(|feature| SscFeature {
+            code: feature.code,
+            base_com_id: feature.u16_at(0),
+            com_id_count: feature.u16_at(2),
+        })
+    }
+
+    pub fn is_opal(&self) -> bool {
+        self.feature(Feature::OPAL_SSC_V2).is_some() || self.feature(Feature::OPAL_SSC_V1).is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::slice;
+
+    /// Opal drive supporting the TCG and IEEE 1667 protocols, with its locking enabled and locked.
+    #[repr(C)]
+    struct TestSecurity {
+        protocol: Protocol,
+        sent: Vec<(u8, u16, Vec<u8>)>,
+    }
+
+    const MEDIA_ID: u32 = 3;
+
+    fn level0_discovery() -> Vec<u8> {
+        let mut bytes = vec![0; 48];
+        bytes[4..8].copy_from_slice(&1u32.to_be_bytes());
+        bytes.extend_from_slice(&[0x00, 0x01, 0x10, 0x0c, 0x11, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0x00, 0x02, 0x10, 0x0c, 0x07, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0x02, 0x03, 0x20, 0x10, 0x10, 0x00, 0x00, 0x01, 0, 0, 4, 0, 8, 0, 0, 0, 0, 0, 0, 0]);
+        let length = (bytes.len() - 4) as u32;
+        bytes[0..4].copy_from_slice(&length.to_be_bytes());
+        bytes
+    }
+
+    fn test_security<'a>(this: *mut Protocol) -> &'a mut TestSecurity {
+        unsafe { &mut *(this as *mut TestSecurity) }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    extern "efiapi" fn receive_data(
+        _: *mut Protocol,
+        media_id: u32,
+        timeout: u64,
+        protocol_id: u8,
+        sp_specific: u16,
+        size: usize,
+        buffer: *mut c_void,
+        transferred: *mut usize,
+    ) -> efi::Status {
+        assert_eq!(MEDIA_ID, media_id);
+        assert_eq!(StorageSecurity::DEFAULT_TIMEOUT, timeout);
+        let data = match (protocol_id, u16::from_be(sp_specific)) {
+            (SecurityProtocol::INFORMATION, 0x0000) => vec![0, 0, 0, 0, 0, 0, 0, 3, 0x00, 0x01, 0xee],
+            (SecurityProtocol::TCG_1, 0x0001) => level0_discovery(),
+            _ => return efi::Status::UNSUPPORTED,
+        };
+        let len = data.len().min(size);
+        unsafe { slice::from_raw_parts_mut(buffer as *mut u8, len) }.copy_from_slice(&data[..len]);
+        unsafe { transferred.write(data.len()) };
+        if len < data.len() {
+            efi::Status::WARN_BUFFER_TOO_SMALL
+        } else {
+            efi::Status::SUCCESS
+        }
+    }
+
+    extern "efiapi" fn send_data(
+        this: *mut Protocol,
+        media_id: u32,
+        _: u64,
+        protocol_id: u8,
+        sp_specific: u16,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        if media_id != MEDIA_ID {
+            return efi::Status::MEDIA_CHANGED;
+        }
+        let payload = unsafe { slice::from_raw_parts(buffer as *const u8, size) }.to_vec();
+        test_security(this).sent.push((protocol_id, u16::from_be(sp_specific), payload));
+        efi::Status::SUCCESS
+    }
+
+    fn storage_security() -> (StorageSecurity, &'static TestSecurity) {
+        let security =
+            Box::leak(Box::new(TestSecurity { protocol: Protocol { receive_data, send_data }, sent: Vec::new() }));
+        let test_security = test_security(&mut security.protocol);
+        (StorageSecurity::from(&mut security.protocol), test_security)
+    }
+
+    #[test]
+    fn test_send_receive() {
+        let (mut security, test_security) = storage_security();
+        assert_eq!(vec![0x00, 0x01, 0xee], security.supported_protocols(MEDIA_ID).unwrap());
+
+        let mut buffer = [0; 8];
+        assert_eq!(8, security.receive(MEDIA_ID, SecurityProtocol::TCG_1, 0x0001, &mut buffer).unwrap());
+        assert_eq!(
+            efi::Status::UNSUPPORTED,
+            security.receive(MEDIA_ID, SecurityProtocol::TCG_2, 0x0001, &mut buffer).unwrap_err()
+        );
+
+        security.send(MEDIA_ID, SecurityProtocol::TCG_1, 0x1000, &[1, 2, 3]).unwrap();
+        assert_eq!(
+            efi::Status::MEDIA_CHANGED,
+            security.send(MEDIA_ID + 1, SecurityProtocol::TCG_1, 0x1000, &[]).unwrap_err()
+        );
+        assert_eq!(vec![(SecurityProtocol::TCG_1, 0x1000, vec![1, 2, 3])], test_security.sent);
+    }
+
+    #[test]
+    fn test_level0_discovery() {
+        let (mut security, _) = storage_security();
+        let discovery = security.level0_discovery(MEDIA_ID).unwrap();
+        assert_eq!(1, discovery.revision);
+        assert_eq!(3, discovery.features.len());
+        assert!(discovery.is_opal());
+        assert_eq!(2, discovery.feature(Feature::OPAL_SSC_V2).unwrap().version);
+
+        let locking = discovery.locking().unwrap();
+        assert!(locking.supported() && locking.enabled() && locking.locked());
+        assert!(!locking.mbr_enabled());
+
+        let ssc = discovery.ssc().unwrap();
+        assert_eq!(Feature::OPAL_SSC_V2, ssc.code);
+        assert_eq!(0x1000, ssc.base_com_id);
+        assert_eq!(1, ssc.com_id_count);
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let bytes = level0_discovery();
+        assert_eq!(efi::Status::INVALID_PARAMETER, Level0Discovery::parse(&bytes[..40]).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, Level0Discovery::parse(&bytes[..bytes.len() - 1]).unwrap_err());
+
+        let mut bytes = bytes;
+        let length = (bytes.len() - 4 - 2) as u32;
+        bytes[0..4].copy_from_slice(&length.to_be_bytes());
+        assert_eq!(efi::Status::INVALID_PARAMETER, Level0Discovery::parse(&bytes).unwrap_err());
+    }
+}
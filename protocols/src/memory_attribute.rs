@@ -0,0 +1,270 @@
+//! Memory Attribute protocol.
+//!
+//! [`MemoryProtection`] reads and changes the access attributes of pages, the read protection (`RP`), write
+//! protection (`RO`) and execution protection (`XP`), for drivers to protect their own code and data:
+//!
+//! ```ignore
+//! let mut protection = MemoryProtection::locate(&boot_services)?;
+//! let range = PageRange::containing(code.as_ptr() as u64, code.len() as u64).ok_or(efi::Status::INVALID_PARAMETER)?;
+//! protection.protect_code(range)?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 37.7. Memory Attribute Protocol](https://uefi.org/specs/UEFI/2.10/37_Secure_Technologies.html#memory-attribute-protocol)
+
+use core::fmt;
+
+use boot_services::{allocation::MemoryAttribute, protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use efi::protocols::memory_attribute;
+
+type MemoryAttributeProtocol = memory_attribute::Protocol;
+
+/// Size of the pages the attributes apply to.
+pub const PAGE_SIZE: u64 = 0x1000;
+
+/// Range of whole pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PageRange {
+    base: efi::PhysicalAddress,
+    pages: u64,
+}
+
+impl PageRange {
+    /// The `pages` pages from `base`, if `base` is page aligned and the range is neither empty nor overflows.
+    pub const fn new(base: efi::PhysicalAddress, pages: u64) -> Option<Self> {
+        if base % PAGE_SIZE != 0 || pages == 0 {
+            return None;
+        }
+        match pages.checked_mul(PAGE_SIZE) {
+            Some(size) if base.checked_add(size).is_some() => Some(Self { base, pages }),
+            _ => None,
+        }
+    }
+
+    /// The pages containing the `size` bytes from `address`.
+    pub const fn containing(address: efi::PhysicalAddress, size: u64) -> Option<Self> {
+        let base = address - address % PAGE_SIZE;
+        match address.checked_add(size) {
+            Some(end) if size != 0 => Self::new(base, (end - base).div_ceil(PAGE_SIZE)),
+            _ => None,
+        }
+    }
+
+    pub const fn base(&self) -> efi::PhysicalAddress {
+        self.base
+    }
+
+    pub const fn pages(&self) -> u64 {
+        self.pages
+    }
+
+    /// Size of the range, in bytes.
+    pub const fn size(&self) -> u64 {
+        self.pages * PAGE_SIZE
+    }
+}
+
+/// Typed access to the Memory Attribute protocol.
+pub struct MemoryProtection(&'static mut MemoryAttributeProtocol);
+
+impl MemoryProtection {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::MemoryAttribute, None).map(Self)
+    }
+
+    fn this(&self) -> *mut MemoryAttributeProtocol {
+        self.0 as *const MemoryAttributeProtocol as *mut MemoryAttributeProtocol
+    }
+
+    /// Checks that `attributes` are access attributes, the only ones the protocol changes, and that there is one.
+    fn check_attributes(attributes: MemoryAttribute) -> Result<u64, EfiError> {
+        let access = MemoryAttribute::RP | MemoryAttribute::RO | MemoryAttribute::XP;
+        let bits: u64 = attributes.into();
+        match bits {
+            0 => Err(efi::Status::INVALID_PARAMETER.into()),
+            _ if !access.contains(attributes) => Err(efi::Status::INVALID_PARAMETER.into()),
+            _ => Ok(bits),
+        }
+    }
+
+    /// The access attributes of `range`.
+    ///
+    /// Returns `NO_MAPPING` if the pages of the range do not all have the same attributes.
+    pub fn get_attributes(&self, range: PageRange) -> Result<MemoryAttribute, EfiError> {
+        let mut attributes = 0;
+        match unsafe {
+            r_efi::efi_call(self.0.get_memory_attributes, (self.this(), range.base, range.size(), &mut attributes))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(MemoryAttribute::from(attributes)),
+        }
+    }
+
+    /// Sets `attributes`, access attributes only, on `range`, keeping the other access attributes of the range.
+    pub fn set_attributes(&mut self, range: PageRange, attributes: MemoryAttribute) -> Result<(), EfiError> {
+        let attributes = Self::check_attributes(attributes)?;
+        match unsafe {
+            r_efi::efi_call(self.0.set_memory_attributes, (self.this(), range.base, range.size(), attributes))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Clears `attributes`, access attributes only, from `range`, keeping the other access attributes of the range.
+    pub fn clear_attributes(&mut self, range: PageRange, attributes: MemoryAttribute) -> Result<(), EfiError> {
+        let attributes = Self::check_attributes(attributes)?;
+        match unsafe {
+            r_efi::efi_call(self.0.clear_memory_attributes, (self.this(), range.base, range.size(), attributes))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Protects code: `range` becomes read only and executable.
+    pub fn protect_code(&mut self, range: PageRange) -> Result<(), EfiError> {
+        self.set_attributes(range, MemoryAttribute::RO)?;
+        self.clear_attributes(range, MemoryAttribute::XP)
+    }
+
+    /// Protects data: `range` becomes writable and not executable.
+    pub fn protect_data(&mut self, range: PageRange) -> Result<(), EfiError> {
+        self.set_attributes(range, MemoryAttribute::XP)?;
+        self.clear_attributes(range, MemoryAttribute::RO)
+    }
+}
+
+impl From<&'static mut MemoryAttributeProtocol> for MemoryProtection {
+    fn from(protocol: &'static mut MemoryAttributeProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for MemoryProtection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryProtection").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec, vec::Vec};
+    use boot_services::MockBootServices;
+
+    /// Attributes of 16 pages from 0x10000, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestMemory {
+        protocol: MemoryAttributeProtocol,
+        pages: Vec<u64>,
+    }
+
+    const BASE: u64 = 0x10000;
+
+    /// The pages of a range, if they are all in the test memory.
+    fn test_pages<'a>(
+        this: *mut MemoryAttributeProtocol,
+        base: efi::PhysicalAddress,
+        length: u64,
+    ) -> Option<&'a mut [u64]> {
+        let memory = unsafe { &mut *(this as *mut TestMemory) };
+        let first = (base.checked_sub(BASE)? / PAGE_SIZE) as usize;
+        memory.pages.get_mut(first..first + (length / PAGE_SIZE) as usize)
+    }
+
+    extern "efiapi" fn get_memory_attributes(
+        this: *mut MemoryAttributeProtocol,
+        base: efi::PhysicalAddress,
+        length: u64,
+        attributes: *mut u64,
+    ) -> efi::Status {
+        let Some(pages) = test_pages(this, base, length) else {
+            return efi::Status::UNSUPPORTED;
+        };
+        if pages.iter().any(|page| *page != pages[0]) {
+            return efi::Status::NO_MAPPING;
+        }
+        unsafe { *attributes = pages[0] };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_memory_attributes(
+        this: *mut MemoryAttributeProtocol,
+        base: efi::PhysicalAddress,
+        length: u64,
+        attributes: u64,
+    ) -> efi::Status {
+        let Some(pages) = test_pages(this, base, length) else {
+            return efi::Status::UNSUPPORTED;
+        };
+        pages.iter_mut().for_each(|page| *page |= attributes);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn clear_memory_attributes(
+        this: *mut MemoryAttributeProtocol,
+        base: efi::PhysicalAddress,
+        length: u64,
+        attributes: u64,
+    ) -> efi::Status {
+        let Some(pages) = test_pages(this, base, length) else {
+            return efi::Status::UNSUPPORTED;
+        };
+        pages.iter_mut().for_each(|page| *page &= !attributes);
+        efi::Status::SUCCESS
+    }
+
+    fn boot_services() -> MockBootServices {
+        let memory = Box::leak(Box::new(TestMemory {
+            protocol: MemoryAttributeProtocol { get_memory_attributes, set_memory_attributes, clear_memory_attributes },
+            pages: vec![efi::MEMORY_XP; 16],
+        }));
+        let memory_ptr = memory as *mut TestMemory as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::MemoryAttribute, MemoryAttributeProtocol>()
+            .returning(move |_, _| Ok(unsafe { &mut (*(memory_ptr as *mut TestMemory)).protocol }));
+        boot_services
+    }
+
+    #[test]
+    fn test_page_range() {
+        assert_eq!(None, PageRange::new(0x1001, 1));
+        assert_eq!(None, PageRange::new(0x1000, 0));
+        assert_eq!(None, PageRange::new(u64::MAX - 0xFFF, 2));
+        let range = PageRange::containing(0x1FFF, 2).unwrap();
+        assert_eq!((0x1000, 2, 0x2000), (range.base(), range.pages(), range.size()));
+        assert_eq!(PageRange::new(0x1000, 1), PageRange::containing(0x1000, 0x1000));
+        assert_eq!(None, PageRange::containing(0x1000, 0));
+    }
+
+    #[test]
+    fn test_attributes() {
+        let boot_services = boot_services();
+        let mut protection = MemoryProtection::locate(&boot_services).unwrap();
+        let code = PageRange::new(BASE, 2).unwrap();
+        let all = PageRange::new(BASE, 16).unwrap();
+        assert_eq!(MemoryAttribute::XP, protection.get_attributes(all).unwrap());
+
+        protection.protect_code(code).unwrap();
+        assert_eq!(MemoryAttribute::RO, protection.get_attributes(code).unwrap());
+        assert_eq!(efi::Status::NO_MAPPING, protection.get_attributes(all).unwrap_err());
+        protection.protect_data(code).unwrap();
+        assert_eq!(MemoryAttribute::XP, protection.get_attributes(all).unwrap());
+
+        protection.set_attributes(code, MemoryAttribute::RP | MemoryAttribute::RO).unwrap();
+        let attributes = protection.get_attributes(code).unwrap();
+        assert!(attributes.contains(MemoryAttribute::RP | MemoryAttribute::RO | MemoryAttribute::XP));
+        protection.clear_attributes(code, MemoryAttribute::RP).unwrap();
+        assert_eq!(MemoryAttribute::RO | MemoryAttribute::XP, protection.get_attributes(code).unwrap());
+
+        let invalid = MemoryAttribute::WB | MemoryAttribute::XP;
+        assert_eq!(efi::Status::INVALID_PARAMETER, protection.set_attributes(code, invalid).unwrap_err());
+        let none = MemoryAttribute::from(0);
+        assert_eq!(efi::Status::INVALID_PARAMETER, protection.clear_attributes(code, none).unwrap_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! MM Communication 2 protocol of the PI specification, the channel from DXE to the handlers of the Management Mode.
+//!
+//! A [`CommBuffer`] holds the `EFI_MM_COMMUNICATE_HEADER` addressing a handler by its GUID and the message, written and
+//! read as structures through the [`zerocopy`] traits, the handler replacing the request by its response:
+//!
+//! ```ignore
+//! #[derive(IntoBytes, FromBytes, Immutable, KnownLayout)]
+//! #[repr(C)]
+//! struct GetPolicy { function: u64, status: u64, policy: u64 }
+//!
+//! let mut mm_communication = MmCommunication::locate(&boot_services)?;
+//! let mut buffer = CommBuffer::with_message(&POLICY_HANDLER_GUID, &GetPolicy { function: 1, status: 0, policy: 0 });
+//! mm_communication.communicate(&mut buffer)?;
+//! let response: GetPolicy = buffer.response()?;
+//! ```
+//!
+//! [PI Spec Documentation: Volume 4 - 6.6 MM Communication Protocol](https://uefi.org/specs/PI/1.8/V4_UEFI_Protocols.html#efi-mm-communication2-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{ffi::c_void, fmt, mem, ops::Deref, slice};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+pub mod variable;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x378daedc, 0xf06b, 0x4446, 0x83, 0x14, &[0x40, 0xab, 0x93, 0x3c, 0x87, 0xa3]);
+
+pub type ProtocolCommunicate = extern "efiapi" fn(*mut Protocol, *mut c_void, *mut c_void, *mut usize) -> efi::Status;
+
+/// FFI definition of `EFI_MM_COMMUNICATION2_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub communicate: ProtocolCommunicate,
+}
+
+/// FFI definition of `EFI_MM_COMMUNICATE_HEADER`, followed by the message.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CommunicateHeader {
+    pub header_guid: efi::Guid,
+    pub message_length: usize,
+}
+
+/// MM Communication 2 protocol.
+pub struct MmCommunication2Protocol;
+
+unsafe impl ProtocolTrait for MmCommunication2Protocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for MmCommunication2Protocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Communication buffer, a [`CommunicateHeader`] followed by room for the message and the response.
+#[derive(Clone)]
+pub struct CommBuffer {
+    /// The header and the message, in 8-byte words so both are aligned.
+    buffer: Vec<u64>,
+    /// Room for the message, in bytes.
+    capacity: usize,
+}
+
+impl CommBuffer {
+    const HEADER_SIZE: usize = mem::size_of::<CommunicateHeader>();
+
+    /// A buffer for the handler of the given GUID, with room for `capacity` bytes of message and response.
+    pub fn new(handler: &efi::Guid, capacity: usize) -> Self {
+        let mut buffer =
+            Self { buffer: vec![0; (Self::HEADER_SIZE + capacity).div_ceil(mem::size_of::<u64>())], capacity };
+        *buffer.header_mut() = CommunicateHeader { header_guid: *handler, message_length: 0 };
+        buffer
+    }
+
+    /// A buffer for the handler of the given GUID with the message, with room for a response of the same size.
+    pub fn with_message<T: IntoBytes + Immutable + ?Sized>(handler: &efi::Guid, message: &T) -> Self {
+        let mut buffer = Self::new(handler, mem::size_of_val(message));
+        buffer.set_message(message).expect("The buffer has room for the message.");
+        buffer
+    }
+
+    fn header(&self) -> &CommunicateHeader {
+        //SAFETY: The buffer starts with the header and is aligned for it.
+        unsafe { &*(self.buffer.as_ptr() as *const CommunicateHeader) }
+    }
+
+    fn header_mut(&mut self) -> &mut CommunicateHeader {
+        //SAFETY: The buffer starts with the header and is aligned for it.
+        unsafe { &mut *(self.buffer.as_mut_ptr() as *mut CommunicateHeader) }
+    }
+
+    /// The room for the message, after the header.
+    fn data_mut(&mut self) -> &mut [u8] {
+        //SAFETY: The header is followed by `capacity` bytes.
+        unsafe {
+            slice::from_raw_parts_mut((self.buffer.as_mut_ptr() as *mut u8).add(Self::HEADER_SIZE), self.capacity)
+        }
+    }
+
+    /// The GUID of the handler.
+    pub fn handler(&self) -> efi::Guid {
+        self.header().header_guid
+    }
+
+    /// Room for the message and the response, in bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Size of the header and the room for the message, the size given to the protocol.
+    pub fn size(&self) -> usize {
+        Self::HEADER_SIZE + self.capacity
+    }
+
+    /// Replaces the message.
+    ///
+    /// Returns `BUFFER_TOO_SMALL` if the message does not fit the capacity.
+    pub fn set_message<T: IntoBytes + Immutable + ?Sized>(&mut self, message: &T) -> Result<(), EfiError> {
+        let bytes = message.as_bytes();
+        if bytes.len() > self.capacity {
+            return Err(efi::Status::BUFFER_TOO_SMALL.into());
+        }
+        self.data_mut()[..bytes.len()].copy_from_slice(bytes);
+        self.header_mut().message_length = bytes.len();
+        Ok(())
+    }
+
+    /// The message, or after a communication the response of the handler.
+    ///
+    /// Returns `BAD_BUFFER_SIZE` if the handler reported a response larger than the capacity.
+    pub fn message(&self) -> Result<&[u8], EfiError> {
+        let length = self.header().message_length;
+        if length > self.capacity {
+            return Err(efi::Status::BAD_BUFFER_SIZE.into());
+        }
+        //SAFETY: The header is followed by `capacity` bytes.
+        Ok(unsafe { slice::from_raw_parts((self.buffer.as_ptr() as *const u8).add(Self::HEADER_SIZE), length) })
+    }
+
+    /// The response of the handler, read from the start of the message.
+    ///
+    /// Returns `BUFFER_TOO_SMALL` if the response is smaller than `T`.
+    pub fn response<T: FromBytes>(&self) -> Result<T, EfiError> {
+        T::read_from_prefix(self.message()?)
+            .map(|(response, _)| response)
+            .map_err(|_| efi::Status::BUFFER_TOO_SMALL.into())
+    }
+
+    /// The response of the handler, referenced in place.
+    ///
+    /// Returns `BUFFER_TOO_SMALL` if the response is smaller than `T`, and `INVALID_PARAMETER` if the message is not
+    /// aligned for `T`.
+    pub fn response_ref<T: FromBytes + KnownLayout + Immutable>(&self) -> Result<&T, EfiError> {
+        match T::ref_from_prefix(self.message()?) {
+            Ok((response, _)) => Ok(response),
+            Err(zerocopy::ConvertError::Alignment(_)) => Err(efi::Status::INVALID_PARAMETER.into()),
+            Err(_) => Err(efi::Status::BUFFER_TOO_SMALL.into()),
+        }
+    }
+
+    /// Reduces the room for the message, when it still fits.
+    fn shrink_to(&mut self, size: usize) -> bool {
+        match size.checked_sub(Self::HEADER_SIZE) {
+            Some(capacity) if capacity >= self.header().message_length && capacity < self.capacity => {
+                self.capacity = capacity;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for CommBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommBuffer")
+            .field("handler", &self.handler())
+            .field("message_length", &self.header().message_length)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// Typed access to the MM Communication 2 protocol.
+pub struct MmCommunication(&'static mut Protocol);
+
+impl MmCommunication {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&MmCommunication2Protocol, None).map(Self)
+    }
+
+    fn communicate_once(&mut self, buffer: &mut CommBuffer, size: &mut usize) -> efi::Status {
+        let buffer_ptr = buffer.buffer.as_mut_ptr() as *mut c_void;
+        // The buffer is identity mapped during boot, its virtual address is its physical one.
+        (self.0.communicate)(self.0 as *mut Protocol, buffer_ptr, buffer_ptr, size)
+    }
+
+    /// Sends the message of the buffer to its handler, the response replacing the message.
+    ///
+    /// When the buffer is larger than the MM implementation accepts, it is sent again with the size the
+    /// implementation reports if the message fits it, else `BAD_BUFFER_SIZE` is returned.
+    pub fn communicate(&mut self, buffer: &mut CommBuffer) -> Result<(), EfiError> {
+        let mut size = buffer.size();
+        let status = match self.communicate_once(buffer, &mut size) {
+            efi::Status::BAD_BUFFER_SIZE if buffer.shrink_to(size) => {
+                size = buffer.size();
+                self.communicate_once(buffer, &mut size)
+            }
+            status => status,
+        };
+        match status {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for MmCommunication {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for MmCommunication {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MmCommunication").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+
+    const HANDLER_GUID: efi::Guid =
+        efi::Guid::from_fields(0x12345678, 0x1234, 0x5678, 0x9a, 0xbc, &[0xde, 0xf0, 0x12, 0x34, 0x56, 0x78]);
+
+    /// Largest buffer the test MM implementation accepts.
+    const MAX_SIZE: usize = CommBuffer::HEADER_SIZE + 64;
+
+    #[derive(Debug, PartialEq, Eq, IntoBytes, FromBytes, Immutable, KnownLayout)]
+    #[repr(C)]
+    struct Request {
+        function: u64,
+        value: u64,
+    }
+
+    #[derive(Debug, PartialEq, Eq, IntoBytes, FromBytes, Immutable, KnownLayout)]
+    #[repr(C)]
+    struct Response {
+        status: u64,
+        values: [u64; 3],
+    }
+
+    /// Handler doubling the value of the request, three times.
+    extern "efiapi" fn communicate(
+        _: *mut Protocol,
+        physical: *mut c_void,
+        virtual_address: *mut c_void,
+        size: *mut usize,
+    ) -> efi::Status {
+        assert_eq!(physical, virtual_address);
+        if unsafe { *size } > MAX_SIZE {
+            unsafe { *size = MAX_SIZE };
+            return efi::Status::BAD_BUFFER_SIZE;
+        }
+        let header = unsafe { &mut *(physical as *mut CommunicateHeader) };
+        if header.header_guid != HANDLER_GUID {
+            return efi::Status::NOT_FOUND;
+        }
+        let data = unsafe { (physical as *mut u8).add(mem::size_of::<CommunicateHeader>()) };
+        let request = unsafe { (data as *const Request).read() };
+        let response = Response { status: 0, values: [request.value * 2; 3] };
+        if unsafe { *size } < mem::size_of::<CommunicateHeader>() + mem::size_of::<Response>() {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { (data as *mut Response).write(response) };
+        header.message_length = mem::size_of::<Response>();
+        efi::Status::SUCCESS
+    }
+
+    fn mm_communication() -> MmCommunication {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<MmCommunication2Protocol, Protocol>()
+            .returning(|_, _| Ok(Box::leak(Box::new(Protocol { communicate }))));
+        MmCommunication::locate(&boot_services).unwrap()
+    }
+
+    #[test]
+    fn test_comm_buffer() {
+        let mut buffer = CommBuffer::with_message(&HANDLER_GUID, &Request { function: 1, value: 2 });
+        assert_eq!(HANDLER_GUID, buffer.handler());
+        assert_eq!(16, buffer.capacity());
+        assert_eq!(Request { function: 1, value: 2 }, buffer.response().unwrap());
+        assert_eq!(&Request { function: 1, value: 2 }, buffer.response_ref::<Request>().unwrap());
+        assert_eq!(efi::Status::BUFFER_TOO_SMALL, buffer.response::<Response>().unwrap_err());
+        assert_eq!(efi::Status::BUFFER_TOO_SMALL, buffer.set_message(&[0_u8; 17]).unwrap_err());
+        buffer.set_message(&[1_u8, 2, 3]).unwrap();
+        assert_eq!(&[1, 2, 3], buffer.message().unwrap());
+    }
+
+    #[test]
+    fn test_communicate() {
+        let mut mm_communication = mm_communication();
+
+        let mut buffer = CommBuffer::new(&HANDLER_GUID, 32);
+        buffer.set_message(&Request { function: 1, value: 21 }).unwrap();
+        mm_communication.communicate(&mut buffer).unwrap();
+        assert_eq!(Response { status: 0, values: [42; 3] }, buffer.response().unwrap());
+
+        // The response does not fit the room of the request.
+        let mut buffer = CommBuffer::with_message(&HANDLER_GUID, &Request { function: 1, value: 21 });
+        assert_eq!(efi::Status::BUFFER_TOO_SMALL, mm_communication.communicate(&mut buffer).unwrap_err());
+
+        // Sent again with the size the MM implementation accepts.
+        let mut buffer = CommBuffer::new(&HANDLER_GUID, 0x1000);
+        buffer.set_message(&Request { function: 1, value: 1 }).unwrap();
+        mm_communication.communicate(&mut buffer).unwrap();
+        assert_eq!(64, buffer.capacity());
+        assert_eq!(&Response { status: 0, values: [2; 3] }, buffer.response_ref::<Response>().unwrap());
+
+        // The message does not fit the size the MM implementation accepts.
+        let mut buffer = CommBuffer::new(&HANDLER_GUID, 0x1000);
+        buffer.set_message(&[0_u8; 0x100]).unwrap();
+        assert_eq!(efi::Status::BAD_BUFFER_SIZE, mm_communication.communicate(&mut buffer).unwrap_err());
+
+        let mut buffer = CommBuffer::new(&efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6]), 32);
+        assert_eq!(efi::Status::NOT_FOUND, mm_communication.communicate(&mut buffer).unwrap_err());
+    }
+}
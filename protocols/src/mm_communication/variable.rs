@@ -0,0 +1,463 @@
+//! Variable services of the MM variable driver, reached through MM Communicate.
+//!
+//! [`SmmVariableServices`] implements [`RuntimeServices`] by sending `SMM_VARIABLE_COMMUNICATE` requests to the
+//! variable handler of the Management Mode, the store itself rather than the runtime cache of the variable driver.
+//! Code written against the runtime services, like a test of the variable reclaim, runs unchanged over it:
+//!
+//! ```ignore
+//! let variables = SmmVariableServices::locate(&boot_services)?;
+//! variables.set_variable(&name, &namespace, efi::VARIABLE_BOOTSERVICE_ACCESS, &data)?;
+//! let (data, attributes): (Vec<u8>, u32) = variables.get_variable(&name, &namespace, None)?;
+//! ```
+//!
+//! The time services, the monotonic count and the pointer conversion are not variable services, they return
+//! `UNSUPPORTED`.
+//!
+//! [EDK II: MdeModulePkg/Include/Guid/SmmVariableCommon.h](https://github.com/tianocore/edk2/blob/master/MdeModulePkg/Include/Guid/SmmVariableCommon.h)
+
+use alloc::vec::Vec;
+use core::{cell::RefCell, ffi::c_void, fmt, mem};
+
+use boot_services::BootServices;
+use r_efi::efi;
+use runtime_services::{
+    variable_services::{GetVariableStatus, VariableInfo},
+    RuntimeServices,
+};
+use status::EfiError;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use super::{CommBuffer, MmCommunication};
+
+/// GUID of the variable handler, `gEfiSmmVariableProtocolGuid`.
+pub const HANDLER_GUID: efi::Guid =
+    efi::Guid::from_fields(0xed32d533, 0x99e6, 0x4209, 0x9c, 0xc0, &[0x2d, 0x72, 0xcd, 0xd9, 0x98, 0xa7]);
+
+const FUNCTION_GET_VARIABLE: usize = 1;
+const FUNCTION_GET_NEXT_VARIABLE_NAME: usize = 2;
+const FUNCTION_SET_VARIABLE: usize = 3;
+const FUNCTION_QUERY_VARIABLE_INFO: usize = 4;
+const FUNCTION_GET_PAYLOAD_SIZE: usize = 11;
+
+/// `SMM_VARIABLE_COMMUNICATE_HEADER`, followed by the data of the function.
+#[derive(Debug, Clone, Copy, IntoBytes, FromBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct VariableHeader {
+    function: usize,
+    return_status: usize,
+}
+
+/// `SMM_VARIABLE_COMMUNICATE_ACCESS_VARIABLE`, followed by the name and the data.
+#[derive(Debug, Clone, Copy, IntoBytes, FromBytes, Immutable, KnownLayout)]
+#[repr(C, packed)]
+struct AccessVariable {
+    guid: [u8; 16],
+    data_size: usize,
+    name_size: usize,
+    attributes: u32,
+}
+
+/// `SMM_VARIABLE_COMMUNICATE_GET_NEXT_VARIABLE_NAME`, followed by the name.
+#[derive(Debug, Clone, Copy, IntoBytes, FromBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct GetNextVariableName {
+    guid: [u8; 16],
+    name_size: usize,
+}
+
+/// `SMM_VARIABLE_COMMUNICATE_QUERY_VARIABLE_INFO`.
+#[derive(Debug, Clone, Copy, IntoBytes, FromBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct QueryVariableInfo {
+    maximum_variable_storage_size: u64,
+    remaining_variable_storage_size: u64,
+    maximum_variable_size: u64,
+    attributes: u32,
+    reserved: u32,
+}
+
+/// `SMM_VARIABLE_COMMUNICATE_GET_PAYLOAD_SIZE`.
+#[derive(Debug, Clone, Copy, IntoBytes, FromBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct GetPayloadSize {
+    variable_payload_size: usize,
+}
+
+/// Size in bytes of a null-terminated name, terminator included.
+fn name_size(name: &[u16]) -> Option<usize> {
+    name.iter().position(|&c| c == 0).map(|end| (end + 1) * mem::size_of::<u16>())
+}
+
+/// Runtime services over the variable handler of the Management Mode.
+pub struct SmmVariableServices {
+    mm_communication: RefCell<MmCommunication>,
+    payload_size: usize,
+}
+
+impl SmmVariableServices {
+    /// Variable services over the MM Communication protocol, asking the handler the size of its payloads.
+    pub fn new(mm_communication: MmCommunication) -> Result<Self, EfiError> {
+        let mut services = Self { mm_communication: RefCell::new(mm_communication), payload_size: usize::MAX };
+        let (status, response) =
+            services.communicate(FUNCTION_GET_PAYLOAD_SIZE, GetPayloadSize { variable_payload_size: 0 }.as_bytes())?;
+        if status.is_error() {
+            return Err(status.into());
+        }
+        let (payload, _) = GetPayloadSize::read_from_prefix(&response).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+        services.payload_size = payload.variable_payload_size;
+        Ok(services)
+    }
+
+    /// Locates the MM Communication protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        Self::new(MmCommunication::locate(boot_services)?)
+    }
+
+    /// Largest data of a function the handler accepts, in bytes, names and variable data included.
+    pub fn payload_size(&self) -> usize {
+        self.payload_size
+    }
+
+    /// Sends the data of a function to the handler, returns the status of the function and its data.
+    fn communicate(&self, function: usize, data: &[u8]) -> Result<(efi::Status, Vec<u8>), EfiError> {
+        let mut message =
+            VariableHeader { function, return_status: efi::Status::SUCCESS.as_usize() }.as_bytes().to_vec();
+        message.extend_from_slice(data);
+        let mut buffer = CommBuffer::with_message(&HANDLER_GUID, message.as_slice());
+        self.mm_communication.borrow_mut().communicate(&mut buffer)?;
+        let (header, data) =
+            VariableHeader::read_from_prefix(buffer.message()?).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+        Ok((efi::Status::from_usize(header.return_status), data.to_vec()))
+    }
+}
+
+impl RuntimeServices for SmmVariableServices {
+    unsafe fn set_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> Result<(), EfiError> {
+        let name_size = name_size(name).ok_or(efi::Status::INVALID_PARAMETER)?;
+        if mem::size_of::<AccessVariable>() + name_size + data.len() > self.payload_size {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let access = AccessVariable { guid: *namespace.as_bytes(), data_size: data.len(), name_size, attributes };
+        let mut request = access.as_bytes().to_vec();
+        request.extend_from_slice(&name.as_bytes()[..name_size]);
+        request.extend_from_slice(data);
+        match self.communicate(FUNCTION_SET_VARIABLE, &request)? {
+            (s, _) if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    unsafe fn get_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        data: Option<&mut [u8]>,
+    ) -> GetVariableStatus {
+        let Some(name_size) = name_size(name) else {
+            return GetVariableStatus::Error(efi::Status::INVALID_PARAMETER);
+        };
+        let Some(room) = self.payload_size.checked_sub(mem::size_of::<AccessVariable>() + name_size) else {
+            return GetVariableStatus::Error(efi::Status::INVALID_PARAMETER);
+        };
+        // The data larger than the payload is reported as a buffer too small.
+        let data_size = data.as_ref().map_or(0, |data| data.len()).min(room);
+        let access = AccessVariable { guid: *namespace.as_bytes(), data_size, name_size, attributes: 0 };
+        let mut request = access.as_bytes().to_vec();
+        request.extend_from_slice(&name.as_bytes()[..name_size]);
+        request.resize(request.len() + data_size, 0);
+
+        let (status, response) = match self.communicate(FUNCTION_GET_VARIABLE, &request) {
+            Ok(response) => response,
+            Err(status) => return GetVariableStatus::Error(status.into()),
+        };
+        let Ok((found, rest)) = AccessVariable::read_from_prefix(&response) else {
+            return GetVariableStatus::Error(efi::Status::BAD_BUFFER_SIZE);
+        };
+        let (found_size, attributes) = (found.data_size, found.attributes);
+        match status {
+            efi::Status::BUFFER_TOO_SMALL => GetVariableStatus::BufferTooSmall { data_size: found_size, attributes },
+            s if s.is_error() => GetVariableStatus::Error(s),
+            s => {
+                let found_data = match rest.get(name_size..name_size + found_size) {
+                    Some(found_data) if found_size <= data_size => found_data,
+                    _ => return GetVariableStatus::Error(efi::Status::BAD_BUFFER_SIZE),
+                };
+                if let Some(data) = data {
+                    data[..found_size].copy_from_slice(found_data);
+                }
+                match s.is_warning() {
+                    true => GetVariableStatus::Warning { status: s, data_size: found_size, attributes },
+                    false => GetVariableStatus::Success { data_size: found_size, attributes },
+                }
+            }
+        }
+    }
+
+    unsafe fn get_next_variable_name_unchecked(
+        &self,
+        prev_name: &[u16],
+        prev_namespace: &efi::Guid,
+        next_name: &mut Vec<u16>,
+        next_namespace: &mut efi::Guid,
+    ) -> Result<(), EfiError> {
+        let prev_name_size = name_size(prev_name).ok_or(efi::Status::INVALID_PARAMETER)?;
+        let room = self.payload_size.saturating_sub(mem::size_of::<GetNextVariableName>());
+        if prev_name_size > room {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let mut name_size = (next_name.len() * mem::size_of::<u16>()).clamp(prev_name_size, room);
+
+        // Sent at most two times, the first response giving the size of the next name when it is larger.
+        for _ in 0..2 {
+            let mut request = GetNextVariableName { guid: *prev_namespace.as_bytes(), name_size }.as_bytes().to_vec();
+            request.extend_from_slice(&prev_name.as_bytes()[..prev_name_size]);
+            request.resize(mem::size_of::<GetNextVariableName>() + name_size, 0);
+
+            let (status, response) = self.communicate(FUNCTION_GET_NEXT_VARIABLE_NAME, &request)?;
+            let (found, name) =
+                GetNextVariableName::read_from_prefix(&response).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+            match status {
+                efi::Status::BUFFER_TOO_SMALL if found.name_size > name_size && found.name_size <= room => {
+                    name_size = found.name_size;
+                }
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    let name = name.get(..found.name_size).ok_or(efi::Status::BAD_BUFFER_SIZE)?;
+                    next_name.clear();
+                    next_name.extend(name.chunks_exact(2).map(|c| u16::from_ne_bytes([c[0], c[1]])));
+                    *next_namespace = efi::Guid::from_bytes(&found.guid);
+                    return Ok(());
+                }
+            }
+        }
+        Err(efi::Status::BUFFER_TOO_SMALL.into())
+    }
+
+    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, EfiError> {
+        let request = QueryVariableInfo {
+            maximum_variable_storage_size: 0,
+            remaining_variable_storage_size: 0,
+            maximum_variable_size: 0,
+            attributes,
+            reserved: 0,
+        };
+        match self.communicate(FUNCTION_QUERY_VARIABLE_INFO, request.as_bytes())? {
+            (s, _) if s.is_error() => Err(s.into()),
+            (_, response) => {
+                let (info, _) =
+                    QueryVariableInfo::read_from_prefix(&response).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+                Ok(VariableInfo {
+                    maximum_variable_storage_size: info.maximum_variable_storage_size,
+                    remaining_variable_storage_size: info.remaining_variable_storage_size,
+                    maximum_variable_size: info.maximum_variable_size,
+                })
+            }
+        }
+    }
+
+    fn get_next_high_monotonic_count(&self) -> Result<u32, EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn convert_pointer(&self, _debug_disposition: usize, _address: *mut *mut c_void) -> Result<(), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn get_wakeup_time_unchecked(&self) -> Result<(bool, bool, efi::Time), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn set_time_unchecked(&self, _time: &efi::Time) -> Result<(), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn get_time_unchecked(&self) -> Result<(efi::Time, efi::TimeCapabilities), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn set_wakeup_time_unchecked(&self, _enable: bool, _time: &efi::Time) -> Result<(), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+}
+
+impl fmt::Debug for SmmVariableServices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmmVariableServices").field("payload_size", &self.payload_size).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mm_communication::{CommunicateHeader, MmCommunication2Protocol, Protocol};
+    use alloc::{boxed::Box, vec};
+    use boot_services::MockBootServices;
+    use core::{ffi::c_void, slice};
+    use runtime_services::{conformance, mock::InMemoryRuntimeServices};
+
+    const PAYLOAD_SIZE: usize = 0x200;
+
+    std::thread_local! {
+        /// Store of the test variable handler, each test running on its own thread.
+        static STORE: InMemoryRuntimeServices = InMemoryRuntimeServices::new();
+    }
+
+    fn to_name(bytes: &[u8]) -> Vec<u16> {
+        let name = bytes.chunks_exact(2).map(|c| u16::from_ne_bytes([c[0], c[1]])).collect::<Vec<_>>();
+        let len = name.iter().position(|&c| c == 0).map_or(name.len(), |end| end + 1);
+        name[..len].to_vec()
+    }
+
+    /// Variable handler over the in-memory store, following the variable driver of EDK II.
+    fn handle(store: &InMemoryRuntimeServices, function: usize, data: &mut [u8]) -> efi::Status {
+        match function {
+            FUNCTION_GET_VARIABLE => {
+                let (mut access, rest) = AccessVariable::read_from_prefix(data).unwrap();
+                let mut name = to_name(&rest[..access.name_size]);
+                let buffer = &mut data[mem::size_of::<AccessVariable>() + access.name_size..][..access.data_size];
+                let namespace = efi::Guid::from_bytes(&access.guid);
+                let status = match unsafe {
+                    store.get_variable_unchecked(&mut name, &namespace, Some(buffer).filter(|b| !b.is_empty()))
+                } {
+                    GetVariableStatus::Error(status) => return status,
+                    GetVariableStatus::BufferTooSmall { data_size, attributes } => {
+                        (access.data_size, access.attributes) = (data_size, attributes);
+                        efi::Status::BUFFER_TOO_SMALL
+                    }
+                    GetVariableStatus::Success { data_size, attributes } => {
+                        (access.data_size, access.attributes) = (data_size, attributes);
+                        efi::Status::SUCCESS
+                    }
+                    GetVariableStatus::Warning { status, data_size, attributes } => {
+                        (access.data_size, access.attributes) = (data_size, attributes);
+                        status
+                    }
+                };
+                access.write_to_prefix(data).unwrap();
+                status
+            }
+            FUNCTION_SET_VARIABLE => {
+                let (access, rest) = AccessVariable::read_from_prefix(data).unwrap();
+                let mut name = to_name(&rest[..access.name_size]);
+                let variable = &rest[access.name_size..][..access.data_size];
+                let namespace = efi::Guid::from_bytes(&access.guid);
+                match unsafe { store.set_variable_unchecked(&mut name, &namespace, access.attributes, variable) } {
+                    Ok(()) => efi::Status::SUCCESS,
+                    Err(status) => status.into(),
+                }
+            }
+            FUNCTION_GET_NEXT_VARIABLE_NAME => {
+                let (mut next, rest) = GetNextVariableName::read_from_prefix(data).unwrap();
+                let prev_name = to_name(&rest[..next.name_size]);
+                let (mut name, mut namespace) = (Vec::new(), efi::Guid::from_bytes(&next.guid));
+                let prev_namespace = namespace;
+                if let Err(status) = unsafe {
+                    store.get_next_variable_name_unchecked(&prev_name, &prev_namespace, &mut name, &mut namespace)
+                } {
+                    return status.into();
+                }
+                let name = to_name(name.as_bytes());
+                let status = match name.as_bytes().len() > next.name_size {
+                    true => efi::Status::BUFFER_TOO_SMALL,
+                    false => {
+                        data[mem::size_of::<GetNextVariableName>()..][..name.as_bytes().len()]
+                            .copy_from_slice(name.as_bytes());
+                        next.guid = *namespace.as_bytes();
+                        efi::Status::SUCCESS
+                    }
+                };
+                next.name_size = name.as_bytes().len();
+                next.write_to_prefix(data).unwrap();
+                status
+            }
+            FUNCTION_QUERY_VARIABLE_INFO => {
+                let (mut query, _) = QueryVariableInfo::read_from_prefix(data).unwrap();
+                match store.query_variable_info(query.attributes) {
+                    Ok(info) => {
+                        query.maximum_variable_storage_size = info.maximum_variable_storage_size;
+                        query.remaining_variable_storage_size = info.remaining_variable_storage_size;
+                        query.maximum_variable_size = info.maximum_variable_size;
+                        query.write_to_prefix(data).unwrap();
+                        efi::Status::SUCCESS
+                    }
+                    Err(status) => status.into(),
+                }
+            }
+            FUNCTION_GET_PAYLOAD_SIZE => {
+                GetPayloadSize { variable_payload_size: PAYLOAD_SIZE }.write_to_prefix(data).unwrap();
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    extern "efiapi" fn communicate(
+        _: *mut Protocol,
+        physical: *mut c_void,
+        _: *mut c_void,
+        _: *mut usize,
+    ) -> efi::Status {
+        let header = unsafe { &*(physical as *const CommunicateHeader) };
+        assert_eq!(HANDLER_GUID, header.header_guid);
+        let message = unsafe {
+            slice::from_raw_parts_mut(
+                (physical as *mut u8).add(mem::size_of::<CommunicateHeader>()),
+                header.message_length,
+            )
+        };
+        let (mut variable_header, _) = VariableHeader::read_from_prefix(message).unwrap();
+        let data = &mut message[mem::size_of::<VariableHeader>()..];
+        assert!(function_fits(variable_header.function, data.len()));
+        let status = STORE.with(|store| handle(store, variable_header.function, data));
+        variable_header.return_status = status.as_usize();
+        variable_header.write_to_prefix(message).unwrap();
+        efi::Status::SUCCESS
+    }
+
+    /// Whether the data of the function fits the payload, the driver rejecting the larger ones.
+    fn function_fits(function: usize, size: usize) -> bool {
+        function == FUNCTION_GET_PAYLOAD_SIZE || size <= PAYLOAD_SIZE
+    }
+
+    fn smm_variable_services() -> SmmVariableServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<MmCommunication2Protocol, Protocol>()
+            .returning(|_, _| Ok(Box::leak(Box::new(Protocol { communicate }))));
+        SmmVariableServices::locate(&boot_services).unwrap()
+    }
+
+    #[test]
+    fn test_conformance() {
+        conformance::run_all(&smm_variable_services());
+    }
+
+    #[test]
+    fn test_payload_size() {
+        let variables = smm_variable_services();
+        assert_eq!(PAYLOAD_SIZE, variables.payload_size());
+        let name = [b'A' as u16, 0];
+        let namespace = conformance::NAMESPACE;
+
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            variables.set_variable(&name, &namespace, efi::VARIABLE_BOOTSERVICE_ACCESS, &vec![0_u8; PAYLOAD_SIZE])
+        );
+        let data = vec![0x5A_u8; PAYLOAD_SIZE / 2];
+        variables.set_variable(&name, &namespace, efi::VARIABLE_BOOTSERVICE_ACCESS, &data).unwrap();
+        // The size hint larger than the payload is reduced to fit.
+        let (found, attributes) = variables.get_variable::<Vec<u8>>(&name, &namespace, Some(PAYLOAD_SIZE * 2)).unwrap();
+        assert_eq!((data.as_slice(), efi::VARIABLE_BOOTSERVICE_ACCESS), (&found[..data.len()], attributes));
+        let info = variables.query_variable_info(efi::VARIABLE_BOOTSERVICE_ACCESS).unwrap();
+        assert_eq!(InMemoryRuntimeServices::DEFAULT_VARIABLE_SIZE as u64, info.maximum_variable_size);
+
+        assert_eq!(efi::Status::UNSUPPORTED, variables.get_time().unwrap_err());
+        assert_eq!(efi::Status::UNSUPPORTED, variables.get_next_high_monotonic_count().unwrap_err());
+    }
+}
@@ -0,0 +1,565 @@
+//! Multi-Processor Services protocol of the PI specification.
+//!
+//! [`MpServices`] describes the processors and runs closures on the application processors (APs), while the
+//! bootstrap processor (BSP) waits for them, or keeps running until an [`ApDispatch`] completes:
+//!
+//! ```ignore
+//! let mut mp_services = MpServices::locate(&boot_services)?;
+//! let initialized = AtomicUsize::new(0);
+//! mp_services.startup_all_aps(false, &|| {
+//!     init_processor();
+//!     initialized.fetch_add(1, Ordering::SeqCst);
+//! })?;
+//!
+//! let dispatch = mp_services.startup_this_ap_nonblocking(&boot_services, 1, 0, move || run_test())?;
+//! do_other_work();
+//! dispatch.wait()?;
+//! ```
+//!
+//! The closures run concurrently with the BSP and with each other, so they are `Sync`. A closure started without
+//! blocking is kept until its dispatch completes, it is leaked if the dispatch is dropped before.
+//!
+//! [PI Spec Documentation: Volume 2 - 13.4 MP Services Protocol](https://uefi.org/specs/PI/1.8/V2_DXE_Boot_Services_Protocol.html#efi-mp-services-protocol)
+
+use alloc::boxed::Box;
+use core::{cell::Cell, ffi::c_void, fmt, mem, ptr};
+
+use boot_services::{event::EventType, protocol_handler, tpl::Tpl, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use efi::protocols::mp_services;
+
+pub mod connect;
+
+type MpServicesProtocol = mp_services::Protocol;
+
+/// Number of processors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorCount {
+    pub total: usize,
+    pub enabled: usize,
+}
+
+/// Information about a processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorInfo {
+    /// The APIC ID on x64, the MPIDR on AArch64.
+    pub processor_id: u64,
+    pub is_bsp: bool,
+    pub enabled: bool,
+    pub healthy: bool,
+    pub package: u32,
+    pub core: u32,
+    pub thread: u32,
+}
+
+impl From<&mp_services::ProcessorInformation> for ProcessorInfo {
+    fn from(information: &mp_services::ProcessorInformation) -> Self {
+        Self {
+            processor_id: information.processor_id,
+            is_bsp: information.status_flag & mp_services::PROCESSOR_AS_BSP_BIT != 0,
+            enabled: information.status_flag & mp_services::PROCESSOR_ENABLED_BIT != 0,
+            healthy: information.status_flag & mp_services::PROCESSOR_HEALTH_STATUS_BIT != 0,
+            package: information.location.package,
+            core: information.location.core,
+            thread: information.location.thread,
+        }
+    }
+}
+
+/// Runs the closure the AP procedure is given as context.
+extern "efiapi" fn run_closure<F: Fn() + Sync>(context: *mut c_void) {
+    //SAFETY: The context is the closure given to the protocol, kept until the APs are done.
+    let closure = unsafe { &*(context as *const F) };
+    closure();
+}
+
+/// Typed access to the Multi-Processor Services protocol.
+pub struct MpServices(&'static mut MpServicesProtocol);
+
+impl MpServices {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::MpService, None).map(Self)
+    }
+
+    fn this(&self) -> *mut MpServicesProtocol {
+        self.0 as *const MpServicesProtocol as *mut MpServicesProtocol
+    }
+
+    /// The number of processors, and of the enabled ones.
+    pub fn number_of_processors(&self) -> Result<ProcessorCount, EfiError> {
+        let (mut total, mut enabled) = (0, 0);
+        match unsafe { r_efi::efi_call(self.0.get_number_of_processors, (self.this(), &mut total, &mut enabled)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(ProcessorCount { total, enabled }),
+        }
+    }
+
+    /// Information about the processor of the given number, from 0 to the total number of processors.
+    pub fn processor_info(&self, processor: usize) -> Result<ProcessorInfo, EfiError> {
+        //SAFETY: The information is plain data, overwritten by the protocol.
+        let mut information: mp_services::ProcessorInformation = unsafe { mem::zeroed() };
+        match unsafe { r_efi::efi_call(self.0.get_processor_info, (self.this(), processor, &mut information)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(ProcessorInfo::from(&information)),
+        }
+    }
+
+    /// The number of the processor calling.
+    pub fn who_am_i(&self) -> Result<usize, EfiError> {
+        let mut processor = 0;
+        match unsafe { r_efi::efi_call(self.0.who_am_i, (self.this(), &mut processor)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(processor),
+        }
+    }
+
+    /// Runs `closure` on every enabled AP, one after the other if `single_thread`, and waits for them to be done.
+    ///
+    /// There is no timeout as APs still running when it expires would outlive the borrow of `closure`, the
+    /// [`MpServices::startup_all_aps_nonblocking`] form owns its closure and takes one.
+    ///
+    /// Returns `NOT_STARTED` if there is no enabled AP.
+    pub fn startup_all_aps<F: Fn() + Sync>(&mut self, single_thread: bool, closure: &F) -> Result<(), EfiError> {
+        match unsafe {
+            r_efi::efi_call(
+                self.0.startup_all_aps,
+                (
+                    self.this(),
+                    run_closure::<F>,
+                    single_thread.into(),
+                    ptr::null_mut(),
+                    0,
+                    closure as *const F as *mut c_void,
+                    ptr::null_mut(),
+                ),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs `closure` on the AP of the given number and waits for it to be done.
+    ///
+    /// Like for [`MpServices::startup_all_aps`], the [`MpServices::startup_this_ap_nonblocking`] form takes a timeout.
+    pub fn startup_this_ap<F: Fn() + Sync>(&mut self, processor: usize, closure: &F) -> Result<(), EfiError> {
+        match unsafe {
+            r_efi::efi_call(
+                self.0.startup_this_ap,
+                (
+                    self.this(),
+                    run_closure::<F>,
+                    processor,
+                    ptr::null_mut(),
+                    0,
+                    closure as *const F as *mut c_void,
+                    ptr::null_mut(),
+                ),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs `closure` on every enabled AP like [`MpServices::startup_all_aps`], without waiting for them.
+    pub fn startup_all_aps_nonblocking<'a, B: BootServices, F: Fn() + Sync + 'static>(
+        &mut self,
+        boot_services: &'a B,
+        single_thread: bool,
+        timeout: usize,
+        closure: F,
+    ) -> Result<ApDispatch<'a, B>, EfiError> {
+        let this = self.this();
+        let startup_all_aps = self.0.startup_all_aps;
+        ApDispatch::start(boot_services, closure, |event, context| unsafe {
+            r_efi::efi_call(
+                startup_all_aps,
+                (this, run_closure::<F>, single_thread.into(), event, timeout, context, ptr::null_mut()),
+            )
+        })
+    }
+
+    /// Runs `closure` on the AP of the given number like [`MpServices::startup_this_ap`], without waiting for it.
+    pub fn startup_this_ap_nonblocking<'a, B: BootServices, F: Fn() + Sync + 'static>(
+        &mut self,
+        boot_services: &'a B,
+        processor: usize,
+        timeout: usize,
+        closure: F,
+    ) -> Result<ApDispatch<'a, B>, EfiError> {
+        let this = self.this();
+        let startup_this_ap = self.0.startup_this_ap;
+        ApDispatch::start(boot_services, closure, |event, context| unsafe {
+            r_efi::efi_call(
+                startup_this_ap,
+                (this, run_closure::<F>, processor, event, timeout, context, ptr::null_mut()),
+            )
+        })
+    }
+
+    /// Makes the processor of the given number the BSP, the current BSP becoming an AP enabled if `enable_old_bsp`.
+    pub fn switch_bsp(&mut self, processor: usize, enable_old_bsp: bool) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.0.switch_bsp, (self.this(), processor, enable_old_bsp.into())) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Enables or disables the AP of the given number, and sets its health when `healthy` is some.
+    pub fn enable_disable_ap(&mut self, processor: usize, enable: bool, healthy: Option<bool>) -> Result<(), EfiError> {
+        let mut health_flag = match healthy {
+            Some(true) => mp_services::PROCESSOR_HEALTH_STATUS_BIT,
+            _ => 0,
+        };
+        let health_flag_ptr = match healthy {
+            Some(_) => &mut health_flag as *mut u32,
+            None => ptr::null_mut(),
+        };
+        match unsafe {
+            r_efi::efi_call(self.0.enable_disable_ap, (self.this(), processor, enable.into(), health_flag_ptr))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut MpServicesProtocol> for MpServices {
+    fn from(protocol: &'static mut MpServicesProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for MpServices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MpServices").field("number_of_processors", &self.number_of_processors()).finish()
+    }
+}
+
+/// A closure started on APs without waiting for them, signaling its event when they are done.
+pub struct ApDispatch<'a, B: BootServices> {
+    boot_services: &'a B,
+    event: efi::Event,
+    /// The closure, boxed so that it does not move while the APs run it.
+    closure: *mut c_void,
+    drop_closure: unsafe fn(*mut c_void),
+    /// Whether [`ApDispatch::is_completed`] consumed the signal of the event.
+    signaled: Cell<bool>,
+    completed: bool,
+}
+
+impl<'a, B: BootServices> ApDispatch<'a, B> {
+    fn start<F: Fn() + Sync + 'static>(
+        boot_services: &'a B,
+        closure: F,
+        startup: impl FnOnce(efi::Event, *mut c_void) -> efi::Status,
+    ) -> Result<Self, EfiError> {
+        unsafe fn drop_closure<F>(closure: *mut c_void) {
+            drop(Box::from_raw(closure as *mut F));
+        }
+
+        let event = boot_services.create_event(EventType::NONE, Tpl::CALLBACK, None, None::<&'static ()>)?;
+        let closure = Box::into_raw(Box::new(closure)) as *mut c_void;
+        let dispatch = Self {
+            boot_services,
+            event,
+            closure,
+            drop_closure: drop_closure::<F>,
+            signaled: Cell::new(false),
+            completed: false,
+        };
+        match startup(event, closure) {
+            s if s.is_error() => {
+                // The APs did not start, the dispatch is complete.
+                let _ = dispatch.complete();
+                Err(s.into())
+            }
+            _ => Ok(dispatch),
+        }
+    }
+
+    /// Frees the closure and the event once the APs are done.
+    fn complete(mut self) -> Result<(), EfiError> {
+        self.completed = true;
+        //SAFETY: The closure was boxed by `start` and the APs no longer run it.
+        unsafe { (self.drop_closure)(self.closure) };
+        self.boot_services.close_event(self.event)
+    }
+
+    /// Whether the APs are done.
+    pub fn is_completed(&self) -> Result<bool, EfiError> {
+        if self.signaled.get() {
+            return Ok(true);
+        }
+        // Checking the event resets its signal, which `wait` must not wait for again.
+        match self.boot_services.check_event(self.event) {
+            Ok(()) => {
+                self.signaled.set(true);
+                Ok(true)
+            }
+            Err(status) if status == efi::Status::NOT_READY => Ok(false),
+            Err(status) => Err(status),
+        }
+    }
+
+    /// Waits for the APs to be done, or for the timeout of the dispatch to expire.
+    pub fn wait(self) -> Result<(), EfiError> {
+        if !self.signaled.get() {
+            self.boot_services.wait_for_event(&mut [self.event])?;
+        }
+        self.complete()
+    }
+}
+
+impl<B: BootServices> Drop for ApDispatch<'_, B> {
+    fn drop(&mut self) {
+        if !self.completed && self.is_completed() == Ok(true) {
+            //SAFETY: The closure was boxed by `start` and the APs are done.
+            unsafe { (self.drop_closure)(self.closure) };
+            let _ = self.boot_services.close_event(self.event);
+        }
+        // Otherwise the APs may still run the closure and signal the event, both are leaked.
+    }
+}
+
+impl<B: BootServices> fmt::Debug for ApDispatch<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApDispatch").field("event", &self.event).field("completed", &self.completed).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        vec::Vec,
+    };
+
+    /// Number of processors of the test, the BSP is the processor 0 and the processor 3 is disabled.
+    const PROCESSORS: usize = 4;
+
+    /// Events signaled by the test protocol.
+    static SIGNALED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    extern "efiapi" fn get_number_of_processors(
+        _: *mut MpServicesProtocol,
+        total: *mut usize,
+        enabled: *mut usize,
+    ) -> efi::Status {
+        unsafe {
+            *total = PROCESSORS;
+            *enabled = PROCESSORS - 1;
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_processor_info(
+        _: *mut MpServicesProtocol,
+        processor: usize,
+        information: *mut mp_services::ProcessorInformation,
+    ) -> efi::Status {
+        if processor >= PROCESSORS {
+            return efi::Status::NOT_FOUND;
+        }
+        let mut status_flag = mp_services::PROCESSOR_HEALTH_STATUS_BIT;
+        if processor == 0 {
+            status_flag |= mp_services::PROCESSOR_AS_BSP_BIT;
+        }
+        if processor != 3 {
+            status_flag |= mp_services::PROCESSOR_ENABLED_BIT;
+        }
+        unsafe {
+            (*information).processor_id = processor as u64 * 2;
+            (*information).status_flag = status_flag;
+            (*information).location =
+                mp_services::CpuPhysicalLocation { package: 0, core: processor as u32, thread: 0 };
+        }
+        efi::Status::SUCCESS
+    }
+
+    /// Runs the procedure on a thread per AP, signaling the event when they are all done.
+    fn run_aps(processors: Vec<usize>, procedure: mp_services::ApProcedure, event: efi::Event, context: *mut c_void) {
+        let context = context as usize;
+        let threads: Vec<_> = processors
+            .into_iter()
+            .map(|_| thread::spawn(move || unsafe { r_efi::efi_call(procedure, (context as *mut c_void,)) }))
+            .collect();
+        match event.is_null() {
+            true => threads.into_iter().for_each(|thread| thread.join().unwrap()),
+            false => {
+                let event = event as usize;
+                thread::spawn(move || {
+                    threads.into_iter().for_each(|thread| thread.join().unwrap());
+                    SIGNALED.lock().unwrap().push(event);
+                });
+            }
+        }
+    }
+
+    extern "efiapi" fn startup_all_aps(
+        _: *mut MpServicesProtocol,
+        procedure: mp_services::ApProcedure,
+        _single_thread: efi::Boolean,
+        event: efi::Event,
+        _timeout: usize,
+        context: *mut c_void,
+        _failed_cpu_list: *mut *mut usize,
+    ) -> efi::Status {
+        run_aps(vec![1, 2], procedure, event, context);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn startup_this_ap(
+        _: *mut MpServicesProtocol,
+        procedure: mp_services::ApProcedure,
+        processor: usize,
+        event: efi::Event,
+        _timeout: usize,
+        context: *mut c_void,
+        _finished: *mut efi::Boolean,
+    ) -> efi::Status {
+        match processor {
+            0 | 3 => efi::Status::INVALID_PARAMETER,
+            _ if processor >= PROCESSORS => efi::Status::NOT_FOUND,
+            _ => {
+                run_aps(vec![processor], procedure, event, context);
+                efi::Status::SUCCESS
+            }
+        }
+    }
+
+    extern "efiapi" fn switch_bsp(_: *mut MpServicesProtocol, _: usize, _: efi::Boolean) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn enable_disable_ap(
+        _: *mut MpServicesProtocol,
+        processor: usize,
+        _enable: efi::Boolean,
+        health_flag: *mut u32,
+    ) -> efi::Status {
+        match (processor, health_flag.is_null()) {
+            (0, _) => efi::Status::UNSUPPORTED,
+            (_, false) if unsafe { *health_flag } != mp_services::PROCESSOR_HEALTH_STATUS_BIT => {
+                efi::Status::INVALID_PARAMETER
+            }
+            _ => efi::Status::SUCCESS,
+        }
+    }
+
+    extern "efiapi" fn who_am_i(_: *mut MpServicesProtocol, processor: *mut usize) -> efi::Status {
+        unsafe { *processor = 0 };
+        efi::Status::SUCCESS
+    }
+
+    fn mp_services() -> MpServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_locate_protocol::<protocol_handler::MpService, MpServicesProtocol>().returning(|_, _| {
+            Ok(Box::leak(Box::new(MpServicesProtocol {
+                get_number_of_processors,
+                get_processor_info,
+                startup_all_aps,
+                startup_this_ap,
+                switch_bsp,
+                enable_disable_ap,
+                who_am_i,
+            })))
+        });
+        MpServices::locate(&boot_services).unwrap()
+    }
+
+    /// Boot services creating the event of a dispatch, signaled by the test protocol.
+    fn dispatch_boot_services(event: usize) -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_create_event::<Option<&'static ()>>()
+            .once()
+            .returning(move |_, _, _, _| Ok(event as efi::Event));
+        boot_services.expect_wait_for_event().returning(|events| {
+            while !SIGNALED.lock().unwrap().contains(&(events[0] as usize)) {
+                thread::yield_now();
+            }
+            Ok(0)
+        });
+        boot_services.expect_check_event().returning(|event| {
+            match SIGNALED.lock().unwrap().contains(&(event as usize)) {
+                true => Ok(()),
+                false => Err(efi::Status::NOT_READY.into()),
+            }
+        });
+        boot_services.expect_close_event().once().returning(|_| Ok(()));
+        boot_services
+    }
+
+    #[test]
+    fn test_processors() {
+        let mut mp_services = mp_services();
+        assert_eq!(ProcessorCount { total: 4, enabled: 3 }, mp_services.number_of_processors().unwrap());
+        assert_eq!(0, mp_services.who_am_i().unwrap());
+        let bsp = mp_services.processor_info(0).unwrap();
+        assert_eq!((0, true, true, true), (bsp.processor_id, bsp.is_bsp, bsp.enabled, bsp.healthy));
+        let disabled = mp_services.processor_info(3).unwrap();
+        assert_eq!((6, false, false, 3), (disabled.processor_id, disabled.is_bsp, disabled.enabled, disabled.core));
+        assert_eq!(efi::Status::NOT_FOUND, mp_services.processor_info(4).unwrap_err());
+
+        assert_eq!(efi::Status::UNSUPPORTED, mp_services.switch_bsp(1, true).unwrap_err());
+        mp_services.enable_disable_ap(3, true, Some(true)).unwrap();
+        mp_services.enable_disable_ap(3, false, None).unwrap();
+        assert_eq!(efi::Status::INVALID_PARAMETER, mp_services.enable_disable_ap(3, true, Some(false)).unwrap_err());
+    }
+
+    #[test]
+    fn test_startup() {
+        let mut mp_services = mp_services();
+        let runs = AtomicUsize::new(0);
+        let run = || _ = runs.fetch_add(1, Ordering::SeqCst);
+        mp_services.startup_all_aps(false, &run).unwrap();
+        assert_eq!(2, runs.load(Ordering::SeqCst));
+        mp_services.startup_this_ap(2, &run).unwrap();
+        assert_eq!(3, runs.load(Ordering::SeqCst));
+        assert_eq!(efi::Status::INVALID_PARAMETER, mp_services.startup_this_ap(0, &run).unwrap_err());
+    }
+
+    #[test]
+    fn test_startup_nonblocking() {
+        let mut mp_services = mp_services();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let boot_services = dispatch_boot_services(0x10);
+        let ap_runs = runs.clone();
+        let dispatch = mp_services
+            .startup_all_aps_nonblocking(&boot_services, false, 0, move || _ = ap_runs.fetch_add(1, Ordering::SeqCst))
+            .unwrap();
+        dispatch.wait().unwrap();
+        assert_eq!(2, runs.load(Ordering::SeqCst));
+
+        let boot_services = dispatch_boot_services(0x20);
+        let ap_runs = runs.clone();
+        let dispatch = mp_services
+            .startup_this_ap_nonblocking(&boot_services, 1, 0, move || _ = ap_runs.fetch_add(1, Ordering::SeqCst))
+            .unwrap();
+        while !dispatch.is_completed().unwrap() {
+            thread::yield_now();
+        }
+        // The closure and the event are freed with the completed dispatch.
+        drop(dispatch);
+        assert_eq!(3, runs.load(Ordering::SeqCst));
+        assert_eq!(1, Arc::strong_count(&runs));
+
+        let boot_services = dispatch_boot_services(0x30);
+        assert_eq!(
+            efi::Status::INVALID_PARAMETER,
+            mp_services.startup_this_ap_nonblocking(&boot_services, 0, 0, || ()).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,530 @@
+//! Connection of controllers overlapping their preparation on the application processors.
+//!
+//! The boot manager connects the controllers one after the other, and the slow bring-up of the storage and network
+//! hardware, like spinning up disks or training links, adds up. [`connect_controllers_parallel`] runs that
+//! preparation on the APs while the BSP connects the controllers that are ready, in the order of their dependencies:
+//!
+//! ```ignore
+//! let mut mp_services = MpServices::locate(&boot_services).ok();
+//! let requests = vec![
+//!     ConnectRequest::new(nvme).prepare(move || wait_controller_ready(nvme_bar)),
+//!     ConnectRequest::new(nic).prepare(move || train_link(nic_bar)),
+//!     ConnectRequest::new(usb).after(nvme),
+//! ];
+//! for (handle, outcome) in connect_controllers_parallel(&boot_services, mp_services.as_mut(), requests)? {
+//!     log::info!("{handle:?}: {outcome:?}");
+//! }
+//! ```
+//!
+//! The boot services are not multiprocessor safe: `ConnectController()`, and the drivers it starts, always run on the
+//! BSP, and the preparation must use neither the boot services nor the protocols. Without MP Services or enabled AP,
+//! the preparation runs on the BSP right before the connection.
+
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::fmt;
+
+use boot_services::{protocol_handler::ConnectError, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::{ApDispatch, MpServices};
+
+/// A controller to connect, with the controllers to connect before it and the work preparing it.
+pub struct ConnectRequest {
+    handle: efi::Handle,
+    after: Vec<efi::Handle>,
+    prepare: Option<Box<dyn Fn() + Sync>>,
+    recursive: bool,
+}
+
+impl ConnectRequest {
+    /// Connects all the drivers that support the controller, and recursively its children.
+    pub fn new(handle: efi::Handle) -> Self {
+        Self { handle, after: Vec::new(), prepare: None, recursive: true }
+    }
+
+    /// Connects the controller once `handle` is, when `handle` is part of the same connection.
+    pub fn after(mut self, handle: efi::Handle) -> Self {
+        self.after.push(handle);
+        self
+    }
+
+    /// Runs `prepare` before connecting the controller, on an AP when one is free.
+    ///
+    /// The preparation runs concurrently with the BSP and with the other preparations, as soon as an AP is free: it
+    /// must not depend on the connection of other controllers.
+    pub fn prepare(mut self, prepare: impl Fn() + Sync + 'static) -> Self {
+        self.prepare = Some(Box::new(prepare));
+        self
+    }
+
+    /// Whether to connect the children of the controller too, the default.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+}
+
+impl fmt::Debug for ConnectRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectRequest")
+            .field("handle", &self.handle)
+            .field("after", &self.after)
+            .field("recursive", &self.recursive)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The outcome of the connection of a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectOutcome {
+    /// Drivers were connected to the controller.
+    Connected,
+    /// No driver supports the controller, which does not hold back the controllers after it.
+    NoDriver,
+    /// The preparation or the connection failed.
+    Failed(efi::Status),
+    /// A controller to connect before failed, or the order of the controllers is a cycle.
+    Blocked,
+}
+
+impl ConnectOutcome {
+    fn holds_back(self) -> bool {
+        matches!(self, ConnectOutcome::Failed(_) | ConnectOutcome::Blocked)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Pending,
+    Preparing,
+    Prepared,
+    Done(ConnectOutcome),
+}
+
+struct Entry {
+    request: ConnectRequest,
+    state: State,
+}
+
+/// The state of the controller of `handle`, `None` if it is not part of the connection.
+fn state_of(entries: &[Entry], handle: efi::Handle) -> Option<State> {
+    entries.iter().find(|entry| entry.request.handle == handle).map(|entry| entry.state)
+}
+
+/// The enabled APs.
+fn enabled_aps(mp_services: &MpServices) -> Result<Vec<usize>, EfiError> {
+    let count = mp_services.number_of_processors()?;
+    Ok((0..count.total)
+        .rev()
+        .filter(|&processor| mp_services.processor_info(processor).is_ok_and(|info| info.enabled && !info.is_bsp))
+        .collect())
+}
+
+/// Connects the controllers of `requests`, running their preparation on the APs of `mp_services`.
+///
+/// A controller is connected once its preparation is done and the controllers to connect before it are connected,
+/// in the order of the requests otherwise. When no controller is ready, the BSP waits for the oldest preparation.
+/// Returns the outcome of every request, in the order of the connections; an error only if the processors cannot be
+/// enumerated.
+pub fn connect_controllers_parallel<B: BootServices>(
+    boot_services: &B,
+    mut mp_services: Option<&mut MpServices>,
+    requests: Vec<ConnectRequest>,
+) -> Result<Vec<(efi::Handle, ConnectOutcome)>, EfiError> {
+    let mut free_aps = match mp_services.as_deref() {
+        Some(mp_services) => enabled_aps(mp_services)?,
+        None => Vec::new(),
+    };
+    let mut entries = requests.into_iter().map(|request| Entry { request, state: State::Pending }).collect::<Vec<_>>();
+    let mut dispatches: VecDeque<(usize, usize, ApDispatch<'_, B>)> = VecDeque::new();
+    let mut outcomes = Vec::with_capacity(entries.len());
+
+    let mut finish = |entries: &mut [Entry], index: usize, outcome: ConnectOutcome| {
+        entries[index].state = State::Done(outcome);
+        outcomes.push((entries[index].request.handle, outcome));
+    };
+
+    loop {
+        // Start the preparations on the free APs, in the order of the requests.
+        if let Some(mp_services) = mp_services.as_deref_mut() {
+            for index in 0..entries.len() {
+                if free_aps.is_empty() {
+                    break;
+                }
+                if entries[index].state != State::Pending || entries[index].request.prepare.is_none() {
+                    continue;
+                }
+                let prepare = entries[index].request.prepare.take().unwrap();
+                let processor = free_aps.pop().unwrap();
+                match mp_services.startup_this_ap_nonblocking(boot_services, processor, 0, prepare) {
+                    Ok(dispatch) => {
+                        entries[index].state = State::Preparing;
+                        dispatches.push_back((index, processor, dispatch));
+                    }
+                    // The AP is left out, the preparation is lost with the dispatch.
+                    Err(status) => finish(&mut entries, index, ConnectOutcome::Failed(status.into())),
+                }
+            }
+        }
+
+        // Collect the preparations that are done, the BSP only waits for them when nothing else is ready.
+        let mut position = 0;
+        while position < dispatches.len() {
+            let completed = dispatches[position].2.is_completed();
+            if completed == Ok(false) {
+                position += 1;
+                continue;
+            }
+            let (index, processor, dispatch) = dispatches.remove(position).unwrap();
+            free_aps.push(processor);
+            match completed.and_then(|_| dispatch.wait()) {
+                Ok(()) => entries[index].state = State::Prepared,
+                Err(status) => finish(&mut entries, index, ConnectOutcome::Failed(status.into())),
+            }
+        }
+
+        let mut progress = false;
+        for index in 0..entries.len() {
+            let ready = match entries[index].state {
+                State::Prepared => true,
+                // Without AP to run the preparation, the BSP prepares the controller itself.
+                State::Pending => {
+                    entries[index].request.prepare.is_none() || (free_aps.is_empty() && dispatches.is_empty())
+                }
+                _ => false,
+            };
+            if !ready {
+                continue;
+            }
+            let states = entries[index]
+                .request
+                .after
+                .iter()
+                .filter_map(|&handle| state_of(&entries, handle))
+                .collect::<Vec<_>>();
+            if states.iter().any(|state| matches!(state, State::Done(outcome) if outcome.holds_back())) {
+                finish(&mut entries, index, ConnectOutcome::Blocked);
+                progress = true;
+                continue;
+            }
+            if states.iter().any(|state| !matches!(state, State::Done(_))) {
+                continue;
+            }
+            if let Some(prepare) = entries[index].request.prepare.take() {
+                prepare();
+            }
+            let outcome =
+                match boot_services.connect_drivers(entries[index].request.handle, entries[index].request.recursive) {
+                    Ok(()) => ConnectOutcome::Connected,
+                    Err(ConnectError::NoDriverConnected) => ConnectOutcome::NoDriver,
+                    Err(ConnectError::Failed(status)) => ConnectOutcome::Failed(status),
+                };
+            finish(&mut entries, index, outcome);
+            progress = true;
+            // Start the next preparations on the APs the connection left free.
+            break;
+        }
+        if progress {
+            continue;
+        }
+
+        match dispatches.pop_front() {
+            Some((index, processor, dispatch)) => {
+                free_aps.push(processor);
+                match dispatch.wait() {
+                    Ok(()) => entries[index].state = State::Prepared,
+                    Err(status) => finish(&mut entries, index, ConnectOutcome::Failed(status.into())),
+                }
+            }
+            None => break,
+        }
+    }
+
+    // The controllers left wait for each other.
+    for index in 0..entries.len() {
+        if !matches!(entries[index].state, State::Done(_)) {
+            finish(&mut entries, index, ConnectOutcome::Blocked);
+        }
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mp_services::MpServicesProtocol;
+    use boot_services::{
+        event::{EventTimerType, EventType},
+        mock::InMemoryBootServices,
+        tpl::Tpl,
+    };
+    use core::{ffi::c_void, ptr};
+    use efi::protocols::{driver_binding, mp_services};
+    use std::sync::Mutex;
+
+    const CONTROLLER_GUID: efi::Guid =
+        efi::Guid::from_fields(0x5f2b1a77, 0x0c43, 0x4c1e, 0x9a, 0x61, &[0x3e, 0x8d, 0x2b, 0x70, 0x14, 0xc9]);
+
+    /// Time of a preparation on an AP, in 100 ns units.
+    const PREPARATION_TIME: u64 = 10_000;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Step {
+        Prepare(efi::Handle),
+        Start(efi::Handle),
+    }
+
+    // Handles are opaque, the log only compares them.
+    unsafe impl Send for Step {}
+
+    /// A driver supporting every controller but `unsupported`, the binding is the first field to be found from its
+    /// pointer.
+    #[repr(C)]
+    struct TestDriver {
+        binding: driver_binding::Protocol,
+        unsupported: efi::Handle,
+        log: &'static Mutex<Vec<Step>>,
+    }
+
+    extern "efiapi" fn supported(
+        this: *mut driver_binding::Protocol,
+        controller: efi::Handle,
+        _: *mut efi::protocols::device_path::Protocol,
+    ) -> efi::Status {
+        match unsafe { &*(this as *mut TestDriver) }.unsupported == controller {
+            true => efi::Status::UNSUPPORTED,
+            false => efi::Status::SUCCESS,
+        }
+    }
+
+    extern "efiapi" fn start(
+        this: *mut driver_binding::Protocol,
+        controller: efi::Handle,
+        _: *mut efi::protocols::device_path::Protocol,
+    ) -> efi::Status {
+        unsafe { &*(this as *mut TestDriver) }.log.lock().unwrap().push(Step::Start(controller));
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn stop(
+        _: *mut driver_binding::Protocol,
+        _: efi::Handle,
+        _: usize,
+        _: *mut efi::Handle,
+    ) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    /// Boot services with `count` controllers and the test driver, which does not support the last one.
+    fn setup(count: usize) -> (&'static InMemoryBootServices, Vec<efi::Handle>, &'static Mutex<Vec<Step>>) {
+        let boot_services = Box::leak(Box::new(InMemoryBootServices::new()));
+        let controllers = (0..count)
+            .map(|_| {
+                unsafe { boot_services.install_protocol_interface_unchecked(None, &CONTROLLER_GUID, ptr::null_mut()) }
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let log = Box::leak(Box::new(Mutex::new(Vec::new())));
+        let driver = Box::leak(Box::new(TestDriver {
+            binding: driver_binding::Protocol {
+                supported,
+                start,
+                stop,
+                version: 1,
+                image_handle: ptr::null_mut(),
+                driver_binding_handle: ptr::null_mut(),
+            },
+            unsupported: controllers[count - 1],
+            log,
+        }));
+        unsafe {
+            boot_services.install_protocol_interface_unchecked(
+                None,
+                &driver_binding::PROTOCOL_GUID,
+                &mut driver.binding as *mut _ as *mut c_void,
+            )
+        }
+        .unwrap();
+        (boot_services, controllers, log)
+    }
+
+    fn prepare(log: &'static Mutex<Vec<Step>>, handle: efi::Handle) -> impl Fn() + Sync + 'static {
+        let handle = handle as usize;
+        move || log.lock().unwrap().push(Step::Prepare(handle as efi::Handle))
+    }
+
+    #[test]
+    fn test_connect_in_order() {
+        let (boot_services, controllers, log) = setup(8);
+        let [a, b, c, d, f, g, h, unsupported] = controllers[..] else { unreachable!() };
+        let invalid = 0x1234 as efi::Handle;
+        let requests = vec![
+            ConnectRequest::new(c).after(b),
+            ConnectRequest::new(b).after(a).prepare(prepare(log, b)),
+            ConnectRequest::new(a),
+            ConnectRequest::new(d).after(invalid),
+            ConnectRequest::new(invalid),
+            ConnectRequest::new(unsupported),
+            ConnectRequest::new(f).after(unsupported).recursive(false),
+            ConnectRequest::new(g).after(h),
+            ConnectRequest::new(h).after(g),
+        ];
+
+        let outcomes = connect_controllers_parallel(boot_services, None, requests).unwrap();
+        assert_eq!(
+            vec![
+                (a, ConnectOutcome::Connected),
+                (b, ConnectOutcome::Connected),
+                (c, ConnectOutcome::Connected),
+                (invalid, ConnectOutcome::Failed(efi::Status::INVALID_PARAMETER)),
+                (d, ConnectOutcome::Blocked),
+                (unsupported, ConnectOutcome::NoDriver),
+                (f, ConnectOutcome::Connected),
+                (g, ConnectOutcome::Blocked),
+                (h, ConnectOutcome::Blocked),
+            ],
+            outcomes
+        );
+        // Without MP Services, the BSP prepares the controller right before connecting it.
+        assert_eq!(
+            vec![Step::Start(a), Step::Prepare(b), Step::Start(b), Step::Start(c), Step::Start(f)],
+            *log.lock().unwrap()
+        );
+    }
+
+    /// MP Services whose APs run the procedures after [`PREPARATION_TIME`], the protocol is the first field.
+    #[repr(C)]
+    struct TestMpServices {
+        protocol: MpServicesProtocol,
+        boot_services: &'static InMemoryBootServices,
+        started: Mutex<Vec<usize>>,
+    }
+
+    /// A procedure running on an AP, the addresses are kept as integers for the context of its timer.
+    struct ApRun {
+        procedure: mp_services::ApProcedure,
+        argument: usize,
+        event: usize,
+        boot_services: usize,
+    }
+
+    extern "efiapi" fn get_number_of_processors(
+        _: *mut MpServicesProtocol,
+        total: *mut usize,
+        enabled: *mut usize,
+    ) -> efi::Status {
+        unsafe { (*total, *enabled) = (3, 3) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_processor_info(
+        _: *mut MpServicesProtocol,
+        processor: usize,
+        information: *mut mp_services::ProcessorInformation,
+    ) -> efi::Status {
+        let bsp = if processor == 0 { mp_services::PROCESSOR_AS_BSP_BIT } else { 0 };
+        unsafe { (*information).status_flag = mp_services::PROCESSOR_ENABLED_BIT | bsp };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn ap_done(timer: efi::Event, run: &'static ApRun) {
+        let boot_services = unsafe { &*(run.boot_services as *const InMemoryBootServices) };
+        unsafe { r_efi::efi_call(run.procedure, (run.argument as *mut c_void,)) };
+        boot_services.signal_event(run.event as efi::Event).unwrap();
+        boot_services.close_event(timer).unwrap();
+    }
+
+    extern "efiapi" fn startup_this_ap(
+        this: *mut MpServicesProtocol,
+        procedure: mp_services::ApProcedure,
+        processor: usize,
+        event: efi::Event,
+        _timeout: usize,
+        argument: *mut c_void,
+        _finished: *mut efi::Boolean,
+    ) -> efi::Status {
+        let mp_services = unsafe { &*(this as *mut TestMpServices) };
+        mp_services.started.lock().unwrap().push(processor);
+        let boot_services = mp_services.boot_services;
+        let run = Box::leak(Box::new(ApRun {
+            procedure,
+            argument: argument as usize,
+            event: event as usize,
+            boot_services: boot_services as *const InMemoryBootServices as usize,
+        }));
+        let timer = boot_services
+            .create_event(EventType::TIMER | EventType::NOTIFY_SIGNAL, Tpl::CALLBACK, Some(ap_done), &*run)
+            .unwrap();
+        boot_services.set_timer(timer, EventTimerType::Relative, PREPARATION_TIME).unwrap();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn unsupported_startup_all_aps(
+        _: *mut MpServicesProtocol,
+        _: mp_services::ApProcedure,
+        _: efi::Boolean,
+        _: efi::Event,
+        _: usize,
+        _: *mut c_void,
+        _: *mut *mut usize,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn unsupported_switch_bsp(_: *mut MpServicesProtocol, _: usize, _: efi::Boolean) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn unsupported_enable_disable_ap(
+        _: *mut MpServicesProtocol,
+        _: usize,
+        _: efi::Boolean,
+        _: *mut u32,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn who_am_i(_: *mut MpServicesProtocol, processor: *mut usize) -> efi::Status {
+        unsafe { *processor = 0 };
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_prepare_on_aps() {
+        let (boot_services, controllers, log) = setup(5);
+        let [n, m, u, v, _] = controllers[..] else { unreachable!() };
+        let test_mp_services = Box::leak(Box::new(TestMpServices {
+            protocol: MpServicesProtocol {
+                get_number_of_processors,
+                get_processor_info,
+                startup_all_aps: unsupported_startup_all_aps,
+                startup_this_ap,
+                switch_bsp: unsupported_switch_bsp,
+                enable_disable_ap: unsupported_enable_disable_ap,
+                who_am_i,
+            },
+            boot_services,
+            started: Mutex::new(Vec::new()),
+        }));
+        let started = &test_mp_services.started;
+        let mut mp_services = MpServices::from(&mut test_mp_services.protocol);
+        let requests = vec![
+            ConnectRequest::new(v).after(n),
+            ConnectRequest::new(n).prepare(prepare(log, n)),
+            ConnectRequest::new(m).prepare(prepare(log, m)),
+            ConnectRequest::new(u),
+        ];
+
+        let outcomes = connect_controllers_parallel(boot_services, Some(&mut mp_services), requests).unwrap();
+        assert_eq!(vec![u, n, v, m], outcomes.iter().map(|&(handle, _)| handle).collect::<Vec<_>>());
+        // The BSP connects the controller without preparation while the APs prepare the others, then the controllers
+        // in the order of the requests.
+        assert_eq!(
+            vec![Step::Start(u), Step::Prepare(n), Step::Prepare(m), Step::Start(n), Step::Start(v), Step::Start(m)],
+            *log.lock().unwrap()
+        );
+        assert_eq!(vec![1, 2], *started.lock().unwrap());
+        assert!(outcomes.iter().all(|&(_, outcome)| outcome == ConnectOutcome::Connected));
+    }
+}
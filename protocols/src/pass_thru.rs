@@ -0,0 +1,89 @@
+//! Pass-thru protocols, sending commands of a storage protocol to the devices of a controller.
+//!
+//! The data of a command is given as a [`DataBuffer`], copied to and from a buffer aligned as required by the
+//! controller. A command that fails gives a [`PassThruError`] with the status reported by the device:
+//!
+//! ```ignore
+//! let mut nvme = NvmePassThru::get(boot_services, controller)?;
+//! let mut identify = [0; 4096];
+//! nvme.execute(NvmePassThru::CONTROLLER, &NvmeCommand::identify_controller(), DataBuffer::In(&mut identify))?;
+//! ```
+//!
+//! [`UfsDeviceConfig`] is not a pass-thru protocol but configures the UFS devices whose logical units are accessed with
+//! [`ScsiPassThru`].
+
+use r_efi::efi;
+use status::EfiError;
+
+use crate::media::block_io::AlignedBuffer;
+
+pub mod nvme;
+pub mod rpmb;
+pub mod scsi;
+pub mod sd_mmc;
+pub mod ufs;
+
+pub use nvme::{NvmeCommand, NvmePassThru};
+pub use rpmb::{Rpmb, RpmbFrame};
+pub use scsi::{Cdb, ScsiPassThru};
+pub use sd_mmc::{SdMmcCommand, SdMmcPassThru};
+pub use ufs::UfsDeviceConfig;
+
+/// The data transferred by a command.
+#[derive(Debug)]
+pub enum DataBuffer<'a> {
+    None,
+    /// Data read from the device.
+    In(&'a mut [u8]),
+    /// Data written to the device.
+    Out(&'a [u8]),
+}
+
+impl DataBuffer<'_> {
+    pub fn len(&self) -> usize {
+        match self {
+            DataBuffer::None => 0,
+            DataBuffer::In(data) => data.len(),
+            DataBuffer::Out(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A copy of the data aligned for the controller, zeroed for the data read from the device.
+    fn aligned(&self, align: u32) -> AlignedBuffer {
+        match self {
+            DataBuffer::Out(data) => AlignedBuffer::from_slice(data, align.max(1) as usize),
+            _ => AlignedBuffer::new(self.len(), align.max(1) as usize),
+        }
+    }
+
+    /// Copies the `transferred` bytes read from the device out of the aligned copy.
+    fn copy_from(&mut self, aligned: &AlignedBuffer, transferred: usize) {
+        if let DataBuffer::In(data) = self {
+            let transferred = transferred.min(data.len());
+            data[..transferred].copy_from_slice(&aligned.as_slice()[..transferred]);
+        }
+    }
+}
+
+/// Error of a command, with its completion reported by the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassThruError<C> {
+    pub status: efi::Status,
+    pub completion: C,
+}
+
+impl<C> From<PassThruError<C>> for efi::Status {
+    fn from(error: PassThruError<C>) -> Self {
+        error.status
+    }
+}
+
+impl<C> From<PassThruError<C>> for EfiError {
+    fn from(error: PassThruError<C>) -> Self {
+        error.status.into()
+    }
+}
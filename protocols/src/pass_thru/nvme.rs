@@ -0,0 +1,526 @@
+//! NVM Express Pass Thru protocol.
+//!
+//! [`NvmePassThru`] sends a [`NvmeCommand`] to the admin or I/O queues of a controller, its [`NvmeCompletion`] gives
+//! the completion queue entry of the command:
+//!
+//! ```ignore
+//! let mut nvme = NvmePassThru::get(boot_services, controller)?;
+//! nvme.execute(NvmePassThru::CONTROLLER, &NvmeCommand::firmware_image_download(0, len), DataBuffer::Out(image))?;
+//! nvme.execute(NvmePassThru::CONTROLLER, &NvmeCommand::firmware_commit(1, 1), DataBuffer::None)?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 13.15. NVM Express Pass Through Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#nvm-express-pass-through-protocol)
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, fmt, ops::Deref, ptr};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::{DataBuffer, PassThruError};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x52c78312, 0x8edc, 0x4233, 0x98, 0xf2, &[0x1a, 0x1a, 0xa5, 0xe3, 0x88, 0xa5]);
+
+pub const ATTRIBUTES_PHYSICAL: u32 = 0x0001;
+pub const ATTRIBUTES_LOGICAL: u32 = 0x0002;
+pub const ATTRIBUTES_NONBLOCKIO: u32 = 0x0004;
+pub const ATTRIBUTES_CMD_SET_NVM: u32 = 0x0008;
+
+pub const QUEUE_TYPE_ADMIN: u8 = 0x00;
+pub const QUEUE_TYPE_IO: u8 = 0x01;
+
+pub const CDW2_VALID: u8 = 0x01;
+pub const CDW3_VALID: u8 = 0x02;
+pub const CDW10_VALID: u8 = 0x04;
+pub const CDW11_VALID: u8 = 0x08;
+pub const CDW12_VALID: u8 = 0x10;
+pub const CDW13_VALID: u8 = 0x20;
+pub const CDW14_VALID: u8 = 0x40;
+pub const CDW15_VALID: u8 = 0x80;
+
+pub type ProtocolPassThru = extern "efiapi" fn(*mut Protocol, u32, *mut Packet, efi::Event) -> efi::Status;
+
+pub type ProtocolGetNextNamespace = extern "efiapi" fn(*mut Protocol, *mut u32) -> efi::Status;
+
+pub type ProtocolBuildDevicePath =
+    extern "efiapi" fn(*mut Protocol, u32, *mut *mut efi::protocols::device_path::Protocol) -> efi::Status;
+
+pub type ProtocolGetNamespace =
+    extern "efiapi" fn(*mut Protocol, *mut efi::protocols::device_path::Protocol, *mut u32) -> efi::Status;
+
+/// FFI definition of `EFI_NVM_EXPRESS_PASS_THRU_MODE`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Mode {
+    pub attributes: u32,
+    /// Alignment required for the buffers, 0 or 1 if there is none.
+    pub io_align: u32,
+    /// Version of the NVMe specification implemented by the controller, as in its VS register.
+    pub nvme_version: u32,
+}
+
+/// FFI definition of `EFI_NVM_EXPRESS_COMMAND`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Command {
+    /// Opcode in bits 0-7 and fused operation in bits 8-9.
+    pub cdw0: u32,
+    /// `CDWx_VALID` flags of the command dwords that are set.
+    pub flags: u8,
+    pub nsid: u32,
+    pub cdw2: u32,
+    pub cdw3: u32,
+    pub cdw10: u32,
+    pub cdw11: u32,
+    pub cdw12: u32,
+    pub cdw13: u32,
+    pub cdw14: u32,
+    pub cdw15: u32,
+}
+
+/// FFI definition of `EFI_NVM_EXPRESS_COMPLETION`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Completion {
+    pub dw0: u32,
+    pub dw1: u32,
+    pub dw2: u32,
+    pub dw3: u32,
+}
+
+/// FFI definition of `EFI_NVM_EXPRESS_PASS_THRU_COMMAND_PACKET`.
+#[repr(C)]
+pub struct Packet {
+    /// Timeout of the command, in units of 100 ns, 0 to wait as long as needed.
+    pub command_timeout: u64,
+    pub transfer_buffer: *mut c_void,
+    pub transfer_length: u32,
+    pub metadata_buffer: *mut c_void,
+    pub metadata_length: u32,
+    pub queue_type: u8,
+    pub nvme_cmd: *mut Command,
+    pub nvme_completion: *mut Completion,
+}
+
+/// FFI definition of `EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub mode: *mut Mode,
+    pub pass_thru: ProtocolPassThru,
+    pub get_next_namespace: ProtocolGetNextNamespace,
+    pub build_device_path: ProtocolBuildDevicePath,
+    pub get_namespace: ProtocolGetNamespace,
+}
+
+/// NVM Express Pass Thru protocol.
+pub struct NvmExpressPassThruProtocol;
+
+unsafe impl ProtocolTrait for NvmExpressPassThruProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for NvmExpressPassThruProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Builder of an NVMe command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NvmeCommand {
+    command: Command,
+    queue_type: u8,
+}
+
+impl NvmeCommand {
+    pub const OP_GET_LOG_PAGE: u8 = 0x02;
+    pub const OP_IDENTIFY: u8 = 0x06;
+    pub const OP_FIRMWARE_COMMIT: u8 = 0x10;
+    pub const OP_FIRMWARE_IMAGE_DOWNLOAD: u8 = 0x11;
+
+    /// An admin command.
+    pub fn new(opcode: u8) -> Self {
+        Self { command: Command { cdw0: opcode as u32, ..Default::default() }, queue_type: QUEUE_TYPE_ADMIN }
+    }
+
+    /// An I/O command.
+    pub fn io(opcode: u8) -> Self {
+        Self { queue_type: QUEUE_TYPE_IO, ..Self::new(opcode) }
+    }
+
+    pub fn opcode(&self) -> u8 {
+        self.command.cdw0 as u8
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.queue_type == QUEUE_TYPE_ADMIN
+    }
+
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    pub fn with_namespace(mut self, nsid: u32) -> Self {
+        self.command.nsid = nsid;
+        self
+    }
+
+    pub fn with_cdw2(mut self, cdw2: u32) -> Self {
+        self.command.cdw2 = cdw2;
+        self.command.flags |= CDW2_VALID;
+        self
+    }
+
+    pub fn with_cdw3(mut self, cdw3: u32) -> Self {
+        self.command.cdw3 = cdw3;
+        self.command.flags |= CDW3_VALID;
+        self
+    }
+
+    pub fn with_cdw10(mut self, cdw10: u32) -> Self {
+        self.command.cdw10 = cdw10;
+        self.command.flags |= CDW10_VALID;
+        self
+    }
+
+    pub fn with_cdw11(mut self, cdw11: u32) -> Self {
+        self.command.cdw11 = cdw11;
+        self.command.flags |= CDW11_VALID;
+        self
+    }
+
+    pub fn with_cdw12(mut self, cdw12: u32) -> Self {
+        self.command.cdw12 = cdw12;
+        self.command.flags |= CDW12_VALID;
+        self
+    }
+
+    pub fn with_cdw13(mut self, cdw13: u32) -> Self {
+        self.command.cdw13 = cdw13;
+        self.command.flags |= CDW13_VALID;
+        self
+    }
+
+    pub fn with_cdw14(mut self, cdw14: u32) -> Self {
+        self.command.cdw14 = cdw14;
+        self.command.flags |= CDW14_VALID;
+        self
+    }
+
+    pub fn with_cdw15(mut self, cdw15: u32) -> Self {
+        self.command.cdw15 = cdw15;
+        self.command.flags |= CDW15_VALID;
+        self
+    }
+
+    /// Identifies the controller, in 4096 bytes.
+    pub fn identify_controller() -> Self {
+        Self::new(Self::OP_IDENTIFY).with_cdw10(0x01)
+    }
+
+    /// Identifies a namespace, in 4096 bytes.
+    pub fn identify_namespace(nsid: u32) -> Self {
+        Self::new(Self::OP_IDENTIFY).with_namespace(nsid).with_cdw10(0x00)
+    }
+
+    /// Gets `len` bytes of a log page, a multiple of 4 bytes.
+    pub fn get_log_page(log_id: u8, nsid: u32, len: u32) -> Self {
+        let dwords = len / 4 - 1;
+        Self::new(Self::OP_GET_LOG_PAGE)
+            .with_namespace(nsid)
+            .with_cdw10(log_id as u32 | (dwords & 0xffff) << 16)
+            .with_cdw11(dwords >> 16)
+    }
+
+    /// Downloads `len` bytes of a firmware image at `offset` bytes, both multiples of 4 bytes.
+    pub fn firmware_image_download(offset: u32, len: u32) -> Self {
+        Self::new(Self::OP_FIRMWARE_IMAGE_DOWNLOAD).with_cdw10(len / 4 - 1).with_cdw11(offset / 4)
+    }
+
+    /// Commits the downloaded firmware image to a slot, with a commit action as defined by the NVMe specification.
+    pub fn firmware_commit(slot: u8, action: u8) -> Self {
+        Self::new(Self::OP_FIRMWARE_COMMIT).with_cdw10((slot & 0x7) as u32 | ((action & 0x7) as u32) << 3)
+    }
+}
+
+/// Completion queue entry of an NVMe command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NvmeCompletion(pub Completion);
+
+impl NvmeCompletion {
+    /// Command specific result in dword 0.
+    pub fn result(&self) -> u32 {
+        self.0.dw0
+    }
+
+    /// Status code, bits 1-8 of the status field.
+    pub fn status_code(&self) -> u8 {
+        (self.0.dw3 >> 17) as u8
+    }
+
+    /// Status code type, bits 9-11 of the status field.
+    pub fn status_code_type(&self) -> u8 {
+        ((self.0.dw3 >> 25) & 0x7) as u8
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status_code() == 0 && self.status_code_type() == 0
+    }
+}
+
+/// Typed access to an instance of the NVM Express Pass Thru protocol.
+pub struct NvmePassThru {
+    protocol: &'static mut Protocol,
+    timeout: u64,
+}
+
+impl NvmePassThru {
+    /// Namespace ID of the commands sent to the controller.
+    pub const CONTROLLER: u32 = 0;
+    /// Namespace ID of the commands sent to all the namespaces.
+    pub const ALL_NAMESPACES: u32 = 0xffffffff;
+    /// Default timeout of a command, in units of 100 ns.
+    pub const DEFAULT_TIMEOUT: u64 = 30 * 10_000_000;
+
+    /// Gets the instance of the protocol installed on the handle of a controller.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &NvmExpressPassThruProtocol).map(Self::from)
+    }
+
+    /// Sets the timeout of a command, in units of 100 ns, 0 to wait as long as needed.
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.protocol as *mut Protocol
+    }
+
+    pub fn mode(&self) -> Mode {
+        //SAFETY: The mode of a valid protocol instance is valid.
+        unsafe { *self.protocol.mode }
+    }
+
+    /// The IDs of the active namespaces of the controller.
+    pub fn namespaces(&mut self) -> Result<Vec<u32>, EfiError> {
+        let mut namespaces = Vec::new();
+        let mut nsid = Self::ALL_NAMESPACES;
+        loop {
+            match (self.protocol.get_next_namespace)(self.this(), &mut nsid) {
+                efi::Status::NOT_FOUND => return Ok(namespaces),
+                s if s.is_error() => return Err(s.into()),
+                _ => namespaces.push(nsid),
+            }
+        }
+    }
+
+    /// Sends a command to a namespace, or to the controller with [`NvmePassThru::CONTROLLER`], and waits for its
+    /// completion.
+    ///
+    /// # Errors
+    ///
+    /// A [`PassThruError`] with the completion of the command if the controller failed it.
+    pub fn execute(
+        &mut self,
+        nsid: u32,
+        command: &NvmeCommand,
+        mut data: DataBuffer,
+    ) -> Result<NvmeCompletion, PassThruError<NvmeCompletion>> {
+        let transfer_length = u32::try_from(data.len()).map_err(|_| PassThruError {
+            status: efi::Status::BAD_BUFFER_SIZE,
+            completion: NvmeCompletion::default(),
+        })?;
+        let mut buffer = data.aligned(self.mode().io_align);
+        let mut nvme_cmd = command.command;
+        nvme_cmd.nsid = nsid;
+        let mut completion = Completion::default();
+        let mut packet = Packet {
+            command_timeout: self.timeout,
+            transfer_buffer: if data.is_empty() {
+                ptr::null_mut()
+            } else {
+                buffer.as_mut_slice().as_mut_ptr() as *mut c_void
+            },
+            transfer_length,
+            metadata_buffer: ptr::null_mut(),
+            metadata_length: 0,
+            queue_type: command.queue_type,
+            nvme_cmd: &mut nvme_cmd,
+            nvme_completion: &mut completion,
+        };
+        let status = (self.protocol.pass_thru)(self.this(), nsid, &mut packet, ptr::null_mut());
+        data.copy_from(&buffer, packet.transfer_length as usize);
+        match status {
+            s if s.is_error() => Err(PassThruError { status: s, completion: NvmeCompletion(completion) }),
+            _ => Ok(NvmeCompletion(completion)),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for NvmePassThru {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self { protocol, timeout: Self::DEFAULT_TIMEOUT }
+    }
+}
+
+impl fmt::Debug for NvmePassThru {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NvmePassThru").field("mode", &self.mode()).field("timeout", &self.timeout).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use core::slice;
+
+    /// Controller with the namespaces 1 and 2, requiring buffers aligned on 4096 bytes. It only supports the identify
+    /// and firmware commands, and fails the commit of a firmware that was not downloaded.
+    #[repr(C)]
+    struct TestNvme {
+        protocol: Protocol,
+        mode: Mode,
+        firmware: Vec<u8>,
+    }
+
+    const MODEL: &[u8] = b"Test NVMe Controller";
+    const INVALID_FIRMWARE_IMAGE: u32 = 0x07 << 17 | 0x01 << 25;
+
+    fn test_nvme<'a>(this: *mut Protocol) -> &'a mut TestNvme {
+        unsafe { &mut *(this as *mut TestNvme) }
+    }
+
+    extern "efiapi" fn pass_thru(this: *mut Protocol, nsid: u32, packet: *mut Packet, _: efi::Event) -> efi::Status {
+        let test_nvme = test_nvme(this);
+        let packet = unsafe { &mut *packet };
+        let command = unsafe { &*packet.nvme_cmd };
+        let completion = unsafe { &mut *packet.nvme_completion };
+        assert_eq!(nsid, command.nsid);
+        assert_eq!(QUEUE_TYPE_ADMIN, packet.queue_type);
+        assert_eq!(0, packet.transfer_buffer as usize % 4096);
+        let data =
+            || unsafe { slice::from_raw_parts_mut(packet.transfer_buffer as *mut u8, packet.transfer_length as usize) };
+        match (command.cdw0 as u8, command.cdw10) {
+            (NvmeCommand::OP_IDENTIFY, 0x01) => data()[24..24 + MODEL.len()].copy_from_slice(MODEL),
+            (NvmeCommand::OP_IDENTIFY, 0x00) => data()[..8].copy_from_slice(&(nsid as u64 * 0x1000).to_le_bytes()),
+            (NvmeCommand::OP_FIRMWARE_IMAGE_DOWNLOAD, dwords) => {
+                assert_eq!(CDW10_VALID | CDW11_VALID, command.flags);
+                assert_eq!((dwords + 1) * 4, packet.transfer_length);
+                assert_eq!(test_nvme.firmware.len() as u32 / 4, command.cdw11);
+                test_nvme.firmware.extend_from_slice(data());
+            }
+            (NvmeCommand::OP_FIRMWARE_COMMIT, _) if test_nvme.firmware.is_empty() => {
+                completion.dw3 = INVALID_FIRMWARE_IMAGE;
+                return efi::Status::DEVICE_ERROR;
+            }
+            (NvmeCommand::OP_FIRMWARE_COMMIT, cdw10) => completion.dw0 = cdw10,
+            _ => return efi::Status::UNSUPPORTED,
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_next_namespace(_: *mut Protocol, nsid: *mut u32) -> efi::Status {
+        let nsid = unsafe { &mut *nsid };
+        match *nsid {
+            NvmePassThru::ALL_NAMESPACES => *nsid = 1,
+            1 => *nsid = 2,
+            _ => return efi::Status::NOT_FOUND,
+        }
+        efi::Status::SUCCESS
+    }
+
+    macro_rules! unused {
+        ($($name:ident($($arg:ty),*);)*) => {
+            $(extern "efiapi" fn $name(_: *mut Protocol, $(_: $arg),*) -> efi::Status {
+                unimplemented!()
+            })*
+        };
+    }
+
+    unused! {
+        build_device_path(u32, *mut *mut efi::protocols::device_path::Protocol);
+        get_namespace(*mut efi::protocols::device_path::Protocol, *mut u32);
+    }
+
+    fn nvme_pass_thru() -> (NvmePassThru, &'static TestNvme) {
+        let nvme = Box::leak(Box::new(TestNvme {
+            protocol: Protocol {
+                mode: ptr::null_mut(),
+                pass_thru,
+                get_next_namespace,
+                build_device_path,
+                get_namespace,
+            },
+            mode: Mode {
+                attributes: ATTRIBUTES_PHYSICAL | ATTRIBUTES_CMD_SET_NVM,
+                io_align: 4096,
+                nvme_version: 0x10400,
+            },
+            firmware: Vec::new(),
+        }));
+        nvme.protocol.mode = &mut nvme.mode;
+        let test_nvme = test_nvme(&mut nvme.protocol);
+        (NvmePassThru::from(&mut nvme.protocol), test_nvme)
+    }
+
+    #[test]
+    fn test_command() {
+        let command = NvmeCommand::firmware_commit(2, 3);
+        assert!(command.is_admin());
+        assert_eq!(NvmeCommand::OP_FIRMWARE_COMMIT, command.opcode());
+        assert_eq!(2 | 3 << 3, command.command().cdw10);
+        assert_eq!(CDW10_VALID, command.command().flags);
+
+        let command = NvmeCommand::get_log_page(0x02, NvmePassThru::ALL_NAMESPACES, 512);
+        assert_eq!(0x02 | 127 << 16, command.command().cdw10);
+        assert_eq!(0, command.command().cdw11);
+        assert!(!NvmeCommand::io(0x02).is_admin());
+    }
+
+    #[test]
+    fn test_identify() {
+        let (mut nvme, _) = nvme_pass_thru();
+        assert_eq!(0x10400, nvme.mode().nvme_version);
+        assert_eq!(vec![1, 2], nvme.namespaces().unwrap());
+
+        let mut identify = vec![0; 4096];
+        let completion = nvme
+            .execute(NvmePassThru::CONTROLLER, &NvmeCommand::identify_controller(), DataBuffer::In(&mut identify))
+            .unwrap();
+        assert!(completion.is_success());
+        assert_eq!(MODEL, &identify[24..24 + MODEL.len()]);
+
+        nvme.execute(2, &NvmeCommand::identify_namespace(2), DataBuffer::In(&mut identify)).unwrap();
+        assert_eq!(0x2000, u64::from_le_bytes(identify[..8].try_into().unwrap()));
+    }
+
+    #[test]
+    fn test_firmware_update() {
+        let (mut nvme, test_nvme) = nvme_pass_thru();
+        let error = nvme.execute(NvmePassThru::CONTROLLER, &NvmeCommand::firmware_commit(1, 1), DataBuffer::None);
+        let error = error.unwrap_err();
+        assert_eq!(efi::Status::DEVICE_ERROR, error.status);
+        assert_eq!(0x07, error.completion.status_code());
+        assert_eq!(0x01, error.completion.status_code_type());
+
+        let image: Vec<u8> = (0..32).collect();
+        for (i, chunk) in image.chunks(16).enumerate() {
+            let command = NvmeCommand::firmware_image_download(i as u32 * 16, chunk.len() as u32);
+            nvme.execute(NvmePassThru::CONTROLLER, &command, DataBuffer::Out(chunk)).unwrap();
+        }
+        assert_eq!(image, test_nvme.firmware);
+
+        let completion =
+            nvme.execute(NvmePassThru::CONTROLLER, &NvmeCommand::firmware_commit(1, 1), DataBuffer::None).unwrap();
+        assert_eq!(1 | 1 << 3, completion.result());
+    }
+}
@@ -0,0 +1,367 @@
+//! Replay protected memory block of eMMC devices.
+//!
+//! The RPMB partition is written with frames authenticated by an HMAC-SHA256 over [`RpmbFrame::mac_data`], keyed
+//! with the key programmed once in the device. The MACs are computed and checked by the caller, [`Rpmb`] only sends
+//! the frames and reads the responses of the device:
+//!
+//! ```ignore
+//! let mut sd_mmc = SdMmcPassThru::get(boot_services, controller)?;
+//! let mut rpmb = sd_mmc.rpmb(slot)?;
+//! let counter = rpmb.write_counter(&nonce)?;
+//! let mut frame = RpmbFrame::request(RpmbFrame::AUTHENTICATED_WRITE);
+//! frame.address = 0;
+//! frame.block_count = 1;
+//! frame.write_counter = counter.write_counter;
+//! frame.data = data;
+//! frame.mac = hmac_sha256(&key, &frame.mac_data());
+//! let response = rpmb.authenticated_write(&[frame])?;
+//! ```
+//!
+//! [JEDEC Standard No. 84-B51: 6.6.22. Replay Protected Memory Block](https://www.jedec.org/standards-documents/docs/jesd84-b51)
+
+use alloc::{vec, vec::Vec};
+
+use r_efi::efi;
+use status::EfiError;
+
+use super::{
+    sd_mmc::{SdMmcCommand, SdMmcPassThru},
+    DataBuffer,
+};
+
+/// Result of an RPMB operation, reported in the response frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RpmbResult(pub u16);
+
+impl RpmbResult {
+    pub const OK: u16 = 0x00;
+    pub const GENERAL_FAILURE: u16 = 0x01;
+    pub const AUTHENTICATION_FAILURE: u16 = 0x02;
+    pub const COUNTER_FAILURE: u16 = 0x03;
+    pub const ADDRESS_FAILURE: u16 = 0x04;
+    pub const WRITE_FAILURE: u16 = 0x05;
+    pub const READ_FAILURE: u16 = 0x06;
+    pub const KEY_NOT_PROGRAMMED: u16 = 0x07;
+
+    /// The result code, one of the constants of this type.
+    pub fn code(&self) -> u16 {
+        self.0 & 0x7f
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.code() == Self::OK
+    }
+
+    /// Whether the write counter reached its maximum, after which the partition is read only.
+    pub fn write_counter_expired(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+}
+
+/// A request or response frame of the RPMB protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpmbFrame {
+    /// MAC of the frame, or the key of a [`RpmbFrame::PROGRAM_KEY`] request.
+    pub mac: [u8; 32],
+    pub data: [u8; 256],
+    pub nonce: [u8; 16],
+    pub write_counter: u32,
+    /// Address of the first half sector of the data.
+    pub address: u16,
+    pub block_count: u16,
+    pub result: RpmbResult,
+    /// Type of the request, or of the response to a request.
+    pub request_type: u16,
+}
+
+impl RpmbFrame {
+    pub const SIZE: usize = 512;
+
+    pub const PROGRAM_KEY: u16 = 0x0001;
+    pub const READ_WRITE_COUNTER: u16 = 0x0002;
+    pub const AUTHENTICATED_WRITE: u16 = 0x0003;
+    pub const AUTHENTICATED_READ: u16 = 0x0004;
+    pub const RESULT_READ: u16 = 0x0005;
+
+    /// Offset of the data covered by the MAC.
+    const MAC_DATA_OFFSET: usize = 228;
+
+    /// An empty request frame.
+    pub fn request(request_type: u16) -> Self {
+        Self {
+            mac: [0; 32],
+            data: [0; 256],
+            nonce: [0; 16],
+            write_counter: 0,
+            address: 0,
+            block_count: 0,
+            result: RpmbResult(RpmbResult::OK),
+            request_type,
+        }
+    }
+
+    /// The type of the response to a request of type `request_type`.
+    pub fn response_type(request_type: u16) -> u16 {
+        request_type << 8
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[196..228].copy_from_slice(&self.mac);
+        bytes[228..484].copy_from_slice(&self.data);
+        bytes[484..500].copy_from_slice(&self.nonce);
+        bytes[500..504].copy_from_slice(&self.write_counter.to_be_bytes());
+        bytes[504..506].copy_from_slice(&self.address.to_be_bytes());
+        bytes[506..508].copy_from_slice(&self.block_count.to_be_bytes());
+        bytes[508..510].copy_from_slice(&self.result.0.to_be_bytes());
+        bytes[510..512].copy_from_slice(&self.request_type.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let u16_at = |offset: usize| u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+        Self {
+            mac: bytes[196..228].try_into().unwrap(),
+            data: bytes[228..484].try_into().unwrap(),
+            nonce: bytes[484..500].try_into().unwrap(),
+            write_counter: u32::from_be_bytes(bytes[500..504].try_into().unwrap()),
+            address: u16_at(504),
+            block_count: u16_at(506),
+            result: RpmbResult(u16_at(508)),
+            request_type: u16_at(510),
+        }
+    }
+
+    /// The bytes of the frame covered by its MAC, from its data to its request type.
+    pub fn mac_data(&self) -> [u8; Self::SIZE - Self::MAC_DATA_OFFSET] {
+        self.to_bytes()[Self::MAC_DATA_OFFSET..].try_into().unwrap()
+    }
+}
+
+/// Access to the RPMB partition of an eMMC device, given by [`SdMmcPassThru::rpmb`].
+///
+/// The device accesses its RPMB partition until this is dropped, which restores the partition it accessed before.
+#[derive(Debug)]
+pub struct Rpmb<'a> {
+    sd_mmc: &'a mut SdMmcPassThru,
+    slot: u8,
+    partition_config: u8,
+    size: usize,
+}
+
+impl<'a> Rpmb<'a> {
+    /// Index of the PARTITION_CONFIG byte of the EXT_CSD register.
+    const PARTITION_CONFIG: u8 = 179;
+    /// Index of the RPMB_SIZE_MULT byte of the EXT_CSD register.
+    const RPMB_SIZE_MULT: usize = 168;
+    /// Value of the PARTITION_ACCESS bits of PARTITION_CONFIG selecting the RPMB partition.
+    const RPMB_ACCESS: u8 = 0x03;
+    const PARTITION_ACCESS_MASK: u8 = 0x07;
+
+    /// Switches the device of a slot to its RPMB partition, `UNSUPPORTED` if it has none.
+    pub(crate) fn open(sd_mmc: &'a mut SdMmcPassThru, slot: u8) -> Result<Self, EfiError> {
+        let ext_csd = sd_mmc.ext_csd(slot)?;
+        let size = ext_csd[Self::RPMB_SIZE_MULT] as usize * 128 * 1024;
+        if size == 0 {
+            return Err(efi::Status::UNSUPPORTED.into());
+        }
+        let partition_config = ext_csd[Self::PARTITION_CONFIG as usize];
+        let rpmb_config = partition_config & !Self::PARTITION_ACCESS_MASK | Self::RPMB_ACCESS;
+        sd_mmc.execute(slot, &SdMmcCommand::switch(Self::PARTITION_CONFIG, rpmb_config), DataBuffer::None)?;
+        Ok(Self { sd_mmc, slot, partition_config, size })
+    }
+
+    /// Size of the partition in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Programs the authentication key, which can only be done once in the life of the device, and gives the
+    /// response to the result read request.
+    pub fn program_key(&mut self, key: &[u8; 32]) -> Result<RpmbFrame, EfiError> {
+        let mut request = RpmbFrame::request(RpmbFrame::PROGRAM_KEY);
+        request.mac = *key;
+        self.send(&[request], true)?;
+        self.read_result(RpmbFrame::PROGRAM_KEY)
+    }
+
+    /// Reads the write counter, the caller checks the MAC of the response and its `nonce`.
+    pub fn write_counter(&mut self, nonce: &[u8; 16]) -> Result<RpmbFrame, EfiError> {
+        let mut request = RpmbFrame::request(RpmbFrame::READ_WRITE_COUNTER);
+        request.nonce = *nonce;
+        self.send(&[request], false)?;
+        Ok(self.receive(1, RpmbFrame::READ_WRITE_COUNTER)?.remove(0))
+    }
+
+    /// Writes frames authenticated by the caller and gives the response to the result read request, with the
+    /// incremented write counter.
+    pub fn authenticated_write(&mut self, frames: &[RpmbFrame]) -> Result<RpmbFrame, EfiError> {
+        self.send(frames, true)?;
+        self.read_result(RpmbFrame::AUTHENTICATED_WRITE)
+    }
+
+    /// Reads `count` half sectors at `address`, the caller checks the MAC of the last frame.
+    pub fn authenticated_read(
+        &mut self,
+        address: u16,
+        count: u16,
+        nonce: &[u8; 16],
+    ) -> Result<Vec<RpmbFrame>, EfiError> {
+        let mut request = RpmbFrame::request(RpmbFrame::AUTHENTICATED_READ);
+        request.address = address;
+        request.nonce = *nonce;
+        self.send(&[request], false)?;
+        self.receive(count, RpmbFrame::AUTHENTICATED_READ)
+    }
+
+    fn read_result(&mut self, request_type: u16) -> Result<RpmbFrame, EfiError> {
+        self.send(&[RpmbFrame::request(RpmbFrame::RESULT_READ)], false)?;
+        Ok(self.receive(1, request_type)?.remove(0))
+    }
+
+    fn send(&mut self, frames: &[RpmbFrame], reliable_write: bool) -> Result<(), EfiError> {
+        let count = u16::try_from(frames.len()).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        let data: Vec<u8> = frames.iter().flat_map(RpmbFrame::to_bytes).collect();
+        self.sd_mmc.execute(self.slot, &SdMmcCommand::set_block_count(count, reliable_write), DataBuffer::None)?;
+        self.sd_mmc.execute(self.slot, &SdMmcCommand::write_multiple_block(0), DataBuffer::Out(&data))?;
+        Ok(())
+    }
+
+    /// Reads the `count` response frames to a request, `DEVICE_ERROR` if the device responds to another request.
+    fn receive(&mut self, count: u16, request_type: u16) -> Result<Vec<RpmbFrame>, EfiError> {
+        let mut data = vec![0; count as usize * RpmbFrame::SIZE];
+        self.sd_mmc.execute(self.slot, &SdMmcCommand::set_block_count(count, false), DataBuffer::None)?;
+        self.sd_mmc.execute(self.slot, &SdMmcCommand::read_multiple_block(0), DataBuffer::In(&mut data))?;
+        let frames: Vec<RpmbFrame> =
+            data.chunks_exact(RpmbFrame::SIZE).map(|frame| RpmbFrame::from_bytes(frame.try_into().unwrap())).collect();
+        match frames.iter().all(|frame| frame.request_type == RpmbFrame::response_type(request_type)) {
+            true => Ok(frames),
+            false => Err(efi::Status::DEVICE_ERROR.into()),
+        }
+    }
+}
+
+impl Drop for Rpmb<'_> {
+    fn drop(&mut self) {
+        let switch = SdMmcCommand::switch(Self::PARTITION_CONFIG, self.partition_config);
+        let _ = self.sd_mmc.execute(self.slot, &switch, DataBuffer::None);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pass_thru::sd_mmc::{
+        test::{respond_ext_csd, sd_mmc_pass_thru, TestSdMmc, SLOT},
+        CommandBlock,
+    };
+
+    /// Answers the RPMB requests from the frames written before, the MACs are not computed.
+    fn respond_rpmb(
+        sd_mmc: &mut TestSdMmc,
+        command: &CommandBlock,
+        data_out: &[u8],
+        data_in: &mut [u8],
+    ) -> efi::Status {
+        if command.command_index != SdMmcCommand::READ_MULTIPLE_BLOCK {
+            return match command.command_index {
+                SdMmcCommand::SET_BLOCK_COUNT | SdMmcCommand::WRITE_MULTIPLE_BLOCK => efi::Status::SUCCESS,
+                _ => respond_ext_csd(sd_mmc, command, data_out, data_in),
+            };
+        }
+        assert_eq!(0x03, sd_mmc.ext_csd[179] & 0x07);
+        let requests: Vec<Vec<RpmbFrame>> = sd_mmc
+            .commands
+            .iter()
+            .filter(|(command, _)| command.command_index == SdMmcCommand::WRITE_MULTIPLE_BLOCK)
+            .map(|(_, data)| data.chunks(512).map(|frame| RpmbFrame::from_bytes(frame.try_into().unwrap())).collect())
+            .collect();
+        let key_programmed = requests.iter().any(|frames| frames[0].request_type == RpmbFrame::PROGRAM_KEY);
+        let writes: Vec<&RpmbFrame> =
+            requests.iter().flatten().filter(|frame| frame.request_type == RpmbFrame::AUTHENTICATED_WRITE).collect();
+        let request = match requests.last().unwrap()[0].request_type {
+            RpmbFrame::RESULT_READ => requests[requests.len() - 2][0],
+            _ => requests.last().unwrap()[0],
+        };
+        let mut response = RpmbFrame::request(RpmbFrame::response_type(request.request_type));
+        response.nonce = request.nonce;
+        response.write_counter = writes.len() as u32;
+        if !key_programmed {
+            response.result = RpmbResult(RpmbResult::KEY_NOT_PROGRAMMED);
+        }
+        let mut frames = vec![response; data_in.len() / 512];
+        if request.request_type == RpmbFrame::AUTHENTICATED_READ {
+            for (i, frame) in frames.iter_mut().enumerate() {
+                let address = request.address + i as u16;
+                frame.address = address;
+                if let Some(write) = writes.iter().rev().find(|write| write.address == address) {
+                    frame.data = write.data;
+                }
+            }
+        }
+        for (frame, bytes) in frames.iter().zip(data_in.chunks_mut(512)) {
+            bytes.copy_from_slice(&frame.to_bytes());
+        }
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut frame = RpmbFrame::request(RpmbFrame::AUTHENTICATED_WRITE);
+        frame.write_counter = 0x01020304;
+        frame.address = 0x0506;
+        frame.data[0] = 0xaa;
+        let bytes = frame.to_bytes();
+        assert_eq!([0x01, 0x02, 0x03, 0x04, 0x05, 0x06], bytes[500..506]);
+        assert_eq!([0x00, 0x03], bytes[510..512]);
+        assert_eq!(0xaa, frame.mac_data()[0]);
+        assert_eq!(frame, RpmbFrame::from_bytes(&bytes));
+
+        let result = RpmbResult(0x0082);
+        assert_eq!(RpmbResult::AUTHENTICATION_FAILURE, result.code());
+        assert!(result.write_counter_expired() && !result.is_ok());
+    }
+
+    #[test]
+    fn test_rpmb() {
+        let (mut sd_mmc, test_sd_mmc) = sd_mmc_pass_thru(respond_rpmb);
+        test_sd_mmc.ext_csd[168] = 4;
+        test_sd_mmc.ext_csd[179] = 0x48;
+        {
+            let mut rpmb = sd_mmc.rpmb(SLOT).unwrap();
+            assert_eq!(512 * 1024, rpmb.size());
+
+            let counter = rpmb.write_counter(&[7; 16]).unwrap();
+            assert_eq!(RpmbResult::KEY_NOT_PROGRAMMED, counter.result.code());
+            assert_eq!([7; 16], counter.nonce);
+
+            assert!(rpmb.program_key(&[0x5a; 32]).unwrap().result.is_ok());
+            let mut frame = RpmbFrame::request(RpmbFrame::AUTHENTICATED_WRITE);
+            frame.address = 2;
+            frame.block_count = 1;
+            frame.data = [0x42; 256];
+            let response = rpmb.authenticated_write(&[frame]).unwrap();
+            assert_eq!(RpmbFrame::response_type(RpmbFrame::AUTHENTICATED_WRITE), response.request_type);
+            assert_eq!(1, response.write_counter);
+
+            let frames = rpmb.authenticated_read(1, 2, &[9; 16]).unwrap();
+            assert_eq!([0; 256], frames[0].data);
+            assert_eq!([0x42; 256], frames[1].data);
+            assert_eq!(0x4b, test_sd_mmc.ext_csd[179]);
+        }
+        assert_eq!(0x48, test_sd_mmc.ext_csd[179]);
+        let reliable_writes = test_sd_mmc
+            .commands
+            .iter()
+            .filter(|(command, _)| command.command_index == SdMmcCommand::SET_BLOCK_COUNT)
+            .filter(|(command, _)| command.command_argument & 0x8000_0000 != 0)
+            .count();
+        assert_eq!(2, reliable_writes);
+    }
+
+    #[test]
+    fn test_rpmb_unsupported() {
+        let (mut sd_mmc, _) = sd_mmc_pass_thru(respond_ext_csd);
+        assert_eq!(efi::Status::UNSUPPORTED, sd_mmc.rpmb(SLOT).unwrap_err());
+    }
+}
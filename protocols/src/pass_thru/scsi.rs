@@ -0,0 +1,496 @@
+//! Extended SCSI Pass Thru protocol.
+//!
+//! [`ScsiPassThru`] sends a [`Cdb`] to a logical unit of a target of a SCSI channel, its [`ScsiCompletion`] gives the
+//! status of the target and its sense data:
+//!
+//! ```ignore
+//! let mut scsi = ScsiPassThru::get(boot_services, controller)?;
+//! for (target, lun) in scsi.target_luns()? {
+//!     let mut inquiry = [0; 36];
+//!     scsi.execute(&target, lun, &Cdb::inquiry(36), DataBuffer::In(&mut inquiry))?;
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 15.7. Extended SCSI Pass Thru Protocol](https://uefi.org/specs/UEFI/2.10/15_Protocols_SCSI_Driver_Models_and_Bus_Support.html#extended-scsi-pass-thru-protocol)
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, fmt, ops::Deref, ptr};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::{DataBuffer, PassThruError};
+use crate::media::block_io::AlignedBuffer;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x143b7632, 0xb81b, 0x4cb7, 0xab, 0xd3, &[0xb6, 0x25, 0xa5, 0xb9, 0xbf, 0xfe]);
+
+/// Size of the ID of a target.
+pub const TARGET_MAX_BYTES: usize = 0x10;
+
+pub const ATTRIBUTES_PHYSICAL: u32 = 0x0001;
+pub const ATTRIBUTES_LOGICAL: u32 = 0x0002;
+pub const ATTRIBUTES_NONBLOCKIO: u32 = 0x0004;
+
+pub const DATA_DIRECTION_READ: u8 = 0;
+pub const DATA_DIRECTION_WRITE: u8 = 1;
+
+pub type ProtocolPassThru = extern "efiapi" fn(*mut Protocol, *mut u8, u64, *mut Packet, efi::Event) -> efi::Status;
+
+pub type ProtocolGetNextTargetLun = extern "efiapi" fn(*mut Protocol, *mut *mut u8, *mut u64) -> efi::Status;
+
+pub type ProtocolBuildDevicePath =
+    extern "efiapi" fn(*mut Protocol, *mut u8, u64, *mut *mut efi::protocols::device_path::Protocol) -> efi::Status;
+
+pub type ProtocolGetTargetLun = extern "efiapi" fn(
+    *mut Protocol,
+    *mut efi::protocols::device_path::Protocol,
+    *mut *mut u8,
+    *mut u64,
+) -> efi::Status;
+
+pub type ProtocolResetChannel = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+pub type ProtocolResetTargetLun = extern "efiapi" fn(*mut Protocol, *mut u8, u64) -> efi::Status;
+
+pub type ProtocolGetNextTarget = extern "efiapi" fn(*mut Protocol, *mut *mut u8) -> efi::Status;
+
+/// FFI definition of `EFI_EXT_SCSI_PASS_THRU_MODE`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Mode {
+    pub adapter_id: u32,
+    pub attributes: u32,
+    /// Alignment required for the buffers, 0 or 1 if there is none.
+    pub io_align: u32,
+}
+
+/// FFI definition of `EFI_EXT_SCSI_PASS_THRU_SCSI_REQUEST_PACKET`.
+#[repr(C)]
+pub struct Packet {
+    /// Timeout of the command, in units of 100 ns, 0 to wait as long as needed.
+    pub timeout: u64,
+    pub in_data_buffer: *mut c_void,
+    pub out_data_buffer: *mut c_void,
+    pub sense_data: *mut c_void,
+    pub cdb: *mut c_void,
+    pub in_transfer_length: u32,
+    pub out_transfer_length: u32,
+    pub cdb_length: u8,
+    pub data_direction: u8,
+    pub host_adapter_status: u8,
+    pub target_status: u8,
+    pub sense_data_length: u8,
+}
+
+/// FFI definition of `EFI_EXT_SCSI_PASS_THRU_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub mode: *mut Mode,
+    pub pass_thru: ProtocolPassThru,
+    pub get_next_target_lun: ProtocolGetNextTargetLun,
+    pub build_device_path: ProtocolBuildDevicePath,
+    pub get_target_lun: ProtocolGetTargetLun,
+    pub reset_channel: ProtocolResetChannel,
+    pub reset_target_lun: ProtocolResetTargetLun,
+    pub get_next_target: ProtocolGetNextTarget,
+}
+
+/// Extended SCSI Pass Thru protocol.
+pub struct ExtScsiPassThruProtocol;
+
+unsafe impl ProtocolTrait for ExtScsiPassThruProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for ExtScsiPassThruProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// ID of a target of a SCSI channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScsiTarget(pub [u8; TARGET_MAX_BYTES]);
+
+/// Command descriptor block of a SCSI command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cdb {
+    bytes: [u8; 16],
+    len: u8,
+}
+
+impl Cdb {
+    pub const OP_TEST_UNIT_READY: u8 = 0x00;
+    pub const OP_REQUEST_SENSE: u8 = 0x03;
+    pub const OP_INQUIRY: u8 = 0x12;
+    pub const OP_READ_CAPACITY10: u8 = 0x25;
+    pub const OP_READ10: u8 = 0x28;
+    pub const OP_WRITE10: u8 = 0x2a;
+
+    /// A block of 6, 10, 12 or 16 bytes, `None` for other sizes.
+    pub fn new(bytes: &[u8]) -> Option<Self> {
+        if ![6, 10, 12, 16].contains(&bytes.len()) {
+            return None;
+        }
+        let mut cdb = Self { bytes: [0; 16], len: bytes.len() as u8 };
+        cdb.bytes[..bytes.len()].copy_from_slice(bytes);
+        Some(cdb)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    pub fn test_unit_ready() -> Self {
+        Self::new(&[Self::OP_TEST_UNIT_READY, 0, 0, 0, 0, 0]).unwrap()
+    }
+
+    /// Requests the sense data of the last command, of up to `len` bytes.
+    pub fn request_sense(len: u8) -> Self {
+        Self::new(&[Self::OP_REQUEST_SENSE, 0, 0, 0, len, 0]).unwrap()
+    }
+
+    /// Requests the standard inquiry data, of up to `len` bytes.
+    pub fn inquiry(len: u16) -> Self {
+        let [len_high, len_low] = len.to_be_bytes();
+        Self::new(&[Self::OP_INQUIRY, 0, 0, len_high, len_low, 0]).unwrap()
+    }
+
+    /// Requests the last LBA and the block size, 8 bytes.
+    pub fn read_capacity10() -> Self {
+        Self::new(&[Self::OP_READ_CAPACITY10, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap()
+    }
+
+    pub fn read10(lba: u32, blocks: u16) -> Self {
+        Self::rw10(Self::OP_READ10, lba, blocks)
+    }
+
+    pub fn write10(lba: u32, blocks: u16) -> Self {
+        Self::rw10(Self::OP_WRITE10, lba, blocks)
+    }
+
+    fn rw10(operation: u8, lba: u32, blocks: u16) -> Self {
+        let [l0, l1, l2, l3] = lba.to_be_bytes();
+        let [b0, b1] = blocks.to_be_bytes();
+        Self::new(&[operation, 0, l0, l1, l2, l3, 0, b0, b1, 0]).unwrap()
+    }
+}
+
+/// Completion of a SCSI command.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ScsiCompletion {
+    pub host_adapter_status: u8,
+    pub target_status: u8,
+    /// The sense data returned by the target, usually with a [`ScsiCompletion::CHECK_CONDITION`] status.
+    pub sense_data: Vec<u8>,
+    /// Number of bytes transferred.
+    pub transferred: usize,
+}
+
+impl ScsiCompletion {
+    pub const GOOD: u8 = 0x00;
+    pub const CHECK_CONDITION: u8 = 0x02;
+
+    pub fn is_good(&self) -> bool {
+        self.host_adapter_status == 0 && self.target_status == Self::GOOD
+    }
+
+    /// The sense key of fixed format sense data.
+    pub fn sense_key(&self) -> Option<u8> {
+        self.sense_data.get(2).map(|key| key & 0x0f)
+    }
+}
+
+/// Typed access to an instance of the Extended SCSI Pass Thru protocol.
+pub struct ScsiPassThru {
+    protocol: &'static mut Protocol,
+    timeout: u64,
+}
+
+impl ScsiPassThru {
+    /// Default timeout of a command, in units of 100 ns.
+    pub const DEFAULT_TIMEOUT: u64 = 30 * 10_000_000;
+
+    /// Size of the sense data requested with the commands.
+    const SENSE_DATA_SIZE: usize = 252;
+
+    /// Gets the instance of the protocol installed on the handle of a controller.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &ExtScsiPassThruProtocol).map(Self::from)
+    }
+
+    /// Sets the timeout of a command, in units of 100 ns, 0 to wait as long as needed.
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.protocol as *mut Protocol
+    }
+
+    pub fn mode(&self) -> Mode {
+        //SAFETY: The mode of a valid protocol instance is valid.
+        unsafe { *self.protocol.mode }
+    }
+
+    /// The logical units of the targets of the channel.
+    pub fn target_luns(&mut self) -> Result<Vec<(ScsiTarget, u64)>, EfiError> {
+        let mut target_luns = Vec::new();
+        let mut target = ScsiTarget([0xff; TARGET_MAX_BYTES]);
+        let mut lun = 0;
+        loop {
+            let mut target_ptr = target.0.as_mut_ptr();
+            match (self.protocol.get_next_target_lun)(self.this(), &mut target_ptr, &mut lun) {
+                efi::Status::NOT_FOUND => return Ok(target_luns),
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    // Drivers usually update the target in place, the pointer then still points to it.
+                    if target_ptr != target.0.as_mut_ptr() {
+                        //SAFETY: The protocol gives a pointer to a target ID, which is not the buffer of `target`.
+                        target.0.copy_from_slice(unsafe { core::slice::from_raw_parts(target_ptr, TARGET_MAX_BYTES) });
+                    }
+                    target_luns.push((target, lun));
+                }
+            }
+        }
+    }
+
+    /// Sends a command to a logical unit and waits for its completion.
+    ///
+    /// # Errors
+    ///
+    /// A [`PassThruError`] with the completion of the command if the device failed it, such as a target status of
+    /// [`ScsiCompletion::CHECK_CONDITION`].
+    pub fn execute(
+        &mut self,
+        target: &ScsiTarget,
+        lun: u64,
+        cdb: &Cdb,
+        mut data: DataBuffer,
+    ) -> Result<ScsiCompletion, PassThruError<ScsiCompletion>> {
+        let to_error = |status| PassThruError { status, completion: ScsiCompletion::default() };
+        let align = self.mode().io_align.max(1) as usize;
+        let transfer_length = u32::try_from(data.len()).map_err(|_| to_error(efi::Status::BAD_BUFFER_SIZE))?;
+        let mut buffer = data.aligned(align as u32);
+        let mut sense = AlignedBuffer::new(Self::SENSE_DATA_SIZE, align);
+        let mut cdb = AlignedBuffer::from_slice(cdb.as_bytes(), align);
+        let (in_data_buffer, out_data_buffer, data_direction) = match data {
+            DataBuffer::Out(_) => (ptr::null_mut(), buffer.as_mut_slice().as_mut_ptr(), DATA_DIRECTION_WRITE),
+            DataBuffer::In(_) => (buffer.as_mut_slice().as_mut_ptr(), ptr::null_mut(), DATA_DIRECTION_READ),
+            DataBuffer::None => (ptr::null_mut(), ptr::null_mut(), DATA_DIRECTION_READ),
+        };
+        let mut packet = Packet {
+            timeout: self.timeout,
+            in_data_buffer: in_data_buffer as *mut c_void,
+            out_data_buffer: out_data_buffer as *mut c_void,
+            sense_data: sense.as_mut_slice().as_mut_ptr() as *mut c_void,
+            cdb: cdb.as_mut_slice().as_mut_ptr() as *mut c_void,
+            in_transfer_length: if in_data_buffer.is_null() { 0 } else { transfer_length },
+            out_transfer_length: if out_data_buffer.is_null() { 0 } else { transfer_length },
+            cdb_length: cdb.as_slice().len() as u8,
+            data_direction,
+            host_adapter_status: 0,
+            target_status: 0,
+            sense_data_length: Self::SENSE_DATA_SIZE as u8,
+        };
+        let mut target = *target;
+        let status = (self.protocol.pass_thru)(self.this(), target.0.as_mut_ptr(), lun, &mut packet, ptr::null_mut());
+        let transferred = packet.in_transfer_length.max(packet.out_transfer_length) as usize;
+        data.copy_from(&buffer, transferred);
+        let sense_data_length = (packet.sense_data_length as usize).min(Self::SENSE_DATA_SIZE);
+        let completion = ScsiCompletion {
+            host_adapter_status: packet.host_adapter_status,
+            target_status: packet.target_status,
+            sense_data: sense.as_slice()[..sense_data_length].to_vec(),
+            transferred,
+        };
+        match status {
+            s if s.is_error() => Err(PassThruError { status: s, completion }),
+            _ => Ok(completion),
+        }
+    }
+
+    /// Resets the channel.
+    pub fn reset_channel(&mut self) -> Result<(), EfiError> {
+        match (self.protocol.reset_channel)(self.this()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resets a logical unit.
+    pub fn reset_target_lun(&mut self, target: &ScsiTarget, lun: u64) -> Result<(), EfiError> {
+        let mut target = *target;
+        match (self.protocol.reset_target_lun)(self.this(), target.0.as_mut_ptr(), lun) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for ScsiPassThru {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self { protocol, timeout: Self::DEFAULT_TIMEOUT }
+    }
+}
+
+impl fmt::Debug for ScsiPassThru {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScsiPassThru").field("mode", &self.mode()).field("timeout", &self.timeout).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use core::slice;
+
+    /// Channel with the LUNs 0 and 1 of a target 2, the LUN 1 is not ready and fails every command. Its buffers are
+    /// aligned on 8 bytes.
+    #[repr(C)]
+    struct TestScsi {
+        protocol: Protocol,
+        mode: Mode,
+        written: Vec<u8>,
+    }
+
+    const TARGET: ScsiTarget = ScsiTarget([2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    const INQUIRY: &[u8] = b"\x00\x00\x05\x02\x1f\x00\x00\x00QEMU    QEMU HARDDISK   2.5+";
+    const NOT_READY: [u8; 18] = [0x70, 0, 0x02, 0, 0, 0, 0, 10, 0, 0, 0, 0, 0x04, 0x01, 0, 0, 0, 0];
+
+    fn test_scsi<'a>(this: *mut Protocol) -> &'a mut TestScsi {
+        unsafe { &mut *(this as *mut TestScsi) }
+    }
+
+    extern "efiapi" fn pass_thru(
+        this: *mut Protocol,
+        target: *mut u8,
+        lun: u64,
+        packet: *mut Packet,
+        _: efi::Event,
+    ) -> efi::Status {
+        let test_scsi = test_scsi(this);
+        let packet = unsafe { &mut *packet };
+        for buffer in [packet.in_data_buffer, packet.out_data_buffer, packet.sense_data, packet.cdb] {
+            assert_eq!(0, buffer as usize % 8);
+        }
+        assert_eq!(TARGET.0, unsafe { slice::from_raw_parts(target, TARGET_MAX_BYTES) });
+        let cdb = unsafe { slice::from_raw_parts(packet.cdb as *const u8, packet.cdb_length as usize) };
+        if lun == 1 {
+            unsafe { slice::from_raw_parts_mut(packet.sense_data as *mut u8, NOT_READY.len()) }
+                .copy_from_slice(&NOT_READY);
+            packet.sense_data_length = NOT_READY.len() as u8;
+            packet.target_status = ScsiCompletion::CHECK_CONDITION;
+            packet.in_transfer_length = 0;
+            return efi::Status::DEVICE_ERROR;
+        }
+        packet.sense_data_length = 0;
+        match cdb[0] {
+            Cdb::OP_INQUIRY => {
+                let len = (packet.in_transfer_length as usize).min(INQUIRY.len());
+                unsafe { slice::from_raw_parts_mut(packet.in_data_buffer as *mut u8, len) }
+                    .copy_from_slice(&INQUIRY[..len]);
+                packet.in_transfer_length = len as u32;
+            }
+            Cdb::OP_WRITE10 => {
+                let data = unsafe {
+                    slice::from_raw_parts(packet.out_data_buffer as *const u8, packet.out_transfer_length as usize)
+                };
+                test_scsi.written.extend_from_slice(data);
+            }
+            Cdb::OP_TEST_UNIT_READY => (),
+            _ => return efi::Status::UNSUPPORTED,
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_next_target_lun(_: *mut Protocol, target: *mut *mut u8, lun: *mut u64) -> efi::Status {
+        let target = unsafe { slice::from_raw_parts_mut(*target, TARGET_MAX_BYTES) };
+        let lun = unsafe { &mut *lun };
+        match (target[0], *lun) {
+            (0xff, _) => {
+                target.copy_from_slice(&TARGET.0);
+                *lun = 0;
+            }
+            (2, 0) => *lun = 1,
+            _ => return efi::Status::NOT_FOUND,
+        }
+        efi::Status::SUCCESS
+    }
+
+    macro_rules! unused {
+        ($($name:ident($($arg:ty),*);)*) => {
+            $(extern "efiapi" fn $name(_: *mut Protocol, $(_: $arg),*) -> efi::Status {
+                unimplemented!()
+            })*
+        };
+    }
+
+    unused! {
+        build_device_path(*mut u8, u64, *mut *mut efi::protocols::device_path::Protocol);
+        get_target_lun(*mut efi::protocols::device_path::Protocol, *mut *mut u8, *mut u64);
+        reset_channel();
+        reset_target_lun(*mut u8, u64);
+        get_next_target(*mut *mut u8);
+    }
+
+    fn scsi_pass_thru() -> (ScsiPassThru, &'static TestScsi) {
+        let scsi = Box::leak(Box::new(TestScsi {
+            protocol: Protocol {
+                mode: ptr::null_mut(),
+                pass_thru,
+                get_next_target_lun,
+                build_device_path,
+                get_target_lun,
+                reset_channel,
+                reset_target_lun,
+                get_next_target,
+            },
+            mode: Mode { adapter_id: 7, attributes: ATTRIBUTES_PHYSICAL | ATTRIBUTES_LOGICAL, io_align: 8 },
+            written: Vec::new(),
+        }));
+        scsi.protocol.mode = &mut scsi.mode;
+        let test_scsi = test_scsi(&mut scsi.protocol);
+        (ScsiPassThru::from(&mut scsi.protocol), test_scsi)
+    }
+
+    #[test]
+    fn test_cdb() {
+        assert_eq!([0x12, 0, 0, 0, 36, 0], Cdb::inquiry(36).as_bytes());
+        assert_eq!([0x28, 0, 0x12, 0x34, 0x56, 0x78, 0, 0, 8, 0], Cdb::read10(0x12345678, 8).as_bytes());
+        assert_eq!(10, Cdb::read_capacity10().as_bytes().len());
+        assert_eq!(None, Cdb::new(&[0; 7]));
+    }
+
+    #[test]
+    fn test_execute() {
+        let (mut scsi, test_scsi) = scsi_pass_thru();
+        assert_eq!(7, scsi.mode().adapter_id);
+        assert_eq!(vec![(TARGET, 0), (TARGET, 1)], scsi.target_luns().unwrap());
+
+        let mut storage = [0u8; 37];
+        let inquiry = &mut storage[1..];
+        let completion = scsi.execute(&TARGET, 0, &Cdb::inquiry(36), DataBuffer::In(inquiry)).unwrap();
+        assert!(completion.is_good());
+        assert_eq!(36, completion.transferred);
+        assert_eq!(INQUIRY, &storage[1..]);
+
+        scsi.execute(&TARGET, 0, &Cdb::write10(0, 1), DataBuffer::Out(&[0xaa; 5])).unwrap();
+        assert_eq!(vec![0xaa; 5], test_scsi.written);
+
+        let error = scsi.execute(&TARGET, 1, &Cdb::test_unit_ready(), DataBuffer::None).unwrap_err();
+        assert_eq!(efi::Status::DEVICE_ERROR, error.status);
+        assert_eq!(ScsiCompletion::CHECK_CONDITION, error.completion.target_status);
+        assert_eq!(Some(0x02), error.completion.sense_key());
+        assert_eq!(NOT_READY.to_vec(), error.completion.sense_data);
+    }
+}
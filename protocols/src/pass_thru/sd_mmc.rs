@@ -0,0 +1,472 @@
+//! SD MMC Pass Thru protocol.
+//!
+//! [`SdMmcPassThru`] sends a [`SdMmcCommand`] to the card of a slot of an SD or eMMC host controller and gives its
+//! [`SdMmcResponse`]. [`SdMmcPassThru::rpmb`] accesses the replay protected memory block of an eMMC device, see
+//! [`super::rpmb`]:
+//!
+//! ```ignore
+//! let mut sd_mmc = SdMmcPassThru::get(boot_services, controller)?;
+//! for slot in sd_mmc.slots()? {
+//!     let ext_csd = sd_mmc.ext_csd(slot)?;
+//!     let sectors = u32::from_le_bytes(ext_csd[212..216].try_into().unwrap());
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 13.17. SD MMC Pass Thru Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#sd-mmc-pass-thru)
+
+use alloc::{vec, vec::Vec};
+use core::{ffi::c_void, fmt, ops::Deref, ptr};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::{rpmb::Rpmb, DataBuffer, PassThruError};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x716ef0d9, 0xff83, 0x4f69, 0x81, 0xe9, &[0x51, 0x8b, 0xd3, 0x9a, 0x8e, 0x70]);
+
+/// Broadcast command without response.
+pub const COMMAND_TYPE_BC: u32 = 0;
+/// Broadcast command with response.
+pub const COMMAND_TYPE_BCR: u32 = 1;
+/// Addressed command without data transfer.
+pub const COMMAND_TYPE_AC: u32 = 2;
+/// Addressed command with data transfer.
+pub const COMMAND_TYPE_ADTC: u32 = 3;
+
+pub const RESPONSE_TYPE_R1: u32 = 0;
+pub const RESPONSE_TYPE_R1B: u32 = 1;
+pub const RESPONSE_TYPE_R2: u32 = 2;
+pub const RESPONSE_TYPE_R3: u32 = 3;
+pub const RESPONSE_TYPE_R4: u32 = 4;
+pub const RESPONSE_TYPE_R5: u32 = 5;
+pub const RESPONSE_TYPE_R5B: u32 = 6;
+pub const RESPONSE_TYPE_R6: u32 = 7;
+pub const RESPONSE_TYPE_R7: u32 = 8;
+
+pub type ProtocolPassThru = extern "efiapi" fn(*mut Protocol, u8, *mut Packet, efi::Event) -> efi::Status;
+
+pub type ProtocolGetNextSlot = extern "efiapi" fn(*mut Protocol, *mut u8) -> efi::Status;
+
+pub type ProtocolBuildDevicePath =
+    extern "efiapi" fn(*mut Protocol, u8, *mut *mut efi::protocols::device_path::Protocol) -> efi::Status;
+
+pub type ProtocolGetSlotNumber =
+    extern "efiapi" fn(*mut Protocol, *mut efi::protocols::device_path::Protocol, *mut u8) -> efi::Status;
+
+pub type ProtocolResetDevice = extern "efiapi" fn(*mut Protocol, u8) -> efi::Status;
+
+/// FFI definition of `EFI_SD_MMC_COMMAND_BLOCK`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandBlock {
+    pub command_index: u16,
+    pub command_argument: u32,
+    pub command_type: u32,
+    pub response_type: u32,
+}
+
+/// FFI definition of `EFI_SD_MMC_STATUS_BLOCK`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatusBlock {
+    pub resp0: u32,
+    pub resp1: u32,
+    pub resp2: u32,
+    pub resp3: u32,
+}
+
+/// FFI definition of `EFI_SD_MMC_PASS_THRU_COMMAND_PACKET`.
+#[repr(C)]
+pub struct Packet {
+    pub sd_mmc_cmd_blk: *mut CommandBlock,
+    pub sd_mmc_status_blk: *mut StatusBlock,
+    /// Timeout of the command, in units of 100 ns, 0 to wait as long as needed.
+    pub timeout: u64,
+    pub in_data_buffer: *mut c_void,
+    pub out_data_buffer: *mut c_void,
+    pub in_transfer_length: u32,
+    pub out_transfer_length: u32,
+    pub transaction_status: efi::Status,
+}
+
+/// FFI definition of `EFI_SD_MMC_PASS_THRU_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    /// Alignment required for the buffers, 0 or 1 if there is none.
+    pub io_align: u32,
+    pub pass_thru: ProtocolPassThru,
+    pub get_next_slot: ProtocolGetNextSlot,
+    pub build_device_path: ProtocolBuildDevicePath,
+    pub get_slot_number: ProtocolGetSlotNumber,
+    pub reset_device: ProtocolResetDevice,
+}
+
+/// SD MMC Pass Thru protocol.
+pub struct SdMmcPassThruProtocol;
+
+unsafe impl ProtocolTrait for SdMmcPassThruProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for SdMmcPassThruProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Builder of an SD or MMC command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdMmcCommand(pub CommandBlock);
+
+impl SdMmcCommand {
+    pub const SWITCH: u16 = 6;
+    pub const SEND_EXT_CSD: u16 = 8;
+    pub const SEND_STATUS: u16 = 13;
+    pub const READ_MULTIPLE_BLOCK: u16 = 18;
+    pub const SET_BLOCK_COUNT: u16 = 23;
+    pub const WRITE_MULTIPLE_BLOCK: u16 = 25;
+
+    /// A command with a `COMMAND_TYPE_*` type and a `RESPONSE_TYPE_*` response.
+    pub fn new(index: u16, command_type: u32, response_type: u32) -> Self {
+        Self(CommandBlock { command_index: index, command_argument: 0, command_type, response_type })
+    }
+
+    pub fn with_argument(mut self, argument: u32) -> Self {
+        self.0.command_argument = argument;
+        self
+    }
+
+    pub fn index(&self) -> u16 {
+        self.0.command_index
+    }
+
+    pub fn argument(&self) -> u32 {
+        self.0.command_argument
+    }
+
+    /// Writes `value` to the byte at `index` of the EXT_CSD register of an eMMC device.
+    pub fn switch(index: u8, value: u8) -> Self {
+        const WRITE_BYTE: u32 = 0x03;
+        Self::new(Self::SWITCH, COMMAND_TYPE_AC, RESPONSE_TYPE_R1B)
+            .with_argument(WRITE_BYTE << 24 | (index as u32) << 16 | (value as u32) << 8)
+    }
+
+    /// Reads the 512 bytes of the EXT_CSD register of an eMMC device.
+    pub fn send_ext_csd() -> Self {
+        Self::new(Self::SEND_EXT_CSD, COMMAND_TYPE_ADTC, RESPONSE_TYPE_R1)
+    }
+
+    /// Reads the status of the card with the relative card address `rca`.
+    pub fn send_status(rca: u16) -> Self {
+        Self::new(Self::SEND_STATUS, COMMAND_TYPE_AC, RESPONSE_TYPE_R1).with_argument((rca as u32) << 16)
+    }
+
+    /// Sets the number of blocks of the next multiple block read or write, a reliable write if `reliable_write`.
+    pub fn set_block_count(count: u16, reliable_write: bool) -> Self {
+        Self::new(Self::SET_BLOCK_COUNT, COMMAND_TYPE_AC, RESPONSE_TYPE_R1)
+            .with_argument(count as u32 | (reliable_write as u32) << 31)
+    }
+
+    pub fn read_multiple_block(lba: u32) -> Self {
+        Self::new(Self::READ_MULTIPLE_BLOCK, COMMAND_TYPE_ADTC, RESPONSE_TYPE_R1).with_argument(lba)
+    }
+
+    pub fn write_multiple_block(lba: u32) -> Self {
+        Self::new(Self::WRITE_MULTIPLE_BLOCK, COMMAND_TYPE_ADTC, RESPONSE_TYPE_R1).with_argument(lba)
+    }
+}
+
+/// Response of a command, the card status for the R1 responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SdMmcResponse(pub [u32; 4]);
+
+impl SdMmcResponse {
+    /// The error bits of the card status of an R1 response.
+    const CARD_STATUS_ERRORS: u32 = 0xfdf9_8088;
+
+    pub fn card_status(&self) -> u32 {
+        self.0[0]
+    }
+
+    /// Whether the card status of an R1 response reports an error.
+    pub fn has_error(&self) -> bool {
+        self.card_status() & Self::CARD_STATUS_ERRORS != 0
+    }
+}
+
+impl From<StatusBlock> for SdMmcResponse {
+    fn from(status: StatusBlock) -> Self {
+        Self([status.resp0, status.resp1, status.resp2, status.resp3])
+    }
+}
+
+/// Typed access to an instance of the SD MMC Pass Thru protocol.
+pub struct SdMmcPassThru {
+    protocol: &'static mut Protocol,
+    timeout: u64,
+}
+
+impl SdMmcPassThru {
+    /// Default timeout of a command, in units of 100 ns.
+    pub const DEFAULT_TIMEOUT: u64 = 3 * 10_000_000;
+
+    /// Size of the EXT_CSD register.
+    pub const EXT_CSD_SIZE: usize = 512;
+
+    /// Gets the instance of the protocol installed on the handle of a host controller.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &SdMmcPassThruProtocol).map(Self::from)
+    }
+
+    /// Sets the timeout of a command, in units of 100 ns, 0 to wait as long as needed.
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.protocol as *mut Protocol
+    }
+
+    /// The slots of the controller with a card.
+    pub fn slots(&mut self) -> Result<Vec<u8>, EfiError> {
+        let mut slots = Vec::new();
+        let mut slot = 0xff;
+        loop {
+            match (self.protocol.get_next_slot)(self.this(), &mut slot) {
+                efi::Status::NOT_FOUND => return Ok(slots),
+                s if s.is_error() => return Err(s.into()),
+                _ => slots.push(slot),
+            }
+        }
+    }
+
+    /// Sends a command to the card of a slot and waits for its completion.
+    ///
+    /// # Errors
+    ///
+    /// A [`PassThruError`] with the response of the card if the command failed.
+    pub fn execute(
+        &mut self,
+        slot: u8,
+        command: &SdMmcCommand,
+        mut data: DataBuffer,
+    ) -> Result<SdMmcResponse, PassThruError<SdMmcResponse>> {
+        let transfer_length = u32::try_from(data.len()).map_err(|_| PassThruError {
+            status: efi::Status::BAD_BUFFER_SIZE,
+            completion: SdMmcResponse::default(),
+        })?;
+        let mut buffer = data.aligned(self.protocol.io_align);
+        let mut command_block = command.0;
+        let mut status_block = StatusBlock::default();
+        let data_buffer = buffer.as_mut_slice().as_mut_ptr() as *mut c_void;
+        let (in_data_buffer, out_data_buffer) = match data {
+            DataBuffer::None => (ptr::null_mut(), ptr::null_mut()),
+            DataBuffer::In(_) => (data_buffer, ptr::null_mut()),
+            DataBuffer::Out(_) => (ptr::null_mut(), data_buffer),
+        };
+        let mut packet = Packet {
+            sd_mmc_cmd_blk: &mut command_block,
+            sd_mmc_status_blk: &mut status_block,
+            timeout: self.timeout,
+            in_data_buffer,
+            out_data_buffer,
+            in_transfer_length: if in_data_buffer.is_null() { 0 } else { transfer_length },
+            out_transfer_length: if out_data_buffer.is_null() { 0 } else { transfer_length },
+            transaction_status: efi::Status::SUCCESS,
+        };
+        let status = (self.protocol.pass_thru)(self.this(), slot, &mut packet, ptr::null_mut());
+        data.copy_from(&buffer, packet.in_transfer_length as usize);
+        match status {
+            s if s.is_error() => Err(PassThruError { status: s, completion: status_block.into() }),
+            _ => Ok(status_block.into()),
+        }
+    }
+
+    /// Reads the EXT_CSD register of the eMMC device of a slot.
+    pub fn ext_csd(&mut self, slot: u8) -> Result<Vec<u8>, EfiError> {
+        let mut ext_csd = vec![0; Self::EXT_CSD_SIZE];
+        self.execute(slot, &SdMmcCommand::send_ext_csd(), DataBuffer::In(&mut ext_csd))?;
+        Ok(ext_csd)
+    }
+
+    /// Switches the eMMC device of a slot to its replay protected memory block, until the returned [`Rpmb`] is dropped.
+    pub fn rpmb(&mut self, slot: u8) -> Result<Rpmb<'_>, EfiError> {
+        Rpmb::open(self, slot)
+    }
+
+    /// Resets the card of a slot.
+    pub fn reset_device(&mut self, slot: u8) -> Result<(), EfiError> {
+        match (self.protocol.reset_device)(self.this(), slot) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for SdMmcPassThru {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self { protocol, timeout: Self::DEFAULT_TIMEOUT }
+    }
+}
+
+impl fmt::Debug for SdMmcPassThru {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SdMmcPassThru")
+            .field("io_align", &self.protocol.io_align)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::slice;
+
+    /// eMMC device in slot 1 of a controller requiring buffers aligned on 8 bytes. It keeps the commands it receives
+    /// and answers with the `respond` function of the test.
+    #[repr(C)]
+    pub(crate) struct TestSdMmc {
+        protocol: Protocol,
+        pub ext_csd: [u8; 512],
+        pub commands: Vec<(CommandBlock, Vec<u8>)>,
+        /// Answers a command with its data written to the device, and fills the data read from the device.
+        pub respond: fn(&mut TestSdMmc, &CommandBlock, &[u8], &mut [u8]) -> efi::Status,
+    }
+
+    pub(crate) const SLOT: u8 = 1;
+
+    fn test_sd_mmc<'a>(this: *mut Protocol) -> &'a mut TestSdMmc {
+        unsafe { &mut *(this as *mut TestSdMmc) }
+    }
+
+    /// Answers the EXT_CSD reads and the switch commands.
+    pub(crate) fn respond_ext_csd(
+        sd_mmc: &mut TestSdMmc,
+        command: &CommandBlock,
+        _: &[u8],
+        data_in: &mut [u8],
+    ) -> efi::Status {
+        match command.command_index {
+            SdMmcCommand::SEND_EXT_CSD => data_in.copy_from_slice(&sd_mmc.ext_csd),
+            SdMmcCommand::SWITCH => {
+                let argument = command.command_argument;
+                sd_mmc.ext_csd[(argument >> 16) as u8 as usize] = (argument >> 8) as u8;
+            }
+            _ => return efi::Status::UNSUPPORTED,
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn pass_thru(this: *mut Protocol, slot: u8, packet: *mut Packet, _: efi::Event) -> efi::Status {
+        let sd_mmc = test_sd_mmc(this);
+        let packet = unsafe { &mut *packet };
+        assert_eq!(SLOT, slot);
+        assert_eq!(0, packet.in_data_buffer as usize % 8);
+        assert_eq!(0, packet.out_data_buffer as usize % 8);
+        let command = unsafe { *packet.sd_mmc_cmd_blk };
+        let data_out = match packet.out_data_buffer.is_null() {
+            true => Vec::new(),
+            false => unsafe {
+                slice::from_raw_parts(packet.out_data_buffer as *const u8, packet.out_transfer_length as usize)
+            }
+            .to_vec(),
+        };
+        let data_in: &mut [u8] = match packet.in_data_buffer.is_null() {
+            true => &mut [],
+            false => unsafe {
+                slice::from_raw_parts_mut(packet.in_data_buffer as *mut u8, packet.in_transfer_length as usize)
+            },
+        };
+        sd_mmc.commands.push((command, data_out.clone()));
+        let respond = sd_mmc.respond;
+        let status = respond(sd_mmc, &command, &data_out, data_in);
+        // The card is in the transfer state.
+        unsafe { (*packet.sd_mmc_status_blk).resp0 = 0x900 };
+        packet.transaction_status = status;
+        status
+    }
+
+    extern "efiapi" fn get_next_slot(_: *mut Protocol, slot: *mut u8) -> efi::Status {
+        match unsafe { *slot } {
+            0xff => unsafe { *slot = SLOT },
+            _ => return efi::Status::NOT_FOUND,
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn reset_device(this: *mut Protocol, _: u8) -> efi::Status {
+        test_sd_mmc(this).commands.clear();
+        efi::Status::SUCCESS
+    }
+
+    macro_rules! unused {
+        ($($name:ident($($arg:ty),*);)*) => {
+            $(extern "efiapi" fn $name(_: *mut Protocol, $(_: $arg),*) -> efi::Status {
+                unimplemented!()
+            })*
+        };
+    }
+
+    unused! {
+        build_device_path(u8, *mut *mut efi::protocols::device_path::Protocol);
+        get_slot_number(*mut efi::protocols::device_path::Protocol, *mut u8);
+    }
+
+    pub(crate) fn sd_mmc_pass_thru(
+        respond: fn(&mut TestSdMmc, &CommandBlock, &[u8], &mut [u8]) -> efi::Status,
+    ) -> (SdMmcPassThru, &'static mut TestSdMmc) {
+        let sd_mmc = Box::leak(Box::new(TestSdMmc {
+            protocol: Protocol {
+                io_align: 8,
+                pass_thru,
+                get_next_slot,
+                build_device_path,
+                get_slot_number,
+                reset_device,
+            },
+            ext_csd: [0; 512],
+            commands: Vec::new(),
+            respond,
+        }));
+        let test_sd_mmc = test_sd_mmc(&mut sd_mmc.protocol);
+        (SdMmcPassThru::from(&mut sd_mmc.protocol), test_sd_mmc)
+    }
+
+    #[test]
+    fn test_command() {
+        assert_eq!(0x03b3_0b00, SdMmcCommand::switch(179, 0x0b).argument());
+        assert_eq!(0x8000_0001, SdMmcCommand::set_block_count(1, true).argument());
+        assert_eq!(0x0001_0000, SdMmcCommand::send_status(1).argument());
+        assert_eq!(COMMAND_TYPE_ADTC, SdMmcCommand::read_multiple_block(8).0.command_type);
+    }
+
+    #[test]
+    fn test_execute() {
+        let (mut sd_mmc, test_sd_mmc) = sd_mmc_pass_thru(respond_ext_csd);
+        assert_eq!(vec![SLOT], sd_mmc.slots().unwrap());
+
+        test_sd_mmc.ext_csd[212..216].copy_from_slice(&0x0074_0000u32.to_le_bytes());
+        let ext_csd = sd_mmc.ext_csd(SLOT).unwrap();
+        assert_eq!(0x0074_0000, u32::from_le_bytes(ext_csd[212..216].try_into().unwrap()));
+
+        let response = sd_mmc.execute(SLOT, &SdMmcCommand::switch(33, 1), DataBuffer::None).unwrap();
+        assert!(!response.has_error());
+        assert_eq!(1, test_sd_mmc.ext_csd[33]);
+
+        let error = sd_mmc.execute(SLOT, &SdMmcCommand::send_status(1), DataBuffer::None).unwrap_err();
+        assert_eq!(efi::Status::UNSUPPORTED, error.status);
+        assert_eq!(3, test_sd_mmc.commands.len());
+        sd_mmc.reset_device(SLOT).unwrap();
+        assert!(test_sd_mmc.commands.is_empty());
+    }
+}
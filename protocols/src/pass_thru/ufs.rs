@@ -0,0 +1,322 @@
+//! UFS Device Config protocol.
+//!
+//! [`UfsDeviceConfig`] reads and writes the descriptors, flags and attributes of a UFS device with query requests,
+//! as needed to provision its logical units:
+//!
+//! ```ignore
+//! let mut ufs = UfsDeviceConfig::get(boot_services, controller)?;
+//! if ufs.read_attribute(UfsDeviceConfig::ATTRIBUTE_CONFIG_DESCR_LOCK, 0, 0)? == 0 {
+//!     ufs.write_descriptor(UfsDeviceConfig::DESCRIPTOR_CONFIGURATION, 0, 0, &configuration)?;
+//!     ufs.write_attribute(UfsDeviceConfig::ATTRIBUTE_CONFIG_DESCR_LOCK, 0, 0, 1)?;
+//! }
+//! ```
+//!
+//! The logical units of the device, including its RPMB well known logical unit, are accessed with the SCSI commands of
+//! [`super::ScsiPassThru`].
+//!
+//! [UEFI Spec Documentation: 13.19. UFS Device Config Protocol](https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#ufs-device-config-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, ops::Deref};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xb81bfab0, 0x0eb3, 0x4cf9, 0x84, 0x65, &[0x7f, 0xa9, 0x86, 0x36, 0x16, 0x64]);
+
+pub type ProtocolRwUfsDescriptor =
+    extern "efiapi" fn(*mut Protocol, efi::Boolean, u8, u8, u8, *mut u8, *mut u32) -> efi::Status;
+
+pub type ProtocolRwUfsFlag = extern "efiapi" fn(*mut Protocol, efi::Boolean, u8, *mut u8) -> efi::Status;
+
+pub type ProtocolRwUfsAttribute =
+    extern "efiapi" fn(*mut Protocol, efi::Boolean, u8, u8, u8, *mut u8, *mut u32) -> efi::Status;
+
+/// FFI definition of `EFI_UFS_DEVICE_CONFIG_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub rw_ufs_descriptor: ProtocolRwUfsDescriptor,
+    pub rw_ufs_flag: ProtocolRwUfsFlag,
+    pub rw_ufs_attribute: ProtocolRwUfsAttribute,
+}
+
+/// UFS Device Config protocol.
+pub struct UfsDeviceConfigProtocol;
+
+unsafe impl ProtocolTrait for UfsDeviceConfigProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for UfsDeviceConfigProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Typed access to an instance of the UFS Device Config protocol.
+pub struct UfsDeviceConfig(&'static mut Protocol);
+
+impl UfsDeviceConfig {
+    pub const DESCRIPTOR_DEVICE: u8 = 0x00;
+    pub const DESCRIPTOR_CONFIGURATION: u8 = 0x01;
+    pub const DESCRIPTOR_UNIT: u8 = 0x02;
+    pub const DESCRIPTOR_INTERCONNECT: u8 = 0x04;
+    pub const DESCRIPTOR_STRING: u8 = 0x05;
+    pub const DESCRIPTOR_GEOMETRY: u8 = 0x07;
+    pub const DESCRIPTOR_POWER: u8 = 0x08;
+    pub const DESCRIPTOR_DEVICE_HEALTH: u8 = 0x09;
+
+    pub const FLAG_DEVICE_INIT: u8 = 0x01;
+    pub const FLAG_PERMANENT_WP_EN: u8 = 0x02;
+    pub const FLAG_POWER_ON_WP_EN: u8 = 0x03;
+    pub const FLAG_BACKGROUND_OPS_EN: u8 = 0x04;
+    pub const FLAG_PURGE_ENABLE: u8 = 0x06;
+
+    pub const ATTRIBUTE_BOOT_LUN_EN: u8 = 0x00;
+    pub const ATTRIBUTE_CURRENT_POWER_MODE: u8 = 0x02;
+    pub const ATTRIBUTE_ACTIVE_ICC_LEVEL: u8 = 0x03;
+    pub const ATTRIBUTE_CONFIG_DESCR_LOCK: u8 = 0x13;
+
+    /// Maximum size of a descriptor.
+    const DESCRIPTOR_MAX_SIZE: usize = 255;
+
+    /// Gets the instance of the protocol installed on the handle of a UFS host controller.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &UfsDeviceConfigProtocol).map(Self)
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.0 as *mut Protocol
+    }
+
+    /// Reads a descriptor, identified by its `DESCRIPTOR_*` ID, its index and its selector.
+    pub fn read_descriptor(&mut self, id: u8, index: u8, selector: u8) -> Result<Vec<u8>, EfiError> {
+        let mut descriptor = vec![0; Self::DESCRIPTOR_MAX_SIZE];
+        let mut size = descriptor.len() as u32;
+        match (self.0.rw_ufs_descriptor)(
+            self.this(),
+            efi::Boolean::TRUE,
+            id,
+            index,
+            selector,
+            descriptor.as_mut_ptr(),
+            &mut size,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => {
+                descriptor.truncate(size as usize);
+                Ok(descriptor)
+            }
+        }
+    }
+
+    /// Writes a descriptor, see [`UfsDeviceConfig::read_descriptor`].
+    pub fn write_descriptor(&mut self, id: u8, index: u8, selector: u8, descriptor: &[u8]) -> Result<(), EfiError> {
+        let mut size = u32::try_from(descriptor.len()).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+        // The descriptor is only read by the protocol.
+        match (self.0.rw_ufs_descriptor)(
+            self.this(),
+            efi::Boolean::FALSE,
+            id,
+            index,
+            selector,
+            descriptor.as_ptr() as *mut u8,
+            &mut size,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads a flag, identified by its `FLAG_*` ID.
+    pub fn read_flag(&mut self, id: u8) -> Result<bool, EfiError> {
+        let mut flag = 0;
+        match (self.0.rw_ufs_flag)(self.this(), efi::Boolean::TRUE, id, &mut flag) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(flag != 0),
+        }
+    }
+
+    /// Sets or clears a flag, see [`UfsDeviceConfig::read_flag`].
+    pub fn write_flag(&mut self, id: u8, value: bool) -> Result<(), EfiError> {
+        let mut flag = value as u8;
+        match (self.0.rw_ufs_flag)(self.this(), efi::Boolean::FALSE, id, &mut flag) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads an attribute, identified by its `ATTRIBUTE_*` ID, its index and its selector.
+    pub fn read_attribute(&mut self, id: u8, index: u8, selector: u8) -> Result<u32, EfiError> {
+        let mut attribute = [0; 4];
+        let mut size = attribute.len() as u32;
+        match (self.0.rw_ufs_attribute)(
+            self.this(),
+            efi::Boolean::TRUE,
+            id,
+            index,
+            selector,
+            attribute.as_mut_ptr(),
+            &mut size,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(u32::from_le_bytes(attribute)),
+        }
+    }
+
+    /// Writes an attribute, see [`UfsDeviceConfig::read_attribute`].
+    pub fn write_attribute(&mut self, id: u8, index: u8, selector: u8, value: u32) -> Result<(), EfiError> {
+        let mut attribute = value.to_le_bytes();
+        let mut size = attribute.len() as u32;
+        match (self.0.rw_ufs_attribute)(
+            self.this(),
+            efi::Boolean::FALSE,
+            id,
+            index,
+            selector,
+            attribute.as_mut_ptr(),
+            &mut size,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for UfsDeviceConfig {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for UfsDeviceConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("UfsDeviceConfig").field(&(self.0 as *const Protocol)).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::slice;
+    use std::collections::BTreeMap;
+
+    /// Device whose descriptors, flags and attributes are kept by ID, the configuration descriptor is locked once
+    /// `ATTRIBUTE_CONFIG_DESCR_LOCK` is set.
+    #[repr(C)]
+    struct TestUfs {
+        protocol: Protocol,
+        descriptors: BTreeMap<u8, Vec<u8>>,
+        flags: BTreeMap<u8, u8>,
+        attributes: BTreeMap<u8, u32>,
+    }
+
+    fn test_ufs<'a>(this: *mut Protocol) -> &'a mut TestUfs {
+        unsafe { &mut *(this as *mut TestUfs) }
+    }
+
+    extern "efiapi" fn rw_ufs_descriptor(
+        this: *mut Protocol,
+        read: efi::Boolean,
+        id: u8,
+        _: u8,
+        _: u8,
+        descriptor: *mut u8,
+        size: *mut u32,
+    ) -> efi::Status {
+        let ufs = test_ufs(this);
+        let size = unsafe { &mut *size };
+        if bool::from(read) {
+            let Some(data) = ufs.descriptors.get(&id) else {
+                return efi::Status::NOT_FOUND;
+            };
+            *size = data.len().min(*size as usize) as u32;
+            unsafe { slice::from_raw_parts_mut(descriptor, *size as usize) }.copy_from_slice(&data[..*size as usize]);
+        } else {
+            if ufs.attributes.get(&UfsDeviceConfig::ATTRIBUTE_CONFIG_DESCR_LOCK) == Some(&1) {
+                return efi::Status::ACCESS_DENIED;
+            }
+            ufs.descriptors.insert(id, unsafe { slice::from_raw_parts(descriptor, *size as usize) }.to_vec());
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn rw_ufs_flag(this: *mut Protocol, read: efi::Boolean, id: u8, flag: *mut u8) -> efi::Status {
+        let ufs = test_ufs(this);
+        match bool::from(read) {
+            true => unsafe { *flag = ufs.flags.get(&id).copied().unwrap_or(0) },
+            false => _ = ufs.flags.insert(id, unsafe { *flag }),
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn rw_ufs_attribute(
+        this: *mut Protocol,
+        read: efi::Boolean,
+        id: u8,
+        _: u8,
+        _: u8,
+        attribute: *mut u8,
+        size: *mut u32,
+    ) -> efi::Status {
+        let ufs = test_ufs(this);
+        assert_eq!(4, unsafe { *size });
+        let attribute = unsafe { &mut *(attribute as *mut [u8; 4]) };
+        match bool::from(read) {
+            true => *attribute = ufs.attributes.get(&id).copied().unwrap_or(0).to_le_bytes(),
+            false => _ = ufs.attributes.insert(id, u32::from_le_bytes(*attribute)),
+        }
+        efi::Status::SUCCESS
+    }
+
+    fn ufs_device_config() -> (UfsDeviceConfig, &'static TestUfs) {
+        let ufs = Box::leak(Box::new(TestUfs {
+            protocol: Protocol { rw_ufs_descriptor, rw_ufs_flag, rw_ufs_attribute },
+            descriptors: BTreeMap::from([(UfsDeviceConfig::DESCRIPTOR_DEVICE, vec![0x40, 0x00, 0x00, 0x01])]),
+            flags: BTreeMap::new(),
+            attributes: BTreeMap::new(),
+        }));
+        let test_ufs = test_ufs(&mut ufs.protocol);
+        (UfsDeviceConfig::from(&mut ufs.protocol), test_ufs)
+    }
+
+    #[test]
+    fn test_descriptors() {
+        let (mut ufs, test_ufs) = ufs_device_config();
+        assert_eq!(
+            vec![0x40, 0x00, 0x00, 0x01],
+            ufs.read_descriptor(UfsDeviceConfig::DESCRIPTOR_DEVICE, 0, 0).unwrap()
+        );
+        assert_eq!(
+            efi::Status::NOT_FOUND,
+            ufs.read_descriptor(UfsDeviceConfig::DESCRIPTOR_GEOMETRY, 0, 0).unwrap_err()
+        );
+
+        let configuration = [0x90, 0x01, 0x01];
+        ufs.write_descriptor(UfsDeviceConfig::DESCRIPTOR_CONFIGURATION, 0, 0, &configuration).unwrap();
+        assert_eq!(configuration.to_vec(), test_ufs.descriptors[&UfsDeviceConfig::DESCRIPTOR_CONFIGURATION]);
+
+        ufs.write_attribute(UfsDeviceConfig::ATTRIBUTE_CONFIG_DESCR_LOCK, 0, 0, 1).unwrap();
+        assert_eq!(1, ufs.read_attribute(UfsDeviceConfig::ATTRIBUTE_CONFIG_DESCR_LOCK, 0, 0).unwrap());
+        assert_eq!(
+            efi::Status::ACCESS_DENIED,
+            ufs.write_descriptor(UfsDeviceConfig::DESCRIPTOR_CONFIGURATION, 0, 0, &configuration).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_flags() {
+        let (mut ufs, _) = ufs_device_config();
+        assert!(!ufs.read_flag(UfsDeviceConfig::FLAG_DEVICE_INIT).unwrap());
+        ufs.write_flag(UfsDeviceConfig::FLAG_DEVICE_INIT, true).unwrap();
+        assert!(ufs.read_flag(UfsDeviceConfig::FLAG_DEVICE_INIT).unwrap());
+    }
+}
@@ -0,0 +1,691 @@
+//! PCI I/O protocol.
+//!
+//! [`PciIo`] gives a driver access to the PCI controller it manages: its configuration space, the memory and I/O
+//! ranges of its BARs, and the DMA of its bus master transfers through a [`Mapping`]:
+//!
+//! ```ignore
+//! let mut pci_io = PciIo::get(boot_services, controller)?;
+//! let vendor_id: u16 = pci_io.read_config(0)?;
+//! pci_io.enable_attributes(PciAttribute::MEMORY | PciAttribute::BUS_MASTER)?;
+//! let status: u32 = pci_io.read_mem(0, STATUS_REGISTER)?;
+//! let mapping = pci_io.map(DmaOperation::BusMasterWrite, &mut buffer)?;
+//! pci_io.write_mem(0, DMA_ADDRESS_REGISTER, mapping.device_address())?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 14.4. EFI PCI I/O Protocol](https://uefi.org/specs/UEFI/2.10/14_Protocols_PCI_Bus_Support.html#efi-pci-i-o-protocol)
+
+use core::{
+    ffi::c_void,
+    fmt,
+    marker::PhantomData,
+    ops::{BitOr, BitOrAssign},
+    ptr,
+};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use efi::protocols::pci_io;
+
+type PciIoProtocol = pci_io::Protocol;
+
+/// A value transferred by the protocol in a single access, implemented for `u8`, `u16`, `u32` and `u64`.
+///
+/// # Safety
+///
+/// The width must be the size of the value.
+pub unsafe trait PciValue: Copy + Default {
+    const WIDTH: pci_io::Width;
+}
+
+unsafe impl PciValue for u8 {
+    const WIDTH: pci_io::Width = pci_io::WIDTH_UINT8;
+}
+
+unsafe impl PciValue for u16 {
+    const WIDTH: pci_io::Width = pci_io::WIDTH_UINT16;
+}
+
+unsafe impl PciValue for u32 {
+    const WIDTH: pci_io::Width = pci_io::WIDTH_UINT32;
+}
+
+unsafe impl PciValue for u64 {
+    const WIDTH: pci_io::Width = pci_io::WIDTH_UINT64;
+}
+
+/// Address of a PCI controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PciLocation {
+    pub segment: usize,
+    pub bus: usize,
+    pub device: usize,
+    pub function: usize,
+}
+
+impl fmt::Display for PciLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}:{:02x}:{:02x}.{:x}", self.segment, self.bus, self.device, self.function)
+    }
+}
+
+/// Attributes of a PCI controller, the resources it decodes and the way it is accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PciAttribute(pub u64);
+
+impl PciAttribute {
+    pub const ISA_MOTHERBOARD_IO: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_ISA_MOTHERBOARD_IO);
+    pub const ISA_IO: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_ISA_IO);
+    pub const VGA_PALETTE_IO: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_VGA_PALETTE_IO);
+    pub const VGA_MEMORY: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_VGA_MEMORY);
+    pub const VGA_IO: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_VGA_IO);
+    pub const IDE_PRIMARY_IO: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_IDE_PRIMARY_IO);
+    pub const IDE_SECONDARY_IO: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_IDE_SECONDARY_IO);
+    pub const MEMORY_WRITE_COMBINE: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_MEMORY_WRITE_COMBINE);
+    /// The controller decodes its I/O ranges.
+    pub const IO: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_IO);
+    /// The controller decodes its memory ranges.
+    pub const MEMORY: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_MEMORY);
+    /// The controller can do DMA.
+    pub const BUS_MASTER: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_BUS_MASTER);
+    pub const MEMORY_CACHED: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_MEMORY_CACHED);
+    pub const MEMORY_DISABLE: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_MEMORY_DISABLE);
+    pub const EMBEDDED_DEVICE: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_EMBEDDED_DEVICE);
+    pub const EMBEDDED_ROM: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_EMBEDDED_ROM);
+    /// The controller can address memory above 4 GiB.
+    pub const DUAL_ADDRESS_CYCLE: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_DUAL_ADDRESS_CYCLE);
+    pub const ISA_IO_16: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_ISA_IO_16);
+    pub const VGA_PALETTE_IO_16: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_VGA_PALETTE_IO_16);
+    pub const VGA_IO_16: PciAttribute = PciAttribute(pci_io::ATTRIBUTE_VGA_IO_16);
+
+    pub const fn contains(&self, attributes: PciAttribute) -> bool {
+        self.0 & attributes.0 == attributes.0
+    }
+}
+
+impl BitOr for PciAttribute {
+    type Output = PciAttribute;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        PciAttribute(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PciAttribute {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0
+    }
+}
+
+/// Direction of a DMA transfer of a bus master.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum DmaOperation {
+    /// The controller reads the buffer.
+    BusMasterRead = pci_io::OPERATION_BUS_MASTER_READ,
+    /// The controller writes the buffer.
+    BusMasterWrite = pci_io::OPERATION_BUS_MASTER_WRITE,
+}
+
+/// Typed access to an instance of the PCI I/O protocol, the protocol of a PCI controller.
+pub struct PciIo(&'static mut PciIoProtocol);
+
+impl PciIo {
+    /// Gets the instance of the protocol installed on the handle of a controller.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::PciIo).map(Self)
+    }
+
+    fn this(&self) -> *mut PciIoProtocol {
+        self.0 as *const PciIoProtocol as *mut PciIoProtocol
+    }
+
+    /// The address of the controller, from `GetLocation`.
+    pub fn location(&self) -> Result<PciLocation, EfiError> {
+        let mut location = PciLocation { segment: 0, bus: 0, device: 0, function: 0 };
+        match unsafe {
+            r_efi::efi_call(
+                self.0.get_location,
+                (self.this(), &mut location.segment, &mut location.bus, &mut location.device, &mut location.function),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(location),
+        }
+    }
+
+    /// Reads a value at an offset of the configuration space.
+    pub fn read_config<T: PciValue>(&self, offset: u32) -> Result<T, EfiError> {
+        let mut value = T::default();
+        match unsafe {
+            r_efi::efi_call(
+                self.0.pci.read,
+                (self.this(), T::WIDTH, offset, 1, ptr::addr_of_mut!(value) as *mut c_void),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(value),
+        }
+    }
+
+    /// Writes a value at an offset of the configuration space.
+    pub fn write_config<T: PciValue>(&mut self, offset: u32, value: T) -> Result<(), EfiError> {
+        let mut value = value;
+        match unsafe {
+            r_efi::efi_call(
+                self.0.pci.write,
+                (self.this(), T::WIDTH, offset, 1, ptr::addr_of_mut!(value) as *mut c_void),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the bytes of the configuration space from an offset.
+    pub fn read_config_bytes(&self, offset: u32, buffer: &mut [u8]) -> Result<(), EfiError> {
+        let (width, count, buffer) = (pci_io::WIDTH_UINT8, buffer.len(), buffer.as_mut_ptr() as *mut c_void);
+        match unsafe { r_efi::efi_call(self.0.pci.read, (self.this(), width, offset, count, buffer)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads a value at an offset of the memory range of a BAR.
+    pub fn read_mem<T: PciValue>(&self, bar: u8, offset: u64) -> Result<T, EfiError> {
+        let mut value = T::default();
+        let buffer = ptr::addr_of_mut!(value) as *mut c_void;
+        match unsafe { r_efi::efi_call(self.0.mem.read, (self.this(), T::WIDTH, bar, offset, 1, buffer)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(value),
+        }
+    }
+
+    /// Writes a value at an offset of the memory range of a BAR.
+    pub fn write_mem<T: PciValue>(&mut self, bar: u8, offset: u64, value: T) -> Result<(), EfiError> {
+        let mut value = value;
+        let buffer = ptr::addr_of_mut!(value) as *mut c_void;
+        match unsafe { r_efi::efi_call(self.0.mem.write, (self.this(), T::WIDTH, bar, offset, 1, buffer)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads a value at an offset of the I/O range of a BAR.
+    pub fn read_io<T: PciValue>(&self, bar: u8, offset: u64) -> Result<T, EfiError> {
+        let mut value = T::default();
+        let buffer = ptr::addr_of_mut!(value) as *mut c_void;
+        match unsafe { r_efi::efi_call(self.0.io.read, (self.this(), T::WIDTH, bar, offset, 1, buffer)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(value),
+        }
+    }
+
+    /// Writes a value at an offset of the I/O range of a BAR.
+    pub fn write_io<T: PciValue>(&mut self, bar: u8, offset: u64, value: T) -> Result<(), EfiError> {
+        let mut value = value;
+        let buffer = ptr::addr_of_mut!(value) as *mut c_void;
+        match unsafe { r_efi::efi_call(self.0.io.write, (self.this(), T::WIDTH, bar, offset, 1, buffer)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Maps a buffer for a DMA transfer of the controller, until the mapping is dropped.
+    ///
+    /// The mapping may only cover the start of the buffer, see [`Mapping::len`]. The buffer is borrowed while mapped,
+    /// its content is only valid for the processor once unmapped.
+    pub fn map<'a>(&self, operation: DmaOperation, buffer: &'a mut [u8]) -> Result<Mapping<'a>, EfiError> {
+        let mut len = buffer.len();
+        let mut device_address = 0;
+        let mut token = ptr::null_mut();
+        match unsafe {
+            r_efi::efi_call(
+                self.0.map,
+                (
+                    self.this(),
+                    operation as pci_io::Operation,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    &mut len,
+                    &mut device_address,
+                    &mut token,
+                ),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(Mapping { protocol: self.this(), token, device_address, len, buffer: PhantomData }),
+        }
+    }
+
+    /// Waits for the posted writes of the controller to reach memory.
+    pub fn flush(&self) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.0.flush, (self.this(),)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn attributes_operation(
+        &self,
+        operation: pci_io::AttributeOperation,
+        attributes: PciAttribute,
+    ) -> Result<PciAttribute, EfiError> {
+        let mut result = 0;
+        match unsafe { r_efi::efi_call(self.0.attributes, (self.this(), operation, attributes.0, &mut result)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(PciAttribute(result)),
+        }
+    }
+
+    /// The current attributes of the controller.
+    pub fn attributes(&self) -> Result<PciAttribute, EfiError> {
+        self.attributes_operation(pci_io::ATTRIBUTE_OPERATION_GET, PciAttribute(0))
+    }
+
+    /// The attributes the controller supports.
+    pub fn supported_attributes(&self) -> Result<PciAttribute, EfiError> {
+        self.attributes_operation(pci_io::ATTRIBUTE_OPERATION_SUPPORTED, PciAttribute(0))
+    }
+
+    /// Replaces the attributes of the controller.
+    pub fn set_attributes(&mut self, attributes: PciAttribute) -> Result<(), EfiError> {
+        self.attributes_operation(pci_io::ATTRIBUTE_OPERATION_SET, attributes).map(|_| ())
+    }
+
+    /// Adds attributes to the controller, such as the decoding of its ranges.
+    pub fn enable_attributes(&mut self, attributes: PciAttribute) -> Result<(), EfiError> {
+        self.attributes_operation(pci_io::ATTRIBUTE_OPERATION_ENABLE, attributes).map(|_| ())
+    }
+
+    /// Removes attributes from the controller.
+    pub fn disable_attributes(&mut self, attributes: PciAttribute) -> Result<(), EfiError> {
+        self.attributes_operation(pci_io::ATTRIBUTE_OPERATION_DISABLE, attributes).map(|_| ())
+    }
+}
+
+impl From<&'static mut PciIoProtocol> for PciIo {
+    fn from(protocol: &'static mut PciIoProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for PciIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PciIo").field(&(self.0 as *const PciIoProtocol)).finish()
+    }
+}
+
+/// A buffer mapped for a DMA transfer by [`PciIo::map`], unmapped when dropped.
+pub struct Mapping<'a> {
+    protocol: *mut PciIoProtocol,
+    token: *mut c_void,
+    device_address: efi::PhysicalAddress,
+    len: usize,
+    buffer: PhantomData<&'a mut [u8]>,
+}
+
+impl Mapping<'_> {
+    /// The address of the buffer for the controller.
+    pub fn device_address(&self) -> efi::PhysicalAddress {
+        self.device_address
+    }
+
+    /// The number of bytes mapped from the start of the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Unmaps the buffer, completing the transfer.
+    pub fn unmap(self) -> Result<(), EfiError> {
+        let mapping = core::mem::ManuallyDrop::new(self);
+        //SAFETY: The protocol outlives the mappings of its controller.
+        match unsafe { ((*mapping.protocol).unmap)(mapping.protocol, mapping.token) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for Mapping<'_> {
+    fn drop(&mut self) {
+        //SAFETY: The protocol outlives the mappings of its controller.
+        let _ = unsafe { ((*self.protocol).unmap)(self.protocol, self.token) };
+    }
+}
+
+impl fmt::Debug for Mapping<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mapping").field("device_address", &self.device_address).field("len", &self.len).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec::Vec};
+    use core::{cell::RefCell, slice};
+
+    /// Controller with a configuration space, a memory BAR 0 and an I/O BAR 1, mapping buffers at their address
+    /// plus `DMA_OFFSET` up to `MAX_MAPPING` bytes.
+    #[repr(C)]
+    struct TestPciIo {
+        protocol: PciIoProtocol,
+        config: RefCell<[u8; 64]>,
+        mem: RefCell<[u8; 16]>,
+        io: RefCell<[u8; 16]>,
+        attributes: RefCell<u64>,
+        mappings: RefCell<Vec<(u32, usize)>>,
+    }
+
+    const DMA_OFFSET: u64 = 0x1000_0000;
+    const MAX_MAPPING: usize = 8;
+    const SUPPORTED: u64 = pci_io::ATTRIBUTE_IO | pci_io::ATTRIBUTE_MEMORY | pci_io::ATTRIBUTE_BUS_MASTER;
+
+    fn test_pci_io<'a>(this: *mut PciIoProtocol) -> &'a TestPciIo {
+        unsafe { &*(this as *const TestPciIo) }
+    }
+
+    fn access(space: &mut [u8], width: pci_io::Width, offset: usize, count: usize) -> Option<&mut [u8]> {
+        let size = 1 << width;
+        space.get_mut(offset..offset + size * count)
+    }
+
+    fn io_mem(
+        space: &RefCell<[u8; 16]>,
+        write: bool,
+        width: u32,
+        offset: u64,
+        count: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let mut space = space.borrow_mut();
+        let Some(range) = access(&mut *space, width, offset as usize, count) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let buffer = unsafe { slice::from_raw_parts_mut(buffer as *mut u8, range.len()) };
+        match write {
+            true => range.copy_from_slice(buffer),
+            false => buffer.copy_from_slice(range),
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn mem_read(
+        this: *mut PciIoProtocol,
+        w: u32,
+        bar: u8,
+        o: u64,
+        c: usize,
+        b: *mut c_void,
+    ) -> efi::Status {
+        match bar {
+            0 => io_mem(&test_pci_io(this).mem, false, w, o, c, b),
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    extern "efiapi" fn mem_write(
+        this: *mut PciIoProtocol,
+        w: u32,
+        bar: u8,
+        o: u64,
+        c: usize,
+        b: *mut c_void,
+    ) -> efi::Status {
+        match bar {
+            0 => io_mem(&test_pci_io(this).mem, true, w, o, c, b),
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    extern "efiapi" fn io_read(
+        this: *mut PciIoProtocol,
+        w: u32,
+        bar: u8,
+        o: u64,
+        c: usize,
+        b: *mut c_void,
+    ) -> efi::Status {
+        match bar {
+            1 => io_mem(&test_pci_io(this).io, false, w, o, c, b),
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    extern "efiapi" fn io_write(
+        this: *mut PciIoProtocol,
+        w: u32,
+        bar: u8,
+        o: u64,
+        c: usize,
+        b: *mut c_void,
+    ) -> efi::Status {
+        match bar {
+            1 => io_mem(&test_pci_io(this).io, true, w, o, c, b),
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    fn config(
+        this: *mut PciIoProtocol,
+        write: bool,
+        width: u32,
+        offset: u32,
+        count: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let mut space = test_pci_io(this).config.borrow_mut();
+        let Some(range) = access(&mut *space, width, offset as usize, count) else {
+            return efi::Status::UNSUPPORTED;
+        };
+        let buffer = unsafe { slice::from_raw_parts_mut(buffer as *mut u8, range.len()) };
+        match write {
+            true => range.copy_from_slice(buffer),
+            false => buffer.copy_from_slice(range),
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn config_read(this: *mut PciIoProtocol, w: u32, o: u32, c: usize, b: *mut c_void) -> efi::Status {
+        config(this, false, w, o, c, b)
+    }
+
+    extern "efiapi" fn config_write(this: *mut PciIoProtocol, w: u32, o: u32, c: usize, b: *mut c_void) -> efi::Status {
+        config(this, true, w, o, c, b)
+    }
+
+    extern "efiapi" fn map(
+        this: *mut PciIoProtocol,
+        operation: pci_io::Operation,
+        host_address: *mut c_void,
+        len: *mut usize,
+        device_address: *mut efi::PhysicalAddress,
+        token: *mut *mut c_void,
+    ) -> efi::Status {
+        let mapped = unsafe { *len }.min(MAX_MAPPING);
+        let mut mappings = test_pci_io(this).mappings.borrow_mut();
+        mappings.push((operation, mapped));
+        unsafe {
+            len.write(mapped);
+            device_address.write(host_address as u64 + DMA_OFFSET);
+            token.write(mappings.len() as *mut c_void);
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn unmap(this: *mut PciIoProtocol, token: *mut c_void) -> efi::Status {
+        match test_pci_io(this).mappings.borrow_mut().get_mut(token as usize - 1) {
+            Some(mapping) if mapping.1 != 0 => {
+                mapping.1 = 0;
+                efi::Status::SUCCESS
+            }
+            _ => efi::Status::INVALID_PARAMETER,
+        }
+    }
+
+    extern "efiapi" fn get_location(
+        _: *mut PciIoProtocol,
+        segment: *mut usize,
+        bus: *mut usize,
+        device: *mut usize,
+        function: *mut usize,
+    ) -> efi::Status {
+        unsafe {
+            segment.write(0);
+            bus.write(3);
+            device.write(0x1c);
+            function.write(2);
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn attributes(
+        this: *mut PciIoProtocol,
+        operation: pci_io::AttributeOperation,
+        attributes: u64,
+        result: *mut u64,
+    ) -> efi::Status {
+        let mut current = test_pci_io(this).attributes.borrow_mut();
+        if attributes & !SUPPORTED != 0 {
+            return efi::Status::UNSUPPORTED;
+        }
+        match operation {
+            pci_io::ATTRIBUTE_OPERATION_GET => unsafe { result.write(*current) },
+            pci_io::ATTRIBUTE_OPERATION_SUPPORTED => unsafe { result.write(SUPPORTED) },
+            pci_io::ATTRIBUTE_OPERATION_SET => *current = attributes,
+            pci_io::ATTRIBUTE_OPERATION_ENABLE => *current |= attributes,
+            pci_io::ATTRIBUTE_OPERATION_DISABLE => *current &= !attributes,
+            _ => return efi::Status::INVALID_PARAMETER,
+        }
+        efi::Status::SUCCESS
+    }
+
+    macro_rules! unused {
+        ($($name:ident($($arg:ty),*);)*) => {
+            $(extern "efiapi" fn $name(_: *mut PciIoProtocol, $(_: $arg),*) -> efi::Status {
+                unimplemented!()
+            })*
+        };
+    }
+
+    unused! {
+        poll_mem(u32, u8, u64, u64, u64, u64, *mut u64);
+        poll_io(u32, u8, u64, u64, u64, u64, *mut u64);
+        copy_mem(u32, u8, u64, u8, u64, usize);
+        allocate_buffer(efi::AllocateType, efi::MemoryType, usize, *mut *mut c_void, u64);
+        free_buffer(usize, *mut c_void);
+        flush();
+        get_bar_attributes(u8, *mut u64, *mut *mut c_void);
+        set_bar_attributes(u64, u8, *mut u64, *mut u64);
+    }
+
+    fn pci_io() -> (PciIo, &'static TestPciIo) {
+        let pci_io = Box::leak(Box::new(TestPciIo {
+            protocol: PciIoProtocol {
+                poll_mem,
+                poll_io,
+                mem: pci_io::Access { read: mem_read, write: mem_write },
+                io: pci_io::Access { read: io_read, write: io_write },
+                pci: pci_io::ConfigAccess { read: config_read, write: config_write },
+                copy_mem,
+                map,
+                unmap,
+                allocate_buffer,
+                free_buffer,
+                flush,
+                get_location,
+                attributes,
+                get_bar_attributes,
+                set_bar_attributes,
+                rom_size: 0,
+                rom_image: ptr::null_mut(),
+            },
+            config: RefCell::new([0; 64]),
+            mem: RefCell::new([0; 16]),
+            io: RefCell::new([0; 16]),
+            attributes: RefCell::new(0),
+            mappings: RefCell::new(Vec::new()),
+        }));
+        let test_pci_io = test_pci_io(&mut pci_io.protocol);
+        (PciIo::from(&mut pci_io.protocol), test_pci_io)
+    }
+
+    #[test]
+    fn test_config_space() {
+        let (mut pci_io, test_pci_io) = pci_io();
+        test_pci_io.config.borrow_mut()[..4].copy_from_slice(&[0x86, 0x80, 0x34, 0x12]);
+
+        assert_eq!(Ok(0x8086u16), pci_io.read_config(0));
+        assert_eq!(Ok(0x1234u16), pci_io.read_config(2));
+        assert_eq!(Ok(0x12348086u32), pci_io.read_config(0));
+        pci_io.write_config(4, 0x0406u16).unwrap();
+        pci_io.write_config(0x3c, 0x0bu8).unwrap();
+        assert_eq!([0x06, 0x04], test_pci_io.config.borrow()[4..6]);
+        assert_eq!(0x0b, test_pci_io.config.borrow()[0x3c]);
+
+        let mut header = [0; 6];
+        pci_io.read_config_bytes(0, &mut header).unwrap();
+        assert_eq!([0x86, 0x80, 0x34, 0x12, 0x06, 0x04], header);
+        assert_eq!(Err(efi::Status::UNSUPPORTED.into()), pci_io.read_config::<u64>(60));
+    }
+
+    #[test]
+    fn test_bar_access() {
+        let (mut pci_io, test_pci_io) = pci_io();
+
+        pci_io.write_mem(0, 8, 0x1122334455667788u64).unwrap();
+        assert_eq!(Ok(0x55667788u32), pci_io.read_mem(0, 8));
+        assert_eq!(Ok(0x11u8), pci_io.read_mem(0, 15));
+        pci_io.write_io(1, 2, 0xabcdu16).unwrap();
+        assert_eq!([0xcd, 0xab], test_pci_io.io.borrow()[2..4]);
+        assert_eq!(Ok(0xabcdu16), pci_io.read_io(1, 2));
+
+        assert_eq!(Err(efi::Status::UNSUPPORTED.into()), pci_io.read_mem::<u8>(1, 0));
+        assert_eq!(Err(efi::Status::UNSUPPORTED.into()), pci_io.write_io(0, 0, 0u8));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), pci_io.read_mem::<u32>(0, 14));
+    }
+
+    #[test]
+    fn test_location_and_attributes() {
+        let (mut pci_io, _) = pci_io();
+        let location = pci_io.location().unwrap();
+        assert_eq!(PciLocation { segment: 0, bus: 3, device: 0x1c, function: 2 }, location);
+        assert_eq!("0000:03:1c.2", format!("{location}"));
+
+        assert_eq!(
+            Ok(PciAttribute::IO | PciAttribute::MEMORY | PciAttribute::BUS_MASTER),
+            pci_io.supported_attributes()
+        );
+        pci_io.enable_attributes(PciAttribute::MEMORY | PciAttribute::BUS_MASTER).unwrap();
+        pci_io.disable_attributes(PciAttribute::BUS_MASTER).unwrap();
+        assert_eq!(Ok(PciAttribute::MEMORY), pci_io.attributes());
+        pci_io.set_attributes(PciAttribute::IO).unwrap();
+        assert!(pci_io.attributes().unwrap().contains(PciAttribute::IO));
+        assert_eq!(Err(efi::Status::UNSUPPORTED.into()), pci_io.enable_attributes(PciAttribute::VGA_IO));
+    }
+
+    #[test]
+    fn test_dma_mapping() {
+        let (pci_io, test_pci_io) = pci_io();
+        let mut buffer = [0u8; 12];
+        let address = buffer.as_ptr() as u64;
+
+        let mapping = pci_io.map(DmaOperation::BusMasterWrite, &mut buffer).unwrap();
+        assert_eq!(address + DMA_OFFSET, mapping.device_address());
+        assert_eq!(MAX_MAPPING, mapping.len());
+        assert_eq!(vec![(pci_io::OPERATION_BUS_MASTER_WRITE, MAX_MAPPING)], *test_pci_io.mappings.borrow());
+        drop(mapping);
+        assert_eq!(vec![(pci_io::OPERATION_BUS_MASTER_WRITE, 0)], *test_pci_io.mappings.borrow());
+
+        let mapping = pci_io.map(DmaOperation::BusMasterRead, &mut buffer[8..]).unwrap();
+        assert_eq!(4, mapping.len());
+        mapping.unmap().unwrap();
+        assert_eq!((pci_io::OPERATION_BUS_MASTER_READ, 0), test_pci_io.mappings.borrow()[1]);
+    }
+}
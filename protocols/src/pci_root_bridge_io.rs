@@ -0,0 +1,525 @@
+//! PCI Root Bridge I/O protocol.
+//!
+//! [`PciRootBridgeIo`] reads the configuration space of the functions behind a root bridge, and enumerates them with
+//! [`PciRootBridgeIo::functions`], scanning the buses the root bridge decodes:
+//!
+//! ```ignore
+//! for root_bridge in PciRootBridgeIo::all(boot_services)? {
+//!     for function in root_bridge.functions() {
+//!         log::info!("{} {:04x}:{:04x}", function.location(), function.vendor_id, function.device_id);
+//!     }
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 14.2. PCI Root Bridge I/O Protocol](https://uefi.org/specs/UEFI/2.10/14_Protocols_PCI_Bus_Support.html#pci-root-bridge-i-o-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{
+    ffi::c_void,
+    fmt,
+    ops::{Deref, RangeInclusive},
+    ptr,
+};
+
+use boot_services::{
+    protocol_handler::{HandleBuffer, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::pci_io::{PciLocation, PciValue};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x2f707ebb, 0x4a1a, 0x11d4, 0x9a, 0x38, &[0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d]);
+
+/// Width of the accesses, the same as the widths of the PCI I/O protocol.
+pub type Width = u32;
+
+pub type ProtocolPollIoMem = extern "efiapi" fn(*mut Protocol, Width, u64, u64, u64, u64, *mut u64) -> efi::Status;
+
+pub type ProtocolIoMem = extern "efiapi" fn(*mut Protocol, Width, u64, usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolCopyMem = extern "efiapi" fn(*mut Protocol, Width, u64, u64, usize) -> efi::Status;
+
+pub type ProtocolMap = extern "efiapi" fn(
+    *mut Protocol,
+    u32,
+    *mut c_void,
+    *mut usize,
+    *mut efi::PhysicalAddress,
+    *mut *mut c_void,
+) -> efi::Status;
+
+pub type ProtocolUnmap = extern "efiapi" fn(*mut Protocol, *mut c_void) -> efi::Status;
+
+pub type ProtocolAllocateBuffer =
+    extern "efiapi" fn(*mut Protocol, efi::AllocateType, efi::MemoryType, usize, *mut *mut c_void, u64) -> efi::Status;
+
+pub type ProtocolFreeBuffer = extern "efiapi" fn(*mut Protocol, usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolFlush = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+pub type ProtocolGetAttributes = extern "efiapi" fn(*mut Protocol, *mut u64, *mut u64) -> efi::Status;
+
+pub type ProtocolSetAttributes = extern "efiapi" fn(*mut Protocol, u64, *mut u64, *mut u64) -> efi::Status;
+
+pub type ProtocolConfiguration = extern "efiapi" fn(*mut Protocol, *mut *mut c_void) -> efi::Status;
+
+/// FFI definition of `EFI_PCI_ROOT_BRIDGE_IO_PROTOCOL_ACCESS`.
+#[repr(C)]
+pub struct Access {
+    pub read: ProtocolIoMem,
+    pub write: ProtocolIoMem,
+}
+
+/// FFI definition of `EFI_PCI_ROOT_BRIDGE_IO_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub parent_handle: efi::Handle,
+    pub poll_mem: ProtocolPollIoMem,
+    pub poll_io: ProtocolPollIoMem,
+    pub mem: Access,
+    pub io: Access,
+    /// Accesses the configuration space, at an address from [`PciAddress::config_address`].
+    pub pci: Access,
+    pub copy_mem: ProtocolCopyMem,
+    pub map: ProtocolMap,
+    pub unmap: ProtocolUnmap,
+    pub allocate_buffer: ProtocolAllocateBuffer,
+    pub free_buffer: ProtocolFreeBuffer,
+    pub flush: ProtocolFlush,
+    pub get_attributes: ProtocolGetAttributes,
+    pub set_attributes: ProtocolSetAttributes,
+    /// Gets the ACPI resource descriptors of the resources the root bridge decodes.
+    pub configuration: ProtocolConfiguration,
+    pub segment_number: u32,
+}
+
+/// PCI Root Bridge I/O protocol.
+pub struct PciRootBridgeIoProtocol;
+
+unsafe impl ProtocolTrait for PciRootBridgeIoProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for PciRootBridgeIoProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Tag of the QWORD address space descriptor of the resources.
+const ACPI_ADDRESS_SPACE_DESCRIPTOR: u8 = 0x8a;
+/// Tag of the end of the resources.
+const ACPI_END_TAG_DESCRIPTOR: u8 = 0x79;
+/// Resource type of the bus numbers in an address space descriptor.
+const ACPI_ADDRESS_SPACE_TYPE_BUS: u8 = 2;
+
+/// Offset of the vendor ID in the configuration space, `0xffff` without a function.
+pub const VENDOR_ID_OFFSET: u32 = 0x00;
+/// Offset of the revision ID, followed by the 3 bytes of the class code.
+pub const REVISION_ID_OFFSET: u32 = 0x08;
+/// Offset of the header type, its bit 7 is set on the function 0 of multi-function devices.
+pub const HEADER_TYPE_OFFSET: u32 = 0x0e;
+
+const HEADER_TYPE_MULTI_FUNCTION: u8 = 0x80;
+const MAX_DEVICE: u8 = 31;
+const MAX_FUNCTION: u8 = 7;
+
+/// Address of a function behind a root bridge, in its segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PciAddress {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl PciAddress {
+    pub const fn new(bus: u8, device: u8, function: u8) -> Self {
+        Self { bus, device, function }
+    }
+
+    /// The `EFI_PCI_ADDRESS` of a register of the configuration space of the function, beyond the first 256 bytes
+    /// in the extended register.
+    pub const fn config_address(&self, offset: u32) -> u64 {
+        let address = (self.bus as u64) << 24 | (self.device as u64) << 16 | (self.function as u64) << 8;
+        match offset {
+            0..=0xff => address | offset as u64,
+            _ => address | (offset as u64) << 32,
+        }
+    }
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}:{:02x}.{:x}", self.bus, self.device, self.function)
+    }
+}
+
+/// Class of a function, from its configuration space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClassCode {
+    pub base_class: u8,
+    pub sub_class: u8,
+    pub programming_interface: u8,
+}
+
+/// A function found by [`PciRootBridgeIo::functions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PciFunction {
+    pub segment: u32,
+    pub address: PciAddress,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub revision_id: u8,
+    pub class_code: ClassCode,
+    /// The layout of the configuration space, without the multi-function bit.
+    pub header_type: u8,
+}
+
+impl PciFunction {
+    /// The location of the function, as given by the PCI I/O protocol of its controller.
+    pub fn location(&self) -> PciLocation {
+        PciLocation {
+            segment: self.segment as usize,
+            bus: self.address.bus as usize,
+            device: self.address.device as usize,
+            function: self.address.function as usize,
+        }
+    }
+}
+
+/// Typed access to an instance of the PCI Root Bridge I/O protocol.
+pub struct PciRootBridgeIo(&'static mut Protocol);
+
+impl PciRootBridgeIo {
+    /// Gets the instance of the protocol installed on the handle of a root bridge.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &PciRootBridgeIoProtocol).map(Self)
+    }
+
+    /// All the root bridges of the system.
+    pub fn all<B: BootServices>(boot_services: &B) -> Result<Vec<Self>, EfiError> {
+        let handles = HandleBuffer::supporting(boot_services, &PciRootBridgeIoProtocol)?;
+        handles.iter().map(|handle| Self::get(boot_services, handle)).collect()
+    }
+
+    fn this(&self) -> *mut Protocol {
+        self.0 as *const Protocol as *mut Protocol
+    }
+
+    /// The segment of the root bridge.
+    pub fn segment(&self) -> u32 {
+        self.0.segment_number
+    }
+
+    /// The bus numbers the root bridge decodes, from its resource descriptors or all of them if it has none.
+    pub fn bus_range(&self) -> Result<RangeInclusive<u8>, EfiError> {
+        let mut resources = ptr::null_mut();
+        match (self.0.configuration)(self.this(), &mut resources) {
+            s if s.is_error() => return Err(s.into()),
+            _ if resources.is_null() => return Ok(0..=u8::MAX),
+            _ => (),
+        }
+        let mut descriptor = resources as *const u8;
+        //SAFETY: The descriptors of the protocol are valid up to their end tag.
+        unsafe {
+            while *descriptor == ACPI_ADDRESS_SPACE_DESCRIPTOR {
+                let length = ptr::read_unaligned(descriptor.add(1) as *const u16) as usize;
+                if *descriptor.add(3) == ACPI_ADDRESS_SPACE_TYPE_BUS {
+                    let min = ptr::read_unaligned(descriptor.add(14) as *const u64);
+                    let len = ptr::read_unaligned(descriptor.add(38) as *const u64);
+                    let max = min.saturating_add(len.saturating_sub(1)).min(u8::MAX as u64);
+                    return match (u8::try_from(min), len) {
+                        (Ok(min), 1..) => Ok(min..=max as u8),
+                        _ => Err(efi::Status::DEVICE_ERROR.into()),
+                    };
+                }
+                descriptor = descriptor.add(3 + length);
+            }
+            match *descriptor {
+                ACPI_END_TAG_DESCRIPTOR => Ok(0..=u8::MAX),
+                _ => Err(efi::Status::DEVICE_ERROR.into()),
+            }
+        }
+    }
+
+    /// Reads a value at an offset of the configuration space of a function.
+    pub fn read_config<T: PciValue>(&self, address: PciAddress, offset: u32) -> Result<T, EfiError> {
+        let mut value = T::default();
+        let buffer = ptr::addr_of_mut!(value) as *mut c_void;
+        match (self.0.pci.read)(self.this(), T::WIDTH, address.config_address(offset), 1, buffer) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(value),
+        }
+    }
+
+    /// Writes a value at an offset of the configuration space of a function.
+    pub fn write_config<T: PciValue>(&mut self, address: PciAddress, offset: u32, value: T) -> Result<(), EfiError> {
+        let mut value = value;
+        let buffer = ptr::addr_of_mut!(value) as *mut c_void;
+        match (self.0.pci.write)(self.this(), T::WIDTH, address.config_address(offset), 1, buffer) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The function at an address, `None` if there is none.
+    pub fn function(&self, address: PciAddress) -> Result<Option<PciFunction>, EfiError> {
+        let ids: u32 = self.read_config(address, VENDOR_ID_OFFSET)?;
+        if ids as u16 == u16::MAX {
+            return Ok(None);
+        }
+        let [revision_id, programming_interface, sub_class, base_class] =
+            self.read_config::<u32>(address, REVISION_ID_OFFSET)?.to_le_bytes();
+        let header_type: u8 = self.read_config(address, HEADER_TYPE_OFFSET)?;
+        Ok(Some(PciFunction {
+            segment: self.segment(),
+            address,
+            vendor_id: ids as u16,
+            device_id: (ids >> 16) as u16,
+            revision_id,
+            class_code: ClassCode { base_class, sub_class, programming_interface },
+            header_type: header_type & !HEADER_TYPE_MULTI_FUNCTION,
+        }))
+    }
+
+    /// The functions of a device, all of them for a multi-function device or only its function 0.
+    fn device_functions(&self, bus: u8, device: u8) -> Vec<PciFunction> {
+        let Ok(Some(function)) = self.function(PciAddress::new(bus, device, 0)) else {
+            return Vec::new();
+        };
+        let mut functions = vec![function];
+        let header_type = self.read_config::<u8>(function.address, HEADER_TYPE_OFFSET);
+        if header_type.is_ok_and(|header_type| header_type & HEADER_TYPE_MULTI_FUNCTION != 0) {
+            functions.extend(
+                (1..=MAX_FUNCTION).filter_map(|f| self.function(PciAddress::new(bus, device, f)).ok().flatten()),
+            );
+        }
+        functions
+    }
+
+    /// Scans the buses of the root bridge for functions, in the order of their addresses.
+    ///
+    /// The functions whose configuration space cannot be read are skipped.
+    pub fn functions(&self) -> impl Iterator<Item = PciFunction> + '_ {
+        let buses = self.bus_range().unwrap_or(0..=u8::MAX);
+        buses.flat_map(move |bus| (0..=MAX_DEVICE).flat_map(move |device| self.device_functions(bus, device)))
+    }
+}
+
+impl From<&'static mut Protocol> for PciRootBridgeIo {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for PciRootBridgeIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PciRootBridgeIo").field("segment", &self.segment()).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, collections::BTreeMap};
+    use core::{cell::RefCell, slice};
+
+    /// Root bridge of segment 1 decoding the buses 0 and 1, with the configuration space of its functions.
+    #[repr(C)]
+    struct TestRootBridge {
+        protocol: Protocol,
+        functions: RefCell<BTreeMap<(u8, u8, u8), [u8; 0x100]>>,
+        resources: Vec<u8>,
+    }
+
+    fn test_root_bridge<'a>(this: *mut Protocol) -> &'a TestRootBridge {
+        unsafe { &*(this as *const TestRootBridge) }
+    }
+
+    fn config(
+        this: *mut Protocol,
+        write: bool,
+        width: Width,
+        address: u64,
+        count: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let [offset, function, device, bus, ..] = address.to_le_bytes();
+        let len = (1 << width) * count;
+        let buffer = unsafe { slice::from_raw_parts_mut(buffer as *mut u8, len) };
+        let mut functions = test_root_bridge(this).functions.borrow_mut();
+        let Some(space) = functions.get_mut(&(bus, device, function)) else {
+            // Reads of absent functions give all ones.
+            buffer.fill(0xff);
+            return efi::Status::SUCCESS;
+        };
+        let range = &mut space[offset as usize..offset as usize + len];
+        match write {
+            true => range.copy_from_slice(buffer),
+            false => buffer.copy_from_slice(range),
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn pci_read(this: *mut Protocol, w: Width, a: u64, c: usize, b: *mut c_void) -> efi::Status {
+        config(this, false, w, a, c, b)
+    }
+
+    extern "efiapi" fn pci_write(this: *mut Protocol, w: Width, a: u64, c: usize, b: *mut c_void) -> efi::Status {
+        config(this, true, w, a, c, b)
+    }
+
+    extern "efiapi" fn configuration(this: *mut Protocol, resources: *mut *mut c_void) -> efi::Status {
+        unsafe { resources.write(test_root_bridge(this).resources.as_ptr() as *mut c_void) };
+        efi::Status::SUCCESS
+    }
+
+    macro_rules! unused {
+        ($($name:ident($($arg:ty),*);)*) => {
+            $(extern "efiapi" fn $name(_: *mut Protocol, $(_: $arg),*) -> efi::Status {
+                unimplemented!()
+            })*
+        };
+    }
+
+    unused! {
+        poll_io_mem(Width, u64, u64, u64, u64, *mut u64);
+        io_mem(Width, u64, usize, *mut c_void);
+        copy_mem(Width, u64, u64, usize);
+        map(u32, *mut c_void, *mut usize, *mut efi::PhysicalAddress, *mut *mut c_void);
+        unmap(*mut c_void);
+        allocate_buffer(efi::AllocateType, efi::MemoryType, usize, *mut *mut c_void, u64);
+        free_buffer(usize, *mut c_void);
+        flush();
+        get_attributes(*mut u64, *mut u64);
+        set_attributes(u64, *mut u64, *mut u64);
+    }
+
+    /// An address space descriptor of a resource type, followed by the end tag.
+    fn resources(resource_type: u8, min: u64, len: u64) -> Vec<u8> {
+        let mut descriptor = vec![ACPI_ADDRESS_SPACE_DESCRIPTOR, 0x2b, 0x00, resource_type];
+        descriptor.resize(46, 0);
+        descriptor[14..22].copy_from_slice(&min.to_le_bytes());
+        descriptor[38..46].copy_from_slice(&len.to_le_bytes());
+        descriptor.extend([ACPI_END_TAG_DESCRIPTOR, 0]);
+        descriptor
+    }
+
+    /// The configuration space of a function.
+    fn space(vendor_id: u16, device_id: u16, class: [u8; 3], header_type: u8) -> [u8; 0x100] {
+        let mut space = [0; 0x100];
+        space[0..2].copy_from_slice(&vendor_id.to_le_bytes());
+        space[2..4].copy_from_slice(&device_id.to_le_bytes());
+        space[8] = 1;
+        space[9..12].copy_from_slice(&[class[2], class[1], class[0]]);
+        space[HEADER_TYPE_OFFSET as usize] = header_type;
+        space
+    }
+
+    fn root_bridge(resources: Vec<u8>) -> (PciRootBridgeIo, &'static TestRootBridge) {
+        let functions = BTreeMap::from([
+            ((0, 0, 0), space(0x8086, 0x0001, [0x06, 0x00, 0x00], 0x00)),
+            ((0, 0x1f, 0), space(0x8086, 0x0002, [0x06, 0x01, 0x00], 0x80)),
+            ((0, 0x1f, 3), space(0x8086, 0x0003, [0x04, 0x03, 0x00], 0x00)),
+            // Only reached if the device is multi-function.
+            ((0, 0x02, 1), space(0x1af4, 0x0004, [0x02, 0x00, 0x00], 0x00)),
+            ((0, 0x02, 0), space(0x1af4, 0x0005, [0x01, 0x08, 0x02], 0x00)),
+            ((1, 0x00, 0), space(0x1b36, 0x0006, [0x0c, 0x03, 0x30], 0x00)),
+            // Beyond the buses of the root bridge.
+            ((2, 0x00, 0), space(0x1b36, 0x0007, [0x03, 0x00, 0x00], 0x00)),
+        ]);
+        let root_bridge = Box::leak(Box::new(TestRootBridge {
+            protocol: Protocol {
+                parent_handle: ptr::null_mut(),
+                poll_mem: poll_io_mem,
+                poll_io: poll_io_mem,
+                mem: Access { read: io_mem, write: io_mem },
+                io: Access { read: io_mem, write: io_mem },
+                pci: Access { read: pci_read, write: pci_write },
+                copy_mem,
+                map,
+                unmap,
+                allocate_buffer,
+                free_buffer,
+                flush,
+                get_attributes,
+                set_attributes,
+                configuration,
+                segment_number: 1,
+            },
+            functions: RefCell::new(functions),
+            resources,
+        }));
+        let test_root_bridge = test_root_bridge(&mut root_bridge.protocol);
+        (PciRootBridgeIo::from(&mut root_bridge.protocol), test_root_bridge)
+    }
+
+    #[test]
+    fn test_config_address() {
+        assert_eq!(0x0312_1f04, PciAddress::new(0x03, 0x12, 0x1f).config_address(0x04));
+        assert_eq!(0x0000_0100_0002_0000, PciAddress::new(0, 2, 0).config_address(0x100));
+        assert_eq!("03:12.7", format!("{}", PciAddress::new(0x03, 0x12, 0x07)));
+    }
+
+    #[test]
+    fn test_bus_range() {
+        assert_eq!(Ok(0..=1), root_bridge(resources(ACPI_ADDRESS_SPACE_TYPE_BUS, 0, 2)).0.bus_range());
+        assert_eq!(Ok(0x80..=0xff), root_bridge(resources(ACPI_ADDRESS_SPACE_TYPE_BUS, 0x80, 0x100)).0.bus_range());
+        assert_eq!(Ok(0..=0xff), root_bridge(resources(0, 0x8000_0000, 0x1000)).0.bus_range());
+        assert_eq!(Ok(0..=0xff), root_bridge(vec![ACPI_END_TAG_DESCRIPTOR, 0]).0.bus_range());
+        assert_eq!(Err(efi::Status::DEVICE_ERROR.into()), root_bridge(vec![0x47]).0.bus_range());
+    }
+
+    #[test]
+    fn test_config_access() {
+        let (mut root_bridge, test_root_bridge) = root_bridge(resources(ACPI_ADDRESS_SPACE_TYPE_BUS, 0, 2));
+        let address = PciAddress::new(1, 0, 0);
+        assert_eq!(Ok(0x1b36u16), root_bridge.read_config(address, VENDOR_ID_OFFSET));
+        root_bridge.write_config(address, 0x04, 0x0006u16).unwrap();
+        assert_eq!([0x06, 0x00], test_root_bridge.functions.borrow()[&(1, 0, 0)][4..6]);
+        assert_eq!(Ok(None), root_bridge.function(PciAddress::new(1, 1, 0)));
+    }
+
+    #[test]
+    fn test_functions() {
+        let (root_bridge, _) = root_bridge(resources(ACPI_ADDRESS_SPACE_TYPE_BUS, 0, 2));
+        let functions = root_bridge.functions().collect::<Vec<_>>();
+        let addresses = functions.iter().map(|function| function.address).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                PciAddress::new(0, 0, 0),
+                PciAddress::new(0, 2, 0),
+                PciAddress::new(0, 0x1f, 0),
+                PciAddress::new(0, 0x1f, 3),
+                PciAddress::new(1, 0, 0)
+            ],
+            addresses
+        );
+
+        assert_eq!(
+            PciFunction {
+                segment: 1,
+                address: PciAddress::new(0, 0x1f, 0),
+                vendor_id: 0x8086,
+                device_id: 0x0002,
+                revision_id: 1,
+                class_code: ClassCode { base_class: 0x06, sub_class: 0x01, programming_interface: 0x00 },
+                header_type: 0,
+            },
+            functions[2]
+        );
+        assert_eq!(
+            ClassCode { base_class: 0x01, sub_class: 0x08, programming_interface: 0x02 },
+            functions[1].class_code
+        );
+        assert_eq!("0001:01:00.0", format!("{}", functions[4].location()));
+    }
+}
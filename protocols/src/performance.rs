@@ -0,0 +1,287 @@
+//! Performance Measurement protocol of EDK II, the producer of the firmware performance records.
+//!
+//! The records end up in the Firmware Basic Boot Performance Table (FBPT) published through the FPDT, next to the ones
+//! of the C modules using `PERF_START` and `PERF_END`. A [`PerfMeasurement`] ends the measurement it started when it
+//! is dropped:
+//!
+//! ```ignore
+//! let mut performance = Performance::locate(boot_services)?;
+//! {
+//!     let _measurement = performance.measure(image_handle, c"InitDevices")?;
+//!     init_devices()?;
+//! }
+//! ```
+//!
+//! The protocol is only installed when the performance measurements are enabled in the firmware.
+
+use core::{
+    ffi::{c_char, c_void, CStr},
+    fmt,
+    ops::Deref,
+    ptr,
+};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::arch::CycleCounter;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xc85d06be, 0x5f75, 0x48ce, 0xa8, 0x0f, &[0x12, 0x36, 0xba, 0x3b, 0x87, 0xb1]);
+
+/// Identifier of a record from its token, like `PERF_START` and `PERF_END`.
+pub const PERF_EVENT_ID: u32 = 0x00;
+pub const MODULE_START_ID: u32 = 0x01;
+pub const MODULE_END_ID: u32 = 0x02;
+pub const MODULE_LOADIMAGE_START_ID: u32 = 0x03;
+pub const MODULE_LOADIMAGE_END_ID: u32 = 0x04;
+pub const MODULE_DB_START_ID: u32 = 0x05;
+pub const MODULE_DB_END_ID: u32 = 0x06;
+pub const MODULE_DB_SUPPORT_START_ID: u32 = 0x07;
+pub const MODULE_DB_SUPPORT_END_ID: u32 = 0x08;
+pub const MODULE_DB_STOP_START_ID: u32 = 0x09;
+pub const MODULE_DB_STOP_END_ID: u32 = 0x0A;
+pub const PERF_EVENTSIGNAL_START_ID: u32 = 0x10;
+pub const PERF_EVENTSIGNAL_END_ID: u32 = 0x11;
+pub const PERF_CALLBACK_START_ID: u32 = 0x20;
+pub const PERF_CALLBACK_END_ID: u32 = 0x21;
+pub const PERF_FUNCTION_START_ID: u32 = 0x30;
+pub const PERF_FUNCTION_END_ID: u32 = 0x31;
+pub const PERF_INMODULE_START_ID: u32 = 0x40;
+pub const PERF_INMODULE_END_ID: u32 = 0x41;
+pub const PERF_CROSSMODULE_START_ID: u32 = 0x50;
+pub const PERF_CROSSMODULE_END_ID: u32 = 0x51;
+
+/// FFI definition of `PERF_MEASUREMENT_ATTRIBUTE`.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    /// Start of a measurement.
+    Start = 0,
+    /// End of a measurement.
+    End = 1,
+    /// A single record, like an event.
+    Entry = 2,
+}
+
+pub type ProtocolCreatePerformanceMeasurement =
+    extern "efiapi" fn(*const c_void, *const c_void, *const c_char, u64, u64, u32, Attribute) -> efi::Status;
+
+/// FFI definition of `EDKII_PERFORMANCE_MEASUREMENT_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub create_performance_measurement: ProtocolCreatePerformanceMeasurement,
+}
+
+/// Performance Measurement protocol.
+pub struct PerformanceMeasurementProtocol;
+
+unsafe impl ProtocolTrait for PerformanceMeasurementProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for PerformanceMeasurementProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// A performance record.
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'a> {
+    /// The image or controller handle, or the address of the function, the record is for.
+    pub caller: *const c_void,
+    /// The GUID of the module or of the event, if any.
+    pub guid: Option<&'a efi::Guid>,
+    /// The token of the record, or the name of the function.
+    pub string: Option<&'a CStr>,
+    /// The time of the record in ticks of the performance counter, `0` for now.
+    pub timestamp: u64,
+    /// The address of the module or of the function, if any.
+    pub address: u64,
+    /// The identifier of the record, [`PERF_EVENT_ID`] to derive it from the string like `PERF_START`.
+    pub identifier: u32,
+    pub attribute: Attribute,
+}
+
+/// Typed access to the Performance Measurement protocol.
+pub struct Performance(&'static mut Protocol);
+
+impl Performance {
+    /// Locates the instance of the protocol.
+    ///
+    /// Returns `NOT_FOUND` when the performance measurements are not enabled.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&PerformanceMeasurementProtocol, None).map(Self)
+    }
+
+    /// Adds a record to the performance table.
+    pub fn create(&mut self, record: &Record<'_>) -> Result<(), EfiError> {
+        match (self.0.create_performance_measurement)(
+            record.caller,
+            record.guid.map_or(ptr::null(), |guid| guid as *const efi::Guid as *const c_void),
+            record.string.map_or(ptr::null(), CStr::as_ptr),
+            record.timestamp,
+            record.address,
+            record.identifier,
+            record.attribute,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Starts the measurement of `token` by `handle`, like `PERF_START`.
+    pub fn start(&mut self, handle: efi::Handle, token: &CStr) -> Result<(), EfiError> {
+        self.create(&Record::token(handle, token, Attribute::Start))
+    }
+
+    /// Ends the measurement of `token` by `handle`, like `PERF_END`.
+    pub fn end(&mut self, handle: efi::Handle, token: &CStr) -> Result<(), EfiError> {
+        self.create(&Record::token(handle, token, Attribute::End))
+    }
+
+    /// Starts the measurement of `token` by `handle`, which ends when the returned guard is dropped.
+    pub fn measure<'a>(&'a mut self, handle: efi::Handle, token: &'a CStr) -> Result<PerfMeasurement<'a>, EfiError> {
+        self.start(handle, token)?;
+        Ok(PerfMeasurement { performance: Some(self), handle, token })
+    }
+}
+
+impl<'a> Record<'a> {
+    /// The record at the current value of `counter` rather than at the time it is added.
+    ///
+    /// The firmware takes its timestamps from `GetPerformanceCounter()`, which reads the
+    /// [`ProcessorCounter`](crate::arch::ProcessorCounter) on most platforms.
+    pub fn at<C: CycleCounter + ?Sized>(self, counter: &C) -> Self {
+        Self { timestamp: counter.ticks(), ..self }
+    }
+
+    /// A record of `token` by `handle` at the current time.
+    fn token(handle: efi::Handle, token: &'a CStr, attribute: Attribute) -> Self {
+        Self {
+            caller: handle as *const c_void,
+            guid: None,
+            string: Some(token),
+            timestamp: 0,
+            address: 0,
+            identifier: PERF_EVENT_ID,
+            attribute,
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for Performance {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for Performance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Performance").finish_non_exhaustive()
+    }
+}
+
+/// A measurement started by [`Performance::measure`], ended when dropped.
+pub struct PerfMeasurement<'a> {
+    /// The protocol, taken when the measurement ends.
+    performance: Option<&'a mut Performance>,
+    handle: efi::Handle,
+    token: &'a CStr,
+}
+
+impl PerfMeasurement<'_> {
+    /// Ends the measurement, returning the error the end record failed with.
+    pub fn end(mut self) -> Result<(), EfiError> {
+        match self.performance.take() {
+            Some(performance) => performance.end(self.handle, self.token),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for PerfMeasurement<'_> {
+    fn drop(&mut self) {
+        if let Some(performance) = self.performance.take() {
+            // Nothing can report the failure from a drop.
+            let _ = performance.end(self.handle, self.token);
+        }
+    }
+}
+
+impl fmt::Debug for PerfMeasurement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PerfMeasurement").field("handle", &self.handle).field("token", &self.token).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use std::{boxed::Box, string::String, sync::Mutex, vec::Vec};
+
+    static RECORDS: Mutex<Vec<(usize, String, u32, Attribute)>> = Mutex::new(Vec::new());
+
+    extern "efiapi" fn create_performance_measurement(
+        caller: *const c_void,
+        _guid: *const c_void,
+        string: *const c_char,
+        timestamp: u64,
+        _address: u64,
+        identifier: u32,
+        attribute: Attribute,
+    ) -> efi::Status {
+        assert_eq!(0, timestamp);
+        let string = unsafe { CStr::from_ptr(string) }.to_str().unwrap();
+        if string == "Full" {
+            return efi::Status::OUT_OF_RESOURCES;
+        }
+        RECORDS.lock().unwrap().push((caller as usize, String::from(string), identifier, attribute));
+        efi::Status::SUCCESS
+    }
+
+    fn performance() -> Performance {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<PerformanceMeasurementProtocol, Protocol>()
+            .returning(|_, _| Ok(Box::leak(Box::new(Protocol { create_performance_measurement }))));
+        Performance::locate(&boot_services).unwrap()
+    }
+
+    #[test]
+    fn test_performance() {
+        let mut performance = performance();
+        let handle = 0x1000 as efi::Handle;
+        performance.start(handle, c"Start").unwrap();
+        {
+            let _measurement = performance.measure(handle, c"Scoped").unwrap();
+        }
+        performance.measure(handle, c"Ended").unwrap().end().unwrap();
+        assert_eq!(efi::Status::OUT_OF_RESOURCES, performance.start(handle, c"Full").unwrap_err());
+        performance.end(handle, c"Start").unwrap();
+
+        let records = RECORDS.lock().unwrap();
+        let expected = [
+            ("Start", Attribute::Start),
+            ("Scoped", Attribute::Start),
+            ("Scoped", Attribute::End),
+            ("Ended", Attribute::Start),
+            ("Ended", Attribute::End),
+            ("Start", Attribute::End),
+        ];
+        assert_eq!(expected.len(), records.len());
+        for ((caller, string, identifier, attribute), (expected_string, expected_attribute)) in
+            records.iter().zip(expected)
+        {
+            assert_eq!((0x1000, PERF_EVENT_ID), (*caller, *identifier));
+            assert_eq!((expected_string, expected_attribute), (string.as_str(), *attribute));
+        }
+    }
+}
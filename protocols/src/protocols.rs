@@ -0,0 +1,50 @@
+//! Safe wrappers over UEFI protocols, for both producers and consumers.
+//!
+//! Protocols that are not defined in r-efi have their FFI definitions next to their wrapper.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod acpi_table;
+pub mod arch;
+pub mod component_name;
+pub mod cpu_arch;
+pub mod debug_port;
+pub mod decompress;
+pub mod deferred_image_load;
+pub mod driver_health;
+pub mod entropy;
+pub mod firmware_management;
+pub mod firmware_volume;
+pub mod firmware_volume_block;
+pub mod graphics_output;
+pub mod hii;
+pub mod hob;
+pub mod http;
+pub mod i2c_io;
+pub mod ip_config;
+pub mod loaded_image;
+pub mod media;
+pub mod memory_attribute;
+pub mod mm_communication;
+pub mod mp_services;
+pub mod pass_thru;
+pub mod pci_io;
+pub mod pci_root_bridge_io;
+pub mod performance;
+pub mod pxe_base_code;
+pub mod reset_notification;
+pub mod rng;
+pub mod security2;
+pub mod serial_io;
+pub mod service_binding;
+pub mod shell;
+pub mod smbios;
+pub mod spi;
+pub mod status_code;
+pub mod tcg2;
+pub mod tcp;
+pub mod timestamp;
+pub mod unicode_collation;
+pub mod usb_io;
+pub mod variable_lock;
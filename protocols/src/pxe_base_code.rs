@@ -0,0 +1,1216 @@
+//! PXE Base Code protocol.
+//!
+//! [`PxeBaseCode`] runs the DHCP and boot server discovery of a network boot and downloads files with TFTP. The
+//! packets received along the way are read with [`Packet::dhcpv4`] and [`Packet::dhcpv6`]:
+//!
+//! ```ignore
+//! let mut pxe = PxeBaseCode::get(&boot_services, nic_handle)?;
+//! pxe.start(false)?;
+//! let boot_file = pxe.dhcp(true)?.dhcpv4().boot_file().ok_or(efi::Status::NOT_FOUND)?.to_string();
+//! let server = pxe.boot_server().ok_or(efi::Status::NOT_FOUND)?;
+//! let image = pxe.tftp_read_file_with_progress(&boot_services, nic_handle, server, &boot_file, |progress| {
+//!     log::info!("{}/{}", progress.received, progress.total);
+//!     true
+//! })?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 24.3. PXE Base Code Protocol](https://uefi.org/specs/UEFI/2.10/24_Network_Protocols_SNP_PXE_BIS.html#pxe-base-code-protocol)
+
+use alloc::{boxed::Box, ffi::CString, vec, vec::Vec};
+use core::{
+    cell::Cell,
+    ffi::c_void,
+    fmt, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::Deref,
+    ptr,
+};
+
+use boot_services::{
+    protocol_handler::{InstalledProtocol, Protocol as ProtocolTrait},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x03c4e603, 0xac28, 0x11d3, 0x9a, 0x2d, &[0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d]);
+
+pub const CALLBACK_PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x245dca21, 0xfb7b, 0x11d3, 0x8f, 0x01, &[0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b]);
+
+pub const CALLBACK_PROTOCOL_REVISION: u64 = 0x00010000;
+
+pub const MAX_IPCNT: usize = 8;
+pub const MAX_ARP_ENTRIES: usize = 8;
+pub const MAX_ROUTE_ENTRIES: usize = 8;
+
+pub type UdpPort = u16;
+
+pub type ProtocolStart = extern "efiapi" fn(*mut Protocol, efi::Boolean) -> efi::Status;
+
+pub type ProtocolStop = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+pub type ProtocolDhcp = extern "efiapi" fn(*mut Protocol, efi::Boolean) -> efi::Status;
+
+pub type ProtocolDiscover =
+    extern "efiapi" fn(*mut Protocol, u16, *mut u16, efi::Boolean, *mut DiscoverInfo) -> efi::Status;
+
+pub type ProtocolMtftp = extern "efiapi" fn(
+    *mut Protocol,
+    TftpOpcode,
+    *mut c_void,
+    efi::Boolean,
+    *mut u64,
+    *mut usize,
+    *mut efi::IpAddress,
+    *mut u8,
+    *mut MtftpInfo,
+    efi::Boolean,
+) -> efi::Status;
+
+pub type ProtocolUdpWrite = extern "efiapi" fn(
+    *mut Protocol,
+    u16,
+    *mut efi::IpAddress,
+    *mut UdpPort,
+    *mut efi::IpAddress,
+    *mut efi::IpAddress,
+    *mut UdpPort,
+    *mut usize,
+    *mut c_void,
+    *mut usize,
+    *mut c_void,
+) -> efi::Status;
+
+pub type ProtocolUdpRead = extern "efiapi" fn(
+    *mut Protocol,
+    u16,
+    *mut efi::IpAddress,
+    *mut UdpPort,
+    *mut efi::IpAddress,
+    *mut UdpPort,
+    *mut usize,
+    *mut c_void,
+    *mut usize,
+    *mut c_void,
+) -> efi::Status;
+
+pub type ProtocolSetIpFilter = extern "efiapi" fn(*mut Protocol, *mut IpFilter) -> efi::Status;
+
+pub type ProtocolArp = extern "efiapi" fn(*mut Protocol, *mut efi::IpAddress, *mut efi::MacAddress) -> efi::Status;
+
+pub type ProtocolSetParameters = extern "efiapi" fn(
+    *mut Protocol,
+    *mut efi::Boolean,
+    *mut efi::Boolean,
+    *mut u8,
+    *mut u8,
+    *mut efi::Boolean,
+) -> efi::Status;
+
+pub type ProtocolSetStationIp =
+    extern "efiapi" fn(*mut Protocol, *mut efi::IpAddress, *mut efi::IpAddress) -> efi::Status;
+
+pub type ProtocolSetPackets = extern "efiapi" fn(
+    *mut Protocol,
+    *mut efi::Boolean,
+    *mut efi::Boolean,
+    *mut efi::Boolean,
+    *mut efi::Boolean,
+    *mut efi::Boolean,
+    *mut efi::Boolean,
+    *mut Packet,
+    *mut Packet,
+    *mut Packet,
+    *mut Packet,
+    *mut Packet,
+    *mut Packet,
+) -> efi::Status;
+
+/// FFI definition of `EFI_PXE_BASE_CODE_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub revision: u64,
+    pub start: ProtocolStart,
+    pub stop: ProtocolStop,
+    pub dhcp: ProtocolDhcp,
+    pub discover: ProtocolDiscover,
+    pub mtftp: ProtocolMtftp,
+    pub udp_write: ProtocolUdpWrite,
+    pub udp_read: ProtocolUdpRead,
+    pub set_ip_filter: ProtocolSetIpFilter,
+    pub arp: ProtocolArp,
+    pub set_parameters: ProtocolSetParameters,
+    pub set_station_ip: ProtocolSetStationIp,
+    pub set_packets: ProtocolSetPackets,
+    pub mode: *mut Mode,
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_PACKET`, a DHCPv4 or DHCPv6 packet.
+#[repr(C, align(4))]
+#[derive(Clone, Copy)]
+pub struct Packet {
+    pub raw: [u8; 1472],
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_IP_FILTER`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IpFilter {
+    pub filters: u8,
+    pub ip_cnt: u8,
+    pub reserved: u16,
+    pub ip_list: [efi::IpAddress; MAX_IPCNT],
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_ARP_ENTRY`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ArpEntry {
+    pub ip_addr: efi::IpAddress,
+    pub mac_addr: efi::MacAddress,
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_ROUTE_ENTRY`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RouteEntry {
+    pub ip_addr: efi::IpAddress,
+    pub subnet_mask: efi::IpAddress,
+    pub gw_addr: efi::IpAddress,
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_ICMP_ERROR`, the union of the rest of the header is kept as raw bytes.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IcmpError {
+    pub r#type: u8,
+    pub code: u8,
+    pub checksum: u16,
+    pub u: u32,
+    pub data: [u8; 494],
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_TFTP_ERROR`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TftpError {
+    pub error_code: u8,
+    pub error_string: [u8; 127],
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_MODE`.
+#[repr(C)]
+pub struct Mode {
+    pub started: efi::Boolean,
+    pub ipv6_available: efi::Boolean,
+    pub ipv6_supported: efi::Boolean,
+    pub using_ipv6: efi::Boolean,
+    pub bis_supported: efi::Boolean,
+    pub bis_detected: efi::Boolean,
+    pub auto_arp: efi::Boolean,
+    pub send_guid: efi::Boolean,
+    pub dhcp_discover_valid: efi::Boolean,
+    pub dhcp_ack_received: efi::Boolean,
+    pub proxy_offer_received: efi::Boolean,
+    pub pxe_discover_valid: efi::Boolean,
+    pub pxe_reply_received: efi::Boolean,
+    pub pxe_bis_reply_received: efi::Boolean,
+    pub icmp_error_received: efi::Boolean,
+    pub tftp_error_received: efi::Boolean,
+    pub make_callbacks: efi::Boolean,
+    pub ttl: u8,
+    pub tos: u8,
+    pub station_ip: efi::IpAddress,
+    pub subnet_mask: efi::IpAddress,
+    pub dhcp_discover: Packet,
+    pub dhcp_ack: Packet,
+    pub proxy_offer: Packet,
+    pub pxe_discover: Packet,
+    pub pxe_reply: Packet,
+    pub pxe_bis_reply: Packet,
+    pub ip_filter: IpFilter,
+    pub arp_cache_entries: u32,
+    pub arp_cache: [ArpEntry; MAX_ARP_ENTRIES],
+    pub route_table_entries: u32,
+    pub route_table: [RouteEntry; MAX_ROUTE_ENTRIES],
+    pub icmp_error: IcmpError,
+    pub tftp_error: TftpError,
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_SRVLIST`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ServerListEntry {
+    pub r#type: u16,
+    pub accept_any_response: efi::Boolean,
+    pub reserved: u8,
+    pub ip_addr: efi::IpAddress,
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_DISCOVER_INFO`, followed by `ip_cnt` server entries.
+#[repr(C)]
+pub struct DiscoverInfo {
+    pub use_m_cast: efi::Boolean,
+    pub use_b_cast: efi::Boolean,
+    pub use_u_cast: efi::Boolean,
+    pub must_use_list: efi::Boolean,
+    pub server_m_cast_ip: efi::IpAddress,
+    pub ip_cnt: u16,
+    pub srv_list: [ServerListEntry; 0],
+}
+
+/// FFI definition of `EFI_PXE_BASE_CODE_MTFTP_INFO`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MtftpInfo {
+    pub m_cast_ip: efi::IpAddress,
+    pub c_port: UdpPort,
+    pub s_port: UdpPort,
+    pub listen_timeout: u16,
+    pub transmit_timeout: u16,
+}
+
+/// Operation of [`Protocol::mtftp`], `EFI_PXE_BASE_CODE_TFTP_OPCODE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct TftpOpcode(pub u32);
+
+impl TftpOpcode {
+    pub const TFTP_GET_FILE_SIZE: TftpOpcode = TftpOpcode(1);
+    pub const TFTP_READ_FILE: TftpOpcode = TftpOpcode(2);
+    pub const TFTP_WRITE_FILE: TftpOpcode = TftpOpcode(3);
+    pub const TFTP_READ_DIRECTORY: TftpOpcode = TftpOpcode(4);
+    pub const MTFTP_GET_FILE_SIZE: TftpOpcode = TftpOpcode(5);
+    pub const MTFTP_READ_FILE: TftpOpcode = TftpOpcode(6);
+    pub const MTFTP_READ_DIRECTORY: TftpOpcode = TftpOpcode(7);
+}
+
+/// Function that calls the callback protocol, `EFI_PXE_BASE_CODE_FUNCTION`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Function(pub u32);
+
+impl Function {
+    pub const DHCP: Function = Function(1);
+    pub const DISCOVER: Function = Function(2);
+    pub const MTFTP: Function = Function(3);
+    pub const UDP_WRITE: Function = Function(4);
+    pub const UDP_READ: Function = Function(5);
+    pub const ARP: Function = Function(6);
+    pub const IGMP: Function = Function(7);
+}
+
+/// Result of the callback protocol, `EFI_PXE_BASE_CODE_CALLBACK_STATUS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct CallbackStatus(pub u32);
+
+impl CallbackStatus {
+    pub const CONTINUE: CallbackStatus = CallbackStatus(0);
+    pub const ABORT: CallbackStatus = CallbackStatus(1);
+}
+
+/// Boot server type of the PXE bootstrap server, `EFI_PXE_BASE_CODE_BOOT_TYPE_BOOTSTRAP`, for
+/// [`PxeBaseCode::discover`].
+pub const BOOT_TYPE_BOOTSTRAP: u16 = 0;
+
+pub type CallbackProtocolCallback =
+    extern "efiapi" fn(*mut CallbackProtocol, Function, efi::Boolean, u32, *mut Packet) -> CallbackStatus;
+
+/// FFI definition of `EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL`.
+#[repr(C)]
+pub struct CallbackProtocol {
+    pub revision: u64,
+    pub callback: CallbackProtocolCallback,
+}
+
+/// PXE Base Code protocol.
+pub struct PxeBaseCodeProtocol;
+
+unsafe impl ProtocolTrait for PxeBaseCodeProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for PxeBaseCodeProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// PXE Base Code Callback protocol with a [`ProgressCallback`] interface, used to produce the protocol.
+pub struct ProgressCallbackProducer;
+
+unsafe impl ProtocolTrait for ProgressCallbackProducer {
+    type Interface = ProgressCallback;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &CALLBACK_PROTOCOL_GUID
+    }
+}
+
+impl Deref for ProgressCallbackProducer {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// DHCP option of a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DhcpOption<'a> {
+    pub code: u16,
+    pub data: &'a [u8],
+}
+
+/// DHCPv4 option codes.
+pub mod dhcpv4 {
+    pub const SUBNET_MASK: u16 = 1;
+    pub const ROUTER: u16 = 3;
+    pub const DOMAIN_NAME_SERVER: u16 = 6;
+    pub const HOST_NAME: u16 = 12;
+    pub const MESSAGE_TYPE: u16 = 53;
+    pub const SERVER_IDENTIFIER: u16 = 54;
+    pub const VENDOR_CLASS_IDENTIFIER: u16 = 60;
+    pub const TFTP_SERVER_NAME: u16 = 66;
+    pub const BOOTFILE_NAME: u16 = 67;
+    pub const CLIENT_SYSTEM_ARCHITECTURE: u16 = 93;
+}
+
+/// DHCPv6 option codes.
+pub mod dhcpv6 {
+    pub const CLIENT_ID: u16 = 1;
+    pub const SERVER_ID: u16 = 2;
+    pub const DNS_SERVERS: u16 = 23;
+    pub const BOOTFILE_URL: u16 = 59;
+    pub const BOOTFILE_PARAM: u16 = 60;
+    pub const CLIENT_ARCH_TYPE: u16 = 61;
+}
+
+/// Offset of the options of a DHCPv4 packet, after the magic cookie.
+const DHCPV4_OPTIONS_OFFSET: usize = 240;
+const DHCPV4_MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+const DHCPV4_OPTION_PAD: u8 = 0;
+const DHCPV4_OPTION_END: u8 = 255;
+
+/// Offset of the options of a DHCPv6 packet, after the message type and transaction id.
+const DHCPV6_OPTIONS_OFFSET: usize = 4;
+
+impl Packet {
+    /// The packet as a DHCPv4 packet.
+    pub fn dhcpv4(&self) -> Dhcpv4Packet<'_> {
+        Dhcpv4Packet(&self.raw)
+    }
+
+    /// The packet as a DHCPv6 packet.
+    pub fn dhcpv6(&self) -> Dhcpv6Packet<'_> {
+        Dhcpv6Packet(&self.raw)
+    }
+}
+
+impl Default for Packet {
+    fn default() -> Self {
+        Self { raw: [0; 1472] }
+    }
+}
+
+impl fmt::Debug for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Packet").finish_non_exhaustive()
+    }
+}
+
+/// The null terminated string of a fixed size field, `None` if it is empty or not UTF-8.
+fn field_str(field: &[u8]) -> Option<&str> {
+    let length = field.iter().position(|c| *c == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..length]).ok().filter(|s| !s.is_empty())
+}
+
+/// A DHCPv4 packet, read from the BOOTP header and the DHCP options.
+#[derive(Debug, Clone, Copy)]
+pub struct Dhcpv4Packet<'a>(&'a [u8]);
+
+impl<'a> Dhcpv4Packet<'a> {
+    fn address(&self, offset: usize) -> Ipv4Addr {
+        Ipv4Addr::new(self.0[offset], self.0[offset + 1], self.0[offset + 2], self.0[offset + 3])
+    }
+
+    /// BOOTP operation, 1 for a request and 2 for a reply.
+    pub fn opcode(&self) -> u8 {
+        self.0[0]
+    }
+
+    pub fn transaction_id(&self) -> u32 {
+        u32::from_be_bytes([self.0[4], self.0[5], self.0[6], self.0[7]])
+    }
+
+    /// `ciaddr`, the address of the client when it already has one.
+    pub fn client_address(&self) -> Ipv4Addr {
+        self.address(12)
+    }
+
+    /// `yiaddr`, the address offered to the client.
+    pub fn your_address(&self) -> Ipv4Addr {
+        self.address(16)
+    }
+
+    /// `siaddr`, the address of the next server of the boot.
+    pub fn server_address(&self) -> Ipv4Addr {
+        self.address(20)
+    }
+
+    /// `giaddr`, the address of the relay agent.
+    pub fn gateway_address(&self) -> Ipv4Addr {
+        self.address(24)
+    }
+
+    /// `chaddr`, the hardware address of the client, of the length given by the header.
+    pub fn client_hardware_address(&self) -> &'a [u8] {
+        &self.0[28..28 + (self.0[2] as usize).min(16)]
+    }
+
+    /// `sname`, the name of the next server of the boot.
+    pub fn server_name(&self) -> Option<&'a str> {
+        field_str(&self.0[44..108])
+    }
+
+    /// The boot file, from its option or from the `file` field.
+    pub fn boot_file(&self) -> Option<&'a str> {
+        match self.option(dhcpv4::BOOTFILE_NAME) {
+            Some(name) => field_str(name),
+            None => field_str(&self.0[108..236]),
+        }
+    }
+
+    /// The DHCP message type, 2 for an offer and 5 for an acknowledgement.
+    pub fn message_type(&self) -> Option<u8> {
+        self.option(dhcpv4::MESSAGE_TYPE).and_then(|data| data.first().copied())
+    }
+
+    /// The options of the packet, empty without the DHCP magic cookie.
+    pub fn options(&self) -> Dhcpv4Options<'a> {
+        match self.0[DHCPV4_OPTIONS_OFFSET - 4..DHCPV4_OPTIONS_OFFSET] == DHCPV4_MAGIC_COOKIE {
+            true => Dhcpv4Options(&self.0[DHCPV4_OPTIONS_OFFSET..]),
+            false => Dhcpv4Options(&[]),
+        }
+    }
+
+    /// The data of the first option with the code.
+    pub fn option(&self, code: u16) -> Option<&'a [u8]> {
+        self.options().find(|option| option.code == code).map(|option| option.data)
+    }
+}
+
+/// Iterator over the options of a DHCPv4 packet.
+#[derive(Debug, Clone)]
+pub struct Dhcpv4Options<'a>(&'a [u8]);
+
+impl<'a> Iterator for Dhcpv4Options<'a> {
+    type Item = DhcpOption<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match *self.0 {
+                [DHCPV4_OPTION_PAD, ref rest @ ..] => self.0 = rest,
+                [code, length, ref rest @ ..] if code != DHCPV4_OPTION_END && length as usize <= rest.len() => {
+                    let (data, rest) = rest.split_at(length as usize);
+                    self.0 = rest;
+                    return Some(DhcpOption { code: code as u16, data });
+                }
+                _ => {
+                    self.0 = &[];
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// A DHCPv6 packet, read from its header and options.
+#[derive(Debug, Clone, Copy)]
+pub struct Dhcpv6Packet<'a>(&'a [u8]);
+
+impl<'a> Dhcpv6Packet<'a> {
+    /// The message type, 2 for an advertise and 7 for a reply.
+    pub fn message_type(&self) -> u8 {
+        self.0[0]
+    }
+
+    pub fn transaction_id(&self) -> u32 {
+        u32::from_be_bytes([0, self.0[1], self.0[2], self.0[3]])
+    }
+
+    /// The boot file URL, from its option.
+    pub fn boot_file_url(&self) -> Option<&'a str> {
+        self.option(dhcpv6::BOOTFILE_URL).and_then(|url| core::str::from_utf8(url).ok())
+    }
+
+    /// The options of the packet.
+    pub fn options(&self) -> Dhcpv6Options<'a> {
+        Dhcpv6Options(&self.0[DHCPV6_OPTIONS_OFFSET..])
+    }
+
+    /// The data of the first option with the code.
+    pub fn option(&self, code: u16) -> Option<&'a [u8]> {
+        self.options().find(|option| option.code == code).map(|option| option.data)
+    }
+}
+
+/// Iterator over the options of a DHCPv6 packet.
+#[derive(Debug, Clone)]
+pub struct Dhcpv6Options<'a>(&'a [u8]);
+
+impl<'a> Iterator for Dhcpv6Options<'a> {
+    type Item = DhcpOption<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match *self.0 {
+            // Code 0 is reserved, it is the zeroed end of the packet buffer.
+            [code_high, code_low, length_high, length_low, ref rest @ ..]
+                if (code_high, code_low) != (0, 0)
+                    && u16::from_be_bytes([length_high, length_low]) as usize <= rest.len() =>
+            {
+                let (data, rest) = rest.split_at(u16::from_be_bytes([length_high, length_low]) as usize);
+                self.0 = rest;
+                Some(DhcpOption { code: u16::from_be_bytes([code_high, code_low]), data })
+            }
+            _ => {
+                self.0 = &[];
+                None
+            }
+        }
+    }
+}
+
+/// Progress of a download, given to the progress callback of [`PxeBaseCode::tftp_read_file_with_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub received: u64,
+    pub total: u64,
+}
+
+/// TFTP opcode of the data packets.
+const TFTP_DATA_OPCODE: u16 = 3;
+/// Size of the header of the TFTP data packets, before the data.
+const TFTP_DATA_HEADER_SIZE: u32 = 4;
+
+type ProgressFn = dyn FnMut(Progress) -> bool;
+
+/// Implementation of the PXE Base Code Callback protocol that reports the progress of a download.
+#[repr(C)]
+pub struct ProgressCallback {
+    protocol: CallbackProtocol,
+    progress: Cell<Progress>,
+    /// The callback of the download in progress, only set for its duration.
+    callback: Cell<Option<*mut ProgressFn>>,
+}
+
+impl ProgressCallback {
+    fn new(total: u64) -> Self {
+        Self {
+            protocol: CallbackProtocol { revision: CALLBACK_PROTOCOL_REVISION, callback: Self::callback },
+            progress: Cell::new(Progress { received: 0, total }),
+            callback: Cell::new(None),
+        }
+    }
+
+    extern "efiapi" fn callback(
+        this: *mut CallbackProtocol,
+        function: Function,
+        received: efi::Boolean,
+        packet_length: u32,
+        packet: *mut Packet,
+    ) -> CallbackStatus {
+        if this.is_null() || packet.is_null() || function != Function::MTFTP || !bool::from(received) {
+            return CallbackStatus::CONTINUE;
+        }
+        //SAFETY: The protocol is the first field of the ProgressCallback that was installed.
+        let this = unsafe { &*(this as *const ProgressCallback) };
+        //SAFETY: The packet is valid for the duration of the call and holds at least the opcode of a TFTP packet.
+        let opcode = unsafe { u16::from_be_bytes([(*packet).raw[0], (*packet).raw[1]]) };
+        let Some(callback) = this.callback.get() else {
+            return CallbackStatus::CONTINUE;
+        };
+        if opcode != TFTP_DATA_OPCODE || packet_length < TFTP_DATA_HEADER_SIZE {
+            return CallbackStatus::CONTINUE;
+        }
+        let mut progress = this.progress.get();
+        progress.received = (progress.received + (packet_length - TFTP_DATA_HEADER_SIZE) as u64).min(progress.total);
+        this.progress.set(progress);
+        //SAFETY: The callback is only set while the download that borrows it is in progress.
+        match unsafe { (*callback)(progress) } {
+            true => CallbackStatus::CONTINUE,
+            false => CallbackStatus::ABORT,
+        }
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressCallback").field("progress", &self.progress.get()).finish()
+    }
+}
+
+fn to_efi_address(address: IpAddr) -> efi::IpAddress {
+    match address {
+        IpAddr::V4(address) => efi::IpAddress { v4: efi::Ipv4Address { addr: address.octets() } },
+        IpAddr::V6(address) => efi::IpAddress { v6: efi::Ipv6Address { addr: address.octets() } },
+    }
+}
+
+/// Wrapper around the PXE Base Code protocol.
+pub struct PxeBaseCode(&'static mut Protocol);
+
+impl PxeBaseCode {
+    /// Gets the instance of the PXE Base Code protocol installed on a network interface.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &PxeBaseCodeProtocol).map(Self)
+    }
+
+    fn protocol_ptr(&self) -> *mut Protocol {
+        self.0 as *const Protocol as *mut Protocol
+    }
+
+    /// The mode of the protocol, with the packets it received.
+    pub fn mode(&self) -> &Mode {
+        //SAFETY: The mode is owned by the protocol and valid while it is installed.
+        unsafe { &*self.0.mode }
+    }
+
+    fn is_ipv6(&self) -> bool {
+        self.mode().using_ipv6.into()
+    }
+
+    /// Starts the protocol, over IPv6 if `ipv6` is set. Starting it again is not an error.
+    pub fn start(&mut self, ipv6: bool) -> Result<(), EfiError> {
+        match (self.0.start)(self.protocol_ptr(), ipv6.into()) {
+            efi::Status::ALREADY_STARTED => Ok(()),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn stop(&mut self) -> Result<(), EfiError> {
+        match (self.0.stop)(self.protocol_ptr()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs DHCP to get an address and the boot parameters, returns the acknowledgement of the server.
+    ///
+    /// With `sort_offers`, the offers are tried from the most to the least complete instead of in order of arrival.
+    pub fn dhcp(&mut self, sort_offers: bool) -> Result<&Packet, EfiError> {
+        match (self.0.dhcp)(self.protocol_ptr(), sort_offers.into()) {
+            s if s.is_error() => Err(s.into()),
+            _ => self.dhcp_ack().ok_or(efi::Status::NO_RESPONSE.into()),
+        }
+    }
+
+    /// Discovers the boot server of a boot server type, such as [`BOOT_TYPE_BOOTSTRAP`], with the discovery
+    /// parameters of the DHCP packets. Returns the reply of the boot server.
+    pub fn discover(&mut self, boot_type: u16) -> Result<&Packet, EfiError> {
+        let mut layer = 0;
+        match (self.0.discover)(self.protocol_ptr(), boot_type, &mut layer, efi::Boolean::FALSE, ptr::null_mut()) {
+            s if s.is_error() => Err(s.into()),
+            _ => self.pxe_reply().ok_or(efi::Status::NO_RESPONSE.into()),
+        }
+    }
+
+    /// The acknowledgement of the DHCP server, once received.
+    pub fn dhcp_ack(&self) -> Option<&Packet> {
+        bool::from(self.mode().dhcp_ack_received).then_some(&self.mode().dhcp_ack)
+    }
+
+    /// The offer of a proxy DHCP server, with the boot parameters, once received.
+    pub fn proxy_offer(&self) -> Option<&Packet> {
+        bool::from(self.mode().proxy_offer_received).then_some(&self.mode().proxy_offer)
+    }
+
+    /// The reply of the boot server, once received.
+    pub fn pxe_reply(&self) -> Option<&Packet> {
+        bool::from(self.mode().pxe_reply_received).then_some(&self.mode().pxe_reply)
+    }
+
+    /// The packets with boot parameters, from the most to the least specific.
+    fn boot_packets(&self) -> impl Iterator<Item = &Packet> {
+        [self.pxe_reply(), self.proxy_offer(), self.dhcp_ack()].into_iter().flatten()
+    }
+
+    /// The address of the station, once configured.
+    pub fn station_ip(&self) -> IpAddr {
+        //SAFETY: The union holds the address of the IP version in use.
+        unsafe {
+            match self.is_ipv6() {
+                true => IpAddr::V6(Ipv6Addr::from(self.mode().station_ip.v6.addr)),
+                false => IpAddr::V4(Ipv4Addr::from(self.mode().station_ip.v4.addr)),
+            }
+        }
+    }
+
+    /// The boot file given by the received DHCPv4 packets, or the boot file URL of the DHCPv6 packets.
+    pub fn boot_file(&self) -> Option<&str> {
+        match self.is_ipv6() {
+            true => self.boot_packets().find_map(|packet| packet.dhcpv6().boot_file_url()),
+            false => self.boot_packets().find_map(|packet| packet.dhcpv4().boot_file()),
+        }
+    }
+
+    /// The next server of the boot given by the received DHCPv4 packets.
+    pub fn boot_server(&self) -> Option<IpAddr> {
+        if self.is_ipv6() {
+            return None;
+        }
+        self.boot_packets()
+            .map(|packet| packet.dhcpv4().server_address())
+            .find(|address| !address.is_unspecified())
+            .map(IpAddr::V4)
+    }
+
+    /// Calls the MTFTP function of the protocol for a file of a server.
+    fn mtftp(
+        &mut self,
+        operation: TftpOpcode,
+        server: IpAddr,
+        filename: &str,
+        buffer: &mut [u8],
+    ) -> Result<u64, EfiError> {
+        let mut server = to_efi_address(server);
+        let filename = CString::new(filename).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        let mut buffer_size = buffer.len() as u64;
+        let buffer_ptr = match buffer.is_empty() {
+            true => ptr::null_mut(),
+            false => buffer.as_mut_ptr() as *mut c_void,
+        };
+        match (self.0.mtftp)(
+            self.protocol_ptr(),
+            operation,
+            buffer_ptr,
+            efi::Boolean::FALSE,
+            &mut buffer_size,
+            ptr::null_mut(),
+            &mut server,
+            filename.as_ptr() as *mut u8,
+            ptr::null_mut(),
+            efi::Boolean::FALSE,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(buffer_size),
+        }
+    }
+
+    /// The size of a file of a TFTP server.
+    pub fn tftp_file_size(&mut self, server: IpAddr, filename: &str) -> Result<u64, EfiError> {
+        self.mtftp(TftpOpcode::TFTP_GET_FILE_SIZE, server, filename, &mut [])
+    }
+
+    /// Downloads a file of a TFTP server.
+    pub fn tftp_read_file(&mut self, server: IpAddr, filename: &str) -> Result<Vec<u8>, EfiError> {
+        let size = self.tftp_file_size(server, filename)?;
+        self.tftp_read(server, filename, size)
+    }
+
+    fn tftp_read(&mut self, server: IpAddr, filename: &str, size: u64) -> Result<Vec<u8>, EfiError> {
+        let mut buffer = vec![0; usize::try_from(size).map_err(|_| efi::Status::BUFFER_TOO_SMALL)?];
+        let size = self.mtftp(TftpOpcode::TFTP_READ_FILE, server, filename, &mut buffer)?;
+        buffer.truncate(size as usize);
+        Ok(buffer)
+    }
+
+    /// Downloads a file of a TFTP server, reporting the progress to `progress` as the data is received. The download
+    /// is aborted when `progress` returns false.
+    ///
+    /// The progress is reported through the PXE Base Code Callback protocol, which is installed on the handle of the
+    /// protocol for the duration of the download.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn tftp_read_file_with_progress<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        handle: efi::Handle,
+        server: IpAddr,
+        filename: &str,
+        mut progress: impl FnMut(Progress) -> bool,
+    ) -> Result<Vec<u8>, EfiError> {
+        let size = self.tftp_file_size(server, filename)?;
+        let installed = InstalledProtocol::install(
+            boot_services,
+            Some(handle),
+            &ProgressCallbackProducer,
+            Box::new(ProgressCallback::new(size)),
+        )?;
+        let callback: &mut (dyn FnMut(Progress) -> bool + '_) = &mut progress;
+        //SAFETY: The lifetime is erased to store the callback in the protocol, which unsets it before it goes out of
+        // scope below.
+        installed
+            .callback
+            .set(Some(unsafe { mem::transmute::<*mut (dyn FnMut(Progress) -> bool + '_), *mut ProgressFn>(callback) }));
+
+        let make_callbacks = self.mode().make_callbacks;
+        let result = self.set_make_callbacks(efi::Boolean::TRUE).and_then(|()| self.tftp_read(server, filename, size));
+        let _ = self.set_make_callbacks(make_callbacks);
+
+        installed.callback.set(None);
+        if let Err((installed, _)) = installed.uninstall() {
+            // The firmware may still call the protocol, which no longer reports the progress.
+            installed.leak();
+        }
+        result
+    }
+
+    fn set_make_callbacks(&mut self, make_callbacks: efi::Boolean) -> Result<(), EfiError> {
+        let mut make_callbacks = make_callbacks;
+        match (self.0.set_parameters)(
+            self.protocol_ptr(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut make_callbacks,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for PxeBaseCode {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for PxeBaseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PxeBaseCode")
+            .field("started", &bool::from(self.mode().started))
+            .field("using_ipv6", &self.is_ipv6())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use core::slice;
+
+    /// Interface of a network interface serving one file, the protocol is the first field to be found from its
+    /// pointer.
+    #[repr(C)]
+    struct TestPxe {
+        protocol: Protocol,
+        mode: Box<Mode>,
+        file: Vec<u8>,
+        callback: Cell<*mut CallbackProtocol>,
+    }
+
+    fn test_pxe<'a>(this: *mut Protocol) -> &'a mut TestPxe {
+        unsafe { &mut *(this as *mut TestPxe) }
+    }
+
+    extern "efiapi" fn start(this: *mut Protocol, use_ipv6: efi::Boolean) -> efi::Status {
+        let mode = &mut test_pxe(this).mode;
+        if mode.started.into() {
+            return efi::Status::ALREADY_STARTED;
+        }
+        mode.started = efi::Boolean::TRUE;
+        mode.using_ipv6 = use_ipv6;
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn stop(this: *mut Protocol) -> efi::Status {
+        test_pxe(this).mode.started = efi::Boolean::FALSE;
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn dhcp(this: *mut Protocol, _sort_offers: efi::Boolean) -> efi::Status {
+        let mode = &mut test_pxe(this).mode;
+        if !bool::from(mode.started) {
+            return efi::Status::NOT_STARTED;
+        }
+        mode.station_ip = to_efi_address(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)));
+        mode.dhcp_ack_received = efi::Boolean::TRUE;
+        mode.dhcp_ack = dhcpv4_packet([10, 0, 0, 1], "boot.efi", &[53, 1, 5, 0, 0, 54, 4, 10, 0, 0, 1, 255]);
+        mode.proxy_offer_received = efi::Boolean::TRUE;
+        mode.proxy_offer =
+            dhcpv4_packet([0; 4], "", &[53, 1, 2, 67, 9, b'p', b'x', b'e', b'.', b'e', b'f', b'i', 0, 0]);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn discover(
+        _this: *mut Protocol,
+        _type: u16,
+        _layer: *mut u16,
+        _use_bis: efi::Boolean,
+        _info: *mut DiscoverInfo,
+    ) -> efi::Status {
+        efi::Status::TIMEOUT
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    extern "efiapi" fn mtftp(
+        this: *mut Protocol,
+        operation: TftpOpcode,
+        buffer: *mut c_void,
+        _overwrite: efi::Boolean,
+        buffer_size: *mut u64,
+        _block_size: *mut usize,
+        server_ip: *mut efi::IpAddress,
+        filename: *mut u8,
+        _info: *mut MtftpInfo,
+        _dont_use_buffer: efi::Boolean,
+    ) -> efi::Status {
+        let pxe = test_pxe(this);
+        let filename = unsafe { core::ffi::CStr::from_ptr(filename as *const _) };
+        if unsafe { (*server_ip).v4.addr } != [10, 0, 0, 1] || filename.to_bytes() != b"boot.efi" {
+            return efi::Status::TFTP_ERROR;
+        }
+        let size = unsafe { &mut *buffer_size };
+        match operation {
+            TftpOpcode::TFTP_GET_FILE_SIZE => *size = pxe.file.len() as u64,
+            TftpOpcode::TFTP_READ_FILE if (*size as usize) < pxe.file.len() => return efi::Status::BUFFER_TOO_SMALL,
+            TftpOpcode::TFTP_READ_FILE => {
+                for (index, block) in pxe.file.chunks(4).enumerate() {
+                    let mut packet = Packet::default();
+                    packet.raw[..4].copy_from_slice(&[0, 3, 0, index as u8 + 1]);
+                    packet.raw[4..4 + block.len()].copy_from_slice(block);
+                    let callback = pxe.callback.get();
+                    if pxe.mode.make_callbacks.into()
+                        && (unsafe { &*callback }.callback)(
+                            callback,
+                            Function::MTFTP,
+                            efi::Boolean::TRUE,
+                            4 + block.len() as u32,
+                            &mut packet,
+                        ) == CallbackStatus::ABORT
+                    {
+                        return efi::Status::ABORTED;
+                    }
+                }
+                unsafe { slice::from_raw_parts_mut(buffer as *mut u8, pxe.file.len()) }.copy_from_slice(&pxe.file);
+                *size = pxe.file.len() as u64;
+            }
+            _ => return efi::Status::UNSUPPORTED,
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_parameters(
+        this: *mut Protocol,
+        _auto_arp: *mut efi::Boolean,
+        _send_guid: *mut efi::Boolean,
+        _ttl: *mut u8,
+        _tos: *mut u8,
+        make_callback: *mut efi::Boolean,
+    ) -> efi::Status {
+        if let Some(make_callback) = unsafe { make_callback.as_ref() } {
+            test_pxe(this).mode.make_callbacks = *make_callback;
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn udp_write(
+        _: *mut Protocol,
+        _: u16,
+        _: *mut efi::IpAddress,
+        _: *mut UdpPort,
+        _: *mut efi::IpAddress,
+        _: *mut efi::IpAddress,
+        _: *mut UdpPort,
+        _: *mut usize,
+        _: *mut c_void,
+        _: *mut usize,
+        _: *mut c_void,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn udp_read(
+        _: *mut Protocol,
+        _: u16,
+        _: *mut efi::IpAddress,
+        _: *mut UdpPort,
+        _: *mut efi::IpAddress,
+        _: *mut UdpPort,
+        _: *mut usize,
+        _: *mut c_void,
+        _: *mut usize,
+        _: *mut c_void,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn set_ip_filter(_: *mut Protocol, _: *mut IpFilter) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn arp(_: *mut Protocol, _: *mut efi::IpAddress, _: *mut efi::MacAddress) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn set_station_ip(_: *mut Protocol, _: *mut efi::IpAddress, _: *mut efi::IpAddress) -> efi::Status {
+        unimplemented!()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    extern "efiapi" fn set_packets(
+        _: *mut Protocol,
+        _: *mut efi::Boolean,
+        _: *mut efi::Boolean,
+        _: *mut efi::Boolean,
+        _: *mut efi::Boolean,
+        _: *mut efi::Boolean,
+        _: *mut efi::Boolean,
+        _: *mut Packet,
+        _: *mut Packet,
+        _: *mut Packet,
+        _: *mut Packet,
+        _: *mut Packet,
+        _: *mut Packet,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    fn dhcpv4_packet(server: [u8; 4], file: &str, options: &[u8]) -> Packet {
+        let mut packet = Packet::default();
+        packet.raw[0] = 2;
+        packet.raw[4..8].copy_from_slice(&0x1234_5678_u32.to_be_bytes());
+        packet.raw[20..24].copy_from_slice(&server);
+        packet.raw[108..108 + file.len()].copy_from_slice(file.as_bytes());
+        packet.raw[236..240].copy_from_slice(&DHCPV4_MAGIC_COOKIE);
+        packet.raw[240..240 + options.len()].copy_from_slice(options);
+        packet
+    }
+
+    fn pxe(file: &[u8]) -> PxeBaseCode {
+        let mut pxe = Box::new(TestPxe {
+            protocol: Protocol {
+                revision: 0x00010000,
+                start,
+                stop,
+                dhcp,
+                discover,
+                mtftp,
+                udp_write,
+                udp_read,
+                set_ip_filter,
+                arp,
+                set_parameters,
+                set_station_ip,
+                set_packets,
+                mode: ptr::null_mut(),
+            },
+            mode: Box::new(unsafe { mem::zeroed() }),
+            file: file.to_vec(),
+            callback: Cell::new(ptr::null_mut()),
+        });
+        pxe.protocol.mode = &mut *pxe.mode;
+        PxeBaseCode::from(&mut Box::leak(pxe).protocol)
+    }
+
+    #[test]
+    fn test_dhcp() {
+        let mut pxe = pxe(&[]);
+        assert_eq!(efi::Status::NOT_STARTED, pxe.dhcp(true).unwrap_err());
+        pxe.start(false).unwrap();
+        pxe.start(false).unwrap();
+
+        let ack = pxe.dhcp(true).unwrap().dhcpv4();
+        assert_eq!(2, ack.opcode());
+        assert_eq!(0x1234_5678, ack.transaction_id());
+        assert_eq!(Some(5), ack.message_type());
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 1), ack.server_address());
+        assert_eq!(Some("boot.efi"), ack.boot_file());
+        assert_eq!(Some([10, 0, 0, 1].as_slice()), ack.option(dhcpv4::SERVER_IDENTIFIER));
+        assert_eq!(vec![53, 54], ack.options().map(|option| option.code).collect::<Vec<_>>());
+
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)), pxe.station_ip());
+        // The proxy offer is more specific than the acknowledgement, but does not give the server.
+        assert_eq!(Some("pxe.efi"), pxe.boot_file());
+        assert_eq!(Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))), pxe.boot_server());
+        assert_eq!(efi::Status::TIMEOUT, pxe.discover(BOOT_TYPE_BOOTSTRAP).unwrap_err());
+        assert!(pxe.pxe_reply().is_none());
+
+        pxe.stop().unwrap();
+        assert!(!bool::from(pxe.mode().started));
+    }
+
+    #[test]
+    fn test_dhcp_options() {
+        let packet = dhcpv4_packet([0; 4], "", &[0, 0, 12, 2, b'p', b'c', 255, 60, 1, 1]);
+        let options = packet.dhcpv4().options().collect::<Vec<_>>();
+        assert_eq!(vec![DhcpOption { code: dhcpv4::HOST_NAME, data: b"pc" }], options);
+        // An option that overruns the packet ends the options.
+        assert_eq!(0, Dhcpv4Options(&[60, 5, 1]).count());
+        let mut packet = Packet::default();
+        assert_eq!(0, packet.dhcpv4().options().count());
+
+        packet.raw[..4].copy_from_slice(&[7, 0xAB, 0xCD, 0xEF]);
+        packet.raw[4..32].copy_from_slice(b"\x00\x3b\x00\x18http://10.0.0.1/boot.efi");
+        let reply = packet.dhcpv6();
+        assert_eq!(7, reply.message_type());
+        assert_eq!(0xABCDEF, reply.transaction_id());
+        assert_eq!(Some("http://10.0.0.1/boot.efi"), reply.boot_file_url());
+        assert_eq!(1, reply.options().count());
+    }
+
+    #[test]
+    fn test_tftp_read_file() {
+        let mut pxe = pxe(b"0123456789");
+        let server = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(10, pxe.tftp_file_size(server, "boot.efi").unwrap());
+        assert_eq!(b"0123456789", pxe.tftp_read_file(server, "boot.efi").unwrap().as_slice());
+        assert_eq!(efi::Status::TFTP_ERROR, pxe.tftp_read_file(server, "missing.efi").unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, pxe.tftp_file_size(server, "boot\0.efi").unwrap_err());
+    }
+
+    #[test]
+    fn test_tftp_read_file_with_progress() {
+        let mut pxe = pxe(b"0123456789");
+        let test_pxe_ptr = pxe.protocol_ptr() as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_install_protocol_interface_unchecked()
+            .withf(|handle, protocol, _| *handle == Some(1_usize as efi::Handle) && *protocol == CALLBACK_PROTOCOL_GUID)
+            .times(2)
+            .returning(move |handle, _, interface| {
+                test_pxe(test_pxe_ptr as *mut Protocol).callback.set(interface as *mut CallbackProtocol);
+                Ok(handle.unwrap())
+            });
+        boot_services
+            .expect_uninstall_protocol_interface_unchecked()
+            .withf(|handle, protocol, _| *handle == 1_usize as efi::Handle && *protocol == CALLBACK_PROTOCOL_GUID)
+            .times(2)
+            .returning(|_, _, _| Ok(()));
+
+        let server = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let mut reports = Vec::new();
+        let file = pxe
+            .tftp_read_file_with_progress(&boot_services, 1_usize as efi::Handle, server, "boot.efi", |progress| {
+                reports.push(progress);
+                true
+            })
+            .unwrap();
+        assert_eq!(b"0123456789", file.as_slice());
+        assert_eq!(
+            vec![
+                Progress { received: 4, total: 10 },
+                Progress { received: 8, total: 10 },
+                Progress { received: 10, total: 10 }
+            ],
+            reports
+        );
+        assert!(!bool::from(pxe.mode().make_callbacks));
+
+        let result =
+            pxe.tftp_read_file_with_progress(&boot_services, 1_usize as efi::Handle, server, "boot.efi", |progress| {
+                progress.received < 8
+            });
+        assert_eq!(efi::Status::ABORTED, result.unwrap_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! Reset Notification protocol, to run code before the platform resets.
+//!
+//! A closure registered with [`ResetNotification::register`] is called by `ResetSystem` before the reset, to flush a
+//! log or put a device in a safe state. It stays registered as long as the returned [`ResetNotify`] is kept:
+//!
+//! ```ignore
+//! let mut reset_notification = ResetNotification::locate(&boot_services)?;
+//! let notify = reset_notification.register(|reset| log::info!("Reset {:#x}: {:?}", reset.reset_type, reset.status))?;
+//! // Registered until `notify` is dropped, or for good with `notify.leak()`.
+//! ```
+//!
+//! The functions of the protocol take no context, so the closures are called from a table of [`MAX_NOTIFICATIONS`]
+//! functions, each registered with the protocol for one closure.
+//!
+//! [UEFI Spec Documentation: 8.5.1. Reset Notification Protocol](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#reset-notification-protocol)
+
+use alloc::boxed::Box;
+use core::{
+    ffi::c_void,
+    fmt,
+    mem::ManuallyDrop,
+    ops::Deref,
+    ptr, slice,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x9da34ae0, 0xeaf9, 0x4bbf, 0x8e, 0xc3, &[0xfd, 0x60, 0x22, 0x6c, 0x44, 0xbe]);
+
+/// Function called before the reset, with the arguments of `ResetSystem`.
+pub type ResetSystem = extern "efiapi" fn(efi::ResetType, efi::Status, usize, *mut c_void);
+
+pub type ProtocolRegisterResetNotify = extern "efiapi" fn(*mut Protocol, ResetSystem) -> efi::Status;
+
+pub type ProtocolUnregisterResetNotify = extern "efiapi" fn(*mut Protocol, ResetSystem) -> efi::Status;
+
+/// FFI definition of `EFI_RESET_NOTIFICATION_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub register_reset_notify: ProtocolRegisterResetNotify,
+    pub unregister_reset_notify: ProtocolUnregisterResetNotify,
+}
+
+/// Reset Notification protocol.
+pub struct ResetNotificationProtocol;
+
+unsafe impl ProtocolTrait for ResetNotificationProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for ResetNotificationProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// A reset about to happen, the arguments of `ResetSystem`.
+#[derive(Debug, Clone, Copy)]
+pub struct Reset<'a> {
+    /// One of `efi::RESET_COLD`, `RESET_WARM`, `RESET_SHUTDOWN` or `RESET_PLATFORM_SPECIFIC`.
+    pub reset_type: efi::ResetType,
+    /// Status of the reset, an error for a reset caused by a failure.
+    pub status: efi::Status,
+    /// Data of the reset, starting with a null-terminated description.
+    pub data: &'a [u8],
+}
+
+/// Number of closures registered at once.
+pub const MAX_NOTIFICATIONS: usize = 16;
+
+/// A registered closure, boxed again for a thin pointer.
+type Notification = Box<dyn Fn(&Reset)>;
+
+/// The closure of each function of [`TRAMPOLINES`], null when the function is free.
+static NOTIFICATIONS: [AtomicPtr<Notification>; MAX_NOTIFICATIONS] =
+    [const { AtomicPtr::new(ptr::null_mut()) }; MAX_NOTIFICATIONS];
+
+fn notify(slot: usize, reset_type: efi::ResetType, status: efi::Status, data_size: usize, data: *mut c_void) {
+    let notification = NOTIFICATIONS[slot].load(Ordering::Acquire);
+    if notification.is_null() {
+        return;
+    }
+    let data = match data.is_null() {
+        true => &[][..],
+        //SAFETY: `ResetSystem` passes `data_size` bytes of data.
+        false => unsafe { slice::from_raw_parts(data as *const u8, data_size) },
+    };
+    //SAFETY: The closure is freed only once its function is unregistered.
+    unsafe { (*notification)(&Reset { reset_type, status, data }) };
+}
+
+macro_rules! trampolines {
+    ($($slot:literal)*) => {
+        [$({
+            extern "efiapi" fn trampoline(
+                reset_type: efi::ResetType,
+                status: efi::Status,
+                data_size: usize,
+                data: *mut c_void,
+            ) {
+                notify($slot, reset_type, status, data_size, data)
+            }
+            trampoline as ResetSystem
+        },)*]
+    };
+}
+
+/// The functions registered with the protocol, each calling the closure of its slot.
+static TRAMPOLINES: [ResetSystem; MAX_NOTIFICATIONS] = trampolines!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15);
+
+/// Typed access to the Reset Notification protocol.
+pub struct ResetNotification(&'static mut Protocol);
+
+impl ResetNotification {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&ResetNotificationProtocol, None).map(Self)
+    }
+
+    /// Registers a closure called before the platform resets, until the returned [`ResetNotify`] is dropped.
+    ///
+    /// Returns `OUT_OF_RESOURCES` when [`MAX_NOTIFICATIONS`] closures are registered.
+    pub fn register<F: Fn(&Reset) + 'static>(&mut self, notify: F) -> Result<ResetNotify, EfiError> {
+        let notification = Box::into_raw(Box::new(Box::new(notify) as Notification));
+        let Some(slot) = NOTIFICATIONS.iter().position(|slot| {
+            slot.compare_exchange(ptr::null_mut(), notification, Ordering::AcqRel, Ordering::Acquire).is_ok()
+        }) else {
+            //SAFETY: The closure was not stored.
+            drop(unsafe { Box::from_raw(notification) });
+            return Err(efi::Status::OUT_OF_RESOURCES.into());
+        };
+        let protocol = self.0 as *mut Protocol;
+        match (self.0.register_reset_notify)(protocol, TRAMPOLINES[slot]) {
+            s if s.is_error() => {
+                free(slot);
+                Err(s.into())
+            }
+            _ => Ok(ResetNotify { protocol, slot }),
+        }
+    }
+}
+
+/// Frees the slot and its closure.
+fn free(slot: usize) {
+    let notification = NOTIFICATIONS[slot].swap(ptr::null_mut(), Ordering::AcqRel);
+    if !notification.is_null() {
+        //SAFETY: The closure was boxed by `register` and is no longer reachable.
+        drop(unsafe { Box::from_raw(notification) });
+    }
+}
+
+impl From<&'static mut Protocol> for ResetNotification {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for ResetNotification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResetNotification").finish_non_exhaustive()
+    }
+}
+
+/// A registered closure, unregistered when dropped.
+pub struct ResetNotify {
+    protocol: *mut Protocol,
+    slot: usize,
+}
+
+impl ResetNotify {
+    /// Unregisters the closure.
+    ///
+    /// When the protocol fails to unregister it, the closure is kept as it may still be called.
+    pub fn unregister(self) -> Result<(), EfiError> {
+        let this = ManuallyDrop::new(self);
+        this.unregister_slot()
+    }
+
+    /// Keeps the closure registered until the reset.
+    pub fn leak(self) {
+        let _ = ManuallyDrop::new(self);
+    }
+
+    fn unregister_slot(&self) -> Result<(), EfiError> {
+        //SAFETY: The protocol was located from the boot services and is never uninstalled.
+        let unregister_reset_notify = unsafe { (*self.protocol).unregister_reset_notify };
+        match unregister_reset_notify(self.protocol, TRAMPOLINES[self.slot]) {
+            s if s.is_error() => Err(s.into()),
+            _ => {
+                free(self.slot);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for ResetNotify {
+    fn drop(&mut self) {
+        let _ = self.unregister_slot();
+    }
+}
+
+impl fmt::Debug for ResetNotify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResetNotify").field("slot", &self.slot).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{rc::Rc, vec::Vec};
+    use boot_services::MockBootServices;
+    use core::cell::RefCell;
+
+    /// Protocol keeping the registered functions, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestResetNotification {
+        protocol: Protocol,
+        registered: RefCell<Vec<ResetSystem>>,
+    }
+
+    fn test_reset_notification<'a>(this: *mut Protocol) -> &'a TestResetNotification {
+        unsafe { &*(this as *const TestResetNotification) }
+    }
+
+    extern "efiapi" fn register_reset_notify(this: *mut Protocol, function: ResetSystem) -> efi::Status {
+        let mut registered = test_reset_notification(this).registered.borrow_mut();
+        if registered.iter().any(|&f| f as usize == function as usize) {
+            return efi::Status::ALREADY_STARTED;
+        }
+        registered.push(function);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn unregister_reset_notify(this: *mut Protocol, function: ResetSystem) -> efi::Status {
+        let mut registered = test_reset_notification(this).registered.borrow_mut();
+        match registered.iter().position(|&f| f as usize == function as usize) {
+            Some(index) => {
+                registered.remove(index);
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::INVALID_PARAMETER,
+        }
+    }
+
+    fn reset(this: &TestResetNotification, reset_type: efi::ResetType, data: &mut [u8]) {
+        for function in this.registered.borrow().clone() {
+            function(reset_type, efi::Status::ABORTED, data.len(), data.as_mut_ptr() as *mut c_void);
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let test = Box::leak(Box::new(TestResetNotification {
+            protocol: Protocol { register_reset_notify, unregister_reset_notify },
+            registered: RefCell::new(Vec::new()),
+        }));
+        let test_ptr = test as *mut TestResetNotification as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<ResetNotificationProtocol, Protocol>()
+            .returning(move |_, _| Ok(unsafe { &mut (*(test_ptr as *mut TestResetNotification)).protocol }));
+        let mut reset_notification = ResetNotification::locate(&boot_services).unwrap();
+
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let first_calls = calls.clone();
+        let first = reset_notification
+            .register(move |reset| {
+                first_calls.borrow_mut().push((1, reset.reset_type, reset.status, reset.data.to_vec()))
+            })
+            .unwrap();
+        let second_calls = calls.clone();
+        let second = reset_notification
+            .register(move |reset| second_calls.borrow_mut().push((2, reset.reset_type, reset.status, Vec::new())))
+            .unwrap();
+
+        reset(test, efi::RESET_WARM, &mut [1, 2]);
+        assert_eq!(
+            vec![
+                (1, efi::RESET_WARM, efi::Status::ABORTED, vec![1, 2]),
+                (2, efi::RESET_WARM, efi::Status::ABORTED, vec![])
+            ],
+            *calls.borrow()
+        );
+
+        calls.borrow_mut().clear();
+        drop(first);
+        reset(test, efi::RESET_COLD, &mut []);
+        assert_eq!(vec![(2, efi::RESET_COLD, efi::Status::ABORTED, vec![])], *calls.borrow());
+
+        second.unregister().unwrap();
+        assert!(test.registered.borrow().is_empty());
+
+        // The slots are reused once freed, up to the maximum.
+        let notifies = (0..MAX_NOTIFICATIONS).map(|_| reset_notification.register(|_| ()).unwrap()).collect::<Vec<_>>();
+        assert_eq!(efi::Status::OUT_OF_RESOURCES, reset_notification.register(|_| ()).unwrap_err());
+        drop(notifies);
+
+        // A closure that could not be unregistered is kept.
+        let notify = reset_notification.register(|_| ()).unwrap();
+        test.registered.borrow_mut().clear();
+        assert_eq!(efi::Status::INVALID_PARAMETER, notify.unregister().unwrap_err());
+        assert_eq!(1, NOTIFICATIONS.iter().filter(|slot| !slot.load(Ordering::Acquire).is_null()).count());
+    }
+}
@@ -0,0 +1,356 @@
+//! Random Number Generator protocol.
+//!
+//! [`Rng`] fills buffers with random bytes from the firmware. With the `rand_core` feature it is a
+//! [`rand_core::RngCore`], and with the `getrandom` feature [`register_getrandom!`](crate::register_getrandom) makes
+//! it the source of `getrandom`, which most cryptographic crates draw their randomness from:
+//!
+//! ```ignore
+//! protocols::register_getrandom!(entry_point::boot_services());
+//! ```
+//!
+//! [UEFI Spec Documentation: 37.5. Random Number Generator Protocol](https://uefi.org/specs/UEFI/2.10/37_Secure_Technologies.html#random-number-generator-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, ptr};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use efi::protocols::rng;
+
+#[cfg(feature = "getrandom")]
+#[doc(hidden)]
+pub use getrandom;
+
+type RngProtocol = rng::Protocol;
+
+/// Algorithm used to generate random numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Sp800_90Hash256,
+    Sp800_90Hmac256,
+    Sp800_90Ctr256,
+    X9_31_3Des,
+    X9_31Aes,
+    /// Raw entropy, without any processing.
+    Raw,
+    Other(efi::Guid),
+}
+
+impl Algorithm {
+    pub const fn guid(&self) -> efi::Guid {
+        match self {
+            Algorithm::Sp800_90Hash256 => rng::ALGORITHM_SP800_90_HASH_256_GUID,
+            Algorithm::Sp800_90Hmac256 => rng::ALGORITHM_SP800_90_HMAC_256_GUID,
+            Algorithm::Sp800_90Ctr256 => rng::ALGORITHM_SP800_90_CTR_256_GUID,
+            Algorithm::X9_31_3Des => rng::ALGORITHM_X9_31_3DES_GUID,
+            Algorithm::X9_31Aes => rng::ALGORITHM_X9_31_AES_GUID,
+            Algorithm::Raw => rng::ALGORITHM_RAW,
+            Algorithm::Other(guid) => *guid,
+        }
+    }
+}
+
+impl From<efi::Guid> for Algorithm {
+    fn from(guid: efi::Guid) -> Self {
+        [
+            Algorithm::Sp800_90Hash256,
+            Algorithm::Sp800_90Hmac256,
+            Algorithm::Sp800_90Ctr256,
+            Algorithm::X9_31_3Des,
+            Algorithm::X9_31Aes,
+            Algorithm::Raw,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.guid() == guid)
+        .unwrap_or(Algorithm::Other(guid))
+    }
+}
+
+/// Typed access to an instance of the Random Number Generator protocol.
+///
+/// The random bytes come from the default algorithm of the firmware, unless another is selected with
+/// [`Rng::set_algorithm`]:
+///
+/// ```ignore
+/// let mut rng = Rng::locate(&boot_services)?;
+/// let mut key = [0; 32];
+/// rng.get_random(&mut key)?;
+/// ```
+pub struct Rng {
+    protocol: &'static mut RngProtocol,
+    algorithm: Option<Algorithm>,
+}
+
+impl Rng {
+    /// Locates the first instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::Rng, None).map(Self::from)
+    }
+
+    /// Gets the instance of the protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &protocol_handler::Rng).map(Self::from)
+    }
+
+    fn this(&self) -> *mut RngProtocol {
+        self.protocol as *const RngProtocol as *mut RngProtocol
+    }
+
+    /// The algorithms supported by the firmware, the first one is its default.
+    pub fn algorithms(&self) -> Result<Vec<Algorithm>, EfiError> {
+        let mut guids = Vec::new();
+        loop {
+            let mut size = guids.len() * core::mem::size_of::<efi::Guid>();
+            match unsafe { r_efi::efi_call(self.protocol.get_info, (self.this(), &mut size, guids.as_mut_ptr())) } {
+                efi::Status::BUFFER_TOO_SMALL => {
+                    guids =
+                        vec![efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6]); size / core::mem::size_of::<efi::Guid>()]
+                }
+                s if s.is_error() => return Err(s.into()),
+                _ => {
+                    guids.truncate(size / core::mem::size_of::<efi::Guid>());
+                    return Ok(guids.into_iter().map(Algorithm::from).collect());
+                }
+            }
+        }
+    }
+
+    /// The algorithm selected with [`Rng::set_algorithm`], `None` for the default of the firmware.
+    pub fn algorithm(&self) -> Option<Algorithm> {
+        self.algorithm
+    }
+
+    /// Selects the algorithm of the following random bytes, `None` for the default of the firmware.
+    ///
+    /// Fails with `UNSUPPORTED` if the firmware does not support the algorithm.
+    pub fn set_algorithm(&mut self, algorithm: Option<Algorithm>) -> Result<(), EfiError> {
+        if let Some(algorithm) = algorithm {
+            if !self.algorithms()?.contains(&algorithm) {
+                return Err(efi::Status::UNSUPPORTED.into());
+            }
+        }
+        self.algorithm = algorithm;
+        Ok(())
+    }
+
+    /// Fills `buffer` with random bytes from the selected algorithm.
+    pub fn get_random(&mut self, buffer: &mut [u8]) -> Result<(), EfiError> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let mut guid = self.algorithm.map(|algorithm| algorithm.guid());
+        let algorithm = guid.as_mut().map_or(ptr::null_mut(), |guid| guid as *mut efi::Guid);
+        match unsafe {
+            r_efi::efi_call(self.protocol.get_rng, (self.this(), algorithm, buffer.len(), buffer.as_mut_ptr()))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut RngProtocol> for Rng {
+    fn from(protocol: &'static mut RngProtocol) -> Self {
+        Self { protocol, algorithm: None }
+    }
+}
+
+impl fmt::Debug for Rng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rng").field("algorithm", &self.algorithm).finish()
+    }
+}
+
+/// The code of the errors of `rand_core` and `getrandom` for a status, in the range of custom errors.
+#[cfg(any(feature = "rand_core", feature = "getrandom"))]
+fn error_code(status: efi::Status) -> core::num::NonZeroU32 {
+    const CUSTOM_START: u32 = (1 << 31) + (1 << 30);
+    core::num::NonZeroU32::new(CUSTOM_START | (status.as_usize() as u32 & !CUSTOM_START)).unwrap()
+}
+
+#[cfg(feature = "rand_core")]
+impl rand_core::RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the firmware fails to generate the bytes.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Err(status) = self.get_random(dest) {
+            panic!("RNG protocol failed: {status}");
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.get_random(dest).map_err(|status| rand_core::Error::from(error_code(status.into())))
+    }
+}
+
+/// The algorithms of the firmware are cryptographically secure.
+#[cfg(feature = "rand_core")]
+impl rand_core::CryptoRng for Rng {}
+
+/// Fills `dest` with random bytes from the first instance of the protocol, as the source of `getrandom`.
+#[cfg(feature = "getrandom")]
+pub fn getrandom_fill<B: BootServices>(boot_services: &B, dest: &mut [u8]) -> Result<(), getrandom::Error> {
+    Rng::locate(boot_services)
+        .and_then(|mut rng| rng.get_random(dest))
+        .map_err(|status| getrandom::Error::from(error_code(status.into())))
+}
+
+/// Registers the Random Number Generator protocol as the source of `getrandom`, for the program built for UEFI.
+///
+/// The expression gives the boot services to locate the protocol with, it is evaluated on every call of `getrandom`.
+#[cfg(feature = "getrandom")]
+#[macro_export]
+macro_rules! register_getrandom {
+    ($boot_services:expr) => {
+        const _: () = {
+            fn getrandom(dest: &mut [u8]) -> ::core::result::Result<(), $crate::rng::getrandom::Error> {
+                $crate::rng::getrandom_fill($boot_services, dest)
+            }
+            $crate::rng::getrandom::register_custom_getrandom!(getrandom);
+        };
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+    use core::slice;
+
+    /// Generator of a counter supporting the CTR and raw algorithms, the protocol is the first field to be found
+    /// from its pointer.
+    #[repr(C)]
+    struct TestRng {
+        protocol: RngProtocol,
+        counter: u8,
+        last_algorithm: Option<efi::Guid>,
+    }
+
+    fn test_rng<'a>(this: *mut RngProtocol) -> &'a mut TestRng {
+        unsafe { &mut *(this as *mut TestRng) }
+    }
+
+    const ALGORITHMS: [efi::Guid; 2] = [rng::ALGORITHM_SP800_90_CTR_256_GUID, rng::ALGORITHM_RAW];
+
+    extern "efiapi" fn get_info(_this: *mut RngProtocol, size: *mut usize, algorithms: *mut efi::Guid) -> efi::Status {
+        let size = unsafe { &mut *size };
+        let available = *size;
+        *size = core::mem::size_of_val(&ALGORITHMS);
+        if available < *size {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(algorithms, ALGORITHMS.len()) }.copy_from_slice(&ALGORITHMS);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_rng(
+        this: *mut RngProtocol,
+        algorithm: *mut efi::Guid,
+        size: usize,
+        value: *mut u8,
+    ) -> efi::Status {
+        let rng = test_rng(this);
+        let algorithm = unsafe { algorithm.as_ref() }.copied();
+        if size == 0 || algorithm.is_some_and(|algorithm| !ALGORITHMS.contains(&algorithm)) {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        rng.last_algorithm = algorithm;
+        for byte in unsafe { slice::from_raw_parts_mut(value, size) } {
+            rng.counter = rng.counter.wrapping_add(1);
+            *byte = rng.counter;
+        }
+        efi::Status::SUCCESS
+    }
+
+    fn boot_services() -> (MockBootServices, &'static TestRng) {
+        let rng = Box::leak(Box::new(TestRng {
+            protocol: RngProtocol { get_info, get_rng },
+            counter: 0,
+            last_algorithm: None,
+        }));
+        let rng_ptr = rng as *mut TestRng as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::Rng, RngProtocol>()
+            .returning(move |_, _| Ok(unsafe { &mut (*(rng_ptr as *mut TestRng)).protocol }));
+        (boot_services, unsafe { &*(rng_ptr as *const TestRng) })
+    }
+
+    #[test]
+    fn test_algorithms() {
+        let (boot_services, test_rng) = boot_services();
+        let mut rng = Rng::locate(&boot_services).unwrap();
+        assert_eq!(vec![Algorithm::Sp800_90Ctr256, Algorithm::Raw], rng.algorithms().unwrap());
+
+        assert_eq!(efi::Status::UNSUPPORTED, rng.set_algorithm(Some(Algorithm::X9_31Aes)).unwrap_err());
+        rng.set_algorithm(Some(Algorithm::Raw)).unwrap();
+        assert_eq!(Some(Algorithm::Raw), rng.algorithm());
+        rng.get_random(&mut [0; 4]).unwrap();
+        assert_eq!(Some(rng::ALGORITHM_RAW), test_rng.last_algorithm);
+
+        rng.set_algorithm(None).unwrap();
+        rng.get_random(&mut [0; 4]).unwrap();
+        assert_eq!(None, test_rng.last_algorithm);
+        assert_eq!(Algorithm::Other(rng::PROTOCOL_GUID), Algorithm::from(rng::PROTOCOL_GUID));
+    }
+
+    #[test]
+    fn test_get_random() {
+        let (boot_services, _) = boot_services();
+        let mut rng = Rng::locate(&boot_services).unwrap();
+        let mut buffer = [0; 3];
+        rng.get_random(&mut buffer).unwrap();
+        assert_eq!([1, 2, 3], buffer);
+        rng.get_random(&mut []).unwrap();
+        rng.get_random(&mut buffer).unwrap();
+        assert_eq!([4, 5, 6], buffer);
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn test_rng_core() {
+        use rand_core::RngCore;
+
+        let (boot_services, _) = boot_services();
+        let mut rng = Rng::locate(&boot_services).unwrap();
+        assert_eq!(u32::from_le_bytes([1, 2, 3, 4]), rng.next_u32());
+        rng.algorithm = Some(Algorithm::X9_31Aes);
+        let error = rng.try_fill_bytes(&mut [0; 4]).unwrap_err();
+        assert_eq!(Some(error_code(efi::Status::INVALID_PARAMETER)), error.code());
+    }
+
+    #[cfg(feature = "getrandom")]
+    mod registration {
+        static BOOT_SERVICES: boot_services::StandardBootServices = boot_services::StandardBootServices::new_uninit();
+        crate::register_getrandom!(&BOOT_SERVICES);
+    }
+
+    #[cfg(feature = "getrandom")]
+    #[test]
+    fn test_getrandom() {
+        let (boot_services, _) = boot_services();
+        let mut buffer = [0; 2];
+        getrandom_fill(&boot_services, &mut buffer).unwrap();
+        assert_eq!([1, 2], buffer);
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::Rng, RngProtocol>()
+            .returning(|_, _| Err(efi::Status::NOT_FOUND.into()));
+        let error = getrandom_fill(&boot_services, &mut buffer).unwrap_err();
+        assert_eq!(error_code(efi::Status::NOT_FOUND), error.code());
+    }
+}
@@ -0,0 +1,165 @@
+//! Security2 Architectural protocol, the authentication of the images dispatched by the DXE core.
+//!
+//! [`Security2::file_authentication`] asks the platform security policy for the verdict the core dispatcher would get
+//! for a file, for a loader to check an image before loading it:
+//!
+//! ```ignore
+//! let security = Security2::locate(&boot_services)?;
+//! match security.file_authentication(Some(file_path), Some(&image), false)? {
+//!     Verdict::Allowed => load(&image),
+//!     Verdict::Denied | Verdict::Deferred => return Err(efi::Status::SECURITY_VIOLATION.into()),
+//! }
+//! ```
+//!
+//! [PI Spec Documentation: Volume 2, 12.9. Security2 Architectural Protocol](https://uefi.org/specs/PI/1.8/V2_DXE_Architectural_Protocols.html#security2-architectural-protocol)
+
+use core::{ffi::c_void, fmt, ops::Deref, ptr};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use device_path::DevicePath;
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x94ab2f58, 0x1438, 0x4ef1, 0x91, 0x52, &[0x18, 0x94, 0x1a, 0x3a, 0x0e, 0x68]);
+
+pub type ProtocolFileAuthentication = extern "efiapi" fn(
+    *const Protocol,
+    *const efi::protocols::device_path::Protocol,
+    *mut c_void,
+    usize,
+    efi::Boolean,
+) -> efi::Status;
+
+/// FFI definition of `EFI_SECURITY2_ARCH_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub file_authentication: ProtocolFileAuthentication,
+}
+
+/// Security2 Architectural protocol.
+pub struct Security2ArchProtocol;
+
+unsafe impl ProtocolTrait for Security2ArchProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for Security2ArchProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Verdict of the platform security policy on a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Verdict {
+    /// The file authenticated, or the policy allows it without authentication.
+    Allowed,
+    /// The file did not authenticate and must not be used, `ACCESS_DENIED`.
+    Denied,
+    /// The file did not authenticate and is placed in the untrusted state, `SECURITY_VIOLATION`.
+    ///
+    /// The core dispatcher keeps such a driver until the platform allows it with `Trust()`.
+    Deferred,
+}
+
+/// Typed access to the Security2 Architectural protocol.
+pub struct Security2(&'static mut Protocol);
+
+impl Security2 {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&Security2ArchProtocol, None).map(Self)
+    }
+
+    /// The verdict of the platform security policy on a file.
+    ///
+    /// The file is given by its `device_path`, its content `file`, or both. `boot_policy` is true for files loaded
+    /// as boot options, whose policy may differ. Other failures of the protocol, such as `INVALID_PARAMETER` when
+    /// neither the device path nor the content is given, are errors.
+    pub fn file_authentication(
+        &self,
+        device_path: Option<&DevicePath>,
+        file: Option<&[u8]>,
+        boot_policy: bool,
+    ) -> Result<Verdict, EfiError> {
+        let device_path = device_path.map_or(ptr::null(), DevicePath::as_ptr);
+        let (buffer, size) = file.map_or((ptr::null_mut(), 0), |file| (file.as_ptr() as *mut c_void, file.len()));
+        // The file is only read by the protocol.
+        match (self.0.file_authentication)(self.0, device_path, buffer, size, boot_policy.into()) {
+            efi::Status::ACCESS_DENIED => Ok(Verdict::Denied),
+            efi::Status::SECURITY_VIOLATION => Ok(Verdict::Deferred),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(Verdict::Allowed),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for Security2 {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for Security2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Security2").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+    use core::slice;
+
+    /// Device path of a file, only made of the end of entire device path node.
+    const DENIED_PATH: [u8; 4] = [0x7F, 0xFF, 0x04, 0x00];
+
+    /// Allows the files that start with `MZ` or loaded as boot options, defers the others and denies any device path.
+    extern "efiapi" fn file_authentication(
+        _: *const Protocol,
+        device_path: *const efi::protocols::device_path::Protocol,
+        file: *mut c_void,
+        size: usize,
+        boot_policy: efi::Boolean,
+    ) -> efi::Status {
+        if !device_path.is_null() {
+            return efi::Status::ACCESS_DENIED;
+        }
+        if file.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        match unsafe { slice::from_raw_parts(file as *const u8, size) } {
+            _ if boot_policy.into() => efi::Status::SUCCESS,
+            [b'M', b'Z', ..] => efi::Status::SUCCESS,
+            _ => efi::Status::SECURITY_VIOLATION,
+        }
+    }
+
+    fn boot_services() -> MockBootServices {
+        let protocol = Box::leak(Box::new(Protocol { file_authentication })) as *mut Protocol as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<Security2ArchProtocol, Protocol>()
+            .returning(move |_, _| Ok(unsafe { &mut *(protocol as *mut Protocol) }));
+        boot_services
+    }
+
+    #[test]
+    fn test_file_authentication() {
+        let boot_services = boot_services();
+        let security = Security2::locate(&boot_services).unwrap();
+        assert_eq!(Verdict::Allowed, security.file_authentication(None, Some(b"MZ\x90\x00"), false).unwrap());
+        assert_eq!(Verdict::Deferred, security.file_authentication(None, Some(b"ELF"), false).unwrap());
+        assert_eq!(Verdict::Allowed, security.file_authentication(None, Some(b"ELF"), true).unwrap());
+        let denied = DevicePath::from_bytes(&DENIED_PATH).unwrap();
+        assert_eq!(Verdict::Denied, security.file_authentication(Some(denied), Some(b"MZ"), false).unwrap());
+        assert_eq!(efi::Status::INVALID_PARAMETER, security.file_authentication(None, None, false).unwrap_err());
+    }
+}
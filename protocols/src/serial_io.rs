@@ -0,0 +1,443 @@
+//! Serial I/O protocol.
+//!
+//! [`SerialIo`] configures a serial port and transfers bytes over it, it implements [`fmt::Write`] to be used as a
+//! log output.
+//!
+//! [UEFI Spec Documentation: 12.8. Serial I/O Protocol](https://uefi.org/specs/UEFI/2.10/12_Protocols_Console_Support.html#serial-i-o-protocol)
+
+use core::{ffi::c_void, fmt, ops::Deref};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xbb25cf6f, 0xf1d4, 0x11d2, 0x9a, 0x0c, &[0x00, 0x90, 0x27, 0x3f, 0xc1, 0xfd]);
+
+pub const REVISION: u32 = 0x00010000;
+pub const REVISION1P1: u32 = 0x00010001;
+
+pub type ProtocolReset = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+pub type ProtocolSetAttributes = extern "efiapi" fn(*mut Protocol, u64, u32, u32, Parity, u8, StopBits) -> efi::Status;
+
+pub type ProtocolSetControl = extern "efiapi" fn(*mut Protocol, u32) -> efi::Status;
+
+pub type ProtocolGetControl = extern "efiapi" fn(*mut Protocol, *mut u32) -> efi::Status;
+
+pub type ProtocolWrite = extern "efiapi" fn(*mut Protocol, *mut usize, *mut c_void) -> efi::Status;
+
+pub type ProtocolRead = extern "efiapi" fn(*mut Protocol, *mut usize, *mut c_void) -> efi::Status;
+
+/// Parity of the transmitted bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u32)]
+pub enum Parity {
+    /// The default of the device.
+    #[default]
+    Default = 0,
+    No = 1,
+    Even = 2,
+    Odd = 3,
+    Mark = 4,
+    Space = 5,
+}
+
+/// Number of stop bits of the transmitted bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u32)]
+pub enum StopBits {
+    /// The default of the device.
+    #[default]
+    Default = 0,
+    One = 1,
+    OneFive = 2,
+    Two = 3,
+}
+
+/// FFI definition of `SERIAL_IO_MODE`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Mode {
+    pub control_mask: u32,
+    pub timeout: u32,
+    pub baud_rate: u64,
+    pub receive_fifo_depth: u32,
+    pub data_bits: u32,
+    pub parity: u32,
+    pub stop_bits: u32,
+}
+
+/// FFI definition of `EFI_SERIAL_IO_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub revision: u32,
+    pub reset: ProtocolReset,
+    pub set_attributes: ProtocolSetAttributes,
+    pub set_control: ProtocolSetControl,
+    pub get_control: ProtocolGetControl,
+    pub write: ProtocolWrite,
+    pub read: ProtocolRead,
+    pub mode: *mut Mode,
+    /// Only present from [`REVISION1P1`].
+    pub device_type_guid: *const efi::Guid,
+}
+
+/// Serial I/O protocol.
+pub struct SerialIoProtocol;
+
+unsafe impl ProtocolTrait for SerialIoProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for SerialIoProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Communication settings of a serial port, a value of 0 or `Default` keeps the default of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SerialAttributes {
+    pub baud_rate: u64,
+    pub receive_fifo_depth: u32,
+    /// Timeout of a read or a write, in microseconds.
+    pub timeout: u32,
+    pub parity: Parity,
+    pub data_bits: u8,
+    pub stop_bits: StopBits,
+}
+
+/// Typed access to an instance of the Serial I/O protocol.
+///
+/// ```ignore
+/// let mut serial = SerialIo::locate(&boot_services)?;
+/// serial.set_attributes(&SerialAttributes { baud_rate: 115200, data_bits: 8, ..Default::default() })?;
+/// writeln!(serial, "Hello")?;
+/// ```
+pub struct SerialIo(&'static mut Protocol);
+
+impl SerialIo {
+    /// Locates the first instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&SerialIoProtocol, None).map(Self)
+    }
+
+    /// Gets the instance of the protocol installed on a handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &SerialIoProtocol).map(Self)
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.0 as *mut Protocol
+    }
+
+    fn mode(&self) -> Mode {
+        //SAFETY: The mode of a valid protocol instance is valid.
+        unsafe { *self.0.mode }
+    }
+
+    /// Resets the serial device.
+    pub fn reset(&mut self) -> Result<(), EfiError> {
+        match (self.0.reset)(self.this()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The current communication settings.
+    pub fn attributes(&self) -> SerialAttributes {
+        let mode = self.mode();
+        let parity = match mode.parity {
+            1 => Parity::No,
+            2 => Parity::Even,
+            3 => Parity::Odd,
+            4 => Parity::Mark,
+            5 => Parity::Space,
+            _ => Parity::Default,
+        };
+        let stop_bits = match mode.stop_bits {
+            1 => StopBits::One,
+            2 => StopBits::OneFive,
+            3 => StopBits::Two,
+            _ => StopBits::Default,
+        };
+        SerialAttributes {
+            baud_rate: mode.baud_rate,
+            receive_fifo_depth: mode.receive_fifo_depth,
+            timeout: mode.timeout,
+            parity,
+            data_bits: mode.data_bits as u8,
+            stop_bits,
+        }
+    }
+
+    /// Sets the communication settings.
+    pub fn set_attributes(&mut self, attributes: &SerialAttributes) -> Result<(), EfiError> {
+        let SerialAttributes { baud_rate, receive_fifo_depth, timeout, parity, data_bits, stop_bits } = *attributes;
+        match (self.0.set_attributes)(self.this(), baud_rate, receive_fifo_depth, timeout, parity, data_bits, stop_bits)
+        {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets the timeout of a read or a write, in microseconds, keeping the other settings.
+    pub fn set_timeout(&mut self, timeout: u32) -> Result<(), EfiError> {
+        self.set_attributes(&SerialAttributes { timeout, ..self.attributes() })
+    }
+
+    /// Writes bytes until they are all written or the timeout expires, returns the number of bytes written.
+    pub fn write(&mut self, buffer: &[u8]) -> Result<usize, EfiError> {
+        let mut size = buffer.len();
+        // The buffer is only read by the protocol.
+        match (self.0.write)(self.this(), &mut size, buffer.as_ptr() as *mut c_void) {
+            efi::Status::TIMEOUT => Ok(size),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Writes every byte, waiting as long as needed.
+    pub fn write_all(&mut self, mut buffer: &[u8]) -> Result<(), EfiError> {
+        while !buffer.is_empty() {
+            let written = self.write(buffer)?;
+            buffer = &buffer[written..];
+        }
+        Ok(())
+    }
+
+    /// Reads bytes until the buffer is full or the timeout expires, returns the number of bytes read.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        let mut size = buffer.len();
+        match (self.0.read)(self.this(), &mut size, buffer.as_mut_ptr() as *mut c_void) {
+            efi::Status::TIMEOUT => Ok(size),
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Fills the buffer, waiting as long as needed.
+    pub fn read_exact(&mut self, mut buffer: &mut [u8]) -> Result<(), EfiError> {
+        while !buffer.is_empty() {
+            let read = self.read(buffer)?;
+            buffer = &mut buffer[read..];
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Write for SerialIo {
+    /// Writes the string, newlines are translated to `\r\n`.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut lines = s.split('\n');
+        if let Some(line) = lines.next() {
+            self.write_all(line.as_bytes()).map_err(|_| fmt::Error)?;
+        }
+        for line in lines {
+            self.write_all(b"\r\n").map_err(|_| fmt::Error)?;
+            self.write_all(line.as_bytes()).map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&'static mut Protocol> for SerialIo {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for SerialIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SerialIo").field("attributes", &self.attributes()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, fmt::Write, ptr, slice};
+
+    /// Serial port transferring at most 4 bytes per call, the protocol is the first field to be found from its
+    /// pointer.
+    #[repr(C)]
+    struct TestSerial {
+        protocol: Protocol,
+        mode: Mode,
+        written: RefCell<Vec<u8>>,
+        input: RefCell<VecDeque<u8>>,
+    }
+
+    fn test_serial<'a>(this: *mut Protocol) -> &'a mut TestSerial {
+        unsafe { &mut *(this as *mut TestSerial) }
+    }
+
+    extern "efiapi" fn reset(this: *mut Protocol) -> efi::Status {
+        test_serial(this).input.borrow_mut().clear();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_attributes(
+        this: *mut Protocol,
+        baud_rate: u64,
+        receive_fifo_depth: u32,
+        timeout: u32,
+        parity: Parity,
+        data_bits: u8,
+        stop_bits: StopBits,
+    ) -> efi::Status {
+        if data_bits != 0 && !(5..=8).contains(&data_bits) {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let mode = &mut test_serial(this).mode;
+        mode.baud_rate = if baud_rate == 0 { 115200 } else { baud_rate };
+        mode.receive_fifo_depth = if receive_fifo_depth == 0 { 1 } else { receive_fifo_depth };
+        mode.timeout = if timeout == 0 { 1000000 } else { timeout };
+        mode.parity = if parity == Parity::Default { Parity::No as u32 } else { parity as u32 };
+        mode.data_bits = if data_bits == 0 { 8 } else { data_bits as u32 };
+        mode.stop_bits = if stop_bits == StopBits::Default { StopBits::One as u32 } else { stop_bits as u32 };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_control(_: *mut Protocol, _: u32) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn get_control(_: *mut Protocol, _: *mut u32) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn write(this: *mut Protocol, size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        let requested = unsafe { *size };
+        let written = requested.min(4);
+        let buffer = unsafe { slice::from_raw_parts(buffer as *const u8, written) };
+        test_serial(this).written.borrow_mut().extend_from_slice(buffer);
+        unsafe { *size = written };
+        if written < requested {
+            efi::Status::TIMEOUT
+        } else {
+            efi::Status::SUCCESS
+        }
+    }
+
+    extern "efiapi" fn read(this: *mut Protocol, size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        let requested = unsafe { *size };
+        let mut input = test_serial(this).input.borrow_mut();
+        let read = requested.min(4).min(input.len());
+        for (i, byte) in input.drain(..read).enumerate() {
+            unsafe { (buffer as *mut u8).add(i).write(byte) };
+        }
+        unsafe { *size = read };
+        if read < requested {
+            efi::Status::TIMEOUT
+        } else {
+            efi::Status::SUCCESS
+        }
+    }
+
+    fn serial_io() -> (SerialIo, &'static TestSerial) {
+        let serial = Box::leak(Box::new(TestSerial {
+            protocol: Protocol {
+                revision: REVISION,
+                reset,
+                set_attributes,
+                set_control,
+                get_control,
+                write,
+                read,
+                mode: ptr::null_mut(),
+                device_type_guid: ptr::null(),
+            },
+            mode: Mode {
+                control_mask: 0,
+                timeout: 1000000,
+                baud_rate: 115200,
+                receive_fifo_depth: 1,
+                data_bits: 8,
+                parity: Parity::No as u32,
+                stop_bits: StopBits::One as u32,
+            },
+            written: RefCell::new(Vec::new()),
+            input: RefCell::new(VecDeque::new()),
+        }));
+        serial.protocol.mode = &mut serial.mode;
+        let serial_ptr = serial as *mut TestSerial as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<SerialIoProtocol, Protocol>()
+            .once()
+            .returning(move |_, _| Ok(unsafe { &mut (*(serial_ptr as *mut TestSerial)).protocol }));
+        (SerialIo::locate(&boot_services).unwrap(), unsafe { &*(serial_ptr as *const TestSerial) })
+    }
+
+    #[test]
+    fn test_attributes() {
+        let (mut serial_io, _) = serial_io();
+        let default = serial_io.attributes();
+        assert_eq!(
+            SerialAttributes {
+                baud_rate: 115200,
+                receive_fifo_depth: 1,
+                timeout: 1000000,
+                parity: Parity::No,
+                data_bits: 8,
+                stop_bits: StopBits::One
+            },
+            default
+        );
+
+        let attributes = SerialAttributes {
+            baud_rate: 9600,
+            parity: Parity::Even,
+            data_bits: 7,
+            stop_bits: StopBits::Two,
+            ..Default::default()
+        };
+        serial_io.set_attributes(&attributes).unwrap();
+        assert_eq!(SerialAttributes { receive_fifo_depth: 1, timeout: 1000000, ..attributes }, serial_io.attributes());
+
+        serial_io.set_timeout(50).unwrap();
+        assert_eq!(50, serial_io.attributes().timeout);
+        assert_eq!(9600, serial_io.attributes().baud_rate);
+
+        let invalid = SerialAttributes { data_bits: 9, ..Default::default() };
+        assert_eq!(efi::Status::INVALID_PARAMETER, serial_io.set_attributes(&invalid).unwrap_err());
+    }
+
+    #[test]
+    fn test_write() {
+        let (mut serial_io, serial) = serial_io();
+        assert_eq!(4, serial_io.write(b"Hello").unwrap());
+        serial_io.write_all(b" World").unwrap();
+        assert_eq!(b"Hell World", serial.written.borrow().as_slice());
+
+        serial.written.borrow_mut().clear();
+        write!(serial_io, "a\nb{}\n", 1).unwrap();
+        assert_eq!(b"a\r\nb1\r\n", serial.written.borrow().as_slice());
+    }
+
+    #[test]
+    fn test_read() {
+        let (mut serial_io, serial) = serial_io();
+        serial.input.borrow_mut().extend(b"0123456789");
+
+        let mut buffer = [0; 6];
+        assert_eq!(4, serial_io.read(&mut buffer).unwrap());
+        assert_eq!(b"0123", &buffer[..4]);
+        serial_io.read_exact(&mut buffer).unwrap();
+        assert_eq!(b"456789", &buffer);
+        assert_eq!(0, serial_io.read(&mut buffer).unwrap());
+
+        serial.input.borrow_mut().extend(b"abc");
+        serial_io.reset().unwrap();
+        assert_eq!(0, serial_io.read(&mut buffer).unwrap());
+    }
+}
@@ -0,0 +1,255 @@
+//! Service Binding protocol.
+//!
+//! [`ServiceBindingChild`] creates a child with the service binding protocol of a service handle and gives access to
+//! the protocol of the child for its lifetime.
+//!
+//! [UEFI Spec Documentation: 11.7. EFI Service Binding Protocol](https://uefi.org/specs/UEFI/2.10/11_Protocols_UEFI_Driver_Model.html#efi-service-binding-protocol)
+
+use core::{
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+use boot_services::{
+    protocol_handler::{self, OpenProtocolAttribute, OpenedProtocol, Protocol},
+    BootServices,
+};
+use r_efi::efi;
+use status::EfiError;
+
+type ServiceBindingProtocol = efi::protocols::service_binding::Protocol;
+
+/// Protocol that is produced on children created with a service binding protocol.
+pub trait ChildProtocol: Protocol + 'static {
+    /// The GUID of the service binding protocol that creates the children.
+    fn service_binding_guid(&self) -> &'static efi::Guid;
+}
+
+macro_rules! impl_child_protocol {
+    ($protocol_struct:ident, $protocol:ident) => {
+        impl ChildProtocol for protocol_handler::$protocol_struct {
+            fn service_binding_guid(&self) -> &'static efi::Guid {
+                &efi::protocols::$protocol::SERVICE_BINDING_PROTOCOL_GUID
+            }
+        }
+    };
+}
+
+impl_child_protocol!(Ip4, ip4);
+impl_child_protocol!(Ip6, ip6);
+impl_child_protocol!(ManagedNetwork, managed_network);
+impl_child_protocol!(Tcp4, tcp4);
+impl_child_protocol!(Tcp6, tcp6);
+impl_child_protocol!(Udp4, udp4);
+impl_child_protocol!(Udp6, udp6);
+
+/// RAII implementation of a child created with a service binding protocol.
+/// When this structure is dropped, the protocol of the child is closed and the child is destroyed.
+#[must_use = "if unused the child will immediately be destroyed"]
+pub struct ServiceBindingChild<'a, P: ChildProtocol, B: BootServices> {
+    service_binding: NonNull<ServiceBindingProtocol>,
+    child_handle: efi::Handle,
+    protocol: ManuallyDrop<OpenedProtocol<'a, P, B>>,
+}
+
+impl<'a, P: ChildProtocol, B: BootServices> ServiceBindingChild<'a, P, B> {
+    /// Creates a child with the service binding protocol of the service handle and opens its protocol with
+    /// [`OpenProtocolAttribute::BY_DRIVER`] on behalf of the agent, using the service handle as controller.
+    ///
+    /// Returns `DEVICE_ERROR` if the service binding protocol reports success without a child handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn create(
+        boot_services: &'a B,
+        service_handle: efi::Handle,
+        protocol: &P,
+        agent_handle: efi::Handle,
+    ) -> Result<Self, EfiError> {
+        //SAFETY: The service binding GUID is associated with the service binding protocol interface.
+        let service_binding =
+            unsafe { boot_services.handle_protocol_unchecked(service_handle, protocol.service_binding_guid()) }?
+                as *mut ServiceBindingProtocol;
+        let service_binding = NonNull::new(service_binding).ok_or(efi::Status::UNSUPPORTED)?;
+
+        let mut child_handle = ptr::null_mut();
+        //SAFETY: The service binding protocol interface is valid while it is installed.
+        match unsafe { (service_binding.as_ref().create_child)(service_binding.as_ptr(), &mut child_handle) } {
+            status if status.is_error() => return Err(status.into()),
+            // Nothing was created that could be destroyed.
+            _ if child_handle.is_null() => return Err(efi::Status::DEVICE_ERROR.into()),
+            _ => (),
+        }
+
+        match OpenedProtocol::open(
+            boot_services,
+            child_handle,
+            protocol,
+            agent_handle,
+            service_handle,
+            OpenProtocolAttribute::BY_DRIVER,
+        ) {
+            Ok(protocol) => Ok(Self { service_binding, child_handle, protocol: ManuallyDrop::new(protocol) }),
+            Err(status) => {
+                //SAFETY: The child was created above with the same service binding protocol.
+                unsafe { (service_binding.as_ref().destroy_child)(service_binding.as_ptr(), child_handle) };
+                Err(status)
+            }
+        }
+    }
+
+    /// The handle of the child.
+    pub fn child_handle(&self) -> efi::Handle {
+        self.child_handle
+    }
+}
+
+impl<P: ChildProtocol, B: BootServices> Drop for ServiceBindingChild<'_, P, B> {
+    fn drop(&mut self) {
+        //SAFETY: The protocol is not used after this point.
+        unsafe { ManuallyDrop::drop(&mut self.protocol) };
+        //SAFETY: The child was created with this service binding protocol.
+        unsafe { (self.service_binding.as_ref().destroy_child)(self.service_binding.as_ptr(), self.child_handle) };
+    }
+}
+
+impl<P: ChildProtocol, B: BootServices> Deref for ServiceBindingChild<'_, P, B> {
+    type Target = P::Interface;
+
+    fn deref(&self) -> &Self::Target {
+        &self.protocol
+    }
+}
+
+impl<P: ChildProtocol, B: BootServices> DerefMut for ServiceBindingChild<'_, P, B> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.protocol
+    }
+}
+
+impl<P: ChildProtocol, B: BootServices> fmt::Debug for ServiceBindingChild<'_, P, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceBindingChild")
+            .field("service_binding", &self.service_binding)
+            .field("child_handle", &self.child_handle)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use core::{
+        ffi::c_void,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+
+    extern "efiapi" fn create_child(_this: *mut ServiceBindingProtocol, child_handle: *mut efi::Handle) -> efi::Status {
+        unsafe { *child_handle = 2_usize as efi::Handle };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn destroy_child(_this: *mut ServiceBindingProtocol, child_handle: efi::Handle) -> efi::Status {
+        assert_eq!(2_usize as efi::Handle, child_handle);
+        DESTROYED.fetch_add(1, Ordering::SeqCst);
+        efi::Status::SUCCESS
+    }
+
+    static mut SERVICE_BINDING: ServiceBindingProtocol = ServiceBindingProtocol { create_child, destroy_child };
+
+    #[test]
+    fn test_service_binding_child() {
+        static mut TCP4: u64 = 0;
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol_unchecked()
+            .withf(|handle, protocol| {
+                *handle == 1_usize as efi::Handle && *protocol == efi::protocols::tcp4::SERVICE_BINDING_PROTOCOL_GUID
+            })
+            .returning(|_, _| Ok(unsafe { ptr::addr_of_mut!(SERVICE_BINDING) } as *mut c_void));
+        boot_services
+            .expect_open_protocol_unchecked()
+            .withf(|handle, protocol, agent_handle, controller_handle, attribute| {
+                *handle == 2_usize as efi::Handle
+                    && *protocol == efi::protocols::tcp4::PROTOCOL_GUID
+                    && *agent_handle == 3_usize as efi::Handle
+                    && *controller_handle == 1_usize as efi::Handle
+                    && *attribute == efi::OPEN_PROTOCOL_BY_DRIVER
+            })
+            .once()
+            .returning(|_, _, _, _, _| Ok(unsafe { ptr::addr_of_mut!(TCP4) } as *mut c_void));
+        boot_services
+            .expect_close_protocol()
+            .withf(|handle, _, _, _| *handle == 2_usize as efi::Handle)
+            .once()
+            .returning(|_, _, _, _| {
+                assert_eq!(
+                    0,
+                    DESTROYED.load(Ordering::SeqCst),
+                    "The protocol must be closed before the child is destroyed."
+                );
+                Ok(())
+            });
+
+        let child = ServiceBindingChild::create(
+            &boot_services,
+            1_usize as efi::Handle,
+            &protocol_handler::Tcp4,
+            3_usize as efi::Handle,
+        )
+        .unwrap();
+        assert_eq!(2_usize as efi::Handle, child.child_handle());
+        assert_eq!(unsafe { ptr::addr_of_mut!(TCP4) } as *const _ as usize, &*child as *const _ as usize);
+        drop(child);
+        assert_eq!(1, DESTROYED.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_service_binding_child_without_service() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_handle_protocol_unchecked().returning(|_, _| Err(efi::Status::UNSUPPORTED.into()));
+
+        let child = ServiceBindingChild::create(
+            &boot_services,
+            1_usize as efi::Handle,
+            &protocol_handler::Udp4,
+            3_usize as efi::Handle,
+        );
+        assert!(matches!(child, Err(error) if error == efi::Status::UNSUPPORTED));
+    }
+
+    #[test]
+    fn test_service_binding_child_without_handle() {
+        extern "efiapi" fn create_null_child(
+            _this: *mut ServiceBindingProtocol,
+            _child_handle: *mut efi::Handle,
+        ) -> efi::Status {
+            efi::Status::SUCCESS
+        }
+        extern "efiapi" fn destroy_null_child(
+            _this: *mut ServiceBindingProtocol,
+            _child_handle: efi::Handle,
+        ) -> efi::Status {
+            panic!("No child was created.")
+        }
+        static mut NULL_SERVICE_BINDING: ServiceBindingProtocol =
+            ServiceBindingProtocol { create_child: create_null_child, destroy_child: destroy_null_child };
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol_unchecked()
+            .returning(|_, _| Ok(unsafe { ptr::addr_of_mut!(NULL_SERVICE_BINDING) } as *mut c_void));
+
+        let child = ServiceBindingChild::create(
+            &boot_services,
+            1_usize as efi::Handle,
+            &protocol_handler::Udp4,
+            3_usize as efi::Handle,
+        );
+        assert!(matches!(child, Err(error) if error == efi::Status::DEVICE_ERROR));
+    }
+}
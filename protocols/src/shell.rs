@@ -0,0 +1,592 @@
+//! Shell and Shell Parameters protocols.
+//!
+//! A shell application finds its command line arguments in [`ShellParameters`] on its image handle, and the services of
+//! the shell running it in [`Shell`]:
+//!
+//! ```ignore
+//! let parameters = ShellParameters::get(&boot_services, image_handle)?;
+//! let shell = Shell::locate(&boot_services)?;
+//! for path in parameters.args().skip(1) {
+//!     let content = shell.open_file_by_name(path, OpenMode::Read)?.read_to_end()?;
+//!     ...
+//! }
+//! let status = shell.execute(image_handle, &String16::try_from("map -r")?)?;
+//! ```
+//!
+//! [UEFI Shell Specification 2.2: 2.2. EFI_SHELL_PROTOCOL](https://uefi.org/sites/default/files/resources/UEFI_Shell_2_2.pdf)
+
+use alloc::vec::Vec;
+use core::{ffi::c_void, fmt, ptr};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+
+use crate::media::OpenMode;
+
+type ShellProtocol = efi::protocols::shell::Protocol;
+
+type ShellParametersProtocol = efi::protocols::shell_parameters::Protocol;
+
+/// Typed access to the Shell protocol.
+pub struct Shell(&'static mut ShellProtocol);
+
+impl Shell {
+    /// Locates the protocol of the running shell.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::Shell, None).map(Self)
+    }
+
+    /// The major and minor version of the shell.
+    pub fn version(&self) -> (u32, u32) {
+        (self.0.major_version, self.0.minor_version)
+    }
+
+    /// The value of an environment variable.
+    pub fn get_env(&self, name: &Str16) -> Option<String16> {
+        // The name is only read by the shell.
+        let value = unsafe { r_efi::efi_call(self.0.get_env, (name.as_ptr() as *mut u16,)) };
+        //SAFETY: The value is a null-terminated string owned by the shell, copied before the environment can change.
+        (!value.is_null()).then(|| String16::from(unsafe { Str16::from_ptr(value) }))
+    }
+
+    /// The names of all environment variables.
+    pub fn env_names(&self) -> Vec<String16> {
+        let mut names = Vec::new();
+        let mut name = unsafe { r_efi::efi_call(self.0.get_env, (ptr::null_mut(),)) };
+        if name.is_null() {
+            return names;
+        }
+        // The names follow each other with their null terminator, the list ends with an empty name.
+        //SAFETY: The list is owned by the shell and is copied before the environment can change.
+        while unsafe { name.read_unaligned() } != 0 {
+            let entry = unsafe { Str16::from_ptr(name) };
+            name = unsafe { name.add(entry.as_slice_with_nul().len()) };
+            names.push(String16::from(entry));
+        }
+        names
+    }
+
+    /// Sets an environment variable, an empty value removes it.
+    ///
+    /// Volatile variables are lost when the shell exits, others are saved in a UEFI variable.
+    pub fn set_env(&mut self, name: &Str16, value: &Str16, volatile: bool) -> Result<(), EfiError> {
+        // The strings are only read by the shell.
+        match unsafe {
+            r_efi::efi_call(self.0.set_env, (name.as_ptr() as *mut u16, value.as_ptr() as *mut u16, volatile.into()))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The current directory of a file system mapping, or of the current file system if `None`.
+    pub fn current_dir(&self, file_system_mapping: Option<&Str16>) -> Option<String16> {
+        let mapping = file_system_mapping.map_or(ptr::null_mut(), |mapping| mapping.as_ptr() as *mut u16);
+        // The mapping is only read by the shell.
+        let dir = unsafe { r_efi::efi_call(self.0.get_cur_dir, (mapping,)) };
+        //SAFETY: The directory is a null-terminated string owned by the shell, copied before it can change.
+        (!dir.is_null()).then(|| String16::from(unsafe { Str16::from_ptr(dir) }))
+    }
+
+    /// Runs a command line in the current environment and waits for it to finish.
+    ///
+    /// Returns the status the command exited with, or an error if the shell could not run it.
+    pub fn execute(&self, parent_image_handle: efi::Handle, command_line: &Str16) -> Result<efi::Status, EfiError> {
+        let mut handle = parent_image_handle;
+        let mut status = efi::Status::SUCCESS;
+        // The command line is only read by the shell.
+        match unsafe {
+            r_efi::efi_call(
+                self.0.execute,
+                (&mut handle, command_line.as_ptr() as *mut u16, ptr::null_mut(), &mut status),
+            )
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(status),
+        }
+    }
+
+    /// Opens a file from a path resolved through the mappings and current directory of the shell.
+    ///
+    /// The path can also be one of the shell's special files, like `NUL` or `CONOUT:`.
+    pub fn open_file_by_name(&self, path: &Str16, mode: OpenMode) -> Result<ShellFile, EfiError> {
+        let mut handle = ptr::null_mut();
+        // The path is only read by the shell.
+        match unsafe {
+            r_efi::efi_call(self.0.open_file_by_name, (path.as_ptr() as *mut u16, &mut handle, mode.into()))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ if handle.is_null() => Err(efi::Status::DEVICE_ERROR.into()),
+            _ => Ok(ShellFile { shell: self.0, handle }),
+        }
+    }
+}
+
+impl From<&'static mut ShellProtocol> for Shell {
+    fn from(protocol: &'static mut ShellProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shell").field("version", &self.version()).finish()
+    }
+}
+
+/// A file opened through the shell, closed on drop.
+pub struct ShellFile {
+    shell: *const ShellProtocol,
+    handle: efi::protocols::shell::FileHandle,
+}
+
+impl ShellFile {
+    fn shell(&self) -> &ShellProtocol {
+        //SAFETY: The shell protocol outlives the files it opened.
+        unsafe { &*self.shell }
+    }
+
+    /// The handle of the file, to give to the shell protocol.
+    pub fn handle(&self) -> efi::protocols::shell::FileHandle {
+        self.handle
+    }
+
+    /// Reads from the current position, returns the number of bytes read, 0 at the end of the file.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        let mut size = buffer.len();
+        match unsafe {
+            r_efi::efi_call(self.shell().read_file, (self.handle, &mut size, buffer.as_mut_ptr() as *mut c_void))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Reads from the current position to the end of the file.
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>, EfiError> {
+        let mut content = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            match self.read(&mut chunk)? {
+                0 => return Ok(content),
+                read => content.extend_from_slice(&chunk[..read]),
+            }
+        }
+    }
+
+    /// Writes at the current position, returns the number of bytes written.
+    pub fn write(&mut self, buffer: &[u8]) -> Result<usize, EfiError> {
+        let mut size = buffer.len();
+        // The buffer is only read by the shell.
+        match unsafe {
+            r_efi::efi_call(self.shell().write_file, (self.handle, &mut size, buffer.as_ptr() as *mut c_void))
+        } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Writes the whole buffer at the current position.
+    pub fn write_all(&mut self, mut buffer: &[u8]) -> Result<(), EfiError> {
+        while !buffer.is_empty() {
+            match self.write(buffer)? {
+                0 => return Err(efi::Status::VOLUME_FULL.into()),
+                written => buffer = &buffer[written..],
+            }
+        }
+        Ok(())
+    }
+
+    /// The size of the file.
+    pub fn size(&self) -> Result<u64, EfiError> {
+        let mut size = 0;
+        match unsafe { r_efi::efi_call(self.shell().get_file_size, (self.handle, &mut size)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(size),
+        }
+    }
+
+    /// Sets the current position in the file, `u64::MAX` moves to the end of the file.
+    pub fn set_position(&mut self, position: u64) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.shell().set_file_position, (self.handle, position)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the data of the file that is still buffered.
+    pub fn flush(&mut self) -> Result<(), EfiError> {
+        match unsafe { r_efi::efi_call(self.shell().flush_file, (self.handle,)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Closes the file, reporting the error the drop would ignore.
+    pub fn close(self) -> Result<(), EfiError> {
+        let status = unsafe { r_efi::efi_call(self.shell().close_file, (self.handle,)) };
+        core::mem::forget(self);
+        match status {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for ShellFile {
+    fn drop(&mut self) {
+        let _ = unsafe { r_efi::efi_call(self.shell().close_file, (self.handle,)) };
+    }
+}
+
+impl fmt::Debug for ShellFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ShellFile").field(&self.handle).finish()
+    }
+}
+
+/// Typed access to the Shell Parameters protocol of a shell application.
+pub struct ShellParameters(&'static mut ShellParametersProtocol);
+
+impl ShellParameters {
+    /// Gets the parameters the shell installed on the image handle of the application.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn get<B: BootServices>(boot_services: &B, image_handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(image_handle, &protocol_handler::ShellParameters).map(Self)
+    }
+
+    /// The command line arguments, starting with the name of the application.
+    pub fn args(&self) -> impl ExactSizeIterator<Item = &Str16> + '_ {
+        let argv = if self.0.argv.is_null() {
+            &[][..]
+        } else {
+            //SAFETY: The shell gives `argc` arguments that live as long as the protocol.
+            unsafe { core::slice::from_raw_parts(self.0.argv, self.0.argc) }
+        };
+        //SAFETY: Each argument is a null-terminated string owned by the shell.
+        argv.iter().map(|&arg| unsafe { Str16::from_ptr(arg) })
+    }
+}
+
+impl From<&'static mut ShellParametersProtocol> for ShellParameters {
+    fn from(protocol: &'static mut ShellParametersProtocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for ShellParameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.args()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, string::String, vec};
+    use boot_services::MockBootServices;
+    use core::{cell::RefCell, slice};
+    use efi::protocols::{device_path, file, shell::FileHandle, shell::FileInfo};
+    use std::collections::BTreeMap;
+
+    std::thread_local! {
+        /// Environment of the shell of the test.
+        static ENV: RefCell<BTreeMap<String, String16>> = const { RefCell::new(BTreeMap::new()) };
+        /// The last environment list given out, kept alive like the shell would.
+        static ENV_NAMES: RefCell<Vec<u16>> = const { RefCell::new(Vec::new()) };
+        /// The command lines run by the shell.
+        static COMMANDS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+        /// The content of the files closed by the shell.
+        static CLOSED: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// A file open in the shell, with its position.
+    struct TestFile(Vec<u8>, usize);
+
+    fn to_string(s: *mut u16) -> String {
+        unsafe { Str16::from_ptr(s) }.to_string_lossy()
+    }
+
+    extern "efiapi" fn execute(
+        parent: *mut efi::Handle,
+        command_line: *mut u16,
+        _environment: *mut *mut u16,
+        status: *mut efi::Status,
+    ) -> efi::Status {
+        assert_eq!(7, unsafe { *parent } as usize);
+        let command_line = to_string(command_line);
+        let result = match command_line.as_str() {
+            "missing" => return efi::Status::NOT_FOUND,
+            "exit 1" => efi::Status::ABORTED,
+            _ => efi::Status::SUCCESS,
+        };
+        COMMANDS.with_borrow_mut(|commands| commands.push(command_line));
+        unsafe { status.write(result) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_env(name: *mut u16) -> *mut u16 {
+        if name.is_null() {
+            return ENV_NAMES.with_borrow_mut(|names| {
+                *names = ENV.with_borrow(|env| env.keys().flat_map(|name| name.encode_utf16().chain([0])).collect());
+                names.push(0);
+                names.as_mut_ptr()
+            });
+        }
+        ENV.with_borrow(|env| match env.get(&to_string(name)) {
+            Some(value) => value.as_ptr() as *mut u16,
+            None => ptr::null_mut(),
+        })
+    }
+
+    extern "efiapi" fn set_env(name: *mut u16, value: *mut u16, _volatile: efi::Boolean) -> efi::Status {
+        let value = unsafe { Str16::from_ptr(value) };
+        ENV.with_borrow_mut(|env| match value.as_slice().is_empty() {
+            true => env.remove(&to_string(name)),
+            false => env.insert(to_string(name), value.into()),
+        });
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_cur_dir(mapping: *mut u16) -> *mut u16 {
+        static FS0: &[u16] = &[b'F' as u16, b'S' as u16, b'0' as u16, b':' as u16, b'\\' as u16, 0];
+        match mapping.is_null() || to_string(mapping) == "FS0:" {
+            true => FS0.as_ptr() as *mut u16,
+            false => ptr::null_mut(),
+        }
+    }
+
+    extern "efiapi" fn open_file_by_name(path: *mut u16, handle: *mut FileHandle, mode: u64) -> efi::Status {
+        let content = match to_string(path).as_str() {
+            r"FS0:\hello.txt" => b"hello".to_vec(),
+            _ if mode & file::MODE_CREATE != 0 => Vec::new(),
+            _ => return efi::Status::NOT_FOUND,
+        };
+        unsafe { handle.write(Box::into_raw(Box::new(TestFile(content, 0))) as FileHandle) };
+        efi::Status::SUCCESS
+    }
+
+    fn test_file<'a>(handle: FileHandle) -> &'a mut TestFile {
+        unsafe { &mut *(handle as *mut TestFile) }
+    }
+
+    extern "efiapi" fn close_file(handle: FileHandle) -> efi::Status {
+        let file = unsafe { Box::from_raw(handle as *mut TestFile) };
+        CLOSED.with_borrow_mut(|closed| closed.push(file.0));
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_file(handle: FileHandle, size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        let file = test_file(handle);
+        let read = unsafe { *size }.min(file.0.len() - file.1);
+        unsafe { slice::from_raw_parts_mut(buffer as *mut u8, read) }.copy_from_slice(&file.0[file.1..file.1 + read]);
+        file.1 += read;
+        unsafe { size.write(read) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn write_file(handle: FileHandle, size: *mut usize, buffer: *mut c_void) -> efi::Status {
+        let file = test_file(handle);
+        let data = unsafe { slice::from_raw_parts(buffer as *const u8, *size) };
+        file.0.truncate(file.1);
+        file.0.extend_from_slice(data);
+        file.1 += data.len();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_file_position(handle: FileHandle, position: u64) -> efi::Status {
+        let file = test_file(handle);
+        file.1 = (position as usize).min(file.0.len());
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_file_size(handle: FileHandle, size: *mut u64) -> efi::Status {
+        unsafe { size.write(test_file(handle).0.len() as u64) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn flush_file(_: FileHandle) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    /// Services of the shell that are not used by the wrapper.
+    macro_rules! unused {
+        ($($name:ident($($arg:ty),*) $(-> $ret:ty)?;)*) => {
+            $(extern "efiapi" fn $name($(_: $arg),*) $(-> $ret)? {
+                unimplemented!()
+            })*
+        };
+    }
+
+    unused! {
+        get_alias(*mut u16, *mut efi::Boolean) -> *mut u16;
+        set_alias(*mut u16, *mut u16, efi::Boolean, efi::Boolean) -> efi::Status;
+        get_help_text(*mut u16, *mut u16, *mut *mut u16) -> efi::Status;
+        get_device_path_from_map(*mut u16) -> *mut device_path::Protocol;
+        get_map_from_device_path(*mut *mut device_path::Protocol) -> *mut u16;
+        get_device_path_from_file_path(*mut u16) -> *mut device_path::Protocol;
+        get_file_path_from_device_path(*mut device_path::Protocol) -> *mut u16;
+        set_map(*mut device_path::Protocol, *mut u16) -> efi::Status;
+        set_cur_dir(*mut u16, *mut u16) -> efi::Status;
+        open_file_list(*mut u16, u64, *mut *mut FileInfo) -> efi::Status;
+        free_file_list(*mut *mut FileInfo) -> efi::Status;
+        remove_dup_in_file_list(*mut *mut FileInfo) -> efi::Status;
+        batch_is_active() -> efi::Boolean;
+        is_root_shell() -> efi::Boolean;
+        enable_page_break();
+        disable_page_break();
+        get_page_break() -> efi::Boolean;
+        get_device_name(efi::Handle, u32, *mut efi::Char8, *mut *mut u16) -> efi::Status;
+        get_file_info(FileHandle) -> *mut file::Info;
+        set_file_info(FileHandle, *mut file::Info) -> efi::Status;
+        create_file(*mut u16, u64, *mut FileHandle) -> efi::Status;
+        delete_file(FileHandle) -> efi::Status;
+        delete_file_by_name(*mut u16) -> efi::Status;
+        get_file_position(FileHandle, *mut u64) -> efi::Status;
+        find_files(*mut u16, *mut *mut FileInfo) -> efi::Status;
+        find_files_in_dir(FileHandle, *mut *mut FileInfo) -> efi::Status;
+        open_root(*mut device_path::Protocol, *mut FileHandle) -> efi::Status;
+        open_root_by_handle(efi::Handle, *mut FileHandle) -> efi::Status;
+        register_guid_name(*mut efi::Guid, *mut u16) -> efi::Status;
+        get_guid_name(*mut efi::Guid, *mut *mut u16) -> efi::Status;
+        get_guid_from_name(*mut u16, *mut efi::Guid) -> efi::Status;
+        get_env_ex(*mut u16, *mut u32) -> *mut u16;
+    }
+
+    fn shell() -> Shell {
+        Shell::from(Box::leak(Box::new(ShellProtocol {
+            execute,
+            get_env,
+            set_env,
+            get_alias,
+            set_alias,
+            get_help_text,
+            get_device_path_from_map,
+            get_map_from_device_path,
+            get_device_path_from_file_path,
+            get_file_path_from_device_path,
+            set_map,
+            get_cur_dir,
+            set_cur_dir,
+            open_file_list,
+            free_file_list,
+            remove_dup_in_file_list,
+            batch_is_active,
+            is_root_shell,
+            enable_page_break,
+            disable_page_break,
+            get_page_break,
+            get_device_name,
+            get_file_info,
+            set_file_info,
+            open_file_by_name,
+            close_file,
+            create_file,
+            read_file,
+            write_file,
+            delete_file,
+            delete_file_by_name,
+            get_file_position,
+            set_file_position,
+            flush_file,
+            find_files,
+            find_files_in_dir,
+            get_file_size,
+            open_root,
+            open_root_by_handle,
+            execution_break: ptr::null_mut(),
+            major_version: 2,
+            minor_version: 2,
+            register_guid_name,
+            get_guid_name,
+            get_guid_from_name,
+            get_env_ex,
+        })))
+    }
+
+    fn s(s: &str) -> String16 {
+        String16::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_env() {
+        let mut shell = shell();
+        assert_eq!((2, 2), shell.version());
+        assert!(shell.get_env(&s("path")).is_none());
+        assert!(shell.env_names().is_empty());
+
+        shell.set_env(&s("path"), &s(r"FS0:\efi\tools"), true).unwrap();
+        shell.set_env(&s("profiles"), &s("Debug1"), false).unwrap();
+        assert_eq!(shell.get_env(&s("path")).unwrap(), r"FS0:\efi\tools");
+        assert_eq!(vec![s("path"), s("profiles")], shell.env_names());
+
+        shell.set_env(&s("path"), &s(""), true).unwrap();
+        assert!(shell.get_env(&s("path")).is_none());
+        assert_eq!(vec![s("profiles")], shell.env_names());
+    }
+
+    #[test]
+    fn test_current_dir() {
+        let shell = shell();
+        assert_eq!(shell.current_dir(None).unwrap(), r"FS0:\");
+        assert_eq!(shell.current_dir(Some(&s("FS0:"))).unwrap(), r"FS0:\");
+        assert!(shell.current_dir(Some(&s("FS1:"))).is_none());
+    }
+
+    #[test]
+    fn test_execute() {
+        let shell = shell();
+        let parent = 7_usize as efi::Handle;
+        assert_eq!(Ok(efi::Status::SUCCESS), shell.execute(parent, &s("ls -r")));
+        assert_eq!(Ok(efi::Status::ABORTED), shell.execute(parent, &s("exit 1")));
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), shell.execute(parent, &s("missing")));
+        assert_eq!(vec!["ls -r", "exit 1"], COMMANDS.with_borrow(|commands| commands.clone()));
+    }
+
+    #[test]
+    fn test_files() {
+        let shell = shell();
+        let mut file = shell.open_file_by_name(&s(r"FS0:\hello.txt"), OpenMode::Read).unwrap();
+        assert_eq!(5, file.size().unwrap());
+        assert_eq!(b"hello".to_vec(), file.read_to_end().unwrap());
+        file.set_position(1).unwrap();
+        let mut buffer = [0; 2];
+        assert_eq!(2, file.read(&mut buffer).unwrap());
+        assert_eq!(b"el", &buffer);
+        drop(file);
+
+        assert_eq!(efi::Status::NOT_FOUND, shell.open_file_by_name(&s(r"FS0:\log.txt"), OpenMode::Read).unwrap_err());
+        let mut log = shell.open_file_by_name(&s(r"FS0:\log.txt"), OpenMode::Create).unwrap();
+        log.write_all(b"passed").unwrap();
+        log.flush().unwrap();
+        log.close().unwrap();
+        assert_eq!(vec![b"hello".to_vec(), b"passed".to_vec()], CLOSED.with_borrow(|closed| closed.clone()));
+    }
+
+    #[test]
+    fn test_parameters() {
+        let args = [s(r"FS0:\runner.efi"), s("-v"), s("tests")];
+        let mut argv = args.iter().map(|arg| arg.as_ptr() as *mut u16).collect::<Vec<_>>();
+        let mut protocol = ShellParametersProtocol {
+            argv: argv.as_mut_ptr(),
+            argc: argv.len(),
+            std_in: ptr::null_mut(),
+            std_out: ptr::null_mut(),
+            std_err: ptr::null_mut(),
+        };
+        let protocol_ptr = &mut protocol as *mut ShellParametersProtocol as usize;
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol::<protocol_handler::ShellParameters, ShellParametersProtocol>()
+            .withf(|handle, _| *handle as usize == 3)
+            .returning(move |_, _| Ok(unsafe { &mut *(protocol_ptr as *mut ShellParametersProtocol) }));
+
+        let parameters = ShellParameters::get(&boot_services, 3_usize as efi::Handle).unwrap();
+        assert_eq!(3, parameters.args().len());
+        assert_eq!(args.to_vec(), parameters.args().map(String16::from).collect::<Vec<_>>());
+        assert_eq!(vec!["-v", "tests"], parameters.args().skip(1).map(|arg| arg.to_string_lossy()).collect::<Vec<_>>());
+    }
+}
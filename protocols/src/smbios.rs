@@ -0,0 +1,539 @@
+//! SMBIOS structures published by the firmware.
+//!
+//! [`SmbiosTables`] finds the structure table from the SMBIOS entry point of the configuration tables and iterates
+//! over its structures, with typed access to the common ones for inventory tools:
+//!
+//! ```ignore
+//! let tables = SmbiosTables::from_system_table(entry_point::system_table())?;
+//! for device in tables.structures_of::<MemoryDevice>() {
+//!     log::info!("{:?}: {:?} MiB", device.device_locator(), device.size_mib());
+//! }
+//! ```
+//!
+//! [`producer::SmbiosProducer`] publishes structures through the SMBIOS protocol.
+//!
+//! [DMTF SMBIOS Specification](https://www.dmtf.org/standards/smbios)
+
+use core::{slice, str};
+
+use boot_services::configuration_table::{self, Smbios, Smbios3};
+use r_efi::efi;
+use status::EfiError;
+
+pub mod producer;
+
+/// Size of the header of every structure.
+const HEADER_SIZE: usize = 4;
+
+/// Type of the structure that ends the table.
+pub const END_OF_TABLE: u8 = 127;
+
+/// A structure of the table, its formatted area followed by its strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Structure<'a> {
+    formatted: &'a [u8],
+    /// The strings, separated by null characters.
+    strings: &'a [u8],
+}
+
+impl<'a> Structure<'a> {
+    pub fn structure_type(&self) -> u8 {
+        self.formatted[0]
+    }
+
+    pub fn handle(&self) -> u16 {
+        u16::from_le_bytes([self.formatted[2], self.formatted[3]])
+    }
+
+    /// The formatted area of the structure, including its header.
+    pub fn formatted(&self) -> &'a [u8] {
+        self.formatted
+    }
+
+    /// The byte at `offset` of the formatted area, `None` if the structure is too short, as in older versions.
+    pub fn byte(&self, offset: usize) -> Option<u8> {
+        self.formatted.get(offset).copied()
+    }
+
+    /// The little-endian word at `offset` of the formatted area.
+    pub fn word(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(self.formatted.get(offset..offset + 2)?.try_into().unwrap()))
+    }
+
+    /// The little-endian double word at `offset` of the formatted area.
+    pub fn dword(&self, offset: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(self.formatted.get(offset..offset + 4)?.try_into().unwrap()))
+    }
+
+    /// The little-endian quad word at `offset` of the formatted area.
+    pub fn qword(&self, offset: usize) -> Option<u64> {
+        Some(u64::from_le_bytes(self.formatted.get(offset..offset + 8)?.try_into().unwrap()))
+    }
+
+    /// The string of number `index`, from 1, `None` for 0, a missing string or a string that is not UTF-8.
+    pub fn string(&self, index: u8) -> Option<&'a str> {
+        if index == 0 || self.strings.is_empty() {
+            return None;
+        }
+        let string = self.strings.split(|byte| *byte == 0).nth(index as usize - 1)?;
+        str::from_utf8(string).ok()
+    }
+
+    /// The string whose number is the byte at `offset` of the formatted area.
+    pub fn string_at(&self, offset: usize) -> Option<&'a str> {
+        self.string(self.byte(offset)?)
+    }
+}
+
+/// Iterator over the structures of a table, returned by [`SmbiosTables::structures`].
+///
+/// The iteration stops at the end of table structure, and after the first malformed structure, which is an
+/// `INVALID_PARAMETER` error.
+#[derive(Debug, Clone)]
+pub struct Structures<'a> {
+    data: &'a [u8],
+    done: bool,
+}
+
+impl<'a> Structures<'a> {
+    fn parse(&mut self) -> Result<Option<Structure<'a>>, EfiError> {
+        if self.data.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+        let length = self.data[1] as usize;
+        if length < HEADER_SIZE || length > self.data.len() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let (formatted, rest) = self.data.split_at(length);
+        // The strings end with a double null, which is also the whole string set of a structure without strings.
+        let end = rest.windows(2).position(|bytes| bytes == [0, 0]).ok_or(efi::Status::INVALID_PARAMETER)?;
+        let strings = &rest[..end];
+        self.data = &rest[end + 2..];
+        Ok(Some(Structure { formatted, strings }))
+    }
+}
+
+impl<'a> Iterator for Structures<'a> {
+    type Item = Result<Structure<'a>, EfiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.parse() {
+            Ok(Some(structure)) if structure.structure_type() != END_OF_TABLE => Some(Ok(structure)),
+            Ok(_) => {
+                self.done = true;
+                None
+            }
+            Err(status) => {
+                self.done = true;
+                Some(Err(status))
+            }
+        }
+    }
+}
+
+/// The structure table of the firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmbiosTables<'a> {
+    data: &'a [u8],
+    version: (u8, u8),
+}
+
+impl SmbiosTables<'static> {
+    /// The table of the SMBIOS 3.x entry point of the configuration tables, or else of the SMBIOS 2.x one.
+    pub fn from_system_table(system_table: &efi::SystemTable) -> Result<Self, EfiError> {
+        let (address, size, version) = match configuration_table::find::<Smbios3>(system_table) {
+            Ok(entry_point) => (
+                entry_point.table_address,
+                entry_point.table_max_size as usize,
+                (entry_point.major_version, entry_point.minor_version),
+            ),
+            Err(_) => {
+                let entry_point = configuration_table::find::<Smbios>(system_table)?;
+                (
+                    entry_point.table_address as u64,
+                    entry_point.table_length as usize,
+                    (entry_point.major_version, entry_point.minor_version),
+                )
+            }
+        };
+        if address == 0 {
+            return Err(efi::Status::NOT_FOUND.into());
+        }
+        //SAFETY: The entry point installed by the firmware points to its structure table.
+        let data = unsafe { slice::from_raw_parts(address as *const u8, size) };
+        Ok(Self::new(data, version))
+    }
+}
+
+impl<'a> SmbiosTables<'a> {
+    /// The structures of `data`, of SMBIOS `version` as `(major, minor)`.
+    pub const fn new(data: &'a [u8], version: (u8, u8)) -> Self {
+        Self { data, version }
+    }
+
+    /// The version of SMBIOS, as `(major, minor)`.
+    pub fn version(&self) -> (u8, u8) {
+        self.version
+    }
+
+    pub fn structures(&self) -> Structures<'a> {
+        Structures { data: self.data, done: false }
+    }
+
+    /// The first structure of `structure_type`.
+    pub fn find(&self, structure_type: u8) -> Option<Structure<'a>> {
+        self.structures().map_while(Result::ok).find(|structure| structure.structure_type() == structure_type)
+    }
+
+    /// The structure of `handle`.
+    pub fn find_by_handle(&self, handle: u16) -> Option<Structure<'a>> {
+        self.structures().map_while(Result::ok).find(|structure| structure.handle() == handle)
+    }
+
+    /// The structures of the type of `T`, until the first malformed structure.
+    pub fn structures_of<T: StructureType<'a> + 'a>(&self) -> impl Iterator<Item = T> + 'a {
+        self.structures()
+            .map_while(Result::ok)
+            .filter(|structure| structure.structure_type() == T::TYPE)
+            .map(T::from_structure)
+    }
+}
+
+/// Typed access to the structures of a type.
+pub trait StructureType<'a>: Sized {
+    const TYPE: u8;
+
+    /// Wraps a structure of the type.
+    fn from_structure(structure: Structure<'a>) -> Self;
+
+    /// Wraps `structure` if it is of the type.
+    fn new(structure: Structure<'a>) -> Option<Self> {
+        (structure.structure_type() == Self::TYPE).then(|| Self::from_structure(structure))
+    }
+}
+
+macro_rules! structure_type {
+    ($(#[$attr:meta])* $name:ident = $type:literal) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $name<'a>(Structure<'a>);
+
+        impl<'a> StructureType<'a> for $name<'a> {
+            const TYPE: u8 = $type;
+            fn from_structure(structure: Structure<'a>) -> Self {
+                Self(structure)
+            }
+        }
+
+        impl<'a> $name<'a> {
+            pub fn structure(&self) -> Structure<'a> {
+                self.0
+            }
+        }
+    };
+}
+
+structure_type!(
+    /// BIOS Information, type 0.
+    BiosInformation = 0
+);
+
+impl<'a> BiosInformation<'a> {
+    pub fn vendor(&self) -> Option<&'a str> {
+        self.0.string_at(0x04)
+    }
+
+    pub fn bios_version(&self) -> Option<&'a str> {
+        self.0.string_at(0x05)
+    }
+
+    pub fn release_date(&self) -> Option<&'a str> {
+        self.0.string_at(0x08)
+    }
+
+    pub fn characteristics(&self) -> Option<u64> {
+        self.0.qword(0x0A)
+    }
+
+    /// The release of the system firmware as `(major, minor)`, since SMBIOS 2.4.
+    pub fn system_bios_release(&self) -> Option<(u8, u8)> {
+        Some((self.0.byte(0x14)?, self.0.byte(0x15)?))
+    }
+}
+
+structure_type!(
+    /// System Information, type 1.
+    SystemInformation = 1
+);
+
+impl<'a> SystemInformation<'a> {
+    pub fn manufacturer(&self) -> Option<&'a str> {
+        self.0.string_at(0x04)
+    }
+
+    pub fn product_name(&self) -> Option<&'a str> {
+        self.0.string_at(0x05)
+    }
+
+    pub fn version(&self) -> Option<&'a str> {
+        self.0.string_at(0x06)
+    }
+
+    pub fn serial_number(&self) -> Option<&'a str> {
+        self.0.string_at(0x07)
+    }
+
+    /// The UUID of the system, whose first fields are little-endian since SMBIOS 2.6, as a GUID.
+    pub fn uuid(&self) -> Option<efi::Guid> {
+        Some(efi::Guid::from_bytes(self.0.formatted.get(0x08..0x18)?.try_into().unwrap()))
+    }
+
+    /// The SKU number, since SMBIOS 2.4.
+    pub fn sku_number(&self) -> Option<&'a str> {
+        self.0.string_at(0x19)
+    }
+
+    /// The family, since SMBIOS 2.4.
+    pub fn family(&self) -> Option<&'a str> {
+        self.0.string_at(0x1A)
+    }
+}
+
+structure_type!(
+    /// Baseboard Information, type 2.
+    BaseboardInformation = 2
+);
+
+impl<'a> BaseboardInformation<'a> {
+    pub fn manufacturer(&self) -> Option<&'a str> {
+        self.0.string_at(0x04)
+    }
+
+    pub fn product(&self) -> Option<&'a str> {
+        self.0.string_at(0x05)
+    }
+
+    pub fn version(&self) -> Option<&'a str> {
+        self.0.string_at(0x06)
+    }
+
+    pub fn serial_number(&self) -> Option<&'a str> {
+        self.0.string_at(0x07)
+    }
+
+    pub fn asset_tag(&self) -> Option<&'a str> {
+        self.0.string_at(0x08)
+    }
+}
+
+structure_type!(
+    /// Processor Information, type 4.
+    ProcessorInformation = 4
+);
+
+impl<'a> ProcessorInformation<'a> {
+    pub fn socket_designation(&self) -> Option<&'a str> {
+        self.0.string_at(0x04)
+    }
+
+    pub fn manufacturer(&self) -> Option<&'a str> {
+        self.0.string_at(0x07)
+    }
+
+    /// The raw processor identification, `CPUID` leaf 1 `EAX` and `EDX` on x86.
+    pub fn processor_id(&self) -> Option<u64> {
+        self.0.qword(0x08)
+    }
+
+    pub fn version(&self) -> Option<&'a str> {
+        self.0.string_at(0x10)
+    }
+
+    /// The maximum speed, in MHz.
+    pub fn max_speed(&self) -> Option<u16> {
+        self.0.word(0x14)
+    }
+
+    /// The current speed, in MHz.
+    pub fn current_speed(&self) -> Option<u16> {
+        self.0.word(0x16)
+    }
+
+    /// The number of cores, since SMBIOS 2.5, from the 16-bit count of SMBIOS 3.0 for more than 255 cores.
+    pub fn core_count(&self) -> Option<u16> {
+        match self.0.byte(0x23)? {
+            0xFF => self.0.word(0x2A).or(Some(0xFF)),
+            count => Some(count as u16),
+        }
+    }
+
+    /// The number of threads, since SMBIOS 2.5, from the 16-bit count of SMBIOS 3.0 for more than 255 threads.
+    pub fn thread_count(&self) -> Option<u16> {
+        match self.0.byte(0x25)? {
+            0xFF => self.0.word(0x2E).or(Some(0xFF)),
+            count => Some(count as u16),
+        }
+    }
+}
+
+structure_type!(
+    /// Memory Device, type 17.
+    MemoryDevice = 17
+);
+
+impl<'a> MemoryDevice<'a> {
+    /// The size of the device in MiB, 0 if no memory is installed, `None` if it is unknown.
+    pub fn size_mib(&self) -> Option<u64> {
+        match self.0.word(0x0C)? {
+            0xFFFF => None,
+            // The size is in the extended size, since SMBIOS 2.7.
+            0x7FFF => Some((self.0.dword(0x1C)? & 0x7FFFFFFF) as u64),
+            size if size & 0x8000 != 0 => Some((size & 0x7FFF) as u64 / 1024),
+            size => Some(size as u64),
+        }
+    }
+
+    pub fn device_locator(&self) -> Option<&'a str> {
+        self.0.string_at(0x10)
+    }
+
+    pub fn bank_locator(&self) -> Option<&'a str> {
+        self.0.string_at(0x11)
+    }
+
+    /// The type of memory, such as `0x1A` for DDR4.
+    pub fn memory_type(&self) -> Option<u8> {
+        self.0.byte(0x12)
+    }
+
+    /// The maximum speed, in MT/s, since SMBIOS 2.3.
+    pub fn speed(&self) -> Option<u16> {
+        self.0.word(0x15)
+    }
+
+    /// The manufacturer, since SMBIOS 2.3.
+    pub fn manufacturer(&self) -> Option<&'a str> {
+        self.0.string_at(0x17)
+    }
+
+    /// The serial number, since SMBIOS 2.3.
+    pub fn serial_number(&self) -> Option<&'a str> {
+        self.0.string_at(0x18)
+    }
+
+    /// The part number, since SMBIOS 2.3.
+    pub fn part_number(&self) -> Option<&'a str> {
+        self.0.string_at(0x1A)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    /// A structure of `structure_type` with the formatted area after the header and `strings`.
+    fn structure(structure_type: u8, handle: u16, formatted: &[u8], strings: &[&str]) -> Vec<u8> {
+        let mut structure = vec![structure_type, (HEADER_SIZE + formatted.len()) as u8];
+        structure.extend_from_slice(&handle.to_le_bytes());
+        structure.extend_from_slice(formatted);
+        for string in strings {
+            structure.extend_from_slice(string.as_bytes());
+            structure.push(0);
+        }
+        if strings.is_empty() {
+            structure.push(0);
+        }
+        structure.push(0);
+        structure
+    }
+
+    fn table() -> Vec<u8> {
+        let mut system = vec![1, 2, 3, 4];
+        system.extend_from_slice(&[0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE, 0, 1, 2, 3, 4, 5, 6, 7]);
+        system.extend_from_slice(&[6, 0, 0]);
+        let mut memory = vec![0; 0x28 - HEADER_SIZE];
+        memory[0x0C - HEADER_SIZE..0x0E - HEADER_SIZE].copy_from_slice(&0x7FFF_u16.to_le_bytes());
+        memory[0x10 - HEADER_SIZE] = 1;
+        memory[0x1A - HEADER_SIZE] = 2;
+        memory[0x1C - HEADER_SIZE..0x20 - HEADER_SIZE].copy_from_slice(&(64 * 1024_u32).to_le_bytes());
+        [
+            structure(
+                0,
+                0,
+                &[1, 2, 0, 0xF0, 3, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 6],
+                &["Vendor", "1.0", "01/01/2024"],
+            ),
+            structure(1, 1, &system, &["Maker", "Product", "1.0", "1234"]),
+            structure(17, 2, &memory, &["DIMM 0", "PN-1"]),
+            structure(17, 3, &[0; 0x28 - HEADER_SIZE], &[]),
+            structure(END_OF_TABLE, 4, &[], &[]),
+            structure(2, 5, &[1], &["After the end"]),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_structures() {
+        let data = table();
+        let tables = SmbiosTables::new(&data, (3, 7));
+        let structures = tables.structures().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(vec![0, 1, 17, 17], structures.iter().map(Structure::structure_type).collect::<Vec<_>>());
+        assert_eq!(vec![0, 1, 2, 3], structures.iter().map(Structure::handle).collect::<Vec<_>>());
+
+        let system = structures[1];
+        assert_eq!(
+            (Some("Maker"), Some("Product"), Some("1234")),
+            (system.string(1), system.string(2), system.string(4))
+        );
+        assert_eq!((None, None), (system.string(0), system.string(5)));
+        assert_eq!(None, structures[3].string(1));
+        assert_eq!(None, tables.find(2));
+        assert_eq!(Some(3), tables.find_by_handle(3).map(|structure| structure.handle()));
+    }
+
+    #[test]
+    fn test_typed_structures() {
+        let data = table();
+        let tables = SmbiosTables::new(&data, (3, 7));
+        let bios = BiosInformation::new(tables.find(0).unwrap()).unwrap();
+        assert_eq!(
+            (Some("Vendor"), Some("1.0"), Some("01/01/2024")),
+            (bios.vendor(), bios.bios_version(), bios.release_date())
+        );
+        assert_eq!(Some((5, 6)), bios.system_bios_release());
+        assert!(SystemInformation::new(tables.find(0).unwrap()).is_none());
+
+        let system = tables.structures_of::<SystemInformation>().next().unwrap();
+        assert_eq!((Some("Maker"), Some("1234")), (system.manufacturer(), system.serial_number()));
+        let uuid = efi::Guid::from_fields(0x76543210, 0xBA98, 0xFEDC, 0, 1, &[2, 3, 4, 5, 6, 7]);
+        assert_eq!(Some(uuid), system.uuid());
+        assert_eq!((None, None), (system.sku_number(), system.family()));
+
+        let memory = tables.structures_of::<MemoryDevice>().collect::<Vec<_>>();
+        assert_eq!(
+            (Some(64 * 1024), Some("DIMM 0"), Some("PN-1")),
+            (memory[0].size_mib(), memory[0].device_locator(), memory[0].part_number())
+        );
+        assert_eq!((Some(0), None), (memory[1].size_mib(), memory[1].device_locator()));
+    }
+
+    #[test]
+    fn test_malformed_structures() {
+        // The strings of the last structure are not terminated, and there is no end of table.
+        let mut data = [structure(0, 0, &[1], &["Vendor"]), structure(1, 1, &[1], &["Maker"])].concat();
+        data.pop();
+        let structures = SmbiosTables::new(&data, (2, 8)).structures().collect::<Vec<_>>();
+        assert_eq!(2, structures.len());
+        assert_eq!(Some(&Err(efi::Status::INVALID_PARAMETER.into())), structures.last());
+
+        let header = [1, 2, 0, 0];
+        assert_eq!(
+            vec![Err(efi::Status::INVALID_PARAMETER.into())],
+            SmbiosTables::new(&header, (2, 8)).structures().collect::<Vec<_>>()
+        );
+    }
+}
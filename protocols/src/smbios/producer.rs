@@ -0,0 +1,466 @@
+//! SMBIOS protocol, to publish structures.
+//!
+//! [`RecordBuilder`] lays out the formatted area of a structure and numbers its strings, and [`SmbiosProducer`] adds
+//! the structure to the table:
+//!
+//! ```ignore
+//! let record = RecordBuilder::new(OEM_TYPE)
+//!     .string("Contoso")
+//!     .word(board_revision)
+//!     .string(serial_number)
+//!     .build()?;
+//! let handle = SmbiosProducer::locate(&boot_services)?.add(Some(image_handle), &record)?;
+//! ```
+//!
+//! [PI Spec Documentation: Volume 5, 6. SMBIOS Protocol](https://uefi.org/specs/PI/1.8/V5_SMBIOS_Protocol.html)
+
+use alloc::{string::String, vec, vec::Vec};
+use core::{fmt, ops::Deref, ptr};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use super::{SmbiosTables, HEADER_SIZE};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x03583ff6, 0xcb36, 0x4940, 0x94, 0x7e, &[0xb9, 0xb3, 0x9f, 0x4a, 0xfa, 0xf7]);
+
+/// Handle asking the protocol to assign a handle to an added structure, or to start an iteration.
+pub const HANDLE_PI_RESERVED: u16 = 0xFFFE;
+
+pub type ProtocolAdd = extern "efiapi" fn(*mut Protocol, efi::Handle, *mut u16, *mut TableHeader) -> efi::Status;
+pub type ProtocolUpdateString = extern "efiapi" fn(*mut Protocol, *mut u16, *mut usize, *mut u8) -> efi::Status;
+pub type ProtocolRemove = extern "efiapi" fn(*mut Protocol, u16) -> efi::Status;
+pub type ProtocolGetNext =
+    extern "efiapi" fn(*mut Protocol, *mut u16, *mut u8, *mut *mut TableHeader, *mut efi::Handle) -> efi::Status;
+
+/// FFI definition of `EFI_SMBIOS_TABLE_HEADER`.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableHeader {
+    pub structure_type: u8,
+    pub length: u8,
+    pub handle: u16,
+}
+
+/// FFI definition of `EFI_SMBIOS_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub add: ProtocolAdd,
+    pub update_string: ProtocolUpdateString,
+    pub remove: ProtocolRemove,
+    pub get_next: ProtocolGetNext,
+    pub major_version: u8,
+    pub minor_version: u8,
+}
+
+/// SMBIOS protocol.
+pub struct SmbiosProtocol;
+
+unsafe impl ProtocolTrait for SmbiosProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for SmbiosProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Builder of a structure, whose fields are appended in the order of the formatted area.
+///
+/// Each string field takes the number of its string, from 1 in the order of the first occurrence of each string, or 0
+/// for an empty string. The strings follow the formatted area and end with a double null.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordBuilder {
+    formatted: Vec<u8>,
+    strings: Vec<String>,
+}
+
+impl RecordBuilder {
+    /// A structure of `structure_type`, whose handle is assigned when it is added.
+    pub fn new(structure_type: u8) -> Self {
+        let mut formatted = vec![structure_type, HEADER_SIZE as u8];
+        formatted.extend_from_slice(&HANDLE_PI_RESERVED.to_le_bytes());
+        Self { formatted, strings: Vec::new() }
+    }
+
+    pub fn byte(mut self, value: u8) -> Self {
+        self.formatted.push(value);
+        self
+    }
+
+    pub fn word(self, value: u16) -> Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn dword(self, value: u32) -> Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn qword(self, value: u64) -> Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn bytes(mut self, value: &[u8]) -> Self {
+        self.formatted.extend_from_slice(value);
+        self
+    }
+
+    /// A GUID, such as the UUID of System Information, with its first fields little-endian.
+    pub fn guid(self, value: &efi::Guid) -> Self {
+        self.bytes(value.as_bytes())
+    }
+
+    /// The handle of another structure, such as the memory array of a memory device.
+    pub fn handle(self, handle: u16) -> Self {
+        self.word(handle)
+    }
+
+    /// The number of `value` in the strings of the structure, which are shared by identical fields.
+    pub fn string(mut self, value: &str) -> Self {
+        let number = match self.strings.iter().position(|string| string == value) {
+            _ if value.is_empty() => 0,
+            Some(index) => index + 1,
+            None => {
+                self.strings.push(value.into());
+                self.strings.len()
+            }
+        };
+        // Too many strings are reported by the build.
+        self.formatted.push(number.min(u8::MAX as usize) as u8);
+        self
+    }
+
+    /// Length of the formatted area, including the header.
+    pub fn len(&self) -> usize {
+        self.formatted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formatted.is_empty()
+    }
+
+    /// The structure, its formatted area followed by its strings.
+    ///
+    /// Returns `INVALID_PARAMETER` if the formatted area is longer than 255 bytes, if there are more than 254 strings or
+    /// if a string contains a null character.
+    pub fn build(&self) -> Result<Vec<u8>, EfiError> {
+        let length = u8::try_from(self.formatted.len()).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        if self.strings.len() >= u8::MAX as usize || self.strings.iter().any(|string| string.contains('\0')) {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let mut record = self.formatted.clone();
+        record[1] = length;
+        for string in &self.strings {
+            record.extend_from_slice(string.as_bytes());
+            record.push(0);
+        }
+        // A structure without strings ends with two nulls too.
+        if self.strings.is_empty() {
+            record.push(0);
+        }
+        record.push(0);
+        Ok(record)
+    }
+}
+
+/// Typed access to the SMBIOS protocol.
+pub struct SmbiosProducer(&'static mut Protocol);
+
+impl SmbiosProducer {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&SmbiosProtocol, None).map(Self)
+    }
+
+    /// The version of SMBIOS of the table, as `(major, minor)`.
+    pub fn version(&self) -> (u8, u8) {
+        (self.0.major_version, self.0.minor_version)
+    }
+
+    /// Adds a copy of `record`, a structure as built by [`RecordBuilder`], returns the handle assigned to it.
+    ///
+    /// `producer_handle` is the handle of the image or driver producing the structure.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn add(&mut self, producer_handle: Option<efi::Handle>, record: &[u8]) -> Result<u16, EfiError> {
+        self.add_with_handle(producer_handle, HANDLE_PI_RESERVED, record)
+    }
+
+    /// Adds a copy of `record` with `handle`, which must not be used by another structure.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn add_with_handle(
+        &mut self,
+        producer_handle: Option<efi::Handle>,
+        handle: u16,
+        record: &[u8],
+    ) -> Result<u16, EfiError> {
+        // The record must be a single well-formed structure, its strings ending with a double null.
+        let mut structures = SmbiosTables::new(record, self.version()).structures();
+        match structures.next() {
+            Some(Ok(_)) if structures.next().is_none() => (),
+            _ => return Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+        let mut handle = handle;
+        // The record is only read by the protocol, which adds a copy.
+        match (self.0.add)(
+            self.0,
+            producer_handle.unwrap_or(ptr::null_mut()),
+            &mut handle,
+            record.as_ptr() as *mut TableHeader,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(handle),
+        }
+    }
+
+    /// Replaces the string of number `string_number`, from 1, of the structure of `handle`.
+    pub fn update_string(&mut self, handle: u16, string_number: usize, string: &str) -> Result<(), EfiError> {
+        if string.contains('\0') {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let mut handle = handle;
+        let mut string_number = string_number;
+        let mut string = [string.as_bytes(), &[0]].concat();
+        match (self.0.update_string)(self.0, &mut handle, &mut string_number, string.as_mut_ptr()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn remove(&mut self, handle: u16) -> Result<(), EfiError> {
+        match (self.0.remove)(self.0, handle) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The handles of the structures of `structure_type`, or of all the structures for `None`.
+    pub fn handles(&mut self, structure_type: Option<u8>) -> Result<Vec<u16>, EfiError> {
+        let mut handles = Vec::new();
+        let mut handle = HANDLE_PI_RESERVED;
+        let mut structure_type = structure_type;
+        let structure_type_ptr = structure_type.as_mut().map_or(ptr::null_mut(), |t| t as *mut u8);
+        loop {
+            let mut record = ptr::null_mut();
+            match (self.0.get_next)(self.0, &mut handle, structure_type_ptr, &mut record, ptr::null_mut()) {
+                efi::Status::NOT_FOUND => return Ok(handles),
+                s if s.is_error() => return Err(s.into()),
+                _ => handles.push(handle),
+            }
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for SmbiosProducer {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for SmbiosProducer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmbiosProducer").field("version", &self.version()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::smbios::{Structure, StructureType, SystemInformation};
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+    use core::{ffi::CStr, slice};
+
+    /// Firmware keeping the structures it adds, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestSmbios {
+        protocol: Protocol,
+        records: Vec<(u16, Vec<u8>)>,
+        next_handle: u16,
+    }
+
+    fn test_smbios<'a>(this: *mut Protocol) -> &'a mut TestSmbios {
+        unsafe { &mut *(this as *mut TestSmbios) }
+    }
+
+    /// The structure of a stored record.
+    fn structure(record: &[u8]) -> Structure<'_> {
+        SmbiosTables::new(record, (3, 7)).structures().next().unwrap().unwrap()
+    }
+
+    extern "efiapi" fn add(
+        this: *mut Protocol,
+        _producer_handle: efi::Handle,
+        handle: *mut u16,
+        record: *mut TableHeader,
+    ) -> efi::Status {
+        let smbios = test_smbios(this);
+        let header = unsafe { record.read_unaligned() };
+        // The structure ends at the first double null after the formatted area.
+        let data = record as *const u8;
+        let mut size = header.length as usize + 2;
+        while unsafe { (*data.add(size - 2), *data.add(size - 1)) } != (0, 0) {
+            size += 1;
+        }
+        let mut record = unsafe { slice::from_raw_parts(data, size) }.to_vec();
+        let handle = unsafe { &mut *handle };
+        if *handle == HANDLE_PI_RESERVED {
+            *handle = smbios.next_handle;
+            smbios.next_handle += 1;
+        } else if smbios.records.iter().any(|(h, _)| h == handle) {
+            return efi::Status::ALREADY_STARTED;
+        }
+        record[2..4].copy_from_slice(&handle.to_le_bytes());
+        smbios.records.push((*handle, record));
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn update_string(
+        this: *mut Protocol,
+        handle: *mut u16,
+        string_number: *mut usize,
+        string: *mut u8,
+    ) -> efi::Status {
+        let smbios = test_smbios(this);
+        let Some((_, record)) = smbios.records.iter_mut().find(|(h, _)| *h == unsafe { *handle }) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let string = unsafe { CStr::from_ptr(string as *const _) }.to_str().unwrap();
+        let structure = structure(record);
+        let mut strings = (1..).map_while(|number| structure.string(number)).collect::<Vec<_>>();
+        let Some(target) = strings.get_mut(unsafe { *string_number }.wrapping_sub(1)) else {
+            return efi::Status::NOT_FOUND;
+        };
+        *target = string;
+        let formatted = structure.formatted().to_vec();
+        let mut builder = RecordBuilder::new(0).bytes(&formatted[HEADER_SIZE..]);
+        for string in strings {
+            builder.strings.push(string.into());
+        }
+        let mut updated = builder.build().unwrap();
+        updated[..HEADER_SIZE].copy_from_slice(&formatted[..HEADER_SIZE]);
+        *record = updated;
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn remove(this: *mut Protocol, handle: u16) -> efi::Status {
+        let smbios = test_smbios(this);
+        match smbios.records.iter().position(|(h, _)| *h == handle) {
+            Some(index) => {
+                smbios.records.remove(index);
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::INVALID_PARAMETER,
+        }
+    }
+
+    extern "efiapi" fn get_next(
+        this: *mut Protocol,
+        handle: *mut u16,
+        structure_type: *mut u8,
+        record: *mut *mut TableHeader,
+        _producer_handle: *mut efi::Handle,
+    ) -> efi::Status {
+        let smbios = test_smbios(this);
+        let start = match unsafe { *handle } {
+            HANDLE_PI_RESERVED => 0,
+            h => smbios.records.iter().position(|(handle, _)| *handle == h).unwrap() + 1,
+        };
+        let next = smbios.records[start..]
+            .iter_mut()
+            .find(|(_, r)| structure_type.is_null() || r[0] == unsafe { *structure_type });
+        match next {
+            Some((h, r)) => {
+                unsafe {
+                    *handle = *h;
+                    *record = r.as_mut_ptr() as *mut TableHeader;
+                }
+                efi::Status::SUCCESS
+            }
+            None => efi::Status::NOT_FOUND,
+        }
+    }
+
+    fn smbios_producer() -> (SmbiosProducer, &'static TestSmbios) {
+        let smbios = Box::leak(Box::new(TestSmbios {
+            protocol: Protocol { add, update_string, remove, get_next, major_version: 3, minor_version: 7 },
+            records: Vec::new(),
+            next_handle: 0x100,
+        }));
+        let smbios_ptr = smbios as *mut TestSmbios as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<SmbiosProtocol, Protocol>()
+            .returning(move |_, _| Ok(unsafe { &mut (*(smbios_ptr as *mut TestSmbios)).protocol }));
+        (SmbiosProducer::locate(&boot_services).unwrap(), unsafe { &*(smbios_ptr as *const TestSmbios) })
+    }
+
+    #[test]
+    fn test_record_builder() {
+        let uuid = efi::Guid::from_fields(0x76543210, 0xBA98, 0xFEDC, 0, 1, &[2, 3, 4, 5, 6, 7]);
+        let record = RecordBuilder::new(1)
+            .string("Maker")
+            .string("Product")
+            .string("")
+            .string("Maker")
+            .guid(&uuid)
+            .byte(6)
+            .build()
+            .unwrap();
+        assert_eq!([1, 0x19, 0xFE, 0xFF, 1, 2, 0, 1], record[..8]);
+        assert_eq!(b"Maker\0Product\0\0", &record[0x19..]);
+        let system = SystemInformation::new(structure(&record)).unwrap();
+        assert_eq!(
+            (Some("Maker"), Some("Product"), None),
+            (system.manufacturer(), system.product_name(), system.version())
+        );
+        assert_eq!(Some(uuid), system.uuid());
+
+        let record = RecordBuilder::new(0x80).dword(1).build().unwrap();
+        assert_eq!(vec![0x80, 8, 0xFE, 0xFF, 1, 0, 0, 0, 0, 0], record);
+        assert_eq!(14, RecordBuilder::new(0x80).qword(1).handle(2).len());
+
+        assert_eq!(efi::Status::INVALID_PARAMETER, RecordBuilder::new(0x80).string("a\0b").build().unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, RecordBuilder::new(0x80).bytes(&[0; 252]).build().unwrap_err());
+        let many = (0..255).fold(RecordBuilder::new(0x80), |builder, i| builder.string(&alloc::format!("{i}")));
+        assert_eq!(efi::Status::INVALID_PARAMETER, many.build().unwrap_err());
+    }
+
+    #[test]
+    fn test_add_records() {
+        let (mut producer, smbios) = smbios_producer();
+        assert_eq!((3, 7), producer.version());
+        let record = RecordBuilder::new(0x80).string("Contoso").word(2).string("SN-1").build().unwrap();
+        let first = producer.add(None, &record).unwrap();
+        let second = producer.add_with_handle(None, 0x10, &RecordBuilder::new(0x81).build().unwrap()).unwrap();
+        assert_eq!((0x100, 0x10), (first, second));
+        assert_eq!(efi::Status::ALREADY_STARTED, producer.add_with_handle(None, 0x10, &record).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, producer.add(None, &record[..record.len() - 1]).unwrap_err());
+        assert_eq!(
+            efi::Status::INVALID_PARAMETER,
+            producer.add(None, &[record.clone(), record.clone()].concat()).unwrap_err()
+        );
+
+        producer.update_string(first, 2, "SN-2").unwrap();
+        let structure = structure(&smbios.records[0].1);
+        assert_eq!(
+            (first, Some("Contoso"), Some("SN-2")),
+            (structure.handle(), structure.string(1), structure.string(2))
+        );
+        assert_eq!(efi::Status::NOT_FOUND, producer.update_string(first, 3, "SN-3").unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, producer.update_string(first, 1, "a\0").unwrap_err());
+
+        producer.add(None, &record).unwrap();
+        assert_eq!(vec![0x100, 0x10, 0x101], producer.handles(None).unwrap());
+        assert_eq!(vec![0x100, 0x101], producer.handles(Some(0x80)).unwrap());
+        producer.remove(first).unwrap();
+        assert_eq!(vec![0x101], producer.handles(Some(0x80)).unwrap());
+        assert_eq!(efi::Status::INVALID_PARAMETER, producer.remove(first).unwrap_err());
+    }
+}
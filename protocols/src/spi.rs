@@ -0,0 +1,664 @@
+//! SPI IO and SPI host controller protocols.
+//!
+//! [`SpiIo`] executes an [`SpiTransaction`] on an SPI peripheral, selected by the SPI bus layer, and [`SpiHc`] on the
+//! host controller for the bus layer itself:
+//!
+//! ```ignore
+//! let mut flash = SpiIo::get(&boot_services, handle, &FLASH_DRIVER_GUID)?;
+//! let mut id = [0; 3];
+//! flash.write_then_read(&[0x9f], &mut id)?;
+//! ```
+//!
+//! SPI transactions are synchronous and the protocols have no timeout; it is up to the host controller driver to
+//! abort a transaction that does not complete.
+//!
+//! [PI Spec Documentation: Volume 5, 18. SPI Protocol Stack](https://uefi.org/specs/PI/1.8/V5_SPI_Protocol_Stack.html)
+
+use core::{ffi::c_void, fmt, ops::Deref, ptr};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub const SUPPORTS_2_BIT_DATA_BUS_WIDTH: u32 = 0x0000_0001;
+pub const SUPPORTS_4_BIT_DATA_BUS_WIDTH: u32 = 0x0000_0002;
+pub const SUPPORTS_8_BIT_DATA_BUS_WIDTH: u32 = 0x0000_0004;
+pub const TRANSFER_SIZE_INCLUDES_OPCODE: u32 = 0x0000_0008;
+pub const TRANSFER_SIZE_INCLUDES_ADDRESS: u32 = 0x0000_0010;
+
+pub const TRANSACTION_FULL_DUPLEX: u32 = 0;
+pub const TRANSACTION_WRITE_ONLY: u32 = 1;
+pub const TRANSACTION_READ_ONLY: u32 = 2;
+pub const TRANSACTION_WRITE_THEN_READ: u32 = 3;
+
+pub type ChipSelect = extern "efiapi" fn(*const Peripheral, efi::Boolean) -> efi::Status;
+
+pub type ProtocolTransaction =
+    extern "efiapi" fn(*const Protocol, u32, efi::Boolean, u32, u32, u32, u32, *mut u8, u32, *mut u8) -> efi::Status;
+pub type ProtocolUpdateSpiPeripheral = extern "efiapi" fn(*mut Protocol, *const Peripheral) -> efi::Status;
+
+/// FFI definition of `EFI_SPI_PART`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Part {
+    pub vendor: *const u16,
+    pub part_number: *const u16,
+    pub min_clock_hz: u32,
+    pub max_clock_hz: u32,
+    pub chip_select_polarity: efi::Boolean,
+}
+
+/// FFI definition of `EFI_SPI_PERIPHERAL`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Peripheral {
+    pub next_spi_peripheral: *const Peripheral,
+    pub friendly_name: *const u16,
+    pub spi_peripheral_driver_guid: *const efi::Guid,
+    pub spi_part: *const Part,
+    pub max_clock_hz: u32,
+    pub clock_polarity: efi::Boolean,
+    pub clock_phase: efi::Boolean,
+    pub attributes: u32,
+    pub configuration_data: *const c_void,
+    pub spi_bus: *const c_void,
+    pub chip_select: Option<ChipSelect>,
+    pub chip_select_parameter: *mut c_void,
+}
+
+/// FFI definition of `EFI_SPI_IO_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub spi_peripheral: *const Peripheral,
+    pub original_spi_peripheral: *const Peripheral,
+    pub frame_size_support_mask: u32,
+    pub maximum_transfer_bytes: u32,
+    pub attributes: u32,
+    pub legacy_spi_protocol: *const c_void,
+    pub transaction: ProtocolTransaction,
+    pub update_spi_peripheral: ProtocolUpdateSpiPeripheral,
+}
+
+/// SPI IO protocol, installed with the GUID of the driver of the peripheral.
+pub struct SpiIoProtocol(pub &'static efi::Guid);
+
+unsafe impl ProtocolTrait for SpiIoProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        self.0
+    }
+}
+
+impl Deref for SpiIoProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// SPI host controller protocol.
+pub mod hc {
+    use super::*;
+
+    pub const PROTOCOL_GUID: efi::Guid =
+        efi::Guid::from_fields(0xc74e5db2, 0xfa96, 0x4ae2, 0xb3, 0x99, &[0x15, 0x97, 0x7f, 0xe3, 0x00, 0x2d]);
+
+    pub const SUPPORTS_WRITE_THEN_READ_OPERATIONS: u32 = 0x0000_0001;
+    pub const SUPPORTS_2_BIT_DATA_BUS_WIDTH: u32 = 0x0000_0002;
+    pub const SUPPORTS_4_BIT_DATA_BUS_WIDTH: u32 = 0x0000_0004;
+    pub const SUPPORTS_8_BIT_DATA_BUS_WIDTH: u32 = 0x0000_0008;
+    pub const TRANSFER_SIZE_INCLUDES_OPCODE: u32 = 0x0000_0010;
+    pub const TRANSFER_SIZE_INCLUDES_ADDRESS: u32 = 0x0000_0020;
+
+    pub type ProtocolChipSelect = extern "efiapi" fn(*const Protocol, *const Peripheral, efi::Boolean) -> efi::Status;
+    pub type ProtocolClock = extern "efiapi" fn(*const Protocol, *const Peripheral, *mut u32) -> efi::Status;
+    pub type ProtocolTransaction = extern "efiapi" fn(*const Protocol, *mut BusTransaction) -> efi::Status;
+
+    /// FFI definition of `EFI_SPI_BUS_TRANSACTION`.
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct BusTransaction {
+        pub spi_peripheral: *const Peripheral,
+        pub transaction_type: u32,
+        pub debug_transaction: efi::Boolean,
+        pub bus_width: u32,
+        pub frame_size: u32,
+        pub write_bytes: u32,
+        pub write_buffer: *mut u8,
+        pub read_bytes: u32,
+        pub read_buffer: *mut u8,
+    }
+
+    /// FFI definition of `EFI_SPI_HC_PROTOCOL`.
+    #[repr(C)]
+    pub struct Protocol {
+        pub attributes: u32,
+        pub frame_size_support_mask: u32,
+        pub maximum_transfer_bytes: u32,
+        pub chip_select: ProtocolChipSelect,
+        pub clock: ProtocolClock,
+        pub transaction: ProtocolTransaction,
+    }
+
+    /// SPI host controller protocol.
+    pub struct SpiHcProtocol;
+
+    unsafe impl ProtocolTrait for SpiHcProtocol {
+        type Interface = Protocol;
+        fn protocol_guid(&self) -> &'static efi::Guid {
+            &PROTOCOL_GUID
+        }
+    }
+
+    impl Deref for SpiHcProtocol {
+        type Target = efi::Guid;
+        fn deref(&self) -> &Self::Target {
+            self.protocol_guid()
+        }
+    }
+}
+
+/// Builder of an SPI transaction.
+#[derive(Debug)]
+pub struct SpiTransaction<'a> {
+    transaction_type: u32,
+    write: &'a [u8],
+    read: &'a mut [u8],
+    bus_width: u32,
+    frame_size: u32,
+    clock_hz: u32,
+    debug: bool,
+}
+
+impl<'a> SpiTransaction<'a> {
+    fn new(transaction_type: u32, write: &'a [u8], read: &'a mut [u8]) -> Self {
+        Self { transaction_type, write, read, bus_width: 1, frame_size: 8, clock_hz: 0, debug: false }
+    }
+
+    pub fn write(data: &'a [u8]) -> Self {
+        Self::new(TRANSACTION_WRITE_ONLY, data, &mut [])
+    }
+
+    pub fn read(buffer: &'a mut [u8]) -> Self {
+        Self::new(TRANSACTION_READ_ONLY, &[], buffer)
+    }
+
+    /// Writes `data`, usually an opcode and an address, then reads `buffer` with the chip select still asserted.
+    pub fn write_then_read(data: &'a [u8], buffer: &'a mut [u8]) -> Self {
+        Self::new(TRANSACTION_WRITE_THEN_READ, data, buffer)
+    }
+
+    /// Reads `buffer` while writing `data`, both of the same length.
+    pub fn full_duplex(data: &'a [u8], buffer: &'a mut [u8]) -> Self {
+        Self::new(TRANSACTION_FULL_DUPLEX, data, buffer)
+    }
+
+    /// Sets the number of data lines, 1 by default.
+    pub fn with_bus_width(mut self, bus_width: u32) -> Self {
+        self.bus_width = bus_width;
+        self
+    }
+
+    /// Sets the number of bits of a frame, 8 by default.
+    pub fn with_frame_size(mut self, frame_size: u32) -> Self {
+        self.frame_size = frame_size;
+        self
+    }
+
+    /// Sets the maximum clock frequency, the one of the peripheral by default.
+    pub fn with_clock_hz(mut self, clock_hz: u32) -> Self {
+        self.clock_hz = clock_hz;
+        self
+    }
+
+    /// Asks the controller driver to display the transaction.
+    pub fn debug(mut self) -> Self {
+        self.debug = true;
+        self
+    }
+
+    pub fn transaction_type(&self) -> u32 {
+        self.transaction_type
+    }
+
+    /// Checks the transaction against the capabilities of a controller.
+    fn validate(&self, frame_size_support_mask: u32, maximum_transfer_bytes: u32) -> Result<(u32, u32), EfiError> {
+        let supported_frame_size = matches!(self.frame_size, 1..=32)
+            && (frame_size_support_mask == 0 || frame_size_support_mask & (1 << (self.frame_size - 1)) != 0);
+        if !supported_frame_size || !matches!(self.bus_width, 1 | 2 | 4 | 8) {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        if self.transaction_type == TRANSACTION_FULL_DUPLEX && self.write.len() != self.read.len() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let write_bytes = u32::try_from(self.write.len()).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+        let read_bytes = u32::try_from(self.read.len()).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+        if maximum_transfer_bytes != 0 && write_bytes.max(read_bytes) > maximum_transfer_bytes {
+            return Err(efi::Status::BAD_BUFFER_SIZE.into());
+        }
+        Ok((write_bytes, read_bytes))
+    }
+
+    fn write_buffer(&self) -> *mut u8 {
+        match self.write.is_empty() {
+            true => ptr::null_mut(),
+            false => self.write.as_ptr() as *mut u8,
+        }
+    }
+
+    fn read_buffer(&mut self) -> *mut u8 {
+        match self.read.is_empty() {
+            true => ptr::null_mut(),
+            false => self.read.as_mut_ptr(),
+        }
+    }
+}
+
+/// Typed access to an instance of the SPI IO protocol.
+pub struct SpiIo {
+    protocol: &'static mut Protocol,
+}
+
+impl SpiIo {
+    /// Gets the instance of the protocol installed with the GUID of the driver of a peripheral on its handle.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(
+        boot_services: &B,
+        handle: efi::Handle,
+        driver_guid: &'static efi::Guid,
+    ) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &SpiIoProtocol(driver_guid)).map(Self::from)
+    }
+
+    /// Description of the peripheral used by the transactions.
+    pub fn peripheral(&self) -> &Peripheral {
+        //SAFETY: The peripheral of a valid protocol instance is valid.
+        unsafe { &*self.protocol.spi_peripheral }
+    }
+
+    pub fn attributes(&self) -> u32 {
+        self.protocol.attributes
+    }
+
+    /// Mask of the supported frame sizes, bit n - 1 for n bits.
+    pub fn frame_size_support_mask(&self) -> u32 {
+        self.protocol.frame_size_support_mask
+    }
+
+    /// Maximum number of bytes of a transaction, 0 if unlimited.
+    pub fn maximum_transfer_bytes(&self) -> u32 {
+        self.protocol.maximum_transfer_bytes
+    }
+
+    pub fn execute(&mut self, transaction: &mut SpiTransaction) -> Result<(), EfiError> {
+        let (write_bytes, read_bytes) =
+            transaction.validate(self.protocol.frame_size_support_mask, self.protocol.maximum_transfer_bytes)?;
+        match (self.protocol.transaction)(
+            self.protocol,
+            transaction.transaction_type,
+            transaction.debug.into(),
+            transaction.clock_hz,
+            transaction.bus_width,
+            transaction.frame_size,
+            write_bytes,
+            transaction.write_buffer(),
+            read_bytes,
+            transaction.read_buffer(),
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), EfiError> {
+        self.execute(&mut SpiTransaction::write(data))
+    }
+
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<(), EfiError> {
+        self.execute(&mut SpiTransaction::read(buffer))
+    }
+
+    pub fn write_then_read(&mut self, data: &[u8], buffer: &mut [u8]) -> Result<(), EfiError> {
+        self.execute(&mut SpiTransaction::write_then_read(data, buffer))
+    }
+
+    /// Replaces the peripheral used by the transactions, e.g. to change its maximum clock frequency.
+    pub fn update_peripheral(&mut self, peripheral: &'static Peripheral) -> Result<(), EfiError> {
+        match (self.protocol.update_spi_peripheral)(self.protocol, peripheral) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for SpiIo {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self { protocol }
+    }
+}
+
+impl fmt::Debug for SpiIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpiIo")
+            .field("attributes", &self.protocol.attributes)
+            .field("frame_size_support_mask", &self.protocol.frame_size_support_mask)
+            .field("maximum_transfer_bytes", &self.protocol.maximum_transfer_bytes)
+            .finish()
+    }
+}
+
+/// Typed access to an instance of the SPI host controller protocol.
+pub struct SpiHc {
+    protocol: &'static mut hc::Protocol,
+}
+
+impl SpiHc {
+    /// Gets the instance of the protocol installed on the handle of a host controller.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &hc::SpiHcProtocol).map(Self::from)
+    }
+
+    pub fn attributes(&self) -> u32 {
+        self.protocol.attributes
+    }
+
+    /// Mask of the supported frame sizes, bit n - 1 for n bits.
+    pub fn frame_size_support_mask(&self) -> u32 {
+        self.protocol.frame_size_support_mask
+    }
+
+    /// Maximum number of bytes of a transaction, 0 if unlimited.
+    pub fn maximum_transfer_bytes(&self) -> u32 {
+        self.protocol.maximum_transfer_bytes
+    }
+
+    /// Drives the chip select of a peripheral to `value`.
+    pub fn chip_select(&mut self, peripheral: &Peripheral, value: bool) -> Result<(), EfiError> {
+        match (self.protocol.chip_select)(self.protocol, peripheral, value.into()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets the clock for a peripheral at most at `clock_hz`, returns the frequency actually set.
+    pub fn clock(&mut self, peripheral: &Peripheral, clock_hz: u32) -> Result<u32, EfiError> {
+        let mut clock_hz = clock_hz;
+        match (self.protocol.clock)(self.protocol, peripheral, &mut clock_hz) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(clock_hz),
+        }
+    }
+
+    /// Executes a transaction on the bus, with the chip select and clock already set for the peripheral.
+    pub fn execute(&mut self, peripheral: &Peripheral, transaction: &mut SpiTransaction) -> Result<(), EfiError> {
+        if transaction.transaction_type == TRANSACTION_WRITE_THEN_READ
+            && self.protocol.attributes & hc::SUPPORTS_WRITE_THEN_READ_OPERATIONS == 0
+        {
+            return Err(efi::Status::UNSUPPORTED.into());
+        }
+        let (write_bytes, read_bytes) =
+            transaction.validate(self.protocol.frame_size_support_mask, self.protocol.maximum_transfer_bytes)?;
+        let mut bus_transaction = hc::BusTransaction {
+            spi_peripheral: peripheral,
+            transaction_type: transaction.transaction_type,
+            debug_transaction: transaction.debug.into(),
+            bus_width: transaction.bus_width,
+            frame_size: transaction.frame_size,
+            write_bytes,
+            write_buffer: transaction.write_buffer(),
+            read_bytes,
+            read_buffer: transaction.read_buffer(),
+        };
+        match (self.protocol.transaction)(self.protocol, &mut bus_transaction) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut hc::Protocol> for SpiHc {
+    fn from(protocol: &'static mut hc::Protocol) -> Self {
+        Self { protocol }
+    }
+}
+
+impl fmt::Debug for SpiHc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpiHc")
+            .field("attributes", &self.protocol.attributes)
+            .field("frame_size_support_mask", &self.protocol.frame_size_support_mask)
+            .field("maximum_transfer_bytes", &self.protocol.maximum_transfer_bytes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec::Vec};
+    use core::{cell::RefCell, slice};
+
+    const PERIPHERAL: Peripheral = Peripheral {
+        next_spi_peripheral: ptr::null(),
+        friendly_name: ptr::null(),
+        spi_peripheral_driver_guid: ptr::null(),
+        spi_part: ptr::null(),
+        max_clock_hz: 50_000_000,
+        clock_polarity: efi::Boolean::FALSE,
+        clock_phase: efi::Boolean::FALSE,
+        attributes: 0,
+        configuration_data: ptr::null(),
+        spi_bus: ptr::null(),
+        chip_select: None,
+        chip_select_parameter: ptr::null_mut(),
+    };
+    static TEST_PERIPHERAL: TestPeripheral = TestPeripheral(PERIPHERAL);
+
+    /// Wrapper to put the peripheral with its raw pointers in a static.
+    struct TestPeripheral(Peripheral);
+    unsafe impl Sync for TestPeripheral {}
+
+    /// Flash answering its JEDEC ID to the 0x9f opcode and echoing full duplex transfers.
+    #[repr(C)]
+    struct TestFlash {
+        protocol: Protocol,
+        written: RefCell<Vec<u8>>,
+    }
+
+    fn test_flash<'a>(this: *const Protocol) -> &'a TestFlash {
+        unsafe { &*(this as *const TestFlash) }
+    }
+
+    fn transfer(
+        written: &RefCell<Vec<u8>>,
+        transaction_type: u32,
+        write_bytes: u32,
+        write_buffer: *mut u8,
+        read_bytes: u32,
+        read_buffer: *mut u8,
+    ) -> efi::Status {
+        let write = match write_buffer.is_null() {
+            true => &[][..],
+            false => unsafe { slice::from_raw_parts(write_buffer, write_bytes as usize) },
+        };
+        let read = match read_buffer.is_null() {
+            true => &mut [][..],
+            false => unsafe { slice::from_raw_parts_mut(read_buffer, read_bytes as usize) },
+        };
+        written.borrow_mut().extend_from_slice(write);
+        match transaction_type {
+            TRANSACTION_FULL_DUPLEX => read.copy_from_slice(write),
+            TRANSACTION_WRITE_THEN_READ if write == [0x9f] => read.copy_from_slice(&[0xef, 0x40, 0x18]),
+            TRANSACTION_WRITE_ONLY | TRANSACTION_READ_ONLY => read.fill(0xff),
+            _ => return efi::Status::DEVICE_ERROR,
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn transaction(
+        this: *const Protocol,
+        transaction_type: u32,
+        _debug: efi::Boolean,
+        _clock_hz: u32,
+        _bus_width: u32,
+        _frame_size: u32,
+        write_bytes: u32,
+        write_buffer: *mut u8,
+        read_bytes: u32,
+        read_buffer: *mut u8,
+    ) -> efi::Status {
+        transfer(&test_flash(this).written, transaction_type, write_bytes, write_buffer, read_bytes, read_buffer)
+    }
+
+    extern "efiapi" fn update_spi_peripheral(this: *mut Protocol, peripheral: *const Peripheral) -> efi::Status {
+        unsafe { (*this).spi_peripheral = peripheral };
+        efi::Status::SUCCESS
+    }
+
+    fn spi_io() -> (SpiIo, &'static TestFlash) {
+        let flash = Box::leak(Box::new(TestFlash {
+            protocol: Protocol {
+                spi_peripheral: &TEST_PERIPHERAL.0,
+                original_spi_peripheral: &TEST_PERIPHERAL.0,
+                frame_size_support_mask: 0x80,
+                maximum_transfer_bytes: 16,
+                attributes: SUPPORTS_2_BIT_DATA_BUS_WIDTH,
+                legacy_spi_protocol: ptr::null(),
+                transaction,
+                update_spi_peripheral,
+            },
+            written: RefCell::new(Vec::new()),
+        }));
+        let test_flash = test_flash(&flash.protocol);
+        (SpiIo::from(&mut flash.protocol), test_flash)
+    }
+
+    #[test]
+    fn test_spi_io() {
+        let (mut spi, flash) = spi_io();
+        assert_eq!(50_000_000, spi.peripheral().max_clock_hz);
+        assert_eq!((0x80, 16), (spi.frame_size_support_mask(), spi.maximum_transfer_bytes()));
+
+        let mut id = [0; 3];
+        spi.write_then_read(&[0x9f], &mut id).unwrap();
+        assert_eq!([0xef, 0x40, 0x18], id);
+        spi.write(&[0x06]).unwrap();
+        assert_eq!([0x9f, 0x06], flash.written.borrow()[..]);
+
+        let mut buffer = [0; 2];
+        spi.read(&mut buffer).unwrap();
+        assert_eq!([0xff, 0xff], buffer);
+        spi.execute(&mut SpiTransaction::full_duplex(&[1, 2], &mut buffer).with_bus_width(2).debug()).unwrap();
+        assert_eq!([1, 2], buffer);
+
+        static UPDATED: TestPeripheral = TestPeripheral(Peripheral { max_clock_hz: 1_000_000, ..PERIPHERAL });
+        spi.update_peripheral(&UPDATED.0).unwrap();
+        assert_eq!(1_000_000, spi.peripheral().max_clock_hz);
+    }
+
+    #[test]
+    fn test_invalid_transaction() {
+        let (mut spi, _) = spi_io();
+        let mut buffer = [0; 2];
+        assert_eq!(
+            efi::Status::INVALID_PARAMETER,
+            spi.execute(&mut SpiTransaction::full_duplex(&[1], &mut buffer)).unwrap_err()
+        );
+        assert_eq!(
+            efi::Status::INVALID_PARAMETER,
+            spi.execute(&mut SpiTransaction::write(&[1]).with_frame_size(16)).unwrap_err()
+        );
+        assert_eq!(
+            efi::Status::INVALID_PARAMETER,
+            spi.execute(&mut SpiTransaction::write(&[1]).with_bus_width(3)).unwrap_err()
+        );
+        assert_eq!(efi::Status::BAD_BUFFER_SIZE, spi.write(&[0; 17]).unwrap_err());
+    }
+
+    /// Host controller with the flash behind it, which records the chip select and clock.
+    #[repr(C)]
+    struct TestHc {
+        protocol: hc::Protocol,
+        written: RefCell<Vec<u8>>,
+        selected: RefCell<bool>,
+    }
+
+    fn test_hc<'a>(this: *const hc::Protocol) -> &'a TestHc {
+        unsafe { &*(this as *const TestHc) }
+    }
+
+    extern "efiapi" fn chip_select(
+        this: *const hc::Protocol,
+        _peripheral: *const Peripheral,
+        value: efi::Boolean,
+    ) -> efi::Status {
+        *test_hc(this).selected.borrow_mut() = value.into();
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn clock(
+        _this: *const hc::Protocol,
+        peripheral: *const Peripheral,
+        clock_hz: *mut u32,
+    ) -> efi::Status {
+        unsafe { *clock_hz = (*clock_hz).min((*peripheral).max_clock_hz).min(25_000_000) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn hc_transaction(this: *const hc::Protocol, transaction: *mut hc::BusTransaction) -> efi::Status {
+        let hc = test_hc(this);
+        let transaction = unsafe { &*transaction };
+        if !*hc.selected.borrow() {
+            return efi::Status::NOT_READY;
+        }
+        transfer(
+            &hc.written,
+            transaction.transaction_type,
+            transaction.write_bytes,
+            transaction.write_buffer,
+            transaction.read_bytes,
+            transaction.read_buffer,
+        )
+    }
+
+    fn spi_hc(attributes: u32) -> SpiHc {
+        let test_hc = Box::leak(Box::new(TestHc {
+            protocol: hc::Protocol {
+                attributes,
+                frame_size_support_mask: 0x80,
+                maximum_transfer_bytes: 0,
+                chip_select,
+                clock,
+                transaction: hc_transaction,
+            },
+            written: RefCell::new(Vec::new()),
+            selected: RefCell::new(false),
+        }));
+        SpiHc::from(&mut test_hc.protocol)
+    }
+
+    #[test]
+    fn test_spi_hc() {
+        let mut controller = spi_hc(hc::SUPPORTS_WRITE_THEN_READ_OPERATIONS);
+        let peripheral = &TEST_PERIPHERAL.0;
+        assert_eq!(hc::SUPPORTS_WRITE_THEN_READ_OPERATIONS, controller.attributes());
+
+        assert_eq!(25_000_000, controller.clock(peripheral, 100_000_000).unwrap());
+        let mut id = [0; 3];
+        assert_eq!(
+            efi::Status::NOT_READY,
+            controller.execute(peripheral, &mut SpiTransaction::write_then_read(&[0x9f], &mut id)).unwrap_err()
+        );
+        controller.chip_select(peripheral, true).unwrap();
+        controller.execute(peripheral, &mut SpiTransaction::write_then_read(&[0x9f], &mut id)).unwrap();
+        assert_eq!([0xef, 0x40, 0x18], id);
+
+        let mut controller = spi_hc(0);
+        controller.chip_select(peripheral, true).unwrap();
+        assert_eq!(
+            efi::Status::UNSUPPORTED,
+            controller.execute(peripheral, &mut SpiTransaction::write_then_read(&[0x9f], &mut id)).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,548 @@
+//! Status Code Runtime protocol of the PI specification, which reports progress, error and debug codes to the
+//! handlers of the platform, like a POST code display, a serial console or an event log.
+//!
+//! The codes are made of a [`StatusCodeType`], progress, error with a severity or debug, and a [`StatusCodeValue`],
+//! the class and subclass of the reporter with the operation:
+//!
+//! ```ignore
+//! let mut status_code = StatusCode::locate(boot_services)?;
+//! status_code.progress(StatusCodeValue::new(Subclass::PERIPHERAL_KEYBOARD, Operation::P_PC_INIT))?;
+//! status_code.error(
+//!     StatusCodeType::ERROR_MAJOR,
+//!     StatusCodeValue::new(Subclass::PERIPHERAL_KEYBOARD, Operation::P_EC_NOT_DETECTED),
+//!     None,
+//! )?;
+//! status_code.debug(DEBUG_INFO, "Keyboard not found")?;
+//! ```
+//!
+//! [PI Spec Documentation: Volume 3 - Status Codes](https://uefi.org/specs/PI/1.8/V3_Status_Codes.html)
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, mem, ops::BitOr, ptr, slice};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xd2b2b828, 0x0826, 0x48a7, 0xb3, 0xdf, &[0x98, 0x3c, 0x00, 0x60, 0x24, 0xf0]);
+
+/// Type of the extended data of debug codes, `gEfiStatusCodeDataTypeDebugGuid`.
+pub const DATA_TYPE_DEBUG_GUID: efi::Guid =
+    efi::Guid::from_fields(0x9a4e9246, 0xd553, 0x11d5, 0x87, 0xe2, &[0x00, 0x06, 0x29, 0x45, 0xc3, 0xb9]);
+
+/// Maximum size of the extended data of a debug code, header included, `EFI_STATUS_CODE_DATA_MAX_SIZE`.
+pub const DEBUG_DATA_MAX_SIZE: usize = 200;
+
+/// Number of arguments of the format string of a debug code.
+const DEBUG_ARGUMENTS: usize = 12;
+
+pub type ProtocolReportStatusCode =
+    extern "efiapi" fn(u32, u32, u32, *const efi::Guid, *const StatusCodeData) -> efi::Status;
+
+/// FFI definition of `EFI_STATUS_CODE_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub report_status_code: ProtocolReportStatusCode,
+}
+
+/// FFI definition of `EFI_STATUS_CODE_DATA`, the header of the extended data.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct StatusCodeData {
+    pub header_size: u16,
+    /// Size of the data following the header.
+    pub size: u16,
+    pub r#type: efi::Guid,
+}
+
+/// Status Code Runtime protocol.
+pub struct StatusCodeProtocol;
+
+unsafe impl ProtocolTrait for StatusCodeProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl core::ops::Deref for StatusCodeProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Type of a status code, `EFI_STATUS_CODE_TYPE`, a code and for errors a severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct StatusCodeType(pub u32);
+
+impl StatusCodeType {
+    pub const CODE_MASK: u32 = 0x0000_00FF;
+    pub const SEVERITY_MASK: u32 = 0xFF00_0000;
+
+    pub const PROGRESS_CODE: StatusCodeType = StatusCodeType(0x1);
+    pub const ERROR_CODE: StatusCodeType = StatusCodeType(0x2);
+    pub const DEBUG_CODE: StatusCodeType = StatusCodeType(0x3);
+
+    /// The error was recovered from.
+    pub const ERROR_MINOR: StatusCodeType = StatusCodeType(0x4000_0000);
+    /// The error required a recovery action.
+    pub const ERROR_MAJOR: StatusCodeType = StatusCodeType(0x8000_0000);
+    /// The error was not recovered from.
+    pub const ERROR_UNRECOVERED: StatusCodeType = StatusCodeType(0x9000_0000);
+    /// The error may have corrupted the system state.
+    pub const ERROR_UNCONTAINED: StatusCodeType = StatusCodeType(0xA000_0000);
+
+    /// The progress, error or debug code.
+    pub const fn code(self) -> StatusCodeType {
+        StatusCodeType(self.0 & Self::CODE_MASK)
+    }
+
+    /// The severity of an error.
+    pub const fn severity(self) -> StatusCodeType {
+        StatusCodeType(self.0 & Self::SEVERITY_MASK)
+    }
+}
+
+impl BitOr for StatusCodeType {
+    type Output = StatusCodeType;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        StatusCodeType(self.0 | rhs.0)
+    }
+}
+
+/// Class of the reporter of a status code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Class(pub u32);
+
+impl Class {
+    pub const COMPUTING_UNIT: Class = Class(0x0000_0000);
+    pub const PERIPHERAL: Class = Class(0x0100_0000);
+    pub const IO_BUS: Class = Class(0x0200_0000);
+    pub const SOFTWARE: Class = Class(0x0300_0000);
+}
+
+/// Subclass of the reporter of a status code, with its class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Subclass(pub u32);
+
+impl Subclass {
+    pub const COMPUTING_UNIT_UNSPECIFIED: Subclass = Subclass(0x0000_0000);
+    pub const COMPUTING_UNIT_HOST_PROCESSOR: Subclass = Subclass(0x0001_0000);
+    pub const COMPUTING_UNIT_FIRMWARE_PROCESSOR: Subclass = Subclass(0x0002_0000);
+    pub const COMPUTING_UNIT_IO_PROCESSOR: Subclass = Subclass(0x0003_0000);
+    pub const COMPUTING_UNIT_CACHE: Subclass = Subclass(0x0004_0000);
+    pub const COMPUTING_UNIT_MEMORY: Subclass = Subclass(0x0005_0000);
+    pub const COMPUTING_UNIT_CHIPSET: Subclass = Subclass(0x0006_0000);
+
+    pub const PERIPHERAL_UNSPECIFIED: Subclass = Subclass(0x0100_0000);
+    pub const PERIPHERAL_KEYBOARD: Subclass = Subclass(0x0101_0000);
+    pub const PERIPHERAL_MOUSE: Subclass = Subclass(0x0102_0000);
+    pub const PERIPHERAL_LOCAL_CONSOLE: Subclass = Subclass(0x0103_0000);
+    pub const PERIPHERAL_REMOTE_CONSOLE: Subclass = Subclass(0x0104_0000);
+    pub const PERIPHERAL_SERIAL_PORT: Subclass = Subclass(0x0105_0000);
+    pub const PERIPHERAL_PARALLEL_PORT: Subclass = Subclass(0x0106_0000);
+    pub const PERIPHERAL_FIXED_MEDIA: Subclass = Subclass(0x0107_0000);
+    pub const PERIPHERAL_REMOVABLE_MEDIA: Subclass = Subclass(0x0108_0000);
+    pub const PERIPHERAL_AUDIO_INPUT: Subclass = Subclass(0x0109_0000);
+    pub const PERIPHERAL_AUDIO_OUTPUT: Subclass = Subclass(0x010A_0000);
+    pub const PERIPHERAL_LCD_DEVICE: Subclass = Subclass(0x010B_0000);
+    pub const PERIPHERAL_NETWORK: Subclass = Subclass(0x010C_0000);
+    pub const PERIPHERAL_DOCKING: Subclass = Subclass(0x010D_0000);
+    pub const PERIPHERAL_TPM: Subclass = Subclass(0x010E_0000);
+
+    pub const IO_BUS_UNSPECIFIED: Subclass = Subclass(0x0200_0000);
+    pub const IO_BUS_PCI: Subclass = Subclass(0x0201_0000);
+    pub const IO_BUS_USB: Subclass = Subclass(0x0202_0000);
+    pub const IO_BUS_IBA: Subclass = Subclass(0x0203_0000);
+    pub const IO_BUS_AGP: Subclass = Subclass(0x0204_0000);
+    pub const IO_BUS_PC_CARD: Subclass = Subclass(0x0205_0000);
+    pub const IO_BUS_LPC: Subclass = Subclass(0x0206_0000);
+    pub const IO_BUS_SCSI: Subclass = Subclass(0x0207_0000);
+    pub const IO_BUS_ATA_ATAPI: Subclass = Subclass(0x0208_0000);
+    pub const IO_BUS_FC: Subclass = Subclass(0x0209_0000);
+    pub const IO_BUS_IP_NETWORK: Subclass = Subclass(0x020A_0000);
+    pub const IO_BUS_SMBUS: Subclass = Subclass(0x020B_0000);
+    pub const IO_BUS_I2C: Subclass = Subclass(0x020C_0000);
+
+    pub const SOFTWARE_UNSPECIFIED: Subclass = Subclass(0x0300_0000);
+    pub const SOFTWARE_SEC: Subclass = Subclass(0x0301_0000);
+    pub const SOFTWARE_PEI_CORE: Subclass = Subclass(0x0302_0000);
+    pub const SOFTWARE_PEI_MODULE: Subclass = Subclass(0x0303_0000);
+    pub const SOFTWARE_DXE_CORE: Subclass = Subclass(0x0304_0000);
+    pub const SOFTWARE_DXE_BS_DRIVER: Subclass = Subclass(0x0305_0000);
+    pub const SOFTWARE_DXE_RT_DRIVER: Subclass = Subclass(0x0306_0000);
+    pub const SOFTWARE_SMM_DRIVER: Subclass = Subclass(0x0307_0000);
+    pub const SOFTWARE_EFI_APPLICATION: Subclass = Subclass(0x0308_0000);
+    pub const SOFTWARE_EFI_OS_LOADER: Subclass = Subclass(0x0309_0000);
+    pub const SOFTWARE_RT: Subclass = Subclass(0x030A_0000);
+    pub const SOFTWARE_AL: Subclass = Subclass(0x030B_0000);
+    pub const SOFTWARE_EBC_EXCEPTION: Subclass = Subclass(0x030C_0000);
+    pub const SOFTWARE_IA32_EXCEPTION: Subclass = Subclass(0x030D_0000);
+    pub const SOFTWARE_IPF_EXCEPTION: Subclass = Subclass(0x030E_0000);
+    pub const SOFTWARE_PEI_SERVICE: Subclass = Subclass(0x030F_0000);
+    pub const SOFTWARE_EFI_BOOT_SERVICE: Subclass = Subclass(0x0310_0000);
+    pub const SOFTWARE_EFI_RUNTIME_SERVICE: Subclass = Subclass(0x0311_0000);
+    pub const SOFTWARE_EFI_DXE_SERVICE: Subclass = Subclass(0x0312_0000);
+    pub const SOFTWARE_X64_EXCEPTION: Subclass = Subclass(0x0313_0000);
+    pub const SOFTWARE_ARM_EXCEPTION: Subclass = Subclass(0x0314_0000);
+
+    pub const fn class(self) -> Class {
+        Class(self.0 & 0xFF00_0000)
+    }
+}
+
+/// Operation of a status code, the ones shared by the subclasses of a class or specific to a subclass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Operation(pub u16);
+
+impl Operation {
+    /// Operation of the debug codes.
+    pub const DC_UNSPECIFIED: Operation = Operation(0x0);
+
+    pub const CU_PC_INIT_BEGIN: Operation = Operation(0x0);
+    pub const CU_PC_INIT_END: Operation = Operation(0x1);
+    pub const CU_EC_NON_SPECIFIC: Operation = Operation(0x0);
+    pub const CU_EC_DISABLED: Operation = Operation(0x1);
+    pub const CU_EC_NOT_SUPPORTED: Operation = Operation(0x2);
+    pub const CU_EC_NOT_DETECTED: Operation = Operation(0x3);
+    pub const CU_EC_NOT_CONFIGURED: Operation = Operation(0x4);
+
+    pub const P_PC_INIT: Operation = Operation(0x0);
+    pub const P_PC_RESET: Operation = Operation(0x1);
+    pub const P_PC_DISABLE: Operation = Operation(0x2);
+    pub const P_PC_PRESENCE_DETECT: Operation = Operation(0x3);
+    pub const P_PC_ENABLE: Operation = Operation(0x4);
+    pub const P_PC_RECONFIG: Operation = Operation(0x5);
+    pub const P_PC_DETECTED: Operation = Operation(0x6);
+    pub const P_PC_REMOVED: Operation = Operation(0x7);
+    pub const P_EC_NON_SPECIFIC: Operation = Operation(0x0);
+    pub const P_EC_DISABLED: Operation = Operation(0x1);
+    pub const P_EC_NOT_SUPPORTED: Operation = Operation(0x2);
+    pub const P_EC_NOT_DETECTED: Operation = Operation(0x3);
+    pub const P_EC_NOT_CONFIGURED: Operation = Operation(0x4);
+    pub const P_EC_INTERFACE_ERROR: Operation = Operation(0x5);
+    pub const P_EC_CONTROLLER_ERROR: Operation = Operation(0x6);
+    pub const P_EC_INPUT_ERROR: Operation = Operation(0x7);
+    pub const P_EC_OUTPUT_ERROR: Operation = Operation(0x8);
+    pub const P_EC_RESOURCE_CONFLICT: Operation = Operation(0x9);
+
+    pub const IOB_PC_INIT: Operation = Operation(0x0);
+    pub const IOB_PC_RESET: Operation = Operation(0x1);
+    pub const IOB_PC_DISABLE: Operation = Operation(0x2);
+    pub const IOB_PC_DETECT: Operation = Operation(0x3);
+    pub const IOB_PC_ENABLE: Operation = Operation(0x4);
+    pub const IOB_PC_RECONFIG: Operation = Operation(0x5);
+    pub const IOB_PC_HOTPLUG: Operation = Operation(0x6);
+    pub const IOB_EC_NON_SPECIFIC: Operation = Operation(0x0);
+    pub const IOB_EC_DISABLED: Operation = Operation(0x1);
+    pub const IOB_EC_NOT_SUPPORTED: Operation = Operation(0x2);
+    pub const IOB_EC_NOT_DETECTED: Operation = Operation(0x3);
+    pub const IOB_EC_NOT_CONFIGURED: Operation = Operation(0x4);
+    pub const IOB_EC_INTERFACE_ERROR: Operation = Operation(0x5);
+    pub const IOB_EC_CONTROLLER_ERROR: Operation = Operation(0x6);
+    pub const IOB_EC_READ_ERROR: Operation = Operation(0x7);
+    pub const IOB_EC_WRITE_ERROR: Operation = Operation(0x8);
+    pub const IOB_EC_RESOURCE_CONFLICT: Operation = Operation(0x9);
+
+    pub const SW_PC_INIT: Operation = Operation(0x0);
+    pub const SW_PC_LOAD: Operation = Operation(0x1);
+    pub const SW_PC_INIT_BEGIN: Operation = Operation(0x2);
+    pub const SW_PC_INIT_END: Operation = Operation(0x3);
+    pub const SW_PC_AUTHENTICATE_BEGIN: Operation = Operation(0x4);
+    pub const SW_PC_AUTHENTICATE_END: Operation = Operation(0x5);
+    pub const SW_PC_INPUT_WAIT: Operation = Operation(0x6);
+    pub const SW_PC_USER_SETUP: Operation = Operation(0x7);
+    pub const SW_EC_NON_SPECIFIC: Operation = Operation(0x0);
+    pub const SW_EC_LOAD_ERROR: Operation = Operation(0x1);
+    pub const SW_EC_INVALID_PARAMETER: Operation = Operation(0x2);
+    pub const SW_EC_UNSUPPORTED: Operation = Operation(0x3);
+    pub const SW_EC_INVALID_BUFFER: Operation = Operation(0x4);
+    pub const SW_EC_OUT_OF_RESOURCES: Operation = Operation(0x5);
+    pub const SW_EC_ABORTED: Operation = Operation(0x6);
+    pub const SW_EC_ILLEGAL_SOFTWARE_STATE: Operation = Operation(0x7);
+    pub const SW_EC_ILLEGAL_HARDWARE_STATE: Operation = Operation(0x8);
+    pub const SW_EC_START_ERROR: Operation = Operation(0x9);
+    pub const SW_EC_BAD_DATE_TIME: Operation = Operation(0xA);
+    pub const SW_EC_CFG_INVALID: Operation = Operation(0xB);
+    pub const SW_EC_CFG_CLR_REQUEST: Operation = Operation(0xC);
+    pub const SW_EC_CFG_DEFAULT: Operation = Operation(0xD);
+    pub const SW_EC_PWD_INVALID: Operation = Operation(0xE);
+    pub const SW_EC_PWD_CLR_REQUEST: Operation = Operation(0xF);
+    pub const SW_EC_PWD_CLEARED: Operation = Operation(0x10);
+    pub const SW_EC_EVENT_LOG_FULL: Operation = Operation(0x11);
+    pub const SW_EC_WRITE_PROTECTED: Operation = Operation(0x12);
+    pub const SW_EC_FV_CORRUPTED: Operation = Operation(0x13);
+    pub const SW_EC_INCONSISTENT_MEMORY_MAP: Operation = Operation(0x14);
+
+    /// An operation defined by the subclass, `EFI_SUBCLASS_SPECIFIC`.
+    pub const fn subclass_specific(operation: u16) -> Operation {
+        Operation(0x1000 | operation)
+    }
+
+    /// An operation defined by the OEM, `EFI_OEM_SPECIFIC`.
+    pub const fn oem_specific(operation: u16) -> Operation {
+        Operation(0x8000 | operation)
+    }
+}
+
+/// Value of a status code, `EFI_STATUS_CODE_VALUE`, a subclass and an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct StatusCodeValue(pub u32);
+
+impl StatusCodeValue {
+    pub const fn new(subclass: Subclass, operation: Operation) -> Self {
+        Self(subclass.0 | operation.0 as u32)
+    }
+
+    pub const fn class(self) -> Class {
+        Class(self.0 & 0xFF00_0000)
+    }
+
+    pub const fn subclass(self) -> Subclass {
+        Subclass(self.0 & 0xFFFF_0000)
+    }
+
+    pub const fn operation(self) -> Operation {
+        Operation(self.0 as u16)
+    }
+}
+
+/// Extended data of a status code, a [`StatusCodeData`] header followed by the data.
+#[derive(Clone)]
+pub struct ExtendedData {
+    /// The header and the data, in 8-byte words so both are aligned.
+    buffer: Vec<u64>,
+}
+
+impl ExtendedData {
+    const HEADER_SIZE: usize = mem::size_of::<StatusCodeData>();
+
+    /// Extended data of type `data_type`.
+    ///
+    /// Returns `BAD_BUFFER_SIZE` if the data does not fit the 16-bit size of the header.
+    pub fn new(data_type: &efi::Guid, data: &[u8]) -> Result<Self, EfiError> {
+        let size = u16::try_from(data.len()).map_err(|_| efi::Status::BAD_BUFFER_SIZE)?;
+        let mut buffer = vec![0_u64; (Self::HEADER_SIZE + data.len()).div_ceil(8)];
+        let header = StatusCodeData { header_size: Self::HEADER_SIZE as u16, size, r#type: *data_type };
+        //SAFETY: The buffer holds the header and the data and is aligned for the header.
+        unsafe {
+            ptr::write(buffer.as_mut_ptr() as *mut StatusCodeData, header);
+            ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                (buffer.as_mut_ptr() as *mut u8).add(Self::HEADER_SIZE),
+                data.len(),
+            );
+        }
+        Ok(Self { buffer })
+    }
+
+    /// Extended data of a debug code, in the format of the debug library of EDK II.
+    ///
+    /// The data is an `EFI_DEBUG_INFO` with the `level` of the message, the arguments of the format string, none, and
+    /// the format string, `message` where `%` is escaped. The message is truncated to fit [`DEBUG_DATA_MAX_SIZE`].
+    pub fn debug_string(level: u32, message: &str) -> Self {
+        let prefix = mem::size_of::<u32>() + DEBUG_ARGUMENTS * mem::size_of::<u64>();
+        // The null terminator is the last byte.
+        let capacity = DEBUG_DATA_MAX_SIZE - Self::HEADER_SIZE - prefix - 1;
+        let mut data = Vec::with_capacity(prefix + capacity + 1);
+        data.extend_from_slice(&level.to_le_bytes());
+        data.resize(prefix, 0);
+        for c in message.chars() {
+            let mut encoded = [0; 4];
+            let encoded = match c {
+                '%' => "%%",
+                c => c.encode_utf8(&mut encoded),
+            };
+            if data.len() + encoded.len() > prefix + capacity {
+                break;
+            }
+            data.extend_from_slice(encoded.as_bytes());
+        }
+        data.push(0);
+        Self::new(&DATA_TYPE_DEBUG_GUID, &data).expect("The debug data is smaller than the maximum size.")
+    }
+
+    /// The header of the data.
+    pub fn header(&self) -> &StatusCodeData {
+        //SAFETY: The buffer starts with the header.
+        unsafe { &*(self.buffer.as_ptr() as *const StatusCodeData) }
+    }
+
+    /// The data following the header.
+    pub fn data(&self) -> &[u8] {
+        //SAFETY: The header is followed by `size` bytes of data.
+        unsafe {
+            slice::from_raw_parts(
+                (self.buffer.as_ptr() as *const u8).add(Self::HEADER_SIZE),
+                self.header().size as usize,
+            )
+        }
+    }
+}
+
+impl fmt::Debug for ExtendedData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedData").field("header", self.header()).field("data", &self.data()).finish()
+    }
+}
+
+/// Typed access to the Status Code Runtime protocol.
+pub struct StatusCode(&'static mut Protocol);
+
+impl StatusCode {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&StatusCodeProtocol, None).map(Self)
+    }
+
+    /// Reports a status code of the `instance` of the reporter identified by `caller_id`, with the extended data.
+    pub fn report(
+        &mut self,
+        code_type: StatusCodeType,
+        value: StatusCodeValue,
+        instance: u32,
+        caller_id: Option<&efi::Guid>,
+        data: Option<&ExtendedData>,
+    ) -> Result<(), EfiError> {
+        match (self.0.report_status_code)(
+            code_type.0,
+            value.0,
+            instance,
+            caller_id.map_or(ptr::null(), |guid| guid as *const efi::Guid),
+            data.map_or(ptr::null(), |data| data.header() as *const StatusCodeData),
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports a progress code.
+    pub fn progress(&mut self, value: StatusCodeValue) -> Result<(), EfiError> {
+        self.report(StatusCodeType::PROGRESS_CODE, value, 0, None, None)
+    }
+
+    /// Reports an error code of the given severity, like [`StatusCodeType::ERROR_MAJOR`].
+    pub fn error(
+        &mut self,
+        severity: StatusCodeType,
+        value: StatusCodeValue,
+        data: Option<&ExtendedData>,
+    ) -> Result<(), EfiError> {
+        self.report(StatusCodeType::ERROR_CODE | severity.severity(), value, 0, None, data)
+    }
+
+    /// Reports a debug message of the given debug level, like `DEBUG_INFO`, see [`ExtendedData::debug_string`].
+    pub fn debug(&mut self, level: u32, message: &str) -> Result<(), EfiError> {
+        let data = ExtendedData::debug_string(level, message);
+        let value = StatusCodeValue::new(Subclass::SOFTWARE_DXE_BS_DRIVER, Operation::DC_UNSPECIFIED);
+        self.report(StatusCodeType::DEBUG_CODE, value, 0, None, Some(&data))
+    }
+}
+
+impl From<&'static mut Protocol> for StatusCode {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatusCode").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use std::{boxed::Box, sync::Mutex};
+
+    /// The type, value and extended data of a reported status code.
+    type Reported = (u32, u32, Option<(efi::Guid, Vec<u8>)>);
+
+    static REPORTED: Mutex<Vec<Reported>> = Mutex::new(Vec::new());
+
+    extern "efiapi" fn report_status_code(
+        code_type: u32,
+        value: u32,
+        instance: u32,
+        caller_id: *const efi::Guid,
+        data: *const StatusCodeData,
+    ) -> efi::Status {
+        assert_eq!((0, true), (instance, caller_id.is_null()));
+        let data = unsafe { data.as_ref() }.map(|header| {
+            let bytes = unsafe {
+                slice::from_raw_parts((data as *const u8).add(header.header_size as usize), header.size as usize)
+            };
+            (header.r#type, bytes.to_vec())
+        });
+        REPORTED.lock().unwrap().push((code_type, value, data));
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_status_code_value() {
+        let value = StatusCodeValue::new(Subclass::IO_BUS_PCI, Operation::subclass_specific(2));
+        assert_eq!(0x0201_1002, value.0);
+        assert_eq!(Class::IO_BUS, value.class());
+        assert_eq!(Subclass::IO_BUS_PCI, value.subclass());
+        assert_eq!(Class::IO_BUS, value.subclass().class());
+        assert_eq!(Operation(0x1002), value.operation());
+        assert_eq!(0x8001, Operation::oem_specific(1).0);
+
+        let code_type = StatusCodeType::ERROR_CODE | StatusCodeType::ERROR_UNRECOVERED;
+        assert_eq!(0x9000_0002, code_type.0);
+        assert_eq!(StatusCodeType::ERROR_CODE, code_type.code());
+        assert_eq!(StatusCodeType::ERROR_UNRECOVERED, code_type.severity());
+    }
+
+    #[test]
+    fn test_extended_data() {
+        let data_type = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+        let data = ExtendedData::new(&data_type, &[1, 2, 3]).unwrap();
+        assert_eq!((20, 3), (data.header().header_size, data.header().size));
+        assert_eq!(data_type, data.header().r#type);
+        assert_eq!(&[1, 2, 3], data.data());
+        assert_eq!(efi::Status::BAD_BUFFER_SIZE, ExtendedData::new(&data_type, &[0; 0x10000]).unwrap_err());
+
+        let data = ExtendedData::debug_string(0x40, "100% done");
+        assert_eq!(DATA_TYPE_DEBUG_GUID, data.header().r#type);
+        assert_eq!(&0x40_u32.to_le_bytes(), &data.data()[..4]);
+        assert!(data.data()[4..100].iter().all(|&b| b == 0));
+        assert_eq!(b"100%% done\0", &data.data()[100..]);
+
+        let data = ExtendedData::debug_string(0x40, &"a".repeat(200));
+        assert_eq!(DEBUG_DATA_MAX_SIZE, 20 + data.data().len());
+        assert_eq!(Some(&0), data.data().last());
+    }
+
+    #[test]
+    fn test_status_code() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<StatusCodeProtocol, Protocol>()
+            .returning(|_, _| Ok(Box::leak(Box::new(Protocol { report_status_code }))));
+        let mut status_code = StatusCode::locate(&boot_services).unwrap();
+
+        let value = StatusCodeValue::new(Subclass::PERIPHERAL_KEYBOARD, Operation::P_PC_INIT);
+        status_code.progress(value).unwrap();
+        let value = StatusCodeValue::new(Subclass::PERIPHERAL_KEYBOARD, Operation::P_EC_NOT_DETECTED);
+        status_code.error(StatusCodeType::ERROR_MAJOR, value, None).unwrap();
+        status_code.debug(0x40, "Keyboard not found").unwrap();
+
+        let reported = REPORTED.lock().unwrap();
+        assert_eq!((0x1, 0x0101_0000, None), reported[0]);
+        assert_eq!((0x8000_0002, 0x0101_0003, None), reported[1]);
+        let (code_type, value, Some((data_type, data))) = &reported[2] else { panic!("Missing debug data.") };
+        assert_eq!((0x3, 0x0305_0000, DATA_TYPE_DEBUG_GUID), (*code_type, *value, *data_type));
+        assert_eq!(b"Keyboard not found\0", &data[100..]);
+    }
+}
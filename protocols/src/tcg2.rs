@@ -0,0 +1,639 @@
+//! TCG2 protocol, the access to the TPM 2.0 of the platform.
+//!
+//! [`Tcg2`] measures data into the PCRs of the TPM and logs the measurements, and sends raw TPM 2.0 commands:
+//!
+//! ```ignore
+//! let mut tcg2 = Tcg2::locate(&boot_services)?;
+//! let pcr = PcrIndex::new(8).unwrap();
+//! tcg2.hash_log_extend_event(ExtendFlags::NONE, pcr, EventType::IPL, &config, b"Platform configuration")?;
+//! ```
+//!
+//! The event log of the measurements is parsed by [`event_log`].
+//!
+//! [TCG EFI Protocol Specification](https://trustedcomputinggroup.org/resource/tcg-efi-protocol-specification/)
+
+use alloc::vec::Vec;
+use core::{fmt, mem, ops::Deref, slice};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub mod event_log;
+
+use event_log::EventLog;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x607f766c, 0x7455, 0x42be, 0x93, 0x0b, &[0xe4, 0xd7, 0x6d, 0xb2, 0x72, 0x0f]);
+
+/// Version of `EFI_TCG2_EVENT_HEADER`.
+pub const EVENT_HEADER_VERSION: u16 = 1;
+
+pub type ProtocolGetCapability = extern "efiapi" fn(*mut Protocol, *mut BootServiceCapability) -> efi::Status;
+
+pub type ProtocolGetEventLog = extern "efiapi" fn(
+    *mut Protocol,
+    EventLogFormat,
+    *mut efi::PhysicalAddress,
+    *mut efi::PhysicalAddress,
+    *mut efi::Boolean,
+) -> efi::Status;
+
+pub type ProtocolHashLogExtendEvent =
+    extern "efiapi" fn(*mut Protocol, ExtendFlags, efi::PhysicalAddress, u64, *mut Event) -> efi::Status;
+
+pub type ProtocolSubmitCommand = extern "efiapi" fn(*mut Protocol, u32, *mut u8, u32, *mut u8) -> efi::Status;
+
+pub type ProtocolGetActivePcrBanks = extern "efiapi" fn(*mut Protocol, *mut HashAlgorithms) -> efi::Status;
+
+pub type ProtocolSetActivePcrBanks = extern "efiapi" fn(*mut Protocol, HashAlgorithms) -> efi::Status;
+
+pub type ProtocolGetResultOfSetActivePcrBanks = extern "efiapi" fn(*mut Protocol, *mut u32, *mut u32) -> efi::Status;
+
+/// FFI definition of `EFI_TCG2_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub get_capability: ProtocolGetCapability,
+    pub get_event_log: ProtocolGetEventLog,
+    pub hash_log_extend_event: ProtocolHashLogExtendEvent,
+    pub submit_command: ProtocolSubmitCommand,
+    pub get_active_pcr_banks: ProtocolGetActivePcrBanks,
+    pub set_active_pcr_banks: ProtocolSetActivePcrBanks,
+    pub get_result_of_set_active_pcr_banks: ProtocolGetResultOfSetActivePcrBanks,
+}
+
+/// FFI definition of `EFI_TCG2_VERSION`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+}
+
+/// Set of hash algorithms, `EFI_TCG2_EVENT_ALGORITHM_BITMAP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct HashAlgorithms(pub u32);
+
+impl HashAlgorithms {
+    pub const SHA1: HashAlgorithms = HashAlgorithms(0x00000001);
+    pub const SHA256: HashAlgorithms = HashAlgorithms(0x00000002);
+    pub const SHA384: HashAlgorithms = HashAlgorithms(0x00000004);
+    pub const SHA512: HashAlgorithms = HashAlgorithms(0x00000008);
+    pub const SM3_256: HashAlgorithms = HashAlgorithms(0x00000010);
+
+    pub const fn contains(&self, algorithms: HashAlgorithms) -> bool {
+        self.0 & algorithms.0 == algorithms.0
+    }
+}
+
+/// Format of an event log, `EFI_TCG2_EVENT_LOG_FORMAT`, also used as a set of formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct EventLogFormat(pub u32);
+
+impl EventLogFormat {
+    /// The SHA-1 only log of TPM 1.2.
+    pub const TCG_1_2: EventLogFormat = EventLogFormat(0x00000001);
+    /// The crypto agile log of TPM 2.0.
+    pub const TCG_2: EventLogFormat = EventLogFormat(0x00000002);
+
+    pub const fn contains(&self, formats: EventLogFormat) -> bool {
+        self.0 & formats.0 == formats.0
+    }
+}
+
+/// Options of a measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct ExtendFlags(pub u64);
+
+impl ExtendFlags {
+    pub const NONE: ExtendFlags = ExtendFlags(0);
+    /// The PCR is extended without logging the event.
+    pub const EXTEND_ONLY: ExtendFlags = ExtendFlags(0x0000000000000001);
+    /// The data is a PE/COFF image, which is measured as Authenticode does.
+    pub const PE_COFF_IMAGE: ExtendFlags = ExtendFlags(0x0000000000000010);
+}
+
+/// Type of an event of the log, `TCG_EVENTTYPE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct EventType(pub u32);
+
+impl EventType {
+    pub const PREBOOT_CERT: EventType = EventType(0x00000000);
+    pub const POST_CODE: EventType = EventType(0x00000001);
+    pub const NO_ACTION: EventType = EventType(0x00000003);
+    pub const SEPARATOR: EventType = EventType(0x00000004);
+    pub const ACTION: EventType = EventType(0x00000005);
+    pub const EVENT_TAG: EventType = EventType(0x00000006);
+    pub const S_CRTM_CONTENTS: EventType = EventType(0x00000007);
+    pub const S_CRTM_VERSION: EventType = EventType(0x00000008);
+    pub const CPU_MICROCODE: EventType = EventType(0x00000009);
+    pub const PLATFORM_CONFIG_FLAGS: EventType = EventType(0x0000000A);
+    pub const TABLE_OF_DEVICES: EventType = EventType(0x0000000B);
+    pub const COMPACT_HASH: EventType = EventType(0x0000000C);
+    pub const IPL: EventType = EventType(0x0000000D);
+    pub const IPL_PARTITION_DATA: EventType = EventType(0x0000000E);
+    pub const NONHOST_CODE: EventType = EventType(0x0000000F);
+    pub const NONHOST_CONFIG: EventType = EventType(0x00000010);
+    pub const NONHOST_INFO: EventType = EventType(0x00000011);
+    pub const OMIT_BOOT_DEVICE_EVENTS: EventType = EventType(0x00000012);
+    pub const EFI_VARIABLE_DRIVER_CONFIG: EventType = EventType(0x80000001);
+    pub const EFI_VARIABLE_BOOT: EventType = EventType(0x80000002);
+    pub const EFI_BOOT_SERVICES_APPLICATION: EventType = EventType(0x80000003);
+    pub const EFI_BOOT_SERVICES_DRIVER: EventType = EventType(0x80000004);
+    pub const EFI_RUNTIME_SERVICES_DRIVER: EventType = EventType(0x80000005);
+    pub const EFI_GPT_EVENT: EventType = EventType(0x80000006);
+    pub const EFI_ACTION: EventType = EventType(0x80000007);
+    pub const EFI_PLATFORM_FIRMWARE_BLOB: EventType = EventType(0x80000008);
+    pub const EFI_HANDOFF_TABLES: EventType = EventType(0x80000009);
+    pub const EFI_PLATFORM_FIRMWARE_BLOB2: EventType = EventType(0x8000000A);
+    pub const EFI_HANDOFF_TABLES2: EventType = EventType(0x8000000B);
+    pub const EFI_VARIABLE_BOOT2: EventType = EventType(0x8000000C);
+    pub const EFI_HCRTM_EVENT: EventType = EventType(0x80000010);
+    pub const EFI_VARIABLE_AUTHORITY: EventType = EventType(0x800000E0);
+    pub const EFI_SPDM_FIRMWARE_BLOB: EventType = EventType(0x800000E1);
+    pub const EFI_SPDM_FIRMWARE_CONFIG: EventType = EventType(0x800000E2);
+}
+
+/// Index of a PCR, from 0 to 23 for the PCRs of the TCG PC Client platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct PcrIndex(u32);
+
+impl PcrIndex {
+    pub const COUNT: u32 = 24;
+
+    /// The PCR of `index`, if it exists.
+    pub const fn new(index: u32) -> Option<Self> {
+        match index < Self::COUNT {
+            true => Some(Self(index)),
+            false => None,
+        }
+    }
+
+    pub const fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+/// FFI definition of `EFI_TCG2_BOOT_SERVICE_CAPABILITY`.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct BootServiceCapability {
+    /// Size of the structure, set by the caller to the size it knows.
+    pub size: u8,
+    pub structure_version: Version,
+    pub protocol_version: Version,
+    pub hash_algorithm_bitmap: HashAlgorithms,
+    pub supported_event_logs: EventLogFormat,
+    pub tpm_present_flag: efi::Boolean,
+    pub max_command_size: u16,
+    pub max_response_size: u16,
+    pub manufacturer_id: u32,
+    pub number_of_pcr_banks: u32,
+    pub active_pcr_banks: HashAlgorithms,
+}
+
+/// FFI definition of `EFI_TCG2_EVENT_HEADER`.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct EventHeader {
+    pub header_size: u32,
+    pub header_version: u16,
+    pub pcr_index: u32,
+    pub event_type: EventType,
+}
+
+/// FFI definition of `EFI_TCG2_EVENT`, followed by the data of the event.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct Event {
+    /// Size of the structure and of the data of the event.
+    pub size: u32,
+    pub header: EventHeader,
+}
+
+/// TCG2 protocol.
+pub struct Tcg2Protocol;
+
+unsafe impl ProtocolTrait for Tcg2Protocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for Tcg2Protocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Description of the TPM and of the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capability {
+    pub protocol_version: Version,
+    /// The hash algorithms supported by the TPM.
+    pub hash_algorithms: HashAlgorithms,
+    pub supported_event_logs: EventLogFormat,
+    pub tpm_present: bool,
+    /// Size of the largest command of [`Tcg2::submit_command`].
+    pub max_command_size: u16,
+    /// Size of the largest response of [`Tcg2::submit_command`].
+    pub max_response_size: u16,
+    /// Vendor of the TPM, `TPM_PT_MANUFACTURER`.
+    pub manufacturer_id: u32,
+    pub number_of_pcr_banks: u32,
+    /// The hash algorithms of the PCR banks in use.
+    pub active_pcr_banks: HashAlgorithms,
+}
+
+impl From<&BootServiceCapability> for Capability {
+    fn from(capability: &BootServiceCapability) -> Self {
+        Self {
+            protocol_version: capability.protocol_version,
+            hash_algorithms: capability.hash_algorithm_bitmap,
+            supported_event_logs: capability.supported_event_logs,
+            tpm_present: capability.tpm_present_flag.into(),
+            max_command_size: capability.max_command_size,
+            max_response_size: capability.max_response_size,
+            manufacturer_id: capability.manufacturer_id,
+            number_of_pcr_banks: capability.number_of_pcr_banks,
+            active_pcr_banks: capability.active_pcr_banks,
+        }
+    }
+}
+
+/// Typed access to the TCG2 protocol.
+pub struct Tcg2(&'static mut Protocol);
+
+impl Tcg2 {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&Tcg2Protocol, None).map(Self)
+    }
+
+    fn this(&self) -> *mut Protocol {
+        self.0 as *const Protocol as *mut Protocol
+    }
+
+    /// The capabilities of the TPM and of the protocol.
+    pub fn capability(&self) -> Result<Capability, EfiError> {
+        //SAFETY: The capability is plain data, for which zeroes are valid.
+        let mut capability: BootServiceCapability = unsafe { mem::zeroed() };
+        capability.size = mem::size_of::<BootServiceCapability>() as u8;
+        match (self.0.get_capability)(self.this(), &mut capability) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(Capability::from(&capability)),
+        }
+    }
+
+    /// The hash algorithms of the PCR banks in use.
+    pub fn active_pcr_banks(&self) -> Result<HashAlgorithms, EfiError> {
+        let mut banks = HashAlgorithms::default();
+        match (self.0.get_active_pcr_banks)(self.this(), &mut banks) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(banks),
+        }
+    }
+
+    /// The event log in `format`, kept by the firmware in boot services memory.
+    ///
+    /// Returns `NOT_FOUND` if there is no TPM.
+    pub fn get_event_log(&self, format: EventLogFormat) -> Result<EventLog<'static>, EfiError> {
+        if !matches!(format, EventLogFormat::TCG_1_2 | EventLogFormat::TCG_2) {
+            return Err(efi::Status::UNSUPPORTED.into());
+        }
+        let (mut location, mut last_entry, mut truncated) = (0, 0, efi::Boolean::FALSE);
+        match (self.0.get_event_log)(self.this(), format, &mut location, &mut last_entry, &mut truncated) {
+            s if s.is_error() => return Err(s.into()),
+            _ if location == 0 => return Err(efi::Status::NOT_FOUND.into()),
+            _ => (),
+        }
+        let last_offset = last_entry.checked_sub(location).ok_or(efi::Status::INVALID_PARAMETER)? as usize;
+        let (location, last_entry) = (location as *const u8, last_entry as *const u8);
+        //SAFETY: The firmware returned its log and the start of the last entry of the log, which is read up to its end.
+        let data = unsafe {
+            let size = match format {
+                EventLogFormat::TCG_2 => {
+                    let header_size = event_log::entry_size(location, None)?;
+                    let header = EventLog::parse(format, slice::from_raw_parts(location, header_size))?;
+                    match last_offset {
+                        0 => header_size,
+                        _ => last_offset + event_log::entry_size(last_entry, header.spec_id())?,
+                    }
+                }
+                _ if last_entry.is_null() => 0,
+                _ => last_offset + event_log::entry_size(last_entry, None)?,
+            };
+            slice::from_raw_parts(location, size)
+        };
+        let mut log = EventLog::parse(format, data)?;
+        log.set_truncated(truncated.into());
+        Ok(log)
+    }
+
+    /// Measures `data` into `pcr`, extending the PCR of every active bank with its hash, and logs the measurement as
+    /// an event of `event_type` with `event_data`.
+    pub fn hash_log_extend_event(
+        &mut self,
+        flags: ExtendFlags,
+        pcr: PcrIndex,
+        event_type: EventType,
+        data: &[u8],
+        event_data: &[u8],
+    ) -> Result<(), EfiError> {
+        let size = mem::size_of::<Event>() + event_data.len();
+        let event = Event {
+            size: u32::try_from(size).map_err(|_| efi::Status::INVALID_PARAMETER)?,
+            header: EventHeader {
+                header_size: mem::size_of::<EventHeader>() as u32,
+                header_version: EVENT_HEADER_VERSION,
+                pcr_index: pcr.index(),
+                event_type,
+            },
+        };
+        let mut buffer = Vec::with_capacity(size);
+        //SAFETY: The event is packed, all its bytes are initialized.
+        buffer.extend_from_slice(unsafe {
+            core::slice::from_raw_parts(&event as *const Event as *const u8, mem::size_of::<Event>())
+        });
+        buffer.extend_from_slice(event_data);
+        // The data is only read by the protocol.
+        let data_address = data.as_ptr() as efi::PhysicalAddress;
+        match (self.0.hash_log_extend_event)(
+            self.this(),
+            flags,
+            data_address,
+            data.len() as u64,
+            buffer.as_mut_ptr() as *mut Event,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends a TPM 2.0 command and receives its response into `response`, returns the size of the response.
+    ///
+    /// The command and the response start with the headers defined by the TPM 2.0 specification, whose size fields
+    /// are big endian.
+    pub fn submit_command(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, EfiError> {
+        let command_size = u32::try_from(command.len()).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        let response_size = u32::try_from(response.len()).unwrap_or(u32::MAX);
+        // The command is only read by the protocol.
+        match (self.0.submit_command)(
+            self.this(),
+            command_size,
+            command.as_ptr() as *mut u8,
+            response_size,
+            response.as_mut_ptr(),
+        ) {
+            s if s.is_error() => return Err(s.into()),
+            _ => (),
+        }
+        // The responseSize field of the header, after the tag.
+        match response.get(2..6) {
+            Some(size) => Ok((u32::from_be_bytes(size.try_into().unwrap()) as usize).min(response.len())),
+            None => Err(efi::Status::DEVICE_ERROR.into()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for Tcg2 {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for Tcg2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tcg2").field("capability", &self.capability().ok()).finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use alloc::{boxed::Box, vec};
+    use boot_services::MockBootServices;
+    use core::{ptr, slice};
+
+    /// TPM with SHA-1 and SHA-256 banks, keeping the events it logs; the protocol is the first field to be found
+    /// from its pointer.
+    #[repr(C)]
+    pub(crate) struct TestTpm {
+        protocol: Protocol,
+        pub events: Vec<(ExtendFlags, Vec<u8>, Vec<u8>)>,
+        pub log: Vec<u8>,
+        pub log_last_entry: usize,
+        pub log_format: EventLogFormat,
+    }
+
+    fn test_tpm<'a>(this: *mut Protocol) -> &'a mut TestTpm {
+        unsafe { &mut *(this as *mut TestTpm) }
+    }
+
+    extern "efiapi" fn get_capability(_this: *mut Protocol, capability: *mut BootServiceCapability) -> efi::Status {
+        let capability = unsafe { &mut *capability };
+        if (capability.size as usize) < mem::size_of::<BootServiceCapability>() {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        *capability = BootServiceCapability {
+            size: capability.size,
+            structure_version: Version { major: 1, minor: 1 },
+            protocol_version: Version { major: 1, minor: 1 },
+            hash_algorithm_bitmap: HashAlgorithms(HashAlgorithms::SHA1.0 | HashAlgorithms::SHA256.0),
+            supported_event_logs: EventLogFormat(EventLogFormat::TCG_1_2.0 | EventLogFormat::TCG_2.0),
+            tpm_present_flag: efi::Boolean::TRUE,
+            max_command_size: 4096,
+            max_response_size: 4096,
+            manufacturer_id: u32::from_be_bytes(*b"MSFT"),
+            number_of_pcr_banks: 2,
+            active_pcr_banks: HashAlgorithms::SHA256,
+        };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_event_log(
+        this: *mut Protocol,
+        format: EventLogFormat,
+        location: *mut efi::PhysicalAddress,
+        last_entry: *mut efi::PhysicalAddress,
+        truncated: *mut efi::Boolean,
+    ) -> efi::Status {
+        let tpm = test_tpm(this);
+        if format != tpm.log_format {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        unsafe {
+            *location = tpm.log.as_ptr() as efi::PhysicalAddress;
+            *last_entry = match tpm.log.is_empty() {
+                true => 0,
+                false => tpm.log[tpm.log_last_entry..].as_ptr() as efi::PhysicalAddress,
+            };
+            *truncated = efi::Boolean::FALSE;
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn hash_log_extend_event(
+        this: *mut Protocol,
+        flags: ExtendFlags,
+        data: efi::PhysicalAddress,
+        data_size: u64,
+        event: *mut Event,
+    ) -> efi::Status {
+        let event = unsafe { slice::from_raw_parts(event as *const u8, (*event).size as usize) };
+        let header = unsafe { ptr::read_unaligned(event[4..].as_ptr() as *const EventHeader) };
+        if header.pcr_index >= PcrIndex::COUNT || { header.header_size } as usize != mem::size_of::<EventHeader>() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let data = match data_size {
+            0 => Vec::new(),
+            size => unsafe { slice::from_raw_parts(data as *const u8, size as usize) }.to_vec(),
+        };
+        test_tpm(this).events.push((flags, event.to_vec(), data));
+        efi::Status::SUCCESS
+    }
+
+    /// Answers every command with a response of `TPM_RC_SUCCESS` holding the command code.
+    extern "efiapi" fn submit_command(
+        _this: *mut Protocol,
+        command_size: u32,
+        command: *mut u8,
+        response_size: u32,
+        response: *mut u8,
+    ) -> efi::Status {
+        let command = unsafe { slice::from_raw_parts(command, command_size as usize) };
+        let mut answer = vec![0x80, 0x01, 0, 0, 0, 14, 0, 0, 0, 0];
+        answer.extend_from_slice(&command[6..10]);
+        if (response_size as usize) < answer.len() {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(response, answer.len()) }.copy_from_slice(&answer);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_active_pcr_banks(_this: *mut Protocol, banks: *mut HashAlgorithms) -> efi::Status {
+        unsafe { *banks = HashAlgorithms::SHA256 };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn set_active_pcr_banks(_this: *mut Protocol, _banks: HashAlgorithms) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn get_result_of_set_active_pcr_banks(
+        _this: *mut Protocol,
+        _operation_present: *mut u32,
+        _response: *mut u32,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    /// Boot services locating a TPM whose event log is `log`, in `log_format`, with its last entry at `last_entry`.
+    pub(crate) fn tpm_boot_services(
+        log: Vec<u8>,
+        last_entry: usize,
+        log_format: EventLogFormat,
+    ) -> (MockBootServices, &'static TestTpm) {
+        let tpm = Box::leak(Box::new(TestTpm {
+            protocol: Protocol {
+                get_capability,
+                get_event_log,
+                hash_log_extend_event,
+                submit_command,
+                get_active_pcr_banks,
+                set_active_pcr_banks,
+                get_result_of_set_active_pcr_banks,
+            },
+            events: Vec::new(),
+            log,
+            log_last_entry: last_entry,
+            log_format,
+        }));
+        let tpm_ptr = tpm as *mut TestTpm as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<Tcg2Protocol, Protocol>()
+            .returning(move |_, _| Ok(unsafe { &mut (*(tpm_ptr as *mut TestTpm)).protocol }));
+        (boot_services, unsafe { &*(tpm_ptr as *const TestTpm) })
+    }
+
+    #[test]
+    fn test_capability() {
+        let (boot_services, _) = tpm_boot_services(Vec::new(), 0, EventLogFormat::TCG_2);
+        let tcg2 = Tcg2::locate(&boot_services).unwrap();
+        let capability = tcg2.capability().unwrap();
+        assert!(capability.tpm_present);
+        assert!(capability.hash_algorithms.contains(HashAlgorithms::SHA256));
+        assert!(!capability.hash_algorithms.contains(HashAlgorithms::SHA384));
+        assert!(capability.supported_event_logs.contains(EventLogFormat::TCG_2));
+        assert_eq!(u32::from_be_bytes(*b"MSFT"), capability.manufacturer_id);
+        assert_eq!(HashAlgorithms::SHA256, tcg2.active_pcr_banks().unwrap());
+        assert_eq!(30, mem::size_of::<BootServiceCapability>());
+    }
+
+    #[test]
+    fn test_hash_log_extend_event() {
+        let (boot_services, tpm) = tpm_boot_services(Vec::new(), 0, EventLogFormat::TCG_2);
+        let mut tcg2 = Tcg2::locate(&boot_services).unwrap();
+        assert_eq!(None, PcrIndex::new(24));
+        let pcr = PcrIndex::new(7).unwrap();
+        tcg2.hash_log_extend_event(ExtendFlags::NONE, pcr, EventType::EFI_ACTION, b"data", b"Event").unwrap();
+
+        let (flags, event, data) = &tpm.events[0];
+        assert_eq!(ExtendFlags::NONE, *flags);
+        assert_eq!(b"data", &data[..]);
+        let mut expected = vec![23, 0, 0, 0, 14, 0, 0, 0, 1, 0, 7, 0, 0, 0, 0x07, 0, 0, 0x80];
+        expected.extend_from_slice(b"Event");
+        assert_eq!(&expected, event);
+    }
+
+    #[test]
+    fn test_submit_command() {
+        let (boot_services, _) = tpm_boot_services(Vec::new(), 0, EventLogFormat::TCG_2);
+        let mut tcg2 = Tcg2::locate(&boot_services).unwrap();
+        // TPM2_GetRandom of 8 bytes.
+        let command = [0x80, 0x01, 0, 0, 0, 12, 0, 0, 0x01, 0x7B, 0, 8];
+        let mut response = [0; 32];
+        assert_eq!(14, tcg2.submit_command(&command, &mut response).unwrap());
+        assert_eq!([0, 0, 0x01, 0x7B], response[10..14]);
+        assert_eq!(efi::Status::BUFFER_TOO_SMALL, tcg2.submit_command(&command, &mut [0; 8]).unwrap_err());
+    }
+
+    #[test]
+    fn test_get_event_log() {
+        let mut log = event_log::test::spec_id_event();
+        let last_entry = log.len();
+        log.extend(event_log::test::pcr_event2(
+            7,
+            EventType::SEPARATOR,
+            &[(event_log::AlgorithmId::SHA256, &[1; 32])],
+            &[0; 4],
+        ));
+        let size = log.len();
+        // Entries past the last one are not part of the log.
+        log.extend([0xFF; 16]);
+        let (boot_services, _) = tpm_boot_services(log, last_entry, EventLogFormat::TCG_2);
+        let tcg2 = Tcg2::locate(&boot_services).unwrap();
+        let log = tcg2.get_event_log(EventLogFormat::TCG_2).unwrap();
+        assert_eq!(size, log.data().len());
+        assert!(!log.is_truncated());
+        let events = log.events().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(2, events.len());
+        assert_eq!(EventType::SEPARATOR, events[1].event_type);
+        assert_eq!(efi::Status::INVALID_PARAMETER, tcg2.get_event_log(EventLogFormat::TCG_1_2).unwrap_err());
+        assert_eq!(efi::Status::UNSUPPORTED, tcg2.get_event_log(EventLogFormat(4)).unwrap_err());
+
+        let log = event_log::test::pcr_event(0, EventType::POST_CODE, [0; 20], b"code");
+        let size = log.len();
+        let (boot_services, _) = tpm_boot_services(log, 0, EventLogFormat::TCG_1_2);
+        let tcg2 = Tcg2::locate(&boot_services).unwrap();
+        assert_eq!(size, tcg2.get_event_log(EventLogFormat::TCG_1_2).unwrap().data().len());
+    }
+}
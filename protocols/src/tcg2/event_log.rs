@@ -0,0 +1,411 @@
+//! Parsing of the TCG event logs.
+//!
+//! An [`EventLog`] is either in the SHA-1 only format of TPM 1.2, a sequence of `TCG_PCR_EVENT`, or in the crypto
+//! agile format of TPM 2.0, a `TCG_PCR_EVENT` holding the `TCG_EfiSpecIDEvent` which lists the digest sizes, followed
+//! by `TCG_PCR_EVENT2` entries holding a digest per PCR bank:
+//!
+//! ```ignore
+//! let tcg2 = Tcg2::locate(&boot_services)?;
+//! for event in tcg2.get_event_log(EventLogFormat::TCG_2)?.events() {
+//!     let event = event?;
+//!     let sha256 = event.digest(AlgorithmId::SHA256);
+//! }
+//! ```
+//!
+//! [TCG PC Client Platform Firmware Profile Specification](https://trustedcomputinggroup.org/resource/pc-client-specific-platform-firmware-profile-specification/)
+
+use alloc::vec::Vec;
+use core::{mem, ptr};
+
+use r_efi::efi;
+use status::EfiError;
+
+use super::{EventLogFormat, EventType, PcrIndex};
+
+/// Size of `TCG_PCR_EVENT` before the data of the event.
+const PCR_EVENT_HEADER_SIZE: usize = 32;
+
+/// Signature of the `TCG_EfiSpecIDEvent` of the crypto agile logs.
+pub const SPEC_ID_EVENT_SIGNATURE: [u8; 16] = *b"Spec ID Event03\0";
+
+/// Hash algorithm of a digest, `TPM_ALG_ID`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct AlgorithmId(pub u16);
+
+impl AlgorithmId {
+    pub const SHA1: AlgorithmId = AlgorithmId(0x0004);
+    pub const SHA256: AlgorithmId = AlgorithmId(0x000B);
+    pub const SHA384: AlgorithmId = AlgorithmId(0x000C);
+    pub const SHA512: AlgorithmId = AlgorithmId(0x000D);
+    pub const SM3_256: AlgorithmId = AlgorithmId(0x0012);
+}
+
+/// Size of the digests of an algorithm of a crypto agile log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AlgorithmSize {
+    pub algorithm: AlgorithmId,
+    pub digest_size: u16,
+}
+
+/// The `TCG_EfiSpecIDEvent` starting a crypto agile log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecIdEvent {
+    pub platform_class: u32,
+    pub spec_version_major: u8,
+    pub spec_version_minor: u8,
+    pub spec_errata: u8,
+    /// Size of `UINTN`, 1 for 32 bits and 2 for 64 bits.
+    pub uintn_size: u8,
+    /// The algorithms of the digests of every event.
+    pub algorithms: Vec<AlgorithmSize>,
+    pub vendor_info: Vec<u8>,
+}
+
+impl SpecIdEvent {
+    /// Parses the data of the first event of a crypto agile log.
+    pub fn parse(data: &[u8]) -> Result<Self, EfiError> {
+        let mut reader = Reader(data);
+        if reader.bytes(SPEC_ID_EVENT_SIGNATURE.len())? != SPEC_ID_EVENT_SIGNATURE {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let platform_class = reader.u32()?;
+        let spec_version_minor = reader.u8()?;
+        let spec_version_major = reader.u8()?;
+        let spec_errata = reader.u8()?;
+        let uintn_size = reader.u8()?;
+        let count = reader.u32()?;
+        // Every algorithm takes 4 bytes, which bounds the allocation to the size of the data.
+        if count as usize > reader.0.len() / 4 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let algorithms = (0..count)
+            .map(|_| Ok(AlgorithmSize { algorithm: AlgorithmId(reader.u16()?), digest_size: reader.u16()? }))
+            .collect::<Result<Vec<_>, EfiError>>()?;
+        let vendor_info_size = reader.u8()?;
+        let vendor_info = reader.bytes(vendor_info_size as usize)?.to_vec();
+        Ok(Self {
+            platform_class,
+            spec_version_major,
+            spec_version_minor,
+            spec_errata,
+            uintn_size,
+            algorithms,
+            vendor_info,
+        })
+    }
+
+    fn digest_size(&self, algorithm: AlgorithmId) -> Option<usize> {
+        self.algorithms.iter().find(|size| size.algorithm == algorithm).map(|size| size.digest_size as usize)
+    }
+}
+
+/// Digest of the measurement of an event, in one PCR bank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Digest<'a> {
+    pub algorithm: AlgorithmId,
+    pub digest: &'a [u8],
+}
+
+/// Entry of an event log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event<'a> {
+    /// Index of the PCR extended with the digests, as logged.
+    pub pcr_index: u32,
+    pub event_type: EventType,
+    /// A SHA-1 digest in the TCG 1.2 format, a digest per bank in the crypto agile format.
+    pub digests: Vec<Digest<'a>>,
+    pub data: &'a [u8],
+}
+
+impl<'a> Event<'a> {
+    /// The PCR extended with the digests, if the index is one of a PCR.
+    pub fn pcr(&self) -> Option<PcrIndex> {
+        PcrIndex::new(self.pcr_index)
+    }
+
+    /// The digest for the bank of `algorithm`.
+    pub fn digest(&self, algorithm: AlgorithmId) -> Option<&'a [u8]> {
+        self.digests.iter().find(|digest| digest.algorithm == algorithm).map(|digest| digest.digest)
+    }
+}
+
+/// Bounds checked reads of little endian fields.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, size: usize) -> Result<&'a [u8], EfiError> {
+        if size > self.0.len() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let (bytes, rest) = self.0.split_at(size);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, EfiError> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, EfiError> {
+        self.bytes(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, EfiError> {
+        self.bytes(4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Parses a `TCG_PCR_EVENT`.
+fn parse_pcr_event<'a>(reader: &mut Reader<'a>) -> Result<Event<'a>, EfiError> {
+    let pcr_index = reader.u32()?;
+    let event_type = EventType(reader.u32()?);
+    let digest = reader.bytes(20)?;
+    let size = reader.u32()?;
+    let data = reader.bytes(size as usize)?;
+    Ok(Event { pcr_index, event_type, digests: alloc::vec![Digest { algorithm: AlgorithmId::SHA1, digest }], data })
+}
+
+/// Parses a `TCG_PCR_EVENT2`, whose digest sizes are given by `spec_id`.
+fn parse_pcr_event2<'a>(reader: &mut Reader<'a>, spec_id: &SpecIdEvent) -> Result<Event<'a>, EfiError> {
+    let pcr_index = reader.u32()?;
+    let event_type = EventType(reader.u32()?);
+    let count = reader.u32()?;
+    if count as usize > spec_id.algorithms.len() {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    let digests = (0..count)
+        .map(|_| {
+            let algorithm = AlgorithmId(reader.u16()?);
+            let size = spec_id.digest_size(algorithm).ok_or(efi::Status::INVALID_PARAMETER)?;
+            Ok(Digest { algorithm, digest: reader.bytes(size)? })
+        })
+        .collect::<Result<Vec<_>, EfiError>>()?;
+    let size = reader.u32()?;
+    let data = reader.bytes(size as usize)?;
+    Ok(Event { pcr_index, event_type, digests, data })
+}
+
+/// Event log of the measurements of the boot.
+#[derive(Debug, Clone)]
+pub struct EventLog<'a> {
+    format: EventLogFormat,
+    data: &'a [u8],
+    spec_id: Option<SpecIdEvent>,
+    truncated: bool,
+}
+
+impl<'a> EventLog<'a> {
+    /// Parses the log `data` in `format`, `TCG_1_2` or `TCG_2`.
+    ///
+    /// The header of a crypto agile log is checked here, the events as they are iterated.
+    pub fn parse(format: EventLogFormat, data: &'a [u8]) -> Result<Self, EfiError> {
+        let spec_id = match format {
+            EventLogFormat::TCG_1_2 => None,
+            EventLogFormat::TCG_2 => {
+                let header = parse_pcr_event(&mut Reader(data))?;
+                if header.event_type != EventType::NO_ACTION {
+                    return Err(efi::Status::INVALID_PARAMETER.into());
+                }
+                Some(SpecIdEvent::parse(header.data)?)
+            }
+            _ => return Err(efi::Status::UNSUPPORTED.into()),
+        };
+        Ok(Self { format, data, spec_id, truncated: false })
+    }
+
+    pub fn format(&self) -> EventLogFormat {
+        self.format
+    }
+
+    /// The header of a crypto agile log.
+    pub fn spec_id(&self) -> Option<&SpecIdEvent> {
+        self.spec_id.as_ref()
+    }
+
+    /// Whether events were dropped because the log was full.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub(crate) fn set_truncated(&mut self, truncated: bool) {
+        self.truncated = truncated;
+    }
+
+    /// The raw log.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The events of the log, from the first one, which is the header of a crypto agile log.
+    ///
+    /// The iteration stops after a malformed event, returned as an `INVALID_PARAMETER` error.
+    pub fn events(&self) -> Events<'_, 'a> {
+        Events { log: self, reader: Reader(self.data), header: true }
+    }
+}
+
+/// Iterator over the events of an [`EventLog`].
+pub struct Events<'l, 'a> {
+    log: &'l EventLog<'a>,
+    reader: Reader<'a>,
+    /// Whether the next event is the first one, in the TCG 1.2 format in every log.
+    header: bool,
+}
+
+impl<'l, 'a> Iterator for Events<'l, 'a> {
+    type Item = Result<Event<'a>, EfiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.0.is_empty() {
+            return None;
+        }
+        let header = mem::replace(&mut self.header, false);
+        let event = match &self.log.spec_id {
+            Some(spec_id) if !header => parse_pcr_event2(&mut self.reader, spec_id),
+            _ => parse_pcr_event(&mut self.reader),
+        };
+        if event.is_err() {
+            self.reader.0 = &[];
+        }
+        Some(event)
+    }
+}
+
+/// Size of the entry at `entry`, in the TCG 1.2 format without `spec_id` and the crypto agile format with it.
+///
+/// # Safety
+///
+/// `entry` must point to a whole entry; its fields are read one after the other to never read past the entry.
+pub(crate) unsafe fn entry_size(entry: *const u8, spec_id: Option<&SpecIdEvent>) -> Result<usize, EfiError> {
+    let read_u32 = |offset: usize| ptr::read_unaligned(entry.add(offset) as *const u32) as usize;
+    let Some(spec_id) = spec_id else {
+        return Ok(PCR_EVENT_HEADER_SIZE + read_u32(PCR_EVENT_HEADER_SIZE - 4));
+    };
+    let mut offset = 12;
+    for _ in 0..read_u32(8) {
+        let algorithm = AlgorithmId(ptr::read_unaligned(entry.add(offset) as *const u16));
+        offset += 2 + spec_id.digest_size(algorithm).ok_or(efi::Status::INVALID_PARAMETER)?;
+    }
+    Ok(offset + 4 + read_u32(offset))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use alloc::vec;
+
+    /// A `TCG_PCR_EVENT`.
+    pub(crate) fn pcr_event(pcr_index: u32, event_type: EventType, digest: [u8; 20], data: &[u8]) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend_from_slice(&pcr_index.to_le_bytes());
+        event.extend_from_slice(&event_type.0.to_le_bytes());
+        event.extend_from_slice(&digest);
+        event.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        event.extend_from_slice(data);
+        event
+    }
+
+    /// A `TCG_PCR_EVENT2`.
+    pub(crate) fn pcr_event2(
+        pcr_index: u32,
+        event_type: EventType,
+        digests: &[(AlgorithmId, &[u8])],
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend_from_slice(&pcr_index.to_le_bytes());
+        event.extend_from_slice(&event_type.0.to_le_bytes());
+        event.extend_from_slice(&(digests.len() as u32).to_le_bytes());
+        for (algorithm, digest) in digests {
+            event.extend_from_slice(&algorithm.0.to_le_bytes());
+            event.extend_from_slice(digest);
+        }
+        event.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        event.extend_from_slice(data);
+        event
+    }
+
+    /// The header of a crypto agile log with SHA-1 and SHA-256 banks.
+    pub(crate) fn spec_id_event() -> Vec<u8> {
+        let mut data = SPEC_ID_EVENT_SIGNATURE.to_vec();
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&[0, 2, 0, 2]);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&[0x04, 0, 20, 0, 0x0B, 0, 32, 0]);
+        data.extend_from_slice(&[3, b'M', b'S', b'F']);
+        pcr_event(0, EventType::NO_ACTION, [0; 20], &data)
+    }
+
+    #[test]
+    fn test_tcg_1_2_log() {
+        let mut data = pcr_event(0, EventType::S_CRTM_VERSION, [1; 20], b"1.0");
+        data.extend(pcr_event(7, EventType::SEPARATOR, [2; 20], &[0; 4]));
+        let log = EventLog::parse(EventLogFormat::TCG_1_2, &data).unwrap();
+        assert_eq!(None, log.spec_id());
+
+        let events = log.events().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(2, events.len());
+        assert_eq!(EventType::S_CRTM_VERSION, events[0].event_type);
+        assert_eq!(b"1.0", events[0].data);
+        assert_eq!(Some(PcrIndex::new(7).unwrap()), events[1].pcr());
+        assert_eq!(Some(&[2; 20][..]), events[1].digest(AlgorithmId::SHA1));
+        assert_eq!(None, events[1].digest(AlgorithmId::SHA256));
+        assert_eq!(data.len() - 35, unsafe { entry_size(data[35..].as_ptr(), None) }.unwrap());
+    }
+
+    #[test]
+    fn test_crypto_agile_log() {
+        let mut data = spec_id_event();
+        let header_size = data.len();
+        data.extend(pcr_event2(
+            4,
+            EventType::EFI_BOOT_SERVICES_APPLICATION,
+            &[(AlgorithmId::SHA256, &[3; 32])],
+            b"app",
+        ));
+        let last = data.len();
+        data.extend(pcr_event2(
+            7,
+            EventType::EFI_VARIABLE_DRIVER_CONFIG,
+            &[(AlgorithmId::SHA1, &[4; 20]), (AlgorithmId::SHA256, &[5; 32])],
+            b"SecureBoot",
+        ));
+        let log = EventLog::parse(EventLogFormat::TCG_2, &data).unwrap();
+        let spec_id = log.spec_id().unwrap();
+        assert_eq!((2, 0, 2), (spec_id.spec_version_major, spec_id.spec_version_minor, spec_id.uintn_size));
+        assert_eq!(AlgorithmSize { algorithm: AlgorithmId::SHA256, digest_size: 32 }, spec_id.algorithms[1]);
+        assert_eq!(b"MSF", &spec_id.vendor_info[..]);
+
+        let events = log.events().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(3, events.len());
+        assert_eq!(EventType::NO_ACTION, events[0].event_type);
+        assert_eq!(Some(&[3; 32][..]), events[1].digest(AlgorithmId::SHA256));
+        assert_eq!(None, events[1].digest(AlgorithmId::SHA1));
+        assert_eq!(2, events[2].digests.len());
+        assert_eq!(Some(&[4; 20][..]), events[2].digest(AlgorithmId::SHA1));
+        assert_eq!(b"SecureBoot", events[2].data);
+
+        assert_eq!(header_size, unsafe { entry_size(data.as_ptr(), None) }.unwrap());
+        assert_eq!(data.len() - last, unsafe { entry_size(data[last..].as_ptr(), Some(spec_id)) }.unwrap());
+    }
+
+    #[test]
+    fn test_malformed_log() {
+        assert_eq!(efi::Status::UNSUPPORTED, EventLog::parse(EventLogFormat(4), &[]).unwrap_err());
+        let header = pcr_event(0, EventType::NO_ACTION, [0; 20], b"Spec ID Event02\0");
+        assert_eq!(efi::Status::INVALID_PARAMETER, EventLog::parse(EventLogFormat::TCG_2, &header).unwrap_err());
+
+        // A digest of an algorithm missing from the header, then a truncated event.
+        let mut data = spec_id_event();
+        data.extend(pcr_event2(0, EventType::POST_CODE, &[(AlgorithmId::SHA384, &[0; 48])], &[]));
+        let log = EventLog::parse(EventLogFormat::TCG_2, &data).unwrap();
+        let events = log.events().collect::<Vec<_>>();
+        assert_eq!(2, events.len());
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), events[1]);
+
+        let data = pcr_event(0, EventType::POST_CODE, [0; 20], b"code");
+        let log = EventLog::parse(EventLogFormat::TCG_1_2, &data[..data.len() - 1]).unwrap();
+        assert_eq!(vec![Err(efi::Status::INVALID_PARAMETER.into())], log.events().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,732 @@
+//! TCP4 and TCP6 protocols.
+//!
+//! [`Tcp`] is a TCP connection over a child of the TCP service binding of a network interface. Its operations return
+//! a [`Completion`], which is waited for with [`Completion::wait`] or, with the `async` feature, awaited as a future:
+//!
+//! ```ignore
+//! let mut tcp = Tcp4::create(&boot_services, nic_handle, image_handle)?;
+//! tcp.connect(Ipv4Addr::new(192, 168, 0, 1), 8080)?.wait()?;
+//! tcp.send(b"GET / HTTP/1.0\r\n\r\n")?.wait()?;
+//! let received = tcp.receive(&mut buffer)?.await?;
+//! tcp.close(false)?.wait()?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 28.1. EFI TCPv4 Protocol](https://uefi.org/specs/UEFI/2.10/28_Network_Protocols_TCP_IP_and_Configuration.html#efi-tcpv4-protocol)
+//!
+//! [UEFI Spec Documentation: 28.2. EFI TCPv6 Protocol](https://uefi.org/specs/UEFI/2.10/28_Network_Protocols_TCP_IP_and_Configuration.html#efi-tcpv6-protocol)
+
+use alloc::boxed::Box;
+use core::{
+    fmt,
+    marker::PhantomData,
+    net::{Ipv4Addr, Ipv6Addr},
+    ptr,
+};
+
+use boot_services::{event::EventType, protocol_handler, tpl::Tpl, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::service_binding::{ChildProtocol, ServiceBindingChild};
+
+use efi::protocols::{tcp4, tcp6};
+
+/// Time to live, or hop limit, of the sent packets.
+const TIME_TO_LIVE: u8 = 64;
+
+/// Version of the TCP protocol, [`protocol_handler::Tcp4`] or [`protocol_handler::Tcp6`].
+///
+/// The tokens of both versions have the same layout, the TCP4 definitions are used for both.
+pub trait TcpVersion: ChildProtocol {
+    /// Address of the remote endpoint.
+    type Address: Copy + fmt::Debug;
+
+    /// The protocol, to create children with its service binding.
+    const PROTOCOL: Self;
+
+    /// Configures an active connection to the remote endpoint from the default address of the interface, or resets
+    /// the configuration if `remote` is `None`.
+    fn configure(tcp: &Self::Interface, remote: Option<(Self::Address, u16)>) -> efi::Status;
+
+    /// The other functions call the function of the protocol with the same name.
+    fn connect(tcp: &Self::Interface, token: *mut tcp4::ConnectionToken) -> efi::Status;
+    fn transmit(tcp: &Self::Interface, token: *mut tcp4::IoToken) -> efi::Status;
+    fn receive(tcp: &Self::Interface, token: *mut tcp4::IoToken) -> efi::Status;
+    fn close(tcp: &Self::Interface, token: *mut tcp4::CloseToken) -> efi::Status;
+    fn cancel(tcp: &Self::Interface, token: *mut tcp4::CompletionToken) -> efi::Status;
+    fn poll(tcp: &Self::Interface) -> efi::Status;
+}
+
+macro_rules! impl_tcp_version {
+    ($protocol_struct:ident, $protocol:ident) => {
+        const PROTOCOL: Self = protocol_handler::$protocol_struct;
+
+        fn connect(tcp: &$protocol::Protocol, token: *mut tcp4::ConnectionToken) -> efi::Status {
+            unsafe {
+                r_efi::efi_call(tcp.connect, (tcp as *const _ as *mut _, token as *mut $protocol::ConnectionToken))
+            }
+        }
+
+        fn transmit(tcp: &$protocol::Protocol, token: *mut tcp4::IoToken) -> efi::Status {
+            unsafe { r_efi::efi_call(tcp.transmit, (tcp as *const _ as *mut _, token as *mut $protocol::IoToken)) }
+        }
+
+        fn receive(tcp: &$protocol::Protocol, token: *mut tcp4::IoToken) -> efi::Status {
+            unsafe { r_efi::efi_call(tcp.receive, (tcp as *const _ as *mut _, token as *mut $protocol::IoToken)) }
+        }
+
+        fn close(tcp: &$protocol::Protocol, token: *mut tcp4::CloseToken) -> efi::Status {
+            unsafe { r_efi::efi_call(tcp.close, (tcp as *const _ as *mut _, token as *mut $protocol::CloseToken)) }
+        }
+
+        fn cancel(tcp: &$protocol::Protocol, token: *mut tcp4::CompletionToken) -> efi::Status {
+            unsafe {
+                r_efi::efi_call(tcp.cancel, (tcp as *const _ as *mut _, token as *mut $protocol::CompletionToken))
+            }
+        }
+
+        fn poll(tcp: &$protocol::Protocol) -> efi::Status {
+            unsafe { r_efi::efi_call(tcp.poll, (tcp as *const _ as *mut _,)) }
+        }
+    };
+}
+
+impl TcpVersion for protocol_handler::Tcp4 {
+    type Address = Ipv4Addr;
+
+    fn configure(tcp: &tcp4::Protocol, remote: Option<(Ipv4Addr, u16)>) -> efi::Status {
+        let Some((address, port)) = remote else {
+            return unsafe { r_efi::efi_call(tcp.configure, (tcp as *const _ as *mut _, ptr::null_mut())) };
+        };
+        let mut config = tcp4::ConfigData {
+            type_of_service: 0,
+            time_to_live: TIME_TO_LIVE,
+            access_point: tcp4::AccessPoint {
+                use_default_address: efi::Boolean::TRUE,
+                remote_address: efi::Ipv4Address { addr: address.octets() },
+                remote_port: port,
+                active_flag: efi::Boolean::TRUE,
+                ..Default::default()
+            },
+            control_option: ptr::null_mut(),
+        };
+        unsafe { r_efi::efi_call(tcp.configure, (tcp as *const _ as *mut _, &mut config)) }
+    }
+
+    impl_tcp_version!(Tcp4, tcp4);
+}
+
+impl TcpVersion for protocol_handler::Tcp6 {
+    type Address = Ipv6Addr;
+
+    fn configure(tcp: &tcp6::Protocol, remote: Option<(Ipv6Addr, u16)>) -> efi::Status {
+        let Some((address, port)) = remote else {
+            return unsafe { r_efi::efi_call(tcp.configure, (tcp as *const _ as *mut _, ptr::null_mut())) };
+        };
+        let mut config = tcp6::ConfigData {
+            traffic_class: 0,
+            hop_limit: TIME_TO_LIVE,
+            access_point: tcp6::AccessPoint {
+                // The unspecified address selects the source address from the routes.
+                station_address: efi::Ipv6Address { addr: [0; 16] },
+                station_port: 0,
+                remote_address: efi::Ipv6Address { addr: address.octets() },
+                remote_port: port,
+                active_flag: efi::Boolean::TRUE,
+            },
+            control_option: ptr::null_mut(),
+        };
+        unsafe { r_efi::efi_call(tcp.configure, (tcp as *const _ as *mut _, &mut config)) }
+    }
+
+    impl_tcp_version!(Tcp6, tcp6);
+}
+
+/// Token of an operation, boxed so that it does not move while the protocol updates it.
+enum Token {
+    Connection(tcp4::ConnectionToken),
+    Transmit(tcp4::IoToken, tcp4::TransmitData<1>),
+    Receive(tcp4::IoToken, tcp4::ReceiveData<1>),
+    Close(tcp4::CloseToken),
+}
+
+impl Token {
+    fn completion_token(&mut self) -> &mut tcp4::CompletionToken {
+        match self {
+            Token::Connection(token) => &mut token.completion_token,
+            Token::Transmit(token, _) | Token::Receive(token, _) => &mut token.completion_token,
+            Token::Close(token) => &mut token.completion_token,
+        }
+    }
+
+    fn completion_token_from_event(event: efi::Event) -> tcp4::CompletionToken {
+        tcp4::CompletionToken { event, status: efi::Status::SUCCESS }
+    }
+
+    /// The number of bytes transferred by an I/O operation.
+    fn data_length(&self) -> usize {
+        match self {
+            Token::Transmit(_, data) => data.data_length as usize,
+            Token::Receive(_, data) => data.data_length as usize,
+            _ => 0,
+        }
+    }
+}
+
+/// A TCP connection, over a child of a TCP service binding.
+///
+/// The child is destroyed when the connection is dropped, which resets the connection if it was not closed.
+pub struct Tcp<'a, P: TcpVersion, B: BootServices> {
+    boot_services: &'a B,
+    child: ServiceBindingChild<'a, P, B>,
+}
+
+/// A TCP connection over IPv4.
+pub type Tcp4<'a, B> = Tcp<'a, protocol_handler::Tcp4, B>;
+
+/// A TCP connection over IPv6.
+pub type Tcp6<'a, B> = Tcp<'a, protocol_handler::Tcp6, B>;
+
+impl<'a, P: TcpVersion, B: BootServices> Tcp<'a, P, B> {
+    /// Creates a TCP child with the service binding of a network interface, on behalf of the agent.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn create(
+        boot_services: &'a B,
+        service_handle: efi::Handle,
+        agent_handle: efi::Handle,
+    ) -> Result<Self, EfiError> {
+        let child = ServiceBindingChild::create(boot_services, service_handle, &P::PROTOCOL, agent_handle)?;
+        Ok(Self { boot_services, child })
+    }
+
+    /// Starts an operation with a token completed through a new event.
+    fn start<T>(
+        &self,
+        mut token: Box<Token>,
+        operation: impl FnOnce(&mut Token) -> efi::Status,
+        output: fn(&Token) -> T,
+    ) -> Result<Completion<'_, P, B, T>, EfiError> {
+        let event = self.boot_services.create_event(EventType::NONE, Tpl::CALLBACK, None, None::<&'static ()>)?;
+        *token.completion_token() = Token::completion_token_from_event(event);
+        match operation(&mut token) {
+            s if s.is_error() => {
+                let _ = self.boot_services.close_event(event);
+                Err(s.into())
+            }
+            _ => Ok(Completion {
+                boot_services: self.boot_services,
+                protocol: &self.child,
+                token,
+                event,
+                completed: false,
+                output,
+                _borrow: PhantomData,
+            }),
+        }
+    }
+
+    /// Connects to a remote endpoint, from the default address of the interface and an ephemeral port.
+    pub fn connect(&mut self, address: P::Address, port: u16) -> Result<Completion<'_, P, B, ()>, EfiError> {
+        match P::configure(&self.child, Some((address, port))) {
+            s if s.is_error() => return Err(s.into()),
+            _ => (),
+        }
+        let token = Box::new(Token::Connection(tcp4::ConnectionToken {
+            completion_token: Token::completion_token_from_event(ptr::null_mut()),
+        }));
+        let protocol = &*self.child;
+        self.start(
+            token,
+            |token| match token {
+                Token::Connection(token) => P::connect(protocol, token),
+                _ => unreachable!(),
+            },
+            |_| (),
+        )
+    }
+
+    /// Sends `data`, the completion gives the number of bytes sent.
+    pub fn send<'t>(&'t self, data: &'t [u8]) -> Result<Completion<'t, P, B, usize>, EfiError> {
+        let data_length = u32::try_from(data.len()).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        let fragment = tcp4::FragmentData { fragment_length: data_length, fragment_buffer: data.as_ptr() as *mut _ };
+        let token = Box::new(Token::Transmit(
+            tcp4::IoToken {
+                completion_token: Token::completion_token_from_event(ptr::null_mut()),
+                packet: tcp4::IoTokenPacket { tx_data: ptr::null_mut() },
+            },
+            tcp4::TransmitData {
+                push: efi::Boolean::TRUE,
+                urgent: efi::Boolean::FALSE,
+                data_length,
+                fragment_count: 1,
+                fragment_table: [fragment],
+            },
+        ));
+        let protocol = &*self.child;
+        self.start(
+            token,
+            |token| match token {
+                Token::Transmit(token, data) => {
+                    token.packet.tx_data = data as *mut tcp4::TransmitData<1> as *mut tcp4::TransmitData;
+                    P::transmit(protocol, token)
+                }
+                _ => unreachable!(),
+            },
+            Token::data_length,
+        )
+    }
+
+    /// Receives data into `buffer`, the completion gives the number of bytes received.
+    pub fn receive<'t>(&'t self, buffer: &'t mut [u8]) -> Result<Completion<'t, P, B, usize>, EfiError> {
+        let data_length = u32::try_from(buffer.len()).unwrap_or(u32::MAX);
+        let fragment =
+            tcp4::FragmentData { fragment_length: data_length, fragment_buffer: buffer.as_mut_ptr() as *mut _ };
+        let token = Box::new(Token::Receive(
+            tcp4::IoToken {
+                completion_token: Token::completion_token_from_event(ptr::null_mut()),
+                packet: tcp4::IoTokenPacket { rx_data: ptr::null_mut() },
+            },
+            tcp4::ReceiveData {
+                urgent_flag: efi::Boolean::FALSE,
+                data_length,
+                fragment_count: 1,
+                fragment_table: [fragment],
+            },
+        ));
+        let protocol = &*self.child;
+        self.start(
+            token,
+            |token| match token {
+                Token::Receive(token, data) => {
+                    token.packet.rx_data = data as *mut tcp4::ReceiveData<1> as *mut tcp4::ReceiveData;
+                    P::receive(protocol, token)
+                }
+                _ => unreachable!(),
+            },
+            Token::data_length,
+        )
+    }
+
+    /// Closes the connection gracefully, or resets it if `abort` is set.
+    pub fn close(&mut self, abort: bool) -> Result<Completion<'_, P, B, ()>, EfiError> {
+        let token = Box::new(Token::Close(tcp4::CloseToken {
+            completion_token: Token::completion_token_from_event(ptr::null_mut()),
+            abort_on_close: abort.into(),
+        }));
+        let protocol = &*self.child;
+        self.start(
+            token,
+            |token| match token {
+                Token::Close(token) => P::close(protocol, token),
+                _ => unreachable!(),
+            },
+            |_| (),
+        )
+    }
+
+    /// Resets the configuration of the connection, so that it can connect again.
+    pub fn reset(&mut self) -> Result<(), EfiError> {
+        match P::configure(&self.child, None) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Processes the packets of the network interface, to make progress on the operations in flight.
+    pub fn poll(&self) {
+        // An error only means that there was nothing to process.
+        let _ = P::poll(&self.child);
+    }
+}
+
+impl<P: TcpVersion, B: BootServices> fmt::Debug for Tcp<'_, P, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tcp").field("child", &self.child).finish()
+    }
+}
+
+/// An operation in flight on a [`Tcp`] connection.
+///
+/// The operation is completed with [`Completion::wait`] or, with the `async` feature, by awaiting the completion.
+/// Dropping it before its end cancels the operation.
+#[must_use = "the operation is cancelled if the completion is dropped"]
+pub struct Completion<'t, P: TcpVersion, B: BootServices, T> {
+    boot_services: &'t B,
+    protocol: &'t P::Interface,
+    token: Box<Token>,
+    event: efi::Event,
+    completed: bool,
+    output: fn(&Token) -> T,
+    /// The connection and the buffers of the operation stay borrowed until its end.
+    _borrow: PhantomData<&'t mut [u8]>,
+}
+
+impl<P: TcpVersion, B: BootServices, T> Completion<'_, P, B, T> {
+    /// The result of the operation, `None` while it is in flight.
+    fn check(&mut self) -> Option<Result<T, EfiError>> {
+        if self.completed {
+            return Some(Err(efi::Status::ALREADY_STARTED.into()));
+        }
+        match self.boot_services.check_event(self.event) {
+            Ok(()) => {
+                self.completed = true;
+                Some(match self.token.completion_token().status {
+                    s if s.is_error() => Err(s.into()),
+                    _ => Ok((self.output)(&self.token)),
+                })
+            }
+            Err(status) if status == efi::Status::NOT_READY => None,
+            Err(status) => Some(Err(status)),
+        }
+    }
+
+    /// Waits for the end of the operation, polling the network interface.
+    pub fn wait(mut self) -> Result<T, EfiError> {
+        loop {
+            if let Some(result) = self.check() {
+                return result;
+            }
+            let _ = P::poll(self.protocol);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<P: TcpVersion, B: BootServices, T> core::future::Future for Completion<'_, P, B, T> {
+    type Output = Result<T, EfiError>;
+
+    fn poll(self: core::pin::Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> core::task::Poll<Self::Output> {
+        use core::task::Poll;
+        // The completion is not pinned structurally, the token it owns is boxed.
+        let this = self.get_mut();
+        if let Some(result) = this.check() {
+            return Poll::Ready(result);
+        }
+        let _ = P::poll(this.protocol);
+        match this.check() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<P: TcpVersion, B: BootServices, T> Drop for Completion<'_, P, B, T> {
+    fn drop(&mut self) {
+        if !self.completed && self.boot_services.check_event(self.event).is_err() {
+            // The protocol no longer uses the token once the operation is cancelled.
+            let _ = P::cancel(self.protocol, self.token.completion_token());
+        }
+        let _ = self.boot_services.close_event(self.event);
+    }
+}
+
+impl<P: TcpVersion, B: BootServices, T> fmt::Debug for Completion<'_, P, B, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Completion").field("event", &self.event).field("completed", &self.completed).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+    use boot_services::MockBootServices;
+    use core::{
+        cell::{Cell, RefCell},
+        ffi::c_void,
+        slice,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use std::sync::Mutex;
+
+    static SIGNALED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+    static NEXT_EVENT: AtomicUsize = AtomicUsize::new(0x100);
+
+    type ServiceBindingProtocol = efi::protocols::service_binding::Protocol;
+
+    extern "efiapi" fn create_child(_this: *mut ServiceBindingProtocol, child_handle: *mut efi::Handle) -> efi::Status {
+        unsafe { *child_handle = 2_usize as efi::Handle };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn destroy_child(_this: *mut ServiceBindingProtocol, _child_handle: efi::Handle) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    static mut SERVICE_BINDING: ServiceBindingProtocol = ServiceBindingProtocol { create_child, destroy_child };
+
+    enum Pending {
+        Connect(*mut tcp4::ConnectionToken),
+        Transmit(*mut tcp4::IoToken),
+        Receive(*mut tcp4::IoToken),
+        Close(*mut tcp4::CloseToken),
+    }
+
+    /// Connection that refuses port 1, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestTcp {
+        protocol: tcp4::Protocol,
+        remote: Cell<Option<([u8; 4], u16)>>,
+        pending: RefCell<Vec<Pending>>,
+        sent: RefCell<Vec<u8>>,
+        incoming: RefCell<Vec<u8>>,
+    }
+
+    impl TestTcp {
+        fn from_ptr<'a>(this: *mut tcp4::Protocol) -> &'a TestTcp {
+            unsafe { &*(this as *const TestTcp) }
+        }
+
+        fn push(this: *mut tcp4::Protocol, pending: Pending) -> efi::Status {
+            Self::from_ptr(this).pending.borrow_mut().push(pending);
+            efi::Status::SUCCESS
+        }
+    }
+
+    fn complete(token: &mut tcp4::CompletionToken, status: efi::Status) {
+        token.status = status;
+        SIGNALED.lock().unwrap().push(token.event as usize);
+    }
+
+    extern "efiapi" fn get_mode_data(
+        _this: *mut tcp4::Protocol,
+        _state: *mut tcp4::ConnectionState,
+        _config: *mut tcp4::ConfigData,
+        _ip4: *mut efi::protocols::ip4::ModeData,
+        _mnp: *mut efi::protocols::managed_network::ConfigData,
+        _snp: *mut efi::protocols::simple_network::Mode,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn configure(this: *mut tcp4::Protocol, config: *mut tcp4::ConfigData) -> efi::Status {
+        let remote = unsafe { config.as_ref() }.map(|config| {
+            assert_eq!(efi::Boolean::TRUE, config.access_point.use_default_address);
+            assert_eq!(efi::Boolean::TRUE, config.access_point.active_flag);
+            (config.access_point.remote_address.addr, config.access_point.remote_port)
+        });
+        TestTcp::from_ptr(this).remote.set(remote);
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn routes(
+        _this: *mut tcp4::Protocol,
+        _delete: efi::Boolean,
+        _subnet: *mut efi::Ipv4Address,
+        _mask: *mut efi::Ipv4Address,
+        _gateway: *mut efi::Ipv4Address,
+    ) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn connect(this: *mut tcp4::Protocol, token: *mut tcp4::ConnectionToken) -> efi::Status {
+        TestTcp::push(this, Pending::Connect(token))
+    }
+
+    extern "efiapi" fn accept(_this: *mut tcp4::Protocol, _token: *mut tcp4::ListenToken) -> efi::Status {
+        unimplemented!()
+    }
+
+    extern "efiapi" fn transmit(this: *mut tcp4::Protocol, token: *mut tcp4::IoToken) -> efi::Status {
+        TestTcp::push(this, Pending::Transmit(token))
+    }
+
+    extern "efiapi" fn receive(this: *mut tcp4::Protocol, token: *mut tcp4::IoToken) -> efi::Status {
+        TestTcp::push(this, Pending::Receive(token))
+    }
+
+    extern "efiapi" fn close(this: *mut tcp4::Protocol, token: *mut tcp4::CloseToken) -> efi::Status {
+        TestTcp::push(this, Pending::Close(token))
+    }
+
+    extern "efiapi" fn cancel(this: *mut tcp4::Protocol, token: *mut tcp4::CompletionToken) -> efi::Status {
+        let mut pending = TestTcp::from_ptr(this).pending.borrow_mut();
+        let before = pending.len();
+        pending.retain(|pending| {
+            let completion_token = match *pending {
+                Pending::Connect(token) => token as *mut tcp4::CompletionToken,
+                Pending::Transmit(token) | Pending::Receive(token) => token as *mut tcp4::CompletionToken,
+                Pending::Close(token) => token as *mut tcp4::CompletionToken,
+            };
+            completion_token != token
+        });
+        match pending.len() == before {
+            true => efi::Status::NOT_FOUND,
+            false => {
+                complete(unsafe { &mut *token }, efi::Status::ABORTED);
+                efi::Status::SUCCESS
+            }
+        }
+    }
+
+    extern "efiapi" fn poll(this: *mut tcp4::Protocol) -> efi::Status {
+        let tcp = TestTcp::from_ptr(this);
+        let pending = tcp.pending.take();
+        for pending in pending {
+            match pending {
+                Pending::Connect(token) => {
+                    let status = match tcp.remote.get() {
+                        Some((_, 1)) => efi::Status::CONNECTION_REFUSED,
+                        Some(_) => efi::Status::SUCCESS,
+                        None => efi::Status::NOT_STARTED,
+                    };
+                    complete(unsafe { &mut (*token).completion_token }, status);
+                }
+                Pending::Transmit(token) => {
+                    let data = unsafe { &*((*token).packet.tx_data as *const tcp4::TransmitData<1>) };
+                    let fragment = &data.fragment_table[0];
+                    tcp.sent.borrow_mut().extend_from_slice(unsafe {
+                        slice::from_raw_parts(fragment.fragment_buffer as *const u8, fragment.fragment_length as usize)
+                    });
+                    complete(unsafe { &mut (*token).completion_token }, efi::Status::SUCCESS);
+                }
+                Pending::Receive(token) if tcp.incoming.borrow().is_empty() => {
+                    tcp.pending.borrow_mut().push(Pending::Receive(token))
+                }
+                Pending::Receive(token) => {
+                    let data = unsafe { &mut *((*token).packet.rx_data as *mut tcp4::ReceiveData<1>) };
+                    let mut incoming = tcp.incoming.borrow_mut();
+                    let size = incoming.len().min(data.data_length as usize);
+                    let fragment = &mut data.fragment_table[0];
+                    unsafe { slice::from_raw_parts_mut(fragment.fragment_buffer as *mut u8, size) }
+                        .copy_from_slice(&incoming[..size]);
+                    incoming.drain(..size);
+                    data.data_length = size as u32;
+                    fragment.fragment_length = size as u32;
+                    complete(unsafe { &mut (*token).completion_token }, efi::Status::SUCCESS);
+                }
+                Pending::Close(token) => {
+                    tcp.remote.set(None);
+                    complete(unsafe { &mut (*token).completion_token }, efi::Status::SUCCESS);
+                }
+            }
+        }
+        efi::Status::SUCCESS
+    }
+
+    fn test_tcp() -> (&'static TestTcp, MockBootServices) {
+        let tcp = Box::leak(Box::new(TestTcp {
+            protocol: tcp4::Protocol {
+                get_mode_data,
+                configure,
+                routes,
+                connect,
+                accept,
+                transmit,
+                receive,
+                close,
+                cancel,
+                poll,
+            },
+            remote: Cell::new(None),
+            pending: RefCell::new(Vec::new()),
+            sent: RefCell::new(Vec::new()),
+            incoming: RefCell::new(Vec::new()),
+        }));
+        let tcp_ptr = tcp as *mut TestTcp as usize;
+
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_handle_protocol_unchecked()
+            .withf(|handle, protocol| {
+                *handle == 1_usize as efi::Handle && *protocol == efi::protocols::tcp4::SERVICE_BINDING_PROTOCOL_GUID
+            })
+            .returning(|_, _| Ok(unsafe { ptr::addr_of_mut!(SERVICE_BINDING) } as *mut c_void));
+        boot_services
+            .expect_open_protocol_unchecked()
+            .withf(|handle, protocol, _, _, _| {
+                *handle == 2_usize as efi::Handle && *protocol == efi::protocols::tcp4::PROTOCOL_GUID
+            })
+            .returning(move |_, _, _, _, _| Ok(tcp_ptr as *mut c_void));
+        boot_services.expect_close_protocol().returning(|_, _, _, _| Ok(()));
+        boot_services
+            .expect_create_event::<Option<&'static ()>>()
+            .returning(|_, _, _, _| Ok(NEXT_EVENT.fetch_add(1, Ordering::SeqCst) as efi::Event));
+        boot_services.expect_check_event().returning(|event| {
+            match SIGNALED.lock().unwrap().contains(&(event as usize)) {
+                true => Ok(()),
+                false => Err(efi::Status::NOT_READY.into()),
+            }
+        });
+        (tcp, boot_services)
+    }
+
+    #[test]
+    fn test_blocking_connection() {
+        let (test_tcp, mut boot_services) = test_tcp();
+        boot_services.expect_close_event().times(4).returning(|_| Ok(()));
+
+        let mut tcp = Tcp4::create(&boot_services, 1_usize as efi::Handle, 3_usize as efi::Handle).unwrap();
+        tcp.connect(Ipv4Addr::new(192, 168, 0, 1), 8080).unwrap().wait().unwrap();
+        assert_eq!(Some(([192, 168, 0, 1], 8080)), test_tcp.remote.get());
+
+        assert_eq!(5, tcp.send(b"hello").unwrap().wait().unwrap());
+        assert_eq!(b"hello", test_tcp.sent.borrow().as_slice());
+
+        test_tcp.incoming.borrow_mut().extend_from_slice(b"world");
+        let mut buffer = [0; 8];
+        assert_eq!(5, tcp.receive(&mut buffer).unwrap().wait().unwrap());
+        assert_eq!(b"world", &buffer[..5]);
+
+        tcp.close(false).unwrap().wait().unwrap();
+        assert_eq!(None, test_tcp.remote.get());
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        let (_, mut boot_services) = test_tcp();
+        boot_services.expect_close_event().once().returning(|_| Ok(()));
+
+        let mut tcp = Tcp4::create(&boot_services, 1_usize as efi::Handle, 3_usize as efi::Handle).unwrap();
+        assert_eq!(
+            efi::Status::CONNECTION_REFUSED,
+            tcp.connect(Ipv4Addr::new(192, 168, 0, 1), 1).unwrap().wait().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_drop_cancels() {
+        let (test_tcp, mut boot_services) = test_tcp();
+        boot_services.expect_close_event().once().returning(|_| Ok(()));
+
+        let tcp = Tcp4::create(&boot_services, 1_usize as efi::Handle, 3_usize as efi::Handle).unwrap();
+        let mut buffer = [0; 8];
+        let receive = tcp.receive(&mut buffer).unwrap();
+        tcp.poll();
+        assert_eq!(1, test_tcp.pending.borrow().len());
+        drop(receive);
+        assert!(test_tcp.pending.borrow().is_empty());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_receive_future() {
+        use core::{
+            future::Future,
+            pin::pin,
+            task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+        };
+
+        const VTABLE: RawWakerVTable =
+            RawWakerVTable::new(|_| RawWaker::new(ptr::null(), &VTABLE), |_| {}, |_| {}, |_| {});
+        let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+        let mut cx = Context::from_waker(&waker);
+
+        let (test_tcp, mut boot_services) = test_tcp();
+        boot_services.expect_close_event().once().returning(|_| Ok(()));
+
+        let tcp = Tcp4::create(&boot_services, 1_usize as efi::Handle, 3_usize as efi::Handle).unwrap();
+        let mut buffer = [0; 4];
+        {
+            let mut receive = pin!(tcp.receive(&mut buffer).unwrap());
+            assert_eq!(Poll::Pending, receive.as_mut().poll(&mut cx));
+            test_tcp.incoming.borrow_mut().extend_from_slice(b"abcdef");
+            assert_eq!(Poll::Ready(Ok(4)), receive.as_mut().poll(&mut cx));
+        }
+        assert_eq!(*b"abcd", buffer);
+        assert_eq!(b"ef", test_tcp.incoming.borrow().as_slice());
+    }
+}
@@ -0,0 +1,242 @@
+//! Timestamp protocol, and a monotonic clock to measure durations without an OS.
+//!
+//! A [`Clock`] reads the counter of the Timestamp protocol, or when it is not installed the counter of the processor
+//! from [`arch`](crate::arch), or any other [`CycleCounter`]. Its [`Instant`]s give the time elapsed since they were
+//! taken:
+//!
+//! ```ignore
+//! let clock = Clock::locate(&boot_services)?;
+//! let start = clock.now();
+//! init_devices()?;
+//! log::info!("Devices initialized in {:?}", start.elapsed());
+//! ```
+//!
+//! [UEFI Spec Documentation: 37.3. Timestamp Protocol](https://uefi.org/specs/UEFI/2.10/37_Secure_Technologies.html#timestamp-protocol)
+
+use alloc::boxed::Box;
+use core::{fmt, time::Duration};
+
+use boot_services::{protocol_handler, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::arch::{self, CycleCounter, ProcessorCounter};
+use efi::protocols::timestamp;
+
+pub use crate::arch::CALIBRATION_STALL;
+pub use timestamp::Properties;
+
+/// Typed access to the Timestamp protocol.
+pub struct Timestamp(&'static mut timestamp::Protocol);
+
+impl Timestamp {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&protocol_handler::Timerstamp, None).map(Self)
+    }
+
+    /// The current value of the counter.
+    pub fn get_timestamp(&self) -> u64 {
+        unsafe { r_efi::efi_call(self.0.get_timestamp, ()) }
+    }
+
+    /// The frequency of the counter, in hertz, and the value after which it rolls over to 0.
+    pub fn properties(&self) -> Result<Properties, EfiError> {
+        let mut properties = Properties { frequency: 0, end_value: 0 };
+        match unsafe { r_efi::efi_call(self.0.get_properties, (&mut properties,)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(properties),
+        }
+    }
+}
+
+impl From<&'static mut timestamp::Protocol> for Timestamp {
+    fn from(protocol: &'static mut timestamp::Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl CycleCounter for Timestamp {
+    fn ticks(&self) -> u64 {
+        self.get_timestamp()
+    }
+
+    fn frequency(&self) -> Option<u64> {
+        self.properties().ok().map(|properties| properties.frequency)
+    }
+
+    fn end_value(&self) -> u64 {
+        self.properties().map_or(u64::MAX, |properties| properties.end_value)
+    }
+}
+
+impl fmt::Debug for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timestamp").field("properties", &self.properties()).finish()
+    }
+}
+
+/// A monotonic clock, over the counter of the Timestamp protocol, of the processor, or any other [`CycleCounter`].
+pub struct Clock {
+    counter: Box<dyn CycleCounter>,
+    properties: Properties,
+}
+
+impl Clock {
+    /// A clock over the Timestamp protocol, or else over the counter of the processor.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        match Timestamp::locate(boot_services) {
+            Ok(timestamp) => Self::timestamp(timestamp),
+            Err(_) => Self::processor(boot_services),
+        }
+    }
+
+    /// A clock over the Timestamp protocol.
+    pub fn timestamp(timestamp: Timestamp) -> Result<Self, EfiError> {
+        let properties = timestamp.properties()?;
+        if properties.frequency == 0 || properties.end_value == 0 {
+            return Err(efi::Status::DEVICE_ERROR.into());
+        }
+        Ok(Self { counter: Box::new(timestamp), properties })
+    }
+
+    /// A clock over the counter of the processor, its frequency is measured over a [`CALIBRATION_STALL`] when the
+    /// processor does not report it.
+    ///
+    /// Returns `UNSUPPORTED` on the architectures without a counter.
+    pub fn processor<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        Self::new(ProcessorCounter::new().ok_or(efi::Status::UNSUPPORTED)?, boot_services)
+    }
+
+    /// A clock over `counter`, its frequency is measured with [`arch::calibrate`] when the counter does not report it.
+    pub fn new<C: CycleCounter + 'static, B: BootServices>(counter: C, boot_services: &B) -> Result<Self, EfiError> {
+        let frequency = arch::calibrate(&counter, boot_services)?;
+        let properties = Properties { frequency, end_value: counter.end_value() };
+        Ok(Self { counter: Box::new(counter), properties })
+    }
+
+    /// The frequency of the counter, in hertz.
+    pub fn frequency(&self) -> u64 {
+        self.properties.frequency
+    }
+
+    /// The current value of the counter.
+    pub fn ticks(&self) -> u64 {
+        self.counter.ticks()
+    }
+
+    /// The current time.
+    pub fn now(&self) -> Instant<'_> {
+        Instant { clock: self, ticks: self.ticks() }
+    }
+
+    /// The ticks from `earlier` to `later`, the counter having rolled over at most once.
+    fn ticks_between(&self, earlier: u64, later: u64) -> u64 {
+        match later.checked_sub(earlier) {
+            Some(ticks) => ticks,
+            None => (self.properties.end_value - earlier).saturating_add(later).saturating_add(1),
+        }
+    }
+
+    /// The duration of a number of ticks.
+    fn duration(&self, ticks: u64) -> Duration {
+        let nanos = ticks as u128 * 1_000_000_000 / self.properties.frequency as u128;
+        Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock").field("properties", &self.properties).finish_non_exhaustive()
+    }
+}
+
+/// A time of a [`Clock`], like `std::time::Instant`.
+#[derive(Clone, Copy)]
+pub struct Instant<'a> {
+    clock: &'a Clock,
+    ticks: u64,
+}
+
+impl Instant<'_> {
+    /// The value of the counter at this time.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// The time elapsed since this time.
+    pub fn elapsed(&self) -> Duration {
+        self.clock.duration(self.clock.ticks_between(self.ticks, self.clock.ticks()))
+    }
+
+    /// The time elapsed from `earlier` to this time, assuming the counter rolled over at most once.
+    pub fn duration_since(&self, earlier: Instant<'_>) -> Duration {
+        self.clock.duration(self.clock.ticks_between(earlier.ticks, self.ticks))
+    }
+}
+
+impl fmt::Debug for Instant<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instant").field("ticks", &self.ticks).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::MockBootServices;
+    use std::{
+        boxed::Box,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    static TICKS: AtomicU64 = AtomicU64::new(0);
+
+    extern "efiapi" fn get_timestamp() -> u64 {
+        TICKS.load(Ordering::Relaxed)
+    }
+
+    extern "efiapi" fn get_properties(properties: *mut Properties) -> efi::Status {
+        unsafe { *properties = Properties { frequency: 1_000_000, end_value: 0xFFFF_FFFF } };
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_timestamp_clock() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::Timerstamp, timestamp::Protocol>()
+            .returning(|_, _| Ok(Box::leak(Box::new(timestamp::Protocol { get_timestamp, get_properties }))));
+        let clock = Clock::locate(&boot_services).unwrap();
+        assert_eq!(1_000_000, clock.frequency());
+
+        TICKS.store(0xFFFF_FF00, Ordering::Relaxed);
+        let start = clock.now();
+        TICKS.store(1_500_000, Ordering::Relaxed);
+        // The counter rolled over.
+        assert_eq!(Duration::from_micros(0x100 + 1_500_000), start.elapsed());
+        let end = clock.now();
+        TICKS.store(2_000_000, Ordering::Relaxed);
+        assert_eq!(Duration::from_micros(500_000), clock.now().duration_since(end));
+        assert_eq!(1_500_000, end.ticks());
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn test_processor_clock() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<protocol_handler::Timerstamp, timestamp::Protocol>()
+            .returning(|_, _| Err(efi::Status::NOT_FOUND.into()));
+        boot_services.expect_stall().returning(|microseconds| {
+            std::thread::sleep(Duration::from_micros(microseconds as u64));
+            Ok(())
+        });
+        let clock = Clock::locate(&boot_services).unwrap();
+        assert!(clock.frequency() > 0);
+
+        let start = clock.now();
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(start.elapsed() > Duration::ZERO);
+    }
+}
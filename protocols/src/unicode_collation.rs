@@ -0,0 +1,275 @@
+//! Unicode Collation 2 protocol.
+//!
+//! [`UnicodeCollation`] gives the language-aware string comparisons used by file systems and shells.
+//!
+//! [UEFI Spec Documentation: 19.1. Unicode Collation Protocol](https://uefi.org/specs/UEFI/2.10/19_Protocol_Protocols_Unicode_Collation_Protocol.html)
+
+use alloc::{vec, vec::Vec};
+use core::{cmp::Ordering, ffi::CStr, fmt, ops::Deref};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xa4c751fc, 0x23ae, 0x4c3e, 0x92, 0xe9, &[0x49, 0x64, 0xcf, 0x63, 0xf3, 0x49]);
+
+pub type ProtocolStriColl = extern "efiapi" fn(*mut Protocol, *mut u16, *mut u16) -> isize;
+
+pub type ProtocolMetaiMatch = extern "efiapi" fn(*mut Protocol, *mut u16, *mut u16) -> efi::Boolean;
+
+pub type ProtocolStrLwr = extern "efiapi" fn(*mut Protocol, *mut u16);
+
+pub type ProtocolStrUpr = extern "efiapi" fn(*mut Protocol, *mut u16);
+
+pub type ProtocolFatToStr = extern "efiapi" fn(*mut Protocol, usize, *mut u8, *mut u16);
+
+pub type ProtocolStrToFat = extern "efiapi" fn(*mut Protocol, *mut u16, usize, *mut u8) -> efi::Boolean;
+
+/// FFI definition of `EFI_UNICODE_COLLATION_PROTOCOL2`.
+#[repr(C)]
+pub struct Protocol {
+    pub stri_coll: ProtocolStriColl,
+    pub metai_match: ProtocolMetaiMatch,
+    pub str_lwr: ProtocolStrLwr,
+    pub str_upr: ProtocolStrUpr,
+    pub fat_to_str: ProtocolFatToStr,
+    pub str_to_fat: ProtocolStrToFat,
+    pub supported_languages: *mut u8,
+}
+
+/// Unicode Collation 2 protocol.
+pub struct UnicodeCollation2;
+
+unsafe impl ProtocolTrait for UnicodeCollation2 {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for UnicodeCollation2 {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Typed access to an instance of the Unicode Collation 2 protocol.
+///
+/// ```ignore
+/// let collation = UnicodeCollation::locate(&boot_services)?;
+/// let pattern = Str16::from_slice_with_nul(u16str!("*.EFI")).unwrap();
+/// if collation.metai_match(&file_name, pattern) { ... }
+/// ```
+pub struct UnicodeCollation(&'static mut Protocol);
+
+impl UnicodeCollation {
+    /// Locates the first instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&UnicodeCollation2, None).map(Self)
+    }
+
+    fn this(&self) -> *mut Protocol {
+        self.0 as *const Protocol as *mut Protocol
+    }
+
+    /// Compares two strings without case sensitivity.
+    pub fn stri_coll(&self, s1: &Str16, s2: &Str16) -> Ordering {
+        // The strings are only read by the protocol.
+        (self.0.stri_coll)(self.this(), s1.as_ptr() as *mut u16, s2.as_ptr() as *mut u16).cmp(&0)
+    }
+
+    /// Returns true if the string matches the pattern without case sensitivity.
+    ///
+    /// The pattern can contain `*` for any sequence of characters, `?` for any single character and `[...]` for a set
+    /// or range (`[a-z]`) of characters.
+    pub fn metai_match(&self, string: &Str16, pattern: &Str16) -> bool {
+        // The strings are only read by the protocol.
+        (self.0.metai_match)(self.this(), string.as_ptr() as *mut u16, pattern.as_ptr() as *mut u16).into()
+    }
+
+    /// Returns the string converted to lower case.
+    pub fn str_lwr(&self, s: &Str16) -> String16 {
+        let mut string = s.as_slice_with_nul().to_vec();
+        (self.0.str_lwr)(self.this(), string.as_mut_ptr());
+        Self::to_string16(string)
+    }
+
+    /// Returns the string converted to upper case.
+    pub fn str_upr(&self, s: &Str16) -> String16 {
+        let mut string = s.as_slice_with_nul().to_vec();
+        (self.0.str_upr)(self.this(), string.as_mut_ptr());
+        Self::to_string16(string)
+    }
+
+    /// Converts a FAT file name in the OEM character set to a string.
+    pub fn fat_to_str(&self, fat: &[u8]) -> String16 {
+        let mut fat = fat.to_vec();
+        let mut string = vec![0; fat.len() + 1];
+        (self.0.fat_to_str)(self.this(), fat.len(), fat.as_mut_ptr(), string.as_mut_ptr());
+        Self::to_string16(string)
+    }
+
+    /// Converts a string to a FAT file name in the OEM character set, `fat` is filled with spaces first.
+    ///
+    /// Returns true if characters had to be substituted because they are not valid in FAT file names.
+    pub fn str_to_fat(&self, s: &Str16, fat: &mut [u8]) -> bool {
+        fat.fill(b' ');
+        // The string is only read by the protocol.
+        (self.0.str_to_fat)(self.this(), s.as_ptr() as *mut u16, fat.len(), fat.as_mut_ptr()).into()
+    }
+
+    /// The languages supported by the protocol, as RFC 4646 language codes separated by `;`.
+    pub fn supported_languages(&self) -> &str {
+        if self.0.supported_languages.is_null() {
+            return "";
+        }
+        //SAFETY: The supported languages are a null-terminated ASCII string owned by the protocol.
+        unsafe { CStr::from_ptr(self.0.supported_languages as *const _) }.to_str().unwrap_or_default()
+    }
+
+    /// The string written by the protocol, up to its first null character.
+    fn to_string16(string: Vec<u16>) -> String16 {
+        Str16::from_slice_until_nul(&string).map(String16::from).unwrap_or_default()
+    }
+}
+
+impl From<&'static mut Protocol> for UnicodeCollation {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for UnicodeCollation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnicodeCollation").field("supported_languages", &self.supported_languages()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+    use ucs2::u16str;
+
+    /// Reads a null-terminated string given to the protocol.
+    fn read(s: *mut u16) -> Vec<u16> {
+        unsafe { Str16::from_ptr(s) }.as_slice().to_vec()
+    }
+
+    fn lower(s: &[u16]) -> Vec<u16> {
+        s.iter().map(|&c| if (b'A' as u16..=b'Z' as u16).contains(&c) { c + 32 } else { c }).collect()
+    }
+
+    extern "efiapi" fn stri_coll(_: *mut Protocol, s1: *mut u16, s2: *mut u16) -> isize {
+        match lower(&read(s1)).cmp(&lower(&read(s2))) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        }
+    }
+
+    // Only supports a trailing `*`.
+    extern "efiapi" fn metai_match(_: *mut Protocol, string: *mut u16, pattern: *mut u16) -> efi::Boolean {
+        let (string, pattern) = (lower(&read(string)), lower(&read(pattern)));
+        match pattern.split_last() {
+            Some((&c, prefix)) if c == b'*' as u16 => string.starts_with(prefix),
+            _ => string == pattern,
+        }
+        .into()
+    }
+
+    extern "efiapi" fn str_lwr(_: *mut Protocol, s: *mut u16) {
+        for (i, c) in lower(&read(s)).into_iter().enumerate() {
+            unsafe { s.add(i).write(c) };
+        }
+    }
+
+    extern "efiapi" fn str_upr(_: *mut Protocol, s: *mut u16) {
+        for (i, c) in read(s).into_iter().enumerate() {
+            unsafe { s.add(i).write(if (b'a' as u16..=b'z' as u16).contains(&c) { c - 32 } else { c }) };
+        }
+    }
+
+    extern "efiapi" fn fat_to_str(_: *mut Protocol, fat_size: usize, fat: *mut u8, string: *mut u16) {
+        let fat = unsafe { core::slice::from_raw_parts(fat, fat_size) };
+        for (i, &c) in fat.iter().take_while(|&&c| c != 0).enumerate() {
+            unsafe { string.add(i).write(c as u16) };
+        }
+    }
+
+    extern "efiapi" fn str_to_fat(_: *mut Protocol, string: *mut u16, fat_size: usize, fat: *mut u8) -> efi::Boolean {
+        let mut substituted = false;
+        for (i, c) in read(string).into_iter().take(fat_size).enumerate() {
+            let c = match u8::try_from(c) {
+                Ok(c) if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+                _ => {
+                    substituted = true;
+                    b'_'
+                }
+            };
+            unsafe { fat.add(i).write(c) };
+        }
+        substituted.into()
+    }
+
+    fn unicode_collation() -> UnicodeCollation {
+        static LANGUAGES: &[u8] = b"en;fr\0";
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_locate_protocol::<UnicodeCollation2, Protocol>().once().returning(|_, _| {
+            Ok(Box::leak(Box::new(Protocol {
+                stri_coll,
+                metai_match,
+                str_lwr,
+                str_upr,
+                fat_to_str,
+                str_to_fat,
+                supported_languages: LANGUAGES.as_ptr() as *mut u8,
+            })))
+        });
+        UnicodeCollation::locate(&boot_services).unwrap()
+    }
+
+    fn str16(s: &'static [u16]) -> &'static Str16 {
+        Str16::from_slice_with_nul(s).unwrap()
+    }
+
+    #[test]
+    fn test_stri_coll() {
+        let collation = unicode_collation();
+        assert_eq!("en;fr", collation.supported_languages());
+        assert_eq!(Ordering::Equal, collation.stri_coll(str16(u16str!("Boot.EFI")), str16(u16str!("boot.efi"))));
+        assert_eq!(Ordering::Less, collation.stri_coll(str16(u16str!("a")), str16(u16str!("B"))));
+        assert_eq!(Ordering::Greater, collation.stri_coll(str16(u16str!("c")), str16(u16str!("B"))));
+    }
+
+    #[test]
+    fn test_metai_match() {
+        let collation = unicode_collation();
+        assert!(collation.metai_match(str16(u16str!("BOOTX64.EFI")), str16(u16str!("boot*"))));
+        assert!(!collation.metai_match(str16(u16str!("Shell.efi")), str16(u16str!("boot*"))));
+    }
+
+    #[test]
+    fn test_case_conversions() {
+        let collation = unicode_collation();
+        assert_eq!(collation.str_lwr(str16(u16str!("Boot.EFI"))), "boot.efi");
+        assert_eq!(collation.str_upr(str16(u16str!("Boot.EFI"))), "BOOT.EFI");
+    }
+
+    #[test]
+    fn test_fat_conversions() {
+        let collation = unicode_collation();
+        assert_eq!(collation.fat_to_str(b"BOOTX64 EFI"), "BOOTX64 EFI");
+        assert_eq!(collation.fat_to_str(b"A\0\0"), "A");
+
+        let mut fat = [0; 11];
+        assert!(!collation.str_to_fat(str16(u16str!("Boot")), &mut fat));
+        assert_eq!(b"BOOT       ", &fat);
+        assert!(collation.str_to_fat(str16(u16str!("a+b")), &mut fat));
+        assert_eq!(b"A_B        ", &fat);
+    }
+}
@@ -0,0 +1,867 @@
+//! USB I/O protocol.
+//!
+//! [`UsbIo`] gives a driver access to the USB interface it manages: control transfers built from a
+//! [`DeviceRequest`], bulk and interrupt transfers on its endpoints, and the descriptors of the device:
+//!
+//! ```ignore
+//! let mut usb_io = UsbIo::get(boot_services, controller)?.with_timeout(1000);
+//! let interface = usb_io.interface_descriptor()?;
+//! let endpoints = usb_io.endpoint_descriptors()?;
+//! let request = DeviceRequest::new(RequestDirection::Out, RequestKind::Class, Recipient::Interface, SET_IDLE)
+//!     .with_index(interface.interface_number as u16);
+//! usb_io.control_transfer(request, &mut [])?;
+//! let read = usb_io.interrupt_in(endpoints[0].endpoint_address, &mut report)?;
+//! ```
+//!
+//! [UEFI Spec Documentation: 17.2. USB I/O Protocol](https://uefi.org/specs/UEFI/2.10/17_Protocols_USB_Support.html#efi-usb-i-o-protocol)
+
+use alloc::{vec, vec::Vec};
+use core::{ffi::c_void, fmt, mem, ops::Deref, ptr, slice};
+
+use boot_services::{protocol_handler::Protocol as ProtocolTrait, BootServices};
+use r_efi::efi;
+use status::EfiError;
+use ucs2::{Str16, String16};
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x2b2f68d6, 0x0cd2, 0x44cf, 0x8e, 0x8b, &[0xbb, 0xa2, 0x0b, 0x1b, 0x5b, 0x75]);
+
+/// `EFI_USB_DATA_DIRECTION` of a control transfer.
+pub type DataDirection = u32;
+
+pub const DATA_IN: DataDirection = 0;
+pub const DATA_OUT: DataDirection = 1;
+pub const NO_DATA: DataDirection = 2;
+
+pub type AsyncTransferCallback = extern "efiapi" fn(*mut c_void, usize, *mut c_void, u32) -> efi::Status;
+
+pub type ProtocolControlTransfer = extern "efiapi" fn(
+    *mut Protocol,
+    *mut DeviceRequest,
+    DataDirection,
+    u32,
+    *mut c_void,
+    usize,
+    *mut u32,
+) -> efi::Status;
+
+pub type ProtocolBulkTransfer =
+    extern "efiapi" fn(*mut Protocol, u8, *mut c_void, *mut usize, usize, *mut u32) -> efi::Status;
+
+pub type ProtocolAsyncInterruptTransfer = extern "efiapi" fn(
+    *mut Protocol,
+    u8,
+    efi::Boolean,
+    usize,
+    usize,
+    Option<AsyncTransferCallback>,
+    *mut c_void,
+) -> efi::Status;
+
+pub type ProtocolSyncInterruptTransfer =
+    extern "efiapi" fn(*mut Protocol, u8, *mut c_void, *mut usize, usize, *mut u32) -> efi::Status;
+
+pub type ProtocolIsochronousTransfer =
+    extern "efiapi" fn(*mut Protocol, u8, *mut c_void, usize, *mut u32) -> efi::Status;
+
+pub type ProtocolAsyncIsochronousTransfer =
+    extern "efiapi" fn(*mut Protocol, u8, *mut c_void, usize, AsyncTransferCallback, *mut c_void) -> efi::Status;
+
+pub type ProtocolGetDeviceDescriptor = extern "efiapi" fn(*mut Protocol, *mut DeviceDescriptor) -> efi::Status;
+
+pub type ProtocolGetConfigDescriptor = extern "efiapi" fn(*mut Protocol, *mut ConfigDescriptor) -> efi::Status;
+
+pub type ProtocolGetInterfaceDescriptor = extern "efiapi" fn(*mut Protocol, *mut InterfaceDescriptor) -> efi::Status;
+
+pub type ProtocolGetEndpointDescriptor = extern "efiapi" fn(*mut Protocol, u8, *mut EndpointDescriptor) -> efi::Status;
+
+pub type ProtocolGetStringDescriptor = extern "efiapi" fn(*mut Protocol, u16, u8, *mut *mut u16) -> efi::Status;
+
+pub type ProtocolGetSupportedLanguages = extern "efiapi" fn(*mut Protocol, *mut *mut u16, *mut u16) -> efi::Status;
+
+pub type ProtocolPortReset = extern "efiapi" fn(*mut Protocol) -> efi::Status;
+
+/// FFI definition of `EFI_USB_IO_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub control_transfer: ProtocolControlTransfer,
+    pub bulk_transfer: ProtocolBulkTransfer,
+    pub async_interrupt_transfer: ProtocolAsyncInterruptTransfer,
+    pub sync_interrupt_transfer: ProtocolSyncInterruptTransfer,
+    pub isochronous_transfer: ProtocolIsochronousTransfer,
+    pub async_isochronous_transfer: ProtocolAsyncIsochronousTransfer,
+    pub get_device_descriptor: ProtocolGetDeviceDescriptor,
+    pub get_config_descriptor: ProtocolGetConfigDescriptor,
+    pub get_interface_descriptor: ProtocolGetInterfaceDescriptor,
+    pub get_endpoint_descriptor: ProtocolGetEndpointDescriptor,
+    pub get_string_descriptor: ProtocolGetStringDescriptor,
+    pub get_supported_languages: ProtocolGetSupportedLanguages,
+    pub port_reset: ProtocolPortReset,
+}
+
+/// USB I/O protocol.
+pub struct UsbIoProtocol;
+
+unsafe impl ProtocolTrait for UsbIoProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for UsbIoProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+pub const DESCRIPTOR_TYPE_DEVICE: u8 = 0x01;
+pub const DESCRIPTOR_TYPE_CONFIG: u8 = 0x02;
+pub const DESCRIPTOR_TYPE_STRING: u8 = 0x03;
+pub const DESCRIPTOR_TYPE_INTERFACE: u8 = 0x04;
+pub const DESCRIPTOR_TYPE_ENDPOINT: u8 = 0x05;
+
+pub const REQUEST_CLEAR_FEATURE: u8 = 0x01;
+pub const REQUEST_GET_DESCRIPTOR: u8 = 0x06;
+pub const REQUEST_SET_CONFIGURATION: u8 = 0x09;
+
+/// The feature of an endpoint cleared to recover from a stall.
+pub const FEATURE_ENDPOINT_HALT: u16 = 0x00;
+
+/// Bit of the address of the endpoints transferring data to the host.
+pub const ENDPOINT_DIRECTION_IN: u8 = 0x80;
+
+/// Direction of the data stage of a control transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestDirection {
+    /// From the host to the device.
+    Out = 0,
+    /// From the device to the host.
+    In = 1,
+}
+
+/// Kind of a request, defined by the USB specification, a device class or the vendor of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    Standard = 0,
+    Class = 1,
+    Vendor = 2,
+}
+
+/// Recipient of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Recipient {
+    Device = 0,
+    Interface = 1,
+    Endpoint = 2,
+    Other = 3,
+}
+
+/// FFI definition of `EFI_USB_DEVICE_REQUEST`, the setup packet of a control transfer.
+///
+/// The length of the data stage is set by [`UsbIo::control_transfer`] from its buffer.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct DeviceRequest {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl DeviceRequest {
+    pub const fn new(direction: RequestDirection, kind: RequestKind, recipient: Recipient, request: u8) -> Self {
+        let request_type = (direction as u8) << 7 | (kind as u8) << 5 | recipient as u8;
+        Self { request_type, request, value: 0, index: 0, length: 0 }
+    }
+
+    pub const fn with_value(mut self, value: u16) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub const fn with_index(mut self, index: u16) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Requests the descriptor of a type and index, in a language for the string descriptors.
+    pub const fn get_descriptor(descriptor_type: u8, index: u8, language: u16) -> Self {
+        Self::new(RequestDirection::In, RequestKind::Standard, Recipient::Device, REQUEST_GET_DESCRIPTOR)
+            .with_value((descriptor_type as u16) << 8 | index as u16)
+            .with_index(language)
+    }
+
+    /// Selects the configuration of a value, 0 to unconfigure the device.
+    pub const fn set_configuration(value: u8) -> Self {
+        Self::new(RequestDirection::Out, RequestKind::Standard, Recipient::Device, REQUEST_SET_CONFIGURATION)
+            .with_value(value as u16)
+    }
+
+    /// Clears the halt of a stalled endpoint.
+    pub const fn clear_endpoint_halt(endpoint_address: u8) -> Self {
+        Self::new(RequestDirection::Out, RequestKind::Standard, Recipient::Endpoint, REQUEST_CLEAR_FEATURE)
+            .with_value(FEATURE_ENDPOINT_HALT)
+            .with_index(endpoint_address as u16)
+    }
+
+    pub const fn direction(&self) -> RequestDirection {
+        match self.request_type >> 7 {
+            0 => RequestDirection::Out,
+            _ => RequestDirection::In,
+        }
+    }
+}
+
+/// FFI definition of `EFI_USB_DEVICE_DESCRIPTOR`.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct DeviceDescriptor {
+    pub length: u8,
+    pub descriptor_type: u8,
+    pub bcd_usb: u16,
+    pub device_class: u8,
+    pub device_sub_class: u8,
+    pub device_protocol: u8,
+    pub max_packet_size0: u8,
+    pub id_vendor: u16,
+    pub id_product: u16,
+    pub bcd_device: u16,
+    /// Index of the string descriptor of the manufacturer, 0 without one.
+    pub str_manufacturer: u8,
+    pub str_product: u8,
+    pub str_serial_number: u8,
+    pub num_configurations: u8,
+}
+
+/// FFI definition of `EFI_USB_CONFIG_DESCRIPTOR`.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct ConfigDescriptor {
+    pub length: u8,
+    pub descriptor_type: u8,
+    /// Length of the configuration with the descriptors of its interfaces and endpoints.
+    pub total_length: u16,
+    pub num_interfaces: u8,
+    pub configuration_value: u8,
+    pub configuration: u8,
+    pub attributes: u8,
+    /// Maximum power drawn from the bus, in units of 2 mA.
+    pub max_power: u8,
+}
+
+/// FFI definition of `EFI_USB_INTERFACE_DESCRIPTOR`.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct InterfaceDescriptor {
+    pub length: u8,
+    pub descriptor_type: u8,
+    pub interface_number: u8,
+    pub alternate_setting: u8,
+    pub num_endpoints: u8,
+    pub interface_class: u8,
+    pub interface_sub_class: u8,
+    pub interface_protocol: u8,
+    pub interface: u8,
+}
+
+/// FFI definition of `EFI_USB_ENDPOINT_DESCRIPTOR`.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct EndpointDescriptor {
+    pub length: u8,
+    pub descriptor_type: u8,
+    /// Number of the endpoint, with [`ENDPOINT_DIRECTION_IN`] for the endpoints transferring data to the host.
+    pub endpoint_address: u8,
+    /// Type of the transfers of the endpoint in the bits 0 and 1.
+    pub attributes: u8,
+    pub max_packet_size: u16,
+    pub interval: u8,
+}
+
+/// A descriptor of the configuration read by [`UsbIo::configuration`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Descriptor {
+    Config(ConfigDescriptor),
+    Interface(InterfaceDescriptor),
+    Endpoint(EndpointDescriptor),
+    /// A descriptor of another type, such as a class descriptor, with its length and type.
+    Other(Vec<u8>),
+}
+
+impl Descriptor {
+    /// Parses the descriptors following each other in a buffer.
+    pub fn parse_all(mut bytes: &[u8]) -> Result<Vec<Descriptor>, EfiError> {
+        let mut descriptors = Vec::new();
+        while !bytes.is_empty() {
+            let length = bytes[0] as usize;
+            if length < 2 || length > bytes.len() {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            let (descriptor, rest) = bytes.split_at(length);
+            descriptors.push(match descriptor[1] {
+                DESCRIPTOR_TYPE_CONFIG if length >= mem::size_of::<ConfigDescriptor>() => {
+                    Descriptor::Config(ConfigDescriptor::read_from_prefix(descriptor).unwrap().0)
+                }
+                DESCRIPTOR_TYPE_INTERFACE if length >= mem::size_of::<InterfaceDescriptor>() => {
+                    Descriptor::Interface(InterfaceDescriptor::read_from_prefix(descriptor).unwrap().0)
+                }
+                DESCRIPTOR_TYPE_ENDPOINT if length >= mem::size_of::<EndpointDescriptor>() => {
+                    Descriptor::Endpoint(EndpointDescriptor::read_from_prefix(descriptor).unwrap().0)
+                }
+                _ => Descriptor::Other(descriptor.to_vec()),
+            });
+            bytes = rest;
+        }
+        Ok(descriptors)
+    }
+}
+
+/// The `EFI_USB_ERR_*` bits of the USB status of a failed transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct UsbStatus(pub u32);
+
+impl UsbStatus {
+    pub const NOT_EXECUTE: UsbStatus = UsbStatus(0x0001);
+    /// The endpoint stalled, it is cleared with [`DeviceRequest::clear_endpoint_halt`].
+    pub const STALL: UsbStatus = UsbStatus(0x0002);
+    pub const BUFFER: UsbStatus = UsbStatus(0x0004);
+    pub const BABBLE: UsbStatus = UsbStatus(0x0008);
+    pub const NAK: UsbStatus = UsbStatus(0x0010);
+    pub const CRC: UsbStatus = UsbStatus(0x0020);
+    pub const TIMEOUT: UsbStatus = UsbStatus(0x0040);
+    pub const BIT_STUFF: UsbStatus = UsbStatus(0x0080);
+    pub const SYSTEM: UsbStatus = UsbStatus(0x0100);
+
+    pub const fn contains(&self, status: UsbStatus) -> bool {
+        self.0 & status.0 == status.0
+    }
+}
+
+/// Error of a transfer, with the USB status reported by the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferError {
+    pub status: efi::Status,
+    pub usb_status: UsbStatus,
+}
+
+impl From<TransferError> for efi::Status {
+    fn from(error: TransferError) -> Self {
+        error.status
+    }
+}
+
+impl From<TransferError> for EfiError {
+    fn from(error: TransferError) -> Self {
+        error.status.into()
+    }
+}
+
+/// Typed access to an instance of the USB I/O protocol, the protocol of a USB interface.
+pub struct UsbIo {
+    protocol: &'static mut Protocol,
+    timeout: u32,
+}
+
+impl UsbIo {
+    /// Default timeout of a transfer, in milliseconds.
+    pub const DEFAULT_TIMEOUT: u32 = 3000;
+
+    /// Gets the instance of the protocol installed on the handle of an interface.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // Handles are opaque and never dereferenced.
+    pub fn get<B: BootServices>(boot_services: &B, handle: efi::Handle) -> Result<Self, EfiError> {
+        boot_services.handle_protocol(handle, &UsbIoProtocol).map(Self::from)
+    }
+
+    /// Sets the timeout of a transfer, in milliseconds, 0 to wait as long as needed.
+    pub fn with_timeout(mut self, timeout: u32) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The timeout of a transfer, in milliseconds.
+    pub fn timeout(&self) -> u32 {
+        self.timeout
+    }
+
+    fn this(&mut self) -> *mut Protocol {
+        self.protocol as *mut Protocol
+    }
+
+    /// Sends a request to the device, with a data stage in the direction of the request unless `data` is empty.
+    pub fn control_transfer(&mut self, request: DeviceRequest, data: &mut [u8]) -> Result<(), TransferError> {
+        let mut request = request;
+        request.length = u16::try_from(data.len())
+            .map_err(|_| TransferError { status: efi::Status::INVALID_PARAMETER, usb_status: UsbStatus(0) })?;
+        let direction = match request.direction() {
+            _ if data.is_empty() => NO_DATA,
+            RequestDirection::In => DATA_IN,
+            RequestDirection::Out => DATA_OUT,
+        };
+        let data_ptr = match data.is_empty() {
+            true => ptr::null_mut(),
+            false => data.as_mut_ptr() as *mut c_void,
+        };
+        let mut usb_status = 0;
+        let (this, timeout) = (self.this(), self.timeout);
+        match (self.protocol.control_transfer)(
+            this,
+            &mut request,
+            direction,
+            timeout,
+            data_ptr,
+            data.len(),
+            &mut usb_status,
+        ) {
+            s if s.is_error() => Err(TransferError { status: s, usb_status: UsbStatus(usb_status) }),
+            _ => Ok(()),
+        }
+    }
+
+    fn transfer(
+        &mut self,
+        transfer: ProtocolBulkTransfer,
+        endpoint_address: u8,
+        data: *mut u8,
+        len: usize,
+    ) -> Result<usize, TransferError> {
+        let mut len = len;
+        let mut usb_status = 0;
+        let (this, timeout) = (self.this(), self.timeout as usize);
+        match transfer(this, endpoint_address, data as *mut c_void, &mut len, timeout, &mut usb_status) {
+            s if s.is_error() => Err(TransferError { status: s, usb_status: UsbStatus(usb_status) }),
+            _ => Ok(len),
+        }
+    }
+
+    /// Reads from a bulk endpoint until the buffer is full or the device ends the transfer with a short packet,
+    /// returns the number of bytes read.
+    pub fn bulk_in(&mut self, endpoint_address: u8, data: &mut [u8]) -> Result<usize, TransferError> {
+        let transfer = self.protocol.bulk_transfer;
+        self.transfer(transfer, endpoint_address | ENDPOINT_DIRECTION_IN, data.as_mut_ptr(), data.len())
+    }
+
+    /// Writes to a bulk endpoint, returns the number of bytes written.
+    pub fn bulk_out(&mut self, endpoint_address: u8, data: &[u8]) -> Result<usize, TransferError> {
+        let transfer = self.protocol.bulk_transfer;
+        // The data is only read by the protocol.
+        self.transfer(transfer, endpoint_address & !ENDPOINT_DIRECTION_IN, data.as_ptr() as *mut u8, data.len())
+    }
+
+    /// Reads from an interrupt endpoint, returns the number of bytes read.
+    pub fn interrupt_in(&mut self, endpoint_address: u8, data: &mut [u8]) -> Result<usize, TransferError> {
+        let transfer = self.protocol.sync_interrupt_transfer;
+        self.transfer(transfer, endpoint_address | ENDPOINT_DIRECTION_IN, data.as_mut_ptr(), data.len())
+    }
+
+    /// Writes to an interrupt endpoint, returns the number of bytes written.
+    pub fn interrupt_out(&mut self, endpoint_address: u8, data: &[u8]) -> Result<usize, TransferError> {
+        let transfer = self.protocol.sync_interrupt_transfer;
+        // The data is only read by the protocol.
+        self.transfer(transfer, endpoint_address & !ENDPOINT_DIRECTION_IN, data.as_ptr() as *mut u8, data.len())
+    }
+
+    /// Clears the halt of an endpoint after a transfer failed with [`UsbStatus::STALL`].
+    pub fn clear_halt(&mut self, endpoint_address: u8) -> Result<(), TransferError> {
+        self.control_transfer(DeviceRequest::clear_endpoint_halt(endpoint_address), &mut [])
+    }
+
+    /// The descriptor of the device.
+    pub fn device_descriptor(&mut self) -> Result<DeviceDescriptor, EfiError> {
+        let mut descriptor = DeviceDescriptor::new_zeroed();
+        match (self.protocol.get_device_descriptor)(self.this(), &mut descriptor) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(descriptor),
+        }
+    }
+
+    /// The descriptor of the current configuration of the device.
+    pub fn config_descriptor(&mut self) -> Result<ConfigDescriptor, EfiError> {
+        let mut descriptor = ConfigDescriptor::new_zeroed();
+        match (self.protocol.get_config_descriptor)(self.this(), &mut descriptor) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(descriptor),
+        }
+    }
+
+    /// The descriptor of the interface.
+    pub fn interface_descriptor(&mut self) -> Result<InterfaceDescriptor, EfiError> {
+        let mut descriptor = InterfaceDescriptor::new_zeroed();
+        match (self.protocol.get_interface_descriptor)(self.this(), &mut descriptor) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(descriptor),
+        }
+    }
+
+    /// The descriptor of an endpoint of the interface, by its index from 0.
+    pub fn endpoint_descriptor(&mut self, index: u8) -> Result<EndpointDescriptor, EfiError> {
+        let mut descriptor = EndpointDescriptor::new_zeroed();
+        match (self.protocol.get_endpoint_descriptor)(self.this(), index, &mut descriptor) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(descriptor),
+        }
+    }
+
+    /// The descriptors of every endpoint of the interface.
+    pub fn endpoint_descriptors(&mut self) -> Result<Vec<EndpointDescriptor>, EfiError> {
+        let count = self.interface_descriptor()?.num_endpoints;
+        (0..count).map(|index| self.endpoint_descriptor(index)).collect()
+    }
+
+    /// The descriptors of a configuration of the device by its index from 0, followed by the descriptors of its
+    /// interfaces and endpoints, read from the device.
+    pub fn configuration(&mut self, index: u8) -> Result<Vec<Descriptor>, TransferError> {
+        let request = DeviceRequest::get_descriptor(DESCRIPTOR_TYPE_CONFIG, index, 0);
+        let mut header = [0; mem::size_of::<ConfigDescriptor>()];
+        self.control_transfer(request, &mut header)?;
+        let (config, _) = ConfigDescriptor::read_from_prefix(&header).unwrap();
+        let mut bytes = vec![0; config.total_length as usize];
+        self.control_transfer(request, &mut bytes)?;
+        Descriptor::parse_all(&bytes)
+            .map_err(|status| TransferError { status: status.into(), usb_status: UsbStatus(0) })
+    }
+
+    /// The language IDs of the string descriptors of the device.
+    pub fn supported_languages(&mut self) -> Result<Vec<u16>, EfiError> {
+        let mut table = ptr::null_mut();
+        let mut size = 0;
+        match (self.protocol.get_supported_languages)(self.this(), &mut table, &mut size) {
+            s if s.is_error() => Err(s.into()),
+            _ if table.is_null() => Ok(Vec::new()),
+            //SAFETY: The table of the protocol has `size` bytes.
+            _ => Ok(unsafe { slice::from_raw_parts(table, size as usize / 2) }.to_vec()),
+        }
+    }
+
+    /// A string descriptor of the device, by its index from a descriptor, in a language of
+    /// [`UsbIo::supported_languages`].
+    pub fn string_descriptor<B: BootServices>(
+        &mut self,
+        boot_services: &B,
+        language: u16,
+        index: u8,
+    ) -> Result<String16, EfiError> {
+        let mut string = ptr::null_mut();
+        match (self.protocol.get_string_descriptor)(self.this(), language, index, &mut string) {
+            s if s.is_error() => return Err(s.into()),
+            _ if string.is_null() => return Err(efi::Status::DEVICE_ERROR.into()),
+            _ => (),
+        }
+        //SAFETY: The string is allocated by the protocol for the caller and null-terminated.
+        let result = String16::from(unsafe { Str16::from_ptr(string) });
+        let _ = boot_services.free_pool(string as *mut u8);
+        Ok(result)
+    }
+
+    /// Resets the port of the device and restores its configuration.
+    pub fn port_reset(&mut self) -> Result<(), EfiError> {
+        match (self.protocol.port_reset)(self.this()) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for UsbIo {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self { protocol, timeout: Self::DEFAULT_TIMEOUT }
+    }
+}
+
+impl fmt::Debug for UsbIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UsbIo").field("timeout", &self.timeout).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+    use boot_services::MockBootServices;
+    use core::cell::RefCell;
+
+    /// A keyboard-like interface with a bulk loopback endpoint pair, its configuration has a HID class descriptor.
+    /// The endpoint 1 stalls until its halt is cleared.
+    #[repr(C)]
+    struct TestUsbIo {
+        protocol: Protocol,
+        loopback: RefCell<Vec<u8>>,
+        stalled: RefCell<bool>,
+        last_timeout: RefCell<usize>,
+    }
+
+    fn test_usb_io<'a>(this: *mut Protocol) -> &'a TestUsbIo {
+        unsafe { &*(this as *const TestUsbIo) }
+    }
+
+    const DEVICE: DeviceDescriptor = DeviceDescriptor {
+        length: 18,
+        descriptor_type: DESCRIPTOR_TYPE_DEVICE,
+        bcd_usb: 0x0200,
+        device_class: 0,
+        device_sub_class: 0,
+        device_protocol: 0,
+        max_packet_size0: 64,
+        id_vendor: 0x046d,
+        id_product: 0xc31c,
+        bcd_device: 0x6400,
+        str_manufacturer: 1,
+        str_product: 2,
+        str_serial_number: 0,
+        num_configurations: 1,
+    };
+
+    const INTERFACE: InterfaceDescriptor = InterfaceDescriptor {
+        length: 9,
+        descriptor_type: DESCRIPTOR_TYPE_INTERFACE,
+        interface_number: 0,
+        alternate_setting: 0,
+        num_endpoints: 2,
+        interface_class: 3,
+        interface_sub_class: 1,
+        interface_protocol: 1,
+        interface: 0,
+    };
+
+    const HID: [u8; 9] = [9, 0x21, 0x11, 0x01, 0, 1, 0x22, 0x41, 0];
+
+    fn endpoint(endpoint_address: u8) -> EndpointDescriptor {
+        EndpointDescriptor {
+            length: 7,
+            descriptor_type: DESCRIPTOR_TYPE_ENDPOINT,
+            endpoint_address,
+            attributes: 2,
+            max_packet_size: 512,
+            interval: 0,
+        }
+    }
+
+    fn configuration() -> Vec<u8> {
+        let mut config = ConfigDescriptor {
+            length: 9,
+            descriptor_type: DESCRIPTOR_TYPE_CONFIG,
+            total_length: 0,
+            num_interfaces: 1,
+            configuration_value: 1,
+            configuration: 0,
+            attributes: 0xa0,
+            max_power: 50,
+        };
+        config.total_length = (9 + 9 + HID.len() + 7 + 7) as u16;
+        let mut bytes = config.as_bytes().to_vec();
+        bytes.extend(INTERFACE.as_bytes());
+        bytes.extend(HID);
+        bytes.extend(endpoint(0x81).as_bytes());
+        bytes.extend(endpoint(0x02).as_bytes());
+        bytes
+    }
+
+    extern "efiapi" fn control_transfer(
+        this: *mut Protocol,
+        request: *mut DeviceRequest,
+        direction: DataDirection,
+        _timeout: u32,
+        data: *mut c_void,
+        len: usize,
+        usb_status: *mut u32,
+    ) -> efi::Status {
+        let request = unsafe { *request };
+        assert_eq!(len, request.length as usize);
+        match (request.request_type, request.request, request.value, request.index) {
+            (0x80, REQUEST_GET_DESCRIPTOR, 0x0200, 0) if direction == DATA_IN => {
+                let config = configuration();
+                let len = len.min(config.len());
+                unsafe { slice::from_raw_parts_mut(data as *mut u8, len) }.copy_from_slice(&config[..len]);
+                efi::Status::SUCCESS
+            }
+            (0x02, REQUEST_CLEAR_FEATURE, FEATURE_ENDPOINT_HALT, 0x81) if direction == NO_DATA => {
+                *test_usb_io(this).stalled.borrow_mut() = false;
+                efi::Status::SUCCESS
+            }
+            _ => {
+                unsafe { usb_status.write(UsbStatus::STALL.0) };
+                efi::Status::DEVICE_ERROR
+            }
+        }
+    }
+
+    extern "efiapi" fn bulk_transfer(
+        this: *mut Protocol,
+        endpoint_address: u8,
+        data: *mut c_void,
+        len: *mut usize,
+        timeout: usize,
+        usb_status: *mut u32,
+    ) -> efi::Status {
+        let test_usb_io = test_usb_io(this);
+        *test_usb_io.last_timeout.borrow_mut() = timeout;
+        let mut loopback = test_usb_io.loopback.borrow_mut();
+        let data = unsafe { slice::from_raw_parts_mut(data as *mut u8, *len) };
+        match endpoint_address {
+            0x81 if *test_usb_io.stalled.borrow() => {
+                unsafe { usb_status.write(UsbStatus::STALL.0) };
+                return efi::Status::DEVICE_ERROR;
+            }
+            0x81 => {
+                let read = data.len().min(loopback.len());
+                data[..read].copy_from_slice(&loopback.drain(..read).collect::<Vec<_>>());
+                unsafe { len.write(read) };
+            }
+            0x02 => loopback.extend_from_slice(data),
+            _ => return efi::Status::INVALID_PARAMETER,
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_device_descriptor(_: *mut Protocol, descriptor: *mut DeviceDescriptor) -> efi::Status {
+        unsafe { descriptor.write(DEVICE) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_interface_descriptor(_: *mut Protocol, descriptor: *mut InterfaceDescriptor) -> efi::Status {
+        unsafe { descriptor.write(INTERFACE) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_endpoint_descriptor(
+        _: *mut Protocol,
+        index: u8,
+        descriptor: *mut EndpointDescriptor,
+    ) -> efi::Status {
+        match index {
+            0 => unsafe { descriptor.write(endpoint(0x81)) },
+            1 => unsafe { descriptor.write(endpoint(0x02)) },
+            _ => return efi::Status::NOT_FOUND,
+        }
+        efi::Status::SUCCESS
+    }
+
+    static LANGUAGES: [u16; 2] = [0x0409, 0x0407];
+
+    extern "efiapi" fn get_supported_languages(_: *mut Protocol, table: *mut *mut u16, size: *mut u16) -> efi::Status {
+        unsafe {
+            table.write(LANGUAGES.as_ptr() as *mut u16);
+            size.write(4);
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_string_descriptor(
+        _: *mut Protocol,
+        language: u16,
+        index: u8,
+        string: *mut *mut u16,
+    ) -> efi::Status {
+        let value = match (language, index) {
+            (0x0409, 2) => "USB Receiver",
+            _ => return efi::Status::NOT_FOUND,
+        };
+        let value = Box::leak(value.encode_utf16().chain([0]).collect::<Vec<_>>().into_boxed_slice());
+        unsafe { string.write(value.as_mut_ptr()) };
+        efi::Status::SUCCESS
+    }
+
+    macro_rules! unused {
+        ($($name:ident($($arg:ty),*);)*) => {
+            $(extern "efiapi" fn $name(_: *mut Protocol, $(_: $arg),*) -> efi::Status {
+                unimplemented!()
+            })*
+        };
+    }
+
+    unused! {
+        async_interrupt_transfer(u8, efi::Boolean, usize, usize, Option<AsyncTransferCallback>, *mut c_void);
+        isochronous_transfer(u8, *mut c_void, usize, *mut u32);
+        async_isochronous_transfer(u8, *mut c_void, usize, AsyncTransferCallback, *mut c_void);
+        get_config_descriptor(*mut ConfigDescriptor);
+        port_reset();
+    }
+
+    fn usb_io() -> (UsbIo, &'static TestUsbIo) {
+        let usb_io = Box::leak(Box::new(TestUsbIo {
+            protocol: Protocol {
+                control_transfer,
+                bulk_transfer,
+                async_interrupt_transfer,
+                sync_interrupt_transfer: bulk_transfer,
+                isochronous_transfer,
+                async_isochronous_transfer,
+                get_device_descriptor,
+                get_config_descriptor,
+                get_interface_descriptor,
+                get_endpoint_descriptor,
+                get_string_descriptor,
+                get_supported_languages,
+                port_reset,
+            },
+            loopback: RefCell::new(Vec::new()),
+            stalled: RefCell::new(false),
+            last_timeout: RefCell::new(0),
+        }));
+        let test_usb_io = test_usb_io(&mut usb_io.protocol);
+        (UsbIo::from(&mut usb_io.protocol), test_usb_io)
+    }
+
+    #[test]
+    fn test_device_request() {
+        let request = DeviceRequest::get_descriptor(DESCRIPTOR_TYPE_STRING, 2, 0x0409);
+        assert_eq!([0x80, 0x06, 0x02, 0x03, 0x09, 0x04, 0, 0], request.as_bytes());
+        assert_eq!(RequestDirection::In, request.direction());
+        let request = DeviceRequest::new(RequestDirection::Out, RequestKind::Class, Recipient::Interface, 0x0a)
+            .with_value(0x0100)
+            .with_index(1);
+        assert_eq!([0x21, 0x0a, 0x00, 0x01, 0x01, 0x00, 0, 0], request.as_bytes());
+        assert_eq!([0x00, 0x09, 0x01, 0x00, 0x00, 0x00, 0, 0], DeviceRequest::set_configuration(1).as_bytes());
+    }
+
+    #[test]
+    fn test_descriptors() {
+        let (mut usb_io, _) = usb_io();
+        assert_eq!(DEVICE, usb_io.device_descriptor().unwrap());
+        assert_eq!(INTERFACE, usb_io.interface_descriptor().unwrap());
+        assert_eq!(vec![endpoint(0x81), endpoint(0x02)], usb_io.endpoint_descriptors().unwrap());
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), usb_io.endpoint_descriptor(2));
+
+        let descriptors = usb_io.configuration(0).unwrap();
+        assert_eq!(5, descriptors.len());
+        assert!(matches!(descriptors[0], Descriptor::Config(config) if config.num_interfaces == 1));
+        assert_eq!(Descriptor::Interface(INTERFACE), descriptors[1]);
+        assert_eq!(Descriptor::Other(HID.to_vec()), descriptors[2]);
+        assert_eq!(Descriptor::Endpoint(endpoint(0x02)), descriptors[4]);
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), Descriptor::parse_all(&[9, 2, 0]));
+
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_free_pool().returning(|_| Ok(()));
+        assert_eq!(vec![0x0409, 0x0407], usb_io.supported_languages().unwrap());
+        assert_eq!(usb_io.string_descriptor(&boot_services, 0x0409, 2).unwrap(), "USB Receiver");
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), usb_io.string_descriptor(&boot_services, 0x0407, 2));
+    }
+
+    #[test]
+    fn test_transfers() {
+        let (usb_io, test_usb_io) = usb_io();
+        let mut usb_io = usb_io.with_timeout(100);
+
+        assert_eq!(Ok(5), usb_io.bulk_out(0x02, b"hello"));
+        assert_eq!(100, *test_usb_io.last_timeout.borrow());
+        let mut data = [0; 8];
+        assert_eq!(Ok(3), usb_io.bulk_in(0x01, &mut data[..3]));
+        assert_eq!(Ok(2), usb_io.interrupt_in(0x81, &mut data[3..]));
+        assert_eq!(b"hello", &data[..5]);
+
+        *test_usb_io.stalled.borrow_mut() = true;
+        let error = usb_io.bulk_in(0x81, &mut data).unwrap_err();
+        assert!(error.usb_status.contains(UsbStatus::STALL));
+        assert_eq!(efi::Status::DEVICE_ERROR, efi::Status::from(error));
+        usb_io.clear_halt(0x81).unwrap();
+        assert_eq!(Ok(0), usb_io.bulk_in(0x81, &mut data));
+
+        let request = DeviceRequest::new(RequestDirection::Out, RequestKind::Vendor, Recipient::Device, 0x42);
+        let error = usb_io.control_transfer(request, &mut [1, 2]).unwrap_err();
+        assert_eq!(UsbStatus::STALL, error.usb_status);
+    }
+}
@@ -0,0 +1,235 @@
+//! Variable Lock protocol of EDK II, which makes variables read only from the end of DXE.
+//!
+//! The variables are locked with [`VariableLock::request_to_lock`] before the end of DXE, when the protocol stops
+//! accepting requests. Rather than each driver hooking the `EndOfDxe` event group, the components of a driver declare
+//! their variables to a [`VariableLockRegistry`], which requests the locks when the group is signaled:
+//!
+//! ```ignore
+//! let mut registry = VariableLockRegistry::new();
+//! registry.add(&SETUP_NAME, &SETUP_GUID)?;
+//! other_component::declare_variables(&mut registry)?;
+//! registry.lock_at_end_of_dxe(boot_services)?;
+//! ```
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{fmt, ops::Deref};
+
+use boot_services::{event::EventType, protocol_handler::Protocol as ProtocolTrait, tpl::Tpl, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0xcd3d0a05, 0x9e24, 0x437c, 0xa8, 0x91, &[0x1e, 0xe0, 0x53, 0xdb, 0x76, 0x38]);
+
+/// Event group signaled at the end of DXE, `gEfiEndOfDxeEventGroupGuid`.
+pub const END_OF_DXE_EVENT_GROUP_GUID: efi::Guid =
+    efi::Guid::from_fields(0x02ce967a, 0xdd7e, 0x4ffc, 0x9e, 0xe7, &[0x81, 0x0c, 0xf0, 0x47, 0x08, 0x80]);
+
+pub type ProtocolRequestToLock = extern "efiapi" fn(*mut Protocol, *mut u16, *mut efi::Guid) -> efi::Status;
+
+/// FFI definition of `EDKII_VARIABLE_LOCK_PROTOCOL`.
+#[repr(C)]
+pub struct Protocol {
+    pub request_to_lock: ProtocolRequestToLock,
+}
+
+/// Variable Lock protocol.
+pub struct VariableLockProtocol;
+
+unsafe impl ProtocolTrait for VariableLockProtocol {
+    type Interface = Protocol;
+    fn protocol_guid(&self) -> &'static efi::Guid {
+        &PROTOCOL_GUID
+    }
+}
+
+impl Deref for VariableLockProtocol {
+    type Target = efi::Guid;
+    fn deref(&self) -> &Self::Target {
+        self.protocol_guid()
+    }
+}
+
+/// Typed access to the Variable Lock protocol.
+pub struct VariableLock(&'static mut Protocol);
+
+impl VariableLock {
+    /// Locates the instance of the protocol.
+    pub fn locate<B: BootServices>(boot_services: &B) -> Result<Self, EfiError> {
+        boot_services.locate_protocol(&VariableLockProtocol, None).map(Self)
+    }
+
+    /// Requests the variable `name`, a null-terminated UCS-2 string, of `namespace` to be locked at the end of DXE.
+    ///
+    /// Returns `ACCESS_DENIED` once DXE has ended.
+    pub fn request_to_lock(&mut self, name: &[u16], namespace: &efi::Guid) -> Result<(), EfiError> {
+        if !name.contains(&0) {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        // The name and the namespace are only read by the protocol.
+        match (self.0.request_to_lock)(
+            self.0,
+            name.as_ptr() as *mut u16,
+            namespace as *const efi::Guid as *mut efi::Guid,
+        ) {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<&'static mut Protocol> for VariableLock {
+    fn from(protocol: &'static mut Protocol) -> Self {
+        Self(protocol)
+    }
+}
+
+impl fmt::Debug for VariableLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VariableLock").finish_non_exhaustive()
+    }
+}
+
+/// Variables to lock at the end of DXE, declared by the components of a driver.
+#[derive(Debug, Default)]
+pub struct VariableLockRegistry {
+    variables: Vec<(Vec<u16>, efi::Guid)>,
+}
+
+impl VariableLockRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares the variable `name`, a null-terminated UCS-2 string, of `namespace`.
+    pub fn add(&mut self, name: &[u16], namespace: &efi::Guid) -> Result<(), EfiError> {
+        let length = name.iter().position(|c| *c == 0).ok_or(efi::Status::INVALID_PARAMETER)?;
+        let name = &name[..=length];
+        if !self.variables.iter().any(|variable| variable.0 == name && variable.1 == *namespace) {
+            self.variables.push((name.to_vec(), *namespace));
+        }
+        Ok(())
+    }
+
+    /// The declared variables.
+    pub fn variables(&self) -> impl Iterator<Item = (&[u16], &efi::Guid)> {
+        self.variables.iter().map(|(name, namespace)| (&name[..], namespace))
+    }
+
+    /// Requests the locks of the variables when the `EndOfDxe` event group is signaled, returns the event.
+    ///
+    /// Every lock is requested, even after a failure, as nothing can report the failures from the notification.
+    pub fn lock_at_end_of_dxe<B: BootServices>(self, boot_services: &'static B) -> Result<efi::Event, EfiError> {
+        boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(lock_variables::<B>),
+            Box::new(EndOfDxeLock { boot_services, registry: self }),
+            &END_OF_DXE_EVENT_GROUP_GUID,
+        )
+    }
+}
+
+/// Context of the `EndOfDxe` notification.
+struct EndOfDxeLock<B: BootServices + 'static> {
+    boot_services: &'static B,
+    registry: VariableLockRegistry,
+}
+
+extern "efiapi" fn lock_variables<B: BootServices>(event: efi::Event, context: Box<EndOfDxeLock<B>>) {
+    // The group is signaled once, the context is dropped with the event.
+    let _ = context.boot_services.close_event(event);
+    let Ok(mut variable_lock) = VariableLock::locate(context.boot_services) else {
+        return;
+    };
+    for (name, namespace) in context.registry.variables() {
+        let _ = variable_lock.request_to_lock(name, namespace);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use boot_services::{event::EventNotifyCallback, MockBootServices};
+    use core::{
+        slice,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+    use std::sync::Mutex;
+
+    /// The variable lock, the protocol is the first field to be found from its pointer.
+    #[repr(C)]
+    struct TestVariableLock {
+        protocol: Protocol,
+        locked: Vec<(Vec<u16>, efi::Guid)>,
+    }
+
+    extern "efiapi" fn request_to_lock(this: *mut Protocol, name: *mut u16, namespace: *mut efi::Guid) -> efi::Status {
+        let variable_lock = unsafe { &mut *(this as *mut TestVariableLock) };
+        let name = unsafe { slice::from_raw_parts(name, (0..).position(|i| *name.add(i) == 0).unwrap() + 1) };
+        variable_lock.locked.push((name.to_vec(), unsafe { *namespace }));
+        efi::Status::SUCCESS
+    }
+
+    const NAMESPACE: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+    const SETUP: [u16; 6] = [b'S' as u16, b'e' as u16, b't' as u16, b'u' as u16, b'p' as u16, 0];
+    const BOOT: [u16; 4] = [b'B' as u16, b'o' as u16, b'o' as u16, 0];
+
+    fn boot_services() -> (MockBootServices, &'static TestVariableLock) {
+        let variable_lock =
+            Box::leak(Box::new(TestVariableLock { protocol: Protocol { request_to_lock }, locked: Vec::new() }));
+        let variable_lock_ptr = variable_lock as *mut TestVariableLock as usize;
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_locate_protocol::<VariableLockProtocol, Protocol>()
+            .returning(move |_, _| Ok(unsafe { &mut (*(variable_lock_ptr as *mut TestVariableLock)).protocol }));
+        (boot_services, unsafe { &*(variable_lock_ptr as *const TestVariableLock) })
+    }
+
+    #[test]
+    fn test_request_to_lock() {
+        let (boot_services, test_variable_lock) = boot_services();
+        let mut variable_lock = VariableLock::locate(&boot_services).unwrap();
+        variable_lock.request_to_lock(&SETUP, &NAMESPACE).unwrap();
+        assert_eq!(vec![(SETUP.to_vec(), NAMESPACE)], test_variable_lock.locked);
+        assert_eq!(efi::Status::INVALID_PARAMETER, variable_lock.request_to_lock(&SETUP[..5], &NAMESPACE).unwrap_err());
+    }
+
+    type Notification = (EventNotifyCallback<Box<EndOfDxeLock<MockBootServices>>>, usize);
+
+    #[test]
+    fn test_lock_at_end_of_dxe() {
+        static NOTIFICATION: Mutex<Option<Notification>> = Mutex::new(None);
+        static CLOSED: AtomicBool = AtomicBool::new(false);
+
+        let (mut boot_services, test_variable_lock) = boot_services();
+        boot_services.expect_create_event_ex::<Box<EndOfDxeLock<MockBootServices>>>().once().returning(
+            |event_type, tpl, notify_function, context, group| {
+                assert_eq!((EventType::NOTIFY_SIGNAL, Tpl::CALLBACK), (event_type, tpl));
+                assert_eq!(END_OF_DXE_EVENT_GROUP_GUID, *group);
+                *NOTIFICATION.lock().unwrap() = Some((notify_function.unwrap(), Box::into_raw(context) as usize));
+                Ok(0x100 as efi::Event)
+            },
+        );
+        boot_services.expect_close_event().once().withf(|event| *event == 0x100 as efi::Event).returning(|_| {
+            CLOSED.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        let boot_services = Box::leak(Box::new(boot_services));
+
+        let mut registry = VariableLockRegistry::new();
+        registry.add(&SETUP, &NAMESPACE).unwrap();
+        registry.add(&BOOT, &NAMESPACE).unwrap();
+        registry.add(&SETUP, &NAMESPACE).unwrap();
+        assert_eq!(efi::Status::INVALID_PARAMETER, registry.add(&SETUP[..5], &NAMESPACE).unwrap_err());
+        assert_eq!(2, registry.variables().count());
+        registry.lock_at_end_of_dxe(boot_services).unwrap();
+        assert!(test_variable_lock.locked.is_empty());
+
+        // The end of DXE.
+        let (notify_function, context) = NOTIFICATION.lock().unwrap().take().unwrap();
+        notify_function(0x100 as efi::Event, unsafe { Box::from_raw(context as *mut EndOfDxeLock<MockBootServices>) });
+        assert_eq!(vec![(SETUP.to_vec(), NAMESPACE), (BOOT.to_vec(), NAMESPACE)], test_variable_lock.locked);
+        assert!(CLOSED.load(Ordering::SeqCst));
+    }
+}
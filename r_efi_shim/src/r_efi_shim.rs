@@ -0,0 +1,86 @@
+//! The `r-efi` definitions the crates of the workspace are built against.
+//!
+//! The crates depend on this shim under the name `r_efi` and use it as they would use `r-efi` itself. Exactly one
+//! feature selects the major version it re-exports, so a platform pinned to an older `r-efi` can take new releases of
+//! the helpers without a second, conflicting set of definitions. The shim has no default version, it is chosen by the
+//! `r-efi-5` or `r-efi-6` feature of `mu_rust_helpers`, `r-efi-5` being one of its default features:
+//!
+//! ```toml
+//! mu_rust_helpers = { version = "...", default-features = false, features = ["r-efi-6", "boot_services"] }
+//! ```
+#![no_std]
+
+#[cfg(not(any(feature = "r-efi-5", feature = "r-efi-6")))]
+compile_error!("One of the r-efi-5 or r-efi-6 features must be enabled.");
+
+#[cfg(all(feature = "r-efi-5", feature = "r-efi-6"))]
+compile_error!("The r-efi-5 and r-efi-6 features are exclusive, disable the default features to select r-efi-6.");
+
+#[cfg(all(feature = "r-efi-5", not(feature = "r-efi-6")))]
+pub use r_efi_5::*;
+
+#[cfg(all(feature = "r-efi-6", not(feature = "r-efi-5")))]
+pub use r_efi_6::*;
+
+/// Calls a function of the firmware with a tuple of arguments.
+///
+/// The functions of the firmware are `unsafe fn` since r-efi 6 and safe before, so their calls are in `unsafe` blocks
+/// which would be needless with r-efi 5. This function is `unsafe` for every version:
+///
+/// ```ignore
+/// let status = unsafe { r_efi::efi_call(protocol.reset, (protocol_ptr, efi::Boolean::FALSE)) };
+/// ```
+///
+/// # Safety
+///
+/// The requirements of the firmware function must be upheld, like the validity of the pointers it is given.
+pub unsafe fn efi_call<F: FirmwareFunction<A>, A>(function: F, arguments: A) -> F::Output {
+    function.call(arguments)
+}
+
+/// A function pointer of the firmware, called with a tuple of its arguments by [`efi_call`].
+pub trait FirmwareFunction<A> {
+    type Output;
+
+    /// Calls the function.
+    ///
+    /// # Safety
+    ///
+    /// See [`efi_call`].
+    unsafe fn call(self, arguments: A) -> Self::Output;
+}
+
+macro_rules! impl_firmware_function {
+    ($($argument:ident: $argument_type:ident),*) => {
+        impl<R, $($argument_type),*> FirmwareFunction<($($argument_type,)*)> for extern "efiapi" fn($($argument_type),*) -> R {
+            type Output = R;
+
+            unsafe fn call(self, ($($argument,)*): ($($argument_type,)*)) -> R {
+                self($($argument),*)
+            }
+        }
+
+        impl<R, $($argument_type),*> FirmwareFunction<($($argument_type,)*)>
+            for unsafe extern "efiapi" fn($($argument_type),*) -> R
+        {
+            type Output = R;
+
+            unsafe fn call(self, ($($argument,)*): ($($argument_type,)*)) -> R {
+                //SAFETY: The caller upholds the requirements of the function.
+                unsafe { self($($argument),*) }
+            }
+        }
+    };
+}
+
+impl_firmware_function!();
+impl_firmware_function!(a: A);
+impl_firmware_function!(a: A, b: B);
+impl_firmware_function!(a: A, b: B, c: C);
+impl_firmware_function!(a: A, b: B, c: C, d: D);
+impl_firmware_function!(a: A, b: B, c: C, d: D, e: E);
+impl_firmware_function!(a: A, b: B, c: C, d: D, e: E, f: F);
+impl_firmware_function!(a: A, b: B, c: C, d: D, e: E, f: F, g: G);
+impl_firmware_function!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H);
+impl_firmware_function!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I);
+impl_firmware_function!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J);
@@ -0,0 +1,412 @@
+//! Capsule result variables, the outcome of the capsules processed by the firmware, reported after the reset.
+//!
+//! The firmware records a `Capsule####` variable for every capsule it processes, in a ring of `CapsuleMax + 1`
+//! variables, and names the latest one in `CapsuleLast`. An update agent reports the outcome of its capsules from
+//! them:
+//!
+//! ```ignore
+//! for (index, result) in capsule_result::capsule_results(&RUNTIME_SERVICES)? {
+//!     if let CapsuleResultPayload::Fmp(fmp) = &result.payload {
+//!         report(fmp.update_image_type_id, result.status);
+//!     }
+//! }
+//! ```
+//!
+//! [UEFI Spec Documentation: 8.5.6. UEFI variable reporting on the Success or any Errors encountered in processing of capsules after restart](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#uefi-variable-reporting-on-the-success-or-any-errors-encountered-in-processing-of-capsules-after-restart)
+
+use alloc::{string::String, vec, vec::Vec};
+use core::{mem, ptr, slice};
+
+use r_efi::efi;
+use status::EfiError;
+
+use crate::RuntimeServices;
+
+/// Namespace of the capsule result variables, `EFI_CAPSULE_REPORT_GUID`
+pub const CAPSULE_REPORT_GUID: efi::Guid =
+    efi::Guid::from_fields(0x39b68c46, 0xf7fb, 0x441b, 0xb6, 0xec, &[0x16, 0xb0, 0xf6, 0x98, 0x21, 0xf3]);
+
+/// GUID of the capsules of firmware management payloads, `EFI_FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID`
+pub const FMP_CAPSULE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x6dcbd5ed, 0xe82d, 0x4c44, 0xbd, 0xa1, &[0x71, 0x94, 0x19, 0x9a, 0xd9, 0x2a]);
+
+/// Name of the variable holding the name of the last result variable, `CapsuleMax`
+pub const CAPSULE_MAX_NAME: [u16; 11] = ucs2_name(b"CapsuleMax\0");
+/// Name of the variable holding the name of the latest result variable, `CapsuleLast`
+pub const CAPSULE_LAST_NAME: [u16; 12] = ucs2_name(b"CapsuleLast\0");
+
+/// Attributes of the capsule result variables and of their bookkeeping variables
+pub const CAPSULE_RESULT_ATTRIBUTES: u32 =
+    efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+
+/// Version of `EFI_CAPSULE_RESULT_VARIABLE_FMP`
+pub const FMP_RESULT_VERSION: u16 = 1;
+
+/// Size of `EFI_CAPSULE_RESULT_VARIABLE_HEADER`, whose status is an `EFI_STATUS` of the size of a pointer
+const HEADER_SIZE: usize = 8 + 16 + mem::size_of::<efi::Time>() + mem::size_of::<usize>();
+
+/// Size of `EFI_CAPSULE_RESULT_VARIABLE_FMP`, before the names of the capsule file and of its target
+const FMP_HEADER_SIZE: usize = 20;
+
+/// Length of the name of a result variable, `Capsule####`, without null terminator
+const VARIABLE_NAME_LEN: usize = 11;
+
+const fn ucs2_name<const N: usize>(ascii: &[u8; N]) -> [u16; N] {
+    let mut name = [0; N];
+    let mut i = 0;
+    while i < N {
+        name[i] = ascii[i] as u16;
+        i += 1;
+    }
+    name
+}
+
+/// The null-terminated name of the result variable at `index`, `Capsule####` in upper case hexadecimal
+pub fn capsule_variable_name(index: u16) -> [u16; VARIABLE_NAME_LEN + 1] {
+    let mut name = ucs2_name(b"Capsule0000\0");
+    for (i, c) in name[7..VARIABLE_NAME_LEN].iter_mut().enumerate() {
+        let digit = (index >> (12 - 4 * i)) & 0xF;
+        *c = match digit {
+            0..=9 => b'0' as u16 + digit,
+            _ => b'A' as u16 + digit - 10,
+        };
+    }
+    name
+}
+
+/// The index of a result variable from its name, `Capsule####` with or without null terminator
+///
+/// The hexadecimal digits may be in upper or lower case. Returns `None` for any other name.
+pub fn parse_capsule_variable_name(name: &[u16]) -> Option<u16> {
+    let name = match name.split_last() {
+        Some((0, name)) => name,
+        _ => name,
+    };
+    if name.len() != VARIABLE_NAME_LEN || name[..7] != ucs2_name(b"Capsule") {
+        return None;
+    }
+    name[7..].iter().try_fold(0, |index, &c| {
+        let digit = char::from_u32(c as u32)?.to_digit(16)?;
+        Some(index << 4 | digit as u16)
+    })
+}
+
+/// The result of an FMP capsule, `EFI_CAPSULE_RESULT_VARIABLE_FMP`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FmpCapsuleResult {
+    /// The index of the payload in the capsule, from 0
+    pub payload_index: u8,
+    /// The image index of the payload, from 1
+    pub update_image_index: u8,
+    /// The type of the firmware image updated by the payload
+    pub update_image_type_id: efi::Guid,
+    /// The name of the capsule file on the media the capsule was delivered on, empty if it was not
+    pub capsule_file_name: String,
+    /// The device path of the target of the payload as text, empty if there is none
+    pub capsule_target: String,
+}
+
+/// The data specific to the type of the capsule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapsuleResultPayload {
+    /// The result of a payload of an FMP capsule, [`FMP_CAPSULE_GUID`]
+    Fmp(FmpCapsuleResult),
+    /// The data following the header for the other capsules, empty for most of them
+    Other(Vec<u8>),
+}
+
+/// A capsule result variable, `EFI_CAPSULE_RESULT_VARIABLE_HEADER` followed by the data of the type of the capsule
+#[derive(Debug, Clone)]
+pub struct CapsuleResult {
+    /// The GUID of the capsule, [`FMP_CAPSULE_GUID`] for the FMP capsules
+    pub capsule_guid: efi::Guid,
+    /// The time the capsule was processed at
+    pub processed: efi::Time,
+    /// The outcome of the processing
+    pub status: efi::Status,
+    pub payload: CapsuleResultPayload,
+}
+
+impl CapsuleResult {
+    /// Parses the data of a `Capsule####` variable
+    ///
+    /// Returns `INVALID_PARAMETER` if the variable is malformed.
+    pub fn parse(data: &[u8]) -> Result<Self, EfiError> {
+        let total_size = match data.first_chunk::<4>() {
+            Some(size) => u32::from_le_bytes(*size) as usize,
+            None => return Err(efi::Status::INVALID_PARAMETER.into()),
+        };
+        if total_size < HEADER_SIZE || total_size > data.len() {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let data = &data[..total_size];
+        let capsule_guid = efi::Guid::from_bytes(data[8..24].try_into().unwrap());
+        //SAFETY: The header holds a whole time after the GUID.
+        let processed = unsafe { ptr::read_unaligned(data[24..].as_ptr() as *const efi::Time) };
+        let status_offset = 24 + mem::size_of::<efi::Time>();
+        let status = usize::from_le_bytes(data[status_offset..HEADER_SIZE].try_into().unwrap());
+        let payload = match capsule_guid == FMP_CAPSULE_GUID {
+            true => CapsuleResultPayload::Fmp(parse_fmp_result(&data[HEADER_SIZE..])?),
+            false => CapsuleResultPayload::Other(data[HEADER_SIZE..].to_vec()),
+        };
+        Ok(Self { capsule_guid, processed, status: efi::Status::from_usize(status), payload })
+    }
+
+    /// Serializes the result into the data of a `Capsule####` variable
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0; 8];
+        data.extend_from_slice(self.capsule_guid.as_bytes());
+        //SAFETY: `efi::Time` is a plain C structure.
+        data.extend_from_slice(unsafe {
+            slice::from_raw_parts(&self.processed as *const efi::Time as *const u8, mem::size_of::<efi::Time>())
+        });
+        data.extend_from_slice(&self.status.as_usize().to_le_bytes());
+        match &self.payload {
+            CapsuleResultPayload::Fmp(fmp) => {
+                data.extend_from_slice(&FMP_RESULT_VERSION.to_le_bytes());
+                data.extend_from_slice(&[fmp.payload_index, fmp.update_image_index]);
+                data.extend_from_slice(fmp.update_image_type_id.as_bytes());
+                for text in [&fmp.capsule_file_name, &fmp.capsule_target] {
+                    data.extend(text.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+                }
+            }
+            CapsuleResultPayload::Other(payload) => data.extend_from_slice(payload),
+        }
+        let total_size = data.len() as u32;
+        data[..4].copy_from_slice(&total_size.to_le_bytes());
+        data
+    }
+}
+
+/// Parses `EFI_CAPSULE_RESULT_VARIABLE_FMP`, the names after its header are optional for older firmware.
+fn parse_fmp_result(data: &[u8]) -> Result<FmpCapsuleResult, EfiError> {
+    if data.len() < FMP_HEADER_SIZE || u16::from_le_bytes([data[0], data[1]]) < FMP_RESULT_VERSION {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    let mut names = data[FMP_HEADER_SIZE..].chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]]));
+    let mut next_name = || -> Result<String, EfiError> {
+        let mut name = Vec::new();
+        loop {
+            match names.next() {
+                Some(0) => return Ok(String::from_utf16_lossy(&name)),
+                Some(c) => name.push(c),
+                None if name.is_empty() => return Ok(String::new()),
+                None => return Err(efi::Status::INVALID_PARAMETER.into()),
+            }
+        }
+    };
+    Ok(FmpCapsuleResult {
+        payload_index: data[2],
+        update_image_index: data[3],
+        update_image_type_id: efi::Guid::from_bytes(data[4..20].try_into().unwrap()),
+        capsule_file_name: next_name()?,
+        capsule_target: next_name()?,
+    })
+}
+
+/// Reads and parses the result variable at `index`
+pub fn read_capsule_result<R: RuntimeServices>(runtime_services: &R, index: u16) -> Result<CapsuleResult, EfiError> {
+    let (data, _) =
+        runtime_services.get_variable::<Vec<u8>>(&capsule_variable_name(index), &CAPSULE_REPORT_GUID, None)?;
+    CapsuleResult::parse(&data)
+}
+
+/// Reads a bookkeeping variable, the name of a result variable without null terminator.
+fn read_index<R>(runtime_services: &R, name: &[u16]) -> Result<Option<u16>, EfiError>
+where
+    R: RuntimeServices,
+{
+    let data = match runtime_services.get_variable::<Vec<u8>>(name, &CAPSULE_REPORT_GUID, None) {
+        Ok((data, _)) => data,
+        Err(status) if status == efi::Status::NOT_FOUND => return Ok(None),
+        Err(status) => return Err(status),
+    };
+    let name = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect::<Vec<_>>();
+    match data.len() % 2 {
+        0 => parse_capsule_variable_name(&name).map(Some).ok_or(efi::Status::INVALID_PARAMETER.into()),
+        _ => Err(efi::Status::INVALID_PARAMETER.into()),
+    }
+}
+
+fn write_index<R>(runtime_services: &R, name: &[u16], index: u16) -> Result<(), EfiError>
+where
+    R: RuntimeServices,
+{
+    let data =
+        capsule_variable_name(index)[..VARIABLE_NAME_LEN].iter().flat_map(|c| c.to_le_bytes()).collect::<Vec<_>>();
+    runtime_services.set_variable(name, &CAPSULE_REPORT_GUID, CAPSULE_RESULT_ATTRIBUTES, &data)
+}
+
+/// The index of the last result variable of the ring, from `CapsuleMax`, `None` if the platform did not set it
+///
+/// Returns `INVALID_PARAMETER` if the variable is not the name of a result variable.
+pub fn capsule_max<R: RuntimeServices>(runtime_services: &R) -> Result<Option<u16>, EfiError> {
+    read_index(runtime_services, &CAPSULE_MAX_NAME)
+}
+
+/// The index of the latest result variable, from `CapsuleLast`, `None` if no capsule was processed
+///
+/// Returns `INVALID_PARAMETER` if the variable is not the name of a result variable.
+pub fn capsule_last<R: RuntimeServices>(runtime_services: &R) -> Result<Option<u16>, EfiError> {
+    read_index(runtime_services, &CAPSULE_LAST_NAME)
+}
+
+/// Sets `CapsuleMax`, the index of the last result variable of the ring, as the platform does at boot
+pub fn set_capsule_max<R: RuntimeServices>(runtime_services: &R, max: u16) -> Result<(), EfiError> {
+    write_index(runtime_services, &CAPSULE_MAX_NAME, max)
+}
+
+/// The result variables, from the oldest to the latest one, with their index
+///
+/// The ring is read from the index after `CapsuleLast`. The variables that are malformed are skipped, as one corrupt
+/// result must not hide the others.
+pub fn capsule_results<R: RuntimeServices>(runtime_services: &R) -> Result<Vec<(u16, CapsuleResult)>, EfiError> {
+    let last = capsule_last(runtime_services)?;
+    let mut indexes = Vec::new();
+    let (mut name, mut namespace) = (vec![0], efi::Guid::from_bytes(&[0; 16]));
+    loop {
+        //SAFETY: The name is null-terminated.
+        match unsafe { runtime_services.get_next_variable_name_in_place(&mut name, &mut namespace) } {
+            Ok(()) if namespace == CAPSULE_REPORT_GUID => indexes.extend(parse_capsule_variable_name(&name)),
+            Ok(()) => continue,
+            Err(status) if status == efi::Status::NOT_FOUND => break,
+            Err(status) => return Err(status),
+        }
+    }
+    // The variables after the latest one are older, they were written before the ring wrapped around.
+    indexes.sort_by_key(|&index| (last.is_some_and(|last| index <= last), index));
+    let mut results = Vec::with_capacity(indexes.len());
+    for index in indexes {
+        match read_capsule_result(runtime_services, index) {
+            Ok(result) => results.push((index, result)),
+            Err(status) if status == efi::Status::INVALID_PARAMETER => continue,
+            Err(status) => return Err(status),
+        }
+    }
+    Ok(results)
+}
+
+/// Records the result of a processed capsule in the next variable of the ring, and updates `CapsuleLast`, as the
+/// firmware does
+///
+/// The ring wraps around after `CapsuleMax`, or after `CapsuleFFFF` if it is not set. Returns the index of the
+/// variable.
+pub fn record_capsule_result<R: RuntimeServices>(
+    runtime_services: &R,
+    result: &CapsuleResult,
+) -> Result<u16, EfiError> {
+    let max = capsule_max(runtime_services)?.unwrap_or(u16::MAX);
+    let index = match capsule_last(runtime_services)? {
+        Some(last) if last < max => last + 1,
+        _ => 0,
+    };
+    runtime_services.set_variable(
+        &capsule_variable_name(index),
+        &CAPSULE_REPORT_GUID,
+        CAPSULE_RESULT_ATTRIBUTES,
+        &result.to_bytes(),
+    )?;
+    write_index(runtime_services, &CAPSULE_LAST_NAME, index)?;
+    Ok(index)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::InMemoryRuntimeServices;
+
+    fn fmp_result(payload_index: u8, status: efi::Status) -> CapsuleResult {
+        CapsuleResult {
+            capsule_guid: FMP_CAPSULE_GUID,
+            processed: efi::Time { year: 2024, month: 3, day: 14, hour: 9, ..Default::default() },
+            status,
+            payload: CapsuleResultPayload::Fmp(FmpCapsuleResult {
+                payload_index,
+                update_image_index: 1,
+                update_image_type_id: efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]),
+                capsule_file_name: String::from("\\EFI\\UpdateCapsule\\firmware.cap"),
+                capsule_target: String::new(),
+            }),
+        }
+    }
+
+    /// Sets the total size of a result to its length, plus `extra` bytes.
+    fn set_total_size(data: &mut [u8], extra: usize) {
+        let size = (data.len() + extra) as u32;
+        data[..4].copy_from_slice(&size.to_le_bytes());
+    }
+
+    #[test]
+    fn test_variable_names() {
+        assert_eq!(ucs2_name(b"Capsule00AF\0"), capsule_variable_name(0xAF));
+        assert_eq!(Some(0xAF), parse_capsule_variable_name(&ucs2_name(b"Capsule00af")));
+        assert_eq!(Some(0xFFFF), parse_capsule_variable_name(&capsule_variable_name(0xFFFF)));
+        assert_eq!(None, parse_capsule_variable_name(&ucs2_name(b"CapsuleMax\0")));
+        assert_eq!(None, parse_capsule_variable_name(&ucs2_name(b"Capsule00AG\0")));
+    }
+
+    #[test]
+    fn test_parse_result() {
+        let result = fmp_result(2, efi::Status::ABORTED);
+        let data = result.to_bytes();
+        assert_eq!(HEADER_SIZE + FMP_HEADER_SIZE + 2 * (31 + 1) + 2, data.len());
+        let parsed = CapsuleResult::parse(&data).unwrap();
+        assert_eq!((FMP_CAPSULE_GUID, efi::Status::ABORTED), (parsed.capsule_guid, parsed.status));
+        assert_eq!(
+            (2024, 3, 14, 9),
+            (parsed.processed.year, parsed.processed.month, parsed.processed.day, parsed.processed.hour)
+        );
+        assert_eq!(result.payload, parsed.payload);
+
+        // Older firmware records no names, other capsules have no data.
+        let mut data = data[..HEADER_SIZE + FMP_HEADER_SIZE].to_vec();
+        set_total_size(&mut data, 0);
+        let CapsuleResultPayload::Fmp(fmp) = CapsuleResult::parse(&data).unwrap().payload else {
+            panic!("not an FMP result");
+        };
+        assert!(fmp.capsule_file_name.is_empty() && fmp.capsule_target.is_empty());
+        let other =
+            CapsuleResult { capsule_guid: CAPSULE_REPORT_GUID, payload: CapsuleResultPayload::Other(vec![]), ..result };
+        assert_eq!(other.payload, CapsuleResult::parse(&other.to_bytes()).unwrap().payload);
+
+        data.extend_from_slice(&[b'x', 0]);
+        set_total_size(&mut data, 0);
+        assert_eq!(efi::Status::INVALID_PARAMETER, CapsuleResult::parse(&data).unwrap_err());
+        set_total_size(&mut data, 1);
+        assert_eq!(efi::Status::INVALID_PARAMETER, CapsuleResult::parse(&data).unwrap_err());
+        assert_eq!(efi::Status::INVALID_PARAMETER, CapsuleResult::parse(&data[..HEADER_SIZE - 1]).unwrap_err());
+    }
+
+    #[test]
+    fn test_record_and_enumerate() {
+        let rs = InMemoryRuntimeServices::new();
+        assert_eq!((None, None), (capsule_max(&rs).unwrap(), capsule_last(&rs).unwrap()));
+        assert!(capsule_results(&rs).unwrap().is_empty());
+
+        set_capsule_max(&rs, 2).unwrap();
+        let (data, attributes) = rs.get_variable::<Vec<u8>>(&CAPSULE_MAX_NAME, &CAPSULE_REPORT_GUID, None).unwrap();
+        assert_eq!((22, CAPSULE_RESULT_ATTRIBUTES), (data.len(), attributes));
+        assert_eq!(Some(2), capsule_max(&rs).unwrap());
+
+        for payload_index in 0..4 {
+            let index = record_capsule_result(&rs, &fmp_result(payload_index, efi::Status::SUCCESS)).unwrap();
+            assert_eq!(payload_index as u16 % 3, index);
+        }
+        assert_eq!(Some(0), capsule_last(&rs).unwrap());
+        rs.set_variable(&capsule_variable_name(1), &CAPSULE_REPORT_GUID, CAPSULE_RESULT_ATTRIBUTES, &vec![0u8; 4])
+            .unwrap();
+
+        // The ring wrapped around, `Capsule0000` is the latest result and the corrupt `Capsule0001` is skipped.
+        let results = capsule_results(&rs).unwrap();
+        let payload_indexes = results
+            .iter()
+            .map(|(index, result)| match &result.payload {
+                CapsuleResultPayload::Fmp(fmp) => (*index, fmp.payload_index),
+                CapsuleResultPayload::Other(_) => panic!("not an FMP result"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(2, 2), (0, 3)], payload_indexes);
+        assert_eq!(efi::Status::INVALID_PARAMETER, read_capsule_result(&rs, 1).unwrap_err());
+        assert_eq!(efi::Status::NOT_FOUND, read_capsule_result(&rs, 3).unwrap_err());
+    }
+}
@@ -0,0 +1,473 @@
+//! Conformance suite of the variable services.
+//!
+//! Every function takes any [`RuntimeServices`] and checks one part of the semantics firmware follows, over names and
+//! data generated from a fixed seed, so the same cases run on every implementation. They panic with the failed case,
+//! like an assertion, and are meant to be called from tests:
+//!
+//! ```ignore
+//! #[test]
+//! fn test_variable_services_conformance() {
+//!     runtime_services::conformance::run_all(&MyRuntimeServices::new());
+//! }
+//! ```
+//!
+//! The variables are created in [`NAMESPACE`] and [`OTHER_NAMESPACE`], which must not hold variables when a function
+//! starts, and are deleted before it returns. Other variables of the store are left untouched.
+//!
+//! [UEFI Spec Documentation: 8.2. Variable Services](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#variable-services)
+
+use alloc::{format, vec, vec::Vec};
+
+use r_efi::efi;
+use status::EfiError;
+
+use crate::{variable_services::GetVariableStatus, RuntimeServices};
+
+/// Namespace of the variables created by the suite.
+pub const NAMESPACE: efi::Guid =
+    efi::Guid::from_fields(0x5C8E_6C1B, 0x3F0A, 0x4E77, 0x9A, 0x61, &[0x2D, 0x4B, 0x8F, 0x03, 0xC7, 0x15]);
+/// Second namespace of the suite, for the same names in two namespaces.
+pub const OTHER_NAMESPACE: efi::Guid =
+    efi::Guid::from_fields(0x5C8E_6C1B, 0x3F0A, 0x4E77, 0x9A, 0x61, &[0x2D, 0x4B, 0x8F, 0x03, 0xC7, 0x16]);
+
+/// Number of generated cases of each property.
+const CASES: usize = 32;
+/// Largest generated data, small enough for any variable store.
+const MAX_DATA_SIZE: usize = 64;
+
+const BS: u32 = efi::VARIABLE_BOOTSERVICE_ACCESS;
+const ATTRIBUTES: [u32; 4] = [
+    BS,
+    BS | efi::VARIABLE_RUNTIME_ACCESS,
+    efi::VARIABLE_NON_VOLATILE | BS,
+    efi::VARIABLE_NON_VOLATILE | BS | efi::VARIABLE_RUNTIME_ACCESS,
+];
+
+/// Xorshift generator of the cases, deterministic across platforms.
+struct Cases(u64);
+
+impl Cases {
+    fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// A null-terminated name of 1 to 16 characters, letters of both cases, digits and characters outside ASCII.
+    fn name(&mut self) -> Vec<u16> {
+        const CHARACTERS: &[u16] = &[
+            b'A' as u16,
+            b'b' as u16,
+            b'C' as u16,
+            b'd' as u16,
+            b'0' as u16,
+            b'9' as u16,
+            b'_' as u16,
+            b'-' as u16,
+            0x00E9,
+            0x03A9,
+            0x4E2D,
+            0xFFFD,
+        ];
+        let len = 1 + self.below(16);
+        (0..len).map(|_| CHARACTERS[self.below(CHARACTERS.len())]).chain([0]).collect()
+    }
+
+    /// 1 to [`MAX_DATA_SIZE`] bytes.
+    fn data(&mut self) -> Vec<u8> {
+        let len = 1 + self.below(MAX_DATA_SIZE);
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+
+    fn attributes(&mut self) -> u32 {
+        ATTRIBUTES[self.below(ATTRIBUTES.len())]
+    }
+}
+
+/// Runs every function of the suite.
+///
+/// # Panics
+///
+/// Panics at the first case the runtime services fail.
+pub fn run_all<R: RuntimeServices>(runtime_services: &R) {
+    attribute_round_trip(runtime_services);
+    attribute_change(runtime_services);
+    append_write(runtime_services);
+    delete(runtime_services);
+    buffer_too_small(runtime_services);
+    invalid_names(runtime_services);
+    name_enumeration(runtime_services);
+}
+
+/// Data and attributes read back are the ones written, and overwriting replaces the data.
+///
+/// # Panics
+///
+/// Panics at the first case the runtime services fail.
+pub fn attribute_round_trip<R: RuntimeServices>(runtime_services: &R) {
+    let mut cases = Cases::new(1);
+    for _ in 0..CASES {
+        let (name, attributes, data) = (cases.name(), cases.attributes(), cases.data());
+        let case = format!("{:?} with attributes {attributes:#x}", &name);
+        assert_eq!(Ok(()), runtime_services.set_variable(&name, &NAMESPACE, attributes, &data), "Set {case}.");
+        assert_eq!(
+            Ok((data.clone(), attributes)),
+            runtime_services.get_variable::<Vec<u8>>(&name, &NAMESPACE, None),
+            "Get {case}."
+        );
+        assert_eq!(
+            Ok((data.len(), attributes)),
+            runtime_services.get_variable_size_and_attributes(&name, &NAMESPACE),
+            "Size of {case}."
+        );
+
+        let data = cases.data();
+        assert_eq!(Ok(()), runtime_services.set_variable(&name, &NAMESPACE, attributes, &data), "Overwrite {case}.");
+        assert_eq!(
+            Ok((data, attributes)),
+            runtime_services.get_variable::<Vec<u8>>(&name, &NAMESPACE, None),
+            "Get overwritten {case}."
+        );
+        remove(runtime_services, &name, &NAMESPACE);
+    }
+}
+
+/// An existing variable is not rewritten with other attributes, and runtime access requires boot services access.
+///
+/// # Panics
+///
+/// Panics at the first case the runtime services fail.
+pub fn attribute_change<R: RuntimeServices>(runtime_services: &R) {
+    let mut cases = Cases::new(2);
+    for _ in 0..CASES {
+        let (name, attributes, data) = (cases.name(), cases.attributes(), cases.data());
+        let case = format!("{:?} with attributes {attributes:#x}", &name);
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            runtime_services.set_variable(&name, &NAMESPACE, efi::VARIABLE_RUNTIME_ACCESS, &data),
+            "Set {:?} with runtime access only.",
+            &name
+        );
+        assert_eq!(Ok(()), runtime_services.set_variable(&name, &NAMESPACE, attributes, &data), "Set {case}.");
+
+        let other_attributes = ATTRIBUTES.iter().copied().find(|&other| other != attributes).unwrap_or(BS);
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            runtime_services.set_variable(&name, &NAMESPACE, other_attributes, &cases.data()),
+            "Rewrite {case} with attributes {other_attributes:#x}."
+        );
+        assert_eq!(
+            Ok((data, attributes)),
+            runtime_services.get_variable::<Vec<u8>>(&name, &NAMESPACE, None),
+            "Get {case} after the rejected rewrite."
+        );
+        remove(runtime_services, &name, &NAMESPACE);
+    }
+}
+
+/// `VARIABLE_APPEND_WRITE` adds the data after the existing one, appending nothing succeeds without changing the
+/// variable, and appending to a variable that does not exist creates it.
+///
+/// # Panics
+///
+/// Panics at the first case the runtime services fail.
+pub fn append_write<R: RuntimeServices>(runtime_services: &R) {
+    let mut cases = Cases::new(3);
+    for _ in 0..CASES {
+        let (name, attributes) = (cases.name(), cases.attributes());
+        let append = attributes | efi::VARIABLE_APPEND_WRITE;
+        let case = format!("{:?} with attributes {attributes:#x}", &name);
+
+        assert_eq!(
+            Ok(()),
+            runtime_services.set_variable(&name, &NAMESPACE, append, &Vec::<u8>::new()),
+            "Append nothing to missing {case}."
+        );
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            runtime_services.get_variable_size_and_attributes(&name, &NAMESPACE),
+            "Get {case} after appending nothing."
+        );
+
+        let mut expected = cases.data();
+        assert_eq!(Ok(()), runtime_services.set_variable(&name, &NAMESPACE, append, &expected), "Create {case}.");
+        for _ in 0..3 {
+            let data = cases.data();
+            assert_eq!(Ok(()), runtime_services.set_variable(&name, &NAMESPACE, append, &data), "Append to {case}.");
+            expected.extend_from_slice(&data);
+        }
+        assert_eq!(
+            Ok(()),
+            runtime_services.set_variable(&name, &NAMESPACE, append, &Vec::<u8>::new()),
+            "Append nothing to {case}."
+        );
+        assert_eq!(
+            Ok((expected, attributes)),
+            runtime_services.get_variable::<Vec<u8>>(&name, &NAMESPACE, None),
+            "Get appended {case}."
+        );
+        remove(runtime_services, &name, &NAMESPACE);
+    }
+}
+
+/// Writing no data or no attributes deletes a variable, and deleting a missing variable returns `NOT_FOUND`.
+///
+/// # Panics
+///
+/// Panics at the first case the runtime services fail.
+pub fn delete<R: RuntimeServices>(runtime_services: &R) {
+    let mut cases = Cases::new(4);
+    for index in 0..CASES {
+        let (name, attributes, data) = (cases.name(), cases.attributes(), cases.data());
+        let case = format!("{:?} with attributes {attributes:#x}", &name);
+        assert_eq!(Ok(()), runtime_services.set_variable(&name, &NAMESPACE, attributes, &data), "Set {case}.");
+        // Alternates between the two ways of deleting.
+        let (delete_attributes, delete_data) = match index % 2 {
+            0 => (attributes, Vec::new()),
+            _ => (0, data),
+        };
+        assert_eq!(
+            Ok(()),
+            runtime_services.set_variable(&name, &NAMESPACE, delete_attributes, &delete_data),
+            "Delete {case} with attributes {delete_attributes:#x} and {} bytes.",
+            delete_data.len()
+        );
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            runtime_services.get_variable_size_and_attributes(&name, &NAMESPACE),
+            "Get deleted {case}."
+        );
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            runtime_services.set_variable(&name, &NAMESPACE, attributes, &Vec::<u8>::new()),
+            "Delete missing {case}."
+        );
+    }
+}
+
+/// Reading with a buffer smaller than the data returns its size and attributes and leaves the buffer untouched.
+///
+/// # Panics
+///
+/// Panics at the first case the runtime services fail.
+pub fn buffer_too_small<R: RuntimeServices>(runtime_services: &R) {
+    let mut cases = Cases::new(5);
+    for _ in 0..CASES {
+        let (mut name, attributes, data) = (cases.name(), cases.attributes(), cases.data());
+        let case = format!("{:?} with attributes {attributes:#x}", &name);
+        assert_eq!(Ok(()), runtime_services.set_variable(&name, &NAMESPACE, attributes, &data), "Set {case}.");
+
+        let mut buffer = vec![0xA5; cases.below(data.len())];
+        let buffer_len = buffer.len();
+        //SAFETY: The name is null-terminated and the buffer is as long as its slice.
+        let status = unsafe {
+            runtime_services.get_variable_unchecked(
+                &mut name,
+                &NAMESPACE,
+                Some(buffer.as_mut_slice()).filter(|b| !b.is_empty()),
+            )
+        };
+        match status {
+            GetVariableStatus::BufferTooSmall { data_size, attributes: found } => {
+                assert_eq!(
+                    (data.len(), attributes),
+                    (data_size, found),
+                    "Size of {case} in a {buffer_len} byte buffer."
+                );
+            }
+            status => panic!("Get {case} in a {buffer_len} byte buffer returned {status:?}."),
+        }
+        assert!(buffer.iter().all(|&b| b == 0xA5), "Get {case} wrote in a {buffer_len} byte buffer.");
+        remove(runtime_services, &name, &NAMESPACE);
+    }
+}
+
+/// Empty names are rejected, and names differing only in case or namespace are different variables.
+///
+/// # Panics
+///
+/// Panics at the first case the runtime services fail.
+pub fn invalid_names<R: RuntimeServices>(runtime_services: &R) {
+    let empty = vec![0_u16];
+    assert_eq!(
+        Err(efi::Status::INVALID_PARAMETER.into()),
+        runtime_services.set_variable(&empty, &NAMESPACE, BS, &vec![1_u8]),
+        "Set an empty name."
+    );
+    assert_eq!(
+        Err(efi::Status::INVALID_PARAMETER.into()),
+        runtime_services.get_variable_size_and_attributes(&empty, &NAMESPACE),
+        "Get an empty name."
+    );
+
+    let mut cases = Cases::new(6);
+    for _ in 0..CASES {
+        let (name, data) = (cases.name(), cases.data());
+        let other_case = name
+            .iter()
+            .map(|&c| match char::from_u32(c as u32) {
+                Some(c) if c.is_ascii_lowercase() => c.to_ascii_uppercase() as u16,
+                Some(c) if c.is_ascii_uppercase() => c.to_ascii_lowercase() as u16,
+                _ => c,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(Ok(()), runtime_services.set_variable(&name, &NAMESPACE, BS, &data), "Set {:?}.", &name);
+        if other_case != name {
+            assert_eq!(
+                Err(efi::Status::NOT_FOUND.into()),
+                runtime_services.get_variable_size_and_attributes(&other_case, &NAMESPACE),
+                "Get {:?} after setting {:?}, names are case sensitive.",
+                &other_case,
+                &name
+            );
+        }
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            runtime_services.get_variable_size_and_attributes(&name, &OTHER_NAMESPACE),
+            "Get {:?} in another namespace.",
+            &name
+        );
+        remove(runtime_services, &name, &NAMESPACE);
+    }
+}
+
+/// Enumerating from an empty name returns every variable once, in the same order every time, and can continue from
+/// any variable. Enumerating from a variable that does not exist returns `INVALID_PARAMETER`.
+///
+/// # Panics
+///
+/// Panics at the first case the runtime services fail.
+pub fn name_enumeration<R: RuntimeServices>(runtime_services: &R) {
+    let mut cases = Cases::new(7);
+    let mut created = Vec::new();
+    for _ in 0..CASES {
+        let name = cases.name();
+        if created.iter().any(|(created, _)| *created == name) {
+            continue;
+        }
+        // Some names are also in the other namespace, and some are prefixes of others.
+        let prefix = name[..name.len() / 2].iter().copied().chain([0]).collect::<Vec<_>>();
+        for (name, namespace) in [(name.clone(), NAMESPACE), (name, OTHER_NAMESPACE), (prefix, NAMESPACE)] {
+            if name.len() > 1 && !created.contains(&(name.clone(), namespace)) && cases.below(3) != 0 {
+                assert_eq!(
+                    Ok(()),
+                    runtime_services.set_variable(&name, &namespace, BS, &cases.data()),
+                    "Set {:?} in {:?}.",
+                    &name,
+                    namespace
+                );
+                created.push((name, namespace));
+            }
+        }
+    }
+
+    let enumerated = enumerate(runtime_services, vec![0], &NAMESPACE);
+    for variable in created.iter() {
+        let count = enumerated.iter().filter(|&enumerated| enumerated == variable).count();
+        assert_eq!(1, count, "Enumeration returned {:?} in {:?} {count} times.", &variable.0, variable.1);
+    }
+    assert_eq!(created.len(), enumerated.len(), "Enumeration returned variables that were not set.");
+    assert_eq!(enumerated, enumerate(runtime_services, vec![0], &NAMESPACE), "Enumeration order changed.");
+    for (index, (name, namespace)) in enumerated.iter().enumerate() {
+        assert_eq!(
+            enumerated[index + 1..],
+            enumerate(runtime_services, name.clone(), namespace),
+            "Enumeration from {name:?} in {namespace:?}."
+        );
+    }
+
+    // No generated name has an exclamation mark.
+    let missing = cases.name().into_iter().filter(|&c| c != 0).chain([b'!' as u16, 0]).collect::<Vec<_>>();
+    assert_eq!(
+        Err(efi::Status::INVALID_PARAMETER.into()),
+        runtime_services.get_next_variable_name(&missing, &NAMESPACE),
+        "Enumerate from missing {:?}.",
+        &missing
+    );
+
+    for (name, namespace) in created {
+        remove(runtime_services, &name, &namespace);
+    }
+}
+
+/// The next variable, with its name cut after its null terminator.
+fn next_variable<R: RuntimeServices>(
+    runtime_services: &R,
+    name: Vec<u16>,
+    namespace: &efi::Guid,
+) -> Result<(Vec<u16>, efi::Guid), EfiError> {
+    let (mut next_name, next_namespace) = runtime_services.get_next_variable_name(&name, namespace)?;
+    let len = next_name.iter().position(|&c| c == 0).map_or(next_name.len(), |end| end + 1);
+    next_name.truncate(len);
+    Ok((next_name, next_namespace))
+}
+
+/// The variables of the suite's namespaces, enumerated after `name` in `namespace`.
+fn enumerate<R: RuntimeServices>(
+    runtime_services: &R,
+    mut name: Vec<u16>,
+    namespace: &efi::Guid,
+) -> Vec<(Vec<u16>, efi::Guid)> {
+    let mut namespace = *namespace;
+    let mut variables = Vec::new();
+    loop {
+        match next_variable(runtime_services, name.clone(), &namespace) {
+            Ok((next_name, next_namespace)) => {
+                if next_namespace == NAMESPACE || next_namespace == OTHER_NAMESPACE {
+                    variables.push((next_name.clone(), next_namespace));
+                }
+                (name, namespace) = (next_name, next_namespace);
+            }
+            Err(status) if status == efi::Status::NOT_FOUND => return variables,
+            Err(status) => {
+                panic!("Enumeration after {name:?} in {namespace:?} returned {status}.")
+            }
+        }
+    }
+}
+
+fn remove<R: RuntimeServices>(runtime_services: &R, name: &[u16], namespace: &efi::Guid) {
+    let (_, attributes) = runtime_services
+        .get_variable_size_and_attributes(name, namespace)
+        .unwrap_or_else(|status| panic!("Get {name:?} to delete it returned {status}."));
+    assert_eq!(
+        Ok(()),
+        runtime_services.set_variable(name, namespace, attributes, &Vec::<u8>::new()),
+        "Delete {name:?}."
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mock::InMemoryRuntimeServices,
+        recording::{RecordingRuntimeServices, RuntimeServicesCall},
+    };
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn test_in_memory_runtime_services() {
+        let runtime_services = InMemoryRuntimeServices::new();
+        run_all(&runtime_services);
+        assert!(runtime_services.is_empty());
+    }
+
+    #[test]
+    fn test_failing_runtime_services() {
+        let runtime_services = RecordingRuntimeServices::new(InMemoryRuntimeServices::new());
+        let is_append = |call: &RuntimeServicesCall| matches!(call, RuntimeServicesCall::SetVariable { attributes, .. } if attributes & efi::VARIABLE_APPEND_WRITE != 0);
+        runtime_services.inject_fault(is_append, 3, efi::Status::DEVICE_ERROR);
+        attribute_round_trip(&runtime_services);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| append_write(&runtime_services))).is_err());
+    }
+}
@@ -0,0 +1,522 @@
+//! In-memory runtime services, for unit tests of code using UEFI variables.
+//!
+//! The variables live in a store following the semantics of the variable services, so tests exercise the same
+//! paths as on real firmware without mocking every call:
+//!
+//! ```ignore
+//! let runtime_services = InMemoryRuntimeServices::new();
+//! runtime_services.set_variable(&name, &namespace, efi::VARIABLE_BOOTSERVICE_ACCESS, &data)?;
+//! assert_eq!((data, efi::VARIABLE_BOOTSERVICE_ACCESS), runtime_services.get_variable(&name, &namespace, None)?);
+//! ```
+//!
+//! Tests can also start from a captured machine state, see [`VariableStoreSnapshot`]. With the `serde` feature, it
+//! is loaded from a fixture in any serde format, the data being stored as hexadecimal strings:
+//!
+//! ```ignore
+//! let snapshot: VariableStoreSnapshot = serde_json::from_str(include_str!("fixtures/variables.json"))?;
+//! let runtime_services = InMemoryRuntimeServices::from_snapshot(&snapshot)?;
+//! ```
+//!
+//! The time services are not emulated and return `UNSUPPORTED`.
+//!
+//! [UEFI Spec Documentation: 8.2. Variable Services](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#variable-services)
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+    mem,
+};
+
+use guid::Guid;
+use r_efi::efi::{self, Time, TimeCapabilities};
+use status::EfiError;
+
+use crate::{
+    variable_services::{GetVariableStatus, VariableInfo},
+    RuntimeServices,
+};
+
+/// Attributes a variable is created with, the others only affect a single call.
+const PERSISTENT_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE
+    | efi::VARIABLE_BOOTSERVICE_ACCESS
+    | efi::VARIABLE_RUNTIME_ACCESS
+    | efi::VARIABLE_HARDWARE_ERROR_RECORD
+    | efi::VARIABLE_AUTHENTICATED_WRITE_ACCESS
+    | efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS
+    | efi::VARIABLE_ENHANCED_AUTHENTICATED_ACCESS;
+
+#[derive(Debug)]
+struct Variable {
+    /// Name without its null terminator.
+    name: Vec<u16>,
+    namespace: efi::Guid,
+    attributes: u32,
+    data: Vec<u8>,
+}
+
+impl Variable {
+    /// Storage used by the variable, its null-terminated name and its data.
+    fn size(&self) -> usize {
+        (self.name.len() + 1) * mem::size_of::<u16>() + self.data.len()
+    }
+}
+
+/// A capture of a variable store, the variables in enumeration order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariableStoreSnapshot {
+    pub maximum_variable_storage_size: usize,
+    pub maximum_variable_size: usize,
+    pub variables: Vec<VariableSnapshot>,
+}
+
+/// A variable of a [`VariableStoreSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariableSnapshot {
+    /// Name without its null terminator.
+    pub name: String,
+    pub namespace: Guid,
+    pub attributes: u32,
+    #[cfg_attr(feature = "serde", serde(with = "hex"))]
+    pub data: Vec<u8>,
+}
+
+/// Serde functions for byte buffers, represented as hexadecimal strings like in most variable dumps.
+#[cfg(feature = "serde")]
+mod hex {
+    use alloc::{string::String, vec::Vec};
+    use core::fmt::Write;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::with_capacity(data.len() * 2);
+        for byte in data {
+            let _ = write!(hex, "{byte:02X}");
+        }
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(D::Error::custom("expected an even number of hexadecimal digits"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// Runtime services backed by an in-memory variable store.
+///
+/// Variables are enumerated in creation order, like the log-structured stores of most firmware.
+#[derive(Debug)]
+pub struct InMemoryRuntimeServices {
+    variables: RefCell<Vec<Variable>>,
+    maximum_variable_storage_size: usize,
+    maximum_variable_size: usize,
+    high_monotonic_count: Cell<u32>,
+}
+
+impl InMemoryRuntimeServices {
+    /// Default size of the store, for the volatile and the non-volatile variables each.
+    pub const DEFAULT_STORAGE_SIZE: usize = 0x10000;
+    /// Default maximum size of a variable, name included.
+    pub const DEFAULT_VARIABLE_SIZE: usize = 0x1000;
+
+    /// Creates an empty store of the default sizes.
+    pub fn new() -> Self {
+        Self::with_limits(Self::DEFAULT_STORAGE_SIZE, Self::DEFAULT_VARIABLE_SIZE)
+    }
+
+    /// Creates an empty store, writes beyond its sizes fail with `OUT_OF_RESOURCES`.
+    pub fn with_limits(maximum_variable_storage_size: usize, maximum_variable_size: usize) -> Self {
+        Self {
+            variables: RefCell::new(Vec::new()),
+            maximum_variable_storage_size,
+            maximum_variable_size,
+            high_monotonic_count: Cell::new(0),
+        }
+    }
+
+    /// Creates a store from a snapshot, the variables are written like with `SetVariable()` so they follow the same
+    /// rules.
+    pub fn from_snapshot(snapshot: &VariableStoreSnapshot) -> Result<Self, EfiError> {
+        let runtime_services =
+            Self::with_limits(snapshot.maximum_variable_storage_size, snapshot.maximum_variable_size);
+        for variable in snapshot.variables.iter() {
+            let mut name = variable.name.encode_utf16().chain([0]).collect::<Vec<_>>();
+            if variable.data.is_empty() || variable.attributes & !PERSISTENT_ATTRIBUTES != 0 {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            //SAFETY: The name is null-terminated.
+            unsafe {
+                runtime_services.set_variable_unchecked(
+                    &mut name,
+                    variable.namespace.as_ref(),
+                    variable.attributes,
+                    &variable.data,
+                )
+            }?;
+        }
+        if runtime_services.len() != snapshot.variables.len() {
+            // The same variable was in the snapshot more than once.
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        Ok(runtime_services)
+    }
+
+    /// Captures the variables of the store.
+    pub fn snapshot(&self) -> VariableStoreSnapshot {
+        VariableStoreSnapshot {
+            maximum_variable_storage_size: self.maximum_variable_storage_size,
+            maximum_variable_size: self.maximum_variable_size,
+            variables: self
+                .variables
+                .borrow()
+                .iter()
+                .map(|variable| VariableSnapshot {
+                    name: String::from_utf16_lossy(&variable.name),
+                    namespace: variable.namespace.into(),
+                    attributes: variable.attributes,
+                    data: variable.data.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The number of variables in the store.
+    pub fn len(&self) -> usize {
+        self.variables.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Storage used by the variables that are volatile or not, like `attributes`.
+    fn used_storage_size(&self, attributes: u32) -> usize {
+        let non_volatile = attributes & efi::VARIABLE_NON_VOLATILE;
+        self.variables
+            .borrow()
+            .iter()
+            .filter(|variable| variable.attributes & efi::VARIABLE_NON_VOLATILE == non_volatile)
+            .map(Variable::size)
+            .sum()
+    }
+
+    fn position(variables: &[Variable], name: &[u16], namespace: &efi::Guid) -> Option<usize> {
+        variables.iter().position(|variable| variable.name == name && variable.namespace == *namespace)
+    }
+}
+
+impl Default for InMemoryRuntimeServices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The name up to its null terminator, `None` if there is none.
+fn trim_name(name: &[u16]) -> Option<&[u16]> {
+    name.iter().position(|&c| c == 0).map(|end| &name[..end])
+}
+
+impl RuntimeServices for InMemoryRuntimeServices {
+    unsafe fn set_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> Result<(), EfiError> {
+        let name = trim_name(name).filter(|name| !name.is_empty()).ok_or(efi::Status::INVALID_PARAMETER)?;
+        if attributes & efi::VARIABLE_RUNTIME_ACCESS != 0 && attributes & efi::VARIABLE_BOOTSERVICE_ACCESS == 0 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let append = attributes & efi::VARIABLE_APPEND_WRITE != 0;
+        let attributes = attributes & PERSISTENT_ATTRIBUTES;
+
+        let mut variables = self.variables.borrow_mut();
+        let Some(index) = Self::position(&variables, name, namespace) else {
+            if attributes == 0 || data.is_empty() {
+                return if append && attributes != 0 { Ok(()) } else { Err(efi::Status::NOT_FOUND.into()) };
+            }
+            let variable = Variable { name: name.to_vec(), namespace: *namespace, attributes, data: data.to_vec() };
+            if variable.size() > self.maximum_variable_size {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            drop(variables);
+            if self.used_storage_size(attributes) + variable.size() > self.maximum_variable_storage_size {
+                return Err(efi::Status::OUT_OF_RESOURCES.into());
+            }
+            self.variables.borrow_mut().push(variable);
+            return Ok(());
+        };
+
+        if attributes == 0 || (data.is_empty() && !append) {
+            variables.remove(index);
+            return Ok(());
+        }
+        if variables[index].attributes != attributes {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let size = (name.len() + 1) * mem::size_of::<u16>()
+            + data.len()
+            + if append { variables[index].data.len() } else { 0 };
+        if size > self.maximum_variable_size {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let old_size = variables[index].size();
+        drop(variables);
+        if self.used_storage_size(attributes) - old_size + size > self.maximum_variable_storage_size {
+            return Err(efi::Status::OUT_OF_RESOURCES.into());
+        }
+        let variable = &mut self.variables.borrow_mut()[index];
+        if !append {
+            variable.data.clear();
+        }
+        variable.data.extend_from_slice(data);
+        Ok(())
+    }
+
+    unsafe fn get_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        data: Option<&mut [u8]>,
+    ) -> GetVariableStatus {
+        let Some(name) = trim_name(name).filter(|name| !name.is_empty()) else {
+            return GetVariableStatus::Error(efi::Status::INVALID_PARAMETER);
+        };
+        let variables = self.variables.borrow();
+        let Some(variable) = Self::position(&variables, name, namespace).map(|index| &variables[index]) else {
+            return GetVariableStatus::Error(efi::Status::NOT_FOUND);
+        };
+        let data_size = variable.data.len();
+        match data {
+            Some(data) if data.len() >= data_size => {
+                data[..data_size].copy_from_slice(&variable.data);
+                GetVariableStatus::Success { data_size, attributes: variable.attributes }
+            }
+            _ => GetVariableStatus::BufferTooSmall { data_size, attributes: variable.attributes },
+        }
+    }
+
+    unsafe fn get_next_variable_name_unchecked(
+        &self,
+        prev_name: &[u16],
+        prev_namespace: &efi::Guid,
+        next_name: &mut Vec<u16>,
+        next_namespace: &mut efi::Guid,
+    ) -> Result<(), EfiError> {
+        let prev_name = trim_name(prev_name).ok_or(efi::Status::INVALID_PARAMETER)?;
+        let variables = self.variables.borrow();
+        let next = if prev_name.is_empty() {
+            0
+        } else {
+            // Enumeration only continues from an existing variable.
+            Self::position(&variables, prev_name, prev_namespace).ok_or(efi::Status::INVALID_PARAMETER)? + 1
+        };
+        let variable = variables.get(next).ok_or(efi::Status::NOT_FOUND)?;
+        next_name.clear();
+        next_name.extend_from_slice(&variable.name);
+        next_name.push(0);
+        *next_namespace = variable.namespace;
+        Ok(())
+    }
+
+    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, EfiError> {
+        if attributes & (efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS) == 0 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        Ok(VariableInfo {
+            maximum_variable_storage_size: self.maximum_variable_storage_size as u64,
+            remaining_variable_storage_size: (self.maximum_variable_storage_size - self.used_storage_size(attributes))
+                as u64,
+            maximum_variable_size: self.maximum_variable_size as u64,
+        })
+    }
+
+    fn get_next_high_monotonic_count(&self) -> Result<u32, EfiError> {
+        let count = self.high_monotonic_count.get().checked_add(1).ok_or(efi::Status::DEVICE_ERROR)?;
+        self.high_monotonic_count.set(count);
+        Ok(count)
+    }
+
+    /// The in-memory services run identity mapped, the pointers are only checked.
+    unsafe fn convert_pointer(&self, debug_disposition: usize, address: *mut *mut c_void) -> Result<(), EfiError> {
+        match address.as_ref() {
+            Some(pointer) if !pointer.is_null() || debug_disposition & efi::OPTIONAL_POINTER as usize != 0 => Ok(()),
+            _ => Err(efi::Status::INVALID_PARAMETER.into()),
+        }
+    }
+
+    unsafe fn get_wakeup_time_unchecked(&self) -> Result<(bool, bool, Time), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn set_time_unchecked(&self, _time: &efi::Time) -> Result<(), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn get_time_unchecked(&self) -> Result<(Time, TimeCapabilities), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+
+    unsafe fn set_wakeup_time_unchecked(&self, _enable: bool, _time: &efi::Time) -> Result<(), EfiError> {
+        Err(efi::Status::UNSUPPORTED.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::variable_services::VariableNameIterator;
+    use fallible_streaming_iterator::FallibleStreamingIterator;
+
+    const NAMESPACE: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+    const OTHER_NAMESPACE: efi::Guid = efi::Guid::from_fields(2, 0, 0, 0, 0, &[0; 6]);
+    const BS: u32 = efi::VARIABLE_BOOTSERVICE_ACCESS;
+    const NV_BS: u32 = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS;
+
+    fn name(name: &str) -> Vec<u16> {
+        name.encode_utf16().chain([0]).collect()
+    }
+
+    #[test]
+    fn test_get_set_variable() {
+        let rs = InMemoryRuntimeServices::new();
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), rs.get_variable::<Vec<u8>>(&name("Var"), &NAMESPACE, None));
+
+        rs.set_variable(&name("Var"), &NAMESPACE, NV_BS, &vec![1_u8, 2, 3]).unwrap();
+        assert_eq!(Ok((vec![1, 2, 3], NV_BS)), rs.get_variable::<Vec<u8>>(&name("Var"), &NAMESPACE, None));
+        assert_eq!(Ok((3, NV_BS)), rs.get_variable_size_and_attributes(&name("Var"), &NAMESPACE));
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            rs.get_variable::<Vec<u8>>(&name("Var"), &OTHER_NAMESPACE, None)
+        );
+
+        let mut buffer = [0_u8; 2];
+        let mut var = name("Var");
+        assert!(matches!(
+            unsafe { rs.get_variable_unchecked(&mut var, &NAMESPACE, Some(&mut buffer)) },
+            GetVariableStatus::BufferTooSmall { data_size: 3, attributes: NV_BS }
+        ));
+
+        // The attributes of an existing variable cannot change, unless it is deleted.
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            rs.set_variable(&name("Var"), &NAMESPACE, BS, &vec![4_u8])
+        );
+        rs.set_variable(&name("Var"), &NAMESPACE, NV_BS | efi::VARIABLE_APPEND_WRITE, &vec![4_u8]).unwrap();
+        assert_eq!(Ok((vec![1, 2, 3, 4], NV_BS)), rs.get_variable::<Vec<u8>>(&name("Var"), &NAMESPACE, None));
+        rs.set_variable(&name("Var"), &NAMESPACE, NV_BS, &vec![5_u8]).unwrap();
+        assert_eq!(Ok((vec![5], NV_BS)), rs.get_variable::<Vec<u8>>(&name("Var"), &NAMESPACE, None));
+
+        rs.set_variable(&name("Var"), &NAMESPACE, NV_BS, &Vec::<u8>::new()).unwrap();
+        assert!(rs.is_empty());
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), rs.set_variable(&name("Var"), &NAMESPACE, 0, &vec![1_u8]));
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            rs.set_variable(&name("Var"), &NAMESPACE, efi::VARIABLE_RUNTIME_ACCESS, &vec![1_u8])
+        );
+    }
+
+    #[test]
+    fn test_get_next_variable_name() {
+        let rs = InMemoryRuntimeServices::new();
+        for (variable, namespace) in [("B", NAMESPACE), ("A", OTHER_NAMESPACE), ("Long Name", NAMESPACE)] {
+            rs.set_variable(&name(variable), &namespace, BS, &vec![0_u8]).unwrap();
+        }
+
+        let mut names = Vec::new();
+        let mut iter = VariableNameIterator::new_from_first(&rs);
+        while let Some(variable) = iter.next().unwrap() {
+            names.push(format!("{variable:?}"));
+        }
+        assert_eq!(3, names.len());
+
+        assert_eq!(Ok((name("A"), OTHER_NAMESPACE)), rs.get_next_variable_name(&name("B"), &NAMESPACE));
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), rs.get_next_variable_name(&name("Long Name"), &NAMESPACE));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), rs.get_next_variable_name(&name("A"), &NAMESPACE));
+    }
+
+    #[test]
+    fn test_query_variable_info() {
+        let rs = InMemoryRuntimeServices::with_limits(32, 16);
+        rs.set_variable(&name("Var"), &NAMESPACE, NV_BS, &vec![0_u8; 8]).unwrap();
+        let info = rs.query_variable_info(NV_BS).unwrap();
+        assert_eq!(32, info.maximum_variable_storage_size);
+        assert_eq!(16, info.remaining_variable_storage_size);
+        assert_eq!(16, info.maximum_variable_size);
+        assert_eq!(32, rs.query_variable_info(BS).unwrap().remaining_variable_storage_size);
+
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            rs.set_variable(&name("Big"), &NAMESPACE, NV_BS, &vec![0_u8; 9])
+        );
+        rs.set_variable(&name("Var2"), &NAMESPACE, NV_BS, &vec![0_u8; 6]).unwrap();
+        assert_eq!(
+            Err(efi::Status::OUT_OF_RESOURCES.into()),
+            rs.set_variable(&name("V"), &NAMESPACE, NV_BS, &vec![0_u8])
+        );
+        assert_eq!(efi::Status::INVALID_PARAMETER, rs.query_variable_info(efi::VARIABLE_NON_VOLATILE).unwrap_err());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let rs = InMemoryRuntimeServices::with_limits(0x100, 0x40);
+        rs.set_variable(&name("Boot0000"), &NAMESPACE, NV_BS, &vec![1_u8, 0xAB]).unwrap();
+        rs.set_variable(&name("Volatile"), &OTHER_NAMESPACE, BS, &vec![2_u8]).unwrap();
+
+        let snapshot = rs.snapshot();
+        assert_eq!("Boot0000", snapshot.variables[0].name);
+        let restored = InMemoryRuntimeServices::from_snapshot(&snapshot).unwrap();
+        assert_eq!(snapshot, restored.snapshot());
+        assert_eq!(Ok((vec![2], BS)), restored.get_variable::<Vec<u8>>(&name("Volatile"), &OTHER_NAMESPACE, None));
+
+        let mut duplicated = snapshot.clone();
+        duplicated.variables.push(snapshot.variables[0].clone());
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            InMemoryRuntimeServices::from_snapshot(&duplicated).map(|_| ())
+        );
+        let mut full = snapshot.clone();
+        full.maximum_variable_storage_size = 0x10;
+        assert_eq!(
+            Err(efi::Status::OUT_OF_RESOURCES.into()),
+            InMemoryRuntimeServices::from_snapshot(&full).map(|_| ())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_snapshot_serde() {
+        let json = r#"{
+            "maximum_variable_storage_size": 65536,
+            "maximum_variable_size": 4096,
+            "variables": [
+                {
+                    "name": "BootOrder",
+                    "namespace": "8BE4DF61-93CA-11D2-AA0D-00E098032B8C",
+                    "attributes": 7,
+                    "data": "01000000"
+                }
+            ]
+        }"#;
+        let snapshot: VariableStoreSnapshot = serde_json::from_str(json).unwrap();
+        let rs = InMemoryRuntimeServices::from_snapshot(&snapshot).unwrap();
+        let global =
+            efi::Guid::from_fields(0x8BE4DF61, 0x93CA, 0x11D2, 0xAA, 0x0D, &[0x00, 0xE0, 0x98, 0x03, 0x2B, 0x8C]);
+        assert_eq!(Ok((vec![1, 0, 0, 0], 7)), rs.get_variable::<Vec<u8>>(&name("BootOrder"), &global, None));
+
+        let json = serde_json::to_string(&rs.snapshot()).unwrap();
+        assert!(json.contains(r#""data":"01000000""#));
+        assert_eq!(snapshot, serde_json::from_str(&json).unwrap());
+        assert!(serde_json::from_str::<VariableStoreSnapshot>(&json.replace("01000000", "010")).is_err());
+    }
+}
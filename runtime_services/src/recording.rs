@@ -0,0 +1,384 @@
+//! Call-recording runtime services, to verify the sequence of runtime services a component performs and exercise
+//! its error paths.
+//!
+//! [`RecordingRuntimeServices`] forwards every call to the runtime services it wraps and records it with its
+//! arguments:
+//!
+//! ```ignore
+//! let runtime_services = RecordingRuntimeServices::new(InMemoryRuntimeServices::new());
+//! component.save_settings(&runtime_services)?;
+//! runtime_services.assert_called_once_with(&RuntimeServicesCall::SetVariable {
+//!     name: name.clone(),
+//!     namespace: SETTINGS_GUID,
+//!     attributes: efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS,
+//!     data: settings.to_vec(),
+//! });
+//! ```
+//!
+//! Faults make a given call fail deterministically, like a variable write running out of storage:
+//!
+//! ```ignore
+//! let is_set_variable = |call: &RuntimeServicesCall| matches!(call, RuntimeServicesCall::SetVariable { .. });
+//! runtime_services.inject_fault(is_set_variable, 1, efi::Status::OUT_OF_RESOURCES);
+//! assert_eq!(Err(efi::Status::OUT_OF_RESOURCES.into()), component.save_settings(&runtime_services));
+//! ```
+//!
+//! Only the services implemented by each [`RuntimeServices`] are recorded, the helpers they are called through, like
+//! [`RuntimeServices::get_variable`] for [`RuntimeServices::get_variable_unchecked`], are not.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{cell::RefCell, ffi::c_void, fmt};
+
+use r_efi::efi::{self, Time, TimeCapabilities};
+use status::EfiError;
+
+use crate::{
+    variable_services::{GetVariableStatus, VariableInfo},
+    RuntimeServices,
+};
+
+/// A time given to the runtime services, compared without its padding.
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedTime(pub efi::Time);
+
+impl PartialEq for RecordedTime {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (&self.0, &other.0);
+        (a.year, a.month, a.day, a.hour, a.minute, a.second, a.nanosecond, a.timezone, a.daylight)
+            == (b.year, b.month, b.day, b.hour, b.minute, b.second, b.nanosecond, b.timezone, b.daylight)
+    }
+}
+
+impl Eq for RecordedTime {}
+
+/// A call to the runtime services, with its arguments.
+///
+/// Variable names are recorded up to their null terminator, included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeServicesCall {
+    SetVariable {
+        name: Vec<u16>,
+        namespace: efi::Guid,
+        attributes: u32,
+        data: Vec<u8>,
+    },
+    /// `data_size` is the size of the buffer given for the data, `None` to only query the size of the variable.
+    GetVariable {
+        name: Vec<u16>,
+        namespace: efi::Guid,
+        data_size: Option<usize>,
+    },
+    GetNextVariableName {
+        prev_name: Vec<u16>,
+        prev_namespace: efi::Guid,
+    },
+    QueryVariableInfo {
+        attributes: u32,
+    },
+    GetNextHighMonotonicCount,
+    ConvertPointer {
+        debug_disposition: usize,
+    },
+    GetTime,
+    SetTime {
+        time: RecordedTime,
+    },
+    GetWakeupTime,
+    SetWakeupTime {
+        enable: bool,
+        time: RecordedTime,
+    },
+}
+
+/// The name up to its null terminator included, the whole name if there is none.
+fn recorded_name(name: &[u16]) -> Vec<u16> {
+    name.iter().position(|&c| c == 0).map_or(name, |end| &name[..=end]).to_vec()
+}
+
+/// A fault injected in the calls matching a predicate.
+struct Fault {
+    predicate: Box<dyn Fn(&RuntimeServicesCall) -> bool>,
+    /// The number of matching calls until the fault, the last one fails.
+    remaining: usize,
+    status: efi::Status,
+}
+
+impl fmt::Debug for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fault")
+            .field("remaining", &self.remaining)
+            .field("status", &self.status)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Runtime services recording every call before forwarding it to the runtime services they wrap, unless a fault is
+/// injected in it.
+#[derive(Debug)]
+pub struct RecordingRuntimeServices<R: RuntimeServices> {
+    runtime_services: R,
+    calls: RefCell<Vec<RuntimeServicesCall>>,
+    faults: RefCell<Vec<Fault>>,
+}
+
+impl<R: RuntimeServices> RecordingRuntimeServices<R> {
+    pub fn new(runtime_services: R) -> Self {
+        Self { runtime_services, calls: RefCell::new(Vec::new()), faults: RefCell::new(Vec::new()) }
+    }
+
+    /// The wrapped runtime services.
+    pub fn inner(&self) -> &R {
+        &self.runtime_services
+    }
+
+    pub fn into_inner(self) -> R {
+        self.runtime_services
+    }
+
+    /// The calls recorded so far, oldest first.
+    pub fn calls(&self) -> Vec<RuntimeServicesCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// Forgets the calls recorded so far.
+    pub fn clear(&self) {
+        self.calls.borrow_mut().clear()
+    }
+
+    /// The number of recorded calls matching `predicate`.
+    pub fn count(&self, predicate: impl Fn(&RuntimeServicesCall) -> bool) -> usize {
+        self.calls.borrow().iter().filter(|call| predicate(call)).count()
+    }
+
+    /// Panics unless `call` was recorded exactly once.
+    #[track_caller]
+    pub fn assert_called_once_with(&self, call: &RuntimeServicesCall) {
+        let count = self.count(|recorded| recorded == call);
+        assert!(count == 1, "expected {call:?} to be called once, got {count} times in {:#?}", self.calls.borrow());
+    }
+
+    /// Panics unless `calls` were recorded in this order, other calls may come between them.
+    #[track_caller]
+    pub fn assert_called_in_order(&self, calls: &[RuntimeServicesCall]) {
+        let recorded = self.calls.borrow();
+        let mut remaining = recorded.iter();
+        for call in calls {
+            assert!(remaining.any(|recorded| recorded == call), "expected {call:?} in order in {recorded:#?}");
+        }
+    }
+
+    /// Fails the `nth` call matching `predicate` from now on with `status`, without forwarding it.
+    ///
+    /// The status is returned as the error of the call, except for warnings injected into GetVariable, which is
+    /// forwarded then completes with the warning when it succeeds. Calls that cannot fail ignore faults.
+    pub fn inject_fault(
+        &self,
+        predicate: impl Fn(&RuntimeServicesCall) -> bool + 'static,
+        nth: usize,
+        status: efi::Status,
+    ) {
+        assert!(nth > 0, "calls are counted from 1");
+        self.faults.borrow_mut().push(Fault { predicate: Box::new(predicate), remaining: nth, status });
+    }
+
+    fn record(&self, call: RuntimeServicesCall) {
+        self.calls.borrow_mut().push(call)
+    }
+
+    /// Records the call, then returns the status of the first fault it triggers.
+    fn intercept(&self, call: RuntimeServicesCall) -> Result<(), efi::Status> {
+        let mut status = None;
+        self.faults.borrow_mut().retain_mut(|fault| {
+            if !(fault.predicate)(&call) {
+                return true;
+            }
+            fault.remaining -= 1;
+            if fault.remaining == 0 {
+                status = status.or(Some(fault.status));
+            }
+            fault.remaining > 0
+        });
+        self.record(call);
+        status.map_or(Ok(()), Err)
+    }
+}
+
+impl<R: RuntimeServices> RuntimeServices for RecordingRuntimeServices<R> {
+    unsafe fn set_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> Result<(), EfiError> {
+        self.intercept(RuntimeServicesCall::SetVariable {
+            name: recorded_name(name),
+            namespace: *namespace,
+            attributes,
+            data: data.to_vec(),
+        })?;
+        self.runtime_services.set_variable_unchecked(name, namespace, attributes, data)
+    }
+
+    unsafe fn get_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        data: Option<&mut [u8]>,
+    ) -> GetVariableStatus {
+        match self.intercept(RuntimeServicesCall::GetVariable {
+            name: recorded_name(name),
+            namespace: *namespace,
+            data_size: data.as_ref().map(|data| data.len()),
+        }) {
+            Err(status) if status.is_warning() => {
+                match self.runtime_services.get_variable_unchecked(name, namespace, data) {
+                    GetVariableStatus::Success { data_size, attributes } => {
+                        GetVariableStatus::Warning { status, data_size, attributes }
+                    }
+                    other => other,
+                }
+            }
+            Err(status) => GetVariableStatus::Error(status),
+            Ok(()) => self.runtime_services.get_variable_unchecked(name, namespace, data),
+        }
+    }
+
+    unsafe fn get_next_variable_name_unchecked(
+        &self,
+        prev_name: &[u16],
+        prev_namespace: &efi::Guid,
+        next_name: &mut Vec<u16>,
+        next_namespace: &mut efi::Guid,
+    ) -> Result<(), EfiError> {
+        self.intercept(RuntimeServicesCall::GetNextVariableName {
+            prev_name: recorded_name(prev_name),
+            prev_namespace: *prev_namespace,
+        })?;
+        self.runtime_services.get_next_variable_name_unchecked(prev_name, prev_namespace, next_name, next_namespace)
+    }
+
+    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, EfiError> {
+        self.intercept(RuntimeServicesCall::QueryVariableInfo { attributes })?;
+        self.runtime_services.query_variable_info(attributes)
+    }
+
+    fn get_next_high_monotonic_count(&self) -> Result<u32, EfiError> {
+        self.intercept(RuntimeServicesCall::GetNextHighMonotonicCount)?;
+        self.runtime_services.get_next_high_monotonic_count()
+    }
+
+    unsafe fn convert_pointer(&self, debug_disposition: usize, address: *mut *mut c_void) -> Result<(), EfiError> {
+        self.intercept(RuntimeServicesCall::ConvertPointer { debug_disposition })?;
+        self.runtime_services.convert_pointer(debug_disposition, address)
+    }
+
+    unsafe fn get_wakeup_time_unchecked(&self) -> Result<(bool, bool, Time), EfiError> {
+        self.intercept(RuntimeServicesCall::GetWakeupTime)?;
+        self.runtime_services.get_wakeup_time_unchecked()
+    }
+
+    unsafe fn set_time_unchecked(&self, time: &efi::Time) -> Result<(), EfiError> {
+        self.intercept(RuntimeServicesCall::SetTime { time: RecordedTime(*time) })?;
+        self.runtime_services.set_time_unchecked(time)
+    }
+
+    unsafe fn get_time_unchecked(&self) -> Result<(Time, TimeCapabilities), EfiError> {
+        self.intercept(RuntimeServicesCall::GetTime)?;
+        self.runtime_services.get_time_unchecked()
+    }
+
+    unsafe fn set_wakeup_time_unchecked(&self, enable: bool, time: &efi::Time) -> Result<(), EfiError> {
+        self.intercept(RuntimeServicesCall::SetWakeupTime { enable, time: RecordedTime(*time) })?;
+        self.runtime_services.set_wakeup_time_unchecked(enable, time)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::InMemoryRuntimeServices;
+
+    const NAMESPACE: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+    const BS: u32 = efi::VARIABLE_BOOTSERVICE_ACCESS;
+
+    #[test]
+    fn test_recording() {
+        let rs = RecordingRuntimeServices::new(InMemoryRuntimeServices::new());
+        let name = "Var".encode_utf16().chain([0]).collect::<Vec<_>>();
+
+        rs.set_variable(&name, &NAMESPACE, BS, &vec![1_u8, 2]).unwrap();
+        assert_eq!(Ok((vec![1, 2], BS)), rs.get_variable::<Vec<u8>>(&name, &NAMESPACE, None));
+        assert_eq!(Err(efi::Status::UNSUPPORTED.into()), rs.get_time().map(|_| ()));
+
+        rs.assert_called_once_with(&RuntimeServicesCall::SetVariable {
+            name: name.clone(),
+            namespace: NAMESPACE,
+            attributes: BS,
+            data: vec![1, 2],
+        });
+        // get_variable queries the size of the variable before reading it.
+        rs.assert_called_in_order(&[
+            RuntimeServicesCall::GetVariable { name: name.clone(), namespace: NAMESPACE, data_size: None },
+            RuntimeServicesCall::GetVariable { name: name.clone(), namespace: NAMESPACE, data_size: Some(2) },
+            RuntimeServicesCall::GetTime,
+        ]);
+        assert_eq!(2, rs.count(|call| matches!(call, RuntimeServicesCall::GetVariable { .. })));
+
+        rs.clear();
+        assert!(rs.calls().is_empty());
+    }
+
+    #[test]
+    fn test_inject_fault() {
+        let rs = RecordingRuntimeServices::new(InMemoryRuntimeServices::new());
+        let name = "Var".encode_utf16().chain([0]).collect::<Vec<_>>();
+        rs.inject_fault(
+            |call| matches!(call, RuntimeServicesCall::SetVariable { .. }),
+            1,
+            efi::Status::OUT_OF_RESOURCES,
+        );
+        rs.inject_fault(|call| matches!(call, RuntimeServicesCall::GetVariable { .. }), 2, efi::Status::DEVICE_ERROR);
+
+        assert_eq!(Err(efi::Status::OUT_OF_RESOURCES.into()), rs.set_variable(&name, &NAMESPACE, BS, &vec![1_u8]));
+        assert!(rs.inner().is_empty());
+        rs.set_variable(&name, &NAMESPACE, BS, &vec![1_u8]).unwrap();
+        // The size query succeeds, reading the data fails.
+        assert_eq!(Err(efi::Status::DEVICE_ERROR.into()), rs.get_variable::<Vec<u8>>(&name, &NAMESPACE, None));
+        assert_eq!(Ok((vec![1], BS)), rs.get_variable::<Vec<u8>>(&name, &NAMESPACE, None));
+    }
+
+    #[test]
+    fn test_inject_warning() {
+        let rs = RecordingRuntimeServices::new(InMemoryRuntimeServices::new());
+        let name = "Var".encode_utf16().chain([0]).collect::<Vec<_>>();
+        rs.set_variable(&name, &NAMESPACE, BS, &vec![1_u8]).unwrap();
+        rs.inject_fault(
+            |call| matches!(call, RuntimeServicesCall::GetVariable { .. }),
+            2,
+            efi::Status::WARN_STALE_DATA,
+        );
+
+        let variable = rs.get_variable_with_warning::<Vec<u8>>(&name, &NAMESPACE, None).unwrap();
+        assert_eq!((vec![1], BS), variable.value);
+        assert_eq!(Some(efi::Status::WARN_STALE_DATA), variable.warning);
+        let variable = rs.get_variable_with_warning::<Vec<u8>>(&name, &NAMESPACE, None).unwrap();
+        assert_eq!(None, variable.warning);
+
+        rs.inject_fault(
+            |call| matches!(call, RuntimeServicesCall::GetVariable { .. }),
+            2,
+            efi::Status::WARN_STALE_DATA,
+        );
+        assert_eq!(Ok((vec![1], BS)), rs.get_variable::<Vec<u8>>(&name, &NAMESPACE, None));
+    }
+
+    #[test]
+    #[should_panic(expected = "to be called once")]
+    fn test_assert_called_once_with() {
+        let rs = RecordingRuntimeServices::new(InMemoryRuntimeServices::new());
+        rs.get_next_high_monotonic_count().unwrap();
+        rs.get_next_high_monotonic_count().unwrap();
+        rs.assert_called_once_with(&RuntimeServicesCall::GetNextHighMonotonicCount);
+    }
+}
@@ -0,0 +1,126 @@
+//! Runtime Properties table, the runtime services the platform supports after ExitBootServices().
+//!
+//! Once given to [`StandardRuntimeServices::set_supported_services`](crate::StandardRuntimeServices::set_supported_services),
+//! the wrappers of the unsupported services return `UNSUPPORTED` without calling the firmware:
+//!
+//! ```ignore
+//! let supported = SupportedServices::from_system_table(system_table)?;
+//! RUNTIME_SERVICES.set_supported_services(supported);
+//! ```
+//!
+//! [UEFI Spec Documentation: 4.6.2. EFI_RT_PROPERTIES_TABLE](https://uefi.org/specs/UEFI/2.10/04_EFI_System_Table.html#efi-rt-properties-table)
+
+use core::{mem, ops::BitOr, slice};
+
+use r_efi::efi;
+use status::EfiError;
+
+/// The runtime services supported by the platform, `EFI_RT_SUPPORTED_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(transparent)]
+pub struct SupportedServices(pub u32);
+
+impl SupportedServices {
+    pub const GET_TIME: SupportedServices = SupportedServices(efi::RT_SUPPORTED_GET_TIME);
+    pub const SET_TIME: SupportedServices = SupportedServices(efi::RT_SUPPORTED_SET_TIME);
+    pub const GET_WAKEUP_TIME: SupportedServices = SupportedServices(efi::RT_SUPPORTED_GET_WAKEUP_TIME);
+    pub const SET_WAKEUP_TIME: SupportedServices = SupportedServices(efi::RT_SUPPORTED_SET_WAKEUP_TIME);
+    pub const GET_VARIABLE: SupportedServices = SupportedServices(efi::RT_SUPPORTED_GET_VARIABLE);
+    pub const GET_NEXT_VARIABLE_NAME: SupportedServices = SupportedServices(efi::RT_SUPPORTED_GET_NEXT_VARIABLE_NAME);
+    pub const SET_VARIABLE: SupportedServices = SupportedServices(efi::RT_SUPPORTED_SET_VARIABLE);
+    pub const SET_VIRTUAL_ADDRESS_MAP: SupportedServices = SupportedServices(efi::RT_SUPPORTED_SET_VIRTUAL_ADDRESS_MAP);
+    pub const CONVERT_POINTER: SupportedServices = SupportedServices(efi::RT_SUPPORTED_CONVERT_POINTER);
+    pub const GET_NEXT_HIGH_MONOTONIC_COUNT: SupportedServices =
+        SupportedServices(efi::RT_SUPPORTED_GET_NEXT_HIGH_MONOTONIC_COUNT);
+    pub const RESET_SYSTEM: SupportedServices = SupportedServices(efi::RT_SUPPORTED_RESET_SYSTEM);
+    pub const UPDATE_CAPSULE: SupportedServices = SupportedServices(efi::RT_SUPPORTED_UPDATE_CAPSULE);
+    pub const QUERY_CAPSULE_CAPABILITIES: SupportedServices =
+        SupportedServices(efi::RT_SUPPORTED_QUERY_CAPSULE_CAPABILITIES);
+    pub const QUERY_VARIABLE_INFO: SupportedServices = SupportedServices(efi::RT_SUPPORTED_QUERY_VARIABLE_INFO);
+
+    /// Every runtime service, as assumed without a Runtime Properties table.
+    pub const ALL: SupportedServices = SupportedServices(0x3FFF);
+
+    /// Returns true if all the services of `services` are supported.
+    pub const fn contains(&self, services: SupportedServices) -> bool {
+        self.0 & services.0 == services.0
+    }
+
+    /// The services of a Runtime Properties table.
+    pub fn from_table(table: &efi::RtPropertiesTable) -> Result<Self, EfiError> {
+        if table.version < efi::RT_PROPERTIES_TABLE_VERSION
+            || (table.length as usize) < mem::size_of::<efi::RtPropertiesTable>()
+        {
+            return Err(efi::Status::INCOMPATIBLE_VERSION.into());
+        }
+        Ok(Self(table.runtime_services_supported))
+    }
+
+    /// The services of the Runtime Properties table installed in the configuration tables.
+    ///
+    /// Without the table, every service is supported.
+    pub fn from_system_table(system_table: &efi::SystemTable) -> Result<Self, EfiError> {
+        if system_table.configuration_table.is_null() {
+            return Ok(Self::ALL);
+        }
+        //SAFETY: The system table holds number_of_table_entries entries at configuration_table.
+        let tables =
+            unsafe { slice::from_raw_parts(system_table.configuration_table, system_table.number_of_table_entries) };
+        match tables.iter().find(|table| table.vendor_guid == efi::RT_PROPERTIES_TABLE_GUID) {
+            Some(table) if table.vendor_table.is_null() => Err(efi::Status::INVALID_PARAMETER.into()),
+            //SAFETY: The installed table is a Runtime Properties table, read unaligned since it is only 8 bytes long.
+            Some(table) => {
+                Self::from_table(&unsafe { (table.vendor_table as *const efi::RtPropertiesTable).read_unaligned() })
+            }
+            None => Ok(Self::ALL),
+        }
+    }
+}
+
+impl BitOr for SupportedServices {
+    type Output = SupportedServices;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        SupportedServices(self.0 | rhs.0)
+    }
+}
+
+impl From<u32> for SupportedServices {
+    fn from(value: u32) -> Self {
+        SupportedServices(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::{ffi::c_void, mem::MaybeUninit, ptr};
+
+    #[test]
+    fn test_from_system_table() {
+        let mut properties = efi::RtPropertiesTable {
+            version: efi::RT_PROPERTIES_TABLE_VERSION,
+            length: mem::size_of::<efi::RtPropertiesTable>() as u16,
+            runtime_services_supported: efi::RT_SUPPORTED_GET_VARIABLE | efi::RT_SUPPORTED_RESET_SYSTEM,
+        };
+        let mut tables = [efi::ConfigurationTable {
+            vendor_guid: efi::RT_PROPERTIES_TABLE_GUID,
+            vendor_table: ptr::addr_of_mut!(properties) as *mut c_void,
+        }];
+        //SAFETY: Only the configuration table fields are read.
+        let mut system_table = unsafe { MaybeUninit::<efi::SystemTable>::zeroed().assume_init() };
+        system_table.number_of_table_entries = tables.len();
+        system_table.configuration_table = tables.as_mut_ptr();
+
+        let supported = SupportedServices::from_system_table(&system_table).unwrap();
+        assert!(supported.contains(SupportedServices::GET_VARIABLE | SupportedServices::RESET_SYSTEM));
+        assert!(!supported.contains(SupportedServices::SET_VARIABLE));
+
+        //SAFETY: The table points to the properties above.
+        unsafe { (*(tables[0].vendor_table as *mut efi::RtPropertiesTable)).version = 0 };
+        assert_eq!(Err(efi::Status::INCOMPATIBLE_VERSION.into()), SupportedServices::from_system_table(&system_table));
+        tables[0].vendor_guid = efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6]);
+        assert_eq!(Ok(SupportedServices::ALL), SupportedServices::from_system_table(&system_table));
+    }
+}
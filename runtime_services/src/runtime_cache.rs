@@ -0,0 +1,342 @@
+//! Boot-time data of runtime drivers, served before and after `SetVirtualAddressMap()`.
+//!
+//! A runtime driver keeps the data its runtime services need in a static [`RuntimeCache`]. The data is allocated in
+//! `EfiRuntimeServicesData` memory at boot, and its address is converted when the OS calls `SetVirtualAddressMap()`,
+//! so that the same accessor works before and after the switch to the virtual address map. The data is snapshotted
+//! one last time when the boot services exit, and served read-only at runtime:
+//!
+//! ```ignore
+//! static PLATFORM: RuntimeCache<PlatformInfo> = RuntimeCache::new();
+//!
+//! // In the entry point of the driver.
+//! let mut platform = PLATFORM.initialize(&BOOT_SERVICES, &RUNTIME_SERVICES, PlatformInfo::default())?;
+//! platform.update(|info| info.boot_mode = boot_mode())?;
+//! platform.snapshot_at_exit_boot_services(|info| info.capture_flash_layout());
+//!
+//! // In a runtime service.
+//! let platform = PLATFORM.at_runtime().ok_or(efi::Status::NOT_READY)?;
+//! ```
+//!
+//! The phase is part of the type of the references: the one returned at boot, `CacheRef<T, Boot>`, updates the data
+//! until `ExitBootServices()`, the ones given at runtime, `CacheRef<T, Runtime>`, only read it. The data must not
+//! point to boot services memory, which the OS reclaims, and is never dropped.
+//!
+//! [UEFI Spec Documentation: 8.4. Virtual Memory Services](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#virtual-memory-services)
+
+use alloc::boxed::Box;
+use core::{
+    ffi::c_void,
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU8, Ordering},
+};
+
+use boot_services::{allocation::MemoryType, event::EventType, tpl::Tpl, BootServices};
+use r_efi::efi;
+use status::EfiError;
+
+use crate::RuntimeServices;
+
+/// Alignment of the pool allocations.
+const POOL_ALIGNMENT: usize = 8;
+
+const UNINITIALIZED: u8 = 0;
+const BOOT: u8 = 1;
+const RUNTIME: u8 = 2;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Phase of the boot a [`CacheRef`] is used in, [`Boot`] or [`Runtime`].
+pub trait Phase: private::Sealed {}
+
+/// Before `ExitBootServices()`, while the data can be updated.
+#[derive(Debug)]
+pub enum Boot {}
+
+/// After `ExitBootServices()`, before and after `SetVirtualAddressMap()`, while the data is read-only.
+#[derive(Debug)]
+pub enum Runtime {}
+
+impl private::Sealed for Boot {}
+impl private::Sealed for Runtime {}
+impl Phase for Boot {}
+impl Phase for Runtime {}
+
+type Snapshot<T> = Box<dyn FnMut(&mut T)>;
+
+/// Data allocated in runtime memory at boot, and served by the runtime services.
+///
+/// The cache is `'static`, as the events of `ExitBootServices()` and `SetVirtualAddressMap()` refer to it.
+pub struct RuntimeCache<T: 'static> {
+    data: AtomicPtr<T>,
+    phase: AtomicU8,
+    /// The runtime services converting the address of the data, of the type given to [`RuntimeCache::initialize`].
+    runtime_services: AtomicPtr<c_void>,
+    /// The boxed [`Snapshot`] of the data, untyped for [`RuntimeCache::new`] to be `const`.
+    snapshot: AtomicPtr<c_void>,
+}
+
+impl<T: 'static> RuntimeCache<T> {
+    pub const fn new() -> Self {
+        Self {
+            data: AtomicPtr::new(ptr::null_mut()),
+            phase: AtomicU8::new(UNINITIALIZED),
+            runtime_services: AtomicPtr::new(ptr::null_mut()),
+            snapshot: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Moves `value` to runtime memory and registers the events of the end of the boot, from the entry point of the
+    /// driver.
+    ///
+    /// `runtime_services` converts the address of the data when the OS sets the virtual address map. Returns
+    /// `ALREADY_STARTED` if the cache is already initialized, and `UNSUPPORTED` if `T` needs a larger alignment than
+    /// the pool allocations provide.
+    pub fn initialize<B: BootServices, R: RuntimeServices + 'static>(
+        &'static self,
+        boot_services: &B,
+        runtime_services: &'static R,
+        value: T,
+    ) -> Result<CacheRef<'static, T, Boot>, EfiError> {
+        if mem::align_of::<T>() > POOL_ALIGNMENT {
+            return Err(efi::Status::UNSUPPORTED.into());
+        }
+        if self.phase.compare_exchange(UNINITIALIZED, BOOT, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return Err(efi::Status::ALREADY_STARTED.into());
+        }
+        let result = self.register(boot_services, runtime_services, value);
+        if result.is_err() {
+            self.phase.store(UNINITIALIZED, Ordering::Release);
+        }
+        result.map(|()| CacheRef { cache: self, phase: PhantomData })
+    }
+
+    fn register<B: BootServices, R: RuntimeServices + 'static>(
+        &'static self,
+        boot_services: &B,
+        runtime_services: &'static R,
+        value: T,
+    ) -> Result<(), EfiError> {
+        let data = boot_services.allocate_pool(MemoryType::RUNTIME_SERVICES_DATA, mem::size_of::<T>())? as *mut T;
+        self.runtime_services.store(runtime_services as *const R as *mut c_void, Ordering::Release);
+        let context = self as *const Self as *mut Self;
+        //SAFETY: The cache is `'static`, the notifications only use its data and runtime services.
+        let exit_boot_services = unsafe {
+            boot_services.create_event_ex_unchecked(
+                EventType::NOTIFY_SIGNAL,
+                Tpl::CALLBACK,
+                exit_boot_services::<T>,
+                context,
+                &efi::EVENT_GROUP_EXIT_BOOT_SERVICES,
+            )
+        };
+        let exit_boot_services = match exit_boot_services {
+            Ok(event) => event,
+            Err(status) => {
+                let _ = boot_services.free_pool(data as *mut u8);
+                return Err(status);
+            }
+        };
+        //SAFETY: As above, and the runtime services are of type `R`.
+        if let Err(status) = unsafe {
+            boot_services.create_event_ex_unchecked(
+                EventType::NOTIFY_SIGNAL,
+                Tpl::NOTIFY,
+                virtual_address_change::<T, R>,
+                context,
+                &efi::EVENT_GROUP_VIRTUAL_ADDRESS_CHANGE,
+            )
+        } {
+            let _ = boot_services.close_event(exit_boot_services);
+            let _ = boot_services.free_pool(data as *mut u8);
+            return Err(status);
+        }
+        //SAFETY: The buffer is allocated for a `T`, and aligned for it.
+        unsafe { data.write(value) };
+        self.data.store(data, Ordering::Release);
+        Ok(())
+    }
+
+    /// Whether the cache was initialized at boot.
+    pub fn is_initialized(&self) -> bool {
+        self.phase.load(Ordering::Acquire) != UNINITIALIZED
+    }
+
+    /// The data, once the boot services exited.
+    ///
+    /// Returns `None` before `ExitBootServices()`, while the data may still change.
+    pub fn at_runtime(&self) -> Option<CacheRef<'_, T, Runtime>> {
+        match self.phase.load(Ordering::Acquire) {
+            RUNTIME => Some(CacheRef { cache: self, phase: PhantomData }),
+            _ => None,
+        }
+    }
+}
+
+impl<T: 'static> Default for RuntimeCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: 'static> fmt::Debug for RuntimeCache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self.phase.load(Ordering::Acquire) {
+            UNINITIALIZED => "Uninitialized",
+            BOOT => "Boot",
+            _ => "Runtime",
+        };
+        f.debug_struct("RuntimeCache").field("phase", &phase).field("data", &self.data.load(Ordering::Relaxed)).finish()
+    }
+}
+
+extern "efiapi" fn exit_boot_services<T: 'static>(_event: efi::Event, cache: *mut RuntimeCache<T>) {
+    //SAFETY: The context is the `'static` cache the event was created for.
+    let cache = unsafe { &*cache };
+    let snapshot = cache.snapshot.load(Ordering::Acquire) as *mut Snapshot<T>;
+    // The snapshot is not freed, memory must not be freed once the boot services exit.
+    if !snapshot.is_null() {
+        //SAFETY: The boot reference does not run during the notification, the data is not borrowed.
+        unsafe { (*snapshot)(&mut *cache.data.load(Ordering::Acquire)) };
+    }
+    cache.phase.store(RUNTIME, Ordering::Release);
+}
+
+extern "efiapi" fn virtual_address_change<T: 'static, R: RuntimeServices>(
+    _event: efi::Event,
+    cache: *mut RuntimeCache<T>,
+) {
+    //SAFETY: The context is the `'static` cache the event was created for, with runtime services of type `R`.
+    let (cache, runtime_services) =
+        unsafe { (&*cache, &*(*cache).runtime_services.load(Ordering::Acquire).cast::<R>()) };
+    // A failed conversion leaves the physical address, the notification has no one to report it to.
+    //SAFETY: The data is in runtime memory, and this is the notification of the virtual address change.
+    let _ = unsafe { runtime_services.convert_pointer(0, cache.data.as_ptr().cast()) };
+}
+
+/// Reference to the data of a [`RuntimeCache`], in a [`Phase`] of the boot.
+pub struct CacheRef<'a, T: 'static, P: Phase> {
+    cache: &'a RuntimeCache<T>,
+    phase: PhantomData<P>,
+}
+
+impl<T: 'static> CacheRef<'_, T, Boot> {
+    /// Changes the data.
+    ///
+    /// Returns `ACCESS_DENIED` once the boot services exited, as the runtime services may be reading the data.
+    pub fn update(&mut self, update: impl FnOnce(&mut T)) -> Result<(), EfiError> {
+        if self.cache.phase.load(Ordering::Acquire) != BOOT {
+            return Err(efi::Status::ACCESS_DENIED.into());
+        }
+        //SAFETY: The boot reference is the only one until the boot services exit, and it is borrowed mutably.
+        update(unsafe { &mut *self.cache.data.load(Ordering::Acquire) });
+        Ok(())
+    }
+
+    /// Sets `snapshot` to update the data when the boot services exit, replacing the previous one.
+    ///
+    /// `snapshot` runs in the notification of `ExitBootServices()`, it must not use the boot services nor allocate
+    /// memory.
+    pub fn snapshot_at_exit_boot_services(&mut self, snapshot: impl FnMut(&mut T) + 'static) {
+        let snapshot: Snapshot<T> = Box::new(snapshot);
+        let snapshot = Box::into_raw(Box::new(snapshot)) as *mut c_void;
+        let previous = self.cache.snapshot.swap(snapshot, Ordering::AcqRel) as *mut Snapshot<T>;
+        if !previous.is_null() {
+            //SAFETY: The previous snapshot was leaked from a box by this function, and did not run.
+            drop(unsafe { Box::from_raw(previous) });
+        }
+    }
+}
+
+impl<T: 'static> Clone for CacheRef<'_, T, Runtime> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: 'static> Copy for CacheRef<'_, T, Runtime> {}
+
+impl<T: 'static, P: Phase> Deref for CacheRef<'_, T, P> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        //SAFETY: The data is written before a reference is given, and only changed through a mutable boot reference.
+        unsafe { &*self.cache.data.load(Ordering::Acquire) }
+    }
+}
+
+impl<T: fmt::Debug + 'static, P: Phase> fmt::Debug for CacheRef<'_, T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockRuntimeServices;
+    use boot_services::{deferred::signal_event_group, mock::InMemoryBootServices};
+
+    fn boot_services() -> &'static InMemoryBootServices {
+        Box::leak(Box::new(InMemoryBootServices::new()))
+    }
+
+    #[test]
+    fn test_boot_and_runtime() {
+        // The OS moves the data to its virtual address, the references read it there.
+        let virtual_data: &'static mut [u32; 2] = Box::leak(Box::new([0; 2]));
+        let virtual_address = virtual_data as *mut [u32; 2] as usize;
+        let mut runtime_services = MockRuntimeServices::new();
+        runtime_services.expect_convert_pointer().times(1).returning(move |debug_disposition, address| {
+            assert_eq!(0, debug_disposition);
+            unsafe {
+                ptr::copy_nonoverlapping(*address as *const [u32; 2], virtual_address as *mut [u32; 2], 1);
+                *address = virtual_address as *mut c_void;
+            }
+            Ok(())
+        });
+        let (boot_services, runtime_services) = (boot_services(), Box::leak(Box::new(runtime_services)));
+
+        let cache: &'static RuntimeCache<[u32; 2]> = Box::leak(Box::new(RuntimeCache::new()));
+        let mut data = cache.initialize(boot_services, runtime_services, [1, 2]).unwrap();
+        assert_eq!(1, boot_services.allocations());
+        assert!(cache.is_initialized() && cache.at_runtime().is_none());
+
+        data.update(|data| data[0] = 10).unwrap();
+        data.snapshot_at_exit_boot_services(|_| unreachable!("the snapshot is replaced"));
+        data.snapshot_at_exit_boot_services(|data| data[1] += 1);
+        assert_eq!([10, 2], *data);
+
+        signal_event_group(boot_services, &efi::EVENT_GROUP_EXIT_BOOT_SERVICES).unwrap();
+        let runtime = cache.at_runtime().unwrap();
+        assert_eq!([10, 3], *runtime);
+        assert_eq!(efi::Status::ACCESS_DENIED, data.update(|data| data[0] = 0).unwrap_err());
+
+        signal_event_group(boot_services, &efi::EVENT_GROUP_VIRTUAL_ADDRESS_CHANGE).unwrap();
+        assert_eq!(virtual_address, &*runtime as *const [u32; 2] as usize);
+        assert_eq!(virtual_address, &*data as *const [u32; 2] as usize);
+        assert_eq!([10, 3], *cache.at_runtime().unwrap());
+    }
+
+    #[test]
+    fn test_initialize_errors() {
+        let (boot_services, runtime_services) = (boot_services(), Box::leak(Box::new(MockRuntimeServices::new())));
+        let cache: &'static RuntimeCache<u64> = Box::leak(Box::new(RuntimeCache::new()));
+        cache.initialize(boot_services, runtime_services, 1).unwrap();
+        assert_eq!(efi::Status::ALREADY_STARTED, cache.initialize(boot_services, runtime_services, 2).unwrap_err());
+
+        #[repr(align(16))]
+        struct Aligned;
+        let cache: &'static RuntimeCache<Aligned> = Box::leak(Box::new(RuntimeCache::new()));
+        assert!(
+            matches!(cache.initialize(boot_services, runtime_services, Aligned), Err(error) if error == efi::Status::UNSUPPORTED)
+        );
+        assert!(!cache.is_initialized());
+        assert_eq!(1, boot_services.allocations());
+    }
+}
@@ -9,29 +9,81 @@
 //! let variable_services::VariableInfo = RUNTIME_SERVICES.query_variable_info(attributes);
 //! ```
 //!
+//! Without the default `alloc` feature, for contexts without a heap, the variable services that allocate are not
+//! available and [`RuntimeServices::get_variable_into`] reads variables into buffers of the caller.
+//!
 
 #![cfg_attr(all(not(test), not(feature = "mockall")), no_std)]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
 /// Variable-services-specific structs and utilities
 pub mod variable_services;
 
+/// Caching reader of the variable services, for variables read repeatedly
+#[cfg(feature = "alloc")]
+pub mod variable_cache;
+
+/// Typed settings stored in UEFI variables, with schema versions
+#[cfg(all(feature = "alloc", feature = "serde"))]
+pub mod variable_store;
+
+/// Boot-time data of runtime drivers, served before and after `SetVirtualAddressMap()`
+#[cfg(all(feature = "alloc", any(test, feature = "boot_services")))]
+pub mod runtime_cache;
+
+/// Secure Boot signature databases and revocation checks
+#[cfg(feature = "alloc")]
+pub mod secure_boot;
+
+/// Capsule result variables, the outcome of capsule processing reported after a reset
+#[cfg(feature = "alloc")]
+pub mod capsule_result;
+
+/// Runtime Properties table, the runtime services supported by the platform
+pub mod rt_properties;
+
+/// In-memory runtime services, for unit tests of code using UEFI variables
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+/// Conformance suite of the variable services, for any implementation of [`RuntimeServices`]
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
+
+/// Call-recording runtime services, to verify the sequence of runtime services a component performs
+#[cfg(any(test, feature = "mock"))]
+pub mod recording;
+
+/// Serde definitions for the r-efi types used by the runtime services
+#[cfg(feature = "serde")]
+pub mod serde_remote;
+
 #[cfg(any(test, feature = "mockall"))]
 use mockall::automock;
 
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::mem;
 use core::{
     ffi::c_void,
     marker::PhantomData,
     mem::MaybeUninit,
     ptr,
-    sync::atomic::{AtomicPtr, Ordering},
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
 };
 
 use r_efi::efi;
 use r_efi::efi::{Boolean, Time, TimeCapabilities};
 
+use rt_properties::SupportedServices;
+use status::EfiError;
+#[cfg(feature = "alloc")]
+use status::WithWarning;
+#[cfg(feature = "alloc")]
+use ucs2::Str16;
 use variable_services::{GetVariableStatus, VariableInfo};
 
 /// The UEFI spec runtime services.
@@ -42,6 +94,7 @@ use variable_services::{GetVariableStatus, VariableInfo};
 #[derive(Debug)]
 pub struct StandardRuntimeServices<'a> {
     efi_runtime_services: AtomicPtr<efi::RuntimeServices>,
+    supported_services: AtomicU32,
     _lifetime_marker: PhantomData<&'a efi::RuntimeServices>,
 }
 
@@ -51,6 +104,7 @@ impl<'a> StandardRuntimeServices<'a> {
         // The efi::RuntimeServices is only read, that is why we use a non mutable reference.
         Self {
             efi_runtime_services: AtomicPtr::new(efi_runtime_services as *const _ as *mut _),
+            supported_services: AtomicU32::new(SupportedServices::ALL.0),
             _lifetime_marker: PhantomData,
         }
     }
@@ -58,7 +112,11 @@ impl<'a> StandardRuntimeServices<'a> {
     /// Create a new StandardRuntimeServices that is uninitialized.
     /// The struct need to be initialize later with [Self::initialize], otherwise, subsequent call will panic.
     pub const fn new_uninit() -> Self {
-        Self { efi_runtime_services: AtomicPtr::new(ptr::null_mut()), _lifetime_marker: PhantomData }
+        Self {
+            efi_runtime_services: AtomicPtr::new(ptr::null_mut()),
+            supported_services: AtomicU32::new(SupportedServices::ALL.0),
+            _lifetime_marker: PhantomData,
+        }
     }
 
     /// Initialize the StandardRuntimeServices with a reference to [efi::RuntimeServices].
@@ -73,6 +131,28 @@ impl<'a> StandardRuntimeServices<'a> {
         }
     }
 
+    /// Sets the services supported by the platform, from its [Runtime Properties table](rt_properties).
+    ///
+    /// The wrappers of the other services return `UNSUPPORTED` without calling the firmware, whose behavior is
+    /// undefined for them. Every service is supported until this is called.
+    pub fn set_supported_services(&self, supported_services: SupportedServices) {
+        self.supported_services.store(supported_services.0, Ordering::SeqCst)
+    }
+
+    /// The services supported by the platform, see [Self::set_supported_services].
+    pub fn supported_services(&self) -> SupportedServices {
+        SupportedServices(self.supported_services.load(Ordering::SeqCst))
+    }
+
+    /// Returns `UNSUPPORTED` if the platform does not support `service`.
+    fn check_supported(&self, service: SupportedServices) -> Result<(), efi::Status> {
+        if self.supported_services().contains(service) {
+            Ok(())
+        } else {
+            Err(efi::Status::UNSUPPORTED)
+        }
+    }
+
     /// # Panics
     /// This function will panic if it was not initialize.
     fn efi_runtime_services(&self) -> &efi::RuntimeServices {
@@ -95,23 +175,26 @@ unsafe impl Send for StandardRuntimeServices<'static> {}
 
 /// Interface for Rust-friendly wrappers of the UEFI Runtime Services
 pub trait RuntimeServices: Sized {
-    /// Get the time.
+    /// Sets a UEFI variable.
+    ///
+    /// `name` is a null-terminated UCS-2 string, [`RuntimeServices::set_variable_str16`] takes a [`Str16`].
     ///
     /// UEFI Spec Documentation: [8.2.3. EFI_RUNTIME_SERVICES.SetVariable()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#setvariable)
     ///
+    #[cfg(feature = "alloc")]
     fn set_variable<T>(
         &self,
         name: &[u16],
         namespace: &efi::Guid,
         attributes: u32,
         data: &T,
-    ) -> Result<(), efi::Status>
+    ) -> Result<(), EfiError>
     where
         T: AsRef<[u8]> + 'static,
     {
         if !name.iter().position(|&c| c == 0).is_some() {
             debug_assert!(false, "Name passed into set_variable is not null-terminated.");
-            return Err(efi::Status::INVALID_PARAMETER);
+            return Err(efi::Status::INVALID_PARAMETER.into());
         }
 
         // Keep a local copy of name to unburden the caller of having to pass in a mutable slice
@@ -120,24 +203,80 @@ pub trait RuntimeServices: Sized {
         unsafe { self.set_variable_unchecked(name_vec.as_mut_slice(), namespace, attributes, data.as_ref()) }
     }
 
+    /// Sets a UEFI variable like [`RuntimeServices::set_variable`], with a [`Str16`] name.
+    #[cfg(feature = "alloc")]
+    fn set_variable_str16<T>(
+        &self,
+        name: &Str16,
+        namespace: &efi::Guid,
+        attributes: u32,
+        data: &T,
+    ) -> Result<(), EfiError>
+    where
+        T: AsRef<[u8]> + 'static,
+    {
+        self.set_variable(name.as_slice_with_nul(), namespace, attributes, data)
+    }
+
     /// Gets a UEFI variable.
     ///
     /// Returns a tuple of (data, attributes)
     ///
+    /// `name` is a null-terminated UCS-2 string, [`RuntimeServices::get_variable_str16`] takes a [`Str16`]. A variable
+    /// returned with a warning, like `WARN_STALE_DATA`, is returned as a success,
+    /// [`RuntimeServices::get_variable_with_warning`] returns the warning too.
+    ///
     /// UEFI Spec Documentation: [8.2.1. EFI_RUNTIME_SERVICES.GetVariable()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#getvariable)
     ///
+    #[cfg(feature = "alloc")]
     fn get_variable<T>(
         &self,
         name: &[u16],
         namespace: &efi::Guid,
         size_hint: Option<usize>,
-    ) -> Result<(T, u32), efi::Status>
+    ) -> Result<(T, u32), EfiError>
+    where
+        T: TryFrom<Vec<u8>> + 'static,
+    {
+        self.get_variable_with_warning(name, namespace, size_hint).map(WithWarning::into_value)
+    }
+
+    /// Gets a UEFI variable like [`RuntimeServices::get_variable`], with a [`Str16`] name.
+    #[cfg(feature = "alloc")]
+    fn get_variable_str16<T>(
+        &self,
+        name: &Str16,
+        namespace: &efi::Guid,
+        size_hint: Option<usize>,
+    ) -> Result<(T, u32), EfiError>
+    where
+        T: TryFrom<Vec<u8>> + 'static,
+    {
+        self.get_variable(name.as_slice_with_nul(), namespace, size_hint)
+    }
+
+    /// Gets a UEFI variable and the warning it was returned with, if any.
+    ///
+    /// Returns a tuple of (data, attributes) with the warning, like `WARN_STALE_DATA` for a variable store that
+    /// could not refresh the data.
+    ///
+    /// `name` is a null-terminated UCS-2 string.
+    ///
+    /// UEFI Spec Documentation: [8.2.1. EFI_RUNTIME_SERVICES.GetVariable()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#getvariable)
+    ///
+    #[cfg(feature = "alloc")]
+    fn get_variable_with_warning<T>(
+        &self,
+        name: &[u16],
+        namespace: &efi::Guid,
+        size_hint: Option<usize>,
+    ) -> Result<WithWarning<(T, u32)>, EfiError>
     where
         T: TryFrom<Vec<u8>> + 'static,
     {
         if !name.iter().position(|&c| c == 0).is_some() {
             debug_assert!(false, "Name passed into get_variable is not null-terminated.");
-            return Err(efi::Status::INVALID_PARAMETER);
+            return Err(efi::Status::INVALID_PARAMETER.into());
         }
 
         // Keep a local copy of name to unburden the caller of having to pass in a mutable slice
@@ -166,34 +305,53 @@ pub trait RuntimeServices: Sized {
 
                 match status {
                     GetVariableStatus::Success { data_size: _, attributes } => match T::try_from(data) {
-                        Ok(d) => return Ok((d, attributes)),
-                        Err(_) => return Err(efi::Status::INVALID_PARAMETER),
+                        Ok(d) => return Ok(WithWarning::new((d, attributes), efi::Status::SUCCESS)),
+                        Err(_) => return Err(efi::Status::INVALID_PARAMETER.into()),
+                    },
+                    GetVariableStatus::Warning { status, data_size: _, attributes } => match T::try_from(data) {
+                        Ok(d) => return Ok(WithWarning::new((d, attributes), status)),
+                        Err(_) => return Err(efi::Status::INVALID_PARAMETER.into()),
                     },
                     GetVariableStatus::BufferTooSmall { data_size, attributes: _ } => {
                         if first_attempt {
                             first_attempt = false;
                             data.resize(data_size, 10);
                         } else {
-                            return Err(efi::Status::BUFFER_TOO_SMALL);
+                            return Err(efi::Status::BUFFER_TOO_SMALL.into());
                         }
                     }
                     GetVariableStatus::Error(e) => {
-                        return Err(e);
+                        return Err(e.into());
                     }
                 }
             }
         }
     }
 
+    /// Gets a UEFI variable into a buffer of the caller, without allocating.
+    ///
+    /// `name` is a null-terminated UCS-2 string. With a buffer too small for the data, or an empty one to only query
+    /// the variable, [`GetVariableStatus::BufferTooSmall`] gives the size of the data and its attributes.
+    ///
+    /// UEFI Spec Documentation: [8.2.1. EFI_RUNTIME_SERVICES.GetVariable()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#getvariable)
+    ///
+    fn get_variable_into(&self, name: &mut [u16], namespace: &efi::Guid, data: &mut [u8]) -> GetVariableStatus {
+        if !name.contains(&0) {
+            return GetVariableStatus::Error(efi::Status::INVALID_PARAMETER);
+        }
+        unsafe { self.get_variable_unchecked(name, namespace, if data.is_empty() { None } else { Some(data) }) }
+    }
+
     /// Helper function to get a UEFI variable's size and attributes
+    #[cfg(feature = "alloc")]
     fn get_variable_size_and_attributes(
         &self,
         name: &[u16],
         namespace: &efi::Guid,
-    ) -> Result<(usize, u32), efi::Status> {
+    ) -> Result<(usize, u32), EfiError> {
         if !name.iter().position(|&c| c == 0).is_some() {
             debug_assert!(false, "Name passed into set_variable is not null-terminated.");
-            return Err(efi::Status::INVALID_PARAMETER);
+            return Err(efi::Status::INVALID_PARAMETER.into());
         }
 
         // Keep a local copy of name to unburden the caller of having to pass in a mutable slice
@@ -202,8 +360,9 @@ pub trait RuntimeServices: Sized {
         unsafe {
             match self.get_variable_unchecked(name_vec.as_mut_slice(), namespace, None) {
                 GetVariableStatus::BufferTooSmall { data_size, attributes } => Ok((data_size, attributes)),
-                GetVariableStatus::Error(e) => Err(e),
-                GetVariableStatus::Success { data_size, attributes } => {
+                GetVariableStatus::Error(e) => Err(e.into()),
+                GetVariableStatus::Success { data_size, attributes }
+                | GetVariableStatus::Warning { data_size, attributes, .. } => {
                     debug_assert!(false, "GetVariable call with zero-sized buffer returned Success.");
                     Ok((data_size, attributes))
                 }
@@ -219,14 +378,15 @@ pub trait RuntimeServices: Sized {
     ///
     /// UEFI Spec Documentation: [8.2.2. EFI_RUNTIME_SERVICES.GetNextVariableName()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#getnextvariablename)
     ///
+    #[cfg(feature = "alloc")]
     fn get_next_variable_name(
         &self,
         prev_name: &[u16],
         prev_namespace: &efi::Guid,
-    ) -> Result<(Vec<u16>, efi::Guid), efi::Status> {
+    ) -> Result<(Vec<u16>, efi::Guid), EfiError> {
         if prev_name.len() == 0 {
             debug_assert!(false, "Zero-length name passed into get_next_variable_name.");
-            return Err(efi::Status::INVALID_PARAMETER);
+            return Err(efi::Status::INVALID_PARAMETER.into());
         }
 
         let mut next_name = Vec::<u16>::new();
@@ -243,7 +403,25 @@ pub trait RuntimeServices: Sized {
     ///
     /// UEFI Spec Documentation: [8.2.4. EFI_RUNTIME_SERVICES.QueryVariableInfo()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#queryvariableinfo)
     ///
-    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, efi::Status>;
+    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, EfiError>;
+
+    /// Returns the next high 32 bits of the platform's monotonic counter.
+    ///
+    /// UEFI Spec Documentation: [8.5.2. EFI_RUNTIME_SERVICES.GetNextHighMonotonicCount()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#getnexthighmonotoniccount)
+    ///
+    fn get_next_high_monotonic_count(&self) -> Result<u32, EfiError>;
+
+    /// Converts a pointer from the physical to the virtual address map of the OS, in place.
+    ///
+    /// With `efi::OPTIONAL_POINTER` in `debug_disposition`, a null pointer is left null instead of being invalid.
+    ///
+    /// UEFI Spec Documentation: [8.4.2. EFI_RUNTIME_SERVICES.ConvertPointer()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#convertpointer)
+    ///
+    /// # Safety
+    ///
+    /// Only valid in a notification of the `EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE` event group, with `address` pointing to
+    /// a pointer into runtime memory.
+    unsafe fn convert_pointer(&self, debug_disposition: usize, address: *mut *mut c_void) -> Result<(), EfiError>;
 
     /// UEFI Spec Documentation:
     /// <a href="https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#gettime" target="_blank">
@@ -253,7 +431,7 @@ pub trait RuntimeServices: Sized {
     /// [^note]: Time capabilities is always returned in this implementation.
     fn get_time(
         &self,
-    ) -> Result<(Time, TimeCapabilities), efi::Status> {
+    ) -> Result<(Time, TimeCapabilities), EfiError> {
         unsafe {
             self.get_time_unchecked()
         }
@@ -268,7 +446,7 @@ pub trait RuntimeServices: Sized {
     fn set_time(
         &self,
         time: &efi::Time,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         unsafe {
             self.set_time_unchecked(time)
         }
@@ -282,7 +460,7 @@ pub trait RuntimeServices: Sized {
     /// </a>
     fn get_wakeup_time(
         &self,
-    ) -> Result<(bool, bool, Time), efi::Status> {
+    ) -> Result<(bool, bool, Time), EfiError> {
         unsafe {
             self.get_wakeup_time_unchecked()
         }
@@ -300,7 +478,7 @@ pub trait RuntimeServices: Sized {
         &self,
         enable: bool,
         time: &efi::Time,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
         unsafe {
             self.set_wakeup_time_unchecked(enable, time)
         }
@@ -309,25 +487,25 @@ pub trait RuntimeServices: Sized {
     /// Prefer normal [`RuntimeServices::get_wakeup_time`] when possible.
     unsafe fn get_wakeup_time_unchecked(
         &self,
-    ) -> Result<(bool, bool, Time), efi::Status>;
+    ) -> Result<(bool, bool, Time), EfiError>;
 
     /// Prefer normal [`RuntimeServices::set_time`] when possible.
     unsafe fn set_time_unchecked(
         &self,
         time: &efi::Time,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
 
     /// Prefer normal [`RuntimeServices::get_time`] when possible.
     unsafe fn get_time_unchecked(
         &self,
-    ) -> Result<(Time, TimeCapabilities), efi::Status>;
+    ) -> Result<(Time, TimeCapabilities), EfiError>;
 
     /// Prefer normal [`RuntimeServices::set_wakeup_time`] when possible.
     unsafe fn set_wakeup_time_unchecked(
         &self,
         enable: bool,
         time: &efi::Time,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
 
     /// Set's a UEFI variable
     ///
@@ -340,7 +518,7 @@ pub trait RuntimeServices: Sized {
         namespace: &efi::Guid,
         attributes: u32,
         data: &[u8],
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
 
     /// Gets a UEFI variable
     ///
@@ -363,17 +541,55 @@ pub trait RuntimeServices: Sized {
     /// Ensure name isn't empty. It can be an empty string,
     /// but there must be some data.
     ///
+    #[cfg(feature = "alloc")]
     unsafe fn get_next_variable_name_unchecked(
         &self,
         prev_name: &[u16],
         prev_namespace: &efi::Guid,
         next_name: &mut Vec<u16>,
         next_namespace: &mut efi::Guid,
-    ) -> Result<(), efi::Status>;
+    ) -> Result<(), EfiError>;
+
+    /// Replaces a UEFI variable name and namespace by the ones of the variable after it.
+    ///
+    /// `name` is reused from call to call: its whole capacity is given to the firmware, it grows only when the next
+    /// name does not fit, and it is cut after the null terminator of the next name. Enumerating every variable with
+    /// the same buffer allocates only for the longest name. On error, the name and namespace are left unchanged.
+    ///
+    /// The default implementation copies the previous name for [`RuntimeServices::get_next_variable_name_unchecked`].
+    ///
+    /// # Safety
+    ///
+    /// Ensure name isn't empty. It can be an empty string,
+    /// but there must be some data.
+    ///
+    #[cfg(feature = "alloc")]
+    unsafe fn get_next_variable_name_in_place(
+        &self,
+        name: &mut Vec<u16>,
+        namespace: &mut efi::Guid,
+    ) -> Result<(), EfiError> {
+        let (prev_name, prev_namespace) = (name.clone(), *namespace);
+        if let Err(status) = self.get_next_variable_name_unchecked(&prev_name, &prev_namespace, name, namespace) {
+            (*name, *namespace) = (prev_name, prev_namespace);
+            return Err(status);
+        }
+        truncate_name(name);
+        Ok(())
+    }
+}
+
+/// Cuts a name after its null terminator, keeping the capacity of the buffer.
+#[cfg(feature = "alloc")]
+fn truncate_name(name: &mut Vec<u16>) {
+    if let Some(end) = name.iter().position(|&c| c == 0) {
+        name.truncate(end + 1);
+    }
 }
 
 impl RuntimeServices for StandardRuntimeServices<'_> {
-    unsafe fn get_time_unchecked(&self) -> Result<(Time, TimeCapabilities), efi::Status> {
+    unsafe fn get_time_unchecked(&self) -> Result<(Time, TimeCapabilities), EfiError> {
+        self.check_supported(SupportedServices::GET_TIME)?;
         let get_time = self.efi_runtime_services().get_time;
         if get_time as usize == 0 {
             panic!("function not initialize.")
@@ -383,11 +599,12 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
         let status = get_time(time.as_mut_ptr(), time_capabilities.as_mut_ptr());
         match status {
            efi::Status::SUCCESS => Ok((time.assume_init(), time_capabilities.assume_init())),
-           some_error => Err(some_error)
+           some_error => Err(some_error.into())
         }
     }
 
-    unsafe fn set_time_unchecked(&self, time: &efi::Time) -> Result<(), efi::Status> {
+    unsafe fn set_time_unchecked(&self, time: &efi::Time) -> Result<(), EfiError> {
+        self.check_supported(SupportedServices::SET_TIME)?;
         let set_time = self.efi_runtime_services().set_time;
         if set_time as usize == 0 {
             panic!("function not initialize.")
@@ -395,11 +612,12 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
         let status = set_time(time as *const efi::Time as *mut efi::Time);
         match status {
            efi::Status::SUCCESS => Ok(()),
-           some_error => Err(some_error)
+           some_error => Err(some_error.into())
         }
     }
 
-    unsafe fn get_wakeup_time_unchecked(&self) -> Result<(bool, bool, Time), efi::Status> {
+    unsafe fn get_wakeup_time_unchecked(&self) -> Result<(bool, bool, Time), EfiError> {
+        self.check_supported(SupportedServices::GET_WAKEUP_TIME)?;
         let get_wakeup_time = self.efi_runtime_services().get_wakeup_time;
         if get_wakeup_time as usize == 0 {
             panic!("function not initialize.")
@@ -410,11 +628,12 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
         let status = get_wakeup_time(enabled.as_mut_ptr(), pending.as_mut_ptr(), time.as_mut_ptr());
         match status {
             efi::Status::SUCCESS => Ok((enabled.assume_init().into(), pending.assume_init().into(), time.assume_init())),
-            some_error => Err(some_error)
+            some_error => Err(some_error.into())
         }
     }
 
-    unsafe fn set_wakeup_time_unchecked(&self, enable: bool, time: &efi::Time) -> Result<(), efi::Status> {
+    unsafe fn set_wakeup_time_unchecked(&self, enable: bool, time: &efi::Time) -> Result<(), EfiError> {
+        self.check_supported(SupportedServices::SET_WAKEUP_TIME)?;
         let set_wakeup_time = self.efi_runtime_services().set_wakeup_time;
         if set_wakeup_time as usize == 0 {
             panic!("function not initialize.")
@@ -422,7 +641,7 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
         let status = set_wakeup_time(enable.into(), time as *const efi::Time as *mut efi::Time);
         match status {
            efi::Status::SUCCESS => Ok(()),
-           some_error => Err(some_error)
+           some_error => Err(some_error.into())
         }
     }
 
@@ -432,11 +651,12 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
         namespace: &efi::Guid,
         attributes: u32,
         data: &[u8],
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
+        self.check_supported(SupportedServices::SET_VARIABLE)?;
         let set_variable = self.efi_runtime_services().set_variable;
         if set_variable as usize == 0 {
             debug_assert!(false, "SetVariable has not initialized in the Runtime Services Table.");
-            return Err(efi::Status::NOT_FOUND);
+            return Err(efi::Status::NOT_FOUND.into());
         }
 
         let status = set_variable(
@@ -448,7 +668,7 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
         );
 
         if status.is_error() {
-            Err(status)
+            Err(status.into())
         } else {
             Ok(())
         }
@@ -460,6 +680,9 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
         namespace: &efi::Guid,
         data: Option<&mut [u8]>,
     ) -> GetVariableStatus {
+        if let Err(status) = self.check_supported(SupportedServices::GET_VARIABLE) {
+            return GetVariableStatus::Error(status);
+        }
         let get_variable = self.efi_runtime_services().get_variable;
         if get_variable as usize == 0 {
             debug_assert!(false, "GetVariable has not initialized in the Runtime Services Table.");
@@ -487,68 +710,68 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
             return GetVariableStatus::BufferTooSmall { data_size: data_size, attributes: attributes };
         } else if status.is_error() {
             return GetVariableStatus::Error(status);
+        } else if status.is_warning() {
+            return GetVariableStatus::Warning { status, data_size, attributes };
         }
 
         GetVariableStatus::Success { data_size: data_size, attributes: attributes }
     }
 
+    #[cfg(feature = "alloc")]
     unsafe fn get_next_variable_name_unchecked(
         &self,
         prev_name: &[u16],
         prev_namespace: &efi::Guid,
         next_name: &mut Vec<u16>,
         next_namespace: &mut efi::Guid,
-    ) -> Result<(), efi::Status> {
+    ) -> Result<(), EfiError> {
+        next_name.clear();
+        next_name.extend_from_slice(prev_name);
+        next_namespace.clone_from(prev_namespace);
+        self.get_next_variable_name_in_place(next_name, next_namespace)
+    }
+
+    #[cfg(feature = "alloc")]
+    unsafe fn get_next_variable_name_in_place(
+        &self,
+        name: &mut Vec<u16>,
+        namespace: &mut efi::Guid,
+    ) -> Result<(), EfiError> {
+        self.check_supported(SupportedServices::GET_NEXT_VARIABLE_NAME)?;
         let get_next_variable_name = self.efi_runtime_services().get_next_variable_name;
         if get_next_variable_name as usize == 0 {
             debug_assert!(false, "GetNextVariableName has not initialized in the Runtime Services Table.");
-            return Err(efi::Status::NOT_FOUND);
+            return Err(efi::Status::NOT_FOUND.into());
         }
-
-        // Copy prev_name and namespace into next name and namespace
-        if next_name.len() < prev_name.len() {
-            next_name.resize(prev_name.len(), 0);
+        if !name.contains(&0) {
+            return Err(efi::Status::INVALID_PARAMETER.into());
         }
-        next_name[..prev_name.len()].clone_from_slice(prev_name);
-        next_namespace.clone_from(prev_namespace);
 
-        let mut next_name_size: usize = next_name.len();
-
-        // Loop at most two times. If the size of the previous name is sufficient for the next, then only
-        // one call to the EFI function will be made. Otherwise, the first call will be used to determine
-        // the appropriate size that the buffer should be resized to for the second call.
-        let mut first_try: bool = true;
-        loop {
-            let status =
-                get_next_variable_name(ptr::addr_of_mut!(next_name_size), next_name.as_mut_ptr(), next_namespace);
-
-            if status == efi::Status::BUFFER_TOO_SMALL && first_try {
-                first_try = false;
-
-                assert!(
-                    next_name_size > next_name.len(),
-                    "get_next_variable_name requested smaller buffer on BUFFER_TOO_SMALL."
-                );
+        // The whole capacity of the buffer is given to the firmware, the name staying at its start.
+        name.resize(name.capacity(), 0);
+        let mut name_size = name.len() * mem::size_of::<u16>();
+        let mut status = get_next_variable_name(ptr::addr_of_mut!(name_size), name.as_mut_ptr(), namespace);
 
-                // Resize name to be able to fit the size of the next name
-                next_name.resize(next_name_size, 0);
+        // The firmware leaves the name unchanged when the next one does not fit, the buffer is grown once for it.
+        if status == efi::Status::BUFFER_TOO_SMALL && name_size > name.len() * mem::size_of::<u16>() {
+            name.resize(name_size.div_ceil(mem::size_of::<u16>()), 0);
+            name_size = name.len() * mem::size_of::<u16>();
+            status = get_next_variable_name(ptr::addr_of_mut!(name_size), name.as_mut_ptr(), namespace);
+        }
 
-                // Reset fields which may have been overwritten
-                next_name[..prev_name.len()].clone_from_slice(prev_name);
-                next_namespace.clone_from(prev_namespace);
-            } else if status.is_error() {
-                return Err(status);
-            } else {
-                return Ok(());
-            }
+        truncate_name(name);
+        if status.is_error() {
+            return Err(status.into());
         }
+        Ok(())
     }
 
-    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, efi::Status> {
+    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, EfiError> {
+        self.check_supported(SupportedServices::QUERY_VARIABLE_INFO)?;
         let query_variable_info = self.efi_runtime_services().query_variable_info;
         if query_variable_info as usize == 0 {
             debug_assert!(false, "QueryVariableInfo has not initialized in the Runtime Services Table.");
-            return Err(efi::Status::NOT_FOUND);
+            return Err(efi::Status::NOT_FOUND.into());
         }
 
         let mut var_info = VariableInfo {
@@ -557,19 +780,49 @@ impl RuntimeServices for StandardRuntimeServices<'_> {
             maximum_variable_size: 0,
         };
 
-        let status = query_variable_info(
-            attributes,
-            ptr::addr_of_mut!(var_info.maximum_variable_storage_size),
-            ptr::addr_of_mut!(var_info.remaining_variable_storage_size),
-            ptr::addr_of_mut!(var_info.maximum_variable_size),
-        );
+        let status = unsafe {
+            r_efi::efi_call(
+                query_variable_info,
+                (
+                    attributes,
+                    ptr::addr_of_mut!(var_info.maximum_variable_storage_size),
+                    ptr::addr_of_mut!(var_info.remaining_variable_storage_size),
+                    ptr::addr_of_mut!(var_info.maximum_variable_size),
+                ),
+            )
+        };
 
         if status.is_error() {
-            return Err(status);
+            return Err(status.into());
         } else {
             return Ok(var_info);
         }
     }
+
+    fn get_next_high_monotonic_count(&self) -> Result<u32, EfiError> {
+        self.check_supported(SupportedServices::GET_NEXT_HIGH_MONOTONIC_COUNT)?;
+        let get_next_high_mono_count = self.efi_runtime_services().get_next_high_mono_count;
+        if get_next_high_mono_count as usize == 0 {
+            panic!("function not initialize.")
+        }
+        let mut high_count = 0;
+        match unsafe { r_efi::efi_call(get_next_high_mono_count, (ptr::addr_of_mut!(high_count),)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(high_count),
+        }
+    }
+
+    unsafe fn convert_pointer(&self, debug_disposition: usize, address: *mut *mut c_void) -> Result<(), EfiError> {
+        self.check_supported(SupportedServices::CONVERT_POINTER)?;
+        let convert_pointer = self.efi_runtime_services().convert_pointer;
+        if convert_pointer as usize == 0 {
+            panic!("function not initialize.")
+        }
+        match unsafe { r_efi::efi_call(convert_pointer, (debug_disposition, address)) } {
+            s if s.is_error() => Err(s.into()),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -754,7 +1007,7 @@ pub(crate) mod test {
         // Ensure the name and namespace are as expected
         unsafe {
             // Return invalid parameter if the name isn't null-terminated per UEFI spec
-            if !slice::from_raw_parts(name, *name_size).iter().position(|&c| c == 0).is_some() {
+            if !slice::from_raw_parts(name, *name_size / 2).contains(&0) {
                 return efi::Status::INVALID_PARAMETER;
             }
 
@@ -764,12 +1017,12 @@ pub(crate) mod test {
 
             // If name is an empty string, return the first variable
             if *name == 0 {
-                if *name_size < DUMMY_FIRST_NAME.len() {
-                    *name_size = DUMMY_FIRST_NAME.len();
+                if *name_size < DUMMY_FIRST_NAME.len() * 2 {
+                    *name_size = DUMMY_FIRST_NAME.len() * 2;
                     return efi::Status::BUFFER_TOO_SMALL;
                 }
 
-                *name_size = DUMMY_FIRST_NAME.len();
+                *name_size = DUMMY_FIRST_NAME.len() * 2;
                 ptr::copy_nonoverlapping(DUMMY_FIRST_NAME.as_ptr(), name, DUMMY_FIRST_NAME.len());
                 *namespace = DUMMY_FIRST_NAMESPACE;
 
@@ -780,12 +1033,12 @@ pub(crate) mod test {
             if DUMMY_FIRST_NAME.iter().enumerate().all(|(i, &c)| *name.offset(i as isize) == c) {
                 assert_eq!(*namespace, DUMMY_FIRST_NAMESPACE);
 
-                if *name_size < DUMMY_SECOND_NAME.len() {
-                    *name_size = DUMMY_SECOND_NAME.len();
+                if *name_size < DUMMY_SECOND_NAME.len() * 2 {
+                    *name_size = DUMMY_SECOND_NAME.len() * 2;
                     return efi::Status::BUFFER_TOO_SMALL;
                 }
 
-                *name_size = DUMMY_SECOND_NAME.len();
+                *name_size = DUMMY_SECOND_NAME.len() * 2;
                 ptr::copy_nonoverlapping(DUMMY_SECOND_NAME.as_ptr(), name, DUMMY_SECOND_NAME.len());
                 *namespace = DUMMY_SECOND_NAMESPACE;
 
@@ -848,6 +1101,18 @@ pub(crate) mod test {
         assert_eq!(data.value, DUMMY_DATA);
     }
 
+    #[test]
+    fn test_get_variable_ucs2_name() {
+        let rs: &StandardRuntimeServices<'_> = runtime_services!(get_variable = mock_efi_get_variable);
+
+        let name = ucs2::String16::from_vec_with_nul(DUMMY_FIRST_NAME.to_vec()).unwrap();
+        let status = rs.get_variable_str16::<DummyVariableType>(&name, &DUMMY_FIRST_NAMESPACE, None);
+        assert_eq!(status.unwrap().0.value, DUMMY_DATA);
+
+        let status = rs.get_variable_str16::<DummyVariableType>(name.as_str16(), &DUMMY_FIRST_NAMESPACE, None);
+        assert_eq!(status.unwrap().0.value, DUMMY_DATA);
+    }
+
     #[test]
     #[should_panic(expected = "Name passed into get_variable is not null-terminated.")]
     fn test_get_variable_non_terminated() {
@@ -878,6 +1143,31 @@ pub(crate) mod test {
         assert_eq!(status.unwrap_err(), efi::Status::NOT_FOUND);
     }
 
+    #[test]
+    fn test_get_variable_into() {
+        let rs: &StandardRuntimeServices<'_> = runtime_services!(get_variable = mock_efi_get_variable);
+
+        let mut name = DUMMY_FIRST_NAME;
+        // The mock writes the data as a u32, the buffer is aligned for it.
+        let mut buffer = [0_u32; 1];
+        let data = unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr().cast::<u8>(), DUMMY_DATA_REPR_SIZE) };
+        assert!(matches!(
+            rs.get_variable_into(&mut name, &DUMMY_FIRST_NAMESPACE, &mut []),
+            GetVariableStatus::BufferTooSmall { data_size: DUMMY_DATA_REPR_SIZE, attributes: DUMMY_ATTRIBUTES }
+        ));
+        assert!(matches!(
+            rs.get_variable_into(&mut name, &DUMMY_FIRST_NAMESPACE, data),
+            GetVariableStatus::Success { data_size: DUMMY_DATA_REPR_SIZE, attributes: DUMMY_ATTRIBUTES }
+        ));
+        assert_eq!(DUMMY_DATA.to_ne_bytes(), data[..]);
+
+        let mut name = DUMMY_NON_NULL_TERMINATED_NAME;
+        assert!(matches!(
+            rs.get_variable_into(&mut name, &DUMMY_FIRST_NAMESPACE, data),
+            GetVariableStatus::Error(efi::Status::INVALID_PARAMETER)
+        ));
+    }
+
     #[test]
     fn test_get_variable_size_and_attributes() {
         let rs: &StandardRuntimeServices<'_> = runtime_services!(get_variable = mock_efi_get_variable);
@@ -906,6 +1196,18 @@ pub(crate) mod test {
         assert!(status.is_ok());
     }
 
+    #[test]
+    fn test_set_variable_ucs2_name() {
+        let rs: &StandardRuntimeServices<'_> = runtime_services!(set_variable = mock_efi_set_variable);
+
+        let name = ucs2::String16::from_vec_with_nul(DUMMY_FIRST_NAME.to_vec()).unwrap();
+        let data = DummyVariableType { value: DUMMY_DATA };
+
+        let status = rs.set_variable_str16::<DummyVariableType>(&name, &DUMMY_FIRST_NAMESPACE, DUMMY_ATTRIBUTES, &data);
+
+        assert!(status.is_ok());
+    }
+
     #[test]
     #[should_panic(expected = "Name passed into set_variable is not null-terminated.")]
     fn test_set_variable_non_terminated() {
@@ -1026,4 +1328,67 @@ pub(crate) mod test {
         assert!(status.is_err());
         assert_eq!(status.unwrap_err(), efi::Status::INVALID_PARAMETER);
     }
+
+    #[test]
+    fn test_get_next_high_monotonic_count() {
+        extern "efiapi" fn efi_get_next_high_mono_count(high_count: *mut u32) -> efi::Status {
+            unsafe { *high_count = 0x1234 };
+            efi::Status::SUCCESS
+        }
+
+        let rs = runtime_services!(get_next_high_mono_count = efi_get_next_high_mono_count);
+        assert_eq!(Ok(0x1234), rs.get_next_high_monotonic_count());
+    }
+
+    #[test]
+    fn test_get_next_high_monotonic_count_error() {
+        extern "efiapi" fn efi_get_next_high_mono_count(_high_count: *mut u32) -> efi::Status {
+            efi::Status::DEVICE_ERROR
+        }
+
+        let rs = runtime_services!(get_next_high_mono_count = efi_get_next_high_mono_count);
+        assert_eq!(Err(efi::Status::DEVICE_ERROR.into()), rs.get_next_high_monotonic_count());
+    }
+
+    #[test]
+    fn test_convert_pointer() {
+        extern "efiapi" fn efi_convert_pointer(debug_disposition: usize, address: *mut *mut c_void) -> efi::Status {
+            match unsafe { *address } {
+                p if p.is_null() && debug_disposition & efi::OPTIONAL_POINTER as usize != 0 => efi::Status::SUCCESS,
+                p if p.is_null() => efi::Status::INVALID_PARAMETER,
+                p => {
+                    unsafe { *address = (p as usize + 0x1000) as *mut c_void };
+                    efi::Status::SUCCESS
+                }
+            }
+        }
+
+        let rs = runtime_services!(convert_pointer = efi_convert_pointer);
+        let mut address = 0x2000 as *mut c_void;
+        assert_eq!(Ok(()), unsafe { rs.convert_pointer(0, &mut address) });
+        assert_eq!(0x3000, address as usize);
+        let mut address = ptr::null_mut();
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), unsafe { rs.convert_pointer(0, &mut address) });
+        assert_eq!(Ok(()), unsafe { rs.convert_pointer(efi::OPTIONAL_POINTER as usize, &mut address) });
+    }
+
+    #[test]
+    fn test_unsupported_services() {
+        extern "efiapi" fn efi_get_next_high_mono_count(_: *mut u32) -> efi::Status {
+            panic!("unsupported service called");
+        }
+
+        let rs = runtime_services!(
+            get_next_high_mono_count = efi_get_next_high_mono_count,
+            get_variable = mock_efi_get_variable
+        );
+        rs.set_supported_services(SupportedServices::GET_VARIABLE);
+        assert_eq!(SupportedServices::GET_VARIABLE, rs.supported_services());
+        assert_eq!(Err(efi::Status::UNSUPPORTED.into()), rs.get_next_high_monotonic_count());
+        assert_eq!(
+            Err(efi::Status::UNSUPPORTED.into()),
+            rs.set_variable(&DUMMY_FIRST_NAME, &DUMMY_FIRST_NAMESPACE, 0, &[0u8])
+        );
+        assert!(rs.get_variable::<Vec<u8>>(&DUMMY_FIRST_NAME, &DUMMY_FIRST_NAMESPACE, None).is_ok());
+    }
 }
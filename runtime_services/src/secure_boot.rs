@@ -0,0 +1,640 @@
+use alloc::vec::Vec;
+use core::{mem, ptr};
+
+use r_efi::efi;
+use status::EfiError;
+use zerocopy::{little_endian::U32, FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use crate::RuntimeServices;
+
+/// Namespace of the `db` and `dbx` variables
+pub const IMAGE_SECURITY_DATABASE_GUID: efi::Guid =
+    efi::Guid::from_fields(0xd719b2cb, 0x3d3a, 0x4596, 0xa3, 0xbc, &[0xda, 0xd0, 0x0e, 0x67, 0x65, 0x6f]);
+
+/// Name of the authorized signature database, `db`
+pub const DB_NAME: [u16; 3] = [b'd' as u16, b'b' as u16, 0];
+/// Name of the forbidden signature database, `dbx`
+pub const DBX_NAME: [u16; 4] = [b'd' as u16, b'b' as u16, b'x' as u16, 0];
+
+/// Namespace of the `PK` and `KEK` variables, `EFI_GLOBAL_VARIABLE`
+pub const GLOBAL_VARIABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x8be4df61, 0x93ca, 0x11d2, 0xaa, 0x0d, &[0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c]);
+
+/// Name of the platform key, `PK`
+pub const PK_NAME: [u16; 3] = [b'P' as u16, b'K' as u16, 0];
+/// Name of the key exchange key database, `KEK`
+pub const KEK_NAME: [u16; 4] = [b'K' as u16, b'E' as u16, b'K' as u16, 0];
+
+/// Attributes of the `PK`, `KEK`, `db` and `dbx` variables
+pub const SECURE_BOOT_VARIABLE_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE
+    | efi::VARIABLE_BOOTSERVICE_ACCESS
+    | efi::VARIABLE_RUNTIME_ACCESS
+    | efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+
+/// Type of the PKCS#7 signatures of the authenticated variables
+pub const CERT_TYPE_PKCS7_GUID: efi::Guid =
+    efi::Guid::from_fields(0x4aafd29d, 0x68df, 0x49ee, 0x8a, 0xa9, &[0x34, 0x7d, 0x37, 0x56, 0x65, 0xa7]);
+
+/// SHA-1 digest of an image
+pub const CERT_SHA1_GUID: efi::Guid =
+    efi::Guid::from_fields(0x826ca512, 0xcf10, 0x4ac9, 0xb1, 0x87, &[0xbe, 0x01, 0x49, 0x66, 0x31, 0xbd]);
+/// SHA-256 digest of an image
+pub const CERT_SHA256_GUID: efi::Guid =
+    efi::Guid::from_fields(0xc1c41626, 0x504c, 0x4092, 0xac, 0xa9, &[0x41, 0xf9, 0x36, 0x93, 0x43, 0x28]);
+/// SHA-384 digest of an image
+pub const CERT_SHA384_GUID: efi::Guid =
+    efi::Guid::from_fields(0xff3e5307, 0x9fd0, 0x48c9, 0x85, 0xf1, &[0x8a, 0xd5, 0x6c, 0x70, 0x1e, 0x01]);
+/// SHA-512 digest of an image
+pub const CERT_SHA512_GUID: efi::Guid =
+    efi::Guid::from_fields(0x093e0fae, 0xa6c4, 0x4f50, 0x9f, 0x1b, &[0xd4, 0x1e, 0x2b, 0x89, 0xc1, 0x9a]);
+/// DER encoded X.509 certificate
+pub const CERT_X509_GUID: efi::Guid =
+    efi::Guid::from_fields(0xa5c059a1, 0x94e4, 0x4aa7, 0x87, 0xb5, &[0xab, 0x15, 0x5c, 0x2b, 0xf0, 0x72]);
+/// SHA-256 digest of the `TBSCertificate` of an X.509 certificate, followed by its time of revocation
+pub const CERT_X509_SHA256_GUID: efi::Guid =
+    efi::Guid::from_fields(0x3bd2a492, 0x96c0, 0x4079, 0xb4, 0x20, &[0xfc, 0xf9, 0x8e, 0xf1, 0x03, 0xed]);
+/// SHA-384 digest of the `TBSCertificate` of an X.509 certificate, followed by its time of revocation
+pub const CERT_X509_SHA384_GUID: efi::Guid =
+    efi::Guid::from_fields(0x7076876e, 0x80c2, 0x4ee6, 0xaa, 0xd2, &[0x28, 0xb3, 0x49, 0xa6, 0x86, 0x5b]);
+/// SHA-512 digest of the `TBSCertificate` of an X.509 certificate, followed by its time of revocation
+pub const CERT_X509_SHA512_GUID: efi::Guid =
+    efi::Guid::from_fields(0x446dbf63, 0x2502, 0x4cda, 0xbc, 0xfa, &[0x24, 0x65, 0xd2, 0xb0, 0xfe, 0x9d]);
+
+/// FFI definition of `EFI_SIGNATURE_LIST`, followed by the header of the list and its signatures
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct SignatureListHeader {
+    pub signature_type: [u8; 16],
+    /// Size of the whole list, in bytes
+    pub signature_list_size: U32,
+    pub signature_header_size: U32,
+    pub signature_size: U32,
+}
+
+/// Size of `EFI_SIGNATURE_LIST`, before the header of the list
+const SIGNATURE_LIST_SIZE: usize = mem::size_of::<SignatureListHeader>();
+
+/// Size of the `SignatureOwner` starting every `EFI_SIGNATURE_DATA`
+const SIGNATURE_OWNER_SIZE: usize = 16;
+
+/// Size of `WIN_CERTIFICATE_UEFI_GUID`, before the signature
+const WIN_CERTIFICATE_UEFI_GUID_SIZE: usize = 24;
+const WIN_CERT_REVISION: u16 = 0x0200;
+const WIN_CERT_TYPE_EFI_GUID: u16 = 0x0EF1;
+
+/// A signature of a signature list, `EFI_SIGNATURE_DATA`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureData {
+    /// The agent which added the signature
+    pub owner: efi::Guid,
+    /// The signature, whose format is given by the type of the list
+    pub data: Vec<u8>,
+}
+
+/// A list of signatures of the same type, `EFI_SIGNATURE_LIST`
+///
+/// UEFI Spec Documentation: [32.4.1. Signature Database](https://uefi.org/specs/UEFI/2.10/32_Secure_Boot_and_Driver_Signing.html#signature-database)
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureList {
+    /// The type of the signatures, like [`CERT_SHA256_GUID`]
+    pub signature_type: efi::Guid,
+    /// The header specific to the type of the signatures
+    pub header: Vec<u8>,
+    /// The size of every `EFI_SIGNATURE_DATA`, owner included
+    pub signature_size: usize,
+    pub signatures: Vec<SignatureData>,
+}
+
+impl SignatureList {
+    /// Creates a list of `signatures` of `signature_type`, without header
+    ///
+    /// Returns `INVALID_PARAMETER` if there is no signature or if their sizes differ.
+    pub fn new(signature_type: efi::Guid, signatures: Vec<SignatureData>) -> Result<Self, EfiError> {
+        let data_size = signatures.first().ok_or(efi::Status::INVALID_PARAMETER)?.data.len();
+        if signatures.iter().any(|signature| signature.data.len() != data_size) {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        Ok(Self { signature_type, header: Vec::new(), signature_size: SIGNATURE_OWNER_SIZE + data_size, signatures })
+    }
+
+    /// Serializes the list into an `EFI_SIGNATURE_LIST`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = SIGNATURE_LIST_SIZE + self.header.len() + self.signature_size * self.signatures.len();
+        let header = SignatureListHeader {
+            signature_type: *self.signature_type.as_bytes(),
+            signature_list_size: U32::new(size as u32),
+            signature_header_size: U32::new(self.header.len() as u32),
+            signature_size: U32::new(self.signature_size as u32),
+        };
+        let mut list = Vec::with_capacity(size);
+        list.extend_from_slice(header.as_bytes());
+        list.extend_from_slice(&self.header);
+        for signature in &self.signatures {
+            debug_assert_eq!(self.signature_size, SIGNATURE_OWNER_SIZE + signature.data.len());
+            list.extend_from_slice(signature.owner.as_bytes());
+            list.extend_from_slice(&signature.data);
+        }
+        list
+    }
+}
+
+/// Serializes signature lists into a signature database
+pub fn signature_lists_to_bytes(lists: &[SignatureList]) -> Vec<u8> {
+    lists.iter().flat_map(SignatureList::to_bytes).collect()
+}
+
+fn guid_at(data: &[u8], offset: usize) -> efi::Guid {
+    efi::Guid::from_bytes(data[offset..offset + 16].try_into().unwrap())
+}
+
+/// Parses a signature database, like the data of the `db` and `dbx` variables, into its signature lists.
+///
+/// Returns `INVALID_PARAMETER` if a list is malformed.
+pub fn parse_signature_lists(mut data: &[u8]) -> Result<Vec<SignatureList>, EfiError> {
+    let mut lists = Vec::new();
+    while !data.is_empty() {
+        let (list_header, _) =
+            SignatureListHeader::ref_from_prefix(data).map_err(|_| efi::Status::INVALID_PARAMETER)?;
+        let list_size = list_header.signature_list_size.get() as usize;
+        let header_size = list_header.signature_header_size.get() as usize;
+        let signature_size = list_header.signature_size.get() as usize;
+        let signatures_size = match SIGNATURE_LIST_SIZE.checked_add(header_size) {
+            Some(size) if size <= list_size && list_size <= data.len() => list_size - size,
+            _ => return Err(efi::Status::INVALID_PARAMETER.into()),
+        };
+        if signature_size < SIGNATURE_OWNER_SIZE || signatures_size % signature_size != 0 {
+            return Err(efi::Status::INVALID_PARAMETER.into());
+        }
+        let signatures = data[SIGNATURE_LIST_SIZE + header_size..list_size]
+            .chunks_exact(signature_size)
+            .map(|signature| SignatureData {
+                owner: guid_at(signature, 0),
+                data: signature[SIGNATURE_OWNER_SIZE..].to_vec(),
+            })
+            .collect();
+        lists.push(SignatureList {
+            signature_type: efi::Guid::from_bytes(&list_header.signature_type),
+            header: data[SIGNATURE_LIST_SIZE..SIGNATURE_LIST_SIZE + header_size].to_vec(),
+            signature_size,
+            signatures,
+        });
+        data = &data[list_size..];
+    }
+    Ok(lists)
+}
+
+/// Reads and parses a signature database variable, like [`DBX_NAME`] in [`IMAGE_SECURITY_DATABASE_GUID`].
+///
+/// A missing variable is an empty database.
+pub fn read_signature_lists<R>(
+    runtime_services: &R,
+    name: &[u16],
+    namespace: &efi::Guid,
+) -> Result<Vec<SignatureList>, EfiError>
+where
+    R: RuntimeServices,
+{
+    match runtime_services.get_variable::<Vec<u8>>(name, namespace, None) {
+        Ok((data, _)) => parse_signature_lists(&data),
+        Err(status) if status == efi::Status::NOT_FOUND => Ok(Vec::new()),
+        Err(some_error) => Err(some_error),
+    }
+}
+
+/// An entry of `dbx` matching an image or a certificate
+#[derive(Debug, Clone, Copy)]
+pub struct Revocation<'a> {
+    /// The type of the list of the entry
+    pub signature_type: efi::Guid,
+    /// The entry
+    pub signature: &'a SignatureData,
+    /// The time from which the signatures of the certificate are revoked, for the digests of certificates
+    pub time_of_revocation: Option<efi::Time>,
+}
+
+fn find_signature<'a>(
+    dbx: &'a [SignatureList],
+    signature_type: &efi::Guid,
+    matches: impl Fn(&[u8]) -> bool,
+) -> Option<&'a SignatureData> {
+    dbx.iter()
+        .filter(|list| list.signature_type == *signature_type)
+        .flat_map(|list| list.signatures.iter())
+        .find(|signature| matches(&signature.data))
+}
+
+/// Checks the digest of an image against `dbx`.
+///
+/// `digest_type` is the type of the digest, like [`CERT_SHA256_GUID`] for the SHA-256 Authenticode digest of the
+/// image.
+pub fn check_image_digest<'a>(
+    dbx: &'a [SignatureList],
+    digest_type: &efi::Guid,
+    digest: &[u8],
+) -> Option<Revocation<'a>> {
+    find_signature(dbx, digest_type, |data| data == digest).map(|signature| Revocation {
+        signature_type: *digest_type,
+        signature,
+        time_of_revocation: None,
+    })
+}
+
+/// Checks a DER encoded certificate against `dbx`, as a whole and by the digests of its `TBSCertificate`.
+///
+/// `tbs_digests` are the digests of the `TBSCertificate` of the certificate, with their type like
+/// [`CERT_X509_SHA256_GUID`]; the entries of the types without a digest are not checked.
+pub fn check_certificate<'a>(
+    dbx: &'a [SignatureList],
+    certificate: &[u8],
+    tbs_digests: &[(efi::Guid, &[u8])],
+) -> Option<Revocation<'a>> {
+    if let Some(signature) = find_signature(dbx, &CERT_X509_GUID, |data| data == certificate) {
+        return Some(Revocation { signature_type: CERT_X509_GUID, signature, time_of_revocation: None });
+    }
+    tbs_digests.iter().find_map(|(digest_type, digest)| {
+        // The digest is followed by its time of revocation.
+        let signature = find_signature(dbx, digest_type, |data| {
+            data.len() == digest.len() + mem::size_of::<efi::Time>() && data.starts_with(digest)
+        })?;
+        //SAFETY: The entry holds a whole time after the digest.
+        let time_of_revocation =
+            unsafe { ptr::read_unaligned(signature.data[digest.len()..].as_ptr() as *const efi::Time) };
+        Some(Revocation { signature_type: *digest_type, signature, time_of_revocation: Some(time_of_revocation) })
+    })
+}
+
+/// The authentication of a write of a Secure Boot key variable, from which its `EFI_VARIABLE_AUTHENTICATION_2` is
+/// built
+///
+/// UEFI Spec Documentation: [8.2.2. Using the EFI_VARIABLE_AUTHENTICATION_2 descriptor](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#using-the-efi-variable-authentication-2-descriptor)
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Authentication<'a> {
+    /// The time of the write, later than the one of the previous write of the variable unless appending
+    pub timestamp: efi::Time,
+    /// The DER encoded PKCS#7 `SignedData` of the [`signed_data`] of the write, by a key of the parent variable
+    pub signature: &'a [u8],
+}
+
+impl Authentication<'_> {
+    /// The authentication of a write in setup mode, which is not signed
+    pub const fn setup_mode(timestamp: efi::Time) -> Self {
+        Self { timestamp, signature: &[] }
+    }
+
+    /// The `EFI_VARIABLE_AUTHENTICATION_2` of the write, followed by `data`
+    pub fn payload(&self, data: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(16 + WIN_CERTIFICATE_UEFI_GUID_SIZE + self.signature.len() + data.len());
+        payload.extend_from_slice(&timestamp_bytes(&self.timestamp));
+        payload.extend_from_slice(&((WIN_CERTIFICATE_UEFI_GUID_SIZE + self.signature.len()) as u32).to_le_bytes());
+        payload.extend_from_slice(&WIN_CERT_REVISION.to_le_bytes());
+        payload.extend_from_slice(&WIN_CERT_TYPE_EFI_GUID.to_le_bytes());
+        payload.extend_from_slice(CERT_TYPE_PKCS7_GUID.as_bytes());
+        payload.extend_from_slice(self.signature);
+        payload.extend_from_slice(data);
+        payload
+    }
+}
+
+/// The `EFI_TIME` of a timestamp, whose fields other than the date and the time must be zero
+fn timestamp_bytes(time: &efi::Time) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    bytes[0..2].copy_from_slice(&time.year.to_le_bytes());
+    bytes[2..7].copy_from_slice(&[time.month, time.day, time.hour, time.minute, time.second]);
+    bytes
+}
+
+/// The data to sign to authenticate a write of `data` to a variable, with `attributes` and `timestamp`
+///
+/// `attributes` are [`SECURE_BOOT_VARIABLE_ATTRIBUTES`], with `VARIABLE_APPEND_WRITE` for the appends.
+pub fn signed_data<N>(name: &N, namespace: &efi::Guid, attributes: u32, timestamp: &efi::Time, data: &[u8]) -> Vec<u8>
+where
+    N: AsRef<[u16]> + ?Sized,
+{
+    let mut signed_data = Vec::new();
+    // The name is signed without its null terminator.
+    name.as_ref().iter().take_while(|&&c| c != 0).for_each(|c| signed_data.extend_from_slice(&c.to_le_bytes()));
+    signed_data.extend_from_slice(namespace.as_bytes());
+    signed_data.extend_from_slice(&attributes.to_le_bytes());
+    signed_data.extend_from_slice(&timestamp_bytes(timestamp));
+    signed_data.extend_from_slice(data);
+    signed_data
+}
+
+fn write_key_variable<R>(
+    runtime_services: &R,
+    name: &[u16],
+    namespace: &efi::Guid,
+    append: bool,
+    data: &[u8],
+    authentication: &Authentication,
+) -> Result<(), EfiError>
+where
+    R: RuntimeServices,
+{
+    let attributes = match append {
+        true => SECURE_BOOT_VARIABLE_ATTRIBUTES | efi::VARIABLE_APPEND_WRITE,
+        false => SECURE_BOOT_VARIABLE_ATTRIBUTES,
+    };
+    runtime_services.set_variable(name, namespace, attributes, &authentication.payload(data))
+}
+
+/// Enrolls the platform key, a list of a single X.509 certificate, which leaves setup mode
+///
+/// Returns `INVALID_PARAMETER` if `pk` is not a single certificate.
+pub fn enroll_pk<R: RuntimeServices>(
+    runtime_services: &R,
+    pk: &SignatureList,
+    authentication: &Authentication,
+) -> Result<(), EfiError> {
+    if pk.signature_type != CERT_X509_GUID || pk.signatures.len() != 1 {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    write_key_variable(runtime_services, &PK_NAME, &GLOBAL_VARIABLE_GUID, false, &pk.to_bytes(), authentication)
+}
+
+/// Replaces the key exchange keys with `kek`, authenticated by the platform key
+pub fn enroll_kek<R: RuntimeServices>(
+    runtime_services: &R,
+    kek: &[SignatureList],
+    authentication: &Authentication,
+) -> Result<(), EfiError> {
+    let data = signature_lists_to_bytes(kek);
+    write_key_variable(runtime_services, &KEK_NAME, &GLOBAL_VARIABLE_GUID, false, &data, authentication)
+}
+
+/// Appends `lists` to the authorized signature database, authenticated by a key exchange key
+pub fn append_db<R: RuntimeServices>(
+    runtime_services: &R,
+    lists: &[SignatureList],
+    authentication: &Authentication,
+) -> Result<(), EfiError> {
+    let data = signature_lists_to_bytes(lists);
+    write_key_variable(runtime_services, &DB_NAME, &IMAGE_SECURITY_DATABASE_GUID, true, &data, authentication)
+}
+
+/// Appends `lists` to the forbidden signature database, authenticated by a key exchange key
+pub fn append_dbx<R: RuntimeServices>(
+    runtime_services: &R,
+    lists: &[SignatureList],
+    authentication: &Authentication,
+) -> Result<(), EfiError> {
+    let data = signature_lists_to_bytes(lists);
+    write_key_variable(runtime_services, &DBX_NAME, &IMAGE_SECURITY_DATABASE_GUID, true, &data, authentication)
+}
+
+/// Deletes the Secure Boot keys, returning to setup mode
+///
+/// The platform key is deleted first with `pk_authentication`, signed by the platform key unless already in setup
+/// mode; `KEK`, `db` and `dbx` are then deleted in setup mode, with the same timestamp. Missing variables are
+/// skipped.
+pub fn clear_secure_boot_keys<R: RuntimeServices>(
+    runtime_services: &R,
+    pk_authentication: &Authentication,
+) -> Result<(), EfiError> {
+    let delete = |result: Result<(), EfiError>| match result {
+        Err(status) if status == efi::Status::NOT_FOUND => Ok(()),
+        result => result,
+    };
+    let setup_mode = Authentication::setup_mode(pk_authentication.timestamp);
+    delete(write_key_variable(runtime_services, &PK_NAME, &GLOBAL_VARIABLE_GUID, false, &[], pk_authentication))?;
+    delete(write_key_variable(runtime_services, &KEK_NAME, &GLOBAL_VARIABLE_GUID, false, &[], &setup_mode))?;
+    delete(write_key_variable(runtime_services, &DB_NAME, &IMAGE_SECURITY_DATABASE_GUID, false, &[], &setup_mode))?;
+    delete(write_key_variable(runtime_services, &DBX_NAME, &IMAGE_SECURITY_DATABASE_GUID, false, &[], &setup_mode))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::{test::runtime_services, StandardRuntimeServices};
+    use alloc::vec;
+    use core::{ffi::c_void, slice};
+
+    pub(crate) const OWNER: efi::Guid =
+        efi::Guid::from_fields(0x77fa9abd, 0x0359, 0x4d32, 0xbd, 0x60, &[0x28, 0xf4, 0xe7, 0x8f, 0x78, 0x4b]);
+
+    /// An `EFI_SIGNATURE_LIST` of `signatures` owned by [`OWNER`].
+    pub(crate) fn signature_list(signature_type: &efi::Guid, signatures: &[&[u8]]) -> Vec<u8> {
+        let signature_size = SIGNATURE_OWNER_SIZE + signatures[0].len();
+        let mut list = Vec::new();
+        list.extend_from_slice(signature_type.as_bytes());
+        list.extend_from_slice(&((SIGNATURE_LIST_SIZE + signature_size * signatures.len()) as u32).to_le_bytes());
+        list.extend_from_slice(&0u32.to_le_bytes());
+        list.extend_from_slice(&(signature_size as u32).to_le_bytes());
+        for signature in signatures {
+            list.extend_from_slice(OWNER.as_bytes());
+            list.extend_from_slice(signature);
+        }
+        list
+    }
+
+    fn revocation_time() -> efi::Time {
+        efi::Time { year: 2023, month: 5, day: 9, ..Default::default() }
+    }
+
+    fn dbx() -> Vec<u8> {
+        let mut x509_sha256 = [3; 32].to_vec();
+        let time = revocation_time();
+        x509_sha256.extend_from_slice(unsafe {
+            slice::from_raw_parts(&time as *const efi::Time as *const u8, mem::size_of::<efi::Time>())
+        });
+        let mut dbx = signature_list(&CERT_SHA256_GUID, &[&[1; 32], &[2; 32]]);
+        dbx.extend(signature_list(&CERT_X509_GUID, &[b"certificate"]));
+        dbx.extend(signature_list(&CERT_X509_SHA256_GUID, &[&x509_sha256]));
+        dbx
+    }
+
+    #[test]
+    fn test_parse_signature_lists() {
+        let lists = parse_signature_lists(&dbx()).unwrap();
+        assert_eq!(3, lists.len());
+        assert_eq!(CERT_SHA256_GUID, lists[0].signature_type);
+        assert_eq!(48, lists[0].signature_size);
+        assert_eq!(SignatureData { owner: OWNER, data: [2; 32].to_vec() }, lists[0].signatures[1]);
+        assert_eq!(b"certificate", &lists[1].signatures[0].data[..]);
+        assert!(parse_signature_lists(&[]).unwrap().is_empty());
+
+        let mut dbx = dbx();
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), parse_signature_lists(&dbx[..dbx.len() - 1]));
+        // A list size which is not made of whole signatures.
+        dbx[16] -= 1;
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), parse_signature_lists(&dbx));
+    }
+
+    #[test]
+    fn test_check_dbx() {
+        let dbx = parse_signature_lists(&dbx()).unwrap();
+        let revocation = check_image_digest(&dbx, &CERT_SHA256_GUID, &[2; 32]).unwrap();
+        assert_eq!(&dbx[0].signatures[1], revocation.signature);
+        assert!(check_image_digest(&dbx, &CERT_SHA256_GUID, &[3; 32]).is_none());
+        assert!(check_image_digest(&dbx, &CERT_SHA384_GUID, &[2; 32]).is_none());
+
+        let revocation = check_certificate(&dbx, b"certificate", &[]).unwrap();
+        assert_eq!(CERT_X509_GUID, revocation.signature_type);
+        assert!(revocation.time_of_revocation.is_none());
+
+        let revocation = check_certificate(&dbx, b"other", &[(CERT_X509_SHA256_GUID, &[3; 32])]).unwrap();
+        assert_eq!(CERT_X509_SHA256_GUID, revocation.signature_type);
+        let time = revocation.time_of_revocation.unwrap();
+        assert_eq!((2023, 5, 9), (time.year, time.month, time.day));
+        assert!(check_certificate(&dbx, b"other", &[(CERT_X509_SHA256_GUID, &[4; 32])]).is_none());
+        assert!(check_certificate(&dbx, b"other", &[(CERT_X509_SHA384_GUID, &[3; 32])]).is_none());
+    }
+
+    extern "efiapi" fn get_dbx(
+        name: *mut u16,
+        namespace: *mut efi::Guid,
+        attributes: *mut u32,
+        data_size: *mut usize,
+        data: *mut c_void,
+    ) -> efi::Status {
+        unsafe {
+            assert_eq!(DBX_NAME, slice::from_raw_parts(name, DBX_NAME.len()));
+            assert_eq!(IMAGE_SECURITY_DATABASE_GUID, *namespace);
+            let dbx = signature_list(&CERT_SHA256_GUID, &[&[1; 32]]);
+            *attributes = 0;
+            if *data_size < dbx.len() {
+                *data_size = dbx.len();
+                return efi::Status::BUFFER_TOO_SMALL;
+            }
+            *data_size = dbx.len();
+            slice::from_raw_parts_mut(data as *mut u8, dbx.len()).copy_from_slice(&dbx);
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_missing_variable(
+        _name: *mut u16,
+        _namespace: *mut efi::Guid,
+        _attributes: *mut u32,
+        _data_size: *mut usize,
+        _data: *mut c_void,
+    ) -> efi::Status {
+        efi::Status::NOT_FOUND
+    }
+
+    #[test]
+    fn test_signature_list_to_bytes() {
+        let dbx = dbx();
+        let lists = parse_signature_lists(&dbx).unwrap();
+        assert_eq!(dbx, signature_lists_to_bytes(&lists));
+
+        let signature = |data: &[u8]| SignatureData { owner: OWNER, data: data.to_vec() };
+        let list = SignatureList::new(CERT_SHA256_GUID, vec![signature(&[1; 32]), signature(&[2; 32])]).unwrap();
+        assert_eq!(dbx[..list.to_bytes().len()], list.to_bytes());
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            SignatureList::new(CERT_SHA256_GUID, vec![signature(&[1; 32]), signature(&[2; 20])])
+        );
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), SignatureList::new(CERT_SHA256_GUID, Vec::new()));
+    }
+
+    /// Name, namespace, attributes and data of a write.
+    type Write = (Vec<u16>, efi::Guid, u32, Vec<u8>);
+
+    std::thread_local! {
+        static WRITES: core::cell::RefCell<Vec<Write>> = Default::default();
+    }
+
+    /// Records the writes, with `NOT_FOUND` for the deletions of `db`.
+    extern "efiapi" fn record_set_variable(
+        name: *mut u16,
+        namespace: *mut efi::Guid,
+        attributes: u32,
+        data_size: usize,
+        data: *mut c_void,
+    ) -> efi::Status {
+        let (name, data) = unsafe {
+            let length = (0..).position(|i| *name.add(i) == 0).unwrap();
+            (slice::from_raw_parts(name, length).to_vec(), slice::from_raw_parts(data as *const u8, data_size).to_vec())
+        };
+        if name == DB_NAME[..2] && data_size == 40 {
+            return efi::Status::NOT_FOUND;
+        }
+        WRITES.with(|writes| writes.borrow_mut().push((name, unsafe { *namespace }, attributes, data)));
+        efi::Status::SUCCESS
+    }
+
+    fn timestamp() -> efi::Time {
+        efi::Time { year: 2024, month: 1, day: 2, hour: 3, minute: 4, second: 5, nanosecond: 6, ..Default::default() }
+    }
+
+    #[test]
+    fn test_enrollment() {
+        let rs: &StandardRuntimeServices<'_> = runtime_services!(set_variable = record_set_variable);
+        let certificate =
+            SignatureList::new(CERT_X509_GUID, vec![SignatureData { owner: OWNER, data: b"PK".to_vec() }]).unwrap();
+        let authentication = Authentication::setup_mode(timestamp());
+        enroll_pk(rs, &certificate, &authentication).unwrap();
+        enroll_kek(rs, &[certificate.clone()], &Authentication { timestamp: timestamp(), signature: b"pkcs7" })
+            .unwrap();
+        let digests = parse_signature_lists(&dbx()).unwrap();
+        append_db(rs, &[certificate.clone()], &authentication).unwrap();
+        append_dbx(rs, &digests, &authentication).unwrap();
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER.into()),
+            enroll_pk(rs, &digests[0], &authentication),
+            "The platform key must be a certificate."
+        );
+
+        let writes = WRITES.with(|writes| writes.take());
+        let names = writes.iter().map(|write| (write.0.clone(), write.1, write.2)).collect::<Vec<_>>();
+        let append = SECURE_BOOT_VARIABLE_ATTRIBUTES | efi::VARIABLE_APPEND_WRITE;
+        assert_eq!(
+            vec![
+                (PK_NAME[..2].to_vec(), GLOBAL_VARIABLE_GUID, SECURE_BOOT_VARIABLE_ATTRIBUTES),
+                (KEK_NAME[..3].to_vec(), GLOBAL_VARIABLE_GUID, SECURE_BOOT_VARIABLE_ATTRIBUTES),
+                (DB_NAME[..2].to_vec(), IMAGE_SECURITY_DATABASE_GUID, append),
+                (DBX_NAME[..3].to_vec(), IMAGE_SECURITY_DATABASE_GUID, append),
+            ],
+            names
+        );
+
+        // The timestamp without its nanoseconds, then the WIN_CERTIFICATE_UEFI_GUID of the signature.
+        let pk = &writes[0].3;
+        assert_eq!([0xE8, 0x07, 1, 2, 3, 4, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0], pk[..16]);
+        assert_eq!([24, 0, 0, 0, 0x00, 0x02, 0xF1, 0x0E], pk[16..24]);
+        assert_eq!(CERT_TYPE_PKCS7_GUID.as_bytes(), &pk[24..40]);
+        assert_eq!(certificate.to_bytes(), pk[40..]);
+        let kek = &writes[1].3;
+        assert_eq!(29, kek[16]);
+        assert_eq!(b"pkcs7", &kek[40..45]);
+        assert_eq!(certificate.to_bytes(), kek[45..]);
+        assert_eq!(dbx(), writes[3].3[40..]);
+    }
+
+    #[test]
+    fn test_clear_secure_boot_keys() {
+        let rs: &StandardRuntimeServices<'_> = runtime_services!(set_variable = record_set_variable);
+        clear_secure_boot_keys(rs, &Authentication { timestamp: timestamp(), signature: b"pkcs7" }).unwrap();
+
+        // The deletion of the missing db is skipped.
+        let writes = WRITES.with(|writes| writes.take());
+        assert_eq!(3, writes.len());
+        assert_eq!(PK_NAME[..2], writes[0].0);
+        assert_eq!(45, writes[0].3.len());
+        assert_eq!(DBX_NAME[..3], writes[2].0);
+        assert_eq!(40, writes[2].3.len());
+        assert!(writes.iter().all(|write| write.2 == SECURE_BOOT_VARIABLE_ATTRIBUTES));
+    }
+
+    #[test]
+    fn test_signed_data() {
+        let data = signed_data(&PK_NAME, &GLOBAL_VARIABLE_GUID, SECURE_BOOT_VARIABLE_ATTRIBUTES, &timestamp(), b"data");
+        assert_eq!([b'P', 0, b'K', 0], data[..4]);
+        assert_eq!(GLOBAL_VARIABLE_GUID.as_bytes(), &data[4..20]);
+        assert_eq!(0x27u32.to_le_bytes(), data[20..24]);
+        assert_eq!(b"data", &data[40..]);
+    }
+
+    #[test]
+    fn test_read_signature_lists() {
+        let rs: &StandardRuntimeServices<'_> = runtime_services!(get_variable = get_dbx);
+        let dbx = read_signature_lists(rs, &DBX_NAME, &IMAGE_SECURITY_DATABASE_GUID).unwrap();
+        assert!(check_image_digest(&dbx, &CERT_SHA256_GUID, &[1; 32]).is_some());
+
+        let rs: &StandardRuntimeServices<'_> = runtime_services!(get_variable = get_missing_variable);
+        assert!(read_signature_lists(rs, &DBX_NAME, &IMAGE_SECURITY_DATABASE_GUID).unwrap().is_empty());
+    }
+}
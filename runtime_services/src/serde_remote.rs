@@ -0,0 +1,112 @@
+//! Serde definitions for the r-efi types returned by the runtime services.
+//!
+//! The r-efi types can not implement the serde traits, these definitions are used through `#[serde(with = "...")]`.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Fixture {
+//!     #[serde(with = "runtime_services::serde_remote::Time")]
+//!     time: efi::Time,
+//! }
+//! ```
+
+use r_efi::efi;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serde definition of [`efi::Time`], the padding fields are skipped.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "efi::Time")]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    #[serde(skip)]
+    pub pad1: u8,
+    pub nanosecond: u32,
+    pub timezone: i16,
+    pub daylight: u8,
+    #[serde(skip)]
+    pub pad2: u8,
+}
+
+/// Serde definition of [`efi::TimeCapabilities`].
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "efi::TimeCapabilities")]
+pub struct TimeCapabilities {
+    pub resolution: u32,
+    pub accuracy: u32,
+    #[serde(with = "boolean")]
+    pub sets_to_zero: efi::Boolean,
+}
+
+/// Serde functions for [`efi::Boolean`], represented as a `bool`.
+pub mod boolean {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &efi::Boolean, serializer: S) -> Result<S::Ok, S::Error> {
+        (*value == true).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<efi::Boolean, D::Error> {
+        bool::deserialize(deserializer).map(efi::Boolean::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Fixture {
+        #[serde(with = "Time")]
+        time: efi::Time,
+        #[serde(with = "TimeCapabilities")]
+        capabilities: efi::TimeCapabilities,
+    }
+
+    #[test]
+    fn test_time_round_trip() {
+        let fixture = Fixture {
+            time: efi::Time {
+                year: 2024,
+                month: 7,
+                day: 14,
+                hour: 13,
+                minute: 37,
+                second: 42,
+                pad1: 0,
+                nanosecond: 500,
+                timezone: efi::UNSPECIFIED_TIMEZONE,
+                daylight: efi::TIME_ADJUST_DAYLIGHT,
+                pad2: 0,
+            },
+            capabilities: efi::TimeCapabilities { resolution: 1, accuracy: 50_000_000, sets_to_zero: true.into() },
+        };
+
+        let json = serde_json::to_string(&fixture).unwrap();
+        assert!(json.contains(r#""year":2024"#));
+        assert!(json.contains(r#""sets_to_zero":true"#));
+        assert!(!json.contains("pad1"));
+
+        let round_trip: Fixture = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            (2024, 7, 14, 13, 37, 42, 500),
+            (
+                round_trip.time.year,
+                round_trip.time.month,
+                round_trip.time.day,
+                round_trip.time.hour,
+                round_trip.time.minute,
+                round_trip.time.second,
+                round_trip.time.nanosecond
+            )
+        );
+        assert_eq!(efi::UNSPECIFIED_TIMEZONE, round_trip.time.timezone);
+        assert_eq!(efi::TIME_ADJUST_DAYLIGHT, round_trip.time.daylight);
+        assert_eq!(50_000_000, round_trip.capabilities.accuracy);
+        assert!(round_trip.capabilities.sets_to_zero == true);
+    }
+}
@@ -0,0 +1,357 @@
+//! Caching reader of the variable services, for the variables read again and again, like `SecureBoot` or `BootOrder`
+//! during BDS.
+//!
+//! [`CachedVariableReader`] wraps any [`RuntimeServices`]: the data and attributes of a variable are kept after its
+//! first read, as is its absence, and the writes made through the reader go to the runtime services it wraps and drop
+//! the variable from the cache.
+//!
+//! Writes made elsewhere are not seen by the cache. The owner of the reader invalidates it when they may have
+//! happened, directly or from the notification function of a platform event signaled on variable changes, through a
+//! [`CacheInvalidator`]:
+//!
+//! ```ignore
+//! let variables = CachedVariableReader::new(&RUNTIME_SERVICES);
+//! let invalidator = variables.invalidator();
+//! boot_services.create_event_ex(
+//!     EventType::NOTIFY_SIGNAL,
+//!     Tpl::CALLBACK,
+//!     Some(invalidate_variable_cache),
+//!     Box::new(invalidator),
+//!     &PLATFORM_VARIABLE_CHANGED_GROUP,
+//! )?;
+//! let (secure_boot, _): (Vec<u8>, u32) = variables.get_variable(&SECURE_BOOT_NAME, &efi::GLOBAL_VARIABLE, None)?;
+//! ```
+
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use r_efi::efi::{self, Time, TimeCapabilities};
+use status::EfiError;
+
+use crate::{
+    variable_services::{GetVariableStatus, VariableInfo},
+    RuntimeServices,
+};
+
+/// A variable as read from the runtime services.
+#[derive(Debug, Clone)]
+enum CachedVariable {
+    Found { data: Vec<u8>, attributes: u32 },
+    NotFound,
+}
+
+/// A variable, its name up to its null terminator and its namespace.
+type Key = (Vec<u16>, [u8; 16]);
+
+fn key(name: &[u16], namespace: &efi::Guid) -> Option<Key> {
+    let end = name.iter().position(|&c| c == 0)?;
+    Some((name[..=end].to_vec(), *namespace.as_bytes()))
+}
+
+/// Invalidates the whole cache of a [`CachedVariableReader`], from any context.
+///
+/// The invalidation is a counter checked by the reader on its next call, so it is safe to use from a notification
+/// function at any TPL.
+#[derive(Debug, Clone)]
+pub struct CacheInvalidator(Arc<AtomicUsize>);
+
+impl CacheInvalidator {
+    /// Drops every variable of the cache.
+    pub fn invalidate_all(&self) {
+        self.0.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Runtime services caching the variables read through them, see the [module](self) documentation.
+pub struct CachedVariableReader<R: RuntimeServices> {
+    runtime_services: R,
+    variables: RefCell<BTreeMap<Key, CachedVariable>>,
+    generation: Arc<AtomicUsize>,
+    cached_generation: Cell<usize>,
+}
+
+impl<R: RuntimeServices> CachedVariableReader<R> {
+    pub fn new(runtime_services: R) -> Self {
+        Self {
+            runtime_services,
+            variables: RefCell::new(BTreeMap::new()),
+            generation: Arc::new(AtomicUsize::new(0)),
+            cached_generation: Cell::new(0),
+        }
+    }
+
+    /// The wrapped runtime services.
+    pub fn inner(&self) -> &R {
+        &self.runtime_services
+    }
+
+    pub fn into_inner(self) -> R {
+        self.runtime_services
+    }
+
+    /// An invalidator of the cache, to be moved to the code notified of the variable changes.
+    pub fn invalidator(&self) -> CacheInvalidator {
+        CacheInvalidator(self.generation.clone())
+    }
+
+    /// Drops a variable from the cache, its next read goes to the runtime services.
+    ///
+    /// `name` is a null-terminated UCS-2 string.
+    pub fn invalidate(&self, name: &[u16], namespace: &efi::Guid) {
+        if let Some(key) = key(name, namespace) {
+            self.variables.borrow_mut().remove(&key);
+        }
+    }
+
+    /// Drops every variable from the cache.
+    pub fn invalidate_all(&self) {
+        self.variables.borrow_mut().clear();
+    }
+
+    /// Number of variables in the cache, found or not.
+    pub fn len(&self) -> usize {
+        self.refresh();
+        self.variables.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the cache if an invalidator was used since it was filled.
+    fn refresh(&self) {
+        let generation = self.generation.load(Ordering::Acquire);
+        if self.cached_generation.replace(generation) != generation {
+            self.variables.borrow_mut().clear();
+        }
+    }
+
+    /// Reads a variable whole from the runtime services, with the warning it was read with, if any, as such a
+    /// variable is not cached. Returns the status of the other failures.
+    ///
+    /// # Safety
+    ///
+    /// Ensure name is null-terminated.
+    unsafe fn read(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        size_hint: usize,
+    ) -> Result<(CachedVariable, Option<efi::Status>), GetVariableStatus> {
+        let mut data = vec![0; size_hint];
+        loop {
+            let buffer = Some(data.as_mut_slice()).filter(|data| !data.is_empty());
+            match self.runtime_services.get_variable_unchecked(name, namespace, buffer) {
+                GetVariableStatus::Success { data_size, attributes } => {
+                    data.truncate(data_size);
+                    return Ok((CachedVariable::Found { data, attributes }, None));
+                }
+                GetVariableStatus::Warning { status, data_size, attributes } => {
+                    data.truncate(data_size);
+                    return Ok((CachedVariable::Found { data, attributes }, Some(status)));
+                }
+                GetVariableStatus::BufferTooSmall { data_size, .. } if data_size > data.len() => {
+                    data.resize(data_size, 0);
+                }
+                GetVariableStatus::Error(efi::Status::NOT_FOUND) => return Ok((CachedVariable::NotFound, None)),
+                status => return Err(status),
+            }
+        }
+    }
+}
+
+impl<R: RuntimeServices> RuntimeServices for CachedVariableReader<R> {
+    unsafe fn set_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> Result<(), EfiError> {
+        // Dropped even when the write fails, it may have changed the variable.
+        self.invalidate(name, namespace);
+        self.runtime_services.set_variable_unchecked(name, namespace, attributes, data)
+    }
+
+    unsafe fn get_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        data: Option<&mut [u8]>,
+    ) -> GetVariableStatus {
+        let Some(key) = key(name, namespace) else {
+            return self.runtime_services.get_variable_unchecked(name, namespace, data);
+        };
+        self.refresh();
+        let cached = self.variables.borrow().get(&key).cloned();
+        let (variable, warning) = match cached {
+            Some(variable) => (variable, None),
+            None => match self.read(name, namespace, data.as_ref().map_or(0, |data| data.len())) {
+                Ok((variable, None)) => {
+                    self.variables.borrow_mut().insert(key, variable.clone());
+                    (variable, None)
+                }
+                Ok(read) => read,
+                Err(status) => return status,
+            },
+        };
+
+        match (variable, data) {
+            (CachedVariable::NotFound, _) => GetVariableStatus::Error(efi::Status::NOT_FOUND),
+            (CachedVariable::Found { data: found, attributes }, Some(data)) if data.len() >= found.len() => {
+                data[..found.len()].copy_from_slice(&found);
+                match warning {
+                    Some(status) => GetVariableStatus::Warning { status, data_size: found.len(), attributes },
+                    None => GetVariableStatus::Success { data_size: found.len(), attributes },
+                }
+            }
+            (CachedVariable::Found { data: found, attributes }, _) => {
+                GetVariableStatus::BufferTooSmall { data_size: found.len(), attributes }
+            }
+        }
+    }
+
+    unsafe fn get_next_variable_name_unchecked(
+        &self,
+        prev_name: &[u16],
+        prev_namespace: &efi::Guid,
+        next_name: &mut Vec<u16>,
+        next_namespace: &mut efi::Guid,
+    ) -> Result<(), EfiError> {
+        self.runtime_services.get_next_variable_name_unchecked(prev_name, prev_namespace, next_name, next_namespace)
+    }
+
+    unsafe fn get_next_variable_name_in_place(
+        &self,
+        name: &mut Vec<u16>,
+        namespace: &mut efi::Guid,
+    ) -> Result<(), EfiError> {
+        self.runtime_services.get_next_variable_name_in_place(name, namespace)
+    }
+
+    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, EfiError> {
+        self.runtime_services.query_variable_info(attributes)
+    }
+
+    fn get_next_high_monotonic_count(&self) -> Result<u32, EfiError> {
+        self.runtime_services.get_next_high_monotonic_count()
+    }
+
+    unsafe fn convert_pointer(&self, debug_disposition: usize, address: *mut *mut c_void) -> Result<(), EfiError> {
+        self.runtime_services.convert_pointer(debug_disposition, address)
+    }
+
+    unsafe fn get_wakeup_time_unchecked(&self) -> Result<(bool, bool, Time), EfiError> {
+        self.runtime_services.get_wakeup_time_unchecked()
+    }
+
+    unsafe fn set_time_unchecked(&self, time: &efi::Time) -> Result<(), EfiError> {
+        self.runtime_services.set_time_unchecked(time)
+    }
+
+    unsafe fn get_time_unchecked(&self) -> Result<(Time, TimeCapabilities), EfiError> {
+        self.runtime_services.get_time_unchecked()
+    }
+
+    unsafe fn set_wakeup_time_unchecked(&self, enable: bool, time: &efi::Time) -> Result<(), EfiError> {
+        self.runtime_services.set_wakeup_time_unchecked(enable, time)
+    }
+}
+
+impl<R: RuntimeServices + fmt::Debug> fmt::Debug for CachedVariableReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachedVariableReader")
+            .field("runtime_services", &self.runtime_services)
+            .field("cached", &self.variables.borrow().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mock::InMemoryRuntimeServices,
+        recording::{RecordingRuntimeServices, RuntimeServicesCall},
+    };
+
+    const NAMESPACE: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+    const BS: u32 = efi::VARIABLE_BOOTSERVICE_ACCESS;
+
+    fn name(name: &str) -> Vec<u16> {
+        name.encode_utf16().chain([0]).collect()
+    }
+
+    fn is_get_variable(call: &RuntimeServicesCall) -> bool {
+        matches!(call, RuntimeServicesCall::GetVariable { .. })
+    }
+
+    #[test]
+    fn test_cached_reads() {
+        let variables = CachedVariableReader::new(RecordingRuntimeServices::new(InMemoryRuntimeServices::new()));
+        variables.set_variable(&name("BootOrder"), &NAMESPACE, BS, &vec![1_u8, 0, 2, 0]).unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(
+                Ok((vec![1, 0, 2, 0], BS)),
+                variables.get_variable::<Vec<u8>>(&name("BootOrder"), &NAMESPACE, None)
+            );
+            assert_eq!(Ok((4, BS)), variables.get_variable_size_and_attributes(&name("BootOrder"), &NAMESPACE));
+            assert_eq!(
+                Err(efi::Status::NOT_FOUND.into()),
+                variables.get_variable::<Vec<u8>>(&name("SecureBoot"), &NAMESPACE, None)
+            );
+        }
+        // The size, then the data of `BootOrder`, and the absence of `SecureBoot`.
+        assert_eq!(3, variables.inner().count(is_get_variable));
+        assert_eq!(2, variables.len());
+
+        // A write through the reader is read back.
+        variables.set_variable(&name("BootOrder"), &NAMESPACE, BS, &vec![2_u8, 0]).unwrap();
+        assert_eq!(Ok((vec![2, 0], BS)), variables.get_variable::<Vec<u8>>(&name("BootOrder"), &NAMESPACE, None));
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let variables = CachedVariableReader::new(RecordingRuntimeServices::new(InMemoryRuntimeServices::new()));
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            variables.get_variable::<Vec<u8>>(&name("SecureBoot"), &NAMESPACE, None)
+        );
+
+        // Written behind the cache.
+        let inner = variables.inner().inner();
+        inner.set_variable(&name("SecureBoot"), &NAMESPACE, BS, &vec![1_u8]).unwrap();
+        assert_eq!(
+            Err(efi::Status::NOT_FOUND.into()),
+            variables.get_variable::<Vec<u8>>(&name("SecureBoot"), &NAMESPACE, None)
+        );
+        variables.invalidate(&name("SecureBoot"), &NAMESPACE);
+        assert_eq!(Ok((vec![1], BS)), variables.get_variable::<Vec<u8>>(&name("SecureBoot"), &NAMESPACE, None));
+
+        inner.set_variable(&name("SecureBoot"), &NAMESPACE, BS, &vec![0_u8]).unwrap();
+        let invalidator = variables.invalidator();
+        std::thread::spawn(move || invalidator.invalidate_all()).join().unwrap();
+        assert_eq!(Ok((vec![0], BS)), variables.get_variable::<Vec<u8>>(&name("SecureBoot"), &NAMESPACE, None));
+
+        variables.invalidate_all();
+        assert!(variables.is_empty());
+    }
+
+    #[test]
+    fn test_warning_not_cached() {
+        let variables = CachedVariableReader::new(RecordingRuntimeServices::new(InMemoryRuntimeServices::new()));
+        variables.set_variable(&name("Stale"), &NAMESPACE, BS, &vec![7_u8]).unwrap();
+        variables.inner().inject_fault(is_get_variable, 1, efi::Status::WARN_STALE_DATA);
+
+        let read = variables.get_variable_with_warning::<Vec<u8>>(&name("Stale"), &NAMESPACE, Some(1)).unwrap();
+        assert_eq!(Some(efi::Status::WARN_STALE_DATA), read.warning);
+        assert_eq!((vec![7], BS), read.into_value());
+        assert!(variables.is_empty());
+    }
+}
@@ -1,13 +1,16 @@
-use core::mem;
+use core::fmt;
 
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
 use fallible_streaming_iterator::FallibleStreamingIterator;
-use r_efi::efi::{self, Guid};
+use r_efi::efi;
+use status::{EfiError, StatusDisplay};
 
+#[cfg(feature = "alloc")]
 use crate::RuntimeServices;
 
 /// Status information returned by [`RuntimeServices::get_variable_unchecked`]
-#[derive(Debug)]
 pub enum GetVariableStatus {
     /// The variable was unable to be retrieved
     Error(efi::Status),
@@ -25,10 +28,40 @@ pub enum GetVariableStatus {
         /// The attributes of the variable
         attributes: u32,
     },
+    /// The variable was retrieved with a warning, like `WARN_STALE_DATA`
+    Warning {
+        /// The warning status
+        status: efi::Status,
+        /// The size of the variable data retrieved
+        data_size: usize,
+        /// The attributes of the variable
+        attributes: u32,
+    },
+}
+
+impl fmt::Debug for GetVariableStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GetVariableStatus::Error(status) => f.debug_tuple("Error").field(&StatusDisplay(*status)).finish(),
+            GetVariableStatus::BufferTooSmall { data_size, attributes } => {
+                f.debug_struct("BufferTooSmall").field("data_size", data_size).field("attributes", attributes).finish()
+            }
+            GetVariableStatus::Success { data_size, attributes } => {
+                f.debug_struct("Success").field("data_size", data_size).field("attributes", attributes).finish()
+            }
+            GetVariableStatus::Warning { status, data_size, attributes } => f
+                .debug_struct("Warning")
+                .field("status", &StatusDisplay(*status))
+                .field("data_size", data_size)
+                .field("attributes", attributes)
+                .finish(),
+        }
+    }
 }
 
 /// Variable information returned by [`RuntimeServices::query_variable_info`]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableInfo {
     /// The maximum size of the storage space available for the EFI variables associated with the attributes specified
     pub maximum_variable_storage_size: u64,
@@ -39,6 +72,7 @@ pub struct VariableInfo {
 }
 
 /// Uniquely identifies a UEFI variable
+#[cfg(feature = "alloc")]
 #[derive(Debug)]
 pub struct VariableIdentifier {
     /// The name of a UEFI variable
@@ -49,7 +83,8 @@ pub struct VariableIdentifier {
 
 /// Provides a [`FallibleStreamingIterator`] over UEFI variable names
 ///
-/// Produces an EFI status on error.
+/// Produces an EFI status on error. The names are read in place in a single buffer, grown only for a name longer
+/// than the previous ones, see [`RuntimeServices::get_next_variable_name_in_place`].
 ///
 /// # Examples
 ///
@@ -75,15 +110,16 @@ pub struct VariableIdentifier {
 ///     some_function(variable_identifier.name, variable_identifier.namespace);
 /// }
 /// ```
+#[cfg(feature = "alloc")]
 #[derive(Debug)]
 pub struct VariableNameIterator<'a, R: RuntimeServices> {
     rs: &'a R,
 
     current: VariableIdentifier,
-    next: VariableIdentifier,
     finished: bool,
 }
 
+#[cfg(feature = "alloc")]
 impl<'a, R: RuntimeServices> VariableNameIterator<'a, R> {
     /// Produce a new iterator from the beginning of the UEFI variable list
     pub fn new_from_first(runtime_services: &'a R) -> Self {
@@ -99,50 +135,42 @@ impl<'a, R: RuntimeServices> VariableNameIterator<'a, R> {
                 },
                 // When calling with an empty name, the GUID is ignored.
                 // We can just set it to zero.
-                namespace: Guid::from_bytes(&[0x0; 16]),
+                namespace: efi::Guid::from_bytes(&[0x0; 16]),
             },
-            next: VariableIdentifier { name: Vec::<u16>::new(), namespace: Guid::from_bytes(&[0x0; 16]) },
             finished: false,
         }
     }
 
     /// Produce a new iterator, starting from a given variable
-    pub fn new_from_variable(name: &[u16], namespace: &efi::Guid, runtime_services: &'a R) -> Self {
+    pub fn new_from_variable<N>(name: &N, namespace: &efi::Guid, runtime_services: &'a R) -> Self
+    where
+        N: AsRef<[u16]> + ?Sized,
+    {
         Self {
             rs: &runtime_services,
-            current: VariableIdentifier { name: name.to_vec(), namespace: namespace.clone() },
-            next: VariableIdentifier { name: Vec::<u16>::new(), namespace: Guid::from_bytes(&[0x0; 16]) },
+            current: VariableIdentifier { name: name.as_ref().to_vec(), namespace: namespace.clone() },
             finished: false,
         }
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<'a, R: RuntimeServices> FallibleStreamingIterator for VariableNameIterator<'a, R> {
     type Item = VariableIdentifier;
-    type Error = efi::Status;
+    type Error = EfiError;
 
     fn advance(&mut self) -> Result<(), Self::Error> {
-        unsafe {
-            // Don't do anything if we've reached the end already
-            if self.finished {
-                return Ok(());
-            }
-
-            let status = self.rs.get_next_variable_name_unchecked(
-                &self.current.name,
-                &self.current.namespace,
-                &mut self.next.name,
-                &mut self.next.namespace,
-            );
-
-            mem::swap(&mut self.current, &mut self.next);
+        // Don't do anything if we've reached the end already
+        if self.finished {
+            return Ok(());
+        }
 
-            if status.is_err() && status.unwrap_err() == efi::Status::NOT_FOUND {
+        match unsafe { self.rs.get_next_variable_name_in_place(&mut self.current.name, &mut self.current.namespace) } {
+            Err(status) if status == efi::Status::NOT_FOUND => {
                 self.finished = true;
-                return Ok(());
-            } else {
-                return status;
+                Ok(())
             }
+            result => result,
         }
     }
 
@@ -175,7 +203,7 @@ mod test {
         // Make sure the first result corresponds to DUMMY_FIRST_NAME
         let mut status = iter.next();
         assert!(status.is_ok());
-        assert!(status.unwrap().is_some());
+        assert!(status.as_ref().unwrap().is_some());
         let mut variable_identifier = status.unwrap().unwrap();
         assert_eq!(variable_identifier.name, DUMMY_FIRST_NAME);
         assert_eq!(variable_identifier.namespace, DUMMY_FIRST_NAMESPACE);
@@ -183,7 +211,7 @@ mod test {
         // Make sure the second result corresponds to DUMMY_SECOND_NAME
         status = iter.next();
         assert!(status.is_ok());
-        assert!(status.unwrap().is_some());
+        assert!(status.as_ref().unwrap().is_some());
         variable_identifier = status.unwrap().unwrap();
         assert_eq!(variable_identifier.name, DUMMY_SECOND_NAME);
         assert_eq!(variable_identifier.namespace, DUMMY_SECOND_NAMESPACE);
@@ -204,7 +232,7 @@ mod test {
         // Make sure the first result corresponds to DUMMY_SECOND_NAME
         let mut status = iter.next();
         assert!(status.is_ok());
-        assert!(status.unwrap().is_some());
+        assert!(status.as_ref().unwrap().is_some());
         let variable_identifier = status.unwrap().unwrap();
         assert_eq!(variable_identifier.name, DUMMY_SECOND_NAME);
         assert_eq!(variable_identifier.namespace, DUMMY_SECOND_NAMESPACE);
@@ -214,4 +242,32 @@ mod test {
         assert!(status.is_ok());
         assert!(status.unwrap().is_none());
     }
+
+    #[test]
+    fn test_get_next_variable_name_in_place() {
+        let rs: &StandardRuntimeServices<'_> =
+            runtime_services!(get_next_variable_name = mock_efi_get_next_variable_name);
+
+        // Room for the longest name, so the buffer is never reallocated.
+        let mut name = Vec::with_capacity(DUMMY_SECOND_NAME.len());
+        name.push(0);
+        let buffer = name.as_ptr();
+        let mut namespace = DUMMY_FIRST_NAMESPACE;
+        unsafe { rs.get_next_variable_name_in_place(&mut name, &mut namespace) }.unwrap();
+        assert_eq!((DUMMY_FIRST_NAME.as_slice(), DUMMY_FIRST_NAMESPACE), (name.as_slice(), namespace));
+        unsafe { rs.get_next_variable_name_in_place(&mut name, &mut namespace) }.unwrap();
+        assert_eq!((DUMMY_SECOND_NAME.as_slice(), DUMMY_SECOND_NAMESPACE), (name.as_slice(), namespace));
+        assert_eq!(buffer, name.as_ptr());
+
+        // The end of the list leaves the last variable.
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), unsafe {
+            rs.get_next_variable_name_in_place(&mut name, &mut namespace)
+        });
+        assert_eq!((DUMMY_SECOND_NAME.as_slice(), DUMMY_SECOND_NAMESPACE), (name.as_slice(), namespace));
+
+        // A name too long for the buffer grows it.
+        let mut name = vec![0];
+        unsafe { rs.get_next_variable_name_in_place(&mut name, &mut namespace) }.unwrap();
+        assert_eq!(DUMMY_FIRST_NAME.as_slice(), name.as_slice());
+    }
 }
@@ -0,0 +1,1101 @@
+//! Typed settings stored in UEFI variables.
+//!
+//! [`VariableStore`] loads and stores a serde type in the variables of a namespace, either with a variable per field
+//! of a struct, named after the field, or packed in a single variable:
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize, Default)]
+//! struct Settings {
+//!     boot_timeout: u16,
+//!     #[serde(default)]
+//!     fast_boot: bool,
+//! }
+//!
+//! let store = VariableStore::<_, Settings>::fields(&RUNTIME_SERVICES, &SETTINGS_NAMESPACE);
+//! let mut settings = store.load_or_default()?;
+//! settings.fast_boot = true;
+//! store.store(&settings)?;
+//! ```
+//!
+//! With a variable per field, only the fields that changed are written. A field without a variable fails the load
+//! unless it has a serde default, so fields can be added without changing the version of the schema.
+//!
+//! A store with a version writes it with the data. Data stored with an older version is loaded as the type of that
+//! version and converted by the migration registered for it, then written back:
+//!
+//! ```ignore
+//! let store = VariableStore::packed(&RUNTIME_SERVICES, u16str!("Setup"), &SETUP_NAMESPACE)
+//!     .with_version(2)
+//!     .with_migration(1, |setup: SetupV1| Setup::from(setup));
+//! ```
+//!
+//! # Encoding
+//!
+//! The values are encoded in a compact binary format, which is not self-describing:
+//! - integers and floats in little-endian, `bool` as one byte and `char` as a `u32`,
+//! - strings, byte arrays, sequences and maps as their length, a `u32`, followed by their content,
+//! - options as a byte, 0 for `None` or 1 followed by the value,
+//! - enums as the index of the variant, a `u32`, followed by its content,
+//! - tuples and structs as their fields in order, and unit types as nothing.
+//!
+//! A packed variable starts with the version as a `u32`. With a variable per field, the version is in the
+//! [`VERSION_VARIABLE_NAME`] variable, absent for version 0.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{cell::Cell, fmt, marker::PhantomData, ops::Range, slice};
+
+use r_efi::efi;
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, Visitor},
+    forward_to_deserialize_any,
+    ser::{self, Serialize},
+};
+use status::EfiError;
+
+use crate::RuntimeServices;
+
+/// Name of the variable with the version of a store with a variable per field.
+///
+/// Serde field names are Rust identifiers, which cannot contain `$`, so it does not collide with a field.
+pub const VERSION_VARIABLE_NAME: &str = "$Version";
+
+/// Attributes of the variables written by a store, unless it is created with others.
+pub const DEFAULT_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS;
+
+enum Layout {
+    Fields,
+    /// The null-terminated name of the variable.
+    Packed(Vec<u16>),
+}
+
+/// The stored data of a store, without its version.
+enum Source<'a, R: RuntimeServices> {
+    Packed(&'a [u8]),
+    Fields(FieldsDecoder<'a, R>),
+}
+
+type Migration<R, T> = Box<dyn Fn(&Source<'_, R>) -> Result<T, EfiError>>;
+
+/// A value of type `T` stored in UEFI variables, see the [module](self) documentation.
+pub struct VariableStore<'a, R: RuntimeServices, T> {
+    runtime_services: &'a R,
+    namespace: efi::Guid,
+    layout: Layout,
+    attributes: u32,
+    version: u32,
+    migrations: Vec<(u32, Migration<R, T>)>,
+}
+
+impl<'a, R: RuntimeServices, T: Serialize + DeserializeOwned> VariableStore<'a, R, T> {
+    /// A store of the struct `T` with a variable per field in `namespace`, named after the field.
+    pub fn fields(runtime_services: &'a R, namespace: &efi::Guid) -> Self {
+        Self::new(runtime_services, namespace, Layout::Fields)
+    }
+
+    /// A store of `T` packed in the variable `name`, a null-terminated UCS-2 string.
+    pub fn packed<N: AsRef<[u16]> + ?Sized>(runtime_services: &'a R, name: &N, namespace: &efi::Guid) -> Self {
+        Self::new(runtime_services, namespace, Layout::Packed(name.as_ref().to_vec()))
+    }
+
+    fn new(runtime_services: &'a R, namespace: &efi::Guid, layout: Layout) -> Self {
+        Self {
+            runtime_services,
+            namespace: *namespace,
+            layout,
+            attributes: DEFAULT_ATTRIBUTES,
+            version: 0,
+            migrations: Vec::new(),
+        }
+    }
+
+    /// Sets the attributes of the variables written, [`DEFAULT_ATTRIBUTES`] otherwise.
+    pub fn with_attributes(mut self, attributes: u32) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Sets the version of the schema of `T`, 0 otherwise.
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Registers the migration of the data stored with the schema `version`, loaded as a `U`.
+    pub fn with_migration<U, F>(mut self, version: u32, migrate: F) -> Self
+    where
+        U: DeserializeOwned,
+        F: Fn(U) -> T + 'static,
+    {
+        self.migrations.push((version, Box::new(move |source: &Source<'_, R>| decode::<U, R>(source).map(&migrate))));
+        self
+    }
+
+    pub fn namespace(&self) -> &efi::Guid {
+        &self.namespace
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Loads the value, migrated and written back if it was stored with another version.
+    ///
+    /// Returns `NOT_FOUND` if nothing is stored, `INCOMPATIBLE_VERSION` if no migration is registered for the
+    /// version of the data and `INVALID_PARAMETER` if the data does not match the schema.
+    pub fn load(&self) -> Result<T, EfiError> {
+        let packed_data;
+        let (version, source) = match &self.layout {
+            Layout::Packed(name) => {
+                packed_data = self.runtime_services.get_variable::<Vec<u8>>(name.as_slice(), &self.namespace, None)?.0;
+                let (version, data) = packed_data.split_first_chunk().ok_or(efi::Status::INVALID_PARAMETER)?;
+                (u32::from_le_bytes(*version), Source::Packed(data))
+            }
+            Layout::Fields => {
+                let version = match self.read_variable(VERSION_VARIABLE_NAME) {
+                    Ok(data) => u32::from_le_bytes(data.try_into().map_err(|_| efi::Status::INVALID_PARAMETER)?),
+                    Err(status) if status == efi::Status::NOT_FOUND => 0,
+                    Err(status) => return Err(status),
+                };
+                (version, Source::Fields(FieldsDecoder::new(self.runtime_services, &self.namespace)))
+            }
+        };
+        if version == self.version {
+            return decode(&source);
+        }
+        let Some((_, migrate)) = self.migrations.iter().find(|(from, _)| *from == version) else {
+            // The version of a store with a variable per field is 0 when nothing is stored.
+            return match decode::<T, R>(&source) {
+                Err(status) if status == efi::Status::NOT_FOUND => Err(status),
+                _ => Err(efi::Status::INCOMPATIBLE_VERSION.into()),
+            };
+        };
+        let value = migrate(&source)?;
+        self.store(&value)?;
+        Ok(value)
+    }
+
+    /// Loads the value, or the default one if nothing is stored.
+    pub fn load_or_default(&self) -> Result<T, EfiError>
+    where
+        T: Default,
+    {
+        match self.load() {
+            Err(status) if status == efi::Status::NOT_FOUND => Ok(T::default()),
+            result => result,
+        }
+    }
+
+    /// Stores `value`, with the version of the store.
+    ///
+    /// Returns `INVALID_PARAMETER` if `value` cannot be encoded, like a sequence of unknown length, or if a field is
+    /// encoded to no data, like `()`, with a variable per field.
+    pub fn store(&self, value: &T) -> Result<(), EfiError> {
+        let mut encoder = Encoder::default();
+        match &self.layout {
+            Layout::Packed(name) => {
+                encoder.output.extend(self.version.to_le_bytes());
+                value.serialize(&mut encoder).map_err(|error| error.0)?;
+                self.runtime_services.set_variable(name.as_slice(), &self.namespace, self.attributes, &encoder.output)
+            }
+            Layout::Fields => {
+                value.serialize(&mut encoder).map_err(|error| error.0)?;
+                let fields = encoder.fields.ok_or(efi::Status::INVALID_PARAMETER)?;
+                if fields.iter().any(|(_, range)| range.is_empty()) {
+                    return Err(efi::Status::INVALID_PARAMETER.into());
+                }
+                for (field, range) in fields {
+                    self.write_variable(field, &encoder.output[range])?;
+                }
+                match self.version {
+                    0 => Ok(()),
+                    version => self.write_variable(VERSION_VARIABLE_NAME, &version.to_le_bytes()),
+                }
+            }
+        }
+    }
+
+    /// Deletes the variables of the store, those of the fields of `T` with a variable per field.
+    pub fn delete(&self) -> Result<(), EfiError> {
+        let names = match &self.layout {
+            Layout::Packed(name) => vec![name.clone()],
+            Layout::Fields => {
+                field_names::<T>()?.iter().chain([&VERSION_VARIABLE_NAME]).map(|name| variable_name(name)).collect()
+            }
+        };
+        for name in names {
+            match self.runtime_services.set_variable(name.as_slice(), &self.namespace, 0, &Vec::<u8>::new()) {
+                Err(status) if status != efi::Status::NOT_FOUND => return Err(status),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_variable(&self, name: &str) -> Result<Vec<u8>, EfiError> {
+        let (data, _) = self.runtime_services.get_variable(&variable_name(name), &self.namespace, None)?;
+        Ok(data)
+    }
+
+    /// Writes a variable unless it already has `data` and the attributes of the store.
+    fn write_variable(&self, name: &str, data: &[u8]) -> Result<(), EfiError> {
+        let name = variable_name(name);
+        match self.runtime_services.get_variable::<Vec<u8>>(&name, &self.namespace, None) {
+            Ok((current, attributes)) if current == data && attributes == self.attributes => Ok(()),
+            _ => self.runtime_services.set_variable(&name, &self.namespace, self.attributes, &data.to_vec()),
+        }
+    }
+}
+
+impl<R: RuntimeServices, T> fmt::Debug for VariableStore<'_, R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VariableStore")
+            .field("namespace", &self.namespace)
+            .field("attributes", &self.attributes)
+            .field("version", &self.version)
+            .field("migrations", &self.migrations.iter().map(|(version, _)| *version).collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The null-terminated UCS-2 name of a variable.
+fn variable_name(name: &str) -> Vec<u16> {
+    name.encode_utf16().chain([0]).collect()
+}
+
+fn decode<U: DeserializeOwned, R: RuntimeServices>(source: &Source<'_, R>) -> Result<U, EfiError> {
+    match source {
+        Source::Packed(data) => {
+            let mut decoder = Decoder { input: data };
+            decoder.decode_all(PhantomData).map_err(|error| error.0)
+        }
+        Source::Fields(decoder) => {
+            decoder.found.set(0);
+            let result = U::deserialize(decoder);
+            match decoder.found.get() {
+                0 => Err(efi::Status::NOT_FOUND.into()),
+                _ => result.map_err(|error| error.0),
+            }
+        }
+    }
+}
+
+/// Error of the encoding, with the status returned for it.
+#[derive(Debug)]
+struct Error(EfiError);
+
+fn invalid() -> Error {
+    Error(efi::Status::INVALID_PARAMETER.into())
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl ser::StdError for Error {}
+
+impl ser::Error for Error {
+    fn custom<M: fmt::Display>(_msg: M) -> Self {
+        invalid()
+    }
+}
+
+impl de::Error for Error {
+    fn custom<M: fmt::Display>(_msg: M) -> Self {
+        invalid()
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    output: Vec<u8>,
+    /// Nesting of the compound value being encoded.
+    depth: usize,
+    /// The fields of the value if it is a struct, with their range in the output.
+    fields: Option<Vec<(&'static str, Range<usize>)>>,
+}
+
+impl Encoder {
+    fn length(&mut self, len: Option<usize>) -> Result<(), Error> {
+        let len = len.and_then(|len| u32::try_from(len).ok()).ok_or_else(invalid)?;
+        self.output.extend(len.to_le_bytes());
+        Ok(())
+    }
+
+    fn enter(&mut self) -> &mut Self {
+        self.depth += 1;
+        self
+    }
+}
+
+macro_rules! serialize_le_bytes {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), Error> {
+                self.output.extend(v.to_le_bytes());
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    serialize_le_bytes!(
+        serialize_i8: i8, serialize_i16: i16, serialize_i32: i32, serialize_i64: i64, serialize_i128: i128,
+        serialize_u8: u8, serialize_u16: u16, serialize_u32: u32, serialize_u64: u64, serialize_u128: u128,
+        serialize_f32: f32, serialize_f64: f64
+    );
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.output.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.length(Some(v.len()))?;
+        self.output.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.output.push(0);
+        Ok(())
+    }
+
+    fn serialize_some<V: ?Sized + Serialize>(self, value: &V) -> Result<(), Error> {
+        self.output.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, index: u32, _variant: &'static str) -> Result<(), Error> {
+        self.serialize_u32(index)
+    }
+
+    fn serialize_newtype_struct<V: ?Sized + Serialize>(self, _name: &'static str, value: &V) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<V: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        value: &V,
+    ) -> Result<(), Error> {
+        self.output.extend(index.to_le_bytes());
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, Error> {
+        self.length(len)?;
+        Ok(self.enter())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, Error> {
+        Ok(self.enter())
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self.enter())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, Error> {
+        self.output.extend(index.to_le_bytes());
+        Ok(self.enter())
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, Error> {
+        self.length(len)?;
+        Ok(self.enter())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        if self.depth == 0 {
+            self.fields = Some(Vec::new());
+        }
+        Ok(self.enter())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, Error> {
+        self.output.extend(index.to_le_bytes());
+        Ok(self.enter())
+    }
+}
+
+impl ser::SerializeSeq for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<K: ?Sized + Serialize>(&mut self, key: &K) -> Result<(), Error> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: ?Sized + Serialize>(&mut self, key: &'static str, value: &V) -> Result<(), Error> {
+        let start = self.output.len();
+        value.serialize(&mut **self)?;
+        let end = self.output.len();
+        match &mut self.fields {
+            Some(fields) if self.depth == 1 => fields.push((key, start..end)),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: ?Sized + Serialize>(&mut self, _key: &'static str, value: &V) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+/// Decoder of the data of a variable.
+///
+/// The strings and byte arrays are not borrowed from the data, so the decoder works for any `'de`.
+struct Decoder<'b> {
+    input: &'b [u8],
+}
+
+impl<'b> Decoder<'b> {
+    fn take(&mut self, len: usize) -> Result<&'b [u8], Error> {
+        if len > self.input.len() {
+            return Err(invalid());
+        }
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        // The slice has the length of the array.
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn length(&mut self) -> Result<usize, Error> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    /// Decodes a value with `seed`, the data must not have anything after it.
+    fn decode_all<'de, S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, Error> {
+        let value = seed.deserialize(&mut *self)?;
+        match self.input.is_empty() {
+            true => Ok(value),
+            false => Err(invalid()),
+        }
+    }
+}
+
+macro_rules! deserialize_le_bytes {
+    ($($method:ident: $ty:ty => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(<$ty>::from_le_bytes(self.array()?))
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Decoder<'_> {
+    type Error = Error;
+
+    deserialize_le_bytes!(
+        deserialize_i8: i8 => visit_i8, deserialize_i16: i16 => visit_i16, deserialize_i32: i32 => visit_i32,
+        deserialize_i64: i64 => visit_i64, deserialize_i128: i128 => visit_i128, deserialize_u8: u8 => visit_u8,
+        deserialize_u16: u16 => visit_u16, deserialize_u32: u32 => visit_u32, deserialize_u64: u64 => visit_u64,
+        deserialize_u128: u128 => visit_u128, deserialize_f32: f32 => visit_f32, deserialize_f64: f64 => visit_f64
+    );
+
+    /// The encoding is not self-describing.
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(invalid())
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.array::<1>()? {
+            [0] => visitor.visit_bool(false),
+            [1] => visitor.visit_bool(true),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_char(char::from_u32(u32::from_le_bytes(self.array()?)).ok_or_else(invalid)?)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let len = self.length()?;
+        visitor.visit_str(core::str::from_utf8(self.take(len)?).map_err(|_| invalid())?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let len = self.length()?;
+        visitor.visit_bytes(self.take(len)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.array::<1>()? {
+            [0] => visitor.visit_none(),
+            [1] => visitor.visit_some(self),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let remaining = self.length()?;
+        visitor.visit_seq(Elements { decoder: self, remaining })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(Elements { decoder: self, remaining: len })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let remaining = self.length()?;
+        visitor.visit_map(Elements { decoder: self, remaining })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self)
+    }
+
+    /// The identifiers are not encoded, the fields are in order and the variants are indexes.
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(invalid())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(invalid())
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// The elements of a sequence, a tuple or a map.
+struct Elements<'a, 'b> {
+    decoder: &'a mut Decoder<'b>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_, '_> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Elements<'_, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        de::SeqAccess::next_element_seed(self, seed)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, Error> {
+        seed.deserialize(&mut *self.decoder)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Decoder<'_> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Self), Error> {
+        let index = u32::from_le_bytes(self.array()?);
+        let variant = seed.deserialize(de::value::U32Deserializer::<Error>::new(index))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Decoder<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+/// Decoder of a struct with a variable per field, counting the variables found.
+struct FieldsDecoder<'a, R: RuntimeServices> {
+    runtime_services: &'a R,
+    namespace: &'a efi::Guid,
+    found: Cell<usize>,
+}
+
+impl<'a, R: RuntimeServices> FieldsDecoder<'a, R> {
+    fn new(runtime_services: &'a R, namespace: &'a efi::Guid) -> Self {
+        Self { runtime_services, namespace, found: Cell::new(0) }
+    }
+}
+
+impl<'de, R: RuntimeServices> de::Deserializer<'de> for &FieldsDecoder<'_, R> {
+    type Error = Error;
+
+    /// Only the structs have fields.
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(invalid())
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_map(FieldVariables { decoder: self, fields: fields.iter(), data: Vec::new() })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+        seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// The fields of a struct that have a variable.
+struct FieldVariables<'d, 'a, R: RuntimeServices> {
+    decoder: &'d FieldsDecoder<'a, R>,
+    fields: slice::Iter<'static, &'static str>,
+    /// The data of the variable of the current field.
+    data: Vec<u8>,
+}
+
+impl<'de, R: RuntimeServices> de::MapAccess<'de> for FieldVariables<'_, '_, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        let FieldsDecoder { runtime_services, namespace, found } = self.decoder;
+        for field in self.fields.by_ref() {
+            match runtime_services.get_variable::<Vec<u8>>(&variable_name(field), namespace, None) {
+                Ok((data, _)) => {
+                    self.data = data;
+                    found.set(found.get() + 1);
+                    return seed.deserialize(de::value::BorrowedStrDeserializer::<Error>::new(field)).map(Some);
+                }
+                Err(status) if status == efi::Status::NOT_FOUND => continue,
+                Err(status) => return Err(Error(status)),
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, Error> {
+        Decoder { input: &self.data }.decode_all(seed)
+    }
+}
+
+/// Deserializer only getting the names of the fields of a struct.
+struct FieldNames(Cell<&'static [&'static str]>);
+
+impl<'de> de::Deserializer<'de> for &FieldNames {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(invalid())
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.0.set(fields);
+        Err(invalid())
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+        seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// The names of the fields of the struct `U`.
+fn field_names<U: DeserializeOwned>() -> Result<&'static [&'static str], EfiError> {
+    let names = FieldNames(Cell::new(&[]));
+    // The deserialization always fails, once the names are known.
+    let _ = U::deserialize(&names);
+    match names.0.get() {
+        [] => Err(efi::Status::INVALID_PARAMETER.into()),
+        fields => Ok(fields),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mock::InMemoryRuntimeServices,
+        recording::{RecordingRuntimeServices, RuntimeServicesCall},
+    };
+    use alloc::{
+        collections::BTreeMap,
+        string::{String, ToString},
+    };
+    use serde::{Deserialize, Serialize};
+
+    const NAMESPACE: efi::Guid = efi::Guid::from_fields(1, 0, 0, 0, 0, &[0; 6]);
+
+    #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+    enum Mode {
+        #[default]
+        Auto,
+        Fixed(u16),
+        Range {
+            min: u8,
+            max: u8,
+        },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+    struct Settings {
+        timeout: u16,
+        name: String,
+        mode: Mode,
+        #[serde(default)]
+        order: Vec<(u8, Option<char>)>,
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            timeout: 5,
+            name: "Boot".to_string(),
+            mode: Mode::Range { min: 1, max: 3 },
+            order: vec![(1, None), (2, Some('é'))],
+        }
+    }
+
+    fn data(runtime_services: &impl RuntimeServices, name: &str) -> Result<Vec<u8>, EfiError> {
+        runtime_services.get_variable(&variable_name(name), &NAMESPACE, None).map(|(data, _)| data)
+    }
+
+    #[test]
+    fn test_packed() {
+        let runtime_services = InMemoryRuntimeServices::new();
+        let store = VariableStore::<_, Settings>::packed(&runtime_services, &variable_name("Setup"), &NAMESPACE);
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), store.load());
+        assert_eq!(Ok(Settings::default()), store.load_or_default());
+
+        store.store(&settings()).unwrap();
+        assert_eq!(Ok(settings()), store.load());
+        #[rustfmt::skip]
+        let expected = [
+            0, 0, 0, 0, // Version.
+            5, 0, 4, 0, 0, 0, b'B', b'o', b'o', b't', // Timeout and name.
+            2, 0, 0, 0, 1, 3, // Mode.
+            2, 0, 0, 0, 1, 0, 2, 1, 0xE9, 0, 0, 0, // Order.
+        ];
+        assert_eq!(Ok(expected.to_vec()), data(&runtime_services, "Setup"));
+
+        // Trailing or truncated data does not match the schema.
+        for data in [[&expected[..], &[0][..]].concat(), expected[..expected.len() - 1].to_vec(), vec![0, 0]] {
+            runtime_services.set_variable(&variable_name("Setup"), &NAMESPACE, DEFAULT_ATTRIBUTES, &data).unwrap();
+            assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), store.load());
+        }
+
+        store.delete().unwrap();
+        assert!(runtime_services.is_empty());
+        store.delete().unwrap();
+    }
+
+    #[test]
+    fn test_packed_collections() {
+        let runtime_services = InMemoryRuntimeServices::new();
+        let store = VariableStore::<_, (BTreeMap<String, bool>, Option<[u64; 2]>, f32)>::packed(
+            &runtime_services,
+            &variable_name("Table"),
+            &NAMESPACE,
+        );
+        let value = (BTreeMap::from([("a".to_string(), true), ("b".to_string(), false)]), Some([1, u64::MAX]), 0.5);
+        store.store(&value).unwrap();
+        assert_eq!(Ok(value), store.load());
+    }
+
+    #[test]
+    fn test_fields() {
+        let runtime_services = RecordingRuntimeServices::new(InMemoryRuntimeServices::new());
+        let store = VariableStore::<_, Settings>::fields(&runtime_services, &NAMESPACE)
+            .with_attributes(efi::VARIABLE_BOOTSERVICE_ACCESS);
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), store.load());
+
+        store.store(&settings()).unwrap();
+        assert_eq!(4, runtime_services.inner().len());
+        assert_eq!(Ok(vec![5, 0]), data(&runtime_services, "timeout"));
+        assert_eq!(Ok(vec![2, 0, 0, 0, 1, 3]), data(&runtime_services, "mode"));
+        assert_eq!(Ok(settings()), store.load());
+
+        // Only the fields that changed are written.
+        runtime_services.clear();
+        store.store(&Settings { timeout: 10, ..settings() }).unwrap();
+        let is_set_variable = |call: &RuntimeServicesCall| matches!(call, RuntimeServicesCall::SetVariable { .. });
+        assert_eq!(1, runtime_services.count(is_set_variable));
+        assert_eq!(Ok(vec![10, 0]), data(&runtime_services, "timeout"));
+
+        // A missing field takes its default, if it has one.
+        runtime_services.set_variable(&variable_name("order"), &NAMESPACE, 0, &Vec::<u8>::new()).unwrap();
+        assert_eq!(Ok(Settings { timeout: 10, order: Vec::new(), ..settings() }), store.load());
+        runtime_services.set_variable(&variable_name("name"), &NAMESPACE, 0, &Vec::<u8>::new()).unwrap();
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), store.load());
+
+        store.delete().unwrap();
+        assert!(runtime_services.inner().is_empty());
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), store.load());
+
+        // Only the fields of structs can be stored in variables.
+        let store = VariableStore::<_, Vec<Settings>>::fields(&runtime_services, &NAMESPACE);
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), store.store(&vec![settings()]));
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER.into()), store.delete());
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct SettingsV1 {
+        timeout: u8,
+        name: String,
+    }
+
+    impl From<SettingsV1> for Settings {
+        fn from(settings: SettingsV1) -> Self {
+            Settings { timeout: settings.timeout as u16 * 60, name: settings.name, ..Default::default() }
+        }
+    }
+
+    #[test]
+    fn test_migration() {
+        let runtime_services = InMemoryRuntimeServices::new();
+        let name = variable_name("Setup");
+        VariableStore::packed(&runtime_services, &name, &NAMESPACE)
+            .with_version(1)
+            .store(&SettingsV1 { timeout: 2, name: "Boot".to_string() })
+            .unwrap();
+
+        let store = VariableStore::<_, Settings>::packed(&runtime_services, &name, &NAMESPACE).with_version(2);
+        assert_eq!(Err(efi::Status::INCOMPATIBLE_VERSION.into()), store.load());
+
+        let store = store.with_migration(1, |settings: SettingsV1| Settings::from(settings));
+        let migrated = Settings { timeout: 120, name: "Boot".to_string(), ..Default::default() };
+        assert_eq!(Ok(migrated.clone()), store.load());
+        // The migrated value was written back with the new version.
+        assert_eq!(Ok(vec![2, 0, 0, 0, 120, 0]), data(&runtime_services, "Setup").map(|data| data[..6].to_vec()));
+        assert_eq!(Ok(migrated), store.load());
+    }
+
+    #[test]
+    fn test_fields_migration() {
+        let runtime_services = InMemoryRuntimeServices::new();
+        let store = VariableStore::<_, Settings>::fields(&runtime_services, &NAMESPACE)
+            .with_version(1)
+            .with_migration(0, |settings: SettingsV1| Settings::from(settings));
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), store.load());
+
+        // Fields stored before the store had a version.
+        let v0 = VariableStore::fields(&runtime_services, &NAMESPACE);
+        v0.store(&SettingsV1 { timeout: 1, name: "Boot".to_string() }).unwrap();
+        assert_eq!(Err(efi::Status::NOT_FOUND.into()), data(&runtime_services, VERSION_VARIABLE_NAME));
+
+        let migrated = Settings { timeout: 60, name: "Boot".to_string(), ..Default::default() };
+        assert_eq!(Ok(migrated.clone()), store.load());
+        assert_eq!(Ok(vec![1, 0, 0, 0]), data(&runtime_services, VERSION_VARIABLE_NAME));
+        assert_eq!(Ok(migrated), store.load());
+        assert_eq!(Err(efi::Status::INCOMPATIBLE_VERSION.into()), v0.load());
+
+        store.delete().unwrap();
+        assert!(runtime_services.is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+//! Fuzzing entry points of the binary parsers.
+//!
+//! The module is built with `--cfg fuzzing`, which cargo-fuzz sets. Every function takes arbitrary bytes, walks all
+//! of what it parsed so lazily parsed parts are covered too, and returns the result without ever panicking:
+//!
+//! ```ignore
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+//!     let _ = mu_rust_helpers::fuzz::load_option(data);
+//! });
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use device_path::{load_option::LoadOption, DevicePath};
+use protocols::{
+    media::partition::{GptHeader, PartitionEntry},
+    smbios::{SmbiosTables, Structure},
+    tcg2::{
+        event_log::{Event, EventLog},
+        EventLogFormat,
+    },
+};
+use r_efi::efi;
+use runtime_services::secure_boot::{self, SignatureList};
+use status::EfiError;
+
+/// Formats values without storing the text, to run the display code on fuzzed data.
+struct Sink;
+
+impl Write for Sink {
+    fn write_str(&mut self, _s: &str) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Parses a signature database, like the data of `db`.
+pub fn signature_lists(data: &[u8]) -> Result<Vec<SignatureList>, EfiError> {
+    secure_boot::parse_signature_lists(data)
+}
+
+/// Parses the data of a `Boot####` variable, and displays its device paths.
+pub fn load_option(data: &[u8]) -> Result<LoadOption<'_>, EfiError> {
+    let option = LoadOption::parse(data)?;
+    for device_path in option.file_paths() {
+        let _ = write!(Sink, "{}{device_path}", option.description);
+    }
+    Ok(option)
+}
+
+/// Parses a device path, and displays it, which parses its nodes.
+pub fn device_path(data: &[u8]) -> Result<&DevicePath, EfiError> {
+    let device_path = DevicePath::from_bytes(data)?;
+    for node in device_path.nodes() {
+        let _ = write!(Sink, "{node}");
+    }
+    let _ = write!(Sink, "{device_path}");
+    Ok(device_path)
+}
+
+/// Parses an SMBIOS 3 structure table, and reads the strings of every structure.
+pub fn smbios(data: &[u8]) -> Result<Vec<Structure<'_>>, EfiError> {
+    let structures = SmbiosTables::new(data, (3, 0)).structures().collect::<Result<Vec<_>, _>>()?;
+    for structure in structures.iter() {
+        let _ = (structure.handle(), structure.structure_type());
+        for index in 0..structure.formatted().len() {
+            let _ = structure.string_at(index);
+        }
+    }
+    Ok(structures)
+}
+
+/// Parses a GUID Partition Table, its header in the first 512 bytes and its partition entry array after them.
+pub fn gpt(data: &[u8]) -> Result<(GptHeader, Vec<PartitionEntry>), EfiError> {
+    let (block, array) = data.split_at(data.len().min(512));
+    let header = GptHeader::from_bytes(block)?;
+    let entries = header.parse_entries(array)?;
+    entries.iter().for_each(|entry| _ = (entry.is_used(), entry.block_count()));
+    Ok((header, entries))
+}
+
+/// Parses a TCG event log and its events, in the TCG 1.2 format if the first byte is even and in the crypto agile
+/// format otherwise.
+pub fn tcg_event_log(data: &[u8]) -> Result<Vec<Event<'_>>, EfiError> {
+    let (&selector, data) = data.split_first().ok_or(efi::Status::INVALID_PARAMETER)?;
+    let format = if selector % 2 == 0 { EventLogFormat::TCG_1_2 } else { EventLogFormat::TCG_2 };
+    EventLog::parse(format, data)?.events().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+    use protocols::media::partition::{crc32, GPT_HEADER_SIGNATURE};
+    use runtime_services::secure_boot::SignatureData;
+
+    /// Every truncation of `sample`, every byte of it replaced by interesting values and pseudo-random buffers.
+    fn mutations(sample: &[u8]) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let truncations = (0..sample.len()).map(|len| sample[..len].to_vec());
+        let replacements = (0..sample.len()).flat_map(move |index| {
+            [0x00, 0x01, 0x7F, 0x80, 0xFF].into_iter().map(move |byte| {
+                let mut data = sample.to_vec();
+                data[index] = byte;
+                data
+            })
+        });
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let random = (0..64).map(move |len| {
+            (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect()
+        });
+        truncations.chain(replacements).chain(random)
+    }
+
+    const PCI: [u8; 10] = [1, 1, 6, 0, 0, 2, 0x7F, 0xFF, 4, 0];
+
+    #[test]
+    fn test_signature_lists() {
+        let signature = SignatureData { owner: efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]), data: vec![7; 32] };
+        let sample = SignatureList::new(secure_boot::CERT_SHA256_GUID, vec![signature.clone(), signature]).unwrap();
+        let sample = sample.to_bytes();
+        assert_eq!(1, signature_lists(&sample).unwrap().len());
+        mutations(&sample).for_each(|data| _ = signature_lists(&data));
+    }
+
+    #[test]
+    fn test_load_option() {
+        let mut sample = vec![1, 0, 0, 0, PCI.len() as u8, 0, b'O', 0, b'S', 0, 0, 0];
+        sample.extend_from_slice(&PCI);
+        sample.extend_from_slice(b"options");
+        assert_eq!(b"options", load_option(&sample).unwrap().optional_data);
+        mutations(&sample).for_each(|data| _ = load_option(&data));
+    }
+
+    #[test]
+    fn test_device_path() {
+        let mut sample = vec![2, 1, 12, 0, 0xD0, 0x41, 0x03, 0x0A, 0, 0, 0, 0];
+        sample.extend_from_slice(&PCI);
+        assert!(device_path(&sample).is_ok());
+        mutations(&sample).for_each(|data| _ = device_path(&data));
+    }
+
+    #[test]
+    fn test_smbios() {
+        let mut sample = vec![0, 0x1A, 0, 0, 1, 2, 0, 0xF0, 3];
+        sample.resize(0x1A, 0);
+        sample.extend_from_slice(b"Vendor\0Version\0Date\0\0");
+        sample.extend_from_slice(&[127, 4, 1, 0, 0, 0]);
+        assert_eq!(1, smbios(&sample).unwrap().len());
+        mutations(&sample).for_each(|data| _ = smbios(&data));
+    }
+
+    #[test]
+    fn test_gpt() {
+        // A used entry ending before it starts, and three unused ones.
+        let mut entries = vec![0; 4 * 128];
+        entries[..16].fill(0xAA);
+        entries[32..40].copy_from_slice(&34_u64.to_le_bytes());
+        entries[40..48].copy_from_slice(&33_u64.to_le_bytes());
+        let mut sample = vec![0; 512];
+        sample[..8].copy_from_slice(&GPT_HEADER_SIGNATURE.to_le_bytes());
+        sample[12..16].copy_from_slice(&92_u32.to_le_bytes());
+        sample[80..84].copy_from_slice(&4_u32.to_le_bytes());
+        sample[84..88].copy_from_slice(&128_u32.to_le_bytes());
+        sample[88..92].copy_from_slice(&crc32(&entries).to_le_bytes());
+        let crc = crc32(&sample[..92]);
+        sample[16..20].copy_from_slice(&crc.to_le_bytes());
+        sample.extend_from_slice(&entries);
+        let (header, entries) = gpt(&sample).unwrap();
+        assert_eq!(Some(4 * 128), header.entries_size());
+        assert_eq!(4, entries.len());
+        assert!(entries[0].is_used());
+        assert_eq!(None, entries[0].block_count());
+        mutations(&sample).for_each(|data| _ = gpt(&data));
+    }
+
+    #[test]
+    fn test_tcg_event_log() {
+        let mut sample = vec![0];
+        for (pcr_index, data) in [(0_u32, &b"1.0"[..]), (7, &[0; 4][..])] {
+            sample.extend_from_slice(&pcr_index.to_le_bytes());
+            sample.extend_from_slice(&8_u32.to_le_bytes());
+            sample.extend_from_slice(&[pcr_index as u8; 20]);
+            sample.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            sample.extend_from_slice(data);
+        }
+        assert_eq!(2, tcg_event_log(&sample).unwrap().len());
+        mutations(&sample).for_each(|data| _ = tcg_event_log(&data));
+        sample[0] = 1;
+        mutations(&sample).for_each(|data| _ = tcg_event_log(&data));
+    }
+}
@@ -0,0 +1,1432 @@
+//! Host-run integration test harness.
+//!
+//! [`Harness`] stands up a system table on the host whose boot and runtime services are backed by
+//! [`InMemoryBootServices`] and [`InMemoryRuntimeServices`], and whose consoles are captured. A component's entry point
+//! then runs unchanged, and what it wrote to the console, log output of a console logger included, can be asserted
+//! on:
+//!
+//! ```ignore
+//! let harness = Harness::new();
+//! assert_eq!(efi::Status::SUCCESS, harness.run(efi_main));
+//! assert_eq!("Hello\r\n", harness.console_output());
+//! assert!(harness.runtime_services().get_variable_size_and_attributes(u16str!("Count"), &VENDOR_GUID).is_ok());
+//! ```
+//!
+//! The tables have a fixed address for the life of the process, which lets `entry_point::init` be called by every
+//! run, and only one harness exists at a time, [`Harness::new`] waits for the previous one to be dropped. What the
+//! in-memory services do not emulate returns `UNSUPPORTED`, like LoadImage() or ExitBootServices(), and
+//! ResetSystem() returns after recording the request, see [`Harness::reset_request`].
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    ffi::c_void,
+    mem, ptr, slice,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex, MutexGuard, Once, PoisonError,
+    },
+};
+
+use boot_services::{
+    allocation::{AllocType, MemoryType},
+    event::{EventNotifyCallback, EventTimerType, EventType},
+    mock::InMemoryBootServices,
+    protocol_handler::HandleSearchType,
+    tpl::Tpl,
+    BootServices,
+};
+use r_efi::{
+    efi,
+    protocols::{loaded_image, simple_text_input, simple_text_output},
+};
+use runtime_services::{mock::InMemoryRuntimeServices, variable_services::GetVariableStatus, RuntimeServices};
+use status::EfiError;
+
+/// Signature of an image entry point, like the `efi_main` generated by `entry_point::entry`.
+pub type EntryPoint = extern "efiapi" fn(efi::Handle, *mut efi::SystemTable) -> efi::Status;
+
+/// Columns and rows of the only mode of the captured consoles.
+const CONSOLE_SIZE: (usize, usize) = (80, 25);
+
+/// Serializes the harnesses, the tables forward to a single state.
+static LOCK: Mutex<()> = Mutex::new(());
+/// The state of the current harness, null when there is none.
+static STATE: AtomicPtr<State> = AtomicPtr::new(ptr::null_mut());
+static TABLES: AtomicPtr<Tables> = AtomicPtr::new(ptr::null_mut());
+static TABLES_INIT: Once = Once::new();
+
+const FIRMWARE_VENDOR: &[u16] = ucs2::u16str!("mu_rust_helpers");
+
+/// The tables given to the entry point, leaked once so their address never changes.
+struct Tables {
+    system_table: efi::SystemTable,
+    boot_services: efi::BootServices,
+    runtime_services: efi::RuntimeServices,
+    con_in: simple_text_input::Protocol,
+    con_out: simple_text_output::Protocol,
+    con_out_mode: simple_text_output::Mode,
+    std_err: simple_text_output::Protocol,
+    std_err_mode: simple_text_output::Mode,
+}
+
+/// What a harness owns, reached by the tables through [`STATE`].
+struct State {
+    boot_services: InMemoryBootServices,
+    runtime_services: InMemoryRuntimeServices,
+    image_handle: efi::Handle,
+    /// Kept alive for the LoadedImage protocol installed on the image handle.
+    loaded_image: Box<loaded_image::Protocol>,
+    console_output: RefCell<String>,
+    std_err_output: RefCell<String>,
+    keys: RefCell<VecDeque<simple_text_input::InputKey>>,
+    configuration_tables: RefCell<Vec<efi::ConfigurationTable>>,
+    reset_request: Cell<Option<(efi::ResetType, efi::Status)>>,
+}
+
+/// A host environment running entry points against in-memory boot and runtime services.
+pub struct Harness {
+    state: Box<State>,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Harness {
+    /// Creates a harness with empty handle database and variable store.
+    pub fn new() -> Self {
+        Self::with_services(InMemoryBootServices::new(), InMemoryRuntimeServices::new())
+    }
+
+    /// Creates a harness using `boot_services` and `runtime_services`, like ones restored from snapshots.
+    ///
+    /// The image handle, with its LoadedImage protocol, and the console handles are installed in `boot_services`.
+    pub fn with_services(boot_services: InMemoryBootServices, runtime_services: InMemoryRuntimeServices) -> Self {
+        let guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let tables = tables();
+
+        let mut loaded_image = Box::new(loaded_image::Protocol {
+            revision: loaded_image::REVISION,
+            parent_handle: ptr::null_mut(),
+            system_table: ptr::null_mut(),
+            device_handle: ptr::null_mut(),
+            file_path: ptr::null_mut(),
+            reserved: ptr::null_mut(),
+            load_options_size: 0,
+            load_options: ptr::null_mut(),
+            image_base: ptr::null_mut(),
+            image_size: 0,
+            image_code_type: efi::LOADER_CODE,
+            image_data_type: efi::LOADER_DATA,
+            unload: None,
+        });
+        //SAFETY: The tables are never freed and the loaded image is owned by the state, both outlive the handles.
+        let (image_handle, console_handle, std_err_handle, wait_for_key) = unsafe {
+            loaded_image.system_table = ptr::addr_of_mut!((*tables).system_table);
+            let image_handle = boot_services
+                .install_protocol_interface_unchecked(
+                    None,
+                    &loaded_image::PROTOCOL_GUID,
+                    loaded_image.as_mut() as *mut _ as *mut c_void,
+                )
+                .expect("Installing a protocol on a new handle does not fail.");
+            let console_handle = boot_services
+                .install_protocol_interface_unchecked(
+                    None,
+                    &simple_text_input::PROTOCOL_GUID,
+                    ptr::addr_of_mut!((*tables).con_in) as *mut c_void,
+                )
+                .expect("Installing a protocol on a new handle does not fail.");
+            boot_services
+                .install_protocol_interface_unchecked(
+                    Some(console_handle),
+                    &simple_text_output::PROTOCOL_GUID,
+                    ptr::addr_of_mut!((*tables).con_out) as *mut c_void,
+                )
+                .expect("The console handle does not have an output protocol yet.");
+            let std_err_handle = boot_services
+                .install_protocol_interface_unchecked(
+                    None,
+                    &simple_text_output::PROTOCOL_GUID,
+                    ptr::addr_of_mut!((*tables).std_err) as *mut c_void,
+                )
+                .expect("Installing a protocol on a new handle does not fail.");
+            let wait_for_key = boot_services
+                .create_event_unchecked::<c_void>(EventType::NONE, Tpl::APPLICATION, None, ptr::null_mut())
+                .expect("An event without notification is always valid.");
+            (image_handle, console_handle, std_err_handle, wait_for_key)
+        };
+
+        //SAFETY: No other harness exists while the lock is held, nothing else accesses the tables.
+        unsafe {
+            let tables = &mut *tables;
+            tables.system_table.console_in_handle = console_handle;
+            tables.system_table.console_out_handle = console_handle;
+            tables.system_table.standard_error_handle = std_err_handle;
+            tables.system_table.number_of_table_entries = 0;
+            tables.system_table.configuration_table = ptr::null_mut();
+            tables.con_in.wait_for_key = wait_for_key;
+            tables.con_out_mode = console_mode();
+            tables.std_err_mode = console_mode();
+        }
+
+        let state = Box::new(State {
+            boot_services,
+            runtime_services,
+            image_handle,
+            loaded_image,
+            console_output: RefCell::new(String::new()),
+            std_err_output: RefCell::new(String::new()),
+            keys: RefCell::new(VecDeque::new()),
+            configuration_tables: RefCell::new(Vec::new()),
+            reset_request: Cell::new(None),
+        });
+        STATE.store(state.as_ref() as *const State as *mut State, Ordering::SeqCst);
+        Self { state, _guard: guard }
+    }
+
+    /// Runs `entry_point` with the image handle and the system table of the harness, and returns its status.
+    ///
+    /// The services can be run again, they keep the state left by the previous runs. Configuration tables installed
+    /// directly in the boot services are added to the system table first.
+    pub fn run(&self, entry_point: EntryPoint) -> efi::Status {
+        update_configuration_tables(&self.state);
+        entry_point(self.image_handle(), self.system_table())
+    }
+
+    /// The handle given to the entry point, on which the LoadedImage protocol is installed.
+    pub fn image_handle(&self) -> efi::Handle {
+        self.state.image_handle
+    }
+
+    /// The system table given to the entry point.
+    pub fn system_table(&self) -> *mut efi::SystemTable {
+        //SAFETY: The tables are never freed.
+        unsafe { ptr::addr_of_mut!((*tables()).system_table) }
+    }
+
+    /// The LoadedImage protocol of the image handle, to set load options before running an entry point.
+    pub fn loaded_image(&mut self) -> &mut loaded_image::Protocol {
+        &mut self.state.loaded_image
+    }
+
+    pub fn boot_services(&self) -> &InMemoryBootServices {
+        &self.state.boot_services
+    }
+
+    pub fn runtime_services(&self) -> &InMemoryRuntimeServices {
+        &self.state.runtime_services
+    }
+
+    /// Queues `text` as key strokes of the console input, signaling its WaitForKey event.
+    pub fn queue_input(&self, text: &str) {
+        let keys = text.encode_utf16().map(|c| simple_text_input::InputKey { scan_code: 0, unicode_char: c });
+        self.state.keys.borrow_mut().extend(keys);
+        self.signal_key();
+    }
+
+    /// What was written to the console output.
+    pub fn console_output(&self) -> String {
+        self.state.console_output.borrow().clone()
+    }
+
+    /// What was written to the standard error console.
+    pub fn std_err_output(&self) -> String {
+        self.state.std_err_output.borrow().clone()
+    }
+
+    /// Clears the captured output of both consoles, like between two steps of a test.
+    pub fn clear_output(&self) {
+        self.state.console_output.borrow_mut().clear();
+        self.state.std_err_output.borrow_mut().clear();
+    }
+
+    /// The type and status of the last call to ResetSystem().
+    pub fn reset_request(&self) -> Option<(efi::ResetType, efi::Status)> {
+        self.state.reset_request.get()
+    }
+
+    fn signal_key(&self) {
+        if !self.state.keys.borrow().is_empty() {
+            //SAFETY: The tables are never freed.
+            let wait_for_key = unsafe { (*tables()).con_in.wait_for_key };
+            let _ = self.state.boot_services.signal_event(wait_for_key);
+        }
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        STATE.store(ptr::null_mut(), Ordering::SeqCst);
+        //SAFETY: The lock is still held.
+        unsafe {
+            let system_table = &mut (*tables()).system_table;
+            system_table.number_of_table_entries = 0;
+            system_table.configuration_table = ptr::null_mut();
+        }
+    }
+}
+
+fn console_mode() -> simple_text_output::Mode {
+    simple_text_output::Mode {
+        max_mode: 1,
+        mode: 0,
+        attribute: 0x07,
+        cursor_column: 0,
+        cursor_row: 0,
+        cursor_visible: efi::Boolean::TRUE,
+    }
+}
+
+fn table_header(signature: u64, header_size: usize) -> efi::TableHeader {
+    efi::TableHeader {
+        signature,
+        revision: efi::SYSTEM_TABLE_REVISION,
+        header_size: header_size as u32,
+        crc32: 0,
+        reserved: 0,
+    }
+}
+
+/// The tables, leaked the first time they are needed.
+fn tables() -> *mut Tables {
+    TABLES_INIT.call_once(|| {
+        let tables = Box::leak(Box::new(Tables {
+            //SAFETY: The pointers of the system table are null until set below, which is valid for raw pointers.
+            system_table: unsafe { mem::zeroed() },
+            boot_services: efi::BootServices {
+                hdr: table_header(efi::BOOT_SERVICES_SIGNATURE, mem::size_of::<efi::BootServices>()),
+                raise_tpl: boot::raise_tpl,
+                restore_tpl: boot::restore_tpl,
+                allocate_pages: boot::allocate_pages,
+                free_pages: boot::free_pages,
+                get_memory_map: boot::get_memory_map,
+                allocate_pool: boot::allocate_pool,
+                free_pool: boot::free_pool,
+                create_event: boot::create_event,
+                set_timer: boot::set_timer,
+                wait_for_event: boot::wait_for_event,
+                signal_event: boot::signal_event,
+                close_event: boot::close_event,
+                check_event: boot::check_event,
+                install_protocol_interface: boot::install_protocol_interface,
+                reinstall_protocol_interface: boot::reinstall_protocol_interface,
+                uninstall_protocol_interface: boot::uninstall_protocol_interface,
+                handle_protocol: boot::handle_protocol,
+                reserved: ptr::null_mut(),
+                register_protocol_notify: boot::register_protocol_notify,
+                locate_handle: boot::locate_handle,
+                locate_device_path: boot::locate_device_path,
+                install_configuration_table: boot::install_configuration_table,
+                load_image: boot::load_image,
+                start_image: boot::start_image,
+                exit: boot::exit,
+                unload_image: boot::unload_image,
+                exit_boot_services: boot::exit_boot_services,
+                get_next_monotonic_count: boot::get_next_monotonic_count,
+                stall: boot::stall,
+                set_watchdog_timer: boot::set_watchdog_timer,
+                connect_controller: boot::connect_controller,
+                disconnect_controller: boot::disconnect_controller,
+                open_protocol: boot::open_protocol,
+                close_protocol: boot::close_protocol,
+                open_protocol_information: boot::open_protocol_information,
+                protocols_per_handle: boot::protocols_per_handle,
+                locate_handle_buffer: boot::locate_handle_buffer,
+                locate_protocol: boot::locate_protocol,
+                install_multiple_protocol_interfaces: boot::install_multiple_protocol_interfaces,
+                uninstall_multiple_protocol_interfaces: boot::uninstall_multiple_protocol_interfaces,
+                calculate_crc32: boot::calculate_crc32,
+                copy_mem: boot::copy_mem,
+                set_mem: boot::set_mem,
+                create_event_ex: boot::create_event_ex,
+            },
+            runtime_services: efi::RuntimeServices {
+                hdr: table_header(efi::RUNTIME_SERVICES_SIGNATURE, mem::size_of::<efi::RuntimeServices>()),
+                get_time: runtime::get_time,
+                set_time: runtime::set_time,
+                get_wakeup_time: runtime::get_wakeup_time,
+                set_wakeup_time: runtime::set_wakeup_time,
+                set_virtual_address_map: runtime::set_virtual_address_map,
+                convert_pointer: runtime::convert_pointer,
+                get_variable: runtime::get_variable,
+                get_next_variable_name: runtime::get_next_variable_name,
+                set_variable: runtime::set_variable,
+                get_next_high_mono_count: runtime::get_next_high_mono_count,
+                reset_system: runtime::reset_system,
+                update_capsule: runtime::update_capsule,
+                query_capsule_capabilities: runtime::query_capsule_capabilities,
+                query_variable_info: runtime::query_variable_info,
+            },
+            con_in: simple_text_input::Protocol {
+                reset: console::input_reset,
+                read_key_stroke: console::read_key_stroke,
+                wait_for_key: ptr::null_mut(),
+            },
+            con_out: console::output_protocol(),
+            con_out_mode: console_mode(),
+            std_err: console::output_protocol(),
+            std_err_mode: console_mode(),
+        }));
+        tables.system_table.hdr = table_header(efi::SYSTEM_TABLE_SIGNATURE, mem::size_of::<efi::SystemTable>());
+        tables.system_table.firmware_vendor = FIRMWARE_VENDOR.as_ptr() as *mut u16;
+        tables.system_table.con_in = &mut tables.con_in;
+        tables.con_out.mode = &mut tables.con_out_mode;
+        tables.system_table.con_out = &mut tables.con_out;
+        tables.std_err.mode = &mut tables.std_err_mode;
+        tables.system_table.std_err = &mut tables.std_err;
+        tables.system_table.boot_services = &mut tables.boot_services;
+        tables.system_table.runtime_services = &mut tables.runtime_services;
+        TABLES.store(tables, Ordering::SeqCst);
+    });
+    TABLES.load(Ordering::SeqCst)
+}
+
+/// Points the system table to the configuration tables of the boot services.
+fn update_configuration_tables(state: &State) {
+    let mut configuration_tables = state.configuration_tables.borrow_mut();
+    *configuration_tables = state.boot_services.configuration_tables();
+    //SAFETY: No other harness exists while the lock of the one owning the state is held, and the entries live as
+    // long as the state.
+    unsafe {
+        let system_table = &mut (*tables()).system_table;
+        system_table.number_of_table_entries = configuration_tables.len();
+        system_table.configuration_table = configuration_tables.as_mut_ptr();
+    }
+}
+
+/// Runs `f` with the state of the current harness, `NOT_READY` if there is none.
+fn call(f: impl FnOnce(&'static State) -> Result<(), EfiError>) -> efi::Status {
+    //SAFETY: The pointer is cleared before the harness owning the state is dropped.
+    match unsafe { STATE.load(Ordering::SeqCst).as_ref() } {
+        Some(state) => f(state).map_or_else(|status| status.into(), |()| efi::Status::SUCCESS),
+        None => efi::Status::NOT_READY,
+    }
+}
+
+/// Writes `value` to the output parameter `ptr`.
+fn write<T>(ptr: *mut T, value: T) -> Result<(), EfiError> {
+    if ptr.is_null() {
+        return Err(efi::Status::INVALID_PARAMETER.into());
+    }
+    //SAFETY: Non-null output parameters are valid per the UEFI calling convention.
+    unsafe { ptr.write(value) };
+    Ok(())
+}
+
+/// Borrows a GUID given by the caller as static, the in-memory services keep the reference of the GUIDs they store.
+///
+/// Callers are expected to use GUIDs of constants or statics, like the wrappers of this crate do.
+fn guid(guid: *mut efi::Guid) -> Result<&'static efi::Guid, EfiError> {
+    //SAFETY: Non-null GUIDs are valid per the UEFI calling convention.
+    unsafe { guid.as_ref() }.ok_or(efi::Status::INVALID_PARAMETER.into())
+}
+
+/// The characters of a null-terminated UCS-2 string, with its terminator.
+///
+/// # Safety
+///
+/// `string` must be null or point to a null-terminated string.
+unsafe fn ucs2<'a>(string: *mut u16) -> Option<&'a mut [u16]> {
+    if string.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *string.add(len) != 0 {
+        len += 1;
+    }
+    Some(slice::from_raw_parts_mut(string, len + 1))
+}
+
+fn option(handle: efi::Handle) -> Option<efi::Handle> {
+    (!handle.is_null()).then_some(handle)
+}
+
+fn search_type(
+    search_type: efi::LocateSearchType,
+    protocol: *mut efi::Guid,
+    search_key: *mut c_void,
+) -> Result<HandleSearchType, EfiError> {
+    match search_type {
+        efi::ALL_HANDLES => Ok(HandleSearchType::AllHandle),
+        efi::BY_REGISTER_NOTIFY => ptr::NonNull::new(search_key)
+            .map(HandleSearchType::ByRegisterNotify)
+            .ok_or(efi::Status::INVALID_PARAMETER.into()),
+        efi::BY_PROTOCOL => guid(protocol).map(HandleSearchType::ByProtocol),
+        _ => Err(efi::Status::INVALID_PARAMETER.into()),
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0_u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg()))
+    })
+}
+
+/// The boot services table entries, forwarding to [`InMemoryBootServices`].
+mod boot {
+    use super::*;
+
+    /// The notify function as the in-memory boot services take it, r-efi 6 declares it `unsafe`.
+    #[allow(clippy::useless_transmute)]
+    fn notify_callback(notify_function: efi::EventNotify) -> EventNotifyCallback<*mut c_void> {
+        //SAFETY: Only the safety of the signature differs, the services call it like the firmware would.
+        unsafe { mem::transmute::<efi::EventNotify, EventNotifyCallback<*mut c_void>>(notify_function) }
+    }
+
+    pub extern "efiapi" fn raise_tpl(new_tpl: efi::Tpl) -> efi::Tpl {
+        //SAFETY: The pointer is cleared before the harness owning the state is dropped.
+        match unsafe { STATE.load(Ordering::SeqCst).as_ref() } {
+            Some(state) => state.boot_services.raise_tpl(Tpl(new_tpl)).0,
+            None => new_tpl,
+        }
+    }
+
+    pub extern "efiapi" fn restore_tpl(old_tpl: efi::Tpl) {
+        call(|state| {
+            state.boot_services.restore_tpl(Tpl(old_tpl));
+            Ok(())
+        });
+    }
+
+    pub extern "efiapi" fn allocate_pages(
+        allocation_type: efi::AllocateType,
+        memory_type: efi::MemoryType,
+        pages: usize,
+        memory: *mut efi::PhysicalAddress,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The address is an input for the types using it, and is checked for null first.
+            let address = || unsafe { memory.as_ref() }.map(|&address| address as usize);
+            let allocation_type = match allocation_type {
+                efi::ALLOCATE_ANY_PAGES => AllocType::AnyPage,
+                efi::ALLOCATE_MAX_ADDRESS => AllocType::MaxAddress(address().ok_or(efi::Status::INVALID_PARAMETER)?),
+                efi::ALLOCATE_ADDRESS => AllocType::Address(address().ok_or(efi::Status::INVALID_PARAMETER)?),
+                _ => return Err(efi::Status::INVALID_PARAMETER.into()),
+            };
+            let address = state.boot_services.allocate_pages(allocation_type, MemoryType::from(memory_type), pages)?;
+            write(memory, address as efi::PhysicalAddress)
+        })
+    }
+
+    pub extern "efiapi" fn free_pages(memory: efi::PhysicalAddress, pages: usize) -> efi::Status {
+        call(|state| state.boot_services.free_pages(memory as usize, pages))
+    }
+
+    pub extern "efiapi" fn get_memory_map(
+        _memory_map_size: *mut usize,
+        _memory_map: *mut efi::MemoryDescriptor,
+        _map_key: *mut usize,
+        _descriptor_size: *mut usize,
+        _descriptor_version: *mut u32,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn allocate_pool(
+        pool_type: efi::MemoryType,
+        size: usize,
+        buffer: *mut *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            let allocation = state.boot_services.allocate_pool(MemoryType::from(pool_type), size)?;
+            write(buffer, allocation as *mut c_void)
+        })
+    }
+
+    pub extern "efiapi" fn free_pool(buffer: *mut c_void) -> efi::Status {
+        call(|state| state.boot_services.free_pool(buffer as *mut u8))
+    }
+
+    pub extern "efiapi" fn create_event(
+        event_type: u32,
+        notify_tpl: efi::Tpl,
+        notify_function: Option<efi::EventNotify>,
+        notify_context: *mut c_void,
+        event: *mut efi::Event,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The context is given back to the notify function as given, like the firmware would.
+            let created = unsafe {
+                state.boot_services.create_event_unchecked(
+                    EventType::from(event_type),
+                    Tpl(notify_tpl),
+                    notify_function.map(notify_callback),
+                    notify_context,
+                )
+            }?;
+            write(event, created)
+        })
+    }
+
+    pub extern "efiapi" fn set_timer(event: efi::Event, timer_type: efi::TimerDelay, trigger_time: u64) -> efi::Status {
+        call(|state| {
+            let timer_type = match timer_type {
+                efi::TIMER_CANCEL => EventTimerType::Cancel,
+                efi::TIMER_PERIODIC => EventTimerType::Periodic,
+                efi::TIMER_RELATIVE => EventTimerType::Relative,
+                _ => return Err(efi::Status::INVALID_PARAMETER.into()),
+            };
+            state.boot_services.set_timer(event, timer_type, trigger_time)
+        })
+    }
+
+    pub extern "efiapi" fn wait_for_event(
+        number_of_events: usize,
+        event: *mut efi::Event,
+        index: *mut usize,
+    ) -> efi::Status {
+        call(|state| {
+            if number_of_events == 0 || event.is_null() {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            //SAFETY: The caller gives an array of `number_of_events` events.
+            let events = unsafe { slice::from_raw_parts_mut(event, number_of_events) };
+            let signaled = state.boot_services.wait_for_event(events)?;
+            write(index, signaled)
+        })
+    }
+
+    pub extern "efiapi" fn signal_event(event: efi::Event) -> efi::Status {
+        call(|state| state.boot_services.signal_event(event))
+    }
+
+    pub extern "efiapi" fn close_event(event: efi::Event) -> efi::Status {
+        call(|state| state.boot_services.close_event(event))
+    }
+
+    pub extern "efiapi" fn check_event(event: efi::Event) -> efi::Status {
+        call(|state| state.boot_services.check_event(event))
+    }
+
+    pub extern "efiapi" fn install_protocol_interface(
+        handle: *mut efi::Handle,
+        protocol: *mut efi::Guid,
+        interface_type: efi::InterfaceType,
+        interface: *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            if handle.is_null() || interface_type != efi::NATIVE_INTERFACE {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            //SAFETY: The handle was checked for null, the interface is only stored.
+            let installed = unsafe {
+                state.boot_services.install_protocol_interface_unchecked(option(*handle), guid(protocol)?, interface)
+            }?;
+            write(handle, installed)
+        })
+    }
+
+    pub extern "efiapi" fn reinstall_protocol_interface(
+        handle: efi::Handle,
+        protocol: *mut efi::Guid,
+        old_interface: *mut c_void,
+        new_interface: *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The interfaces are only compared and stored.
+            unsafe {
+                state.boot_services.reinstall_protocol_interface_unchecked(
+                    handle,
+                    guid(protocol)?,
+                    old_interface,
+                    new_interface,
+                )
+            }
+        })
+    }
+
+    pub extern "efiapi" fn uninstall_protocol_interface(
+        handle: efi::Handle,
+        protocol: *mut efi::Guid,
+        interface: *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The interface is only compared.
+            unsafe { state.boot_services.uninstall_protocol_interface_unchecked(handle, guid(protocol)?, interface) }
+        })
+    }
+
+    pub extern "efiapi" fn handle_protocol(
+        handle: efi::Handle,
+        protocol: *mut efi::Guid,
+        interface: *mut *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The interface is only returned.
+            let found = unsafe { state.boot_services.handle_protocol_unchecked(handle, guid(protocol)?) }?;
+            write(interface, found)
+        })
+    }
+
+    pub extern "efiapi" fn register_protocol_notify(
+        protocol: *mut efi::Guid,
+        event: efi::Event,
+        registration: *mut *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            let registered = state.boot_services.register_protocol_notify(guid(protocol)?, event)?;
+            write(registration, registered.as_ptr())
+        })
+    }
+
+    pub extern "efiapi" fn locate_handle(
+        search: efi::LocateSearchType,
+        protocol: *mut efi::Guid,
+        search_key: *mut c_void,
+        buffer_size: *mut usize,
+        buffer: *mut efi::Handle,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The size is checked for null first.
+            let available = unsafe { buffer_size.as_ref() }.copied().ok_or(efi::Status::INVALID_PARAMETER)?;
+            let handles = state.boot_services.locate_handle(search_type(search, protocol, search_key)?)?;
+            let size = mem::size_of_val(&*handles);
+            write(buffer_size, size)?;
+            if available < size {
+                return Err(efi::Status::BUFFER_TOO_SMALL.into());
+            }
+            if buffer.is_null() {
+                return Err(efi::Status::INVALID_PARAMETER.into());
+            }
+            //SAFETY: The caller's buffer holds `available` bytes, at least `size`.
+            unsafe { ptr::copy_nonoverlapping(handles.as_ptr(), buffer, handles.len()) };
+            Ok(())
+        })
+    }
+
+    pub extern "efiapi" fn locate_device_path(
+        protocol: *mut efi::Guid,
+        device_path: *mut *mut efi::protocols::device_path::Protocol,
+        device: *mut efi::Handle,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The device path is forwarded as given.
+            let found = unsafe { state.boot_services.locate_device_path(guid(protocol)?, device_path) }?;
+            write(device, found)
+        })
+    }
+
+    pub extern "efiapi" fn install_configuration_table(protocol: *mut efi::Guid, table: *mut c_void) -> efi::Status {
+        call(|state| {
+            //SAFETY: The table is only stored.
+            unsafe { state.boot_services.install_configuration_table_unchecked(guid(protocol)?, table) }?;
+            update_configuration_tables(state);
+            Ok(())
+        })
+    }
+
+    pub extern "efiapi" fn load_image(
+        _boot_policy: efi::Boolean,
+        _parent_image_handle: efi::Handle,
+        _device_path: *mut efi::protocols::device_path::Protocol,
+        _source_buffer: *mut c_void,
+        _source_size: usize,
+        _image_handle: *mut efi::Handle,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn start_image(
+        _image_handle: efi::Handle,
+        _exit_data_size: *mut usize,
+        _exit_data: *mut *mut u16,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn exit(
+        _image_handle: efi::Handle,
+        _exit_status: efi::Status,
+        _exit_data_size: usize,
+        _exit_data: *mut u16,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn unload_image(_image_handle: efi::Handle) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn exit_boot_services(_image_handle: efi::Handle, _map_key: usize) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn get_next_monotonic_count(count: *mut u64) -> efi::Status {
+        call(|state| write(count, state.boot_services.get_next_monotonic_count()?))
+    }
+
+    /// Advances the virtual time of the boot services instead of waiting.
+    pub extern "efiapi" fn stall(microseconds: usize) -> efi::Status {
+        call(|state| state.boot_services.stall(microseconds))
+    }
+
+    pub extern "efiapi" fn set_watchdog_timer(
+        _timeout: usize,
+        _watchdog_code: u64,
+        _data_size: usize,
+        _watchdog_data: *mut u16,
+    ) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    pub extern "efiapi" fn connect_controller(
+        controller_handle: efi::Handle,
+        driver_image_handle: *mut efi::Handle,
+        remaining_device_path: *mut efi::protocols::device_path::Protocol,
+        recursive: efi::Boolean,
+    ) -> efi::Status {
+        call(|state| {
+            let mut drivers = Vec::new();
+            //SAFETY: The driver list is null or null-terminated.
+            unsafe {
+                while !driver_image_handle.is_null() && !(*driver_image_handle.add(drivers.len())).is_null() {
+                    drivers.push(*driver_image_handle.add(drivers.len()));
+                }
+                state.boot_services.connect_controller(
+                    controller_handle,
+                    drivers,
+                    remaining_device_path,
+                    recursive.into(),
+                )
+            }
+        })
+    }
+
+    pub extern "efiapi" fn disconnect_controller(
+        controller_handle: efi::Handle,
+        driver_image_handle: efi::Handle,
+        child_handle: efi::Handle,
+    ) -> efi::Status {
+        call(|state| {
+            state.boot_services.disconnect_controller(
+                controller_handle,
+                option(driver_image_handle),
+                option(child_handle),
+            )
+        })
+    }
+
+    pub extern "efiapi" fn open_protocol(
+        handle: efi::Handle,
+        protocol: *mut efi::Guid,
+        interface: *mut *mut c_void,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+        attributes: u32,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The interface is only returned.
+            let opened = unsafe {
+                state.boot_services.open_protocol_unchecked(
+                    handle,
+                    guid(protocol)?,
+                    agent_handle,
+                    controller_handle,
+                    attributes,
+                )
+            }?;
+            // The interface is optional when testing for the protocol.
+            match interface.is_null() {
+                true if attributes == efi::OPEN_PROTOCOL_TEST_PROTOCOL => Ok(()),
+                _ => write(interface, opened),
+            }
+        })
+    }
+
+    pub extern "efiapi" fn close_protocol(
+        handle: efi::Handle,
+        protocol: *mut efi::Guid,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+    ) -> efi::Status {
+        call(|state| state.boot_services.close_protocol(handle, guid(protocol)?, agent_handle, controller_handle))
+    }
+
+    pub extern "efiapi" fn open_protocol_information(
+        handle: efi::Handle,
+        protocol: *mut efi::Guid,
+        entry_buffer: *mut *mut efi::OpenProtocolInformationEntry,
+        entry_count: *mut usize,
+    ) -> efi::Status {
+        call(|state| {
+            // The buffer is freed by the caller with FreePool().
+            let entries = state.boot_services.open_protocol_information(handle, guid(protocol)?)?.leak();
+            write(entry_count, entries.len())?;
+            write(entry_buffer, entries.as_mut_ptr())
+        })
+    }
+
+    pub extern "efiapi" fn protocols_per_handle(
+        handle: efi::Handle,
+        protocol_buffer: *mut *mut *mut efi::Guid,
+        protocol_buffer_count: *mut usize,
+    ) -> efi::Status {
+        call(|state| {
+            // The buffer is freed by the caller with FreePool().
+            let protocols = state.boot_services.protocols_per_handle(handle)?.leak();
+            write(protocol_buffer_count, protocols.len())?;
+            write(protocol_buffer, protocols.as_mut_ptr() as *mut *mut efi::Guid)
+        })
+    }
+
+    pub extern "efiapi" fn locate_handle_buffer(
+        search: efi::LocateSearchType,
+        protocol: *mut efi::Guid,
+        search_key: *mut c_void,
+        no_handles: *mut usize,
+        buffer: *mut *mut efi::Handle,
+    ) -> efi::Status {
+        call(|state| {
+            // The buffer is freed by the caller with FreePool().
+            let handles = state.boot_services.locate_handle_buffer(search_type(search, protocol, search_key)?)?.leak();
+            write(no_handles, handles.len())?;
+            write(buffer, handles.as_mut_ptr())
+        })
+    }
+
+    pub extern "efiapi" fn locate_protocol(
+        protocol: *mut efi::Guid,
+        registration: *mut c_void,
+        interface: *mut *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The interface is only returned.
+            let found = unsafe { state.boot_services.locate_protocol_unchecked(guid(protocol)?, registration) }?;
+            write(interface, found)
+        })
+    }
+
+    /// Variadic in the specification, which Rust cannot implement.
+    pub extern "efiapi" fn install_multiple_protocol_interfaces(
+        _handle: *mut efi::Handle,
+        _arg1: *mut c_void,
+        _arg2: *mut c_void,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    /// Variadic in the specification, which Rust cannot implement.
+    pub extern "efiapi" fn uninstall_multiple_protocol_interfaces(
+        _handle: efi::Handle,
+        _arg1: *mut c_void,
+        _arg2: *mut c_void,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn calculate_crc32(data: *mut c_void, data_size: usize, crc32: *mut u32) -> efi::Status {
+        if data.is_null() || data_size == 0 {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        //SAFETY: The caller gives a buffer of `data_size` bytes.
+        let data = unsafe { slice::from_raw_parts(data as *const u8, data_size) };
+        write(crc32, super::crc32(data)).map_or_else(|status| status.into(), |()| efi::Status::SUCCESS)
+    }
+
+    pub extern "efiapi" fn copy_mem(destination: *mut c_void, source: *mut c_void, length: usize) {
+        //SAFETY: The caller gives buffers of `length` bytes, which may overlap.
+        unsafe { ptr::copy(source as *const u8, destination as *mut u8, length) };
+    }
+
+    pub extern "efiapi" fn set_mem(buffer: *mut c_void, size: usize, value: u8) {
+        //SAFETY: The caller gives a buffer of `size` bytes.
+        unsafe { ptr::write_bytes(buffer as *mut u8, value, size) };
+    }
+
+    pub extern "efiapi" fn create_event_ex(
+        event_type: u32,
+        notify_tpl: efi::Tpl,
+        notify_function: Option<efi::EventNotify>,
+        notify_context: *const c_void,
+        event_group: *const efi::Guid,
+        event: *mut efi::Event,
+    ) -> efi::Status {
+        if event_group.is_null() {
+            return create_event(event_type, notify_tpl, notify_function, notify_context as *mut c_void, event);
+        }
+        call(|state| {
+            let notify_function = notify_function.ok_or(efi::Status::INVALID_PARAMETER)?;
+            //SAFETY: The context is given back to the notify function as given, like the firmware would.
+            let created = unsafe {
+                state.boot_services.create_event_ex_unchecked(
+                    EventType::from(event_type),
+                    Tpl(notify_tpl),
+                    notify_callback(notify_function),
+                    notify_context as *mut c_void,
+                    guid(event_group as *mut efi::Guid)?,
+                )
+            }?;
+            write(event, created)
+        })
+    }
+}
+
+/// The runtime services table entries, forwarding to [`InMemoryRuntimeServices`].
+mod runtime {
+    use super::*;
+
+    pub extern "efiapi" fn get_time(time: *mut efi::Time, capabilities: *mut efi::TimeCapabilities) -> efi::Status {
+        call(|state| {
+            //SAFETY: The in-memory services do not call the firmware.
+            let (current, current_capabilities) = unsafe { state.runtime_services.get_time_unchecked() }?;
+            write(time, current)?;
+            if !capabilities.is_null() {
+                write(capabilities, current_capabilities)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub extern "efiapi" fn set_time(time: *mut efi::Time) -> efi::Status {
+        call(|state| {
+            //SAFETY: Non-null times are valid per the UEFI calling convention.
+            let time = unsafe { time.as_ref() }.ok_or(efi::Status::INVALID_PARAMETER)?;
+            //SAFETY: The in-memory services do not call the firmware.
+            unsafe { state.runtime_services.set_time_unchecked(time) }
+        })
+    }
+
+    pub extern "efiapi" fn get_wakeup_time(
+        enabled: *mut efi::Boolean,
+        pending: *mut efi::Boolean,
+        time: *mut efi::Time,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The in-memory services do not call the firmware.
+            let (is_enabled, is_pending, wakeup_time) = unsafe { state.runtime_services.get_wakeup_time_unchecked() }?;
+            write(enabled, is_enabled.into())?;
+            write(pending, is_pending.into())?;
+            write(time, wakeup_time)
+        })
+    }
+
+    pub extern "efiapi" fn set_wakeup_time(enable: efi::Boolean, time: *mut efi::Time) -> efi::Status {
+        call(|state| {
+            //SAFETY: Non-null times are valid per the UEFI calling convention.
+            let time = unsafe { time.as_ref() }.ok_or(efi::Status::INVALID_PARAMETER)?;
+            //SAFETY: The in-memory services do not call the firmware.
+            unsafe { state.runtime_services.set_wakeup_time_unchecked(enable.into(), time) }
+        })
+    }
+
+    pub extern "efiapi" fn set_virtual_address_map(
+        _memory_map_size: usize,
+        _descriptor_size: usize,
+        _descriptor_version: u32,
+        _virtual_map: *mut efi::MemoryDescriptor,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn convert_pointer(_debug_disposition: usize, _address: *mut *mut c_void) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn get_variable(
+        variable_name: *mut u16,
+        vendor_guid: *mut efi::Guid,
+        attributes: *mut u32,
+        data_size: *mut usize,
+        data: *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The name is null-terminated per the UEFI calling convention.
+            let name = unsafe { ucs2(variable_name) }.ok_or(efi::Status::INVALID_PARAMETER)?;
+            //SAFETY: The size is checked for null first.
+            let size = unsafe { data_size.as_ref() }.copied().ok_or(efi::Status::INVALID_PARAMETER)?;
+            let buffer = match data.is_null() || size == 0 {
+                true => None,
+                //SAFETY: The caller's buffer holds `size` bytes.
+                false => Some(unsafe { slice::from_raw_parts_mut(data as *mut u8, size) }),
+            };
+            //SAFETY: The in-memory services do not call the firmware.
+            match unsafe { state.runtime_services.get_variable_unchecked(name, guid(vendor_guid)?, buffer) } {
+                GetVariableStatus::Success { data_size: size, attributes: variable_attributes } => {
+                    if !attributes.is_null() {
+                        write(attributes, variable_attributes)?;
+                    }
+                    write(data_size, size)
+                }
+                GetVariableStatus::Warning { status, data_size: size, attributes: variable_attributes } => {
+                    if !attributes.is_null() {
+                        write(attributes, variable_attributes)?;
+                    }
+                    write(data_size, size)?;
+                    // Warnings are returned like errors by the thunks.
+                    Err(status.into())
+                }
+                GetVariableStatus::BufferTooSmall { data_size: size, .. } => {
+                    write(data_size, size)?;
+                    Err(efi::Status::BUFFER_TOO_SMALL.into())
+                }
+                GetVariableStatus::Error(status) => Err(status.into()),
+            }
+        })
+    }
+
+    pub extern "efiapi" fn get_next_variable_name(
+        variable_name_size: *mut usize,
+        variable_name: *mut u16,
+        vendor_guid: *mut efi::Guid,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The size is checked for null first.
+            let available = unsafe { variable_name_size.as_ref() }.copied().ok_or(efi::Status::INVALID_PARAMETER)?;
+            //SAFETY: The previous name is null-terminated per the UEFI calling convention.
+            let previous_name = unsafe { ucs2(variable_name) }.ok_or(efi::Status::INVALID_PARAMETER)?;
+            let previous_namespace = guid(vendor_guid)?;
+            let mut name = Vec::new();
+            let mut namespace = *previous_namespace;
+            //SAFETY: The in-memory services do not call the firmware.
+            unsafe {
+                state.runtime_services.get_next_variable_name_unchecked(
+                    previous_name,
+                    previous_namespace,
+                    &mut name,
+                    &mut namespace,
+                )
+            }?;
+            let size = mem::size_of_val(name.as_slice());
+            write(variable_name_size, size)?;
+            if available < size {
+                return Err(efi::Status::BUFFER_TOO_SMALL.into());
+            }
+            //SAFETY: The caller's buffer holds `available` bytes, at least `size`.
+            unsafe { ptr::copy_nonoverlapping(name.as_ptr(), variable_name, name.len()) };
+            write(vendor_guid, namespace)
+        })
+    }
+
+    pub extern "efiapi" fn set_variable(
+        variable_name: *mut u16,
+        vendor_guid: *mut efi::Guid,
+        attributes: u32,
+        data_size: usize,
+        data: *mut c_void,
+    ) -> efi::Status {
+        call(|state| {
+            //SAFETY: The name is null-terminated per the UEFI calling convention.
+            let name = unsafe { ucs2(variable_name) }.ok_or(efi::Status::INVALID_PARAMETER)?;
+            let data = match data_size {
+                0 => &[][..],
+                _ if data.is_null() => return Err(efi::Status::INVALID_PARAMETER.into()),
+                //SAFETY: The caller's buffer holds `data_size` bytes.
+                _ => unsafe { slice::from_raw_parts(data as *const u8, data_size) },
+            };
+            //SAFETY: The in-memory services do not call the firmware.
+            unsafe { state.runtime_services.set_variable_unchecked(name, guid(vendor_guid)?, attributes, data) }
+        })
+    }
+
+    pub extern "efiapi" fn get_next_high_mono_count(high_count: *mut u32) -> efi::Status {
+        call(|state| write(high_count, state.runtime_services.get_next_high_monotonic_count()?))
+    }
+
+    /// Records the request instead of resetting, and returns to the caller.
+    pub extern "efiapi" fn reset_system(
+        reset_type: efi::ResetType,
+        reset_status: efi::Status,
+        _data_size: usize,
+        _reset_data: *mut c_void,
+    ) {
+        call(|state| {
+            state.reset_request.set(Some((reset_type, reset_status)));
+            Ok(())
+        });
+    }
+
+    pub extern "efiapi" fn update_capsule(
+        _capsule_header_array: *mut *mut efi::CapsuleHeader,
+        _capsule_count: usize,
+        _scatter_gather_list: efi::PhysicalAddress,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn query_capsule_capabilities(
+        _capsule_header_array: *mut *mut efi::CapsuleHeader,
+        _capsule_count: usize,
+        _maximum_capsule_size: *mut u64,
+        _reset_type: *mut efi::ResetType,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    pub extern "efiapi" fn query_variable_info(
+        attributes: u32,
+        maximum_variable_storage_size: *mut u64,
+        remaining_variable_storage_size: *mut u64,
+        maximum_variable_size: *mut u64,
+    ) -> efi::Status {
+        call(|state| {
+            let info = state.runtime_services.query_variable_info(attributes)?;
+            write(maximum_variable_storage_size, info.maximum_variable_storage_size)?;
+            write(remaining_variable_storage_size, info.remaining_variable_storage_size)?;
+            write(maximum_variable_size, info.maximum_variable_size)
+        })
+    }
+}
+
+/// The console protocols, capturing the output in the state of the harness.
+mod console {
+    use super::*;
+
+    pub fn output_protocol() -> simple_text_output::Protocol {
+        simple_text_output::Protocol {
+            reset: output_reset,
+            output_string,
+            test_string,
+            query_mode,
+            set_mode,
+            set_attribute,
+            clear_screen,
+            set_cursor_position,
+            enable_cursor,
+            mode: ptr::null_mut(),
+        }
+    }
+
+    /// The mode of `this`, null-checked.
+    fn mode<'a>(this: *mut simple_text_output::Protocol) -> Result<&'a mut simple_text_output::Mode, efi::Status> {
+        //SAFETY: The protocols are the ones of the tables, whose modes are never freed.
+        unsafe { this.as_ref().and_then(|this| this.mode.as_mut()) }.ok_or(efi::Status::INVALID_PARAMETER)
+    }
+
+    pub extern "efiapi" fn input_reset(
+        _this: *mut simple_text_input::Protocol,
+        _extended: efi::Boolean,
+    ) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    pub extern "efiapi" fn read_key_stroke(
+        this: *mut simple_text_input::Protocol,
+        key: *mut simple_text_input::InputKey,
+    ) -> efi::Status {
+        call(|state| {
+            let next = state.keys.borrow_mut().pop_front().ok_or(efi::Status::NOT_READY)?;
+            write(key, next)?;
+            if !state.keys.borrow().is_empty() {
+                //SAFETY: The protocol is the one of the tables.
+                let wait_for_key = unsafe { this.as_ref() }.ok_or(efi::Status::INVALID_PARAMETER)?.wait_for_key;
+                state.boot_services.signal_event(wait_for_key)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub extern "efiapi" fn output_reset(
+        this: *mut simple_text_output::Protocol,
+        _extended: efi::Boolean,
+    ) -> efi::Status {
+        clear_screen(this)
+    }
+
+    /// Appends the string to the output captured for `this`, carriage returns and line feeds included.
+    pub extern "efiapi" fn output_string(this: *mut simple_text_output::Protocol, string: *mut u16) -> efi::Status {
+        call(|state| {
+            //SAFETY: The string is null-terminated per the UEFI calling convention.
+            let string = unsafe { ucs2(string) }.ok_or(efi::Status::INVALID_PARAMETER)?;
+            let string = &string[..string.len() - 1];
+            //SAFETY: The tables are never freed.
+            let output = match ptr::eq(this, unsafe { ptr::addr_of!((*tables()).std_err) }) {
+                true => &state.std_err_output,
+                false => &state.console_output,
+            };
+            let mode = mode(this)?;
+            for c in char::decode_utf16(string.iter().copied()) {
+                let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+                output.borrow_mut().push(c);
+                match c {
+                    '\r' => mode.cursor_column = 0,
+                    '\n' => mode.cursor_row = (mode.cursor_row + 1).min(CONSOLE_SIZE.1 as i32 - 1),
+                    _ => mode.cursor_column = (mode.cursor_column + 1).min(CONSOLE_SIZE.0 as i32 - 1),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub extern "efiapi" fn test_string(_this: *mut simple_text_output::Protocol, _string: *mut u16) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    pub extern "efiapi" fn query_mode(
+        _this: *mut simple_text_output::Protocol,
+        mode_number: usize,
+        columns: *mut usize,
+        rows: *mut usize,
+    ) -> efi::Status {
+        if mode_number != 0 {
+            return efi::Status::UNSUPPORTED;
+        }
+        write(columns, CONSOLE_SIZE.0)
+            .and_then(|()| write(rows, CONSOLE_SIZE.1))
+            .map_or_else(|s| s.into(), |()| efi::Status::SUCCESS)
+    }
+
+    pub extern "efiapi" fn set_mode(this: *mut simple_text_output::Protocol, mode_number: usize) -> efi::Status {
+        match mode_number {
+            0 => clear_screen(this),
+            _ => efi::Status::UNSUPPORTED,
+        }
+    }
+
+    pub extern "efiapi" fn set_attribute(this: *mut simple_text_output::Protocol, attribute: usize) -> efi::Status {
+        mode(this).map_or_else(
+            |status| status,
+            |mode| {
+                mode.attribute = attribute as i32;
+                efi::Status::SUCCESS
+            },
+        )
+    }
+
+    pub extern "efiapi" fn clear_screen(this: *mut simple_text_output::Protocol) -> efi::Status {
+        set_cursor_position(this, 0, 0)
+    }
+
+    pub extern "efiapi" fn set_cursor_position(
+        this: *mut simple_text_output::Protocol,
+        column: usize,
+        row: usize,
+    ) -> efi::Status {
+        if column >= CONSOLE_SIZE.0 || row >= CONSOLE_SIZE.1 {
+            return efi::Status::UNSUPPORTED;
+        }
+        mode(this).map_or_else(
+            |status| status,
+            |mode| {
+                mode.cursor_column = column as i32;
+                mode.cursor_row = row as i32;
+                efi::Status::SUCCESS
+            },
+        )
+    }
+
+    pub extern "efiapi" fn enable_cursor(
+        this: *mut simple_text_output::Protocol,
+        visible: efi::Boolean,
+    ) -> efi::Status {
+        mode(this).map_or_else(
+            |status| status,
+            |mode| {
+                mode.cursor_visible = visible;
+                efi::Status::SUCCESS
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    const VENDOR_GUID: efi::Guid =
+        efi::Guid::from_fields(0x1234_5678, 0x9ABC, 0xDEF0, 0x12, 0x34, &[5, 6, 7, 8, 9, 10]);
+
+    fn print(text: &str) {
+        let mut text = text.encode_utf16().chain([0]).collect::<Vec<_>>();
+        let con_out = entry_point::system_table().con_out;
+        //SAFETY: The system table given to the entry point has a console.
+        unsafe { ((*con_out).output_string)(con_out, text.as_mut_ptr()) };
+    }
+
+    /// Counts its runs in a variable, prints the count and the configuration tables.
+    #[entry_point::entry]
+    fn main() -> Result<(), EfiError> {
+        let runtime_services = entry_point::runtime_services();
+        let name = ucs2::u16str!("Runs");
+        let count = match runtime_services.get_variable::<[u8; 1]>(name, &VENDOR_GUID, None) {
+            Ok(([count], _)) => count + 1,
+            Err(status) if status == efi::Status::NOT_FOUND => 1,
+            Err(status) => return Err(status),
+        };
+        runtime_services.set_variable(name, &VENDOR_GUID, efi::VARIABLE_BOOTSERVICE_ACCESS, &[count])?;
+        print(&format!("Run {count}\r\n"));
+
+        let boot_services = entry_point::boot_services();
+        //SAFETY: The interface of the LoadedImage protocol is a loaded_image::Protocol.
+        let image = unsafe {
+            &*(boot_services.handle_protocol_unchecked(entry_point::image_handle(), &loaded_image::PROTOCOL_GUID)?
+                as *const loaded_image::Protocol)
+        };
+        if !ptr::eq(image.system_table, entry_point::system_table()) {
+            return Err(efi::Status::COMPROMISED_DATA.into());
+        }
+        print(&format!("{} tables\r\n", entry_point::system_table().number_of_table_entries));
+        Err(efi::Status::ABORTED.into())
+    }
+
+    #[test]
+    fn test_run() {
+        let harness = Harness::new();
+        assert_eq!(efi::Status::ABORTED, harness.run(efi_main));
+        assert_eq!("Run 1\r\n0 tables\r\n", harness.console_output());
+
+        harness.clear_output();
+        let table = Box::leak(Box::new(5_u32)) as *mut u32 as *mut c_void;
+        assert_eq!(Ok(()), unsafe {
+            harness.boot_services().install_configuration_table_unchecked(&VENDOR_GUID, table)
+        });
+        assert_eq!(efi::Status::ABORTED, harness.run(efi_main));
+        assert_eq!("Run 2\r\n1 tables\r\n", harness.console_output());
+        assert!(harness.std_err_output().is_empty());
+        assert_eq!(1, harness.runtime_services().len());
+        drop(harness);
+
+        // A new harness starts from empty services, with the same tables.
+        let harness = Harness::new();
+        assert_eq!(efi::Status::ABORTED, harness.run(efi_main));
+        assert_eq!("Run 1\r\n0 tables\r\n", harness.console_output());
+    }
+
+    extern "efiapi" fn echo(_image_handle: efi::Handle, system_table: *mut efi::SystemTable) -> efi::Status {
+        let system_table = unsafe { &*system_table };
+        let boot_services = unsafe { &*system_table.boot_services };
+        let con_in = unsafe { &mut *system_table.con_in };
+        let mut index = 0;
+        let mut line = Vec::new();
+        loop {
+            let mut event = con_in.wait_for_key;
+            let status = unsafe { r_efi::efi_call(boot_services.wait_for_event, (1, &mut event, &mut index)) };
+            if status.is_error() {
+                break;
+            }
+            let mut key = simple_text_input::InputKey { scan_code: 0, unicode_char: 0 };
+            if unsafe { r_efi::efi_call(con_in.read_key_stroke, (con_in, &mut key)) } == efi::Status::SUCCESS {
+                line.push(key.unicode_char);
+            }
+        }
+        unsafe { r_efi::efi_call(boot_services.stall, (1500,)) };
+        line.push(0);
+        let std_err = system_table.std_err;
+        unsafe { ((*std_err).output_string)(std_err, line.as_mut_ptr()) };
+        unsafe {
+            ((*system_table.runtime_services).reset_system)(efi::RESET_WARM, efi::Status::SUCCESS, 0, ptr::null_mut())
+        };
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_console_input() {
+        let harness = Harness::new();
+        harness.queue_input("héllo");
+        assert_eq!(efi::Status::SUCCESS, harness.run(echo));
+        assert_eq!("héllo", harness.std_err_output());
+        assert!(harness.console_output().is_empty());
+        assert_eq!(Duration::from_micros(1500), harness.boot_services().time());
+        assert_eq!(Some((efi::RESET_WARM, efi::Status::SUCCESS)), harness.reset_request());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+    }
+}
@@ -1,15 +1,97 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "harness")), no_std)]
 
 extern crate alloc;
 
+#[cfg(all(
+    any(test, fuzzing),
+    feature = "device_path",
+    feature = "protocols",
+    feature = "runtime_services",
+    feature = "status"
+))]
+pub mod fuzz;
+
+#[cfg(any(feature = "harness", all(test, feature = "entry_point", feature = "status", feature = "ucs2")))]
+pub mod harness;
+
 #[cfg(feature = "boot_services")]
 pub use boot_services;
 
+#[cfg(feature = "console")]
+pub use console;
+
+#[cfg(feature = "device_path")]
+pub use device_path;
+
+#[cfg(feature = "entry_point")]
+pub use entry_point;
+
 #[cfg(feature = "runtime_services")]
 pub use runtime_services;
 
 #[cfg(feature = "guid")]
 pub use guid;
 
+#[cfg(feature = "logger")]
+pub use logger;
+
+#[cfg(feature = "protocols")]
+pub use protocols;
+
+#[cfg(feature = "status")]
+pub use status;
+
+/// Result of the helpers, the errors of the modules convert into [`status::EfiError`] so `?` works across them.
+#[cfg(feature = "status")]
+pub type Result<T, E = status::EfiError> = core::result::Result<T, E>;
+
 #[cfg(feature = "tpl_mutex")]
 pub use tpl_mutex;
+
+#[cfg(feature = "ucs2")]
+pub use ucs2;
+
+#[cfg(all(test, feature = "boot_services", feature = "guid", feature = "status", feature = "ucs2"))]
+mod test {
+    use core::str::FromStr;
+
+    use r_efi::efi;
+
+    use crate::{boot_services::protocol_handler::ConnectError, guid::Guid, status::EfiError, ucs2::String16};
+
+    fn parse(name: &str, guid: &str) -> crate::Result<(String16, Guid)> {
+        Ok((String16::try_from(name)?, Guid::from_str(guid)?))
+    }
+
+    #[test]
+    fn test_result_conversions() {
+        assert!(parse("BootOrder", "8BE4DF61-93CA-11D2-AA0D-00E098032B8C").is_ok());
+        assert_eq!(
+            Err(efi::Status::INVALID_PARAMETER),
+            parse("Boot\0Order", "8BE4DF61-93CA-11D2-AA0D-00E098032B8C").map_err(efi::Status::from)
+        );
+        assert_eq!(Err(efi::Status::INVALID_PARAMETER), parse("BootOrder", "8BE4DF61").map_err(efi::Status::from));
+
+        let mut buffer = [0u16; 4];
+        let error = EfiError::from(crate::ucs2::convert::utf8_to_ucs2("BootOrder", &mut buffer).unwrap_err());
+        assert_eq!(efi::Status::BUFFER_TOO_SMALL, error.status());
+        let error = EfiError::from(ConnectError::NoDriverConnected);
+        assert_eq!(efi::Status::NOT_FOUND, error.status());
+        assert_eq!(Some("ConnectController"), error.operation());
+    }
+
+    #[test]
+    fn test_derive_protocol_through_reexport() {
+        use crate::boot_services::protocol_handler::Protocol;
+
+        #[derive(Protocol)]
+        #[protocol(crate = "crate::boot_services")]
+        #[protocol_guid = "8F644FA9-E850-4DB1-9CE2-0B44698E8DA4"]
+        struct MyProtocol;
+
+        assert_eq!(
+            &efi::Guid::from_fields(0x8F644FA9, 0xE850, 0x4DB1, 0x9C, 0xE2, &[0x0B, 0x44, 0x69, 0x8E, 0x8D, 0xA4]),
+            MyProtocol.protocol_guid()
+        );
+    }
+}
@@ -0,0 +1,545 @@
+//! Error type of the UEFI wrappers.
+//!
+//! [`EfiError`] wraps the [`efi::Status`] a service failed with and the context it failed in, the operation, the
+//! variable or the handle, so the message logged far from the call still says what failed:
+//!
+//! ```ignore
+//! let result = runtime_services.get_variable::<Vec<u8>>(name, &namespace, None);
+//! let (data, _) = result.map_err(|status| EfiError::from(status).with_operation("GetVariable").with_variable(name))?;
+//! // GetVariable failed for variable "BootOrder": EFI_NOT_FOUND
+//! ```
+//!
+//! It converts from and into [`efi::Status`], so `?` works between functions returning either. With the `std`
+//! feature it implements [`std::error::Error`], `core::error::Error` is not stable on the toolchain of the crate.
+//! Without the default `alloc` feature, for contexts without a heap, the errors do not keep the variable name.
+//!
+//! [`status_name`] and [`StatusDisplay`] give the name of a status from the specification, `EFI_NOT_FOUND` rather
+//! than the `Status(14)` of its `Debug` implementation, for the messages of the crates.
+//!
+//! [`StatusExt`] turns the status a service returned into a `Result` in one call, keeping warnings on the `Ok` side,
+//! and [`WithWarning`] carries them next to the value of services that complete with a warning. [`ResultExt`] adds
+//! the context to the errors of a `Result` and logs them where they happen:
+//!
+//! ```ignore
+//! let info = runtime_services
+//!     .query_variable_info(attributes)
+//!     .context("QueryVariableInfo")
+//!     .log_err(Level::Warn, "Variable store")?;
+//! // WARN: Variable store: QueryVariableInfo failed: EFI_UNSUPPORTED
+//! ```
+//!
+//! [UEFI Spec Documentation: Appendix D - Status Codes](https://uefi.org/specs/UEFI/2.10/Apx_D_Status_Codes.html)
+#![cfg_attr(all(not(test), not(feature = "std")), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::fmt;
+
+use r_efi::efi;
+
+/// A failed UEFI service, with the context it failed in.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct EfiError {
+    status: efi::Status,
+    operation: Option<&'static str>,
+    #[cfg(feature = "alloc")]
+    variable: Option<String>,
+    /// The address of the handle, which keeps the error `Send` and `Sync`.
+    handle: Option<usize>,
+}
+
+impl EfiError {
+    /// An error without context.
+    pub const fn new(status: efi::Status) -> Self {
+        Self {
+            status,
+            operation: None,
+            #[cfg(feature = "alloc")]
+            variable: None,
+            handle: None,
+        }
+    }
+
+    /// Sets the operation that failed, like the name of the service.
+    pub fn with_operation(mut self, operation: &'static str) -> Self {
+        self.operation = Some(operation);
+        self
+    }
+
+    /// Sets the variable the operation failed for, from its UCS-2 name with or without null terminator.
+    #[cfg(feature = "alloc")]
+    pub fn with_variable(mut self, name: &[u16]) -> Self {
+        let name = name.iter().position(|&c| c == 0).map_or(name, |end| &name[..end]);
+        self.variable =
+            Some(char::decode_utf16(name.iter().copied()).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect());
+        self
+    }
+
+    /// Sets the handle the operation failed on.
+    pub fn with_handle(mut self, handle: efi::Handle) -> Self {
+        self.handle = Some(handle as usize);
+        self
+    }
+
+    pub fn status(&self) -> efi::Status {
+        self.status
+    }
+
+    pub fn operation(&self) -> Option<&'static str> {
+        self.operation
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn variable(&self) -> Option<&str> {
+        self.variable.as_deref()
+    }
+
+    pub fn handle(&self) -> Option<efi::Handle> {
+        self.handle.map(|handle| handle as efi::Handle)
+    }
+}
+
+impl From<efi::Status> for EfiError {
+    fn from(status: efi::Status) -> Self {
+        Self::new(status)
+    }
+}
+
+impl From<EfiError> for efi::Status {
+    fn from(error: EfiError) -> Self {
+        error.status
+    }
+}
+
+#[cfg(feature = "uefi")]
+impl From<uefi::Status> for EfiError {
+    fn from(status: uefi::Status) -> Self {
+        Self::new(efi::Status::from_usize(status.0))
+    }
+}
+
+#[cfg(feature = "uefi")]
+impl From<EfiError> for uefi::Status {
+    fn from(error: EfiError) -> Self {
+        uefi::Status(error.status.as_usize())
+    }
+}
+
+/// The data of the error is dropped, only its status is kept.
+#[cfg(feature = "uefi")]
+impl<T: fmt::Debug> From<uefi::Error<T>> for EfiError {
+    fn from(error: uefi::Error<T>) -> Self {
+        error.status().into()
+    }
+}
+
+/// The context of the error is dropped, only its status is kept.
+#[cfg(feature = "uefi")]
+impl From<EfiError> for uefi::Error {
+    fn from(error: EfiError) -> Self {
+        uefi::Error::new(error.into(), ())
+    }
+}
+
+impl PartialEq<efi::Status> for EfiError {
+    fn eq(&self, status: &efi::Status) -> bool {
+        self.status == *status
+    }
+}
+
+impl PartialEq<EfiError> for efi::Status {
+    fn eq(&self, error: &EfiError) -> bool {
+        *self == error.status
+    }
+}
+
+impl fmt::Debug for EfiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("EfiError");
+        debug.field("status", &StatusDisplay(self.status)).field("operation", &self.operation);
+        #[cfg(feature = "alloc")]
+        debug.field("variable", &self.variable);
+        debug.field("handle", &self.handle).finish()
+    }
+}
+
+impl fmt::Display for EfiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.operation {
+            Some(operation) => write!(f, "{operation} failed")?,
+            None => f.write_str("failed")?,
+        }
+        #[cfg(feature = "alloc")]
+        if let Some(variable) = &self.variable {
+            write!(f, " for variable {variable:?}")?;
+        }
+        if let Some(handle) = self.handle {
+            write!(f, " on handle {handle:#x}")?;
+        }
+        write!(f, ": {}", StatusDisplay(self.status))
+    }
+}
+
+/// Conversion of the status returned by a service into a [`Result`].
+///
+/// Warnings are not errors, they end up in the `Ok` value next to `SUCCESS` so the caller can still look at them with
+/// [`efi::Status::is_warning`]:
+///
+/// ```ignore
+/// let status = (runtime_services.get_variable)(name, guid, &mut attributes, &mut size, data);
+/// let status = status.ok_or_context("GetVariable")?;
+/// if status.is_warning() {
+///     log::warn!("GetVariable returned {status:?}");
+/// }
+/// ```
+pub trait StatusExt: Sized {
+    /// `Ok` with the status when it is a success or a warning, `Err` when it is an error.
+    fn into_result(self) -> Result<efi::Status, EfiError>;
+
+    /// Same as [`StatusExt::into_result`], with the operation set on the error.
+    fn ok_or_context(self, operation: &'static str) -> Result<efi::Status, EfiError> {
+        self.into_result().map_err(|error| error.with_operation(operation))
+    }
+
+    /// `Ok` with the value `f` returns when the status is not an error, usually what the service wrote to an out
+    /// parameter.
+    fn map_ok<T>(self, f: impl FnOnce() -> T) -> Result<T, EfiError> {
+        self.into_result().map(|_| f())
+    }
+
+    /// `Ok(())` when the status is not an error.
+    fn ok(self) -> Result<(), EfiError> {
+        self.map_ok(|| ())
+    }
+
+    /// Same as [`StatusExt::map_ok`], with the warning the status is, if any, next to the value.
+    fn with_warning<T>(self, f: impl FnOnce() -> T) -> Result<WithWarning<T>, EfiError> {
+        self.into_result().map(|status| WithWarning::new(f(), status))
+    }
+}
+
+impl StatusExt for efi::Status {
+    fn into_result(self) -> Result<efi::Status, EfiError> {
+        match self {
+            s if s.is_error() => Err(EfiError::new(s)),
+            s => Ok(s),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EfiError {}
+
+/// Context and logging of the errors of a `Result`, keeping their status.
+pub trait ResultExt<T> {
+    /// Converts the error into an [`EfiError`] with the operation that failed.
+    fn context(self, operation: &'static str) -> Result<T, EfiError>;
+
+    /// Converts the error into an [`EfiError`] and logs it at `level`, after `message`.
+    fn log_err(self, level: log::Level, message: &str) -> Result<T, EfiError>;
+}
+
+impl<T, E: Into<EfiError>> ResultExt<T> for Result<T, E> {
+    fn context(self, operation: &'static str) -> Result<T, EfiError> {
+        self.map_err(|error| error.into().with_operation(operation))
+    }
+
+    fn log_err(self, level: log::Level, message: &str) -> Result<T, EfiError> {
+        self.map_err(|error| {
+            let error = error.into();
+            log::log!(level, "{message}: {error}");
+            error
+        })
+    }
+}
+
+/// The value of a service that completed, with the warning it completed with.
+///
+/// Warnings are successes with something to report, like `EFI_WARN_STALE_DATA` for a variable read from a store that
+/// could not be refreshed. Callers that do not care take the value with [`WithWarning::into_value`], the ones that
+/// can not use the value in that case fail with [`WithWarning::deny_warning`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WithWarning<T> {
+    pub value: T,
+    /// The warning status, `None` for `SUCCESS`.
+    pub warning: Option<efi::Status>,
+}
+
+impl<T> WithWarning<T> {
+    /// The value of a service that completed with `status`, which is kept when it is a warning.
+    pub fn new(value: T, status: efi::Status) -> Self {
+        Self { value, warning: status.is_warning().then_some(status) }
+    }
+
+    /// The value, dropping the warning.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    /// The value, or the warning as an error.
+    pub fn deny_warning(self) -> Result<T, EfiError> {
+        match self.warning {
+            Some(warning) => Err(EfiError::new(warning)),
+            None => Ok(self.value),
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> WithWarning<U> {
+        WithWarning { value: f(self.value), warning: self.warning }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for WithWarning<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithWarning")
+            .field("value", &self.value)
+            .field("warning", &self.warning.map(StatusDisplay))
+            .finish()
+    }
+}
+
+/// Display a status as its name, followed by its code when it is only known by range.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct StatusDisplay(pub efi::Status);
+
+impl fmt::Display for StatusDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match name(self.0) {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{} ({:#x})", status_name(self.0), self.0.as_usize()),
+        }
+    }
+}
+
+impl fmt::Debug for StatusDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// The name of a status.
+///
+/// Statuses of the UEFI and PI specifications have their own name, the others the name of the range they are in:
+/// `EFI_UNKNOWN_ERROR`, `EFI_PI_ERROR` and `EFI_OEM_ERROR` for the errors and the same with `WARNING` for the warnings.
+///
+/// [UEFI Spec Documentation: Appendix D - Status Codes](https://uefi.org/specs/UEFI/2.10/Apx_D_Status_Codes.html)
+pub fn status_name(status: efi::Status) -> &'static str {
+    if let Some(name) = name(status) {
+        return name;
+    }
+    // The high bit marks errors and the two bits below it the specification the code is reserved for, r-efi only
+    // counts the codes of the UEFI and PI ranges as errors.
+    let error = status.as_usize() >> (usize::BITS - 1) == 1;
+    let range = (status.as_usize() >> (usize::BITS - 3)) & 0b11;
+    match (error, range) {
+        (true, 0) => "EFI_UNKNOWN_ERROR",
+        (true, 1) => "EFI_PI_ERROR",
+        (true, _) => "EFI_OEM_ERROR",
+        (false, 0) => "EFI_UNKNOWN_WARNING",
+        (false, 1) => "EFI_PI_WARNING",
+        (false, _) => "EFI_OEM_WARNING",
+    }
+}
+
+const PI_ERROR: usize = (1 << (usize::BITS - 1)) | (1 << (usize::BITS - 3));
+const PI_REQUEST_UNLOAD_IMAGE: efi::Status = efi::Status::from_usize(PI_ERROR | 1);
+const PI_NOT_AVAILABLE_YET: efi::Status = efi::Status::from_usize(PI_ERROR | 2);
+
+/// The name of the statuses defined by the specifications.
+fn name(status: efi::Status) -> Option<&'static str> {
+    Some(match status {
+        efi::Status::SUCCESS => "EFI_SUCCESS",
+        efi::Status::LOAD_ERROR => "EFI_LOAD_ERROR",
+        efi::Status::INVALID_PARAMETER => "EFI_INVALID_PARAMETER",
+        efi::Status::UNSUPPORTED => "EFI_UNSUPPORTED",
+        efi::Status::BAD_BUFFER_SIZE => "EFI_BAD_BUFFER_SIZE",
+        efi::Status::BUFFER_TOO_SMALL => "EFI_BUFFER_TOO_SMALL",
+        efi::Status::NOT_READY => "EFI_NOT_READY",
+        efi::Status::DEVICE_ERROR => "EFI_DEVICE_ERROR",
+        efi::Status::WRITE_PROTECTED => "EFI_WRITE_PROTECTED",
+        efi::Status::OUT_OF_RESOURCES => "EFI_OUT_OF_RESOURCES",
+        efi::Status::VOLUME_CORRUPTED => "EFI_VOLUME_CORRUPTED",
+        efi::Status::VOLUME_FULL => "EFI_VOLUME_FULL",
+        efi::Status::NO_MEDIA => "EFI_NO_MEDIA",
+        efi::Status::MEDIA_CHANGED => "EFI_MEDIA_CHANGED",
+        efi::Status::NOT_FOUND => "EFI_NOT_FOUND",
+        efi::Status::ACCESS_DENIED => "EFI_ACCESS_DENIED",
+        efi::Status::NO_RESPONSE => "EFI_NO_RESPONSE",
+        efi::Status::NO_MAPPING => "EFI_NO_MAPPING",
+        efi::Status::TIMEOUT => "EFI_TIMEOUT",
+        efi::Status::NOT_STARTED => "EFI_NOT_STARTED",
+        efi::Status::ALREADY_STARTED => "EFI_ALREADY_STARTED",
+        efi::Status::ABORTED => "EFI_ABORTED",
+        efi::Status::ICMP_ERROR => "EFI_ICMP_ERROR",
+        efi::Status::TFTP_ERROR => "EFI_TFTP_ERROR",
+        efi::Status::PROTOCOL_ERROR => "EFI_PROTOCOL_ERROR",
+        efi::Status::INCOMPATIBLE_VERSION => "EFI_INCOMPATIBLE_VERSION",
+        efi::Status::SECURITY_VIOLATION => "EFI_SECURITY_VIOLATION",
+        efi::Status::CRC_ERROR => "EFI_CRC_ERROR",
+        efi::Status::END_OF_MEDIA => "EFI_END_OF_MEDIA",
+        efi::Status::END_OF_FILE => "EFI_END_OF_FILE",
+        efi::Status::INVALID_LANGUAGE => "EFI_INVALID_LANGUAGE",
+        efi::Status::COMPROMISED_DATA => "EFI_COMPROMISED_DATA",
+        efi::Status::IP_ADDRESS_CONFLICT => "EFI_IP_ADDRESS_CONFLICT",
+        efi::Status::HTTP_ERROR => "EFI_HTTP_ERROR",
+        efi::Status::NETWORK_UNREACHABLE => "EFI_NETWORK_UNREACHABLE",
+        efi::Status::HOST_UNREACHABLE => "EFI_HOST_UNREACHABLE",
+        efi::Status::PROTOCOL_UNREACHABLE => "EFI_PROTOCOL_UNREACHABLE",
+        efi::Status::PORT_UNREACHABLE => "EFI_PORT_UNREACHABLE",
+        efi::Status::CONNECTION_FIN => "EFI_CONNECTION_FIN",
+        efi::Status::CONNECTION_RESET => "EFI_CONNECTION_RESET",
+        efi::Status::CONNECTION_REFUSED => "EFI_CONNECTION_REFUSED",
+        efi::Status::WARN_UNKNOWN_GLYPH => "EFI_WARN_UNKNOWN_GLYPH",
+        efi::Status::WARN_DELETE_FAILURE => "EFI_WARN_DELETE_FAILURE",
+        efi::Status::WARN_WRITE_FAILURE => "EFI_WARN_WRITE_FAILURE",
+        efi::Status::WARN_BUFFER_TOO_SMALL => "EFI_WARN_BUFFER_TOO_SMALL",
+        efi::Status::WARN_STALE_DATA => "EFI_WARN_STALE_DATA",
+        efi::Status::WARN_FILE_SYSTEM => "EFI_WARN_FILE_SYSTEM",
+        efi::Status::WARN_RESET_REQUIRED => "EFI_WARN_RESET_REQUIRED",
+        PI_REQUEST_UNLOAD_IMAGE => "EFI_REQUEST_UNLOAD_IMAGE",
+        PI_NOT_AVAILABLE_YET => "EFI_NOT_AVAILABLE_YET",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_conversions() {
+        let error = EfiError::from(efi::Status::NOT_FOUND);
+        assert_eq!(error, efi::Status::NOT_FOUND);
+        assert_eq!(efi::Status::NOT_FOUND, error);
+        assert_eq!(efi::Status::NOT_FOUND, efi::Status::from(error.clone().with_operation("GetVariable")));
+
+        fn inner() -> Result<(), efi::Status> {
+            Err(efi::Status::ACCESS_DENIED)
+        }
+        fn outer() -> Result<(), EfiError> {
+            inner()?;
+            Ok(())
+        }
+        assert_eq!(Err(EfiError::new(efi::Status::ACCESS_DENIED)), outer());
+    }
+
+    #[test]
+    #[cfg(feature = "uefi")]
+    fn test_uefi_conversions() {
+        let error = EfiError::from(uefi::Status::NOT_FOUND);
+        assert_eq!(error, efi::Status::NOT_FOUND);
+        assert_eq!(uefi::Status::NOT_FOUND, uefi::Status::from(error.with_operation("GetVariable")));
+
+        let error = EfiError::from(uefi::Error::new(uefi::Status::BUFFER_TOO_SMALL, Some(16usize)));
+        assert_eq!(error, efi::Status::BUFFER_TOO_SMALL);
+        assert_eq!(uefi::Status::BUFFER_TOO_SMALL, uefi::Error::from(error).status());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("failed: EFI_NOT_FOUND", EfiError::new(efi::Status::NOT_FOUND).to_string());
+        let name = [b'B' as u16, b'o' as u16, b'o' as u16, b't' as u16, 0];
+        let error = EfiError::new(efi::Status::SECURITY_VIOLATION)
+            .with_operation("SetVariable")
+            .with_variable(&name)
+            .with_handle(0x1000 as efi::Handle);
+        assert_eq!(Some("Boot"), error.variable());
+        assert_eq!(Some(0x1000 as efi::Handle), error.handle());
+        assert_eq!(
+            "SetVariable failed for variable \"Boot\" on handle 0x1000: EFI_SECURITY_VIOLATION",
+            error.to_string()
+        );
+        let vendor = efi::Status::from_usize(0x8000_0000_0000_0000 | 0x2000_0001);
+        assert_eq!(format!("failed: EFI_UNKNOWN_ERROR ({:#x})", vendor.as_usize()), EfiError::new(vendor).to_string());
+    }
+
+    #[test]
+    fn test_status_name() {
+        assert_eq!("EFI_SUCCESS", status_name(efi::Status::SUCCESS));
+        assert_eq!("EFI_SECURITY_VIOLATION", status_name(efi::Status::SECURITY_VIOLATION));
+        assert_eq!("EFI_WARN_STALE_DATA", status_name(efi::Status::WARN_STALE_DATA));
+        assert_eq!("EFI_NOT_AVAILABLE_YET", status_name(efi::Status::from_usize(0xA000_0000_0000_0002)));
+        assert_eq!("EFI_PI_ERROR", status_name(efi::Status::from_usize(0xA000_0000_0000_0010)));
+        assert_eq!("EFI_OEM_ERROR", status_name(efi::Status::from_usize(0xC000_0000_0000_0001)));
+        assert_eq!("EFI_OEM_ERROR", status_name(efi::Status::from_usize(0xE000_0000_0000_0001)));
+        assert_eq!("EFI_UNKNOWN_WARNING", status_name(efi::Status::from_usize(0x100)));
+        assert_eq!("EFI_PI_WARNING", status_name(efi::Status::from_usize(0x2000_0000_0000_0001)));
+        assert_eq!("EFI_OEM_WARNING", status_name(efi::Status::from_usize(0x4000_0000_0000_0001)));
+
+        assert_eq!("EFI_NOT_FOUND", format!("{:?}", StatusDisplay(efi::Status::NOT_FOUND)));
+        assert_eq!(
+            "EFI_OEM_WARNING (0x4000000000000001)",
+            StatusDisplay(efi::Status::from_usize(0x4000_0000_0000_0001)).to_string()
+        );
+        assert!(format!("{:?}", EfiError::new(efi::Status::ABORTED)).contains("status: EFI_ABORTED"));
+    }
+
+    #[test]
+    fn test_status_ext() {
+        assert_eq!(Ok(efi::Status::SUCCESS), efi::Status::SUCCESS.into_result());
+        assert_eq!(Ok(efi::Status::WARN_STALE_DATA), efi::Status::WARN_STALE_DATA.into_result());
+        assert_eq!(Err(EfiError::new(efi::Status::NOT_FOUND)), efi::Status::NOT_FOUND.into_result());
+
+        let error = efi::Status::NOT_FOUND.ok_or_context("GetVariable").unwrap_err();
+        assert_eq!(Some("GetVariable"), error.operation());
+        assert_eq!(efi::Status::NOT_FOUND, error.status());
+
+        assert_eq!(Ok(42), efi::Status::SUCCESS.map_ok(|| 42));
+        assert_eq!(Ok(42), efi::Status::WARN_BUFFER_TOO_SMALL.map_ok(|| 42));
+        assert_eq!(Err(EfiError::new(efi::Status::DEVICE_ERROR)), efi::Status::DEVICE_ERROR.map_ok(|| 42));
+        assert_eq!(Ok(()), efi::Status::SUCCESS.ok());
+        assert_eq!(Err(EfiError::new(efi::Status::ABORTED)), efi::Status::ABORTED.ok());
+    }
+
+    #[test]
+    fn test_result_ext() {
+        struct Logger;
+
+        static MESSAGES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+        impl log::Log for Logger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                MESSAGES.lock().unwrap().push(format!("{}: {}", record.level(), record.args()));
+            }
+
+            fn flush(&self) {}
+        }
+
+        log::set_logger(&Logger).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let result: Result<(), efi::Status> = Err(efi::Status::NOT_FOUND);
+        let error = result.context("GetVariable").log_err(log::Level::Warn, "Boot order").unwrap_err();
+        assert_eq!(efi::Status::NOT_FOUND, error.status());
+        assert_eq!(Some("GetVariable"), error.operation());
+        assert_eq!(Ok(1), Ok::<_, efi::Status>(1).log_err(log::Level::Error, "Unused"));
+        assert_eq!(
+            vec![String::from("WARN: Boot order: GetVariable failed: EFI_NOT_FOUND")],
+            *MESSAGES.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_with_warning() {
+        let value = efi::Status::SUCCESS.with_warning(|| 42).unwrap();
+        assert_eq!(WithWarning { value: 42, warning: None }, value);
+        assert_eq!(Ok(42), value.deny_warning());
+
+        let value = efi::Status::WARN_STALE_DATA.with_warning(|| 42).unwrap();
+        assert_eq!(Some(efi::Status::WARN_STALE_DATA), value.warning);
+        assert_eq!(Some(efi::Status::WARN_STALE_DATA), value.map(|value| value + 1).warning);
+        assert_eq!(Err(EfiError::new(efi::Status::WARN_STALE_DATA)), value.deny_warning());
+        assert_eq!(42, value.into_value());
+        assert_eq!("WithWarning { value: 42, warning: Some(EFI_WARN_STALE_DATA) }", format!("{value:?}"));
+
+        assert_eq!(Err(EfiError::new(efi::Status::NOT_FOUND)), efi::Status::NOT_FOUND.with_warning(|| 42));
+    }
+}
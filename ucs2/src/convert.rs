@@ -0,0 +1,193 @@
+//! Conversions between UTF-8 and UCS-2 into caller-provided buffers, usable without allocations.
+//!
+//! The strict functions fail on the first character that can not be converted while the lossy ones replace it by
+//! [`char::REPLACEMENT_CHARACTER`]. UCS-2 outputs are always null-terminated and UCS-2 inputs end at their first null
+//! character, if any.
+
+use core::{char, fmt, str};
+
+/// Reason of a [`ConversionError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionErrorReason {
+    /// A null character can not be part of a null-terminated UCS-2 string.
+    NullCharacter,
+    /// The character is outside of the basic multilingual plane and can not be represented in UCS-2.
+    UnsupportedCharacter,
+    /// UCS-2 strings can not contain surrogate code units.
+    Surrogate,
+    /// The output buffer is too small for the converted string.
+    BufferTooSmall,
+}
+
+/// Error returned by the conversion functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionError {
+    pub(crate) position: usize,
+    pub(crate) reason: ConversionErrorReason,
+}
+
+impl ConversionError {
+    /// Index of the offending code unit in the input, a byte for UTF-8 and a `u16` for UCS-2.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Why the conversion failed.
+    pub fn reason(&self) -> ConversionErrorReason {
+        self.reason
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            ConversionErrorReason::NullCharacter => "unexpected null character",
+            ConversionErrorReason::UnsupportedCharacter => "character outside of the basic multilingual plane",
+            ConversionErrorReason::Surrogate => "unexpected surrogate code unit",
+            ConversionErrorReason::BufferTooSmall => "output buffer too small",
+        };
+        write!(f, "{reason} at position {}", self.position)
+    }
+}
+
+#[cfg(feature = "status")]
+impl From<ConversionError> for status::EfiError {
+    fn from(error: ConversionError) -> Self {
+        status::EfiError::new(match error.reason {
+            ConversionErrorReason::BufferTooSmall => r_efi::efi::Status::BUFFER_TOO_SMALL,
+            _ => r_efi::efi::Status::INVALID_PARAMETER,
+        })
+    }
+}
+
+/// Encode a character in UCS-2, replacing the ones that can not be part of a null-terminated UCS-2 string.
+pub(crate) fn encode_lossy(c: char) -> u16 {
+    match u16::try_from(c as u32) {
+        Ok(0) | Err(_) => char::REPLACEMENT_CHARACTER as u16,
+        Ok(c) => c,
+    }
+}
+
+fn utf8_to_ucs2_impl(s: &str, buffer: &mut [u16], lossy: bool) -> Result<usize, ConversionError> {
+    let mut len = 0;
+    for (position, c) in s.char_indices() {
+        let unit = match u16::try_from(c as u32) {
+            _ if lossy => encode_lossy(c),
+            Ok(0) => return Err(ConversionError { position, reason: ConversionErrorReason::NullCharacter }),
+            Ok(unit) => unit,
+            Err(_) => return Err(ConversionError { position, reason: ConversionErrorReason::UnsupportedCharacter }),
+        };
+        if len + 1 >= buffer.len() {
+            return Err(ConversionError { position, reason: ConversionErrorReason::BufferTooSmall });
+        }
+        buffer[len] = unit;
+        len += 1;
+    }
+    match buffer.get_mut(len) {
+        Some(nul) => *nul = 0,
+        None => return Err(ConversionError { position: s.len(), reason: ConversionErrorReason::BufferTooSmall }),
+    }
+    Ok(len)
+}
+
+/// Convert `s` to null-terminated UCS-2 in `buffer`.
+///
+/// Returns the number of characters written, without the null terminator.
+pub fn utf8_to_ucs2(s: &str, buffer: &mut [u16]) -> Result<usize, ConversionError> {
+    utf8_to_ucs2_impl(s, buffer, false)
+}
+
+/// Convert `s` to null-terminated UCS-2 in `buffer`, replacing null characters and characters outside of the basic
+/// multilingual plane.
+///
+/// Returns the number of characters written, without the null terminator.
+pub fn utf8_to_ucs2_lossy(s: &str, buffer: &mut [u16]) -> Result<usize, ConversionError> {
+    utf8_to_ucs2_impl(s, buffer, true)
+}
+
+fn ucs2_to_utf8_impl<'a>(s: &[u16], buffer: &'a mut [u8], lossy: bool) -> Result<&'a str, ConversionError> {
+    let mut len = 0;
+    for (position, &unit) in s.iter().enumerate().take_while(|(_, &unit)| unit != 0) {
+        let c = match char::from_u32(unit as u32) {
+            Some(c) => c,
+            None if lossy => char::REPLACEMENT_CHARACTER,
+            None => return Err(ConversionError { position, reason: ConversionErrorReason::Surrogate }),
+        };
+        let end = len + c.len_utf8();
+        if end > buffer.len() {
+            return Err(ConversionError { position, reason: ConversionErrorReason::BufferTooSmall });
+        }
+        c.encode_utf8(&mut buffer[len..end]);
+        len = end;
+    }
+    //SAFETY: Only complete UTF-8 encoded characters were written.
+    Ok(unsafe { str::from_utf8_unchecked(&buffer[..len]) })
+}
+
+/// Convert the UCS-2 string `s` to UTF-8 in `buffer`.
+///
+/// Returns the converted part of the buffer.
+pub fn ucs2_to_utf8<'a>(s: &[u16], buffer: &'a mut [u8]) -> Result<&'a str, ConversionError> {
+    ucs2_to_utf8_impl(s, buffer, false)
+}
+
+/// Convert the UCS-2 string `s` to UTF-8 in `buffer`, replacing surrogate code units.
+///
+/// Returns the converted part of the buffer.
+pub fn ucs2_to_utf8_lossy<'a>(s: &[u16], buffer: &'a mut [u8]) -> Result<&'a str, ConversionError> {
+    ucs2_to_utf8_impl(s, buffer, true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_utf8_to_ucs2() {
+        let mut buffer = [0xFFFF; 8];
+        assert_eq!(Ok(3), utf8_to_ucs2("aé€", &mut buffer));
+        assert_eq!([0x61, 0xE9, 0x20AC, 0], buffer[..4]);
+
+        let error = utf8_to_ucs2("aé😀", &mut buffer).unwrap_err();
+        assert_eq!(3, error.position());
+        assert_eq!(ConversionErrorReason::UnsupportedCharacter, error.reason());
+        assert_eq!(
+            Err(ConversionError { position: 1, reason: ConversionErrorReason::NullCharacter }),
+            utf8_to_ucs2("a\0", &mut buffer)
+        );
+
+        assert_eq!(Ok(4), utf8_to_ucs2_lossy("a\0b😀", &mut buffer));
+        assert_eq!([0x61, 0xFFFD, 0x62, 0xFFFD, 0], buffer[..5]);
+    }
+
+    #[test]
+    fn test_utf8_to_ucs2_buffer_too_small() {
+        let mut buffer = [0; 3];
+        assert_eq!(Ok(2), utf8_to_ucs2("ab", &mut buffer));
+        assert_eq!(
+            Err(ConversionError { position: 2, reason: ConversionErrorReason::BufferTooSmall }),
+            utf8_to_ucs2("abc", &mut buffer)
+        );
+        assert_eq!(
+            Err(ConversionError { position: 0, reason: ConversionErrorReason::BufferTooSmall }),
+            utf8_to_ucs2_lossy("", &mut [])
+        );
+    }
+
+    #[test]
+    fn test_ucs2_to_utf8() {
+        let mut buffer = [0; 8];
+        assert_eq!(Ok("aé€"), ucs2_to_utf8(&[0x61, 0xE9, 0x20AC, 0, 0x62], &mut buffer));
+        assert_eq!(Ok("ab"), ucs2_to_utf8(&[0x61, 0x62], &mut buffer));
+
+        let error = ucs2_to_utf8(&[0x61, 0xD83D, 0xDE00], &mut buffer).unwrap_err();
+        assert_eq!(1, error.position());
+        assert_eq!(ConversionErrorReason::Surrogate, error.reason());
+        assert_eq!(Ok("a\u{FFFD}\u{FFFD}"), ucs2_to_utf8_lossy(&[0x61, 0xD83D, 0xDE00], &mut buffer));
+
+        assert_eq!(
+            Err(ConversionError { position: 2, reason: ConversionErrorReason::BufferTooSmall }),
+            ucs2_to_utf8(&[0x61, 0x62, 0x20AC], &mut buffer[..4])
+        );
+    }
+}
@@ -0,0 +1,619 @@
+//! Null-terminated UCS-2 strings.
+//!
+//! [`Str16`] and [`String16`] are to UEFI strings what [`core::ffi::CStr`] and `CString` are to C strings. They can be
+//! created from Rust strings, given to UEFI interfaces as pointers or `&[u16]` and displayed.
+//! [`String16`] and [`format_u16!`] need the default `alloc` feature, [`Str16`], [`u16str!`], [`convert`],
+//! [`Ucs2Writer`] and [`u16_format_into`] do not allocate and remain available without it.
+//!
+//! ```ignore
+//! let name = String16::try_from("BootOrder")?;
+//! let (boot_order, _) = runtime_services.get_variable_str16::<Vec<u8>>(&name, &GLOBAL_VARIABLE, None)?;
+//! let description = format_u16!("UEFI {} {}", model, serial_number);
+//! ```
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::{
+    borrow::Borrow,
+    ops::{Add, AddAssign, Deref},
+    str::FromStr,
+};
+use core::{
+    char,
+    fmt::{self, Write},
+    slice,
+};
+
+pub mod convert;
+mod writer;
+
+#[cfg(feature = "alloc")]
+pub use writer::u16_format;
+pub use writer::{u16_format_into, Ucs2Writer};
+
+/// Macro for creating a null-terminated `&'static [u16]` from a string literal at compile time.
+///
+/// Characters outside of the basic multilingual plane, which would need a UTF-16 surrogate pair, and null characters
+/// fail the build.
+///
+/// ```
+/// let boot_order: &'static [u16] = ucs2::u16str!("BootOrder");
+/// assert_eq!(Some(&0), boot_order.last());
+/// ```
+#[macro_export]
+macro_rules! u16str {
+    ($s:expr) => {{
+        const LEN: usize = $crate::__private::encoded_len($s);
+        const STR: &[u16] = &$crate::__private::encode_with_nul::<LEN>($s);
+        STR
+    }};
+}
+
+/// Macro for creating a [`String16`] from formatted text, like `alloc::format!` and without an intermediate UTF-8
+/// string.
+///
+/// Characters that can not be represented in a null-terminated UCS-2 string are replaced by
+/// [`char::REPLACEMENT_CHARACTER`]. [`u16_format_into`] formats into a buffer instead.
+///
+/// ```
+/// let description = ucs2::format_u16!("Boot{:04X}", 1);
+/// assert_eq!(description, "Boot0001");
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! format_u16 {
+    ($($arg:tt)*) => {
+        $crate::u16_format(::core::format_args!($($arg)*))
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    //! Helpers used by the code generated from the macros of this crate.
+
+    /// Number of UCS-2 characters needed to encode `s`, including the null terminator.
+    pub const fn encoded_len(s: &str) -> usize {
+        let bytes = s.as_bytes();
+        let mut len = 1;
+        let mut i = 0;
+        while i < bytes.len() {
+            // Count every byte that is not a UTF-8 continuation byte.
+            if bytes[i] & 0xC0 != 0x80 {
+                len += 1;
+            }
+            i += 1;
+        }
+        len
+    }
+
+    /// Encode `s` in null-terminated UCS-2, `N` must be [`encoded_len`] of `s`.
+    ///
+    /// Panics, failing the build when used in a const, if `s` can not be represented in UCS-2.
+    pub const fn encode_with_nul<const N: usize>(s: &str) -> [u16; N] {
+        let bytes = s.as_bytes();
+        let mut out = [0; N];
+        let mut i = 0;
+        let mut j = 0;
+        while i < bytes.len() {
+            let (c, len) = match bytes[i] {
+                b if b < 0x80 => (b as u32, 1),
+                b if b < 0xE0 => (((b & 0x1F) as u32) << 6 | (bytes[i + 1] & 0x3F) as u32, 2),
+                b if b < 0xF0 => {
+                    (((b & 0x0F) as u32) << 12 | ((bytes[i + 1] & 0x3F) as u32) << 6 | (bytes[i + 2] & 0x3F) as u32, 3)
+                }
+                _ => panic!("Invalid UCS-2 string, character outside of the basic multilingual plane."),
+            };
+            if c == 0 {
+                panic!("Invalid UCS-2 string, unexpected null character.");
+            }
+            out[j] = c as u16;
+            i += len;
+            j += 1;
+        }
+        if j + 1 != N {
+            panic!("Invalid UCS-2 string length.");
+        }
+        out
+    }
+}
+
+/// Error returned when creating a UCS-2 string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ucs2Error {
+    /// The slice does not end with a null character.
+    NotNullTerminated,
+    /// The string contains a null character at the given index, before its end.
+    InteriorNull(usize),
+    /// The character is outside of the basic multilingual plane and can not be represented in UCS-2.
+    UnsupportedCharacter(char),
+}
+
+impl fmt::Display for Ucs2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ucs2Error::NotNullTerminated => write!(f, "the string is not null-terminated"),
+            Ucs2Error::InteriorNull(index) => write!(f, "unexpected null character at index {index}"),
+            Ucs2Error::UnsupportedCharacter(c) => write!(f, "the character {c:?} can not be represented in UCS-2"),
+        }
+    }
+}
+
+#[cfg(feature = "status")]
+impl From<Ucs2Error> for status::EfiError {
+    fn from(_: Ucs2Error) -> Self {
+        status::EfiError::new(r_efi::efi::Status::INVALID_PARAMETER)
+    }
+}
+
+/// A borrowed null-terminated UCS-2 string.
+///
+/// It always ends with a null character and does not contain any other.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Str16([u16]);
+
+impl Str16 {
+    /// Create a Str16 from a slice ending with its only null character.
+    pub fn from_slice_with_nul(s: &[u16]) -> Result<&Self, Ucs2Error> {
+        match s.iter().position(|c| *c == 0) {
+            //SAFETY: The only null character is the last one.
+            Some(i) if i == s.len() - 1 => Ok(unsafe { Self::from_slice_with_nul_unchecked(s) }),
+            Some(i) => Err(Ucs2Error::InteriorNull(i)),
+            None => Err(Ucs2Error::NotNullTerminated),
+        }
+    }
+
+    /// Create a Str16 from the beginning of a slice up to and including its first null character.
+    pub fn from_slice_until_nul(s: &[u16]) -> Result<&Self, Ucs2Error> {
+        match s.iter().position(|c| *c == 0) {
+            //SAFETY: The slice is cut after its first null character.
+            Some(i) => Ok(unsafe { Self::from_slice_with_nul_unchecked(&s[..=i]) }),
+            None => Err(Ucs2Error::NotNullTerminated),
+        }
+    }
+
+    /// Create a Str16 from a slice without checking it.
+    ///
+    /// # Safety
+    ///
+    /// The slice must end with a null character and must not contain any other.
+    pub const unsafe fn from_slice_with_nul_unchecked(s: &[u16]) -> &Self {
+        &*(s as *const [u16] as *const Self)
+    }
+
+    /// Create a Str16 from a pointer to a null-terminated UCS-2 string.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to a null-terminated string that stays valid and unchanged for `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *const u16) -> &'a Self {
+        let mut len = 0;
+        while ptr.add(len).read_unaligned() != 0 {
+            len += 1;
+        }
+        Self::from_slice_with_nul_unchecked(slice::from_raw_parts(ptr, len + 1))
+    }
+
+    /// Pointer to the null-terminated string, to give to UEFI interfaces.
+    pub const fn as_ptr(&self) -> *const u16 {
+        self.0.as_ptr()
+    }
+
+    /// The characters of the string, without the null terminator.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.0[..self.0.len() - 1]
+    }
+
+    /// The characters of the string, including the null terminator.
+    pub const fn as_slice_with_nul(&self) -> &[u16] {
+        &self.0
+    }
+
+    /// Number of characters, without the null terminator.
+    pub const fn len(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    /// Returns true if the string has no characters other than the null terminator.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterator over the characters of the string, invalid characters are replaced by
+    /// [`char::REPLACEMENT_CHARACTER`].
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        char::decode_utf16(self.as_slice().iter().copied()).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    /// Convert the string to a Rust [`String`], invalid characters are replaced by
+    /// [`char::REPLACEMENT_CHARACTER`].
+    #[cfg(feature = "alloc")]
+    pub fn to_string_lossy(&self) -> String {
+        self.chars().collect()
+    }
+}
+
+impl<'a> Default for &'a Str16 {
+    fn default() -> Self {
+        //SAFETY: The slice only contains the null terminator.
+        unsafe { Str16::from_slice_with_nul_unchecked(&[0]) }
+    }
+}
+
+impl AsRef<Str16> for Str16 {
+    fn as_ref(&self) -> &Str16 {
+        self
+    }
+}
+
+/// The slice includes the null terminator, as expected by UEFI interfaces.
+impl AsRef<[u16]> for Str16 {
+    fn as_ref(&self) -> &[u16] {
+        self.as_slice_with_nul()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ToOwned for Str16 {
+    type Owned = String16;
+
+    fn to_owned(&self) -> Self::Owned {
+        String16(self.0.to_vec())
+    }
+}
+
+impl PartialEq<str> for Str16 {
+    fn eq(&self, other: &str) -> bool {
+        self.as_slice().iter().copied().eq(other.encode_utf16())
+    }
+}
+
+impl PartialEq<&str> for Str16 {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<String16> for Str16 {
+    fn eq(&self, other: &String16) -> bool {
+        *self == **other
+    }
+}
+
+impl fmt::Display for Str16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chars().try_for_each(|c| f.write_char(c))
+    }
+}
+
+impl fmt::Debug for Str16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        self.chars().try_for_each(|c| write!(f, "{}", c.escape_debug()))?;
+        f.write_char('"')
+    }
+}
+
+/// An owned null-terminated UCS-2 string.
+///
+/// It always ends with a null character and does not contain any other.
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct String16(Vec<u16>);
+
+#[cfg(feature = "alloc")]
+impl String16 {
+    /// Create an empty String16.
+    pub fn new() -> Self {
+        Self(vec![0])
+    }
+
+    /// Create a String16 from a vector ending with its only null character.
+    pub fn from_vec_with_nul(v: Vec<u16>) -> Result<Self, Ucs2Error> {
+        Str16::from_slice_with_nul(&v)?;
+        Ok(Self(v))
+    }
+
+    /// The borrowed string.
+    pub fn as_str16(&self) -> &Str16 {
+        //SAFETY: The vector always ends with its only null character.
+        unsafe { Str16::from_slice_with_nul_unchecked(&self.0) }
+    }
+
+    /// The characters of the string, including the null terminator.
+    pub fn into_vec_with_nul(self) -> Vec<u16> {
+        self.0
+    }
+
+    /// Append a character to the string.
+    pub fn push(&mut self, c: char) -> Result<(), Ucs2Error> {
+        let c = encode(c, self.len())?;
+        self.0.insert(self.len(), c);
+        Ok(())
+    }
+
+    /// Append a Rust string to the string, nothing is appended if it can not be represented in UCS-2.
+    pub fn push_str(&mut self, s: &str) -> Result<(), Ucs2Error> {
+        let start = self.len();
+        let encoded = s.chars().enumerate().map(|(i, c)| encode(c, start + i)).collect::<Result<Vec<_>, _>>()?;
+        self.0.splice(start..start, encoded);
+        Ok(())
+    }
+
+    /// Append a UCS-2 string to the string.
+    pub fn push_str16(&mut self, s: &Str16) {
+        let start = self.len();
+        self.0.splice(start..start, s.as_slice().iter().copied());
+    }
+}
+
+/// Encode a character of a Rust string at `index` in UCS-2.
+#[cfg(feature = "alloc")]
+fn encode(c: char, index: usize) -> Result<u16, Ucs2Error> {
+    match c {
+        '\0' => Err(Ucs2Error::InteriorNull(index)),
+        c => u16::try_from(c as u32).map_err(|_| Ucs2Error::UnsupportedCharacter(c)),
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Default for String16 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Deref for String16 {
+    type Target = Str16;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str16()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Borrow<Str16> for String16 {
+    fn borrow(&self) -> &Str16 {
+        self.as_str16()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl AsRef<Str16> for String16 {
+    fn as_ref(&self) -> &Str16 {
+        self.as_str16()
+    }
+}
+
+/// The slice includes the null terminator, as expected by UEFI interfaces.
+#[cfg(feature = "alloc")]
+impl AsRef<[u16]> for String16 {
+    fn as_ref(&self) -> &[u16] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<&Str16> for String16 {
+    fn from(s: &Str16) -> Self {
+        s.to_owned()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl TryFrom<&str> for String16 {
+    type Error = Ucs2Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let mut string = String16::new();
+        string.push_str(s)?;
+        Ok(string)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl FromStr for String16 {
+    type Err = Ucs2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Add<&Str16> for String16 {
+    type Output = String16;
+
+    fn add(mut self, rhs: &Str16) -> Self::Output {
+        self.push_str16(rhs);
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl AddAssign<&Str16> for String16 {
+    fn add_assign(&mut self, rhs: &Str16) {
+        self.push_str16(rhs);
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<str> for String16 {
+    fn eq(&self, other: &str) -> bool {
+        **self == *other
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<&str> for String16 {
+    fn eq(&self, other: &&str) -> bool {
+        **self == **other
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<Str16> for String16 {
+    fn eq(&self, other: &Str16) -> bool {
+        **self == *other
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for String16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str16(), f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for String16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str16(), f)
+    }
+}
+
+/// A `uefi` string is valid UCS-2, the conversion can not fail.
+#[cfg(feature = "uefi")]
+impl<'a> From<&'a uefi::CStr16> for &'a Str16 {
+    fn from(s: &'a uefi::CStr16) -> Self {
+        //SAFETY: A CStr16 ends with its only null character.
+        unsafe { Str16::from_slice_with_nul_unchecked(s.to_u16_slice_with_nul()) }
+    }
+}
+
+/// Fails if the string contains UTF-16 surrogates, which the `uefi` crate rejects.
+#[cfg(feature = "uefi")]
+impl<'a> TryFrom<&'a Str16> for &'a uefi::CStr16 {
+    type Error = uefi::data_types::FromSliceWithNulError;
+
+    fn try_from(s: &'a Str16) -> Result<Self, Self::Error> {
+        uefi::CStr16::from_u16_with_nul(s.as_slice_with_nul())
+    }
+}
+
+#[cfg(all(feature = "uefi", feature = "alloc"))]
+impl From<uefi::CString16> for String16 {
+    fn from(s: uefi::CString16) -> Self {
+        <&Str16>::from(&*s).to_owned()
+    }
+}
+
+#[cfg(all(feature = "uefi", feature = "alloc"))]
+impl TryFrom<String16> for uefi::CString16 {
+    type Error = uefi::data_types::FromSliceWithNulError;
+
+    fn try_from(s: String16) -> Result<Self, Self::Error> {
+        <&uefi::CStr16>::try_from(s.as_str16()).map(uefi::CString16::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HELLO: [u16; 6] = [0x48, 0x65, 0x6C, 0x6C, 0x6F, 0];
+
+    #[test]
+    #[cfg(feature = "uefi")]
+    fn test_uefi_conversions() {
+        let hello = <&Str16>::from(uefi::cstr16!("Hello"));
+        assert_eq!(&HELLO, hello.as_slice_with_nul());
+        assert_eq!(uefi::cstr16!("Hello"), <&uefi::CStr16>::try_from(hello).unwrap());
+
+        let string = String16::from(uefi::CString16::try_from("Hello").unwrap());
+        assert_eq!(string, "Hello");
+        assert_eq!(uefi::cstr16!("Hello"), &*uefi::CString16::try_from(string).unwrap());
+
+        let surrogate = Str16::from_slice_with_nul(&[0xD800, 0]).unwrap();
+        assert!(<&uefi::CStr16>::try_from(surrogate).is_err());
+    }
+
+    #[test]
+    fn test_str16_from_slice() {
+        let hello = Str16::from_slice_with_nul(&HELLO).unwrap();
+        assert_eq!(5, hello.len());
+        assert_eq!(&HELLO[..5], hello.as_slice());
+        assert_eq!(&HELLO, hello.as_slice_with_nul());
+        assert_eq!("Hello", hello.to_string_lossy());
+        assert_eq!(*hello, "Hello");
+
+        assert_eq!(Err(Ucs2Error::NotNullTerminated), Str16::from_slice_with_nul(&HELLO[..5]));
+        assert_eq!(Err(Ucs2Error::InteriorNull(1)), Str16::from_slice_with_nul(&[0x48, 0, 0x48, 0]));
+        assert_eq!(*Str16::from_slice_until_nul(&[0x48, 0, 0x48, 0]).unwrap(), "H");
+        assert!(<&Str16>::default().is_empty());
+    }
+
+    #[test]
+    fn test_str16_from_ptr() {
+        let hello = unsafe { Str16::from_ptr(HELLO.as_ptr()) };
+        assert_eq!(&HELLO, hello.as_slice_with_nul());
+        assert_eq!(HELLO.as_ptr(), hello.as_ptr());
+    }
+
+    #[test]
+    fn test_string16_from_str() {
+        let hello = String16::try_from("Hello").unwrap();
+        assert_eq!(&HELLO, hello.as_slice_with_nul());
+        assert_eq!("Hello", hello.to_string());
+        assert_eq!("\"Hello\"", format!("{hello:?}"));
+        assert_eq!(hello, "Hello");
+        assert_eq!(Ok(hello), "Hello".parse());
+
+        assert_eq!(Err(Ucs2Error::InteriorNull(2)), String16::try_from("He\0llo"));
+        assert_eq!(Err(Ucs2Error::UnsupportedCharacter('😀')), String16::try_from("Hi😀"));
+        assert!(String16::new().is_empty());
+    }
+
+    #[test]
+    fn test_string16_concatenation() {
+        let mut string = String16::try_from("Boot").unwrap() + &String16::try_from("Order").unwrap();
+        assert_eq!(string, "BootOrder");
+
+        string += Str16::from_slice_with_nul(&HELLO).unwrap();
+        string.push('!').unwrap();
+        assert_eq!(string, "BootOrderHello!");
+
+        assert!(string.push_str(" 😀").is_err());
+        assert_eq!(string, "BootOrderHello!");
+        string.push_str(" é").unwrap();
+        assert_eq!(string, "BootOrderHello! é");
+        assert_eq!(Some(&0), string.as_slice_with_nul().last());
+    }
+
+    #[test]
+    fn test_u16str() {
+        const BOOT_ORDER: &[u16] = u16str!("BootOrder");
+        assert_eq!(&[0x42, 0x6F, 0x6F, 0x74, 0x4F, 0x72, 0x64, 0x65, 0x72, 0], BOOT_ORDER);
+        assert_eq!(&[0], u16str!(""));
+        assert_eq!(&"é€\u{FFFD}".encode_utf16().chain([0]).collect::<Vec<_>>()[..], u16str!("é€\u{FFFD}"));
+        assert_eq!(*Str16::from_slice_with_nul(u16str!("Hello")).unwrap(), "Hello");
+    }
+
+    #[test]
+    #[should_panic = "character outside of the basic multilingual plane"]
+    fn test_u16str_surrogate() {
+        let _ = __private::encode_with_nul::<2>("😀");
+    }
+
+    #[test]
+    #[should_panic = "unexpected null character"]
+    fn test_u16str_null() {
+        let _ = __private::encode_with_nul::<3>("a\0");
+    }
+
+    #[test]
+    fn test_ordering() {
+        let a = String16::try_from("a").unwrap();
+        let b = String16::try_from("b").unwrap();
+        assert!(a < b);
+        assert!(a.as_str16() < b.as_str16());
+        assert_eq!(a, *a.as_str16());
+    }
+}
@@ -0,0 +1,222 @@
+//! [`core::fmt::Write`] adapter for UCS-2 outputs, and formatting into UCS-2 strings.
+
+use core::fmt::{self, Write};
+
+use crate::{
+    convert::{encode_lossy, ConversionError, ConversionErrorReason},
+    Str16,
+};
+
+/// Number of characters converted at once before being given to a callback.
+const CHUNK_LEN: usize = 64;
+
+enum Target<'a> {
+    Buffer { buffer: &'a mut [u16], len: usize },
+    Callback(&'a mut dyn FnMut(&Str16) -> fmt::Result),
+}
+
+/// Writer converting formatted text to null-terminated UCS-2, without intermediate UTF-8 allocations.
+///
+/// Characters that can not be represented in a null-terminated UCS-2 string (null and characters outside of the basic
+/// multilingual plane) are replaced by [`char::REPLACEMENT_CHARACTER`].
+///
+/// ```ignore
+/// let mut output = |s: &Str16| match (con_out.output_string)(con_out, s.as_ptr() as *mut _) {
+///     s if s.is_error() => Err(fmt::Error),
+///     _ => Ok(()),
+/// };
+/// write!(Ucs2Writer::from_callback(&mut output), "Booting {}...\r\n", name)?;
+/// ```
+pub struct Ucs2Writer<'a> {
+    target: Target<'a>,
+}
+
+impl<'a> Ucs2Writer<'a> {
+    /// Writer filling `buffer`, which stays null-terminated.
+    ///
+    /// When the buffer is full, the text is truncated and the write returns an error.
+    pub fn from_buffer(buffer: &'a mut [u16]) -> Self {
+        if let Some(c) = buffer.first_mut() {
+            *c = 0;
+        }
+        Self { target: Target::Buffer { buffer, len: 0 } }
+    }
+
+    /// Writer giving the text to `callback`, in null-terminated chunks.
+    pub fn from_callback(callback: &'a mut dyn FnMut(&Str16) -> fmt::Result) -> Self {
+        Self { target: Target::Callback(callback) }
+    }
+
+    /// The text written in the buffer, `None` if the writer uses a callback.
+    pub fn as_str16(&self) -> Option<&Str16> {
+        match &self.target {
+            //SAFETY: The buffer is kept null-terminated after the written characters, which are never null.
+            Target::Buffer { buffer, len } if !buffer.is_empty() => {
+                Some(unsafe { Str16::from_slice_with_nul_unchecked(&buffer[..=*len]) })
+            }
+            Target::Buffer { .. } => Some(<&Str16>::default()),
+            Target::Callback(_) => None,
+        }
+    }
+}
+
+impl fmt::Write for Ucs2Writer<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match &mut self.target {
+            Target::Buffer { buffer, len } => {
+                for c in s.chars() {
+                    if *len + 1 >= buffer.len() {
+                        return Err(fmt::Error);
+                    }
+                    buffer[*len] = encode_lossy(c);
+                    *len += 1;
+                    buffer[*len] = 0;
+                }
+                Ok(())
+            }
+            Target::Callback(callback) => {
+                let mut chunk = [0; CHUNK_LEN + 1];
+                let mut len = 0;
+                for c in s.chars() {
+                    chunk[len] = encode_lossy(c);
+                    len += 1;
+                    if len == CHUNK_LEN {
+                        //SAFETY: The chunk ends with its only null character.
+                        callback(unsafe { Str16::from_slice_with_nul_unchecked(&chunk) })?;
+                        len = 0;
+                    }
+                }
+                if len > 0 {
+                    chunk[len] = 0;
+                    //SAFETY: The characters before len are never null.
+                    callback(unsafe { Str16::from_slice_with_nul_unchecked(&chunk[..=len]) })?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Format `args` to null-terminated UCS-2 in `buffer`, as `Print2` would, replacing the characters that can not be
+/// represented like [`Ucs2Writer`] does.
+///
+/// ```
+/// let mut buffer = [0; 16];
+/// let name = ucs2::u16_format_into(&mut buffer, format_args!("Boot{:04X}", 1)).unwrap();
+/// assert_eq!(*name, "Boot0001");
+/// ```
+///
+/// Returns `BufferTooSmall` at the position of the first character that does not fit if the text is truncated, or
+/// if a formatting trait implementation fails.
+pub fn u16_format_into<'a>(buffer: &'a mut [u16], args: fmt::Arguments<'_>) -> Result<&'a Str16, ConversionError> {
+    if buffer.is_empty() {
+        return Err(ConversionError { position: 0, reason: ConversionErrorReason::BufferTooSmall });
+    }
+    let mut writer = Ucs2Writer::from_buffer(buffer);
+    let result = writer.write_fmt(args);
+    let Target::Buffer { buffer, len } = writer.target else {
+        unreachable!("the writer uses the buffer");
+    };
+    match result {
+        //SAFETY: The buffer is kept null-terminated after the written characters, which are never null.
+        Ok(()) => Ok(unsafe { Str16::from_slice_with_nul_unchecked(&buffer[..=len]) }),
+        Err(_) => Err(ConversionError { position: len, reason: ConversionErrorReason::BufferTooSmall }),
+    }
+}
+
+/// Format `args` to a new [`String16`](crate::String16), the implementation of [`format_u16!`](crate::format_u16).
+///
+/// Panics if a formatting trait implementation fails, as `alloc::format!` does.
+#[cfg(feature = "alloc")]
+pub fn u16_format(args: fmt::Arguments<'_>) -> crate::String16 {
+    let mut string = crate::String16::new();
+    let mut append = |s: &Str16| {
+        string += s;
+        Ok(())
+    };
+    Ucs2Writer::from_callback(&mut append)
+        .write_fmt(args)
+        .expect("a formatting trait implementation returned an error");
+    string
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{format_u16, String16};
+
+    #[test]
+    fn test_write_to_buffer() {
+        let mut buffer = [0xFFFF; 16];
+        let mut writer = Ucs2Writer::from_buffer(&mut buffer);
+        write!(writer, "Boot{:04X}", 1).unwrap();
+        assert_eq!(*writer.as_str16().unwrap(), "Boot0001");
+        assert_eq!(&"Boot0001".encode_utf16().chain([0]).collect::<Vec<_>>()[..], &buffer[..9]);
+    }
+
+    #[test]
+    fn test_write_to_buffer_truncated() {
+        let mut buffer = [0; 5];
+        let mut writer = Ucs2Writer::from_buffer(&mut buffer);
+        assert!(write!(writer, "Hello").is_err());
+        assert_eq!(*writer.as_str16().unwrap(), "Hell");
+
+        let mut writer = Ucs2Writer::from_buffer(&mut []);
+        assert!(write!(writer, "Hello").is_err());
+        assert!(writer.as_str16().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_write_replacement_character() {
+        let mut buffer = [0; 8];
+        let mut writer = Ucs2Writer::from_buffer(&mut buffer);
+        write!(writer, "a\0b😀").unwrap();
+        assert_eq!(*writer.as_str16().unwrap(), "a\u{FFFD}b\u{FFFD}");
+    }
+
+    #[test]
+    fn test_write_to_callback() {
+        let mut output = String16::new();
+        let mut chunks = 0;
+        let mut callback = |s: &Str16| {
+            output += s;
+            chunks += 1;
+            Ok(())
+        };
+        let mut writer = Ucs2Writer::from_callback(&mut callback);
+        assert!(writer.as_str16().is_none());
+        write!(writer, "{}", "x".repeat(CHUNK_LEN * 2 + 1)).unwrap();
+        assert_eq!(output, *"x".repeat(CHUNK_LEN * 2 + 1));
+        assert_eq!(3, chunks);
+    }
+
+    #[test]
+    fn test_write_to_callback_error() {
+        let mut callback = |_: &Str16| Err(fmt::Error);
+        let mut writer = Ucs2Writer::from_callback(&mut callback);
+        assert!(write!(writer, "Hello").is_err());
+    }
+
+    #[test]
+    fn test_format_into() {
+        let mut buffer = [0xFFFF; 8];
+        let s = u16_format_into(&mut buffer, format_args!("{}-{:>3}", 'é', 7)).unwrap();
+        assert_eq!(*s, "é-  7");
+        assert_eq!(Some(&0), s.as_slice_with_nul().last());
+
+        let error = u16_format_into(&mut buffer, format_args!("Boot{:04X}", 0x10)).unwrap_err();
+        assert_eq!((7, ConversionErrorReason::BufferTooSmall), (error.position(), error.reason()));
+        assert_eq!([0x42, 0x6F, 0x6F, 0x74, 0x30, 0x30, 0x31, 0], buffer);
+        assert_eq!(0, u16_format_into(&mut [], format_args!("")).unwrap_err().position());
+    }
+
+    #[test]
+    fn test_format_u16() {
+        let description = format_u16!("UEFI {} {:?}", "Shell", 2);
+        assert_eq!(description, "UEFI Shell 2");
+        assert_eq!(Some(&0), description.as_slice_with_nul().last());
+        assert_eq!(format_u16!("{}", "x".repeat(CHUNK_LEN * 2)), *"x".repeat(CHUNK_LEN * 2));
+        assert_eq!(format_u16!("a\0b😀"), "a\u{FFFD}b\u{FFFD}");
+        assert!(format_u16!("").is_empty());
+    }
+}